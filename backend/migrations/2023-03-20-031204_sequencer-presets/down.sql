@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS sequencer_presets_tags;
+DROP TABLE IF EXISTS sequencer_presets;
@@ -0,0 +1,14 @@
+CREATE TABLE sequencer_presets (
+  id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  name TEXT NOT NULL,
+  description TEXT NOT NULL,
+  serialized_sequencer_state LONGTEXT NOT NULL,
+  user_id BIGINT REFERENCES users(id)
+);
+CREATE INDEX sequencer_presets_user_id ON sequencer_presets(user_id);
+
+CREATE TABLE sequencer_presets_tags (
+  id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  sequencer_preset_id BIGINT NOT NULL REFERENCES sequencer_presets(id),
+  tag_id BIGINT NOT NULL REFERENCES tags(id)
+);
@@ -0,0 +1,6 @@
+ALTER TABLE voice_presets DROP FOREIGN KEY IF EXISTS voice_presets_forked_from_fk;
+ALTER TABLE voice_presets DROP COLUMN IF EXISTS forked_from;
+ALTER TABLE voice_presets DROP COLUMN IF EXISTS is_public;
+ALTER TABLE synth_presets DROP FOREIGN KEY IF EXISTS synth_presets_forked_from_fk;
+ALTER TABLE synth_presets DROP COLUMN IF EXISTS forked_from;
+ALTER TABLE synth_presets DROP COLUMN IF EXISTS is_public;
@@ -0,0 +1,6 @@
+ALTER TABLE synth_presets ADD COLUMN IF NOT EXISTS is_public BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE synth_presets ADD COLUMN IF NOT EXISTS forked_from BIGINT;
+ALTER TABLE synth_presets ADD CONSTRAINT synth_presets_forked_from_fk FOREIGN KEY (forked_from) REFERENCES synth_presets(id) ON DELETE SET NULL;
+ALTER TABLE voice_presets ADD COLUMN IF NOT EXISTS is_public BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE voice_presets ADD COLUMN IF NOT EXISTS forked_from BIGINT;
+ALTER TABLE voice_presets ADD CONSTRAINT voice_presets_forked_from_fk FOREIGN KEY (forked_from) REFERENCES voice_presets(id) ON DELETE SET NULL;
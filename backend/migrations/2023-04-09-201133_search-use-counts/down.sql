@@ -0,0 +1,4 @@
+ALTER TABLE looper_presets DROP COLUMN IF EXISTS use_count;
+ALTER TABLE synth_presets DROP COLUMN IF EXISTS use_count;
+ALTER TABLE midi_compositions DROP COLUMN IF EXISTS use_count;
+ALTER TABLE compositions DROP COLUMN IF EXISTS use_count;
@@ -0,0 +1,4 @@
+ALTER TABLE compositions ADD COLUMN IF NOT EXISTS use_count BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE midi_compositions ADD COLUMN IF NOT EXISTS use_count BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE synth_presets ADD COLUMN IF NOT EXISTS use_count BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE looper_presets ADD COLUMN IF NOT EXISTS use_count BIGINT NOT NULL DEFAULT 0;
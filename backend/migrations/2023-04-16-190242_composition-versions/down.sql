@@ -0,0 +1,2 @@
+DROP TABLE midi_composition_versions;
+DROP TABLE composition_versions;
@@ -0,0 +1,27 @@
+CREATE TABLE composition_versions (
+  id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  composition_id BIGINT NOT NULL REFERENCES compositions(id) ON DELETE CASCADE,
+  version BIGINT NOT NULL,
+  title TEXT NOT NULL,
+  description TEXT NOT NULL,
+  content LONGTEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE KEY composition_versions_composition_id_version (composition_id, version)
+);
+
+CREATE TABLE midi_composition_versions (
+  id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  midi_composition_id BIGINT NOT NULL REFERENCES midi_compositions(id) ON DELETE CASCADE,
+  version BIGINT NOT NULL,
+  name TEXT NOT NULL,
+  description TEXT NOT NULL,
+  composition_json TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE KEY midi_composition_versions_midi_composition_id_version (midi_composition_id, version)
+);
+
+-- Everything saved so far becomes the first version of itself
+INSERT INTO composition_versions (composition_id, version, title, description, content)
+  SELECT id, 1, title, description, content FROM compositions;
+INSERT INTO midi_composition_versions (midi_composition_id, version, name, description, composition_json)
+  SELECT id, 1, name, description, composition_json FROM midi_compositions;
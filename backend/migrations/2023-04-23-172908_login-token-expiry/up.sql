@@ -0,0 +1,3 @@
+ALTER TABLE login_tokens ADD COLUMN expires_at DATETIME NULL;
+UPDATE login_tokens SET expires_at = created_at + INTERVAL 30 DAY;
+ALTER TABLE login_tokens MODIFY expires_at DATETIME NOT NULL;
@@ -0,0 +1,12 @@
+CREATE TABLE user_samples (
+  id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  user_id BIGINT NOT NULL,
+  name TEXT NOT NULL,
+  byte_size BIGINT NOT NULL,
+  checksum VARCHAR(64) NOT NULL,
+  content_type VARCHAR(255) NOT NULL,
+  storage_url TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  INDEX user_samples_user_id_checksum (user_id, checksum)
+);
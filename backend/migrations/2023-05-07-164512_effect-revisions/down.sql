@@ -0,0 +1,3 @@
+DROP TABLE compiled_effect_artifacts;
+DROP TABLE effect_revisions;
+ALTER TABLE effects DROP COLUMN revision;
@@ -0,0 +1,25 @@
+ALTER TABLE effects ADD COLUMN revision BIGINT NOT NULL DEFAULT 1;
+
+CREATE TABLE effect_revisions (
+  id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  effect_id BIGINT NOT NULL REFERENCES effects(id) ON DELETE CASCADE,
+  revision BIGINT NOT NULL,
+  code TEXT NOT NULL,
+  -- Hex-encoded SHA-1 hash of `code`.  This matches the hash that the Faust compiler uses to build
+  -- module IDs and cache compiled modules.
+  code_hash VARCHAR(40) NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE KEY effect_revisions_effect_id_revision (effect_id, revision)
+);
+
+CREATE TABLE compiled_effect_artifacts (
+  code_hash VARCHAR(40) NOT NULL,
+  optimized BOOLEAN NOT NULL,
+  module_id VARCHAR(64) NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (code_hash, optimized)
+);
+
+-- The current code of all existing effects becomes their first revision
+INSERT INTO effect_revisions (effect_id, revision, code, code_hash)
+  SELECT id, 1, code, SHA1(code) FROM effects;
@@ -0,0 +1 @@
+DROP TABLE midi_composition_collaborators;
@@ -0,0 +1,5 @@
+CREATE TABLE midi_composition_collaborators (
+  midi_composition_id BIGINT NOT NULL REFERENCES midi_compositions(id) ON DELETE CASCADE,
+  user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  PRIMARY KEY (midi_composition_id, user_id)
+);
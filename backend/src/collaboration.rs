@@ -0,0 +1,369 @@
+//! Realtime WebSocket server that lets multiple users edit the same MIDI composition at once.
+//! Clients join the room of a saved MIDI composition by connecting to
+//! `/rooms/<midi_composition_id>`, and every note operation sent by one of them is relayed to all
+//! the others.  Operations are merged on each client, so the server only validates them and keeps
+//! the latest one per note so that new clients can be brought up to date.
+//!
+//! Clients authenticate with the same login tokens as the HTTP API, passed either in the
+//! `Authorization` header or the `token` query parameter, and can only join the rooms of MIDI
+//! compositions that they own or have been added to as collaborators.  Each connection is assigned
+//! a client ID derived from its user, and all operations it sends must be made with it.
+//!
+//! Rocket doesn't support WebSockets, so this runs on its own port alongside it.  Room state is
+//! only kept in memory while at least one client is connected; compositions are persisted by
+//! saving them normally.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use diesel::{
+    r2d2::{ConnectionManager, Pool},
+    MysqlConnection, QueryResult,
+};
+use futures_util::{SinkExt, StreamExt};
+use rocket::{Build, Rocket};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::broadcast::{self, error::RecvError},
+};
+use tokio_tungstenite::{
+    accept_hdr_async_with_config,
+    tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        http::StatusCode,
+        protocol::{frame::coding::CloseCode, CloseFrame, WebSocketConfig},
+        Message,
+    },
+};
+
+use crate::{
+    db_util::{
+        login::get_login_token_user_id,
+        midi_composition_collaborators::can_collaborate_on_midi_composition,
+    },
+    models::collaboration::{
+        build_client_id, parse_login_token_param, parse_room_id, ClientMessage, CollabRoomState,
+        ServerMessage,
+    },
+};
+
+pub type DbPool = Pool<ConnectionManager<MysqlConnection>>;
+
+/// Connections are only needed to authorize clients when they join a room
+const DB_POOL_SIZE: u32 = 2;
+
+/// Number of relayed messages that can be buffered for a client before it's considered lagged
+/// and re-synced
+const ROOM_CHANNEL_CAPACITY: usize = 1024;
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+/// Max number of rooms that can be open at once across all compositions
+const MAX_ROOMS: usize = 256;
+const MAX_PEERS_PER_ROOM: usize = 16;
+
+struct Room {
+    state: Mutex<CollabRoomState>,
+    /// Serialized operations along with the ID of the connection that sent them
+    tx: broadcast::Sender<(u64, Arc<String>)>,
+}
+
+impl Room {
+    fn build_sync_message(&self) -> String {
+        let state = self.state.lock().unwrap();
+        serde_json::to_string(&ServerMessage::Sync {
+            ops: state.snapshot(),
+        })
+        .unwrap()
+    }
+
+    /// Validates and applies an operation sent by a client, broadcasting it to everyone else in
+    /// the room if it wasn't stale
+    fn handle_client_message(
+        &self,
+        conn_id: u64,
+        client_id: &str,
+        msg: &str,
+    ) -> Result<(), String> {
+        let ClientMessage::Op { op } = serde_json::from_str(msg)
+            .map_err(|err| format!("Invalid collaboration message: {}", err))?;
+        op.validate(client_id)?;
+
+        // The lock is held while broadcasting so that all clients receive operations in the same
+        // order they were applied to the room
+        let mut state = self.state.lock().unwrap();
+        let serialized = serde_json::to_string(&ServerMessage::Op { op: &op }).unwrap();
+        if state.apply(op)? {
+            // Only fails if there are no receivers, which can't happen while the sender is
+            // connected
+            let _ = self.tx.send((conn_id, Arc::new(serialized)));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Rooms {
+    rooms: Mutex<HashMap<i64, Arc<Room>>>,
+    next_conn_id: AtomicU64,
+}
+
+impl Rooms {
+    /// Fails if the room is full, or if it isn't open yet and no more rooms can be opened
+    fn join(
+        &self,
+        room_id: i64,
+    ) -> Result<(Arc<Room>, broadcast::Receiver<(u64, Arc<String>)>), &'static str> {
+        let mut rooms = self.rooms.lock().unwrap();
+        if !rooms.contains_key(&room_id) && rooms.len() >= MAX_ROOMS {
+            return Err("Too many collaboration rooms are open; try again later");
+        }
+
+        let room = rooms
+            .entry(room_id)
+            .or_insert_with(|| {
+                Arc::new(Room {
+                    state: Mutex::new(CollabRoomState::default()),
+                    tx: broadcast::channel(ROOM_CHANNEL_CAPACITY).0,
+                })
+            })
+            .clone();
+        if room.tx.receiver_count() >= MAX_PEERS_PER_ROOM {
+            return Err("This collaboration room is full");
+        }
+        let rx = room.tx.subscribe();
+        Ok((room, rx))
+    }
+
+    /// Must be called after the connection's receiver has been dropped.  Rooms are discarded once
+    /// the last client leaves them.
+    fn leave(&self, room_id: i64) {
+        let mut rooms = self.rooms.lock().unwrap();
+        if let Some(room) = rooms.get(&room_id) {
+            if room.tx.receiver_count() == 0 {
+                rooms.remove(&room_id);
+                info!("Closed collaboration room {}", room_id);
+            }
+        }
+    }
+}
+
+fn build_error_message(message: String) -> String {
+    serde_json::to_string(&ServerMessage::Error { message }).unwrap()
+}
+
+/// Rocket only hands out connections from its database pool through a reference to itself, which
+/// the server can't hold on to, so the server gets a small pool of its own built from the same
+/// config.  Connections are opened lazily so that this doesn't block startup.
+pub fn build_db_pool(rocket: &Rocket<Build>) -> Result<DbPool, String> {
+    let config = rocket_sync_db_pools::Config::from("web_synth", rocket)
+        .map_err(|err| format!("Invalid database config: {}", err))?;
+    Ok(Pool::builder()
+        .max_size(DB_POOL_SIZE)
+        .connection_timeout(Duration::from_secs(config.timeout as u64))
+        .build_unchecked(ConnectionManager::new(&config.url)))
+}
+
+/// Runs `f` with a connection from the pool on a thread where it's allowed to block
+async fn run_db<R: Send + 'static>(
+    pool: &DbPool,
+    f: impl FnOnce(&MysqlConnection) -> QueryResult<R> + Send + 'static,
+) -> Result<R, String> {
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool
+            .get()
+            .map_err(|err| format!("Error getting DB connection: {}", err))?;
+        f(&conn).map_err(|err| format!("{:?}", err))
+    })
+    .await
+    .unwrap_or_else(|err| Err(format!("DB task failed: {}", err)))
+}
+
+/// Checks that the login token is valid and that its user owns the room's MIDI composition or is
+/// one of its collaborators, returning the user's ID
+async fn authorize(
+    pool: &DbPool,
+    login_token: Option<String>,
+    room_id: i64,
+) -> Result<i64, &'static str> {
+    let login_token = login_token.ok_or("Missing login token")?;
+
+    let user_id = match run_db(pool, move |conn| get_login_token_user_id(conn, login_token)).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => return Err("Invalid or expired login token"),
+        Err(err) => {
+            error!("Error while validating login token: {}", err);
+            return Err("DB error");
+        },
+    };
+    match run_db(pool, move |conn| {
+        can_collaborate_on_midi_composition(conn, room_id, user_id)
+    })
+    .await
+    {
+        Ok(true) => Ok(user_id),
+        Ok(false) => Err(
+            "MIDI composition not found, or the logged-in user isn't its owner or a collaborator",
+        ),
+        Err(err) => {
+            error!("Error checking MIDI composition access: {}", err);
+            Err("DB error")
+        },
+    }
+}
+
+async fn handle_connection(rooms: Arc<Rooms>, pool: DbPool, stream: TcpStream, addr: SocketAddr) {
+    let mut room_id = None;
+    let mut login_token = None;
+    let check_path = |req: &Request, res: Response| -> Result<Response, ErrorResponse> {
+        match parse_room_id(req.uri().path()) {
+            Some(id) => {
+                room_id = Some(id);
+                login_token = req
+                    .headers()
+                    .get("Authorization")
+                    .and_then(|token| token.to_str().ok())
+                    .filter(|token| !token.is_empty())
+                    .map(String::from)
+                    .or_else(|| req.uri().query().and_then(parse_login_token_param));
+                Ok(res)
+            },
+            None => {
+                let mut err = ErrorResponse::new(Some(String::from("Invalid room ID")));
+                *err.status_mut() = StatusCode::NOT_FOUND;
+                Err(err)
+            },
+        }
+    };
+    let config = WebSocketConfig {
+        max_message_size: Some(MAX_MESSAGE_BYTES),
+        max_frame_size: Some(MAX_MESSAGE_BYTES),
+        ..Default::default()
+    };
+    let mut ws = match accept_hdr_async_with_config(stream, check_path, Some(config)).await {
+        Ok(ws) => ws,
+        Err(err) => {
+            warn!(
+                "Error accepting collaboration connection from {}: {}",
+                addr, err
+            );
+            return;
+        },
+    };
+    let room_id = match room_id {
+        Some(room_id) => room_id,
+        None => return,
+    };
+
+    // The handshake callback can't wait on the database, so the connection is closed right after
+    // it's accepted if the client isn't allowed in the room
+    let joined = match authorize(&pool, login_token, room_id).await {
+        Ok(user_id) => rooms.join(room_id).map(|joined| (user_id, joined)),
+        Err(err) => Err(err),
+    };
+    let (user_id, (room, mut rx)) = match joined {
+        Ok(joined) => joined,
+        Err(err) => {
+            info!(
+                "Rejected {} from collaboration room {}: {}",
+                addr, room_id, err
+            );
+            let _ = ws
+                .send(Message::Text(build_error_message(err.to_owned())))
+                .await;
+            let _ = ws
+                .close(Some(CloseFrame {
+                    code: CloseCode::Policy,
+                    reason: err.into(),
+                }))
+                .await;
+            return;
+        },
+    };
+    let conn_id = rooms.next_conn_id.fetch_add(1, Ordering::Relaxed);
+    let client_id = build_client_id(user_id, conn_id);
+    info!(
+        "{} joined collaboration room {} as {}",
+        addr, room_id, client_id
+    );
+
+    let (mut sink, mut stream) = ws.split();
+    let joined_msg = serde_json::to_string(&ServerMessage::Joined {
+        client_id: client_id.clone(),
+    })
+    .unwrap();
+    let mut outgoing = match sink.send(Message::Text(joined_msg)).await {
+        Ok(()) => Some(room.build_sync_message()),
+        // The loop below stops as soon as it sees that the connection was closed
+        Err(_) => None,
+    };
+    loop {
+        if let Some(msg) = outgoing.take() {
+            if sink.send(Message::Text(msg)).await.is_err() {
+                break;
+            }
+        }
+
+        tokio::select! {
+            msg = stream.next() => match msg {
+                Some(Ok(Message::Text(msg))) => {
+                    if let Err(err) = room.handle_client_message(conn_id, &client_id, &msg) {
+                        outgoing = Some(build_error_message(err));
+                    }
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pings are answered automatically
+                Some(Ok(_)) => (),
+            },
+            relayed = rx.recv() => match relayed {
+                Ok((sender_conn_id, _)) if sender_conn_id == conn_id => (),
+                Ok((_, msg)) => outgoing = Some((*msg).clone()),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "{} fell {} operations behind in collaboration room {}; re-syncing",
+                        addr, skipped, room_id
+                    );
+                    outgoing = Some(room.build_sync_message());
+                },
+                Err(RecvError::Closed) => break,
+            },
+        }
+    }
+
+    drop(rx);
+    rooms.leave(room_id);
+    info!("{} left collaboration room {}", addr, room_id);
+}
+
+pub async fn run_collaboration_server(port: u16, pool: DbPool) {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Error binding collaboration server to {}: {}", addr, err);
+            return;
+        },
+    };
+    info!("Collaboration server listening on {}", addr);
+
+    let rooms = Arc::new(Rooms::default());
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => {
+                tokio::spawn(handle_connection(
+                    Arc::clone(&rooms),
+                    pool.clone(),
+                    stream,
+                    addr,
+                ));
+            },
+            Err(err) => warn!("Error accepting collaboration connection: {}", err),
+        }
+    }
+}
@@ -2,6 +2,8 @@ use dotenv;
 
 pub struct Conf {
     pub auth_token: String,
+    /// Port that the realtime collaboration WebSocket server listens on
+    pub collaboration_port: u16,
 }
 
 lazy_static! {
@@ -13,6 +15,14 @@ impl Default for Conf {
         Conf {
             auth_token: dotenv::var("AUTH_TOKEN")
                 .expect("The `AUTH_TOKEN` environment variable must be supplied"),
+            collaboration_port: dotenv::var("COLLABORATION_PORT")
+                .ok()
+                .map(|port| {
+                    port.parse().expect(
+                        "The `COLLABORATION_PORT` environment variable must be a valid port",
+                    )
+                })
+                .unwrap_or(7468),
         }
     }
 }
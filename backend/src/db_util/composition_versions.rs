@@ -0,0 +1,169 @@
+use diesel::{dsl::max, prelude::*, QueryResult};
+
+use crate::{
+    db_util::get_and_create_tag_ids,
+    models::{
+        compositions::{NewCompositionTag, NewCompositionVersion},
+        midi_composition::{NewMIDICompositionVersion, NewMidiCompositionTag},
+    },
+};
+
+/// Returns `true` if the composition exists and is owned by `user_id`.  Compositions saved without
+/// being logged in don't have an owner, so they can't be versioned.
+pub fn is_composition_owner(
+    conn: &MysqlConnection,
+    composition_id: i64,
+    user_id: Option<i64>,
+) -> QueryResult<bool> {
+    use crate::schema::compositions;
+
+    let user_id = match user_id {
+        Some(user_id) => user_id,
+        None => return Ok(false),
+    };
+    let owner: Option<Option<i64>> = compositions::table
+        .find(composition_id)
+        .select(compositions::dsl::user_id)
+        .first(conn)
+        .optional()?;
+    Ok(owner == Some(Some(user_id)))
+}
+
+/// Stores the provided state as the next version of the composition, returning the new version
+/// number.
+pub fn insert_composition_version(
+    conn: &MysqlConnection,
+    composition_id: i64,
+    title: String,
+    description: String,
+    content: String,
+) -> QueryResult<i64> {
+    use crate::schema::composition_versions;
+
+    let latest_version: Option<i64> = composition_versions::table
+        .filter(composition_versions::dsl::composition_id.eq(composition_id))
+        .select(max(composition_versions::dsl::version))
+        .first(conn)?;
+    let version = latest_version.unwrap_or(0) + 1;
+
+    diesel::insert_into(composition_versions::table)
+        .values(NewCompositionVersion {
+            composition_id,
+            version,
+            title,
+            description,
+            content,
+        })
+        .execute(conn)?;
+    Ok(version)
+}
+
+/// Replaces all tags of the composition with `tags`
+pub fn set_composition_tags(
+    conn: &MysqlConnection,
+    composition_id: i64,
+    tags: Vec<String>,
+) -> QueryResult<()> {
+    use crate::schema::compositions_tags;
+
+    diesel::delete(
+        compositions_tags::table.filter(compositions_tags::dsl::composition_id.eq(composition_id)),
+    )
+    .execute(conn)?;
+
+    let tag_count = tags.len();
+    let tag_ids = get_and_create_tag_ids(conn, tags)?;
+    assert_eq!(tag_count, tag_ids.len());
+
+    let new_tags: Vec<NewCompositionTag> = tag_ids
+        .into_iter()
+        .map(|tag_id| NewCompositionTag {
+            composition_id,
+            tag_id,
+        })
+        .collect();
+    diesel::insert_into(compositions_tags::table)
+        .values(new_tags)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Returns `true` if the MIDI composition exists and is owned by `user_id`
+pub fn is_midi_composition_owner(
+    conn: &MysqlConnection,
+    midi_composition_id: i64,
+    user_id: Option<i64>,
+) -> QueryResult<bool> {
+    use crate::schema::midi_compositions;
+
+    let user_id = match user_id {
+        Some(user_id) => user_id,
+        None => return Ok(false),
+    };
+    let owner: Option<Option<i64>> = midi_compositions::table
+        .find(midi_composition_id)
+        .select(midi_compositions::dsl::user_id)
+        .first(conn)
+        .optional()?;
+    Ok(owner == Some(Some(user_id)))
+}
+
+/// Stores the provided state as the next version of the MIDI composition, returning the new
+/// version number.
+pub fn insert_midi_composition_version(
+    conn: &MysqlConnection,
+    midi_composition_id: i64,
+    name: String,
+    description: String,
+    composition_json: String,
+) -> QueryResult<i64> {
+    use crate::schema::midi_composition_versions;
+
+    let latest_version: Option<i64> = midi_composition_versions::table
+        .filter(midi_composition_versions::dsl::midi_composition_id.eq(midi_composition_id))
+        .select(max(midi_composition_versions::dsl::version))
+        .first(conn)?;
+    let version = latest_version.unwrap_or(0) + 1;
+
+    diesel::insert_into(midi_composition_versions::table)
+        .values(NewMIDICompositionVersion {
+            midi_composition_id,
+            version,
+            name,
+            description,
+            composition_json,
+        })
+        .execute(conn)?;
+    Ok(version)
+}
+
+/// Replaces all tags of the MIDI composition with `tags`
+pub fn set_midi_composition_tags(
+    conn: &MysqlConnection,
+    midi_composition_id: i64,
+    tags: Vec<String>,
+) -> QueryResult<()> {
+    use crate::schema::midi_compositions_tags;
+
+    diesel::delete(
+        midi_compositions_tags::table
+            .filter(midi_compositions_tags::dsl::midi_composition_id.eq(midi_composition_id)),
+    )
+    .execute(conn)?;
+
+    let tag_count = tags.len();
+    let tag_ids = get_and_create_tag_ids(conn, tags)?;
+    assert_eq!(tag_count, tag_ids.len());
+
+    let new_tags: Vec<NewMidiCompositionTag> = tag_ids
+        .into_iter()
+        .map(|tag_id| NewMidiCompositionTag {
+            midi_composition_id,
+            tag_id,
+        })
+        .collect();
+    diesel::insert_into(midi_compositions_tags::table)
+        .values(new_tags)
+        .execute(conn)?;
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use diesel::{dsl::max, prelude::*, QueryResult};
+
+use crate::{
+    db_util::last_insert_id,
+    models::effects::{hash_effect_code, InsertableEffect, NewEffectRevision},
+};
+
+/// Inserts a new effect along with its first revision, returning the ID of the new effect.  Should
+/// be called inside of a transaction.
+pub fn insert_effect(conn: &MysqlConnection, effect: &InsertableEffect) -> QueryResult<i64> {
+    use crate::schema::{effect_revisions, effects};
+
+    diesel::insert_into(effects::table)
+        .values(effect)
+        .execute(conn)?;
+    let effect_id: i64 = diesel::select(last_insert_id).first(conn)?;
+
+    diesel::insert_into(effect_revisions::table)
+        .values(NewEffectRevision {
+            effect_id,
+            revision: 1,
+            code: effect.code.clone(),
+            code_hash: hash_effect_code(&effect.code),
+        })
+        .execute(conn)?;
+    Ok(effect_id)
+}
+
+/// Stores `code` as the next revision of the effect and makes it the effect's current code,
+/// returning the new revision number.  Should be called inside of a transaction.
+pub fn insert_effect_revision(
+    conn: &MysqlConnection,
+    effect_id: i64,
+    code: String,
+) -> QueryResult<i64> {
+    use crate::schema::{effect_revisions, effects};
+
+    let latest_revision: Option<i64> = effect_revisions::table
+        .filter(effect_revisions::dsl::effect_id.eq(effect_id))
+        .select(max(effect_revisions::dsl::revision))
+        .first(conn)?;
+    let revision = latest_revision.unwrap_or(0) + 1;
+
+    diesel::update(effects::table.find(effect_id))
+        .set((
+            effects::dsl::code.eq(&code),
+            effects::dsl::revision.eq(revision),
+        ))
+        .execute(conn)?;
+    diesel::insert_into(effect_revisions::table)
+        .values(NewEffectRevision {
+            effect_id,
+            revision,
+            code_hash: hash_effect_code(&code),
+            code,
+        })
+        .execute(conn)?;
+    Ok(revision)
+}
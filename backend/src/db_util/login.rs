@@ -1,10 +1,9 @@
-use std::convert::TryFrom;
-
+use argon2::Argon2;
 use diesel::{prelude::*, QueryResult};
 use scrypt::{
     password_hash::{
         rand_core::{OsRng, RngCore},
-        PasswordHash, PasswordHasher, PasswordVerifier, Salt, SaltString,
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
     },
     Scrypt,
 };
@@ -14,24 +13,44 @@ use crate::{
     WebSynthDbConn,
 };
 
+/// Login tokens stop being accepted this long after they're issued unless they're refreshed first
+pub const LOGIN_TOKEN_TTL_DAYS: i64 = 30;
+
 fn hash_password(password: &str) -> Result<String, scrypt::password_hash::Error> {
     let salt = SaltString::generate(&mut OsRng);
-    let params = scrypt::Params::new(15, 2, 2).unwrap();
-    let hash = Scrypt
-        .hash_password_customized(
-            password.as_bytes(),
-            None,
-            None,
-            params,
-            Salt::try_from(salt.as_ref())?,
-        )?
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
         .to_string();
     Ok(hash)
 }
 
+/// Passwords used to be hashed with scrypt, so those hashes are still accepted.  They're replaced
+/// with argon2 hashes the next time the user logs in.
 pub fn verify_password(password: &str, hash: &str) -> bool {
-    let hash = PasswordHash::new(hash).unwrap();
-    Scrypt.verify_password(password.as_bytes(), &hash).is_ok()
+    let hash = match PasswordHash::new(hash) {
+        Ok(hash) => hash,
+        Err(err) => {
+            error!("Invalid password hash: {}", err);
+            return false;
+        },
+    };
+
+    if hash.algorithm == scrypt::ALG_ID {
+        Scrypt.verify_password(password.as_bytes(), &hash).is_ok()
+    } else {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    }
+}
+
+/// Returns `true` if the hash was created with an algorithm other than the one currently used for
+/// new passwords
+pub fn password_needs_rehash(hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(hash) => hash.algorithm != argon2::ARGON2ID_IDENT,
+        Err(_) => false,
+    }
 }
 
 pub fn generate_login_token() -> String {
@@ -63,21 +82,30 @@ pub async fn get_user_by_username(
     Ok(user)
 }
 
-/// If the login token is valid, returns the ID of the logged-in user.
-pub async fn validate_login_token(
-    conn: &WebSynthDbConn,
+fn current_time() -> chrono::NaiveDateTime { chrono::Utc::now().naive_utc() }
+
+/// If the login token is valid and hasn't expired, returns the ID of the logged-in user.
+pub fn get_login_token_user_id(
+    conn: &MysqlConnection,
     login_token: String,
 ) -> QueryResult<Option<i64>> {
     use crate::schema::login_tokens;
 
-    conn.run(move |conn| -> QueryResult<Option<_>> {
-        login_tokens::table
-            .filter(login_tokens::dsl::token.eq(login_token))
-            .select(login_tokens::dsl::user_id)
-            .first(conn)
-            .optional()
-    })
-    .await
+    login_tokens::table
+        .filter(login_tokens::dsl::token.eq(login_token))
+        .filter(login_tokens::dsl::expires_at.gt(current_time()))
+        .select(login_tokens::dsl::user_id)
+        .first(conn)
+        .optional()
+}
+
+/// Async version of `get_login_token_user_id` that runs on a connection from Rocket's pool
+pub async fn validate_login_token(
+    conn: &WebSynthDbConn,
+    login_token: String,
+) -> QueryResult<Option<i64>> {
+    conn.run(move |conn| get_login_token_user_id(conn, login_token))
+        .await
 }
 
 pub async fn insert_new_user(
@@ -114,6 +142,8 @@ pub async fn insert_new_user(
     Ok(user_id)
 }
 
+/// Stores a new login token for the user, valid for `LOGIN_TOKEN_TTL_DAYS`.  Any of the user's
+/// tokens that have already expired are cleaned up at the same time.
 pub async fn insert_new_login_token(
     conn: &WebSynthDbConn,
     user_id: i64,
@@ -122,8 +152,72 @@ pub async fn insert_new_login_token(
     use crate::schema::login_tokens;
 
     conn.run(move |conn| {
-        diesel::insert_into(login_tokens::table)
-            .values(NewLoginToken { user_id, token })
+        let conn = &*conn;
+        conn.transaction(|| {
+            diesel::delete(
+                login_tokens::table
+                    .filter(login_tokens::dsl::user_id.eq(user_id))
+                    .filter(login_tokens::dsl::expires_at.le(current_time())),
+            )
+            .execute(conn)?;
+
+            diesel::insert_into(login_tokens::table)
+                .values(NewLoginToken {
+                    user_id,
+                    token,
+                    expires_at: current_time() + chrono::Duration::days(LOGIN_TOKEN_TTL_DAYS),
+                })
+                .execute(conn)
+        })
+    })
+    .await
+    .map(drop)
+}
+
+/// Exchanges a valid login token for a new one, revoking the old token.  Returns `None` if the
+/// provided token is invalid or has expired.
+pub async fn refresh_login_token(
+    conn: &WebSynthDbConn,
+    login_token: String,
+) -> QueryResult<Option<String>> {
+    let user_id = match validate_login_token(conn, login_token.clone()).await? {
+        Some(user_id) => user_id,
+        None => return Ok(None),
+    };
+
+    let new_login_token = generate_login_token();
+    insert_new_login_token(conn, user_id, new_login_token.clone()).await?;
+    revoke_login_token(conn, login_token).await?;
+    Ok(Some(new_login_token))
+}
+
+pub async fn revoke_login_token(conn: &WebSynthDbConn, login_token: String) -> QueryResult<()> {
+    use crate::schema::login_tokens;
+
+    conn.run(move |conn| {
+        diesel::delete(login_tokens::table.filter(login_tokens::dsl::token.eq(login_token)))
+            .execute(conn)
+    })
+    .await
+    .map(drop)
+}
+
+/// Re-hashes the user's password with the current algorithm
+pub async fn update_password_hash(
+    conn: &WebSynthDbConn,
+    user_id: i64,
+    password: String,
+) -> QueryResult<()> {
+    use crate::schema::users;
+
+    let hashed_password = hash_password(&password).map_err(|err| {
+        error!("Error hashing password: {}", err);
+        diesel::result::Error::RollbackTransaction
+    })?;
+
+    conn.run(move |conn| {
+        diesel::update(users::table.find(user_id))
+            .set(users::dsl::hashed_password.eq(hashed_password))
             .execute(conn)
     })
     .await
@@ -132,9 +226,28 @@ pub async fn insert_new_login_token(
 
 #[test]
 fn test_hash_password() {
+    use scrypt::password_hash::Salt;
+    use std::convert::TryFrom;
+
     let password = "password";
     let hash = hash_password(password).unwrap();
     assert!(verify_password(password, &hash));
+    assert!(!verify_password("wrong password", &hash));
+    assert!(!password_needs_rehash(&hash));
+
+    let salt = SaltString::generate(&mut OsRng);
+    let legacy_hash = Scrypt
+        .hash_password_customized(
+            password.as_bytes(),
+            None,
+            None,
+            scrypt::Params::new(10, 2, 2).unwrap(),
+            Salt::try_from(salt.as_ref()).unwrap(),
+        )
+        .unwrap()
+        .to_string();
+    assert!(verify_password(password, &legacy_hash));
+    assert!(password_needs_rehash(&legacy_hash));
 }
 
 pub async fn get_logged_in_user_id(
@@ -0,0 +1,38 @@
+use diesel::{prelude::*, QueryResult};
+
+use super::composition_versions::is_midi_composition_owner;
+
+/// Returns `true` if the user owns the MIDI composition or has been added as one of its
+/// collaborators by its owner
+pub fn can_collaborate_on_midi_composition(
+    conn: &MysqlConnection,
+    midi_composition_id: i64,
+    user_id: i64,
+) -> QueryResult<bool> {
+    use crate::schema::midi_composition_collaborators;
+
+    if is_midi_composition_owner(conn, midi_composition_id, Some(user_id))? {
+        return Ok(true);
+    }
+    let collaborator: Option<i64> = midi_composition_collaborators::table
+        .find((midi_composition_id, user_id))
+        .select(midi_composition_collaborators::dsl::user_id)
+        .first(conn)
+        .optional()?;
+    Ok(collaborator.is_some())
+}
+
+/// Returns the usernames of all collaborators of the MIDI composition, not including its owner
+pub fn get_midi_composition_collaborators(
+    conn: &MysqlConnection,
+    midi_composition_id: i64,
+) -> QueryResult<Vec<String>> {
+    use crate::schema::{midi_composition_collaborators, users};
+
+    midi_composition_collaborators::table
+        .inner_join(users::table)
+        .filter(midi_composition_collaborators::dsl::midi_composition_id.eq(midi_composition_id))
+        .select(users::dsl::username)
+        .order(users::dsl::username.asc())
+        .load(conn)
+}
@@ -9,8 +9,13 @@ use crate::{
     WebSynthDbConn,
 };
 
+pub mod composition_versions;
+pub mod effects;
 pub mod login;
+pub mod midi_composition_collaborators;
 pub mod private_sample_libraries;
+pub mod search;
+pub mod user_samples;
 
 // Facilitate getting the primary key of the last inserted item
 //
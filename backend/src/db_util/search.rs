@@ -0,0 +1,159 @@
+use diesel::{prelude::*, QueryResult};
+use itertools::Itertools;
+use rocket::serde::json::Json;
+
+use crate::{
+    models::{
+        search::{SearchParams, SearchResultDescriptor, SearchResults, SearchSort},
+        tags::EntityIdTag,
+    },
+    WebSynthDbConn,
+};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// (id, title, description, user_id, user_name, use_count)
+pub type SearchRow = (i64, String, String, Option<i64>, Option<String>, i64);
+
+/// Validated version of `SearchParams` that's passed to the query for each searchable entity
+pub struct SearchQuery {
+    /// One `LIKE` pattern for each term of the text query
+    pub like_patterns: Vec<String>,
+    pub tags: Vec<String>,
+    pub sort: SearchSort,
+    pub page: i64,
+    pub page_size: i64,
+    /// Set for entities that can be private, so that the user's own private entities are included
+    pub logged_in_user_id: Option<i64>,
+}
+
+impl SearchQuery {
+    pub fn new(params: SearchParams, logged_in_user_id: Option<i64>) -> Self {
+        SearchQuery {
+            like_patterns: build_like_patterns(params.q.as_deref().unwrap_or_default()),
+            tags: params.tags.into_iter().unique().collect(),
+            sort: params.sort.unwrap_or_default(),
+            page: params.page.unwrap_or(0).max(0),
+            page_size: params
+                .page_size
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+            logged_in_user_id,
+        }
+    }
+
+    pub fn offset(&self) -> i64 { self.page * self.page_size }
+}
+
+/// Builds a pattern matching each whitespace-separated term in `query` anywhere in a string.  `%`
+/// and `_` are escaped so that they're matched literally.
+pub fn build_like_patterns(query: &str) -> Vec<String> {
+    query
+        .split_whitespace()
+        .map(|term| {
+            let escaped = term
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            format!("%{}%", escaped)
+        })
+        .collect()
+}
+
+/// Given the tags matching any of `tags` for a set of entities, returns the IDs of the entities
+/// that have all of them.  `tags` must not contain duplicates.
+pub fn get_ids_with_all_tags(entity_tags: Vec<EntityIdTag>, tags: &[String]) -> Vec<i64> {
+    entity_tags
+        .into_iter()
+        .filter(|entity_tag| tags.contains(&entity_tag.tag))
+        .map(|entity_tag| (entity_tag.entity_id, entity_tag.tag))
+        .unique()
+        .into_group_map()
+        .into_iter()
+        .filter(|(_, entity_tags)| entity_tags.len() == tags.len())
+        .map(|(entity_id, _)| entity_id)
+        .sorted()
+        .collect()
+}
+
+/// Runs `search` and builds the response from the matching page of rows.  `search` returns the
+/// total number of matches, the rows for the requested page, and the tags for those rows.
+pub async fn run_search(
+    conn: WebSynthDbConn,
+    query: SearchQuery,
+    entity_name: &'static str,
+    search: impl FnOnce(
+            &mut MysqlConnection,
+            &SearchQuery,
+        ) -> QueryResult<(i64, Vec<SearchRow>, Vec<EntityIdTag>)>
+        + Send
+        + 'static,
+) -> Result<Json<SearchResults>, String> {
+    let (query, (total, rows, page_tags)) = conn
+        .run(move |conn| {
+            let res = search(conn, &query);
+            res.map(|res| (query, res))
+        })
+        .await
+        .map_err(|err| {
+            error!("DB error searching {}: {}", entity_name, err);
+            format!("DB error searching {}", entity_name)
+        })?;
+
+    let mut tags_by_entity_id = page_tags.into_iter().into_group_map_by(|tag| tag.entity_id);
+    let results = rows
+        .into_iter()
+        .map(
+            |(id, title, description, user_id, user_name, use_count)| SearchResultDescriptor {
+                id,
+                title,
+                description,
+                tags: tags_by_entity_id
+                    .remove(&id)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|tag| tag.tag)
+                    .collect(),
+                user_id,
+                user_name,
+                use_count,
+            },
+        )
+        .collect();
+
+    Ok(Json(SearchResults {
+        results,
+        total,
+        page: query.page,
+        page_size: query.page_size,
+    }))
+}
+
+#[test]
+fn test_build_like_patterns() {
+    assert_eq!(build_like_patterns(""), Vec::<String>::new());
+    assert_eq!(build_like_patterns("  bass  100%_wet "), vec![
+        String::from("%bass%"),
+        String::from("%100\\%\\_wet%")
+    ]);
+}
+
+#[test]
+fn test_get_ids_with_all_tags() {
+    let entity_tag = |entity_id: i64, tag: &str| EntityIdTag {
+        entity_id,
+        tag: tag.to_owned(),
+    };
+    let entity_tags = vec![
+        entity_tag(1, "ambient"),
+        entity_tag(1, "pad"),
+        entity_tag(2, "ambient"),
+        entity_tag(3, "pad"),
+        entity_tag(3, "ambient"),
+        entity_tag(3, "ambient"),
+        entity_tag(4, "bass"),
+    ];
+    let tags = vec![String::from("ambient"), String::from("pad")];
+    assert_eq!(get_ids_with_all_tags(entity_tags, &tags), vec![1, 3]);
+}
@@ -0,0 +1,19 @@
+use diesel::{prelude::*, QueryResult};
+
+use crate::{models::user_samples::UserSample, WebSynthDbConn};
+
+/// Returns all samples uploaded by the user, newest first
+pub async fn get_user_samples_for_user(
+    conn: &WebSynthDbConn,
+    user_id: i64,
+) -> QueryResult<Vec<UserSample>> {
+    use crate::schema::user_samples;
+
+    conn.run(move |conn| {
+        user_samples::table
+            .filter(user_samples::dsl::user_id.eq(user_id))
+            .order(user_samples::dsl::id.desc())
+            .load::<UserSample>(conn)
+    })
+    .await
+}
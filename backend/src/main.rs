@@ -14,14 +14,16 @@ extern crate log;
 extern crate rocket_sync_db_pools;
 
 use rocket::{
-    fairing::{Fairing, Info, Kind},
+    fairing::{AdHoc, Fairing, Info, Kind},
     http::{Method, Status},
     Request, Response,
 };
 
+pub mod collaboration;
 pub mod conf;
 pub mod db_util;
 pub mod models;
+pub mod rate_limit;
 pub mod routes;
 pub mod schema;
 
@@ -64,21 +66,45 @@ async fn main() {
 
     let mut ship = rocket::build()
         .attach(WebSynthDbConn::fairing())
+        .manage(rate_limit::RateLimiter::default())
         .mount("/", routes![
             routes::index,
             routes::create_effect,
             routes::list_effects,
+            routes::save_effect_revision,
+            routes::get_effect_revisions,
+            routes::get_effect_revision,
+            routes::register_compiled_effect_artifact,
             routes::save_composition,
+            routes::export_composition_bundle,
+            routes::import_composition_bundle,
             routes::get_compositions,
             routes::get_synth_presets,
             routes::create_synth_preset,
             routes::get_synth_voice_presets,
             routes::create_synth_voice_preset,
+            routes::publish_synth_preset,
+            routes::unpublish_synth_preset,
+            routes::fork_synth_preset,
+            routes::publish_synth_voice_preset,
+            routes::unpublish_synth_voice_preset,
+            routes::fork_synth_voice_preset,
             routes::get_composition_by_id,
+            routes::get_composition_versions,
+            routes::restore_composition_version,
             routes::list_remote_samples,
             routes::store_remote_sample,
+            routes::list_user_samples,
+            routes::upload_user_sample,
+            routes::rename_user_sample,
+            routes::delete_user_sample,
             routes::save_midi_composition,
             routes::get_midi_compositions,
+            routes::get_midi_composition_versions,
+            routes::restore_midi_composition_version,
+            routes::get_midi_composition_collaborator_usernames,
+            routes::add_midi_composition_collaborator,
+            routes::remove_midi_composition_collaborator,
             routes::get_looper_presets,
             routes::get_looper_preset_by_id,
             routes::create_looper_preset,
@@ -88,13 +114,37 @@ async fn main() {
             routes::login::login,
             routes::login::register,
             routes::login::get_logged_in_username,
+            routes::login::refresh_login_token,
+            routes::login::logout,
             routes::get_wavetable_presets,
             routes::get_wavetable_preset_by_id,
             routes::create_wavetable_preset,
             routes::get_wavetable_preset_tags,
+            routes::get_sequencer_presets,
+            routes::get_sequencer_preset_by_id,
+            routes::create_sequencer_preset,
+            routes::get_sequencer_preset_tags,
+            routes::search_compositions,
+            routes::search_midi_compositions,
+            routes::search_synth_presets,
+            routes::search_looper_presets,
         ])
         .attach(CorsFairing);
 
+    match collaboration::build_db_pool(&ship) {
+        // Started once Rocket is up so that it isn't left running if Rocket fails to launch
+        Ok(pool) =>
+            ship = ship.attach(AdHoc::on_liftoff("Collaboration Server", |_| {
+                Box::pin(async move {
+                    tokio::spawn(collaboration::run_collaboration_server(
+                        conf::CONF.collaboration_port,
+                        pool,
+                    ));
+                })
+            })),
+        Err(err) => error!("{}; not starting collaboration server", err),
+    }
+
     if cfg!(not(debug_assertions)) {
         ship = ship.attach(rocket_async_compression::Compression::fairing());
     }
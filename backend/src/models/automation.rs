@@ -0,0 +1,155 @@
+//! Automation lanes are stored as part of the serialized state of `customAudio/automation` foreign
+//! connectables, so they're persisted along with the rest of the composition's `vcmState`.  These
+//! models mirror `src/graphEditor/nodes/CustomAudio/Automation/types.ts` and are used to validate
+//! automation when compositions are saved.
+
+use serde_json::{Map, Value};
+
+pub const AUTOMATION_NODE_TYPE: &str = "customAudio/automation";
+/// Must match `MAX_LANE_COUNT` in `engine/automation/src/lib.rs`
+pub const MAX_LANE_COUNT: usize = 16;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum AutomationCurve {
+    Step,
+    Linear,
+    Exponential { exponent: f32 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AutomationPoint {
+    pub beat: f64,
+    pub value: f32,
+    pub curve: AutomationCurve,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AutomationLane {
+    pub name: String,
+    pub points: Vec<AutomationPoint>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AutomationNodeState {
+    pub lanes: Vec<AutomationLane>,
+}
+
+impl AutomationNodeState {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.lanes.len() > MAX_LANE_COUNT {
+            return Err(format!(
+                "Automation can have at most {} lanes; found {}",
+                MAX_LANE_COUNT,
+                self.lanes.len()
+            ));
+        }
+
+        for lane in &self.lanes {
+            let mut last_beat = f64::NEG_INFINITY;
+            for point in &lane.points {
+                if !point.beat.is_finite() || !point.value.is_finite() {
+                    return Err(format!(
+                        "Automation lane \"{}\" has a non-finite point",
+                        lane.name
+                    ));
+                }
+                if point.beat < 0. {
+                    return Err(format!(
+                        "Automation lane \"{}\" has a point at a negative beat",
+                        lane.name
+                    ));
+                }
+                if point.beat < last_beat {
+                    return Err(format!(
+                        "Automation lane \"{}\" has points that aren't sorted by beat",
+                        lane.name
+                    ));
+                }
+                if let AutomationCurve::Exponential { exponent } = point.curve {
+                    if !exponent.is_finite() || exponent <= 0. {
+                        return Err(format!(
+                            "Automation lane \"{}\" has an invalid curve exponent",
+                            lane.name
+                        ));
+                    }
+                }
+                last_beat = point.beat;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct ForeignConnectable {
+    #[serde(rename = "type")]
+    pub _type: String,
+    #[serde(rename = "serializedState")]
+    pub serialized_state: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct VcmState {
+    #[serde(default)]
+    pub foreign_connectables: Vec<ForeignConnectable>,
+}
+
+/// Validates the state of all automation nodes in the `vcmState` entry of a composition's content.
+/// Compositions without a `vcmState` entry or without any automation nodes are always valid.
+pub fn validate_composition_automation(content: &Map<String, Value>) -> Result<(), String> {
+    let vcm_state: VcmState = match content.get("vcmState") {
+        Some(Value::String(raw)) => serde_json::from_str(raw),
+        Some(val) => serde_json::from_value(val.clone()),
+        None => return Ok(()),
+    }
+    .map_err(|err| format!("Invalid `vcmState` in composition: {}", err))?;
+
+    for connectable in vcm_state.foreign_connectables {
+        if connectable._type != AUTOMATION_NODE_TYPE {
+            continue;
+        }
+        let state = match connectable.serialized_state {
+            Some(state) => state,
+            None => continue,
+        };
+
+        let state: AutomationNodeState = serde_json::from_value(state)
+            .map_err(|err| format!("Invalid automation state in composition: {}", err))?;
+        state.validate()?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_automation_point_validation() {
+    let lane = |beats: &[f64]| AutomationNodeState {
+        lanes: vec![AutomationLane {
+            name: String::from("lane"),
+            points: beats
+                .iter()
+                .map(|&beat| AutomationPoint {
+                    beat,
+                    value: 0.,
+                    curve: AutomationCurve::Linear,
+                })
+                .collect(),
+        }],
+    };
+
+    assert!(lane(&[0., 0., 1.5]).validate().is_ok());
+    assert_eq!(
+        lane(&[-1., 0.]).validate(),
+        Err(String::from(
+            "Automation lane \"lane\" has a point at a negative beat"
+        ))
+    );
+    assert_eq!(
+        lane(&[2., 1.]).validate(),
+        Err(String::from(
+            "Automation lane \"lane\" has points that aren't sorted by beat"
+        ))
+    );
+}
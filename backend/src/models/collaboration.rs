@@ -0,0 +1,240 @@
+//! Messages exchanged over the realtime collaboration WebSocket along with the state kept for each
+//! room.  Note operations are mirrored by `NoteOperation` in
+//! `engine/note_container/src/collaboration.rs`, which merges them into the MIDI editor on each
+//! client.  Every note is a last-writer-wins register, so the server only needs to keep the latest
+//! operation for each note in order to bring newly joined clients up to date.
+
+use std::collections::HashMap;
+
+use crate::models::validation::{check_len, check_non_negative};
+
+pub const MAX_CLIENT_ID_LENGTH: usize = 64;
+pub const MAX_LINE_IX: usize = 1024;
+/// Max number of notes, including deleted ones, that a single room can contain
+pub const MAX_ROOM_NOTES: usize = 50_000;
+
+/// Globally unique ID of a note: the client that created it and its clock when it did
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CollabNoteId {
+    pub client_id: String,
+    pub clock: u32,
+}
+
+/// Orders operations consistently across all clients: by Lamport clock and then by client ID
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OpStamp {
+    pub clock: u32,
+    pub client_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NotePlacement {
+    pub line_ix: usize,
+    pub start_beat: f64,
+    pub length: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum NoteOp {
+    Insert(NotePlacement),
+    Move(NotePlacement),
+    Delete,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteOperation {
+    pub note_id: CollabNoteId,
+    pub stamp: OpStamp,
+    pub op: NoteOp,
+}
+
+fn check_client_id(field: &str, client_id: &str) -> Result<(), String> {
+    if client_id.is_empty() {
+        return Err(format!("`{}` must not be empty", field));
+    }
+    check_len(field, client_id, MAX_CLIENT_ID_LENGTH)
+}
+
+/// Client IDs are assigned by the server to each connection so that clients can't make
+/// operations on behalf of others.  Connection IDs are never reused while the server is running,
+/// so client IDs are unique for the whole lifetime of each room.
+pub fn build_client_id(user_id: i64, conn_id: u64) -> String { format!("{}-{}", user_id, conn_id) }
+
+impl NoteOperation {
+    /// Checks that the operation is well-formed and that it was made by `client_id`, the ID
+    /// assigned to the connection that sent it.  Any client can move or delete notes created by
+    /// others, but new notes must be created with the sender's own ID.
+    pub fn validate(&self, client_id: &str) -> Result<(), String> {
+        check_client_id("noteId.clientId", &self.note_id.client_id)?;
+        if self.stamp.client_id != client_id {
+            return Err(String::from(
+                "`stamp.clientId` must be the client ID assigned by the server",
+            ));
+        }
+
+        match self.op {
+            NoteOp::Insert(_) if self.note_id.client_id != client_id =>
+                return Err(String::from(
+                    "Notes must be inserted with the client ID assigned by the server",
+                )),
+            NoteOp::Insert(placement) | NoteOp::Move(placement) => {
+                if placement.line_ix > MAX_LINE_IX {
+                    return Err(format!("`lineIx` must be at most {}", MAX_LINE_IX));
+                }
+                check_non_negative("startBeat", placement.start_beat)?;
+                check_non_negative("length", placement.length)?;
+                if placement.length == 0. {
+                    return Err(String::from("`length` must be greater than zero"));
+                }
+            },
+            NoteOp::Delete => (),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ClientMessage {
+    Op { op: NoteOperation },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ServerMessage<'a> {
+    /// Sent first when a client joins a room with the ID that it must make its operations with
+    #[serde(rename_all = "camelCase")]
+    Joined {
+        client_id: String,
+    },
+    /// Sent when a client joins a room, or if it falls too far behind to be relayed every
+    /// operation.  Contains the latest operation for every note in the room.
+    Sync {
+        ops: Vec<&'a NoteOperation>,
+    },
+    Op {
+        op: &'a NoteOperation,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Each room is used to edit one saved MIDI composition.  Its ID comes from the path that the
+/// WebSocket connection is opened on: `/rooms/<midi_composition_id>`
+pub fn parse_room_id(path: &str) -> Option<i64> {
+    let room_id = path.strip_prefix("/rooms/")?;
+    if room_id.is_empty() || !room_id.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    room_id.parse().ok()
+}
+
+/// Browsers can't set headers on WebSocket connections, so they pass their login token in the
+/// `token` query parameter instead of the `Authorization` header
+pub fn parse_login_token_param(query: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|param| param.strip_prefix("token="))
+        .find(|token| !token.is_empty())
+        .and_then(|token| urlencoding::decode(token).ok())
+        .map(|token| token.into_owned())
+}
+
+/// The merged state of all notes in a room
+#[derive(Default)]
+pub struct CollabRoomState {
+    notes: HashMap<CollabNoteId, NoteOperation>,
+}
+
+impl CollabRoomState {
+    /// Stores the operation if it's newer than the latest one for its note, returning `false` if it
+    /// was stale and doesn't need to be relayed to other clients.
+    pub fn apply(&mut self, op: NoteOperation) -> Result<bool, String> {
+        match self.notes.get(&op.note_id) {
+            Some(latest) if latest.stamp >= op.stamp => return Ok(false),
+            Some(_) => (),
+            None if self.notes.len() >= MAX_ROOM_NOTES =>
+                return Err(format!(
+                    "Rooms can contain at most {} notes",
+                    MAX_ROOM_NOTES
+                )),
+            None => (),
+        }
+        self.notes.insert(op.note_id.clone(), op);
+        Ok(true)
+    }
+
+    pub fn snapshot(&self) -> Vec<&NoteOperation> { self.notes.values().collect() }
+}
+
+#[test]
+fn test_collab_room_state() {
+    let op: NoteOperation = serde_json::from_value(serde_json::json!({
+        "noteId": { "clientId": "a", "clock": 1 },
+        "stamp": { "clientId": "a", "clock": 1 },
+        "op": { "type": "insert", "lineIx": 3, "startBeat": 2.5, "length": 1 },
+    }))
+    .unwrap();
+    assert!(op.validate("a").is_ok());
+    // Clients can't make operations on behalf of others
+    assert!(op.validate("b").is_err());
+    assert_eq!(
+        op.op,
+        NoteOp::Insert(NotePlacement {
+            line_ix: 3,
+            start_beat: 2.5,
+            length: 1.,
+        })
+    );
+
+    let mut deleted = op.clone();
+    deleted.stamp = OpStamp {
+        clock: 2,
+        client_id: String::from("b"),
+    };
+    deleted.op = NoteOp::Delete;
+    // ...but can delete notes created by others
+    assert!(deleted.validate("b").is_ok());
+
+    let mut state = CollabRoomState::default();
+    assert_eq!(state.apply(op.clone()), Ok(true));
+    assert_eq!(state.apply(deleted.clone()), Ok(true));
+    // The insert is older than the delete, so replaying it doesn't resurrect the note
+    assert_eq!(state.apply(op.clone()), Ok(false));
+    assert_eq!(state.snapshot(), vec![&deleted]);
+
+    let mut invalid = op;
+    invalid.op = NoteOp::Move(NotePlacement {
+        line_ix: 0,
+        start_beat: f64::NAN,
+        length: 1.,
+    });
+    assert!(invalid.validate("a").is_err());
+
+    assert_eq!(
+        serde_json::to_value(&ServerMessage::Joined {
+            client_id: build_client_id(7, 3),
+        })
+        .unwrap(),
+        serde_json::json!({ "type": "joined", "clientId": "7-3" })
+    );
+
+    assert_eq!(parse_room_id("/rooms/12"), Some(12));
+    assert_eq!(parse_room_id("/rooms/../etc"), None);
+    assert_eq!(parse_room_id("/rooms/-12"), None);
+    assert_eq!(parse_room_id("/rooms/99999999999999999999"), None);
+    assert_eq!(parse_room_id("/other/12"), None);
+
+    assert_eq!(
+        parse_login_token_param("v=1&token=a%2Bb%3D%3D"),
+        Some(String::from("a+b=="))
+    );
+    assert_eq!(parse_login_token_param("token="), None);
+    assert_eq!(parse_login_token_param("other=token"), None);
+}
@@ -0,0 +1,248 @@
+//! A composition bundle is a single portable JSON document containing a composition along with
+//! everything it references: synth presets, voice presets, effects, and remote samples.  Bundles
+//! can be exported from one instance and imported into another, where all of the referenced rows
+//! are recreated with new IDs.  These models are mirrored by `CompositionBundle` in
+//! `engine/engine/src/views/composition_sharing/bundle.rs`.
+
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+/// Bumped whenever the bundle format changes in a way that older importers can't handle
+pub const COMPOSITION_BUNDLE_FORMAT_VERSION: u32 = 1;
+
+/// Nodes that were loaded from a saved synth preset store its ID under this key in their state
+pub const SYNTH_PRESET_ID_KEY: &str = "synthPresetId";
+pub const VOICE_PRESET_ID_KEY: &str = "voicePresetId";
+pub const EFFECT_ID_KEY: &str = "effectId";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundledComposition {
+    pub title: String,
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub content: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundledPreset {
+    /// ID of the preset on the instance it was exported from.  References to it in the
+    /// composition's content are rewritten to the new ID when the bundle is imported.
+    pub id: i64,
+    pub title: String,
+    pub description: String,
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundledEffect {
+    /// ID of the effect on the instance it was exported from
+    pub id: i64,
+    pub title: String,
+    pub description: String,
+    pub code: String,
+}
+
+/// A remote sample referenced by the composition.  Sample URLs are absolute, so these are included
+/// for completeness and don't need to be rewritten on import.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BundledSample {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompositionBundle {
+    pub format_version: u32,
+    pub composition: BundledComposition,
+    #[serde(default)]
+    pub synth_presets: Vec<BundledPreset>,
+    #[serde(default)]
+    pub voice_presets: Vec<BundledPreset>,
+    #[serde(default)]
+    pub effects: Vec<BundledEffect>,
+    #[serde(default)]
+    pub samples: Vec<BundledSample>,
+}
+
+/// Everything referenced from a composition's content that needs to be included in its bundle
+#[derive(Default, Debug, PartialEq)]
+pub struct BundleReferences {
+    pub synth_preset_ids: Vec<i64>,
+    pub voice_preset_ids: Vec<i64>,
+    pub effect_ids: Vec<i64>,
+    pub samples: Vec<BundledSample>,
+}
+
+/// Maps IDs from the instance a bundle was exported from to the IDs of the rows created for them
+/// when it was imported
+#[derive(Default)]
+pub struct BundleIdMapping {
+    pub synth_presets: HashMap<i64, i64>,
+    pub voice_presets: HashMap<i64, i64>,
+    pub effects: HashMap<i64, i64>,
+}
+
+/// Calls `visit` with every JSON object in `value`, including ones nested inside of strings that
+/// contain serialized JSON; most of a composition's `localStorage` entries are stored that way.
+/// `visit` returns `true` if it modified the object, in which case any strings it was found in are
+/// re-serialized.  Returns `true` if anything was modified.
+fn walk_objects(
+    value: &mut Value,
+    visit: &mut impl FnMut(&mut Map<String, Value>) -> bool,
+) -> bool {
+    match value {
+        Value::Object(obj) => {
+            let mut modified = visit(obj);
+            for val in obj.values_mut() {
+                modified |= walk_objects(val, visit);
+            }
+            modified
+        },
+        Value::Array(arr) => {
+            let mut modified = false;
+            for val in arr {
+                modified |= walk_objects(val, visit);
+            }
+            modified
+        },
+        Value::String(serialized) => {
+            let trimmed = serialized.trim_start();
+            if !trimmed.starts_with('{') && !trimmed.starts_with('[') {
+                return false;
+            }
+            let mut parsed: Value = match serde_json::from_str(serialized) {
+                Ok(parsed) => parsed,
+                Err(_) => return false,
+            };
+            if !walk_objects(&mut parsed, visit) {
+                return false;
+            }
+            *serialized = parsed.to_string();
+            true
+        },
+        _ => false,
+    }
+}
+
+/// IDs are stored as numbers by some nodes and as strings by others, such as control panel selects
+fn get_id(obj: &Map<String, Value>, key: &str) -> Option<i64> {
+    match obj.get(key)? {
+        Value::Number(id) => id.as_i64(),
+        Value::String(id) => id.parse().ok(),
+        _ => None,
+    }
+}
+
+fn get_sample(obj: &Map<String, Value>) -> Option<BundledSample> {
+    if obj.get("isLocal") != Some(&Value::Bool(false)) {
+        return None;
+    }
+
+    Some(BundledSample {
+        id: obj.get("id").and_then(Value::as_str).map(String::from),
+        name: obj.get("name")?.as_str()?.to_owned(),
+        url: obj.get("url")?.as_str()?.to_owned(),
+    })
+}
+
+fn push_unique<T: PartialEq>(items: &mut Vec<T>, item: T) {
+    if !items.contains(&item) {
+        items.push(item);
+    }
+}
+
+pub fn collect_bundle_references(content: &Map<String, Value>) -> BundleReferences {
+    let mut refs = BundleReferences::default();
+    let mut content = Value::Object(content.clone());
+    walk_objects(&mut content, &mut |obj| {
+        if let Some(id) = get_id(obj, SYNTH_PRESET_ID_KEY) {
+            push_unique(&mut refs.synth_preset_ids, id);
+        }
+        if let Some(id) = get_id(obj, VOICE_PRESET_ID_KEY) {
+            push_unique(&mut refs.voice_preset_ids, id);
+        }
+        if let Some(id) = get_id(obj, EFFECT_ID_KEY) {
+            push_unique(&mut refs.effect_ids, id);
+        }
+        if let Some(sample) = get_sample(obj) {
+            push_unique(&mut refs.samples, sample);
+        }
+        false
+    });
+    refs
+}
+
+fn remap_id(obj: &mut Map<String, Value>, key: &str, mapping: &HashMap<i64, i64>) -> bool {
+    let new_id = match get_id(obj, key).and_then(|old_id| mapping.get(&old_id)) {
+        Some(&new_id) => new_id,
+        None => return false,
+    };
+    let new_val = match obj[key] {
+        Value::String(_) => Value::String(new_id.to_string()),
+        _ => Value::from(new_id),
+    };
+    obj.insert(key.to_owned(), new_val);
+    true
+}
+
+/// Rewrites all references in the composition's content to point to the newly imported rows.
+/// References to anything that wasn't included in the bundle are left as-is.
+pub fn remap_bundle_references(content: &mut Map<String, Value>, mapping: &BundleIdMapping) {
+    for val in content.values_mut() {
+        walk_objects(val, &mut |obj| {
+            let mut modified = remap_id(obj, SYNTH_PRESET_ID_KEY, &mapping.synth_presets);
+            modified |= remap_id(obj, VOICE_PRESET_ID_KEY, &mapping.voice_presets);
+            modified |= remap_id(obj, EFFECT_ID_KEY, &mapping.effects);
+            modified
+        });
+    }
+}
+
+#[test]
+fn test_bundle_references() {
+    let content: Map<String, Value> = serde_json::from_value(serde_json::json!({
+        "synthDesigner_1": "{\"synthPresetId\":4,\"voices\":[{\"voicePresetId\":\"7\"}]}",
+        "faustEditor_2": "{\"effectId\":9,\"sample\":{\"isLocal\":false,\"name\":\"kick.wav\",\"url\":\"https://samples/kick.wav\"}}",
+        "sampler_3": "{\"samples\":[{\"isLocal\":false,\"name\":\"kick.wav\",\"url\":\"https://samples/kick.wav\"},{\"isLocal\":true,\"name\":\"local.wav\"}]}",
+        "plain": "not json",
+    }))
+    .unwrap();
+
+    let refs = collect_bundle_references(&content);
+    assert_eq!(refs, BundleReferences {
+        synth_preset_ids: vec![4],
+        voice_preset_ids: vec![7],
+        effect_ids: vec![9],
+        samples: vec![BundledSample {
+            id: None,
+            name: String::from("kick.wav"),
+            url: String::from("https://samples/kick.wav"),
+        }],
+    });
+
+    let mut remapped = content.clone();
+    let mut mapping = BundleIdMapping::default();
+    mapping.synth_presets.insert(4, 40);
+    mapping.voice_presets.insert(7, 70);
+    remap_bundle_references(&mut remapped, &mapping);
+    let refs = collect_bundle_references(&remapped);
+    assert_eq!(refs.synth_preset_ids, vec![40]);
+    assert_eq!(refs.voice_preset_ids, vec![70]);
+    assert_eq!(refs.effect_ids, vec![9]);
+    assert!(remapped["synthDesigner_1"]
+        .as_str()
+        .unwrap()
+        .contains("\"voicePresetId\":\"70\""));
+    // Entries without any remapped references are left untouched
+    assert_eq!(remapped["faustEditor_2"], content["faustEditor_2"]);
+    assert_eq!(remapped["plain"], content["plain"]);
+}
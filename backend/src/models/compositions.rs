@@ -1,14 +1,19 @@
 use serde_json::{Map, Value};
 
-use crate::schema::{compositions, compositions_tags};
+use crate::schema::{composition_versions, compositions, compositions_tags};
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct NewCompositionRequest {
     pub title: String,
     pub description: String,
     pub content: Map<String, Value>,
     #[serde(default)]
     pub tags: Vec<String>,
+    /// If set and owned by the logged-in user, the composition is saved as a new version of this
+    /// one rather than as a new composition.
+    #[serde(default)]
+    pub parent_id: Option<i64>,
 }
 
 #[derive(Insertable)]
@@ -47,3 +52,23 @@ pub struct NewCompositionTag {
     pub composition_id: i64,
     pub tag_id: i64,
 }
+
+#[derive(Insertable)]
+#[table_name = "composition_versions"]
+pub struct NewCompositionVersion {
+    pub composition_id: i64,
+    pub version: i64,
+    pub title: String,
+    pub description: String,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompositionVersionDescriptor {
+    pub version: i64,
+    pub title: String,
+    pub description: String,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+}
@@ -1,4 +1,6 @@
-use crate::schema::effects;
+use sha1::{Digest, Sha1};
+
+use crate::schema::{compiled_effect_artifacts, effect_revisions, effects};
 
 /// An effect is a component in the audio graph that transforms input signals into output signals.
 /// Its functionality is described by Faust code.
@@ -12,6 +14,8 @@ pub struct Effect {
     pub code: String,
     pub user_id: Option<i64>,
     pub user_name: Option<String>,
+    /// The revision that `code` belongs to
+    pub revision: i64,
 }
 
 #[derive(Deserialize, Insertable)]
@@ -23,3 +27,94 @@ pub struct InsertableEffect {
     #[serde(default)]
     pub user_id: Option<i64>,
 }
+
+/// Every edit to an effect's code is stored as a new revision so that compositions which reference
+/// a specific revision keep working the same way after the effect is changed.
+#[derive(Insertable)]
+#[table_name = "effect_revisions"]
+pub struct NewEffectRevision {
+    pub effect_id: i64,
+    pub revision: i64,
+    pub code: String,
+    pub code_hash: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveEffectRevisionRequest {
+    pub code: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectRevisionDescriptor {
+    pub revision: i64,
+    pub code_hash: String,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectRevision {
+    pub effect_id: i64,
+    pub revision: i64,
+    pub code: String,
+    pub code_hash: String,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    /// Modules that have already been compiled from this revision's code.  These can be loaded
+    /// directly from the Faust compiler's module cache without compiling the code again.
+    pub compiled_artifacts: Vec<CompiledEffectArtifact>,
+}
+
+/// A module built from Faust code by the Faust compiler, which caches compiled modules by the hash
+/// of the code they were compiled from
+#[derive(Serialize, Queryable, Insertable)]
+#[serde(rename_all = "camelCase")]
+#[table_name = "compiled_effect_artifacts"]
+pub struct CompiledEffectArtifact {
+    #[serde(skip)]
+    pub code_hash: String,
+    pub optimized: bool,
+    pub module_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterCompiledEffectArtifactRequest {
+    pub optimized: bool,
+    pub module_id: String,
+}
+
+/// Hashes effect code the same way that the Faust compiler does when caching compiled modules
+pub fn hash_effect_code(code: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(code.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// Builds the ID that the Faust compiler assigns to the module compiled from code with the given
+/// hash
+pub fn build_faust_module_id(code_hash: &str, optimized: bool) -> String {
+    if optimized {
+        format!("{}_optimized", code_hash)
+    } else {
+        code_hash.to_owned()
+    }
+}
+
+#[test]
+fn test_faust_module_ids() {
+    let code_hash = hash_effect_code("abc");
+    assert_eq!(code_hash, "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(build_faust_module_id(&code_hash, false), code_hash);
+    assert_eq!(
+        build_faust_module_id(&code_hash, true),
+        "a9993e364706816aba3e25717850c26c9cd0d89d_optimized"
+    );
+}
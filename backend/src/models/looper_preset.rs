@@ -2,7 +2,7 @@ use diesel::Insertable;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    models::midi_composition::MIDIComposition,
+    models::{midi_composition::MIDIComposition, validation::check_finite},
     schema::{looper_presets, looper_presets_tags},
 };
 
@@ -29,6 +29,43 @@ pub struct SerializedLooperInstState {
     pub active_module_ix: usize,
 }
 
+impl SerializedLooperInstState {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.modules.is_empty() && self.active_module_ix >= self.modules.len() {
+            return Err(String::from(
+                "`activeModuleIx` must refer to one of the looper's modules",
+            ));
+        }
+
+        for (module_ix, module) in self.modules.iter().enumerate() {
+            if let Some(active_bank_ix) = module.active_bank_ix {
+                if active_bank_ix >= module.banks.len() {
+                    return Err(format!(
+                        "`activeBankIx` of module {} must refer to one of its banks",
+                        module_ix
+                    ));
+                }
+            }
+
+            for bank in &module.banks {
+                check_finite("lenBeats", bank.len_beats)?;
+                if bank.len_beats <= 0. {
+                    return Err(String::from("`lenBeats` must be positive"));
+                }
+                if let Some(composition) = &bank.loaded_composition {
+                    composition.composition.validate().map_err(|err| {
+                        format!(
+                            "Invalid composition loaded into bank {} of module {}: {}",
+                            bank.id, module_ix, err
+                        )
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Queryable)]
 #[serde(rename_all = "camelCase")]
 pub struct LooperPresetDescriptor {
@@ -1,4 +1,12 @@
-use crate::schema::{midi_compositions, midi_compositions_tags};
+use crate::{
+    models::validation::{check_finite, check_non_negative, check_range},
+    schema::{
+        midi_composition_collaborators, midi_composition_versions, midi_compositions,
+        midi_compositions_tags,
+    },
+};
+
+const MAX_BPM: f64 = 10_000.;
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -35,12 +43,39 @@ pub struct SerializedMIDIEditorState {
     pub loop_point: Option<f64>,
 }
 
+impl SerializedMIDIEditorState {
+    pub fn validate(&self) -> Result<(), String> {
+        for line in &self.lines {
+            for note in &line.notes {
+                check_non_negative("notes.startPoint", note.start_point)?;
+                check_non_negative("notes.length", note.length)?;
+            }
+        }
+
+        check_finite("view.pxPerBeat", self.view.px_per_beat)?;
+        check_finite(
+            "view.scrollHorizontalBeats",
+            self.view.scroll_horizontal_beats,
+        )?;
+        check_finite("view.scrollVerticalPx", self.view.scroll_vertical_px)?;
+        check_non_negative("view.beatsPerMeasure", self.view.beats_per_measure)?;
+        check_non_negative("beatSnapInterval", self.beat_snap_interval)?;
+        check_finite("cursorPosBeats", self.cursor_pos_beats)?;
+        check_range("localBPM", self.local_bpm, 0., MAX_BPM)?;
+        if let Some(loop_point) = self.loop_point {
+            check_non_negative("loopPoint", loop_point)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Insertable)]
 #[table_name = "midi_compositions"]
 pub struct InsertableMIDIComposition {
     pub name: String,
     pub description: String,
     pub composition_json: String,
+    pub user_id: Option<i64>,
 }
 
 #[derive(Serialize, Queryable)]
@@ -61,6 +96,10 @@ pub struct NewMIDIComposition {
     pub composition: SerializedMIDIEditorState,
     pub tags: Vec<String>,
     pub user_id: Option<i64>,
+    /// If set and owned by the logged-in user, the composition is saved as a new version of this
+    /// one rather than as a new composition.
+    #[serde(default)]
+    pub parent_id: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -80,3 +119,36 @@ pub struct NewMidiCompositionTag {
     pub midi_composition_id: i64,
     pub tag_id: i64,
 }
+
+#[derive(Insertable)]
+#[table_name = "midi_composition_versions"]
+pub struct NewMIDICompositionVersion {
+    pub midi_composition_id: i64,
+    pub version: i64,
+    pub name: String,
+    pub description: String,
+    pub composition_json: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MIDICompositionVersionDescriptor {
+    pub version: i64,
+    pub name: String,
+    pub description: String,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[table_name = "midi_composition_collaborators"]
+pub struct NewMIDICompositionCollaborator {
+    pub midi_composition_id: i64,
+    pub user_id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddMIDICompositionCollaboratorRequest {
+    pub username: String,
+}
@@ -1,10 +1,17 @@
+pub mod automation;
+pub mod collaboration;
+pub mod composition_bundle;
 pub mod compositions;
 pub mod effects;
 pub mod looper_preset;
 pub mod midi_composition;
 pub mod private_sample_libraries;
 pub mod remote_samples;
+pub mod search;
+pub mod sequencer_preset;
 pub mod synth_preset;
 pub mod tags;
 pub mod user;
+pub mod user_samples;
+pub mod validation;
 pub mod wavetable_preset;
@@ -0,0 +1,47 @@
+#[derive(Clone, Copy, FromFormField)]
+pub enum SearchSort {
+    #[field(value = "newest")]
+    Newest,
+    /// Compositions and looper presets are used when they're loaded; synth presets when they're
+    /// forked.
+    #[field(value = "most_used")]
+    MostUsed,
+}
+
+impl Default for SearchSort {
+    fn default() -> Self { SearchSort::Newest }
+}
+
+#[derive(FromForm)]
+pub struct SearchParams {
+    /// Whitespace-separated terms which must all appear in either the title or the description
+    pub q: Option<String>,
+    /// Only entities that have all of these tags are returned
+    pub tags: Vec<String>,
+    pub sort: Option<SearchSort>,
+    /// Zero-indexed
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResultDescriptor {
+    pub id: i64,
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub user_id: Option<i64>,
+    pub user_name: Option<String>,
+    pub use_count: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    pub results: Vec<SearchResultDescriptor>,
+    /// Total number of matches across all pages
+    pub total: i64,
+    pub page: i64,
+    pub page_size: i64,
+}
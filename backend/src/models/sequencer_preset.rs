@@ -0,0 +1,86 @@
+use crate::schema::{sequencer_presets, sequencer_presets_tags};
+
+#[derive(Queryable, Serialize, Deserialize)]
+pub struct SequencerPreset {
+    pub id: i64,
+    pub name: String,
+    pub description: String,
+    pub serialized_sequencer_state: String,
+    pub user_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SequencerStep {
+    pub enabled: bool,
+    pub note: u8,
+    pub velocity: f32,
+    pub gate: f32,
+    pub probability: f32,
+    /// Locked value for each lockable param, or `None` if the param isn't locked on this step
+    pub param_locks: Vec<Option<f32>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EuclideanConfig {
+    pub pulses: usize,
+    pub steps: usize,
+    pub rotation: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SequencerTrack {
+    pub name: String,
+    #[serde(rename = "mailboxID")]
+    pub mailbox_id: Option<String>,
+    pub muted: bool,
+    pub param_defaults: Vec<Option<f32>>,
+    pub steps: Vec<SequencerStep>,
+    #[serde(default)]
+    pub euclidean: Option<EuclideanConfig>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerializedSequencerState {
+    pub step_count: usize,
+    pub beats_per_step: f64,
+    pub tracks: Vec<SequencerTrack>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SequencerPresetDescriptor {
+    pub id: i64,
+    pub name: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub user_id: Option<i64>,
+    pub user_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveSequencerPresetRequest {
+    pub name: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub serialized_sequencer_state: SerializedSequencerState,
+}
+
+#[derive(Insertable)]
+#[table_name = "sequencer_presets"]
+pub struct NewSequencerPreset {
+    pub name: String,
+    pub description: String,
+    pub serialized_sequencer_state: String,
+    pub user_id: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[table_name = "sequencer_presets_tags"]
+pub struct NewSequencerPresetTag {
+    pub sequencer_preset_id: i64,
+    pub tag_id: i64,
+}
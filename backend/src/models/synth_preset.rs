@@ -1,4 +1,10 @@
-use crate::schema::{synth_presets, voice_presets};
+use crate::{
+    models::validation::{check_finite, check_non_negative, check_range},
+    schema::{synth_presets, voice_presets},
+};
+
+/// Highest filter frequency that's accepted in presets, well above the audible range
+const MAX_FILTER_FREQUENCY: f64 = 100_000.;
 
 #[derive(Serialize, Deserialize)]
 pub struct SynthPreset {
@@ -221,6 +227,100 @@ pub struct VoiceDefinition {
     pitch_multiplier: f32,
 }
 
+impl FilterParams {
+    fn validate(&self) -> Result<(), String> {
+        check_range("filter.frequency", self.frequency, 0., MAX_FILTER_FREQUENCY)?;
+        if let Some(q) = self.q {
+            check_finite("filter.Q", q)?;
+        }
+        check_finite("filter.gain", self.gain)?;
+        check_finite("filter.detune", self.detune)
+    }
+}
+
+impl ADSRValues {
+    fn validate(&self) -> Result<(), String> {
+        for (name, val) in [
+            ("attack", &self.attack),
+            ("decay", &self.decay),
+            ("release", &self.release),
+        ] {
+            check_range(
+                &format!("gainEnvelope.{}.pos", name),
+                val.pos as f64,
+                0.,
+                1.,
+            )?;
+            check_range(
+                &format!("gainEnvelope.{}.magnitude", name),
+                val.magnitude as f64,
+                0.,
+                1.,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Adsr {
+    fn validate(&self) -> Result<(), String> {
+        for step in &self.steps {
+            check_range("filterEnvelope.steps.x", step.x as f64, 0., 1.)?;
+            check_range("filterEnvelope.steps.y", step.y as f64, 0., 1.)?;
+            if let RampFn::Exponential { exponent } = step.ramper {
+                check_finite("filterEnvelope.steps.ramper.exponent", exponent as f64)?;
+                if exponent <= 0. {
+                    return Err(String::from(
+                        "`filterEnvelope.steps.ramper.exponent` must be positive",
+                    ));
+                }
+            }
+        }
+        check_non_negative("filterEnvelope.lenSamples", self.len_samples as f64)?;
+        if let Some(loop_point) = self.loop_point {
+            if loop_point >= self.steps.len() {
+                return Err(String::from(
+                    "`filterEnvelope.loopPoint` must refer to one of the envelope's steps",
+                ));
+            }
+        }
+        check_range(
+            "filterEnvelope.releasePoint",
+            self.release_point as f64,
+            0.,
+            1.,
+        )
+    }
+}
+
+impl VoiceDefinition {
+    /// Rejects voices with non-finite or out-of-range parameters
+    pub fn validate(&self) -> Result<(), String> {
+        self.filter.validate()?;
+        check_finite("masterGain", self.master_gain as f64)?;
+        if let Some(gain_envelope) = &self.gain_envelope {
+            gain_envelope.validate()?;
+        }
+        if let Some(gain_adsr_length) = self.gain_adsr_length {
+            check_non_negative("gainADSRLength", gain_adsr_length as f64)?;
+        }
+        self.filter_envelope.validate()?;
+        check_non_negative("filterADSRLength", self.filter_adsr_length as f64)?;
+        check_non_negative("pitchMultiplier", self.pitch_multiplier as f64)
+    }
+}
+
+impl SynthPreset {
+    pub fn validate(&self) -> Result<(), String> {
+        for (voice_ix, voice) in self.voices.iter().enumerate() {
+            voice
+                .validate()
+                .map_err(|err| format!("Invalid voice {}: {}", voice_ix, err))?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Serialize)]
 pub struct SynthPresetEntry {
     pub id: i64,
@@ -230,19 +330,29 @@ pub struct SynthPresetEntry {
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct InlineSynthPresetEntry {
     pub id: i64,
     pub title: String,
     pub description: String,
     pub body: InlineSynthPreset,
     pub user_id: Option<i64>,
+    pub user_name: Option<String>,
+    pub is_public: bool,
+    pub forked_from: Option<i64>,
 }
 
+fn default_is_public() -> bool { true }
+
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ReceivedSynthPresetEntry {
     pub title: String,
     pub description: String,
     pub body: SynthPreset,
+    /// Only applies to presets created by a logged-in user; anonymous presets are always public
+    #[serde(default = "default_is_public")]
+    pub is_public: bool,
 }
 
 #[derive(Insertable)]
@@ -252,6 +362,8 @@ pub struct NewSynthPresetEntry {
     pub description: String,
     pub body: String,
     pub user_id: Option<i64>,
+    pub is_public: bool,
+    pub forked_from: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -262,13 +374,20 @@ pub struct SynthVoicePresetEntry {
     pub description: String,
     pub body: VoiceDefinition,
     pub user_id: Option<i64>,
+    pub user_name: Option<String>,
+    pub is_public: bool,
+    pub forked_from: Option<i64>,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct UserProvidedNewSynthVoicePreset {
     pub title: String,
     pub description: String,
     pub body: VoiceDefinition,
+    /// See `ReceivedSynthPresetEntry::is_public`
+    #[serde(default = "default_is_public")]
+    pub is_public: bool,
 }
 
 #[derive(Insertable)]
@@ -278,4 +397,6 @@ pub struct NewSynthVoicePresetEntry {
     pub description: String,
     pub body: String,
     pub user_id: Option<i64>,
+    pub is_public: bool,
+    pub forked_from: Option<i64>,
 }
@@ -1,11 +1,16 @@
 use std::convert::Infallible;
 
 use rocket::{
+    http::Status,
     request::{FromRequest, Outcome},
     Request,
 };
 
-use crate::schema::{login_tokens, users};
+use crate::{
+    db_util::login::validate_login_token,
+    schema::{login_tokens, users},
+    WebSynthDbConn,
+};
 
 #[derive(Insertable)]
 #[table_name = "users"]
@@ -19,6 +24,7 @@ pub struct NewUser {
 pub struct NewLoginToken {
     pub user_id: i64,
     pub token: String,
+    pub expires_at: chrono::NaiveDateTime,
 }
 
 #[derive(Deserialize)]
@@ -51,3 +57,72 @@ impl<'r> FromRequest<'r> for MaybeLoginToken {
         Outcome::Success(MaybeLoginToken(token))
     }
 }
+
+/// Request guard for endpoints that require a valid, unexpired login token in the `Authorization`
+/// header.  Fails with `401 Unauthorized` otherwise.  Endpoints where logging in is optional can
+/// take a `MaybeAuthenticatedUser` instead.
+#[derive(Clone)]
+pub struct AuthenticatedUser {
+    pub id: i64,
+    pub login_token: String,
+}
+
+impl AuthenticatedUser {
+    async fn authenticate(request: &Request<'_>) -> Result<Self, (Status, &'static str)> {
+        let login_token = match request.guard::<MaybeLoginToken>().await {
+            Outcome::Success(MaybeLoginToken(Some(login_token))) => login_token,
+            _ => return Err((Status::Unauthorized, "Missing login token")),
+        };
+        let conn = match request.guard::<WebSynthDbConn>().await {
+            Outcome::Success(conn) => conn,
+            _ => return Err((Status::ServiceUnavailable, "Database unavailable")),
+        };
+
+        match validate_login_token(&conn, login_token.clone()).await {
+            Ok(Some(id)) => Ok(AuthenticatedUser { id, login_token }),
+            Ok(None) => Err((Status::Unauthorized, "Invalid or expired login token")),
+            Err(err) => {
+                error!("Error while validating login token: {:?}", err);
+                Err((Status::InternalServerError, "DB error"))
+            },
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthenticatedUser {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        // Cached so that other guards, like the rate limiter, can check the logged-in user without
+        // validating the token again
+        let res: &Result<Self, (Status, &'static str)> = request
+            .local_cache_async(AuthenticatedUser::authenticate(request))
+            .await;
+        match res {
+            Ok(user) => Outcome::Success(user.clone()),
+            Err(err) => Outcome::Failure(*err),
+        }
+    }
+}
+
+/// Request guard for endpoints where logging in is optional.  Requests without a login token are
+/// anonymous, but unlike with `Option<AuthenticatedUser>`, requests with a login token that isn't
+/// valid fail with `401 Unauthorized` rather than silently being treated as anonymous.
+pub struct MaybeAuthenticatedUser(pub Option<AuthenticatedUser>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MaybeAuthenticatedUser {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.guard::<MaybeLoginToken>().await {
+            Outcome::Success(MaybeLoginToken(None)) =>
+                Outcome::Success(MaybeAuthenticatedUser(None)),
+            _ => request
+                .guard::<AuthenticatedUser>()
+                .await
+                .map(|user| MaybeAuthenticatedUser(Some(user))),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use chrono::NaiveDateTime;
+
+use super::remote_samples::RemoteSample;
+use crate::schema::user_samples;
+
+#[derive(Insertable)]
+#[table_name = "user_samples"]
+pub struct NewUserSample {
+    pub user_id: i64,
+    pub name: String,
+    pub byte_size: i64,
+    /// Hex-encoded SHA-256 hash of the uploaded file
+    pub checksum: String,
+    pub content_type: String,
+    pub storage_url: String,
+}
+
+#[derive(Queryable)]
+pub struct UserSample {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub byte_size: i64,
+    pub checksum: String,
+    pub content_type: String,
+    pub storage_url: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl UserSample {
+    /// Uploaded samples are listed alongside the other remote samples so that they show up in the
+    /// sample library
+    pub fn to_remote_sample(&self) -> RemoteSample {
+        RemoteSample {
+            id: format!("user_sample_{}", self.id),
+            name: self.name.clone(),
+            sample_url: self.storage_url.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSampleDescriptor {
+    pub id: i64,
+    pub name: String,
+    pub byte_size: i64,
+    pub checksum: String,
+    pub content_type: String,
+    pub storage_url: String,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+}
+
+impl From<UserSample> for UserSampleDescriptor {
+    fn from(sample: UserSample) -> Self {
+        UserSampleDescriptor {
+            id: sample.id,
+            name: sample.name,
+            byte_size: sample.byte_size,
+            checksum: sample.checksum,
+            content_type: sample.content_type,
+            storage_url: sample.storage_url,
+            created_at: sample.created_at.timestamp(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RenameUserSampleRequest {
+    pub name: String,
+}
@@ -0,0 +1,102 @@
+//! Helpers for validating user-provided presets and compositions before they're stored.  Saved
+//! bodies are loaded directly into the synth engine, so non-finite or wildly out-of-range numbers
+//! can break playback for anyone who loads them.
+
+pub const MAX_TITLE_LENGTH: usize = 256;
+pub const MAX_DESCRIPTION_LENGTH: usize = 16 * 1024;
+pub const MAX_TAG_COUNT: usize = 32;
+pub const MAX_TAG_LENGTH: usize = 64;
+/// Max size of the serialized body of a single synth or voice preset
+pub const MAX_PRESET_BODY_BYTES: usize = 1024 * 1024;
+/// Max size of the serialized state of a looper preset or MIDI composition
+pub const MAX_SERIALIZED_STATE_BYTES: usize = 4 * 1024 * 1024;
+/// Max size of the serialized content of a composition, which includes the state of every module
+pub const MAX_COMPOSITION_CONTENT_BYTES: usize = 16 * 1024 * 1024;
+
+pub fn check_finite(field: &str, val: f64) -> Result<(), String> {
+    if !val.is_finite() {
+        return Err(format!("`{}` must be a finite number", field));
+    }
+    Ok(())
+}
+
+/// Checks that `val` is finite and within `[min, max]`
+pub fn check_range(field: &str, val: f64, min: f64, max: f64) -> Result<(), String> {
+    check_finite(field, val)?;
+    if val < min || val > max {
+        return Err(format!(
+            "`{}` must be between {} and {}; found {}",
+            field, min, max, val
+        ));
+    }
+    Ok(())
+}
+
+pub fn check_non_negative(field: &str, val: f64) -> Result<(), String> {
+    check_finite(field, val)?;
+    if val < 0. {
+        return Err(format!("`{}` must not be negative; found {}", field, val));
+    }
+    Ok(())
+}
+
+pub fn check_len(field: &str, val: &str, max_chars: usize) -> Result<(), String> {
+    if val.chars().count() > max_chars {
+        return Err(format!(
+            "`{}` must be at most {} characters",
+            field, max_chars
+        ));
+    }
+    Ok(())
+}
+
+pub fn check_serialized_size(
+    field: &str,
+    serialized: &str,
+    max_bytes: usize,
+) -> Result<(), String> {
+    if serialized.len() > max_bytes {
+        return Err(format!(
+            "`{}` must be at most {} bytes when serialized; found {}",
+            field,
+            max_bytes,
+            serialized.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Validates the title, description, and tags that are saved along with all presets and
+/// compositions
+pub fn validate_metadata(title: &str, description: &str, tags: &[String]) -> Result<(), String> {
+    check_len("title", title, MAX_TITLE_LENGTH)?;
+    check_len("description", description, MAX_DESCRIPTION_LENGTH)?;
+
+    if tags.len() > MAX_TAG_COUNT {
+        return Err(format!("At most {} tags can be provided", MAX_TAG_COUNT));
+    }
+    for tag in tags {
+        if tag.trim().is_empty() {
+            return Err(String::from("Tags must not be empty"));
+        }
+        check_len("tag", tag, MAX_TAG_LENGTH)?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validation_helpers() {
+    assert!(check_range("gain", 0.5, 0., 1.).is_ok());
+    assert!(check_range("gain", 1.5, 0., 1.).is_err());
+    assert!(check_range("gain", f64::NAN, 0., 1.).is_err());
+    assert!(check_finite("gain", f64::INFINITY).is_err());
+    assert!(check_non_negative("length", 0.).is_ok());
+    assert!(check_non_negative("length", -1.).is_err());
+
+    assert!(validate_metadata("bass", "", &[String::from("bass")]).is_ok());
+    assert!(validate_metadata("bass", "", &[String::from(" ")]).is_err());
+    assert!(validate_metadata(&"a".repeat(MAX_TITLE_LENGTH + 1), "", &[]).is_err());
+    let too_many_tags = vec![String::from("tag"); MAX_TAG_COUNT + 1];
+    assert!(validate_metadata("bass", "", &too_many_tags).is_err());
+}
@@ -0,0 +1,121 @@
+//! Simple in-memory rate limiting for endpoints that write to the database.  Requests are counted
+//! per logged-in user, falling back to the client's IP address for anonymous requests.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request,
+};
+
+use crate::models::user::AuthenticatedUser;
+
+const SAVE_RATE_LIMIT_MAX_REQUESTS: u32 = 30;
+const SAVE_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+/// Expired windows are pruned once this many keys are being tracked
+const PRUNE_THRESHOLD: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    User(i64),
+    Ip(IpAddr),
+}
+
+/// Fixed-window rate limiter that allows up to `max_requests` requests per key in each window
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    /// Maps each key to the start of its current window and the number of requests made in it
+    windows: Mutex<HashMap<RateLimitKey, (Instant, u32)>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self { RateLimiter::new(SAVE_RATE_LIMIT_MAX_REQUESTS, SAVE_RATE_LIMIT_WINDOW) }
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        RateLimiter {
+            max_requests,
+            window,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a request for `key` made at `now`, returning `false` if it exceeds the limit
+    pub fn check_at(&self, key: RateLimitKey, now: Instant) -> bool {
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() >= PRUNE_THRESHOLD {
+            let window = self.window;
+            windows.retain(|_, (window_start, _)| now.duration_since(*window_start) < window);
+        }
+
+        let (window_start, count) = windows.entry(key).or_insert((now, 0));
+        if now.duration_since(*window_start) >= self.window {
+            *window_start = now;
+            *count = 0;
+        }
+        if *count >= self.max_requests {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    pub fn check(&self, key: RateLimitKey) -> bool { self.check_at(key, Instant::now()) }
+}
+
+/// Request guard that fails with 429 Too Many Requests if the user or IP making the request has
+/// exceeded the save rate limit.  Requests whose origin can't be determined aren't limited.
+pub struct SaveRateLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SaveRateLimit {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let limiter = match request.rocket().state::<RateLimiter>() {
+            Some(limiter) => limiter,
+            None => return Outcome::Success(SaveRateLimit),
+        };
+        let key = match request.guard::<AuthenticatedUser>().await {
+            Outcome::Success(user) => RateLimitKey::User(user.id),
+            _ => match request.client_ip() {
+                Some(ip) => RateLimitKey::Ip(ip),
+                None => return Outcome::Success(SaveRateLimit),
+            },
+        };
+
+        if limiter.check(key) {
+            Outcome::Success(SaveRateLimit)
+        } else {
+            warn!("Rate limit exceeded for {:?}", key);
+            Outcome::Failure((
+                Status::TooManyRequests,
+                "Too many requests; try again later",
+            ))
+        }
+    }
+}
+
+#[test]
+fn test_rate_limiter() {
+    let limiter = RateLimiter::new(2, Duration::from_secs(60));
+    let user = RateLimitKey::User(1);
+    let ip = RateLimitKey::Ip(IpAddr::from([127, 0, 0, 1]));
+    let start = Instant::now();
+
+    assert!(limiter.check_at(user, start));
+    assert!(limiter.check_at(user, start + Duration::from_secs(1)));
+    assert!(!limiter.check_at(user, start + Duration::from_secs(2)));
+    // Keys are limited independently
+    assert!(limiter.check_at(ip, start + Duration::from_secs(2)));
+    // The count resets once the window expires
+    assert!(limiter.check_at(user, start + Duration::from_secs(60)));
+}
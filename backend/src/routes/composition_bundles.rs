@@ -0,0 +1,319 @@
+use diesel::prelude::*;
+use rocket::{http::Status, response::status::Custom, serde::json::Json};
+use serde_json::{Map, Value};
+
+use crate::{
+    db_util::{
+        composition_versions::{insert_composition_version, set_composition_tags},
+        effects::insert_effect,
+        last_insert_id,
+    },
+    models::{
+        automation::validate_composition_automation,
+        composition_bundle::{
+            collect_bundle_references, remap_bundle_references, BundleIdMapping, BundleReferences,
+            BundledComposition, BundledEffect, BundledPreset, CompositionBundle,
+            COMPOSITION_BUNDLE_FORMAT_VERSION,
+        },
+        compositions::NewComposition,
+        effects::InsertableEffect,
+        synth_preset::{
+            NewSynthPresetEntry, NewSynthVoicePresetEntry, SynthPreset, VoiceDefinition,
+        },
+        user::{AuthenticatedUser, MaybeAuthenticatedUser},
+        validation::{
+            check_serialized_size, validate_metadata, MAX_COMPOSITION_CONTENT_BYTES,
+            MAX_PRESET_BODY_BYTES,
+        },
+    },
+    rate_limit::SaveRateLimit,
+    WebSynthDbConn,
+};
+
+fn db_error(err: diesel::result::Error) -> Custom<String> {
+    error!(
+        "DB error exporting or importing composition bundle: {:?}",
+        err
+    );
+    Custom(Status::InternalServerError, String::from("DB error"))
+}
+
+/// Bundles can be hand-edited or come from other instances, so everything in them is held to the
+/// same validation as presets and compositions saved directly
+fn validate_bundle(bundle: &CompositionBundle) -> Result<(), String> {
+    let composition = &bundle.composition;
+    validate_metadata(
+        &composition.title,
+        &composition.description,
+        &composition.tags,
+    )?;
+    validate_composition_automation(&composition.content)?;
+    let content = Value::Object(composition.content.clone()).to_string();
+    check_serialized_size(
+        "composition.content",
+        &content,
+        MAX_COMPOSITION_CONTENT_BYTES,
+    )?;
+
+    for preset in &bundle.synth_presets {
+        validate_metadata(&preset.title, &preset.description, &[])?;
+        check_serialized_size("synthPresets.body", &preset.body, MAX_PRESET_BODY_BYTES)?;
+        let body: SynthPreset = serde_json::from_str(&preset.body)
+            .map_err(|err| format!("Invalid body for synth preset {}: {}", preset.id, err))?;
+        body.validate()
+            .map_err(|err| format!("Invalid synth preset {}: {}", preset.id, err))?;
+    }
+    for preset in &bundle.voice_presets {
+        validate_metadata(&preset.title, &preset.description, &[])?;
+        check_serialized_size("voicePresets.body", &preset.body, MAX_PRESET_BODY_BYTES)?;
+        let body: VoiceDefinition = serde_json::from_str(&preset.body)
+            .map_err(|err| format!("Invalid body for voice preset {}: {}", preset.id, err))?;
+        body.validate()
+            .map_err(|err| format!("Invalid voice preset {}: {}", preset.id, err))?;
+    }
+    for effect in &bundle.effects {
+        validate_metadata(&effect.title, &effect.description, &[])?;
+    }
+    Ok(())
+}
+
+/// Exports the composition along with all of the presets, effects, and samples that it references.
+/// Private presets are only included if they're owned by the logged-in user.
+#[get("/compositions/<composition_id>/export")]
+pub async fn export_composition_bundle(
+    conn: WebSynthDbConn,
+    user: Option<AuthenticatedUser>,
+    composition_id: i64,
+) -> Result<Json<CompositionBundle>, Custom<String>> {
+    use crate::schema::{
+        compositions, compositions_tags, effects, synth_presets, tags, voice_presets,
+    };
+
+    let logged_in_user_id = user.map(|user| user.id);
+    let composition: Option<(String, String, String)> = conn
+        .run(move |conn| {
+            compositions::table
+                .find(composition_id)
+                .select((
+                    compositions::dsl::title,
+                    compositions::dsl::description,
+                    compositions::dsl::content,
+                ))
+                .first(conn)
+                .optional()
+        })
+        .await
+        .map_err(db_error)?;
+    let (title, description, content) = match composition {
+        Some(composition) => composition,
+        None =>
+            return Err(Custom(
+                Status::NotFound,
+                String::from("Composition not found"),
+            )),
+    };
+    let content: Map<String, Value> = serde_json::from_str(&content).map_err(|err| {
+        error!(
+            "Error parsing content of composition {}: {:?}",
+            composition_id, err
+        );
+        Custom(
+            Status::InternalServerError,
+            String::from("Error parsing composition content"),
+        )
+    })?;
+    let BundleReferences {
+        synth_preset_ids,
+        voice_preset_ids,
+        effect_ids,
+        samples,
+    } = collect_bundle_references(&content);
+
+    let (tags, synth_presets, voice_presets, effects) = conn
+        .run(move |conn| -> QueryResult<_> {
+            let tags: Vec<String> = compositions_tags::table
+                .inner_join(tags::table)
+                .filter(compositions_tags::dsl::composition_id.eq(composition_id))
+                .select(tags::dsl::tag)
+                .load(conn)?;
+            let synth_presets: Vec<(i64, String, String, String)> = synth_presets::table
+                .filter(synth_presets::dsl::id.eq_any(synth_preset_ids))
+                .filter(
+                    synth_presets::dsl::is_public
+                        .eq(true)
+                        .or(synth_presets::dsl::user_id.eq(logged_in_user_id)),
+                )
+                .select((
+                    synth_presets::dsl::id,
+                    synth_presets::dsl::title,
+                    synth_presets::dsl::description,
+                    synth_presets::dsl::body,
+                ))
+                .load(conn)?;
+            let voice_presets: Vec<(i64, String, String, String)> = voice_presets::table
+                .filter(voice_presets::dsl::id.eq_any(voice_preset_ids))
+                .filter(
+                    voice_presets::dsl::is_public
+                        .eq(true)
+                        .or(voice_presets::dsl::user_id.eq(logged_in_user_id)),
+                )
+                .select((
+                    voice_presets::dsl::id,
+                    voice_presets::dsl::title,
+                    voice_presets::dsl::description,
+                    voice_presets::dsl::body,
+                ))
+                .load(conn)?;
+            let effects: Vec<(i64, String, String, String)> = effects::table
+                .filter(effects::dsl::id.eq_any(effect_ids))
+                .select((
+                    effects::dsl::id,
+                    effects::dsl::title,
+                    effects::dsl::description,
+                    effects::dsl::code,
+                ))
+                .load(conn)?;
+            Ok((tags, synth_presets, voice_presets, effects))
+        })
+        .await
+        .map_err(db_error)?;
+
+    let build_preset =
+        |(id, title, description, body): (i64, String, String, String)| BundledPreset {
+            id,
+            title,
+            description,
+            body,
+        };
+    Ok(Json(CompositionBundle {
+        format_version: COMPOSITION_BUNDLE_FORMAT_VERSION,
+        composition: BundledComposition {
+            title,
+            description,
+            tags,
+            content,
+        },
+        synth_presets: synth_presets.into_iter().map(build_preset).collect(),
+        voice_presets: voice_presets.into_iter().map(build_preset).collect(),
+        effects: effects
+            .into_iter()
+            .map(|(id, title, description, code)| BundledEffect {
+                id,
+                title,
+                description,
+                code,
+            })
+            .collect(),
+        samples,
+    }))
+}
+
+/// Creates a new composition from the bundle along with new copies of all of the presets and
+/// effects it contains, returning the ID of the new composition.
+#[post("/compositions/import", data = "<bundle>")]
+pub async fn import_composition_bundle(
+    conn: WebSynthDbConn,
+    user: MaybeAuthenticatedUser,
+    _rate_limit: SaveRateLimit,
+    bundle: Json<CompositionBundle>,
+) -> Result<Json<i64>, Custom<String>> {
+    use crate::schema::{compositions, synth_presets, voice_presets};
+
+    if bundle.format_version > COMPOSITION_BUNDLE_FORMAT_VERSION {
+        return Err(Custom(
+            Status::BadRequest,
+            format!(
+                "Unsupported composition bundle format version {}; at most {} is supported",
+                bundle.format_version, COMPOSITION_BUNDLE_FORMAT_VERSION
+            ),
+        ));
+    }
+    validate_bundle(&bundle).map_err(|err| Custom(Status::BadRequest, err))?;
+
+    let CompositionBundle {
+        format_version: _,
+        composition,
+        synth_presets: bundled_synth_presets,
+        voice_presets: bundled_voice_presets,
+        effects: bundled_effects,
+        samples: _,
+    } = bundle.into_inner();
+
+    let user_id = user.0.map(|user| user.id);
+    // Presets saved without being logged in are always public, so imported ones are too
+    let is_public = user_id.is_none();
+    let BundledComposition {
+        title,
+        description,
+        tags,
+        mut content,
+    } = composition;
+
+    let composition_id = conn
+        .run(move |conn| {
+            let conn = &*conn;
+            conn.transaction(|| -> QueryResult<i64> {
+                let mut mapping = BundleIdMapping::default();
+                for preset in bundled_synth_presets {
+                    diesel::insert_into(synth_presets::table)
+                        .values(&NewSynthPresetEntry {
+                            title: preset.title,
+                            description: preset.description,
+                            body: preset.body,
+                            user_id,
+                            is_public,
+                            forked_from: None,
+                        })
+                        .execute(conn)?;
+                    let new_id = diesel::select(last_insert_id).first(conn)?;
+                    mapping.synth_presets.insert(preset.id, new_id);
+                }
+                for preset in bundled_voice_presets {
+                    diesel::insert_into(voice_presets::table)
+                        .values(&NewSynthVoicePresetEntry {
+                            title: preset.title,
+                            description: preset.description,
+                            body: preset.body,
+                            user_id,
+                            is_public,
+                            forked_from: None,
+                        })
+                        .execute(conn)?;
+                    let new_id = diesel::select(last_insert_id).first(conn)?;
+                    mapping.voice_presets.insert(preset.id, new_id);
+                }
+                for effect in bundled_effects {
+                    let new_id = insert_effect(conn, &InsertableEffect {
+                        title: effect.title,
+                        description: effect.description,
+                        code: effect.code,
+                        user_id,
+                    })?;
+                    mapping.effects.insert(effect.id, new_id);
+                }
+
+                remap_bundle_references(&mut content, &mapping);
+                let content = Value::Object(content).to_string();
+                diesel::insert_into(compositions::table)
+                    .values(&NewComposition {
+                        title: title.clone(),
+                        description: description.clone(),
+                        content: content.clone(),
+                        user_id,
+                    })
+                    .execute(conn)?;
+                let composition_id = diesel::select(last_insert_id).first(conn)?;
+                set_composition_tags(conn, composition_id, tags)?;
+                insert_composition_version(conn, composition_id, title, description, content)?;
+                Ok(composition_id)
+            })
+        })
+        .await
+        .map_err(db_error)?;
+
+    info!(
+        "Imported composition bundle as composition id={}",
+        composition_id
+    );
+    Ok(Json(composition_id))
+}
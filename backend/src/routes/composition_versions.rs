@@ -0,0 +1,223 @@
+//! Version history for compositions and MIDI compositions.  Saving over a composition owned by the
+//! logged-in user stores a new version rather than replacing the old one, and any old version can
+//! be restored.  Restoring is itself saved as a new version so that nothing is ever lost.
+
+use diesel::prelude::*;
+use rocket::{http::Status, response::status::Custom, serde::json::Json};
+
+use crate::{
+    db_util::composition_versions::{
+        insert_composition_version, insert_midi_composition_version, is_composition_owner,
+        is_midi_composition_owner,
+    },
+    models::{
+        compositions::CompositionVersionDescriptor,
+        midi_composition::MIDICompositionVersionDescriptor, user::AuthenticatedUser,
+    },
+    WebSynthDbConn,
+};
+
+#[get("/compositions/<composition_id>/versions")]
+pub async fn get_composition_versions(
+    conn: WebSynthDbConn,
+    composition_id: i64,
+) -> Result<Json<Vec<CompositionVersionDescriptor>>, String> {
+    use crate::schema::composition_versions;
+
+    let versions: Vec<(i64, String, String, chrono::NaiveDateTime)> = conn
+        .run(move |conn| {
+            composition_versions::table
+                .filter(composition_versions::dsl::composition_id.eq(composition_id))
+                .select((
+                    composition_versions::dsl::version,
+                    composition_versions::dsl::title,
+                    composition_versions::dsl::description,
+                    composition_versions::dsl::created_at,
+                ))
+                .order(composition_versions::dsl::version.desc())
+                .load(conn)
+        })
+        .await
+        .map_err(|err| {
+            error!("Error querying composition versions: {:?}", err);
+            "Error querying composition versions from the database".to_string()
+        })?;
+
+    Ok(Json(
+        versions
+            .into_iter()
+            .map(
+                |(version, title, description, created_at)| CompositionVersionDescriptor {
+                    version,
+                    title,
+                    description,
+                    created_at: created_at.timestamp(),
+                },
+            )
+            .collect(),
+    ))
+}
+
+/// Makes the given version the current state of the composition, returning the number of the new
+/// version that was created for it
+#[post("/compositions/<composition_id>/versions/<version>/restore")]
+pub async fn restore_composition_version(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    composition_id: i64,
+    version: i64,
+) -> Result<Json<i64>, Custom<String>> {
+    use crate::schema::{composition_versions, compositions};
+
+    conn.run(move |conn| {
+        let conn = &*conn;
+        conn.transaction(|| -> QueryResult<Result<i64, Custom<String>>> {
+            if !is_composition_owner(conn, composition_id, Some(user.id))? {
+                return Ok(Err(Custom(
+                    Status::Forbidden,
+                    String::from("Only the owner of a composition can restore its versions"),
+                )));
+            }
+
+            let restored: Option<(String, String, String)> = composition_versions::table
+                .filter(composition_versions::dsl::composition_id.eq(composition_id))
+                .filter(composition_versions::dsl::version.eq(version))
+                .select((
+                    composition_versions::dsl::title,
+                    composition_versions::dsl::description,
+                    composition_versions::dsl::content,
+                ))
+                .first(conn)
+                .optional()?;
+            let (title, description, content) = match restored {
+                Some(restored) => restored,
+                None =>
+                    return Ok(Err(Custom(
+                        Status::NotFound,
+                        String::from("Composition version not found"),
+                    ))),
+            };
+
+            diesel::update(compositions::table.find(composition_id))
+                .set((
+                    compositions::dsl::title.eq(&title),
+                    compositions::dsl::description.eq(&description),
+                    compositions::dsl::content.eq(&content),
+                ))
+                .execute(conn)?;
+            insert_composition_version(conn, composition_id, title, description, content).map(Ok)
+        })
+    })
+    .await
+    .map_err(|err| {
+        error!("Error restoring composition version: {:?}", err);
+        Custom(Status::InternalServerError, String::from("DB error"))
+    })?
+    .map(Json)
+}
+
+#[get("/midi_compositions/<midi_composition_id>/versions")]
+pub async fn get_midi_composition_versions(
+    conn: WebSynthDbConn,
+    midi_composition_id: i64,
+) -> Result<Json<Vec<MIDICompositionVersionDescriptor>>, String> {
+    use crate::schema::midi_composition_versions;
+
+    let versions: Vec<(i64, String, String, chrono::NaiveDateTime)> = conn
+        .run(move |conn| {
+            midi_composition_versions::table
+                .filter(midi_composition_versions::dsl::midi_composition_id.eq(midi_composition_id))
+                .select((
+                    midi_composition_versions::dsl::version,
+                    midi_composition_versions::dsl::name,
+                    midi_composition_versions::dsl::description,
+                    midi_composition_versions::dsl::created_at,
+                ))
+                .order(midi_composition_versions::dsl::version.desc())
+                .load(conn)
+        })
+        .await
+        .map_err(|err| {
+            error!("Error querying MIDI composition versions: {:?}", err);
+            "Error querying MIDI composition versions from the database".to_string()
+        })?;
+
+    Ok(Json(
+        versions
+            .into_iter()
+            .map(
+                |(version, name, description, created_at)| MIDICompositionVersionDescriptor {
+                    version,
+                    name,
+                    description,
+                    created_at: created_at.timestamp(),
+                },
+            )
+            .collect(),
+    ))
+}
+
+/// Makes the given version the current state of the MIDI composition, returning the number of the
+/// new version that was created for it
+#[post("/midi_compositions/<midi_composition_id>/versions/<version>/restore")]
+pub async fn restore_midi_composition_version(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    midi_composition_id: i64,
+    version: i64,
+) -> Result<Json<i64>, Custom<String>> {
+    use crate::schema::{midi_composition_versions, midi_compositions};
+
+    conn.run(move |conn| {
+        let conn = &*conn;
+        conn.transaction(|| -> QueryResult<Result<i64, Custom<String>>> {
+            if !is_midi_composition_owner(conn, midi_composition_id, Some(user.id))? {
+                return Ok(Err(Custom(
+                    Status::Forbidden,
+                    String::from("Only the owner of a MIDI composition can restore its versions"),
+                )));
+            }
+
+            let restored: Option<(String, String, String)> = midi_composition_versions::table
+                .filter(midi_composition_versions::dsl::midi_composition_id.eq(midi_composition_id))
+                .filter(midi_composition_versions::dsl::version.eq(version))
+                .select((
+                    midi_composition_versions::dsl::name,
+                    midi_composition_versions::dsl::description,
+                    midi_composition_versions::dsl::composition_json,
+                ))
+                .first(conn)
+                .optional()?;
+            let (name, description, composition_json) = match restored {
+                Some(restored) => restored,
+                None =>
+                    return Ok(Err(Custom(
+                        Status::NotFound,
+                        String::from("MIDI composition version not found"),
+                    ))),
+            };
+
+            diesel::update(midi_compositions::table.find(midi_composition_id))
+                .set((
+                    midi_compositions::dsl::name.eq(&name),
+                    midi_compositions::dsl::description.eq(&description),
+                    midi_compositions::dsl::composition_json.eq(&composition_json),
+                ))
+                .execute(conn)?;
+            insert_midi_composition_version(
+                conn,
+                midi_composition_id,
+                name,
+                description,
+                composition_json,
+            )
+            .map(Ok)
+        })
+    })
+    .await
+    .map_err(|err| {
+        error!("Error restoring MIDI composition version: {:?}", err);
+        Custom(Status::InternalServerError, String::from("DB error"))
+    })?
+    .map(Json)
+}
@@ -0,0 +1,248 @@
+//! Revision history for effects.  Every edit to an effect's code is stored as a new revision, and
+//! compositions can reference a specific revision so that they're reproducible after the effect is
+//! changed.  Modules compiled from a revision's code can be registered so that they can be loaded
+//! straight from the Faust compiler's cache.
+
+use diesel::prelude::*;
+use rocket::{http::Status, response::status::Custom, serde::json::Json};
+
+use crate::{
+    db_util::effects::insert_effect_revision,
+    models::{
+        effects::{
+            build_faust_module_id, CompiledEffectArtifact, EffectRevision,
+            EffectRevisionDescriptor, RegisterCompiledEffectArtifactRequest,
+            SaveEffectRevisionRequest,
+        },
+        user::AuthenticatedUser,
+        validation::{check_serialized_size, validate_metadata, MAX_PRESET_BODY_BYTES},
+    },
+    rate_limit::SaveRateLimit,
+    WebSynthDbConn,
+};
+
+fn db_error(err: diesel::result::Error) -> Custom<String> {
+    error!("DB error managing effect revisions: {:?}", err);
+    Custom(Status::InternalServerError, String::from("DB error"))
+}
+
+/// Saves new code for the effect as its next revision, returning the new revision number.  If the
+/// code is unchanged, the current revision is returned instead.
+#[post("/effects/<effect_id>/revisions", data = "<request>")]
+pub async fn save_effect_revision(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    _rate_limit: SaveRateLimit,
+    effect_id: i64,
+    request: Json<SaveEffectRevisionRequest>,
+) -> Result<Json<i64>, Custom<String>> {
+    use crate::schema::effects;
+
+    let SaveEffectRevisionRequest {
+        code,
+        title,
+        description,
+    } = request.into_inner();
+    validate_metadata(
+        title.as_deref().unwrap_or_default(),
+        description.as_deref().unwrap_or_default(),
+        &[],
+    )
+    .map_err(|err| Custom(Status::BadRequest, err))?;
+    check_serialized_size("code", &code, MAX_PRESET_BODY_BYTES)
+        .map_err(|err| Custom(Status::BadRequest, err))?;
+
+    let user_id = user.id;
+    conn.run(move |conn| {
+        let conn = &*conn;
+        conn.transaction(|| -> QueryResult<Result<i64, Custom<String>>> {
+            let effect: Option<(Option<i64>, String, i64)> = effects::table
+                .find(effect_id)
+                .select((
+                    effects::dsl::user_id,
+                    effects::dsl::code,
+                    effects::dsl::revision,
+                ))
+                .first(conn)
+                .optional()?;
+            let (owner_id, current_code, current_revision) = match effect {
+                Some(effect) => effect,
+                None =>
+                    return Ok(Err(Custom(
+                        Status::NotFound,
+                        String::from("Effect not found"),
+                    ))),
+            };
+            if owner_id != Some(user_id) {
+                return Ok(Err(Custom(
+                    Status::Forbidden,
+                    String::from("Only the owner of an effect can edit it"),
+                )));
+            }
+
+            if let Some(title) = title {
+                diesel::update(effects::table.find(effect_id))
+                    .set(effects::dsl::title.eq(title))
+                    .execute(conn)?;
+            }
+            if let Some(description) = description {
+                diesel::update(effects::table.find(effect_id))
+                    .set(effects::dsl::description.eq(description))
+                    .execute(conn)?;
+            }
+            if code == current_code {
+                return Ok(Ok(current_revision));
+            }
+            insert_effect_revision(conn, effect_id, code).map(Ok)
+        })
+    })
+    .await
+    .map_err(db_error)?
+    .map(Json)
+}
+
+#[get("/effects/<effect_id>/revisions")]
+pub async fn get_effect_revisions(
+    conn: WebSynthDbConn,
+    effect_id: i64,
+) -> Result<Json<Vec<EffectRevisionDescriptor>>, Custom<String>> {
+    use crate::schema::effect_revisions;
+
+    let revisions: Vec<(i64, String, chrono::NaiveDateTime)> = conn
+        .run(move |conn| {
+            effect_revisions::table
+                .filter(effect_revisions::dsl::effect_id.eq(effect_id))
+                .select((
+                    effect_revisions::dsl::revision,
+                    effect_revisions::dsl::code_hash,
+                    effect_revisions::dsl::created_at,
+                ))
+                .order(effect_revisions::dsl::revision.desc())
+                .load(conn)
+        })
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(
+        revisions
+            .into_iter()
+            .map(
+                |(revision, code_hash, created_at)| EffectRevisionDescriptor {
+                    revision,
+                    code_hash,
+                    created_at: created_at.timestamp(),
+                },
+            )
+            .collect(),
+    ))
+}
+
+#[get("/effects/<effect_id>/revisions/<revision>")]
+pub async fn get_effect_revision(
+    conn: WebSynthDbConn,
+    effect_id: i64,
+    revision: i64,
+) -> Result<Json<EffectRevision>, Custom<String>> {
+    use crate::schema::{compiled_effect_artifacts, effect_revisions};
+
+    let found = conn
+        .run(move |conn| -> QueryResult<_> {
+            let found: Option<(String, String, chrono::NaiveDateTime)> = effect_revisions::table
+                .filter(effect_revisions::dsl::effect_id.eq(effect_id))
+                .filter(effect_revisions::dsl::revision.eq(revision))
+                .select((
+                    effect_revisions::dsl::code,
+                    effect_revisions::dsl::code_hash,
+                    effect_revisions::dsl::created_at,
+                ))
+                .first(conn)
+                .optional()?;
+            let (code, code_hash, created_at) = match found {
+                Some(found) => found,
+                None => return Ok(None),
+            };
+
+            let compiled_artifacts: Vec<CompiledEffectArtifact> = compiled_effect_artifacts::table
+                .filter(compiled_effect_artifacts::dsl::code_hash.eq(&code_hash))
+                .select((
+                    compiled_effect_artifacts::dsl::code_hash,
+                    compiled_effect_artifacts::dsl::optimized,
+                    compiled_effect_artifacts::dsl::module_id,
+                ))
+                .load(conn)?;
+            Ok(Some(EffectRevision {
+                effect_id,
+                revision,
+                code,
+                code_hash,
+                created_at: created_at.timestamp(),
+                compiled_artifacts,
+            }))
+        })
+        .await
+        .map_err(db_error)?;
+
+    match found {
+        Some(found) => Ok(Json(found)),
+        None => Err(Custom(
+            Status::NotFound,
+            String::from("Effect revision not found"),
+        )),
+    }
+}
+
+/// Records that the code of the given revision has been compiled by the Faust compiler into the
+/// module with the provided ID
+#[post(
+    "/effects/<effect_id>/revisions/<revision>/compiled_artifacts",
+    data = "<request>"
+)]
+pub async fn register_compiled_effect_artifact(
+    conn: WebSynthDbConn,
+    _rate_limit: SaveRateLimit,
+    effect_id: i64,
+    revision: i64,
+    request: Json<RegisterCompiledEffectArtifactRequest>,
+) -> Result<(), Custom<String>> {
+    use crate::schema::{compiled_effect_artifacts, effect_revisions};
+
+    let RegisterCompiledEffectArtifactRequest {
+        optimized,
+        module_id,
+    } = request.into_inner();
+    conn.run(move |conn| -> QueryResult<Result<(), Custom<String>>> {
+        let code_hash: Option<String> = effect_revisions::table
+            .filter(effect_revisions::dsl::effect_id.eq(effect_id))
+            .filter(effect_revisions::dsl::revision.eq(revision))
+            .select(effect_revisions::dsl::code_hash)
+            .first(conn)
+            .optional()?;
+        let code_hash = match code_hash {
+            Some(code_hash) => code_hash,
+            None =>
+                return Ok(Err(Custom(
+                    Status::NotFound,
+                    String::from("Effect revision not found"),
+                ))),
+        };
+        // Module IDs are derived from the hash of the compiled code, so this guards against
+        // registering a module that was compiled from different code
+        if module_id != build_faust_module_id(&code_hash, optimized) {
+            return Ok(Err(Custom(
+                Status::BadRequest,
+                String::from("Module ID doesn't match the code of this revision"),
+            )));
+        }
+
+        diesel::insert_or_ignore_into(compiled_effect_artifacts::table)
+            .values(CompiledEffectArtifact {
+                code_hash,
+                optimized,
+                module_id,
+            })
+            .execute(conn)?;
+        Ok(Ok(()))
+    })
+    .await
+    .map_err(db_error)?
+}
@@ -3,9 +3,9 @@ use rocket::{http::Status, response::status::Custom, serde::json::Json};
 use crate::{
     db_util::login::{
         generate_login_token, get_user_by_username, insert_new_login_token, insert_new_user,
-        verify_password,
+        password_needs_rehash, update_password_hash, verify_password,
     },
-    models::user::{LoginRequest, MaybeLoginToken},
+    models::user::{AuthenticatedUser, LoginRequest, MaybeLoginToken},
     WebSynthDbConn,
 };
 
@@ -34,6 +34,12 @@ pub async fn login(
         ));
     }
 
+    if password_needs_rehash(&user.hashed_password) {
+        if let Err(err) = update_password_hash(&conn, user.id, login_request.password).await {
+            error!("DB error updating password hash: {}", err);
+        }
+    }
+
     let login_token = generate_login_token();
     insert_new_login_token(&conn, user.id, login_token.clone())
         .await
@@ -96,3 +102,37 @@ pub async fn get_logged_in_username(
 
     Ok(user.username)
 }
+
+/// Exchanges the current login token for a new one with a fresh expiry.  The old token stops being
+/// valid.
+#[post("/refresh_login_token")]
+pub async fn refresh_login_token(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+) -> Result<String, Custom<String>> {
+    match crate::db_util::login::refresh_login_token(&conn, user.login_token).await {
+        Ok(Some(login_token)) => Ok(login_token),
+        Ok(None) => Err(Custom(
+            Status::Unauthorized,
+            String::from("Invalid login token"),
+        )),
+        Err(err) => {
+            error!("DB error refreshing login token: {}", err);
+            Err(Custom(
+                Status::InternalServerError,
+                String::from("DB error"),
+            ))
+        },
+    }
+}
+
+/// Revokes the current login token
+#[post("/logout")]
+pub async fn logout(conn: WebSynthDbConn, user: AuthenticatedUser) -> Result<(), Custom<String>> {
+    crate::db_util::login::revoke_login_token(&conn, user.login_token)
+        .await
+        .map_err(|err| {
+            error!("DB error revoking login token: {}", err);
+            Custom(Status::InternalServerError, String::from("DB error"))
+        })
+}
@@ -1,20 +1,20 @@
 use diesel::prelude::*;
 use itertools::Itertools;
-use rocket::serde::json::Json;
+use rocket::{response::status::Custom, serde::json::Json};
 
+use super::{bad_request, internal_error};
 use crate::{
-    db_util::{
-        build_tags_with_counts, get_and_create_tag_ids, last_insert_id,
-        login::get_logged_in_user_id,
-    },
+    db_util::{build_tags_with_counts, get_and_create_tag_ids, last_insert_id},
     models::{
         looper_preset::{
             LooperPresetDescriptor, NewLooperPreset, NewLooperPresetTag, SaveLooperPresetRequest,
             SerializedLooperInstState,
         },
         tags::{EntityIdTag, TagCount},
-        user::MaybeLoginToken,
+        user::MaybeAuthenticatedUser,
+        validation::{check_serialized_size, validate_metadata, MAX_SERIALIZED_STATE_BYTES},
     },
+    rate_limit::SaveRateLimit,
     WebSynthDbConn,
 };
 
@@ -87,11 +87,17 @@ pub async fn get_looper_preset_by_id(
 
     let serialized_looper_inst_state: Option<String> = conn
         .run(move |conn| -> QueryResult<Option<_>> {
-            looper_presets::table
+            let serialized_looper_inst_state = looper_presets::table
                 .find(preset_id)
                 .select(looper_presets::dsl::serialized_looper_inst_state)
                 .first(conn)
-                .optional()
+                .optional()?;
+            if serialized_looper_inst_state.is_some() {
+                diesel::update(looper_presets::table.find(preset_id))
+                    .set(looper_presets::dsl::use_count.eq(looper_presets::dsl::use_count + 1))
+                    .execute(conn)?;
+            }
+            Ok(serialized_looper_inst_state)
         })
         .await
         .map_err(|err| {
@@ -116,11 +122,12 @@ pub async fn get_looper_preset_by_id(
 pub async fn create_looper_preset(
     conn: WebSynthDbConn,
     looper_preset: Json<SaveLooperPresetRequest>,
-    login_token: MaybeLoginToken,
-) -> Result<Json<i64>, String> {
+    user: MaybeAuthenticatedUser,
+    _rate_limit: SaveRateLimit,
+) -> Result<Json<i64>, Custom<String>> {
     use crate::schema::{looper_presets, looper_presets_tags};
 
-    let user_id = get_logged_in_user_id(&conn, login_token).await;
+    let user_id = user.0.map(|user| user.id);
 
     let SaveLooperPresetRequest {
         serialized_looper_inst_state,
@@ -128,8 +135,18 @@ pub async fn create_looper_preset(
         description,
         tags,
     } = looper_preset.into_inner();
+    validate_metadata(&name, &description, &tags).map_err(bad_request)?;
+    serialized_looper_inst_state
+        .validate()
+        .map_err(bad_request)?;
     let serialized_looper_inst_state: String =
         serde_json::to_string(&serialized_looper_inst_state).unwrap();
+    check_serialized_size(
+        "serializedLooperInstState",
+        &serialized_looper_inst_state,
+        MAX_SERIALIZED_STATE_BYTES,
+    )
+    .map_err(bad_request)?;
 
     let created_preset_id = conn
         .run(move |conn| -> QueryResult<i64> {
@@ -168,7 +185,7 @@ pub async fn create_looper_preset(
         .await
         .map_err(|err| {
             error!("DB error inserting looper preset into DB: {}", err);
-            String::from("DB error inserting looper preset into DB")
+            internal_error(String::from("DB error inserting looper preset into DB"))
         })?;
 
     Ok(Json(created_preset_id))
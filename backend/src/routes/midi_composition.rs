@@ -1,13 +1,23 @@
 use diesel::prelude::*;
 use itertools::Itertools;
-use rocket::serde::json::Json;
+use rocket::{response::status::Custom, serde::json::Json};
 
+use super::{bad_request, internal_error};
 use crate::{
-    db_util::{build_tags_with_counts, get_and_create_tag_ids, last_insert_id},
+    db_util::{
+        build_tags_with_counts,
+        composition_versions::{
+            insert_midi_composition_version, is_midi_composition_owner, set_midi_composition_tags,
+        },
+        last_insert_id,
+    },
     models::{
         midi_composition::*,
         tags::{EntityIdTag, TagCount},
+        user::MaybeAuthenticatedUser,
+        validation::{check_serialized_size, validate_metadata, MAX_SERIALIZED_STATE_BYTES},
     },
+    rate_limit::SaveRateLimit,
     WebSynthDbConn,
 };
 
@@ -20,7 +30,13 @@ pub async fn get_midi_compositions(
     let compositions: Vec<QueryableMIDIComposition> = conn
         .run(|conn| {
             midi_compositions::table
-                .select(midi_compositions::all_columns)
+                .select((
+                    midi_compositions::id,
+                    midi_compositions::name,
+                    midi_compositions::description,
+                    midi_compositions::composition_json,
+                    midi_compositions::user_id,
+                ))
                 .load(conn)
                 .map_err(|err| {
                     error!("Error querying MIDI compositions from DB: {:?}", err);
@@ -92,55 +108,82 @@ pub async fn get_midi_compositions(
     ))
 }
 
+/// Saves a new MIDI composition, or a new version of an existing one if `parent_id` is provided and
+/// owned by the logged-in user.  Returns the ID of the MIDI composition.
 #[post("/midi_compositions", data = "<composition>")]
 pub async fn save_midi_composition(
     conn: WebSynthDbConn,
     composition: Json<NewMIDIComposition>,
-) -> Result<(), String> {
-    use crate::schema::{midi_compositions, midi_compositions_tags};
+    user: MaybeAuthenticatedUser,
+    _rate_limit: SaveRateLimit,
+) -> Result<Json<i64>, Custom<String>> {
+    use crate::schema::midi_compositions;
 
+    validate_metadata(
+        &composition.name,
+        &composition.description,
+        &composition.tags,
+    )
+    .map_err(bad_request)?;
+    composition.composition.validate().map_err(bad_request)?;
+
+    let user_id = user.0.map(|user| user.id);
     let serialized_comp = serde_json::to_string(&composition.0.composition)
         .expect("Failed to serialize MIDI composition");
+    check_serialized_size("composition", &serialized_comp, MAX_SERIALIZED_STATE_BYTES)
+        .map_err(bad_request)?;
     let insertable_comp = InsertableMIDIComposition {
         name: composition.name.clone(),
         description: composition.description.clone(),
         composition_json: serialized_comp,
+        user_id,
     };
     let tags = composition.tags.clone();
+    let parent_id = composition.parent_id;
 
-    conn.run(|conn| {
-        conn.transaction(|| -> QueryResult<()> {
-            diesel::insert_into(midi_compositions::table)
-                .values(insertable_comp)
-                .execute(conn)?;
-            let created_preset_id = diesel::select(last_insert_id).first(conn)?;
-
-            // Insert tags
-            let tag_count = tags.len();
-            let tag_ids = get_and_create_tag_ids(conn, tags)?;
-            assert_eq!(tag_count, tag_ids.len());
+    let saved_composition_id = conn
+        .run(move |conn| {
+            conn.transaction(|| -> QueryResult<i64> {
+                let saved_composition_id = match parent_id {
+                    Some(parent_id) if is_midi_composition_owner(conn, parent_id, user_id)? => {
+                        diesel::update(midi_compositions::table.find(parent_id))
+                            .set((
+                                midi_compositions::dsl::name.eq(&insertable_comp.name),
+                                midi_compositions::dsl::description
+                                    .eq(&insertable_comp.description),
+                                midi_compositions::dsl::composition_json
+                                    .eq(&insertable_comp.composition_json),
+                            ))
+                            .execute(conn)?;
+                        parent_id
+                    },
+                    // Saving over someone else's composition creates a new one instead
+                    _ => {
+                        diesel::insert_into(midi_compositions::table)
+                            .values(&insertable_comp)
+                            .execute(conn)?;
+                        diesel::select(last_insert_id).first(conn)?
+                    },
+                };
 
-            let new_tags: Vec<NewMidiCompositionTag> = tag_ids
-                .into_iter()
-                .map(|tag_id| NewMidiCompositionTag {
-                    midi_composition_id: created_preset_id,
-                    tag_id,
-                })
-                .collect();
-
-            diesel::insert_into(midi_compositions_tags::table)
-                .values(new_tags)
-                .execute(conn)?;
+                set_midi_composition_tags(conn, saved_composition_id, tags)?;
+                insert_midi_composition_version(
+                    conn,
+                    saved_composition_id,
+                    insertable_comp.name,
+                    insertable_comp.description,
+                    insertable_comp.composition_json,
+                )?;
 
-            Ok(())
+                Ok(saved_composition_id)
+            })
         })
-    })
-    .await
-    .map_err(|err| {
-        error!("Error inserting MIDI composition into DB: {:?}", err);
-        String::from("DB Error")
-    })?;
-    Ok(())
+        .await
+        .map_err(|err| {
+            error!("Error inserting MIDI composition into DB: {:?}", err);
+            internal_error(String::from("DB Error"))
+        })?;
+    Ok(Json(saved_composition_id))
 }
 
 #[get("/midi_composition_tags")]
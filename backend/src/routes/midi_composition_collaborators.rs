@@ -0,0 +1,142 @@
+//! Sharing of MIDI compositions with other users.  The owner of a MIDI composition can add other
+//! users as its collaborators, which lets them join its realtime collaboration room.
+
+use diesel::prelude::*;
+use rocket::{http::Status, response::status::Custom, serde::json::Json};
+
+use crate::{
+    db_util::{
+        composition_versions::is_midi_composition_owner, login::get_user_by_username,
+        midi_composition_collaborators::get_midi_composition_collaborators,
+    },
+    models::{
+        midi_composition::{AddMIDICompositionCollaboratorRequest, NewMIDICompositionCollaborator},
+        user::AuthenticatedUser,
+    },
+    WebSynthDbConn,
+};
+
+async fn check_midi_composition_owner(
+    conn: &WebSynthDbConn,
+    midi_composition_id: i64,
+    user_id: i64,
+) -> Result<(), Custom<String>> {
+    let is_owner = conn
+        .run(move |conn| is_midi_composition_owner(conn, midi_composition_id, Some(user_id)))
+        .await
+        .map_err(|err| {
+            error!("Error checking MIDI composition ownership: {:?}", err);
+            Custom(Status::InternalServerError, String::from("DB error"))
+        })?;
+    if !is_owner {
+        return Err(Custom(
+            Status::Forbidden,
+            String::from("Only the owner of a MIDI composition can manage its collaborators"),
+        ));
+    }
+    Ok(())
+}
+
+/// Looks up the ID of the user with the given username, failing if they don't exist
+async fn get_collaborator_user_id(
+    conn: &WebSynthDbConn,
+    username: String,
+) -> Result<i64, Custom<String>> {
+    match get_user_by_username(conn, username).await {
+        Ok(Some(user)) => Ok(user.id),
+        Ok(None) => Err(Custom(Status::NotFound, String::from("User not found"))),
+        Err(err) => Err(Custom(Status::InternalServerError, err)),
+    }
+}
+
+/// Returns the usernames of the MIDI composition's collaborators
+#[get("/midi_compositions/<midi_composition_id>/collaborators")]
+pub async fn get_midi_composition_collaborator_usernames(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    midi_composition_id: i64,
+) -> Result<Json<Vec<String>>, Custom<String>> {
+    check_midi_composition_owner(&conn, midi_composition_id, user.id).await?;
+
+    conn.run(move |conn| get_midi_composition_collaborators(conn, midi_composition_id))
+        .await
+        .map_err(|err| {
+            error!("Error loading MIDI composition collaborators: {:?}", err);
+            Custom(Status::InternalServerError, String::from("DB error"))
+        })
+        .map(Json)
+}
+
+#[post(
+    "/midi_compositions/<midi_composition_id>/collaborators",
+    data = "<req>"
+)]
+pub async fn add_midi_composition_collaborator(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    midi_composition_id: i64,
+    req: Json<AddMIDICompositionCollaboratorRequest>,
+) -> Result<(), Custom<String>> {
+    use crate::schema::midi_composition_collaborators;
+
+    check_midi_composition_owner(&conn, midi_composition_id, user.id).await?;
+    let collaborator_user_id = get_collaborator_user_id(&conn, req.into_inner().username).await?;
+    if collaborator_user_id == user.id {
+        return Err(Custom(
+            Status::BadRequest,
+            String::from("The owner of a MIDI composition can't be added as its collaborator"),
+        ));
+    }
+
+    let entry = NewMIDICompositionCollaborator {
+        midi_composition_id,
+        user_id: collaborator_user_id,
+    };
+    conn.run(move |conn| {
+        diesel::insert_or_ignore_into(midi_composition_collaborators::table)
+            .values(&entry)
+            .execute(conn)
+    })
+    .await
+    .map_err(|err| {
+        error!("Error adding MIDI composition collaborator: {:?}", err);
+        Custom(Status::InternalServerError, String::from("DB error"))
+    })
+    .map(drop)
+}
+
+/// Removes a collaborator from the MIDI composition.  They aren't disconnected from its
+/// collaboration room if they're currently in it, but they won't be able to join it again.
+#[delete("/midi_compositions/<midi_composition_id>/collaborators/<username>")]
+pub async fn remove_midi_composition_collaborator(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    midi_composition_id: i64,
+    username: String,
+) -> Result<(), Custom<String>> {
+    use crate::schema::midi_composition_collaborators;
+
+    check_midi_composition_owner(&conn, midi_composition_id, user.id).await?;
+    let collaborator_user_id = get_collaborator_user_id(&conn, username).await?;
+
+    let deleted_count = conn
+        .run(move |conn| {
+            diesel::delete(
+                midi_composition_collaborators::table
+                    .find((midi_composition_id, collaborator_user_id)),
+            )
+            .execute(conn)
+        })
+        .await
+        .map_err(|err| {
+            error!("Error removing MIDI composition collaborator: {:?}", err);
+            Custom(Status::InternalServerError, String::from("DB error"))
+        })?;
+    if deleted_count == 0 {
+        return Err(Custom(
+            Status::NotFound,
+            String::from("User isn't a collaborator of this MIDI composition"),
+        ));
+    }
+    Ok(())
+}
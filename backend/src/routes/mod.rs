@@ -1,19 +1,20 @@
-use std::collections::HashMap;
-
 use diesel::{self, prelude::*};
 use itertools::Itertools;
-use rocket::serde::json::Json;
+use rocket::{http::Status, response::status::Custom, serde::json::Json};
 
 use crate::{
     db_util::{
-        build_tags_with_counts, get_and_create_tag_ids, last_insert_id,
+        build_tags_with_counts,
+        composition_versions::{
+            insert_composition_version, is_composition_owner, set_composition_tags,
+        },
+        effects::insert_effect,
+        last_insert_id,
         login::get_logged_in_user_id,
     },
     models::{
-        compositions::{
-            Composition, CompositionDescriptor, NewComposition, NewCompositionRequest,
-            NewCompositionTag,
-        },
+        automation::validate_composition_automation,
+        compositions::{Composition, CompositionDescriptor, NewComposition, NewCompositionRequest},
         effects::{Effect, InsertableEffect},
         synth_preset::{
             InlineSynthPreset, InlineSynthPresetEntry, NewSynthPresetEntry,
@@ -21,15 +22,32 @@ use crate::{
             UserProvidedNewSynthVoicePreset, VoiceDefinition,
         },
         tags::{EntityIdTag, TagCount},
-        user::MaybeLoginToken,
+        user::{MaybeAuthenticatedUser, MaybeLoginToken},
+        validation::{
+            check_serialized_size, validate_metadata, MAX_COMPOSITION_CONTENT_BYTES,
+            MAX_PRESET_BODY_BYTES,
+        },
     },
+    rate_limit::SaveRateLimit,
     schema, WebSynthDbConn,
 };
 
+mod composition_bundles;
+mod composition_versions;
+mod effect_revisions;
 mod looper_preset;
 pub mod midi_composition;
+mod midi_composition_collaborators;
 mod remote_samples;
-pub use self::{looper_preset::*, midi_composition::*, remote_samples::*};
+mod search;
+mod sequencer_preset;
+mod synth_preset_sharing;
+mod user_samples;
+pub use self::{
+    composition_bundles::*, composition_versions::*, effect_revisions::*, looper_preset::*,
+    midi_composition::*, midi_composition_collaborators::*, remote_samples::*, search::*,
+    sequencer_preset::*, synth_preset_sharing::*, user_samples::*,
+};
 pub mod login;
 mod wavetable_preset;
 pub use self::wavetable_preset::*;
@@ -37,20 +55,26 @@ pub use self::wavetable_preset::*;
 #[get("/")]
 pub fn index() -> &'static str { "Application successfully started!" }
 
+pub(crate) fn bad_request(err: String) -> Custom<String> { Custom(Status::BadRequest, err) }
+
+pub(crate) fn internal_error(err: String) -> Custom<String> {
+    Custom(Status::InternalServerError, err)
+}
+
+/// Creates a new effect with its code as the first revision, returning the ID of the new effect
 #[post("/effects", data = "<effect>")]
 pub async fn create_effect(
     conn: WebSynthDbConn,
     mut effect: Json<InsertableEffect>,
     maybe_login_token: MaybeLoginToken,
-) -> Result<String, String> {
+) -> Result<Json<i64>, String> {
     let user_id = get_logged_in_user_id(&conn, maybe_login_token).await;
     effect.0.user_id = user_id;
 
-    let inserted_rows = conn
+    let effect_id = conn
         .run(move |conn| {
-            diesel::insert_into(schema::effects::table)
-                .values(&effect.0)
-                .execute(conn)
+            let conn = &*conn;
+            conn.transaction(|| insert_effect(conn, &effect.0))
         })
         .await
         .map_err(|err| -> String {
@@ -58,7 +82,7 @@ pub async fn create_effect(
             "Error inserting row into database".into()
         })?;
 
-    Ok(format!("Inserted {} row(s).", inserted_rows))
+    Ok(Json(effect_id))
 }
 
 #[get("/effects")]
@@ -76,6 +100,7 @@ pub async fn list_effects(conn: WebSynthDbConn) -> Result<Json<Vec<Effect>>, Str
                     effects::code,
                     effects::user_id,
                     users::username.nullable(),
+                    effects::revision,
                 ))
                 .load(conn)
         })
@@ -87,62 +112,84 @@ pub async fn list_effects(conn: WebSynthDbConn) -> Result<Json<Vec<Effect>>, Str
     Ok(Json(all_effects))
 }
 
+/// Saves a new composition, or a new version of an existing one if `parent_id` is provided and
+/// owned by the logged-in user.  Returns the ID of the composition.
 #[post("/compositions", data = "<composition>")]
 pub async fn save_composition(
     conn: WebSynthDbConn,
     mut composition: Json<NewCompositionRequest>,
-    login_token: MaybeLoginToken,
-) -> Result<Json<i64>, String> {
-    let user_id = get_logged_in_user_id(&conn, login_token).await;
+    user: MaybeAuthenticatedUser,
+    _rate_limit: SaveRateLimit,
+) -> Result<Json<i64>, Custom<String>> {
+    validate_metadata(
+        &composition.0.title,
+        &composition.0.description,
+        &composition.0.tags,
+    )
+    .map_err(bad_request)?;
+    validate_composition_automation(&composition.0.content).map_err(bad_request)?;
+
+    let user_id = user.0.map(|user| user.id);
+    let content = serde_json::to_string(&composition.0.content).map_err(|err| {
+        error!("Failed to serialize composition to JSON string: {:?}", err);
+        internal_error(format!("Failed to serialize composition to JSON string"))
+    })?;
+    check_serialized_size("content", &content, MAX_COMPOSITION_CONTENT_BYTES)
+        .map_err(bad_request)?;
     let new_composition = NewComposition {
         title: composition.0.title,
         description: composition.0.description,
-        content: serde_json::to_string(&composition.0.content).map_err(|err| {
-            error!("Failed to serialize composition to JSON string: {:?}", err);
-            format!("Failed to serialize composition to JSON string")
-        })?,
+        content,
         user_id,
     };
     let tags: Vec<String> = std::mem::take(&mut composition.0.tags);
+    let parent_id = composition.0.parent_id;
 
-    let saved_composition_id = conn
+    let (saved_composition_id, version) = conn
         .run(move |conn| {
-            conn.transaction(|| -> QueryResult<i64> {
-                diesel::insert_into(schema::compositions::table)
-                    .values(&new_composition)
-                    .execute(conn)?;
-
-                let saved_comp_id = diesel::select(last_insert_id).first(conn)?;
-
-                // Insert tags
-                let tag_count = tags.len();
-                let tag_ids = get_and_create_tag_ids(conn, tags)?;
-                assert_eq!(tag_count, tag_ids.len());
-
-                let new_tags: Vec<NewCompositionTag> = tag_ids
-                    .into_iter()
-                    .map(|tag_id| NewCompositionTag {
-                        composition_id: saved_comp_id,
-                        tag_id,
-                    })
-                    .collect();
+            conn.transaction(|| -> QueryResult<(i64, i64)> {
+                let saved_comp_id = match parent_id {
+                    Some(parent_id) if is_composition_owner(conn, parent_id, user_id)? => {
+                        diesel::update(schema::compositions::table.find(parent_id))
+                            .set((
+                                schema::compositions::dsl::title.eq(&new_composition.title),
+                                schema::compositions::dsl::description
+                                    .eq(&new_composition.description),
+                                schema::compositions::dsl::content.eq(&new_composition.content),
+                            ))
+                            .execute(conn)?;
+                        parent_id
+                    },
+                    // Saving over someone else's composition creates a new one instead
+                    _ => {
+                        diesel::insert_into(schema::compositions::table)
+                            .values(&new_composition)
+                            .execute(conn)?;
+                        diesel::select(last_insert_id).first(conn)?
+                    },
+                };
 
-                diesel::insert_into(schema::compositions_tags::table)
-                    .values(new_tags)
-                    .execute(conn)?;
+                set_composition_tags(conn, saved_comp_id, tags)?;
+                let version = insert_composition_version(
+                    conn,
+                    saved_comp_id,
+                    new_composition.title,
+                    new_composition.description,
+                    new_composition.content,
+                )?;
 
-                Ok(saved_comp_id)
+                Ok((saved_comp_id, version))
             })
         })
         .await
-        .map_err(|err| -> String {
+        .map_err(|err| {
             error!("Error inserting row: {:?}", err);
-            "Error inserting row into database".into()
+            internal_error("Error inserting row into database".into())
         })?;
 
     info!(
-        "Successfully saved new composition with id={}",
-        saved_composition_id
+        "Successfully saved composition with id={}, version={}",
+        saved_composition_id, version
     );
     Ok(Json(saved_composition_id))
 }
@@ -155,7 +202,16 @@ pub async fn get_composition_by_id(
     use crate::schema::compositions::dsl::*;
 
     let composition_opt = match conn
-        .run(move |conn| compositions.find(composition_id).first::<Composition>(conn))
+        .run(move |conn| -> QueryResult<Composition> {
+            let composition = compositions
+                .find(composition_id)
+                .select((id, title, description, content, user_id))
+                .first::<Composition>(conn)?;
+            diesel::update(compositions.find(composition_id))
+                .set(use_count.eq(use_count + 1))
+                .execute(conn)?;
+            Ok(composition)
+        })
         .await
     {
         Ok(composition) => Some(Json(composition)),
@@ -247,75 +303,68 @@ pub async fn get_composition_tags(conn: WebSynthDbConn) -> Result<Json<Vec<TagCo
 
 #[get("/synth_presets")]
 pub async fn get_synth_presets(
-    conn0: WebSynthDbConn,
-    conn1: WebSynthDbConn,
+    conn: WebSynthDbConn,
+    login_token: MaybeLoginToken,
 ) -> Result<Json<Vec<InlineSynthPresetEntry>>, String> {
-    use crate::schema::{synth_presets, voice_presets};
-
-    let (synth_presets_, voice_presets_): (
-        Vec<(i64, String, String, String, Option<i64>)>,
-        Vec<(i64, String, String, String, Option<i64>)>,
-    ) = tokio::try_join!(
-        conn0.run(|conn| {
+    use crate::schema::{synth_presets, users};
+
+    let logged_in_user_id = get_logged_in_user_id(&conn, login_token).await;
+
+    let synth_presets_: Vec<(
+        i64,
+        String,
+        String,
+        String,
+        Option<i64>,
+        Option<String>,
+        bool,
+        Option<i64>,
+    )> = conn
+        .run(move |conn| {
             synth_presets::table
+                .left_join(users::table)
+                // `user_id = NULL` is never true, so anonymous users only see public presets
+                .filter(
+                    synth_presets::is_public
+                        .eq(true)
+                        .or(synth_presets::user_id.eq(logged_in_user_id)),
+                )
                 .select((
                     synth_presets::id,
                     synth_presets::title,
                     synth_presets::description,
                     synth_presets::body,
                     synth_presets::user_id,
+                    users::dsl::username.nullable(),
+                    synth_presets::is_public,
+                    synth_presets::forked_from,
                 ))
                 .load(conn)
-                .map_err(|err| {
-                    error!("Error querying synth presets: {:?}", err);
-                    "Error querying synth presets from the database".to_string()
-                })
-        }),
-        conn1.run(|conn| {
-            voice_presets::table
-                .select((
-                    voice_presets::id,
-                    voice_presets::title,
-                    voice_presets::description,
-                    voice_presets::body,
-                    voice_presets::user_id,
-                ))
-                .load(conn)
-                .map_err(|err| {
-                    error!("Error querying synth voice presets: {:?}", err);
-                    "Error querying synth voice presets from the database".to_string()
-                })
-        }),
-    )?;
-
-    // build a mapping of voice preset id to voice preset
-    let mut voice_presets_by_id: HashMap<i64, SynthVoicePresetEntry> = HashMap::new();
-    for (id_, title_, description_, body_, user_id_) in voice_presets_ {
-        let body_ = serde_json::from_str(&body_).map_err(|err| -> String {
-            error!("Error parsing voice preset entry stored in DB: {:?}", err);
-            "Error parsing voice preset entry stored in DB".into()
+        })
+        .await
+        .map_err(|err| {
+            error!("Error querying synth presets: {:?}", err);
+            "Error querying synth presets from the database".to_string()
         })?;
 
-        let voice_preset = SynthVoicePresetEntry {
-            id: id_,
-            title: title_,
-            description: description_,
-            body: body_,
-            user_id: user_id_,
-        };
-
-        voice_presets_by_id.insert(voice_preset.id, voice_preset);
-    }
-
     let presets = synth_presets_
         .into_iter()
         .map(
-            |(synth_preset_id, title_, description_, body_, user_id_)| -> Result<InlineSynthPresetEntry, String> {
-                let body_: SynthPreset =
-                    serde_json::from_str(&body_).map_err(|err| -> String {
-                        error!("Invalid synth preset body provided: {:?}", err);
-                        "Invalid synth preset body provided".into()
-                    })?;
+            |(
+                synth_preset_id,
+                title_,
+                description_,
+                body_,
+                user_id_,
+                user_name,
+                is_public_,
+                forked_from_,
+            )|
+             -> Result<InlineSynthPresetEntry, String> {
+                let body_: SynthPreset = serde_json::from_str(&body_).map_err(|err| -> String {
+                    error!("Invalid synth preset body provided: {:?}", err);
+                    "Invalid synth preset body provided".into()
+                })?;
                 let inlined_body = InlineSynthPreset {
                     voices: body_.voices,
                 };
@@ -325,6 +374,9 @@ pub async fn get_synth_presets(
                     description: description_,
                     body: inlined_body,
                     user_id: user_id_,
+                    user_name,
+                    is_public: is_public_,
+                    forked_from: forked_from_,
                 })
             },
         )
@@ -337,22 +389,30 @@ pub async fn get_synth_presets(
 pub async fn create_synth_preset(
     conn: WebSynthDbConn,
     preset: Json<ReceivedSynthPresetEntry>,
-    login_token: MaybeLoginToken,
-) -> Result<(), String> {
+    user: MaybeAuthenticatedUser,
+    _rate_limit: SaveRateLimit,
+) -> Result<(), Custom<String>> {
     use crate::schema::synth_presets::dsl::*;
 
-    let user_id_ = get_logged_in_user_id(&conn, login_token).await;
+    validate_metadata(&preset.title, &preset.description, &[]).map_err(bad_request)?;
+    preset.body.validate().map_err(bad_request)?;
+
+    let user_id_ = user.0.map(|user| user.id);
 
-    let body_: String = serde_json::to_string(&preset.body).map_err(|err| -> String {
+    let body_: String = serde_json::to_string(&preset.body).map_err(|err| {
         let err_msg = format!("Error parsing provided synth preset body: {:?}", err);
         error!("{}", err_msg);
-        err_msg
+        internal_error(err_msg)
     })?;
+    check_serialized_size("body", &body_, MAX_PRESET_BODY_BYTES).map_err(bad_request)?;
     let entry = NewSynthPresetEntry {
         title: preset.0.title,
         description: preset.0.description,
         body: body_,
+        // Presets without an owner couldn't be published again once hidden
+        is_public: preset.0.is_public || user_id_.is_none(),
         user_id: user_id_,
+        forked_from: None,
     };
 
     conn.run(move |conn| {
@@ -361,9 +421,9 @@ pub async fn create_synth_preset(
             .execute(conn)
     })
     .await
-    .map_err(|err| -> String {
+    .map_err(|err| {
         error!("Error inserting synth preset into database: {:?}", err);
-        "Error inserting synth preset into database".into()
+        internal_error("Error inserting synth preset into database".into())
     })
     .map(drop)
 }
@@ -371,13 +431,32 @@ pub async fn create_synth_preset(
 #[get("/synth_voice_presets")]
 pub async fn get_synth_voice_presets(
     conn: WebSynthDbConn,
+    login_token: MaybeLoginToken,
 ) -> Result<Json<Vec<SynthVoicePresetEntry>>, String> {
-    use crate::schema::voice_presets::dsl::*;
+    use crate::schema::{users, voice_presets};
+
+    let logged_in_user_id = get_logged_in_user_id(&conn, login_token).await;
 
     let all_presets = conn
-        .run(|conn| {
-            voice_presets
-                .select((id, title, description, body, user_id))
+        .run(move |conn| {
+            voice_presets::table
+                .left_join(users::table)
+                // `user_id = NULL` is never true, so anonymous users only see public presets
+                .filter(
+                    voice_presets::is_public
+                        .eq(true)
+                        .or(voice_presets::user_id.eq(logged_in_user_id)),
+                )
+                .select((
+                    voice_presets::id,
+                    voice_presets::title,
+                    voice_presets::description,
+                    voice_presets::body,
+                    voice_presets::user_id,
+                    users::dsl::username.nullable(),
+                    voice_presets::is_public,
+                    voice_presets::forked_from,
+                ))
                 .load(conn)
         })
         .await
@@ -385,32 +464,49 @@ pub async fn get_synth_voice_presets(
             error!("Error querying synth presets: {:?}", err);
             "Error querying synth presets from the database".to_string()
         })?;
-    let all_presets: Vec<SynthVoicePresetEntry> = all_presets
-        .into_iter()
-        .map(
-            |(id_, title_, description_, body_, user_id_): (
-                i64,
-                String,
-                String,
-                String,
-                Option<i64>,
-            )| {
-                let preset: VoiceDefinition =
-                    serde_json::from_str(&body_).map_err(|err| -> String {
-                        let err_msg = format!("Error parsing provided synth definition: {:?}", err);
-                        error!("{}", err_msg);
-                        err_msg
-                    })?;
-                Ok(SynthVoicePresetEntry {
-                    id: id_,
-                    title: title_,
-                    description: description_,
-                    body: preset,
-                    user_id: user_id_,
-                })
-            },
-        )
-        .collect::<Result<Vec<_>, String>>()?;
+    let all_presets: Vec<SynthVoicePresetEntry> =
+        all_presets
+            .into_iter()
+            .map(
+                |(
+                    id_,
+                    title_,
+                    description_,
+                    body_,
+                    user_id_,
+                    user_name,
+                    is_public_,
+                    forked_from_,
+                ): (
+                    i64,
+                    String,
+                    String,
+                    String,
+                    Option<i64>,
+                    Option<String>,
+                    bool,
+                    Option<i64>,
+                )| {
+                    let preset: VoiceDefinition =
+                        serde_json::from_str(&body_).map_err(|err| -> String {
+                            let err_msg =
+                                format!("Error parsing provided synth definition: {:?}", err);
+                            error!("{}", err_msg);
+                            err_msg
+                        })?;
+                    Ok(SynthVoicePresetEntry {
+                        id: id_,
+                        title: title_,
+                        description: description_,
+                        body: preset,
+                        user_id: user_id_,
+                        user_name,
+                        is_public: is_public_,
+                        forked_from: forked_from_,
+                    })
+                },
+            )
+            .collect::<Result<Vec<_>, String>>()?;
     Ok(Json(all_presets))
 }
 
@@ -418,22 +514,29 @@ pub async fn get_synth_voice_presets(
 pub async fn create_synth_voice_preset(
     conn: WebSynthDbConn,
     voice_preset: Json<UserProvidedNewSynthVoicePreset>,
-    login_token: MaybeLoginToken,
-) -> Result<(), String> {
+    user: MaybeAuthenticatedUser,
+    _rate_limit: SaveRateLimit,
+) -> Result<(), Custom<String>> {
     use crate::schema::voice_presets::dsl::*;
 
-    let user_id_ = get_logged_in_user_id(&conn, login_token).await;
+    validate_metadata(&voice_preset.title, &voice_preset.description, &[]).map_err(bad_request)?;
+    voice_preset.body.validate().map_err(bad_request)?;
+
+    let user_id_ = user.0.map(|user| user.id);
 
-    let body_: String = serde_json::to_string(&voice_preset.0.body).map_err(|err| -> String {
+    let body_: String = serde_json::to_string(&voice_preset.0.body).map_err(|err| {
         let err_msg = format!("Error parsing provided synth preset body: {:?}", err);
         error!("{}", err_msg);
-        err_msg
+        internal_error(err_msg)
     })?;
+    check_serialized_size("body", &body_, MAX_PRESET_BODY_BYTES).map_err(bad_request)?;
     let entry = NewSynthVoicePresetEntry {
         title: voice_preset.0.title,
         description: voice_preset.0.description,
         body: body_,
+        is_public: voice_preset.0.is_public || user_id_.is_none(),
         user_id: user_id_,
+        forked_from: None,
     };
 
     conn.run(move |conn| {
@@ -442,9 +545,9 @@ pub async fn create_synth_voice_preset(
             .execute(conn)
     })
     .await
-    .map_err(|err| -> String {
+    .map_err(|err| {
         error!("Error inserting synth preset into database: {:?}", err);
-        "Error inserting synth preset into database".into()
+        internal_error("Error inserting synth preset into database".into())
     })
     .map(drop)
 }
@@ -11,8 +11,9 @@ use crate::{
     db_util::{
         login::validate_login_token,
         private_sample_libraries::get_private_sample_libraries_for_user,
+        user_samples::get_user_samples_for_user,
     },
-    models::{remote_samples::RemoteSample, user::MaybeLoginToken},
+    models::{remote_samples::RemoteSample, user::MaybeLoginToken, user_samples::UserSample},
     WebSynthDbConn,
 };
 
@@ -96,6 +97,16 @@ pub async fn list_remote_samples(
             String::from("DB error")
         })?;
 
+    if let Some(user_id) = user_id {
+        let user_samples = get_user_samples_for_user(&conn, user_id)
+            .await
+            .map_err(|err| {
+                error!("Error querying DB for user samples: {:?}", err);
+                String::from("DB error")
+            })?;
+        all_remote_samples.extend(user_samples.iter().map(UserSample::to_remote_sample));
+    }
+
     if let Some(private_samples_rx) = private_samples_rx {
         let private_samples = private_samples_rx.await.unwrap_or_else(|err| {
             error!("Error getting private samples: {:?}", err);
@@ -149,6 +160,45 @@ fn encode_to_wav(sample_data: Vec<u8>) -> Result<Vec<u8>, String> {
     Ok(encoded_buf)
 }
 
+/// Returns the public URL that a sample uploaded with `upload_sample_to_bucket` is served from
+pub(super) fn build_sample_bucket_url(sample_id: &str) -> String {
+    format!("{}{}", REMOTE_SAMPLES_BUCKET_URL, sample_id)
+}
+
+/// Stores the sample in the remote samples bucket under `sample_id`
+pub(super) async fn upload_sample_to_bucket(
+    sample_id: &str,
+    sample_data: Vec<u8>,
+) -> Result<(), String> {
+    // Forward the request to the Faust server / generic go server that we forced into doing
+    // other things as well
+    let res = reqwest::Client::new()
+        .post(&format!(
+            "{}/remote_samples/{}?token={}",
+            FAUST_SERVER_URL,
+            sample_id,
+            crate::conf::CONF.auth_token
+        ))
+        .body(sample_data)
+        .send()
+        .await;
+    let res = match res {
+        Ok(res) => res,
+        Err(err) => {
+            error!("Error querying go server RPC to store sample: {:?}", err);
+            return Err(String::from("Error saving sample"));
+        },
+    };
+    if res.status() != 200 {
+        error!(
+            "Error querying go server RPC to store sample: {:?}",
+            res.text().await
+        );
+        return Err(String::from("Error saving sample"));
+    }
+    Ok(())
+}
+
 #[post("/remote_samples?<name>", data = "<sample_data>")]
 pub async fn store_remote_sample(
     mut name: String,
@@ -190,7 +240,7 @@ pub async fn store_remote_sample(
     // Check if we already have this sample stored.  If so, we don't need to do anything since the
     // hash guarentees it's the exact same one.
     let sample_id_c = sample_id.clone();
-    let sample_url = format!("{}{}", REMOTE_SAMPLES_BUCKET_URL, sample_id);
+    let sample_url = build_sample_bucket_url(&sample_id);
     let remote_sample = RemoteSample {
         name: name.clone(),
         id: sample_id,
@@ -218,32 +268,7 @@ pub async fn store_remote_sample(
     }
 
     if existing_samples_for_id.is_empty() {
-        // Forward the request to the Faust server / generic go server that we forced into doing
-        // other things as well
-        let res = reqwest::Client::new()
-            .post(&format!(
-                "{}/remote_samples/{}?token={}",
-                FAUST_SERVER_URL,
-                remote_sample.id,
-                crate::conf::CONF.auth_token
-            ))
-            .body(encoded_sample_data)
-            .send()
-            .await;
-        let res = match res {
-            Ok(res) => res,
-            Err(err) => {
-                error!("Error querying go server RPC to store sample: {:?}", err);
-                return Err(String::from("Error saving sample"));
-            },
-        };
-        if res.status() != 200 {
-            error!(
-                "Error querying go server RPC to store sample: {:?}",
-                res.text().await
-            );
-            return Err(String::from("Error saving sample"));
-        }
+        upload_sample_to_bucket(&remote_sample.id, encoded_sample_data).await?;
     }
 
     let remote_sample_c = remote_sample.clone();
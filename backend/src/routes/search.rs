@@ -0,0 +1,306 @@
+use diesel::prelude::*;
+use rocket::serde::json::Json;
+
+use crate::{
+    db_util::{
+        login::get_logged_in_user_id,
+        search::{get_ids_with_all_tags, run_search, SearchQuery, SearchRow},
+    },
+    models::{
+        search::{SearchParams, SearchResults, SearchSort},
+        tags::EntityIdTag,
+        user::MaybeLoginToken,
+    },
+    WebSynthDbConn,
+};
+
+#[get("/search/compositions?<params..>")]
+pub async fn search_compositions(
+    conn: WebSynthDbConn,
+    params: SearchParams,
+) -> Result<Json<SearchResults>, String> {
+    use crate::schema::{compositions, compositions_tags, tags, users};
+
+    run_search(
+        conn,
+        SearchQuery::new(params, None),
+        "compositions",
+        |conn, query| {
+            let matching_ids = if query.tags.is_empty() {
+                None
+            } else {
+                let entity_tags: Vec<EntityIdTag> = compositions_tags::table
+                    .inner_join(tags::table)
+                    .filter(tags::dsl::tag.eq_any(query.tags.clone()))
+                    .select((compositions_tags::dsl::composition_id, tags::dsl::tag))
+                    .load(conn)?;
+                Some(get_ids_with_all_tags(entity_tags, &query.tags))
+            };
+            let build_query = || {
+                let mut boxed_query = compositions::table
+                    .left_join(
+                        users::table.on(compositions::dsl::user_id.eq(users::dsl::id.nullable())),
+                    )
+                    .into_boxed();
+                for pattern in &query.like_patterns {
+                    boxed_query = boxed_query.filter(
+                        compositions::dsl::title
+                            .like(pattern.clone())
+                            .or(compositions::dsl::description.like(pattern.clone())),
+                    );
+                }
+                if let Some(matching_ids) = &matching_ids {
+                    boxed_query =
+                        boxed_query.filter(compositions::dsl::id.eq_any(matching_ids.clone()));
+                }
+                boxed_query
+            };
+
+            let total: i64 = build_query().count().get_result(conn)?;
+            let page_query = build_query().select((
+                compositions::dsl::id,
+                compositions::dsl::title,
+                compositions::dsl::description,
+                compositions::dsl::user_id,
+                users::dsl::username.nullable(),
+                compositions::dsl::use_count,
+            ));
+            let page_query = match query.sort {
+                SearchSort::Newest => page_query.order(compositions::dsl::id.desc()),
+                SearchSort::MostUsed => page_query.order((
+                    compositions::dsl::use_count.desc(),
+                    compositions::dsl::id.desc(),
+                )),
+            };
+            let rows: Vec<SearchRow> = page_query
+                .limit(query.page_size)
+                .offset(query.offset())
+                .load(conn)?;
+
+            let page_ids: Vec<i64> = rows.iter().map(|row| row.0).collect();
+            let page_tags: Vec<EntityIdTag> = compositions_tags::table
+                .inner_join(tags::table)
+                .filter(compositions_tags::dsl::composition_id.eq_any(page_ids))
+                .select((compositions_tags::dsl::composition_id, tags::dsl::tag))
+                .load(conn)?;
+            Ok((total, rows, page_tags))
+        },
+    )
+    .await
+}
+
+#[get("/search/midi_compositions?<params..>")]
+pub async fn search_midi_compositions(
+    conn: WebSynthDbConn,
+    params: SearchParams,
+) -> Result<Json<SearchResults>, String> {
+    use crate::schema::{midi_compositions, midi_compositions_tags, tags, users};
+
+    run_search(
+        conn,
+        SearchQuery::new(params, None),
+        "MIDI compositions",
+        |conn, query| {
+            let matching_ids = if query.tags.is_empty() {
+                None
+            } else {
+                let entity_tags: Vec<EntityIdTag> = midi_compositions_tags::table
+                    .inner_join(tags::table)
+                    .filter(tags::dsl::tag.eq_any(query.tags.clone()))
+                    .select((
+                        midi_compositions_tags::dsl::midi_composition_id,
+                        tags::dsl::tag,
+                    ))
+                    .load(conn)?;
+                Some(get_ids_with_all_tags(entity_tags, &query.tags))
+            };
+            let build_query = || {
+                let mut boxed_query = midi_compositions::table
+                    .left_join(users::table)
+                    .into_boxed();
+                for pattern in &query.like_patterns {
+                    boxed_query = boxed_query.filter(
+                        midi_compositions::dsl::name
+                            .like(pattern.clone())
+                            .or(midi_compositions::dsl::description.like(pattern.clone())),
+                    );
+                }
+                if let Some(matching_ids) = &matching_ids {
+                    boxed_query =
+                        boxed_query.filter(midi_compositions::dsl::id.eq_any(matching_ids.clone()));
+                }
+                boxed_query
+            };
+
+            let total: i64 = build_query().count().get_result(conn)?;
+            let page_query = build_query().select((
+                midi_compositions::dsl::id,
+                midi_compositions::dsl::name,
+                midi_compositions::dsl::description,
+                midi_compositions::dsl::user_id,
+                users::dsl::username.nullable(),
+                midi_compositions::dsl::use_count,
+            ));
+            let page_query = match query.sort {
+                SearchSort::Newest => page_query.order(midi_compositions::dsl::id.desc()),
+                SearchSort::MostUsed => page_query.order((
+                    midi_compositions::dsl::use_count.desc(),
+                    midi_compositions::dsl::id.desc(),
+                )),
+            };
+            let rows: Vec<SearchRow> = page_query
+                .limit(query.page_size)
+                .offset(query.offset())
+                .load(conn)?;
+
+            let page_ids: Vec<i64> = rows.iter().map(|row| row.0).collect();
+            let page_tags: Vec<EntityIdTag> = midi_compositions_tags::table
+                .inner_join(tags::table)
+                .filter(midi_compositions_tags::dsl::midi_composition_id.eq_any(page_ids))
+                .select((
+                    midi_compositions_tags::dsl::midi_composition_id,
+                    tags::dsl::tag,
+                ))
+                .load(conn)?;
+            Ok((total, rows, page_tags))
+        },
+    )
+    .await
+}
+
+/// Only public presets and the logged-in user's own private presets are returned.  Synth presets
+/// can't be tagged, so nothing matches if any tags are provided.
+#[get("/search/synth_presets?<params..>")]
+pub async fn search_synth_presets(
+    conn: WebSynthDbConn,
+    params: SearchParams,
+    login_token: MaybeLoginToken,
+) -> Result<Json<SearchResults>, String> {
+    use crate::schema::{synth_presets, users};
+
+    let logged_in_user_id = get_logged_in_user_id(&conn, login_token).await;
+
+    run_search(
+        conn,
+        SearchQuery::new(params, logged_in_user_id),
+        "synth presets",
+        |conn, query| {
+            if !query.tags.is_empty() {
+                return Ok((0, Vec::new(), Vec::new()));
+            }
+
+            let build_query = || {
+                let mut boxed_query = synth_presets::table
+                    .left_join(users::table)
+                    .filter(
+                        synth_presets::dsl::is_public
+                            .eq(true)
+                            .or(synth_presets::dsl::user_id.eq(query.logged_in_user_id)),
+                    )
+                    .into_boxed();
+                for pattern in &query.like_patterns {
+                    boxed_query = boxed_query.filter(
+                        synth_presets::dsl::title
+                            .like(pattern.clone())
+                            .or(synth_presets::dsl::description.like(pattern.clone())),
+                    );
+                }
+                boxed_query
+            };
+
+            let total: i64 = build_query().count().get_result(conn)?;
+            let page_query = build_query().select((
+                synth_presets::dsl::id,
+                synth_presets::dsl::title,
+                synth_presets::dsl::description,
+                synth_presets::dsl::user_id,
+                users::dsl::username.nullable(),
+                synth_presets::dsl::use_count,
+            ));
+            let page_query = match query.sort {
+                SearchSort::Newest => page_query.order(synth_presets::dsl::id.desc()),
+                SearchSort::MostUsed => page_query.order((
+                    synth_presets::dsl::use_count.desc(),
+                    synth_presets::dsl::id.desc(),
+                )),
+            };
+            let rows: Vec<SearchRow> = page_query
+                .limit(query.page_size)
+                .offset(query.offset())
+                .load(conn)?;
+            Ok((total, rows, Vec::new()))
+        },
+    )
+    .await
+}
+
+#[get("/search/looper_presets?<params..>")]
+pub async fn search_looper_presets(
+    conn: WebSynthDbConn,
+    params: SearchParams,
+) -> Result<Json<SearchResults>, String> {
+    use crate::schema::{looper_presets, looper_presets_tags, tags, users};
+
+    run_search(
+        conn,
+        SearchQuery::new(params, None),
+        "looper presets",
+        |conn, query| {
+            let matching_ids = if query.tags.is_empty() {
+                None
+            } else {
+                let entity_tags: Vec<EntityIdTag> = looper_presets_tags::table
+                    .inner_join(tags::table)
+                    .filter(tags::dsl::tag.eq_any(query.tags.clone()))
+                    .select((looper_presets_tags::dsl::looper_preset_id, tags::dsl::tag))
+                    .load(conn)?;
+                Some(get_ids_with_all_tags(entity_tags, &query.tags))
+            };
+            let build_query = || {
+                let mut boxed_query = looper_presets::table.left_join(users::table).into_boxed();
+                for pattern in &query.like_patterns {
+                    boxed_query = boxed_query.filter(
+                        looper_presets::dsl::name
+                            .like(pattern.clone())
+                            .or(looper_presets::dsl::description.like(pattern.clone())),
+                    );
+                }
+                if let Some(matching_ids) = &matching_ids {
+                    boxed_query =
+                        boxed_query.filter(looper_presets::dsl::id.eq_any(matching_ids.clone()));
+                }
+                boxed_query
+            };
+
+            let total: i64 = build_query().count().get_result(conn)?;
+            let page_query = build_query().select((
+                looper_presets::dsl::id,
+                looper_presets::dsl::name,
+                looper_presets::dsl::description,
+                looper_presets::dsl::user_id,
+                users::dsl::username.nullable(),
+                looper_presets::dsl::use_count,
+            ));
+            let page_query = match query.sort {
+                SearchSort::Newest => page_query.order(looper_presets::dsl::id.desc()),
+                SearchSort::MostUsed => page_query.order((
+                    looper_presets::dsl::use_count.desc(),
+                    looper_presets::dsl::id.desc(),
+                )),
+            };
+            let rows: Vec<SearchRow> = page_query
+                .limit(query.page_size)
+                .offset(query.offset())
+                .load(conn)?;
+
+            let page_ids: Vec<i64> = rows.iter().map(|row| row.0).collect();
+            let page_tags: Vec<EntityIdTag> = looper_presets_tags::table
+                .inner_join(tags::table)
+                .filter(looper_presets_tags::dsl::looper_preset_id.eq_any(page_ids))
+                .select((looper_presets_tags::dsl::looper_preset_id, tags::dsl::tag))
+                .load(conn)?;
+            Ok((total, rows, page_tags))
+        },
+    )
+    .await
+}
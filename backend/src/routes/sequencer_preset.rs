@@ -0,0 +1,202 @@
+use diesel::{prelude::*, QueryResult};
+use itertools::Itertools;
+use rocket::{response::status::Custom, serde::json::Json};
+
+use super::{bad_request, internal_error};
+use crate::{
+    db_util::{build_tags_with_counts, get_and_create_tag_ids, last_insert_id},
+    models::{
+        sequencer_preset::{
+            NewSequencerPreset, NewSequencerPresetTag, SaveSequencerPresetRequest, SequencerPreset,
+            SequencerPresetDescriptor, SerializedSequencerState,
+        },
+        tags::{EntityIdTag, TagCount},
+        user::MaybeAuthenticatedUser,
+        validation::{check_serialized_size, validate_metadata, MAX_SERIALIZED_STATE_BYTES},
+    },
+    rate_limit::SaveRateLimit,
+    WebSynthDbConn,
+};
+
+#[get("/sequencer_presets")]
+pub async fn get_sequencer_presets(
+    conn: WebSynthDbConn,
+) -> Result<Json<Vec<SequencerPresetDescriptor>>, String> {
+    let (sequencer_presets, preset_tags) = conn
+        .run(|conn| -> QueryResult<(_, _)> {
+            use crate::schema::{sequencer_presets, sequencer_presets_tags, tags, users};
+
+            let presets = sequencer_presets::table
+                .left_join(users::table)
+                .select((
+                    sequencer_presets::dsl::id,
+                    sequencer_presets::dsl::name,
+                    sequencer_presets::dsl::description,
+                    users::dsl::id.nullable(),
+                    users::dsl::username.nullable(),
+                ))
+                .load::<(i64, String, String, Option<i64>, Option<String>)>(conn)?;
+
+            let preset_tags: Vec<EntityIdTag> = sequencer_presets_tags::table
+                .inner_join(tags::table)
+                .select((
+                    sequencer_presets_tags::dsl::sequencer_preset_id,
+                    tags::dsl::tag,
+                ))
+                .load(conn)?;
+
+            Ok((presets, preset_tags))
+        })
+        .await
+        .map_err(|err| {
+            error!("DB error loading sequencer presets from DB: {}", err);
+            String::from("DB error loading sequencer presets from DB")
+        })?;
+
+    let mut tags_by_preset_id = preset_tags
+        .into_iter()
+        .into_group_map_by(|tag| tag.entity_id);
+
+    let sequencer_presets = sequencer_presets
+        .into_iter()
+        .map(|(id, name, description, user_id, user_name)| {
+            let tags = tags_by_preset_id
+                .remove(&id)
+                .unwrap_or_default()
+                .iter()
+                .map(|tag| tag.tag.clone())
+                .collect_vec();
+
+            SequencerPresetDescriptor {
+                id,
+                name,
+                description,
+                tags,
+                user_id,
+                user_name,
+            }
+        })
+        .collect_vec();
+
+    Ok(Json(sequencer_presets))
+}
+
+#[get("/sequencer_preset/<preset_id>")]
+pub async fn get_sequencer_preset_by_id(
+    conn: WebSynthDbConn,
+    preset_id: i64,
+) -> Result<Option<Json<SerializedSequencerState>>, String> {
+    let preset = conn
+        .run(move |conn| {
+            use crate::schema::sequencer_presets::dsl::*;
+
+            sequencer_presets
+                .filter(id.eq(preset_id))
+                .first::<SequencerPreset>(conn)
+                .optional()
+        })
+        .await
+        .map_err(|err| {
+            error!("DB error loading sequencer preset from DB: {}", err);
+            String::from("DB error loading sequencer preset from DB")
+        })?;
+
+    let preset = match preset {
+        Some(preset) =>
+            serde_json::from_str::<SerializedSequencerState>(&preset.serialized_sequencer_state)
+                .map_err(|err| {
+                    error!("Error deserializing sequencer preset: {}", err);
+                    String::from("Error deserializing sequencer preset")
+                })?,
+        None => return Ok(None),
+    };
+
+    Ok(Some(Json(preset)))
+}
+
+#[post("/sequencer_preset", data = "<sequencer_preset>")]
+pub async fn create_sequencer_preset(
+    conn: WebSynthDbConn,
+    sequencer_preset: Json<SaveSequencerPresetRequest>,
+    user: MaybeAuthenticatedUser,
+    _rate_limit: SaveRateLimit,
+) -> Result<Json<i64>, Custom<String>> {
+    let SaveSequencerPresetRequest {
+        name,
+        description,
+        tags,
+        serialized_sequencer_state,
+    } = sequencer_preset.into_inner();
+    validate_metadata(&name, &description, &tags).map_err(bad_request)?;
+    let serialized_sequencer_state = serde_json::to_string(&serialized_sequencer_state).unwrap();
+    check_serialized_size(
+        "serializedSequencerState",
+        &serialized_sequencer_state,
+        MAX_SERIALIZED_STATE_BYTES,
+    )
+    .map_err(bad_request)?;
+
+    let user_id = user.0.map(|user| user.id);
+
+    let created_preset_id = conn
+        .run(move |conn| -> QueryResult<i64> {
+            use crate::schema::{sequencer_presets, sequencer_presets_tags};
+
+            let conn = &*conn;
+            conn.transaction(move || {
+                diesel::insert_into(sequencer_presets::table)
+                    .values(NewSequencerPreset {
+                        name,
+                        description,
+                        serialized_sequencer_state,
+                        user_id,
+                    })
+                    .execute(conn)?;
+                let created_preset_id = diesel::select(last_insert_id).first(conn)?;
+
+                // Insert tags
+                let tag_count = tags.len();
+                let tag_ids = get_and_create_tag_ids(conn, tags)?;
+                assert_eq!(tag_count, tag_ids.len());
+
+                let new_tags: Vec<NewSequencerPresetTag> = tag_ids
+                    .into_iter()
+                    .map(|tag_id| NewSequencerPresetTag {
+                        sequencer_preset_id: created_preset_id,
+                        tag_id,
+                    })
+                    .collect();
+
+                diesel::insert_into(sequencer_presets_tags::table)
+                    .values(new_tags)
+                    .execute(conn)?;
+
+                Ok(created_preset_id)
+            })
+        })
+        .await
+        .map_err(|err| {
+            error!("DB error inserting sequencer preset into DB: {}", err);
+            internal_error(String::from("DB error inserting sequencer preset into DB"))
+        })?;
+
+    Ok(Json(created_preset_id))
+}
+
+#[get("/sequencer_preset_tags")]
+pub async fn get_sequencer_preset_tags(
+    conn: WebSynthDbConn,
+) -> Result<Json<Vec<TagCount>>, String> {
+    use crate::schema::{sequencer_presets_tags, tags};
+
+    build_tags_with_counts(conn, move |conn| -> QueryResult<Vec<_>> {
+        sequencer_presets_tags::table
+            .inner_join(tags::table)
+            .select((
+                sequencer_presets_tags::dsl::sequencer_preset_id,
+                tags::dsl::tag,
+            ))
+            .load(conn)
+    })
+    .await
+}
@@ -0,0 +1,273 @@
+//! Publishing, unpublishing, and forking of synth and voice presets.  Private presets are only
+//! visible to their owner; forking copies a visible preset into a new one owned by the current user
+//! that keeps track of where it came from.
+
+use diesel::prelude::*;
+use rocket::{http::Status, response::status::Custom, serde::json::Json};
+
+use crate::{
+    db_util::last_insert_id,
+    models::{
+        synth_preset::{NewSynthPresetEntry, NewSynthVoicePresetEntry},
+        user::AuthenticatedUser,
+    },
+    WebSynthDbConn,
+};
+
+/// Checks that the preset exists and is owned by `user_id`.  `owner` is `None` if the preset wasn't
+/// found.
+fn check_preset_owner(owner: Option<Option<i64>>, user_id: i64) -> Result<(), Custom<String>> {
+    match owner {
+        None => Err(Custom(Status::NotFound, String::from("Preset not found"))),
+        Some(owner) if owner != Some(user_id) => Err(Custom(
+            Status::Forbidden,
+            String::from("Only the owner of a preset can change its visibility"),
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+fn check_preset_visible(
+    preset: Option<(Option<i64>, bool)>,
+    user_id: i64,
+) -> Result<(), Custom<String>> {
+    match preset {
+        Some((owner, is_public)) if is_public || owner == Some(user_id) => Ok(()),
+        // Private presets are reported as missing so that their existence isn't leaked
+        _ => Err(Custom(Status::NotFound, String::from("Preset not found"))),
+    }
+}
+
+async fn set_synth_preset_visibility(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    preset_id: i64,
+    is_public_: bool,
+) -> Result<(), Custom<String>> {
+    use crate::schema::synth_presets::dsl::*;
+
+    let logged_in_user_id = user.id;
+
+    let owner: Option<Option<i64>> = conn
+        .run(move |conn| {
+            synth_presets
+                .find(preset_id)
+                .select(user_id)
+                .first(conn)
+                .optional()
+        })
+        .await
+        .map_err(|err| {
+            error!("Error loading synth preset owner: {:?}", err);
+            Custom(Status::InternalServerError, String::from("DB error"))
+        })?;
+    check_preset_owner(owner, logged_in_user_id)?;
+
+    conn.run(move |conn| {
+        diesel::update(synth_presets.find(preset_id))
+            .set(is_public.eq(is_public_))
+            .execute(conn)
+    })
+    .await
+    .map_err(|err| {
+        error!("Error updating synth preset visibility: {:?}", err);
+        Custom(Status::InternalServerError, String::from("DB error"))
+    })
+    .map(drop)
+}
+
+#[post("/synth_presets/<preset_id>/publish")]
+pub async fn publish_synth_preset(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    preset_id: i64,
+) -> Result<(), Custom<String>> {
+    set_synth_preset_visibility(conn, user, preset_id, true).await
+}
+
+#[post("/synth_presets/<preset_id>/unpublish")]
+pub async fn unpublish_synth_preset(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    preset_id: i64,
+) -> Result<(), Custom<String>> {
+    set_synth_preset_visibility(conn, user, preset_id, false).await
+}
+
+/// Copies a synth preset into a new private preset owned by the current user, returning its id
+#[post("/synth_presets/<preset_id>/fork")]
+pub async fn fork_synth_preset(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    preset_id: i64,
+) -> Result<Json<i64>, Custom<String>> {
+    use crate::schema::synth_presets::dsl::*;
+
+    let logged_in_user_id = user.id;
+
+    let preset: Option<(String, String, String, Option<i64>, bool)> = conn
+        .run(move |conn| {
+            synth_presets
+                .find(preset_id)
+                .select((title, description, body, user_id, is_public))
+                .first(conn)
+                .optional()
+        })
+        .await
+        .map_err(|err| {
+            error!("Error loading synth preset to fork: {:?}", err);
+            Custom(Status::InternalServerError, String::from("DB error"))
+        })?;
+    check_preset_visible(
+        preset
+            .as_ref()
+            .map(|(_, _, _, owner, is_public_)| (*owner, *is_public_)),
+        logged_in_user_id,
+    )?;
+    let (title_, description_, body_, ..) = preset.unwrap();
+
+    let entry = NewSynthPresetEntry {
+        title: title_,
+        description: description_,
+        body: body_,
+        user_id: Some(logged_in_user_id),
+        is_public: false,
+        forked_from: Some(preset_id),
+    };
+    let forked_preset_id = conn
+        .run(move |conn| -> QueryResult<i64> {
+            let conn = &*conn;
+            conn.transaction(move || {
+                diesel::insert_into(synth_presets)
+                    .values(&entry)
+                    .execute(conn)?;
+                let forked_preset_id = diesel::select(last_insert_id).first(conn)?;
+                diesel::update(synth_presets.find(preset_id))
+                    .set(use_count.eq(use_count + 1))
+                    .execute(conn)?;
+                Ok(forked_preset_id)
+            })
+        })
+        .await
+        .map_err(|err| {
+            error!("Error inserting forked synth preset: {:?}", err);
+            Custom(Status::InternalServerError, String::from("DB error"))
+        })?;
+
+    Ok(Json(forked_preset_id))
+}
+
+async fn set_synth_voice_preset_visibility(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    preset_id: i64,
+    is_public_: bool,
+) -> Result<(), Custom<String>> {
+    use crate::schema::voice_presets::dsl::*;
+
+    let logged_in_user_id = user.id;
+
+    let owner: Option<Option<i64>> = conn
+        .run(move |conn| {
+            voice_presets
+                .find(preset_id)
+                .select(user_id)
+                .first(conn)
+                .optional()
+        })
+        .await
+        .map_err(|err| {
+            error!("Error loading synth voice preset owner: {:?}", err);
+            Custom(Status::InternalServerError, String::from("DB error"))
+        })?;
+    check_preset_owner(owner, logged_in_user_id)?;
+
+    conn.run(move |conn| {
+        diesel::update(voice_presets.find(preset_id))
+            .set(is_public.eq(is_public_))
+            .execute(conn)
+    })
+    .await
+    .map_err(|err| {
+        error!("Error updating synth voice preset visibility: {:?}", err);
+        Custom(Status::InternalServerError, String::from("DB error"))
+    })
+    .map(drop)
+}
+
+#[post("/synth_voice_presets/<preset_id>/publish")]
+pub async fn publish_synth_voice_preset(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    preset_id: i64,
+) -> Result<(), Custom<String>> {
+    set_synth_voice_preset_visibility(conn, user, preset_id, true).await
+}
+
+#[post("/synth_voice_presets/<preset_id>/unpublish")]
+pub async fn unpublish_synth_voice_preset(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    preset_id: i64,
+) -> Result<(), Custom<String>> {
+    set_synth_voice_preset_visibility(conn, user, preset_id, false).await
+}
+
+/// Copies a voice preset into a new private preset owned by the current user, returning its id
+#[post("/synth_voice_presets/<preset_id>/fork")]
+pub async fn fork_synth_voice_preset(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    preset_id: i64,
+) -> Result<Json<i64>, Custom<String>> {
+    use crate::schema::voice_presets::dsl::*;
+
+    let logged_in_user_id = user.id;
+
+    let preset: Option<(String, String, String, Option<i64>, bool)> = conn
+        .run(move |conn| {
+            voice_presets
+                .find(preset_id)
+                .select((title, description, body, user_id, is_public))
+                .first(conn)
+                .optional()
+        })
+        .await
+        .map_err(|err| {
+            error!("Error loading synth voice preset to fork: {:?}", err);
+            Custom(Status::InternalServerError, String::from("DB error"))
+        })?;
+    check_preset_visible(
+        preset
+            .as_ref()
+            .map(|(_, _, _, owner, is_public_)| (*owner, *is_public_)),
+        logged_in_user_id,
+    )?;
+    let (title_, description_, body_, ..) = preset.unwrap();
+
+    let entry = NewSynthVoicePresetEntry {
+        title: title_,
+        description: description_,
+        body: body_,
+        user_id: Some(logged_in_user_id),
+        is_public: false,
+        forked_from: Some(preset_id),
+    };
+    let forked_preset_id = conn
+        .run(move |conn| -> QueryResult<i64> {
+            let conn = &*conn;
+            conn.transaction(move || {
+                diesel::insert_into(voice_presets)
+                    .values(&entry)
+                    .execute(conn)?;
+                diesel::select(last_insert_id).first(conn)
+            })
+        })
+        .await
+        .map_err(|err| {
+            error!("Error inserting forked synth voice preset: {:?}", err);
+            Custom(Status::InternalServerError, String::from("DB error"))
+        })?;
+
+    Ok(Json(forked_preset_id))
+}
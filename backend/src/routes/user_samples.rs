@@ -0,0 +1,235 @@
+//! Samples uploaded by logged-in users.  The files themselves live in the remote samples bucket,
+//! keyed by their checksum; these routes manage the per-user metadata that points at them.
+
+use std::path::Path;
+
+use diesel::prelude::*;
+use rocket::{
+    data::ToByteUnit,
+    http::{ContentType, Status},
+    response::status::Custom,
+    serde::json::Json,
+    Data,
+};
+use sha2::{Digest, Sha256};
+
+use super::remote_samples::{build_sample_bucket_url, upload_sample_to_bucket};
+use crate::{
+    db_util::{last_insert_id, user_samples::get_user_samples_for_user},
+    models::{
+        user::AuthenticatedUser,
+        user_samples::{NewUserSample, RenameUserSampleRequest, UserSample, UserSampleDescriptor},
+    },
+    WebSynthDbConn,
+};
+
+const MAX_USER_SAMPLE_SIZE_MEBIBYTES: usize = 20;
+const MAX_USER_SAMPLE_NAME_LENGTH: usize = 255;
+
+fn validate_sample_name(name: &str) -> Result<String, Custom<String>> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Custom(
+            Status::BadRequest,
+            String::from("Sample name must not be empty"),
+        ));
+    }
+    if name.chars().count() > MAX_USER_SAMPLE_NAME_LENGTH {
+        return Err(Custom(
+            Status::BadRequest,
+            format!(
+                "Sample name must be at most {} characters",
+                MAX_USER_SAMPLE_NAME_LENGTH
+            ),
+        ));
+    }
+    Ok(name.to_owned())
+}
+
+fn db_error(err: diesel::result::Error) -> Custom<String> {
+    error!("DB error managing user samples: {:?}", err);
+    Custom(Status::InternalServerError, String::from("DB error"))
+}
+
+#[get("/user_samples")]
+pub async fn list_user_samples(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<UserSampleDescriptor>>, Custom<String>> {
+    let samples = get_user_samples_for_user(&conn, user.id)
+        .await
+        .map_err(db_error)?;
+    Ok(Json(samples.into_iter().map(Into::into).collect()))
+}
+
+/// Stores the uploaded audio file as-is.  Uploading a file that the user has already uploaded
+/// returns the existing sample rather than creating a duplicate.
+#[post("/user_samples?<name>", data = "<sample_data>")]
+pub async fn upload_user_sample(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    name: String,
+    content_type: Option<&ContentType>,
+    sample_data: Data<'_>,
+) -> Result<Json<UserSampleDescriptor>, Custom<String>> {
+    use crate::schema::user_samples;
+
+    let name = validate_sample_name(&name)?;
+    let content_type = match content_type {
+        Some(content_type) if content_type.top() == "audio" => content_type.to_string(),
+        _ =>
+            return Err(Custom(
+                Status::UnsupportedMediaType,
+                String::from("Samples must be uploaded with an audio content type"),
+            )),
+    };
+
+    let sample_data = sample_data
+        .open(MAX_USER_SAMPLE_SIZE_MEBIBYTES.mebibytes())
+        .into_bytes()
+        .await
+        .map_err(|err| {
+            error!("Error reading sample data from request: {:?}", err);
+            Custom(
+                Status::BadRequest,
+                String::from("Error reading request body"),
+            )
+        })?;
+    if !sample_data.is_complete() {
+        return Err(Custom(
+            Status::PayloadTooLarge,
+            format!(
+                "Samples must be at most {} MiB",
+                MAX_USER_SAMPLE_SIZE_MEBIBYTES
+            ),
+        ));
+    }
+    let sample_data = sample_data.into_inner();
+
+    let mut hasher = Sha256::new();
+    hasher.update(&sample_data);
+    let checksum = hex::encode(hasher.finalize());
+
+    let user_id = user.id;
+    let checksum_c = checksum.clone();
+    let existing_sample: Option<UserSample> = conn
+        .run(move |conn| {
+            user_samples::table
+                .filter(user_samples::dsl::user_id.eq(user_id))
+                .filter(user_samples::dsl::checksum.eq(checksum_c))
+                .first(conn)
+                .optional()
+        })
+        .await
+        .map_err(db_error)?;
+    if let Some(existing_sample) = existing_sample {
+        return Ok(Json(existing_sample.into()));
+    }
+
+    let sample_id = format!(
+        "{}{}",
+        checksum,
+        Path::new(&name)
+            .extension()
+            .map(|s| format!(".{}", s.to_string_lossy()))
+            .unwrap_or_default()
+    );
+    let byte_size = sample_data.len() as i64;
+    upload_sample_to_bucket(&sample_id, sample_data)
+        .await
+        .map_err(|err| Custom(Status::InternalServerError, err))?;
+
+    let new_sample = NewUserSample {
+        user_id,
+        name,
+        byte_size,
+        checksum,
+        content_type,
+        storage_url: build_sample_bucket_url(&sample_id),
+    };
+    let saved_sample: UserSample = conn
+        .run(move |conn| {
+            let conn = &*conn;
+            conn.transaction(|| -> QueryResult<UserSample> {
+                diesel::insert_into(user_samples::table)
+                    .values(&new_sample)
+                    .execute(conn)?;
+                let saved_sample_id: i64 = diesel::select(last_insert_id).first(conn)?;
+                user_samples::table.find(saved_sample_id).first(conn)
+            })
+        })
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(saved_sample.into()))
+}
+
+#[post("/user_samples/<sample_id>/rename", data = "<rename_request>")]
+pub async fn rename_user_sample(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    sample_id: i64,
+    rename_request: Json<RenameUserSampleRequest>,
+) -> Result<(), Custom<String>> {
+    use crate::schema::user_samples;
+
+    let name = validate_sample_name(&rename_request.name)?;
+    let user_id = user.id;
+    let updated_rows = conn
+        .run(move |conn| {
+            diesel::update(
+                user_samples::table
+                    .filter(user_samples::dsl::id.eq(sample_id))
+                    .filter(user_samples::dsl::user_id.eq(user_id)),
+            )
+            .set(user_samples::dsl::name.eq(name))
+            .execute(conn)
+        })
+        .await
+        .map_err(db_error)?;
+
+    if updated_rows == 0 {
+        return Err(Custom(Status::NotFound, String::from("Sample not found")));
+    }
+    Ok(())
+}
+
+/// Only the metadata is deleted.  Stored files are keyed by their checksum and may be shared with
+/// other users' samples, so they're left in the bucket.
+#[post("/user_samples/<sample_id>/delete")]
+pub async fn delete_user_sample(
+    conn: WebSynthDbConn,
+    user: AuthenticatedUser,
+    sample_id: i64,
+) -> Result<(), Custom<String>> {
+    use crate::schema::user_samples;
+
+    let user_id = user.id;
+    let deleted_rows = conn
+        .run(move |conn| {
+            diesel::delete(
+                user_samples::table
+                    .filter(user_samples::dsl::id.eq(sample_id))
+                    .filter(user_samples::dsl::user_id.eq(user_id)),
+            )
+            .execute(conn)
+        })
+        .await
+        .map_err(db_error)?;
+
+    if deleted_rows == 0 {
+        return Err(Custom(Status::NotFound, String::from("Sample not found")));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_validate_sample_name() {
+    assert_eq!(
+        validate_sample_name("  kick.wav ").unwrap(),
+        String::from("kick.wav")
+    );
+    assert!(validate_sample_name("   ").is_err());
+    assert!(validate_sample_name(&"a".repeat(MAX_USER_SAMPLE_NAME_LENGTH)).is_ok());
+    assert!(validate_sample_name(&"a".repeat(MAX_USER_SAMPLE_NAME_LENGTH + 1)).is_err());
+}
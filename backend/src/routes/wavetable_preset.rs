@@ -1,20 +1,20 @@
 use diesel::{prelude::*, QueryResult};
 use itertools::Itertools;
-use rocket::serde::json::Json;
+use rocket::{response::status::Custom, serde::json::Json};
 
+use super::{bad_request, internal_error};
 use crate::{
-    db_util::{
-        build_tags_with_counts, get_and_create_tag_ids, last_insert_id,
-        login::get_logged_in_user_id,
-    },
+    db_util::{build_tags_with_counts, get_and_create_tag_ids, last_insert_id},
     models::{
         tags::{EntityIdTag, TagCount},
-        user::MaybeLoginToken,
+        user::MaybeAuthenticatedUser,
+        validation::{check_serialized_size, validate_metadata, MAX_SERIALIZED_STATE_BYTES},
         wavetable_preset::{
             NewWavetablePreset, NewWavetablePresetTag, SaveWavetablePresetRequest,
             SerializedWavetableInstState, WavetablePreset, WavetablePresetDescriptor,
         },
     },
+    rate_limit::SaveRateLimit,
     WebSynthDbConn,
 };
 
@@ -119,16 +119,26 @@ pub async fn get_wavetable_preset_by_id(
 pub async fn create_wavetable_preset(
     conn: WebSynthDbConn,
     wavetable_preset: Json<SaveWavetablePresetRequest>,
-    login_token: MaybeLoginToken,
-) -> Result<Json<i64>, String> {
+    user: MaybeAuthenticatedUser,
+    _rate_limit: SaveRateLimit,
+) -> Result<Json<i64>, Custom<String>> {
     let SaveWavetablePresetRequest {
         name,
         description,
         tags,
         serialized_wavetable_inst_state,
     } = wavetable_preset.into_inner();
-
-    let user_id = get_logged_in_user_id(&conn, login_token).await;
+    validate_metadata(&name, &description, &tags).map_err(bad_request)?;
+    let serialized_wavetable_inst_state =
+        serde_json::to_string(&serialized_wavetable_inst_state).unwrap();
+    check_serialized_size(
+        "serializedWavetableInstState",
+        &serialized_wavetable_inst_state,
+        MAX_SERIALIZED_STATE_BYTES,
+    )
+    .map_err(bad_request)?;
+
+    let user_id = user.0.map(|user| user.id);
 
     let created_preset_id = conn
         .run(move |conn| -> QueryResult<i64> {
@@ -140,10 +150,7 @@ pub async fn create_wavetable_preset(
                     .values(NewWavetablePreset {
                         name,
                         description,
-                        serialized_wavetable_inst_state: serde_json::to_string(
-                            &serialized_wavetable_inst_state,
-                        )
-                        .unwrap(),
+                        serialized_wavetable_inst_state,
                         user_id,
                     })
                     .execute(conn)?;
@@ -172,7 +179,7 @@ pub async fn create_wavetable_preset(
         .await
         .map_err(|err| {
             error!("DB error inserting wavetable preset into DB: {}", err);
-            String::from("DB error inserting wavetable preset into DB")
+            internal_error(String::from("DB error inserting wavetable preset into DB"))
         })?;
 
     Ok(Json(created_preset_id))
@@ -1,5 +1,14 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    compiled_effect_artifacts (code_hash, optimized) {
+        code_hash -> Varchar,
+        optimized -> Bool,
+        module_id -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     compositions (id) {
         id -> Bigint,
@@ -7,6 +16,19 @@ diesel::table! {
         description -> Text,
         content -> Longtext,
         user_id -> Nullable<Bigint>,
+        use_count -> Bigint,
+    }
+}
+
+diesel::table! {
+    composition_versions (id) {
+        id -> Bigint,
+        composition_id -> Bigint,
+        version -> Bigint,
+        title -> Text,
+        description -> Text,
+        content -> Longtext,
+        created_at -> Timestamp,
     }
 }
 
@@ -18,6 +40,17 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    effect_revisions (id) {
+        id -> Bigint,
+        effect_id -> Bigint,
+        revision -> Bigint,
+        code -> Text,
+        code_hash -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     effects (id) {
         id -> Bigint,
@@ -25,6 +58,7 @@ diesel::table! {
         description -> Text,
         code -> Text,
         user_id -> Nullable<Bigint>,
+        revision -> Bigint,
     }
 }
 
@@ -34,6 +68,7 @@ diesel::table! {
         user_id -> Bigint,
         token -> Text,
         created_at -> Timestamp,
+        expires_at -> Timestamp,
     }
 }
 
@@ -44,6 +79,7 @@ diesel::table! {
         description -> Text,
         serialized_looper_inst_state -> Longtext,
         user_id -> Nullable<Bigint>,
+        use_count -> Bigint,
     }
 }
 
@@ -55,6 +91,25 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    midi_composition_collaborators (midi_composition_id, user_id) {
+        midi_composition_id -> Bigint,
+        user_id -> Bigint,
+    }
+}
+
+diesel::table! {
+    midi_composition_versions (id) {
+        id -> Bigint,
+        midi_composition_id -> Bigint,
+        version -> Bigint,
+        name -> Text,
+        description -> Text,
+        composition_json -> Text,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     midi_compositions (id) {
         id -> Bigint,
@@ -62,6 +117,7 @@ diesel::table! {
         description -> Text,
         composition_json -> Text,
         user_id -> Nullable<Bigint>,
+        use_count -> Bigint,
     }
 }
 
@@ -94,6 +150,24 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    sequencer_presets (id) {
+        id -> Bigint,
+        name -> Text,
+        description -> Text,
+        serialized_sequencer_state -> Longtext,
+        user_id -> Nullable<Bigint>,
+    }
+}
+
+diesel::table! {
+    sequencer_presets_tags (id) {
+        id -> Bigint,
+        sequencer_preset_id -> Bigint,
+        tag_id -> Bigint,
+    }
+}
+
 diesel::table! {
     synth_presets (id) {
         id -> Bigint,
@@ -101,6 +175,9 @@ diesel::table! {
         description -> Text,
         body -> Text,
         user_id -> Nullable<Bigint>,
+        is_public -> Bool,
+        forked_from -> Nullable<Bigint>,
+        use_count -> Bigint,
     }
 }
 
@@ -111,6 +188,19 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    user_samples (id) {
+        id -> Bigint,
+        user_id -> Bigint,
+        name -> Text,
+        byte_size -> Bigint,
+        checksum -> Varchar,
+        content_type -> Varchar,
+        storage_url -> Text,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     users (id) {
         id -> Bigint,
@@ -127,6 +217,8 @@ diesel::table! {
         description -> Text,
         body -> Longtext,
         user_id -> Nullable<Bigint>,
+        is_public -> Bool,
+        forked_from -> Nullable<Bigint>,
     }
 }
 
@@ -148,37 +240,54 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(composition_versions -> compositions (composition_id));
 diesel::joinable!(compositions -> users (user_id));
 diesel::joinable!(compositions_tags -> compositions (composition_id));
 diesel::joinable!(compositions_tags -> tags (tag_id));
+diesel::joinable!(effect_revisions -> effects (effect_id));
 diesel::joinable!(effects -> users (user_id));
 diesel::joinable!(login_tokens -> users (user_id));
 diesel::joinable!(looper_presets -> users (user_id));
 diesel::joinable!(looper_presets_tags -> looper_presets (looper_preset_id));
 diesel::joinable!(looper_presets_tags -> tags (tag_id));
+diesel::joinable!(midi_composition_collaborators -> midi_compositions (midi_composition_id));
+diesel::joinable!(midi_composition_collaborators -> users (user_id));
+diesel::joinable!(midi_composition_versions -> midi_compositions (midi_composition_id));
 diesel::joinable!(midi_compositions -> users (user_id));
 diesel::joinable!(midi_compositions_tags -> midi_compositions (midi_composition_id));
 diesel::joinable!(midi_compositions_tags -> tags (tag_id));
 diesel::joinable!(private_sample_libraries -> users (user_id));
+diesel::joinable!(sequencer_presets -> users (user_id));
+diesel::joinable!(sequencer_presets_tags -> sequencer_presets (sequencer_preset_id));
+diesel::joinable!(sequencer_presets_tags -> tags (tag_id));
 diesel::joinable!(synth_presets -> users (user_id));
+diesel::joinable!(user_samples -> users (user_id));
 diesel::joinable!(voice_presets -> users (user_id));
 diesel::joinable!(wavetable_presets -> users (user_id));
 diesel::joinable!(wavetable_presets_tags -> tags (tag_id));
 diesel::joinable!(wavetable_presets_tags -> wavetable_presets (wavetable_preset_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    compiled_effect_artifacts,
+    composition_versions,
     compositions,
     compositions_tags,
+    effect_revisions,
     effects,
     login_tokens,
     looper_presets,
     looper_presets_tags,
+    midi_composition_collaborators,
+    midi_composition_versions,
     midi_compositions,
     midi_compositions_tags,
     private_sample_libraries,
     remote_sample_urls,
+    sequencer_presets,
+    sequencer_presets_tags,
     synth_presets,
     tags,
+    user_samples,
     users,
     voice_presets,
     wavetable_presets,
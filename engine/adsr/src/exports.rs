@@ -1,11 +1,17 @@
 use std::rc::Rc;
 
+use common::ffi::{self, ErrorCode};
+
 use crate::{managed_adsr::ManagedAdsr, Adsr, AdsrStep, RampFn, RENDERED_BUFFER_SIZE};
 
+#[cfg(target_arch = "wasm32")]
 extern "C" {
   fn log_err(msg: *const u8, len: usize);
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+use common::native::log_err;
+
 pub struct AdsrContext {
   pub adsrs: Vec<ManagedAdsr>,
   pub most_recent_gated_ix: usize,
@@ -44,7 +50,10 @@ fn decode_steps(encoded_steps: &[f32]) -> Vec<AdsrStep> {
           x if x == 2. => RampFn::Exponential {
             exponent: ramp_fn_param,
           },
-          _ => unreachable!("Invalid ramp fn type val"),
+          _ => {
+            logging::warn!("invalid ramp fn type {ramp_fn_type}; falling back to linear");
+            RampFn::Linear
+          },
         };
         AdsrStep {
           x: round_tiny_to_zero(x),
@@ -83,7 +92,10 @@ impl AdsrLengthMode {
     match val {
       0 => AdsrLengthMode::Ms,
       1 => AdsrLengthMode::Beats,
-      _ => unreachable!("Invalid AdsrLengthMode value"),
+      _ => {
+        logging::warn!("invalid length mode {val}; falling back to ms");
+        AdsrLengthMode::Ms
+      },
     }
   }
 }
@@ -163,12 +175,17 @@ pub unsafe extern "C" fn create_adsr_ctx(
 // pub unsafe extern "C" fn free_adsr_ctx(ctx: *mut AdsrContext) { drop(Box::from_raw(ctx)) }
 
 #[no_mangle]
-pub unsafe extern "C" fn update_adsr_steps(ctx: *mut AdsrContext) {
+pub unsafe extern "C" fn update_adsr_steps(ctx: *mut AdsrContext) -> ErrorCode {
+  let ctx = match ffi::handle(ctx, "update_adsr_steps") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
   let decoded_steps = decode_steps(ENCODED_ADSR_STEP_BUF.as_slice());
-  for adsr in &mut (*ctx).adsrs {
+  for adsr in &mut ctx.adsrs {
     adsr.adsr.set_steps(decoded_steps.clone());
   }
-  (*ctx).adsrs[0].render();
+  ctx.adsrs[0].render();
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -176,26 +193,63 @@ pub unsafe extern "C" fn update_adsr_len_ms(
   ctx: *mut AdsrContext,
   new_length: f32,
   new_raw_length_mode: u32,
-) {
+) -> ErrorCode {
+  let ctx = match ffi::handle(ctx, "update_adsr_len_ms") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("length_mode", new_raw_length_mode, 0, 1) {
+    return code;
+  }
+
   let new_length_mode = AdsrLengthMode::from_u32(new_raw_length_mode);
-  for adsr in &mut (*ctx).adsrs {
+  for adsr in &mut ctx.adsrs {
     adsr.set_length(new_length_mode, new_length);
   }
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn gate_adsr(ctx: *mut AdsrContext, index: usize, cur_beat: f32) {
-  (*ctx).adsrs[index].adsr.gate(cur_beat);
-  (*ctx).most_recent_gated_ix = index;
+pub unsafe extern "C" fn gate_adsr(
+  ctx: *mut AdsrContext,
+  index: usize,
+  cur_beat: f32,
+) -> ErrorCode {
+  let ctx = match ffi::handle(ctx, "gate_adsr") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  let adsr_count = ctx.adsrs.len();
+  let Some(adsr) = ctx.adsrs.get_mut(index) else {
+    return ffi::set_last_error(
+      ErrorCode::ParamOutOfRange,
+      &format!("tried to gate adsr {index} but there are only {adsr_count}"),
+    );
+  };
+  adsr.adsr.gate(cur_beat);
+  ctx.most_recent_gated_ix = index;
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn ungate_adsr(ctx: *mut AdsrContext, index: usize) {
-  (*ctx).adsrs[index].adsr.ungate()
+pub unsafe extern "C" fn ungate_adsr(ctx: *mut AdsrContext, index: usize) -> ErrorCode {
+  let ctx = match ffi::handle(ctx, "ungate_adsr") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  let adsr_count = ctx.adsrs.len();
+  let Some(adsr) = ctx.adsrs.get_mut(index) else {
+    return ffi::set_last_error(
+      ErrorCode::ParamOutOfRange,
+      &format!("tried to ungate adsr {index} but there are only {adsr_count}"),
+    );
+  };
+  adsr.adsr.ungate();
+  ErrorCode::Ok
 }
 
-/// Updates all ADSRs, rendering them to their respective output buffers.  Returns the current phase
-/// of the most recent gated ADSR.
+/// Updates all ADSRs, rendering `frame_size` samples to their respective output buffers.  Returns
+/// the current phase of the most recent gated ADSR.
 #[no_mangle]
 pub unsafe extern "C" fn process_adsr(
   ctx: *mut AdsrContext,
@@ -203,10 +257,12 @@ pub unsafe extern "C" fn process_adsr(
   output_range_max: f32,
   cur_bpm: f32,
   cur_beat: f32,
+  frame_size: usize,
 ) -> f32 {
   let shift = output_range_min;
   let scale = output_range_max - output_range_min;
   for adsr in &mut (*ctx).adsrs {
+    adsr.adsr.set_frame_size(frame_size);
     adsr.render_frame(scale, shift, cur_bpm, cur_beat);
   }
 
@@ -214,31 +270,57 @@ pub unsafe extern "C" fn process_adsr(
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn adsr_set_loop_point(ctx: *mut AdsrContext, new_loop_point: f32) {
-  for adsr in &mut (*ctx).adsrs {
+pub unsafe extern "C" fn adsr_set_loop_point(
+  ctx: *mut AdsrContext,
+  new_loop_point: f32,
+) -> ErrorCode {
+  let ctx = match ffi::handle(ctx, "adsr_set_loop_point") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("loop_point", new_loop_point, f32::NEG_INFINITY, 1.) {
+    return code;
+  }
+
+  for adsr in &mut ctx.adsrs {
     adsr.adsr.set_loop_point(if new_loop_point < 0. {
       None
     } else {
       Some(new_loop_point)
     });
   }
+  ErrorCode::Ok
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn adsr_set_release_start_phase(
   ctx: *mut AdsrContext,
   new_release_start_phase: f32,
-) {
-  for adsr in &mut (*ctx).adsrs {
+) -> ErrorCode {
+  let ctx = match ffi::handle(ctx, "adsr_set_release_start_phase") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("release_start_phase", new_release_start_phase, 0., 1.) {
+    return code;
+  }
+
+  for adsr in &mut ctx.adsrs {
     adsr.adsr.set_release_start_phase(new_release_start_phase);
   }
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn adsr_set_log_scale(ctx: *mut AdsrContext, log_scale: bool) {
-  for adsr in &mut (*ctx).adsrs {
+pub unsafe extern "C" fn adsr_set_log_scale(ctx: *mut AdsrContext, log_scale: bool) -> ErrorCode {
+  let ctx = match ffi::handle(ctx, "adsr_set_log_scale") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  for adsr in &mut ctx.adsrs {
     adsr.adsr.log_scale = log_scale;
   }
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -262,14 +344,19 @@ pub unsafe extern "C" fn adsr_set_frozen_output_value(
   new_frozen_output_value: f32,
   output_range_min: f32,
   output_range_max: f32,
-) {
+) -> ErrorCode {
+  let ctx = match ffi::handle(ctx, "adsr_set_frozen_output_value") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
   let scale = output_range_max - output_range_min;
   let shift = output_range_min;
-  for adsr in &mut (*ctx).adsrs {
+  for adsr in &mut ctx.adsrs {
     adsr
       .adsr
       .set_frozen_output_value(new_frozen_output_value, scale, shift);
   }
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -278,12 +365,17 @@ pub unsafe extern "C" fn adsr_set_frozen_output_value_from_phase(
   new_frozen_output_phase: f32,
   output_range_min: f32,
   output_range_max: f32,
-) {
+) -> ErrorCode {
+  let ctx = match ffi::handle(ctx, "adsr_set_frozen_output_value_from_phase") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
   let scale = output_range_max - output_range_min;
   let shift = output_range_min;
-  for adsr in &mut (*ctx).adsrs {
+  for adsr in &mut ctx.adsrs {
     adsr
       .adsr
       .set_frozen_output_value_from_phase(new_frozen_output_phase, scale, shift);
   }
+  ErrorCode::Ok
 }
@@ -2,7 +2,7 @@
 
 use std::rc::Rc;
 
-use dsp::mk_linear_to_log;
+use dsp::{mk_linear_to_log, FRAME_SIZE, MAX_FRAME_SIZE};
 
 #[cfg(feature = "exports")]
 pub mod exports;
@@ -10,14 +10,21 @@ pub mod managed_adsr;
 #[cfg(test)]
 mod tests;
 
+#[cfg(target_arch = "wasm32")]
 extern "C" {
   pub fn debug1(v1: f32, v2: f32, v3: f32);
 }
 
-/// Samples per second
-const SAMPLE_RATE: usize = 44_100;
-pub const RENDERED_BUFFER_SIZE: usize = SAMPLE_RATE;
-const FRAME_SIZE: usize = 128;
+/// Does nothing outside of the browser so that debug calls left in the render path stay cheap.
+///
+/// # Safety
+///
+/// Always safe to call; it's only `unsafe` to match the signature of the Wasm import.
+#[cfg(not(target_arch = "wasm32"))]
+pub unsafe fn debug1(_v1: f32, _v2: f32, _v3: f32) {}
+
+/// Resolution of the rendered envelope lookup table.  This is independent of the sample rate.
+pub const RENDERED_BUFFER_SIZE: usize = 44_100;
 
 #[derive(Clone, Copy)]
 pub enum RampFn {
@@ -93,6 +100,14 @@ pub enum GateStatus {
   Done,
 }
 
+/// A gate or ungate scheduled partway through the next frame by `Adsr::gate_at` or
+/// `Adsr::ungate_at`
+#[derive(Clone, Copy, Debug)]
+enum GateTransition {
+  Gate { cur_beat: f32 },
+  Ungate,
+}
+
 /// Method that we use if releasing the ADSR before we reach the release point
 #[derive(Clone, PartialEq)]
 pub enum EarlyReleaseStrategy {
@@ -142,7 +157,7 @@ impl Default for EarlyReleaseConfig {
   fn default() -> Self {
     EarlyReleaseConfig {
       strategy: EarlyReleaseStrategy::LinearMix,
-      len_samples: 2_640,
+      len_samples: dsp::ms_to_samples(60.) as usize,
     }
   }
 }
@@ -162,7 +177,9 @@ pub struct Adsr {
   /// avoid having to compute ramp points every sample
   rendered: Rc<[f32; RENDERED_BUFFER_SIZE]>,
   /// A buffer into which the current output for the ADSR is rendered each frame
-  cur_frame_output: Box<[f32; FRAME_SIZE]>,
+  cur_frame_output: Box<[f32; MAX_FRAME_SIZE]>,
+  /// Number of samples rendered by each call to `render_frame`
+  frame_size: usize,
   len_samples: f32,
   /// Beat on which the current gate started.  If loop is enabled, this is the beat on which the
   /// current loop started.
@@ -178,6 +195,9 @@ pub struct Adsr {
   /// containing the current phase of all active ADSRs.
   pub store_phase_to: Option<*mut f32>,
   pub log_scale: bool,
+  /// Gate or ungate applied at the contained sample offset into the next frame.  The frame is
+  /// rendered in two parts around it.
+  pending_transition: Option<(usize, GateTransition)>,
 }
 
 const DEFAULT_FIRST_STEP: AdsrStep = AdsrStep {
@@ -208,23 +228,27 @@ impl Adsr {
       steps,
       loop_point,
       rendered,
-      cur_frame_output: Box::new([0.; FRAME_SIZE]),
+      cur_frame_output: Box::new([0.; MAX_FRAME_SIZE]),
+      frame_size: FRAME_SIZE,
       len_samples,
       gated_beat: 0.,
       len_beats,
       cached_phase_diff_per_sample: (1. / len_samples),
       store_phase_to: None,
       log_scale,
+      pending_transition: None,
     }
   }
 
   pub fn gate(&mut self, cur_beat: f32) {
+    self.pending_transition = None;
     self.phase = 0.;
     self.gated_beat = cur_beat;
     self.gate_status = GateStatus::Gated;
   }
 
   pub fn ungate(&mut self) {
+    self.pending_transition = None;
     if self.phase < self.release_start_phase {
       self.gate_status = GateStatus::EarlyRelease {
         start_x: self.phase,
@@ -247,6 +271,37 @@ impl Adsr {
     }
   }
 
+  /// Gates the ADSR `sample_offset` samples into the next frame rendered by `render_frame`
+  pub fn gate_at(&mut self, cur_beat: f32, sample_offset: usize) {
+    self.schedule_transition(sample_offset, GateTransition::Gate { cur_beat });
+  }
+
+  /// Ungates the ADSR `sample_offset` samples into the next frame rendered by `render_frame`
+  pub fn ungate_at(&mut self, sample_offset: usize) {
+    self.schedule_transition(sample_offset, GateTransition::Ungate);
+  }
+
+  fn apply_transition(&mut self, transition: GateTransition) {
+    match transition {
+      GateTransition::Gate { cur_beat } => self.gate(cur_beat),
+      GateTransition::Ungate => self.ungate(),
+    }
+  }
+
+  /// Only one transition can be pending at a time, so one that's already pending is applied
+  /// immediately.  Transitions must be scheduled in order of their offsets.
+  fn schedule_transition(&mut self, sample_offset: usize, transition: GateTransition) {
+    if let Some((_, pending)) = self.pending_transition.take() {
+      self.apply_transition(pending);
+    }
+
+    if sample_offset == 0 {
+      self.apply_transition(transition);
+    } else {
+      self.pending_transition = Some((sample_offset, transition));
+    }
+  }
+
   /// Renders the ADSR into the shared buffer.  Only needs to be called once for all ADSRs that
   /// share this associated buffer.
   pub fn render(&mut self) {
@@ -355,15 +410,16 @@ impl Adsr {
       let cur_loop_progress_beats = (cur_frame_start_beat - self.gated_beat).max(0.);
       let cur_frame_expected_start_phase = cur_loop_progress_beats / len_beats;
 
-      let cur_frame_phase_length = self.cached_phase_diff_per_sample * FRAME_SIZE as f32;
+      let cur_frame_phase_length = self.cached_phase_diff_per_sample * self.frame_size as f32;
       let cur_sample_expected_phase = cur_frame_expected_start_phase
         + cur_frame_phase_length * cur_oversampled_ix_in_frame as f32
-          / (FRAME_SIZE * oversample_factor - 1) as f32;
+          / (self.frame_size * oversample_factor - 1) as f32;
       let cur_sample_expected_phase = cur_sample_expected_phase.max(0.);
       let cur_sample_computed_phase =
         *cur_frame_start_phase + (phase_diff * cur_oversampled_ix_in_frame as f32);
 
-      let mix = cur_oversampled_ix_in_frame as f32 / (FRAME_SIZE * oversample_factor - 1) as f32;
+      let mix =
+        cur_oversampled_ix_in_frame as f32 / (self.frame_size * oversample_factor - 1) as f32;
       self.phase = cur_sample_expected_phase * mix + cur_sample_computed_phase * (1. - mix);
     } else {
       if matches!(self.gate_status, GateStatus::Gated) {
@@ -495,20 +551,49 @@ impl Adsr {
 
       frozen_output = mk_linear_to_log(min, max, max.signum())(frozen_output);
     }
-    for i in 0..FRAME_SIZE {
-      self.cur_frame_output[i] = frozen_output;
-    }
+    // The whole buffer is filled so that the output stays frozen if the frame size grows
+    self.cur_frame_output.fill(frozen_output);
   }
 
-  /// Populates `self.cur_frame_output` with samples for the current frame
+  /// Populates `self.cur_frame_output` with samples for the current frame.  If a gate or ungate is
+  /// pending, the samples before and after it are rendered separately so that it takes effect at
+  /// the exact sample it was scheduled for.
   pub fn render_frame(&mut self, scale: f32, shift: f32, cur_frame_start_beat: f32) {
+    let frame_size = self.frame_size;
+    let (sample_offset, transition) = match self.pending_transition.take() {
+      Some((sample_offset, transition)) if sample_offset < frame_size =>
+        (sample_offset, transition),
+      Some((_, transition)) => {
+        self.apply_transition(transition);
+        return self.render_frame_inner(scale, shift, cur_frame_start_beat);
+      },
+      None => return self.render_frame_inner(scale, shift, cur_frame_start_beat),
+    };
+
+    self.frame_size = sample_offset;
+    self.render_frame_inner(scale, shift, cur_frame_start_beat);
+    let mut head = [0.; MAX_FRAME_SIZE];
+    head[..sample_offset].copy_from_slice(&self.cur_frame_output[..sample_offset]);
+
+    self.apply_transition(transition);
+    self.frame_size = frame_size - sample_offset;
+    self.render_frame_inner(scale, shift, cur_frame_start_beat);
+    self.frame_size = frame_size;
+
+    self
+      .cur_frame_output
+      .copy_within(..frame_size - sample_offset, sample_offset);
+    self.cur_frame_output[..sample_offset].copy_from_slice(&head[..sample_offset]);
+  }
+
+  fn render_frame_inner(&mut self, scale: f32, shift: f32, cur_frame_start_beat: f32) {
     let mut cur_frame_start_phase = self.phase;
     match self.gate_status {
       GateStatus::Gated if self.loop_point.is_none() && self.phase >= self.release_start_phase => {
         let final_sample = self.get_sample(
           &mut cur_frame_start_phase,
           cur_frame_start_beat,
-          FRAME_SIZE - 1,
+          self.frame_size - 1,
         );
         self.fill_buffer_with_value(final_sample, scale, shift);
         self.gate_status = if self.early_release_config.strategy == EarlyReleaseStrategy::Freeze {
@@ -540,12 +625,12 @@ impl Adsr {
     }
 
     if self.log_scale {
-      for i in 0..FRAME_SIZE {
+      for i in 0..self.frame_size {
         self.cur_frame_output[i] =
           self.get_sample(&mut cur_frame_start_phase, cur_frame_start_beat, i) * 100.;
       }
     } else {
-      for i in 0..FRAME_SIZE {
+      for i in 0..self.frame_size {
         self.cur_frame_output[i] =
           self.get_sample(&mut cur_frame_start_phase, cur_frame_start_beat, i) * scale + shift;
       }
@@ -560,13 +645,19 @@ impl Adsr {
       }
       let linear_to_log = mk_linear_to_log(min, max, max.signum());
 
-      for i in 0..FRAME_SIZE {
+      for i in 0..self.frame_size {
         self.cur_frame_output[i] = linear_to_log(self.cur_frame_output[i]);
       }
     }
   }
 
-  pub fn get_cur_frame_output(&self) -> &[f32; FRAME_SIZE] { &self.cur_frame_output }
+  pub fn get_cur_frame_output(&self) -> &[f32] { &self.cur_frame_output[..self.frame_size] }
+
+  /// Sets the number of samples rendered by each call to `render_frame`, clamped to
+  /// `[1, MAX_FRAME_SIZE]`
+  pub fn set_frame_size(&mut self, frame_size: usize) {
+    self.frame_size = frame_size.clamp(1, MAX_FRAME_SIZE);
+  }
 
   pub fn set_frozen_output_value(&mut self, new_frozen_output_value: f32, scale: f32, shift: f32) {
     match self.gate_status {
@@ -1,6 +1,6 @@
-use crate::{exports::AdsrLengthMode, Adsr, SAMPLE_RATE};
+use dsp::ms_to_samples;
 
-fn ms_to_samples(ms: f32) -> f32 { (ms / 1000.) * SAMPLE_RATE as f32 }
+use crate::{exports::AdsrLengthMode, Adsr};
 
 #[derive(Clone)]
 pub struct ManagedAdsr {
@@ -16,7 +16,7 @@ impl ManagedAdsr {
       AdsrLengthMode::Beats => {
         let cur_bps = cur_bpm / 60.;
         let seconds_per_beat = 1. / cur_bps;
-        let samples_per_beat = seconds_per_beat * SAMPLE_RATE as f32;
+        let samples_per_beat = seconds_per_beat * dsp::sample_rate();
         samples_per_beat * self.length
       },
     }
@@ -39,3 +39,32 @@ impl ManagedAdsr {
     self.adsr.render_frame(scale, shift, cur_frame_start_beat);
   }
 }
+
+#[test]
+fn length_conversion_follows_sample_rate() {
+  use std::rc::Rc;
+
+  let mut managed = ManagedAdsr {
+    adsr: Adsr::new(
+      Vec::new(),
+      None,
+      1.,
+      None,
+      1.,
+      Rc::new([0.; crate::RENDERED_BUFFER_SIZE]),
+      Default::default(),
+      false,
+    ),
+    length_mode: AdsrLengthMode::Ms,
+    length: 250.,
+  };
+
+  for sample_rate in [44_100., 48_000., 96_000.] {
+    assert!(dsp::set_sample_rate(sample_rate));
+    managed.set_length(AdsrLengthMode::Ms, 250.);
+    assert_eq!(managed.get_len_samples(120.), sample_rate / 4.);
+    // 2 beats at 120 BPM is 1 second
+    managed.set_length(AdsrLengthMode::Beats, 2.);
+    assert_eq!(managed.get_len_samples(120.), sample_rate);
+  }
+}
@@ -1,5 +1,7 @@
 use std::rc::Rc;
 
+use dsp::FRAME_SIZE;
+
 use crate::{Adsr, AdsrStep, EarlyReleaseConfig, GateStatus, RampFn, RENDERED_BUFFER_SIZE};
 
 #[test]
@@ -102,3 +104,74 @@ fn gain_envelope_click_debug() {
 
   println!("Cur frame output: {:?}", adsr.get_cur_frame_output());
 }
+
+fn linear_adsr() -> Adsr {
+  let steps = vec![
+    AdsrStep {
+      x: 0.,
+      y: 0.,
+      ramper: RampFn::Linear,
+    },
+    AdsrStep {
+      x: 1.,
+      y: 1.,
+      ramper: RampFn::Linear,
+    },
+  ];
+  let mut adsr = Adsr::new(
+    steps,
+    None,
+    44_100.,
+    None,
+    0.9,
+    Rc::new([0.; RENDERED_BUFFER_SIZE]),
+    EarlyReleaseConfig::default(),
+    false,
+  );
+  adsr.render();
+  adsr
+}
+
+#[test]
+fn gate_at_splits_frame() {
+  let mut reference = linear_adsr();
+  reference.gate(0.);
+  reference.render_frame(1., 0., 0.);
+
+  let mut adsr = linear_adsr();
+  adsr.gate_at(0., 40);
+  assert_eq!(adsr.gate_status, GateStatus::Done);
+  adsr.render_frame(1., 0., 0.);
+  assert_eq!(adsr.gate_status, GateStatus::Gated);
+
+  let output = adsr.get_cur_frame_output();
+  assert_eq!(output.len(), FRAME_SIZE);
+  assert!(output[..40].iter().all(|&sample| sample == 0.));
+  assert_eq!(
+    &output[40..],
+    &reference.get_cur_frame_output()[..FRAME_SIZE - 40]
+  );
+}
+
+#[test]
+fn ungate_at_splits_frame() {
+  let mut reference = linear_adsr();
+  reference.gate(0.);
+  reference.render_frame(1., 0., 0.);
+  let mut adsr = reference.clone();
+
+  reference.render_frame(1., 0., 0.);
+  adsr.ungate_at(100);
+  adsr.render_frame(1., 0., 0.);
+  assert_eq!(
+    &adsr.get_cur_frame_output()[..100],
+    &reference.get_cur_frame_output()[..100]
+  );
+  assert!(matches!(adsr.gate_status, GateStatus::EarlyRelease { .. }));
+
+  // Gating directly drops the pending ungate
+  adsr.ungate_at(10);
+  adsr.gate(0.);
+  adsr.render_frame(1., 0., 0.);
+  assert_eq!(adsr.gate_status, GateStatus::Gated);
+}
@@ -0,0 +1,143 @@
+/// Shape of the segment between an automation point and the point after it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutomationCurve {
+  /// Holds the point's value until the next point is reached
+  Step,
+  Linear,
+  /// Interpolates along `t^exponent`.  Exponents > 1 start slow and end fast, exponents < 1 do the
+  /// opposite.
+  Exponential {
+    exponent: f32,
+  },
+}
+
+impl AutomationCurve {
+  /// Decodes a curve from the `(type, param)` pair used to pass points across the FFI boundary.
+  /// Unknown types fall back to linear.
+  pub fn from_parts(curve_type: f64, param: f64) -> Self {
+    match curve_type as u32 {
+      0 => AutomationCurve::Step,
+      2 if param.is_finite() && param > 0. => AutomationCurve::Exponential {
+        exponent: param as f32,
+      },
+      _ => AutomationCurve::Linear,
+    }
+  }
+
+  /// Inverse of `from_parts`
+  pub fn to_parts(&self) -> (f64, f64) {
+    match *self {
+      AutomationCurve::Step => (0., 0.),
+      AutomationCurve::Linear => (1., 0.),
+      AutomationCurve::Exponential { exponent } => (2., exponent as f64),
+    }
+  }
+
+  /// Maps `t` in `[0, 1]` to the fraction of the way between two points' values
+  fn shape(&self, t: f32) -> f32 {
+    match *self {
+      AutomationCurve::Step => 0.,
+      AutomationCurve::Linear => t,
+      AutomationCurve::Exponential { exponent } => t.powf(exponent),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutomationPoint {
+  pub beat: f64,
+  pub value: f32,
+  /// Shape of the segment from this point to the next one
+  pub curve: AutomationCurve,
+}
+
+/// Automation for a single parameter.  Before the first point the lane holds the first point's
+/// value, and after the last point it holds the last point's value.  An empty lane outputs 0.
+#[derive(Clone, Debug, Default)]
+pub struct AutomationLane {
+  points: Vec<AutomationPoint>,
+}
+
+impl AutomationLane {
+  /// Points must be sorted by beat.  Multiple points can share the same beat to create an
+  /// instantaneous jump.
+  pub fn set_points(&mut self, points: impl IntoIterator<Item = AutomationPoint>) {
+    self.points.clear();
+    self.points.extend(points);
+    debug_assert!(self.points.windows(2).all(|w| w[0].beat <= w[1].beat));
+  }
+
+  pub fn points(&self) -> &[AutomationPoint] { &self.points }
+
+  /// Inserts `point` after any existing points at the same beat
+  pub fn insert(&mut self, point: AutomationPoint) {
+    let ix = self.points.partition_point(|p| p.beat <= point.beat);
+    self.points.insert(ix, point);
+  }
+
+  /// Replaces the points in `(from_beat, point.beat]` with `point`
+  pub fn overwrite(&mut self, from_beat: f64, point: AutomationPoint) {
+    let start_ix = self.points.partition_point(|p| p.beat <= from_beat);
+    let end_ix = self.points.partition_point(|p| p.beat <= point.beat);
+    self
+      .points
+      .splice(start_ix..end_ix.max(start_ix), std::iter::once(point));
+  }
+
+  pub fn sample(&self, beat: f64) -> f32 {
+    // Index of the first point after `beat`
+    let next_ix = self.points.partition_point(|p| p.beat <= beat);
+    let prev = match next_ix {
+      0 => return self.points.first().map(|p| p.value).unwrap_or(0.),
+      ix => &self.points[ix - 1],
+    };
+    let next = match self.points.get(next_ix) {
+      Some(next) => next,
+      None => return prev.value,
+    };
+
+    let t = ((beat - prev.beat) / (next.beat - prev.beat)) as f32;
+    prev.value + (next.value - prev.value) * prev.curve.shape(t)
+  }
+}
+
+#[cfg(test)]
+fn pt(beat: f64, value: f32, curve: AutomationCurve) -> AutomationPoint {
+  AutomationPoint { beat, value, curve }
+}
+
+#[test]
+fn lane_sampling() {
+  let mut lane = AutomationLane::default();
+  assert_eq!(lane.sample(1.), 0.);
+
+  lane.set_points([
+    pt(1., 0., AutomationCurve::Linear),
+    pt(3., 1., AutomationCurve::Step),
+    pt(4., 0.5, AutomationCurve::Exponential { exponent: 2. }),
+    pt(6., 1.5, AutomationCurve::Linear),
+  ]);
+
+  assert_eq!(lane.sample(0.), 0.);
+  assert_eq!(lane.sample(2.), 0.5);
+  assert_eq!(lane.sample(3.), 1.);
+  assert_eq!(lane.sample(3.9), 1.);
+  assert_eq!(lane.sample(4.), 0.5);
+  assert_eq!(lane.sample(5.), 0.75);
+  assert_eq!(lane.sample(100.), 1.5);
+}
+
+#[test]
+fn lane_sampling_with_jump() {
+  let mut lane = AutomationLane::default();
+  lane.set_points([
+    pt(0., 0., AutomationCurve::Linear),
+    pt(2., 1., AutomationCurve::Linear),
+    pt(2., -1., AutomationCurve::Linear),
+    pt(4., 0., AutomationCurve::Linear),
+  ]);
+
+  assert_eq!(lane.sample(1.), 0.5);
+  assert_eq!(lane.sample(2.), -1.);
+  assert_eq!(lane.sample(3.), -0.5);
+}
@@ -0,0 +1,445 @@
+//! Automation lanes sampled on the audio thread.  Each lane is a list of (beat, value, curve)
+//! points for a single parameter, and every frame each lane is interpolated across the span of
+//! beats that the transport covered to produce one output value per sample.
+//!
+//! Param changes made in the UI can also be recorded into separate lanes keyed by module and param
+//! and played back later; see `recorder`.
+
+use common::ffi::{self, ErrorCode, FfiResult};
+
+use crate::{
+  lane::{AutomationCurve, AutomationLane, AutomationPoint},
+  recorder::AutomationRecorder,
+};
+
+pub mod lane;
+pub mod recorder;
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+  fn log_err(ptr: *const u8, len: usize);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+use common::native::log_err;
+
+const FRAME_SIZE: usize = 128;
+pub const MAX_LANE_COUNT: usize = 16;
+/// Each point in the points buffer has the following format:
+///
+/// [0] = beat
+/// [1] = value
+/// [2] = curve type; 0 = step, 1 = linear, 2 = exponential
+/// [3] = curve param; exponent for exponential curves, ignored otherwise
+const POINT_SIZE: usize = 4;
+/// Each param change in the recorded changes buffer has the following format:
+///
+/// [0] = module id
+/// [1] = param id
+/// [2] = value
+const CHANGE_SIZE: usize = 3;
+
+pub struct AutomationCtx {
+  pub lanes: Vec<AutomationLane>,
+  /// `FRAME_SIZE` samples for each lane, laid out one lane after another
+  pub output: Vec<f32>,
+  pub points_buf: Vec<f64>,
+  pub recorder: AutomationRecorder,
+  /// Changes produced by the last call to `automation_play_recorded`.  See `CHANGE_SIZE` for the
+  /// layout.
+  pub recorded_changes_buf: Vec<f64>,
+}
+
+#[no_mangle]
+pub extern "C" fn automation_create_ctx(lane_count: usize) -> *mut AutomationCtx {
+  common::set_raw_panic_hook(log_err);
+
+  let lane_count = lane_count.min(MAX_LANE_COUNT);
+  let ctx = AutomationCtx {
+    lanes: vec![AutomationLane::default(); lane_count],
+    output: vec![0.; lane_count * FRAME_SIZE],
+    points_buf: Vec::new(),
+    recorder: AutomationRecorder::default(),
+    recorded_changes_buf: Vec::new(),
+  };
+  Box::into_raw(Box::new(ctx))
+}
+
+#[no_mangle]
+pub extern "C" fn automation_set_lane_count(
+  ctx: *mut AutomationCtx,
+  lane_count: usize,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "automation_set_lane_count") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("lane_count", lane_count, 0, MAX_LANE_COUNT) {
+    return code;
+  }
+
+  ctx.lanes.resize_with(lane_count, AutomationLane::default);
+  ctx.output.resize(lane_count * FRAME_SIZE, 0.);
+  ErrorCode::Ok
+}
+
+/// Returns a pointer to a buffer with room for `point_count` points which should be filled in
+/// before calling `automation_commit_lane_points`.  See `POINT_SIZE` for the layout.
+#[no_mangle]
+pub extern "C" fn automation_get_points_buf_ptr(
+  ctx: *mut AutomationCtx,
+  point_count: usize,
+) -> *mut f64 {
+  let ctx = match unsafe { ffi::handle(ctx, "automation_get_points_buf_ptr") } {
+    Ok(ctx) => ctx,
+    Err(_) => return std::ptr::null_mut(),
+  };
+  ctx.points_buf.resize(point_count * POINT_SIZE, 0.);
+  ctx.points_buf.as_mut_ptr()
+}
+
+/// Validates the first `point_count` points from the points buffer.  Points must have finite,
+/// non-negative beats and finite values and be sorted by beat, matching the checks that the backend
+/// makes on saved automation.
+fn read_points_buf(points_buf: &[f64], point_count: usize) -> FfiResult<&[f64]> {
+  ffi::check_range("point_count", point_count, 0, points_buf.len() / POINT_SIZE)?;
+
+  let raw_points = &points_buf[..point_count * POINT_SIZE];
+  let mut last_beat = 0.;
+  for point in raw_points.chunks_exact(POINT_SIZE) {
+    ffi::check_range("beat", point[0], last_beat, f64::MAX)?;
+    ffi::check_range("value", point[1], f64::MIN, f64::MAX)?;
+    last_beat = point[0];
+  }
+  Ok(raw_points)
+}
+
+fn decode_points(raw_points: &[f64]) -> impl Iterator<Item = AutomationPoint> + '_ {
+  raw_points
+    .chunks_exact(POINT_SIZE)
+    .map(|point| AutomationPoint {
+      beat: point[0],
+      value: point[1] as f32,
+      curve: AutomationCurve::from_parts(point[2], point[3]),
+    })
+}
+
+/// Replaces the points of lane `lane_ix` with the first `point_count` points from the points
+/// buffer.  Points must have finite, non-negative beats and finite values and be sorted by beat.
+#[no_mangle]
+pub extern "C" fn automation_commit_lane_points(
+  ctx: *mut AutomationCtx,
+  lane_ix: usize,
+  point_count: usize,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "automation_commit_lane_points") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_index("lane_ix", lane_ix, ctx.lanes.len())?;
+    let raw_points = read_points_buf(&ctx.points_buf, point_count)?;
+    ctx.lanes[lane_ix].set_points(decode_points(raw_points));
+    Ok(())
+  })())
+}
+
+/// Samples every lane across the frame spanning `[start_beat, end_beat)`, writing the results to
+/// the buffer returned by `automation_get_output_ptr`.
+#[no_mangle]
+pub extern "C" fn automation_process(
+  ctx: *mut AutomationCtx,
+  start_beat: f64,
+  end_beat: f64,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "automation_process") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  let beats_per_sample = (end_beat - start_beat) / FRAME_SIZE as f64;
+
+  for (lane, out) in ctx
+    .lanes
+    .iter()
+    .zip(ctx.output.chunks_exact_mut(FRAME_SIZE))
+  {
+    for (sample_ix, out) in out.iter_mut().enumerate() {
+      *out = lane.sample(start_beat + sample_ix as f64 * beats_per_sample);
+    }
+  }
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn automation_get_output_ptr(ctx: *mut AutomationCtx) -> *const f32 {
+  match unsafe { ffi::handle(ctx, "automation_get_output_ptr") } {
+    Ok(ctx) => ctx.output.as_ptr(),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+/// While armed, param changes passed to `automation_record_param_change` are recorded.  Disarming
+/// ends the current recording pass.
+#[no_mangle]
+pub extern "C" fn automation_set_recording_armed(
+  ctx: *mut AutomationCtx,
+  armed: bool,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "automation_set_recording_armed") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ctx.recorder.set_armed(armed);
+  ErrorCode::Ok
+}
+
+/// Records a change of `param_id` on `module_id` made at `beat`.  Should only be called while the
+/// transport is running; does nothing if recording isn't armed.
+#[no_mangle]
+pub extern "C" fn automation_record_param_change(
+  ctx: *mut AutomationCtx,
+  module_id: u32,
+  param_id: u32,
+  value: f32,
+  beat: f64,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "automation_record_param_change") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status(ctx.recorder.record(module_id, param_id, value, beat))
+}
+
+/// Releases all params touched during the current recording pass so that they're played back
+/// again.  Should be called when the transport stops.
+#[no_mangle]
+pub extern "C" fn automation_end_recording_pass(ctx: *mut AutomationCtx) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "automation_end_recording_pass") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ctx.recorder.end_pass();
+  ErrorCode::Ok
+}
+
+/// Samples the recorded lanes at `beat`, writing the params whose values changed since the last
+/// call to the buffer returned by `automation_get_recorded_changes_ptr`; see `CHANGE_SIZE` for the
+/// layout.  The number of changes is returned by `automation_get_recorded_change_count`.
+#[no_mangle]
+pub extern "C" fn automation_play_recorded(ctx: *mut AutomationCtx, beat: f64) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "automation_play_recorded") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("beat", beat, f64::MIN, f64::MAX) {
+    return code;
+  }
+  ctx.recorder.play(beat);
+
+  ctx.recorded_changes_buf.clear();
+  for change in &ctx.recorder.changes {
+    let encoded: [f64; CHANGE_SIZE] = [
+      change.module_id as f64,
+      change.param_id as f64,
+      change.value as f64,
+    ];
+    ctx.recorded_changes_buf.extend_from_slice(&encoded);
+  }
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn automation_get_recorded_change_count(ctx: *mut AutomationCtx) -> usize {
+  match unsafe { ffi::handle(ctx, "automation_get_recorded_change_count") } {
+    Ok(ctx) => ctx.recorded_changes_buf.len() / CHANGE_SIZE,
+    Err(_) => 0,
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn automation_get_recorded_changes_ptr(ctx: *mut AutomationCtx) -> *const f64 {
+  match unsafe { ffi::handle(ctx, "automation_get_recorded_changes_ptr") } {
+    Ok(ctx) => ctx.recorded_changes_buf.as_ptr(),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn automation_get_recorded_lane_count(ctx: *mut AutomationCtx) -> usize {
+  match unsafe { ffi::handle(ctx, "automation_get_recorded_lane_count") } {
+    Ok(ctx) => ctx.recorder.lanes.len(),
+    Err(_) => 0,
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn automation_get_recorded_lane_module_id(
+  ctx: *mut AutomationCtx,
+  lane_ix: usize,
+) -> u32 {
+  match unsafe { ffi::handle(ctx, "automation_get_recorded_lane_module_id") } {
+    Ok(ctx) => ctx
+      .recorder
+      .lanes
+      .get(lane_ix)
+      .map(|lane| lane.module_id)
+      .unwrap_or(0),
+    Err(_) => 0,
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn automation_get_recorded_lane_param_id(
+  ctx: *mut AutomationCtx,
+  lane_ix: usize,
+) -> u32 {
+  match unsafe { ffi::handle(ctx, "automation_get_recorded_lane_param_id") } {
+    Ok(ctx) => ctx
+      .recorder
+      .lanes
+      .get(lane_ix)
+      .map(|lane| lane.param_id)
+      .unwrap_or(0),
+    Err(_) => 0,
+  }
+}
+
+/// Returns the number of points in the points buffer, such as after a call to
+/// `automation_export_recorded_lane_points`
+#[no_mangle]
+pub extern "C" fn automation_get_points_buf_point_count(ctx: *mut AutomationCtx) -> usize {
+  match unsafe { ffi::handle(ctx, "automation_get_points_buf_point_count") } {
+    Ok(ctx) => ctx.points_buf.len() / POINT_SIZE,
+    Err(_) => 0,
+  }
+}
+
+/// Writes the points of recorded lane `lane_ix` to the points buffer.  The number of points is
+/// returned by `automation_get_points_buf_point_count`, and they can then be read via
+/// `automation_get_points_buf_ptr` with that point count.
+#[no_mangle]
+pub extern "C" fn automation_export_recorded_lane_points(
+  ctx: *mut AutomationCtx,
+  lane_ix: usize,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "automation_export_recorded_lane_points") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_index("lane_ix", lane_ix, ctx.recorder.lanes.len()) {
+    return code;
+  }
+  let points = ctx.recorder.lanes[lane_ix].lane.points();
+
+  ctx.points_buf.clear();
+  for point in points {
+    let (curve_type, curve_param) = point.curve.to_parts();
+    ctx
+      .points_buf
+      .extend_from_slice(&[point.beat, point.value as f64, curve_type, curve_param]);
+  }
+  ErrorCode::Ok
+}
+
+/// Replaces the recorded lane for `param_id` on `module_id` with the first `point_count` points
+/// from the points buffer, creating the lane if it doesn't exist.  Used to restore saved
+/// recordings.
+#[no_mangle]
+pub extern "C" fn automation_commit_recorded_lane_points(
+  ctx: *mut AutomationCtx,
+  module_id: u32,
+  param_id: u32,
+  point_count: usize,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "automation_commit_recorded_lane_points") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    let raw_points = read_points_buf(&ctx.points_buf, point_count)?;
+    ctx
+      .recorder
+      .set_lane_points(module_id, param_id, decode_points(raw_points))
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn automation_clear_recording(ctx: *mut AutomationCtx) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "automation_clear_recording") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ctx.recorder.clear();
+  ErrorCode::Ok
+}
+
+#[test]
+fn commit_lane_points_validation() {
+  let ctx = automation_create_ctx(1);
+  let commit = |points: &[[f64; POINT_SIZE]]| {
+    let buf = automation_get_points_buf_ptr(ctx, points.len());
+    let buf = unsafe { std::slice::from_raw_parts_mut(buf, points.len() * POINT_SIZE) };
+    buf.copy_from_slice(points.concat().as_slice());
+    automation_commit_lane_points(ctx, 0, points.len())
+  };
+
+  assert_eq!(
+    commit(&[[0., 0.5, 1., 0.], [2., 1., 1., 0.]]),
+    ErrorCode::Ok
+  );
+  assert_eq!(commit(&[[-1., 0.5, 1., 0.]]), ErrorCode::ParamOutOfRange);
+  assert_eq!(
+    commit(&[[2., 0.5, 1., 0.], [1., 1., 1., 0.]]),
+    ErrorCode::ParamOutOfRange
+  );
+
+  assert_eq!(automation_set_lane_count(ctx, 0), ErrorCode::Ok);
+  assert_eq!(commit(&[]), ErrorCode::ParamOutOfRange);
+  assert!(!automation_get_output_ptr(ctx).is_null());
+  assert!(automation_get_output_ptr(std::ptr::null_mut()).is_null());
+
+  drop(unsafe { Box::from_raw(ctx) });
+}
+
+#[test]
+fn recorded_lane_exports() {
+  let ctx = automation_create_ctx(0);
+  assert_eq!(automation_set_recording_armed(ctx, true), ErrorCode::Ok);
+  assert_eq!(
+    automation_record_param_change(ctx, 4, 2, 0.5, 1.),
+    ErrorCode::Ok
+  );
+  assert_eq!(automation_end_recording_pass(ctx), ErrorCode::Ok);
+  assert_eq!(automation_set_recording_armed(ctx, false), ErrorCode::Ok);
+
+  assert_eq!(automation_get_recorded_lane_count(ctx), 1);
+  assert_eq!(automation_get_recorded_lane_module_id(ctx, 0), 4);
+  assert_eq!(automation_get_recorded_lane_param_id(ctx, 0), 2);
+  assert_eq!(
+    automation_export_recorded_lane_points(ctx, 0),
+    ErrorCode::Ok
+  );
+  assert_eq!(automation_get_points_buf_point_count(ctx), 1);
+  assert_eq!(
+    automation_export_recorded_lane_points(ctx, 1),
+    ErrorCode::ParamOutOfRange
+  );
+
+  assert_eq!(automation_play_recorded(ctx, 2.), ErrorCode::Ok);
+  assert_eq!(automation_get_recorded_change_count(ctx), 1);
+  assert_eq!(
+    automation_play_recorded(ctx, f64::NAN),
+    ErrorCode::ParamOutOfRange
+  );
+
+  let null = std::ptr::null_mut();
+  assert_eq!(automation_play_recorded(null, 2.), ErrorCode::InvalidHandle);
+  assert_eq!(
+    automation_export_recorded_lane_points(null, 0),
+    ErrorCode::InvalidHandle
+  );
+  assert_eq!(automation_get_recorded_lane_count(null), 0);
+  assert!(automation_get_recorded_changes_ptr(null).is_null());
+
+  drop(unsafe { Box::from_raw(ctx) });
+}
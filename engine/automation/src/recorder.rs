@@ -0,0 +1,224 @@
+//! Records live param changes into automation lanes and plays them back.
+//!
+//! While armed, each change made to a param while the transport is running is written into a lane
+//! for that (module, param) pair at the current beat, overwriting whatever was previously recorded
+//! between the last recorded point and the new one.  A recording pass lasts until the transport
+//! stops or recording is disarmed.  During a pass, params that have been changed are "touched"
+//! and aren't played back so that the recorded lane doesn't fight the user; every other recorded
+//! lane keeps playing.
+
+use common::ffi::{self, FfiResult};
+
+use crate::lane::{AutomationCurve, AutomationLane, AutomationPoint};
+
+pub const MAX_RECORDED_LANE_COUNT: usize = 256;
+
+pub struct RecordedLane {
+  pub module_id: u32,
+  pub param_id: u32,
+  pub lane: AutomationLane,
+  /// Beat of the last point recorded during the current pass, or `None` if this param hasn't been
+  /// touched yet
+  last_recorded_beat: Option<f64>,
+  /// Last value reported by playback so that only changes are reported
+  last_played_value: Option<f32>,
+}
+
+impl RecordedLane {
+  fn new(module_id: u32, param_id: u32) -> Self {
+    RecordedLane {
+      module_id,
+      param_id,
+      lane: AutomationLane::default(),
+      last_recorded_beat: None,
+      last_played_value: None,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecordedChange {
+  pub module_id: u32,
+  pub param_id: u32,
+  pub value: f32,
+}
+
+#[derive(Default)]
+pub struct AutomationRecorder {
+  armed: bool,
+  pub lanes: Vec<RecordedLane>,
+  /// Param changes produced by the last call to `play`
+  pub changes: Vec<RecordedChange>,
+}
+
+impl AutomationRecorder {
+  pub fn is_armed(&self) -> bool { self.armed }
+
+  pub fn set_armed(&mut self, armed: bool) {
+    if !armed {
+      self.end_pass();
+    }
+    self.armed = armed;
+  }
+
+  /// Releases all touched params so that their lanes are played back again.  Called when the
+  /// transport stops.
+  pub fn end_pass(&mut self) {
+    for lane in &mut self.lanes {
+      lane.last_recorded_beat = None;
+      lane.last_played_value = None;
+    }
+  }
+
+  fn lane_ix(&self, module_id: u32, param_id: u32) -> Option<usize> {
+    self
+      .lanes
+      .iter()
+      .position(|lane| lane.module_id == module_id && lane.param_id == param_id)
+  }
+
+  fn lane_mut(&mut self, module_id: u32, param_id: u32) -> FfiResult<&mut RecordedLane> {
+    let ix = match self.lane_ix(module_id, param_id) {
+      Some(ix) => ix,
+      None => {
+        ffi::check_range(
+          "recorded lane count",
+          self.lanes.len() + 1,
+          0,
+          MAX_RECORDED_LANE_COUNT,
+        )?;
+        self.lanes.push(RecordedLane::new(module_id, param_id));
+        self.lanes.len() - 1
+      },
+    };
+    Ok(&mut self.lanes[ix])
+  }
+
+  /// Records a change of `param_id` on `module_id` to `value` at `beat`.  Changes are ignored
+  /// unless recording is armed; the caller is responsible for only passing changes made while the
+  /// transport is running.
+  pub fn record(&mut self, module_id: u32, param_id: u32, value: f32, beat: f64) -> FfiResult {
+    if !self.armed {
+      return Ok(());
+    }
+    ffi::check_range("value", value, f32::MIN, f32::MAX)?;
+    ffi::check_range("beat", beat, f64::MIN, f64::MAX)?;
+
+    let recorded = self.lane_mut(module_id, param_id)?;
+    let point = AutomationPoint {
+      beat,
+      value,
+      curve: AutomationCurve::Linear,
+    };
+    match recorded.last_recorded_beat {
+      // The transport looping back to an earlier beat starts a new segment as well
+      Some(last_beat) if beat >= last_beat => recorded.lane.overwrite(last_beat, point),
+      _ => {
+        // Jump from the previously recorded automation to the new value rather than ramping
+        // from the previous point
+        if !recorded.lane.points().is_empty() {
+          let prev_value = recorded.lane.sample(beat);
+          recorded.lane.overwrite(beat, AutomationPoint {
+            value: prev_value,
+            ..point
+          });
+        }
+        recorded.lane.insert(point);
+      },
+    }
+    recorded.last_recorded_beat = Some(beat);
+    Ok(())
+  }
+
+  /// Samples every untouched lane at `beat`, collecting the params whose values changed since the
+  /// last call into `changes`.
+  pub fn play(&mut self, beat: f64) {
+    self.changes.clear();
+    for recorded in &mut self.lanes {
+      if recorded.last_recorded_beat.is_some() || recorded.lane.points().is_empty() {
+        continue;
+      }
+
+      let value = recorded.lane.sample(beat);
+      if recorded.last_played_value == Some(value) {
+        continue;
+      }
+      recorded.last_played_value = Some(value);
+      self.changes.push(RecordedChange {
+        module_id: recorded.module_id,
+        param_id: recorded.param_id,
+        value,
+      });
+    }
+  }
+
+  /// Replaces the recorded lane for `param_id` on `module_id`, creating it if necessary.  Used to
+  /// restore recordings from saved state.
+  pub fn set_lane_points(
+    &mut self,
+    module_id: u32,
+    param_id: u32,
+    points: impl IntoIterator<Item = AutomationPoint>,
+  ) -> FfiResult {
+    let recorded = self.lane_mut(module_id, param_id)?;
+    recorded.lane.set_points(points);
+    recorded.last_recorded_beat = None;
+    recorded.last_played_value = None;
+    Ok(())
+  }
+
+  pub fn clear(&mut self) {
+    self.lanes.clear();
+    self.changes.clear();
+  }
+}
+
+#[test]
+fn records_and_plays_back_param_changes() {
+  let mut recorder = AutomationRecorder::default();
+
+  // Nothing is recorded while disarmed
+  recorder.record(1, 0, 0.5, 0.).unwrap();
+  assert!(recorder.lanes.is_empty());
+
+  recorder.set_armed(true);
+  recorder.record(1, 0, 0., 0.).unwrap();
+  recorder.record(1, 0, 1., 2.).unwrap();
+  recorder.record(2, 3, 0.25, 1.).unwrap();
+
+  // Touched params aren't played back during the pass
+  recorder.play(1.);
+  assert!(recorder.changes.is_empty());
+
+  recorder.end_pass();
+  recorder.play(1.);
+  assert_eq!(recorder.changes, vec![
+    RecordedChange {
+      module_id: 1,
+      param_id: 0,
+      value: 0.5
+    },
+    RecordedChange {
+      module_id: 2,
+      param_id: 3,
+      value: 0.25
+    },
+  ]);
+  // Only changed values are reported
+  recorder.play(1.5);
+  assert_eq!(recorder.changes, vec![RecordedChange {
+    module_id: 1,
+    param_id: 0,
+    value: 0.75
+  }]);
+
+  // Overdubbing replaces the recording from the punch-in point onwards, jumping from the old value
+  recorder.record(1, 0, -1., 1.).unwrap();
+  recorder.record(1, 0, -1., 3.).unwrap();
+  recorder.set_armed(false);
+  let lane = &recorder.lanes[0].lane;
+  assert_eq!(lane.sample(0.5), 0.25);
+  assert_eq!(lane.sample(1.), -1.);
+  assert_eq!(lane.sample(2.), -1.);
+  assert_eq!(lane.points().len(), 4);
+}
@@ -0,0 +1,174 @@
+//! Shared status codes for `extern "C"` exports.
+//!
+//! Exports that can fail return an `ErrorCode` rather than `()` so that the JS side can tell when a
+//! call was rejected.  A human-readable description of the most recent failure is stored and can
+//! be read out of wasm memory via `get_last_error_message` + `get_last_error_message_len`.
+
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+  Ok = 0,
+  /// A null or otherwise unusable context pointer was passed in
+  InvalidHandle = 1,
+  /// A parameter was outside of the range that the module can handle
+  ParamOutOfRange = 2,
+  /// The module was used before its init function was called
+  NotInitialized = 3,
+  /// The requested operation or variant isn't supported by this module
+  Unsupported = 4,
+}
+
+pub type FfiResult<T = ()> = Result<T, ErrorCode>;
+
+/// NUL-terminated so it can also be consumed as a C string
+static mut LAST_ERROR_MESSAGE: Vec<u8> = Vec::new();
+
+/// Records `msg` as the last error message and returns `code` for convenience.
+pub fn set_last_error(code: ErrorCode, msg: &str) -> ErrorCode {
+  let buf = unsafe { &mut LAST_ERROR_MESSAGE };
+  buf.clear();
+  buf.extend_from_slice(msg.as_bytes());
+  buf.push(0);
+  code
+}
+
+pub fn get_last_error() -> &'static str {
+  let buf = unsafe { &LAST_ERROR_MESSAGE };
+  match buf.split_last() {
+    Some((_nul, msg)) => std::str::from_utf8(msg).unwrap_or_default(),
+    None => "",
+  }
+}
+
+/// Converts a raw context pointer passed in from JS into a reference, failing with
+/// `ErrorCode::InvalidHandle` if it's null.
+///
+/// # Safety
+///
+/// `ptr` must either be null or point to a live, properly aligned `T` that was handed out by the
+/// module's init function and not yet freed.  No other reference to it may be alive for `'a`.
+pub unsafe fn handle<'a, T>(ptr: *mut T, export_name: &str) -> FfiResult<&'a mut T> {
+  if ptr.is_null() {
+    return Err(set_last_error(
+      ErrorCode::InvalidHandle,
+      &format!("{export_name}: null context pointer"),
+    ));
+  }
+
+  Ok(&mut *ptr)
+}
+
+/// Checks that `val` is within `[min, max]` (inclusive), failing with `ErrorCode::ParamOutOfRange`
+/// if not.  NaN is always out of range.
+pub fn check_range<T: PartialOrd + std::fmt::Display>(
+  param_name: &str,
+  val: T,
+  min: T,
+  max: T,
+) -> FfiResult {
+  if val >= min && val <= max {
+    return Ok(());
+  }
+
+  Err(set_last_error(
+    ErrorCode::ParamOutOfRange,
+    &format!("{param_name}={val} is outside of the valid range [{min}, {max}]"),
+  ))
+}
+
+/// Checks that `ix` is a valid index into a collection of `len` items, failing with
+/// `ErrorCode::ParamOutOfRange` if not.  Prefer this to `check_range` for indices since there's no
+/// valid inclusive upper bound to pass it when the collection is empty.
+pub fn check_index(param_name: &str, ix: usize, len: usize) -> FfiResult {
+  if ix < len {
+    return Ok(());
+  }
+
+  Err(set_last_error(
+    ErrorCode::ParamOutOfRange,
+    &format!("{param_name}={ix} is out of bounds for a length of {len}"),
+  ))
+}
+
+/// Clamps `val` to `[min, max]`, failing with `ErrorCode::ParamOutOfRange` only if it's NaN.  Use
+/// this rather than `check_range` for continuous params where values that stray slightly out of
+/// range, from automation for example, should be tolerated rather than rejected.
+pub fn clamp_param(param_name: &str, val: f32, min: f32, max: f32) -> FfiResult<f32> {
+  if val.is_nan() {
+    return Err(set_last_error(
+      ErrorCode::ParamOutOfRange,
+      &format!("{param_name} is NaN"),
+    ));
+  }
+
+  Ok(val.clamp(min, max))
+}
+
+/// Collapses the result of an export's body into the status code that gets returned to JS.
+pub fn status(res: FfiResult) -> ErrorCode {
+  match res {
+    Ok(()) => ErrorCode::Ok,
+    Err(code) => code,
+  }
+}
+
+/// The inverse of `status`, for when one export calls into another and wants to propagate its
+/// failure with `?`.
+pub fn result(code: ErrorCode) -> FfiResult {
+  match code {
+    ErrorCode::Ok => Ok(()),
+    code => Err(code),
+  }
+}
+
+/// Returns a pointer to the message describing the most recent error.  The message is
+/// NUL-terminated; its length (excluding the terminator) is returned by
+/// `get_last_error_message_len`.
+#[no_mangle]
+pub extern "C" fn get_last_error_message() -> *const u8 {
+  let buf = unsafe { &mut LAST_ERROR_MESSAGE };
+  if buf.is_empty() {
+    buf.push(0);
+  }
+  buf.as_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn get_last_error_message_len() -> usize { get_last_error().len() }
+
+#[test]
+fn last_error_roundtrip() {
+  assert_eq!(get_last_error(), "");
+
+  let code = status(check_range("gain", 2., 0., 1.));
+  assert_eq!(code, ErrorCode::ParamOutOfRange);
+  assert_eq!(
+    get_last_error(),
+    "gain=2 is outside of the valid range [0, 1]"
+  );
+  assert_eq!(
+    unsafe { *get_last_error_message().add(get_last_error_message_len()) },
+    0
+  );
+
+  assert_eq!(
+    status(check_range("gain", f32::NAN, 0., 1.)),
+    ErrorCode::ParamOutOfRange
+  );
+  assert_eq!(check_index("ix", 1, 2), Ok(()));
+  assert_eq!(check_index("ix", 0, 0), Err(ErrorCode::ParamOutOfRange));
+  assert_eq!(get_last_error(), "ix=0 is out of bounds for a length of 0");
+  assert_eq!(clamp_param("gain", 2., 0., 1.), Ok(1.));
+  assert_eq!(
+    clamp_param("gain", f32::NAN, 0., 1.),
+    Err(ErrorCode::ParamOutOfRange)
+  );
+  assert_eq!(get_last_error(), "gain is NaN");
+  assert_eq!(
+    status(unsafe { handle::<u8>(std::ptr::null_mut(), "foo") }.map(drop)),
+    ErrorCode::InvalidHandle
+  );
+  assert_eq!(get_last_error(), "foo: null context pointer");
+  assert_eq!(result(ErrorCode::Ok), Ok(()));
+  assert_eq!(result(ErrorCode::Unsupported), Err(ErrorCode::Unsupported));
+}
@@ -6,7 +6,12 @@ use rand::prelude::*;
 use rand_pcg::Pcg32;
 use uuid::Uuid;
 
+pub mod ffi;
+pub mod humanize;
 mod init;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod native;
+pub mod state;
 
 pub use crate::init::*;
 
@@ -0,0 +1,23 @@
+//! Native stand-ins for the functions that Wasm modules import from the host.  They let the DSP
+//! crates be built and run outside of the browser so that they can be tested and profiled with
+//! regular tooling; see the `native_host` crate.
+
+use std::{sync::OnceLock, time::Instant};
+
+/// Writes the message to stderr.  Used as the panic hook, so it must not panic itself.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` bytes that are valid for reads for the duration of the call, matching
+/// the contract of the host function that this stands in for.
+pub unsafe extern "C" fn log_err(ptr: *const u8, len: usize) {
+  let bytes = std::slice::from_raw_parts(ptr, len);
+  eprintln!("{}", String::from_utf8_lossy(bytes));
+}
+
+/// Milliseconds since the first call, matching the resolution of `performance.now()`.  Unlike the
+/// imported version this is safe to call, so it can be used directly as the profiling clock.
+pub fn now() -> f64 {
+  static START: OnceLock<Instant> = OnceLock::new();
+  START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.
+}
@@ -0,0 +1,352 @@
+//! Uniform snapshot/recall of a module's parameter state.
+//!
+//! Modules implement `ModuleState` and expose a `*_get_state_json` + `*_set_state_json` pair built
+//! on `export_state` and `import_state`.  State is a flat JSON object mapping param names to
+//! numbers or booleans, so it can be stored alongside the rest of a composition without any
+//! per-module serialization code on the JS side.
+//!
+//! JSON is passed through a single buffer.  After `*_get_state_json` returns the length, the JSON
+//! can be read from `get_state_json_ptr`.  To restore state, JS writes the JSON into the buffer
+//! returned by `alloc_state_json_buf` and then calls `*_set_state_json` with its length.
+//!
+//! `ABCompare` builds on this to let users flip between two param sets on a single module.
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+use miniserde::{
+  de::Visitor,
+  json, make_place,
+  ser::{self, Fragment},
+  Deserialize, Serialize,
+};
+
+use crate::ffi::{self, ErrorCode, FfiResult};
+
+static mut STATE_JSON_BUF: Vec<u8> = Vec::new();
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StateValue {
+  Number(f64),
+  Bool(bool),
+}
+
+/// Param names and values in the order they were added.  Snapshots parsed from JSON are sorted by
+/// name instead.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StateSnapshot {
+  pub entries: Vec<(String, StateValue)>,
+}
+
+impl StateSnapshot {
+  pub fn push_number(&mut self, name: &str, val: f32) {
+    self
+      .entries
+      .push((name.to_owned(), StateValue::Number(val as f64)));
+  }
+
+  pub fn push_bool(&mut self, name: &str, val: bool) {
+    self.entries.push((name.to_owned(), StateValue::Bool(val)));
+  }
+
+  pub fn get(&self, name: &str) -> Option<StateValue> {
+    self
+      .entries
+      .iter()
+      .find(|(key, _)| key == name)
+      .map(|(_, val)| *val)
+  }
+
+  /// Returns the value of `name` if it's present, failing if it's not a number
+  pub fn number(&self, name: &str) -> FfiResult<Option<f32>> {
+    match self.get(name) {
+      None => Ok(None),
+      Some(StateValue::Number(val)) => Ok(Some(val as f32)),
+      Some(StateValue::Bool(_)) => Err(ffi::set_last_error(
+        ErrorCode::ParamOutOfRange,
+        &format!("expected {name} to be a number"),
+      )),
+    }
+  }
+
+  /// Returns the value of `name` if it's present, failing if it's not a boolean
+  pub fn bool(&self, name: &str) -> FfiResult<Option<bool>> {
+    match self.get(name) {
+      None => Ok(None),
+      Some(StateValue::Bool(val)) => Ok(Some(val)),
+      Some(StateValue::Number(_)) => Err(ffi::set_last_error(
+        ErrorCode::ParamOutOfRange,
+        &format!("expected {name} to be a boolean"),
+      )),
+    }
+  }
+
+  /// Non-finite numbers are written as `null`, which is treated as missing when parsed
+  pub fn to_json(&self) -> String { json::to_string(self) }
+
+  /// Parses a flat JSON object with number, boolean, or null values
+  pub fn from_json(json: &str) -> Result<Self, String> {
+    let entries: BTreeMap<String, Option<StateValue>> = json::from_str(json).map_err(|_| {
+      "state JSON must be an object with number, boolean, or null values".to_owned()
+    })?;
+    Ok(StateSnapshot {
+      entries: entries
+        .into_iter()
+        .filter_map(|(name, val)| Some((name, val?)))
+        .collect(),
+    })
+  }
+}
+
+// miniserde can only derive impls for structs with named fields, which doesn't fit a value that's
+// either a number or a boolean or a snapshot keyed by param name
+impl Serialize for StateValue {
+  fn begin(&self) -> Fragment<'_> {
+    match *self {
+      StateValue::Number(val) => Fragment::F64(val),
+      StateValue::Bool(val) => Fragment::Bool(val),
+    }
+  }
+}
+
+make_place!(Place);
+
+impl Visitor for Place<StateValue> {
+  fn boolean(&mut self, b: bool) -> miniserde::Result<()> {
+    self.out = Some(StateValue::Bool(b));
+    Ok(())
+  }
+
+  fn negative(&mut self, n: i64) -> miniserde::Result<()> { self.float(n as f64) }
+
+  fn nonnegative(&mut self, n: u64) -> miniserde::Result<()> { self.float(n as f64) }
+
+  fn float(&mut self, n: f64) -> miniserde::Result<()> {
+    self.out = Some(StateValue::Number(n));
+    Ok(())
+  }
+}
+
+impl Deserialize for StateValue {
+  fn begin(out: &mut Option<Self>) -> &mut dyn Visitor { Place::new(out) }
+}
+
+impl Serialize for StateSnapshot {
+  fn begin(&self) -> Fragment<'_> { Fragment::Map(Box::new(EntriesStream(self.entries.iter()))) }
+}
+
+struct EntriesStream<'a>(std::slice::Iter<'a, (String, StateValue)>);
+
+impl ser::Map for EntriesStream<'_> {
+  fn next(&mut self) -> Option<(Cow<'_, str>, &dyn Serialize)> {
+    let (name, val) = self.0.next()?;
+    Some((Cow::Borrowed(name), val))
+  }
+}
+
+pub trait ModuleState {
+  fn get_state(&self) -> StateSnapshot;
+
+  /// Params missing from `state` keep their current values and unknown params are ignored, so
+  /// state saved by older or newer versions of a module can still be loaded.  Nothing should be
+  /// applied if any param is invalid.
+  fn set_state(&mut self, state: &StateSnapshot) -> FfiResult;
+}
+
+/// Serializes `snapshot` into the state JSON buffer, returning its length in bytes
+pub fn write_state_json(snapshot: &StateSnapshot) -> usize {
+  let json = snapshot.to_json();
+  let buf = unsafe { &mut STATE_JSON_BUF };
+  buf.clear();
+  buf.extend_from_slice(json.as_bytes());
+  buf.len()
+}
+
+/// Parses the first `len` bytes of the state JSON buffer
+pub fn read_state_json(len: usize, export_name: &str) -> FfiResult<StateSnapshot> {
+  let buf = unsafe { &STATE_JSON_BUF };
+  ffi::check_range("len", len, 0, buf.len())?;
+  std::str::from_utf8(&buf[..len])
+    .map_err(|err| err.to_string())
+    .and_then(StateSnapshot::from_json)
+    .map_err(|err| {
+      ffi::set_last_error(ErrorCode::ParamOutOfRange, &format!("{export_name}: {err}"))
+    })
+}
+
+/// Serializes the module's state into the state JSON buffer, returning its length in bytes
+pub fn export_state<T: ModuleState>(module: &T) -> usize { write_state_json(&module.get_state()) }
+
+/// Loads state from the first `len` bytes of the state JSON buffer
+pub fn import_state<T: ModuleState>(module: &mut T, len: usize, export_name: &str) -> ErrorCode {
+  ffi::status(read_state_json(len, export_name).and_then(|snapshot| module.set_state(&snapshot)))
+}
+
+pub const AB_SLOT_COUNT: usize = 2;
+pub const MAX_AB_FADE_MS: f32 = 10_000.;
+
+/// A/B comparison between two param sets of a single module instance.  Params are stored in the
+/// module's own order as `f32`s, with booleans stored as 0 or 1.
+///
+/// The active slot, initially the first, tracks whatever params are in use.  Selecting a slot saves
+/// the current params into the active one before crossfading to the selected slot's params.  A
+/// slot that's never been selected starts as a copy of the current params.  While a fade is in
+/// progress the blended params override whatever the host passes in; once it completes, the host's
+/// params are used again, so the host should update its params to the selected slot when selecting
+/// it.
+#[derive(Clone, Debug, Default)]
+pub struct ABCompare {
+  slots: [Option<Vec<f32>>; AB_SLOT_COUNT],
+  active_slot: usize,
+  /// Params in use when the current fade started
+  fade_from: Vec<f32>,
+  fade_pos: usize,
+  fade_len: usize,
+  fading: bool,
+}
+
+impl ABCompare {
+  pub fn slot(&self, slot: usize) -> Option<&[f32]> {
+    self.slots.get(slot).and_then(|params| params.as_deref())
+  }
+
+  pub fn active_slot(&self) -> usize { self.active_slot }
+
+  /// `current` holds the params in use right now
+  pub fn select(&mut self, slot: usize, current: &[f32], fade_samples: usize) -> FfiResult {
+    ffi::check_range("slot", slot, 0, AB_SLOT_COUNT - 1)?;
+    // Mid-fade params are a blend rather than something the user set, so they aren't saved
+    if !self.fading {
+      self.slots[self.active_slot] = Some(current.to_vec());
+    }
+    self.active_slot = slot;
+
+    let Some(target) = &self.slots[slot] else {
+      self.slots[slot] = Some(current.to_vec());
+      return Ok(());
+    };
+    if target.len() != current.len() {
+      return Err(ffi::set_last_error(
+        ErrorCode::ParamOutOfRange,
+        &format!(
+          "A/B slot {slot} has {} params but {} were provided",
+          target.len(),
+          current.len()
+        ),
+      ));
+    }
+
+    self.fade_from.clear();
+    self.fade_from.extend_from_slice(current);
+    self.fade_pos = 0;
+    self.fade_len = fade_samples;
+    self.fading = true;
+    Ok(())
+  }
+
+  /// Advances the fade by `frame_size` samples and writes the blended params for the frame into
+  /// `params`.  Returns `false` without touching `params` if no fade is in progress.
+  pub fn tick(&mut self, frame_size: usize, params: &mut [f32]) -> bool {
+    if !self.fading {
+      return false;
+    }
+    let Some(target) = &self.slots[self.active_slot] else {
+      return false;
+    };
+
+    self.fade_pos = (self.fade_pos + frame_size).min(self.fade_len);
+    let t = if self.fade_len == 0 {
+      1.
+    } else {
+      self.fade_pos as f32 / self.fade_len as f32
+    };
+    for ((param, &from), &to) in params.iter_mut().zip(&self.fade_from).zip(target) {
+      *param = from + (to - from) * t;
+    }
+    self.fading = self.fade_pos < self.fade_len;
+    true
+  }
+}
+
+/// Returns a pointer to the JSON written by the most recent `*_get_state_json` call
+#[no_mangle]
+pub extern "C" fn get_state_json_ptr() -> *const u8 { unsafe { STATE_JSON_BUF.as_ptr() } }
+
+/// Resizes the state JSON buffer to `len` bytes and returns a pointer to it for JS to write state
+/// into before calling `*_set_state_json`
+#[no_mangle]
+pub extern "C" fn alloc_state_json_buf(len: usize) -> *mut u8 {
+  let buf = unsafe { &mut STATE_JSON_BUF };
+  buf.clear();
+  buf.resize(len, 0);
+  buf.as_mut_ptr()
+}
+
+#[test]
+fn state_json_roundtrip() {
+  let escaped_name = "threshold \"db\"\\\n\u{1}";
+  let mut snapshot = StateSnapshot::default();
+  snapshot.push_number("mix", 0.25);
+  snapshot.push_number(escaped_name, -24.);
+  snapshot.push_bool("auto_release", true);
+  snapshot.push_number("broken", f32::NAN);
+
+  let json = snapshot.to_json();
+  assert_eq!(
+    json,
+    r#"{"mix":0.25,"threshold \"db\"\\\n\u0001":-24.0,"auto_release":true,"broken":null}"#
+  );
+  let parsed = StateSnapshot::from_json(&json).unwrap();
+  assert_eq!(parsed.entries.len(), 3);
+  assert_eq!(parsed.number("mix"), Ok(Some(0.25)));
+  assert_eq!(parsed.number(escaped_name), Ok(Some(-24.)));
+  assert_eq!(parsed.bool("auto_release"), Ok(Some(true)));
+  assert_eq!(parsed.number("broken"), Ok(None));
+  assert_eq!(
+    parsed.number("auto_release"),
+    Err(ErrorCode::ParamOutOfRange)
+  );
+
+  let parsed = StateSnapshot::from_json(" { \"a\" : 1e-3 , \"b\":false, \"c\": -2 }\n").unwrap();
+  assert_eq!(parsed.get("a"), Some(StateValue::Number(1e-3)));
+  assert_eq!(parsed.get("c"), Some(StateValue::Number(-2.)));
+  assert_eq!(StateSnapshot::from_json("{}").unwrap().entries.len(), 0);
+  assert!(StateSnapshot::from_json("{\"a\":}").is_err());
+  assert!(StateSnapshot::from_json("{\"a\":1").is_err());
+  assert!(StateSnapshot::from_json("{\"a\":1}x").is_err());
+  assert!(StateSnapshot::from_json("{\"a\":\"1\"}").is_err());
+  assert!(StateSnapshot::from_json("[1]").is_err());
+}
+
+#[test]
+fn ab_compare_crossfades_between_slots() {
+  let mut ab = ABCompare::default();
+  let mut params = [0., 1.];
+  assert!(!ab.tick(128, &mut params));
+
+  assert_eq!(ab.active_slot(), 0);
+
+  // Switching to the unused B slot saves A and copies the current params into B without fading
+  ab.select(1, &[0., 1.], 256).unwrap();
+  assert_eq!(ab.slot(0), Some(&[0., 1.][..]));
+  assert_eq!(ab.slot(1), Some(&[0., 1.][..]));
+  assert!(!ab.tick(128, &mut params));
+
+  // Edits made while B is active are saved into it when switching back to A
+  ab.select(0, &[1., 0.], 256).unwrap();
+  assert_eq!(ab.slot(1), Some(&[1., 0.][..]));
+  assert!(ab.tick(128, &mut params));
+  assert_eq!(params, [0.5, 0.5]);
+  assert!(ab.tick(128, &mut params));
+  assert_eq!(params, [0., 1.]);
+  assert!(!ab.tick(128, &mut params));
+
+  // Switching mid-fade starts from the blended params without saving them
+  ab.select(1, &[0., 1.], 256).unwrap();
+  ab.tick(64, &mut params);
+  ab.select(0, &params, 0).unwrap();
+  assert_eq!(ab.slot(1), Some(&[1., 0.][..]));
+  assert!(ab.tick(64, &mut params));
+  assert_eq!(params, [0., 1.]);
+
+  assert_eq!(ab.select(2, &params, 0), Err(ErrorCode::ParamOutOfRange));
+}
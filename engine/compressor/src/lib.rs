@@ -1,11 +1,22 @@
+use common::{
+  ffi::{self, ErrorCode, FfiResult},
+  state::{ABCompare, ModuleState, StateSnapshot, MAX_AB_FADE_MS},
+};
 use dsp::{
   circular_buffer::CircularBuffer,
   db_to_gain,
-  filters::biquad::{compute_higher_order_biquad_q_factors, BiquadFilter, FilterMode},
-  gain_to_db, one_pole, SAMPLE_RATE,
+  filters::{
+    biquad::{compute_higher_order_biquad_q_factors, BiquadFilter, FilterMode},
+    dc_blocker::{
+      DCBlocker, MAX_CUTOFF_HZ as MAX_DC_BLOCKER_CUTOFF_HZ,
+      MIN_CUTOFF_HZ as MIN_DC_BLOCKER_CUTOFF_HZ,
+    },
+  },
+  gain_to_db,
+  smoothed_param::{SmoothedParam, SmoothingMode},
+  MAX_FRAME_SIZE, MAX_SAMPLE_RATE,
 };
-
-const FRAME_SIZE: usize = 128;
+use std::ptr;
 
 #[repr(u8)]
 #[derive(Clone, Copy)]
@@ -16,27 +27,117 @@ pub enum SensingMethod {
 
 const BAND_SPLITTER_FILTER_ORDER: usize = 16;
 const BAND_SPLITTER_FILTER_CHAIN_LENGTH: usize = BAND_SPLITTER_FILTER_ORDER / 2;
-const MAX_LOOKAHEAD_SAMPLES: usize = SAMPLE_RATE as usize / 10;
+/// Holds the max lookahead period plus the largest frame, rounded up to a power of two so that
+/// indexing into the lookahead buffers is a mask
+const MAX_LOOKAHEAD_SAMPLES: usize =
+  (MAX_SAMPLE_RATE as usize / 10 + MAX_FRAME_SIZE).next_power_of_two();
 const LOW_BAND_CUTOFF: f32 = 88.3;
 const MID_BAND_CUTOFF: f32 = 2500.;
 const SAB_SIZE: usize = 16;
+/// Ramp time for gain and mix changes coming from the UI
+const PARAM_SMOOTHING_MS: f32 = 20.;
 
-#[repr(C)]
-pub enum LogLevel {
-  Error = 0,
-  Warn = 1,
-  Info = 2,
-}
+// Valid ranges for the parameters passed to `process_compressor`.  Values outside of these are
+// clamped; NaNs are rejected.
+const MAX_GAIN: f32 = 20.;
+const MAX_ENVELOPE_TIME_MS: f32 = 1000.;
+const MIN_THRESHOLD_DB: f32 = -100.;
+const MAX_THRESHOLD_DB: f32 = 24.;
+const MAX_RATIO: f32 = 1024.;
+const MAX_KNEE_DB: f32 = 40.;
+/// The RMS window is read out of the lookahead buffers, so its max has to fit in them alongside a
+/// full frame just like the lookahead period does.
+const MIN_RMS_WINDOW_MS: f32 = 0.1;
+const MAX_RMS_WINDOW_MS: f32 = 100.;
+/// Matches the max of the `lookahead_ms` param in `CompressorAWP`, which always fits in the
+/// lookahead buffers
+const MAX_LOOKAHEAD_MS: f32 = 100.;
+/// In auto release mode, the slow envelope releases this many times slower than the configured
+/// release time
+const AUTO_RELEASE_SLOW_MULTIPLIER: f32 = 8.;
 
-extern "C" {
-  pub fn log_raw(ptr: *const u8, len: usize, level: LogLevel);
-}
+/// Layout version of the packed param block read by `process_compressor_packed`.  Must match
+/// `PACKED_PARAMS_VERSION` in `CompressorAWP`.
+const PACKED_PARAMS_VERSION: f32 = 1.;
+const PACKED_PARAMS_VERSION_IX: usize = 0;
+/// Set to 1 by the host whenever it writes new params into the block and cleared once they've been
+/// read
+const PACKED_PARAMS_DIRTY_IX: usize = 1;
+const PACKED_PARAMS_HEADER_LEN: usize = 2;
+/// Params follow the header in the same order as the arguments of `process_compressor`.  Auto
+/// release flags are 0 or 1.
+const PACKED_PARAM_COUNT: usize = 30;
+const PACKED_PARAMS_LEN: usize = PACKED_PARAMS_HEADER_LEN + PACKED_PARAM_COUNT;
 
-fn error(msg: &str) {
-  unsafe {
-    log_raw(msg.as_ptr(), msg.len(), LogLevel::Error);
-  }
-}
+/// Name, valid range, and default value of each numeric param included in the compressor's state.
+/// Names match the `AudioParam`s of `CompressorAWP`.
+const STATE_PARAMS: [(&str, f32, f32, f32); 27] = [
+  ("mix", 0., 1., 0.),
+  ("pre_gain", 0., MAX_GAIN, 1.),
+  ("post_gain", 0., MAX_GAIN, 1.),
+  ("low_band_gain", 0., MAX_GAIN, 1.),
+  ("mid_band_gain", 0., MAX_GAIN, 1.),
+  ("high_band_gain", 0., MAX_GAIN, 1.),
+  ("low_band_attack_ms", 0., MAX_ENVELOPE_TIME_MS, 3.),
+  ("low_band_release_ms", 0., MAX_ENVELOPE_TIME_MS, 300.),
+  ("mid_band_attack_ms", 0., MAX_ENVELOPE_TIME_MS, 3.),
+  ("mid_band_release_ms", 0., MAX_ENVELOPE_TIME_MS, 300.),
+  ("high_band_attack_ms", 0., MAX_ENVELOPE_TIME_MS, 3.),
+  ("high_band_release_ms", 0., MAX_ENVELOPE_TIME_MS, 300.),
+  (
+    "low_band_bottom_threshold_db",
+    MIN_THRESHOLD_DB,
+    MAX_THRESHOLD_DB,
+    -40.,
+  ),
+  (
+    "mid_band_bottom_threshold_db",
+    MIN_THRESHOLD_DB,
+    MAX_THRESHOLD_DB,
+    -40.,
+  ),
+  (
+    "high_band_bottom_threshold_db",
+    MIN_THRESHOLD_DB,
+    MAX_THRESHOLD_DB,
+    -40.,
+  ),
+  (
+    "low_band_top_threshold_db",
+    MIN_THRESHOLD_DB,
+    MAX_THRESHOLD_DB,
+    -20.,
+  ),
+  (
+    "mid_band_top_threshold_db",
+    MIN_THRESHOLD_DB,
+    MAX_THRESHOLD_DB,
+    -20.,
+  ),
+  (
+    "high_band_top_threshold_db",
+    MIN_THRESHOLD_DB,
+    MAX_THRESHOLD_DB,
+    -20.,
+  ),
+  ("low_band_bottom_ratio", 0., MAX_RATIO, 1.),
+  ("mid_band_bottom_ratio", 0., MAX_RATIO, 1.),
+  ("high_band_bottom_ratio", 0., MAX_RATIO, 1.),
+  ("low_band_top_ratio", 1., MAX_RATIO, 1.),
+  ("mid_band_top_ratio", 1., MAX_RATIO, 1.),
+  ("high_band_top_ratio", 1., MAX_RATIO, 1.),
+  ("knee", 0., MAX_KNEE_DB, 0.),
+  ("lookahead_ms", 0., MAX_LOOKAHEAD_MS, 0.),
+  ("rms_window_ms", MIN_RMS_WINDOW_MS, MAX_RMS_WINDOW_MS, 30.),
+];
+const STATE_AUTO_RELEASE_PARAMS: [&str; 3] = [
+  "low_band_auto_release",
+  "mid_band_auto_release",
+  "high_band_auto_release",
+];
+/// `STATE_PARAMS` followed by the auto release flags as 0 or 1, which is the layout used for A/B
+/// slots
+const FLAT_PARAM_COUNT: usize = STATE_PARAMS.len() + STATE_AUTO_RELEASE_PARAMS.len();
 
 // SAB Layout:
 // 0: low band detected level
@@ -56,28 +157,147 @@ fn error(msg: &str) {
 pub struct Compressor {
   pub bottom_envelope: f32,
   pub top_envelope: f32,
+  /// Slow envelopes used by auto release mode.  They track the sustained level of the input and
+  /// set the floor that the main envelopes release towards.
+  pub slow_bottom_envelope: f32,
+  pub slow_top_envelope: f32,
   pub last_detected_level_linear: f32,
   pub last_output_level_db: f32,
   pub last_applied_gain: f32,
-  pub lookback_period_squared_samples_sum: f32,
+  /// Running sum of the squares of the last `rms_window_samples` input samples
+  pub rms_window_squared_samples_sum: f32,
+  pub rms_window_samples: usize,
 }
 
 #[derive(Clone)]
 pub struct MultibandCompressor {
   pub sensing_method: SensingMethod,
-  pub input_buffer: [f32; FRAME_SIZE],
+  pub input_buffer: [f32; MAX_FRAME_SIZE],
   pub low_band_lookahead_buffer: CircularBuffer<MAX_LOOKAHEAD_SAMPLES>,
   pub mid_band_lookahead_buffer: CircularBuffer<MAX_LOOKAHEAD_SAMPLES>,
   pub high_band_lookahead_buffer: CircularBuffer<MAX_LOOKAHEAD_SAMPLES>,
+  /// The input as it came in, before the DC blocker, pre gain, and band splitting.  It's delayed
+  /// along with the bands so that the dry signal mixed in lines up with the compressed one.
+  pub dry_lookahead_buffer: CircularBuffer<MAX_LOOKAHEAD_SAMPLES>,
   pub low_band_filter_chain: [BiquadFilter; BAND_SPLITTER_FILTER_CHAIN_LENGTH],
   pub mid_band_filter_chain: [BiquadFilter; BAND_SPLITTER_FILTER_CHAIN_LENGTH * 2],
   pub high_band_filter_chain: [BiquadFilter; BAND_SPLITTER_FILTER_CHAIN_LENGTH],
   pub low_band_compressor: Compressor,
   pub mid_band_compressor: Compressor,
   pub high_band_compressor: Compressor,
-  pub output_buffer: [f32; FRAME_SIZE],
+  pub output_buffer: [f32; MAX_FRAME_SIZE],
   pub sab: [f32; SAB_SIZE],
-  pub mix_state: f32,
+  /// Blend between the dry input (0) and the compressed signal (1) for parallel compression
+  pub mix: SmoothedParam,
+  pub pre_gain: SmoothedParam,
+  pub post_gain: SmoothedParam,
+  /// Low, mid, and high
+  pub band_pre_gains: [SmoothedParam; 3],
+  /// Low, mid, and high
+  pub band_post_gains: [SmoothedParam; 3],
+  /// Removes DC from the input before it reaches the band splitter.  Upstream distortion often
+  /// leaves an offset which sits in the low band and holds its envelope up, causing it to compress
+  /// even when nothing audible is there.
+  pub input_dc_blocker: Option<DCBlocker>,
+  /// Skips band splitting and runs the whole signal through the mid band's compressor using the
+  /// mid band's params, which avoids the cost of the crossover filters when a simple compressor
+  /// is all that's needed
+  pub wideband: bool,
+  /// Clamped params from the most recent `process_compressor` call in the order of
+  /// `STATE_PARAMS`, used to snapshot the compressor's state
+  pub last_params: [f32; STATE_PARAMS.len()],
+  /// Low, mid, and high
+  pub last_auto_release: [bool; 3],
+  pub ab: ABCompare,
+  /// Written by the host when using `process_compressor_packed`.  See `PACKED_PARAMS_*`.
+  pub packed_params: [f32; PACKED_PARAMS_LEN],
+  /// Params decoded from `packed_params` the last time it was marked dirty
+  decoded_packed_params: ProcessParams,
+}
+
+/// Raw, unclamped params for a single call to `process_compressor`
+#[derive(Clone, Copy, Default)]
+struct ProcessParams {
+  mix: f32,
+  pre_gain: f32,
+  post_gain: f32,
+  low_band_pre_gain: f32,
+  mid_band_pre_gain: f32,
+  high_band_pre_gain: f32,
+  low_band_attack_ms: f32,
+  low_band_release_ms: f32,
+  mid_band_attack_ms: f32,
+  mid_band_release_ms: f32,
+  high_band_attack_ms: f32,
+  high_band_release_ms: f32,
+  low_band_auto_release: bool,
+  mid_band_auto_release: bool,
+  high_band_auto_release: bool,
+  low_band_bottom_threshold_db: f32,
+  mid_band_bottom_threshold_db: f32,
+  high_band_bottom_threshold_db: f32,
+  low_band_top_threshold_db: f32,
+  mid_band_top_threshold_db: f32,
+  high_band_top_threshold_db: f32,
+  low_band_bottom_ratio: f32,
+  mid_band_bottom_ratio: f32,
+  high_band_bottom_ratio: f32,
+  low_band_top_ratio: f32,
+  mid_band_top_ratio: f32,
+  high_band_top_ratio: f32,
+  knee: f32,
+  lookahead_samples: usize,
+  rms_window_ms: f32,
+}
+
+impl ProcessParams {
+  fn from_packed(packed: &[f32; PACKED_PARAM_COUNT]) -> Self {
+    #[rustfmt::skip]
+    let [
+      mix, pre_gain, post_gain, low_band_pre_gain, mid_band_pre_gain, high_band_pre_gain,
+      low_band_attack_ms, low_band_release_ms, mid_band_attack_ms, mid_band_release_ms,
+      high_band_attack_ms, high_band_release_ms, low_band_auto_release, mid_band_auto_release,
+      high_band_auto_release, low_band_bottom_threshold_db, mid_band_bottom_threshold_db,
+      high_band_bottom_threshold_db, low_band_top_threshold_db, mid_band_top_threshold_db,
+      high_band_top_threshold_db, low_band_bottom_ratio, mid_band_bottom_ratio,
+      high_band_bottom_ratio, low_band_top_ratio, mid_band_top_ratio, high_band_top_ratio, knee,
+      lookahead_samples, rms_window_ms,
+    ] = *packed;
+
+    ProcessParams {
+      mix,
+      pre_gain,
+      post_gain,
+      low_band_pre_gain,
+      mid_band_pre_gain,
+      high_band_pre_gain,
+      low_band_attack_ms,
+      low_band_release_ms,
+      mid_band_attack_ms,
+      mid_band_release_ms,
+      high_band_attack_ms,
+      high_band_release_ms,
+      low_band_auto_release: low_band_auto_release >= 0.5,
+      mid_band_auto_release: mid_band_auto_release >= 0.5,
+      high_band_auto_release: high_band_auto_release >= 0.5,
+      low_band_bottom_threshold_db,
+      mid_band_bottom_threshold_db,
+      high_band_bottom_threshold_db,
+      low_band_top_threshold_db,
+      mid_band_top_threshold_db,
+      high_band_top_threshold_db,
+      low_band_bottom_ratio,
+      mid_band_bottom_ratio,
+      high_band_bottom_ratio,
+      low_band_top_ratio,
+      mid_band_top_ratio,
+      high_band_top_ratio,
+      knee,
+      // Negative and NaN lookaheads saturate to 0 and are then range checked like usual
+      lookahead_samples: lookahead_samples as usize,
+      rms_window_ms,
+    }
+  }
 }
 
 impl Default for MultibandCompressor {
@@ -143,47 +363,72 @@ impl Default for MultibandCompressor {
 
     Self {
       sensing_method: SensingMethod::Peak,
-      input_buffer: [0.0; FRAME_SIZE],
+      input_buffer: [0.0; MAX_FRAME_SIZE],
       low_band_lookahead_buffer: CircularBuffer::new(),
       mid_band_lookahead_buffer: CircularBuffer::new(),
       high_band_lookahead_buffer: CircularBuffer::new(),
+      dry_lookahead_buffer: CircularBuffer::new(),
       low_band_filter_chain,
       mid_band_filter_chain,
       high_band_filter_chain,
       low_band_compressor: Compressor::default(),
       mid_band_compressor: Compressor::default(),
       high_band_compressor: Compressor::default(),
-      output_buffer: [0.0; FRAME_SIZE],
+      output_buffer: [0.0; MAX_FRAME_SIZE],
       sab: [0.0; SAB_SIZE],
-      mix_state: 0.,
+      mix: SmoothedParam::new(SmoothingMode::Exponential, 0., PARAM_SMOOTHING_MS),
+      pre_gain: SmoothedParam::new(SmoothingMode::Linear, 1., PARAM_SMOOTHING_MS),
+      post_gain: SmoothedParam::new(SmoothingMode::Linear, 1., PARAM_SMOOTHING_MS),
+      band_pre_gains: std::array::from_fn(|_| {
+        SmoothedParam::new(SmoothingMode::Linear, 1., PARAM_SMOOTHING_MS)
+      }),
+      band_post_gains: std::array::from_fn(|_| {
+        SmoothedParam::new(SmoothingMode::Linear, 1., PARAM_SMOOTHING_MS)
+      }),
+      input_dc_blocker: None,
+      wideband: false,
+      last_params: STATE_PARAMS.map(|(_, _, _, default)| default),
+      last_auto_release: [false; 3],
+      ab: ABCompare::default(),
+      packed_params: [0.; PACKED_PARAMS_LEN],
+      decoded_packed_params: ProcessParams::default(),
     }
   }
 }
 
 fn apply_filter_chain_full<const N: usize>(
   chain: &mut [BiquadFilter; N],
-  input_buf: [f32; FRAME_SIZE],
+  input_buf: &[f32],
   output_lookahead_buf: &mut CircularBuffer<MAX_LOOKAHEAD_SAMPLES>,
-  gain: f32,
+  gain: &mut SmoothedParam,
 ) {
-  let mut filtered = input_buf;
+  let mut filtered = [0.; MAX_FRAME_SIZE];
+  let filtered = &mut filtered[..input_buf.len()];
+  filtered.copy_from_slice(input_buf);
   for filter in chain.iter_mut() {
-    for i in 0..FRAME_SIZE {
-      filtered[i] = filter.apply(filtered[i]);
+    for sample in filtered.iter_mut() {
+      *sample = filter.apply(*sample);
     }
   }
 
-  for i in 0..FRAME_SIZE {
-    output_lookahead_buf.set(filtered[i] * gain);
+  for sample in filtered.iter_mut() {
+    *sample *= gain.tick();
   }
+  output_lookahead_buf.write_block(filtered);
 }
 
+/// Quality policy: peak detection only checks every `n`th sample in the lookahead window when
+/// quality is reduced
+fn peak_detection_stride() -> usize { dsp::quality::quality_level().pick(1, 2, 4) }
+
 #[inline(never)]
 fn detect_level_peak(
   buf: &CircularBuffer<MAX_LOOKAHEAD_SAMPLES>,
   lookahead_samples: isize,
+  frame_size: isize,
   sample_ix_in_frame: usize,
   old_max: f32,
+  stride: usize,
 ) -> f32 {
   // Try to fast-path.  If the old max hasn't been removed from the lookahead buffer yet and it's
   // still the max, then we can just return it.
@@ -197,9 +442,11 @@ fn detect_level_peak(
   // }
 
   // Might be cool to SIMD-ize this if we can't figure out a more efficient level detection method
+  //
+  // Iterating backwards makes sure that the newest sample is always checked when striding
   let mut max = 0.;
-  for i in 0..lookahead_samples {
-    let ix = -lookahead_samples - FRAME_SIZE as isize + sample_ix_in_frame as isize + i;
+  for i in (0..lookahead_samples).rev().step_by(stride) {
+    let ix = -lookahead_samples - frame_size + sample_ix_in_frame as isize + i;
     let abs_sample = buf.get(ix).abs();
     if abs_sample > max {
       max = abs_sample;
@@ -208,48 +455,57 @@ fn detect_level_peak(
   max
 }
 
-/// Given the attack time in milliseconds, compute the coefficient for a one-pole lowpass filter to
-/// be used in the envelope follower.
-fn compute_attack_coefficient(attack_time_ms: f32) -> f32 {
-  let attack_time_s = (attack_time_ms * 0.001).max(0.0001);
-  let attack_time_samples = attack_time_s * SAMPLE_RATE;
-  let attack_coefficient = 1. - 1. / attack_time_samples;
-  attack_coefficient
-}
-
-/// Given the release time in milliseconds, compute the coefficient for a one-pole highpass filter
-/// to be used in the envelope follower.
-fn compute_release_coefficient(release_time_ms: f32) -> f32 {
-  let release_time_s = (release_time_ms * 0.001).max(0.0001);
-  let release_time_samples = release_time_s * SAMPLE_RATE;
-  let release_coefficient = 1. / release_time_samples;
-  release_coefficient
+/// Given an attack or release time in milliseconds, computes the coefficient for the one-pole
+/// lowpass filter used in the envelope follower.  The result is always in `[0, 1)`, where 0 means
+/// that the envelope jumps straight to the detected level; that's what a time of zero or anything
+/// shorter than a sample gives.
+fn compute_envelope_coefficient(time_ms: f32) -> f32 {
+  let time_samples = dsp::ms_to_samples(time_ms);
+  if time_samples.is_nan() || time_samples <= 0. {
+    return 0.;
+  }
+  (-1. / time_samples).exp()
 }
 
-/// Given a frame of samples, computes the average volume of the frame in decibels.
+/// Computes the RMS level over the `rms_window_samples` samples leading up to and including the
+/// newest sample at `sample_ix_in_frame`, updating the running sum of squares in O(1).
 fn detect_level_rms(
   buf: &CircularBuffer<MAX_LOOKAHEAD_SAMPLES>,
-  lookahead_samples: isize,
+  rms_window_samples: isize,
+  frame_size: isize,
   sample_ix_in_frame: usize,
-  lookback_period_squared_samples_sum: &mut f32,
+  rms_window_squared_samples_sum: &mut f32,
 ) -> f32 {
-  let prev_ix = -lookahead_samples - FRAME_SIZE as isize + sample_ix_in_frame as isize - 1;
-  let removed_sample = buf.get(prev_ix);
-  *lookback_period_squared_samples_sum -= removed_sample * removed_sample;
+  let cur_ix = -frame_size + sample_ix_in_frame as isize;
+  let removed_sample = buf.get(cur_ix - rms_window_samples);
+  *rms_window_squared_samples_sum -= removed_sample * removed_sample;
 
-  let cur_ix = -(FRAME_SIZE as isize) + sample_ix_in_frame as isize;
   let cur_sample = buf.get(cur_ix);
-  *lookback_period_squared_samples_sum += cur_sample * cur_sample;
+  *rms_window_squared_samples_sum += cur_sample * cur_sample;
 
-  if *lookback_period_squared_samples_sum < 0.0001 {
-    *lookback_period_squared_samples_sum = 0.;
-  } else if lookback_period_squared_samples_sum.is_nan()
-    || lookback_period_squared_samples_sum.is_infinite()
+  if *rms_window_squared_samples_sum < 0.0001 {
+    *rms_window_squared_samples_sum = 0.;
+  } else if rms_window_squared_samples_sum.is_nan() || rms_window_squared_samples_sum.is_infinite()
   {
-    panic!("{}, {}", *lookback_period_squared_samples_sum, cur_sample);
+    panic!("{}, {}", *rms_window_squared_samples_sum, cur_sample);
   }
 
-  (*lookback_period_squared_samples_sum / lookahead_samples as f32).sqrt()
+  (*rms_window_squared_samples_sum / rms_window_samples as f32).sqrt()
+}
+
+/// Sums the squares of the `rms_window_samples` samples preceding the first sample of the current
+/// frame.  Used to re-seed the running sum when the window size changes.
+fn compute_rms_window_squared_samples_sum(
+  buf: &CircularBuffer<MAX_LOOKAHEAD_SAMPLES>,
+  rms_window_samples: isize,
+  frame_size: isize,
+) -> f32 {
+  let mut sum = 0.;
+  for ix in (-frame_size - rms_window_samples)..-frame_size {
+    let sample = buf.get(ix);
+    sum += sample * sample;
+  }
+  sum
 }
 
 impl Compressor {
@@ -257,52 +513,98 @@ impl Compressor {
     &mut self,
     input_buf: &CircularBuffer<MAX_LOOKAHEAD_SAMPLES>,
     lookahead_samples: usize,
-    output_buf: &mut [f32; FRAME_SIZE],
+    rms_window_samples: usize,
+    output_buf: &mut [f32],
     attack_ms: f32,
     release_ms: f32,
+    auto_release: bool,
     bottom_threshold_db: f32,
     top_threshold_db: f32,
     bottom_ratio: f32,
     top_ratio: f32,
     knee: f32,
     sensing_method: SensingMethod,
-    post_gain: f32,
+    post_gain: &[f32],
   ) -> f32 {
     let mut bottom_envelope = self.bottom_envelope;
     let mut top_envelope = self.top_envelope;
+    let mut slow_bottom_envelope = self.slow_bottom_envelope;
+    let mut slow_top_envelope = self.slow_top_envelope;
 
     let lookahead_samples = lookahead_samples as isize;
-    let attack_coefficient = compute_attack_coefficient(attack_ms);
-    let release_coefficient = compute_release_coefficient(release_ms);
+    let frame_size = output_buf.len() as isize;
+    if rms_window_samples != self.rms_window_samples {
+      self.rms_window_samples = rms_window_samples;
+      self.rms_window_squared_samples_sum =
+        compute_rms_window_squared_samples_sum(input_buf, rms_window_samples as isize, frame_size);
+    }
+    let attack_coefficient = compute_envelope_coefficient(attack_ms);
+    let release_coefficient = compute_envelope_coefficient(release_ms);
+    let slow_release_coefficient =
+      compute_envelope_coefficient(release_ms * AUTO_RELEASE_SLOW_MULTIPLIER);
 
     let mut detected_level_db = self.last_output_level_db;
     let mut detected_level_linear = self.last_detected_level_linear;
     let mut target_volume_db = detected_level_db;
     let mut gain = 1.;
+    let peak_detection_stride = peak_detection_stride();
 
-    for i in 0..FRAME_SIZE {
-      let input = input_buf.get(-lookahead_samples - FRAME_SIZE as isize + i as isize);
+    for i in 0..output_buf.len() {
+      let input = input_buf.get(-lookahead_samples - frame_size + i as isize);
 
       detected_level_linear = match sensing_method {
-        SensingMethod::Peak =>
-          detect_level_peak(input_buf, lookahead_samples, i, detected_level_linear),
-        SensingMethod::RMS => detect_level_rms(
+        SensingMethod::Peak => detect_level_peak(
           input_buf,
           lookahead_samples,
+          frame_size,
           i,
-          &mut self.lookback_period_squared_samples_sum,
+          detected_level_linear,
+          peak_detection_stride,
+        ),
+        SensingMethod::RMS => detect_level_rms(
+          input_buf,
+          rms_window_samples as isize,
+          frame_size,
+          i,
+          &mut self.rms_window_squared_samples_sum,
         ),
       };
 
       detected_level_db = gain_to_db(detected_level_linear);
 
+      // In auto release mode, the envelopes release quickly down to the slow envelopes rather
+      // than all the way to the detected level.  Short transients are recovered from quickly,
+      // while sustained level changes release at the slow rate.
+      let (top_release_target_db, bottom_release_target_db) = if auto_release {
+        if detected_level_db > slow_top_envelope {
+          slow_top_envelope =
+            attack_coefficient * slow_top_envelope + (1. - attack_coefficient) * detected_level_db;
+        } else {
+          slow_top_envelope = slow_release_coefficient * slow_top_envelope
+            + (1. - slow_release_coefficient) * detected_level_db;
+        }
+        if detected_level_db < slow_bottom_envelope {
+          slow_bottom_envelope = attack_coefficient * slow_bottom_envelope
+            + (1. - attack_coefficient) * detected_level_db;
+        } else {
+          slow_bottom_envelope = slow_release_coefficient * slow_bottom_envelope
+            + (1. - slow_release_coefficient) * detected_level_db;
+        }
+        (
+          detected_level_db.max(slow_top_envelope),
+          detected_level_db.min(slow_bottom_envelope),
+        )
+      } else {
+        (detected_level_db, detected_level_db)
+      };
+
       // Compute the envelope
       if detected_level_db > top_envelope {
         top_envelope =
           attack_coefficient * top_envelope + (1. - attack_coefficient) * detected_level_db;
       } else {
         top_envelope =
-          release_coefficient * top_envelope + (1. - release_coefficient) * detected_level_db;
+          release_coefficient * top_envelope + (1. - release_coefficient) * top_release_target_db;
       }
       if cfg!(debug_assertions) && (top_envelope.is_nan() || top_envelope.is_infinite()) {
         panic!(
@@ -317,8 +619,8 @@ impl Compressor {
         bottom_envelope =
           attack_coefficient * bottom_envelope + (1. - attack_coefficient) * detected_level_db;
       } else {
-        bottom_envelope =
-          release_coefficient * bottom_envelope + (1. - release_coefficient) * detected_level_db;
+        bottom_envelope = release_coefficient * bottom_envelope
+          + (1. - release_coefficient) * bottom_release_target_db;
       }
       if cfg!(debug_assertions) && (bottom_envelope.is_nan() || bottom_envelope.is_infinite()) {
         panic!(
@@ -360,6 +662,7 @@ impl Compressor {
         1.
       };
 
+      let post_gain = post_gain[i];
       let output = input * gain * post_gain;
       if cfg!(debug_assertions) && output.is_infinite() || output.is_nan() {
         panic!("input={input}, gain={gain}, post_gain={post_gain}, output={output}");
@@ -369,6 +672,15 @@ impl Compressor {
 
     self.bottom_envelope = bottom_envelope;
     self.top_envelope = top_envelope;
+    // Keep the slow envelopes following along while auto release is off so that switching it on
+    // doesn't cause a jump
+    if auto_release {
+      self.slow_bottom_envelope = slow_bottom_envelope;
+      self.slow_top_envelope = slow_top_envelope;
+    } else {
+      self.slow_bottom_envelope = bottom_envelope;
+      self.slow_top_envelope = top_envelope;
+    }
     self.last_detected_level_linear = detected_level_linear;
     self.last_output_level_db = target_volume_db;
     self.last_applied_gain = gain;
@@ -376,37 +688,99 @@ impl Compressor {
   }
 }
 
+/// Multiplies `buf` by the smoothed gain, skipping the work once the gain has settled at 1
+fn apply_smoothed_gain(gain: &mut SmoothedParam, target: f32, buf: &mut [f32]) {
+  gain.set_target(target);
+  if !gain.is_smoothing() && gain.current() == 1. {
+    return;
+  }
+
+  for sample in buf {
+    *sample *= gain.tick();
+  }
+}
+
+/// Renders the per-sample post gain for a band, scaled by the wet mix
+fn render_band_post_gain(gain: &mut SmoothedParam, target: f32, mix: &[f32], out: &mut [f32]) {
+  gain.set_target(target);
+  gain.fill(out);
+  for (gain, mix) in out.iter_mut().zip(mix) {
+    *gain *= mix;
+  }
+}
+
 impl MultibandCompressor {
+  /// Clears all filter, lookahead, and envelope state in place.  Gain and mix smoothing skips
+  /// straight to the current targets.
+  pub fn reset(&mut self) {
+    for filter in self
+      .low_band_filter_chain
+      .iter_mut()
+      .chain(self.mid_band_filter_chain.iter_mut())
+      .chain(self.high_band_filter_chain.iter_mut())
+    {
+      filter.reset();
+    }
+    if let Some(dc_blocker) = &mut self.input_dc_blocker {
+      dc_blocker.reset();
+    }
+    self.low_band_lookahead_buffer.clear();
+    self.mid_band_lookahead_buffer.clear();
+    self.high_band_lookahead_buffer.clear();
+    self.dry_lookahead_buffer.clear();
+    self.low_band_compressor = Compressor::default();
+    self.mid_band_compressor = Compressor::default();
+    self.high_band_compressor = Compressor::default();
+    self.output_buffer.fill(0.);
+    self.sab.fill(0.);
+    for param in [&mut self.mix, &mut self.pre_gain, &mut self.post_gain]
+      .into_iter()
+      .chain(self.band_pre_gains.iter_mut())
+      .chain(self.band_post_gains.iter_mut())
+    {
+      param.set_immediate(param.target());
+    }
+  }
+
   #[inline]
   pub fn apply_bandsplitting(
     &mut self,
+    frame_size: usize,
     low_band_gain: f32,
     mid_band_gain: f32,
     high_band_gain: f32,
   ) {
+    let input = &self.input_buffer[..frame_size];
+    let [low_band_pre_gain, mid_band_pre_gain, high_band_pre_gain] = &mut self.band_pre_gains;
+    low_band_pre_gain.set_target(low_band_gain);
+    mid_band_pre_gain.set_target(mid_band_gain);
+    high_band_pre_gain.set_target(high_band_gain);
+
     apply_filter_chain_full(
       &mut self.low_band_filter_chain,
-      self.input_buffer,
+      input,
       &mut self.low_band_lookahead_buffer,
-      low_band_gain,
+      low_band_pre_gain,
     );
     apply_filter_chain_full(
       &mut self.mid_band_filter_chain,
-      self.input_buffer,
+      input,
       &mut self.mid_band_lookahead_buffer,
-      mid_band_gain,
+      mid_band_pre_gain,
     );
     apply_filter_chain_full(
       &mut self.high_band_filter_chain,
-      self.input_buffer,
+      input,
       &mut self.high_band_lookahead_buffer,
-      high_band_gain,
+      high_band_pre_gain,
     );
   }
 
+  /// Processes the first `frame_size` samples of the input buffer into the output buffer
   #[inline]
   pub fn apply(
     &mut self,
+    frame_size: usize,
     mix: f32,
     pre_gain: f32,
     post_gain: f32,
@@ -419,6 +793,9 @@ impl MultibandCompressor {
     mid_band_release_ms: f32,
     high_band_attack_ms: f32,
     high_band_release_ms: f32,
+    low_band_auto_release: bool,
+    mid_band_auto_release: bool,
+    high_band_auto_release: bool,
     low_band_bottom_threshold_db: f32,
     mid_band_bottom_threshold_db: f32,
     high_band_bottom_threshold_db: f32,
@@ -433,110 +810,201 @@ impl MultibandCompressor {
     high_band_top_ratio: f32,
     knee: f32,
     lookahead_samples: usize,
+    rms_window_samples: usize,
     low_band_post_gain: f32,
     mid_band_post_gain: f32,
     high_band_post_gain: f32,
   ) {
-    // apply pre gain
-    if pre_gain != 1. {
-      for i in 0..FRAME_SIZE {
-        self.input_buffer[i] *= pre_gain;
-      }
+    self
+      .dry_lookahead_buffer
+      .write_block(&self.input_buffer[..frame_size]);
+    if let Some(dc_blocker) = &mut self.input_dc_blocker {
+      dc_blocker.apply_all(&mut self.input_buffer[..frame_size]);
     }
+    apply_smoothed_gain(
+      &mut self.pre_gain,
+      pre_gain,
+      &mut self.input_buffer[..frame_size],
+    );
 
-    self.apply_bandsplitting(low_band_pre_gain, mid_band_pre_gain, high_band_pre_gain);
+    if self.wideband {
+      let mid_band_pre_gain_param = &mut self.band_pre_gains[1];
+      mid_band_pre_gain_param.set_target(mid_band_pre_gain);
+      apply_filter_chain_full(
+        &mut [],
+        &self.input_buffer[..frame_size],
+        &mut self.mid_band_lookahead_buffer,
+        mid_band_pre_gain_param,
+      );
+    } else {
+      self.apply_bandsplitting(
+        frame_size,
+        low_band_pre_gain,
+        mid_band_pre_gain,
+        high_band_pre_gain,
+      );
+    }
 
-    self.output_buffer.fill(0.);
-    let mix = one_pole(&mut self.mix_state, mix, 0.1);
-    if mix != 1. {
+    let output_buffer = &mut self.output_buffer[..frame_size];
+    output_buffer.fill(0.);
+    let mut mix_buf = [0.; MAX_FRAME_SIZE];
+    let mix_buf = &mut mix_buf[..frame_size];
+    self.mix.set_target(mix);
+    self.mix.fill(mix_buf);
+    if mix_buf.iter().any(|&mix| mix != 1.) {
       let lookahead_samples = lookahead_samples as isize;
-      for input_buf in &[
-        &self.low_band_lookahead_buffer,
-        &self.mid_band_lookahead_buffer,
-        &self.high_band_lookahead_buffer,
-      ] {
-        for i in 0..FRAME_SIZE {
-          let ix = -lookahead_samples - FRAME_SIZE as isize + i as isize;
-          let input = input_buf.get(ix);
-          self.output_buffer[i] += input * (1. - mix);
-        }
+      for (i, (output, mix)) in output_buffer.iter_mut().zip(mix_buf.iter()).enumerate() {
+        let ix = -lookahead_samples - frame_size as isize + i as isize;
+        *output += self.dry_lookahead_buffer.get(ix) * (1. - mix);
       }
     }
 
     // Apply compression to each band
     let sensing_method = SensingMethod::RMS;
-    let low_band_detected_level = self.low_band_compressor.apply(
-      &self.low_band_lookahead_buffer,
-      lookahead_samples,
-      &mut self.output_buffer,
-      low_band_attack_ms,
-      low_band_release_ms,
-      low_band_bottom_threshold_db,
-      low_band_top_threshold_db,
-      low_band_bottom_ratio,
-      low_band_top_ratio,
-      knee,
-      sensing_method,
-      low_band_post_gain * mix,
+    let mut band_post_gain_buf = [0.; MAX_FRAME_SIZE];
+    let band_post_gain_buf = &mut band_post_gain_buf[..frame_size];
+    if !self.wideband {
+      render_band_post_gain(
+        &mut self.band_post_gains[0],
+        low_band_post_gain,
+        mix_buf,
+        band_post_gain_buf,
+      );
+      let low_band_detected_level = self.low_band_compressor.apply(
+        &self.low_band_lookahead_buffer,
+        lookahead_samples,
+        rms_window_samples,
+        output_buffer,
+        low_band_attack_ms,
+        low_band_release_ms,
+        low_band_auto_release,
+        low_band_bottom_threshold_db,
+        low_band_top_threshold_db,
+        low_band_bottom_ratio,
+        low_band_top_ratio,
+        knee,
+        sensing_method,
+        band_post_gain_buf,
+      );
+      self.sab[0] = low_band_detected_level;
+      self.sab[3] = self.low_band_compressor.bottom_envelope;
+      self.sab[6] = self.low_band_compressor.last_output_level_db;
+      self.sab[9] = self.low_band_compressor.last_applied_gain;
+    }
+    render_band_post_gain(
+      &mut self.band_post_gains[1],
+      mid_band_post_gain,
+      mix_buf,
+      band_post_gain_buf,
     );
-    self.sab[0] = low_band_detected_level;
-    self.sab[3] = self.low_band_compressor.bottom_envelope;
-    self.sab[6] = self.low_band_compressor.last_output_level_db;
-    self.sab[9] = self.low_band_compressor.last_applied_gain;
     let mid_band_detected_level = self.mid_band_compressor.apply(
       &self.mid_band_lookahead_buffer,
       lookahead_samples,
-      &mut self.output_buffer,
+      rms_window_samples,
+      output_buffer,
       mid_band_attack_ms,
       mid_band_release_ms,
+      mid_band_auto_release,
       mid_band_bottom_threshold_db,
       mid_band_top_threshold_db,
       mid_band_bottom_ratio,
       mid_band_top_ratio,
       knee,
       sensing_method,
-      mid_band_post_gain * mix,
+      band_post_gain_buf,
     );
     self.sab[1] = mid_band_detected_level;
     self.sab[4] = self.mid_band_compressor.bottom_envelope;
     self.sab[7] = self.mid_band_compressor.last_output_level_db;
     self.sab[10] = self.mid_band_compressor.last_applied_gain;
-    let high_band_detected_level = self.high_band_compressor.apply(
-      &self.high_band_lookahead_buffer,
-      lookahead_samples,
-      &mut self.output_buffer,
-      high_band_attack_ms,
-      high_band_release_ms,
-      high_band_bottom_threshold_db,
-      high_band_top_threshold_db,
-      high_band_bottom_ratio,
-      high_band_top_ratio,
-      knee,
-      sensing_method,
-      high_band_post_gain * mix,
-    );
-    self.sab[2] = high_band_detected_level;
-    self.sab[5] = self.high_band_compressor.bottom_envelope;
-    self.sab[8] = self.high_band_compressor.last_output_level_db;
-    self.sab[11] = self.high_band_compressor.last_applied_gain;
-
-    // apply post gain
-    if post_gain != 1. {
-      for i in 0..FRAME_SIZE {
-        self.output_buffer[i] *= post_gain;
+    if !self.wideband {
+      render_band_post_gain(
+        &mut self.band_post_gains[2],
+        high_band_post_gain,
+        mix_buf,
+        band_post_gain_buf,
+      );
+      let high_band_detected_level = self.high_band_compressor.apply(
+        &self.high_band_lookahead_buffer,
+        lookahead_samples,
+        rms_window_samples,
+        output_buffer,
+        high_band_attack_ms,
+        high_band_release_ms,
+        high_band_auto_release,
+        high_band_bottom_threshold_db,
+        high_band_top_threshold_db,
+        high_band_bottom_ratio,
+        high_band_top_ratio,
+        knee,
+        sensing_method,
+        band_post_gain_buf,
+      );
+      self.sab[2] = high_band_detected_level;
+      self.sab[5] = self.high_band_compressor.bottom_envelope;
+      self.sab[8] = self.high_band_compressor.last_output_level_db;
+      self.sab[11] = self.high_band_compressor.last_applied_gain;
+    }
+
+    apply_smoothed_gain(&mut self.post_gain, post_gain, output_buffer);
+  }
+}
+
+impl MultibandCompressor {
+  fn flat_params(&self) -> [f32; FLAT_PARAM_COUNT] {
+    let mut params = [0.; FLAT_PARAM_COUNT];
+    let (numbers, flags) = params.split_at_mut(STATE_PARAMS.len());
+    numbers.copy_from_slice(&self.last_params);
+    for (flag, &val) in flags.iter_mut().zip(&self.last_auto_release) {
+      *flag = if val { 1. } else { 0. };
+    }
+    params
+  }
+}
+
+fn flat_params_to_state(params: &[f32]) -> StateSnapshot {
+  let (numbers, flags) = params.split_at(STATE_PARAMS.len());
+  let mut state = StateSnapshot::default();
+  for ((name, ..), &val) in STATE_PARAMS.iter().zip(numbers) {
+    state.push_number(name, val);
+  }
+  for (name, &val) in STATE_AUTO_RELEASE_PARAMS.iter().zip(flags) {
+    state.push_bool(name, val >= 0.5);
+  }
+  state
+}
+
+/// State is the params from the most recent `process_compressor` call.  Those params are passed
+/// in every frame, so restoring state only records it; `CompressorAWP` relays the restored state
+/// to its `AudioParam`s.
+impl ModuleState for MultibandCompressor {
+  fn get_state(&self) -> StateSnapshot { flat_params_to_state(&self.flat_params()) }
+
+  fn set_state(&mut self, state: &StateSnapshot) -> FfiResult {
+    let mut params = self.last_params;
+    for (&(name, min, max, _), param) in STATE_PARAMS.iter().zip(&mut params) {
+      if let Some(val) = state.number(name)? {
+        ffi::check_range(name, val, min, max)?;
+        *param = val;
       }
     }
+    let mut auto_release = self.last_auto_release;
+    for (name, param) in STATE_AUTO_RELEASE_PARAMS.iter().zip(&mut auto_release) {
+      if let Some(val) = state.bool(name)? {
+        *param = val;
+      }
+    }
+
+    self.last_params = params;
+    self.last_auto_release = auto_release;
+    Ok(())
   }
 }
 
 #[no_mangle]
 pub extern "C" fn init_compressor() -> *mut MultibandCompressor {
-  use std::fmt::Write;
   std::panic::set_hook(Box::new(|panic_info| {
-    // log with `error`
-    let mut buf = String::new();
-    let _ = write!(buf, "panic: {:?}", panic_info);
-    error(&buf);
+    logging::error!("panic: {:?}", panic_info);
   }));
 
   let compressor = MultibandCompressor::default();
@@ -576,6 +1044,9 @@ pub extern "C" fn process_compressor(
   mid_band_release_ms: f32,
   high_band_attack_ms: f32,
   high_band_release_ms: f32,
+  low_band_auto_release: bool,
+  mid_band_auto_release: bool,
+  high_band_auto_release: bool,
   low_band_bottom_threshold_db: f32,
   mid_band_bottom_threshold_db: f32,
   high_band_bottom_threshold_db: f32,
@@ -590,22 +1061,97 @@ pub extern "C" fn process_compressor(
   high_band_top_ratio: f32,
   knee: f32,
   lookahead_samples: usize,
-) {
-  // let low_band_pre_gain = low_band_pre_gain * db_to_gain(5.2);
-  let low_band_pre_gain = low_band_pre_gain * 1.8197008586099834;
-  // let mid_band_pre_gain = mid_band_pre_gain * db_to_gain(5.2);
-  let mid_band_pre_gain = mid_band_pre_gain * 1.8197008586099834;
-  // let high_band_pre_gain = high_band_pre_gain * db_to_gain(5.2);
-  let high_band_pre_gain = high_band_pre_gain * 1.8197008586099834;
-  // let low_band_post_gain = db_to_gain(10.3);
-  let low_band_post_gain = 3.273406948788382;
-  // let mid_band_post_gain = db_to_gain(5.7);
-  let mid_band_post_gain = 1.9275249131909362;
-  // let high_band_post_gain = db_to_gain(10.3);
-  let high_band_post_gain = 3.273406948788382;
+  rms_window_ms: f32,
+  frame_size: usize,
+) -> ErrorCode {
+  let compressor = match unsafe { ffi::handle(compressor, "process_compressor") } {
+    Ok(compressor) => compressor,
+    Err(code) => return code,
+  };
+  let params = ProcessParams {
+    mix,
+    pre_gain,
+    post_gain,
+    low_band_pre_gain,
+    mid_band_pre_gain,
+    high_band_pre_gain,
+    low_band_attack_ms,
+    low_band_release_ms,
+    mid_band_attack_ms,
+    mid_band_release_ms,
+    high_band_attack_ms,
+    high_band_release_ms,
+    low_band_auto_release,
+    mid_band_auto_release,
+    high_band_auto_release,
+    low_band_bottom_threshold_db,
+    mid_band_bottom_threshold_db,
+    high_band_bottom_threshold_db,
+    low_band_top_threshold_db,
+    mid_band_top_threshold_db,
+    high_band_top_threshold_db,
+    low_band_bottom_ratio,
+    mid_band_bottom_ratio,
+    high_band_bottom_ratio,
+    low_band_top_ratio,
+    mid_band_top_ratio,
+    high_band_top_ratio,
+    knee,
+    lookahead_samples,
+    rms_window_ms,
+  };
+  process_compressor_inner(compressor, &params, frame_size)
+}
 
-  let compressor = unsafe { &mut *compressor };
-  compressor.apply(
+/// Same as `process_compressor`, but reads its params from the packed param block rather than
+/// taking them as arguments.  The block is only decoded when the host has marked it dirty, and the
+/// previously decoded params are reused otherwise.
+#[no_mangle]
+pub extern "C" fn process_compressor_packed(
+  compressor: *mut MultibandCompressor,
+  frame_size: usize,
+) -> ErrorCode {
+  let compressor = match unsafe { ffi::handle(compressor, "process_compressor_packed") } {
+    Ok(compressor) => compressor,
+    Err(code) => return code,
+  };
+
+  if compressor.packed_params[PACKED_PARAMS_DIRTY_IX] != 0. {
+    let version = compressor.packed_params[PACKED_PARAMS_VERSION_IX];
+    if version != PACKED_PARAMS_VERSION {
+      return ffi::set_last_error(
+        ErrorCode::Unsupported,
+        &format!("process_compressor_packed: unsupported packed params version {version}"),
+      );
+    }
+
+    let packed = compressor.packed_params[PACKED_PARAMS_HEADER_LEN..]
+      .try_into()
+      .unwrap();
+    compressor.decoded_packed_params = ProcessParams::from_packed(packed);
+    compressor.packed_params[PACKED_PARAMS_DIRTY_IX] = 0.;
+  }
+
+  let params = compressor.decoded_packed_params;
+  process_compressor_inner(compressor, &params, frame_size)
+}
+
+#[no_mangle]
+pub extern "C" fn compressor_get_packed_params_ptr(
+  compressor: *mut MultibandCompressor,
+) -> *mut f32 {
+  match unsafe { ffi::handle(compressor, "compressor_get_packed_params_ptr") } {
+    Ok(compressor) => compressor.packed_params.as_mut_ptr(),
+    Err(_) => ptr::null_mut(),
+  }
+}
+
+fn process_compressor_inner(
+  compressor: &mut MultibandCompressor,
+  params: &ProcessParams,
+  frame_size: usize,
+) -> ErrorCode {
+  let ProcessParams {
     mix,
     pre_gain,
     post_gain,
@@ -618,6 +1164,9 @@ pub extern "C" fn process_compressor(
     mid_band_release_ms,
     high_band_attack_ms,
     high_band_release_ms,
+    low_band_auto_release,
+    mid_band_auto_release,
+    high_band_auto_release,
     low_band_bottom_threshold_db,
     mid_band_bottom_threshold_db,
     high_band_bottom_threshold_db,
@@ -632,8 +1181,626 @@ pub extern "C" fn process_compressor(
     high_band_top_ratio,
     knee,
     lookahead_samples,
-    low_band_post_gain,
-    mid_band_post_gain,
-    high_band_post_gain,
+    rms_window_ms,
+  } = *params;
+
+  if let Err(code) = ffi::check_range("frame_size", frame_size, 1, MAX_FRAME_SIZE) {
+    return code;
+  }
+  // The lookahead buffers need to hold the lookahead period plus the current frame
+  if let Err(code) = ffi::check_range(
+    "lookahead_samples",
+    lookahead_samples,
+    0,
+    MAX_LOOKAHEAD_SAMPLES - frame_size - 1,
+  ) {
+    return code;
+  }
+
+  ffi::status((|| {
+    let mix = ffi::clamp_param("mix", mix, 0., 1.)?;
+    let pre_gain = ffi::clamp_param("pre_gain", pre_gain, 0., MAX_GAIN)?;
+    let post_gain = ffi::clamp_param("post_gain", post_gain, 0., MAX_GAIN)?;
+    let low_band_pre_gain = ffi::clamp_param("low_band_pre_gain", low_band_pre_gain, 0., MAX_GAIN)?;
+    let mid_band_pre_gain = ffi::clamp_param("mid_band_pre_gain", mid_band_pre_gain, 0., MAX_GAIN)?;
+    let high_band_pre_gain =
+      ffi::clamp_param("high_band_pre_gain", high_band_pre_gain, 0., MAX_GAIN)?;
+    let low_band_attack_ms = ffi::clamp_param(
+      "low_band_attack_ms",
+      low_band_attack_ms,
+      0.,
+      MAX_ENVELOPE_TIME_MS,
+    )?;
+    let low_band_release_ms = ffi::clamp_param(
+      "low_band_release_ms",
+      low_band_release_ms,
+      0.,
+      MAX_ENVELOPE_TIME_MS,
+    )?;
+    let mid_band_attack_ms = ffi::clamp_param(
+      "mid_band_attack_ms",
+      mid_band_attack_ms,
+      0.,
+      MAX_ENVELOPE_TIME_MS,
+    )?;
+    let mid_band_release_ms = ffi::clamp_param(
+      "mid_band_release_ms",
+      mid_band_release_ms,
+      0.,
+      MAX_ENVELOPE_TIME_MS,
+    )?;
+    let high_band_attack_ms = ffi::clamp_param(
+      "high_band_attack_ms",
+      high_band_attack_ms,
+      0.,
+      MAX_ENVELOPE_TIME_MS,
+    )?;
+    let high_band_release_ms = ffi::clamp_param(
+      "high_band_release_ms",
+      high_band_release_ms,
+      0.,
+      MAX_ENVELOPE_TIME_MS,
+    )?;
+    let low_band_bottom_threshold_db = ffi::clamp_param(
+      "low_band_bottom_threshold_db",
+      low_band_bottom_threshold_db,
+      MIN_THRESHOLD_DB,
+      MAX_THRESHOLD_DB,
+    )?;
+    let mid_band_bottom_threshold_db = ffi::clamp_param(
+      "mid_band_bottom_threshold_db",
+      mid_band_bottom_threshold_db,
+      MIN_THRESHOLD_DB,
+      MAX_THRESHOLD_DB,
+    )?;
+    let high_band_bottom_threshold_db = ffi::clamp_param(
+      "high_band_bottom_threshold_db",
+      high_band_bottom_threshold_db,
+      MIN_THRESHOLD_DB,
+      MAX_THRESHOLD_DB,
+    )?;
+    let low_band_top_threshold_db = ffi::clamp_param(
+      "low_band_top_threshold_db",
+      low_band_top_threshold_db,
+      MIN_THRESHOLD_DB,
+      MAX_THRESHOLD_DB,
+    )?;
+    let mid_band_top_threshold_db = ffi::clamp_param(
+      "mid_band_top_threshold_db",
+      mid_band_top_threshold_db,
+      MIN_THRESHOLD_DB,
+      MAX_THRESHOLD_DB,
+    )?;
+    let high_band_top_threshold_db = ffi::clamp_param(
+      "high_band_top_threshold_db",
+      high_band_top_threshold_db,
+      MIN_THRESHOLD_DB,
+      MAX_THRESHOLD_DB,
+    )?;
+    let low_band_bottom_ratio = ffi::clamp_param(
+      "low_band_bottom_ratio",
+      low_band_bottom_ratio,
+      0.,
+      MAX_RATIO,
+    )?;
+    let mid_band_bottom_ratio = ffi::clamp_param(
+      "mid_band_bottom_ratio",
+      mid_band_bottom_ratio,
+      0.,
+      MAX_RATIO,
+    )?;
+    let high_band_bottom_ratio = ffi::clamp_param(
+      "high_band_bottom_ratio",
+      high_band_bottom_ratio,
+      0.,
+      MAX_RATIO,
+    )?;
+    let low_band_top_ratio =
+      ffi::clamp_param("low_band_top_ratio", low_band_top_ratio, 1., MAX_RATIO)?;
+    let mid_band_top_ratio =
+      ffi::clamp_param("mid_band_top_ratio", mid_band_top_ratio, 1., MAX_RATIO)?;
+    let high_band_top_ratio =
+      ffi::clamp_param("high_band_top_ratio", high_band_top_ratio, 1., MAX_RATIO)?;
+    let knee = ffi::clamp_param("knee", knee, 0., MAX_KNEE_DB)?;
+    let rms_window_ms = ffi::clamp_param(
+      "rms_window_ms",
+      rms_window_ms,
+      MIN_RMS_WINDOW_MS,
+      MAX_RMS_WINDOW_MS,
+    )?;
+
+    let mut params = [
+      mix,
+      pre_gain,
+      post_gain,
+      low_band_pre_gain,
+      mid_band_pre_gain,
+      high_band_pre_gain,
+      low_band_attack_ms,
+      low_band_release_ms,
+      mid_band_attack_ms,
+      mid_band_release_ms,
+      high_band_attack_ms,
+      high_band_release_ms,
+      low_band_bottom_threshold_db,
+      mid_band_bottom_threshold_db,
+      high_band_bottom_threshold_db,
+      low_band_top_threshold_db,
+      mid_band_top_threshold_db,
+      high_band_top_threshold_db,
+      low_band_bottom_ratio,
+      mid_band_bottom_ratio,
+      high_band_bottom_ratio,
+      low_band_top_ratio,
+      mid_band_top_ratio,
+      high_band_top_ratio,
+      knee,
+      lookahead_samples as f32 / dsp::ms_to_samples(1.),
+      rms_window_ms,
+    ];
+    let mut auto_release = [
+      low_band_auto_release,
+      mid_band_auto_release,
+      high_band_auto_release,
+    ];
+    // Blended params take precedence over the ones passed in while an A/B crossfade is running
+    let mut ab_params = [0.; FLAT_PARAM_COUNT];
+    let ab_fading = compressor.ab.tick(frame_size, &mut ab_params);
+    if ab_fading {
+      let (ab_params, ab_auto_release) = ab_params.split_at(STATE_PARAMS.len());
+      params.copy_from_slice(ab_params);
+      for (flag, &val) in auto_release.iter_mut().zip(ab_auto_release) {
+        *flag = val >= 0.5;
+      }
+    }
+    compressor.last_params = params;
+    compressor.last_auto_release = auto_release;
+
+    #[rustfmt::skip]
+    let [
+      mix, pre_gain, post_gain, low_band_pre_gain, mid_band_pre_gain, high_band_pre_gain,
+      low_band_attack_ms, low_band_release_ms, mid_band_attack_ms, mid_band_release_ms,
+      high_band_attack_ms, high_band_release_ms, low_band_bottom_threshold_db,
+      mid_band_bottom_threshold_db, high_band_bottom_threshold_db, low_band_top_threshold_db,
+      mid_band_top_threshold_db, high_band_top_threshold_db, low_band_bottom_ratio,
+      mid_band_bottom_ratio, high_band_bottom_ratio, low_band_top_ratio, mid_band_top_ratio,
+      high_band_top_ratio, knee, lookahead_ms, rms_window_ms,
+    ] = params;
+    let [low_band_auto_release, mid_band_auto_release, high_band_auto_release] = auto_release;
+    let lookahead_samples = if ab_fading {
+      (dsp::ms_to_samples(lookahead_ms) as usize).min(MAX_LOOKAHEAD_SAMPLES - frame_size - 1)
+    } else {
+      lookahead_samples
+    };
+    let rms_window_samples = (dsp::ms_to_samples(rms_window_ms) as usize).max(1);
+
+    // let low_band_pre_gain = low_band_pre_gain * db_to_gain(5.2);
+    let low_band_pre_gain = low_band_pre_gain * 1.8197008586099834;
+    // let mid_band_pre_gain = mid_band_pre_gain * db_to_gain(5.2);
+    let mid_band_pre_gain = mid_band_pre_gain * 1.8197008586099834;
+    // let high_band_pre_gain = high_band_pre_gain * db_to_gain(5.2);
+    let high_band_pre_gain = high_band_pre_gain * 1.8197008586099834;
+    // let low_band_post_gain = db_to_gain(10.3);
+    let low_band_post_gain = 3.273406948788382;
+    // let mid_band_post_gain = db_to_gain(5.7);
+    let mid_band_post_gain = 1.9275249131909362;
+    // let high_band_post_gain = db_to_gain(10.3);
+    let high_band_post_gain = 3.273406948788382;
+
+    compressor.apply(
+      frame_size,
+      mix,
+      pre_gain,
+      post_gain,
+      low_band_pre_gain,
+      mid_band_pre_gain,
+      high_band_pre_gain,
+      low_band_attack_ms,
+      low_band_release_ms,
+      mid_band_attack_ms,
+      mid_band_release_ms,
+      high_band_attack_ms,
+      high_band_release_ms,
+      low_band_auto_release,
+      mid_band_auto_release,
+      high_band_auto_release,
+      low_band_bottom_threshold_db,
+      mid_band_bottom_threshold_db,
+      high_band_bottom_threshold_db,
+      low_band_top_threshold_db,
+      mid_band_top_threshold_db,
+      high_band_top_threshold_db,
+      low_band_bottom_ratio,
+      mid_band_bottom_ratio,
+      high_band_bottom_ratio,
+      low_band_top_ratio,
+      mid_band_top_ratio,
+      high_band_top_ratio,
+      knee,
+      lookahead_samples,
+      rms_window_samples,
+      low_band_post_gain,
+      mid_band_post_gain,
+      high_band_post_gain,
+    );
+
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn reset_compressor(compressor: *mut MultibandCompressor) -> ErrorCode {
+  let compressor = match unsafe { ffi::handle(compressor, "reset_compressor") } {
+    Ok(compressor) => compressor,
+    Err(code) => return code,
+  };
+  compressor.reset();
+  ErrorCode::Ok
+}
+
+/// Enables or disables the DC blocker at the compressor's input.  `cutoff_hz` is clamped to the
+/// range supported by `DCBlocker` and is ignored when disabling.
+#[no_mangle]
+pub extern "C" fn set_compressor_input_dc_blocker(
+  compressor: *mut MultibandCompressor,
+  enabled: bool,
+  cutoff_hz: f32,
+) -> ErrorCode {
+  let compressor = match unsafe { ffi::handle(compressor, "set_compressor_input_dc_blocker") } {
+    Ok(compressor) => compressor,
+    Err(code) => return code,
+  };
+  if !enabled {
+    compressor.input_dc_blocker = None;
+    return ErrorCode::Ok;
+  }
+
+  ffi::status((|| {
+    let cutoff_hz = ffi::clamp_param(
+      "cutoff_hz",
+      cutoff_hz,
+      MIN_DC_BLOCKER_CUTOFF_HZ,
+      MAX_DC_BLOCKER_CUTOFF_HZ,
+    )?;
+    match &mut compressor.input_dc_blocker {
+      Some(dc_blocker) => dc_blocker.set_cutoff_hz(cutoff_hz),
+      None => compressor.input_dc_blocker = Some(DCBlocker::new(cutoff_hz)),
+    }
+    Ok(())
+  })())
+}
+
+/// Switches between splitting the input into three bands and compressing it as a single band with
+/// the mid band's params.  Changing modes resets the compressor so that stale band state isn't
+/// played back.
+#[no_mangle]
+pub extern "C" fn set_compressor_wideband_mode(
+  compressor: *mut MultibandCompressor,
+  enabled: bool,
+) -> ErrorCode {
+  let compressor = match unsafe { ffi::handle(compressor, "set_compressor_wideband_mode") } {
+    Ok(compressor) => compressor,
+    Err(code) => return code,
+  };
+  if compressor.wideband != enabled {
+    compressor.wideband = enabled;
+    compressor.reset();
+  }
+  ErrorCode::Ok
+}
+
+/// Snapshots the compressor's params as JSON; see `common::state`.  Returns the length of the JSON
+/// in bytes.
+#[no_mangle]
+pub extern "C" fn compressor_get_state_json(compressor: *const MultibandCompressor) -> usize {
+  let compressor = unsafe { &*compressor };
+  common::state::export_state(compressor)
+}
+
+/// Restores params from the first `len` bytes of the state JSON buffer
+#[no_mangle]
+pub extern "C" fn compressor_set_state_json(
+  compressor: *mut MultibandCompressor,
+  len: usize,
+) -> ErrorCode {
+  let compressor = match unsafe { ffi::handle(compressor, "compressor_set_state_json") } {
+    Ok(compressor) => compressor,
+    Err(code) => return code,
+  };
+  common::state::import_state(compressor, len, "compressor_set_state_json")
+}
+
+/// Saves the current params into the active A/B slot and crossfades to the params in `slot`.  See
+/// `common::state::ABCompare`.
+#[no_mangle]
+pub extern "C" fn compressor_ab_select(
+  compressor: *mut MultibandCompressor,
+  slot: usize,
+  fade_ms: f32,
+) -> ErrorCode {
+  let compressor = match unsafe { ffi::handle(compressor, "compressor_ab_select") } {
+    Ok(compressor) => compressor,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("fade_ms", fade_ms, 0., MAX_AB_FADE_MS)?;
+    let current = compressor.flat_params();
+    let fade_samples = dsp::ms_to_samples(fade_ms) as usize;
+    compressor.ab.select(slot, &current, fade_samples)
+  })())
+}
+
+/// Writes the params in A/B `slot` as state JSON, returning its length in bytes or 0 if the slot
+/// is empty
+#[no_mangle]
+pub extern "C" fn compressor_ab_get_slot_state_json(
+  compressor: *const MultibandCompressor,
+  slot: usize,
+) -> usize {
+  let compressor = unsafe { &*compressor };
+  match compressor.ab.slot(slot) {
+    Some(params) => common::state::write_state_json(&flat_params_to_state(params)),
+    None => 0,
+  }
+}
+
+#[test]
+fn envelope_coefficients_are_stable() {
+  for time_ms in [0., 0.0001, 0.01, 1., 10., MAX_ENVELOPE_TIME_MS] {
+    let coefficient = compute_envelope_coefficient(time_ms);
+    assert!(
+      (0. ..1.).contains(&coefficient),
+      "{time_ms}ms -> {coefficient}"
+    );
+  }
+  assert!(compute_envelope_coefficient(10.) < compute_envelope_coefficient(100.));
+}
+
+#[test]
+fn rms_running_sum_matches_window() {
+  let mut buf = CircularBuffer::<MAX_LOOKAHEAD_SAMPLES>::new();
+  let frame_size = 128;
+  let rms_window_samples = 300;
+  let mut sum = 0.;
+  for frame_ix in 0..8 {
+    for i in 0..frame_size {
+      buf.set(((frame_ix * frame_size + i) as f32 * 0.05).sin());
+    }
+    for i in 0..frame_size {
+      detect_level_rms(&buf, rms_window_samples, frame_size as isize, i, &mut sum);
+    }
+  }
+
+  // After the last frame, the window ends at the newest sample in the buffer
+  let expected = compute_rms_window_squared_samples_sum(&buf, rms_window_samples, 0);
+  assert!((sum - expected).abs() < 1e-3, "{sum} != {expected}");
+}
+
+#[test]
+fn state_roundtrip() {
+  let mut compressor = MultibandCompressor::default();
+  let mut state = compressor.get_state();
+  assert_eq!(state.entries.len(), STATE_PARAMS.len() + 3);
+  state.entries.clear();
+  state.push_number("knee", 6.);
+  state.push_number("lookahead_ms", 5.);
+  state.push_bool("mid_band_auto_release", true);
+  compressor.set_state(&state).unwrap();
+
+  let restored = compressor.get_state();
+  assert_eq!(restored.number("knee"), Ok(Some(6.)));
+  assert_eq!(restored.number("lookahead_ms"), Ok(Some(5.)));
+  assert_eq!(restored.number("rms_window_ms"), Ok(Some(30.)));
+  assert_eq!(restored.bool("mid_band_auto_release"), Ok(Some(true)));
+
+  // Nothing is applied if any param is invalid
+  let mut invalid = StateSnapshot::default();
+  invalid.push_number("knee", 12.);
+  invalid.push_number("low_band_top_ratio", 0.5);
+  assert_eq!(
+    compressor.set_state(&invalid),
+    Err(ErrorCode::ParamOutOfRange)
+  );
+  assert_eq!(compressor.get_state().number("knee"), Ok(Some(6.)));
+}
+
+#[test]
+fn ab_select_crossfades_params() {
+  let mut compressor = MultibandCompressor::default();
+  let process = |compressor: &mut MultibandCompressor, knee: f32, auto_release: bool| {
+    let status = process_compressor(
+      compressor,
+      1.,
+      1.,
+      1.,
+      1.,
+      1.,
+      1.,
+      3.,
+      300.,
+      3.,
+      300.,
+      3.,
+      300.,
+      auto_release,
+      false,
+      false,
+      -40.,
+      -40.,
+      -40.,
+      -20.,
+      -20.,
+      -20.,
+      1.,
+      1.,
+      1.,
+      4.,
+      4.,
+      4.,
+      knee,
+      0,
+      30.,
+      128,
+    );
+    assert_eq!(status, ErrorCode::Ok);
+  };
+
+  process(&mut compressor, 0., false);
+  assert_eq!(compressor_ab_select(&mut compressor, 1, 10.), ErrorCode::Ok);
+  // Edit B, then switch back to A
+  process(&mut compressor, 20., true);
+  assert_eq!(compressor_ab_select(&mut compressor, 0, 10.), ErrorCode::Ok);
+
+  // The params passed in are ignored in favor of the blend from B to A until the fade completes
+  let fade_samples = dsp::ms_to_samples(10.) as usize;
+  let mut knees = Vec::new();
+  for _ in 0..fade_samples.div_ceil(128) + 1 {
+    process(&mut compressor, 20., true);
+    knees.push(compressor.last_params[24]);
+  }
+  let fade_knees = &knees[..knees.len() - 1];
+  assert!(
+    fade_knees.windows(2).all(|pair| pair[1] <= pair[0]),
+    "{knees:?}"
+  );
+  assert!(knees[0] < 20. && knees[0] > 0., "{knees:?}");
+  assert_eq!(knees[knees.len() - 2], 0.);
+  assert_eq!(knees[knees.len() - 1], 20.);
+  assert_eq!(compressor.ab.slot(1).unwrap()[24], 20.);
+  assert_eq!(compressor.ab.slot(1).unwrap()[STATE_PARAMS.len()], 1.);
+}
+
+#[cfg(test)]
+fn sine(sample_ix: usize) -> f32 {
+  let t = sample_ix as f32 / dsp::sample_rate();
+  (2. * std::f32::consts::PI * 1000. * t).sin() * 0.5
+}
+
+/// Runs a 1kHz sine through the compressor with ratios of 1, so that no compression is applied,
+/// and returns the output of frame `frame_count - 1`
+#[cfg(test)]
+fn process_uncompressed_sine(
+  compressor: &mut MultibandCompressor,
+  mix: f32,
+  frame_count: usize,
+) -> [f32; MAX_FRAME_SIZE] {
+  for frame_ix in 0..frame_count {
+    compressor.input_buffer = std::array::from_fn(|i| sine(frame_ix * MAX_FRAME_SIZE + i));
+    let status = process_compressor(
+      compressor,
+      mix,
+      1.,
+      1.,
+      1.,
+      1.,
+      1.,
+      3.,
+      300.,
+      3.,
+      300.,
+      3.,
+      300.,
+      false,
+      false,
+      false,
+      -40.,
+      -40.,
+      -40.,
+      -20.,
+      -20.,
+      -20.,
+      1.,
+      1.,
+      1.,
+      1.,
+      1.,
+      1.,
+      0.,
+      0,
+      30.,
+      MAX_FRAME_SIZE,
+    );
+    assert_eq!(status, ErrorCode::Ok);
+  }
+  compressor.output_buffer
+}
+
+#[test]
+fn wideband_mode_skips_band_splitting() {
+  let mut compressor = MultibandCompressor::default();
+  assert_eq!(
+    set_compressor_wideband_mode(&mut compressor, true),
+    ErrorCode::Ok
   );
+
+  // Without band splitting, the fully wet output is the input delayed by a sample with the mid
+  // band's fixed 5.2dB pre gain and 5.7dB post gain applied
+  let frame_count = 32;
+  let output = process_uncompressed_sine(&mut compressor, 1., frame_count);
+  let frame_start = (frame_count - 1) * MAX_FRAME_SIZE;
+  let gain = 1.8197008586099834 * 1.9275249131909362;
+  for (i, &out) in output.iter().enumerate() {
+    let expected = sine(frame_start + i - 1) * gain;
+    assert!((out - expected).abs() < 1e-3, "{out} != {expected}");
+  }
+  // The low and high bands aren't processed at all
+  assert_eq!(
+    compressor.low_band_compressor.last_detected_level_linear,
+    0.
+  );
+  assert_eq!(
+    compressor.high_band_compressor.last_detected_level_linear,
+    0.
+  );
+  assert!(compressor.mid_band_compressor.last_detected_level_linear > 0.);
+}
+
+#[test]
+fn dry_mix_is_unprocessed_input() {
+  let mut compressor = MultibandCompressor::default();
+  // Fully dry output skips the band splitting and the band gains entirely
+  let frame_count = 4;
+  let output = process_uncompressed_sine(&mut compressor, 0., frame_count);
+  let frame_start = (frame_count - 1) * MAX_FRAME_SIZE;
+  for (i, &out) in output.iter().enumerate() {
+    let expected = sine(frame_start + i - 1);
+    assert!((out - expected).abs() < 1e-6, "{out} != {expected}");
+  }
+}
+
+#[test]
+fn packed_params_match_scalar_params() {
+  let frame_count = 4;
+  let mut scalar = MultibandCompressor::default();
+  let expected = process_uncompressed_sine(&mut scalar, 0.5, frame_count);
+
+  let mut packed = MultibandCompressor::default();
+  #[rustfmt::skip]
+  let params = [
+    0.5, 1., 1., 1., 1., 1., 3., 300., 3., 300., 3., 300., 0., 0., 0., -40., -40., -40., -20.,
+    -20., -20., 1., 1., 1., 1., 1., 1., 0., 0., 30.,
+  ];
+  packed.packed_params[PACKED_PARAMS_HEADER_LEN..].copy_from_slice(&params);
+  packed.packed_params[PACKED_PARAMS_DIRTY_IX] = 1.;
+  assert_eq!(
+    process_compressor_packed(&mut packed, MAX_FRAME_SIZE),
+    ErrorCode::Unsupported
+  );
+
+  packed.packed_params[PACKED_PARAMS_VERSION_IX] = PACKED_PARAMS_VERSION;
+  for frame_ix in 0..frame_count {
+    packed.input_buffer = std::array::from_fn(|i| sine(frame_ix * MAX_FRAME_SIZE + i));
+    assert_eq!(
+      process_compressor_packed(&mut packed, MAX_FRAME_SIZE),
+      ErrorCode::Ok
+    );
+    // The params decoded from the first frame keep being used once the block is clean
+    assert_eq!(packed.packed_params[PACKED_PARAMS_DIRTY_IX], 0.);
+  }
+  assert_eq!(packed.output_buffer, expected);
+}
+
+#[test]
+fn packed_params_ptr_rejects_null_compressor() {
+  assert!(compressor_get_packed_params_ptr(ptr::null_mut()).is_null());
 }
@@ -1,12 +1,17 @@
-use dsp::filters::butterworth::ButterworthFilter;
+use common::ffi::{self, ErrorCode};
+use dsp::{
+  delay_line::{DelayInterpolation, DelayLine},
+  filters::butterworth::ButterworthFilter,
+};
 
-const SAMPLE_RATE: usize = 44_100;
 const FRAME_SIZE: usize = 128;
 const MAX_DELAY_MS: usize = 60 * 1000;
-const MAX_DELAY_SAMPLES: usize = MAX_DELAY_MS * (SAMPLE_RATE / 1000);
+/// Sized for the default sample rate; at higher sample rates, the maximum delay is shorter than
+/// `MAX_DELAY_MS`.
+const MAX_DELAY_SAMPLES: usize = MAX_DELAY_MS * (dsp::DEFAULT_SAMPLE_RATE as usize / 1000);
 
 pub struct DelayCtx {
-  pub delay_line: dsp::circular_buffer::CircularBuffer<MAX_DELAY_SAMPLES>,
+  pub delay_line: DelayLine<MAX_DELAY_SAMPLES>,
   pub main_io_buffer: Box<[f32; FRAME_SIZE]>,
   pub delay_output_buffer: Box<[f32; FRAME_SIZE]>,
   // Params
@@ -23,7 +28,7 @@ fn uninit<T>() -> T { unsafe { std::mem::MaybeUninit::uninit().assume_init() } }
 #[no_mangle]
 pub extern "C" fn init_delay_ctx() -> *mut DelayCtx {
   let delay_ctx = DelayCtx {
-    delay_line: dsp::circular_buffer::CircularBuffer::new(),
+    delay_line: DelayLine::new(DelayInterpolation::Linear),
     main_io_buffer: Box::new(uninit()),
     delay_output_buffer: Box::new(uninit()),
     delay_ms: Box::new(uninit()),
@@ -66,8 +71,11 @@ pub unsafe extern "C" fn get_highpass_cutoff_ptr(ctx: *mut DelayCtx) -> *mut f32
 }
 
 #[no_mangle]
-pub extern "C" fn process_delay(ctx: *mut DelayCtx) {
-  let ctx = unsafe { &mut *ctx };
+pub extern "C" fn process_delay(ctx: *mut DelayCtx) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "process_delay") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
 
   for sample_ix in 0..ctx.main_io_buffer.len() {
     let sample = ctx.main_io_buffer[sample_ix];
@@ -76,13 +84,15 @@ pub extern "C" fn process_delay(ctx: *mut DelayCtx) {
     let feedback = ctx.feedback[sample_ix];
     let highpass_cutoff = ctx.highpass_cutoff[sample_ix];
 
-    let delay_samples = delay_ms * (1. / 1000.) * SAMPLE_RATE as f32;
-    let delayed_sample = ctx.delay_line.read_interpolated(-delay_samples);
+    ctx.delay_line.set_delay_ms(delay_ms);
+    let delayed_sample = ctx.delay_line.read();
     let highpassed_sample = ctx.highpass_filter.highpass(highpass_cutoff, sample);
     ctx
       .delay_line
-      .set(highpassed_sample + delayed_sample * feedback);
+      .write(highpassed_sample + delayed_sample * feedback);
     ctx.delay_output_buffer[sample_ix] = delayed_sample;
     ctx.main_io_buffer[sample_ix] = sample + delayed_sample * delay_gain;
   }
+
+  ErrorCode::Ok
 }
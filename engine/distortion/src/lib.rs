@@ -1,3 +1,4 @@
+use common::ffi::{self, ErrorCode};
 use dsp::circular_buffer::CircularBuffer;
 
 pub const FRAME_SIZE: usize = 128;
@@ -115,9 +116,24 @@ pub unsafe extern "C" fn distortion_get_param_buffer_pr(ctx: *mut WaveStretcher)
 pub unsafe extern "C" fn distortion_set_length_samples(
   ctx: *mut WaveStretcher,
   new_len_samples: usize,
-) {
-  (*ctx).len_samples = new_len_samples;
+) -> ErrorCode {
+  let ctx = match ffi::handle(ctx, "distortion_set_length_samples") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("len_samples", new_len_samples, 1, CIRCULAR_BUFFER_SIZE)?;
+    ctx.len_samples = new_len_samples;
+    Ok(())
+  })())
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn distortion_process(ctx: *mut WaveStretcher) { (*ctx).process() }
+pub unsafe extern "C" fn distortion_process(ctx: *mut WaveStretcher) -> ErrorCode {
+  let ctx = match ffi::handle(ctx, "distortion_process") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ctx.process();
+  ErrorCode::Ok
+}
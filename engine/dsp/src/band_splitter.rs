@@ -1,5 +1,14 @@
+//! Splits a signal into low, mid, and high bands.  `BandSplitter` uses minimum-phase IIR
+//! crossovers with no latency.  `LinearPhaseBandSplitter` uses FIR crossovers applied with FFT
+//! convolution, which keep transients intact and shift every band by the same amount at the cost
+//! of `LINEAR_PHASE_LATENCY_SAMPLES` of latency.
+
+use std::f64::consts::PI;
+
 use crate::{
+  fft::Fft,
   filters::biquad::{BiquadFilter, FilterMode},
+  resampler::blackman,
   FRAME_SIZE,
 };
 
@@ -10,19 +19,15 @@ const MID_BAND_CUTOFF: f32 = 2500.;
 
 fn apply_filter_chain_full<const N: usize>(
   chain: &mut [BiquadFilter; N],
-  input_buf: [f32; FRAME_SIZE],
-  output_buf: &mut [f32; FRAME_SIZE],
+  input_buf: &[f32],
+  output_buf: &mut [f32],
 ) {
-  let mut filtered = input_buf;
+  output_buf.copy_from_slice(input_buf);
   for filter in chain.iter_mut() {
-    for i in 0..FRAME_SIZE {
-      filtered[i] = filter.apply(filtered[i]);
+    for sample in output_buf.iter_mut() {
+      *sample = filter.apply(*sample);
     }
   }
-
-  for i in 0..FRAME_SIZE {
-    output_buf[i] = filtered[i];
-  }
 }
 
 pub struct BandSplitter {
@@ -108,21 +113,252 @@ impl BandSplitter {
     low_band_output_buf: &mut [f32; FRAME_SIZE],
     mid_band_output_buf: &mut [f32; FRAME_SIZE],
     high_band_output_buf: &mut [f32; FRAME_SIZE],
+  ) {
+    self.apply(
+      samples,
+      low_band_output_buf,
+      mid_band_output_buf,
+      high_band_output_buf,
+    );
+  }
+
+  /// Splits a block of any length into bands.  All output buffers must be the same length as
+  /// `samples`.
+  pub fn apply(
+    &mut self,
+    samples: &[f32],
+    low_band_output_buf: &mut [f32],
+    mid_band_output_buf: &mut [f32],
+    high_band_output_buf: &mut [f32],
   ) {
     apply_filter_chain_full(
       &mut self.low_band_filter_chain,
-      *samples,
+      samples,
       low_band_output_buf,
     );
     apply_filter_chain_full(
       &mut self.mid_band_filter_chain,
-      *samples,
+      samples,
       mid_band_output_buf,
     );
     apply_filter_chain_full(
       &mut self.high_band_filter_chain,
-      *samples,
+      samples,
       high_band_output_buf,
     );
   }
+
+  pub fn reset(&mut self) {
+    for filter in self
+      .low_band_filter_chain
+      .iter_mut()
+      .chain(self.mid_band_filter_chain.iter_mut())
+      .chain(self.high_band_filter_chain.iter_mut())
+    {
+      filter.reset();
+    }
+  }
+}
+
+/// Taps in each linear-phase crossover filter.  Odd so that the filters delay by a whole number of
+/// samples.  At 44.1 kHz, the crossovers are about 60 Hz wide.
+const LINEAR_PHASE_FIR_LEN: usize = 4097;
+const LINEAR_PHASE_FFT_SIZE: usize = 8192;
+/// Input is filtered in blocks of this many samples using overlap-save, one FFT each way per block
+const LINEAR_PHASE_BLOCK_SIZE: usize = LINEAR_PHASE_FFT_SIZE - LINEAR_PHASE_FIR_LEN + 1;
+const LINEAR_PHASE_FIR_DELAY: usize = (LINEAR_PHASE_FIR_LEN - 1) / 2;
+/// The delay of the crossover filters plus the time spent collecting a block of input
+pub const LINEAR_PHASE_LATENCY_SAMPLES: usize = LINEAR_PHASE_BLOCK_SIZE + LINEAR_PHASE_FIR_DELAY;
+
+/// Returns the spectrum of a Blackman-windowed sinc lowpass at `cutoff_hz`, zero-padded to the FFT
+/// size
+fn build_linear_phase_lowpass(fft: &Fft, cutoff_hz: f32) -> (Vec<f32>, Vec<f32>) {
+  let cutoff = (cutoff_hz / crate::sample_rate()) as f64 * 2.;
+  let taps: Vec<f64> = (0..LINEAR_PHASE_FIR_LEN)
+    .map(|i| {
+      let d = i as f64 - LINEAR_PHASE_FIR_DELAY as f64;
+      let x = PI * cutoff * d;
+      let sinc = if x == 0. { 1. } else { x.sin() / x };
+      cutoff * sinc * blackman(d / LINEAR_PHASE_FIR_DELAY as f64)
+    })
+    .collect();
+  // Normalize to unity gain at DC so that the bands sum back to the input exactly
+  let dc_gain: f64 = taps.iter().sum();
+
+  let mut re = vec![0.; LINEAR_PHASE_FFT_SIZE];
+  let mut im = vec![0.; LINEAR_PHASE_FFT_SIZE];
+  for (re, tap) in re.iter_mut().zip(taps) {
+    *re = (tap / dc_gain) as f32;
+  }
+  fft.forward(&mut re, &mut im);
+  (re, im)
+}
+
+/// Linear-phase version of `BandSplitter` with the same crossover frequencies.  The low band is the
+/// output of a lowpass at the low crossover, the high band is the input minus a lowpass at the high
+/// crossover, and the mid band is what's left over, so the bands always sum back to the input
+/// delayed by `LINEAR_PHASE_LATENCY_SAMPLES`.
+pub struct LinearPhaseBandSplitter {
+  fft: Fft,
+  low_crossover_re: Vec<f32>,
+  low_crossover_im: Vec<f32>,
+  high_crossover_re: Vec<f32>,
+  high_crossover_im: Vec<f32>,
+  /// The last `LINEAR_PHASE_FIR_LEN - 1` samples of the previous block followed by the block being
+  /// collected
+  input: Vec<f32>,
+  /// Bands computed from the previous block, played back while the next one is collected
+  low_output: Vec<f32>,
+  mid_output: Vec<f32>,
+  high_output: Vec<f32>,
+  block_pos: usize,
+  scratch_re: Vec<f32>,
+  scratch_im: Vec<f32>,
+}
+
+impl LinearPhaseBandSplitter {
+  pub fn new() -> Self {
+    let fft = Fft::new(LINEAR_PHASE_FFT_SIZE);
+    let (low_crossover_re, low_crossover_im) = build_linear_phase_lowpass(&fft, LOW_BAND_CUTOFF);
+    let (high_crossover_re, high_crossover_im) = build_linear_phase_lowpass(&fft, MID_BAND_CUTOFF);
+    LinearPhaseBandSplitter {
+      fft,
+      low_crossover_re,
+      low_crossover_im,
+      high_crossover_re,
+      high_crossover_im,
+      input: vec![0.; LINEAR_PHASE_FFT_SIZE],
+      low_output: vec![0.; LINEAR_PHASE_BLOCK_SIZE],
+      mid_output: vec![0.; LINEAR_PHASE_BLOCK_SIZE],
+      high_output: vec![0.; LINEAR_PHASE_BLOCK_SIZE],
+      block_pos: 0,
+      scratch_re: vec![0.; LINEAR_PHASE_FFT_SIZE],
+      scratch_im: vec![0.; LINEAR_PHASE_FFT_SIZE],
+    }
+  }
+
+  /// Filters the collected block with both crossovers at once.  Their outputs are real, so the low
+  /// crossover's output is computed in the real part of the inverse FFT and the high crossover's in
+  /// the imaginary part.
+  fn process_block(&mut self) {
+    self.scratch_re.copy_from_slice(&self.input);
+    self.scratch_im.fill(0.);
+    self.fft.forward(&mut self.scratch_re, &mut self.scratch_im);
+    for bin_ix in 0..LINEAR_PHASE_FFT_SIZE {
+      let (x_re, x_im) = (self.scratch_re[bin_ix], self.scratch_im[bin_ix]);
+      let (low_re, low_im) = (self.low_crossover_re[bin_ix], self.low_crossover_im[bin_ix]);
+      let (high_re, high_im) = (
+        self.high_crossover_re[bin_ix],
+        self.high_crossover_im[bin_ix],
+      );
+      let low = (x_re * low_re - x_im * low_im, x_re * low_im + x_im * low_re);
+      let high = (
+        x_re * high_re - x_im * high_im,
+        x_re * high_im + x_im * high_re,
+      );
+      self.scratch_re[bin_ix] = low.0 - high.1;
+      self.scratch_im[bin_ix] = low.1 + high.0;
+    }
+    self.fft.inverse(&mut self.scratch_re, &mut self.scratch_im);
+
+    // With overlap-save, only the outputs past the first `LINEAR_PHASE_FIR_LEN - 1` are valid
+    let valid_start = LINEAR_PHASE_FIR_LEN - 1;
+    for i in 0..LINEAR_PHASE_BLOCK_SIZE {
+      let low = self.scratch_re[valid_start + i];
+      let below_high_crossover = self.scratch_im[valid_start + i];
+      let delayed_input = self.input[valid_start + i - LINEAR_PHASE_FIR_DELAY];
+      self.low_output[i] = low;
+      self.mid_output[i] = below_high_crossover - low;
+      self.high_output[i] = delayed_input - below_high_crossover;
+    }
+
+    self.input.copy_within(LINEAR_PHASE_BLOCK_SIZE.., 0);
+  }
+
+  /// Splits a block of any length into bands.  All output buffers must be the same length as
+  /// `samples`.
+  pub fn apply(
+    &mut self,
+    samples: &[f32],
+    low_band_output_buf: &mut [f32],
+    mid_band_output_buf: &mut [f32],
+    high_band_output_buf: &mut [f32],
+  ) {
+    for (sample_ix, &sample) in samples.iter().enumerate() {
+      self.input[LINEAR_PHASE_FIR_LEN - 1 + self.block_pos] = sample;
+      low_band_output_buf[sample_ix] = self.low_output[self.block_pos];
+      mid_band_output_buf[sample_ix] = self.mid_output[self.block_pos];
+      high_band_output_buf[sample_ix] = self.high_output[self.block_pos];
+
+      self.block_pos += 1;
+      if self.block_pos == LINEAR_PHASE_BLOCK_SIZE {
+        self.process_block();
+        self.block_pos = 0;
+      }
+    }
+  }
+
+  pub fn reset(&mut self) {
+    self.input.fill(0.);
+    self.low_output.fill(0.);
+    self.mid_output.fill(0.);
+    self.high_output.fill(0.);
+    self.block_pos = 0;
+  }
+}
+
+#[test]
+fn block_size_does_not_affect_output() {
+  let input: Vec<f32> = (0..FRAME_SIZE * 4)
+    .map(|i| (i as f32 * 0.37).sin())
+    .collect();
+  let split = |block_size: usize| {
+    let mut splitter = BandSplitter::new();
+    let mut low = vec![0.; input.len()];
+    let mut mid = vec![0.; input.len()];
+    let mut high = vec![0.; input.len()];
+    for (((block, low), mid), high) in input
+      .chunks(block_size)
+      .zip(low.chunks_mut(block_size))
+      .zip(mid.chunks_mut(block_size))
+      .zip(high.chunks_mut(block_size))
+    {
+      splitter.apply(block, low, mid, high);
+    }
+    (low, mid, high)
+  };
+
+  assert_eq!(split(FRAME_SIZE), split(37));
+}
+
+#[test]
+fn linear_phase_bands_sum_to_delayed_input() {
+  let input: Vec<f32> = (0..LINEAR_PHASE_LATENCY_SAMPLES * 3)
+    .map(|i| (i as f32 * 1.5).sin() + 0.5 * (i as f32 * 0.004).sin())
+    .collect();
+  let mut splitter = LinearPhaseBandSplitter::new();
+  let mut low = vec![0.; input.len()];
+  let mut mid = vec![0.; input.len()];
+  let mut high = vec![0.; input.len()];
+  for (((block, low), mid), high) in input
+    .chunks(FRAME_SIZE)
+    .zip(low.chunks_mut(FRAME_SIZE))
+    .zip(mid.chunks_mut(FRAME_SIZE))
+    .zip(high.chunks_mut(FRAME_SIZE))
+  {
+    splitter.apply(block, low, mid, high);
+  }
+
+  for i in LINEAR_PHASE_LATENCY_SAMPLES..input.len() {
+    let sum = low[i] + mid[i] + high[i];
+    let expected = input[i - LINEAR_PHASE_LATENCY_SAMPLES];
+    assert!((sum - expected).abs() < 1e-4, "{i}: {sum} != {expected}");
+  }
+
+  // The slow sine is well below the low crossover and the fast one is well above the high one
+  let tail = LINEAR_PHASE_LATENCY_SAMPLES * 2..input.len();
+  let rms = |buf: &[f32]| (buf.iter().map(|x| x * x).sum::<f32>() / buf.len() as f32).sqrt();
+  assert!(rms(&mid[tail.clone()]) < 0.01);
+  assert!((rms(&low[tail.clone()]) - 0.5 / 2f32.sqrt()).abs() < 0.01);
+  assert!((rms(&high[tail]) - 1. / 2f32.sqrt()).abs() < 0.01);
 }
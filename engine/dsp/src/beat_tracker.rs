@@ -0,0 +1,223 @@
+//! Estimates the tempo and beat phase of live audio from the onsets detected in it, for following
+//! an external source such as a drummer or a turntable.
+//!
+//! The intervals between every pair of recent onsets are folded into the allowed tempo range by
+//! doubling or halving them, on the assumption that most onsets fall on beats or simple
+//! subdivisions of them.  The tempo with the most support among those intervals wins.  The phase is
+//! then the weighted circular mean of where the onsets fall within a beat at that tempo.
+
+use std::{collections::VecDeque, f32::consts::TAU};
+
+/// Onsets older than this are forgotten
+const HISTORY_SECONDS: f32 = 8.;
+/// Pairs of onsets further apart than this aren't compared since small tempo errors add up over
+/// long intervals
+const MAX_INTERVAL_SECONDS: f32 = 2.;
+/// Tempo estimates aren't made from fewer onsets than this
+const MIN_ONSET_COUNT: usize = 4;
+/// Width of the tempo histogram's bins
+const BPM_RESOLUTION: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TempoEstimate {
+  pub bpm: f32,
+  /// Sample index at which a beat falls.  Beats fall every `60 / bpm` seconds before and after it.
+  pub beat_sample: u64,
+  /// How much of the evidence supports this tempo, from 0 to 1
+  pub confidence: f32,
+}
+
+impl TempoEstimate {
+  /// Position within the current beat at `sample_ix`, from 0 (on the beat) up to 1
+  pub fn phase_at(&self, sample_ix: u64, sample_rate: f32) -> f32 {
+    let samples_per_beat = sample_rate as f64 * 60. / self.bpm as f64;
+    let elapsed = sample_ix as f64 - self.beat_sample as f64;
+    (elapsed / samples_per_beat).rem_euclid(1.) as f32
+  }
+}
+
+pub struct BeatTracker {
+  /// Sample index and strength of each recent onset, oldest first
+  onsets: VecDeque<(u64, f32)>,
+  min_bpm: f32,
+  max_bpm: f32,
+  histogram: Vec<f32>,
+  estimate: Option<TempoEstimate>,
+}
+
+impl BeatTracker {
+  /// `max_bpm` must be at least double `min_bpm` so that every interval can be folded into range
+  pub fn new(min_bpm: f32, max_bpm: f32) -> Self {
+    let mut tracker = BeatTracker {
+      onsets: VecDeque::new(),
+      min_bpm: 0.,
+      max_bpm: 0.,
+      histogram: Vec::new(),
+      estimate: None,
+    };
+    tracker.set_bpm_range(min_bpm, max_bpm);
+    tracker
+  }
+
+  /// Changes the range that tempos are estimated in.  `max_bpm` is raised to double `min_bpm` if
+  /// it's lower than that.
+  pub fn set_bpm_range(&mut self, min_bpm: f32, max_bpm: f32) {
+    let max_bpm = max_bpm.max(min_bpm * 2.);
+    if min_bpm == self.min_bpm && max_bpm == self.max_bpm {
+      return;
+    }
+
+    self.min_bpm = min_bpm;
+    self.max_bpm = max_bpm;
+    let bin_count = ((max_bpm - min_bpm) / BPM_RESOLUTION).ceil() as usize + 1;
+    self.histogram = vec![0.; bin_count];
+  }
+
+  /// The most recent estimate, or `None` if there haven't been enough onsets to make one
+  pub fn estimate(&self) -> Option<&TempoEstimate> { self.estimate.as_ref() }
+
+  /// Records an onset and updates the estimate.  Onsets must be added in order.
+  pub fn add_onset(&mut self, sample_ix: u64, strength: f32, sample_rate: f32) {
+    let history_samples = (HISTORY_SECONDS * sample_rate) as u64;
+    while self
+      .onsets
+      .front()
+      .is_some_and(|&(onset, _)| onset + history_samples < sample_ix)
+    {
+      self.onsets.pop_front();
+    }
+    self.onsets.push_back((sample_ix, strength.max(0.)));
+
+    if self.onsets.len() >= MIN_ONSET_COUNT {
+      self.estimate = self.compute_estimate(sample_rate);
+    }
+  }
+
+  /// Folds `bpm` into the allowed range by doubling or halving it
+  fn fold_bpm(&self, mut bpm: f32) -> f32 {
+    while bpm < self.min_bpm {
+      bpm *= 2.;
+    }
+    while bpm > self.max_bpm {
+      bpm /= 2.;
+    }
+    bpm
+  }
+
+  fn compute_estimate(&mut self, sample_rate: f32) -> Option<TempoEstimate> {
+    self.histogram.fill(0.);
+    let max_interval = (MAX_INTERVAL_SECONDS * sample_rate) as u64;
+    let min_interval = (sample_rate * 60. / (self.max_bpm * 4.)) as u64;
+    let last_bin = self.histogram.len() - 1;
+    let mut total_weight = 0.;
+    for (i, &(onset_a, strength_a)) in self.onsets.iter().enumerate() {
+      for &(onset_b, strength_b) in self.onsets.iter().skip(i + 1) {
+        let interval = onset_b - onset_a;
+        if interval > max_interval {
+          break;
+        }
+        if interval < min_interval {
+          continue;
+        }
+
+        let bpm = self.fold_bpm(sample_rate * 60. / interval as f32);
+        // Shorter intervals are more likely to be between neighboring beats
+        let weight = strength_a * strength_b / (interval as f32 / sample_rate).max(0.1);
+        // Spread over neighboring bins so that slightly jittery intervals still add up
+        let bin = (bpm - self.min_bpm) / BPM_RESOLUTION;
+        let low_bin = bin.floor() as usize;
+        let frac = bin - low_bin as f32;
+        self.histogram[low_bin.min(last_bin)] += weight * (1. - frac);
+        self.histogram[(low_bin + 1).min(last_bin)] += weight * frac;
+        total_weight += weight;
+      }
+    }
+    if total_weight <= 0. {
+      return None;
+    }
+
+    // Smooths the histogram over a few bins before picking the peak
+    let smoothed: Vec<f32> = (0..self.histogram.len())
+      .map(|bin| {
+        let start = bin.saturating_sub(2);
+        let end = (bin + 3).min(self.histogram.len());
+        self.histogram[start..end].iter().sum()
+      })
+      .collect();
+    let (peak_bin, &peak_weight) = smoothed
+      .iter()
+      .enumerate()
+      .max_by(|a, b| a.1.total_cmp(b.1))?;
+    // Refines the tempo to the weighted center of the bins around the peak
+    let start = peak_bin.saturating_sub(2);
+    let end = (peak_bin + 3).min(self.histogram.len());
+    let centroid = (start..end)
+      .map(|bin| bin as f32 * self.histogram[bin])
+      .sum::<f32>()
+      / self.histogram[start..end]
+        .iter()
+        .sum::<f32>()
+        .max(f32::MIN_POSITIVE);
+    let bpm = self.min_bpm + centroid * BPM_RESOLUTION;
+
+    // Recent onsets count more towards the phase so that it follows drift
+    let samples_per_beat = sample_rate as f64 * 60. / bpm as f64;
+    let newest = self.onsets.back()?.0;
+    let (mut sin_sum, mut cos_sum) = (0., 0.);
+    for &(onset, strength) in &self.onsets {
+      let age_seconds = (newest - onset) as f32 / sample_rate;
+      let weight = strength * (-age_seconds / (HISTORY_SECONDS / 2.)).exp();
+      let phase = ((onset as f64 / samples_per_beat).rem_euclid(1.) as f32) * TAU;
+      sin_sum += phase.sin() * weight;
+      cos_sum += phase.cos() * weight;
+    }
+    let mean_phase = (sin_sum.atan2(cos_sum) / TAU).rem_euclid(1.) as f64;
+    // The beat closest to the newest onset
+    let beats_to_newest = ((newest as f64 / samples_per_beat) - mean_phase).round();
+    let beat_sample = ((beats_to_newest + mean_phase) * samples_per_beat).max(0.) as u64;
+
+    Some(TempoEstimate {
+      bpm,
+      beat_sample,
+      confidence: (peak_weight / total_weight).min(1.),
+    })
+  }
+
+  pub fn reset(&mut self) {
+    self.onsets.clear();
+    self.estimate = None;
+  }
+}
+
+#[test]
+fn tracks_tempo_and_phase_of_regular_onsets() {
+  let sample_rate = 44_100.;
+  let mut tracker = BeatTracker::new(70., 180.);
+  // 128 BPM starting a third of the way into a beat, with some jitter, a missed beat, and an
+  // off-beat hit
+  let samples_per_beat = sample_rate * 60. / 128.;
+  let offset = samples_per_beat / 3.;
+  for beat_ix in 0..16 {
+    if beat_ix == 9 {
+      continue;
+    }
+    let jitter = [0., 60., -40., 20.][beat_ix % 4];
+    let onset = (offset + beat_ix as f32 * samples_per_beat + jitter) as u64;
+    tracker.add_onset(onset, 1., sample_rate);
+    if beat_ix == 5 {
+      tracker.add_onset(onset + samples_per_beat as u64 / 2, 0.5, sample_rate);
+    }
+  }
+
+  let estimate = *tracker.estimate().unwrap();
+  assert!((estimate.bpm - 128.).abs() < 1., "{estimate:?}");
+  let phase = estimate.phase_at(offset as u64, sample_rate);
+  assert!(!(0.05..=0.95).contains(&phase), "{phase}");
+  assert!(estimate.confidence > 0.2, "{estimate:?}");
+
+  // Too few onsets for an estimate
+  tracker.reset();
+  tracker.add_onset(0, 1., sample_rate);
+  tracker.add_onset(20_000, 1., sample_rate);
+  assert!(tracker.estimate().is_none());
+}
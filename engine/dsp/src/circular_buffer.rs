@@ -1,3 +1,8 @@
+/// Fixed-size ring buffer of samples that is read relative to the most recently written value.
+///
+/// When `LENGTH` is a power of two, wrapping is done with a bitmask rather than a modulo.
+/// Since `LENGTH` is a const, the unused branch is compiled out, so buffers in hot paths should be
+/// sized with `usize::next_power_of_two`.
 #[derive(Clone)]
 pub struct CircularBuffer<const LENGTH: usize> {
   buffer: [f32; LENGTH],
@@ -6,6 +11,9 @@ pub struct CircularBuffer<const LENGTH: usize> {
 }
 
 impl<const LENGTH: usize> CircularBuffer<LENGTH> {
+  const IS_POWER_OF_TWO: bool = LENGTH.is_power_of_two();
+  const MASK: usize = LENGTH.wrapping_sub(1);
+
   #[inline]
   pub const fn new() -> Self {
     CircularBuffer {
@@ -14,6 +22,23 @@ impl<const LENGTH: usize> CircularBuffer<LENGTH> {
     }
   }
 
+  /// Maps `ix` to an index into the buffer, wrapping around in both directions
+  #[inline(always)]
+  fn wrap(ix: isize) -> usize {
+    if Self::IS_POWER_OF_TWO {
+      (ix as usize) & Self::MASK
+    } else {
+      ix.rem_euclid(LENGTH as isize) as usize
+    }
+  }
+
+  /// Fills the buffer with zeros in place.  Prefer this over assigning `CircularBuffer::new()` for
+  /// large buffers since that builds the whole array on the stack first.
+  pub fn clear(&mut self) {
+    self.buffer.fill(0.);
+    self.head = 0;
+  }
+
   #[inline]
   pub fn set(&mut self, val: f32) {
     self.head += 1;
@@ -25,17 +50,54 @@ impl<const LENGTH: usize> CircularBuffer<LENGTH> {
   }
 
   /// Returns the value at `head + ix` in the buffer; you're always going to want this to be
-  /// negative to avoid reading either old or uninitialized values
+  /// negative to avoid reading either old or uninitialized values.  `get(0)` returns the most
+  /// recently written value and `get(-(LENGTH as isize) + 1)` the oldest; indices further back than
+  /// that wrap around.
   #[inline]
   pub fn get(&self, ix: isize) -> f32 {
     debug_assert!(ix <= 0);
-    let ix = (self.head as isize + ix) % ((LENGTH - 1) as isize);
+    let ix = Self::wrap(self.head as isize + ix);
+    if cfg!(debug_assertions) {
+      self.buffer[ix]
+    } else {
+      unsafe { *self.buffer.get_unchecked(ix) }
+    }
+  }
 
-    if ix >= 0 {
-      self.buffer[ix as usize]
+  /// Writes all of `vals` to the buffer in order, as if `set` were called for each of them.  If
+  /// `vals` is longer than the buffer, only the last `LENGTH` values are kept.
+  pub fn write_block(&mut self, vals: &[f32]) {
+    if vals.is_empty() {
+      return;
+    }
+    let vals = &vals[vals.len().saturating_sub(LENGTH)..];
+
+    let start = if self.head + 1 == LENGTH {
+      0
     } else {
-      self.buffer[(LENGTH as isize + ix) as usize]
+      self.head + 1
+    };
+    let first_len = (LENGTH - start).min(vals.len());
+    self.buffer[start..start + first_len].copy_from_slice(&vals[..first_len]);
+    self.buffer[..vals.len() - first_len].copy_from_slice(&vals[first_len..]);
+    self.head = (start + vals.len() - 1) % LENGTH;
+  }
+
+  /// Fills `out` with consecutive values starting at `head + offset`, so that `out[i]` is
+  /// `get(offset + i)`.  The whole block must have been written already, meaning
+  /// `offset + out.len() - 1` must be at most zero.
+  pub fn read_block(&self, offset: isize, out: &mut [f32]) {
+    if out.is_empty() {
+      return;
     }
+    debug_assert!(offset > -(LENGTH as isize));
+    debug_assert!(offset + out.len() as isize - 1 <= 0);
+
+    let start = Self::wrap(self.head as isize + offset);
+    let first_len = (LENGTH - start).min(out.len());
+    let second_len = out.len() - first_len;
+    out[..first_len].copy_from_slice(&self.buffer[start..start + first_len]);
+    out[first_len..].copy_from_slice(&self.buffer[..second_len]);
   }
 
   #[inline]
@@ -56,3 +118,45 @@ impl<const LENGTH: usize> CircularBuffer<LENGTH> {
     crate::mix(1. - sample_ix.fract().abs(), base_val, next_val)
   }
 }
+
+#[cfg(test)]
+fn check_against_reference<const LENGTH: usize>() {
+  let mut buf = CircularBuffer::<LENGTH>::new();
+  let mut block_buf = CircularBuffer::<LENGTH>::new();
+  // The buffer starts out filled with zeros
+  let mut written = vec![0.; LENGTH];
+  let mut next_val = 1.;
+
+  for block_len in [1, 3, LENGTH - 1, LENGTH, 2, LENGTH + 5, 7, 1] {
+    let block: Vec<f32> = (0..block_len)
+      .map(|_| {
+        next_val += 1.;
+        next_val
+      })
+      .collect();
+    for &val in &block {
+      buf.set(val);
+    }
+    block_buf.write_block(&block);
+    written.extend_from_slice(&block);
+
+    for ix in 0..LENGTH {
+      let expected = written[written.len() - 1 - ix];
+      assert_eq!(buf.get(-(ix as isize)), expected);
+      assert_eq!(block_buf.get(-(ix as isize)), expected);
+    }
+
+    for read_len in 1..=LENGTH {
+      let offset = -(read_len as isize) + 1;
+      let mut out = vec![0.; read_len];
+      block_buf.read_block(offset, &mut out);
+      assert_eq!(out, written[written.len() - read_len..]);
+    }
+  }
+}
+
+#[test]
+fn indexing_covers_the_whole_buffer() {
+  check_against_reference::<8>();
+  check_against_reference::<13>();
+}
@@ -0,0 +1,121 @@
+//! Crossfades and panning.  A plain linear crossfade between uncorrelated signals dips by 3 dB at
+//! its midpoint, which is audible at loop boundaries and when panning; the equal power law keeps
+//! the summed power constant instead.
+
+use std::f32::consts::FRAC_PI_2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrossfadeLaw {
+  /// Gains sum to 1.  Right for correlated signals (the same signal at two points in time that are
+  /// close together, for example), but dips by 3 dB at the midpoint for uncorrelated ones.
+  Linear,
+  /// Squared gains sum to 1, so the power of uncorrelated signals stays constant throughout
+  EqualPower,
+  /// Like `Linear` but eased in and out with a smoothstep, avoiding the abrupt change in slope at
+  /// either end of the fade
+  SCurve,
+}
+
+impl CrossfadeLaw {
+  /// Returns the gains applied to the signal being faded out and the one being faded in at
+  /// position `t` in `[0, 1]`.
+  #[inline]
+  pub fn gains(self, t: f32) -> (f32, f32) {
+    let t = t.clamp(0., 1.);
+    match self {
+      CrossfadeLaw::Linear => (1. - t, t),
+      CrossfadeLaw::EqualPower => {
+        let (sin, cos) = (t * FRAC_PI_2).sin_cos();
+        (cos, sin)
+      },
+      CrossfadeLaw::SCurve => {
+        let eased = t * t * (3. - 2. * t);
+        (1. - eased, eased)
+      },
+    }
+  }
+}
+
+/// Fades from `a` at `t = 0` to `b` at `t = 1`
+#[inline]
+pub fn crossfade(a: f32, b: f32, t: f32, law: CrossfadeLaw) -> f32 {
+  let (a_gain, b_gain) = law.gains(t);
+  a * a_gain + b * b_gain
+}
+
+/// Crossfades `a` and `b` into `output` with `t` moving linearly from `start_t` at the first sample
+/// to `end_t` at the last.  Processes as many samples as the shortest of the buffers holds.
+pub fn crossfade_block(
+  a: &[f32],
+  b: &[f32],
+  output: &mut [f32],
+  start_t: f32,
+  end_t: f32,
+  law: CrossfadeLaw,
+) {
+  let len = output.len().min(a.len()).min(b.len());
+  let step = if len > 1 {
+    (end_t - start_t) / (len - 1) as f32
+  } else {
+    0.
+  };
+  for (i, ((out, a), b)) in output.iter_mut().zip(a).zip(b).enumerate() {
+    *out = crossfade(*a, *b, start_t + step * i as f32, law);
+  }
+}
+
+/// Returns the left and right gains for panning position `pos` in `[-1, 1]`, where -1 is hard left.
+/// Both gains are `sqrt(0.5)` in the center.
+#[inline]
+pub fn equal_power_pan(pos: f32) -> (f32, f32) {
+  CrossfadeLaw::EqualPower.gains((pos.clamp(-1., 1.) + 1.) / 2.)
+}
+
+/// Pans `input` into `left` and `right` with the position moving linearly from `start_pos` at the
+/// first sample to `end_pos` at the last.
+pub fn equal_power_pan_block(
+  input: &[f32],
+  left: &mut [f32],
+  right: &mut [f32],
+  start_pos: f32,
+  end_pos: f32,
+) {
+  let len = input.len().min(left.len()).min(right.len());
+  let step = if len > 1 {
+    (end_pos - start_pos) / (len - 1) as f32
+  } else {
+    0.
+  };
+  for i in 0..len {
+    let (left_gain, right_gain) = equal_power_pan(start_pos + step * i as f32);
+    left[i] = input[i] * left_gain;
+    right[i] = input[i] * right_gain;
+  }
+}
+
+#[test]
+fn crossfade_laws() {
+  for law in [
+    CrossfadeLaw::Linear,
+    CrossfadeLaw::EqualPower,
+    CrossfadeLaw::SCurve,
+  ] {
+    assert_eq!(law.gains(0.), (1., 0.));
+    let (a_gain, b_gain) = law.gains(1.);
+    assert!(a_gain.abs() < 1e-6 && (b_gain - 1.).abs() < 1e-6);
+  }
+
+  for t in [0.1, 0.25, 0.5, 0.9] {
+    let (a_gain, b_gain) = CrossfadeLaw::EqualPower.gains(t);
+    assert!(
+      (a_gain * a_gain + b_gain * b_gain - 1.).abs() < 1e-6,
+      "t={t}"
+    );
+    let (a_gain, b_gain) = CrossfadeLaw::SCurve.gains(t);
+    assert!((a_gain + b_gain - 1.).abs() < 1e-6, "t={t}");
+  }
+
+  let (left, right) = equal_power_pan(0.);
+  assert!((left - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+  assert!((right - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+}
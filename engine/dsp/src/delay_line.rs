@@ -0,0 +1,162 @@
+//! Fractional delay line built on top of `CircularBuffer`.  Intended as the shared building block
+//! for echo, chorus, flanger, and similar effects so that they don't each need to handle buffer
+//! indexing and interpolation themselves.
+
+use crate::{circular_buffer::CircularBuffer, ms_to_samples};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DelayInterpolation {
+  /// Cheap and works well for any delay time, but slightly lowpasses the output for fractional
+  /// delays.
+  Linear,
+  /// First-order allpass interpolation.  Has a flat magnitude response, which makes it a good fit
+  /// for feedback delays and slowly modulated effects like chorus and flanger.  It has internal
+  /// state, so jumping the delay time abruptly can cause small transients.
+  Allpass,
+}
+
+/// Delays its input by a possibly fractional number of samples.  Delay times are measured such that
+/// calling `read` before `write` for each sample gives an output delayed by exactly the delay
+/// time; this means the minimum delay is one sample and the maximum is `LENGTH - 1` samples.
+#[derive(Clone)]
+pub struct DelayLine<const LENGTH: usize> {
+  buffer: CircularBuffer<LENGTH>,
+  interpolation: DelayInterpolation,
+  delay_samples: f32,
+  /// Previous output of the allpass interpolator
+  allpass_state: f32,
+}
+
+impl<const LENGTH: usize> DelayLine<LENGTH> {
+  pub const MAX_DELAY_SAMPLES: f32 = (LENGTH - 1) as f32;
+  pub const MIN_DELAY_SAMPLES: f32 = 1.;
+
+  pub const fn new(interpolation: DelayInterpolation) -> Self {
+    DelayLine {
+      buffer: CircularBuffer::new(),
+      interpolation,
+      delay_samples: Self::MIN_DELAY_SAMPLES,
+      allpass_state: 0.,
+    }
+  }
+
+  /// Sets the delay time used by `read` and the `process` helpers, clamped to
+  /// `[MIN_DELAY_SAMPLES, MAX_DELAY_SAMPLES]`.  This can be called every sample to modulate the
+  /// delay time.
+  #[inline]
+  pub fn set_delay_samples(&mut self, delay_samples: f32) {
+    self.delay_samples = Self::clamp_delay(delay_samples);
+  }
+
+  #[inline]
+  pub fn set_delay_ms(&mut self, delay_ms: f32) { self.set_delay_samples(ms_to_samples(delay_ms)) }
+
+  pub fn delay_samples(&self) -> f32 { self.delay_samples }
+
+  #[inline]
+  fn clamp_delay(delay_samples: f32) -> f32 {
+    if delay_samples.is_nan() {
+      return Self::MIN_DELAY_SAMPLES;
+    }
+    delay_samples.clamp(Self::MIN_DELAY_SAMPLES, Self::MAX_DELAY_SAMPLES)
+  }
+
+  /// Reads the delayed sample at the current delay time
+  #[inline]
+  pub fn read(&mut self) -> f32 { self.read_at(self.delay_samples) }
+
+  /// Reads the delayed sample at `delay_samples` without changing the current delay time.  Useful
+  /// for multi-tap delays.
+  #[inline]
+  pub fn read_at(&mut self, delay_samples: f32) -> f32 {
+    let delay_samples = Self::clamp_delay(delay_samples);
+    // `get(0)` holds the sample written one call ago, which has a delay of 1
+    let offset = delay_samples - 1.;
+
+    match self.interpolation {
+      DelayInterpolation::Linear => self.buffer.read_interpolated(-offset),
+      DelayInterpolation::Allpass => {
+        // Keep the fractional part in `(0, 1]` so that the allpass coefficient stays in `[0, 1)`
+        let mut int_part = offset.floor();
+        let mut frac = offset - int_part;
+        if frac == 0. {
+          if int_part == 0. {
+            // Minimum delay; there's no older sample to borrow from
+            self.allpass_state = self.buffer.get(0);
+            return self.allpass_state;
+          }
+          int_part -= 1.;
+          frac = 1.;
+        }
+        let int_part = int_part as isize;
+        let coefficient = (1. - frac) / (1. + frac);
+
+        let newer = self.buffer.get(-int_part);
+        let older = self.buffer.get(-int_part - 1);
+        let output = coefficient * newer + older - coefficient * self.allpass_state;
+        self.allpass_state = output;
+        output
+      },
+    }
+  }
+
+  #[inline]
+  pub fn write(&mut self, sample: f32) { self.buffer.set(sample); }
+
+  /// Reads the delayed sample and then writes `input`
+  #[inline]
+  pub fn process(&mut self, input: f32) -> f32 {
+    let output = self.read();
+    self.write(input);
+    output
+  }
+
+  /// Reads the delayed sample and then writes `input` plus the delayed sample scaled by `feedback`.
+  /// Returns the delayed sample.
+  #[inline]
+  pub fn process_with_feedback(&mut self, input: f32, feedback: f32) -> f32 {
+    let output = self.read();
+    self.write(input + output * feedback);
+    output
+  }
+
+  /// Clears the buffer and interpolator state, leaving the delay time unchanged
+  pub fn reset(&mut self) {
+    self.buffer.clear();
+    self.allpass_state = 0.;
+  }
+}
+
+#[test]
+fn integer_delays_are_exact() {
+  for interpolation in [DelayInterpolation::Linear, DelayInterpolation::Allpass] {
+    let mut delay: DelayLine<64> = DelayLine::new(interpolation);
+    delay.set_delay_samples(5.);
+    let output: Vec<f32> = (0..20)
+      .map(|i| delay.process(if i == 0 { 1. } else { 0. }))
+      .collect();
+    let mut expected = vec![0.; 20];
+    expected[5] = 1.;
+    assert_eq!(output, expected, "{interpolation:?}");
+  }
+}
+
+#[test]
+fn fractional_delays_are_accurate() {
+  // A slow sine delayed by a fractional amount should come out at the same level, shifted in time
+  for interpolation in [DelayInterpolation::Linear, DelayInterpolation::Allpass] {
+    let mut delay: DelayLine<256> = DelayLine::new(interpolation);
+    delay.set_delay_samples(10.5);
+    let freq = 0.01;
+    let mut max_err: f32 = 0.;
+    for i in 0..2000 {
+      let input = (i as f32 * freq * std::f32::consts::TAU).sin();
+      let output = delay.process(input);
+      if i > 200 {
+        let expected = ((i as f32 - 10.5) * freq * std::f32::consts::TAU).sin();
+        max_err = max_err.max((output - expected).abs());
+      }
+    }
+    assert!(max_err < 0.01, "{interpolation:?}: {max_err}");
+  }
+}
@@ -0,0 +1,117 @@
+//! In-place radix-2 FFT, used for fast convolution with long FIR filters.  Real and imaginary parts
+//! are kept in separate buffers.
+
+use std::f64::consts::PI;
+
+pub struct Fft {
+  size: usize,
+  /// Real and imaginary parts of `e^(-2 * PI * i * k / size)` for `k` in `0..size / 2`
+  twiddles_re: Vec<f32>,
+  twiddles_im: Vec<f32>,
+  /// Index that each element is moved to before the butterflies
+  bit_reversed: Vec<u32>,
+}
+
+impl Fft {
+  /// `size` must be a power of two
+  pub fn new(size: usize) -> Self {
+    assert!(size.is_power_of_two(), "FFT size must be a power of two");
+    let bits = size.trailing_zeros();
+    let (twiddles_re, twiddles_im) = (0..size / 2)
+      .map(|k| {
+        let phase = -2. * PI * k as f64 / size as f64;
+        (phase.cos() as f32, phase.sin() as f32)
+      })
+      .unzip();
+    let bit_reversed = (0..size as u32)
+      .map(|i| {
+        if bits == 0 {
+          0
+        } else {
+          i.reverse_bits() >> (32 - bits)
+        }
+      })
+      .collect();
+
+    Fft {
+      size,
+      twiddles_re,
+      twiddles_im,
+      bit_reversed,
+    }
+  }
+
+  pub fn size(&self) -> usize { self.size }
+
+  fn transform(&self, re: &mut [f32], im: &mut [f32], inverse: bool) {
+    assert_eq!(re.len(), self.size);
+    assert_eq!(im.len(), self.size);
+
+    for (i, &j) in self.bit_reversed.iter().enumerate() {
+      let j = j as usize;
+      if j > i {
+        re.swap(i, j);
+        im.swap(i, j);
+      }
+    }
+
+    let mut half_len = 1;
+    while half_len < self.size {
+      let twiddle_stride = self.size / (half_len * 2);
+      for start in (0..self.size).step_by(half_len * 2) {
+        for k in 0..half_len {
+          let w_re = self.twiddles_re[k * twiddle_stride];
+          let w_im = self.twiddles_im[k * twiddle_stride];
+          let w_im = if inverse { -w_im } else { w_im };
+          let (a, b) = (start + k, start + k + half_len);
+          let t_re = re[b] * w_re - im[b] * w_im;
+          let t_im = re[b] * w_im + im[b] * w_re;
+          re[b] = re[a] - t_re;
+          im[b] = im[a] - t_im;
+          re[a] += t_re;
+          im[a] += t_im;
+        }
+      }
+      half_len *= 2;
+    }
+  }
+
+  pub fn forward(&self, re: &mut [f32], im: &mut [f32]) { self.transform(re, im, false); }
+
+  /// Scaled by `1 / size`, so this exactly undoes `forward`
+  pub fn inverse(&self, re: &mut [f32], im: &mut [f32]) {
+    self.transform(re, im, true);
+    let scale = 1. / self.size as f32;
+    for (re, im) in re.iter_mut().zip(im.iter_mut()) {
+      *re *= scale;
+      *im *= scale;
+    }
+  }
+}
+
+#[test]
+fn matches_naive_dft_and_round_trips() {
+  let size = 64;
+  let input: Vec<f32> = (0..size).map(|i| (i as f32 * 0.7).sin() + 0.25).collect();
+  let fft = Fft::new(size);
+  let mut re = input.clone();
+  let mut im = vec![0.; size];
+  fft.forward(&mut re, &mut im);
+
+  for k in 0..size {
+    let (mut expected_re, mut expected_im) = (0., 0.);
+    for (n, &x) in input.iter().enumerate() {
+      let phase = -2. * PI * (k * n) as f64 / size as f64;
+      expected_re += x as f64 * phase.cos();
+      expected_im += x as f64 * phase.sin();
+    }
+    assert!((re[k] as f64 - expected_re).abs() < 1e-4, "bin {k}");
+    assert!((im[k] as f64 - expected_im).abs() < 1e-4, "bin {k}");
+  }
+
+  fft.inverse(&mut re, &mut im);
+  for (x, (re, im)) in input.iter().zip(re.iter().zip(im.iter())) {
+    assert!((x - re).abs() < 1e-5);
+    assert!(im.abs() < 1e-5);
+  }
+}
@@ -1,6 +1,6 @@
 use std::f32::consts::PI;
 
-use crate::{linear_to_db_checked, NYQUIST};
+use crate::{linear_to_db_checked, nyquist};
 
 /// Second-order biquad filter
 #[derive(Clone, Copy, Default)]
@@ -30,7 +30,7 @@ impl BiquadFilter {
   pub fn set_coefficients(&mut self, mode: FilterMode, q: f32, detune: f32, freq: f32, gain: f32) {
     // From: https://webaudio.github.io/web-audio-api/#filters-characteristics
     let computed_frequency = freq * 2.0f32.powf(detune / 1200.0);
-    let normalized_freq = computed_frequency / NYQUIST;
+    let normalized_freq = computed_frequency / nyquist();
     let w0 = PI * normalized_freq;
     #[allow(non_snake_case)]
     let A = 10.0_f32.powf(gain / 40.0);
@@ -127,6 +127,13 @@ impl BiquadFilter {
 
     output
   }
+
+  /// Clears the filter's state, leaving its coefficients unchanged
+  #[inline]
+  pub fn reset(&mut self) {
+    self.x = [0.; 2];
+    self.y = [0.; 2];
+  }
 }
 
 /// Coefficients and state are stored as SoA.  Since applying biquad filter chains has a serial
@@ -264,6 +271,38 @@ impl<const BANK_COUNT: usize, const BANK_LENGTH: usize>
     }
     // }
   }
+
+  /// Portable version of the above for native targets.  Since the state is stored as SoA, the
+  /// compiler is able to auto-vectorize this.
+  #[cfg(not(target_arch = "wasm32"))]
+  #[inline]
+  pub fn apply_simd(&mut self, output: &mut [f32; BANK_COUNT], depth: usize) {
+    let b0_over_a0 = &self.b0_over_a0[depth];
+    let b1_over_a0 = &self.b1_over_a0[depth];
+    let b2_over_a0 = &self.b2_over_a0[depth];
+    let a1_over_a0 = &self.a1_over_a0[depth];
+    let a2_over_a0 = &self.a2_over_a0[depth];
+    let x0 = &mut self.x0[depth];
+    let x1 = &mut self.x1[depth];
+    let y0 = &mut self.y0[depth];
+    let y1 = &mut self.y1[depth];
+
+    for band_ix in 0..BANK_COUNT {
+      let ins = output[band_ix];
+      let outs = b0_over_a0[band_ix] * ins
+        + b1_over_a0[band_ix] * x0[band_ix]
+        + b2_over_a0[band_ix] * x1[band_ix]
+        - a1_over_a0[band_ix] * y0[band_ix]
+        - a2_over_a0[band_ix] * y1[band_ix];
+
+      x1[band_ix] = x0[band_ix];
+      x0[band_ix] = ins;
+      y1[band_ix] = y0[band_ix];
+      y0[band_ix] = outs;
+
+      output[band_ix] = outs;
+    }
+  }
 }
 
 /// higher-order filter Q factors determined using this: https://www.earlevel.com/main/2016/09/29/cascading-filters/
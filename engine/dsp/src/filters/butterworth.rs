@@ -1,4 +1,4 @@
-use crate::SAMPLE_RATE;
+use crate::sample_rate;
 
 #[derive(Clone, Copy, Default)]
 pub struct ButterworthFilter {
@@ -43,7 +43,7 @@ impl ButterworthFilter {
       crate::clamp_normalize(1., 18_000., cutoff_freq),
       0.99,
     );
-    let c = 1. / ((std::f32::consts::PI / SAMPLE_RATE) * cutoff_freq).tan();
+    let c = 1. / ((std::f32::consts::PI / sample_rate()) * cutoff_freq).tan();
     let c2 = c * c;
     let csqr2 = std::f32::consts::SQRT_2 * c;
     let d = c2 + csqr2 + 1.;
@@ -68,7 +68,7 @@ impl ButterworthFilter {
       crate::clamp_normalize(1., 18_000., cutoff_freq),
       0.99,
     );
-    let mut c = ((std::f32::consts::PI / SAMPLE_RATE) * cutoff_freq).tan();
+    let mut c = ((std::f32::consts::PI / sample_rate()) * cutoff_freq).tan();
     if c.abs() < 0.002 {
       c = c.signum() * 0.002;
     }
@@ -97,7 +97,7 @@ impl ButterworthFilter {
       crate::clamp_normalize(1., 18_000., cutoff_freq),
       0.99,
     );
-    let c = 1. / ((std::f32::consts::PI / SAMPLE_RATE) * cutoff_freq).tan();
+    let c = 1. / ((std::f32::consts::PI / sample_rate()) * cutoff_freq).tan();
     let d = 1. + c;
     let amp_in0 = 1. / d;
     let amp_in1 = 0.;
@@ -105,7 +105,7 @@ impl ButterworthFilter {
     let amp_out1 =
             // TODO: Verify that this is correct; it was `cutoffFreq/sr` and idk what sr is but
             // I can't think of anything else
-            (-c * 2. * (std::f32::consts::PI * 2. * cutoff_freq / SAMPLE_RATE).cos()) / d;
+            (-c * 2. * (std::f32::consts::PI * 2. * cutoff_freq / sample_rate()).cos()) / d;
     let amp_out2 = (c - 1.) / d;
 
     let output = self.get_output(amp_in0, amp_in1, amp_in2, amp_out1, amp_out2, input);
@@ -116,3 +116,25 @@ impl ButterworthFilter {
     output
   }
 }
+
+#[test]
+fn lowpass_cutoff_is_independent_of_sample_rate() {
+  for sample_rate in [44_100., 48_000., 96_000.] {
+    assert!(crate::set_sample_rate(sample_rate));
+    let mut filter = ButterworthFilter::default();
+    let sample_count = sample_rate as usize * 2;
+    let mut peak = 0.0f32;
+    for i in 0..sample_count {
+      let input = (2. * std::f32::consts::PI * 1_000. * i as f32 / sample_rate).cos();
+      let output = filter.lowpass(1_000., input);
+      if i > sample_count - sample_rate as usize / 10 {
+        peak = peak.max(output.abs());
+      }
+    }
+    // -3dB at the cutoff frequency
+    assert!(
+      (peak - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.01,
+      "sample_rate={sample_rate}, peak={peak}"
+    );
+  }
+}
@@ -1,12 +1,118 @@
-/// https://ccrma.stanford.edu/~jos/fp/DC_Blocker.html
+//! https://ccrma.stanford.edu/~jos/fp/DC_Blocker.html
+//!
+//! One-pole highpass that removes DC offset and subsonic content.  Asymmetric nonlinearities
+//! (wavefolders, driven filters, etc.) introduce DC that eats headroom and skews envelope detection
+//! further down the chain.
 
-#[derive(Clone, Default)]
+/// Used by `DCBlocker::default()`.  Since it doesn't take the sample rate into account, the cutoff
+/// is about 35 Hz at 44.1 kHz and rises with the sample rate.  Use `DCBlocker::new` where the
+/// cutoff matters.
+const DEFAULT_POLE: f32 = 0.995;
+/// Configurable cutoffs are clamped to this range.  Anything lower takes too long to settle, and
+/// anything higher starts eating into the audible bass.
+pub const MIN_CUTOFF_HZ: f32 = 5.;
+pub const MAX_CUTOFF_HZ: f32 = 30.;
+/// Well below the audible range while still settling quickly
+pub const DEFAULT_SUBSONIC_CUTOFF_HZ: f32 = 10.;
+
+#[derive(Clone)]
 pub struct DCBlocker {
-  last_sample: f32,
+  pole: f32,
+  last_input: f32,
+  last_output: f32,
+}
+
+impl Default for DCBlocker {
+  fn default() -> Self {
+    DCBlocker {
+      pole: DEFAULT_POLE,
+      last_input: 0.,
+      last_output: 0.,
+    }
+  }
+}
+
+fn compute_pole(cutoff_hz: f32) -> f32 {
+  let cutoff_hz = cutoff_hz.clamp(MIN_CUTOFF_HZ, MAX_CUTOFF_HZ);
+  (-std::f32::consts::TAU * cutoff_hz / crate::sample_rate()).exp()
 }
 
 impl DCBlocker {
+  /// Creates a blocker with its -3 dB point at `cutoff_hz`, clamped to
+  /// `[MIN_CUTOFF_HZ, MAX_CUTOFF_HZ]`, for the current sample rate.
+  pub fn new(cutoff_hz: f32) -> Self {
+    DCBlocker {
+      pole: compute_pole(cutoff_hz),
+      ..Default::default()
+    }
+  }
+
+  pub fn set_cutoff_hz(&mut self, cutoff_hz: f32) { self.pole = compute_pole(cutoff_hz); }
+
+  #[inline]
   pub fn apply(&mut self, sample: f32) -> f32 {
-    sample - self.last_sample + 0.995 * self.last_sample
+    let output = sample - self.last_input + self.pole * self.last_output;
+    self.last_input = sample;
+    self.last_output = output;
+    output
+  }
+
+  pub fn apply_all(&mut self, samples: &mut [f32]) {
+    for sample in samples {
+      *sample = self.apply(*sample);
+    }
+  }
+
+  pub fn reset(&mut self) {
+    self.last_input = 0.;
+    self.last_output = 0.;
+  }
+}
+
+#[test]
+fn dc_blocker_removes_offset() {
+  let mut blocker = DCBlocker::default();
+  let mut out = 0.;
+  for _ in 0..10_000 {
+    out = blocker.apply(0.5);
+  }
+  assert!(out.abs() < 1e-4);
+}
+
+#[test]
+fn configurable_cutoff() {
+  // Settling time scales inversely with the cutoff
+  for cutoff_hz in [MIN_CUTOFF_HZ, DEFAULT_SUBSONIC_CUTOFF_HZ, MAX_CUTOFF_HZ] {
+    let mut blocker = DCBlocker::new(cutoff_hz);
+    let mut out = 0.;
+    for _ in 0..(crate::sample_rate() * 2.) as usize {
+      out = blocker.apply(0.5);
+    }
+    assert!(out.abs() < 1e-3, "cutoff_hz={cutoff_hz}, out={out}");
+  }
+
+  // A 1 kHz tone passes essentially untouched
+  let mut blocker = DCBlocker::new(MAX_CUTOFF_HZ);
+  let mut peak = 0.0f32;
+  for i in 0..44_100 {
+    let sample = (std::f32::consts::TAU * 1000. * i as f32 / crate::sample_rate()).sin();
+    let out = blocker.apply(sample);
+    if i > 22_050 {
+      peak = peak.max(out.abs());
+    }
+  }
+  assert!(peak > 0.99, "peak={peak}");
+}
+
+#[test]
+fn dc_blocker_impulse_response() {
+  let mut blocker = DCBlocker::default();
+  let response: Vec<f32> = [1., 0., 0.]
+    .into_iter()
+    .map(|sample| blocker.apply(sample))
+    .collect();
+  let expected = [1., DEFAULT_POLE - 1., DEFAULT_POLE * (DEFAULT_POLE - 1.)];
+  for (actual, expected) in response.into_iter().zip(expected) {
+    assert!((actual - expected).abs() < 1e-7, "{actual} != {expected}");
   }
 }
@@ -1,17 +1,69 @@
 #![feature(portable_simd)]
 
+use std::cell::Cell;
+
 use fastapprox::fast;
 
 pub mod band_splitter;
+pub mod beat_tracker;
 pub mod circular_buffer;
+pub mod crossfade;
+pub mod delay_line;
+pub mod fft;
 pub mod filters;
 pub mod lookup_tables;
+pub mod onset;
 pub mod oscillator;
+pub mod output_guard;
+pub mod profiling;
+pub mod quality;
+pub mod resampler;
 pub mod rms_level_detector;
-
-pub const SAMPLE_RATE: f32 = 44_100.;
-pub const NYQUIST: f32 = SAMPLE_RATE / 2.;
+pub mod smoothed_param;
+pub mod spectral_features;
+pub mod time_stretch;
+pub mod tuning;
+
+/// Sample rate used until `set_sample_rate` is called
+pub const DEFAULT_SAMPLE_RATE: f32 = 44_100.;
+pub const MIN_SAMPLE_RATE: f32 = 8_000.;
+/// Buffers whose length is defined in terms of time are sized to hold that much audio at this
+/// sample rate.
+pub const MAX_SAMPLE_RATE: f32 = 96_000.;
+/// Size of the Web Audio render quantum, which is the block size that most modules process
 pub const FRAME_SIZE: usize = 128;
+/// Largest block that modules with variable block size support can process at once
+pub const MAX_FRAME_SIZE: usize = 1024;
+
+thread_local! {
+  // Each Wasm module instance is single-threaded and has its own copy of this, so it's effectively
+  // a global.  Keeping it thread-local lets tests run at different sample rates in parallel.
+  static SAMPLE_RATE: Cell<f32> = const { Cell::new(DEFAULT_SAMPLE_RATE) };
+}
+
+/// Sets the sample rate that all coefficient and time computations are based on.  This should be
+/// called once when the module is initialized, before any state is created; state that was created
+/// before the sample rate changed isn't updated.
+///
+/// Returns `false` and leaves the sample rate unchanged if `sample_rate` is outside of
+/// `[MIN_SAMPLE_RATE, MAX_SAMPLE_RATE]`.
+#[no_mangle]
+pub extern "C" fn set_sample_rate(sample_rate: f32) -> bool {
+  if !(MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&sample_rate) {
+    return false;
+  }
+  SAMPLE_RATE.with(|sr| sr.set(sample_rate));
+  true
+}
+
+#[inline]
+pub fn sample_rate() -> f32 { SAMPLE_RATE.with(Cell::get) }
+
+#[inline]
+pub fn nyquist() -> f32 { sample_rate() / 2. }
+
+#[inline]
+pub fn ms_to_samples(ms: f32) -> f32 { ms * 0.001 * sample_rate() }
 
 /// For `coefficient` values between 0 and 1, applies smoothing to a value, interpolating between
 /// previous values and new values.  Values closer to 0 applier heavier smoothing.
@@ -178,3 +230,19 @@ fn test_quantize() {
   assert_eq!(quantize(0., 1., 1., 0.4), 0.);
   assert_eq!(quantize(0., 1., 1., 0.6), 1.);
 }
+
+#[test]
+fn sample_rate_configuration() {
+  assert_eq!(sample_rate(), DEFAULT_SAMPLE_RATE);
+  for sr in [44_100., 48_000., 96_000.] {
+    assert!(set_sample_rate(sr));
+    assert_eq!(sample_rate(), sr);
+    assert_eq!(nyquist(), sr / 2.);
+    assert_eq!(ms_to_samples(500.), sr / 2.);
+  }
+
+  assert!(!set_sample_rate(0.));
+  assert!(!set_sample_rate(f32::NAN));
+  assert!(!set_sample_rate(1_000_000.));
+  assert_eq!(sample_rate(), 96_000.);
+}
@@ -0,0 +1,316 @@
+//! Onset detection for finding the hits in a sample or recorded loop, or in live input.
+//!
+//! Offline, the source is split into short hops and the rise in level from one hop to the next is
+//! measured in dB.  Hops where that rise is a local peak and clears both an adaptive threshold and
+//! the configured sensitivity are treated as onsets, and each onset is then refined to the sample
+//! where the attack starts.
+//!
+//! Live input is handled by `SpectralFluxDetector` instead, which measures how much energy appears
+//! across the spectrum from one hop to the next.  That picks up new notes that don't change the
+//! overall level much, and it only needs a single hop of lookahead.
+
+use std::{collections::VecDeque, f32::consts::PI};
+
+use crate::fft::Fft;
+
+/// Length of the hops that levels are measured over
+const HOP_MS: f32 = 5.;
+/// Hops quieter than this are ignored so that noise floors and reverb tails don't produce onsets
+const MIN_LEVEL_DB: f32 = -60.;
+/// Number of hops on either side of a candidate that it must be the largest rise among
+const PEAK_RADIUS_HOPS: usize = 3;
+/// Number of hops on either side of a candidate that are averaged to get the adaptive threshold
+const MEAN_RADIUS_HOPS: usize = 10;
+
+#[derive(Clone, Copy, Debug)]
+pub struct OnsetDetectionParams {
+  /// In [0, 1].  Higher values detect quieter and less sharp attacks.
+  pub sensitivity: f32,
+  /// Onsets closer than this to the previous one are dropped
+  pub min_gap_samples: usize,
+}
+
+fn hop_levels_db(source: &[f32], hop: usize) -> Vec<f32> {
+  source
+    .chunks(hop)
+    .map(|chunk| {
+      let mean_square = chunk.iter().map(|&x| x * x).sum::<f32>() / chunk.len() as f32;
+      10. * (mean_square + 1e-12).log10()
+    })
+    .collect()
+}
+
+/// Finds the start of the attack in the hop starting at `hop_start`: the zero crossing preceding
+/// the first sample that reaches a quarter of the hop's peak
+fn refine_onset(source: &[f32], hop_start: usize, hop: usize) -> usize {
+  let hop_end = (hop_start + hop).min(source.len());
+  let peak = source[hop_start..hop_end]
+    .iter()
+    .fold(0.0f32, |acc, &x| acc.max(x.abs()));
+  let attack_ix = (hop_start..hop_end)
+    .find(|&i| source[i].abs() >= peak * 0.25)
+    .unwrap_or(hop_start);
+
+  let search_start = attack_ix.saturating_sub(hop);
+  (search_start + 1..=attack_ix)
+    .rev()
+    .find(|&i| (source[i - 1] <= 0.) != (source[i] <= 0.))
+    .unwrap_or(attack_ix)
+}
+
+/// Returns the sample indices of the onsets in `source` in ascending order
+pub fn detect_onsets(source: &[f32], params: OnsetDetectionParams) -> Vec<usize> {
+  let hop = (crate::ms_to_samples(HOP_MS).round() as usize).max(1);
+  let levels = hop_levels_db(source, hop);
+  if levels.len() < 2 {
+    return Vec::new();
+  }
+
+  // The first hop is compared against silence so that a hit right at the start is detected
+  let rises: Vec<f32> = std::iter::once((levels[0] - MIN_LEVEL_DB).max(0.))
+    .chain(levels.windows(2).map(|pair| (pair[1] - pair[0]).max(0.)))
+    .collect();
+  let sensitivity = params.sensitivity.clamp(0., 1.);
+  // Ranges from a 12dB jump at the lowest sensitivity down to a 1dB jump at the highest
+  let min_rise_db = 1. + (1. - sensitivity) * 11.;
+
+  let mut onsets: Vec<usize> = Vec::new();
+  for (hop_ix, &rise) in rises.iter().enumerate() {
+    if rise < min_rise_db || levels[hop_ix] < MIN_LEVEL_DB {
+      continue;
+    }
+
+    let neighborhood =
+      |radius: usize| &rises[hop_ix.saturating_sub(radius)..(hop_ix + radius + 1).min(rises.len())];
+    // Ties go to the earliest hop so that plateaus produce a single onset
+    let is_peak = neighborhood(PEAK_RADIUS_HOPS)
+      .iter()
+      .enumerate()
+      .all(|(i, &other)| {
+        let other_ix = hop_ix.saturating_sub(PEAK_RADIUS_HOPS) + i;
+        other < rise || (other == rise && other_ix >= hop_ix)
+      });
+    let local = neighborhood(MEAN_RADIUS_HOPS);
+    let local_mean = local.iter().sum::<f32>() / local.len() as f32;
+    if !is_peak || rise < local_mean * 2. {
+      continue;
+    }
+
+    let onset = refine_onset(source, hop_ix * hop, hop);
+    let far_enough = onsets
+      .last()
+      .is_none_or(|&prev| onset >= prev + params.min_gap_samples);
+    if far_enough {
+      onsets.push(onset);
+    }
+  }
+
+  onsets
+}
+
+const FLUX_FFT_SIZE: usize = 1024;
+const FLUX_HOP_SIZE: usize = 256;
+/// Number of previous hops whose flux is averaged to get the adaptive threshold
+const FLUX_MEAN_HOPS: usize = 16;
+/// Flux below this never counts as an onset so that noise floors don't trigger detections
+const MIN_FLUX: f32 = 0.02;
+
+/// Realtime onset detector based on spectral flux: the total increase in log magnitude across all
+/// bins from one hop to the next.  A hop is an onset if its flux is a local peak that clears the
+/// recent average by a margin set by the sensitivity.
+pub struct SpectralFluxDetector {
+  fft: Fft,
+  window: Vec<f32>,
+  /// Circular buffer holding the most recent `FLUX_FFT_SIZE` samples
+  input: Vec<f32>,
+  write_ix: usize,
+  samples_until_hop: usize,
+  re: Vec<f32>,
+  im: Vec<f32>,
+  prev_log_magnitudes: Vec<f32>,
+  /// Flux of recent hops, most recent last
+  flux_history: VecDeque<f32>,
+  /// Total number of samples processed, used to timestamp onsets
+  sample_count: u64,
+  last_onset: Option<u64>,
+  /// In [0, 1].  Higher values detect quieter and less sharp attacks.
+  pub sensitivity: f32,
+  /// Onsets closer than this to the previous one are dropped
+  pub min_gap_samples: u64,
+}
+
+impl Default for SpectralFluxDetector {
+  fn default() -> Self {
+    SpectralFluxDetector {
+      fft: Fft::new(FLUX_FFT_SIZE),
+      window: (0..FLUX_FFT_SIZE)
+        .map(|i| 0.5 - 0.5 * (2. * PI * i as f32 / FLUX_FFT_SIZE as f32).cos())
+        .collect(),
+      input: vec![0.; FLUX_FFT_SIZE],
+      write_ix: 0,
+      samples_until_hop: FLUX_HOP_SIZE,
+      re: vec![0.; FLUX_FFT_SIZE],
+      im: vec![0.; FLUX_FFT_SIZE],
+      prev_log_magnitudes: vec![0.; FLUX_FFT_SIZE / 2],
+      flux_history: VecDeque::with_capacity(FLUX_MEAN_HOPS + 1),
+      sample_count: 0,
+      last_onset: None,
+      sensitivity: 0.5,
+      min_gap_samples: crate::ms_to_samples(50.) as u64,
+    }
+  }
+}
+
+impl SpectralFluxDetector {
+  /// Total number of samples processed since the detector was created or reset
+  pub fn sample_count(&self) -> u64 { self.sample_count }
+
+  /// Buffers `samples`, calling `on_onset` with the sample index and flux of each onset detected.
+  /// Onsets are reported one hop after they occur since a peak can't be confirmed until the hop
+  /// after it has been measured.
+  pub fn process(&mut self, samples: &[f32], mut on_onset: impl FnMut(u64, f32)) {
+    for &sample in samples {
+      self.input[self.write_ix] = if sample.is_finite() { sample } else { 0. };
+      self.write_ix = (self.write_ix + 1) % FLUX_FFT_SIZE;
+      self.sample_count += 1;
+      self.samples_until_hop -= 1;
+      if self.samples_until_hop == 0 {
+        self.samples_until_hop = FLUX_HOP_SIZE;
+        let flux = self.measure_flux();
+        if let Some(onset) = self.pick_peak(flux) {
+          on_onset(onset.0, onset.1);
+        }
+      }
+    }
+  }
+
+  fn measure_flux(&mut self) -> f32 {
+    // `write_ix` points at the oldest sample
+    for i in 0..FLUX_FFT_SIZE {
+      self.re[i] = self.input[(self.write_ix + i) % FLUX_FFT_SIZE] * self.window[i];
+    }
+    self.im.fill(0.);
+    self.fft.forward(&mut self.re, &mut self.im);
+
+    let mut flux = 0.;
+    for (bin_ix, prev) in self.prev_log_magnitudes.iter_mut().enumerate() {
+      let magnitude = (self.re[bin_ix].powi(2) + self.im[bin_ix].powi(2)).sqrt();
+      // Log compression keeps loud low notes from drowning out changes in the highs
+      let log_magnitude = (1. + magnitude).ln();
+      flux += (log_magnitude - *prev).max(0.);
+      *prev = log_magnitude;
+    }
+    flux / self.prev_log_magnitudes.len() as f32
+  }
+
+  /// Adds the flux of the newest hop and checks whether the hop before it was an onset
+  fn pick_peak(&mut self, flux: f32) -> Option<(u64, f32)> {
+    self.flux_history.push_back(flux);
+    if self.flux_history.len() > FLUX_MEAN_HOPS + 1 {
+      self.flux_history.pop_front();
+    }
+    let len = self.flux_history.len();
+    if len < 3 {
+      return None;
+    }
+
+    let candidate = self.flux_history[len - 2];
+    let is_peak = candidate > self.flux_history[len - 3] && candidate >= flux;
+    let mean = self.flux_history.iter().take(len - 2).sum::<f32>() / (len - 2) as f32;
+    // Ranges from 3x the recent average at the lowest sensitivity down to 1.2x at the highest
+    let threshold_multiplier = 1.2 + (1. - self.sensitivity.clamp(0., 1.)) * 1.8;
+    if !is_peak || candidate < MIN_FLUX || candidate < mean * threshold_multiplier {
+      return None;
+    }
+
+    // The candidate's window ended a hop ago, and the energy that raised its flux arrived during
+    // the hop before that
+    let onset = self.sample_count.saturating_sub(2 * FLUX_HOP_SIZE as u64);
+    if self
+      .last_onset
+      .is_some_and(|last| onset < last + self.min_gap_samples)
+    {
+      return None;
+    }
+    self.last_onset = Some(onset);
+    Some((onset, candidate))
+  }
+
+  pub fn reset(&mut self) {
+    self.input.fill(0.);
+    self.write_ix = 0;
+    self.samples_until_hop = FLUX_HOP_SIZE;
+    self.prev_log_magnitudes.fill(0.);
+    self.flux_history.clear();
+    self.sample_count = 0;
+    self.last_onset = None;
+  }
+}
+
+#[cfg(test)]
+fn render_hits(hit_positions: &[usize], len: usize) -> Vec<f32> {
+  let sample_rate = crate::sample_rate();
+  let mut source = vec![0.; len];
+  for &start in hit_positions {
+    for i in 0..(sample_rate as usize / 10).min(len - start) {
+      let t = i as f32 / sample_rate;
+      let env = (-t * 40.).exp();
+      source[start + i] += (2. * std::f32::consts::PI * 220. * t).sin() * env * 0.8;
+    }
+  }
+  source
+}
+
+#[test]
+fn detects_decaying_hits() {
+  let sample_rate = crate::sample_rate() as usize;
+  let hit_positions = [
+    0,
+    sample_rate / 4 + 37,
+    sample_rate / 2 + 101,
+    sample_rate * 3 / 4 + 211,
+  ];
+  let source = render_hits(&hit_positions, sample_rate);
+
+  let onsets = detect_onsets(&source, OnsetDetectionParams {
+    sensitivity: 0.5,
+    min_gap_samples: crate::ms_to_samples(50.) as usize,
+  });
+  assert_eq!(onsets.len(), hit_positions.len(), "{onsets:?}");
+  for (&onset, &expected) in onsets.iter().zip(&hit_positions) {
+    assert!(onset.abs_diff(expected) < 16, "{onset} {expected}");
+  }
+
+  // Hits closer together than the minimum gap are merged
+  let onsets = detect_onsets(&source, OnsetDetectionParams {
+    sensitivity: 0.5,
+    min_gap_samples: sample_rate / 3,
+  });
+  assert_eq!(onsets.len(), 2, "{onsets:?}");
+  assert!(onsets[1].abs_diff(hit_positions[2]) < 16, "{onsets:?}");
+}
+
+#[test]
+fn spectral_flux_detects_hits_in_realtime() {
+  let sample_rate = crate::sample_rate() as usize;
+  let hit_positions = [
+    sample_rate / 8,
+    sample_rate / 4 + 37,
+    sample_rate / 2 + 101,
+    sample_rate * 3 / 4 + 211,
+  ];
+  let source = render_hits(&hit_positions, sample_rate);
+
+  let mut detector = SpectralFluxDetector::default();
+  let mut onsets = Vec::new();
+  for frame in source.chunks(128) {
+    detector.process(frame, |onset, _flux| onsets.push(onset as usize));
+  }
+  assert_eq!(onsets.len(), hit_positions.len(), "{onsets:?}");
+  for (&onset, &expected) in onsets.iter().zip(&hit_positions) {
+    assert!(
+      onset.abs_diff(expected) <= FLUX_HOP_SIZE,
+      "{onset} {expected}"
+    );
+  }
+}
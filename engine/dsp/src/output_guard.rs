@@ -0,0 +1,80 @@
+//! Last-resort protection for a module's output.  Non-finite samples are replaced with silence, DC
+//! is removed, and anything left over `CEILING` is hard clipped, so that a blown-up filter or a
+//! runaway gain stage can't send full-scale garbage downstream.  Unlike the global safety limiter
+//! this doesn't try to sound good; it only exists to contain failures.
+
+use crate::{filters::dc_blocker::DCBlocker, MAX_FRAME_SIZE};
+
+/// About +12 dBFS.  Leaves plenty of headroom for modules mid-chain while still catching samples
+/// that have clearly gone off the rails.
+pub const CEILING: f32 = 4.;
+
+/// Index into `OutputGuard::stats` of the number of samples that have been hard clipped
+pub const STATS_CLIPPED_SAMPLE_COUNT_IX: usize = 0;
+/// Index into `OutputGuard::stats` of the number of non-finite samples that have been replaced
+pub const STATS_NON_FINITE_SAMPLE_COUNT_IX: usize = 1;
+pub const STATS_LEN: usize = 2;
+
+#[derive(Clone, Default)]
+pub struct OutputGuard {
+  dc_blockers: Vec<DCBlocker>,
+  /// Running totals since creation or the last `reset`.  Stored as `f32` so that they can be
+  /// copied straight into a `Float32Array`-backed SAB.
+  pub stats: [f32; STATS_LEN],
+}
+
+impl OutputGuard {
+  pub fn new(channel_count: usize) -> Self {
+    OutputGuard {
+      dc_blockers: vec![DCBlocker::default(); channel_count],
+      stats: [0.; STATS_LEN],
+    }
+  }
+
+  /// Processes the first `frame_size` samples of each channel of `buf` in place.  `buf` is planar
+  /// with channel `n` starting at `n * MAX_FRAME_SIZE`.
+  pub fn apply(&mut self, buf: &mut [f32], frame_size: usize) {
+    for (channel, dc_blocker) in buf
+      .chunks_exact_mut(MAX_FRAME_SIZE)
+      .zip(&mut self.dc_blockers)
+    {
+      for sample in &mut channel[..frame_size] {
+        if !sample.is_finite() {
+          *sample = 0.;
+          // Filter state is poisoned as well if a NaN made it through
+          dc_blocker.reset();
+          self.stats[STATS_NON_FINITE_SAMPLE_COUNT_IX] += 1.;
+        }
+        *sample = dc_blocker.apply(*sample);
+        if sample.abs() > CEILING {
+          *sample = CEILING.copysign(*sample);
+          self.stats[STATS_CLIPPED_SAMPLE_COUNT_IX] += 1.;
+        }
+      }
+    }
+  }
+
+  pub fn reset(&mut self) {
+    for dc_blocker in &mut self.dc_blockers {
+      dc_blocker.reset();
+    }
+    self.stats = [0.; STATS_LEN];
+  }
+}
+
+#[test]
+fn garbage_is_contained() {
+  let mut guard = OutputGuard::new(2);
+  let mut buf = vec![0.; MAX_FRAME_SIZE * 2];
+  buf[0] = f32::NAN;
+  buf[1] = f32::INFINITY;
+  buf[2] = 100.;
+  buf[MAX_FRAME_SIZE] = -100.;
+  guard.apply(&mut buf, 128);
+
+  assert!(buf
+    .iter()
+    .all(|sample| sample.is_finite() && sample.abs() <= CEILING));
+  assert_eq!(guard.stats[STATS_NON_FINITE_SAMPLE_COUNT_IX], 2.);
+  assert_eq!(guard.stats[STATS_CLIPPED_SAMPLE_COUNT_IX], 2.);
+}
@@ -0,0 +1,91 @@
+//! Rolling processing time stats for finding out which modules or voices are eating the audio
+//! thread's budget.  Wasm has no clock of its own, so timestamps come from a `now()` function
+//! imported from the host which returns milliseconds.
+//!
+//! Stats are kept for a fixed number of slots (one per voice, for example) and laid out flat so
+//! that they can be copied straight into a `Float32Array`-backed SAB: `STATS_PER_SLOT` entries for
+//! slot 0, then slot 1, and so on.
+
+/// Index within a slot's stats of the exponential moving average of its processing time in ms
+pub const STATS_AVG_MS_IX: usize = 0;
+/// Index within a slot's stats of its recent peak processing time in ms
+pub const STATS_PEAK_MS_IX: usize = 1;
+pub const STATS_PER_SLOT: usize = 2;
+
+/// The average follows the last ~100 frames; around a third of a second at 44.1 kHz
+const AVERAGE_COEFFICIENT: f32 = 1. / 100.;
+/// Peaks halve after ~140 frames so that a one-off spike stays visible long enough to be noticed
+const PEAK_DECAY: f32 = 0.995;
+
+#[derive(Clone, Default)]
+pub struct Profiler {
+  /// When disabled, `start` doesn't call into the host at all and stats are left as they were
+  pub enabled: bool,
+  stats: Vec<f32>,
+}
+
+impl Profiler {
+  pub fn new(slot_count: usize) -> Self {
+    Profiler {
+      enabled: false,
+      stats: vec![0.; slot_count * STATS_PER_SLOT],
+    }
+  }
+
+  /// Returns the timestamp to pass to `end`, or `None` if profiling is disabled
+  #[inline]
+  pub fn start(&self, now: impl Fn() -> f64) -> Option<f64> {
+    if self.enabled {
+      Some(now())
+    } else {
+      None
+    }
+  }
+
+  #[inline]
+  pub fn end(&mut self, slot_ix: usize, start: Option<f64>, now: impl Fn() -> f64) {
+    if let Some(start) = start {
+      self.record(slot_ix, (now() - start) as f32);
+    }
+  }
+
+  /// Folds a single measurement into the stats for `slot_ix`.  Idle slots should record 0 so that
+  /// their average decays rather than holding its last value.
+  pub fn record(&mut self, slot_ix: usize, elapsed_ms: f32) {
+    let Some(stats) = self.stats.chunks_exact_mut(STATS_PER_SLOT).nth(slot_ix) else {
+      return;
+    };
+    // The host's clock isn't guaranteed to be monotonic
+    let elapsed_ms = elapsed_ms.max(0.);
+    stats[STATS_AVG_MS_IX] += (elapsed_ms - stats[STATS_AVG_MS_IX]) * AVERAGE_COEFFICIENT;
+    stats[STATS_PEAK_MS_IX] = (stats[STATS_PEAK_MS_IX] * PEAK_DECAY).max(elapsed_ms);
+  }
+
+  pub fn stats(&self) -> &[f32] { &self.stats }
+
+  pub fn stats_mut(&mut self) -> &mut [f32] { &mut self.stats }
+
+  pub fn reset(&mut self) { self.stats.fill(0.); }
+}
+
+#[test]
+fn profiler_tracks_average_and_peak() {
+  let mut profiler = Profiler::new(2);
+  assert_eq!(profiler.start(|| unreachable!()), None);
+
+  profiler.enabled = true;
+  for _ in 0..2000 {
+    profiler.record(0, 1.);
+    profiler.record(1, 0.);
+  }
+  profiler.record(1, 3.);
+  let stats = profiler.stats();
+  assert!((stats[STATS_AVG_MS_IX] - 1.).abs() < 1e-3);
+  assert!((stats[STATS_PEAK_MS_IX] - 1.).abs() < 1e-3);
+  assert_eq!(stats[STATS_PER_SLOT + STATS_PEAK_MS_IX], 3.);
+  assert!(stats[STATS_PER_SLOT + STATS_AVG_MS_IX] < 0.1);
+
+  // Out of range slots are ignored
+  profiler.record(2, 1.);
+  assert_eq!(profiler.stats().len(), 2 * STATS_PER_SLOT);
+}
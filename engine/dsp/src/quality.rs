@@ -0,0 +1,136 @@
+//! Global quality governor that trades fidelity for lower DSP cost when the audio thread is
+//! struggling to keep up.  The host reports underruns and its current render load over FFI, and
+//! each module maps the resulting `QualityLevel` onto its own settings (oversampling factor, voice
+//! count, analysis stride, etc.) with a small policy function of its own.
+//!
+//! Underruns drop quality by one level immediately.  Quality is raised again one level at a time
+//! once the host has reported a low load for long enough, so that it doesn't oscillate between
+//! levels while the load is borderline.
+
+use std::cell::Cell;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum QualityLevel {
+  Full = 0,
+  Reduced = 1,
+  Minimum = 2,
+}
+
+impl QualityLevel {
+  fn lower(self) -> Self {
+    match self {
+      QualityLevel::Full => QualityLevel::Reduced,
+      QualityLevel::Reduced | QualityLevel::Minimum => QualityLevel::Minimum,
+    }
+  }
+
+  fn raise(self) -> Self {
+    match self {
+      QualityLevel::Full | QualityLevel::Reduced => QualityLevel::Full,
+      QualityLevel::Minimum => QualityLevel::Reduced,
+    }
+  }
+
+  /// Returns `full`, `reduced`, or `minimum` depending on the level.  Shorthand for simple
+  /// per-module policies.
+  pub fn pick<T>(self, full: T, reduced: T, minimum: T) -> T {
+    match self {
+      QualityLevel::Full => full,
+      QualityLevel::Reduced => reduced,
+      QualityLevel::Minimum => minimum,
+    }
+  }
+}
+
+/// Loads reported below this fraction of the render budget count towards restoring quality
+const RESTORE_LOAD_THRESHOLD: f32 = 0.6;
+/// Number of consecutive low load reports needed before quality is raised by one level
+const RESTORE_REPORT_COUNT: u32 = 16;
+
+#[derive(Clone, Copy)]
+struct Governor {
+  level: QualityLevel,
+  low_load_report_count: u32,
+}
+
+thread_local! {
+  // Like the sample rate, each Wasm module instance has its own copy of this.  The host is
+  // responsible for reporting to every module.
+  static GOVERNOR: Cell<Governor> = const {
+    Cell::new(Governor {
+      level: QualityLevel::Full,
+      low_load_report_count: 0,
+    })
+  };
+}
+
+fn update_governor(f: impl FnOnce(&mut Governor)) -> QualityLevel {
+  GOVERNOR.with(|cell| {
+    let mut governor = cell.get();
+    f(&mut governor);
+    cell.set(governor);
+    governor.level
+  })
+}
+
+/// The quality level that modules should currently be rendering at
+#[inline]
+pub fn quality_level() -> QualityLevel { GOVERNOR.with(|governor| governor.get().level) }
+
+/// Called by the host when the audio output underruns.  Returns the new quality level.
+#[no_mangle]
+pub extern "C" fn quality_report_underrun() -> u32 {
+  update_governor(|governor| {
+    governor.level = governor.level.lower();
+    governor.low_load_report_count = 0;
+  }) as u32
+}
+
+/// Called periodically by the host with the fraction of the render budget that was used since the
+/// last report.  Returns the new quality level.
+#[no_mangle]
+pub extern "C" fn quality_report_load(load: f32) -> u32 {
+  update_governor(|governor| {
+    if load.is_nan() || load >= RESTORE_LOAD_THRESHOLD {
+      governor.low_load_report_count = 0;
+      return;
+    }
+
+    governor.low_load_report_count += 1;
+    if governor.low_load_report_count >= RESTORE_REPORT_COUNT {
+      governor.level = governor.level.raise();
+      governor.low_load_report_count = 0;
+    }
+  }) as u32
+}
+
+#[no_mangle]
+pub extern "C" fn quality_get_level() -> u32 { quality_level() as u32 }
+
+#[test]
+fn underruns_lower_quality_and_low_load_restores_it() {
+  assert_eq!(quality_level(), QualityLevel::Full);
+  assert_eq!(quality_report_underrun(), QualityLevel::Reduced as u32);
+  assert_eq!(quality_report_underrun(), QualityLevel::Minimum as u32);
+  assert_eq!(quality_report_underrun(), QualityLevel::Minimum as u32);
+
+  // A high load report interrupts the run of low load reports
+  for _ in 0..RESTORE_REPORT_COUNT - 1 {
+    quality_report_load(0.2);
+  }
+  quality_report_load(f32::NAN);
+  assert_eq!(quality_level(), QualityLevel::Minimum);
+
+  for _ in 0..RESTORE_REPORT_COUNT {
+    quality_report_load(0.2);
+  }
+  assert_eq!(quality_level(), QualityLevel::Reduced);
+  quality_report_underrun();
+  assert_eq!(quality_level(), QualityLevel::Minimum);
+  for _ in 0..RESTORE_REPORT_COUNT * 2 {
+    quality_report_load(0.5);
+  }
+  assert_eq!(quality_level(), QualityLevel::Full);
+  assert_eq!(quality_level().pick(2, 1, 0), 2);
+}
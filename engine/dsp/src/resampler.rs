@@ -0,0 +1,355 @@
+//! Streaming sample rate conversion with selectable interpolation quality.  Used both for
+//! converting imported samples to the engine sample rate and for pitched sample playback, where the
+//! ratio changes as the pitch does.
+
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResamplerQuality {
+  /// Cheapest option; fine for small ratio changes but aliases and dulls the signal noticeably for
+  /// anything else.
+  Linear,
+  /// 4-point Catmull-Rom interpolation
+  Cubic,
+  /// Blackman-windowed sinc.  Band-limited to the lower of the two sample rates, so it's the only
+  /// option that doesn't alias when downsampling.
+  Sinc,
+}
+
+/// Number of zero crossings of the sinc kernel on each side of the interpolation point
+const SINC_HALF_WIDTH: usize = 16;
+/// Number of kernel values stored per unit of input sample distance; values in between are
+/// linearly interpolated.
+const SINC_TABLE_OVERSAMPLE: usize = 256;
+
+impl ResamplerQuality {
+  /// Number of input samples needed before and after the interpolation point, not counting the
+  /// sample at the interpolation point itself
+  fn taps_before(self) -> usize { self.half_width() - 1 }
+
+  fn taps_after(self) -> usize { self.half_width() }
+
+  fn half_width(self) -> usize {
+    match self {
+      ResamplerQuality::Linear => 1,
+      ResamplerQuality::Cubic => 2,
+      ResamplerQuality::Sinc => SINC_HALF_WIDTH,
+    }
+  }
+}
+
+pub(crate) fn blackman(x: f64) -> f64 {
+  // `x` is in [-1, 1]
+  let x = (x + 1.) / 2.;
+  0.42 - 0.5 * (2. * PI * x).cos() + 0.08 * (4. * PI * x).cos()
+}
+
+const SINC_TABLE_LEN: usize = 2 * SINC_HALF_WIDTH * SINC_TABLE_OVERSAMPLE + 1;
+
+/// Input sample distance of each sinc table entry from the interpolation point
+#[inline]
+fn sinc_table_distance(i: usize) -> f64 {
+  i as f64 / SINC_TABLE_OVERSAMPLE as f64 - SINC_HALF_WIDTH as f64
+}
+
+fn build_sinc_window() -> Vec<f64> {
+  (0..SINC_TABLE_LEN)
+    .map(|i| blackman(sinc_table_distance(i) / SINC_HALF_WIDTH as f64))
+    .collect()
+}
+
+/// Fills `table` with the windowed sinc kernel for `cutoff` without allocating, since this runs on
+/// the audio thread when the ratio of pitched playback changes
+fn fill_sinc_table(table: &mut [f32], window: &[f64], cutoff: f64) {
+  for (i, (val, window)) in table.iter_mut().zip(window).enumerate() {
+    let x = PI * cutoff * sinc_table_distance(i);
+    let sinc = if x == 0. { 1. } else { x.sin() / x };
+    *val = (cutoff * sinc * window) as f32;
+  }
+}
+
+#[inline]
+fn catmull_rom(y0: f32, y1: f32, y2: f32, y3: f32, t: f32) -> f32 {
+  let a = -0.5 * y0 + 1.5 * y1 - 1.5 * y2 + 0.5 * y3;
+  let b = y0 - 2.5 * y1 + 2. * y2 - 0.5 * y3;
+  let c = -0.5 * y0 + 0.5 * y2;
+  ((a * t + b) * t + c) * t + y1
+}
+
+/// Converts a stream of samples from one rate to another.  Input can be fed in arbitrarily sized
+/// chunks; output is produced as soon as enough input is buffered to compute it.
+pub struct Resampler {
+  quality: ResamplerQuality,
+  /// Number of input samples to advance per output sample; `input_rate / output_rate`
+  ratio: f64,
+  /// Buffered input, starting `taps_before` samples before the sample at `ix`
+  history: Vec<f32>,
+  /// Index in `history` of the input sample at or before the next output sample
+  ix: usize,
+  /// Position of the next output sample between `ix` and `ix + 1`.  Kept separate from `ix` so
+  /// that dropping consumed input doesn't introduce rounding error, making the output independent
+  /// of how the input is chunked.
+  frac: f64,
+  /// Kernel for the current cutoff.  Allocated once up front along with `sinc_window` and
+  /// re-filled in place when the cutoff changes; both are empty for other qualities.
+  sinc_table: Vec<f32>,
+  /// Blackman window for each entry of `sinc_table`, which doesn't depend on the cutoff
+  sinc_window: Vec<f64>,
+}
+
+impl Resampler {
+  pub fn new(quality: ResamplerQuality, input_rate: f64, output_rate: f64) -> Self {
+    let (sinc_table, sinc_window) = if quality == ResamplerQuality::Sinc {
+      let window = build_sinc_window();
+      let mut table = vec![0.; SINC_TABLE_LEN];
+      fill_sinc_table(&mut table, &window, 1.);
+      (table, window)
+    } else {
+      (Vec::new(), Vec::new())
+    };
+    let mut resampler = Resampler {
+      quality,
+      ratio: 1.,
+      history: Vec::new(),
+      ix: 0,
+      frac: 0.,
+      sinc_table,
+      sinc_window,
+    };
+    resampler.set_ratio(input_rate / output_rate);
+    resampler.reset();
+    resampler
+  }
+
+  pub fn quality(&self) -> ResamplerQuality { self.quality }
+
+  pub fn ratio(&self) -> f64 { self.ratio }
+
+  /// Sets the number of input samples to advance per output sample.  Can be changed between calls
+  /// to `process` without resetting, which is how pitched playback is implemented.
+  pub fn set_ratio(&mut self, ratio: f64) {
+    let ratio = if ratio.is_finite() && ratio > 0. {
+      ratio
+    } else {
+      1.
+    };
+    if self.quality == ResamplerQuality::Sinc {
+      // Only downsampling needs the cutoff to move below the input Nyquist frequency
+      let cutoff = (1. / ratio).min(1.);
+      let old_cutoff = (1. / self.ratio).min(1.);
+      if cutoff != old_cutoff {
+        fill_sinc_table(&mut self.sinc_table, &self.sinc_window, cutoff);
+      }
+    }
+    self.ratio = ratio;
+  }
+
+  /// Clears all buffered input so that the next call to `process` starts a new stream
+  pub fn reset(&mut self) {
+    self.history.clear();
+    let taps_before = self.quality.taps_before();
+    self.history.resize(taps_before, 0.);
+    self.ix = taps_before;
+    self.frac = 0.;
+  }
+
+  #[inline]
+  fn sinc_kernel(&self, d: f32) -> f32 {
+    let table_pos = (d + SINC_HALF_WIDTH as f32) * SINC_TABLE_OVERSAMPLE as f32;
+    let ix = table_pos as usize;
+    let next = self.sinc_table.get(ix + 1).copied().unwrap_or(0.);
+    crate::mix(table_pos.fract(), next, self.sinc_table[ix])
+  }
+
+  #[inline]
+  fn interpolate(&self) -> f32 {
+    let ix = self.ix;
+    let t = self.frac as f32;
+    let h = &self.history;
+    match self.quality {
+      ResamplerQuality::Linear => crate::mix(t, h[ix + 1], h[ix]),
+      ResamplerQuality::Cubic => catmull_rom(h[ix - 1], h[ix], h[ix + 1], h[ix + 2], t),
+      ResamplerQuality::Sinc => {
+        let start = ix - (SINC_HALF_WIDTH - 1);
+        h[start..=ix + SINC_HALF_WIDTH]
+          .iter()
+          .enumerate()
+          .map(|(i, sample)| {
+            let d = (start + i) as f32 - ix as f32 - t;
+            sample * self.sinc_kernel(d)
+          })
+          .sum()
+      },
+    }
+  }
+
+  /// Appends output samples to `out` while they're before `end_ix` and enough input is buffered to
+  /// compute them, then drops input that's no longer needed.
+  fn generate(&mut self, end_ix: usize, out: &mut Vec<f32>) {
+    let taps_after = self.quality.taps_after();
+    while self.ix < end_ix && self.ix + taps_after < self.history.len() {
+      out.push(self.interpolate());
+      self.frac += self.ratio;
+      let whole = self.frac.trunc();
+      self.ix += whole as usize;
+      self.frac -= whole;
+    }
+
+    let consumed = self
+      .ix
+      .saturating_sub(self.quality.taps_before())
+      .min(self.history.len());
+    self.history.drain(..consumed);
+    self.ix -= consumed;
+  }
+
+  /// Feeds `input` into the resampler, appending any output that can be computed to `out`
+  pub fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+    self.history.extend_from_slice(input);
+    self.generate(usize::MAX, out);
+  }
+
+  /// Produces the remaining output for the end of the stream, treating everything after the last
+  /// input sample as silence, and resets the resampler.
+  pub fn flush(&mut self, out: &mut Vec<f32>) {
+    let end_ix = self.history.len();
+    let new_len = self.history.len() + self.quality.taps_after();
+    self.history.resize(new_len, 0.);
+    self.generate(end_ix, out);
+    self.reset();
+  }
+}
+
+/// Converts a full buffer of samples from `input_rate` to `output_rate`.  The output length is the
+/// input length scaled by the rate ratio, rounded to the nearest sample.
+pub fn resample(
+  input: &[f32],
+  input_rate: f64,
+  output_rate: f64,
+  quality: ResamplerQuality,
+) -> Vec<f32> {
+  let mut resampler = Resampler::new(quality, input_rate, output_rate);
+  let out_len = (input.len() as f64 / resampler.ratio()).round() as usize;
+  let mut out = Vec::with_capacity(out_len + 1);
+  resampler.process(input, &mut out);
+  resampler.flush(&mut out);
+  // Accumulated rounding error in the read position can produce one extra sample at the end
+  out.truncate(out_len);
+  out
+}
+
+/// Like `resample`, but for interleaved samples with `channel_count` channels.  Each channel is
+/// resampled separately.
+pub fn resample_interleaved(
+  input: &[f32],
+  channel_count: usize,
+  input_rate: f64,
+  output_rate: f64,
+  quality: ResamplerQuality,
+) -> Vec<f32> {
+  if channel_count <= 1 {
+    return resample(input, input_rate, output_rate, quality);
+  }
+
+  let channels: Vec<Vec<f32>> = (0..channel_count)
+    .map(|channel_ix| {
+      let channel: Vec<f32> = input
+        .iter()
+        .skip(channel_ix)
+        .step_by(channel_count)
+        .copied()
+        .collect();
+      resample(&channel, input_rate, output_rate, quality)
+    })
+    .collect();
+  // Channels can differ in length by a sample if the input ended with a partial frame
+  let frame_count = channels.iter().map(Vec::len).min().unwrap_or(0);
+  (0..frame_count)
+    .flat_map(|frame_ix| channels.iter().map(move |channel| channel[frame_ix]))
+    .collect()
+}
+
+#[cfg(test)]
+fn sine(len: usize, freq: f64, sample_rate: f64) -> Vec<f32> {
+  (0..len)
+    .map(|i| (2. * PI * freq * i as f64 / sample_rate).sin() as f32)
+    .collect()
+}
+
+#[test]
+fn resampled_sine_matches_expected() {
+  for (quality, tolerance) in [
+    (ResamplerQuality::Linear, 0.02),
+    (ResamplerQuality::Cubic, 0.002),
+    (ResamplerQuality::Sinc, 0.002),
+  ] {
+    let input = sine(4410, 440., 44_100.);
+    let out = resample(&input, 44_100., 48_000., quality);
+    assert_eq!(out.len(), 4800);
+
+    let expected = sine(4800, 440., 48_000.);
+    // The edges are affected by the implicit silence before and after the input
+    for (actual, expected) in out[100..4700].iter().zip(&expected[100..4700]) {
+      assert!(
+        (actual - expected).abs() < tolerance,
+        "{quality:?}: {actual} != {expected}"
+      );
+    }
+  }
+}
+
+#[test]
+fn chunked_resampling_matches_full_resampling() {
+  let input = sine(10_000, 1234., 44_100.);
+  for quality in [
+    ResamplerQuality::Linear,
+    ResamplerQuality::Cubic,
+    ResamplerQuality::Sinc,
+  ] {
+    let full = resample(&input, 96_000., 44_100., quality);
+
+    let mut resampler = Resampler::new(quality, 96_000., 44_100.);
+    let mut chunked = Vec::new();
+    for chunk in input.chunks(37) {
+      resampler.process(chunk, &mut chunked);
+    }
+    resampler.flush(&mut chunked);
+    assert_eq!(full, chunked);
+  }
+}
+
+#[test]
+fn sinc_downsampling_removes_content_above_nyquist() {
+  // 30kHz is above the 22.05kHz Nyquist frequency of the output and would alias down to 18.2kHz
+  let input = sine(9600, 30_000., 96_000.);
+  let rms = |buf: &[f32]| (buf.iter().map(|s| s * s).sum::<f32>() / buf.len() as f32).sqrt();
+
+  let sinc = resample(&input, 96_000., 44_100., ResamplerQuality::Sinc);
+  let linear = resample(&input, 96_000., 44_100., ResamplerQuality::Linear);
+  assert!(rms(&sinc[100..4000]) < 0.01);
+  assert!(rms(&linear[100..4000]) > 0.1);
+}
+
+#[test]
+fn interleaved_channels_are_resampled_separately() {
+  let left = sine(4410, 440., 44_100.);
+  let interleaved: Vec<f32> = left.iter().flat_map(|&sample| [sample, 0.]).collect();
+
+  let out = resample_interleaved(&interleaved, 2, 44_100., 48_000., ResamplerQuality::Sinc);
+  assert_eq!(out.len(), 4800 * 2);
+  let expected_left = resample(&left, 44_100., 48_000., ResamplerQuality::Sinc);
+  for (frame, expected) in out.chunks_exact(2).zip(&expected_left) {
+    assert_eq!(frame, [*expected, 0.]);
+  }
+}
+
+#[test]
+fn changing_sinc_ratio_refills_table_in_place() {
+  let mut resampler = Resampler::new(ResamplerQuality::Sinc, 44_100., 44_100.);
+  let table_ptr = resampler.sinc_table.as_ptr();
+  resampler.set_ratio(96_000. / 44_100.);
+  assert_eq!(resampler.sinc_table.as_ptr(), table_ptr);
+
+  let fresh = Resampler::new(ResamplerQuality::Sinc, 96_000., 44_100.);
+  assert_eq!(resampler.sinc_table, fresh.sinc_table);
+}
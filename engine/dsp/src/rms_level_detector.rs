@@ -1,6 +1,8 @@
 use crate::circular_buffer::CircularBuffer;
 
-pub const MAX_LEVEL_DETECTION_WINDOW_SAMPLES: usize = crate::SAMPLE_RATE as usize;
+/// One second at the default sample rate.  This is a fixed number of samples, so the maximum window
+/// length in time is shorter at higher sample rates.
+pub const MAX_LEVEL_DETECTION_WINDOW_SAMPLES: usize = crate::DEFAULT_SAMPLE_RATE as usize;
 
 pub struct RMSLevelDetector<const TEND_TOWARDS_ZERO: bool> {
   pub buf: CircularBuffer<MAX_LEVEL_DETECTION_WINDOW_SAMPLES>,
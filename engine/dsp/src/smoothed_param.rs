@@ -0,0 +1,186 @@
+//! Smoothing for parameters that are set from the UI.  Jumping straight to a new gain or mix value
+//! produces an audible click, so the value is ramped towards its target over a short time instead.
+
+use crate::ms_to_samples;
+
+/// `ln(100)`; an exponential ramp of this many time constants has covered 99% of the distance to
+/// its target.
+const LN_100: f32 = 4.605_17;
+/// Exponential ramps snap to their target once they get this close to it
+const SNAP_THRESHOLD: f32 = 1e-6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmoothingMode {
+  /// Moves towards the target in equal steps, arriving there after exactly the ramp time
+  Linear,
+  /// One-pole lowpass towards the target, covering 99% of the distance after the ramp time.  This
+  /// handles targets that change every sample more gracefully than `Linear` since it doesn't
+  /// restart its ramp when the target changes.
+  Exponential,
+}
+
+#[derive(Clone, Debug)]
+pub struct SmoothedParam {
+  mode: SmoothingMode,
+  current: f32,
+  target: f32,
+  ramp_samples: f32,
+  /// Amount `current` changes by each sample during a linear ramp
+  step: f32,
+  /// Number of samples left in the current linear ramp
+  remaining_samples: usize,
+  /// Coefficient passed to `one_pole` for exponential smoothing
+  coefficient: f32,
+}
+
+impl SmoothedParam {
+  pub fn new(mode: SmoothingMode, initial_value: f32, ramp_ms: f32) -> Self {
+    let mut param = SmoothedParam {
+      mode,
+      current: initial_value,
+      target: initial_value,
+      ramp_samples: 0.,
+      step: 0.,
+      remaining_samples: 0,
+      coefficient: 1.,
+    };
+    param.set_ramp_ms(ramp_ms);
+    param
+  }
+
+  /// Sets how long it takes to move to a new target.  A ramp time of zero causes new targets to be
+  /// applied immediately.
+  pub fn set_ramp_ms(&mut self, ramp_ms: f32) {
+    self.ramp_samples = ms_to_samples(ramp_ms).max(0.);
+    self.coefficient = if self.ramp_samples < 1. {
+      1.
+    } else {
+      1. - (-LN_100 / self.ramp_samples).exp()
+    };
+
+    if self.mode == SmoothingMode::Linear && self.remaining_samples > 0 {
+      self.start_linear_ramp();
+    }
+  }
+
+  fn start_linear_ramp(&mut self) {
+    self.remaining_samples = self.ramp_samples.round() as usize;
+    if self.remaining_samples == 0 {
+      self.current = self.target;
+    } else {
+      self.step = (self.target - self.current) / self.remaining_samples as f32;
+    }
+  }
+
+  /// Starts moving towards `target` from the current value
+  pub fn set_target(&mut self, target: f32) {
+    if target == self.target {
+      return;
+    }
+    self.target = target;
+
+    match self.mode {
+      SmoothingMode::Linear => self.start_linear_ramp(),
+      SmoothingMode::Exponential =>
+        if self.coefficient >= 1. {
+          self.current = target;
+        },
+    }
+  }
+
+  /// Jumps to `value` without smoothing, cancelling any ramp in progress
+  pub fn set_immediate(&mut self, value: f32) {
+    self.current = value;
+    self.target = value;
+    self.remaining_samples = 0;
+  }
+
+  pub fn target(&self) -> f32 { self.target }
+
+  pub fn current(&self) -> f32 { self.current }
+
+  pub fn is_smoothing(&self) -> bool { self.current != self.target }
+
+  /// Advances the ramp by one sample and returns the new value
+  #[inline]
+  pub fn tick(&mut self) -> f32 {
+    match self.mode {
+      SmoothingMode::Linear =>
+        if self.remaining_samples > 0 {
+          self.remaining_samples -= 1;
+          if self.remaining_samples == 0 {
+            self.current = self.target;
+          } else {
+            self.current += self.step;
+          }
+        },
+      SmoothingMode::Exponential =>
+        if self.current != self.target {
+          crate::one_pole(&mut self.current, self.target, self.coefficient);
+          if (self.target - self.current).abs() < SNAP_THRESHOLD {
+            self.current = self.target;
+          }
+        },
+    }
+    self.current
+  }
+
+  /// Fills `buf` with successive values of the ramp, advancing it by `buf.len()` samples
+  pub fn fill(&mut self, buf: &mut [f32]) {
+    if !self.is_smoothing() {
+      buf.fill(self.current);
+      return;
+    }
+
+    for sample in buf {
+      *sample = self.tick();
+    }
+  }
+}
+
+#[test]
+fn linear_ramp_arrives_after_ramp_time() {
+  let mut param = SmoothedParam::new(SmoothingMode::Linear, 0., 10.);
+  let ramp_samples = ms_to_samples(10.).round() as usize;
+  param.set_target(1.);
+
+  let mut buf = vec![0.; ramp_samples + 16];
+  param.fill(&mut buf);
+  for pair in buf[..ramp_samples].windows(2) {
+    assert!(pair[1] > pair[0]);
+  }
+  assert!(buf[ramp_samples - 2] < 1.);
+  assert!(buf[ramp_samples - 1..].iter().all(|&val| val == 1.));
+  assert!(!param.is_smoothing());
+
+  // Retargeting mid-ramp starts a new ramp from the current value
+  param.set_target(0.);
+  let mid = (0..ramp_samples / 2).map(|_| param.tick()).last().unwrap();
+  param.set_target(1.);
+  assert!(param.tick() > mid);
+}
+
+#[test]
+fn exponential_ramp_settles_within_ramp_time() {
+  let mut param = SmoothedParam::new(SmoothingMode::Exponential, 1., 20.);
+  let ramp_samples = ms_to_samples(20.).round() as usize;
+  param.set_target(0.);
+
+  let mut buf = vec![0.; ramp_samples];
+  param.fill(&mut buf);
+  for pair in buf.windows(2) {
+    assert!(pair[1] <= pair[0]);
+  }
+  assert!(buf[0] < 1.);
+  assert!(buf[ramp_samples - 1] <= 0.011, "{}", buf[ramp_samples - 1]);
+
+  for _ in 0..ramp_samples * 4 {
+    param.tick();
+  }
+  assert_eq!(param.current(), 0.);
+  assert!(!param.is_smoothing());
+
+  let mut immediate = SmoothedParam::new(SmoothingMode::Exponential, 0., 0.);
+  immediate.set_target(0.5);
+  assert_eq!(immediate.tick(), 0.5);
+}
@@ -0,0 +1,245 @@
+//! Spectral features measured from short windows of a signal, for driving visuals and modulation
+//! from the character of the audio rather than just its level.
+//!
+//! The input is buffered and a Hann-windowed FFT is taken every hop.  Each analysis produces the
+//! spectral centroid (the "center of mass" of the spectrum, which tracks perceived brightness), the
+//! rolloff frequency below which most of the energy lies, the spectral flatness (near 0 for tonal
+//! material and near 1 for noise), and the RMS level of the signal in a set of fixed bands.
+
+use std::f32::consts::PI;
+
+use crate::fft::Fft;
+
+/// Bands that `SpectralFeatures::band_rms` is measured over, given by their edges in Hz.  Spaced
+/// roughly evenly in pitch so that each band covers a musically meaningful range.
+pub const BAND_EDGES_HZ: [f32; BAND_COUNT + 1] = [
+  20., 60., 150., 400., 1_000., 2_500., 6_000., 12_000., 20_000.,
+];
+pub const BAND_COUNT: usize = 8;
+/// Powers below this are treated as silence, leaving all features at zero
+const SILENCE_POWER: f32 = 1e-12;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SpectralFeatures {
+  pub centroid_hz: f32,
+  pub rolloff_hz: f32,
+  /// Geometric mean of the power spectrum divided by its arithmetic mean, from 0 to 1
+  pub flatness: f32,
+  /// RMS level of the whole window
+  pub rms: f32,
+  /// RMS level of the part of the signal that falls in each of `BAND_EDGES_HZ`
+  pub band_rms: [f32; BAND_COUNT],
+}
+
+/// Computes features from the power (squared magnitude) of the bins of a real FFT, from DC up to
+/// and including Nyquist.
+///
+/// `power_scale` converts summed bin powers into mean square signal level; see
+/// `SpectralAnalyzer::new`.  `rolloff_fraction` is the portion of the total energy that lies below
+/// the rolloff frequency.
+pub fn compute_features(
+  powers: &[f32],
+  bin_hz: f32,
+  power_scale: f32,
+  rolloff_fraction: f32,
+) -> SpectralFeatures {
+  // DC is left out so that offsets don't drag the centroid down
+  let bins = powers.iter().enumerate().skip(1);
+  let total_power: f32 = bins.clone().map(|(_, &power)| power).sum();
+  if total_power < SILENCE_POWER || powers.len() < 2 {
+    return SpectralFeatures::default();
+  }
+
+  let (weighted_freq_sum, magnitude_sum) =
+    bins
+      .clone()
+      .fold((0., 0.), |(weighted, sum), (bin_ix, &power)| {
+        let magnitude = power.sqrt();
+        (
+          weighted + bin_ix as f32 * bin_hz * magnitude,
+          sum + magnitude,
+        )
+      });
+
+  let rolloff_threshold = total_power * rolloff_fraction;
+  let mut cumulative_power = 0.;
+  let mut rolloff_bin = powers.len() - 1;
+  for (bin_ix, &power) in bins.clone() {
+    cumulative_power += power;
+    if cumulative_power >= rolloff_threshold {
+      rolloff_bin = bin_ix;
+      break;
+    }
+  }
+
+  let bin_count = (powers.len() - 1) as f32;
+  let log_sum: f32 = bins
+    .clone()
+    .map(|(_, &power)| (power + SILENCE_POWER).ln())
+    .sum();
+  let geometric_mean = (log_sum / bin_count).exp();
+  let arithmetic_mean = total_power / bin_count;
+
+  let mut band_rms = [0.; BAND_COUNT];
+  for (band_ix, rms) in band_rms.iter_mut().enumerate() {
+    let start_bin = (BAND_EDGES_HZ[band_ix] / bin_hz).ceil() as usize;
+    let end_bin = ((BAND_EDGES_HZ[band_ix + 1] / bin_hz).ceil() as usize).min(powers.len());
+    if start_bin >= end_bin {
+      continue;
+    }
+    let band_power: f32 = powers[start_bin..end_bin].iter().sum();
+    *rms = (band_power * power_scale).sqrt();
+  }
+
+  SpectralFeatures {
+    centroid_hz: weighted_freq_sum / magnitude_sum,
+    rolloff_hz: rolloff_bin as f32 * bin_hz,
+    flatness: (geometric_mean / arithmetic_mean).min(1.),
+    // DC isn't mirrored, so it only counts once
+    rms: ((total_power + powers[0] * 0.5) * power_scale).sqrt(),
+    band_rms,
+  }
+}
+
+/// Buffers a stream of samples and measures its spectral features every `hop_size` samples
+pub struct SpectralAnalyzer {
+  fft: Fft,
+  window: Vec<f32>,
+  /// Circular buffer holding the most recent `fft_size` samples
+  input: Vec<f32>,
+  write_ix: usize,
+  hop_size: usize,
+  samples_until_analysis: usize,
+  re: Vec<f32>,
+  im: Vec<f32>,
+  powers: Vec<f32>,
+  /// Converts summed bin powers into mean square level, accounting for the window
+  power_scale: f32,
+  pub rolloff_fraction: f32,
+  features: SpectralFeatures,
+}
+
+impl SpectralAnalyzer {
+  /// `fft_size` must be a power of two
+  pub fn new(fft_size: usize, hop_size: usize) -> Self {
+    let window: Vec<f32> = (0..fft_size)
+      .map(|i| 0.5 - 0.5 * (2. * PI * i as f32 / fft_size as f32).cos())
+      .collect();
+    // By Parseval's theorem, the mean square of the windowed signal is the sum of the powers of
+    // all bins divided by `fft_size^2`.  Bins other than DC and Nyquist are mirrored in the half of
+    // the spectrum that isn't kept, so they count twice.  Dividing by the window's mean square
+    // undoes its attenuation.
+    let window_power: f32 = window.iter().map(|w| w * w).sum();
+    let power_scale = 2. / (fft_size as f32 * window_power);
+
+    SpectralAnalyzer {
+      fft: Fft::new(fft_size),
+      window,
+      input: vec![0.; fft_size],
+      write_ix: 0,
+      hop_size: hop_size.max(1),
+      samples_until_analysis: hop_size.max(1),
+      re: vec![0.; fft_size],
+      im: vec![0.; fft_size],
+      powers: vec![0.; fft_size / 2 + 1],
+      power_scale,
+      rolloff_fraction: 0.85,
+      features: SpectralFeatures::default(),
+    }
+  }
+
+  /// The features measured by the most recent analysis
+  pub fn features(&self) -> &SpectralFeatures { &self.features }
+
+  /// Buffers `samples`, running an analysis each time another hop's worth has been received.
+  /// Returns `true` if the features were updated.
+  pub fn process(&mut self, samples: &[f32]) -> bool {
+    let mut analyzed = false;
+    for &sample in samples {
+      self.input[self.write_ix] = if sample.is_finite() { sample } else { 0. };
+      self.write_ix = (self.write_ix + 1) % self.input.len();
+      self.samples_until_analysis -= 1;
+      if self.samples_until_analysis == 0 {
+        self.samples_until_analysis = self.hop_size;
+        self.analyze();
+        analyzed = true;
+      }
+    }
+    analyzed
+  }
+
+  fn analyze(&mut self) {
+    let fft_size = self.input.len();
+    // `write_ix` points at the oldest sample
+    for i in 0..fft_size {
+      self.re[i] = self.input[(self.write_ix + i) % fft_size] * self.window[i];
+    }
+    self.im.fill(0.);
+    self.fft.forward(&mut self.re, &mut self.im);
+
+    for (bin_ix, power) in self.powers.iter_mut().enumerate() {
+      *power = self.re[bin_ix] * self.re[bin_ix] + self.im[bin_ix] * self.im[bin_ix];
+    }
+    let bin_hz = crate::sample_rate() / fft_size as f32;
+    self.features = compute_features(
+      &self.powers,
+      bin_hz,
+      self.power_scale,
+      self.rolloff_fraction,
+    );
+  }
+
+  pub fn reset(&mut self) {
+    self.input.fill(0.);
+    self.write_ix = 0;
+    self.samples_until_analysis = self.hop_size;
+    self.features = SpectralFeatures::default();
+  }
+}
+
+#[test]
+fn sine_features_match_its_frequency_and_level() {
+  let mut analyzer = SpectralAnalyzer::new(2048, 512);
+  let freq = 1_000.;
+  let samples: Vec<f32> = (0..8192)
+    .map(|i| (2. * PI * freq * i as f32 / crate::sample_rate()).sin())
+    .collect();
+  assert!(analyzer.process(&samples));
+
+  let features = analyzer.features();
+  assert!((features.centroid_hz - freq).abs() < 50., "{features:?}");
+  assert!((features.rolloff_hz - freq).abs() < 50., "{features:?}");
+  assert!(features.flatness < 0.01, "{features:?}");
+  let expected_rms = std::f32::consts::FRAC_1_SQRT_2;
+  assert!((features.rms - expected_rms).abs() < 0.02, "{features:?}");
+  // 1kHz falls at the edge between two bands, so its energy is split between them
+  let band_power: f32 = features.band_rms.iter().map(|rms| rms * rms).sum();
+  assert!(
+    (band_power.sqrt() - expected_rms).abs() < 0.02,
+    "{features:?}"
+  );
+}
+
+#[test]
+fn noise_is_flat_and_silence_has_no_features() {
+  // xorshift32; good enough for a white spectrum without pulling in a dependency
+  let mut state = 0x1234_5678u32;
+  let mut analyzer = SpectralAnalyzer::new(1024, 1024);
+  let noise: Vec<f32> = (0..4096)
+    .map(|_| {
+      state ^= state << 13;
+      state ^= state >> 17;
+      state ^= state << 5;
+      state as f32 / u32::MAX as f32 * 2. - 1.
+    })
+    .collect();
+  analyzer.process(&noise);
+  let features = *analyzer.features();
+  assert!(features.flatness > 0.4, "{features:?}");
+  // White noise has its centroid around a quarter of the sample rate
+  let quarter_rate = crate::sample_rate() / 4.;
+  assert!((features.centroid_hz - quarter_rate).abs() < quarter_rate * 0.2);
+
+  analyzer.process(&vec![0.; 1024]);
+  assert_eq!(*analyzer.features(), SpectralFeatures::default());
+}
@@ -0,0 +1,308 @@
+//! WSOLA (waveform similarity overlap-add) time stretching for audio that's available ahead of
+//! time, like samples and recorded loops.  Playback speed can be changed without changing pitch,
+//! so material recorded at one tempo can follow the transport at another.
+//!
+//! Output is built from overlapping windowed grains read from the source.  Each grain is read from
+//! near where the playback position says it should be, nudged within a search window to the offset
+//! whose waveform best lines up with the end of the previous grain.  This avoids most of the
+//! phasiness and comb filtering of plain overlap-add.
+
+use std::f32::consts::PI;
+
+use crate::ms_to_samples;
+
+/// A hop is treated as containing a transient if its energy is this many times that of the hop
+/// before it
+const TRANSIENT_ENERGY_RATIO: f32 = 4.;
+/// Mean squared level below which hops are treated as silent when detecting transients
+const TRANSIENT_MIN_ENERGY: f32 = 1e-6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeStretchQuality {
+  /// Short grains and a narrow search.  Cheapest and quickest to respond to speed changes, but
+  /// rough on bass and sustained tonal material.
+  Fast = 0,
+  Balanced = 1,
+  /// Long grains and a wide search.  Smoothest on tonal material, but uses the most CPU and is the
+  /// slowest to respond to speed changes.
+  High = 2,
+}
+
+impl TimeStretchQuality {
+  pub fn from_u32(quality: u32) -> Option<Self> {
+    match quality {
+      0 => Some(TimeStretchQuality::Fast),
+      1 => Some(TimeStretchQuality::Balanced),
+      2 => Some(TimeStretchQuality::High),
+      _ => None,
+    }
+  }
+
+  /// Returns the grain length and search radius in milliseconds along with the stride of the
+  /// coarse similarity search in samples
+  fn settings(self) -> (f32, f32, usize) {
+    match self {
+      TimeStretchQuality::Fast => (20., 4., 4),
+      TimeStretchQuality::Balanced => (40., 8., 2),
+      TimeStretchQuality::High => (80., 12., 1),
+    }
+  }
+}
+
+fn mean_square(source: &[f32], start: usize, len: usize) -> f32 {
+  let end = (start + len).min(source.len());
+  if start >= end {
+    return 0.;
+  }
+  source[start..end].iter().map(|&x| x * x).sum::<f32>() / len as f32
+}
+
+/// Plays back a source buffer at a variable speed without changing its pitch
+#[derive(Clone)]
+pub struct TimeStretcher {
+  quality: TimeStretchQuality,
+  /// Distance in output samples between the starts of consecutive grains.  Grains are twice this
+  /// long so that each output sample is covered by exactly two of them.
+  hop: usize,
+  search_radius: usize,
+  search_stride: usize,
+  window: Vec<f32>,
+  /// Overlap-add buffer covering the output of the most recently added grain
+  accumulator: Vec<f32>,
+  /// Index into `accumulator` of the next output sample.  The next grain is added once this
+  /// reaches `hop`.
+  out_ix: usize,
+  /// Source position of the next grain if playback exactly followed the requested speed
+  ideal_pos: f64,
+  /// Source position around which the next grain is searched for.  This drifts from `ideal_pos`
+  /// while transients are being preserved and is pulled back towards it afterwards.
+  pos: f64,
+  prev_grain_start: Option<usize>,
+  /// If set, grains are read contiguously from the source around transients rather than being
+  /// realigned, which keeps attacks from being smeared or repeated when stretching.
+  pub preserve_transients: bool,
+}
+
+impl TimeStretcher {
+  pub fn new(quality: TimeStretchQuality, preserve_transients: bool) -> Self {
+    let (grain_ms, search_radius_ms, search_stride) = quality.settings();
+    let hop = ((ms_to_samples(grain_ms) / 2.).round() as usize).max(1);
+    let grain_len = hop * 2;
+    // Periodic Hann windows overlapping by half sum to exactly 1
+    let window = (0..grain_len)
+      .map(|i| 0.5 - 0.5 * (2. * PI * i as f32 / grain_len as f32).cos())
+      .collect();
+
+    TimeStretcher {
+      quality,
+      hop,
+      search_radius: ms_to_samples(search_radius_ms).round() as usize,
+      search_stride,
+      window,
+      accumulator: vec![0.; grain_len],
+      out_ix: hop,
+      ideal_pos: 0.,
+      pos: 0.,
+      prev_grain_start: None,
+      preserve_transients,
+    }
+  }
+
+  pub fn quality(&self) -> TimeStretchQuality { self.quality }
+
+  /// Speed changes take effect at grain boundaries, so they can take up to this many samples to
+  /// be heard
+  pub fn latency_samples(&self) -> usize { self.hop }
+
+  /// Restarts playback from `source_pos`
+  pub fn reset(&mut self, source_pos: f64) {
+    self.accumulator.fill(0.);
+    self.out_ix = self.hop;
+    self.ideal_pos = source_pos;
+    self.pos = source_pos;
+    self.prev_grain_start = None;
+  }
+
+  /// Returns `true` once everything up to the end of `source` has been played
+  pub fn is_finished(&self, source_len: usize) -> bool {
+    self.out_ix >= self.hop
+      && self
+        .prev_grain_start
+        .is_some_and(|start| start >= source_len)
+  }
+
+  fn is_transient(&self, source: &[f32], nominal_start: usize) -> bool {
+    let hop = self.hop;
+    let before = mean_square(source, nominal_start.saturating_sub(hop), hop);
+    let first_half = mean_square(source, nominal_start, hop);
+    let second_half = mean_square(source, nominal_start + hop, hop);
+    (first_half > TRANSIENT_MIN_ENERGY && first_half > before * TRANSIENT_ENERGY_RATIO)
+      || (second_half > TRANSIENT_MIN_ENERGY && second_half > first_half * TRANSIENT_ENERGY_RATIO)
+  }
+
+  /// Normalized cross-correlation between the first half of the grain starting at `candidate` and
+  /// the source following `continuation`
+  fn similarity(&self, source: &[f32], candidate: usize, continuation: usize) -> f32 {
+    let mut dot = 0.;
+    let mut energy = 0.;
+    for i in 0..self.hop {
+      let a = source.get(continuation + i).copied().unwrap_or(0.);
+      let b = source.get(candidate + i).copied().unwrap_or(0.);
+      dot += a * b;
+      energy += b * b;
+    }
+    dot / (energy + 1e-9).sqrt()
+  }
+
+  /// Finds the grain start within the search window around `nominal_start` that best continues the
+  /// waveform of the previous grain.  A coarse search is refined around the best match, and ties
+  /// go to the candidate closest to `nominal_start`.
+  fn find_best_start(&self, source: &[f32], nominal_start: usize, continuation: usize) -> usize {
+    let mut best_start = nominal_start;
+    let mut best_score = self.similarity(source, nominal_start, continuation);
+    let check = |candidate: usize, best_start: &mut usize, best_score: &mut f32| {
+      let score = self.similarity(source, candidate, continuation);
+      if score > *best_score + best_score.abs() * 1e-4 {
+        *best_score = score;
+        *best_start = candidate;
+      }
+    };
+
+    let stride = self.search_stride;
+    for offset in (stride..=self.search_radius).step_by(stride) {
+      check(nominal_start + offset, &mut best_start, &mut best_score);
+      if let Some(candidate) = nominal_start.checked_sub(offset) {
+        check(candidate, &mut best_start, &mut best_score);
+      }
+    }
+
+    let coarse_best = best_start;
+    for offset in 1..stride {
+      check(coarse_best + offset, &mut best_start, &mut best_score);
+      if let Some(candidate) = coarse_best.checked_sub(offset) {
+        check(candidate, &mut best_start, &mut best_score);
+      }
+    }
+
+    best_start
+  }
+
+  fn add_grain(&mut self, source: &[f32], speed: f32) {
+    let hop = self.hop;
+    self.accumulator.copy_within(hop.., 0);
+    self.accumulator[hop..].fill(0.);
+    self.out_ix = 0;
+
+    let nominal_start = self.pos.round().max(0.) as usize;
+    let (start, is_transient) = match self.prev_grain_start {
+      None => (nominal_start, false),
+      Some(prev_start) => {
+        let continuation = prev_start + hop;
+        if self.preserve_transients && self.is_transient(source, nominal_start) {
+          (continuation, true)
+        } else {
+          (
+            self.find_best_start(source, nominal_start, continuation),
+            false,
+          )
+        }
+      },
+    };
+
+    // The first grain isn't faded in so that the start of the source plays at full level
+    let skip_fade_in = self.prev_grain_start.is_none();
+    for (i, (out, &window)) in self.accumulator.iter_mut().zip(&self.window).enumerate() {
+      let gain = if skip_fade_in && i < hop { 1. } else { window };
+      *out += source.get(start + i).copied().unwrap_or(0.) * gain;
+    }
+    self.prev_grain_start = Some(start);
+
+    let hop_in = hop as f64 * speed.max(0.) as f64;
+    if is_transient {
+      self.pos = start as f64;
+    }
+    self.ideal_pos += hop_in;
+    let drift = self.ideal_pos - (self.pos + hop_in);
+    self.pos += hop_in + drift.clamp(-hop_in / 2., hop_in / 2.);
+  }
+
+  /// Returns the next output sample.  `speed` is the number of source samples to advance per output
+  /// sample; 1 plays at the original speed.
+  #[inline]
+  pub fn next_sample(&mut self, source: &[f32], speed: f32) -> f32 {
+    if self.out_ix >= self.hop {
+      self.add_grain(source, speed);
+    }
+    let sample = self.accumulator[self.out_ix];
+    self.out_ix += 1;
+    sample
+  }
+
+  pub fn process(&mut self, source: &[f32], speed: f32, out: &mut [f32]) {
+    for sample in out {
+      *sample = self.next_sample(source, speed);
+    }
+  }
+}
+
+#[cfg(test)]
+fn render(stretcher: &mut TimeStretcher, source: &[f32], speed: f32) -> Vec<f32> {
+  let mut out = Vec::new();
+  while !stretcher.is_finished(source.len()) {
+    out.push(stretcher.next_sample(source, speed));
+  }
+  out
+}
+
+#[test]
+fn stretching_preserves_pitch_and_scales_duration() {
+  let sample_rate = crate::sample_rate();
+  let freq = 440.;
+  let source: Vec<f32> = (0..sample_rate as usize / 2)
+    .map(|i| (2. * PI * freq * i as f32 / sample_rate).sin() * 0.5)
+    .collect();
+
+  for quality in [
+    TimeStretchQuality::Fast,
+    TimeStretchQuality::Balanced,
+    TimeStretchQuality::High,
+  ] {
+    for speed in [0.5, 1., 1.5] {
+      let mut stretcher = TimeStretcher::new(quality, false);
+      let out = render(&mut stretcher, &source, speed);
+
+      let expected_len = source.len() as f32 / speed;
+      let len_error = (out.len() as f32 - expected_len).abs();
+      assert!(
+        len_error < stretcher.latency_samples() as f32 * 3.,
+        "{quality:?} {speed}"
+      );
+
+      // Count zero crossings away from the edges to estimate the frequency
+      let middle = &out[out.len() / 4..out.len() * 3 / 4];
+      let crossings = middle
+        .windows(2)
+        .filter(|pair| (pair[0] < 0.) != (pair[1] < 0.))
+        .count();
+      let measured_freq = crossings as f32 / 2. / (middle.len() as f32 / sample_rate);
+      assert!(
+        (measured_freq - freq).abs() < freq * 0.02,
+        "{quality:?} {speed} {measured_freq}"
+      );
+    }
+  }
+}
+
+#[test]
+fn transients_are_not_repeated() {
+  let mut source = vec![0.; crate::sample_rate() as usize / 2];
+  let click_ix = source.len() / 3;
+  source[click_ix] = 1.;
+
+  let mut stretcher = TimeStretcher::new(TimeStretchQuality::Balanced, true);
+  let out = render(&mut stretcher, &source, 0.5);
+  let loud_sample_count = out.iter().filter(|&&x| x.abs() > 0.25).count();
+  assert_eq!(loud_sample_count, 1);
+  let peak = out.iter().fold(0.0f32, |acc, &x| acc.max(x.abs()));
+  assert!((peak - 1.).abs() < 1e-4, "{peak}");
+}
@@ -0,0 +1,53 @@
+//! Equal-division-of-the-octave tunings.  Note numbers are interpreted as scale degrees of the
+//! tuning rather than as 12-TET semitones, so that e.g. 19 consecutive note numbers span one octave
+//! in 19-EDO.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tuning {
+  pub divisions_per_octave: usize,
+  /// Note number that plays at exactly `reference_frequency`
+  pub reference_note: usize,
+  pub reference_frequency: f32,
+}
+
+impl Default for Tuning {
+  fn default() -> Self {
+    Tuning {
+      divisions_per_octave: 12,
+      reference_note: 69,
+      reference_frequency: 440.,
+    }
+  }
+}
+
+impl Tuning {
+  pub fn new(divisions_per_octave: usize, reference_note: usize, reference_frequency: f32) -> Self {
+    Tuning {
+      divisions_per_octave: divisions_per_octave.max(1),
+      reference_note,
+      reference_frequency,
+    }
+  }
+
+  #[inline]
+  pub fn note_to_frequency(&self, note: usize) -> f32 {
+    let degrees_from_reference = note as f32 - self.reference_note as f32;
+    self.reference_frequency
+      * (2.0f32).powf(degrees_from_reference / self.divisions_per_octave as f32)
+  }
+}
+
+#[test]
+fn tuning_note_to_frequency() {
+  let twelve_tet = Tuning::default();
+  assert_eq!(twelve_tet.note_to_frequency(69), 440.);
+  assert_eq!(twelve_tet.note_to_frequency(81), 880.);
+  assert_eq!(
+    twelve_tet.note_to_frequency(60),
+    crate::midi_number_to_frequency(60)
+  );
+
+  let nineteen_edo = Tuning::new(19, 69, 440.);
+  assert_eq!(nineteen_edo.note_to_frequency(69 + 19), 880.);
+  assert_eq!(nineteen_edo.note_to_frequency(69 - 19), 220.);
+}
@@ -0,0 +1,458 @@
+//! Ducker for EDM-style "pumping".  Each time it's triggered, an attenuation envelope with
+//! attack, hold, and release stages is applied to the main signal.  Triggers come either from a
+//! sidechain signal crossing a threshold or from the global transport hitting a beat interval.
+//!
+//! This is much cheaper than running a full compressor with a sidechain input since no level
+//! detection is done on the main signal.
+
+use common::ffi::{self, ErrorCode};
+use dsp::{
+  db_to_gain,
+  smoothed_param::{SmoothedParam, SmoothingMode},
+  MAX_FRAME_SIZE,
+};
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+  fn log_err(ptr: *const u8, len: usize);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+use common::native::log_err;
+
+const CHANNEL_COUNT: usize = 2;
+/// The release curve exponent ranges from `1 / SHAPE_RANGE` to `SHAPE_RANGE`
+const SHAPE_RANGE: f32 = 8.;
+const DEPTH_SMOOTHING_MS: f32 = 20.;
+/// Release time of the peak follower used to detect triggers in the sidechain signal
+const TRIGGER_DETECTOR_RELEASE_MS: f32 = 10.;
+/// The trigger re-arms once the sidechain level falls this far below the threshold, which keeps it
+/// from chattering when the level hovers around the threshold.
+const TRIGGER_REARM_RATIO: f32 = 0.5;
+pub const MIN_BEAT_INTERVAL: f32 = 1. / 16.;
+pub const MAX_BEAT_INTERVAL: f32 = 16.;
+
+/// Index into the SAB of the gain applied to the last sample of the last frame
+pub const SAB_GAIN_IX: usize = 0;
+/// Index into the SAB of the number of times the envelope has been triggered since creation
+pub const SAB_TRIGGER_COUNT_IX: usize = 1;
+const SAB_SIZE: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriggerMode {
+  /// Triggered when the level of the trigger input rises above the threshold
+  Signal = 0,
+  /// Triggered at the start of each beat interval while the global transport is running
+  Beat = 1,
+}
+
+impl TriggerMode {
+  pub fn from_u32(mode: u32) -> Option<Self> {
+    match mode {
+      0 => Some(TriggerMode::Signal),
+      1 => Some(TriggerMode::Beat),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DuckerParams {
+  /// Amount of attenuation at the peak of the envelope, from 0 (none) to 1 (silence)
+  pub depth: f32,
+  pub attack_ms: f32,
+  pub hold_ms: f32,
+  pub release_ms: f32,
+  /// Curve of the release stage in `[-1, 1]`.  Positive values keep the signal ducked for longer
+  /// before it snaps back, negative values recover quickly and then ease back to full level, and 0
+  /// is linear.
+  pub shape: f32,
+  /// Level that the trigger input needs to reach to trigger the envelope in `TriggerMode::Signal`
+  pub threshold_db: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stage {
+  Idle,
+  Attack,
+  Hold,
+  Release,
+}
+
+/// Produces the amount of ducking to apply in `[0, 1]`, where 1 is fully ducked
+#[derive(Clone)]
+struct DuckEnvelope {
+  stage: Stage,
+  /// Samples elapsed in the current stage
+  pos: f32,
+  value: f32,
+  /// Value that the current attack started from.  Retriggering during the release ramps up from
+  /// wherever the envelope is rather than jumping back to zero first.
+  attack_start: f32,
+}
+
+impl Default for DuckEnvelope {
+  fn default() -> Self {
+    DuckEnvelope {
+      stage: Stage::Idle,
+      pos: 0.,
+      value: 0.,
+      attack_start: 0.,
+    }
+  }
+}
+
+impl DuckEnvelope {
+  fn trigger(&mut self) {
+    self.stage = Stage::Attack;
+    self.pos = 0.;
+    self.attack_start = self.value;
+  }
+
+  /// `release_exponent` above 1 keeps the signal ducked for longer before snapping back, and
+  /// values below 1 recover quickly and then ease back to full level.
+  #[inline]
+  fn tick(
+    &mut self,
+    attack_samples: f32,
+    hold_samples: f32,
+    release_samples: f32,
+    release_exponent: f32,
+  ) -> f32 {
+    if self.stage == Stage::Attack && self.pos >= attack_samples {
+      self.stage = Stage::Hold;
+      self.pos = 0.;
+    }
+    if self.stage == Stage::Hold && self.pos >= hold_samples {
+      self.stage = Stage::Release;
+      self.pos = 0.;
+    }
+    if self.stage == Stage::Release && self.pos >= release_samples {
+      self.stage = Stage::Idle;
+    }
+
+    self.value = match self.stage {
+      Stage::Idle => 0.,
+      Stage::Attack => {
+        let phase = self.pos / attack_samples;
+        self.attack_start + (1. - self.attack_start) * phase
+      },
+      Stage::Hold => 1.,
+      Stage::Release => 1. - (self.pos / release_samples).powf(release_exponent),
+    };
+    self.pos += 1.;
+    self.value
+  }
+}
+
+pub struct DuckerCtx {
+  /// Planar stereo buffer; the left channel starts at index 0 and the right channel at index
+  /// `MAX_FRAME_SIZE`, regardless of the frame size being processed.
+  pub io_buffer: [f32; MAX_FRAME_SIZE * CHANNEL_COUNT],
+  pub trigger_buffer: [f32; MAX_FRAME_SIZE],
+  pub trigger_mode: TriggerMode,
+  pub beat_interval: f32,
+  envelope: DuckEnvelope,
+  depth: SmoothedParam,
+  trigger_level: f32,
+  trigger_armed: bool,
+  /// Index of the beat interval that the transport was in as of the last sample, or `None` if the
+  /// transport was stopped.
+  last_beat_interval_ix: Option<i64>,
+  pub sab: [f32; SAB_SIZE],
+}
+
+impl Default for DuckerCtx {
+  fn default() -> Self {
+    Self {
+      io_buffer: [0.; MAX_FRAME_SIZE * CHANNEL_COUNT],
+      trigger_buffer: [0.; MAX_FRAME_SIZE],
+      trigger_mode: TriggerMode::Signal,
+      beat_interval: 1.,
+      envelope: DuckEnvelope::default(),
+      depth: SmoothedParam::new(SmoothingMode::Exponential, 0., DEPTH_SMOOTHING_MS),
+      trigger_level: 0.,
+      trigger_armed: true,
+      last_beat_interval_ix: None,
+      sab: [1., 0.],
+    }
+  }
+}
+
+impl DuckerCtx {
+  /// Returns `true` if the envelope should be triggered at sample `i` of the current frame.
+  /// `start_beat` is the transport position at the start of the frame, or `None` if the transport
+  /// isn't running.
+  #[inline]
+  fn check_trigger(
+    &mut self,
+    i: usize,
+    start_beat: Option<f64>,
+    beats_per_sample: f64,
+    threshold: f32,
+    detector_release_coefficient: f32,
+  ) -> bool {
+    match self.trigger_mode {
+      TriggerMode::Signal => {
+        let sample = self.trigger_buffer[i].abs();
+        self.trigger_level = if sample.is_finite() && sample > self.trigger_level {
+          sample
+        } else {
+          self.trigger_level * detector_release_coefficient
+        };
+
+        if self.trigger_armed && self.trigger_level >= threshold {
+          self.trigger_armed = false;
+          true
+        } else {
+          if !self.trigger_armed && self.trigger_level < threshold * TRIGGER_REARM_RATIO {
+            self.trigger_armed = true;
+          }
+          false
+        }
+      },
+      TriggerMode::Beat => {
+        let Some(start_beat) = start_beat else {
+          self.last_beat_interval_ix = None;
+          return false;
+        };
+        let beat = start_beat + i as f64 * beats_per_sample;
+        let interval_ix = (beat / self.beat_interval as f64).floor() as i64;
+        let triggered = self.last_beat_interval_ix != Some(interval_ix);
+        self.last_beat_interval_ix = Some(interval_ix);
+        triggered
+      },
+    }
+  }
+
+  /// Ducks the contents of the IO buffer.  `cur_beat` is the transport position at the start of
+  /// the frame and is ignored unless `transport_running` is set.
+  pub fn process(
+    &mut self,
+    frame_size: usize,
+    cur_beat: f64,
+    bpm: f32,
+    transport_running: bool,
+    params: &DuckerParams,
+  ) {
+    self.depth.set_target(params.depth);
+    let attack_samples = dsp::ms_to_samples(params.attack_ms);
+    let hold_samples = dsp::ms_to_samples(params.hold_ms);
+    let release_samples = dsp::ms_to_samples(params.release_ms);
+    let release_exponent = SHAPE_RANGE.powf(params.shape);
+    let threshold = db_to_gain(params.threshold_db);
+    let detector_release_coefficient =
+      (-1. / dsp::ms_to_samples(TRIGGER_DETECTOR_RELEASE_MS)).exp();
+    let start_beat = if transport_running {
+      Some(cur_beat)
+    } else {
+      None
+    };
+    let beats_per_sample = bpm as f64 / 60. / dsp::sample_rate() as f64;
+
+    let mut gain = 1.;
+    for i in 0..frame_size {
+      if self.check_trigger(
+        i,
+        start_beat,
+        beats_per_sample,
+        threshold,
+        detector_release_coefficient,
+      ) {
+        self.envelope.trigger();
+        self.sab[SAB_TRIGGER_COUNT_IX] += 1.;
+      }
+
+      let duck_amount = self.envelope.tick(
+        attack_samples,
+        hold_samples,
+        release_samples,
+        release_exponent,
+      );
+      gain = 1. - self.depth.tick() * duck_amount;
+      self.io_buffer[i] *= gain;
+      self.io_buffer[MAX_FRAME_SIZE + i] *= gain;
+    }
+
+    self.sab[SAB_GAIN_IX] = gain;
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn ducker_create_ctx() -> *mut DuckerCtx {
+  common::set_raw_panic_hook(log_err);
+
+  Box::into_raw(Box::default())
+}
+
+#[no_mangle]
+pub extern "C" fn ducker_get_io_buf_ptr(ctx: *mut DuckerCtx) -> *mut f32 {
+  match unsafe { ffi::handle(ctx, "ducker_get_io_buf_ptr") } {
+    Ok(ctx) => ctx.io_buffer.as_mut_ptr(),
+    Err(_) => std::ptr::null_mut(),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn ducker_get_trigger_buf_ptr(ctx: *mut DuckerCtx) -> *mut f32 {
+  match unsafe { ffi::handle(ctx, "ducker_get_trigger_buf_ptr") } {
+    Ok(ctx) => ctx.trigger_buffer.as_mut_ptr(),
+    Err(_) => std::ptr::null_mut(),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn ducker_get_sab_ptr(ctx: *mut DuckerCtx) -> *const f32 {
+  match unsafe { ffi::handle(ctx, "ducker_get_sab_ptr") } {
+    Ok(ctx) => ctx.sab.as_ptr(),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn ducker_set_trigger_mode(ctx: *mut DuckerCtx, mode: u32) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "ducker_set_trigger_mode") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  let Some(mode) = TriggerMode::from_u32(mode) else {
+    return ffi::set_last_error(
+      ErrorCode::Unsupported,
+      &format!("unknown trigger mode {mode}"),
+    );
+  };
+
+  ctx.trigger_mode = mode;
+  ctx.last_beat_interval_ix = None;
+  ctx.trigger_armed = true;
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn ducker_set_beat_interval(ctx: *mut DuckerCtx, beat_interval: f32) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "ducker_set_beat_interval") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range(
+    "beat_interval",
+    beat_interval,
+    MIN_BEAT_INTERVAL,
+    MAX_BEAT_INTERVAL,
+  ) {
+    return code;
+  }
+
+  ctx.beat_interval = beat_interval;
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn ducker_process(
+  ctx: *mut DuckerCtx,
+  frame_size: usize,
+  cur_beat: f64,
+  bpm: f32,
+  transport_running: bool,
+  depth: f32,
+  attack_ms: f32,
+  hold_ms: f32,
+  release_ms: f32,
+  shape: f32,
+  threshold_db: f32,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "ducker_process") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("frame_size", frame_size, 1, MAX_FRAME_SIZE)?;
+    ffi::check_range("bpm", bpm, 0., 1000.)?;
+    ffi::check_range("depth", depth, 0., 1.)?;
+    ffi::check_range("attack_ms", attack_ms, 0., 2000.)?;
+    ffi::check_range("hold_ms", hold_ms, 0., 2000.)?;
+    ffi::check_range("release_ms", release_ms, 0., 2000.)?;
+    ffi::check_range("shape", shape, -1., 1.)?;
+    ffi::check_range("threshold_db", threshold_db, -100., 12.)?;
+
+    ctx.process(
+      frame_size,
+      cur_beat,
+      bpm,
+      transport_running,
+      &DuckerParams {
+        depth,
+        attack_ms,
+        hold_ms,
+        release_ms,
+        shape,
+        threshold_db,
+      },
+    );
+    Ok(())
+  })())
+}
+
+#[cfg(test)]
+const TEST_PARAMS: DuckerParams = DuckerParams {
+  depth: 0.8,
+  attack_ms: 10.,
+  hold_ms: 20.,
+  release_ms: 50.,
+  shape: 0.,
+  threshold_db: -12.,
+};
+
+#[test]
+fn envelope_stages() {
+  let mut ctx = DuckerCtx::default();
+  ctx.depth.set_immediate(TEST_PARAMS.depth);
+  let ms = |ms: f32| dsp::ms_to_samples(ms).round() as usize;
+
+  // Trigger on the first sample, then let the envelope run its course
+  ctx.trigger_buffer[0] = 1.;
+  let mut gains = Vec::new();
+  for _ in 0..(ms(200.) / 128 + 1) {
+    ctx.io_buffer.fill(1.);
+    ctx.process(128, 0., 120., false, &TEST_PARAMS);
+    gains.extend_from_slice(&ctx.io_buffer[..128]);
+    ctx.trigger_buffer[0] = 0.;
+  }
+
+  assert!(gains[0] > 0.99, "{}", gains[0]);
+  let mid_attack = gains[ms(5.)];
+  assert!(mid_attack < 0.7 && mid_attack > 0.5, "{mid_attack}");
+  for &gain in &gains[ms(10.) + 1..ms(30.)] {
+    assert!((gain - 0.2).abs() < 1e-4, "{gain}");
+  }
+  for pair in gains[ms(30.)..ms(80.)].windows(2) {
+    assert!(pair[1] >= pair[0]);
+  }
+  assert!(gains[ms(81.)..].iter().all(|&gain| gain == 1.));
+  assert_eq!(ctx.sab[SAB_TRIGGER_COUNT_IX], 1.);
+}
+
+#[test]
+fn beat_triggers() {
+  let mut ctx = DuckerCtx {
+    trigger_mode: TriggerMode::Beat,
+    beat_interval: 0.5,
+    ..Default::default()
+  };
+
+  // Two seconds at 120 BPM is four beats, or eight half-beat intervals
+  let bpm = 120.;
+  let frame_count = (dsp::sample_rate() * 2.) as usize / 128;
+  let beats_per_frame = 128. * bpm as f64 / 60. / dsp::sample_rate() as f64;
+  for frame_ix in 0..frame_count {
+    let cur_beat = frame_ix as f64 * beats_per_frame;
+    ctx.process(128, cur_beat, bpm, true, &TEST_PARAMS);
+  }
+  assert_eq!(ctx.sab[SAB_TRIGGER_COUNT_IX], 8.);
+
+  // Nothing is triggered while the transport is stopped
+  for _ in 0..frame_count {
+    ctx.process(128, 0., bpm, false, &TEST_PARAMS);
+  }
+  assert_eq!(ctx.sab[SAB_TRIGGER_COUNT_IX], 8.);
+}
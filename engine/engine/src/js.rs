@@ -6,6 +6,7 @@ use wasm_bindgen::prelude::*;
 extern "C" {
   pub fn init_view_contexts(
     active_context_ix: usize,
+    split_context_ix: Option<usize>,
     view_context_definitions: &str,
     connections_json: &str,
     foreign_connectables_json: &str,
@@ -13,6 +14,7 @@ extern "C" {
   pub fn add_view_context(id: &str, name: &str);
   pub fn delete_view_context(id: &str);
   pub fn set_active_vc_ix(new_ix: usize);
+  pub fn set_split_vc_ix(new_ix: Option<usize>);
   pub fn list_foreign_node_used_samples(id: &str) -> Vec<JsValue>;
   /// Returns the ID of the active view context to display after initializing
   pub fn initialize_default_vcm_state();
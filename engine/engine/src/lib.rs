@@ -91,6 +91,17 @@ pub fn switch_view_context(uuid_str: &str) {
   get_vcm().set_active_view_by_id(uuid);
 }
 
+/// Displays the view context with the provided ID side by side with the active one
+#[wasm_bindgen]
+pub fn set_split_view_context(uuid_str: &str) {
+  let uuid =
+    Uuid::from_str(uuid_str).expect("Invalid UUID string passed to `set_split_view_context`!");
+  get_vcm().set_split_view_by_id(uuid);
+}
+
+#[wasm_bindgen]
+pub fn clear_split_view_context() { get_vcm().set_split_view(None); }
+
 #[wasm_bindgen]
 pub fn reset_vcm() {
   info!("Resetting VCM...");
@@ -62,6 +62,9 @@ pub struct ForeignConnectable {
 
 pub struct ViewContextManager {
   pub active_context_ix: usize,
+  /// If set, this view context is displayed side by side with the active one.  Never the same as
+  /// `active_context_ix`.
+  pub split_context_ix: Option<usize>,
   pub contexts: Vec<ViewContextEntry>,
   pub connections: Vec<(ConnectionDescriptor, ConnectionDescriptor)>,
   pub foreign_connectables: Vec<ForeignConnectable>,
@@ -71,6 +74,7 @@ impl Default for ViewContextManager {
   fn default() -> Self {
     ViewContextManager {
       active_context_ix: 0,
+      split_context_ix: None,
       contexts: Vec::new(),
       connections: Vec::new(),
       foreign_connectables: Vec::new(),
@@ -108,6 +112,7 @@ struct ViewContextManagerState {
   /// them are found in separate `localStorage` entries.
   pub view_context_ids: Vec<String>,
   pub active_view_ix: usize,
+  pub split_view_ix: Option<usize>,
   pub patch_network_connections: Vec<(ConnectionDescriptor, ConnectionDescriptor)>,
   pub foreign_connectables: Vec<ForeignConnectable>,
 }
@@ -211,6 +216,7 @@ impl ViewContextManager {
     }
 
     self.active_context_ix = vcm_state.active_view_ix;
+    self.split_context_ix = vcm_state.split_view_ix;
     self.connections = vcm_state.patch_network_connections;
     self.foreign_connectables = vcm_state.foreign_connectables;
   }
@@ -240,7 +246,17 @@ impl ViewContextManager {
     } else if self.active_context_ix >= self.contexts.len() {
       self.active_context_ix = 0;
     }
+    if self
+      .split_context_ix
+      .map(|ix| ix >= self.contexts.len() || ix == self.active_context_ix)
+      .unwrap_or(false)
+    {
+      self.split_context_ix = None;
+    }
     self.contexts[self.active_context_ix].context.unhide();
+    if let Some(split_ix) = self.split_context_ix {
+      self.contexts[split_ix].context.unhide();
+    }
 
     self.commit();
   }
@@ -276,6 +292,7 @@ impl ViewContextManager {
 
     js::init_view_contexts(
       self.active_context_ix,
+      self.split_context_ix,
       &definitions_str,
       &connections_json,
       &foreign_connectables_json,
@@ -308,6 +325,7 @@ impl ViewContextManager {
     js::delete_localstorage_key(&get_vc_key(id));
 
     let old_active_vc_ix = self.active_context_ix;
+    let old_split_vc_ix = self.split_context_ix;
     if self.active_context_ix == ix {
       // If the deleted VC was the active VC, pick the one before it to be the active VC.
       self.active_context_ix = ix.saturating_sub(1);
@@ -315,6 +333,16 @@ impl ViewContextManager {
       // If the active view context is above the one that was removed, shift it one down
       self.active_context_ix = self.active_context_ix - 1;
     }
+    self.split_context_ix = match self.split_context_ix {
+      Some(split_ix) if split_ix == ix => None,
+      Some(split_ix) if split_ix > ix => Some(split_ix - 1),
+      split_ix => split_ix,
+    };
+    // The split VC is already visible, so it takes over as the active one rather than the
+    // fallback picked above
+    if old_active_vc_ix == ix && self.split_context_ix.is_some() {
+      self.active_context_ix = self.split_context_ix.take().unwrap();
+    }
 
     if let Some(vc_entry) = self.contexts.get_mut(self.active_context_ix) {
       vc_entry.context.unhide();
@@ -325,6 +353,9 @@ impl ViewContextManager {
       self.get_active_view_mut().unhide();
       js::set_active_vc_ix(self.active_context_ix);
     }
+    if old_split_vc_ix != self.split_context_ix {
+      js::set_split_vc_ix(self.split_context_ix);
+    }
 
     self.save_all();
   }
@@ -351,6 +382,7 @@ impl ViewContextManager {
     let state = ViewContextManagerState {
       view_context_ids,
       active_view_ix: self.active_context_ix,
+      split_view_ix: self.split_context_ix,
       patch_network_connections: self.connections.clone(),
       foreign_connectables: self.foreign_connectables.clone(),
     };
@@ -361,6 +393,17 @@ impl ViewContextManager {
   }
 
   pub fn set_active_view(&mut self, view_ix: usize) {
+    // Activating the split view swaps it with the active one, keeping both visible
+    if self.split_context_ix == Some(view_ix) {
+      let old_active_ix = self.active_context_ix;
+      self.active_context_ix = view_ix;
+      self.split_context_ix = Some(old_active_ix);
+      self.save_all();
+      js::set_active_vc_ix(view_ix);
+      js::set_split_vc_ix(self.split_context_ix);
+      return;
+    }
+
     self.save_all();
     self.get_active_view_mut().hide();
     self.active_context_ix = view_ix;
@@ -368,6 +411,36 @@ impl ViewContextManager {
     js::set_active_vc_ix(view_ix);
   }
 
+  /// Displays the view context at `view_ix` side by side with the active one, or goes back to
+  /// only displaying the active view context if `None` is provided.
+  pub fn set_split_view(&mut self, view_ix: Option<usize>) {
+    let view_ix = view_ix.filter(|&ix| ix != self.active_context_ix);
+    if view_ix == self.split_context_ix {
+      return;
+    }
+
+    if let Some(old_split_ix) = self.split_context_ix {
+      self.contexts[old_split_ix].context.hide();
+    }
+    self.split_context_ix = view_ix;
+    if let Some(new_split_ix) = view_ix {
+      self.contexts[new_split_ix].context.unhide();
+    }
+    self.save_all();
+    js::set_split_vc_ix(view_ix);
+  }
+
+  /// Given the UUID of a managed `ViewContext`, displays it side by side with the active view.
+  pub fn set_split_view_by_id(&mut self, id: Uuid) {
+    match self.get_vc_position(id) {
+      Some(ix) => self.set_split_view(Some(ix)),
+      None => error!(
+        "Tried to display VC with ID {} in a split view but it wasn't found.",
+        id
+      ),
+    }
+  }
+
   pub fn set_connections(
     &mut self,
     new_connections: Vec<(ConnectionDescriptor, ConnectionDescriptor)>,
@@ -0,0 +1,69 @@
+//! Mirrors the composition bundle format defined in `backend/src/models/composition_bundle.rs`.
+//! Bundles contain a composition along with the presets, effects, and samples it references so
+//! that it can be moved between instances as a single file.
+
+use std::collections::BTreeMap;
+
+use miniserde::{json, Deserialize, Serialize};
+
+/// Must match `COMPOSITION_BUNDLE_FORMAT_VERSION` in the backend
+pub const COMPOSITION_BUNDLE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BundledComposition {
+  pub title: String,
+  pub description: String,
+  pub tags: Vec<String>,
+  /// Maps `localStorage` keys to their values, the same as the content of saved compositions
+  pub content: BTreeMap<String, json::Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BundledPreset {
+  pub id: i64,
+  pub title: String,
+  pub description: String,
+  pub body: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BundledEffect {
+  pub id: i64,
+  pub title: String,
+  pub description: String,
+  pub code: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BundledSample {
+  pub id: Option<String>,
+  pub name: String,
+  pub url: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompositionBundle {
+  #[serde(rename = "formatVersion")]
+  pub format_version: u32,
+  pub composition: BundledComposition,
+  #[serde(rename = "synthPresets")]
+  pub synth_presets: Vec<BundledPreset>,
+  #[serde(rename = "voicePresets")]
+  pub voice_presets: Vec<BundledPreset>,
+  pub effects: Vec<BundledEffect>,
+  pub samples: Vec<BundledSample>,
+}
+
+/// Parses a bundle exported from the backend, rejecting ones with a newer format than this version
+/// of the application understands.
+pub fn parse_composition_bundle(serialized: &str) -> Result<CompositionBundle, String> {
+  let bundle: CompositionBundle =
+    json::from_str(serialized).map_err(|_| String::from("Invalid composition bundle"))?;
+  if bundle.format_version > COMPOSITION_BUNDLE_FORMAT_VERSION {
+    return Err(format!(
+      "Unsupported composition bundle format version {}; at most {} is supported",
+      bundle.format_version, COMPOSITION_BUNDLE_FORMAT_VERSION
+    ));
+  }
+  Ok(bundle)
+}
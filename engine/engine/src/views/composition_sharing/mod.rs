@@ -4,6 +4,8 @@ use uuid::Uuid;
 
 use crate::{js, view_context::ViewContext};
 
+pub mod bundle;
+
 /// This is just a shim to the JS-based composition sharing UI.  Since there really aren't any
 /// complicated interactive or graphical components of this view context, the actual implementation
 /// for this is done in JS.
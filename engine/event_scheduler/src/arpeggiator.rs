@@ -0,0 +1,341 @@
+//! Arpeggiator that turns a set of held notes into a stream of MIDI attack/release events synced to
+//! the global beat counter.  Generated events are pushed into the same beat-scheduled event queue
+//! used by MIDI editor playback, so they're delivered to the destination mailbox in exactly the
+//! same way.
+
+use common::ffi::{self, ErrorCode, FfiResult};
+use rand::Rng;
+
+/// Shortest step length that can be set.  Steps shorter than this would generate bursts of events
+/// on every tick rather than anything resembling an arpeggio.
+pub const MIN_RATE_BEATS: f64 = 1. / 64.;
+/// The transport moving backwards or advancing further than this between ticks is treated as a
+/// seek.  Ticks happen every frame, which covers a small fraction of a beat at any usable tempo.
+const SEEK_THRESHOLD_BEATS: f64 = 2.;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArpPattern {
+  Up = 0,
+  Down = 1,
+  UpDown = 2,
+  Random = 3,
+  AsPlayed = 4,
+}
+
+impl ArpPattern {
+  pub fn from_u8(val: u8) -> Option<Self> {
+    match val {
+      0 => Some(ArpPattern::Up),
+      1 => Some(ArpPattern::Down),
+      2 => Some(ArpPattern::UpDown),
+      3 => Some(ArpPattern::Random),
+      4 => Some(ArpPattern::AsPlayed),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArpEventKind {
+  Attack,
+  Release,
+}
+
+#[derive(Clone, Copy)]
+struct HeldNote {
+  pub note: u8,
+  pub velocity: f32,
+}
+
+pub struct Arpeggiator {
+  /// Index of the MIDI event mailbox that generated events are delivered to
+  pub mailbox_ix: usize,
+  pub pattern: ArpPattern,
+  /// Number of octaves that the held notes are repeated over, starting at the played octave
+  pub octave_range: u8,
+  /// Length of a single step in beats
+  pub rate_beats: f64,
+  /// Fraction of the step length that each note is held for, in (0, 1]
+  pub gate_length: f64,
+  /// Fraction of half a step by which every other step is delayed, in [0, 1)
+  pub swing: f64,
+  /// Held notes in the order they were pressed
+  held_notes: Vec<HeldNote>,
+  /// Held notes expanded over the octave range and ordered according to `pattern`
+  sequence: Vec<HeldNote>,
+  step_ix: usize,
+  /// Beat at which the next step starts before swing is applied.  `None` if the arpeggiator isn't
+  /// currently running.
+  next_step_beat: Option<f64>,
+  /// Beat passed to the previous call to `tick`, used to detect seeks
+  last_beat: Option<f64>,
+}
+
+impl Arpeggiator {
+  pub fn new(mailbox_ix: usize) -> Self {
+    Arpeggiator {
+      mailbox_ix,
+      pattern: ArpPattern::Up,
+      octave_range: 1,
+      rate_beats: 0.25,
+      gate_length: 0.5,
+      swing: 0.,
+      held_notes: Vec::new(),
+      sequence: Vec::new(),
+      step_ix: 0,
+      next_step_beat: None,
+      last_beat: None,
+    }
+  }
+
+  /// Fails with `ErrorCode::ParamOutOfRange` without changing anything if any of the values are
+  /// NaN or infinite.  Finite values are clamped to their valid ranges.
+  pub fn set_config(
+    &mut self,
+    pattern: ArpPattern,
+    octave_range: u8,
+    rate_beats: f64,
+    gate_length: f64,
+    swing: f64,
+  ) -> FfiResult {
+    for (name, val) in [
+      ("rate_beats", rate_beats),
+      ("gate_length", gate_length),
+      ("swing", swing),
+    ] {
+      if !val.is_finite() {
+        return Err(ffi::set_last_error(
+          ErrorCode::ParamOutOfRange,
+          &format!("{name}={val} must be finite"),
+        ));
+      }
+    }
+
+    self.pattern = pattern;
+    self.octave_range = octave_range.max(1);
+    self.rate_beats = rate_beats.max(MIN_RATE_BEATS);
+    self.gate_length = gate_length.clamp(0.01, 1.);
+    self.swing = swing.clamp(0., 0.99);
+    self.rebuild_sequence();
+    Ok(())
+  }
+
+  pub fn note_on(&mut self, note: u8, velocity: f32) {
+    if self.held_notes.iter().any(|held| held.note == note) {
+      return;
+    }
+    self.held_notes.push(HeldNote { note, velocity });
+    self.rebuild_sequence();
+  }
+
+  pub fn note_off(&mut self, note: u8) {
+    self.held_notes.retain(|held| held.note != note);
+    self.rebuild_sequence();
+  }
+
+  pub fn clear(&mut self) {
+    self.held_notes.clear();
+    self.rebuild_sequence();
+  }
+
+  /// Resets the arpeggiator's position so that it re-aligns to the beat grid the next time it's
+  /// ticked.  Held notes are kept.
+  pub fn reset_transport(&mut self) {
+    self.step_ix = 0;
+    self.next_step_beat = None;
+    self.last_beat = None;
+  }
+
+  fn rebuild_sequence(&mut self) {
+    let mut base = self.held_notes.clone();
+    if self.pattern != ArpPattern::AsPlayed {
+      base.sort_unstable_by_key(|held| held.note);
+    }
+
+    self.sequence.clear();
+    for octave in 0..self.octave_range {
+      for held in &base {
+        let note = held.note as usize + 12 * octave as usize;
+        if note > 127 {
+          continue;
+        }
+        self.sequence.push(HeldNote {
+          note: note as u8,
+          velocity: held.velocity,
+        });
+      }
+    }
+
+    match self.pattern {
+      ArpPattern::Down => self.sequence.reverse(),
+      ArpPattern::UpDown if self.sequence.len() > 2 => {
+        // Don't repeat the top and bottom notes when changing direction
+        let descending: Vec<HeldNote> = self.sequence[1..self.sequence.len() - 1]
+          .iter()
+          .rev()
+          .copied()
+          .collect();
+        self.sequence.extend(descending);
+      },
+      _ => (),
+    }
+  }
+
+  fn pick_step_note(&mut self) -> HeldNote {
+    let ix = match self.pattern {
+      ArpPattern::Random => common::rng().gen_range(0, self.sequence.len()),
+      _ => self.step_ix % self.sequence.len(),
+    };
+    self.sequence[ix]
+  }
+
+  /// Generates events for all steps that start at or before `cur_beats`, calling `emit` with the
+  /// beat, event kind, note, and velocity of each.  Releases are emitted ahead of time with the
+  /// beat at which they should fire.
+  pub fn tick(&mut self, cur_beats: f64, mut emit: impl FnMut(f64, ArpEventKind, u8, f32)) {
+    if self.sequence.is_empty() || self.rate_beats <= 0. {
+      self.reset_transport();
+      return;
+    }
+
+    // The transport was restarted or seeked.  Re-align to the beat grid at the new position rather
+    // than firing every step that was skipped over.
+    if let Some(last_beat) = self.last_beat {
+      if last_beat > cur_beats || cur_beats - last_beat > SEEK_THRESHOLD_BEATS {
+        self.reset_transport();
+      }
+    }
+    self.last_beat = Some(cur_beats);
+
+    // Start on the next step boundary so that the arpeggio lines up with the beat grid
+    let rate_beats = self.rate_beats;
+    let mut step_beat = *self
+      .next_step_beat
+      .get_or_insert_with(|| (cur_beats / rate_beats).ceil() * rate_beats);
+
+    loop {
+      let swing_offset = if self.step_ix % 2 == 1 {
+        self.swing * rate_beats * 0.5
+      } else {
+        0.
+      };
+      let start_beat = step_beat + swing_offset;
+      if start_beat > cur_beats {
+        break;
+      }
+
+      let HeldNote { note, velocity } = self.pick_step_note();
+      let gate_beats = (rate_beats - swing_offset) * self.gate_length;
+      emit(start_beat, ArpEventKind::Attack, note, velocity);
+      emit(start_beat + gate_beats, ArpEventKind::Release, note, 0.);
+
+      self.step_ix = self.step_ix.wrapping_add(1);
+      step_beat += rate_beats;
+    }
+    self.next_step_beat = Some(step_beat);
+  }
+}
+
+#[cfg(test)]
+fn collect_notes(arp: &mut Arpeggiator, step_count: usize) -> Vec<u8> {
+  let mut notes = Vec::new();
+  for step in 0..step_count {
+    arp.tick(step as f64 * arp.rate_beats, |_, kind, note, _| {
+      if kind == ArpEventKind::Attack {
+        notes.push(note)
+      }
+    });
+  }
+  notes
+}
+
+#[test]
+fn arp_patterns() {
+  let mut arp = Arpeggiator::new(0);
+  arp.note_on(64, 1.);
+  arp.note_on(60, 1.);
+  arp.note_on(67, 1.);
+
+  arp.set_config(ArpPattern::Up, 2, 0.25, 0.5, 0.).unwrap();
+  assert_eq!(collect_notes(&mut arp, 7), vec![60, 64, 67, 72, 76, 79, 60]);
+
+  arp.reset_transport();
+  arp
+    .set_config(ArpPattern::UpDown, 1, 0.25, 0.5, 0.)
+    .unwrap();
+  assert_eq!(collect_notes(&mut arp, 6), vec![60, 64, 67, 64, 60, 64]);
+
+  arp.reset_transport();
+  arp
+    .set_config(ArpPattern::AsPlayed, 1, 0.25, 0.5, 0.)
+    .unwrap();
+  assert_eq!(collect_notes(&mut arp, 4), vec![64, 60, 67, 64]);
+}
+
+#[test]
+fn arp_swing_and_gate() {
+  let mut arp = Arpeggiator::new(0);
+  arp.note_on(60, 1.);
+  arp.set_config(ArpPattern::Up, 1, 1., 0.5, 0.5).unwrap();
+
+  let mut events = Vec::new();
+  arp.tick(0., |beat, kind, _, _| events.push((beat, kind)));
+  arp.tick(2., |beat, kind, _, _| events.push((beat, kind)));
+  assert_eq!(events, vec![
+    (0., ArpEventKind::Attack),
+    (0.5, ArpEventKind::Release),
+    (1.25, ArpEventKind::Attack),
+    (1.625, ArpEventKind::Release),
+    (2., ArpEventKind::Attack),
+    (2.5, ArpEventKind::Release),
+  ]);
+}
+
+#[test]
+fn arp_rejects_non_finite_rates_and_clamps_tiny_ones() {
+  let mut arp = Arpeggiator::new(0);
+  arp.note_on(60, 1.);
+  assert_eq!(
+    arp.set_config(ArpPattern::Up, 1, f64::NAN, 0.5, 0.),
+    Err(ErrorCode::ParamOutOfRange)
+  );
+  assert!(arp
+    .set_config(ArpPattern::Up, 1, 0.25, f64::INFINITY, 0.)
+    .is_err());
+  assert_eq!(arp.rate_beats, 0.25);
+
+  arp.set_config(ArpPattern::Up, 1, 1e-12, 0.5, 0.).unwrap();
+  assert_eq!(arp.rate_beats, MIN_RATE_BEATS);
+  let mut attack_count = 0;
+  arp.tick(0., |_, _, _, _| ());
+  arp.tick(1., |_, kind, _, _| {
+    if kind == ArpEventKind::Attack {
+      attack_count += 1;
+    }
+  });
+  assert_eq!(attack_count, 64);
+}
+
+#[test]
+fn arp_realigns_after_seek() {
+  let mut arp = Arpeggiator::new(0);
+  arp.note_on(60, 1.);
+  arp.set_config(ArpPattern::Up, 1, 0.25, 0.5, 0.).unwrap();
+
+  let mut attack_beats = Vec::new();
+  let mut tick = |arp: &mut Arpeggiator, cur_beats: f64| {
+    arp.tick(cur_beats, |beat, kind, _, _| {
+      if kind == ArpEventKind::Attack {
+        attack_beats.push(beat);
+      }
+    })
+  };
+  tick(&mut arp, 0.);
+  // Seeking forwards doesn't fire all of the steps in between
+  tick(&mut arp, 100.1);
+  tick(&mut arp, 100.25);
+  // Seeking backwards picks up from the new position rather than waiting to get back to 100
+  tick(&mut arp, 8.);
+  assert_eq!(attack_beats, vec![0., 100.25, 8.]);
+}
@@ -1,17 +1,43 @@
 use std::cmp::Reverse;
 
+use common::ffi::{self, ErrorCode, FfiResult};
 use float_ord::FloatOrd;
 use heapless::binary_heap::{BinaryHeap, Min};
 
+use crate::{
+  arpeggiator::{ArpEventKind, ArpPattern, Arpeggiator},
+  midi_clock::MidiClockOutput,
+  phase_sync::PhaseSync,
+  tempo::{ClockFollower, TapTempo, MIDI_CLOCK_TICKS_PER_BEAT},
+};
+
+pub mod arpeggiator;
+pub mod midi_clock;
+pub mod phase_sync;
+pub mod tempo;
+
+#[cfg(target_arch = "wasm32")]
 extern "C" {
   fn run_callback(cb_id: i32);
 
   fn run_midi_callback(mailbox_ix: usize, event_type: u8, param_0: f32, param_1: f32);
 
+  fn send_midi_output(ptr: *const u8, len: usize, time: f64);
+
   #[allow(dead_code)]
   fn debug1(v: i32);
 }
 
+/// There's no JS to call back into outside of Wasm, so scheduled callbacks are dropped
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn run_callback(_cb_id: i32) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn run_midi_callback(_mailbox_ix: usize, _event_type: u8, _param_0: f32, _param_1: f32) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn send_midi_output(_ptr: *const u8, _len: usize, _time: f64) {}
+
 #[derive(Clone, PartialEq)]
 struct MidiEvent {
   pub mailbox_ix: usize,
@@ -53,19 +79,46 @@ impl PartialOrd for ScheduledEvent {
   }
 }
 
+/// `MIDIEventType` values from `eventScheduler.ts`
+const MIDI_EVENT_TYPE_ATTACK: u8 = 0;
+const MIDI_EVENT_TYPE_RELEASE: u8 = 1;
+
+/// Callback ID used for events generated by arpeggiators.  These are never cancelled by ID.
+const ARPEGGIATOR_CB_ID: i32 = -1;
+
 static mut SCHEDULED_EVENTS: BinaryHeap<ScheduledEvent, Min, 1048576> = BinaryHeap::new();
 static mut SCHEDULED_BEAT_EVENTS: BinaryHeap<ScheduledEvent, Min, 1048576> = BinaryHeap::new();
+static mut ARPEGGIATORS: Vec<Option<Arpeggiator>> = Vec::new();
+static mut TAP_TEMPO: TapTempo = TapTempo::new();
+static mut EXTERNAL_CLOCK: ClockFollower = ClockFollower::new(MIDI_CLOCK_TICKS_PER_BEAT);
+static mut MIDI_CLOCK_OUTPUT: MidiClockOutput = MidiClockOutput::new();
+static mut PHASE_SYNC: PhaseSync = PhaseSync::new();
 
 #[no_mangle]
-pub unsafe extern "C" fn stop() {
+pub unsafe extern "C" fn stop() -> ErrorCode {
   SCHEDULED_EVENTS.clear();
   SCHEDULED_BEAT_EVENTS.clear();
+  for arp in ARPEGGIATORS.iter_mut().flatten() {
+    arp.reset_transport();
+  }
+  ErrorCode::Ok
 }
 
-#[no_mangle]
-pub extern "C" fn schedule(time: f64, cb_id: i32) {
+/// Callback ID 0 is reserved by the JS side to mean "no callback"
+fn check_cb_id(cb_id: i32) -> FfiResult {
   if cb_id == 0 {
-    panic!();
+    return Err(ffi::set_last_error(
+      ErrorCode::ParamOutOfRange,
+      "cb_id must be non-zero",
+    ));
+  }
+  Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn schedule(time: f64, cb_id: i32) -> ErrorCode {
+  if let Err(code) = check_cb_id(cb_id) {
+    return code;
   }
 
   unsafe {
@@ -75,6 +128,7 @@ pub extern "C" fn schedule(time: f64, cb_id: i32) {
       midi_evt: None,
     })
   }
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -85,9 +139,9 @@ pub extern "C" fn schedule_beats(
   midi_event_type: u8,
   midi_param_0: f32,
   midi_param_1: f32,
-) {
-  if cb_id == 0 {
-    panic!();
+) -> ErrorCode {
+  if let Err(code) = check_cb_id(cb_id) {
+    return code;
   }
 
   let midi_evt = if mailbox_ix >= 0 {
@@ -108,6 +162,7 @@ pub extern "C" fn schedule_beats(
       midi_evt,
     })
   }
+  ErrorCode::Ok
 }
 
 fn handle_event(evt: ScheduledEvent) {
@@ -125,8 +180,39 @@ fn handle_event(evt: ScheduledEvent) {
   }
 }
 
+fn tick_arpeggiators(cur_beats: f64) {
+  let arpeggiators = unsafe { &mut ARPEGGIATORS };
+  let scheduled_beat_events = unsafe { &mut SCHEDULED_BEAT_EVENTS };
+
+  for arp in arpeggiators.iter_mut().flatten() {
+    let mailbox_ix = arp.mailbox_ix;
+    arp.tick(cur_beats, |beat, kind, note, velocity| {
+      let event_type = match kind {
+        ArpEventKind::Attack => MIDI_EVENT_TYPE_ATTACK,
+        ArpEventKind::Release => MIDI_EVENT_TYPE_RELEASE,
+      };
+      // Events that don't fit in the queue are dropped.  Attacks are only scheduled if there's
+      // room for their release as well so that a full queue can't leave notes stuck on.
+      let free_slots = scheduled_beat_events.capacity() - scheduled_beat_events.len();
+      if kind == ArpEventKind::Attack && free_slots < 2 {
+        return;
+      }
+      let _ = scheduled_beat_events.push(ScheduledEvent {
+        time: beat,
+        cb_id: ARPEGGIATOR_CB_ID,
+        midi_evt: Some(MidiEvent {
+          mailbox_ix,
+          event_type,
+          param_0: note as f32,
+          param_1: velocity,
+        }),
+      });
+    });
+  }
+}
+
 #[no_mangle]
-pub extern "C" fn run(raw_cur_time: f64, cur_beats: f64) {
+pub extern "C" fn run(raw_cur_time: f64, cur_beats: f64) -> ErrorCode {
   let scheduled_events = unsafe { &mut SCHEDULED_EVENTS };
   loop {
     match scheduled_events.peek() {
@@ -139,6 +225,8 @@ pub extern "C" fn run(raw_cur_time: f64, cur_beats: f64) {
     handle_event(evt);
   }
 
+  tick_arpeggiators(cur_beats);
+
   let scheduled_beat_events = unsafe { &mut SCHEDULED_BEAT_EVENTS };
   loop {
     match scheduled_beat_events.peek() {
@@ -150,6 +238,8 @@ pub extern "C" fn run(raw_cur_time: f64, cur_beats: f64) {
     let evt = unsafe { scheduled_beat_events.pop_unchecked() };
     handle_event(evt);
   }
+
+  ErrorCode::Ok
 }
 
 static mut IDS_BUFFER: *mut Vec<i32> = std::ptr::null_mut();
@@ -211,3 +301,191 @@ pub extern "C" fn cancel_events_by_ids() -> usize {
 
   actually_cancelled_evt_count
 }
+
+fn get_arpeggiator(arp_ix: usize) -> FfiResult<&'static mut Arpeggiator> {
+  match unsafe { ARPEGGIATORS.get_mut(arp_ix) } {
+    Some(Some(arp)) => Ok(arp),
+    _ => Err(ffi::set_last_error(
+      ErrorCode::InvalidHandle,
+      &format!("No arpeggiator with index {arp_ix}"),
+    )),
+  }
+}
+
+/// Creates a new arpeggiator that sends the events it generates to the MIDI event mailbox with
+/// index `mailbox_ix`.  Returns the arpeggiator's index which is used to refer to it in the other
+/// `arpeggiator_*` exports.
+#[no_mangle]
+pub extern "C" fn arpeggiator_create(mailbox_ix: usize) -> usize {
+  let arpeggiators = unsafe { &mut ARPEGGIATORS };
+  let arp = Arpeggiator::new(mailbox_ix);
+  match arpeggiators.iter().position(Option::is_none) {
+    Some(free_ix) => {
+      arpeggiators[free_ix] = Some(arp);
+      free_ix
+    },
+    None => {
+      arpeggiators.push(Some(arp));
+      arpeggiators.len() - 1
+    },
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn arpeggiator_delete(arp_ix: usize) -> ErrorCode {
+  ffi::status((|| {
+    get_arpeggiator(arp_ix)?;
+    unsafe { ARPEGGIATORS[arp_ix] = None };
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn arpeggiator_set_config(
+  arp_ix: usize,
+  pattern: u8,
+  octave_range: u8,
+  rate_beats: f64,
+  gate_length: f64,
+  swing: f64,
+) -> ErrorCode {
+  ffi::status((|| {
+    let arp = get_arpeggiator(arp_ix)?;
+    let pattern = ArpPattern::from_u8(pattern).ok_or_else(|| {
+      ffi::set_last_error(
+        ErrorCode::ParamOutOfRange,
+        &format!("Invalid arp pattern: {pattern}"),
+      )
+    })?;
+    arp.set_config(pattern, octave_range, rate_beats, gate_length, swing)
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn arpeggiator_note_on(arp_ix: usize, note: u8, velocity: f32) -> ErrorCode {
+  ffi::status((|| {
+    ffi::check_range("note", note, 0, 127)?;
+    get_arpeggiator(arp_ix)?.note_on(note, velocity);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn arpeggiator_note_off(arp_ix: usize, note: u8) -> ErrorCode {
+  ffi::status((|| {
+    get_arpeggiator(arp_ix)?.note_off(note);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn arpeggiator_clear(arp_ix: usize) -> ErrorCode {
+  ffi::status((|| {
+    get_arpeggiator(arp_ix)?.clear();
+    Ok(())
+  })())
+}
+
+/// Records a tapped beat at `time` seconds.  Returns the tempo of the taps so far in BPM, or 0 if
+/// there aren't enough taps yet to estimate it.
+#[no_mangle]
+pub extern "C" fn tap_tempo(time: f64) -> f64 { unsafe { TAP_TEMPO.tap(time) }.unwrap_or(0.) }
+
+#[no_mangle]
+pub extern "C" fn tap_tempo_reset() -> ErrorCode {
+  unsafe { TAP_TEMPO.reset() };
+  ErrorCode::Ok
+}
+
+/// Records a tick of an external clock at `time` seconds.  Returns the clock's tempo in BPM once
+/// per beat after the first, and 0 otherwise.
+#[no_mangle]
+pub extern "C" fn external_clock_tick(time: f64) -> f64 {
+  unsafe { EXTERNAL_CLOCK.tick(time) }.unwrap_or(0.)
+}
+
+#[no_mangle]
+pub extern "C" fn external_clock_set_ticks_per_beat(ticks_per_beat: usize) -> ErrorCode {
+  ffi::status((|| {
+    ffi::check_range("ticks_per_beat", ticks_per_beat, 1, 960)?;
+    unsafe { EXTERNAL_CLOCK.set_ticks_per_beat(ticks_per_beat) };
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn external_clock_reset() -> ErrorCode {
+  unsafe { EXTERNAL_CLOCK.reset() };
+  ErrorCode::Ok
+}
+
+/// Hands a raw MIDI message to the host to be sent to MIDI outputs at `time`
+fn emit_midi_output(bytes: &[u8], time: f64) {
+  unsafe { send_midi_output(bytes.as_ptr(), bytes.len(), time) }
+}
+
+#[no_mangle]
+pub extern "C" fn midi_clock_output_set_enabled(
+  enabled: bool,
+  send_mmc: bool,
+  cur_time: f64,
+) -> ErrorCode {
+  unsafe { MIDI_CLOCK_OUTPUT.set_enabled(enabled, send_mmc, cur_time, emit_midi_output) };
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn midi_clock_output_start(cur_time: f64) -> ErrorCode {
+  unsafe { MIDI_CLOCK_OUTPUT.start(cur_time, emit_midi_output) };
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn midi_clock_output_stop(cur_time: f64) -> ErrorCode {
+  unsafe { MIDI_CLOCK_OUTPUT.stop(cur_time, emit_midi_output) };
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn midi_clock_output_run(cur_time: f64, cur_beats: f64, bpm: f64) -> ErrorCode {
+  unsafe { MIDI_CLOCK_OUTPUT.run(cur_time, cur_beats, bpm, emit_midi_output) };
+  ErrorCode::Ok
+}
+
+/// Fails with `ErrorCode::ParamOutOfRange` unless `val` is positive and finite
+fn check_positive(param_name: &str, val: f64) -> FfiResult {
+  ffi::check_range(param_name, val, f64::MIN_POSITIVE, f64::MAX)
+}
+
+#[no_mangle]
+pub extern "C" fn phase_sync_set_quantum(quantum: f64) -> ErrorCode {
+  ffi::status((|| {
+    check_positive("quantum", quantum)?;
+    unsafe { PHASE_SYNC.set_quantum(quantum) };
+    Ok(())
+  })())
+}
+
+/// Records a report from the host that the shared session was at `beat` at audio context time
+/// `time`, running at `bpm`
+#[no_mangle]
+pub extern "C" fn phase_sync_add_report(time: f64, beat: f64, bpm: f64) -> ErrorCode {
+  ffi::status((|| {
+    check_positive("bpm", bpm)?;
+    unsafe { PHASE_SYNC.add_report(time, beat, bpm) };
+    Ok(())
+  })())
+}
+
+/// Returns the tempo that the global beat counter should run at to stay in phase with the shared
+/// session, or 0 if no session has been reported
+#[no_mangle]
+pub extern "C" fn phase_sync_steer(cur_time: f64, cur_beats: f64) -> f64 {
+  unsafe { PHASE_SYNC.steer(cur_time, cur_beats) }.unwrap_or(0.)
+}
+
+#[no_mangle]
+pub extern "C" fn phase_sync_reset() -> ErrorCode {
+  unsafe { PHASE_SYNC.reset() };
+  ErrorCode::Ok
+}
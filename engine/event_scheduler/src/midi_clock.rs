@@ -0,0 +1,186 @@
+//! Generates outgoing MIDI clock and transport messages from the global beat counter so that
+//! hardware synths and drum machines can follow it.
+//!
+//! Clock ticks are generated a little ahead of time along with the times at which they should be
+//! sent, which lets the host schedule them on its MIDI outputs so that they don't pick up the
+//! jitter of the audio thread.  Ticks are only sent while the transport is running.
+
+use crate::tempo::MIDI_CLOCK_TICKS_PER_BEAT;
+
+const TIMING_CLOCK: u8 = 0xf8;
+const START: u8 = 0xfa;
+const CONTINUE: u8 = 0xfb;
+const STOP: u8 = 0xfc;
+const SONG_POSITION_POINTER: u8 = 0xf2;
+/// Song position pointer counts in sixteenth notes
+const TICKS_PER_SONG_POSITION: u64 = MIDI_CLOCK_TICKS_PER_BEAT as u64 / 4;
+const MAX_SONG_POSITION: u64 = 0x3fff;
+/// MIDI machine control commands, sent to all devices
+const MMC_STOP: [u8; 6] = [0xf0, 0x7f, 0x7f, 0x06, 0x01, 0xf7];
+const MMC_PLAY: [u8; 6] = [0xf0, 0x7f, 0x7f, 0x06, 0x02, 0xf7];
+/// How far ahead of the current time clock ticks are generated.  Needs to cover the time between
+/// audio thread renders plus the time it takes to hand the messages to the host.
+const LOOKAHEAD_SECONDS: f64 = 0.05;
+
+#[derive(Default)]
+pub struct MidiClockOutput {
+  enabled: bool,
+  /// Also send MIDI machine control play and stop commands along with the transport messages
+  send_mmc: bool,
+  is_playing: bool,
+  /// Set when output is enabled while the transport is already running so that receivers are
+  /// moved to the current position and continued from there
+  needs_continue: bool,
+  /// Index of the next clock tick to send, counted from the start of the transport
+  next_tick_ix: u64,
+}
+
+impl MidiClockOutput {
+  pub const fn new() -> Self {
+    MidiClockOutput {
+      enabled: false,
+      send_mmc: false,
+      is_playing: false,
+      needs_continue: false,
+      next_tick_ix: 0,
+    }
+  }
+
+  pub fn set_enabled(
+    &mut self,
+    enabled: bool,
+    send_mmc: bool,
+    time: f64,
+    mut emit: impl FnMut(&[u8], f64),
+  ) {
+    if self.enabled && !enabled && self.is_playing {
+      // Receivers would otherwise keep waiting for the next tick
+      self.emit_stop(time, &mut emit);
+    }
+    self.needs_continue = enabled && !self.enabled && self.is_playing;
+    self.enabled = enabled;
+    self.send_mmc = send_mmc;
+  }
+
+  fn emit_stop(&self, time: f64, emit: &mut impl FnMut(&[u8], f64)) {
+    emit(&[STOP], time);
+    if self.send_mmc {
+      emit(&MMC_STOP, time);
+    }
+  }
+
+  /// Called when the transport is started from the beginning at `time`
+  pub fn start(&mut self, time: f64, mut emit: impl FnMut(&[u8], f64)) {
+    self.is_playing = true;
+    self.needs_continue = false;
+    self.next_tick_ix = 0;
+    if !self.enabled {
+      return;
+    }
+
+    emit(&[START], time);
+    if self.send_mmc {
+      emit(&MMC_PLAY, time);
+    }
+  }
+
+  pub fn stop(&mut self, time: f64, mut emit: impl FnMut(&[u8], f64)) {
+    if self.enabled && self.is_playing {
+      self.emit_stop(time, &mut emit);
+    }
+    self.is_playing = false;
+    self.needs_continue = false;
+  }
+
+  /// Emits all clock ticks that fall between the last call and `LOOKAHEAD_SECONDS` after
+  /// `cur_time`.  Tick times are extrapolated from the current position at the current tempo.
+  pub fn run(&mut self, cur_time: f64, cur_beats: f64, bpm: f64, mut emit: impl FnMut(&[u8], f64)) {
+    if !self.enabled || !self.is_playing || bpm <= 0. {
+      return;
+    }
+    let seconds_per_beat = 60. / bpm;
+
+    if self.needs_continue {
+      self.needs_continue = false;
+      // Song position can only point at sixteenth notes, so playback picks up at the next one
+      let song_position = ((cur_beats * 4.).ceil().max(0.) as u64).min(MAX_SONG_POSITION);
+      emit(
+        &[
+          SONG_POSITION_POINTER,
+          (song_position & 0x7f) as u8,
+          (song_position >> 7) as u8,
+        ],
+        cur_time,
+      );
+      emit(&[CONTINUE], cur_time);
+      if self.send_mmc {
+        emit(&MMC_PLAY, cur_time);
+      }
+      self.next_tick_ix = song_position * TICKS_PER_SONG_POSITION;
+    }
+
+    let end_beat = cur_beats + LOOKAHEAD_SECONDS / seconds_per_beat;
+    loop {
+      let tick_beat = self.next_tick_ix as f64 / MIDI_CLOCK_TICKS_PER_BEAT as f64;
+      if tick_beat > end_beat {
+        break;
+      }
+
+      let tick_time = cur_time + (tick_beat - cur_beats) * seconds_per_beat;
+      emit(&[TIMING_CLOCK], tick_time.max(cur_time));
+      self.next_tick_ix += 1;
+    }
+  }
+}
+
+#[cfg(test)]
+fn collect_messages(f: impl FnOnce(&mut dyn FnMut(&[u8], f64))) -> Vec<(Vec<u8>, f64)> {
+  let mut messages = Vec::new();
+  f(&mut |bytes: &[u8], time: f64| messages.push((bytes.to_vec(), time)));
+  messages
+}
+
+#[test]
+fn clock_follows_transport() {
+  let mut clock = MidiClockOutput::new();
+  clock.set_enabled(true, false, 0., |_, _| {});
+
+  let messages = collect_messages(|emit| clock.start(1., emit));
+  assert_eq!(messages, vec![(vec![START], 1.)]);
+
+  // At 120 BPM, ticks are 1/48 seconds apart and the lookahead covers the first three
+  let messages = collect_messages(|emit| clock.run(1., 0., 120., emit));
+  assert_eq!(messages.len(), 3);
+  for (tick_ix, (bytes, time)) in messages.iter().enumerate() {
+    assert_eq!(bytes, &[TIMING_CLOCK]);
+    assert!((time - (1. + tick_ix as f64 / 48.)).abs() < 1e-9);
+  }
+  // Ticks that were already sent aren't sent again
+  let messages = collect_messages(|emit| clock.run(1.01, 0.02, 120., emit));
+  assert_eq!(messages.len(), 0);
+
+  let messages = collect_messages(|emit| clock.stop(2., emit));
+  assert_eq!(messages, vec![(vec![STOP], 2.)]);
+  let messages = collect_messages(|emit| clock.run(2.1, 0.2, 120., emit));
+  assert!(messages.is_empty());
+}
+
+#[test]
+fn enabling_while_playing_continues_from_song_position() {
+  let mut clock = MidiClockOutput::new();
+  clock.start(0., |_, _| {});
+  clock.set_enabled(true, true, 10., |_, _| {});
+
+  // 20.1 beats in; the next sixteenth note is at beat 20.25, which is song position 81
+  let messages = collect_messages(|emit| clock.run(10., 20.1, 120., emit));
+  assert_eq!(messages[0], (vec![SONG_POSITION_POINTER, 81, 0], 10.));
+  assert_eq!(messages[1], (vec![CONTINUE], 10.));
+  assert_eq!(messages[2], (MMC_PLAY.to_vec(), 10.));
+  // The lookahead doesn't reach the next sixteenth note yet
+  assert_eq!(messages.len(), 3);
+
+  let messages = collect_messages(|emit| clock.run(10.05, 20.2, 120., emit));
+  let (bytes, time) = &messages[0];
+  assert_eq!(bytes, &[TIMING_CLOCK]);
+  assert!((time - 10.075).abs() < 1e-9);
+}
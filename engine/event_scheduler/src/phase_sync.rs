@@ -0,0 +1,150 @@
+//! Keeps the global beat counter in phase with a shared session timeline, such as one from Ableton
+//! Link bridged in by a native helper or peers connected over WebRTC.
+//!
+//! The host reports the session's beat and tempo at points in time.  Those reports arrive with
+//! network and scheduling jitter, so rather than being used directly they're blended into a
+//! smoothed estimate of the session timeline.  The local transport is then steered towards that
+//! timeline by running it slightly faster or slower than the session tempo until the phase error is
+//! gone, which avoids jumps in the beat counter that would skip or repeat scheduled events.
+//!
+//! As with Link, only the phase within a quantum (a bar, typically) is synced.  Local beat numbers
+//! can differ from the session's by any multiple of the quantum.
+
+/// Portion of each report's deviation from the smoothed timeline that's applied to it
+const JITTER_SMOOTHING: f64 = 0.2;
+/// Reports that deviate from the smoothed timeline by more than this are assumed to be real changes
+/// to the session, like another peer restarting, rather than jitter, and are adopted as-is
+const RESYNC_THRESHOLD_BEATS: f64 = 0.25;
+/// Phase errors are corrected over roughly this long
+const CORRECTION_SECONDS: f64 = 1.;
+/// Limits how far the local tempo is pushed away from the session tempo while correcting, as a
+/// fraction of the session tempo, so that large errors don't audibly warp tempo-synced effects
+const MAX_CORRECTION: f64 = 0.05;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Timeline {
+  bpm: f64,
+  origin_time: f64,
+  origin_beat: f64,
+}
+
+impl Timeline {
+  fn beat_at(&self, time: f64) -> f64 {
+    self.origin_beat + (time - self.origin_time) * self.bpm / 60.
+  }
+}
+
+/// Returns the difference between two beat positions wrapped to within half a quantum
+fn wrap_phase(beat_diff: f64, quantum: f64) -> f64 {
+  beat_diff - quantum * (beat_diff / quantum).round()
+}
+
+pub struct PhaseSync {
+  quantum: f64,
+  timeline: Option<Timeline>,
+}
+
+impl PhaseSync {
+  pub const fn new() -> Self {
+    PhaseSync {
+      quantum: 4.,
+      timeline: None,
+    }
+  }
+
+  pub fn set_quantum(&mut self, quantum: f64) {
+    if quantum > 0. {
+      self.quantum = quantum;
+    }
+  }
+
+  /// The smoothed session tempo, or `None` if nothing has been reported yet
+  pub fn session_bpm(&self) -> Option<f64> { self.timeline.map(|timeline| timeline.bpm) }
+
+  /// Records a report from the host that the session was at `beat` at `time`, running at `bpm`
+  pub fn add_report(&mut self, time: f64, beat: f64, bpm: f64) {
+    if bpm <= 0. {
+      return;
+    }
+
+    let reported = Timeline {
+      bpm,
+      origin_time: time,
+      origin_beat: beat,
+    };
+    self.timeline = Some(match self.timeline {
+      Some(timeline) if timeline.bpm == bpm => {
+        let predicted_beat = timeline.beat_at(time);
+        let deviation = beat - predicted_beat;
+        if deviation.abs() > RESYNC_THRESHOLD_BEATS {
+          reported
+        } else {
+          Timeline {
+            origin_beat: predicted_beat + deviation * JITTER_SMOOTHING,
+            ..reported
+          }
+        }
+      },
+      // Tempo changes are exact rather than jittery
+      _ => reported,
+    });
+  }
+
+  /// Returns the tempo that the local transport should run at to converge on the session's phase,
+  /// given that it's at `local_beat` at `time`.  Returns `None` if nothing has been reported yet.
+  pub fn steer(&self, time: f64, local_beat: f64) -> Option<f64> {
+    let timeline = self.timeline?;
+    let phase_error = wrap_phase(timeline.beat_at(time) - local_beat, self.quantum);
+    let max_correction_bpm = timeline.bpm * MAX_CORRECTION;
+    let correction_bpm =
+      (phase_error * 60. / CORRECTION_SECONDS).clamp(-max_correction_bpm, max_correction_bpm);
+    Some(timeline.bpm + correction_bpm)
+  }
+
+  pub fn reset(&mut self) { self.timeline = None; }
+}
+
+impl Default for PhaseSync {
+  fn default() -> Self { Self::new() }
+}
+
+#[test]
+fn jittery_reports_are_smoothed() {
+  let mut sync = PhaseSync::new();
+  for report_ix in 0..100 {
+    let time = report_ix as f64 * 0.1;
+    // The session is at 120 BPM, reported up to 20ms early or late
+    let jitter = if report_ix % 2 == 0 { 0.04 } else { -0.04 };
+    sync.add_report(time, time * 2. + jitter, 120.);
+  }
+  let timeline = sync.timeline.unwrap();
+  assert!((timeline.beat_at(20.) - 40.).abs() < 0.01, "{timeline:?}");
+
+  // A big jump is adopted right away
+  sync.add_report(10., 4., 120.);
+  assert!((sync.timeline.unwrap().beat_at(10.) - 4.).abs() < 1e-9);
+}
+
+#[test]
+fn steering_converges_on_session_phase() {
+  let mut sync = PhaseSync::new();
+  sync.add_report(0., 0., 120.);
+
+  // The local transport starts a beat behind the session and moves forward in 128-sample steps
+  let step_seconds = 128. / 44_100.;
+  let mut local_beat = -1.;
+  let mut time = 0.;
+  let initial_bpm = sync.steer(time, local_beat).unwrap();
+  assert!(initial_bpm > 120. && initial_bpm <= 126., "{initial_bpm}");
+  while time < 30. {
+    let bpm = sync.steer(time, local_beat).unwrap();
+    local_beat += bpm / 60. * step_seconds;
+    time += step_seconds;
+  }
+  let phase_error = wrap_phase(time * 2. - local_beat, 4.);
+  assert!(phase_error.abs() < 0.001, "{phase_error}");
+
+  // Being a whole quantum off is already in phase
+  let bpm = sync.steer(time, local_beat - 4.).unwrap();
+  assert!((bpm - 120.).abs() < 0.1, "{bpm}");
+}
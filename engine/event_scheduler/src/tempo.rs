@@ -0,0 +1,172 @@
+//! Tempo estimation from outside sources: beats tapped in by the user and external clocks such as
+//! MIDI clock.  Both take the times of incoming events in seconds on any clock that doesn't jump,
+//! so the main thread can pass whatever timestamps it has for them.
+
+/// Taps further apart than this start a new tap sequence
+const TAP_TIMEOUT_SECONDS: f64 = 2.;
+/// Only the intervals between this many of the most recent taps are averaged
+const MAX_TAP_COUNT: usize = 8;
+/// Intervals that differ from the median interval by more than this fraction of it are treated as
+/// mistimed and left out of the average
+const OUTLIER_TOLERANCE: f64 = 0.2;
+/// MIDI clock sends 24 ticks per quarter note
+pub const MIDI_CLOCK_TICKS_PER_BEAT: usize = 24;
+/// An external clock that hasn't ticked for this long is assumed to have stopped
+const CLOCK_TIMEOUT_SECONDS: f64 = 1.;
+/// Number of beats' worth of tick intervals averaged to estimate an external clock's tempo.  Clock
+/// ticks from hardware and over the network are jittery, so a single beat isn't enough.
+const CLOCK_WINDOW_BEATS: usize = 2;
+
+/// Returns the mean of `intervals` after dropping those that are too far from their median
+fn mean_interval_without_outliers(intervals: &[f64]) -> Option<f64> {
+  if intervals.is_empty() {
+    return None;
+  }
+
+  let mut sorted = intervals.to_vec();
+  sorted.sort_by(|a, b| a.total_cmp(b));
+  let median = sorted[sorted.len() / 2];
+
+  let (sum, count) = intervals
+    .iter()
+    .filter(|&&interval| (interval - median).abs() <= median * OUTLIER_TOLERANCE)
+    .fold((0., 0), |(sum, count), interval| {
+      (sum + interval, count + 1)
+    });
+  if count == 0 || sum <= 0. {
+    return None;
+  }
+  Some(sum / count as f64)
+}
+
+#[derive(Default)]
+pub struct TapTempo {
+  tap_times: Vec<f64>,
+}
+
+impl TapTempo {
+  pub const fn new() -> Self {
+    TapTempo {
+      tap_times: Vec::new(),
+    }
+  }
+
+  /// Records a tap at `time` and returns the tempo in BPM of the taps so far, or `None` if this tap
+  /// started a new sequence.
+  pub fn tap(&mut self, time: f64) -> Option<f64> {
+    if let Some(&last_tap_time) = self.tap_times.last() {
+      if time <= last_tap_time || time - last_tap_time > TAP_TIMEOUT_SECONDS {
+        self.tap_times.clear();
+      }
+    }
+    if self.tap_times.len() == MAX_TAP_COUNT {
+      self.tap_times.remove(0);
+    }
+    self.tap_times.push(time);
+
+    let intervals: Vec<f64> = self.tap_times.windows(2).map(|w| w[1] - w[0]).collect();
+    mean_interval_without_outliers(&intervals).map(|interval| 60. / interval)
+  }
+
+  pub fn reset(&mut self) { self.tap_times.clear(); }
+}
+
+/// Follows the tempo of a clock that sends a fixed number of ticks per beat
+pub struct ClockFollower {
+  ticks_per_beat: usize,
+  last_tick_time: Option<f64>,
+  /// The most recent intervals between ticks, oldest first
+  intervals: Vec<f64>,
+  ticks_since_estimate: usize,
+}
+
+impl ClockFollower {
+  pub const fn new(ticks_per_beat: usize) -> Self {
+    ClockFollower {
+      ticks_per_beat,
+      last_tick_time: None,
+      intervals: Vec::new(),
+      ticks_since_estimate: 0,
+    }
+  }
+
+  pub fn set_ticks_per_beat(&mut self, ticks_per_beat: usize) {
+    self.ticks_per_beat = ticks_per_beat.max(1);
+    self.reset();
+  }
+
+  /// Records a tick at `time`.  Once at least a beat's worth of ticks has been received, returns
+  /// the estimated tempo in BPM once per beat so that consumers aren't flooded with updates.
+  pub fn tick(&mut self, time: f64) -> Option<f64> {
+    let last_tick_time = self.last_tick_time.replace(time);
+    let interval = match last_tick_time {
+      Some(last_tick_time) if time > last_tick_time => time - last_tick_time,
+      _ => return None,
+    };
+    if interval > CLOCK_TIMEOUT_SECONDS {
+      self.intervals.clear();
+      self.ticks_since_estimate = 0;
+      return None;
+    }
+
+    if self.intervals.len() == self.ticks_per_beat * CLOCK_WINDOW_BEATS {
+      self.intervals.remove(0);
+    }
+    self.intervals.push(interval);
+    self.ticks_since_estimate += 1;
+    if self.intervals.len() < self.ticks_per_beat || self.ticks_since_estimate < self.ticks_per_beat
+    {
+      return None;
+    }
+
+    self.ticks_since_estimate = 0;
+    mean_interval_without_outliers(&self.intervals)
+      .map(|interval| 60. / (interval * self.ticks_per_beat as f64))
+  }
+
+  pub fn reset(&mut self) {
+    self.last_tick_time = None;
+    self.intervals.clear();
+    self.ticks_since_estimate = 0;
+  }
+}
+
+#[test]
+fn tap_tempo_rejects_mistimed_taps() {
+  let mut tap_tempo = TapTempo::new();
+  assert_eq!(tap_tempo.tap(10.), None);
+  assert_eq!(tap_tempo.tap(10.5), Some(120.));
+
+  // One late tap followed by one early one; both intervals are thrown out
+  tap_tempo.tap(11.2);
+  let bpm = tap_tempo.tap(11.5).unwrap();
+  tap_tempo.tap(12.);
+  let bpm_after = tap_tempo.tap(12.5).unwrap();
+  assert!((bpm - 120.).abs() < 1e-6, "{bpm}");
+  assert!((bpm_after - 120.).abs() < 1e-6, "{bpm_after}");
+
+  // A long pause starts over
+  assert_eq!(tap_tempo.tap(20.), None);
+  assert_eq!(tap_tempo.tap(21.), Some(60.));
+}
+
+#[test]
+fn clock_follower_estimates_once_per_beat() {
+  let mut clock = ClockFollower::new(MIDI_CLOCK_TICKS_PER_BEAT);
+  let tick_interval = 60. / (140. * MIDI_CLOCK_TICKS_PER_BEAT as f64);
+  let estimates: Vec<f64> = (0..MIDI_CLOCK_TICKS_PER_BEAT * 4 + 1)
+    .filter_map(|tick_ix| {
+      // Alternate ticks are a bit early or late like they would be from real hardware
+      let jitter = if tick_ix % 2 == 0 { 0.0005 } else { -0.0005 };
+      clock.tick(tick_ix as f64 * tick_interval + jitter)
+    })
+    .collect();
+
+  assert_eq!(estimates.len(), 4);
+  for bpm in estimates {
+    assert!((bpm - 140.).abs() < 0.5, "{bpm}");
+  }
+
+  // The clock stopping and starting again doesn't produce a bogus estimate from the gap
+  assert_eq!(clock.tick(100.), None);
+}
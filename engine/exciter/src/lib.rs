@@ -0,0 +1,391 @@
+//! Multiband harmonic exciter.  The input is split into low, mid, and high bands by the same band
+//! splitter used by the multiband compressor, and each band is run through its own soft saturator
+//! to generate harmonics.  Only the harmonics (the difference between the saturated and clean
+//! band) are mixed back into the untouched dry signal, so the dry path doesn't pick up the phase
+//! shift of the crossovers and quiet material passes through unchanged.  Meant to sit next to the
+//! multiband compressor in mastering chains.
+//!
+//! In linear-phase mode the bands are split with `LinearPhaseBandSplitter` instead, and the dry
+//! signal is rebuilt from the bands so that it lines up with the harmonics.  This adds
+//! `LINEAR_PHASE_LATENCY_SAMPLES` of latency, which is reported to the host.
+//!
+//! Exposed through the standard `module_api` entry points.
+
+use common::ffi::{self, FfiResult};
+use dsp::{
+  band_splitter::{BandSplitter, LinearPhaseBandSplitter, LINEAR_PHASE_LATENCY_SAMPLES},
+  db_to_gain,
+  filters::dc_blocker::DCBlocker,
+  MAX_FRAME_SIZE,
+};
+use module_api::DspModule;
+
+const CHANNEL_COUNT: usize = 2;
+/// Low, mid, and high; the crossovers are fixed by `BandSplitter`
+pub const BAND_COUNT: usize = 3;
+
+/// Param buffer layout: the mix and output gain come first so that they can be automated by the
+/// host, followed by the per-band params interleaved as drive, amount, asymmetry.  The linear-phase
+/// flag comes last.
+const PARAM_MIX_IX: usize = 0;
+const PARAM_OUTPUT_GAIN_DB_IX: usize = 1;
+const BAND_PARAMS_OFFSET: usize = 2;
+pub const BAND_PARAM_COUNT: usize = 3;
+/// 0 or 1
+const PARAM_LINEAR_PHASE_IX: usize = BAND_PARAMS_OFFSET + BAND_COUNT * BAND_PARAM_COUNT;
+const PARAM_COUNT: usize = PARAM_LINEAR_PHASE_IX + 1;
+const BAND_PARAM_DRIVE_DB_IX: usize = 0;
+const BAND_PARAM_AMOUNT_IX: usize = 1;
+const BAND_PARAM_ASYMMETRY_IX: usize = 2;
+
+const MIN_OUTPUT_GAIN_DB: f32 = -24.;
+const MAX_OUTPUT_GAIN_DB: f32 = 24.;
+const MAX_DRIVE_DB: f32 = 36.;
+
+/// `tanh` soft saturator normalized to unity gain for small signals, so that subtracting the clean
+/// signal from its output leaves only what the curve added.  Asymmetry biases the operating point
+/// along the curve, which bends the positive and negative halves of the waveform differently and
+/// adds even harmonics on top of the odd ones.
+#[derive(Clone, Copy)]
+struct Saturator {
+  drive: f32,
+  bias: f32,
+  /// Output of the curve at the operating point, subtracted so that silence stays silent
+  bias_output: f32,
+  /// Inverse of the slope of the curve at the operating point
+  normalization: f32,
+}
+
+impl Saturator {
+  fn new(drive_db: f32, asymmetry: f32) -> Self {
+    let drive = db_to_gain(drive_db);
+    let bias_output = asymmetry.tanh();
+    Saturator {
+      drive,
+      bias: asymmetry,
+      bias_output,
+      normalization: 1. / (drive * (1. - bias_output * bias_output)),
+    }
+  }
+
+  #[inline]
+  fn apply(&self, sample: f32) -> f32 {
+    ((self.drive * sample + self.bias).tanh() - self.bias_output) * self.normalization
+  }
+}
+
+pub struct ExciterCtx {
+  /// Planar stereo: left channel at 0, right channel at `MAX_FRAME_SIZE`.  Processed in place.
+  pub io_buffer: [f32; MAX_FRAME_SIZE * CHANNEL_COUNT],
+  /// Written by JS; see `PARAM_MIX_IX` for the layout
+  pub params: [f32; PARAM_COUNT],
+  splitters: [BandSplitter; CHANNEL_COUNT],
+  /// Allocated the first time linear-phase mode is enabled since the FFT buffers are large
+  linear_phase_splitters: Option<Box<[LinearPhaseBandSplitter; CHANNEL_COUNT]>>,
+  /// Whether the last frame was processed in linear-phase mode
+  linear_phase_active: bool,
+  /// Scratch space for the output of the band splitter, one channel at a time
+  band_bufs: [[f32; MAX_FRAME_SIZE]; BAND_COUNT],
+  /// Asymmetric saturation leaves DC in the generated harmonics
+  dc_blockers: [DCBlocker; CHANNEL_COUNT],
+  /// Peak level of the harmonics generated by each band over the last frame, for metering
+  pub sab: [f32; BAND_COUNT],
+}
+
+impl Default for ExciterCtx {
+  fn default() -> Self {
+    let mut params = [0.; PARAM_COUNT];
+    params[PARAM_MIX_IX] = 0.5;
+    params[PARAM_OUTPUT_GAIN_DB_IX] = 0.;
+    for params in
+      params[BAND_PARAMS_OFFSET..PARAM_LINEAR_PHASE_IX].chunks_exact_mut(BAND_PARAM_COUNT)
+    {
+      params[BAND_PARAM_DRIVE_DB_IX] = 12.;
+      params[BAND_PARAM_AMOUNT_IX] = 1.;
+      params[BAND_PARAM_ASYMMETRY_IX] = 0.;
+    }
+
+    ExciterCtx {
+      io_buffer: [0.; MAX_FRAME_SIZE * CHANNEL_COUNT],
+      params,
+      splitters: [BandSplitter::new(), BandSplitter::new()],
+      linear_phase_splitters: None,
+      linear_phase_active: false,
+      band_bufs: [[0.; MAX_FRAME_SIZE]; BAND_COUNT],
+      dc_blockers: [DCBlocker::default(), DCBlocker::default()],
+      sab: [0.; BAND_COUNT],
+    }
+  }
+}
+
+impl ExciterCtx {
+  /// Clamps the params into their valid ranges in place.  NaNs are rejected.
+  fn validate_params(&mut self) -> FfiResult {
+    self.params[PARAM_MIX_IX] = ffi::clamp_param("mix", self.params[PARAM_MIX_IX], 0., 1.)?;
+    self.params[PARAM_OUTPUT_GAIN_DB_IX] = ffi::clamp_param(
+      "output_gain_db",
+      self.params[PARAM_OUTPUT_GAIN_DB_IX],
+      MIN_OUTPUT_GAIN_DB,
+      MAX_OUTPUT_GAIN_DB,
+    )?;
+    for params in
+      self.params[BAND_PARAMS_OFFSET..PARAM_LINEAR_PHASE_IX].chunks_exact_mut(BAND_PARAM_COUNT)
+    {
+      params[BAND_PARAM_DRIVE_DB_IX] =
+        ffi::clamp_param("drive_db", params[BAND_PARAM_DRIVE_DB_IX], 0., MAX_DRIVE_DB)?;
+      params[BAND_PARAM_AMOUNT_IX] =
+        ffi::clamp_param("amount", params[BAND_PARAM_AMOUNT_IX], 0., 1.)?;
+      params[BAND_PARAM_ASYMMETRY_IX] =
+        ffi::clamp_param("asymmetry", params[BAND_PARAM_ASYMMETRY_IX], 0., 1.)?;
+    }
+    self.params[PARAM_LINEAR_PHASE_IX] =
+      ffi::clamp_param("linear_phase", self.params[PARAM_LINEAR_PHASE_IX], 0., 1.)?;
+    Ok(())
+  }
+
+  /// Builds the saturator and harmonics amount of each band from the band params
+  fn build_bands(&self) -> [(Saturator, f32); BAND_COUNT] {
+    let mut bands = [(Saturator::new(0., 0.), 0.); BAND_COUNT];
+    for (band, params) in bands
+      .iter_mut()
+      .zip(self.params[BAND_PARAMS_OFFSET..PARAM_LINEAR_PHASE_IX].chunks_exact(BAND_PARAM_COUNT))
+    {
+      *band = (
+        Saturator::new(
+          params[BAND_PARAM_DRIVE_DB_IX],
+          params[BAND_PARAM_ASYMMETRY_IX],
+        ),
+        params[BAND_PARAM_AMOUNT_IX],
+      );
+    }
+    bands
+  }
+
+  fn linear_phase(&self) -> bool { self.params[PARAM_LINEAR_PHASE_IX] >= 0.5 }
+
+  pub fn apply(&mut self, frame_size: usize) {
+    let mix = self.params[PARAM_MIX_IX];
+    let output_gain = db_to_gain(self.params[PARAM_OUTPUT_GAIN_DB_IX]);
+    let bands = self.build_bands();
+
+    let linear_phase = self.linear_phase();
+    if linear_phase && !self.linear_phase_active {
+      // Don't play back anything left over from the last time linear-phase mode was enabled
+      match &mut self.linear_phase_splitters {
+        Some(splitters) =>
+          for splitter in splitters.iter_mut() {
+            splitter.reset();
+          },
+        None =>
+          self.linear_phase_splitters = Some(Box::new([
+            LinearPhaseBandSplitter::new(),
+            LinearPhaseBandSplitter::new(),
+          ])),
+      }
+    }
+    self.linear_phase_active = linear_phase;
+
+    self.sab.fill(0.);
+    for (channel_ix, channel) in self.io_buffer.chunks_exact_mut(MAX_FRAME_SIZE).enumerate() {
+      let channel = &mut channel[..frame_size];
+      let [low, mid, high] = &mut self.band_bufs;
+      let (low, mid, high) = (
+        &mut low[..frame_size],
+        &mut mid[..frame_size],
+        &mut high[..frame_size],
+      );
+      match &mut self.linear_phase_splitters {
+        Some(splitters) if linear_phase => {
+          splitters[channel_ix].apply(channel, low, mid, high);
+          // The bands sum back to the delayed input
+          for (sample_ix, sample) in channel.iter_mut().enumerate() {
+            *sample = low[sample_ix] + mid[sample_ix] + high[sample_ix];
+          }
+        },
+        _ => self.splitters[channel_ix].apply(channel, low, mid, high),
+      }
+
+      let dc_blocker = &mut self.dc_blockers[channel_ix];
+      for (sample_ix, sample) in channel.iter_mut().enumerate() {
+        let mut harmonics = 0.;
+        for ((band_buf, (saturator, amount)), peak) in self
+          .band_bufs
+          .iter()
+          .zip(bands.iter())
+          .zip(self.sab.iter_mut())
+        {
+          let band_sample = band_buf[sample_ix];
+          let band_harmonics = amount * (saturator.apply(band_sample) - band_sample);
+          *peak = peak.max(band_harmonics.abs());
+          harmonics += band_harmonics;
+        }
+
+        let harmonics = dc_blocker.apply(harmonics);
+        *sample = (*sample + mix * harmonics) * output_gain;
+      }
+    }
+  }
+}
+
+impl DspModule for ExciterCtx {
+  const AUTOMATABLE_PARAM_COUNT: usize = 2;
+  const CHANNEL_COUNT: usize = CHANNEL_COUNT;
+  const NAME: &'static str = "exciter";
+  const PARAM_COUNT: usize = PARAM_COUNT;
+  const SAB_LEN: usize = BAND_COUNT;
+  const VERSION: u32 = 2;
+
+  fn new() -> Self { Self::default() }
+
+  fn param_name(param_ix: usize) -> String {
+    match param_ix {
+      PARAM_MIX_IX => return "mix".to_owned(),
+      PARAM_OUTPUT_GAIN_DB_IX => return "output_gain_db".to_owned(),
+      PARAM_LINEAR_PHASE_IX => return "linear_phase".to_owned(),
+      _ => (),
+    }
+
+    let band_ix = (param_ix - BAND_PARAMS_OFFSET) / BAND_PARAM_COUNT;
+    let name = match (param_ix - BAND_PARAMS_OFFSET) % BAND_PARAM_COUNT {
+      BAND_PARAM_DRIVE_DB_IX => "drive_db",
+      BAND_PARAM_AMOUNT_IX => "amount",
+      _ => "asymmetry",
+    };
+    format!("band_{band_ix}_{name}")
+  }
+
+  fn io_buffer(&mut self) -> &mut [f32] { &mut self.io_buffer }
+
+  fn params(&mut self) -> &mut [f32] { &mut self.params }
+
+  fn sab(&mut self) -> &mut [f32] { &mut self.sab }
+
+  fn process(&mut self, frame_size: usize) -> FfiResult {
+    self.validate_params()?;
+    self.apply(frame_size);
+    Ok(())
+  }
+
+  fn reset(&mut self) {
+    for splitter in &mut self.splitters {
+      splitter.reset();
+    }
+    if let Some(splitters) = &mut self.linear_phase_splitters {
+      for splitter in splitters.iter_mut() {
+        splitter.reset();
+      }
+    }
+    for dc_blocker in &mut self.dc_blockers {
+      dc_blocker.reset();
+    }
+    self.sab.fill(0.);
+  }
+
+  fn latency_samples(&self) -> usize {
+    if self.linear_phase() {
+      LINEAR_PHASE_LATENCY_SAMPLES
+    } else {
+      0
+    }
+  }
+}
+
+module_api::export_module!(ExciterCtx);
+
+/// Renders a sine through the left channel, returning the input and output once the filters have
+/// settled
+#[cfg(test)]
+fn render_sine(ctx: &mut ExciterCtx, freq: f32, amplitude: f32) -> (Vec<f32>, Vec<f32>) {
+  let frame_size = 128;
+  let frame_count = 200;
+  let mut input = Vec::new();
+  let mut output = Vec::new();
+  for frame_ix in 0..frame_count {
+    let offset = frame_ix * frame_size;
+    for i in 0..frame_size {
+      let t = (offset + i) as f32 / dsp::sample_rate();
+      ctx.io_buffer[i] = amplitude * (std::f32::consts::TAU * freq * t).sin();
+    }
+    if frame_ix >= frame_count / 2 {
+      input.extend_from_slice(&ctx.io_buffer[..frame_size]);
+    }
+    ctx.apply(frame_size);
+    if frame_ix >= frame_count / 2 {
+      output.extend_from_slice(&ctx.io_buffer[..frame_size]);
+    }
+  }
+  (input, output)
+}
+
+/// Magnitude of the component of `samples` at `freq`, relative to a full-scale sine
+#[cfg(test)]
+fn magnitude_at(samples: &[f32], freq: f32) -> f32 {
+  let (mut re, mut im) = (0., 0.);
+  for (i, sample) in samples.iter().enumerate() {
+    let phase = std::f32::consts::TAU * freq * i as f32 / dsp::sample_rate();
+    re += sample * phase.cos();
+    im += sample * phase.sin();
+  }
+  2. * (re * re + im * im).sqrt() / samples.len() as f32
+}
+
+#[test]
+fn quiet_signals_pass_through_unchanged() {
+  let mut ctx = ExciterCtx::default();
+  ctx.params[PARAM_MIX_IX] = 1.;
+  let (input, output) = render_sine(&mut ctx, 1000., db_to_gain(-60.));
+  for (input, output) in input.iter().zip(output.iter()) {
+    assert!((input - output).abs() < 1e-6, "{input} -> {output}");
+  }
+}
+
+#[test]
+fn saturation_adds_harmonics() {
+  // 1 kHz lands in the mid band; the analysis window holds a whole number of periods of it
+  let (freq, amplitude) = (1000., db_to_gain(-6.));
+  let mut ctx = ExciterCtx::default();
+  ctx.params[PARAM_MIX_IX] = 1.;
+  let (_, output) = render_sine(&mut ctx, freq, amplitude);
+  let output = &output[..4410];
+  assert!(magnitude_at(output, freq * 3.) > db_to_gain(-40.));
+  assert!(magnitude_at(output, freq * 2.) < db_to_gain(-80.));
+
+  // Asymmetry adds even harmonics
+  let mut ctx = ExciterCtx::default();
+  ctx.params[PARAM_MIX_IX] = 1.;
+  for params in
+    ctx.params[BAND_PARAMS_OFFSET..PARAM_LINEAR_PHASE_IX].chunks_exact_mut(BAND_PARAM_COUNT)
+  {
+    params[BAND_PARAM_ASYMMETRY_IX] = 0.5;
+  }
+  let (_, output) = render_sine(&mut ctx, freq, amplitude);
+  assert!(magnitude_at(&output[..4410], freq * 2.) > db_to_gain(-40.));
+
+  // Nothing is added with the mix all the way down
+  let mut ctx = ExciterCtx::default();
+  ctx.params[PARAM_MIX_IX] = 0.;
+  let (input, output) = render_sine(&mut ctx, freq, amplitude);
+  assert_eq!(input, output);
+}
+
+#[test]
+fn linear_phase_mode_delays_dry_signal_by_reported_latency() {
+  let mut ctx = ExciterCtx::default();
+  ctx.params[PARAM_MIX_IX] = 0.;
+  ctx.params[PARAM_LINEAR_PHASE_IX] = 1.;
+  assert_eq!(ctx.latency_samples(), LINEAR_PHASE_LATENCY_SAMPLES);
+
+  let frame_size = 128;
+  let input: Vec<f32> = (0..LINEAR_PHASE_LATENCY_SAMPLES * 2)
+    .map(|i| (i as f32 * 0.05).sin() * 0.5)
+    .collect();
+  let mut output = Vec::new();
+  for frame in input.chunks(frame_size) {
+    ctx.io_buffer[..frame.len()].copy_from_slice(frame);
+    ctx.apply(frame.len());
+    output.extend_from_slice(&ctx.io_buffer[..frame.len()]);
+  }
+  for i in LINEAR_PHASE_LATENCY_SAMPLES..input.len() {
+    assert!((output[i] - input[i - LINEAR_PHASE_LATENCY_SAMPLES]).abs() < 1e-4);
+  }
+}
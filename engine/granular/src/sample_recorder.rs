@@ -1,4 +1,4 @@
-use std::io::Cursor;
+use common::ffi::{self, ErrorCode};
 
 #[derive(Default)]
 pub struct SampleRcorderContext {
@@ -38,19 +38,12 @@ pub unsafe extern "C" fn sample_recorder_get_samples_ptr(
 }
 
 fn encode_to_wav(samples: &[f32]) -> Vec<u8> {
-  let spec = hound::WavSpec {
-    channels: 1,
+  let spec = wav::WavSpec {
+    channel_count: 1,
     sample_rate: 44100,
-    bits_per_sample: 32,
-    sample_format: hound::SampleFormat::Float,
+    format: wav::SampleFormat::Float32,
   };
-  let mut encoded_buf = Vec::new();
-  let mut writer = hound::WavWriter::new(Cursor::new(&mut encoded_buf), spec).unwrap();
-  for sample in samples {
-    writer.write_sample(*sample).unwrap();
-  }
-  writer.finalize().unwrap();
-  encoded_buf
+  wav::encode(&spec, samples).unwrap()
 }
 
 /// Encodes the sample into the specified format and writes it into a buffer.  Returns the length of
@@ -81,6 +74,14 @@ pub unsafe extern "C" fn sample_recorder_get_encoded_output_ptr(
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn free_sample_recording_ctx(ctx: *mut SampleRcorderContext) {
-  drop(Box::from_raw(ctx))
+pub unsafe extern "C" fn free_sample_recording_ctx(ctx: *mut SampleRcorderContext) -> ErrorCode {
+  if ctx.is_null() {
+    return ffi::set_last_error(
+      ErrorCode::InvalidHandle,
+      "free_sample_recording_ctx: null context pointer",
+    );
+  }
+
+  drop(Box::from_raw(ctx));
+  ErrorCode::Ok
 }
@@ -1,9 +1,17 @@
-use dsp::{rms_level_detector::RMSLevelDetector, FRAME_SIZE};
+use common::ffi::{self, ErrorCode};
+use dsp::{
+  rms_level_detector::{RMSLevelDetector, MAX_LEVEL_DETECTION_WINDOW_SAMPLES},
+  FRAME_SIZE,
+};
 
+#[cfg(target_arch = "wasm32")]
 extern "C" {
   fn log_err(ptr: *const u8, len: usize);
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+use common::native::log_err;
+
 pub struct LevelDetectorCtx {
   pub io_buffer: [f32; FRAME_SIZE],
   pub detector: RMSLevelDetector<true>,
@@ -42,7 +50,23 @@ pub extern "C" fn level_detector_get_io_buf_ptr(ctx: *mut LevelDetectorCtx) -> *
 }
 
 #[no_mangle]
-pub extern "C" fn level_detector_process(ctx: *mut LevelDetectorCtx, window_size_samples: usize) {
-  let ctx = unsafe { &mut *ctx };
+pub extern "C" fn level_detector_process(
+  ctx: *mut LevelDetectorCtx,
+  window_size_samples: usize,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "level_detector_process") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range(
+    "window_size_samples",
+    window_size_samples,
+    1,
+    MAX_LEVEL_DETECTION_WINDOW_SAMPLES - 1,
+  ) {
+    return code;
+  }
+
   ctx.process(window_size_samples);
+  ErrorCode::Ok
 }
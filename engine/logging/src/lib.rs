@@ -0,0 +1,179 @@
+//! Levelled logging for Wasm modules that aren't built with `wasm-bindgen` (see `wbg_logging` for
+//! those).  Messages are passed to the host via the imported `log_raw(ptr, len, level)` function
+//! and are tagged with the module path of the call site, like `[wavetable::fm] ...`.
+//!
+//! Every call site is rate limited on its own so that something logging once per sample or frame
+//! can't flood the console and stall the audio thread.  The first `BURST_LEN` messages from a call
+//! site go through as-is; after that only every power-of-two'th one does, along with a count of
+//! how many were dropped in between.
+//!
+//! The max level defaults to `Warn` (`Debug` in debug builds) and can be changed at runtime by the
+//! host with the exported `set_log_level` function.
+
+use std::{
+  fmt::{self, Write},
+  sync::atomic::{AtomicU32, Ordering},
+};
+
+use common::ffi::{self, ErrorCode};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum LogLevel {
+  Error = 0,
+  Warn = 1,
+  Info = 2,
+  Debug = 3,
+}
+
+impl LogLevel {
+  pub fn from_u32(val: u32) -> Option<Self> {
+    match val {
+      0 => Some(LogLevel::Error),
+      1 => Some(LogLevel::Warn),
+      2 => Some(LogLevel::Info),
+      3 => Some(LogLevel::Debug),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+  fn log_raw(ptr: *const u8, len: usize, level: LogLevel);
+}
+
+#[cfg(target_arch = "wasm32")]
+fn emit(level: LogLevel, msg: &str) { unsafe { log_raw(msg.as_ptr(), msg.len(), level) } }
+
+/// There's no host to send messages to outside of Wasm (tests, mostly) so they go to stderr
+#[cfg(not(target_arch = "wasm32"))]
+fn emit(level: LogLevel, msg: &str) { eprintln!("{level:?}: {msg}") }
+
+const DEFAULT_MAX_LEVEL: LogLevel = if cfg!(debug_assertions) {
+  LogLevel::Debug
+} else {
+  LogLevel::Warn
+};
+
+static MAX_LEVEL: AtomicU32 = AtomicU32::new(DEFAULT_MAX_LEVEL as u32);
+
+/// Sets the most verbose level that will be logged.  Fails with `ErrorCode::ParamOutOfRange` and
+/// leaves the level unchanged if `level` isn't a valid `LogLevel`.
+#[no_mangle]
+pub extern "C" fn set_log_level(level: u32) -> ErrorCode {
+  match LogLevel::from_u32(level) {
+    Some(level) => {
+      MAX_LEVEL.store(level as u32, Ordering::Relaxed);
+      ErrorCode::Ok
+    },
+    None => ffi::set_last_error(
+      ErrorCode::ParamOutOfRange,
+      &format!("invalid log level {level}"),
+    ),
+  }
+}
+
+#[inline]
+pub fn enabled(level: LogLevel) -> bool { level as u32 <= MAX_LEVEL.load(Ordering::Relaxed) }
+
+/// Messages from a single call site that are always let through before rate limiting kicks in.
+/// Must be a power of two.
+const BURST_LEN: u32 = 8;
+
+/// Per-call-site message counter.  One of these is created as a `static` by each logging macro
+/// invocation.
+pub struct RateLimiter {
+  count: AtomicU32,
+}
+
+impl RateLimiter {
+  pub const fn new() -> Self {
+    RateLimiter {
+      count: AtomicU32::new(0),
+    }
+  }
+
+  /// Records a message, returning `None` if it should be dropped or `Some` with the number of
+  /// messages dropped since the last one that was let through.
+  pub fn check(&self) -> Option<u32> {
+    let count = self.count.fetch_add(1, Ordering::Relaxed).saturating_add(1);
+    if count <= BURST_LEN {
+      Some(0)
+    } else if count.is_power_of_two() {
+      Some(count / 2 - 1)
+    } else {
+      None
+    }
+  }
+}
+
+impl Default for RateLimiter {
+  fn default() -> Self { Self::new() }
+}
+
+/// Used by the logging macros; call those instead.
+pub fn log(level: LogLevel, tag: &str, args: fmt::Arguments, rate_limiter: &RateLimiter) {
+  if !enabled(level) {
+    return;
+  }
+  let Some(dropped_count) = rate_limiter.check() else {
+    return;
+  };
+
+  let mut msg = String::new();
+  let _ = write!(msg, "[{tag}] {args}");
+  if dropped_count > 0 {
+    let _ = write!(msg, " ({dropped_count} similar messages dropped)");
+  }
+  emit(level, &msg);
+}
+
+#[macro_export]
+macro_rules! log {
+  ($level:expr, $($arg:tt)+) => {{
+    static RATE_LIMITER: $crate::RateLimiter = $crate::RateLimiter::new();
+    $crate::log($level, module_path!(), format_args!($($arg)+), &RATE_LIMITER)
+  }};
+}
+
+#[macro_export]
+macro_rules! error {
+  ($($arg:tt)+) => { $crate::log!($crate::LogLevel::Error, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! warn {
+  ($($arg:tt)+) => { $crate::log!($crate::LogLevel::Warn, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! info {
+  ($($arg:tt)+) => { $crate::log!($crate::LogLevel::Info, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! debug {
+  ($($arg:tt)+) => { $crate::log!($crate::LogLevel::Debug, $($arg)+) };
+}
+
+#[test]
+fn rate_limiter_backs_off() {
+  let rate_limiter = RateLimiter::new();
+  let passed: Vec<(u32, u32)> = (1..=100)
+    .filter_map(|count| rate_limiter.check().map(|dropped| (count, dropped)))
+    .collect();
+  assert_eq!(passed, vec![
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (16, 7),
+    (32, 15),
+    (64, 31)
+  ]);
+}
@@ -1,3 +1,4 @@
+use common::ffi::{self, ErrorCode, FfiResult};
 use float_ord::FloatOrd;
 
 extern "C" {
@@ -130,21 +131,22 @@ impl TransitionAlgorithm {
     }
   }
 
-  pub fn from_parts(algorithm_type: usize, data: &[f32]) -> Self {
+  /// Returns `None` if `algorithm_type` is unknown or `data` doesn't hold its params
+  pub fn from_parts(algorithm_type: usize, data: &[f32]) -> Option<Self> {
     match algorithm_type {
-      0 => TransitionAlgorithm::Constant {
-        bank_ix: if data[0] < 0. {
-          None
-        } else {
-          Some(data[0].floor() as usize)
+      0 => Some(TransitionAlgorithm::Constant {
+        bank_ix: match data.first() {
+          Some(&bank_ix) if bank_ix < 0. => None,
+          Some(&bank_ix) => Some(bank_ix.floor() as usize),
+          None => return None,
         },
-      },
-      1 => TransitionAlgorithm::StaticPattern {
+      }),
+      1 => Some(TransitionAlgorithm::StaticPattern {
         cur_ix: 0,
         pattern: data.iter().map(|x| x.floor() as usize).collect(),
         reset_step_on_start: true,
-      },
-      _ => panic!("Unknown transition algorithm type"),
+      }),
+      _ => None,
     }
   }
 }
@@ -182,25 +184,57 @@ fn ctx(module_ix: usize) -> &'static mut LooperCtx {
   &mut ctxs()[module_ix]
 }
 
+fn check_initialized(export_name: &str) -> FfiResult {
+  if unsafe { CTXS.is_null() } {
+    return Err(ffi::set_last_error(
+      ErrorCode::NotInitialized,
+      &format!("looper `{export_name}` called before `looper_init`"),
+    ));
+  }
+  Ok(())
+}
+
+/// Like `ctx`, but fails if the looper hasn't been initialized yet
+fn checked_ctx(module_ix: usize, export_name: &str) -> FfiResult<&'static mut LooperCtx> {
+  check_initialized(export_name)?;
+  Ok(ctx(module_ix))
+}
+
+fn get_bank(ctx: &mut LooperCtx, bank_ix: usize) -> FfiResult<&mut LooperBank> {
+  let bank_count = ctx.banks.len();
+  ctx.banks.get_mut(bank_ix).ok_or_else(|| {
+    ffi::set_last_error(
+      ErrorCode::ParamOutOfRange,
+      &format!("bank_ix={bank_ix} but there are only {bank_count} banks"),
+    )
+  })
+}
+
+fn check_len_beats(len_beats: f32) -> FfiResult {
+  ffi::check_range("len_beats", len_beats, f32::MIN_POSITIVE, f32::MAX)
+}
+
 #[no_mangle]
-pub extern "C" fn looper_init() {
+pub extern "C" fn looper_init() -> ErrorCode {
   unsafe {
     CTXS = Box::into_raw(Box::new(Vec::new()));
   }
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub extern "C" fn looper_clear_bank(module_ix: usize, bank_ix: usize) {
-  let ctx = ctx(module_ix);
+pub extern "C" fn looper_clear_bank(module_ix: usize, bank_ix: usize) -> ErrorCode {
+  let ctx = match checked_ctx(module_ix, "looper_clear_bank") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
   while ctx.banks.len() <= bank_ix {
     ctx.banks.push(Default::default());
   }
-  while ctxs().len() <= module_ix {
-    ctxs().push(LooperCtx::default());
-  }
 
   let bank = &mut ctx.banks[bank_ix];
   bank.clear();
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -210,38 +244,57 @@ pub extern "C" fn looper_add_evt(
   note: u8,
   beat: f32,
   is_gate: bool,
-) {
-  let bank = &mut ctx(module_ix).banks[bank_ix];
-  bank.events.push(MIDIEvent {
-    note,
-    beat,
-    is_gate,
-  });
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = checked_ctx(module_ix, "looper_add_evt")?;
+    get_bank(ctx, bank_ix)?.events.push(MIDIEvent {
+      note,
+      beat,
+      is_gate,
+    });
+    Ok(())
+  })())
 }
 
 #[no_mangle]
-pub extern "C" fn looper_finalize_bank(module_ix: usize, bank_ix: usize, len_beats: f32) {
-  let bank = &mut ctx(module_ix).banks[bank_ix];
-  bank.len_beats = len_beats;
-  bank.events.sort_unstable();
+pub extern "C" fn looper_finalize_bank(
+  module_ix: usize,
+  bank_ix: usize,
+  len_beats: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = checked_ctx(module_ix, "looper_finalize_bank")?;
+    check_len_beats(len_beats)?;
+    let bank = get_bank(ctx, bank_ix)?;
+    bank.len_beats = len_beats;
+    bank.events.sort_unstable();
+    Ok(())
+  })())
 }
 
 /// Switch immediately to the next bank, skipping ahead in the new one to match the current beat
 #[no_mangle]
-pub extern "C" fn looper_activate_bank(module_ix: usize, bank_ix: isize, cur_beat: f32) {
+pub extern "C" fn looper_activate_bank(
+  module_ix: usize,
+  bank_ix: isize,
+  cur_beat: f32,
+) -> ErrorCode {
   let bank_ix = if bank_ix < 0 {
     None
   } else {
     Some(bank_ix as usize)
   };
 
-  let ctx = ctx(module_ix);
+  let ctx = match checked_ctx(module_ix, "looper_activate_bank") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
   ctx.active_bank_ix = bank_ix;
   ctx.transition_algorithm.handle_manual_transition(bank_ix);
 
   let bank_ix = match bank_ix {
     Some(bank_ix) => bank_ix,
-    None => return,
+    None => return ErrorCode::Ok,
   };
   while ctx.banks.len() <= bank_ix {
     ctx.banks.push(Default::default());
@@ -267,6 +320,8 @@ pub extern "C" fn looper_activate_bank(module_ix: usize, bank_ix: isize, cur_bea
       None
     };
   }
+
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -278,18 +333,27 @@ pub extern "C" fn looper_get_playing_bank_ix(module_ix: usize) -> isize {
 }
 
 #[no_mangle]
-pub extern "C" fn looper_set_next_bank_ix(module_ix: usize, new_bank_ix: isize) {
-  ctx(module_ix)
+pub extern "C" fn looper_set_next_bank_ix(module_ix: usize, new_bank_ix: isize) -> ErrorCode {
+  let ctx = match checked_ctx(module_ix, "looper_set_next_bank_ix") {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ctx
     .transition_algorithm
     .handle_manual_transition(if new_bank_ix < 0 {
       None
     } else {
       Some(new_bank_ix as usize)
     });
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub extern "C" fn looper_on_playback_start() {
+pub extern "C" fn looper_on_playback_start() -> ErrorCode {
+  if let Err(code) = check_initialized("looper_on_playback_start") {
+    return code;
+  }
+
   for (module_ix, ctx) in ctxs().iter_mut().enumerate() {
     let new_active_bank_ix = {
       let old_transition_algorithm = std::mem::take(&mut ctx.transition_algorithm);
@@ -307,10 +371,15 @@ pub extern "C" fn looper_on_playback_start() {
       });
     }
   }
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub extern "C" fn looper_on_playback_stop() {
+pub extern "C" fn looper_on_playback_stop() -> ErrorCode {
+  if let Err(code) = check_initialized("looper_on_playback_stop") {
+    return code;
+  }
+
   for (module_ix, ctx) in ctxs().iter_mut().enumerate() {
     for (note, is_playing) in ctx.playing_notes.iter_mut().enumerate() {
       if *is_playing {
@@ -319,6 +388,7 @@ pub extern "C" fn looper_on_playback_stop() {
       }
     }
   }
+  ErrorCode::Ok
 }
 
 fn process_looper_module(cur_beat: f32, module_ix: usize, ctx: &mut LooperCtx) -> f32 {
@@ -406,23 +476,39 @@ pub extern "C" fn looper_process(module_ix_for_which_to_report_phase: usize, cur
 }
 
 #[no_mangle]
-pub extern "C" fn looper_delete_module(module_ix: usize) {
+pub extern "C" fn looper_delete_module(module_ix: usize) -> ErrorCode {
+  if let Err(code) = check_initialized("looper_delete_module") {
+    return code;
+  }
   if ctxs().len() <= module_ix {
-    return;
+    return ffi::set_last_error(
+      ErrorCode::InvalidHandle,
+      &format!("No looper module with index {module_ix}"),
+    );
   }
+
   ctxs().remove(module_ix);
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub extern "C" fn looper_set_loop_len_beats(module_ix: usize, bank_ix: usize, len_beats: f32) {
-  let ctx = ctx(module_ix);
-
-  while ctx.banks.len() <= bank_ix {
-    ctx.banks.push(Default::default());
-  }
+pub extern "C" fn looper_set_loop_len_beats(
+  module_ix: usize,
+  bank_ix: usize,
+  len_beats: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = checked_ctx(module_ix, "looper_set_loop_len_beats")?;
+    check_len_beats(len_beats)?;
+
+    while ctx.banks.len() <= bank_ix {
+      ctx.banks.push(Default::default());
+    }
 
-  let bank = &mut ctx.banks[bank_ix];
-  bank.len_beats = len_beats;
+    let bank = &mut ctx.banks[bank_ix];
+    bank.len_beats = len_beats;
+    Ok(())
+  })())
 }
 
 static mut TRANSITION_ALGORITHM_BUFFER: *mut Vec<f32> = std::ptr::null_mut();
@@ -437,9 +523,10 @@ fn transition_algorithm_buffer() -> &'static mut Vec<f32> {
 }
 
 #[no_mangle]
-pub extern "C" fn looper_init_transition_algorithm_buffer(f32_count: usize) {
+pub extern "C" fn looper_init_transition_algorithm_buffer(f32_count: usize) -> ErrorCode {
   let buffer = transition_algorithm_buffer();
   buffer.resize(f32_count, 0.);
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -448,8 +535,21 @@ pub extern "C" fn looper_get_transition_algorithm_buffer_ptr() -> *const f32 {
 }
 
 #[no_mangle]
-pub extern "C" fn looper_set_transition_algorithm(module_ix: usize, algorithm_type: usize) {
-  let ctx = ctx(module_ix);
-  ctx.transition_algorithm =
-    TransitionAlgorithm::from_parts(algorithm_type, transition_algorithm_buffer());
+pub extern "C" fn looper_set_transition_algorithm(
+  module_ix: usize,
+  algorithm_type: usize,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = checked_ctx(module_ix, "looper_set_transition_algorithm")?;
+    ctx.transition_algorithm =
+      TransitionAlgorithm::from_parts(algorithm_type, transition_algorithm_buffer()).ok_or_else(
+        || {
+          ffi::set_last_error(
+            ErrorCode::ParamOutOfRange,
+            &format!("Unknown transition algorithm type {algorithm_type} or missing params for it"),
+          )
+        },
+      )?;
+    Ok(())
+  })())
 }
@@ -15,6 +15,7 @@ pub struct MsgHandlerContext {
   pub voice_manager:
     PolySynth<Box<dyn Fn(usize, usize, u8, Option<f32>)>, Box<dyn Fn(usize, usize, Option<f32>)>>,
   pub generic_control_handler: Option<Function>,
+  pub program_change_handler: Option<Function>,
 }
 
 #[wasm_bindgen]
@@ -24,6 +25,7 @@ pub fn create_msg_handler_context(
   pitch_bend: Option<Function>,
   mod_wheel: Option<Function>,
   generic_control_handler: Option<Function>,
+  program_change_handler: Option<Function>,
 ) -> usize {
   common::maybe_init(None);
   wbg_logging::maybe_init();
@@ -40,6 +42,7 @@ pub fn create_msg_handler_context(
       trigger_attack: Box::new(|_, _, _, _| panic!()),
     }),
     generic_control_handler,
+    program_change_handler,
   });
 
   // Replace the temporary synth cb pointers with real ones
@@ -164,6 +167,18 @@ pub fn handle_midi_evt(evt_bytes: Vec<u8>, ctx_ptr: *mut MsgHandlerContext) {
       } else {
         Ok(())
       },
+    Status::ProgramChange => match &ctx.program_change_handler {
+      Some(handler) => handler
+        .call1(
+          &JsValue::NULL,
+          &JsValue::from(*evt.data.get(1).unwrap_or(&0)),
+        )
+        .map(|_| ()),
+      None => {
+        trace!("Ignoring program change event since no program change handler in context");
+        Ok(())
+      },
+    },
     status =>
       if let Some(handler) = &ctx.generic_control_handler {
         handler
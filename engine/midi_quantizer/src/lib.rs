@@ -1,3 +1,5 @@
+use common::ffi::{self, ErrorCode};
+
 extern "C" {
   fn play_note(note: usize);
 
@@ -40,19 +42,24 @@ static mut STATE: MIDIQuantizerState = MIDIQuantizerState::default();
 fn state() -> &'static mut MIDIQuantizerState { unsafe { &mut STATE } }
 
 #[no_mangle]
-pub extern "C" fn set_octave_range(low: isize, high: isize) {
+pub extern "C" fn set_octave_range(low: isize, high: isize) -> ErrorCode {
   state().octave_range = [low.max(-2).min(6), high.max(-2).min(6)];
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub extern "C" fn set_note_active(note_ix: usize, is_active: bool) {
-  state().active_note_flags[note_ix] = is_active;
+pub extern "C" fn set_note_active(note_ix: usize, is_active: bool) -> ErrorCode {
+  ffi::status((|| {
+    ffi::check_range("note_ix", note_ix, 0, NOTES_PER_OCTAVE - 1)?;
+    state().active_note_flags[note_ix] = is_active;
+    Ok(())
+  })())
 }
 
 #[no_mangle]
-pub extern "C" fn set_is_running(is_running: bool) {
+pub extern "C" fn set_is_running(is_running: bool) -> ErrorCode {
   if state().is_running == is_running {
-    return;
+    return ErrorCode::Ok;
   }
   state().is_running = is_running;
 
@@ -63,10 +70,11 @@ pub extern "C" fn set_is_running(is_running: bool) {
     state().playing_note = None;
     state().last_sample = -200.;
   }
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub extern "C" fn finalize_state_update() {
+pub extern "C" fn finalize_state_update() -> ErrorCode {
   let state = state();
 
   state.active_note_ids.clear();
@@ -81,11 +89,16 @@ pub extern "C" fn finalize_state_update() {
         .push(note_offset_from_c0 as usize + note_ix);
     }
   }
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub extern "C" fn process(sample: f32) {
-  let state = state();
+pub extern "C" fn process(sample: f32) -> ErrorCode {
+  process_sample(state(), sample);
+  ErrorCode::Ok
+}
+
+fn process_sample(state: &mut MIDIQuantizerState, sample: f32) {
   if !state.is_running || state.active_note_ids.is_empty() {
     return;
   }
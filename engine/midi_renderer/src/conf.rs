@@ -1,4 +1,4 @@
 pub const MINIMAP_HEIGHT_PX: usize = 120;
 pub const NOTE_COLOR: &str = "#70ff03";
 pub const MIN_MIDI_NUMBER_RANGE: usize = 12;
-// pub const MEASURE_LINE_COLOR: &str = "#444444";
+pub const MEASURE_LINE_COLOR: &str = "#444444";
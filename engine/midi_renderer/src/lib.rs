@@ -1,8 +1,11 @@
 //! Renders MIDI notes into a SVG that can be displayed as a minimap.
 
-use svg::{node::element::Rectangle, Document};
+use svg::{
+  node::element::{Line, Rectangle},
+  Document,
+};
 
-use crate::conf::{MINIMAP_HEIGHT_PX, MIN_MIDI_NUMBER_RANGE, NOTE_COLOR};
+use crate::conf::{MEASURE_LINE_COLOR, MINIMAP_HEIGHT_PX, MIN_MIDI_NUMBER_RANGE, NOTE_COLOR};
 
 mod conf;
 
@@ -17,6 +20,18 @@ pub extern "C" fn get_encoded_notes_buf_ptr(encoded_byte_length: usize) -> *mut
   }
 }
 
+/// Beats at which measure lines are drawn, laid out by the MIDI editor according to its time
+/// signature
+static mut MEASURE_LINES_BUFFER: Vec<f32> = Vec::new();
+
+#[no_mangle]
+pub extern "C" fn get_measure_lines_buf_ptr(measure_line_count: usize) -> *mut f32 {
+  unsafe {
+    MEASURE_LINES_BUFFER = vec![0.; measure_line_count];
+    MEASURE_LINES_BUFFER.as_mut_ptr()
+  }
+}
+
 static mut RENDERED_SVG_TEXT: String = String::new();
 
 #[repr(packed)]
@@ -27,7 +42,7 @@ struct EncodedMIDINote {
 }
 
 #[no_mangle]
-pub extern "C" fn midi_minimap_render_minimap(_beats_per_measure: f32) -> *const u8 {
+pub extern "C" fn midi_minimap_render_minimap() -> *const u8 {
   let data_buf: &[u8] = unsafe { ENCODED_DATA_BUFFER.as_slice() };
   assert_eq!(data_buf.len() % 12, 0);
   let encoded_notes: &[EncodedMIDINote] = unsafe {
@@ -68,6 +83,20 @@ pub extern "C" fn midi_minimap_render_minimap(_beats_per_measure: f32) -> *const
 
   let mut doc = Document::new();
 
+  // x values are in beats and get stretched to match the zoom level of the MIDI editor, so the
+  // lines are drawn with a non-scaling stroke
+  for &beat in unsafe { MEASURE_LINES_BUFFER.iter() } {
+    let line = Line::new()
+      .set("x1", beat)
+      .set("x2", beat)
+      .set("y1", 0)
+      .set("y2", full_height)
+      .set("stroke", MEASURE_LINE_COLOR)
+      .set("stroke-width", 1)
+      .set("vector-effect", "non-scaling-stroke");
+    doc = doc.add(line);
+  }
+
   for note in encoded_notes {
     if note.midi_number < 0 {
       continue;
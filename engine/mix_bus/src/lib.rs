@@ -0,0 +1,412 @@
+//! Native mixer.  Each stereo input has a fader, a pan, mute/solo, and a send level to each of the
+//! buses.  Each bus has its own gain and is output separately, and the master output is the sum of
+//! all buses scaled by the master gain.  The master and each bus are separate stereo outputs of the
+//! module so that they can be routed to different destinations, like a cue bus for monitoring.
+//! Doing this in one module rather than with a web of `GainNode`s keeps the whole submix in a
+//! single render quantum and makes per-channel metering cheap.
+//!
+//! Gain changes of any kind (faders, pans, mutes, sends) are ramped linearly across the frame to
+//! avoid zipper noise.
+//!
+//! Exposed through the standard `module_api` entry points.
+
+use std::f32::consts::SQRT_2;
+
+use common::ffi::{self, FfiResult};
+use dsp::{crossfade::equal_power_pan, MAX_FRAME_SIZE};
+use module_api::{ChannelLayout, DspModule};
+
+pub const INPUT_COUNT: usize = 8;
+pub const BUS_COUNT: usize = 4;
+/// Every input is stereo, planar: input `n` occupies channels `2n` and `2n + 1`
+const CHANNEL_COUNT: usize = INPUT_COUNT * 2;
+/// Master output in channels 0 and 1, followed by each bus in order
+const OUTPUT_CHANNEL_COUNT: usize = (1 + BUS_COUNT) * 2;
+const OUTPUT_LAYOUTS: [ChannelLayout; 1 + BUS_COUNT] = [ChannelLayout::Stereo; 1 + BUS_COUNT];
+
+/// Param buffer layout.  The master gain and the input faders come first so that they can be
+/// automated by the host.  They're followed by the per-input params interleaved as pan, mute, solo,
+/// and then the send level to each bus.  The bus gains come last.
+const PARAM_MASTER_GAIN_IX: usize = 0;
+const INPUT_GAINS_OFFSET: usize = 1;
+const INPUT_PARAMS_OFFSET: usize = INPUT_GAINS_OFFSET + INPUT_COUNT;
+pub const INPUT_PARAM_COUNT: usize = 3 + BUS_COUNT;
+const INPUT_PARAM_PAN_IX: usize = 0;
+const INPUT_PARAM_MUTE_IX: usize = 1;
+const INPUT_PARAM_SOLO_IX: usize = 2;
+const INPUT_PARAM_SENDS_OFFSET: usize = 3;
+const BUS_GAINS_OFFSET: usize = INPUT_PARAMS_OFFSET + INPUT_COUNT * INPUT_PARAM_COUNT;
+const PARAM_COUNT: usize = BUS_GAINS_OFFSET + BUS_COUNT;
+
+/// SAB layout: the peak level of each channel over the most recently processed frame.  Input levels
+/// are measured post-fader and post-pan.
+const SAB_INPUT_LEVELS_OFFSET: usize = 0;
+const SAB_BUS_LEVELS_OFFSET: usize = SAB_INPUT_LEVELS_OFFSET + INPUT_COUNT * 2;
+const SAB_MASTER_LEVELS_OFFSET: usize = SAB_BUS_LEVELS_OFFSET + BUS_COUNT * 2;
+const SAB_LEN: usize = SAB_MASTER_LEVELS_OFFSET + 2;
+
+/// +12 dB
+const MAX_GAIN: f32 = 4.;
+
+/// Equal power pan law, normalized so that a centered input passes through at unity gain.  Hard
+/// panning boosts the remaining side by 3 dB.
+fn pan_gains(pan: f32) -> (f32, f32) {
+  let (left, right) = equal_power_pan(pan);
+  (left * SQRT_2, right * SQRT_2)
+}
+
+/// Spreads changes to a gain linearly across a frame
+#[derive(Clone, Copy)]
+struct GainRamp {
+  current: f32,
+}
+
+impl GainRamp {
+  const fn new(gain: f32) -> Self { GainRamp { current: gain } }
+
+  /// Returns the gain at the start of the frame and the per-sample step that arrives at `target` by
+  /// its end
+  #[inline]
+  fn start_frame(&mut self, target: f32, frame_size: usize) -> (f32, f32) {
+    let start = self.current;
+    self.current = target;
+    (start, (target - start) / frame_size as f32)
+  }
+}
+
+#[derive(Clone, Copy)]
+struct InputState {
+  left_gain: GainRamp,
+  right_gain: GainRamp,
+  sends: [GainRamp; BUS_COUNT],
+}
+
+impl Default for InputState {
+  fn default() -> Self {
+    let mut sends = [GainRamp::new(0.); BUS_COUNT];
+    sends[0] = GainRamp::new(1.);
+    InputState {
+      left_gain: GainRamp::new(1.),
+      right_gain: GainRamp::new(1.),
+      sends,
+    }
+  }
+}
+
+struct InputTargets {
+  left_gain: f32,
+  right_gain: f32,
+  sends: [f32; BUS_COUNT],
+}
+
+fn peak(buf: &[f32]) -> f32 { buf.iter().fold(0., |acc, s| acc.max(s.abs())) }
+
+pub struct MixBusCtx {
+  /// Planar; see `CHANNEL_COUNT` for the input layout and `OUTPUT_CHANNEL_COUNT` for the output
+  /// layout.  Outputs are written over the inputs.
+  pub io_buffer: [f32; MAX_FRAME_SIZE * CHANNEL_COUNT],
+  /// Written by JS; see `PARAM_MASTER_GAIN_IX` for the layout
+  pub params: [f32; PARAM_COUNT],
+  inputs: [InputState; INPUT_COUNT],
+  bus_gains: [GainRamp; BUS_COUNT],
+  master_gain: GainRamp,
+  /// Planar stereo, one pair of channels per bus
+  bus_buffers: [[f32; MAX_FRAME_SIZE]; BUS_COUNT * 2],
+  /// Per-channel peak levels; see `SAB_INPUT_LEVELS_OFFSET` for the layout
+  pub sab: [f32; SAB_LEN],
+}
+
+impl Default for MixBusCtx {
+  fn default() -> Self {
+    let mut params = [0.; PARAM_COUNT];
+    params[PARAM_MASTER_GAIN_IX] = 1.;
+    params[INPUT_GAINS_OFFSET..INPUT_PARAMS_OFFSET].fill(1.);
+    for params in params[INPUT_PARAMS_OFFSET..BUS_GAINS_OFFSET].chunks_exact_mut(INPUT_PARAM_COUNT)
+    {
+      // Everything goes to the first bus by default, making this a plain stereo mixer
+      params[INPUT_PARAM_SENDS_OFFSET] = 1.;
+    }
+    params[BUS_GAINS_OFFSET..].fill(1.);
+
+    MixBusCtx {
+      io_buffer: [0.; MAX_FRAME_SIZE * CHANNEL_COUNT],
+      params,
+      inputs: [InputState::default(); INPUT_COUNT],
+      bus_gains: [GainRamp::new(1.); BUS_COUNT],
+      master_gain: GainRamp::new(1.),
+      bus_buffers: [[0.; MAX_FRAME_SIZE]; BUS_COUNT * 2],
+      sab: [0.; SAB_LEN],
+    }
+  }
+}
+
+impl MixBusCtx {
+  fn input_params(&self, input_ix: usize) -> &[f32] {
+    let offset = INPUT_PARAMS_OFFSET + input_ix * INPUT_PARAM_COUNT;
+    &self.params[offset..offset + INPUT_PARAM_COUNT]
+  }
+
+  /// Clamps the params into their valid ranges in place.  NaNs are rejected.
+  fn validate_params(&mut self) -> FfiResult {
+    let (gains, rest) = self.params.split_at_mut(INPUT_PARAMS_OFFSET);
+    for gain in gains {
+      *gain = ffi::clamp_param("gain", *gain, 0., MAX_GAIN)?;
+    }
+    let (input_params, bus_gains) = rest.split_at_mut(BUS_GAINS_OFFSET - INPUT_PARAMS_OFFSET);
+    for params in input_params.chunks_exact_mut(INPUT_PARAM_COUNT) {
+      params[INPUT_PARAM_PAN_IX] = ffi::clamp_param("pan", params[INPUT_PARAM_PAN_IX], -1., 1.)?;
+      params[INPUT_PARAM_MUTE_IX] = ffi::clamp_param("mute", params[INPUT_PARAM_MUTE_IX], 0., 1.)?;
+      params[INPUT_PARAM_SOLO_IX] = ffi::clamp_param("solo", params[INPUT_PARAM_SOLO_IX], 0., 1.)?;
+      for send in &mut params[INPUT_PARAM_SENDS_OFFSET..] {
+        *send = ffi::clamp_param("send", *send, 0., MAX_GAIN)?;
+      }
+    }
+    for gain in bus_gains {
+      *gain = ffi::clamp_param("bus_gain", *gain, 0., MAX_GAIN)?;
+    }
+    Ok(())
+  }
+
+  fn any_soloed(&self) -> bool {
+    (0..INPUT_COUNT).any(|input_ix| self.input_params(input_ix)[INPUT_PARAM_SOLO_IX] > 0.5)
+  }
+
+  /// Computes the gains that the input's ramps should arrive at for the current params
+  fn input_targets(&self, input_ix: usize, any_soloed: bool) -> InputTargets {
+    let params = self.input_params(input_ix);
+    let muted =
+      params[INPUT_PARAM_MUTE_IX] > 0.5 || (any_soloed && params[INPUT_PARAM_SOLO_IX] <= 0.5);
+    let fader = if muted {
+      0.
+    } else {
+      self.params[INPUT_GAINS_OFFSET + input_ix]
+    };
+    let (pan_left, pan_right) = pan_gains(params[INPUT_PARAM_PAN_IX]);
+    let mut sends = [0.; BUS_COUNT];
+    sends.copy_from_slice(&params[INPUT_PARAM_SENDS_OFFSET..]);
+    InputTargets {
+      left_gain: fader * pan_left,
+      right_gain: fader * pan_right,
+      sends,
+    }
+  }
+
+  pub fn apply(&mut self, frame_size: usize) {
+    let any_soloed = self.any_soloed();
+
+    for bus_buffer in &mut self.bus_buffers {
+      bus_buffer[..frame_size].fill(0.);
+    }
+
+    for input_ix in 0..INPUT_COUNT {
+      let targets = self.input_targets(input_ix, any_soloed);
+      let state = &mut self.inputs[input_ix];
+      let (left_start, left_step) = state.left_gain.start_frame(targets.left_gain, frame_size);
+      let (right_start, right_step) = state.right_gain.start_frame(targets.right_gain, frame_size);
+      let mut sends = [(0., 0.); BUS_COUNT];
+      for ((send, ramp), target) in sends.iter_mut().zip(&mut state.sends).zip(targets.sends) {
+        *send = ramp.start_frame(target, frame_size);
+      }
+
+      let left_offset = input_ix * 2 * MAX_FRAME_SIZE;
+      let right_offset = left_offset + MAX_FRAME_SIZE;
+      let (mut left_peak, mut right_peak) = (0.0f32, 0.0f32);
+      for i in 0..frame_size {
+        let left = self.io_buffer[left_offset + i] * (left_start + left_step * i as f32);
+        let right = self.io_buffer[right_offset + i] * (right_start + right_step * i as f32);
+        left_peak = left_peak.max(left.abs());
+        right_peak = right_peak.max(right.abs());
+
+        for (bus_ix, (send_start, send_step)) in sends.iter().enumerate() {
+          let send = send_start + send_step * i as f32;
+          self.bus_buffers[bus_ix * 2][i] += left * send;
+          self.bus_buffers[bus_ix * 2 + 1][i] += right * send;
+        }
+      }
+      self.sab[SAB_INPUT_LEVELS_OFFSET + input_ix * 2] = left_peak;
+      self.sab[SAB_INPUT_LEVELS_OFFSET + input_ix * 2 + 1] = right_peak;
+    }
+
+    // All inputs have been consumed, so the outputs can now be written over them
+    let (master, buses) = self.io_buffer.split_at_mut(2 * MAX_FRAME_SIZE);
+    master.fill(0.);
+    for (bus_ix, gain) in self.bus_gains.iter_mut().enumerate() {
+      let (start, step) = gain.start_frame(self.params[BUS_GAINS_OFFSET + bus_ix], frame_size);
+      for channel_ix in 0..2 {
+        let bus_buffer = &mut self.bus_buffers[bus_ix * 2 + channel_ix][..frame_size];
+        for (i, sample) in bus_buffer.iter_mut().enumerate() {
+          *sample *= start + step * i as f32;
+        }
+        self.sab[SAB_BUS_LEVELS_OFFSET + bus_ix * 2 + channel_ix] = peak(bus_buffer);
+
+        let master_channel = &mut master[channel_ix * MAX_FRAME_SIZE..][..frame_size];
+        for (master_sample, bus_sample) in master_channel.iter_mut().zip(bus_buffer.iter()) {
+          *master_sample += *bus_sample;
+        }
+        let output_offset = (bus_ix * 2 + channel_ix) * MAX_FRAME_SIZE;
+        buses[output_offset..output_offset + frame_size].copy_from_slice(bus_buffer);
+      }
+    }
+
+    let (start, step) = self
+      .master_gain
+      .start_frame(self.params[PARAM_MASTER_GAIN_IX], frame_size);
+    for channel_ix in 0..2 {
+      let master_channel = &mut master[channel_ix * MAX_FRAME_SIZE..][..frame_size];
+      for (i, sample) in master_channel.iter_mut().enumerate() {
+        *sample *= start + step * i as f32;
+      }
+      self.sab[SAB_MASTER_LEVELS_OFFSET + channel_ix] = peak(master_channel);
+    }
+  }
+}
+
+impl DspModule for MixBusCtx {
+  const AUTOMATABLE_PARAM_COUNT: usize = INPUT_PARAMS_OFFSET;
+  const CHANNEL_COUNT: usize = CHANNEL_COUNT;
+  const NAME: &'static str = "mix_bus";
+  const OUTPUT_CHANNEL_COUNT: usize = OUTPUT_CHANNEL_COUNT;
+  const OUTPUT_LAYOUTS: &'static [ChannelLayout] = &OUTPUT_LAYOUTS;
+  const PARAM_COUNT: usize = PARAM_COUNT;
+  const SAB_LEN: usize = SAB_LEN;
+  const VERSION: u32 = 1;
+
+  fn new() -> Self { Self::default() }
+
+  fn param_name(param_ix: usize) -> String {
+    if param_ix == PARAM_MASTER_GAIN_IX {
+      return "master_gain".to_owned();
+    }
+    if param_ix < INPUT_PARAMS_OFFSET {
+      return format!("input_{}_gain", param_ix - INPUT_GAINS_OFFSET);
+    }
+    if param_ix >= BUS_GAINS_OFFSET {
+      return format!("bus_{}_gain", param_ix - BUS_GAINS_OFFSET);
+    }
+
+    let input_ix = (param_ix - INPUT_PARAMS_OFFSET) / INPUT_PARAM_COUNT;
+    match (param_ix - INPUT_PARAMS_OFFSET) % INPUT_PARAM_COUNT {
+      INPUT_PARAM_PAN_IX => format!("input_{input_ix}_pan"),
+      INPUT_PARAM_MUTE_IX => format!("input_{input_ix}_mute"),
+      INPUT_PARAM_SOLO_IX => format!("input_{input_ix}_solo"),
+      ix => format!("input_{input_ix}_send_{}", ix - INPUT_PARAM_SENDS_OFFSET),
+    }
+  }
+
+  fn io_buffer(&mut self) -> &mut [f32] { &mut self.io_buffer }
+
+  fn params(&mut self) -> &mut [f32] { &mut self.params }
+
+  fn sab(&mut self) -> &mut [f32] { &mut self.sab }
+
+  fn process(&mut self, frame_size: usize) -> FfiResult {
+    self.validate_params()?;
+    self.apply(frame_size);
+    Ok(())
+  }
+
+  fn reset(&mut self) {
+    // Jump straight to the current params rather than ramping from wherever we left off
+    let any_soloed = self.any_soloed();
+    for input_ix in 0..INPUT_COUNT {
+      let targets = self.input_targets(input_ix, any_soloed);
+      let state = &mut self.inputs[input_ix];
+      state.left_gain = GainRamp::new(targets.left_gain);
+      state.right_gain = GainRamp::new(targets.right_gain);
+      for (ramp, send) in state.sends.iter_mut().zip(targets.sends) {
+        *ramp = GainRamp::new(send);
+      }
+    }
+    for (bus_ix, ramp) in self.bus_gains.iter_mut().enumerate() {
+      *ramp = GainRamp::new(self.params[BUS_GAINS_OFFSET + bus_ix]);
+    }
+    self.master_gain = GainRamp::new(self.params[PARAM_MASTER_GAIN_IX]);
+    self.sab.fill(0.);
+  }
+}
+
+module_api::export_module!(MixBusCtx);
+
+#[test]
+fn constant_power_pan() {
+  let (left, right) = pan_gains(0.);
+  assert!((left - 1.).abs() < 1e-6 && (right - 1.).abs() < 1e-6);
+
+  for pan in [-1., -0.5, 0.25, 1.] {
+    let (left, right) = pan_gains(pan);
+    assert!((left * left + right * right - 2.).abs() < 1e-5, "pan={pan}");
+  }
+  let (left, right) = pan_gains(-1.);
+  assert!(right.abs() < 1e-6 && left > 1.);
+}
+
+#[test]
+fn solo_mutes_other_inputs() {
+  let mut ctx = MixBusCtx::default();
+  let frame_size = 128;
+  for input_ix in 0..INPUT_COUNT {
+    let offset = input_ix * 2 * MAX_FRAME_SIZE;
+    ctx.io_buffer[offset..offset + 2 * MAX_FRAME_SIZE].fill((input_ix + 1) as f32 * 0.01);
+  }
+  ctx.params[INPUT_PARAMS_OFFSET + 2 * INPUT_PARAM_COUNT + INPUT_PARAM_SOLO_IX] = 1.;
+  // Settle the gain ramps
+  ctx.reset();
+  ctx.process(frame_size).unwrap();
+
+  for input_ix in 0..INPUT_COUNT {
+    let expected = if input_ix == 2 { 0.03 } else { 0. };
+    let level = ctx.sab[SAB_INPUT_LEVELS_OFFSET + input_ix * 2];
+    assert!((level - expected).abs() < 1e-6, "input {input_ix}: {level}");
+  }
+  assert!((ctx.io_buffer[0] - 0.03).abs() < 1e-6);
+  assert!((ctx.io_buffer[MAX_FRAME_SIZE + frame_size - 1] - 0.03).abs() < 1e-6);
+}
+
+#[test]
+fn state_json_roundtrip() {
+  use module_api::{exports, registry::HandleRegistry};
+
+  let registry: exports::Registry<MixBusCtx> = std::cell::RefCell::new(HandleRegistry::new());
+  let handle = exports::create(&registry);
+  let read_state = || {
+    let len = exports::get_state_json(&registry, handle);
+    let json = unsafe { std::slice::from_raw_parts(common::state::get_state_json_ptr(), len) };
+    String::from_utf8(json.to_vec()).unwrap()
+  };
+  let write_state = |json: &str| {
+    let buf = common::state::alloc_state_json_buf(json.len());
+    unsafe { std::ptr::copy_nonoverlapping(json.as_ptr(), buf, json.len()) };
+    exports::set_state_json(&registry, handle, json.len())
+  };
+
+  let json = read_state();
+  assert!(
+    json.starts_with(r#"{"master_gain":1.0,"input_0_gain":1.0,"#),
+    "{json}"
+  );
+  assert!(json.contains(r#""input_2_send_1":0.0,"#), "{json}");
+  // Params come first, followed by the state of the module's macros
+  assert!(
+    json.contains(r#""bus_3_gain":1.0,"macro_0.value":0.0,"#),
+    "{json}"
+  );
+  assert!(json.ends_with(r#""macro_7.value":0.0}"#), "{json}");
+
+  assert_eq!(
+    write_state(r#"{"input_2_pan":-0.5,"bus_1_gain":0.25,"unknown":3}"#),
+    ffi::ErrorCode::Ok
+  );
+  let read_param =
+    |param_ix: usize| unsafe { *exports::param_buf_ptr(&registry, handle).add(param_ix) };
+  let pan_ix = INPUT_PARAMS_OFFSET + 2 * INPUT_PARAM_COUNT + INPUT_PARAM_PAN_IX;
+  assert_eq!(read_param(pan_ix), -0.5);
+  assert_eq!(read_param(BUS_GAINS_OFFSET + 1), 0.25);
+  assert_eq!(read_param(PARAM_MASTER_GAIN_IX), 1.);
+
+  // Invalid state is rejected without applying anything
+  assert_eq!(
+    write_state(r#"{"master_gain":0.5,"input_2_pan":true}"#),
+    ffi::ErrorCode::ParamOutOfRange
+  );
+  assert_eq!(read_param(PARAM_MASTER_GAIN_IX), 1.);
+}
@@ -0,0 +1,144 @@
+//! Control-rate signals passed between modules, for modular-synth-style cross-module modulation
+//! like one module's envelope follower sweeping another module's filter.
+//!
+//! Control signals have one value per `CONTROL_BLOCK_SIZE` samples.  Modules publish them through
+//! `DspModule::control_outputs`, which the host samples after each control block, and any module
+//! can subscribe to them by routing a bus channel to one of its params.  Routed params are offset
+//! by the signal scaled by the route's depth for the duration of each control block and restored
+//! afterwards, so the param buffer keeps holding the unmodulated values that JS writes.
+//!
+//! The bus itself lives in the host: after processing, it copies each published output into a
+//! named bus channel, and before processing it copies the channels that a module subscribes to into
+//! that module's control inputs.  Modules don't need to do anything to be modulated.
+
+use common::ffi::{self, FfiResult};
+use dsp::MAX_FRAME_SIZE;
+
+pub const CONTROL_BLOCK_SIZE: usize = 16;
+/// Control signals are stored as this many values per frame, of which the first
+/// `frame_size / CONTROL_BLOCK_SIZE` (rounded up) are used
+pub const MAX_CONTROL_BLOCKS: usize = MAX_FRAME_SIZE / CONTROL_BLOCK_SIZE;
+/// Each instance can have this many control inputs routed to its params
+pub const MAX_CONTROL_ROUTES: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ControlRoute {
+  pub param_ix: usize,
+  /// The signal is multiplied by this before being added to the param
+  pub depth: f32,
+}
+
+/// The control inputs of a single instance and the params they're routed to
+pub struct ControlInputs {
+  routes: [Option<ControlRoute>; MAX_CONTROL_ROUTES],
+  /// Planar; route `n`'s values start at `n * MAX_CONTROL_BLOCKS`.  Written by the host before
+  /// each frame.
+  pub values: [f32; MAX_CONTROL_ROUTES * MAX_CONTROL_BLOCKS],
+  /// Unmodulated values of the routed params while a block is being processed, by route
+  saved_params: [f32; MAX_CONTROL_ROUTES],
+}
+
+impl Default for ControlInputs {
+  fn default() -> Self {
+    ControlInputs {
+      routes: [None; MAX_CONTROL_ROUTES],
+      values: [0.; MAX_CONTROL_ROUTES * MAX_CONTROL_BLOCKS],
+      saved_params: [0.; MAX_CONTROL_ROUTES],
+    }
+  }
+}
+
+impl ControlInputs {
+  pub fn set_route(
+    &mut self,
+    route_ix: usize,
+    route: Option<ControlRoute>,
+    param_count: usize,
+  ) -> FfiResult {
+    ffi::check_range("route_ix", route_ix, 0, MAX_CONTROL_ROUTES - 1)?;
+    if let Some(route) = &route {
+      ffi::check_range("param_ix", route.param_ix, 0, param_count.saturating_sub(1))?;
+      ffi::clamp_param("depth", route.depth, f32::MIN, f32::MAX)?;
+    }
+    self.routes[route_ix] = route;
+    self.values[route_ix * MAX_CONTROL_BLOCKS..(route_ix + 1) * MAX_CONTROL_BLOCKS].fill(0.);
+    Ok(())
+  }
+
+  pub fn is_active(&self) -> bool { self.routes.iter().any(Option::is_some) }
+
+  /// Offsets the routed params by their inputs' values for the control block containing
+  /// `frame_offset`.  Must be followed by `restore` once the block has been processed.
+  pub fn apply(&mut self, frame_offset: usize, params: &mut [f32]) {
+    let block_ix = (frame_offset / CONTROL_BLOCK_SIZE).min(MAX_CONTROL_BLOCKS - 1);
+    for (route_ix, route) in self.routes.iter().enumerate() {
+      let Some(route) = route else { continue };
+      self.saved_params[route_ix] = params[route.param_ix];
+      let value = self.values[route_ix * MAX_CONTROL_BLOCKS + block_ix];
+      if value.is_finite() {
+        params[route.param_ix] += value * route.depth;
+      }
+    }
+  }
+
+  /// Puts back the unmodulated param values saved by `apply`.  Routes are restored in reverse so
+  /// that several routes to the same param end up at its original value.
+  pub fn restore(&self, params: &mut [f32]) {
+    for (route_ix, route) in self.routes.iter().enumerate().rev() {
+      if let Some(route) = route {
+        params[route.param_ix] = self.saved_params[route_ix];
+      }
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.routes = [None; MAX_CONTROL_ROUTES];
+    self.values.fill(0.);
+  }
+}
+
+#[test]
+fn routes_offset_params_per_block_and_restore_them() {
+  let mut inputs = ControlInputs::default();
+  assert!(inputs
+    .set_route(
+      0,
+      Some(ControlRoute {
+        param_ix: 2,
+        depth: 1.
+      }),
+      2
+    )
+    .is_err());
+  inputs
+    .set_route(
+      0,
+      Some(ControlRoute {
+        param_ix: 1,
+        depth: 0.5,
+      }),
+      2,
+    )
+    .unwrap();
+  inputs
+    .set_route(
+      3,
+      Some(ControlRoute {
+        param_ix: 1,
+        depth: 2.,
+      }),
+      2,
+    )
+    .unwrap();
+  inputs.values[1] = 2.;
+  inputs.values[3 * MAX_CONTROL_BLOCKS + 1] = 1.;
+
+  let mut params = [10., 20.];
+  inputs.apply(CONTROL_BLOCK_SIZE + 3, &mut params);
+  assert_eq!(params, [10., 23.]);
+  inputs.restore(&mut params);
+  assert_eq!(params, [10., 20.]);
+
+  inputs.apply(0, &mut params);
+  assert_eq!(params, [10., 20.]);
+}
@@ -0,0 +1,1176 @@
+//! Standard FFI surface for standalone DSP modules.
+//!
+//! A module implements `DspModule` and invokes `export_module!` once.  That exports the same set of
+//! entry points from every module's Wasm binary, so the JS side can host any of them with the
+//! generic `ModuleHostAWP` instead of a bespoke AWP per module:
+//!
+//! - `module_create() -> u32`: creates an instance and returns its handle, or 0 on failure
+//! - `module_destroy(handle) -> ErrorCode`
+//! - `module_reset(handle) -> ErrorCode`: clears DSP state, keeping params
+//! - `module_process(handle, frame_size) -> ErrorCode`: processes the IO buffer in place
+//! - `module_get_io_buf_ptr(handle)`, `module_get_param_buf_ptr(handle)`,
+//!   `module_get_sab_ptr(handle)`: pointers into the instance, or null for a bad handle
+//! - `module_set_output_guard_enabled(handle, enabled) -> ErrorCode` +
+//!   `module_get_output_guard_stats_ptr(handle)`: see `dsp::output_guard`
+//! - `module_set_profiling_enabled(handle, enabled) -> ErrorCode` +
+//!   `module_get_profiling_stats_ptr(handle)`: time spent in `module_process`, see `dsp::profiling`
+//! - `module_get_capabilities_ptr()` + `module_get_capabilities_len()`: see `CAPABILITY_*_IX`
+//! - `module_get_output_layouts_ptr()`: the `ChannelLayout` of each output as a `u32`, with
+//!   `CAPABILITY_OUTPUT_COUNT_IX` entries
+//! - `module_get_name_ptr()` + `module_get_name_len()`
+//! - `module_get_state_json(handle) -> usize` + `module_set_state_json(handle, len) -> ErrorCode`:
+//!   snapshots and restores the param buffer and macros as JSON, see `common::state`
+//! - `module_ab_select(handle, slot, fade_ms) -> ErrorCode` +
+//!   `module_ab_get_slot_state_json(handle, slot) -> usize`: A/B comparison of two param sets, see
+//!   `common::state::ABCompare`
+//! - `module_schedule_param_change(handle, param_ix, value, frame_offset) -> ErrorCode`: sets a
+//!   param `frame_offset` samples into the next frame processed, see `param_events`
+//! - `module_clear_param_events(handle) -> ErrorCode`
+//! - `module_get_latency_samples(handle) -> u32`: see `DspModule::latency_samples`
+//! - `module_set_control_outputs_enabled(handle, enabled) -> ErrorCode` +
+//!   `module_get_control_outputs_ptr(handle)`: the module's control outputs, sampled once per
+//!   control block while enabled, see `control_bus`
+//! - `module_set_control_route(handle, route_ix, param_ix, depth) -> ErrorCode`,
+//!   `module_clear_control_route(handle, route_ix) -> ErrorCode`, and
+//!   `module_get_control_inputs_ptr(handle)`: modulates params from control signals written by the
+//!   host, see `control_bus`
+//! - `module_set_macro_value(handle, macro_ix, value) -> ErrorCode`,
+//!   `module_set_macro_target(handle, macro_ix, target_ix, param_ix, min, max, curve) ->
+//!   ErrorCode`, and `module_clear_macro_target(handle, macro_ix, target_ix) -> ErrorCode`: see
+//!   `macros`
+//! - `module_set_scene_param(handle, scene_ix, param_ix, value) -> ErrorCode`,
+//!   `module_clear_scene(handle, scene_ix) -> ErrorCode`, and `module_recall_scene(handle,
+//!   scene_ix, glide_ms) -> ErrorCode`: see `scenes`
+//!
+//! Modules import `log_err(ptr, len)`, called with the panic message if the module panics, and
+//! `now() -> f64`, a timestamp in milliseconds used for profiling.
+//!
+//! Instances are referred to by handles from a `HandleRegistry` rather than raw pointers, so a
+//! stale or garbage handle coming from JS produces `ErrorCode::InvalidHandle` rather than UB.
+
+use std::cell::RefCell;
+
+use common::{
+  ffi::{self, ErrorCode, FfiResult},
+  state::{ABCompare, StateSnapshot, MAX_AB_FADE_MS},
+};
+use dsp::{output_guard::OutputGuard, profiling::Profiler, MAX_FRAME_SIZE};
+
+pub mod control_bus;
+pub mod macros;
+pub mod param_events;
+pub mod registry;
+pub mod scenes;
+
+// Re-exported for use by `export_module!`
+pub use common;
+
+use crate::{
+  control_bus::{ControlInputs, ControlRoute, CONTROL_BLOCK_SIZE, MAX_CONTROL_BLOCKS},
+  macros::{MacroTarget, Macros},
+  param_events::{ParamEvent, ParamEventQueue},
+  registry::HandleRegistry,
+  scenes::{Scenes, MAX_SCENE_GLIDE_MS},
+};
+
+/// Bumped whenever the exported entry points or the capabilities layout change incompatibly
+pub const API_VERSION: u32 = 1;
+
+// Layout of the capabilities buffer.  New entries are only ever appended, so hosts can read the
+// entries they know about from any module with the same `API_VERSION`.
+pub const CAPABILITY_API_VERSION_IX: usize = 0;
+pub const CAPABILITY_MODULE_VERSION_IX: usize = 1;
+pub const CAPABILITY_CHANNEL_COUNT_IX: usize = 2;
+pub const CAPABILITY_MAX_FRAME_SIZE_IX: usize = 3;
+pub const CAPABILITY_PARAM_COUNT_IX: usize = 4;
+pub const CAPABILITY_AUTOMATABLE_PARAM_COUNT_IX: usize = 5;
+pub const CAPABILITY_SAB_LEN_IX: usize = 6;
+pub const CAPABILITY_OUTPUT_CHANNEL_COUNT_IX: usize = 7;
+/// Number of entries in the output layouts buffer.  0 means that all output channels go to a
+/// single output.
+pub const CAPABILITY_OUTPUT_COUNT_IX: usize = 8;
+pub const CAPABILITY_CONTROL_OUTPUT_COUNT_IX: usize = 9;
+pub const CAPABILITIES_LEN: usize = 10;
+
+/// The host exposes this many generic k-rate `AudioParam`s
+pub const MAX_AUTOMATABLE_PARAMS: usize = 16;
+
+/// Web Audio nodes can have any number of outputs, but each output is limited to this many channels
+/// to keep it within the speaker layouts that hosts know how to route.
+pub const MAX_CHANNELS_PER_OUTPUT: usize = 8;
+
+/// Speaker layout of one of a module's outputs.  Channels are ordered the same way as Web Audio's
+/// speaker layouts so that hosts can connect outputs to other nodes without remapping them.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelLayout {
+  Mono = 0,
+  /// L, R
+  Stereo = 1,
+  /// L, R, SL, SR
+  Quad = 2,
+  /// L, R, C, LFE, SL, SR
+  Surround5_1 = 3,
+  /// L, R, C, LFE, SL, SR, BL, BR.  Web Audio has no speaker layout for 8 channels, so hosts treat
+  /// these as discrete.
+  Surround7_1 = 4,
+}
+
+impl ChannelLayout {
+  pub const fn channel_count(self) -> usize {
+    match self {
+      ChannelLayout::Mono => 1,
+      ChannelLayout::Stereo => 2,
+      ChannelLayout::Quad => 4,
+      ChannelLayout::Surround5_1 => 6,
+      ChannelLayout::Surround7_1 => 8,
+    }
+  }
+}
+
+/// Each instance is profiled as a whole, so its profiling stats are a single slot
+pub const PROFILING_STATS_LEN: usize = dsp::profiling::STATS_PER_SLOT;
+
+pub trait DspModule: Sized + 'static {
+  const NAME: &'static str;
+  /// Version of the module's own param and SAB layout
+  const VERSION: u32;
+  const CHANNEL_COUNT: usize;
+  /// Number of channels read back out of the IO buffer after processing, starting from channel 0.
+  /// Modules with more inputs than outputs (mixers etc.) write their outputs over the first
+  /// channels.
+  const OUTPUT_CHANNEL_COUNT: usize = Self::CHANNEL_COUNT;
+  /// Splits the output channels into separate outputs, in order starting from channel 0.  The
+  /// layouts' channel counts must add up to `OUTPUT_CHANNEL_COUNT`.  If empty, all output channels
+  /// go to a single output, so there can be at most `MAX_CHANNELS_PER_OUTPUT` of them.
+  const OUTPUT_LAYOUTS: &'static [ChannelLayout] = &[];
+  const PARAM_COUNT: usize;
+  /// The first this many params are driven by the host's `AudioParam`s every frame.  The rest are
+  /// only written when JS sets them explicitly.
+  const AUTOMATABLE_PARAM_COUNT: usize = 0;
+  const SAB_LEN: usize = 0;
+  /// Number of control-rate signals the module publishes for other modules to subscribe to.  See
+  /// `control_bus`.
+  const CONTROL_OUTPUT_COUNT: usize = 0;
+  fn new() -> Self;
+
+  /// Key used for the param at `param_ix` in state snapshots.  Names should stay stable across
+  /// module versions so that saved state keeps loading.
+  fn param_name(param_ix: usize) -> String { format!("param_{param_ix}") }
+
+  /// Planar: channel `n` starts at `n * MAX_FRAME_SIZE`.  Must be `CHANNEL_COUNT * MAX_FRAME_SIZE`
+  /// long.
+  fn io_buffer(&mut self) -> &mut [f32];
+
+  /// Must be `PARAM_COUNT` long
+  fn params(&mut self) -> &mut [f32];
+
+  /// Must be `SAB_LEN` long
+  fn sab(&mut self) -> &mut [f32] { &mut [] }
+
+  /// Current value of each control output.  Must be `CONTROL_OUTPUT_COUNT` long.  Read by the host
+  /// at the end of every control block while the outputs are published.
+  fn control_outputs(&self) -> &[f32] { &[] }
+
+  /// Processes the first `frame_size` samples of each channel of the IO buffer in place.
+  /// `frame_size` has already been validated.
+  fn process(&mut self, frame_size: usize) -> FfiResult;
+
+  /// Clears filter and envelope state without touching params
+  fn reset(&mut self);
+
+  /// Number of samples that the output lags behind the input, for modules that use lookahead or
+  /// linear-phase filtering.  Hosts delay parallel signal paths by this much so that they line up
+  /// when mixed back together.  Can change as params change; hosts poll it after each frame.
+  fn latency_samples(&self) -> usize { 0 }
+}
+
+pub const fn capabilities<M: DspModule>() -> [u32; CAPABILITIES_LEN] {
+  assert!(M::AUTOMATABLE_PARAM_COUNT <= M::PARAM_COUNT);
+  assert!(M::AUTOMATABLE_PARAM_COUNT <= MAX_AUTOMATABLE_PARAMS);
+  assert!(M::OUTPUT_CHANNEL_COUNT <= M::CHANNEL_COUNT);
+  if M::OUTPUT_LAYOUTS.is_empty() {
+    assert!(M::OUTPUT_CHANNEL_COUNT <= MAX_CHANNELS_PER_OUTPUT);
+  } else {
+    let mut layout_channel_count = 0;
+    let mut output_ix = 0;
+    while output_ix < M::OUTPUT_LAYOUTS.len() {
+      layout_channel_count += M::OUTPUT_LAYOUTS[output_ix].channel_count();
+      output_ix += 1;
+    }
+    assert!(layout_channel_count == M::OUTPUT_CHANNEL_COUNT);
+  }
+
+  let mut capabilities = [0; CAPABILITIES_LEN];
+  capabilities[CAPABILITY_API_VERSION_IX] = API_VERSION;
+  capabilities[CAPABILITY_MODULE_VERSION_IX] = M::VERSION;
+  capabilities[CAPABILITY_CHANNEL_COUNT_IX] = M::CHANNEL_COUNT as u32;
+  capabilities[CAPABILITY_MAX_FRAME_SIZE_IX] = MAX_FRAME_SIZE as u32;
+  capabilities[CAPABILITY_PARAM_COUNT_IX] = M::PARAM_COUNT as u32;
+  capabilities[CAPABILITY_AUTOMATABLE_PARAM_COUNT_IX] = M::AUTOMATABLE_PARAM_COUNT as u32;
+  capabilities[CAPABILITY_SAB_LEN_IX] = M::SAB_LEN as u32;
+  capabilities[CAPABILITY_OUTPUT_CHANNEL_COUNT_IX] = M::OUTPUT_CHANNEL_COUNT as u32;
+  capabilities[CAPABILITY_OUTPUT_COUNT_IX] = M::OUTPUT_LAYOUTS.len() as u32;
+  capabilities[CAPABILITY_CONTROL_OUTPUT_COUNT_IX] = M::CONTROL_OUTPUT_COUNT as u32;
+  capabilities
+}
+
+/// Implementations of the exports generated by `export_module!`.  They're generic over the module
+/// so that the macro itself stays a thin shim.
+pub mod exports {
+  use super::*;
+
+  /// A module along with the host-side state kept for it
+  pub struct Instance<M> {
+    module: M,
+    /// Applied to the module's outputs after each frame when enabled.  Off by default.
+    output_guard: Option<OutputGuard>,
+    output_guard_stats: [f32; dsp::output_guard::STATS_LEN],
+    /// Covers processing and the output guard.  Off by default.
+    profiler: Profiler,
+    ab: ABCompare,
+    param_events: ParamEventQueue,
+    /// Holds the frame's input and the sub-blocks' outputs while a frame is split into sub-blocks
+    /// by param events or control signals.  Allocated the first time either is used.
+    event_scratch: Vec<f32>,
+    control_inputs: ControlInputs,
+    /// Planar; output `n`'s values start at `n * MAX_CONTROL_BLOCKS`
+    control_outputs: Vec<f32>,
+    control_outputs_enabled: bool,
+    macros: Macros,
+    scenes: Scenes,
+  }
+
+  impl<M: DspModule> Instance<M> {
+    fn alloc_event_scratch(&mut self) {
+      if self.event_scratch.is_empty() {
+        self.event_scratch = vec![0.; 2 * M::CHANNEL_COUNT * MAX_FRAME_SIZE];
+      }
+    }
+  }
+
+  pub type Registry<M> = RefCell<HandleRegistry<Instance<M>>>;
+
+  /// A module's state is its whole param buffer.  Params driven by `AudioParam`s are included,
+  /// but restoring them only lasts until the host next writes them.
+  fn snapshot_params<M: DspModule>(params: &[f32]) -> StateSnapshot {
+    let mut snapshot = StateSnapshot::default();
+    for (param_ix, &val) in params.iter().enumerate() {
+      snapshot.push_number(&M::param_name(param_ix), val);
+    }
+    snapshot
+  }
+
+  fn restore_params<M: DspModule>(module: &mut M, state: &StateSnapshot) -> FfiResult {
+    let mut new_params = module.params().to_vec();
+    for (param_ix, param) in new_params.iter_mut().enumerate() {
+      let name = M::param_name(param_ix);
+      if let Some(val) = state.number(&name)? {
+        *param = ffi::clamp_param(&name, val, f32::MIN, f32::MAX)?;
+      }
+    }
+    module.params().copy_from_slice(&new_params);
+    Ok(())
+  }
+
+  /// Returns the end of the sub-block starting at `block_start`.  Frames are split at the offsets
+  /// of param events and, while control signals are in use, at every control block boundary.
+  fn next_block_end<M: DspModule>(
+    instance: &Instance<M>,
+    block_start: usize,
+    frame_size: usize,
+  ) -> usize {
+    let block_end = instance
+      .param_events
+      .next_offset_within(frame_size)
+      .unwrap_or(frame_size);
+    if instance.control_outputs_enabled || instance.control_inputs.is_active() {
+      let control_block_end = (block_start / CONTROL_BLOCK_SIZE + 1) * CONTROL_BLOCK_SIZE;
+      block_end.min(control_block_end)
+    } else {
+      block_end
+    }
+  }
+
+  /// Processes one sub-block with the routed params modulated by their control inputs, then
+  /// samples the control outputs into the control block that the sub-block ends in
+  fn process_block<M: DspModule>(
+    instance: &mut Instance<M>,
+    block_start: usize,
+    block_len: usize,
+  ) -> FfiResult {
+    instance
+      .control_inputs
+      .apply(block_start, instance.module.params());
+    let res = instance.module.process(block_len);
+    instance.control_inputs.restore(instance.module.params());
+
+    if instance.control_outputs_enabled {
+      let block_ix = (block_start + block_len - 1) / CONTROL_BLOCK_SIZE;
+      for (output_ix, &value) in instance.module.control_outputs().iter().enumerate() {
+        instance.control_outputs[output_ix * MAX_CONTROL_BLOCKS + block_ix] = value;
+      }
+    }
+    res
+  }
+
+  /// Processes the frame in sub-blocks split at the offsets of the param events that land inside
+  /// it so that each event takes effect on the exact sample it was scheduled for, and at control
+  /// block boundaries while control signals are in use.  Sub-blocks are processed at the start of
+  /// the IO buffer, so the frame's input is stashed and the outputs are collected separately before
+  /// being copied back.
+  fn process_with_events<M: DspModule>(instance: &mut Instance<M>, frame_size: usize) -> FfiResult {
+    instance.param_events.apply_due(0, instance.module.params());
+    let block_end = next_block_end(instance, 0, frame_size);
+    if block_end == frame_size {
+      return process_block(instance, 0, frame_size);
+    }
+
+    // Taken out of the instance while the frame is processed so that the rest of it can be borrowed
+    let mut scratch = std::mem::take(&mut instance.event_scratch);
+    let res = process_sub_blocks(instance, &mut scratch, block_end, frame_size);
+    instance.event_scratch = scratch;
+    res
+  }
+
+  fn process_sub_blocks<M: DspModule>(
+    instance: &mut Instance<M>,
+    scratch: &mut [f32],
+    mut block_end: usize,
+    frame_size: usize,
+  ) -> FfiResult {
+    let (input, output) = scratch.split_at_mut(M::CHANNEL_COUNT * MAX_FRAME_SIZE);
+    input.copy_from_slice(instance.module.io_buffer());
+    let mut block_start = 0;
+    loop {
+      let block_len = block_end - block_start;
+      let io = instance.module.io_buffer();
+      for channel_ix in 0..M::CHANNEL_COUNT {
+        let offset = channel_ix * MAX_FRAME_SIZE;
+        io[offset..offset + block_len]
+          .copy_from_slice(&input[offset + block_start..offset + block_end]);
+      }
+      process_block(instance, block_start, block_len)?;
+      let io = instance.module.io_buffer();
+      for channel_ix in 0..M::OUTPUT_CHANNEL_COUNT {
+        let offset = channel_ix * MAX_FRAME_SIZE;
+        output[offset + block_start..offset + block_end]
+          .copy_from_slice(&io[offset..offset + block_len]);
+      }
+
+      if block_end == frame_size {
+        break;
+      }
+      instance
+        .param_events
+        .apply_due(block_end, instance.module.params());
+      block_start = block_end;
+      block_end = next_block_end(instance, block_start, frame_size);
+    }
+
+    let outputs = &output[..M::OUTPUT_CHANNEL_COUNT * MAX_FRAME_SIZE];
+    instance.module.io_buffer()[..outputs.len()].copy_from_slice(outputs);
+    Ok(())
+  }
+
+  pub fn create<M: DspModule>(registry: &Registry<M>) -> u32 {
+    let mut module = M::new();
+    debug_assert_eq!(module.io_buffer().len(), M::CHANNEL_COUNT * MAX_FRAME_SIZE);
+    debug_assert_eq!(module.params().len(), M::PARAM_COUNT);
+    debug_assert_eq!(module.sab().len(), M::SAB_LEN);
+    debug_assert_eq!(module.control_outputs().len(), M::CONTROL_OUTPUT_COUNT);
+    registry.borrow_mut().insert(Box::new(Instance {
+      module,
+      output_guard: None,
+      output_guard_stats: [0.; dsp::output_guard::STATS_LEN],
+      profiler: Profiler::new(1),
+      ab: ABCompare::default(),
+      param_events: ParamEventQueue::default(),
+      event_scratch: Vec::new(),
+      control_inputs: ControlInputs::default(),
+      control_outputs: vec![0.; M::CONTROL_OUTPUT_COUNT * MAX_CONTROL_BLOCKS],
+      control_outputs_enabled: false,
+      macros: Macros::default(),
+      scenes: Scenes::default(),
+    }))
+  }
+
+  pub fn destroy<M: DspModule>(registry: &Registry<M>, handle: u32) -> ErrorCode {
+    ffi::status(registry.borrow_mut().remove(handle, "module_destroy"))
+  }
+
+  pub fn reset<M: DspModule>(registry: &Registry<M>, handle: u32) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_reset")?;
+      instance.module.reset();
+      if let Some(output_guard) = &mut instance.output_guard {
+        output_guard.reset();
+      }
+      instance.output_guard_stats = [0.; dsp::output_guard::STATS_LEN];
+      instance.profiler.reset();
+      instance.param_events.clear();
+      instance.control_outputs.fill(0.);
+      Ok(())
+    })())
+  }
+
+  pub fn process<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    frame_size: usize,
+    now: fn() -> f64,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_process")?;
+      ffi::check_range("frame_size", frame_size, 1, MAX_FRAME_SIZE)?;
+      let start = instance.profiler.start(now);
+      instance.ab.tick(frame_size, instance.module.params());
+      instance.scenes.tick(frame_size, instance.module.params());
+      instance.macros.apply(instance.module.params());
+      let res = process_with_events(instance, frame_size);
+      instance.param_events.advance(frame_size);
+      // Guard the output even if processing failed since the buffer may be half-written
+      if let Some(output_guard) = &mut instance.output_guard {
+        let outputs = &mut instance.module.io_buffer()[..M::OUTPUT_CHANNEL_COUNT * MAX_FRAME_SIZE];
+        output_guard.apply(outputs, frame_size);
+        instance.output_guard_stats = output_guard.stats;
+      }
+      instance.profiler.end(0, start, now);
+      res
+    })())
+  }
+
+  pub fn set_output_guard_enabled<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    enabled: bool,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_set_output_guard_enabled")?;
+      if !enabled {
+        instance.output_guard = None;
+      } else if instance.output_guard.is_none() {
+        let mut output_guard = OutputGuard::new(M::OUTPUT_CHANNEL_COUNT);
+        // Keep counting from where we left off if the guard was enabled before
+        output_guard.stats = instance.output_guard_stats;
+        instance.output_guard = Some(output_guard);
+      }
+      Ok(())
+    })())
+  }
+
+  pub fn set_profiling_enabled<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    enabled: bool,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_set_profiling_enabled")?;
+      instance.profiler.enabled = enabled;
+      Ok(())
+    })())
+  }
+
+  /// Returns the length of the JSON in bytes, or 0 for a bad handle
+  pub fn get_state_json<M: DspModule>(registry: &Registry<M>, handle: u32) -> usize {
+    match registry.borrow_mut().get(handle, "module_get_state_json") {
+      Ok(instance) => {
+        let mut snapshot = snapshot_params::<M>(instance.module.params());
+        instance.macros.push_state(&mut snapshot, M::param_name);
+        common::state::write_state_json(&snapshot)
+      },
+      Err(_) => 0,
+    }
+  }
+
+  pub fn set_state_json<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    len: usize,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_set_state_json")?;
+      let state = common::state::read_state_json(len, "module_set_state_json")?;
+      let param_names: Vec<String> = (0..M::PARAM_COUNT).map(M::param_name).collect();
+      let macros = Macros::from_state(&state, &param_names)?;
+      restore_params(&mut instance.module, &state)?;
+      // State saved before macros existed leaves the current ones in place
+      if let Some(macros) = macros {
+        instance.macros = macros;
+      }
+      Ok(())
+    })())
+  }
+
+  /// Saves the current params into the active A/B slot and crossfades to the params in `slot`
+  pub fn ab_select<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    slot: usize,
+    fade_ms: f32,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_ab_select")?;
+      ffi::check_range("fade_ms", fade_ms, 0., MAX_AB_FADE_MS)?;
+      let current = instance.module.params().to_vec();
+      let fade_samples = dsp::ms_to_samples(fade_ms) as usize;
+      instance.ab.select(slot, &current, fade_samples)
+    })())
+  }
+
+  /// Writes the params in A/B `slot` as state JSON, returning its length in bytes.  Returns 0 for
+  /// a bad handle or an empty slot.
+  pub fn ab_get_slot_state_json<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    slot: usize,
+  ) -> usize {
+    let mut registry = registry.borrow_mut();
+    let Ok(instance) = registry.get(handle, "module_ab_get_slot_state_json") else {
+      return 0;
+    };
+    match instance.ab.slot(slot) {
+      Some(params) => common::state::write_state_json(&snapshot_params::<M>(params)),
+      None => 0,
+    }
+  }
+
+  /// Sets param `param_ix` to `value` starting `frame_offset` samples into the next frame
+  /// processed.  Offsets past the end of that frame land in later frames.  Events for params driven
+  /// by `AudioParam`s only last until the host next writes them.
+  pub fn schedule_param_change<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    param_ix: usize,
+    value: f32,
+    frame_offset: usize,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_schedule_param_change")?;
+      ffi::check_range("param_ix", param_ix, 0, M::PARAM_COUNT.saturating_sub(1))?;
+      let value = ffi::clamp_param("value", value, f32::MIN, f32::MAX)?;
+      instance.alloc_event_scratch();
+      instance.param_events.schedule(ParamEvent {
+        param_ix,
+        value,
+        frame_offset,
+      })
+    })())
+  }
+
+  pub fn clear_param_events<M: DspModule>(registry: &Registry<M>, handle: u32) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_clear_param_events")?;
+      instance.param_events.clear();
+      Ok(())
+    })())
+  }
+
+  /// While enabled, frames are processed in control blocks and the module's control outputs are
+  /// sampled at the end of each one.  Off by default since it splits up processing.
+  pub fn set_control_outputs_enabled<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    enabled: bool,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_set_control_outputs_enabled")?;
+      instance.control_outputs_enabled = enabled && M::CONTROL_OUTPUT_COUNT > 0;
+      if instance.control_outputs_enabled {
+        instance.alloc_event_scratch();
+      }
+      Ok(())
+    })())
+  }
+
+  /// Offsets param `param_ix` by control input `route_ix` scaled by `depth`.  The input's values
+  /// are zeroed, so the host needs to write them before the next frame.
+  pub fn set_control_route<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    route_ix: usize,
+    param_ix: usize,
+    depth: f32,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_set_control_route")?;
+      let route = ControlRoute { param_ix, depth };
+      instance
+        .control_inputs
+        .set_route(route_ix, Some(route), M::PARAM_COUNT)?;
+      instance.alloc_event_scratch();
+      Ok(())
+    })())
+  }
+
+  pub fn clear_control_route<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    route_ix: usize,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_clear_control_route")?;
+      instance
+        .control_inputs
+        .set_route(route_ix, None, M::PARAM_COUNT)
+    })())
+  }
+
+  /// Macro values range from 0 to 1.  Targets are updated at the start of the next frame.
+  pub fn set_macro_value<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    macro_ix: usize,
+    value: f32,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_set_macro_value")?;
+      instance.macros.set_value(macro_ix, value)
+    })())
+  }
+
+  /// Maps macro `macro_ix` onto param `param_ix` from `min` to `max`, see `MacroTarget`.  The
+  /// param's own value is overwritten from the next frame on.
+  #[allow(clippy::too_many_arguments)]
+  pub fn set_macro_target<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    macro_ix: usize,
+    target_ix: usize,
+    param_ix: usize,
+    min: f32,
+    max: f32,
+    curve: f32,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_set_macro_target")?;
+      let target = MacroTarget {
+        param_ix,
+        min,
+        max,
+        curve,
+      };
+      instance
+        .macros
+        .set_target(macro_ix, target_ix, Some(target), M::PARAM_COUNT)
+    })())
+  }
+
+  /// The param keeps the last value the macro set it to
+  pub fn clear_macro_target<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    macro_ix: usize,
+    target_ix: usize,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_clear_macro_target")?;
+      instance
+        .macros
+        .set_target(macro_ix, target_ix, None, M::PARAM_COUNT)
+    })())
+  }
+
+  pub fn set_scene_param<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    scene_ix: usize,
+    param_ix: usize,
+    value: f32,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_set_scene_param")?;
+      instance
+        .scenes
+        .set_param(scene_ix, param_ix, value, M::PARAM_COUNT)
+    })())
+  }
+
+  pub fn clear_scene<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    scene_ix: usize,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_clear_scene")?;
+      instance.scenes.clear(scene_ix)
+    })())
+  }
+
+  /// Glides the params in scene `scene_ix` to their values in it over `glide_ms`, starting with the
+  /// next frame.  As with A/B fades, params driven by `AudioParam`s only keep the scene's values
+  /// until the host next writes them after the glide.
+  pub fn recall_scene<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    scene_ix: usize,
+    glide_ms: f32,
+  ) -> ErrorCode {
+    ffi::status((|| {
+      let mut registry = registry.borrow_mut();
+      let instance = registry.get(handle, "module_recall_scene")?;
+      ffi::check_range("glide_ms", glide_ms, 0., MAX_SCENE_GLIDE_MS)?;
+      let glide_samples = dsp::ms_to_samples(glide_ms) as usize;
+      instance
+        .scenes
+        .recall(scene_ix, glide_samples, instance.module.params())
+    })())
+  }
+
+  /// Returns 0 for a bad handle
+  pub fn get_latency_samples<M: DspModule>(registry: &Registry<M>, handle: u32) -> u32 {
+    let mut registry = registry.borrow_mut();
+    match registry.get(handle, "module_get_latency_samples") {
+      Ok(instance) => instance.module.latency_samples() as u32,
+      Err(_) => 0,
+    }
+  }
+
+  fn buf_ptr<M: DspModule>(
+    registry: &Registry<M>,
+    handle: u32,
+    export_name: &str,
+    get_buf: fn(&mut Instance<M>) -> &mut [f32],
+  ) -> *mut f32 {
+    match registry.borrow_mut().get(handle, export_name) {
+      Ok(instance) => get_buf(instance).as_mut_ptr(),
+      Err(_) => std::ptr::null_mut(),
+    }
+  }
+
+  pub fn io_buf_ptr<M: DspModule>(registry: &Registry<M>, handle: u32) -> *mut f32 {
+    buf_ptr(registry, handle, "module_get_io_buf_ptr", |instance| {
+      instance.module.io_buffer()
+    })
+  }
+
+  pub fn param_buf_ptr<M: DspModule>(registry: &Registry<M>, handle: u32) -> *mut f32 {
+    buf_ptr(registry, handle, "module_get_param_buf_ptr", |instance| {
+      instance.module.params()
+    })
+  }
+
+  pub fn sab_ptr<M: DspModule>(registry: &Registry<M>, handle: u32) -> *mut f32 {
+    buf_ptr(registry, handle, "module_get_sab_ptr", |instance| {
+      instance.module.sab()
+    })
+  }
+
+  /// The stats live on the instance rather than the guard so that the pointer stays valid as the
+  /// guard is toggled.  They're zeroed by `module_reset` but kept when the guard is disabled.
+  pub fn output_guard_stats_ptr<M: DspModule>(registry: &Registry<M>, handle: u32) -> *mut f32 {
+    buf_ptr(
+      registry,
+      handle,
+      "module_get_output_guard_stats_ptr",
+      |instance| &mut instance.output_guard_stats,
+    )
+  }
+
+  /// `CONTROL_OUTPUT_COUNT * MAX_CONTROL_BLOCKS` long
+  pub fn control_outputs_ptr<M: DspModule>(registry: &Registry<M>, handle: u32) -> *mut f32 {
+    buf_ptr(
+      registry,
+      handle,
+      "module_get_control_outputs_ptr",
+      |instance| &mut instance.control_outputs,
+    )
+  }
+
+  /// `MAX_CONTROL_ROUTES * MAX_CONTROL_BLOCKS` long
+  pub fn control_inputs_ptr<M: DspModule>(registry: &Registry<M>, handle: u32) -> *mut f32 {
+    buf_ptr(
+      registry,
+      handle,
+      "module_get_control_inputs_ptr",
+      |instance| &mut instance.control_inputs.values,
+    )
+  }
+
+  /// `PROFILING_STATS_LEN` long.  Like the output guard's stats, these are kept while profiling is
+  /// disabled and zeroed by `module_reset`.
+  pub fn profiling_stats_ptr<M: DspModule>(registry: &Registry<M>, handle: u32) -> *mut f32 {
+    buf_ptr(
+      registry,
+      handle,
+      "module_get_profiling_stats_ptr",
+      |instance| instance.profiler.stats_mut(),
+    )
+  }
+}
+
+/// Exports the standard module entry points for `$module`, which must implement `DspModule`.  Can
+/// only be invoked once per crate since the exported symbol names are fixed.
+#[macro_export]
+macro_rules! export_module {
+  ($module:ty) => {
+    mod __module_api_exports {
+      use super::*;
+      use $crate::exports;
+
+      #[cfg(target_arch = "wasm32")]
+      extern "C" {
+        fn log_err(ptr: *const u8, len: usize);
+
+        fn now() -> f64;
+      }
+
+      #[cfg(not(target_arch = "wasm32"))]
+      use $crate::common::native::{log_err, now as host_now};
+
+      #[cfg(target_arch = "wasm32")]
+      fn host_now() -> f64 { unsafe { now() } }
+
+      ::std::thread_local! {
+        static REGISTRY: exports::Registry<$module> =
+          ::std::cell::RefCell::new($crate::registry::HandleRegistry::new());
+      }
+
+      static CAPABILITIES: [u32; $crate::CAPABILITIES_LEN] = $crate::capabilities::<$module>();
+
+      #[no_mangle]
+      pub extern "C" fn module_create() -> u32 {
+        $crate::common::set_raw_panic_hook(log_err);
+        REGISTRY.with(exports::create)
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_destroy(handle: u32) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::destroy(registry, handle))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_reset(handle: u32) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::reset(registry, handle))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_process(
+        handle: u32,
+        frame_size: usize,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::process(registry, handle, frame_size, host_now))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_io_buf_ptr(handle: u32) -> *mut f32 {
+        REGISTRY.with(|registry| exports::io_buf_ptr(registry, handle))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_param_buf_ptr(handle: u32) -> *mut f32 {
+        REGISTRY.with(|registry| exports::param_buf_ptr(registry, handle))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_sab_ptr(handle: u32) -> *mut f32 {
+        REGISTRY.with(|registry| exports::sab_ptr(registry, handle))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_set_output_guard_enabled(
+        handle: u32,
+        enabled: bool,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::set_output_guard_enabled(registry, handle, enabled))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_output_guard_stats_ptr(handle: u32) -> *mut f32 {
+        REGISTRY.with(|registry| exports::output_guard_stats_ptr(registry, handle))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_set_profiling_enabled(
+        handle: u32,
+        enabled: bool,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::set_profiling_enabled(registry, handle, enabled))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_profiling_stats_ptr(handle: u32) -> *mut f32 {
+        REGISTRY.with(|registry| exports::profiling_stats_ptr(registry, handle))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_state_json(handle: u32) -> usize {
+        REGISTRY.with(|registry| exports::get_state_json(registry, handle))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_set_state_json(
+        handle: u32,
+        len: usize,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::set_state_json(registry, handle, len))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_ab_select(
+        handle: u32,
+        slot: usize,
+        fade_ms: f32,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::ab_select(registry, handle, slot, fade_ms))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_ab_get_slot_state_json(handle: u32, slot: usize) -> usize {
+        REGISTRY.with(|registry| exports::ab_get_slot_state_json(registry, handle, slot))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_schedule_param_change(
+        handle: u32,
+        param_ix: usize,
+        value: f32,
+        frame_offset: usize,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| {
+          exports::schedule_param_change(registry, handle, param_ix, value, frame_offset)
+        })
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_clear_param_events(handle: u32) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::clear_param_events(registry, handle))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_latency_samples(handle: u32) -> u32 {
+        REGISTRY.with(|registry| exports::get_latency_samples(registry, handle))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_set_control_outputs_enabled(
+        handle: u32,
+        enabled: bool,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::set_control_outputs_enabled(registry, handle, enabled))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_control_outputs_ptr(handle: u32) -> *mut f32 {
+        REGISTRY.with(|registry| exports::control_outputs_ptr(registry, handle))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_set_control_route(
+        handle: u32,
+        route_ix: usize,
+        param_ix: usize,
+        depth: f32,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY
+          .with(|registry| exports::set_control_route(registry, handle, route_ix, param_ix, depth))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_clear_control_route(
+        handle: u32,
+        route_ix: usize,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::clear_control_route(registry, handle, route_ix))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_control_inputs_ptr(handle: u32) -> *mut f32 {
+        REGISTRY.with(|registry| exports::control_inputs_ptr(registry, handle))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_set_macro_value(
+        handle: u32,
+        macro_ix: usize,
+        value: f32,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::set_macro_value(registry, handle, macro_ix, value))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_set_macro_target(
+        handle: u32,
+        macro_ix: usize,
+        target_ix: usize,
+        param_ix: usize,
+        min: f32,
+        max: f32,
+        curve: f32,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| {
+          exports::set_macro_target(
+            registry, handle, macro_ix, target_ix, param_ix, min, max, curve,
+          )
+        })
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_clear_macro_target(
+        handle: u32,
+        macro_ix: usize,
+        target_ix: usize,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::clear_macro_target(registry, handle, macro_ix, target_ix))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_set_scene_param(
+        handle: u32,
+        scene_ix: usize,
+        param_ix: usize,
+        value: f32,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY
+          .with(|registry| exports::set_scene_param(registry, handle, scene_ix, param_ix, value))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_clear_scene(
+        handle: u32,
+        scene_ix: usize,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::clear_scene(registry, handle, scene_ix))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_recall_scene(
+        handle: u32,
+        scene_ix: usize,
+        glide_ms: f32,
+      ) -> $crate::common::ffi::ErrorCode {
+        REGISTRY.with(|registry| exports::recall_scene(registry, handle, scene_ix, glide_ms))
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_capabilities_ptr() -> *const u32 { CAPABILITIES.as_ptr() }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_capabilities_len() -> usize { CAPABILITIES.len() }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_output_layouts_ptr() -> *const u32 {
+        <$module as $crate::DspModule>::OUTPUT_LAYOUTS.as_ptr() as *const u32
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_name_ptr() -> *const u8 {
+        <$module as $crate::DspModule>::NAME.as_ptr()
+      }
+
+      #[no_mangle]
+      pub extern "C" fn module_get_name_len() -> usize {
+        <$module as $crate::DspModule>::NAME.len()
+      }
+    }
+  };
+}
+
+/// Outputs its one param as a constant signal
+#[cfg(test)]
+struct ParamEcho {
+  io: Vec<f32>,
+  params: [f32; 1],
+}
+
+#[cfg(test)]
+impl DspModule for ParamEcho {
+  const CHANNEL_COUNT: usize = 1;
+  const NAME: &'static str = "param_echo";
+  const PARAM_COUNT: usize = 1;
+  const VERSION: u32 = 0;
+
+  fn new() -> Self {
+    ParamEcho {
+      io: vec![0.; MAX_FRAME_SIZE],
+      params: [0.],
+    }
+  }
+
+  fn io_buffer(&mut self) -> &mut [f32] { &mut self.io }
+
+  fn params(&mut self) -> &mut [f32] { &mut self.params }
+
+  fn process(&mut self, frame_size: usize) -> FfiResult {
+    self.io[..frame_size].fill(self.params[0]);
+    Ok(())
+  }
+
+  fn reset(&mut self) {}
+}
+
+#[test]
+fn param_events_land_mid_frame() {
+  let registry: exports::Registry<ParamEcho> = RefCell::new(HandleRegistry::new());
+  let handle = exports::create(&registry);
+  let schedule =
+    |value, frame_offset| exports::schedule_param_change(&registry, handle, 0, value, frame_offset);
+  assert_eq!(schedule(1., 0), ErrorCode::Ok);
+  assert_eq!(schedule(2., 50), ErrorCode::Ok);
+  assert_eq!(schedule(3., 100), ErrorCode::Ok);
+  assert_eq!(schedule(4., 130), ErrorCode::Ok);
+  assert_eq!(
+    exports::schedule_param_change(&registry, handle, 1, 0., 0),
+    ErrorCode::ParamOutOfRange
+  );
+
+  let process = |frame_size| {
+    assert_eq!(
+      exports::process(&registry, handle, frame_size, || 0.),
+      ErrorCode::Ok
+    );
+    let ptr = exports::io_buf_ptr(&registry, handle);
+    unsafe { std::slice::from_raw_parts(ptr, frame_size) }.to_vec()
+  };
+  let out = process(128);
+  assert!(out[..50].iter().all(|&x| x == 1.));
+  assert!(out[50..100].iter().all(|&x| x == 2.));
+  assert!(out[100..].iter().all(|&x| x == 3.));
+
+  let out = process(128);
+  assert!(out[..2].iter().all(|&x| x == 3.));
+  assert!(out[2..].iter().all(|&x| x == 4.));
+}
+
+#[test]
+fn control_inputs_modulate_params_per_control_block() {
+  let registry: exports::Registry<ParamEcho> = RefCell::new(HandleRegistry::new());
+  let handle = exports::create(&registry);
+  assert_eq!(
+    exports::set_control_route(&registry, handle, 0, 0, 2.),
+    ErrorCode::Ok
+  );
+  let inputs = exports::control_inputs_ptr(&registry, handle);
+  for block_ix in 0..4 {
+    unsafe { *inputs.add(block_ix) = block_ix as f32 };
+  }
+  let params = exports::param_buf_ptr(&registry, handle);
+  unsafe { *params = 10. };
+
+  assert_eq!(
+    exports::process(&registry, handle, 64, || 0.),
+    ErrorCode::Ok
+  );
+  let ptr = exports::io_buf_ptr(&registry, handle);
+  let out = unsafe { std::slice::from_raw_parts(ptr, 64) };
+  for (block_ix, block) in out.chunks(CONTROL_BLOCK_SIZE).enumerate() {
+    assert!(block.iter().all(|&x| x == 10. + 2. * block_ix as f32));
+  }
+  assert_eq!(unsafe { *params }, 10.);
+}
@@ -0,0 +1,252 @@
+//! Macro controls: single values from 0 to 1 that each drive several of a module's params at once,
+//! so that a performer can make complex timbre changes with one knob.
+//!
+//! Each macro has up to `MAX_MACRO_TARGETS` targets.  A target maps the macro's value onto the
+//! range from its `min` to its `max` through a power curve and overwrites its param with the result
+//! at the start of every frame, before param events and control signals are applied.  Setting `min`
+//! above `max` inverts the mapping.
+//!
+//! Macros are included in the module's state so that they're saved with presets.  Targets are
+//! stored by param name rather than index, like the params themselves, under keys of the form
+//! `macro_<n>.<param name>.<min|max|curve>`, and each macro's value under `macro_<n>.value`.
+
+use common::{
+  ffi::{self, ErrorCode, FfiResult},
+  state::{StateSnapshot, StateValue},
+};
+
+pub const MAX_MACROS: usize = 8;
+pub const MAX_MACRO_TARGETS: usize = 8;
+/// A curve of 1 raises the macro's value to this power before mapping it onto the target's range,
+/// and a curve of -1 to its inverse
+const MAX_CURVE_EXPONENT: f32 = 4.;
+const STATE_KEY_PREFIX: &str = "macro_";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MacroTarget {
+  pub param_ix: usize,
+  pub min: f32,
+  pub max: f32,
+  /// From -1 to 1.  0 is linear, positive values spend more of the macro's travel near `min`, and
+  /// negative values more of it near `max`.
+  pub curve: f32,
+}
+
+impl MacroTarget {
+  /// Maps a macro value from 0 to 1 onto the target's range
+  pub fn map(&self, value: f32) -> f32 {
+    let exponent = MAX_CURVE_EXPONENT.powf(self.curve);
+    self.min + (self.max - self.min) * value.powf(exponent)
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Macro {
+  value: f32,
+  targets: [Option<MacroTarget>; MAX_MACRO_TARGETS],
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Macros {
+  macros: [Macro; MAX_MACROS],
+}
+
+impl Macros {
+  pub fn set_value(&mut self, macro_ix: usize, value: f32) -> FfiResult {
+    ffi::check_range("macro_ix", macro_ix, 0, MAX_MACROS - 1)?;
+    self.macros[macro_ix].value = ffi::clamp_param("value", value, 0., 1.)?;
+    Ok(())
+  }
+
+  /// Sets or clears target `target_ix` of macro `macro_ix`.  Each param can only be targeted once
+  /// per macro.
+  pub fn set_target(
+    &mut self,
+    macro_ix: usize,
+    target_ix: usize,
+    target: Option<MacroTarget>,
+    param_count: usize,
+  ) -> FfiResult {
+    ffi::check_range("macro_ix", macro_ix, 0, MAX_MACROS - 1)?;
+    ffi::check_range("target_ix", target_ix, 0, MAX_MACRO_TARGETS - 1)?;
+    let Some(mut target) = target else {
+      self.macros[macro_ix].targets[target_ix] = None;
+      return Ok(());
+    };
+
+    ffi::check_range(
+      "param_ix",
+      target.param_ix,
+      0,
+      param_count.saturating_sub(1),
+    )?;
+    target.min = ffi::clamp_param("min", target.min, f32::MIN, f32::MAX)?;
+    target.max = ffi::clamp_param("max", target.max, f32::MIN, f32::MAX)?;
+    target.curve = ffi::clamp_param("curve", target.curve, -1., 1.)?;
+    let targets = &mut self.macros[macro_ix].targets;
+    let already_targeted = targets.iter().enumerate().any(|(other_ix, other)| {
+      other_ix != target_ix && other.is_some_and(|other| other.param_ix == target.param_ix)
+    });
+    if already_targeted {
+      return Err(ffi::set_last_error(
+        ErrorCode::ParamOutOfRange,
+        &format!(
+          "param {} is already targeted by macro {macro_ix}",
+          target.param_ix
+        ),
+      ));
+    }
+    targets[target_ix] = Some(target);
+    Ok(())
+  }
+
+  /// Overwrites every targeted param with its macro's mapped value
+  pub fn apply(&self, params: &mut [f32]) {
+    for m in &self.macros {
+      for target in m.targets.iter().flatten() {
+        params[target.param_ix] = target.map(m.value);
+      }
+    }
+  }
+
+  pub fn push_state(&self, snapshot: &mut StateSnapshot, param_name: impl Fn(usize) -> String) {
+    for (macro_ix, m) in self.macros.iter().enumerate() {
+      snapshot.push_number(&format!("{STATE_KEY_PREFIX}{macro_ix}.value"), m.value);
+      for target in m.targets.iter().flatten() {
+        let prefix = format!(
+          "{STATE_KEY_PREFIX}{macro_ix}.{}",
+          param_name(target.param_ix)
+        );
+        snapshot.push_number(&format!("{prefix}.min"), target.min);
+        snapshot.push_number(&format!("{prefix}.max"), target.max);
+        snapshot.push_number(&format!("{prefix}.curve"), target.curve);
+      }
+    }
+  }
+
+  /// Reads macros saved with `push_state`, or returns `None` if `state` has none, as is the case
+  /// for state saved before macros existed.  Targets of params that no longer exist are dropped.
+  pub fn from_state(state: &StateSnapshot, param_names: &[String]) -> FfiResult<Option<Self>> {
+    let mut macros = Macros::default();
+    let mut found = false;
+    for (key, val) in &state.entries {
+      let Some((macro_ix, field)) = key
+        .strip_prefix(STATE_KEY_PREFIX)
+        .and_then(|key| key.split_once('.'))
+        .and_then(|(macro_ix, field)| Some((macro_ix.parse::<usize>().ok()?, field)))
+      else {
+        continue;
+      };
+      let &StateValue::Number(val) = val else {
+        return Err(ffi::set_last_error(
+          ErrorCode::ParamOutOfRange,
+          &format!("expected {key} to be a number"),
+        ));
+      };
+      found = true;
+      let val = val as f32;
+
+      if field == "value" {
+        macros.set_value(macro_ix, val)?;
+        continue;
+      }
+      let Some((param_name, target_field)) = field.rsplit_once('.') else {
+        continue;
+      };
+      let Some(param_ix) = param_names.iter().position(|name| name == param_name) else {
+        continue;
+      };
+      ffi::check_range("macro_ix", macro_ix, 0, MAX_MACROS - 1)?;
+      let targets = &macros.macros[macro_ix].targets;
+      let target_ix = targets
+        .iter()
+        .position(|target| target.is_some_and(|target| target.param_ix == param_ix))
+        .or_else(|| targets.iter().position(Option::is_none))
+        .ok_or_else(|| {
+          ffi::set_last_error(
+            ErrorCode::ParamOutOfRange,
+            &format!("macro {macro_ix} has more than {MAX_MACRO_TARGETS} targets"),
+          )
+        })?;
+      let mut target = targets[target_ix].unwrap_or(MacroTarget {
+        param_ix,
+        min: 0.,
+        max: 1.,
+        curve: 0.,
+      });
+      match target_field {
+        "min" => target.min = val,
+        "max" => target.max = val,
+        "curve" => target.curve = val,
+        _ => continue,
+      }
+      macros.set_target(macro_ix, target_ix, Some(target), param_names.len())?;
+    }
+    Ok(found.then_some(macros))
+  }
+}
+
+#[test]
+fn targets_follow_macro_through_their_curves() {
+  let mut macros = Macros::default();
+  let target = |param_ix, min, max, curve| {
+    Some(MacroTarget {
+      param_ix,
+      min,
+      max,
+      curve,
+    })
+  };
+  macros
+    .set_target(0, 0, target(0, 100., 200., 0.), 3)
+    .unwrap();
+  macros.set_target(0, 1, target(1, 1., 0., 1.), 3).unwrap();
+  assert!(macros.set_target(0, 2, target(0, 0., 1., 0.), 3).is_err());
+  assert!(macros.set_target(0, 2, target(3, 0., 1., 0.), 3).is_err());
+  assert!(macros.set_target(0, 2, target(2, 0., 1., 2.), 3).is_ok());
+  macros.set_value(0, 0.5).unwrap();
+
+  let mut params = [0., 0., 5.];
+  macros.apply(&mut params);
+  assert_eq!(params[0], 150.);
+  // Inverted and curved by the max exponent
+  assert!((params[1] - (1. - 0.5f32.powf(MAX_CURVE_EXPONENT))).abs() < 1e-6);
+  // The curve was clamped to 1
+  assert!((params[2] - 0.5f32.powf(MAX_CURVE_EXPONENT)).abs() < 1e-6);
+}
+
+#[test]
+fn macros_round_trip_through_state() {
+  let param_names = vec!["cutoff".to_owned(), "resonance".to_owned()];
+  let mut macros = Macros::default();
+  macros.set_value(3, 0.25).unwrap();
+  macros
+    .set_target(
+      3,
+      5,
+      Some(MacroTarget {
+        param_ix: 1,
+        min: 0.2,
+        max: 0.8,
+        curve: -0.5,
+      }),
+      2,
+    )
+    .unwrap();
+
+  let mut snapshot = StateSnapshot::default();
+  macros.push_state(&mut snapshot, |param_ix| param_names[param_ix].clone());
+  let json = snapshot.to_json();
+  assert!(json.contains("\"macro_3.resonance.curve\":-0.5"), "{json}");
+  let restored = Macros::from_state(&StateSnapshot::from_json(&json).unwrap(), &param_names)
+    .unwrap()
+    .unwrap();
+  let mut params = [0.; 2];
+  let mut restored_params = [0.; 2];
+  macros.apply(&mut params);
+  restored.apply(&mut restored_params);
+  assert_eq!(params, restored_params);
+
+  let old_state = StateSnapshot::from_json("{\"cutoff\":1}").unwrap();
+  assert_eq!(Macros::from_state(&old_state, &param_names).unwrap(), None);
+}
@@ -0,0 +1,106 @@
+//! Param changes scheduled to land on a specific sample rather than at the start of a frame.
+//!
+//! Events are kept sorted by their offset from the start of the next frame to be processed.  When a
+//! frame is processed, it's split into sub-blocks at the offsets of the events that land inside it
+//! and each event is applied right before the sub-block starting at its offset.  Events past the
+//! end of the frame carry over to later frames.
+
+use std::collections::VecDeque;
+
+use common::ffi::{self, FfiResult};
+
+/// Scheduling more events than this without processing any of them is an error
+pub const MAX_PENDING_PARAM_EVENTS: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParamEvent {
+  pub param_ix: usize,
+  pub value: f32,
+  /// Samples from the start of the next frame to be processed
+  pub frame_offset: usize,
+}
+
+#[derive(Default)]
+pub struct ParamEventQueue {
+  events: VecDeque<ParamEvent>,
+}
+
+impl ParamEventQueue {
+  /// Events scheduled for the same offset are applied in the order they were scheduled
+  pub fn schedule(&mut self, event: ParamEvent) -> FfiResult {
+    ffi::check_range(
+      "pending param event count",
+      self.events.len() + 1,
+      0,
+      MAX_PENDING_PARAM_EVENTS,
+    )?;
+    let ix = self
+      .events
+      .partition_point(|other| other.frame_offset <= event.frame_offset);
+    self.events.insert(ix, event);
+    Ok(())
+  }
+
+  /// Offset of the next event if it lands before `frame_size`
+  pub fn next_offset_within(&self, frame_size: usize) -> Option<usize> {
+    self
+      .events
+      .front()
+      .map(|event| event.frame_offset)
+      .filter(|&offset| offset < frame_size)
+  }
+
+  /// Applies every event at or before `frame_offset` to `params`
+  pub fn apply_due(&mut self, frame_offset: usize, params: &mut [f32]) {
+    while let Some(event) = self.events.front() {
+      if event.frame_offset > frame_offset {
+        break;
+      }
+      if let Some(param) = params.get_mut(event.param_ix) {
+        *param = event.value;
+      }
+      self.events.pop_front();
+    }
+  }
+
+  /// Moves the remaining events' offsets to be relative to the frame after this one
+  pub fn advance(&mut self, frame_size: usize) {
+    for event in &mut self.events {
+      event.frame_offset = event.frame_offset.saturating_sub(frame_size);
+    }
+  }
+
+  pub fn len(&self) -> usize { self.events.len() }
+
+  pub fn is_empty(&self) -> bool { self.events.is_empty() }
+
+  pub fn clear(&mut self) { self.events.clear(); }
+}
+
+#[test]
+fn applies_events_in_offset_order() {
+  let mut queue = ParamEventQueue::default();
+  let event = |param_ix, value, frame_offset| ParamEvent {
+    param_ix,
+    value,
+    frame_offset,
+  };
+  queue.schedule(event(0, 3., 200)).unwrap();
+  queue.schedule(event(0, 1., 10)).unwrap();
+  queue.schedule(event(1, 2., 10)).unwrap();
+  queue.schedule(event(0, 4., 10)).unwrap();
+
+  let mut params = [0.; 2];
+  assert_eq!(queue.next_offset_within(128), Some(10));
+  queue.apply_due(9, &mut params);
+  assert_eq!(params, [0., 0.]);
+  queue.apply_due(10, &mut params);
+  assert_eq!(params, [4., 2.]);
+  assert_eq!(queue.next_offset_within(128), None);
+
+  queue.advance(128);
+  assert_eq!(queue.next_offset_within(128), Some(72));
+  queue.apply_due(72, &mut params);
+  assert_eq!(params, [3., 2.]);
+  assert!(queue.is_empty());
+}
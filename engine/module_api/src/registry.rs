@@ -0,0 +1,136 @@
+//! Maps the opaque `u32` handles given out to JS to module instances.
+//!
+//! Handles pack a slot index in the low 16 bits and the slot's generation in the high 16 bits.
+//! The generation is bumped every time a slot is freed, so a handle that outlives its instance is
+//! rejected instead of silently aliasing whatever instance reuses the slot.  0 is never a valid
+//! handle.
+
+use common::ffi::{self, ErrorCode, FfiResult};
+
+const INDEX_BITS: u32 = 16;
+const INDEX_MASK: u32 = (1 << INDEX_BITS) - 1;
+pub const MAX_INSTANCES: usize = 1 << INDEX_BITS;
+
+struct Slot<T> {
+  generation: u16,
+  value: Option<Box<T>>,
+}
+
+pub struct HandleRegistry<T> {
+  slots: Vec<Slot<T>>,
+  free_list: Vec<u32>,
+}
+
+impl<T> Default for HandleRegistry<T> {
+  fn default() -> Self {
+    HandleRegistry {
+      slots: Vec::new(),
+      free_list: Vec::new(),
+    }
+  }
+}
+
+impl<T> HandleRegistry<T> {
+  pub const fn new() -> Self {
+    HandleRegistry {
+      slots: Vec::new(),
+      free_list: Vec::new(),
+    }
+  }
+
+  fn build_handle(index: u32, generation: u16) -> u32 {
+    ((generation as u32) << INDEX_BITS) | index
+  }
+
+  /// Stores `value` and returns its handle, or 0 if the registry is full.  Values are boxed so that
+  /// pointers into them handed out to JS stay valid as the registry grows.
+  pub fn insert(&mut self, value: Box<T>) -> u32 {
+    if let Some(index) = self.free_list.pop() {
+      let slot = &mut self.slots[index as usize];
+      slot.value = Some(value);
+      return Self::build_handle(index, slot.generation);
+    }
+
+    if self.slots.len() >= MAX_INSTANCES {
+      ffi::set_last_error(
+        ErrorCode::Unsupported,
+        &format!("Can't create more than {MAX_INSTANCES} module instances"),
+      );
+      return 0;
+    }
+    let index = self.slots.len() as u32;
+    // Generations start at 1 so that no valid handle is ever 0
+    self.slots.push(Slot {
+      generation: 1,
+      value: Some(value),
+    });
+    Self::build_handle(index, 1)
+  }
+
+  fn slot_index(&self, handle: u32, export_name: &str) -> FfiResult<usize> {
+    let index = (handle & INDEX_MASK) as usize;
+    let generation = (handle >> INDEX_BITS) as u16;
+    match self.slots.get(index) {
+      Some(slot) if slot.generation == generation && slot.value.is_some() => Ok(index),
+      _ => Err(ffi::set_last_error(
+        ErrorCode::InvalidHandle,
+        &format!("{export_name}: unknown or destroyed module handle {handle}"),
+      )),
+    }
+  }
+
+  pub fn get(&mut self, handle: u32, export_name: &str) -> FfiResult<&mut T> {
+    let index = self.slot_index(handle, export_name)?;
+    Ok(self.slots[index].value.as_deref_mut().unwrap())
+  }
+
+  /// Drops the instance behind `handle` and invalidates the handle
+  pub fn remove(&mut self, handle: u32, export_name: &str) -> FfiResult {
+    let index = self.slot_index(handle, export_name)?;
+    let slot = &mut self.slots[index];
+    slot.value = None;
+    // Skip 0 on wraparound so that handles stay non-zero
+    slot.generation = slot.generation.checked_add(1).unwrap_or(1);
+    self.free_list.push(index as u32);
+    Ok(())
+  }
+
+  pub fn len(&self) -> usize { self.slots.len() - self.free_list.len() }
+
+  pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+#[test]
+fn stale_handles_are_rejected() {
+  let mut registry = HandleRegistry::new();
+  let a = registry.insert(Box::new(1));
+  let b = registry.insert(Box::new(2));
+  assert_ne!(a, 0);
+  assert_ne!(a, b);
+  assert_eq!(*registry.get(b, "test").unwrap(), 2);
+
+  registry.remove(a, "test").unwrap();
+  assert_eq!(
+    registry.get(a, "test").err(),
+    Some(ErrorCode::InvalidHandle)
+  );
+  assert_eq!(
+    registry.remove(a, "test").err(),
+    Some(ErrorCode::InvalidHandle)
+  );
+
+  // The freed slot is reused under a new generation
+  let c = registry.insert(Box::new(3));
+  assert_ne!(c, a);
+  assert_eq!(c & INDEX_MASK, a & INDEX_MASK);
+  assert_eq!(
+    registry.get(a, "test").err(),
+    Some(ErrorCode::InvalidHandle)
+  );
+  assert_eq!(*registry.get(c, "test").unwrap(), 3);
+  assert_eq!(registry.len(), 2);
+  assert_eq!(
+    registry.get(0, "test").err(),
+    Some(ErrorCode::InvalidHandle)
+  );
+}
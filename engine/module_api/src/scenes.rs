@@ -0,0 +1,140 @@
+//! Scenes: snapshots of selected params that can be recalled with a glide, for switching between
+//! sounds during a live set.
+//!
+//! Each instance holds up to `MAX_SCENES` scenes, one per MIDI program.  A scene is just a list of
+//! param values; naming scenes and grouping them across modules happens on the JS side, which
+//! recalls a scene on every module that has it at once.  Recalling a scene ramps each of its params
+//! linearly from its current value to the scene's over the glide time.  While a glide is in
+//! progress the ramped values override whatever the host writes, like an A/B fade.
+
+use common::ffi::{self, FfiResult};
+
+pub const MAX_SCENES: usize = 128;
+pub const MAX_SCENE_GLIDE_MS: f32 = 60_000.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct GlidingParam {
+  param_ix: usize,
+  from: f32,
+  to: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Scenes {
+  /// `(param_ix, value)` pairs of each scene in the order they were set
+  scenes: Vec<Vec<(usize, f32)>>,
+  glide: Vec<GlidingParam>,
+  glide_pos: usize,
+  glide_len: usize,
+}
+
+impl Default for Scenes {
+  fn default() -> Self {
+    Scenes {
+      scenes: vec![Vec::new(); MAX_SCENES],
+      glide: Vec::new(),
+      glide_pos: 0,
+      glide_len: 0,
+    }
+  }
+}
+
+impl Scenes {
+  /// Adds param `param_ix` to scene `scene_ix` with `value`, replacing its value if it's already
+  /// in the scene
+  pub fn set_param(
+    &mut self,
+    scene_ix: usize,
+    param_ix: usize,
+    value: f32,
+    param_count: usize,
+  ) -> FfiResult {
+    ffi::check_range("scene_ix", scene_ix, 0, MAX_SCENES - 1)?;
+    ffi::check_range("param_ix", param_ix, 0, param_count.saturating_sub(1))?;
+    let value = ffi::clamp_param("value", value, f32::MIN, f32::MAX)?;
+    let scene = &mut self.scenes[scene_ix];
+    match scene.iter_mut().find(|(ix, _)| *ix == param_ix) {
+      Some((_, existing)) => *existing = value,
+      None => scene.push((param_ix, value)),
+    }
+    Ok(())
+  }
+
+  pub fn clear(&mut self, scene_ix: usize) -> FfiResult {
+    ffi::check_range("scene_ix", scene_ix, 0, MAX_SCENES - 1)?;
+    self.scenes[scene_ix].clear();
+    Ok(())
+  }
+
+  /// Starts gliding the params in scene `scene_ix` from their values in `params` to the scene's
+  /// over `glide_samples`.  Replaces any glide already in progress, starting from wherever it had
+  /// got to.  Recalling an empty scene does nothing.
+  pub fn recall(&mut self, scene_ix: usize, glide_samples: usize, params: &[f32]) -> FfiResult {
+    ffi::check_range("scene_ix", scene_ix, 0, MAX_SCENES - 1)?;
+    let scene = &self.scenes[scene_ix];
+    if scene.is_empty() {
+      return Ok(());
+    }
+
+    self.glide.clear();
+    self
+      .glide
+      .extend(scene.iter().map(|&(param_ix, to)| GlidingParam {
+        param_ix,
+        from: params[param_ix],
+        to,
+      }));
+    self.glide_pos = 0;
+    self.glide_len = glide_samples;
+    Ok(())
+  }
+
+  pub fn is_gliding(&self) -> bool { !self.glide.is_empty() }
+
+  /// Advances the glide by `frame_size` samples and writes the ramped params for the frame into
+  /// `params`
+  pub fn tick(&mut self, frame_size: usize, params: &mut [f32]) {
+    if self.glide.is_empty() {
+      return;
+    }
+
+    self.glide_pos = (self.glide_pos + frame_size).min(self.glide_len);
+    let t = if self.glide_len == 0 {
+      1.
+    } else {
+      self.glide_pos as f32 / self.glide_len as f32
+    };
+    for param in &self.glide {
+      params[param.param_ix] = param.from + (param.to - param.from) * t;
+    }
+    if self.glide_pos == self.glide_len {
+      self.glide.clear();
+    }
+  }
+}
+
+#[test]
+fn recall_glides_scene_params() {
+  let mut scenes = Scenes::default();
+  scenes.set_param(5, 1, 10., 3).unwrap();
+  scenes.set_param(5, 2, -4., 3).unwrap();
+  scenes.set_param(5, 1, 20., 3).unwrap();
+  assert!(scenes.set_param(5, 3, 0., 3).is_err());
+  assert!(scenes.set_param(MAX_SCENES, 0, 0., 3).is_err());
+
+  let mut params = [1., 0., 0.];
+  scenes.recall(4, 256, &params).unwrap();
+  assert!(!scenes.is_gliding());
+  scenes.recall(5, 256, &params).unwrap();
+  scenes.tick(128, &mut params);
+  assert_eq!(params, [1., 10., -2.]);
+  scenes.tick(128, &mut params);
+  assert_eq!(params, [1., 20., -4.]);
+  assert!(!scenes.is_gliding());
+
+  // Without a glide, the scene is applied on the next frame
+  scenes.set_param(0, 0, 3., 3).unwrap();
+  scenes.recall(0, 0, &params).unwrap();
+  scenes.tick(128, &mut params);
+  assert_eq!(params[0], 3.);
+}
@@ -1,3 +1,4 @@
+use common::ffi::{self, ErrorCode};
 use dsp::{band_splitter::BandSplitter, FRAME_SIZE};
 
 static mut INPUT_BUFFER: [f32; FRAME_SIZE] = [0.0; FRAME_SIZE];
@@ -7,10 +8,11 @@ static mut MID_BAND_OUTPUT_BUFFER: [f32; FRAME_SIZE] = [0.0; FRAME_SIZE];
 static mut HIGH_BAND_OUTPUT_BUFFER: [f32; FRAME_SIZE] = [0.0; FRAME_SIZE];
 
 #[no_mangle]
-pub extern "C" fn init() {
+pub extern "C" fn init() -> ErrorCode {
   unsafe {
     BAND_SPLITTER = Box::into_raw(Box::new(BandSplitter::new()));
   }
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -34,7 +36,11 @@ pub extern "C" fn get_high_output_buf_ptr() -> *mut f32 {
 }
 
 #[no_mangle]
-pub extern "C" fn process() {
+pub extern "C" fn process() -> ErrorCode {
+  if unsafe { BAND_SPLITTER.is_null() } {
+    return ffi::set_last_error(ErrorCode::NotInitialized, "`process` called before `init`");
+  }
+
   let splitter = unsafe { &mut *BAND_SPLITTER };
   unsafe {
     splitter.apply_frame(
@@ -44,4 +50,5 @@ pub extern "C" fn process() {
       &mut HIGH_BAND_OUTPUT_BUFFER,
     );
   }
+  ErrorCode::Ok
 }
@@ -0,0 +1,244 @@
+//! Benchmarks for the hot paths of the DSP modules, run natively with:
+//!
+//! ```sh
+//! cargo bench -p native_host
+//! ```
+//!
+//! Every iteration processes a single frame of `FRAME_SIZE` samples, so throughput is reported in
+//! frames per second.  A frame needs to be processed in under `FRAME_SIZE / sample_rate` seconds
+//! (~2.9ms at 44.1kHz) to keep up in realtime.
+
+use std::rc::Rc;
+
+use adsr::{Adsr, AdsrStep, EarlyReleaseConfig, GateStatus, RampFn, RENDERED_BUFFER_SIZE};
+use compressor::MultibandCompressor;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dsp::{
+  filters::biquad::{BiquadFilter, BiquadFilterBank2D, FilterMode},
+  FRAME_SIZE,
+};
+use native_host::{build_test_signal, compress_frame, default_lookahead_samples};
+use wavetable::fm::{
+  effects::{moog::MoogFilter, Effect},
+  ParamSource,
+};
+
+/// Matches the vocoder's filter bank dimensions
+const BAND_COUNT: usize = 32;
+const FILTERS_PER_BAND: usize = 4;
+
+/// A few seconds of test signal, processed one frame at a time and wrapping around at the end
+fn build_frames() -> Vec<[f32; FRAME_SIZE]> {
+  build_test_signal(dsp::DEFAULT_SAMPLE_RATE as u32, 6.)
+    .chunks_exact(FRAME_SIZE)
+    .map(|chunk| chunk.try_into().unwrap())
+    .collect()
+}
+
+fn compressor(c: &mut Criterion) {
+  let frames = build_frames();
+  let mut group = c.benchmark_group("compressor");
+  group.throughput(Throughput::Elements(1));
+
+  let max_lookahead_samples = (dsp::sample_rate() / 10.) as usize;
+  for lookahead_samples in [
+    0,
+    max_lookahead_samples / 30,
+    default_lookahead_samples(),
+    max_lookahead_samples,
+  ] {
+    group.bench_with_input(
+      BenchmarkId::new("lookahead_samples", lookahead_samples),
+      &lookahead_samples,
+      |b, &lookahead_samples| {
+        let mut compressor = Box::new(MultibandCompressor::default());
+        let mut frame_ix = 0;
+        b.iter(|| {
+          let mut frame = frames[frame_ix % frames.len()];
+          frame_ix += 1;
+          compress_frame(&mut compressor, &mut frame, lookahead_samples).unwrap();
+          black_box(frame)
+        })
+      },
+    );
+  }
+  group.finish();
+}
+
+fn build_band_filters() -> [[BiquadFilter; BAND_COUNT]; FILTERS_PER_BAND] {
+  let q_factors = dsp::filters::biquad::compute_higher_order_biquad_q_factors(FILTERS_PER_BAND * 2);
+  let mut filters = [[BiquadFilter::default(); BAND_COUNT]; FILTERS_PER_BAND];
+  for band_ix in 0..BAND_COUNT {
+    let freq = 80. * 2f32.powf(band_ix as f32 / 4.);
+    for (filter_ix, q) in q_factors.iter().enumerate().take(FILTERS_PER_BAND) {
+      filters[filter_ix][band_ix] = BiquadFilter::new(FilterMode::Bandpass, *q, 0., freq, 0.);
+    }
+  }
+  filters
+}
+
+/// Runs every band's chain of filters over the frame, once with each band processed in series and
+/// once with all bands processed in parallel by `BiquadFilterBank2D`
+fn biquad(c: &mut Criterion) {
+  let frames = build_frames();
+  let mut group = c.benchmark_group("biquad");
+  group.throughput(Throughput::Elements(1));
+
+  group.bench_function(
+    BenchmarkId::new("chains", format!("{BAND_COUNT}x{FILTERS_PER_BAND}")),
+    |b| {
+      let mut filters = build_band_filters();
+      let mut frame_ix = 0;
+      b.iter(|| {
+        let frame = &frames[frame_ix % frames.len()];
+        frame_ix += 1;
+        let mut output = [0.; FRAME_SIZE];
+        for band_ix in 0..BAND_COUNT {
+          for (sample_ix, &sample) in frame.iter().enumerate() {
+            let mut sample = sample;
+            for chain in filters.iter_mut() {
+              sample = chain[band_ix].apply(sample);
+            }
+            output[sample_ix] += sample;
+          }
+        }
+        black_box(output)
+      })
+    },
+  );
+
+  group.bench_function(
+    BenchmarkId::new("bank_2d", format!("{BAND_COUNT}x{FILTERS_PER_BAND}")),
+    |b| {
+      let mut bank = Box::new(BiquadFilterBank2D::new(&build_band_filters()));
+      let mut frame_ix = 0;
+      b.iter(|| {
+        let frame = &frames[frame_ix % frames.len()];
+        frame_ix += 1;
+        let mut output = [0.; FRAME_SIZE];
+        let mut band_outputs = [0.; BAND_COUNT];
+        for (sample_ix, &sample) in frame.iter().enumerate() {
+          band_outputs.fill(sample);
+          for depth in 0..FILTERS_PER_BAND {
+            bank.apply_simd(&mut band_outputs, depth);
+          }
+          output[sample_ix] = band_outputs.iter().sum();
+        }
+        black_box(output)
+      })
+    },
+  );
+  group.finish();
+}
+
+fn build_adsr(loop_point: Option<f32>) -> Adsr {
+  let steps = vec![
+    AdsrStep {
+      x: 0.,
+      y: 0.,
+      ramper: RampFn::Linear,
+    },
+    AdsrStep {
+      x: 0.1,
+      y: 1.,
+      ramper: RampFn::Exponential { exponent: 0.8 },
+    },
+    AdsrStep {
+      x: 0.5,
+      y: 0.6,
+      ramper: RampFn::Exponential { exponent: 1.2 },
+    },
+    AdsrStep {
+      x: 1.,
+      y: 0.,
+      ramper: RampFn::Linear,
+    },
+  ];
+  let mut adsr = Adsr::new(
+    steps,
+    loop_point,
+    dsp::sample_rate(),
+    None,
+    0.5,
+    Rc::new([0.; RENDERED_BUFFER_SIZE]),
+    EarlyReleaseConfig::default(),
+    false,
+  );
+  adsr.render();
+  adsr
+}
+
+fn adsr(c: &mut Criterion) {
+  let mut group = c.benchmark_group("adsr");
+  group.throughput(Throughput::Elements(1));
+
+  // Looping keeps the envelope from freezing at the release point, so every frame is rendered
+  group.bench_function("render_frame", |b| {
+    let mut adsr = build_adsr(Some(0.));
+    adsr.gate(0.);
+    b.iter(|| {
+      adsr.render_frame(1., 0., 0.);
+      black_box(adsr.get_cur_frame_output());
+    })
+  });
+
+  group.bench_function("render_frame_log_scale", |b| {
+    let mut adsr = build_adsr(Some(0.));
+    adsr.log_scale = true;
+    adsr.gate(0.);
+    b.iter(|| {
+      adsr.render_frame(1., 0., 0.);
+      black_box(adsr.get_cur_frame_output());
+    })
+  });
+
+  group.bench_function("render_frame_release", |b| {
+    let mut adsr = build_adsr(None);
+    b.iter(|| {
+      if adsr.gate_status == GateStatus::Done {
+        adsr.gate(0.);
+        adsr.ungate();
+      }
+      adsr.render_frame(1., 0., 0.);
+      black_box(adsr.get_cur_frame_output());
+    })
+  });
+  group.finish();
+}
+
+fn moog(c: &mut Criterion) {
+  let frames = build_frames();
+  let mut group = c.benchmark_group("moog");
+  group.throughput(Throughput::Elements(1));
+
+  for resonance in [0., 10.] {
+    group.bench_with_input(
+      BenchmarkId::new("resonance", resonance),
+      &resonance,
+      |b, &resonance| {
+        let mut filter = MoogFilter::new(
+          ParamSource::new_constant(1200.),
+          ParamSource::new_constant(resonance),
+          ParamSource::new_constant(2.),
+        );
+        let rendered_params = [
+          [1200.; FRAME_SIZE],
+          [resonance; FRAME_SIZE],
+          [2.; FRAME_SIZE],
+        ];
+        let base_frequencies = [440.; FRAME_SIZE];
+        let mut frame_ix = 0;
+        b.iter(|| {
+          let mut frame = frames[frame_ix % frames.len()];
+          frame_ix += 1;
+          filter.apply_all(&rendered_params, &base_frequencies, &mut frame);
+          black_box(frame)
+        })
+      },
+    );
+  }
+  group.finish();
+}
+
+criterion_group!(benches, compressor, biquad, adsr, moog);
+criterion_main!(benches);
@@ -0,0 +1,76 @@
+//! Shared between the `native_host` binary and the benchmarks in `benches/`
+
+use compressor::{process_compressor, MultibandCompressor};
+use dsp::FRAME_SIZE;
+
+/// Bursts of low, mid, and high tones at a range of levels so that each band of the compressor
+/// has something to act on
+pub fn build_test_signal(sample_rate: u32, seconds: f32) -> Vec<f32> {
+  const BURST_SECONDS: f32 = 0.5;
+  const LEVELS_DB: [f32; 4] = [-36., -24., -12., -3.];
+  const FREQUENCIES: [f32; 3] = [60., 1000., 6000.];
+
+  let len = (sample_rate as f32 * seconds) as usize;
+  (0..len)
+    .map(|i| {
+      let t = i as f32 / sample_rate as f32;
+      let burst_ix = (t / BURST_SECONDS) as usize;
+      let gain = dsp::db_to_gain(LEVELS_DB[burst_ix % LEVELS_DB.len()]);
+      let freq = FREQUENCIES[(burst_ix / LEVELS_DB.len()) % FREQUENCIES.len()];
+      (t * freq * std::f32::consts::TAU).sin() * gain
+    })
+    .collect()
+}
+
+/// Lookahead used by the compressor node by default
+pub fn default_lookahead_samples() -> usize { (dsp::sample_rate() / 10. / 3.) as usize }
+
+/// Processes one frame of at most `FRAME_SIZE` samples in place using the default settings of the
+/// compressor node
+pub fn compress_frame(
+  compressor: &mut MultibandCompressor,
+  frame: &mut [f32],
+  lookahead_samples: usize,
+) -> Result<(), String> {
+  debug_assert!(frame.len() <= FRAME_SIZE);
+  compressor.input_buffer[..frame.len()].copy_from_slice(frame);
+  let status = process_compressor(
+    compressor,
+    1.,
+    1.,
+    1.,
+    1.,
+    1.,
+    1.,
+    3.,
+    250.,
+    3.,
+    250.,
+    3.,
+    250.,
+    false,
+    false,
+    false,
+    -40.8,
+    -41.8,
+    -40.8,
+    -35.5,
+    -30.2,
+    -33.8,
+    1.,
+    1.,
+    1.,
+    444.,
+    66.7,
+    66.7,
+    30.,
+    lookahead_samples,
+    30.,
+    frame.len(),
+  );
+  if status != common::ffi::ErrorCode::Ok {
+    return Err(format!("{:?}: {}", status, common::ffi::get_last_error()));
+  }
+  frame.copy_from_slice(&compressor.output_buffer[..frame.len()]);
+  Ok(())
+}
@@ -0,0 +1,219 @@
+//! Runs DSP modules natively rather than in the browser so that they can be debugged and profiled
+//! with regular tooling.  Audio is read from a WAV file or generated as a test signal, processed
+//! through the multiband compressor in the same frame-sized chunks that the audio worklet uses,
+//! and then written to a WAV file and/or played on the default output device.
+//!
+//! Usage:
+//!
+//! ```sh
+//! cargo run --release -p native_host -- [--input in.wav] [--output out.wav] [--seconds 8]
+//! cargo run --release -p native_host --features playback -- --input in.wav --play
+//! ```
+//!
+//! Output is written at the input's sample rate unless `--sample-rate` is given, in which case
+//! it's converted with the windowed-sinc resampler after processing.
+//!
+//! Output is written as 32-bit float by default.  `--bit-depth 16` or `--bit-depth 24` writes PCM
+//! instead, dithered with the curve selected by `--noise-shaping` (`flat`, `first-order`, or
+//! `e-weighted`; defaults to `flat`).
+
+use std::{borrow::Cow, process::exit, time::Instant};
+
+use compressor::MultibandCompressor;
+use dsp::{
+  resampler::{resample_interleaved, ResamplerQuality},
+  FRAME_SIZE,
+};
+use native_host::{build_test_signal, compress_frame, default_lookahead_samples};
+use wav::{NoiseShaping, SampleFormat, WavSpec};
+
+#[cfg(feature = "playback")]
+mod playback;
+
+const DEFAULT_TEST_SIGNAL_SECONDS: f32 = 8.;
+
+struct Args {
+  input: Option<String>,
+  output: Option<String>,
+  play: bool,
+  seconds: f32,
+  output_format: SampleFormat,
+  noise_shaping: NoiseShaping,
+  output_sample_rate: Option<u32>,
+}
+
+fn usage() -> ! {
+  eprintln!(
+    "Usage: native_host [--input <in.wav>] [--output <out.wav>] [--play] [--seconds <len>] \
+     [--sample-rate <rate>] [--bit-depth <16|24|32>] [--noise-shaping \
+     <flat|first-order|e-weighted>]\n\nA test signal of `--seconds` length is generated if no \
+     input is provided."
+  );
+  exit(1)
+}
+
+fn parse_args() -> Args {
+  let mut args = Args {
+    input: None,
+    output: None,
+    play: false,
+    seconds: DEFAULT_TEST_SIGNAL_SECONDS,
+    output_format: SampleFormat::Float32,
+    noise_shaping: NoiseShaping::Flat,
+    output_sample_rate: None,
+  };
+  let mut raw_args = std::env::args().skip(1);
+  while let Some(arg) = raw_args.next() {
+    match arg.as_str() {
+      "--input" => args.input = Some(raw_args.next().unwrap_or_else(|| usage())),
+      "--output" => args.output = Some(raw_args.next().unwrap_or_else(|| usage())),
+      "--play" => args.play = true,
+      "--seconds" =>
+        args.seconds = match raw_args.next().and_then(|val| val.parse().ok()) {
+          Some(seconds) if seconds > 0. => seconds,
+          _ => usage(),
+        },
+      "--sample-rate" =>
+        args.output_sample_rate = match raw_args.next().and_then(|val| val.parse().ok()) {
+          Some(rate) if (wav::MIN_SAMPLE_RATE..=wav::MAX_SAMPLE_RATE).contains(&rate) => Some(rate),
+          _ => usage(),
+        },
+      "--bit-depth" =>
+        args.output_format = match raw_args.next().as_deref() {
+          Some("16") => SampleFormat::Pcm16,
+          Some("24") => SampleFormat::Pcm24,
+          Some("32") => SampleFormat::Float32,
+          _ => usage(),
+        },
+      "--noise-shaping" =>
+        args.noise_shaping = match raw_args.next().as_deref() {
+          Some("flat") => NoiseShaping::Flat,
+          Some("first-order") => NoiseShaping::FirstOrder,
+          Some("e-weighted") => NoiseShaping::EWeighted,
+          _ => usage(),
+        },
+      _ => usage(),
+    }
+  }
+
+  if args.output.is_none() && !args.play {
+    eprintln!("At least one of `--output` or `--play` must be provided");
+    usage();
+  }
+  if args.play && !cfg!(feature = "playback") {
+    eprintln!("`--play` requires building with `--features playback`");
+    exit(1);
+  }
+  args
+}
+
+fn load_input(args: &Args) -> (WavSpec, Vec<f32>) {
+  let path = match &args.input {
+    Some(path) => path,
+    None => {
+      let spec = WavSpec {
+        channel_count: 1,
+        sample_rate: dsp::DEFAULT_SAMPLE_RATE as u32,
+        format: SampleFormat::Float32,
+      };
+      return (spec, build_test_signal(spec.sample_rate, args.seconds));
+    },
+  };
+
+  let data = std::fs::read(path).unwrap_or_else(|err| {
+    eprintln!("Error reading {path}: {err}");
+    exit(1)
+  });
+  wav::decode(&data).unwrap_or_else(|err| {
+    eprintln!("Error decoding {path}: {err}");
+    exit(1)
+  })
+}
+
+/// Processes one channel of `samples` in place using the default settings of the compressor node
+fn compress_channel(compressor: &mut MultibandCompressor, samples: &mut [f32]) {
+  let lookahead_samples = default_lookahead_samples();
+  for frame in samples.chunks_mut(FRAME_SIZE) {
+    if let Err(err) = compress_frame(compressor, frame, lookahead_samples) {
+      eprintln!("Error processing compressor: {err}");
+      exit(1);
+    }
+  }
+}
+
+fn main() {
+  let args = parse_args();
+  let (spec, mut samples) = load_input(&args);
+  if !dsp::set_sample_rate(spec.sample_rate as f32) {
+    eprintln!("Unsupported sample rate: {}", spec.sample_rate);
+    exit(1);
+  }
+
+  // The compressor is mono, so each channel is de-interleaved and processed separately
+  let channel_count = spec.channel_count as usize;
+  let start = Instant::now();
+  for channel_ix in 0..channel_count {
+    let mut channel: Vec<f32> = samples
+      .iter()
+      .skip(channel_ix)
+      .step_by(channel_count)
+      .copied()
+      .collect();
+    let mut compressor = Box::new(MultibandCompressor::default());
+    compress_channel(&mut compressor, &mut channel);
+    for (sample, processed) in samples
+      .iter_mut()
+      .skip(channel_ix)
+      .step_by(channel_count)
+      .zip(channel)
+    {
+      *sample = processed;
+    }
+  }
+  let elapsed = start.elapsed();
+  let audio_seconds = samples.len() as f64 / channel_count as f64 / spec.sample_rate as f64;
+  println!(
+    "Processed {:.2}s of audio in {:.2}ms ({:.1}x realtime)",
+    audio_seconds,
+    elapsed.as_secs_f64() * 1000.,
+    audio_seconds / elapsed.as_secs_f64()
+  );
+
+  if let Some(path) = &args.output {
+    let out_spec = WavSpec {
+      format: args.output_format,
+      sample_rate: args.output_sample_rate.unwrap_or(spec.sample_rate),
+      ..spec
+    };
+    // Resampled before dithering so that the dither noise isn't filtered by the resampler
+    let out_samples = if out_spec.sample_rate == spec.sample_rate {
+      Cow::Borrowed(&samples[..])
+    } else {
+      Cow::Owned(resample_interleaved(
+        &samples,
+        channel_count,
+        spec.sample_rate as f64,
+        out_spec.sample_rate as f64,
+        ResamplerQuality::Sinc,
+      ))
+    };
+    let encoded = wav::encode_dithered(&out_spec, &out_samples, args.noise_shaping, 0)
+      .unwrap_or_else(|err| {
+        eprintln!("Error encoding output: {err}");
+        exit(1)
+      });
+    if let Err(err) = std::fs::write(path, encoded) {
+      eprintln!("Error writing {path}: {err}");
+      exit(1);
+    }
+    println!("Wrote {path}");
+  }
+
+  #[cfg(feature = "playback")]
+  if args.play {
+    if let Err(err) = playback::play(&spec, samples) {
+      eprintln!("Error playing audio: {err}");
+      exit(1);
+    }
+  }
+}
@@ -0,0 +1,68 @@
+//! Plays rendered audio through the default output device with `cpal`
+
+use std::{sync::mpsc, time::Duration};
+
+use cpal::{
+  traits::{DeviceTrait, HostTrait, StreamTrait},
+  SampleFormat, SampleRate,
+};
+use wav::WavSpec;
+
+/// Blocks until all of `samples`, which are interleaved according to `spec`, have been played
+pub fn play(spec: &WavSpec, samples: Vec<f32>) -> Result<(), String> {
+  let device = cpal::default_host()
+    .default_output_device()
+    .ok_or_else(|| String::from("No output device available"))?;
+  // The audio has already been rendered at this sample rate, so the device has to support it
+  let sample_rate = SampleRate(spec.sample_rate);
+  let config = device
+    .supported_output_configs()
+    .map_err(|err| err.to_string())?
+    .find(|config| {
+      config.sample_format() == SampleFormat::F32
+        && config.min_sample_rate() <= sample_rate
+        && config.max_sample_rate() >= sample_rate
+    })
+    .ok_or_else(|| {
+      format!(
+        "The default output device doesn't support {}hz f32 output",
+        spec.sample_rate
+      )
+    })?
+    .with_sample_rate(sample_rate)
+    .config();
+
+  let src_channel_count = spec.channel_count as usize;
+  let dst_channel_count = config.channels as usize;
+  let frame_count = samples.len() / src_channel_count;
+  let mut frame_ix = 0;
+  let (done_tx, done_rx) = mpsc::channel();
+  let stream = device
+    .build_output_stream(
+      &config,
+      move |out: &mut [f32], _| {
+        for out_frame in out.chunks_mut(dst_channel_count) {
+          for (channel_ix, sample) in out_frame.iter_mut().enumerate() {
+            // Mono audio is played on every channel, and extra source channels are dropped
+            let src_channel_ix = channel_ix.min(src_channel_count - 1);
+            *sample = samples
+              .get(frame_ix * src_channel_count + src_channel_ix)
+              .copied()
+              .unwrap_or(0.);
+          }
+          frame_ix += 1;
+        }
+        if frame_ix >= frame_count {
+          let _ = done_tx.send(());
+        }
+      },
+      |err| eprintln!("Output stream error: {err}"),
+    )
+    .map_err(|err| err.to_string())?;
+  stream.play().map_err(|err| err.to_string())?;
+
+  let len = Duration::from_secs_f64(frame_count as f64 / spec.sample_rate as f64);
+  // The timeout is just a fallback in case the stream stops calling back
+  let _ = done_rx.recv_timeout(len + Duration::from_secs(1));
+  Ok(())
+}
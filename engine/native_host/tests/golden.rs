@@ -0,0 +1,192 @@
+//! Golden-audio regression tests.  Known input signals are rendered through DSP modules and the
+//! output is compared against buffers stored in `tests/golden/`, so that refactors (SIMD,
+//! smoothing, etc.) can be verified not to change how things sound.
+//!
+//! If a change to the output is intentional, re-generate the golden buffers with:
+//!
+//! ```sh
+//! UPDATE_GOLDEN=1 cargo test -p native_host --test golden
+//! ```
+//!
+//! and listen to the new files before committing them.
+
+use std::path::PathBuf;
+
+use compressor::MultibandCompressor;
+use dsp::{band_splitter::BandSplitter, FRAME_SIZE};
+use native_host::{compress_frame, default_lookahead_samples};
+use wav::{SampleFormat, WavSpec};
+use wavetable::fm::{
+  effects::{moog::MoogFilter, Effect},
+  ParamSource,
+};
+
+/// 64 frames; a bit under 200ms at 44.1kHz
+const SIGNAL_LEN: usize = FRAME_SIZE * 64;
+/// Max absolute difference allowed between any rendered sample and its golden counterpart.  Leaves
+/// room for differences in floating point operation order but is well below anything audible.
+const TOLERANCE: f32 = 1e-4;
+
+fn impulse() -> Vec<f32> {
+  let mut signal = vec![0.; SIGNAL_LEN];
+  signal[0] = 1.;
+  signal
+}
+
+/// Exponential sine sweep from 20Hz to 20kHz
+fn sweep() -> Vec<f32> {
+  const START_FREQ: f32 = 20.;
+  const END_FREQ: f32 = 20_000.;
+
+  let sample_rate = dsp::sample_rate();
+  let duration = SIGNAL_LEN as f32 / sample_rate;
+  let k = (END_FREQ / START_FREQ).ln();
+  (0..SIGNAL_LEN)
+    .map(|i| {
+      let t = i as f32 / sample_rate;
+      let phase =
+        std::f32::consts::TAU * START_FREQ * duration / k * ((t / duration * k).exp() - 1.);
+      phase.sin() * 0.5
+    })
+    .collect()
+}
+
+/// 1kHz tone bursts stepping through a range of levels
+fn bursts() -> Vec<f32> {
+  const BURST_LEN: usize = SIGNAL_LEN / 8;
+  const LEVELS_DB: [f32; 4] = [-3., -36., -12., -24.];
+
+  (0..SIGNAL_LEN)
+    .map(|i| {
+      let t = i as f32 / dsp::sample_rate();
+      let gain = dsp::db_to_gain(LEVELS_DB[(i / BURST_LEN) % LEVELS_DB.len()]);
+      (t * 1000. * std::f32::consts::TAU).sin() * gain
+    })
+    .collect()
+}
+
+fn golden_path(name: &str) -> PathBuf {
+  PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    .join("tests")
+    .join("golden")
+    .join(format!("{name}.wav"))
+}
+
+/// Compares interleaved `rendered` output to the stored golden buffer with the given name, or
+/// overwrites the golden buffer if `UPDATE_GOLDEN` is set
+fn check_golden(name: &str, channel_count: u16, rendered: &[f32]) {
+  assert!(
+    rendered.iter().all(|sample| sample.is_finite()),
+    "{name}: rendered output contains non-finite samples"
+  );
+
+  let path = golden_path(name);
+  if std::env::var_os("UPDATE_GOLDEN").is_some() {
+    let spec = WavSpec {
+      channel_count,
+      sample_rate: dsp::sample_rate() as u32,
+      format: SampleFormat::Float32,
+    };
+    std::fs::write(&path, wav::encode(&spec, rendered).unwrap()).unwrap();
+    return;
+  }
+
+  let data = std::fs::read(&path).unwrap_or_else(|err| {
+    panic!(
+      "{name}: error reading golden buffer at {}: {err}; run with `UPDATE_GOLDEN=1` to create it",
+      path.display()
+    )
+  });
+  let (spec, golden) = wav::decode(&data).unwrap();
+  assert_eq!(spec.channel_count, channel_count, "{name}: channel count");
+  assert_eq!(golden.len(), rendered.len(), "{name}: length");
+
+  let (worst_ix, worst_diff) = golden
+    .iter()
+    .zip(rendered)
+    .map(|(golden, rendered)| (golden - rendered).abs())
+    .enumerate()
+    .fold(
+      (0, 0.),
+      |acc, (ix, diff)| if diff > acc.1 { (ix, diff) } else { acc },
+    );
+  assert!(
+    worst_diff <= TOLERANCE,
+    "{name}: output differs from golden buffer by {worst_diff} at sample {} of channel {}",
+    worst_ix / channel_count as usize,
+    worst_ix % channel_count as usize
+  );
+}
+
+fn render_compressor(input: &[f32]) -> Vec<f32> {
+  let mut compressor = Box::new(MultibandCompressor::default());
+  let mut output = input.to_owned();
+  for frame in output.chunks_mut(FRAME_SIZE) {
+    compress_frame(&mut compressor, frame, default_lookahead_samples()).unwrap();
+  }
+  output
+}
+
+fn render_moog(input: &[f32]) -> Vec<f32> {
+  const CUTOFF: f32 = 1200.;
+  const RESONANCE: f32 = 2.;
+  const DRIVE: f32 = 2.;
+
+  let mut filter = MoogFilter::new(
+    ParamSource::new_constant(CUTOFF),
+    ParamSource::new_constant(RESONANCE),
+    ParamSource::new_constant(DRIVE),
+  );
+  let rendered_params = [
+    [CUTOFF; FRAME_SIZE],
+    [RESONANCE; FRAME_SIZE],
+    [DRIVE; FRAME_SIZE],
+  ];
+  let base_frequencies = [440.; FRAME_SIZE];
+  let mut output = input.to_owned();
+  for frame in output.chunks_exact_mut(FRAME_SIZE) {
+    filter.apply_all(
+      &rendered_params,
+      &base_frequencies,
+      frame.try_into().unwrap(),
+    );
+  }
+  output
+}
+
+/// Returns the low, mid, and high bands interleaved
+fn render_band_splitter(input: &[f32]) -> Vec<f32> {
+  let mut splitter = BandSplitter::new();
+  let mut low = vec![0.; input.len()];
+  let mut mid = vec![0.; input.len()];
+  let mut high = vec![0.; input.len()];
+  splitter.apply(input, &mut low, &mut mid, &mut high);
+  low
+    .into_iter()
+    .zip(mid)
+    .zip(high)
+    .flat_map(|((low, mid), high)| [low, mid, high])
+    .collect()
+}
+
+#[test]
+fn compressor_golden() {
+  check_golden("compressor_bursts", 1, &render_compressor(&bursts()));
+  check_golden("compressor_sweep", 1, &render_compressor(&sweep()));
+}
+
+#[test]
+fn moog_golden() {
+  check_golden("moog_impulse", 1, &render_moog(&impulse()));
+  check_golden("moog_sweep", 1, &render_moog(&sweep()));
+}
+
+#[test]
+fn band_splitter_golden() {
+  check_golden(
+    "band_splitter_impulse",
+    3,
+    &render_band_splitter(&impulse()),
+  );
+  check_golden("band_splitter_sweep", 3, &render_band_splitter(&sweep()));
+}
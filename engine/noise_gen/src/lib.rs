@@ -1,4 +1,7 @@
-use common::rng;
+use common::{
+  ffi::{self, ErrorCode},
+  rng,
+};
 use rand::prelude::*;
 
 #[derive(Clone, Copy)]
@@ -16,29 +19,53 @@ static mut SMOOTHING_COEFFICIENT: f32 = 0.;
 static mut QUANTIZATION_FACTOR: usize = 0;
 
 #[no_mangle]
-pub unsafe extern "C" fn set_noise_type(noise_type: u32, update_freq_samples: u32) {
+pub unsafe extern "C" fn set_noise_type(noise_type: u32, update_freq_samples: u32) -> ErrorCode {
   NOISE_TYPE = match noise_type {
     0 => NoiseType::White,
-    1 => NoiseType::Pink,
-    2 => NoiseType::Brown,
+    // Pink and brown noise aren't implemented yet
+    1 | 2 =>
+      return ffi::set_last_error(
+        ErrorCode::Unsupported,
+        &format!("Noise type {noise_type} isn't supported yet"),
+      ),
     3 => NoiseType::SteppedRandom {
       update_freq_samples,
     },
-    _ => panic!("Invalid noise type: {}", noise_type),
-  }
+    _ =>
+      return ffi::set_last_error(
+        ErrorCode::ParamOutOfRange,
+        &format!("Invalid noise type: {noise_type}"),
+      ),
+  };
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn set_gain(gain: f32) { GAIN = gain; }
+pub unsafe extern "C" fn set_gain(gain: f32) -> ErrorCode {
+  match ffi::clamp_param("gain", gain, f32::MIN, f32::MAX) {
+    Ok(gain) => {
+      GAIN = gain;
+      ErrorCode::Ok
+    },
+    Err(code) => code,
+  }
+}
 
 #[no_mangle]
-pub unsafe extern "C" fn set_smoothing_coefficient(smoothing_cofficient: f32) {
-  SMOOTHING_COEFFICIENT = smoothing_cofficient;
+pub unsafe extern "C" fn set_smoothing_coefficient(smoothing_cofficient: f32) -> ErrorCode {
+  match ffi::check_range("smoothing_coefficient", smoothing_cofficient, 0., 1.) {
+    Ok(()) => {
+      SMOOTHING_COEFFICIENT = smoothing_cofficient;
+      ErrorCode::Ok
+    },
+    Err(code) => code,
+  }
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn set_quantization_factor(quantize_factor: usize) {
+pub unsafe extern "C" fn set_quantization_factor(quantize_factor: usize) -> ErrorCode {
   QUANTIZATION_FACTOR = quantize_factor;
+  ErrorCode::Ok
 }
 
 fn gen_white_noise() -> f32 { rng().gen_range(-1., 1.) }
@@ -0,0 +1,31 @@
+//! Articulation flags that change how notes are played back without changing the notes
+//! themselves.  Each note's flags are stored as a bitmask; see `NoteLines::articulations`.
+
+/// The note is released after `STACCATO_LENGTH_SCALE` of its length
+pub const ARTICULATION_STACCATO: u8 = 1 << 0;
+/// The note's velocity is raised by `ACCENT_VELOCITY_BOOST`
+pub const ARTICULATION_ACCENT: u8 = 1 << 1;
+/// The note is tied to the note starting where it ends on the same line, as if legato was enabled
+/// for just that pair of notes
+pub const ARTICULATION_TIE: u8 = 1 << 2;
+pub const ARTICULATION_ALL: u8 = ARTICULATION_STACCATO | ARTICULATION_ACCENT | ARTICULATION_TIE;
+
+pub const STACCATO_LENGTH_SCALE: f64 = 0.5;
+pub const ACCENT_VELOCITY_BOOST: f32 = 0.25;
+
+/// Returns the beat at which a note spanning from `start_point` to `end_point` should be released
+pub fn release_beat(start_point: f64, end_point: f64, articulation: u8) -> f64 {
+  if articulation & ARTICULATION_STACCATO != 0 {
+    start_point + (end_point - start_point) * STACCATO_LENGTH_SCALE
+  } else {
+    end_point
+  }
+}
+
+pub fn articulated_velocity(velocity: f32, articulation: u8) -> f32 {
+  if articulation & ARTICULATION_ACCENT != 0 {
+    (velocity + ACCENT_VELOCITY_BOOST).min(1.)
+  } else {
+    velocity
+  }
+}
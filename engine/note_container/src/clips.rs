@@ -0,0 +1,524 @@
+//! Clips are named note patterns of a fixed length in beats that loop while they're playing, like
+//! the clips in Ableton Live's session view.  Each clip's notes are stored in a `NoteLines` so that
+//! they can be edited with the same exports as the notes in the MIDI editor.
+//!
+//! A `ClipLauncher` holds the clips for one track.  Only one of them plays at a time; launching a
+//! clip stops whichever one is playing.  Clips are launched and stopped on the next multiple of the
+//! launcher's quantization so that they stay in time with the transport.
+//!
+//! Playback is pulled in consecutive windows of beats with `ClipLauncher::playback_events`, which
+//! also moves clips between states as the windows pass the beats they were queued for.
+//!
+//! Each clip can have a follow action which is evaluated once it's played through a set number of
+//! times, such as launching the next clip or a random one.  Chains of follow actions are resolved
+//! while pulling events, so whole arrangements can play out without the UI stepping in.
+
+use rand::prelude::*;
+use rand_pcg::Pcg32;
+
+use crate::note_lines::NoteLines;
+
+/// What a clip does once it's played through `Clip::follow_action_repeats` times
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FollowAction {
+  /// Keeps looping until it's stopped or another clip is launched
+  None,
+  /// Launches the clip after it, wrapping around to the first clip
+  PlayNext,
+  /// Launches a random clip other than itself if there are any
+  PlayRandom,
+  Stop,
+}
+
+/// Must match `FollowAction` in `src/midiEditor/clips.ts`
+pub const FOLLOW_ACTION_NONE: u8 = 0;
+pub const FOLLOW_ACTION_PLAY_NEXT: u8 = 1;
+pub const FOLLOW_ACTION_PLAY_RANDOM: u8 = 2;
+pub const FOLLOW_ACTION_STOP: u8 = 3;
+
+impl FollowAction {
+  pub fn from_code(code: u8) -> Option<Self> {
+    match code {
+      FOLLOW_ACTION_NONE => Some(FollowAction::None),
+      FOLLOW_ACTION_PLAY_NEXT => Some(FollowAction::PlayNext),
+      FOLLOW_ACTION_PLAY_RANDOM => Some(FollowAction::PlayRandom),
+      FOLLOW_ACTION_STOP => Some(FollowAction::Stop),
+      _ => None,
+    }
+  }
+}
+
+pub struct Clip {
+  pub name: String,
+  pub length_beats: f64,
+  /// Boxed so that pointers to it handed out through the FFI stay valid as clips are added
+  pub notes: Box<NoteLines>,
+  pub follow_action: FollowAction,
+  /// Number of times the clip plays through before its follow action is evaluated.  Treated as 1
+  /// if it's 0.
+  pub follow_action_repeats: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClipState {
+  Stopped,
+  /// Starts playing at `launch_beat`
+  Queued {
+    launch_beat: f64,
+  },
+  /// Has been playing since `start_beat`, which is the start of its first loop
+  Playing {
+    start_beat: f64,
+  },
+  /// Keeps playing until `stop_beat`, at which point all of its held notes are released
+  Stopping {
+    start_beat: f64,
+    stop_beat: f64,
+  },
+}
+
+/// Must match `ClipStateType` in `src/midiEditor/clips.ts`
+pub const CLIP_STATE_STOPPED: u32 = 0;
+pub const CLIP_STATE_QUEUED: u32 = 1;
+pub const CLIP_STATE_PLAYING: u32 = 2;
+pub const CLIP_STATE_STOPPING: u32 = 3;
+
+impl ClipState {
+  /// Returns the state's type along with the beat at which it next changes, or the beat at which
+  /// the clip started playing if it's playing until stopped.  The beat is 0 for stopped clips.
+  pub fn encode(&self) -> (u32, f64) {
+    match *self {
+      ClipState::Stopped => (CLIP_STATE_STOPPED, 0.),
+      ClipState::Queued { launch_beat } => (CLIP_STATE_QUEUED, launch_beat),
+      ClipState::Playing { start_beat } => (CLIP_STATE_PLAYING, start_beat),
+      ClipState::Stopping { stop_beat, .. } => (CLIP_STATE_STOPPING, stop_beat),
+    }
+  }
+
+  /// Returns the beat at which the clip starts its first loop and the beat at which it stops, if
+  /// it's playing or going to play
+  fn active_range(&self) -> Option<(f64, f64)> {
+    match *self {
+      ClipState::Stopped => None,
+      ClipState::Queued { launch_beat } => Some((launch_beat, f64::INFINITY)),
+      ClipState::Playing { start_beat } => Some((start_beat, f64::INFINITY)),
+      ClipState::Stopping {
+        start_beat,
+        stop_beat,
+      } => Some((start_beat, stop_beat)),
+    }
+  }
+}
+
+/// A note attack or release from one of the clips in a launcher
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipEvent {
+  pub clip_ix: usize,
+  pub is_attack: bool,
+  pub line_ix: usize,
+  pub beat: f64,
+  pub velocity: f32,
+}
+
+pub struct ClipLauncher {
+  pub clips: Vec<Clip>,
+  pub states: Vec<ClipState>,
+  /// Clips are launched and stopped on multiples of this many beats.  If it's 0, they're launched
+  /// and stopped immediately.
+  pub quantization_beats: f64,
+  /// Picks clips for `FollowAction::PlayRandom`.  Re-seeded on reset so that follow actions play
+  /// out the same way every time the transport is started.
+  rng: Pcg32,
+  seed: u64,
+}
+
+impl ClipLauncher {
+  pub fn new(quantization_beats: f64, seed: u64) -> Self {
+    ClipLauncher {
+      clips: Vec::new(),
+      states: Vec::new(),
+      quantization_beats,
+      rng: Pcg32::seed_from_u64(seed),
+      seed,
+    }
+  }
+
+  /// Returns the index of the new clip
+  pub fn add_clip(&mut self, name: String, length_beats: f64, line_count: usize) -> usize {
+    self.clips.push(Clip {
+      name,
+      length_beats,
+      notes: Box::new(NoteLines::new(line_count)),
+      follow_action: FollowAction::None,
+      follow_action_repeats: 1,
+    });
+    self.states.push(ClipState::Stopped);
+    self.clips.len() - 1
+  }
+
+  /// Removes the clip without releasing any notes it's holding, so it should be stopped first
+  pub fn remove_clip(&mut self, clip_ix: usize) {
+    self.clips.remove(clip_ix);
+    self.states.remove(clip_ix);
+  }
+
+  /// Returns the first multiple of the quantization at or after `beat`
+  pub fn quantize(&self, beat: f64) -> f64 {
+    if self.quantization_beats <= 0. {
+      return beat;
+    }
+    (beat / self.quantization_beats).ceil() * self.quantization_beats
+  }
+
+  /// Queues the clip to start playing at the next quantized beat, stopping any other clip that's
+  /// playing at that point.  Launching a clip that's already playing or queued does nothing, and
+  /// launching a clip that's stopping cancels the stop.
+  pub fn launch(&mut self, clip_ix: usize, cur_beat: f64) {
+    self.launch_at(clip_ix, self.quantize(cur_beat));
+  }
+
+  fn launch_at(&mut self, clip_ix: usize, launch_beat: f64) {
+    match self.states[clip_ix] {
+      ClipState::Queued { .. } | ClipState::Playing { .. } => return,
+      ClipState::Stopping {
+        start_beat,
+        stop_beat,
+      } if stop_beat > launch_beat => self.states[clip_ix] = ClipState::Playing { start_beat },
+      ClipState::Stopping { .. } | ClipState::Stopped =>
+        self.states[clip_ix] = ClipState::Queued { launch_beat },
+    }
+
+    for (other_ix, state) in self.states.iter_mut().enumerate() {
+      if other_ix == clip_ix {
+        continue;
+      }
+      *state = match *state {
+        ClipState::Queued { .. } => ClipState::Stopped,
+        ClipState::Playing { start_beat } => ClipState::Stopping {
+          start_beat,
+          stop_beat: launch_beat,
+        },
+        ClipState::Stopping {
+          start_beat,
+          stop_beat,
+        } => ClipState::Stopping {
+          start_beat,
+          stop_beat: stop_beat.min(launch_beat),
+        },
+        ClipState::Stopped => ClipState::Stopped,
+      };
+    }
+  }
+
+  /// Stops the clip at the next quantized beat
+  pub fn stop(&mut self, clip_ix: usize, cur_beat: f64) {
+    let stop_beat = self.quantize(cur_beat);
+    self.states[clip_ix] = match self.states[clip_ix] {
+      ClipState::Stopped | ClipState::Queued { .. } => ClipState::Stopped,
+      ClipState::Playing { start_beat } => ClipState::Stopping {
+        start_beat,
+        stop_beat,
+      },
+      stopping @ ClipState::Stopping { .. } => stopping,
+    };
+  }
+
+  pub fn stop_all(&mut self, cur_beat: f64) {
+    for clip_ix in 0..self.clips.len() {
+      self.stop(clip_ix, cur_beat);
+    }
+  }
+
+  /// Stops all clips immediately without emitting releases for their held notes, such as when the
+  /// transport is stopped
+  pub fn reset(&mut self) {
+    self.states.fill(ClipState::Stopped);
+    self.rng = Pcg32::seed_from_u64(self.seed);
+  }
+
+  /// Returns the first beat at or after `beat` at which a clip that's playing or queued reaches its
+  /// follow action, along with the index of that clip
+  fn next_follow_action(&self, beat: f64) -> Option<(f64, usize)> {
+    let mut next: Option<(f64, usize)> = None;
+    for (clip_ix, (clip, state)) in self.clips.iter().zip(&self.states).enumerate() {
+      let start_beat = match *state {
+        ClipState::Queued { launch_beat } => launch_beat,
+        ClipState::Playing { start_beat } => start_beat,
+        ClipState::Stopped | ClipState::Stopping { .. } => continue,
+      };
+      if clip.follow_action == FollowAction::None
+        || clip.length_beats.is_nan()
+        || clip.length_beats <= 0.
+      {
+        continue;
+      }
+
+      let mut follow_beat =
+        start_beat + clip.length_beats * clip.follow_action_repeats.max(1) as f64;
+      // The follow action may have been set after the clip already played past it, in which case
+      // it's evaluated at the end of the current loop instead
+      if follow_beat < beat {
+        follow_beat =
+          start_beat + ((beat - start_beat) / clip.length_beats).ceil() * clip.length_beats;
+      }
+      if next
+        .map(|(next_beat, _)| follow_beat < next_beat)
+        .unwrap_or(true)
+      {
+        next = Some((follow_beat, clip_ix));
+      }
+    }
+    next
+  }
+
+  fn apply_follow_action(&mut self, clip_ix: usize, beat: f64) {
+    let target_ix = match self.clips[clip_ix].follow_action {
+      FollowAction::None => return,
+      FollowAction::Stop => {
+        if let ClipState::Playing { start_beat } = self.states[clip_ix] {
+          self.states[clip_ix] = ClipState::Stopping {
+            start_beat,
+            stop_beat: beat,
+          };
+        }
+        return;
+      },
+      FollowAction::PlayNext => (clip_ix + 1) % self.clips.len(),
+      FollowAction::PlayRandom if self.clips.len() > 1 => {
+        let ix = self.rng.gen_range(0, self.clips.len() - 1);
+        if ix >= clip_ix {
+          ix + 1
+        } else {
+          ix
+        }
+      },
+      FollowAction::PlayRandom => clip_ix,
+    };
+
+    if target_ix == clip_ix {
+      // Starts counting repeats again from here
+      self.states[clip_ix] = ClipState::Playing { start_beat: beat };
+    } else {
+      self.launch_at(target_ix, beat);
+    }
+  }
+
+  /// Returns the attacks and releases of all playing clips from `start_beat_inclusive` to
+  /// `end_beat_exclusive` in the order they occur, and then moves clips to the states they're in at
+  /// `end_beat_exclusive`.  Windows should be consecutive; each one should start where the last one
+  /// ended.
+  ///
+  /// Notes that extend past the end of their clip are released at the end of each loop, and all
+  /// notes that are held when a clip stops are released when it stops.
+  ///
+  /// Follow actions that are reached during the window are applied as they're reached, so the
+  /// events include any clips that they launch.
+  pub fn playback_events(
+    &mut self,
+    start_beat_inclusive: f64,
+    end_beat_exclusive: f64,
+  ) -> Vec<ClipEvent> {
+    let mut events = Vec::new();
+    let mut window_start = start_beat_inclusive;
+    loop {
+      let follow_action = self
+        .next_follow_action(window_start)
+        .filter(|&(beat, _)| beat < end_beat_exclusive);
+      let window_end = follow_action
+        .map(|(beat, _)| beat)
+        .unwrap_or(end_beat_exclusive);
+      self.collect_events(window_start, window_end, &mut events);
+      self.advance(window_end);
+
+      let Some((beat, clip_ix)) = follow_action else {
+        break;
+      };
+      self.apply_follow_action(clip_ix, beat);
+      window_start = beat;
+    }
+    // Releases go first so that a note that ends where another starts is re-triggered
+    events.sort_by(|a, b| {
+      a.beat
+        .total_cmp(&b.beat)
+        .then(a.is_attack.cmp(&b.is_attack))
+    });
+    events
+  }
+
+  fn collect_events(
+    &self,
+    start_beat_inclusive: f64,
+    end_beat_exclusive: f64,
+    events: &mut Vec<ClipEvent>,
+  ) {
+    for (clip_ix, (clip, state)) in self.clips.iter().zip(&self.states).enumerate() {
+      let Some((active_start, active_end)) = state.active_range() else {
+        continue;
+      };
+      if clip.length_beats.is_nan() || clip.length_beats <= 0. {
+        continue;
+      }
+
+      let pattern_events = clip
+        .notes
+        .playback_events(0., clip.length_beats, None, false, None);
+      // Notes on a line never overlap, so each release belongs to the last attack on its line
+      let mut attacks_by_line: Vec<Option<(f64, f32)>> = vec![None; clip.notes.lines.len()];
+      let mut notes = Vec::new();
+      for evt in pattern_events {
+        if evt.is_attack {
+          attacks_by_line[evt.line_ix] = Some((evt.beat, evt.velocity));
+        } else if let Some((attack_beat, velocity)) = attacks_by_line[evt.line_ix].take() {
+          notes.push((
+            evt.line_ix,
+            attack_beat,
+            evt.beat.min(clip.length_beats),
+            velocity,
+          ));
+        }
+      }
+
+      // A note released at the very end of a loop is released at the start of the next one, so
+      // the window can contain a release from the loop before the one it starts in
+      let first_loop_ix =
+        ((start_beat_inclusive - active_start) / clip.length_beats).floor() as i64 - 1;
+      let mut loop_ix = first_loop_ix.max(0);
+      loop {
+        let loop_start = active_start + loop_ix as f64 * clip.length_beats;
+        if loop_start >= end_beat_exclusive || loop_start >= active_end {
+          break;
+        }
+
+        for &(line_ix, attack_beat, release_beat, velocity) in &notes {
+          let attack_beat = loop_start + attack_beat;
+          if attack_beat >= active_end {
+            continue;
+          }
+          let release_beat = (loop_start + release_beat).min(active_end);
+
+          for (is_attack, beat) in [(true, attack_beat), (false, release_beat)] {
+            if beat >= start_beat_inclusive && beat < end_beat_exclusive {
+              events.push(ClipEvent {
+                clip_ix,
+                is_attack,
+                line_ix,
+                beat,
+                velocity,
+              });
+            }
+          }
+        }
+        loop_ix += 1;
+      }
+    }
+  }
+
+  fn advance(&mut self, beat: f64) {
+    for state in &mut self.states {
+      *state = match *state {
+        ClipState::Queued { launch_beat } if launch_beat < beat => ClipState::Playing {
+          start_beat: launch_beat,
+        },
+        // Stays stopping through its stop beat so that the releases at that beat are emitted
+        ClipState::Stopping { stop_beat, .. } if stop_beat < beat => ClipState::Stopped,
+        state => state,
+      };
+    }
+  }
+}
+
+#[cfg(test)]
+fn summarize_clip_events(events: &[ClipEvent]) -> Vec<(usize, bool, f64)> {
+  events
+    .iter()
+    .map(|evt| (evt.clip_ix, evt.is_attack, evt.beat))
+    .collect()
+}
+
+#[test]
+fn clips_launch_on_quantized_beats_and_loop() {
+  use crate::note_container::Note;
+
+  let mut launcher = ClipLauncher::new(4., 0);
+  let clip_ix = launcher.add_clip("a".into(), 2., 1);
+  launcher.clips[clip_ix].notes.lines[0].add_note(0.5, Note { id: 1, length: 1. });
+
+  launcher.launch(clip_ix, 1.5);
+  assert_eq!(launcher.states[clip_ix], ClipState::Queued {
+    launch_beat: 4.
+  });
+  assert!(launcher.playback_events(1.5, 4.).is_empty());
+
+  assert_eq!(
+    summarize_clip_events(&launcher.playback_events(4., 7.)),
+    vec![(0, true, 4.5), (0, false, 5.5), (0, true, 6.5),]
+  );
+  assert_eq!(launcher.states[clip_ix], ClipState::Playing {
+    start_beat: 4.
+  });
+
+  // The held note is released when the clip stops rather than at its end
+  launcher.quantization_beats = 1.;
+  launcher.stop(clip_ix, 6.75);
+  assert_eq!(
+    summarize_clip_events(&launcher.playback_events(7., 12.)),
+    vec![(0, false, 7.)]
+  );
+  assert_eq!(launcher.states[clip_ix], ClipState::Stopped);
+}
+
+#[test]
+fn launching_a_clip_stops_the_playing_one() {
+  use crate::note_container::Note;
+
+  let mut launcher = ClipLauncher::new(1., 0);
+  let first = launcher.add_clip("first".into(), 4., 1);
+  let second = launcher.add_clip("second".into(), 4., 1);
+  launcher.clips[first].notes.lines[0].add_note(0., Note { id: 1, length: 4. });
+  launcher.clips[second].notes.lines[0].add_note(0., Note { id: 2, length: 1. });
+
+  launcher.launch(first, 0.);
+  assert_eq!(
+    summarize_clip_events(&launcher.playback_events(0., 1.)),
+    vec![(0, true, 0.)]
+  );
+
+  launcher.launch(second, 1.25);
+  assert_eq!(
+    summarize_clip_events(&launcher.playback_events(1., 3.)),
+    vec![(0, false, 2.), (1, true, 2.)]
+  );
+  assert_eq!(launcher.states[first], ClipState::Stopped);
+  assert_eq!(launcher.states[second], ClipState::Playing {
+    start_beat: 2.
+  });
+}
+
+#[test]
+fn follow_actions_chain_clips() {
+  use crate::note_container::Note;
+
+  let mut launcher = ClipLauncher::new(1., 0);
+  let first = launcher.add_clip("first".into(), 1., 1);
+  let second = launcher.add_clip("second".into(), 2., 1);
+  launcher.clips[first].notes.lines[0].add_note(0., Note { id: 1, length: 1. });
+  launcher.clips[second].notes.lines[0].add_note(0., Note { id: 2, length: 0.5 });
+  launcher.clips[first].follow_action = FollowAction::PlayNext;
+  launcher.clips[first].follow_action_repeats = 2;
+  launcher.clips[second].follow_action = FollowAction::Stop;
+
+  launcher.launch(first, 0.);
+  assert_eq!(
+    summarize_clip_events(&launcher.playback_events(0., 8.)),
+    vec![
+      (0, true, 0.),
+      (0, false, 1.),
+      (0, true, 1.),
+      (0, false, 2.),
+      (1, true, 2.),
+      (1, false, 2.5)
+    ]
+  );
+  assert_eq!(launcher.states[first], ClipState::Stopped);
+  assert_eq!(launcher.states[second], ClipState::Stopped);
+}
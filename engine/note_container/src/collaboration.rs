@@ -0,0 +1,584 @@
+//! Conflict-free merging of note edits made concurrently by multiple users editing the same MIDI
+//! editor.  Every note is a last-writer-wins register keyed by a globally unique ID.  Operations
+//! are ordered by their Lamport clock with ties broken by client ID, so all replicas converge to
+//! the same state no matter what order they receive operations in.  Deleted notes are kept around
+//! as tombstones so that delayed operations can't bring them back.
+
+use std::collections::{HashMap, HashSet};
+
+#[cfg(test)]
+use crate::note_container::NoteEntry;
+use crate::{exports::get_new_note_id, note_container::Note, note_lines::NoteLines};
+
+/// Globally unique ID of a note: the client that created it and its clock when it did
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct CollabNoteId {
+  pub client_id: String,
+  pub clock: u32,
+}
+
+/// Orders operations consistently across all clients
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct OpStamp {
+  pub clock: u32,
+  pub client_id: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NotePlacement {
+  pub line_ix: usize,
+  pub start_beat: f64,
+  pub length: f64,
+}
+
+/// Inserts and moves both carry the full placement of the note, so the latest one always fully
+/// determines where the note is no matter which other operations were missed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NoteOp {
+  Insert(NotePlacement),
+  Move(NotePlacement),
+  Delete,
+}
+
+impl NoteOp {
+  pub fn placement(&self) -> Option<NotePlacement> {
+    match self {
+      NoteOp::Insert(placement) | NoteOp::Move(placement) => Some(*placement),
+      NoteOp::Delete => None,
+    }
+  }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct NoteOperation {
+  pub note_id: CollabNoteId,
+  pub stamp: OpStamp,
+  pub op: NoteOp,
+}
+
+struct NoteRegister {
+  stamp: OpStamp,
+  /// `None` if the note has been deleted
+  placement: Option<NotePlacement>,
+}
+
+/// The merged state of all notes that any client has created
+#[derive(Default)]
+pub struct CollabState {
+  clock: u32,
+  notes: HashMap<CollabNoteId, NoteRegister>,
+}
+
+impl CollabState {
+  /// Advances the local clock for a new local operation
+  pub fn tick(&mut self) -> u32 {
+    self.clock += 1;
+    self.clock
+  }
+
+  /// Applies the operation if it's newer than the latest one applied to its note.  Returns the
+  /// note's previous and new placements if it was applied.
+  pub fn apply(
+    &mut self,
+    operation: &NoteOperation,
+  ) -> Option<(Option<NotePlacement>, Option<NotePlacement>)> {
+    self.clock = self.clock.max(operation.stamp.clock);
+
+    let new_placement = operation.op.placement();
+    match self.notes.get_mut(&operation.note_id) {
+      Some(register) if register.stamp >= operation.stamp => None,
+      Some(register) => {
+        let old_placement = register.placement;
+        register.stamp = operation.stamp.clone();
+        register.placement = new_placement;
+        Some((old_placement, new_placement))
+      },
+      None => {
+        self.notes.insert(operation.note_id.clone(), NoteRegister {
+          stamp: operation.stamp.clone(),
+          placement: new_placement,
+        });
+        Some((None, new_placement))
+      },
+    }
+  }
+
+  /// Returns one operation per note which can be applied to an empty state to reproduce this one
+  pub fn snapshot(&self) -> Vec<NoteOperation> {
+    self
+      .notes
+      .iter()
+      .map(|(note_id, register)| NoteOperation {
+        note_id: note_id.clone(),
+        stamp: register.stamp.clone(),
+        op: match register.placement {
+          Some(placement) => NoteOp::Move(placement),
+          None => NoteOp::Delete,
+        },
+      })
+      .collect()
+  }
+
+  /// Returns the placements of all notes that haven't been deleted
+  pub fn live_notes(&self) -> impl Iterator<Item = (&CollabNoteId, &NotePlacement)> + '_ {
+    self
+      .notes
+      .iter()
+      .filter_map(|(note_id, register)| register.placement.as_ref().map(|p| (note_id, p)))
+  }
+}
+
+/// A note as it's actually rendered into `NoteLines`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RenderedNote {
+  pub line_ix: usize,
+  pub start_beat: f64,
+  pub length: f64,
+}
+
+/// Notes shorter than this after being trimmed are hidden entirely
+const MIN_RENDERED_NOTE_LENGTH: f64 = 0.0001;
+
+/// Deterministically resolves overlaps between the notes on a single line.  Notes are placed in
+/// priority order, most recently edited first.  Lower priority notes are trimmed to end where a
+/// higher priority note starts, and are hidden entirely if they start inside of one.  Hidden and
+/// trimmed notes keep their full placement in the merged state, so they're restored as soon as the
+/// notes blocking them are moved or deleted.
+pub fn resolve_line<'a>(
+  mut notes: Vec<(&'a OpStamp, &'a CollabNoteId, NotePlacement)>,
+) -> Vec<(&'a CollabNoteId, RenderedNote)> {
+  notes.sort_unstable_by(|(a, ..), (b, ..)| b.cmp(a));
+
+  let mut rendered: Vec<(&CollabNoteId, RenderedNote)> = Vec::with_capacity(notes.len());
+  for (_, note_id, placement) in notes {
+    let start = placement.start_beat;
+    let mut end = start + placement.length;
+    let mut is_hidden = false;
+    for (_, other) in &rendered {
+      let other_end = other.start_beat + other.length;
+      if start >= other.start_beat && start < other_end {
+        is_hidden = true;
+        break;
+      }
+      if other.start_beat > start {
+        end = end.min(other.start_beat);
+      }
+    }
+
+    if is_hidden || end - start < MIN_RENDERED_NOTE_LENGTH {
+      continue;
+    }
+    rendered.push((note_id, RenderedNote {
+      line_ix: placement.line_ix,
+      start_beat: start,
+      length: end - start,
+    }));
+  }
+  rendered
+}
+
+/// Keeps a set of `NoteLines` in sync with the merged state of a collaborative editing session.
+/// The rendered notes are always fully determined by the merged state, so all clients that have
+/// seen the same operations display the same notes.
+///
+/// Local edits are queued until they're taken with `take_pending_ops`, so edits made while
+/// disconnected are sent and merged once the connection is re-established.
+pub struct CollabSession {
+  pub client_id: String,
+  pub state: CollabState,
+  local_ids: HashMap<CollabNoteId, u32>,
+  collab_ids: HashMap<u32, CollabNoteId>,
+  /// Where each visible note is currently rendered in the note lines
+  rendered: HashMap<CollabNoteId, RenderedNote>,
+  /// Local operations that haven't been sent to other clients yet
+  pending: Vec<NoteOperation>,
+  /// Where each note whose rendering changed since the last call to `take_render_changes` is now
+  /// rendered, or `None` if it was removed or hidden, keyed by local note ID
+  render_changes: HashMap<u32, Option<RenderedNote>>,
+}
+
+impl CollabSession {
+  pub fn new(client_id: String) -> Self {
+    CollabSession {
+      client_id,
+      state: CollabState::default(),
+      local_ids: HashMap::new(),
+      collab_ids: HashMap::new(),
+      rendered: HashMap::new(),
+      pending: Vec::new(),
+      render_changes: HashMap::new(),
+    }
+  }
+
+  /// Re-renders all notes on the line from the merged state
+  fn render_line(&mut self, lines: &mut NoteLines, line_ix: usize) {
+    if line_ix >= lines.lines.len() {
+      return;
+    }
+
+    let notes = self
+      .state
+      .notes
+      .iter()
+      .filter_map(|(note_id, register)| match register.placement {
+        Some(placement) if placement.line_ix == line_ix =>
+          Some((&register.stamp, note_id, placement)),
+        _ => None,
+      })
+      .collect();
+    let resolved = resolve_line(notes);
+
+    let rendered = &mut self.rendered;
+    let local_ids = &mut self.local_ids;
+    let collab_ids = &mut self.collab_ids;
+    let render_changes = &mut self.render_changes;
+    rendered.retain(|note_id, note| {
+      if note.line_ix != line_ix {
+        return true;
+      }
+      lines.lines[line_ix].remove_note(note.start_beat, local_ids[note_id]);
+      render_changes.insert(local_ids[note_id], None);
+      false
+    });
+    for (note_id, note) in resolved {
+      let local_id = *local_ids.entry(note_id.clone()).or_insert_with(|| {
+        let local_id = get_new_note_id();
+        collab_ids.insert(local_id, note_id.clone());
+        local_id
+      });
+      // The note was moved here from another line that hasn't been re-rendered yet
+      if let Some(prev) = rendered.insert(note_id.clone(), note) {
+        lines.lines[prev.line_ix].remove_note(prev.start_beat, local_id);
+      }
+      lines.lines[line_ix].add_note(note.start_beat, Note {
+        id: local_id,
+        length: note.length,
+      });
+      render_changes.insert(local_id, Some(note));
+    }
+  }
+
+  /// Records an edit that has already been made to the local note lines and queues it to be sent
+  /// to other clients.  Edits to notes that haven't been seen before are recorded as inserts.
+  pub fn record_local_op(&mut self, lines: &mut NoteLines, local_note_id: u32, op: NoteOp) {
+    let clock = self.state.tick();
+    let (note_id, op) = match self.collab_ids.get(&local_note_id) {
+      Some(note_id) => (note_id.clone(), op),
+      None => {
+        let note_id = CollabNoteId {
+          client_id: self.client_id.clone(),
+          clock,
+        };
+        self.local_ids.insert(note_id.clone(), local_note_id);
+        self.collab_ids.insert(local_note_id, note_id.clone());
+        let op = match op {
+          NoteOp::Move(placement) => NoteOp::Insert(placement),
+          op => op,
+        };
+        (note_id, op)
+      },
+    };
+
+    let operation = NoteOperation {
+      note_id,
+      stamp: OpStamp {
+        clock,
+        client_id: self.client_id.clone(),
+      },
+      op,
+    };
+    self.state.apply(&operation);
+
+    // The edit has already been made to the note lines, so they just need to be updated to match
+    let old_line_ix = self
+      .rendered
+      .remove(&operation.note_id)
+      .map(|note| note.line_ix);
+    let new_line_ix = operation.op.placement().map(|placement| {
+      self
+        .rendered
+        .insert(operation.note_id.clone(), RenderedNote {
+          line_ix: placement.line_ix,
+          start_beat: placement.start_beat,
+          length: placement.length,
+        });
+      placement.line_ix
+    });
+    // Moving or deleting the note may have made room for notes that were trimmed or hidden
+    if let Some(old_line_ix) = old_line_ix.filter(|&line_ix| Some(line_ix) != new_line_ix) {
+      self.render_line(lines, old_line_ix);
+    }
+    if let Some(new_line_ix) = new_line_ix {
+      self.render_line(lines, new_line_ix);
+    }
+
+    self.pending.push(operation);
+  }
+
+  /// Returns all local operations that haven't been sent yet.  Only the latest operation for each
+  /// note is kept since it supersedes all previous ones.
+  pub fn take_pending_ops(&mut self) -> Vec<NoteOperation> {
+    let mut latest: HashMap<CollabNoteId, NoteOperation> = HashMap::new();
+    for operation in self.pending.drain(..) {
+      latest.insert(operation.note_id.clone(), operation);
+    }
+    let mut ops: Vec<NoteOperation> = latest.into_values().collect();
+    ops.sort_unstable_by(|a, b| a.stamp.cmp(&b.stamp));
+    ops
+  }
+
+  /// Merges operations received from other clients into the local note lines, such as the full
+  /// snapshot sent when (re)joining a room.  Returns `true` if anything changed.
+  pub fn apply_remote_ops(&mut self, lines: &mut NoteLines, operations: &[NoteOperation]) -> bool {
+    let mut dirty_lines = HashSet::new();
+    for operation in operations {
+      let (old_placement, new_placement) = match self.state.apply(operation) {
+        Some(change) => change,
+        None => continue,
+      };
+      dirty_lines.extend(old_placement.map(|placement| placement.line_ix));
+      dirty_lines.extend(new_placement.map(|placement| placement.line_ix));
+    }
+
+    for &line_ix in &dirty_lines {
+      self.render_line(lines, line_ix);
+    }
+    !dirty_lines.is_empty()
+  }
+
+  pub fn apply_remote_op(&mut self, lines: &mut NoteLines, operation: &NoteOperation) -> bool {
+    self.apply_remote_ops(lines, std::slice::from_ref(operation))
+  }
+
+  /// Returns where every note that was added, moved, trimmed, or removed in the note lines since
+  /// the last call is now rendered, so that the MIDI editor can update the notes it displays.
+  /// Notes that were removed or hidden are returned with `None`.
+  pub fn take_render_changes(&mut self) -> Vec<(u32, Option<RenderedNote>)> {
+    self.render_changes.drain().collect()
+  }
+}
+
+#[cfg(test)]
+fn build_op(client_id: &str, note_clock: u32, clock: u32, op: NoteOp) -> NoteOperation {
+  NoteOperation {
+    note_id: CollabNoteId {
+      client_id: client_id.to_owned(),
+      clock: note_clock,
+    },
+    stamp: OpStamp {
+      clock,
+      client_id: client_id.to_owned(),
+    },
+    op,
+  }
+}
+
+#[test]
+fn concurrent_ops_converge() {
+  let placement = |line_ix, start_beat| NotePlacement {
+    line_ix,
+    start_beat,
+    length: 1.,
+  };
+  let ops = vec![
+    build_op("a", 1, 1, NoteOp::Insert(placement(0, 0.))),
+    build_op("a", 1, 2, NoteOp::Move(placement(0, 2.))),
+    // Concurrent move of the same note by another client with the same clock
+    NoteOperation {
+      stamp: OpStamp {
+        clock: 2,
+        client_id: String::from("b"),
+      },
+      ..build_op("a", 1, 2, NoteOp::Move(placement(1, 4.)))
+    },
+    build_op("b", 3, 3, NoteOp::Insert(placement(2, 0.))),
+    build_op("b", 3, 4, NoteOp::Delete),
+  ];
+
+  let mut in_order = CollabState::default();
+  for op in &ops {
+    in_order.apply(op);
+  }
+  let mut reversed = CollabState::default();
+  for op in ops.iter().rev() {
+    reversed.apply(op);
+  }
+
+  let live = |state: &CollabState| {
+    let mut notes: Vec<_> = state
+      .live_notes()
+      .map(|(id, placement)| (id.clone(), *placement))
+      .collect();
+    notes.sort_by_key(|(id, _)| id.clock);
+    notes
+  };
+  assert_eq!(live(&in_order), live(&reversed));
+  // Client "b" wins the tie, and the deleted note stays deleted
+  assert_eq!(live(&in_order).len(), 1);
+  assert_eq!(live(&in_order)[0].1, placement(1, 4.));
+
+  // Replaying a snapshot reproduces the same state
+  let mut replayed = CollabState::default();
+  for op in in_order.snapshot() {
+    replayed.apply(&op);
+  }
+  assert_eq!(live(&replayed), live(&in_order));
+}
+
+#[test]
+fn overlapping_notes_are_trimmed_by_priority() {
+  let mut lines = NoteLines {
+    lines: vec![Default::default(), Default::default()],
+    velocities: HashMap::new(),
+    probabilities: HashMap::new(),
+    round_robin_groups: HashMap::new(),
+    articulations: HashMap::new(),
+    input_recording: None,
+  };
+  let mut session = CollabSession::new(String::from("local"));
+  let placement = |line_ix, start_beat, length| NotePlacement {
+    line_ix,
+    start_beat,
+    length,
+  };
+  let rendered_lengths = |lines: &NoteLines, session: &CollabSession| {
+    let mut notes: Vec<(f64, f64)> = lines.lines[0]
+      .inner
+      .iter()
+      .filter_map(|(start, entry)| match entry {
+        NoteEntry::NoteStart { note }
+        | NoteEntry::StartAndEnd {
+          start_note: note, ..
+        } => Some((start.0, note.length)),
+        NoteEntry::NoteEnd { .. } => None,
+      })
+      .collect();
+    notes.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    assert_eq!(
+      notes.len(),
+      session.rendered.values().filter(|n| n.line_ix == 0).count()
+    );
+    notes
+  };
+
+  let a_insert = build_op("a", 1, 1, NoteOp::Insert(placement(0, 0., 4.)));
+  let b_insert = build_op("b", 1, 2, NoteOp::Insert(placement(0, 2., 4.)));
+  // Starts inside of `b`'s note, which has a higher priority
+  let c_insert = build_op("c", 1, 1, NoteOp::Insert(placement(0, 3., 1.)));
+
+  // Every delivery order renders the same notes
+  let mut reversed = CollabSession::new(String::from("other"));
+  let mut reversed_lines = NoteLines {
+    lines: vec![Default::default(), Default::default()],
+    velocities: HashMap::new(),
+    probabilities: HashMap::new(),
+    round_robin_groups: HashMap::new(),
+    articulations: HashMap::new(),
+    input_recording: None,
+  };
+  assert!(session.apply_remote_ops(&mut lines, &[
+    a_insert.clone(),
+    b_insert.clone(),
+    c_insert.clone()
+  ]));
+  for op in [&c_insert, &b_insert, &a_insert] {
+    reversed.apply_remote_op(&mut reversed_lines, op);
+  }
+  assert_eq!(rendered_lengths(&lines, &session), vec![(0., 2.), (2., 4.)]);
+  assert_eq!(
+    rendered_lengths(&reversed_lines, &reversed),
+    rendered_lengths(&lines, &session)
+  );
+
+  // Moving `b`'s note away un-hides `c`'s, which now trims `a`'s instead
+  let b_move = build_op("b", 1, 3, NoteOp::Move(placement(1, 2., 4.)));
+  session.take_render_changes();
+  assert!(session.apply_remote_op(&mut lines, &b_move));
+  assert_eq!(rendered_lengths(&lines, &session), vec![(0., 3.), (3., 1.)]);
+  let mut changes = session.take_render_changes();
+  changes.sort_by_key(|(local_id, _)| *local_id);
+  let mut expected = vec![
+    (
+      session.local_ids[&a_insert.note_id],
+      Some(RenderedNote {
+        line_ix: 0,
+        start_beat: 0.,
+        length: 3.,
+      }),
+    ),
+    (
+      session.local_ids[&b_insert.note_id],
+      Some(RenderedNote {
+        line_ix: 1,
+        start_beat: 2.,
+        length: 4.,
+      }),
+    ),
+    (
+      session.local_ids[&c_insert.note_id],
+      Some(RenderedNote {
+        line_ix: 0,
+        start_beat: 3.,
+        length: 1.,
+      }),
+    ),
+  ];
+  expected.sort_by_key(|(local_id, _)| *local_id);
+  assert_eq!(changes, expected);
+  let a_local_id = session.local_ids[&a_insert.note_id];
+  lines.lines[0].remove_note(0., a_local_id);
+  session.record_local_op(&mut lines, a_local_id, NoteOp::Delete);
+  assert_eq!(rendered_lengths(&lines, &session), vec![(3., 1.)]);
+
+  // Stale operations are ignored
+  assert!(!session.apply_remote_op(&mut lines, &b_insert));
+}
+
+#[test]
+fn local_ops_are_queued_until_taken() {
+  let mut lines = NoteLines {
+    lines: vec![Default::default()],
+    velocities: HashMap::new(),
+    probabilities: HashMap::new(),
+    round_robin_groups: HashMap::new(),
+    articulations: HashMap::new(),
+    input_recording: None,
+  };
+  let mut session = CollabSession::new(String::from("local"));
+  let placement = |start_beat| NotePlacement {
+    line_ix: 0,
+    start_beat,
+    length: 1.,
+  };
+
+  // Simulates notes created and moved in the MIDI editor while disconnected
+  let (first_id, second_id) = (get_new_note_id(), get_new_note_id());
+  lines.lines[0].add_note(0., Note {
+    id: first_id,
+    length: 1.,
+  });
+  session.record_local_op(&mut lines, first_id, NoteOp::Move(placement(0.)));
+  lines.lines[0].add_note(4., Note {
+    id: second_id,
+    length: 1.,
+  });
+  session.record_local_op(&mut lines, second_id, NoteOp::Move(placement(4.)));
+  lines.lines[0].remove_note(0., first_id);
+  lines.lines[0].add_note(2., Note {
+    id: first_id,
+    length: 1.,
+  });
+  session.record_local_op(&mut lines, first_id, NoteOp::Move(placement(2.)));
+
+  let pending = session.take_pending_ops();
+  assert_eq!(pending.len(), 2);
+  // New notes are sent as inserts, and only the latest op for each note is kept
+  assert_eq!(pending[0].op, NoteOp::Insert(placement(4.)));
+  assert_eq!(pending[1].op, NoteOp::Move(placement(2.)));
+  assert!(session.take_pending_ops().is_empty());
+
+  // Ops from other clients made while disconnected are merged once reconnected
+  let remote = build_op("remote", 1, 1, NoteOp::Insert(placement(6.)));
+  assert!(session.apply_remote_ops(&mut lines, &[remote]));
+  assert_eq!(lines.iter_notes(0, 0, 0., 10.).len(), 3);
+}
@@ -1,36 +1,37 @@
-use std::{collections::HashMap, ops::Bound};
+use std::{
+  collections::HashSet,
+  sync::atomic::{AtomicU32, Ordering},
+};
 
-use float_ord::FloatOrd;
-use js_sys::Function;
+use common::humanize::Humanizer;
+use js_sys::{Array, Function};
 use wasm_bindgen::prelude::*;
 
 use crate::{
-  note_container::{Note, NoteContainer, NoteEntry},
+  clips::{ClipLauncher, FollowAction},
+  collaboration::{CollabNoteId, CollabSession, NoteOp, NoteOperation, NotePlacement, OpStamp},
+  input_recording::{InputRecording, RecordedInput},
+  note_container::{Note, NoteContainer},
   note_lines::NoteLines,
+  playback_variation::PlaybackVariation,
+  snapshot::{decode_snapshot, encode_snapshot},
 };
 
 #[wasm_bindgen]
 pub fn create_note_lines(line_count: usize) -> *mut NoteLines {
   common::maybe_init(None);
   wbg_logging::maybe_init();
-  let mut lines = Vec::with_capacity(line_count);
-  for _ in 0..line_count {
-    lines.push(NoteContainer::default())
-  }
-  Box::into_raw(Box::new(NoteLines { lines }))
+  Box::into_raw(Box::new(NoteLines::new(line_count)))
 }
 
 #[wasm_bindgen]
 pub fn free_note_lines(lines: *mut NoteLines) { unsafe { drop(Box::from_raw(lines)) } }
 
-static mut NOTE_ID_COUNT: u32 = 0;
+/// Note IDs are unique across all `NoteLines` instances rather than per-instance so that IDs
+/// handed out to different editors and collaboration sessions never collide
+static NOTE_ID_COUNT: AtomicU32 = AtomicU32::new(0);
 
-pub fn get_new_note_id() -> u32 {
-  unsafe {
-    NOTE_ID_COUNT += 1;
-    NOTE_ID_COUNT
-  }
-}
+pub fn get_new_note_id() -> u32 { NOTE_ID_COUNT.fetch_add(1, Ordering::Relaxed) + 1 }
 
 #[wasm_bindgen]
 pub fn create_note(
@@ -41,12 +42,18 @@ pub fn create_note(
   note_id: u32,
 ) -> u32 {
   let notes = unsafe { &mut *lines };
-  let container = &mut notes.lines[line_ix];
   let note_id = if note_id == 0 {
     get_new_note_id()
   } else {
     note_id
   };
+  notes.record_input(RecordedInput::CreateNote {
+    line_ix: line_ix as u32,
+    start_point,
+    length,
+    note_id,
+  });
+  let container = &mut notes.lines[line_ix];
   container.add_note(start_point, Note {
     id: note_id,
     length,
@@ -57,8 +64,44 @@ pub fn create_note(
 #[wasm_bindgen]
 pub fn delete_note(lines: *mut NoteLines, line_ix: usize, start_point: f64, note_id: u32) {
   let notes = unsafe { &mut *lines };
-  let container = &mut notes.lines[line_ix];
-  container.remove_note(start_point, note_id);
+  notes.record_input(RecordedInput::DeleteNote {
+    line_ix: line_ix as u32,
+    start_point,
+    note_id,
+  });
+  notes.delete_note(line_ix, start_point, note_id);
+}
+
+/// Moves a note to `new_start_point` on line `dst_line_ix` with length `new_length`, keeping its ID
+/// and per-note data such as velocity.  Returns `true` if the note was moved, or `false` if it was
+/// left where it was because it's blocked by another note in the destination.
+#[wasm_bindgen]
+pub fn move_note(
+  lines: *mut NoteLines,
+  src_line_ix: usize,
+  start_point: f64,
+  note_id: u32,
+  dst_line_ix: usize,
+  new_start_point: f64,
+  new_length: f64,
+) -> bool {
+  let notes = unsafe { &mut *lines };
+  notes.record_input(RecordedInput::MoveNote {
+    src_line_ix: src_line_ix as u32,
+    start_point,
+    note_id,
+    dst_line_ix: dst_line_ix as u32,
+    new_start_point,
+    new_length,
+  });
+  notes.move_note(
+    src_line_ix,
+    start_point,
+    note_id,
+    dst_line_ix,
+    new_start_point,
+    new_length,
+  )
 }
 
 #[wasm_bindgen]
@@ -70,6 +113,12 @@ pub fn move_note_horizontal(
   desired_new_start_point: f64,
 ) -> f64 {
   let notes = unsafe { &mut *lines };
+  notes.record_input(RecordedInput::MoveNoteHorizontal {
+    line_ix: line_ix as u32,
+    start_point,
+    note_id,
+    desired_new_start_point,
+  });
   let container = &mut notes.lines[line_ix];
   container.move_note_horizontal(start_point, note_id, desired_new_start_point)
 }
@@ -83,6 +132,12 @@ pub fn resize_note_horizontal_start(
   new_start_point: f64,
 ) -> f64 {
   let notes = unsafe { &mut *lines };
+  notes.record_input(RecordedInput::ResizeNoteStart {
+    line_ix: line_ix as u32,
+    start_point,
+    note_id,
+    new_start_point,
+  });
   let container = &mut notes.lines[line_ix];
   container.resize_note_start(start_point, note_id, new_start_point)
 }
@@ -96,10 +151,201 @@ pub fn resize_note_horizontal_end(
   new_end_point: f64,
 ) -> f64 {
   let notes = unsafe { &mut *lines };
+  notes.record_input(RecordedInput::ResizeNoteEnd {
+    line_ix: line_ix as u32,
+    start_point,
+    note_id,
+    new_end_point,
+  });
   let container = &mut notes.lines[line_ix];
   container.resize_note_end(start_point, note_id, new_end_point)
 }
 
+/// Sets the velocity of a note in [0, 1]
+#[wasm_bindgen]
+pub fn set_note_velocity(lines: *mut NoteLines, note_id: u32, velocity: f32) {
+  let notes = unsafe { &mut *lines };
+  notes.record_input(RecordedInput::SetVelocity { note_id, velocity });
+  notes.set_velocity(note_id, velocity);
+}
+
+#[wasm_bindgen]
+pub fn get_note_velocity(lines: *const NoteLines, note_id: u32) -> f32 {
+  let notes = unsafe { &*lines };
+  notes.get_velocity(note_id)
+}
+
+/// Sets the chance of a note being played in [0, 1] when playback variation is enabled
+#[wasm_bindgen]
+pub fn set_note_probability(lines: *mut NoteLines, note_id: u32, probability: f32) {
+  let notes = unsafe { &mut *lines };
+  notes.record_input(RecordedInput::SetProbability {
+    note_id,
+    probability,
+  });
+  notes.set_probability(note_id, probability);
+}
+
+#[wasm_bindgen]
+pub fn get_note_probability(lines: *const NoteLines, note_id: u32) -> f32 {
+  let notes = unsafe { &*lines };
+  notes.get_probability(note_id)
+}
+
+/// Sets the round-robin group of a note.  Negative values remove the note from its group.
+#[wasm_bindgen]
+pub fn set_note_round_robin_group(lines: *mut NoteLines, note_id: u32, group: i32) {
+  let notes = unsafe { &mut *lines };
+  notes.record_input(RecordedInput::SetRoundRobinGroup { note_id, group });
+  notes.set_round_robin_group(note_id, u32::try_from(group).ok());
+}
+
+/// Returns -1 if the note isn't in a round-robin group
+#[wasm_bindgen]
+pub fn get_note_round_robin_group(lines: *const NoteLines, note_id: u32) -> i32 {
+  let notes = unsafe { &*lines };
+  notes
+    .get_round_robin_group(note_id)
+    .map(|group| group as i32)
+    .unwrap_or(-1)
+}
+
+/// Sets the articulation of a note as a bitmask of the `ARTICULATION_*` flags in
+/// `crate::articulation`.  Unknown flags are ignored.
+#[wasm_bindgen]
+pub fn set_note_articulation(lines: *mut NoteLines, note_id: u32, articulation: u8) {
+  let notes = unsafe { &mut *lines };
+  notes.record_input(RecordedInput::SetArticulation {
+    note_id,
+    articulation,
+  });
+  notes.set_articulation(note_id, articulation);
+}
+
+#[wasm_bindgen]
+pub fn get_note_articulation(lines: *const NoteLines, note_id: u32) -> u8 {
+  let notes = unsafe { &*lines };
+  notes.get_articulation(note_id)
+}
+
+/// Returns a compact binary snapshot of all notes in `lines`.  See `crate::snapshot` for the
+/// format.
+#[wasm_bindgen]
+pub fn snapshot_note_lines(lines: *const NoteLines) -> Vec<u8> {
+  let notes = unsafe { &*lines };
+  encode_snapshot(notes)
+}
+
+/// Decodes a snapshot created with `snapshot_note_lines` so that its notes can be restored.  The
+/// first element of the returned array is the line count, followed by seven elements for each note:
+///
+/// [line index, start beat, length, velocity, probability, round-robin group, articulation]
+///
+/// The round-robin group is -1 if the note isn't in a group.  Returns an empty array if the
+/// snapshot is invalid.
+#[wasm_bindgen]
+pub fn decode_note_lines_snapshot(snapshot: &[u8]) -> Vec<f64> {
+  let (line_count, notes) = match decode_snapshot(snapshot) {
+    Ok(decoded) => decoded,
+    Err(err) => {
+      log::error!("Failed to decode note lines snapshot: {err}");
+      return Vec::new();
+    },
+  };
+
+  let mut out = Vec::with_capacity(1 + notes.len() * 7);
+  out.push(line_count as f64);
+  for note in notes {
+    out.extend_from_slice(&[
+      note.line_ix as f64,
+      note.start_point,
+      note.length,
+      note.velocity as f64,
+      note.probability as f64,
+      note
+        .round_robin_group
+        .map(|group| group as f64)
+        .unwrap_or(-1.),
+      note.articulation as f64,
+    ]);
+  }
+  out
+}
+
+/// Creates the state used to apply note probabilities and round-robin groups during playback.
+/// Using the same `seed` produces the same notes for each playthrough.
+#[wasm_bindgen]
+pub fn create_playback_variation(seed: u32) -> *mut PlaybackVariation {
+  Box::into_raw(Box::new(PlaybackVariation::new(seed as u64)))
+}
+
+#[wasm_bindgen]
+pub fn free_playback_variation(variation: *mut PlaybackVariation) {
+  unsafe { drop(Box::from_raw(variation)) }
+}
+
+/// Creates a humanizer which applies random offsets of up to `timing_amount` beats and
+/// `velocity_amount` (as a fraction of full velocity) to notes.  Using the same `seed` produces the
+/// same offsets.
+#[wasm_bindgen]
+pub fn create_humanizer(seed: u32, timing_amount: f64, velocity_amount: f32) -> *mut Humanizer {
+  Box::into_raw(Box::new(Humanizer::new(
+    seed as u64,
+    timing_amount,
+    velocity_amount,
+  )))
+}
+
+#[wasm_bindgen]
+pub fn free_humanizer(humanizer: *mut Humanizer) { unsafe { drop(Box::from_raw(humanizer)) } }
+
+/// Moves the note by a random amount and randomizes its velocity.  The note stops early if it runs
+/// into another note.  Returns the new start point of the note.
+#[wasm_bindgen]
+pub fn humanize_note(
+  lines: *mut NoteLines,
+  humanizer: *mut Humanizer,
+  line_ix: usize,
+  start_point: f64,
+  note_id: u32,
+) -> f64 {
+  let notes = unsafe { &mut *lines };
+  let humanizer = unsafe { &mut *humanizer };
+
+  let velocity = humanizer.humanize_velocity(notes.get_velocity(note_id), 1.);
+  notes.record_input(RecordedInput::SetVelocity { note_id, velocity });
+  notes.set_velocity(note_id, velocity);
+
+  let desired_start_point = (start_point + humanizer.timing_offset()).max(0.);
+  notes.record_input(RecordedInput::MoveNoteHorizontal {
+    line_ix: line_ix as u32,
+    start_point,
+    note_id,
+    desired_new_start_point: desired_start_point,
+  });
+  notes.lines[line_ix].move_note_horizontal(start_point, note_id, desired_start_point)
+}
+
+/// Moves a note to another line, keeping its start point and ID.  Returns `false` and leaves the
+/// note where it is if it's blocked by another note in the destination line.
+#[wasm_bindgen]
+pub fn move_note_vertically(
+  lines: *mut NoteLines,
+  src_line_ix: usize,
+  dst_line_ix: usize,
+  start_point: f64,
+  note_id: u32,
+) -> bool {
+  let notes = unsafe { &mut *lines };
+  notes.record_input(RecordedInput::MoveNoteVertically {
+    src_line_ix: src_line_ix as u32,
+    dst_line_ix: dst_line_ix as u32,
+    start_point,
+    note_id,
+  });
+  notes.move_note_vertically(src_line_ix, dst_line_ix, start_point, note_id)
+}
+
 #[wasm_bindgen]
 pub fn check_can_add_note(
   lines: *const NoteLines,
@@ -124,115 +370,380 @@ pub fn iter_notes(
   notes.iter_notes(start_line_ix, end_line_ix, start_point, end_point)
 }
 
+/// Returns the IDs of all notes longer than `min_length` and shorter than `max_length` beats
+#[wasm_bindgen]
+pub fn iter_notes_by_length(lines: *const NoteLines, min_length: f64, max_length: f64) -> Vec<u32> {
+  let notes = unsafe { &*lines };
+  notes.iter_notes_by_length(min_length, max_length)
+}
+
 /// Calls `cb` for each note in all lines. If `end_beat_exclusive` is negative, it will be treated
 /// as unbounded.
 ///
-/// `cb` is called with three arguments:
+/// `cb` is called with four arguments:
 ///
 /// 1. an `is_attack` flag which is true if the note is starting and false if the note is ending
 /// 2. line index
 /// 3. beat
+/// 4. velocity of the note in [0, 1]
+///
+/// If `humanizer` is not null, random timing and velocity offsets are applied to each note as it's
+/// emitted.  Notes are moved as a whole so that their lengths are preserved.
+///
+/// If `legato` is set, notes that start exactly where the previous note on the same line ends are
+/// tied to it rather than being re-triggered.
+///
+/// If `variation` is not null, notes are skipped according to their probability and round-robin
+/// group.
 #[wasm_bindgen]
 pub fn iter_notes_with_cb(
   lines: *const NoteLines,
   start_beat_inclusive: f64,
   end_beat_exclusive: f64,
   cb: Function,
+  humanizer: *mut Humanizer,
+  legato: bool,
+  variation: *mut PlaybackVariation,
 ) {
   let notes = unsafe { &*lines };
+  let humanizer = unsafe { humanizer.as_mut() };
+  let variation = unsafe { variation.as_mut() };
 
-  struct UnreleasedNote {
-    line_ix: usize,
-    start_point: f64,
-    note: Note,
+  let events = notes.playback_events(
+    start_beat_inclusive,
+    end_beat_exclusive,
+    humanizer,
+    legato,
+    variation,
+  );
+  for evt in events {
+    let _ = cb.apply(
+      &JsValue::NULL,
+      &Array::of4(
+        &JsValue::from(evt.is_attack),
+        &JsValue::from(evt.line_ix as u32),
+        &JsValue::from(evt.beat),
+        &JsValue::from(evt.velocity),
+      ),
+    );
   }
+}
 
-  let mut unreleased_notes: HashMap<u32, UnreleasedNote> = HashMap::default();
-  let mut events: Vec<(bool, usize, f64)> = Vec::default();
-  let iter = notes.lines.iter().enumerate().flat_map(|(line_ix, line)| {
-    line
-      .inner
-      .range((
-        Bound::Included(FloatOrd(start_beat_inclusive)),
-        if end_beat_exclusive < 0. {
-          Bound::Unbounded
-        } else {
-          Bound::Excluded(FloatOrd(end_beat_exclusive))
-        },
-      ))
-      .map(move |(pos, entry)| (line_ix, pos.0, entry))
+#[wasm_bindgen]
+pub fn set_line_count(lines: *mut NoteLines, new_line_count: usize) {
+  let lines = unsafe { &mut *lines };
+  lines.record_input(RecordedInput::SetLineCount {
+    line_count: new_line_count as u32,
   });
-  for (line_ix, pos, entry) in iter {
-    match entry {
-      NoteEntry::NoteStart { note } => {
-        events.push((true, line_ix, pos));
-        let existing = unreleased_notes.insert(note.id, UnreleasedNote {
-          line_ix,
-          start_point: pos,
-          note: note.clone(),
-        });
-        assert!(
-          existing.is_none(),
-          "Note cannot be gated more than once before being released"
-        );
-      },
-      NoteEntry::NoteEnd { note_id } => {
-        let existing = unreleased_notes.remove(&note_id);
-        if existing.is_some() {
-          events.push((false, line_ix, pos));
-        }
-      },
-      NoteEntry::StartAndEnd {
-        start_note,
-        end_note_id,
-      } => {
-        // release before attack
-        let existing = unreleased_notes.remove(&end_note_id);
-        if existing.is_some() {
-          events.push((false, line_ix, pos));
-        }
-
-        events.push((true, line_ix, pos));
-        let existing = unreleased_notes.insert(start_note.id, UnreleasedNote {
-          line_ix,
-          start_point: pos,
-          note: start_note.clone(),
-        });
-        assert!(
-          existing.is_none(),
-          "Note cannot be gated more than once before being released"
-        );
-      },
+
+  while lines.lines.len() < new_line_count {
+    lines.lines.push(NoteContainer::default());
+  }
+  while lines.lines.len() > new_line_count {
+    let removed_line = lines.lines.pop().unwrap();
+    let mut removed_note_ids = HashSet::new();
+    removed_line.iter_notes(&mut removed_note_ids, 0., f64::INFINITY);
+    for note_id in removed_note_ids {
+      lines.forget_note(note_id);
     }
   }
+}
 
-  // Make sure that we include the release events for notes that exceed the end of the selection
-  if end_beat_exclusive < 0. {
-    assert!(unreleased_notes.is_empty());
-  }
-  for note in unreleased_notes.values() {
-    let release_time = note.start_point + note.note.length;
-    events.push((false, note.line_ix, release_time));
+/// Starts recording all inputs made to `lines` through the FFI, replacing any recording that's
+/// already in progress.  See `crate::input_recording`.
+#[wasm_bindgen]
+pub fn start_input_recording(lines: *mut NoteLines) {
+  let notes = unsafe { &mut *lines };
+  notes.input_recording = Some(InputRecording::start(notes));
+}
+
+/// Should be called once per rendered frame while recording so that inputs are tagged with the
+/// frame they were made during
+#[wasm_bindgen]
+pub fn advance_input_recording_frame(lines: *mut NoteLines) {
+  let notes = unsafe { &mut *lines };
+  if let Some(recording) = &mut notes.input_recording {
+    recording.advance_frame();
   }
+}
 
-  for (is_attack, line_ix, beat) in events {
-    let _ = cb.call3(
-      &JsValue::NULL,
-      &JsValue::from(is_attack),
-      &JsValue::from(line_ix as u32),
-      &JsValue::from(beat),
-    );
+/// Stops recording and returns the encoded recording, which can be replayed natively with
+/// `InputRecording::decode` and `InputRecording::replay`.  Returns an empty array if `lines`
+/// wasn't being recorded.
+#[wasm_bindgen]
+pub fn stop_input_recording(lines: *mut NoteLines) -> Vec<u8> {
+  let notes = unsafe { &mut *lines };
+  notes
+    .input_recording
+    .take()
+    .map(|recording| recording.encode())
+    .unwrap_or_default()
+}
+
+#[wasm_bindgen]
+pub fn create_collab_session(client_id: String) -> *mut CollabSession {
+  Box::into_raw(Box::new(CollabSession::new(client_id)))
+}
+
+#[wasm_bindgen]
+pub fn free_collab_session(session: *mut CollabSession) { unsafe { drop(Box::from_raw(session)) } }
+
+fn build_note_op(is_delete: bool, line_ix: usize, start_beat: f64, length: f64) -> NoteOp {
+  if is_delete {
+    NoteOp::Delete
+  } else {
+    NoteOp::Move(NotePlacement {
+      line_ix,
+      start_beat,
+      length,
+    })
   }
 }
 
+/// Records an edit that was made to the note with ID `local_note_id` in the local note lines.
+/// Deleting a note is recorded by setting `is_delete`; otherwise the note's full current placement
+/// must be provided.  The operation is queued until it's taken with `collab_take_pending_ops`.
 #[wasm_bindgen]
-pub fn set_line_count(lines: *mut NoteLines, new_line_count: usize) {
+pub fn collab_record_local_op(
+  session: *mut CollabSession,
+  lines: *mut NoteLines,
+  local_note_id: u32,
+  is_delete: bool,
+  line_ix: usize,
+  start_beat: f64,
+  length: f64,
+) {
+  let session = unsafe { &mut *session };
   let lines = unsafe { &mut *lines };
+  session.record_local_op(
+    lines,
+    local_note_id,
+    build_note_op(is_delete, line_ix, start_beat, length),
+  );
+}
 
-  while lines.lines.len() < new_line_count {
-    lines.lines.push(NoteContainer::default());
-  }
-  while lines.lines.len() > new_line_count {
-    lines.lines.pop();
+/// Returns all local operations that haven't been sent to other clients yet.  Edits made while
+/// disconnected stay queued, so this should only be called once the connection is open.
+///
+/// Each operation is returned as `[noteClientId, noteClock, clientId, clock, type, lineIx,
+/// startBeat, length]` where `type` is one of `"insert"`, `"move"`, or `"delete"`.
+#[wasm_bindgen]
+pub fn collab_take_pending_ops(session: *mut CollabSession) -> Array {
+  let session = unsafe { &mut *session };
+  session
+    .take_pending_ops()
+    .into_iter()
+    .map(|operation| {
+      let (op_type, placement) = match operation.op {
+        NoteOp::Insert(placement) => ("insert", Some(placement)),
+        NoteOp::Move(placement) => ("move", Some(placement)),
+        NoteOp::Delete => ("delete", None),
+      };
+      let placement = placement.unwrap_or(NotePlacement {
+        line_ix: 0,
+        start_beat: 0.,
+        length: 0.,
+      });
+      let serialized = Array::new();
+      serialized.push(&JsValue::from(operation.note_id.client_id));
+      serialized.push(&JsValue::from(operation.note_id.clock));
+      serialized.push(&JsValue::from(operation.stamp.client_id));
+      serialized.push(&JsValue::from(operation.stamp.clock));
+      serialized.push(&JsValue::from(op_type));
+      serialized.push(&JsValue::from(placement.line_ix as u32));
+      serialized.push(&JsValue::from(placement.start_beat));
+      serialized.push(&JsValue::from(placement.length));
+      JsValue::from(serialized)
+    })
+    .collect()
+}
+
+/// Applies an operation received from another client to the note lines.  Returns `true` if it
+/// changed anything, in which case the MIDI editor should re-render its notes.
+#[wasm_bindgen]
+pub fn collab_apply_remote_op(
+  session: *mut CollabSession,
+  lines: *mut NoteLines,
+  note_client_id: String,
+  note_clock: u32,
+  client_id: String,
+  clock: u32,
+  is_delete: bool,
+  line_ix: usize,
+  start_beat: f64,
+  length: f64,
+) -> bool {
+  let session = unsafe { &mut *session };
+  let lines = unsafe { &mut *lines };
+  session.apply_remote_op(lines, &NoteOperation {
+    note_id: CollabNoteId {
+      client_id: note_client_id,
+      clock: note_clock,
+    },
+    stamp: OpStamp { clock, client_id },
+    op: build_note_op(is_delete, line_ix, start_beat, length),
+  })
+}
+
+/// Returns where every note that was added, moved, trimmed, or removed by collaboration operations
+/// since the last call is now rendered in the note lines.  This includes local operations, which
+/// can un-hide or trim other notes.
+///
+/// Each change is returned as `[localNoteId, lineIx, startBeat, length]`.  `lineIx` is -1 if the
+/// note was removed or hidden.
+#[wasm_bindgen]
+pub fn collab_take_render_changes(session: *mut CollabSession) -> Array {
+  let session = unsafe { &mut *session };
+  session
+    .take_render_changes()
+    .into_iter()
+    .map(|(local_id, note)| {
+      let serialized = Array::new();
+      serialized.push(&JsValue::from(local_id));
+      match note {
+        Some(note) => {
+          serialized.push(&JsValue::from(note.line_ix as i32));
+          serialized.push(&JsValue::from(note.start_beat));
+          serialized.push(&JsValue::from(note.length));
+        },
+        None => {
+          serialized.push(&JsValue::from(-1));
+          serialized.push(&JsValue::from(0.));
+          serialized.push(&JsValue::from(0.));
+        },
+      }
+      JsValue::from(serialized)
+    })
+    .collect()
+}
+
+/// `seed` determines which clips are picked by random follow actions
+#[wasm_bindgen]
+pub fn create_clip_launcher(quantization_beats: f64, seed: u32) -> *mut ClipLauncher {
+  Box::into_raw(Box::new(ClipLauncher::new(quantization_beats, seed as u64)))
+}
+
+#[wasm_bindgen]
+pub fn free_clip_launcher(launcher: *mut ClipLauncher) { unsafe { drop(Box::from_raw(launcher)) } }
+
+/// Returns the index of the new clip
+#[wasm_bindgen]
+pub fn clip_launcher_add_clip(
+  launcher: *mut ClipLauncher,
+  name: String,
+  length_beats: f64,
+  line_count: usize,
+) -> usize {
+  let launcher = unsafe { &mut *launcher };
+  launcher.add_clip(name, length_beats, line_count)
+}
+
+#[wasm_bindgen]
+pub fn clip_launcher_remove_clip(launcher: *mut ClipLauncher, clip_ix: usize) {
+  let launcher = unsafe { &mut *launcher };
+  launcher.remove_clip(clip_ix);
+}
+
+/// Returns a pointer to the clip's notes which can be passed to all of the other note exports.  It
+/// stays valid until the clip is removed or the launcher is freed.
+#[wasm_bindgen]
+pub fn clip_launcher_get_clip_notes(launcher: *mut ClipLauncher, clip_ix: usize) -> *mut NoteLines {
+  let launcher = unsafe { &mut *launcher };
+  &mut *launcher.clips[clip_ix].notes
+}
+
+#[wasm_bindgen]
+pub fn clip_launcher_set_clip_length(
+  launcher: *mut ClipLauncher,
+  clip_ix: usize,
+  length_beats: f64,
+) {
+  let launcher = unsafe { &mut *launcher };
+  launcher.clips[clip_ix].length_beats = length_beats;
+}
+
+/// Sets the action taken once the clip has played through `repeats` times; see `FollowAction`.
+/// Unknown actions are treated as `FollowAction::None`.
+#[wasm_bindgen]
+pub fn clip_launcher_set_follow_action(
+  launcher: *mut ClipLauncher,
+  clip_ix: usize,
+  action: u8,
+  repeats: u32,
+) {
+  let launcher = unsafe { &mut *launcher };
+  let clip = &mut launcher.clips[clip_ix];
+  clip.follow_action = FollowAction::from_code(action).unwrap_or(FollowAction::None);
+  clip.follow_action_repeats = repeats;
+}
+
+#[wasm_bindgen]
+pub fn clip_launcher_set_quantization(launcher: *mut ClipLauncher, quantization_beats: f64) {
+  let launcher = unsafe { &mut *launcher };
+  launcher.quantization_beats = quantization_beats;
+}
+
+#[wasm_bindgen]
+pub fn clip_launcher_launch(launcher: *mut ClipLauncher, clip_ix: usize, cur_beat: f64) {
+  let launcher = unsafe { &mut *launcher };
+  launcher.launch(clip_ix, cur_beat);
+}
+
+#[wasm_bindgen]
+pub fn clip_launcher_stop(launcher: *mut ClipLauncher, clip_ix: usize, cur_beat: f64) {
+  let launcher = unsafe { &mut *launcher };
+  launcher.stop(clip_ix, cur_beat);
+}
+
+#[wasm_bindgen]
+pub fn clip_launcher_stop_all(launcher: *mut ClipLauncher, cur_beat: f64) {
+  let launcher = unsafe { &mut *launcher };
+  launcher.stop_all(cur_beat);
+}
+
+/// Stops all clips immediately.  Should be called when the transport stops.
+#[wasm_bindgen]
+pub fn clip_launcher_reset(launcher: *mut ClipLauncher) {
+  let launcher = unsafe { &mut *launcher };
+  launcher.reset();
+}
+
+/// Returns the state type and beat of each clip in order; see `ClipState::encode`
+#[wasm_bindgen]
+pub fn clip_launcher_get_states(launcher: *const ClipLauncher) -> Vec<f64> {
+  let launcher = unsafe { &*launcher };
+  launcher
+    .states
+    .iter()
+    .flat_map(|state| {
+      let (state_type, beat) = state.encode();
+      [state_type as f64, beat]
+    })
+    .collect()
+}
+
+/// Calls `cb` for each note attack and release from the launcher's clips between the two beats and
+/// then advances clips' states to `end_beat_exclusive`.  See `ClipLauncher::playback_events`.
+///
+/// `cb` is called with the clip index followed by the same four arguments as `iter_notes_with_cb`.
+#[wasm_bindgen]
+pub fn clip_launcher_iter_events_with_cb(
+  launcher: *mut ClipLauncher,
+  start_beat_inclusive: f64,
+  end_beat_exclusive: f64,
+  cb: Function,
+) {
+  let launcher = unsafe { &mut *launcher };
+  for evt in launcher.playback_events(start_beat_inclusive, end_beat_exclusive) {
+    let _ = cb.apply(
+      &JsValue::NULL,
+      &Array::of5(
+        &JsValue::from(evt.clip_ix as u32),
+        &JsValue::from(evt.is_attack),
+        &JsValue::from(evt.line_ix as u32),
+        &JsValue::from(evt.beat),
+        &JsValue::from(evt.velocity),
+      ),
+    );
   }
 }
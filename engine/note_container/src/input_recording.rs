@@ -0,0 +1,612 @@
+//! Recording of all inputs made to a `NoteLines` through the FFI so that editor bugs reported by
+//! users can be reproduced.  Mouse, keyboard, and MIDI input are all handled by the UI, which turns
+//! them into calls to the exports in `crate::exports`.  Recording those calls along with the notes
+//! as they were when recording started is enough to reproduce the notes exactly by replaying the
+//! calls against a fresh `NoteLines`, natively and without a browser.
+//!
+//! Each input is tagged with the frame that it was made during.  The UI advances the frame once per
+//! rendered frame so that replay can be stopped at any frame to find where things went wrong.
+//!
+//! Inputs are recorded before they're applied so that an input which panics is still included.
+//! Notes changed by remote collaborators aren't recorded.
+//!
+//! Recordings are encoded as little-endian values:
+//!
+//! magic (4 bytes), version (u8), initial snapshot length (u32), initial snapshot (see
+//! `crate::snapshot`), initial note count (u32), initial note IDs (u32 each), input count (u32)
+//!
+//! followed by each input: frame (u32), input type (u8), and the input's fields in order.
+
+use std::fmt;
+
+use crate::{
+  exports,
+  note_container::Note,
+  note_lines::NoteLines,
+  snapshot::{decode_snapshot, encode_snapshot, notes_in_snapshot_order, SnapshotError},
+};
+
+pub const RECORDING_MAGIC: [u8; 4] = *b"WSIR";
+pub const RECORDING_VERSION: u8 = 1;
+
+/// A call to one of the exports that modify notes, with the same arguments.  Inputs whose exports
+/// generate values, such as note IDs or humanized timing, store the generated values so that
+/// replay doesn't depend on global state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordedInput {
+  CreateNote {
+    line_ix: u32,
+    start_point: f64,
+    length: f64,
+    note_id: u32,
+  },
+  DeleteNote {
+    line_ix: u32,
+    start_point: f64,
+    note_id: u32,
+  },
+  MoveNoteHorizontal {
+    line_ix: u32,
+    start_point: f64,
+    note_id: u32,
+    desired_new_start_point: f64,
+  },
+  ResizeNoteStart {
+    line_ix: u32,
+    start_point: f64,
+    note_id: u32,
+    new_start_point: f64,
+  },
+  ResizeNoteEnd {
+    line_ix: u32,
+    start_point: f64,
+    note_id: u32,
+    new_end_point: f64,
+  },
+  MoveNoteVertically {
+    src_line_ix: u32,
+    dst_line_ix: u32,
+    start_point: f64,
+    note_id: u32,
+  },
+  SetVelocity {
+    note_id: u32,
+    velocity: f32,
+  },
+  SetProbability {
+    note_id: u32,
+    probability: f32,
+  },
+  SetRoundRobinGroup {
+    note_id: u32,
+    group: i32,
+  },
+  SetArticulation {
+    note_id: u32,
+    articulation: u8,
+  },
+  SetLineCount {
+    line_count: u32,
+  },
+  MoveNote {
+    src_line_ix: u32,
+    start_point: f64,
+    note_id: u32,
+    dst_line_ix: u32,
+    new_start_point: f64,
+    new_length: f64,
+  },
+}
+
+const INPUT_TYPE_CREATE_NOTE: u8 = 0;
+const INPUT_TYPE_DELETE_NOTE: u8 = 1;
+const INPUT_TYPE_MOVE_NOTE_HORIZONTAL: u8 = 2;
+const INPUT_TYPE_RESIZE_NOTE_START: u8 = 3;
+const INPUT_TYPE_RESIZE_NOTE_END: u8 = 4;
+const INPUT_TYPE_MOVE_NOTE_VERTICALLY: u8 = 5;
+const INPUT_TYPE_SET_VELOCITY: u8 = 6;
+const INPUT_TYPE_SET_PROBABILITY: u8 = 7;
+const INPUT_TYPE_SET_ROUND_ROBIN_GROUP: u8 = 8;
+const INPUT_TYPE_SET_ARTICULATION: u8 = 9;
+const INPUT_TYPE_SET_LINE_COUNT: u8 = 10;
+const INPUT_TYPE_MOVE_NOTE: u8 = 11;
+
+impl RecordedInput {
+  /// Makes the same export call that was recorded
+  fn apply(&self, lines: &mut NoteLines) {
+    let lines: *mut NoteLines = lines;
+    match *self {
+      RecordedInput::CreateNote {
+        line_ix,
+        start_point,
+        length,
+        note_id,
+      } => {
+        exports::create_note(lines, line_ix as usize, start_point, length, note_id);
+      },
+      RecordedInput::DeleteNote {
+        line_ix,
+        start_point,
+        note_id,
+      } => exports::delete_note(lines, line_ix as usize, start_point, note_id),
+      RecordedInput::MoveNoteHorizontal {
+        line_ix,
+        start_point,
+        note_id,
+        desired_new_start_point,
+      } => {
+        exports::move_note_horizontal(
+          lines,
+          line_ix as usize,
+          start_point,
+          note_id,
+          desired_new_start_point,
+        );
+      },
+      RecordedInput::ResizeNoteStart {
+        line_ix,
+        start_point,
+        note_id,
+        new_start_point,
+      } => {
+        exports::resize_note_horizontal_start(
+          lines,
+          line_ix as usize,
+          start_point,
+          note_id,
+          new_start_point,
+        );
+      },
+      RecordedInput::ResizeNoteEnd {
+        line_ix,
+        start_point,
+        note_id,
+        new_end_point,
+      } => {
+        exports::resize_note_horizontal_end(
+          lines,
+          line_ix as usize,
+          start_point,
+          note_id,
+          new_end_point,
+        );
+      },
+      RecordedInput::MoveNoteVertically {
+        src_line_ix,
+        dst_line_ix,
+        start_point,
+        note_id,
+      } => {
+        exports::move_note_vertically(
+          lines,
+          src_line_ix as usize,
+          dst_line_ix as usize,
+          start_point,
+          note_id,
+        );
+      },
+      RecordedInput::SetVelocity { note_id, velocity } =>
+        exports::set_note_velocity(lines, note_id, velocity),
+      RecordedInput::SetProbability {
+        note_id,
+        probability,
+      } => exports::set_note_probability(lines, note_id, probability),
+      RecordedInput::SetRoundRobinGroup { note_id, group } =>
+        exports::set_note_round_robin_group(lines, note_id, group),
+      RecordedInput::SetArticulation {
+        note_id,
+        articulation,
+      } => exports::set_note_articulation(lines, note_id, articulation),
+      RecordedInput::SetLineCount { line_count } =>
+        exports::set_line_count(lines, line_count as usize),
+      RecordedInput::MoveNote {
+        src_line_ix,
+        start_point,
+        note_id,
+        dst_line_ix,
+        new_start_point,
+        new_length,
+      } => {
+        exports::move_note(
+          lines,
+          src_line_ix as usize,
+          start_point,
+          note_id,
+          dst_line_ix as usize,
+          new_start_point,
+          new_length,
+        );
+      },
+    }
+  }
+
+  fn encode(&self, out: &mut Vec<u8>) {
+    match *self {
+      RecordedInput::CreateNote {
+        line_ix,
+        start_point,
+        length,
+        note_id,
+      } => {
+        out.push(INPUT_TYPE_CREATE_NOTE);
+        out.extend_from_slice(&line_ix.to_le_bytes());
+        out.extend_from_slice(&start_point.to_le_bytes());
+        out.extend_from_slice(&length.to_le_bytes());
+        out.extend_from_slice(&note_id.to_le_bytes());
+      },
+      RecordedInput::DeleteNote {
+        line_ix,
+        start_point,
+        note_id,
+      } => {
+        out.push(INPUT_TYPE_DELETE_NOTE);
+        out.extend_from_slice(&line_ix.to_le_bytes());
+        out.extend_from_slice(&start_point.to_le_bytes());
+        out.extend_from_slice(&note_id.to_le_bytes());
+      },
+      RecordedInput::MoveNoteHorizontal {
+        line_ix,
+        start_point,
+        note_id,
+        desired_new_start_point: new_point,
+      }
+      | RecordedInput::ResizeNoteStart {
+        line_ix,
+        start_point,
+        note_id,
+        new_start_point: new_point,
+      }
+      | RecordedInput::ResizeNoteEnd {
+        line_ix,
+        start_point,
+        note_id,
+        new_end_point: new_point,
+      } => {
+        out.push(match self {
+          RecordedInput::MoveNoteHorizontal { .. } => INPUT_TYPE_MOVE_NOTE_HORIZONTAL,
+          RecordedInput::ResizeNoteStart { .. } => INPUT_TYPE_RESIZE_NOTE_START,
+          _ => INPUT_TYPE_RESIZE_NOTE_END,
+        });
+        out.extend_from_slice(&line_ix.to_le_bytes());
+        out.extend_from_slice(&start_point.to_le_bytes());
+        out.extend_from_slice(&note_id.to_le_bytes());
+        out.extend_from_slice(&new_point.to_le_bytes());
+      },
+      RecordedInput::MoveNoteVertically {
+        src_line_ix,
+        dst_line_ix,
+        start_point,
+        note_id,
+      } => {
+        out.push(INPUT_TYPE_MOVE_NOTE_VERTICALLY);
+        out.extend_from_slice(&src_line_ix.to_le_bytes());
+        out.extend_from_slice(&dst_line_ix.to_le_bytes());
+        out.extend_from_slice(&start_point.to_le_bytes());
+        out.extend_from_slice(&note_id.to_le_bytes());
+      },
+      RecordedInput::SetVelocity {
+        note_id,
+        velocity: value,
+      }
+      | RecordedInput::SetProbability {
+        note_id,
+        probability: value,
+      } => {
+        out.push(match self {
+          RecordedInput::SetVelocity { .. } => INPUT_TYPE_SET_VELOCITY,
+          _ => INPUT_TYPE_SET_PROBABILITY,
+        });
+        out.extend_from_slice(&note_id.to_le_bytes());
+        out.extend_from_slice(&value.to_le_bytes());
+      },
+      RecordedInput::SetRoundRobinGroup { note_id, group } => {
+        out.push(INPUT_TYPE_SET_ROUND_ROBIN_GROUP);
+        out.extend_from_slice(&note_id.to_le_bytes());
+        out.extend_from_slice(&group.to_le_bytes());
+      },
+      RecordedInput::SetArticulation {
+        note_id,
+        articulation,
+      } => {
+        out.push(INPUT_TYPE_SET_ARTICULATION);
+        out.extend_from_slice(&note_id.to_le_bytes());
+        out.push(articulation);
+      },
+      RecordedInput::SetLineCount { line_count } => {
+        out.push(INPUT_TYPE_SET_LINE_COUNT);
+        out.extend_from_slice(&line_count.to_le_bytes());
+      },
+      RecordedInput::MoveNote {
+        src_line_ix,
+        start_point,
+        note_id,
+        dst_line_ix,
+        new_start_point,
+        new_length,
+      } => {
+        out.push(INPUT_TYPE_MOVE_NOTE);
+        out.extend_from_slice(&src_line_ix.to_le_bytes());
+        out.extend_from_slice(&start_point.to_le_bytes());
+        out.extend_from_slice(&note_id.to_le_bytes());
+        out.extend_from_slice(&dst_line_ix.to_le_bytes());
+        out.extend_from_slice(&new_start_point.to_le_bytes());
+        out.extend_from_slice(&new_length.to_le_bytes());
+      },
+    }
+  }
+
+  fn decode(reader: &mut Reader) -> Result<Self, RecordingError> {
+    Ok(match reader.u8()? {
+      INPUT_TYPE_CREATE_NOTE => RecordedInput::CreateNote {
+        line_ix: reader.u32()?,
+        start_point: reader.f64()?,
+        length: reader.f64()?,
+        note_id: reader.u32()?,
+      },
+      INPUT_TYPE_DELETE_NOTE => RecordedInput::DeleteNote {
+        line_ix: reader.u32()?,
+        start_point: reader.f64()?,
+        note_id: reader.u32()?,
+      },
+      INPUT_TYPE_MOVE_NOTE_HORIZONTAL => RecordedInput::MoveNoteHorizontal {
+        line_ix: reader.u32()?,
+        start_point: reader.f64()?,
+        note_id: reader.u32()?,
+        desired_new_start_point: reader.f64()?,
+      },
+      INPUT_TYPE_RESIZE_NOTE_START => RecordedInput::ResizeNoteStart {
+        line_ix: reader.u32()?,
+        start_point: reader.f64()?,
+        note_id: reader.u32()?,
+        new_start_point: reader.f64()?,
+      },
+      INPUT_TYPE_RESIZE_NOTE_END => RecordedInput::ResizeNoteEnd {
+        line_ix: reader.u32()?,
+        start_point: reader.f64()?,
+        note_id: reader.u32()?,
+        new_end_point: reader.f64()?,
+      },
+      INPUT_TYPE_MOVE_NOTE_VERTICALLY => RecordedInput::MoveNoteVertically {
+        src_line_ix: reader.u32()?,
+        dst_line_ix: reader.u32()?,
+        start_point: reader.f64()?,
+        note_id: reader.u32()?,
+      },
+      INPUT_TYPE_SET_VELOCITY => RecordedInput::SetVelocity {
+        note_id: reader.u32()?,
+        velocity: reader.f32()?,
+      },
+      INPUT_TYPE_SET_PROBABILITY => RecordedInput::SetProbability {
+        note_id: reader.u32()?,
+        probability: reader.f32()?,
+      },
+      INPUT_TYPE_SET_ROUND_ROBIN_GROUP => RecordedInput::SetRoundRobinGroup {
+        note_id: reader.u32()?,
+        group: reader.i32()?,
+      },
+      INPUT_TYPE_SET_ARTICULATION => RecordedInput::SetArticulation {
+        note_id: reader.u32()?,
+        articulation: reader.u8()?,
+      },
+      INPUT_TYPE_SET_LINE_COUNT => RecordedInput::SetLineCount {
+        line_count: reader.u32()?,
+      },
+      INPUT_TYPE_MOVE_NOTE => RecordedInput::MoveNote {
+        src_line_ix: reader.u32()?,
+        start_point: reader.f64()?,
+        note_id: reader.u32()?,
+        dst_line_ix: reader.u32()?,
+        new_start_point: reader.f64()?,
+        new_length: reader.f64()?,
+      },
+      other => return Err(RecordingError::UnknownInputType(other)),
+    })
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordingError {
+  BadMagic,
+  UnsupportedVersion(u8),
+  Truncated,
+  UnknownInputType(u8),
+  InvalidSnapshot(SnapshotError),
+}
+
+impl fmt::Display for RecordingError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RecordingError::BadMagic => write!(f, "not an input recording"),
+      RecordingError::UnsupportedVersion(version) =>
+        write!(f, "unsupported input recording version: {version}"),
+      RecordingError::Truncated => write!(f, "input recording is truncated"),
+      RecordingError::UnknownInputType(input_type) => write!(f, "unknown input type: {input_type}"),
+      RecordingError::InvalidSnapshot(err) => write!(f, "invalid initial snapshot: {err}"),
+    }
+  }
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  offset: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn take<const N: usize>(&mut self) -> Result<[u8; N], RecordingError> {
+    let end = self.offset + N;
+    let field = self
+      .bytes
+      .get(self.offset..end)
+      .ok_or(RecordingError::Truncated)?;
+    self.offset = end;
+    Ok(field.try_into().unwrap())
+  }
+
+  fn take_slice(&mut self, len: usize) -> Result<&'a [u8], RecordingError> {
+    let end = self
+      .offset
+      .checked_add(len)
+      .ok_or(RecordingError::Truncated)?;
+    let slice = self
+      .bytes
+      .get(self.offset..end)
+      .ok_or(RecordingError::Truncated)?;
+    self.offset = end;
+    Ok(slice)
+  }
+
+  fn u8(&mut self) -> Result<u8, RecordingError> { Ok(self.take::<1>()?[0]) }
+
+  fn u32(&mut self) -> Result<u32, RecordingError> { Ok(u32::from_le_bytes(self.take()?)) }
+
+  fn i32(&mut self) -> Result<i32, RecordingError> { Ok(i32::from_le_bytes(self.take()?)) }
+
+  fn f32(&mut self) -> Result<f32, RecordingError> { Ok(f32::from_le_bytes(self.take()?)) }
+
+  fn f64(&mut self) -> Result<f64, RecordingError> { Ok(f64::from_le_bytes(self.take()?)) }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputRecording {
+  /// Snapshot of the notes when recording started
+  initial_snapshot: Vec<u8>,
+  /// IDs of the notes in `initial_snapshot` in the same order, which aren't stored in snapshots
+  initial_note_ids: Vec<u32>,
+  frame: u32,
+  /// Each input along with the frame it was made during
+  pub inputs: Vec<(u32, RecordedInput)>,
+}
+
+impl InputRecording {
+  pub fn start(lines: &NoteLines) -> Self {
+    InputRecording {
+      initial_snapshot: encode_snapshot(lines),
+      initial_note_ids: notes_in_snapshot_order(lines)
+        .into_iter()
+        .map(|(_, _, note_id, _)| note_id)
+        .collect(),
+      frame: 0,
+      inputs: Vec::new(),
+    }
+  }
+
+  pub fn record(&mut self, input: RecordedInput) { self.inputs.push((self.frame, input)); }
+
+  pub fn advance_frame(&mut self) { self.frame += 1; }
+
+  pub fn encode(&self) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&RECORDING_MAGIC);
+    out.push(RECORDING_VERSION);
+    out.extend_from_slice(&(self.initial_snapshot.len() as u32).to_le_bytes());
+    out.extend_from_slice(&self.initial_snapshot);
+    out.extend_from_slice(&(self.initial_note_ids.len() as u32).to_le_bytes());
+    for note_id in &self.initial_note_ids {
+      out.extend_from_slice(&note_id.to_le_bytes());
+    }
+    out.extend_from_slice(&(self.inputs.len() as u32).to_le_bytes());
+    for (frame, input) in &self.inputs {
+      out.extend_from_slice(&frame.to_le_bytes());
+      input.encode(&mut out);
+    }
+    out
+  }
+
+  pub fn decode(bytes: &[u8]) -> Result<Self, RecordingError> {
+    let mut reader = Reader { bytes, offset: 0 };
+    if reader.take::<4>()? != RECORDING_MAGIC {
+      return Err(RecordingError::BadMagic);
+    }
+    let version = reader.u8()?;
+    if version != RECORDING_VERSION {
+      return Err(RecordingError::UnsupportedVersion(version));
+    }
+
+    let snapshot_len = reader.u32()? as usize;
+    let initial_snapshot = reader.take_slice(snapshot_len)?.to_vec();
+    let note_id_count = reader.u32()? as usize;
+    let initial_note_ids = (0..note_id_count)
+      .map(|_| reader.u32())
+      .collect::<Result<Vec<_>, _>>()?;
+    let input_count = reader.u32()? as usize;
+    let inputs = (0..input_count)
+      .map(|_| Ok((reader.u32()?, RecordedInput::decode(&mut reader)?)))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(InputRecording {
+      initial_snapshot,
+      initial_note_ids,
+      frame: inputs.last().map(|(frame, _)| *frame).unwrap_or(0),
+      inputs,
+    })
+  }
+
+  /// Restores the notes as they were when recording started and replays all inputs made before
+  /// `end_frame`, or all inputs if it's `None`.  Panics in the same way that the original inputs
+  /// did if they triggered a bug that panics.
+  pub fn replay(&self, end_frame: Option<u32>) -> Result<NoteLines, RecordingError> {
+    let (line_count, notes) =
+      decode_snapshot(&self.initial_snapshot).map_err(RecordingError::InvalidSnapshot)?;
+    if notes.len() != self.initial_note_ids.len() {
+      return Err(RecordingError::Truncated);
+    }
+
+    let mut lines = NoteLines::new(line_count);
+    for (note, &note_id) in notes.iter().zip(&self.initial_note_ids) {
+      lines.lines[note.line_ix].add_note(note.start_point, Note {
+        id: note_id,
+        length: note.length,
+      });
+      lines.set_velocity(note_id, note.velocity);
+      lines.set_probability(note_id, note.probability);
+      lines.set_round_robin_group(note_id, note.round_robin_group);
+      lines.set_articulation(note_id, note.articulation);
+    }
+
+    for (frame, input) in &self.inputs {
+      if end_frame.is_some_and(|end_frame| *frame >= end_frame) {
+        break;
+      }
+      input.apply(&mut lines);
+    }
+    Ok(lines)
+  }
+}
+
+#[test]
+fn recordings_replay_to_the_same_notes() {
+  let mut lines = NoteLines::new(4);
+  exports::create_note(&mut lines, 0, 0., 1., 1);
+  exports::set_note_velocity(&mut lines, 1, 0.5);
+  lines.input_recording = Some(InputRecording::start(&lines));
+
+  exports::create_note(&mut lines, 1, 2., 1., 2);
+  exports::move_note_horizontal(&mut lines, 0, 0., 1, 0.5);
+  lines.input_recording.as_mut().unwrap().advance_frame();
+  exports::resize_note_horizontal_end(&mut lines, 1, 2., 2, 4.);
+  exports::move_note_vertically(&mut lines, 1, 3, 2., 2);
+  exports::set_note_articulation(&mut lines, 2, 1);
+  exports::set_note_round_robin_group(&mut lines, 1, 3);
+  exports::move_note(&mut lines, 0, 0.5, 1, 2, 1., 2.);
+
+  let encoded = lines.input_recording.take().unwrap().encode();
+  let recording = InputRecording::decode(&encoded).unwrap();
+  assert_eq!(recording.inputs.len(), 7);
+
+  let replayed = recording.replay(None).unwrap();
+  assert_eq!(encode_snapshot(&replayed), encode_snapshot(&lines));
+
+  // Only the first frame's inputs are replayed
+  let replayed = recording.replay(Some(1)).unwrap();
+  let (_, notes) = decode_snapshot(&encode_snapshot(&replayed)).unwrap();
+  let summary: Vec<_> = notes
+    .iter()
+    .map(|note| (note.line_ix, note.start_point, note.length, note.velocity))
+    .collect();
+  assert_eq!(summary, vec![(0, 0.5, 1., 0.5), (1, 2., 1., 1.)]);
+
+  assert_eq!(
+    InputRecording::decode(&encoded[..encoded.len() - 1]),
+    Err(RecordingError::Truncated)
+  );
+}
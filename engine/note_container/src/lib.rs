@@ -5,6 +5,14 @@
 
 #![feature(vec_into_raw_parts)]
 
+pub mod articulation;
+pub mod clips;
+pub mod collaboration;
 pub mod exports;
+pub mod input_recording;
 pub mod note_container;
 pub mod note_lines;
+pub mod playback_variation;
+pub mod snapshot;
+#[cfg(test)]
+mod tests;
@@ -324,11 +324,18 @@ impl NoteContainer {
               break;
             }
           },
-          NoteEntry::NoteEnd { .. } =>
+          NoteEntry::NoteEnd { .. } => {
+            // Starting after a note that ends past the target start point would move us further
+            // than was requested
+            if point.0 > new_start_point {
+              continue;
+            }
+
             if self.check_can_add_note(point.0, note.length) {
               best_start_point = Some(point.0);
               break;
-            },
+            }
+          },
           _ => continue,
         }
       }
@@ -726,6 +733,21 @@ pub fn resize_note_end_blocked() {
   assert_eq!(new_end_point, 4.);
 }
 
+#[test]
+pub fn move_horizontal_right_does_not_overshoot() {
+  let mut container = NoteContainer::default();
+  let blocking_note = Note { id: 0, length: 1.5 };
+  let moving_note = Note {
+    id: 1,
+    length: 0.25,
+  };
+  container.add_note(12.75, blocking_note);
+  container.add_note(0., moving_note);
+  // The end of the blocking note is past the target, so the note should stop before it instead
+  let new_start_point = container.move_note_horizontal(0., moving_note.id, 14.);
+  assert_eq!(new_start_point, 12.5);
+}
+
 #[test]
 /// It should not be possible to move a note such that it is entirely within another note
 pub fn prevent_moving_inside_other_notes() {
@@ -1,14 +1,154 @@
-use std::collections::HashSet;
+use std::{
+  collections::{BTreeMap, HashMap, HashSet},
+  ops::Bound,
+};
 
-use crate::note_container::NoteContainer;
+use common::humanize::Humanizer;
+use float_ord::FloatOrd;
+
+use crate::{
+  articulation::{self, ARTICULATION_ALL, ARTICULATION_TIE},
+  input_recording::{InputRecording, RecordedInput},
+  note_container::{Note, NoteContainer, NoteEntry},
+  playback_variation::PlaybackVariation,
+};
+
+/// A note attack or release generated for playback
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlaybackEvent {
+  pub is_attack: bool,
+  pub line_ix: usize,
+  pub beat: f64,
+  /// In [0, 1]
+  pub velocity: f32,
+}
 
 /// Represents an array of `NoteContainer`s, matching the representation of the MIDI editor where
 /// each note has its own container.
 pub struct NoteLines {
   pub lines: Vec<NoteContainer>,
+  /// Velocity of each note in [0, 1] keyed by note ID.  Notes without an entry have full velocity.
+  /// Entries of this and the other per-note maps are removed when their notes are deleted with
+  /// `delete_note` and kept when they're moved with `move_note`.
+  pub velocities: HashMap<u32, f32>,
+  /// Chance of each note being played in [0, 1] keyed by note ID.  Notes without an entry are
+  /// always played.
+  pub probabilities: HashMap<u32, f32>,
+  /// Round-robin group of each note keyed by note ID.  Notes without an entry aren't in a group.
+  pub round_robin_groups: HashMap<u32, u32>,
+  /// Bitmask of `ARTICULATION_*` flags for each note keyed by note ID.  Notes without an entry
+  /// have no articulations.
+  pub articulations: HashMap<u32, u8>,
+  /// If set, all inputs made to these lines through the FFI are recorded so that they can be
+  /// replayed later.  See `crate::input_recording`.
+  pub input_recording: Option<InputRecording>,
 }
 
 impl NoteLines {
+  pub fn new(line_count: usize) -> Self {
+    NoteLines {
+      lines: (0..line_count).map(|_| NoteContainer::default()).collect(),
+      velocities: HashMap::new(),
+      probabilities: HashMap::new(),
+      round_robin_groups: HashMap::new(),
+      articulations: HashMap::new(),
+      input_recording: None,
+    }
+  }
+
+  pub fn record_input(&mut self, input: RecordedInput) {
+    if let Some(recording) = &mut self.input_recording {
+      recording.record(input);
+    }
+  }
+
+  pub fn get_velocity(&self, note_id: u32) -> f32 {
+    self.velocities.get(&note_id).copied().unwrap_or(1.)
+  }
+
+  pub fn set_velocity(&mut self, note_id: u32, velocity: f32) {
+    if velocity >= 1. {
+      self.velocities.remove(&note_id);
+    } else {
+      self.velocities.insert(note_id, velocity.max(0.));
+    }
+  }
+
+  pub fn get_probability(&self, note_id: u32) -> f32 {
+    self.probabilities.get(&note_id).copied().unwrap_or(1.)
+  }
+
+  pub fn set_probability(&mut self, note_id: u32, probability: f32) {
+    if probability >= 1. {
+      self.probabilities.remove(&note_id);
+    } else {
+      self.probabilities.insert(note_id, probability.max(0.));
+    }
+  }
+
+  pub fn get_round_robin_group(&self, note_id: u32) -> Option<u32> {
+    self.round_robin_groups.get(&note_id).copied()
+  }
+
+  pub fn set_round_robin_group(&mut self, note_id: u32, group: Option<u32>) {
+    match group {
+      Some(group) => self.round_robin_groups.insert(note_id, group),
+      None => self.round_robin_groups.remove(&note_id),
+    };
+  }
+
+  pub fn get_articulation(&self, note_id: u32) -> u8 {
+    self.articulations.get(&note_id).copied().unwrap_or(0)
+  }
+
+  pub fn set_articulation(&mut self, note_id: u32, articulation: u8) {
+    let articulation = articulation & ARTICULATION_ALL;
+    if articulation == 0 {
+      self.articulations.remove(&note_id);
+    } else {
+      self.articulations.insert(note_id, articulation);
+    }
+  }
+
+  /// Removes a note along with its velocity, probability, round-robin group, and articulation
+  pub fn delete_note(&mut self, line_ix: usize, note_start: f64, note_id: u32) {
+    self.lines[line_ix].remove_note(note_start, note_id);
+    self.forget_note(note_id);
+  }
+
+  /// Removes the velocity, probability, round-robin group, and articulation of a note that no
+  /// longer exists
+  pub fn forget_note(&mut self, note_id: u32) {
+    self.velocities.remove(&note_id);
+    self.probabilities.remove(&note_id);
+    self.round_robin_groups.remove(&note_id);
+    self.articulations.remove(&note_id);
+  }
+
+  /// Moves a note to `new_start` on line `dst_line_ix` with length `new_length`, keeping its ID
+  /// along with its velocity and other per-note data.  Returns `true` if the move was successful,
+  /// `false` if it was blocked in the destination, in which case the note is left where it was.
+  pub fn move_note(
+    &mut self,
+    src_line_ix: usize,
+    note_start: f64,
+    note_id: u32,
+    dst_line_ix: usize,
+    new_start: f64,
+    new_length: f64,
+  ) -> bool {
+    let note = self.lines[src_line_ix].remove_note(note_start, note_id);
+    if !self.lines[dst_line_ix].check_can_add_note(new_start, new_length) {
+      self.lines[src_line_ix].add_note(note_start, note);
+      return false;
+    }
+    self.lines[dst_line_ix].add_note(new_start, Note {
+      id: note_id,
+      length: new_length,
+    });
+    true
+  }
+
   /// Returns `true` if the move was successful, `false` if it was blocked in the destination
   pub fn move_note_vertically(
     &mut self,
@@ -41,4 +181,425 @@ impl NoteLines {
     }
     acc.into_iter().collect()
   }
+
+  /// Returns the IDs of all notes longer than `min_length` and shorter than `max_length` beats
+  pub fn iter_notes_by_length(&self, min_length: f64, max_length: f64) -> Vec<u32> {
+    self
+      .lines
+      .iter()
+      .flat_map(|line| line.inner.values())
+      .filter_map(|entry| match entry {
+        NoteEntry::NoteStart { note }
+        | NoteEntry::StartAndEnd {
+          start_note: note, ..
+        } => Some(note),
+        NoteEntry::NoteEnd { .. } => None,
+      })
+      .filter(|note| note.length > min_length && note.length < max_length)
+      .map(|note| note.id)
+      .collect()
+  }
+
+  /// Returns attack and release events for all notes in all lines in the order they occur, with
+  /// releases ahead of attacks at the same beat.  If `end_beat_exclusive` is negative, it will be
+  /// treated as unbounded.  Notes that extend past the end of the range are released at their end.
+  ///
+  /// If `humanizer` is set, random timing and velocity offsets are applied to each note as it's
+  /// emitted.  Notes are moved as a whole so that their lengths are preserved, except that a note
+  /// is released early if needed so that it ends before the next note on its line starts.
+  ///
+  /// If `legato` is set, notes that start exactly where the previous note on the same line ends
+  /// are tied to it: neither the release of the first note nor the attack of the second is
+  /// emitted, so the note sounds until the end of the last note in the chain without being
+  /// re-triggered.
+  ///
+  /// If `variation` is set, notes are skipped according to their probability and only one of the
+  /// alternate takes in each round-robin group is played.
+  ///
+  /// Each note's articulation is applied as well: staccato notes are released early, accented notes
+  /// are louder, and tied notes are always tied to the note after them as if `legato` was set.
+  pub fn playback_events(
+    &self,
+    start_beat_inclusive: f64,
+    end_beat_exclusive: f64,
+    mut humanizer: Option<&mut Humanizer>,
+    legato: bool,
+    variation: Option<&mut PlaybackVariation>,
+  ) -> Vec<PlaybackEvent> {
+    struct UnreleasedNote {
+      line_ix: usize,
+      start_point: f64,
+      note: Note,
+    }
+    // Staccato notes are released early.  When notes are tied, the last note in the chain decides.
+    let release_beat = |unreleased: &UnreleasedNote, end_point: f64| {
+      articulation::release_beat(
+        unreleased.start_point,
+        end_point,
+        self.get_articulation(unreleased.note.id),
+      )
+    };
+
+    let range = (
+      Bound::Included(FloatOrd(start_beat_inclusive)),
+      if end_beat_exclusive < 0. {
+        Bound::Unbounded
+      } else {
+        Bound::Excluded(FloatOrd(end_beat_exclusive))
+      },
+    );
+    let skipped_notes = match variation {
+      Some(variation) => self.pick_skipped_notes(range, variation),
+      None => HashSet::default(),
+    };
+
+    let mut unreleased_notes: HashMap<u32, UnreleasedNote> = HashMap::default();
+    // Maps notes that were tied onto a previous note to the first note of the chain, which is the
+    // only one whose attack was emitted
+    let mut tied_to: HashMap<u32, u32> = HashMap::default();
+    let mut events: Vec<(bool, usize, f64, u32)> = Vec::default();
+    let iter = self.lines.iter().enumerate().flat_map(|(line_ix, line)| {
+      line
+        .inner
+        .range(range)
+        .map(move |(pos, entry)| (line_ix, pos.0, entry))
+    });
+    for (line_ix, pos, entry) in iter {
+      match entry {
+        NoteEntry::NoteStart { note } => {
+          if skipped_notes.contains(&note.id) {
+            continue;
+          }
+
+          events.push((true, line_ix, pos, note.id));
+          let existing = unreleased_notes.insert(note.id, UnreleasedNote {
+            line_ix,
+            start_point: pos,
+            note: *note,
+          });
+          assert!(
+            existing.is_none(),
+            "Note cannot be gated more than once before being released"
+          );
+        },
+        NoteEntry::NoteEnd { note_id } =>
+          if let Some(existing) = unreleased_notes.remove(note_id) {
+            events.push((false, line_ix, release_beat(&existing, pos), *note_id));
+          },
+        NoteEntry::StartAndEnd {
+          start_note,
+          end_note_id,
+        } => {
+          let existing = unreleased_notes.remove(end_note_id);
+          let is_skipped = skipped_notes.contains(&start_note.id);
+          let is_tied = legato || self.get_articulation(*end_note_id) & ARTICULATION_TIE != 0;
+          if existing.is_some() && is_tied && !is_skipped {
+            let root_note_id = tied_to.remove(end_note_id).unwrap_or(*end_note_id);
+            tied_to.insert(start_note.id, root_note_id);
+          } else {
+            // release before attack
+            if let Some(existing) = existing {
+              events.push((false, line_ix, release_beat(&existing, pos), *end_note_id));
+            }
+            if !is_skipped {
+              events.push((true, line_ix, pos, start_note.id));
+            }
+          }
+          if is_skipped {
+            continue;
+          }
+
+          let existing = unreleased_notes.insert(start_note.id, UnreleasedNote {
+            line_ix,
+            start_point: pos,
+            note: *start_note,
+          });
+          assert!(
+            existing.is_none(),
+            "Note cannot be gated more than once before being released"
+          );
+        },
+      }
+    }
+
+    // Make sure that we include the release events for notes that exceed the end of the selection
+    if end_beat_exclusive < 0. {
+      assert!(unreleased_notes.is_empty());
+    }
+    for note in unreleased_notes.values() {
+      let beat = release_beat(note, note.start_point + note.note.length);
+      events.push((false, note.line_ix, beat, note.note.id));
+    }
+
+    // Attack and release of each note are shifted by the same amount.  Tied notes are shifted by
+    // the amount of the first note in their chain.
+    let mut humanized_notes: HashMap<u32, (f64, f32)> = HashMap::default();
+    let mut events: Vec<PlaybackEvent> = events
+      .into_iter()
+      .map(|(is_attack, line_ix, beat, note_id)| {
+        let velocity = articulation::articulated_velocity(
+          self.get_velocity(note_id),
+          self.get_articulation(note_id),
+        );
+        let (beat, velocity) = match humanizer.as_mut() {
+          Some(humanizer) => {
+            let (offset, velocity) = if is_attack {
+              let humanized = (
+                humanizer.timing_offset(),
+                humanizer.humanize_velocity(velocity, 1.),
+              );
+              humanized_notes.insert(note_id, humanized);
+              humanized
+            } else {
+              let root_note_id = tied_to.get(&note_id).copied().unwrap_or(note_id);
+              humanized_notes
+                .remove(&root_note_id)
+                .unwrap_or((0., velocity))
+            };
+            ((beat + offset).max(start_beat_inclusive), velocity)
+          },
+          None => (beat, velocity),
+        };
+
+        PlaybackEvent {
+          is_attack,
+          line_ix,
+          beat,
+          velocity,
+        }
+      })
+      .collect();
+
+    // Humanizing can push a release past the attack of the next note on its line, which would cut
+    // that note off as soon as it starts.  Events of each line are still in the order of their
+    // notes here, so walking backwards finds the next attack of each release.
+    if humanizer.is_some() {
+      let mut next_attack_beats = vec![f64::INFINITY; self.lines.len()];
+      for evt in events.iter_mut().rev() {
+        let next_attack_beat = &mut next_attack_beats[evt.line_ix];
+        if evt.is_attack {
+          *next_attack_beat = evt.beat;
+        } else {
+          evt.beat = evt.beat.min(*next_attack_beat);
+        }
+      }
+    }
+
+    // Releases go before attacks at the same beat so that re-triggered notes aren't cut off
+    events.sort_by(|a, b| {
+      a.beat
+        .total_cmp(&b.beat)
+        .then(a.is_attack.cmp(&b.is_attack))
+    });
+    events
+  }
+
+  /// Returns the IDs of notes starting in `range` that shouldn't be played because they lost their
+  /// probability roll or aren't the current take of their round-robin group.
+  fn pick_skipped_notes(
+    &self,
+    range: (Bound<FloatOrd<f64>>, Bound<FloatOrd<f64>>),
+    variation: &mut PlaybackVariation,
+  ) -> HashSet<u32> {
+    let mut skipped_notes = HashSet::default();
+    // Alternate takes keyed by group and start beat, ordered by line.  A `BTreeMap` is used so
+    // that groups are always visited in the same order for a given seed.
+    let mut takes: BTreeMap<(u32, FloatOrd<f64>), Vec<u32>> = BTreeMap::new();
+    for line in &self.lines {
+      for (pos, entry) in line.inner.range(range) {
+        let note_id = match entry {
+          NoteEntry::NoteStart { note } => note.id,
+          NoteEntry::StartAndEnd { start_note, .. } => start_note.id,
+          NoteEntry::NoteEnd { .. } => continue,
+        };
+
+        if !variation.should_play(self.get_probability(note_id)) {
+          skipped_notes.insert(note_id);
+        }
+        if let Some(group) = self.get_round_robin_group(note_id) {
+          takes.entry((group, *pos)).or_default().push(note_id);
+        }
+      }
+    }
+
+    for ((group, beat), note_ids) in takes {
+      let take_ix = variation.next_take(group, beat.0, note_ids.len());
+      for (ix, note_id) in note_ids.into_iter().enumerate() {
+        if ix != take_ix {
+          skipped_notes.insert(note_id);
+        }
+      }
+    }
+
+    skipped_notes
+  }
+}
+
+#[cfg(test)]
+fn build_tied_note_lines() -> NoteLines {
+  let mut lines = NoteLines {
+    lines: vec![NoteContainer::default(), NoteContainer::default()],
+    velocities: HashMap::new(),
+    probabilities: HashMap::new(),
+    round_robin_groups: HashMap::new(),
+    articulations: HashMap::new(),
+    input_recording: None,
+  };
+  lines.lines[0].add_note(0., Note { id: 1, length: 1. });
+  lines.lines[0].add_note(1., Note { id: 2, length: 1. });
+  lines.lines[0].add_note(2., Note { id: 3, length: 1. });
+  lines.lines[1].add_note(1., Note { id: 4, length: 1. });
+  lines
+}
+
+#[cfg(test)]
+fn summarize_events(events: &[PlaybackEvent]) -> Vec<(bool, usize, f64)> {
+  events
+    .iter()
+    .map(|evt| (evt.is_attack, evt.line_ix, evt.beat))
+    .collect()
+}
+
+#[test]
+fn adjacent_notes_are_retriggered_by_default() {
+  let lines = build_tied_note_lines();
+  let events = lines.playback_events(0., -1., None, false, None);
+  assert_eq!(summarize_events(&events), vec![
+    (true, 0, 0.),
+    (false, 0, 1.),
+    (true, 0, 1.),
+    (true, 1, 1.),
+    (false, 0, 2.),
+    (false, 1, 2.),
+    (true, 0, 2.),
+    (false, 0, 3.),
+  ]);
+}
+
+#[test]
+fn adjacent_notes_are_tied_when_legato() {
+  let lines = build_tied_note_lines();
+  let events = lines.playback_events(0., -1., None, true, None);
+  assert_eq!(summarize_events(&events), vec![
+    (true, 0, 0.),
+    (true, 1, 1.),
+    (false, 1, 2.),
+    (false, 0, 3.),
+  ]);
+
+  // Ties are only followed within the range, so starting partway through a chain attacks the note
+  // under the start of the range
+  let events = lines.playback_events(1., 2.5, None, true, None);
+  assert_eq!(summarize_events(&events), vec![
+    (true, 0, 1.),
+    (true, 1, 1.),
+    (false, 1, 2.),
+    (false, 0, 3.),
+  ]);
+}
+
+#[test]
+fn round_robin_takes_alternate_and_skipped_notes_are_never_played() {
+  let mut lines = build_tied_note_lines();
+  lines.set_round_robin_group(1, Some(0));
+  lines.set_round_robin_group(2, Some(0));
+  lines.set_round_robin_group(4, Some(0));
+  lines.set_probability(3, 0.);
+  let mut variation = PlaybackVariation::new(0);
+
+  // Notes 2 and 4 are alternate takes at beat 1, and note 1 is the only take at beat 0
+  let events = lines.playback_events(0., 3., None, true, Some(&mut variation));
+  assert_eq!(summarize_events(&events), vec![
+    (true, 0, 0.),
+    (false, 0, 2.)
+  ]);
+  let events = lines.playback_events(0., 3., None, true, Some(&mut variation));
+  assert_eq!(summarize_events(&events), vec![
+    (true, 0, 0.),
+    (false, 0, 1.),
+    (true, 1, 1.),
+    (false, 1, 2.),
+  ]);
+}
+
+#[test]
+fn notes_are_filtered_by_length() {
+  let mut lines = build_tied_note_lines();
+  lines.lines[1].add_note(3., Note {
+    id: 5,
+    length: 0.25,
+  });
+
+  let mut ids = lines.iter_notes_by_length(0.5, f64::INFINITY);
+  ids.sort_unstable();
+  assert_eq!(ids, vec![1, 2, 3, 4]);
+  assert_eq!(lines.iter_notes_by_length(0., 0.5), vec![5]);
+}
+
+#[test]
+fn articulations_are_applied_during_playback() {
+  use crate::articulation::{ARTICULATION_ACCENT, ARTICULATION_STACCATO};
+
+  let mut lines = build_tied_note_lines();
+  lines.set_articulation(1, ARTICULATION_TIE);
+  lines.set_articulation(3, ARTICULATION_STACCATO | ARTICULATION_ACCENT);
+  lines.set_velocity(3, 0.5);
+
+  // Note 1 is tied into note 2 without legato, and note 3 is released halfway through
+  let events = lines.playback_events(0., -1., None, false, None);
+  assert_eq!(summarize_events(&events), vec![
+    (true, 0, 0.),
+    (true, 1, 1.),
+    (false, 0, 2.),
+    (false, 1, 2.),
+    (true, 0, 2.),
+    (false, 0, 2.5),
+  ]);
+  assert_eq!(events[4].velocity, 0.75);
+}
+
+#[test]
+fn humanized_events_are_sorted_and_released_before_the_next_attack() {
+  let lines = build_tied_note_lines();
+  for seed in 0..32 {
+    let mut humanizer = Humanizer::new(seed, 0.4, 0.);
+    let events = lines.playback_events(0., -1., Some(&mut humanizer), false, None);
+    assert_eq!(events.len(), 8);
+    assert!(events.windows(2).all(|pair| pair[0].beat <= pair[1].beat));
+    // Each note is released before the next one on its line is attacked
+    for line_ix in 0..lines.lines.len() {
+      let line_events = events.iter().filter(|evt| evt.line_ix == line_ix);
+      assert!(line_events
+        .enumerate()
+        .all(|(i, evt)| evt.is_attack == (i % 2 == 0)));
+    }
+  }
+}
+
+#[test]
+fn deleted_notes_forget_their_data_and_moved_notes_keep_it() {
+  let mut lines = build_tied_note_lines();
+  for note_id in [1, 2] {
+    lines.set_velocity(note_id, 0.5);
+    lines.set_probability(note_id, 0.5);
+    lines.set_round_robin_group(note_id, Some(1));
+    lines.set_articulation(note_id, ARTICULATION_TIE);
+  }
+
+  lines.delete_note(0, 0., 1);
+  assert_eq!(lines.get_velocity(1), 1.);
+  assert_eq!(lines.get_probability(1), 1.);
+  assert_eq!(lines.get_round_robin_group(1), None);
+  assert_eq!(lines.get_articulation(1), 0);
+
+  assert!(lines.move_note(0, 1., 2, 1, 3., 0.5));
+  assert_eq!(lines.iter_notes(1, 1, 3., 3.25), vec![2]);
+  assert_eq!(lines.get_velocity(2), 0.5);
+  assert_eq!(lines.get_probability(2), 0.5);
+  assert_eq!(lines.get_round_robin_group(2), Some(1));
+  assert_eq!(lines.get_articulation(2), ARTICULATION_TIE);
+
+  // Blocked by note 4, so it stays where it was
+  assert!(!lines.move_note(1, 3., 2, 1, 0.5, 1.));
+  assert_eq!(lines.iter_notes(1, 1, 3., 3.25), vec![2]);
 }
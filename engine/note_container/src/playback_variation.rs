@@ -0,0 +1,54 @@
+//! Generative variation applied to notes as they're played back.  Notes can have a probability of
+//! being played, and notes that are members of the same round-robin group and start at the same
+//! beat are treated as alternate takes of which only one is played each time that beat is reached.
+
+use std::collections::HashMap;
+
+use rand::prelude::*;
+use rand_pcg::Pcg32;
+
+pub struct PlaybackVariation {
+  rng: Pcg32,
+  /// Number of times each group has been triggered at each beat, keyed by `(group, beat bits)`
+  round_robin_positions: HashMap<(u32, u64), usize>,
+}
+
+impl PlaybackVariation {
+  pub fn new(seed: u64) -> Self {
+    PlaybackVariation {
+      rng: Pcg32::seed_from_u64(seed),
+      round_robin_positions: HashMap::new(),
+    }
+  }
+
+  /// Returns `true` if a note with the given probability in [0, 1] should be played.  Notes with a
+  /// probability of 1 are always played without advancing the RNG.
+  pub fn should_play(&mut self, probability: f32) -> bool {
+    if probability >= 1. {
+      return true;
+    }
+    self.rng.gen::<f32>() < probability
+  }
+
+  /// Returns the index of the take to play out of `take_count` alternate takes of `group` starting
+  /// at `beat`, cycling through them in order each time it's called.
+  pub fn next_take(&mut self, group: u32, beat: f64, take_count: usize) -> usize {
+    let position = self
+      .round_robin_positions
+      .entry((group, beat.to_bits()))
+      .or_insert(0);
+    let take_ix = *position % take_count;
+    *position += 1;
+    take_ix
+  }
+}
+
+#[test]
+fn round_robin_cycles_through_takes_per_beat() {
+  let mut variation = PlaybackVariation::new(0);
+  let takes: Vec<usize> = (0..4).map(|_| variation.next_take(1, 0., 3)).collect();
+  assert_eq!(takes, vec![0, 1, 2, 0]);
+  // Each beat and group keeps its own position
+  assert_eq!(variation.next_take(1, 1., 3), 0);
+  assert_eq!(variation.next_take(2, 0., 3), 0);
+}
@@ -0,0 +1,206 @@
+//! Compact binary snapshots of all notes in a `NoteLines`.  The MIDI editor periodically hands
+//! these to the host to persist so that notes added since the last explicit save can be recovered
+//! after the tab crashes.
+//!
+//! All values are little-endian.  The snapshot starts with a header:
+//!
+//! magic (4 bytes), version (u8), line count (u32), note count (u32)
+//!
+//! followed by one record per note in order of line and then start beat:
+//!
+//! line index (u32), start beat (f64), length (f64), velocity (f32), probability (f32),
+//! round-robin group (i32, -1 if none), articulation (u8)
+
+use std::fmt;
+
+use crate::{note_container::NoteEntry, note_lines::NoteLines};
+
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"WSNL";
+pub const SNAPSHOT_VERSION: u8 = 1;
+const HEADER_SIZE: usize = 4 + 1 + 4 + 4;
+const NOTE_RECORD_SIZE: usize = 4 + 8 + 8 + 4 + 4 + 4 + 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SnapshotNote {
+  pub line_ix: usize,
+  pub start_point: f64,
+  pub length: f64,
+  pub velocity: f32,
+  pub probability: f32,
+  pub round_robin_group: Option<u32>,
+  pub articulation: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SnapshotError {
+  BadMagic,
+  UnsupportedVersion(u8),
+  Truncated,
+  InvalidNote(&'static str),
+}
+
+impl fmt::Display for SnapshotError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SnapshotError::BadMagic => write!(f, "not a note lines snapshot"),
+      SnapshotError::UnsupportedVersion(version) =>
+        write!(f, "unsupported snapshot version: {version}"),
+      SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+      SnapshotError::InvalidNote(reason) => write!(f, "invalid note in snapshot: {reason}"),
+    }
+  }
+}
+
+/// Returns the line index, start beat, ID, and length of each note in the order that they're stored
+/// in snapshots
+pub(crate) fn notes_in_snapshot_order(lines: &NoteLines) -> Vec<(usize, f64, u32, f64)> {
+  lines
+    .lines
+    .iter()
+    .enumerate()
+    .flat_map(|(line_ix, line)| {
+      line
+        .inner
+        .iter()
+        .filter_map(move |(start_point, entry)| match entry {
+          NoteEntry::NoteStart { note }
+          | NoteEntry::StartAndEnd {
+            start_note: note, ..
+          } => Some((line_ix, start_point.0, note.id, note.length)),
+          NoteEntry::NoteEnd { .. } => None,
+        })
+    })
+    .collect()
+}
+
+pub fn encode_snapshot(lines: &NoteLines) -> Vec<u8> {
+  let notes = notes_in_snapshot_order(lines);
+  let mut out = Vec::with_capacity(HEADER_SIZE + notes.len() * NOTE_RECORD_SIZE);
+  out.extend_from_slice(&SNAPSHOT_MAGIC);
+  out.push(SNAPSHOT_VERSION);
+  out.extend_from_slice(&(lines.lines.len() as u32).to_le_bytes());
+  out.extend_from_slice(&(notes.len() as u32).to_le_bytes());
+  for (line_ix, start_point, note_id, length) in notes {
+    let round_robin_group = lines
+      .get_round_robin_group(note_id)
+      .map(|group| group as i32)
+      .unwrap_or(-1);
+
+    out.extend_from_slice(&(line_ix as u32).to_le_bytes());
+    out.extend_from_slice(&start_point.to_le_bytes());
+    out.extend_from_slice(&length.to_le_bytes());
+    out.extend_from_slice(&lines.get_velocity(note_id).to_le_bytes());
+    out.extend_from_slice(&lines.get_probability(note_id).to_le_bytes());
+    out.extend_from_slice(&round_robin_group.to_le_bytes());
+    out.push(lines.get_articulation(note_id));
+  }
+  out
+}
+
+fn read_array<const N: usize>(bytes: &[u8], offset: &mut usize) -> Result<[u8; N], SnapshotError> {
+  let end = *offset + N;
+  let field = bytes.get(*offset..end).ok_or(SnapshotError::Truncated)?;
+  *offset = end;
+  Ok(field.try_into().unwrap())
+}
+
+/// Returns the line count of the snapshot along with all of its notes.  Notes are validated so that
+/// they can be added to a `NoteLines` with the returned line count without panicking.
+pub fn decode_snapshot(bytes: &[u8]) -> Result<(usize, Vec<SnapshotNote>), SnapshotError> {
+  let mut offset = 0;
+  if read_array::<4>(bytes, &mut offset)? != SNAPSHOT_MAGIC {
+    return Err(SnapshotError::BadMagic);
+  }
+  let [version] = read_array::<1>(bytes, &mut offset)?;
+  if version != SNAPSHOT_VERSION {
+    return Err(SnapshotError::UnsupportedVersion(version));
+  }
+  let line_count = u32::from_le_bytes(read_array(bytes, &mut offset)?) as usize;
+  let note_count = u32::from_le_bytes(read_array(bytes, &mut offset)?) as usize;
+  let records_size = note_count
+    .checked_mul(NOTE_RECORD_SIZE)
+    .ok_or(SnapshotError::Truncated)?;
+  if bytes.len() - offset < records_size {
+    return Err(SnapshotError::Truncated);
+  }
+
+  let mut notes = Vec::with_capacity(note_count);
+  for _ in 0..note_count {
+    let line_ix = u32::from_le_bytes(read_array(bytes, &mut offset)?) as usize;
+    let start_point = f64::from_le_bytes(read_array(bytes, &mut offset)?);
+    let length = f64::from_le_bytes(read_array(bytes, &mut offset)?);
+    let velocity = f32::from_le_bytes(read_array(bytes, &mut offset)?);
+    let probability = f32::from_le_bytes(read_array(bytes, &mut offset)?);
+    let round_robin_group = i32::from_le_bytes(read_array(bytes, &mut offset)?);
+    let [articulation] = read_array::<1>(bytes, &mut offset)?;
+
+    if line_ix >= line_count {
+      return Err(SnapshotError::InvalidNote("line index out of range"));
+    }
+    if start_point.is_nan() || start_point < 0. {
+      return Err(SnapshotError::InvalidNote("negative start point"));
+    }
+    if !length.is_normal() || length <= 0. {
+      return Err(SnapshotError::InvalidNote("invalid length"));
+    }
+
+    notes.push(SnapshotNote {
+      line_ix,
+      start_point,
+      length,
+      velocity,
+      probability,
+      round_robin_group: u32::try_from(round_robin_group).ok(),
+      articulation,
+    });
+  }
+  Ok((line_count, notes))
+}
+
+#[test]
+fn snapshots_round_trip() {
+  use std::collections::HashMap;
+
+  use crate::{
+    articulation::ARTICULATION_ACCENT,
+    note_container::{Note, NoteContainer},
+  };
+
+  let mut lines = NoteLines {
+    lines: vec![NoteContainer::default(), NoteContainer::default()],
+    velocities: HashMap::new(),
+    probabilities: HashMap::new(),
+    round_robin_groups: HashMap::new(),
+    articulations: HashMap::new(),
+    input_recording: None,
+  };
+  lines.lines[0].add_note(0., Note { id: 1, length: 1. });
+  lines.lines[0].add_note(1., Note { id: 2, length: 0.5 });
+  lines.lines[1].add_note(2.25, Note { id: 3, length: 4. });
+  lines.set_velocity(2, 0.5);
+  lines.set_probability(3, 0.25);
+  lines.set_round_robin_group(3, Some(7));
+  lines.set_articulation(1, ARTICULATION_ACCENT);
+
+  let snapshot = encode_snapshot(&lines);
+  assert_eq!(snapshot.len(), HEADER_SIZE + 3 * NOTE_RECORD_SIZE);
+
+  let (line_count, notes) = decode_snapshot(&snapshot).unwrap();
+  assert_eq!(line_count, 2);
+  let summary: Vec<_> = notes
+    .iter()
+    .map(|note| (note.line_ix, note.start_point, note.length))
+    .collect();
+  assert_eq!(summary, vec![(0, 0., 1.), (0, 1., 0.5), (1, 2.25, 4.)]);
+  assert_eq!(notes[0].articulation, ARTICULATION_ACCENT);
+  assert_eq!(notes[1].velocity, 0.5);
+  assert_eq!(notes[1].round_robin_group, None);
+  assert_eq!(notes[2].probability, 0.25);
+  assert_eq!(notes[2].round_robin_group, Some(7));
+
+  assert_eq!(
+    decode_snapshot(&snapshot[..snapshot.len() - 1]),
+    Err(SnapshotError::Truncated)
+  );
+  assert_eq!(decode_snapshot(b"RIFF"), Err(SnapshotError::BadMagic));
+}
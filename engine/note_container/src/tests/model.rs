@@ -0,0 +1,350 @@
+//! Checks `NoteContainer` against a naive model that stores notes in a `Vec` and answers every
+//! query with a linear scan.  Operations are generated randomly by proptest, and the full set of
+//! invariants is verified after each one.
+//!
+//! Operations refer to notes by their index into the model rather than by ID or position, so any
+//! subsequence of a failing sequence is still valid.  This lets proptest shrink failures down to
+//! the handful of operations that actually matter.
+
+use std::collections::HashSet;
+
+use proptest::prelude::*;
+
+use crate::note_container::{Note, NoteContainer, NoteEntry};
+
+/// Positions are generated on a grid of this resolution so that notes frequently touch exactly,
+/// which is where most of the edge cases are, and so that all arithmetic on them is exact
+const GRID_RESOLUTION: f64 = 0.25;
+const MAX_GRID_POS: u32 = 64;
+
+fn grid_pos(steps: u32) -> f64 { steps as f64 * GRID_RESOLUTION }
+
+#[derive(Clone, Copy, Debug)]
+pub enum Op {
+  Insert { start: u32, length: u32 },
+  Remove { note_ix: usize },
+  Move { note_ix: usize, new_start: u32 },
+  ResizeEnd { note_ix: usize, new_end: u32 },
+  ResizeStart { note_ix: usize, new_start: u32 },
+  Query { start: u32, length: u32 },
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+  let pos = 0..=MAX_GRID_POS;
+  let length = 1..=16u32;
+  let note_ix = any::<usize>();
+  prop_oneof![
+    3 => (pos.clone(), length.clone()).prop_map(|(start, length)| Op::Insert { start, length }),
+    1 => note_ix.clone().prop_map(|note_ix| Op::Remove { note_ix }),
+    2 => (note_ix.clone(), pos.clone())
+      .prop_map(|(note_ix, new_start)| Op::Move { note_ix, new_start }),
+    1 => (note_ix.clone(), pos.clone())
+      .prop_map(|(note_ix, new_end)| Op::ResizeEnd { note_ix, new_end }),
+    1 => (note_ix, pos.clone())
+      .prop_map(|(note_ix, new_start)| Op::ResizeStart { note_ix, new_start }),
+    1 => (pos, length).prop_map(|(start, length)| Op::Query { start, length }),
+  ]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ModelNote {
+  id: u32,
+  start: f64,
+  end: f64,
+}
+
+#[derive(Default)]
+struct Model {
+  notes: Vec<ModelNote>,
+  next_id: u32,
+}
+
+impl Model {
+  fn is_free(&self, start: f64, end: f64, ignored_id: Option<u32>) -> bool {
+    self
+      .notes
+      .iter()
+      .filter(|note| Some(note.id) != ignored_id)
+      .all(|note| note.end <= start || note.start >= end)
+  }
+
+  /// Returns `None` if there are no notes to pick from
+  fn pick(&self, note_ix: usize) -> Option<ModelNote> {
+    if self.notes.is_empty() {
+      None
+    } else {
+      Some(self.notes[note_ix % self.notes.len()])
+    }
+  }
+
+  fn update(&mut self, updated: ModelNote) {
+    let note = self
+      .notes
+      .iter_mut()
+      .find(|note| note.id == updated.id)
+      .unwrap();
+    *note = updated;
+  }
+}
+
+/// Walks the raw entries of the container to reconstruct the notes it holds, panicking if the
+/// entries are out of order, overlapping, or don't match up with each other
+fn collect_notes(container: &NoteContainer) -> Vec<ModelNote> {
+  let mut notes = Vec::new();
+  let mut open_note: Option<(f64, Note)> = None;
+  let mut last_pos = f64::NEG_INFINITY;
+
+  let mut close = |open_note: &mut Option<(f64, Note)>, pos: f64, note_id: u32| {
+    let (start, note) = open_note
+      .take()
+      .unwrap_or_else(|| panic!("Note end for id={} at {} without a start", note_id, pos));
+    assert_eq!(note.id, note_id, "Mismatched note end at {}", pos);
+    assert_eq!(
+      start + note.length,
+      pos,
+      "Note length doesn't match end entry"
+    );
+    notes.push(ModelNote {
+      id: note.id,
+      start,
+      end: pos,
+    });
+  };
+
+  for (pos, entry) in &container.inner {
+    let pos = pos.0;
+    assert!(
+      pos.is_finite() && pos >= 0.,
+      "Invalid entry position: {}",
+      pos
+    );
+    assert!(pos > last_pos, "Entries out of order at {}", pos);
+    last_pos = pos;
+
+    match *entry {
+      NoteEntry::NoteStart { note } => {
+        assert!(
+          open_note.is_none(),
+          "Note starting at {} overlaps another note",
+          pos
+        );
+        open_note = Some((pos, note));
+      },
+      NoteEntry::NoteEnd { note_id } => close(&mut open_note, pos, note_id),
+      NoteEntry::StartAndEnd {
+        start_note,
+        end_note_id,
+      } => {
+        close(&mut open_note, pos, end_note_id);
+        open_note = Some((pos, start_note));
+      },
+    }
+  }
+  assert!(
+    open_note.is_none(),
+    "Note was never closed: {:?}",
+    open_note
+  );
+
+  notes.sort_by(|a, b| a.id.cmp(&b.id));
+  notes
+}
+
+fn check_invariants(container: &NoteContainer, model: &Model) {
+  let mut expected = model.notes.clone();
+  expected.sort_by(|a, b| a.id.cmp(&b.id));
+  assert_eq!(collect_notes(container), expected);
+}
+
+/// Applies `op` to both the container and the model, asserting that their results agree.  Ops
+/// that refer to a note when there are none or that would be rejected by the container's
+/// preconditions are skipped.
+fn apply_op(container: &mut NoteContainer, model: &mut Model, op: Op) {
+  match op {
+    Op::Insert { start, length } => {
+      let (start, length) = (grid_pos(start), grid_pos(length));
+      let can_add = model.is_free(start, start + length, None);
+      assert_eq!(container.check_can_add_note(start, length), can_add);
+      if can_add {
+        let id = model.next_id;
+        model.next_id += 1;
+        container.add_note(start, Note { id, length });
+        model.notes.push(ModelNote {
+          id,
+          start,
+          end: start + length,
+        });
+      }
+    },
+    Op::Remove { note_ix } => {
+      let Some(note) = model.pick(note_ix) else {
+        return;
+      };
+      let removed = container.remove_note(note.start, note.id);
+      assert_eq!(removed, Note {
+        id: note.id,
+        length: note.end - note.start,
+      });
+      model.notes.retain(|n| n.id != note.id);
+    },
+    Op::Move { note_ix, new_start } => {
+      let Some(note) = model.pick(note_ix) else {
+        return;
+      };
+      let new_start = grid_pos(new_start);
+      let length = note.end - note.start;
+      let real_new_start = container.move_note_horizontal(note.start, note.id, new_start);
+
+      // Moves stop early if blocked, so the note should end up between where it was and where it
+      // was headed, and at the target exactly if nothing was in the way
+      let (lo, hi) = if new_start < note.start {
+        (new_start, note.start)
+      } else {
+        (note.start, new_start)
+      };
+      assert!(
+        real_new_start >= lo && real_new_start <= hi,
+        "Moved note to {} when moving from {} to {}",
+        real_new_start,
+        note.start,
+        new_start
+      );
+      if model.is_free(new_start, new_start + length, Some(note.id)) {
+        assert_eq!(real_new_start, new_start);
+      }
+      model.update(ModelNote {
+        start: real_new_start,
+        end: real_new_start + length,
+        ..note
+      });
+    },
+    Op::ResizeEnd { note_ix, new_end } => {
+      let Some(note) = model.pick(note_ix) else {
+        return;
+      };
+      let new_end = grid_pos(new_end);
+      if new_end <= note.start {
+        return;
+      }
+      // Growing stops at the start of the next note
+      let expected_end = model
+        .notes
+        .iter()
+        .filter(|other| other.start >= note.end && other.start <= new_end)
+        .map(|other| other.start)
+        .fold(new_end, f64::min);
+      let expected_end = if new_end < note.end {
+        new_end
+      } else {
+        expected_end
+      };
+      let real_new_end = container.resize_note_end(note.start, note.id, new_end);
+      assert_eq!(real_new_end, expected_end);
+      model.update(ModelNote {
+        end: real_new_end,
+        ..note
+      });
+    },
+    Op::ResizeStart { note_ix, new_start } => {
+      let Some(note) = model.pick(note_ix) else {
+        return;
+      };
+      let new_start = grid_pos(new_start);
+      if new_start >= note.end {
+        return;
+      }
+      // Growing stops at the end of the previous note
+      let expected_start = model
+        .notes
+        .iter()
+        .filter(|other| other.end <= note.start && other.end >= new_start)
+        .map(|other| other.end)
+        .fold(new_start, f64::max);
+      let expected_start = if new_start > note.start {
+        new_start
+      } else {
+        expected_start
+      };
+      let real_new_start = container.resize_note_start(note.start, note.id, new_start);
+      assert_eq!(real_new_start, expected_start);
+      model.update(ModelNote {
+        start: real_new_start,
+        ..note
+      });
+    },
+    Op::Query { start, length } => {
+      let (start, end) = (grid_pos(start), grid_pos(start + length));
+      let mut found = HashSet::new();
+      container.iter_notes(&mut found, start, end);
+      let expected: HashSet<u32> = model
+        .notes
+        .iter()
+        .filter(|note| note.start <= end && note.end >= start)
+        .map(|note| note.id)
+        .collect();
+      assert_eq!(found, expected, "Query for [{}, {}]", start, end);
+    },
+  }
+}
+
+/// Runs the ops against a fresh container and model, checking invariants after each one
+pub fn check_model(ops: &[Op]) {
+  let mut container = NoteContainer::default();
+  let mut model = Model::default();
+  for &op in ops {
+    apply_op(&mut container, &mut model, op);
+    check_invariants(&container, &model);
+  }
+}
+
+#[test]
+fn model_touching_notes() {
+  check_model(&[
+    Op::Insert {
+      start: 0,
+      length: 4,
+    },
+    Op::Insert {
+      start: 8,
+      length: 4,
+    },
+    Op::Insert {
+      start: 4,
+      length: 4,
+    },
+    Op::Query {
+      start: 4,
+      length: 0,
+    },
+    Op::ResizeEnd {
+      note_ix: 0,
+      new_end: 12,
+    },
+    Op::Remove { note_ix: 2 },
+    Op::ResizeEnd {
+      note_ix: 0,
+      new_end: 12,
+    },
+    Op::ResizeStart {
+      note_ix: 1,
+      new_start: 0,
+    },
+    Op::Move {
+      note_ix: 1,
+      new_start: 0,
+    },
+    Op::Query {
+      start: 2,
+      length: 1,
+    },
+  ]);
+}
+
+proptest! {
+  #![proptest_config(ProptestConfig::with_cases(512))]
+
+  #[test]
+  fn note_container_matches_model(ops in prop::collection::vec(op_strategy(), 1..64)) {
+    check_model(&ops);
+  }
+}
@@ -0,0 +1,55 @@
+//! Regression tests for editor bugs reproduced from input recordings.  Each `.wsir` file in
+//! `tests/input_recordings/` is a recording exported from the MIDI editor; it's replayed and the
+//! resulting notes are compared against the `.snapshot` file next to it.
+//!
+//! To add a test for a bug, reproduce it while recording, fix it, and then generate the expected
+//! snapshot from the fixed engine with:
+//!
+//! ```sh
+//! UPDATE_INPUT_RECORDINGS=1 cargo test -p note_container --test input_recordings
+//! ```
+//!
+//! and check that the notes in the new snapshot look right before committing both files.
+
+use std::path::PathBuf;
+
+use note_container::{input_recording::InputRecording, snapshot::encode_snapshot};
+
+#[test]
+fn input_recordings_replay_to_expected_notes() {
+  let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/input_recordings");
+  let Ok(entries) = std::fs::read_dir(&dir) else {
+    return;
+  };
+  let update = std::env::var_os("UPDATE_INPUT_RECORDINGS").is_some();
+
+  for path in entries.map(|entry| entry.unwrap().path()) {
+    if path.extension().and_then(|ext| ext.to_str()) != Some("wsir") {
+      continue;
+    }
+
+    let recording = InputRecording::decode(&std::fs::read(&path).unwrap())
+      .unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+    let notes = recording
+      .replay(None)
+      .unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+    let snapshot = encode_snapshot(&notes);
+
+    let snapshot_path = path.with_extension("snapshot");
+    if update {
+      std::fs::write(&snapshot_path, &snapshot).unwrap();
+      continue;
+    }
+    let expected = std::fs::read(&snapshot_path).unwrap_or_else(|_| {
+      panic!(
+        "missing {}; generate it with UPDATE_INPUT_RECORDINGS=1",
+        snapshot_path.display()
+      )
+    });
+    assert!(
+      snapshot == expected,
+      "{} replayed to different notes than expected",
+      path.display()
+    );
+  }
+}
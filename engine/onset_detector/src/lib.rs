@@ -0,0 +1,198 @@
+//! Detects onsets in live input and follows its tempo, so that the transport can be synced to an
+//! external source like a turntable or a drummer.  See `dsp::onset::SpectralFluxDetector` and
+//! `dsp::beat_tracker` for how each works.
+//!
+//! The input is passed through unchanged.  Detected onsets and the current tempo estimate are
+//! written to the SAB after every frame.  Times in the SAB are in seconds of audio processed since
+//! the module was created or reset ("stream time"), which the host can relate to its own clock
+//! using the stream time of the latest frame.
+//!
+//! Exposed through the standard `module_api` entry points.
+
+use common::ffi::{self, FfiResult};
+use dsp::{beat_tracker::BeatTracker, onset::SpectralFluxDetector, MAX_FRAME_SIZE};
+use module_api::DspModule;
+
+const CHANNEL_COUNT: usize = 1;
+
+/// In [0, 1]; higher values detect quieter and less sharp attacks
+const PARAM_SENSITIVITY_IX: usize = 0;
+/// Range of tempos that the beat tracker considers.  Tempos outside it are doubled or halved into
+/// it.
+const PARAM_MIN_BPM_IX: usize = 1;
+const PARAM_MAX_BPM_IX: usize = 2;
+const PARAM_COUNT: usize = 3;
+const MIN_BPM: f32 = 40.;
+const MAX_BPM: f32 = 300.;
+
+/// SAB layout:
+///
+/// [0] = stream time at the end of the latest frame
+/// [1] = total number of onsets detected
+/// [2] = estimated tempo in BPM, or 0 if there isn't an estimate yet
+/// [3] = beat phase at the end of the latest frame, from 0 (on the beat) up to 1
+/// [4] = confidence of the tempo estimate, from 0 to 1
+/// [5..] = stream times of the most recent `ONSET_HISTORY_LEN` onsets.  Onset `n` (counting from
+///         0) is at `SAB_ONSET_TIMES_OFFSET + n % ONSET_HISTORY_LEN`.
+const SAB_STREAM_TIME_IX: usize = 0;
+const SAB_ONSET_COUNT_IX: usize = 1;
+const SAB_BPM_IX: usize = 2;
+const SAB_PHASE_IX: usize = 3;
+const SAB_CONFIDENCE_IX: usize = 4;
+const SAB_ONSET_TIMES_OFFSET: usize = 5;
+const ONSET_HISTORY_LEN: usize = 16;
+const SAB_LEN: usize = SAB_ONSET_TIMES_OFFSET + ONSET_HISTORY_LEN;
+
+pub struct OnsetDetectorCtx {
+  pub io_buffer: [f32; MAX_FRAME_SIZE * CHANNEL_COUNT],
+  /// Written by JS; see `PARAM_SENSITIVITY_IX` for the layout
+  pub params: [f32; PARAM_COUNT],
+  detector: SpectralFluxDetector,
+  tracker: BeatTracker,
+  onset_count: u32,
+  pub sab: [f32; SAB_LEN],
+}
+
+impl Default for OnsetDetectorCtx {
+  fn default() -> Self {
+    let mut params = [0.; PARAM_COUNT];
+    params[PARAM_SENSITIVITY_IX] = 0.5;
+    params[PARAM_MIN_BPM_IX] = 80.;
+    params[PARAM_MAX_BPM_IX] = 160.;
+
+    OnsetDetectorCtx {
+      io_buffer: [0.; MAX_FRAME_SIZE * CHANNEL_COUNT],
+      params,
+      detector: SpectralFluxDetector::default(),
+      tracker: BeatTracker::new(params[PARAM_MIN_BPM_IX], params[PARAM_MAX_BPM_IX]),
+      onset_count: 0,
+      sab: [0.; SAB_LEN],
+    }
+  }
+}
+
+impl OnsetDetectorCtx {
+  /// Clamps the params into their valid ranges in place.  NaNs are rejected.
+  fn validate_params(&mut self) -> FfiResult {
+    self.params[PARAM_SENSITIVITY_IX] =
+      ffi::clamp_param("sensitivity", self.params[PARAM_SENSITIVITY_IX], 0., 1.)?;
+    self.params[PARAM_MIN_BPM_IX] = ffi::clamp_param(
+      "min_bpm",
+      self.params[PARAM_MIN_BPM_IX],
+      MIN_BPM,
+      MAX_BPM / 2.,
+    )?;
+    self.params[PARAM_MAX_BPM_IX] = ffi::clamp_param(
+      "max_bpm",
+      self.params[PARAM_MAX_BPM_IX],
+      self.params[PARAM_MIN_BPM_IX] * 2.,
+      MAX_BPM,
+    )?;
+    Ok(())
+  }
+
+  fn apply(&mut self, frame_size: usize) {
+    let sample_rate = dsp::sample_rate();
+    self.detector.sensitivity = self.params[PARAM_SENSITIVITY_IX];
+    self
+      .tracker
+      .set_bpm_range(self.params[PARAM_MIN_BPM_IX], self.params[PARAM_MAX_BPM_IX]);
+
+    let (tracker, sab, onset_count) = (&mut self.tracker, &mut self.sab, &mut self.onset_count);
+    self
+      .detector
+      .process(&self.io_buffer[..frame_size], |onset, strength| {
+        tracker.add_onset(onset, strength, sample_rate);
+        sab[SAB_ONSET_TIMES_OFFSET + *onset_count as usize % ONSET_HISTORY_LEN] =
+          onset as f32 / sample_rate;
+        *onset_count += 1;
+      });
+
+    let now = self.detector.sample_count();
+    self.sab[SAB_STREAM_TIME_IX] = now as f32 / sample_rate;
+    self.sab[SAB_ONSET_COUNT_IX] = self.onset_count as f32;
+    if let Some(estimate) = self.tracker.estimate() {
+      self.sab[SAB_BPM_IX] = estimate.bpm;
+      self.sab[SAB_PHASE_IX] = estimate.phase_at(now, sample_rate);
+      self.sab[SAB_CONFIDENCE_IX] = estimate.confidence;
+    }
+  }
+}
+
+impl DspModule for OnsetDetectorCtx {
+  const CHANNEL_COUNT: usize = CHANNEL_COUNT;
+  const NAME: &'static str = "onset_detector";
+  const PARAM_COUNT: usize = PARAM_COUNT;
+  const SAB_LEN: usize = SAB_LEN;
+  const VERSION: u32 = 1;
+
+  fn new() -> Self { Self::default() }
+
+  fn param_name(param_ix: usize) -> String {
+    match param_ix {
+      PARAM_SENSITIVITY_IX => "sensitivity",
+      PARAM_MIN_BPM_IX => "min_bpm",
+      _ => "max_bpm",
+    }
+    .to_owned()
+  }
+
+  fn io_buffer(&mut self) -> &mut [f32] { &mut self.io_buffer }
+
+  fn params(&mut self) -> &mut [f32] { &mut self.params }
+
+  fn sab(&mut self) -> &mut [f32] { &mut self.sab }
+
+  fn process(&mut self, frame_size: usize) -> FfiResult {
+    self.validate_params()?;
+    self.apply(frame_size);
+    Ok(())
+  }
+
+  fn reset(&mut self) {
+    self.detector.reset();
+    self.tracker.reset();
+    self.onset_count = 0;
+    self.sab = [0.; SAB_LEN];
+  }
+}
+
+module_api::export_module!(OnsetDetectorCtx);
+
+#[test]
+fn follows_tempo_of_clicks() {
+  let mut ctx = OnsetDetectorCtx::default();
+  let sample_rate = dsp::sample_rate();
+  let bpm = 120.;
+  let samples_per_beat = (sample_rate * 60. / bpm) as usize;
+  let frame_size = 128;
+  // Starts half a beat in so that the first click isn't at the very start of the stream
+  let offset = samples_per_beat / 2;
+  let total_samples = offset + samples_per_beat * 12;
+  for frame_start in (0..total_samples).step_by(frame_size) {
+    for i in 0..frame_size {
+      let sample_ix = frame_start + i;
+      ctx.io_buffer[i] = if sample_ix < offset {
+        0.
+      } else {
+        // Decaying bursts of a high tone on every beat
+        let t = ((sample_ix - offset) % samples_per_beat) as f32 / sample_rate;
+        (std::f32::consts::TAU * 1_500. * t).sin() * (-t * 60.).exp() * 0.8
+      };
+    }
+    let input = ctx.io_buffer;
+    ctx.process(frame_size).unwrap();
+    assert_eq!(ctx.io_buffer, input);
+  }
+
+  assert_eq!(ctx.sab[SAB_ONSET_COUNT_IX], 12.);
+  assert!((ctx.sab[SAB_BPM_IX] - bpm).abs() < 1., "{:?}", ctx.sab);
+  let last_onset = ctx.sab[SAB_ONSET_TIMES_OFFSET + 11];
+  assert!(
+    (last_onset - (0.25 + 11. * 0.5)).abs() < 0.01,
+    "{last_onset}"
+  );
+  // The stream ends exactly on a beat
+  let phase = ctx.sab[SAB_PHASE_IX];
+  assert!(!(0.05..=0.95).contains(&phase), "{phase}");
+}
@@ -1,5 +1,6 @@
 use self::oscilloscope::{PreviousWindow, Viz, WindowLength};
 use canvas_utils::VizView;
+use common::ffi::{self, ErrorCode};
 use f0_estimation::YinCtx;
 
 pub(crate) mod conf;
@@ -81,7 +82,7 @@ pub extern "C" fn oscilloscope_renderer_set_view(
   width: usize,
   height: usize,
   dpr: usize,
-) {
+) -> ErrorCode {
   maybe_set_panic_hook();
 
   log(&format!(
@@ -90,20 +91,35 @@ pub extern "C" fn oscilloscope_renderer_set_view(
   ));
   let viz = unsafe { &mut VIZ };
   viz.set_view(cur_bpm, VizView { width, height, dpr });
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub extern "C" fn oscilloscope_renderer_set_window(window_mode: u8, window_length: f32) {
+pub extern "C" fn oscilloscope_renderer_set_window(
+  window_mode: u8,
+  window_length: f32,
+) -> ErrorCode {
   let viz = unsafe { &mut VIZ };
-  let window = WindowLength::from_parts(window_mode, window_length);
+  let Some(window) = WindowLength::from_parts(window_mode, window_length) else {
+    return ffi::set_last_error(
+      ErrorCode::ParamOutOfRange,
+      &format!("Invalid window mode {window_mode} or length {window_length}"),
+    );
+  };
   viz.set_window(window);
+  ErrorCode::Ok
 }
 
 /// Process all samples in `FRAME_DATA_BUFFER` and update the viz
 #[no_mangle]
-pub extern "C" fn oscilloscope_renderer_process(cur_bpm: f32, cur_beat: f32, cur_time: f32) {
+pub extern "C" fn oscilloscope_renderer_process(
+  cur_bpm: f32,
+  cur_beat: f32,
+  cur_time: f32,
+) -> ErrorCode {
   let viz = unsafe { &mut VIZ };
   viz.process(cur_bpm, cur_beat, cur_time);
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -112,10 +128,11 @@ pub extern "C" fn oscilloscope_renderer_get_frame_data_ptr() -> *const f32 {
 }
 
 #[no_mangle]
-pub extern "C" fn oscilloscope_renderer_commit_samples() {
+pub extern "C" fn oscilloscope_renderer_commit_samples() -> ErrorCode {
   let viz = unsafe { &mut VIZ };
   let frame_data = unsafe { &FRAME_DATA_BUFFER };
   viz.commit_samples(frame_data);
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -131,15 +148,17 @@ pub extern "C" fn oscilloscope_get_image_data_buf_len() -> usize {
 }
 
 #[no_mangle]
-pub extern "C" fn oscilloscope_renderer_set_frozen(frozen: bool) {
+pub extern "C" fn oscilloscope_renderer_set_frozen(frozen: bool) -> ErrorCode {
   let viz = unsafe { &mut VIZ };
   viz.set_frozen(frozen);
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub extern "C" fn oscilloscope_renderer_set_frame_by_frame(frame_by_frame: bool) {
+pub extern "C" fn oscilloscope_renderer_set_frame_by_frame(frame_by_frame: bool) -> ErrorCode {
   let viz = unsafe { &mut VIZ };
   viz.set_frame_by_frame(frame_by_frame);
+  ErrorCode::Ok
 }
 
 /// Returns pointer to zero-terminated string
@@ -150,7 +169,8 @@ pub extern "C" fn oscilloscope_renderer_get_detected_f0_display() -> *const u8 {
 }
 
 #[no_mangle]
-pub extern "C" fn oscilloscope_renderer_set_snap_f0_to_midi(snap_f0_to_midi: bool) {
+pub extern "C" fn oscilloscope_renderer_set_snap_f0_to_midi(snap_f0_to_midi: bool) -> ErrorCode {
   let viz = unsafe { &mut VIZ };
   viz.snap_f0_to_midi = snap_f0_to_midi;
+  ErrorCode::Ok
 }
@@ -24,20 +24,19 @@ pub enum WindowLength {
 }
 
 impl WindowLength {
-  pub(crate) fn from_parts(window_mode: u8, window_length: f32) -> Self {
-    match window_mode {
+  /// Returns `None` if `window_mode` is unknown or `window_length` is negative
+  pub(crate) fn from_parts(window_mode: u8, window_length: f32) -> Option<Self> {
+    if window_length.is_nan() || window_length < 0. {
+      return None;
+    }
+
+    Some(match window_mode {
       0 => WindowLength::Beats(window_length),
       1 => WindowLength::Seconds(window_length),
-      2 => {
-        if window_length < 0.0 {
-          panic!("Invalid window length: {window_length}");
-        }
-        let window_length = window_length.trunc();
-        WindowLength::Samples(window_length as usize)
-      },
+      2 => WindowLength::Samples(window_length.trunc() as usize),
       3 => WindowLength::Wavelengths(window_length),
-      _ => panic!("Invalid window mode: {window_mode}"),
-    }
+      _ => return None,
+    })
   }
 }
 
@@ -191,10 +191,13 @@ impl<
     }
   }
 
-  pub fn release_all(&mut self) {
+  pub fn release_all(&mut self) { self.release_all_at(None) }
+
+  /// Releases all playing notes, passing `offset` through to the release callback
+  pub fn release_all_at(&mut self, offset: Option<f32>) {
     for i in 0..POLY_SYNTH_VOICE_COUNT {
       if let VoicePlayingStatus::Playing(note_id) = self.voices[i].playing {
-        self.trigger_release(note_id, None);
+        self.trigger_release(note_id, offset);
       }
     }
   }
@@ -1,3 +1,4 @@
+use common::ffi::{self, ErrorCode};
 use dsp::FRAME_SIZE;
 
 #[repr(u8)]
@@ -8,6 +9,18 @@ pub enum QuantizationMode {
   Trunc = 3,
 }
 
+impl QuantizationMode {
+  pub fn from_u8(val: u8) -> Option<Self> {
+    match val {
+      0 => Some(QuantizationMode::Round),
+      1 => Some(QuantizationMode::Floor),
+      2 => Some(QuantizationMode::Ceil),
+      3 => Some(QuantizationMode::Trunc),
+      _ => None,
+    }
+  }
+}
+
 pub struct QuantizerState {
   pub quantization_interval: f32,
   pub mode: QuantizationMode,
@@ -56,10 +69,21 @@ static mut STATE: QuantizerState = QuantizerState {
 fn state() -> &'static mut QuantizerState { unsafe { &mut STATE } }
 
 #[no_mangle]
-pub extern "C" fn set_quantization_state(quantization_interval: f32, mode: u8) {
+pub extern "C" fn set_quantization_state(quantization_interval: f32, mode: u8) -> ErrorCode {
+  let Some(mode) = QuantizationMode::from_u8(mode) else {
+    return ffi::set_last_error(
+      ErrorCode::ParamOutOfRange,
+      &format!("Invalid quantization mode: {mode}"),
+    );
+  };
+  if quantization_interval.is_nan() {
+    return ffi::set_last_error(ErrorCode::ParamOutOfRange, "quantization_interval is NaN");
+  }
+
   let state = state();
   state.quantization_interval = quantization_interval;
-  state.mode = unsafe { std::mem::transmute(mode) };
+  state.mode = mode;
+  ErrorCode::Ok
 }
 
 static mut IO_BUFFER: [f32; FRAME_SIZE] = [0.; FRAME_SIZE];
@@ -69,15 +93,15 @@ fn io_buf() -> &'static mut [f32; FRAME_SIZE] { unsafe { &mut IO_BUFFER } }
 pub extern "C" fn get_io_buf_ptr() -> *mut f32 { io_buf().as_mut_ptr() }
 
 #[no_mangle]
-pub extern "C" fn process() {
+pub extern "C" fn process() -> ErrorCode {
   let state = state();
-  if state.quantization_interval <= 0. {
-    return;
-  }
-
-  for sample in io_buf() {
-    *sample = state.quantize(*sample)
+  // A non-positive interval disables quantization
+  if state.quantization_interval > 0. {
+    for sample in io_buf() {
+      *sample = state.quantize(*sample)
+    }
   }
+  ErrorCode::Ok
 }
 
 #[test]
@@ -0,0 +1,154 @@
+use crate::CHANNEL_COUNT;
+
+/// Storage for recorded frames.  Samples are stored interleaved.
+pub enum RecordBuffer {
+  /// Keeps everything that's recorded up to its reserved capacity.  Room for more frames is
+  /// reserved with `reserve_frames` between calls to `process`; frames that arrive once it's
+  /// exhausted are dropped and counted rather than growing the buffer while processing.
+  Growable {
+    samples: Vec<f32>,
+    /// Number of frames that were dropped because there was no reserved room left for them since
+    /// the buffer was last cleared
+    overflowed_frame_count: usize,
+  },
+  /// Keeps only the most recent `capacity_frames` frames, overwriting the oldest ones once full
+  Ring {
+    samples: Vec<f32>,
+    capacity_frames: usize,
+    /// Index of the frame that will be written next
+    write_frame_ix: usize,
+    frame_count: usize,
+    /// Number of frames that have been overwritten since the buffer was last cleared
+    dropped_frame_count: usize,
+  },
+}
+
+impl RecordBuffer {
+  pub fn growable(capacity_frames: usize) -> Self {
+    RecordBuffer::Growable {
+      samples: Vec::with_capacity(capacity_frames * CHANNEL_COUNT),
+      overflowed_frame_count: 0,
+    }
+  }
+
+  pub fn ring(capacity_frames: usize) -> Self {
+    RecordBuffer::Ring {
+      samples: vec![0.; capacity_frames * CHANNEL_COUNT],
+      capacity_frames,
+      write_frame_ix: 0,
+      frame_count: 0,
+      dropped_frame_count: 0,
+    }
+  }
+
+  #[inline]
+  pub fn push_frame(&mut self, frame: [f32; CHANNEL_COUNT]) {
+    match self {
+      RecordBuffer::Growable {
+        samples,
+        overflowed_frame_count,
+      } =>
+        if samples.capacity() - samples.len() >= CHANNEL_COUNT {
+          samples.extend_from_slice(&frame);
+        } else {
+          *overflowed_frame_count += 1;
+        },
+      RecordBuffer::Ring {
+        samples,
+        capacity_frames,
+        write_frame_ix,
+        frame_count,
+        dropped_frame_count,
+      } => {
+        let start_ix = *write_frame_ix * CHANNEL_COUNT;
+        samples[start_ix..start_ix + CHANNEL_COUNT].copy_from_slice(&frame);
+        *write_frame_ix = (*write_frame_ix + 1) % *capacity_frames;
+        if *frame_count == *capacity_frames {
+          *dropped_frame_count += 1;
+        } else {
+          *frame_count += 1;
+        }
+      },
+    }
+  }
+
+  pub fn frame_count(&self) -> usize {
+    match self {
+      RecordBuffer::Growable { samples, .. } => samples.len() / CHANNEL_COUNT,
+      RecordBuffer::Ring { frame_count, .. } => *frame_count,
+    }
+  }
+
+  pub fn dropped_frame_count(&self) -> usize {
+    match self {
+      RecordBuffer::Growable { .. } => 0,
+      RecordBuffer::Ring {
+        dropped_frame_count,
+        ..
+      } => *dropped_frame_count,
+    }
+  }
+
+  /// Number of frames that were dropped because a growable buffer ran out of reserved room.  Ring
+  /// buffers never overflow.
+  pub fn overflowed_frame_count(&self) -> usize {
+    match self {
+      RecordBuffer::Growable {
+        overflowed_frame_count,
+        ..
+      } => *overflowed_frame_count,
+      RecordBuffer::Ring { .. } => 0,
+    }
+  }
+
+  /// Makes sure that a growable buffer has room for at least `frame_count` more frames.  This may
+  /// allocate, so it must never be called from `process`.  Ring buffers are left as-is.
+  pub fn reserve_frames(&mut self, frame_count: usize) {
+    if let RecordBuffer::Growable { samples, .. } = self {
+      samples.reserve_exact(frame_count * CHANNEL_COUNT);
+    }
+  }
+
+  pub fn clear(&mut self) {
+    match self {
+      RecordBuffer::Growable {
+        samples,
+        overflowed_frame_count,
+      } => {
+        samples.clear();
+        *overflowed_frame_count = 0;
+      },
+      RecordBuffer::Ring {
+        write_frame_ix,
+        frame_count,
+        dropped_frame_count,
+        ..
+      } => {
+        *write_frame_ix = 0;
+        *frame_count = 0;
+        *dropped_frame_count = 0;
+      },
+    }
+  }
+
+  /// Returns the recorded samples from oldest to newest as two slices, the second of which is
+  /// empty unless the ring buffer has wrapped around.
+  pub fn as_slices(&self) -> (&[f32], &[f32]) {
+    match self {
+      RecordBuffer::Growable { samples, .. } => (samples, &[]),
+      RecordBuffer::Ring {
+        samples,
+        capacity_frames,
+        write_frame_ix,
+        frame_count,
+        ..
+      } =>
+        if *frame_count < *capacity_frames {
+          (&samples[..*frame_count * CHANNEL_COUNT], &[])
+        } else {
+          let (newer, older) = samples.split_at(*write_frame_ix * CHANNEL_COUNT);
+          (older, newer)
+        },
+    }
+  }
+}
@@ -0,0 +1,627 @@
+//! Records stereo audio fed into it frame by frame.  Recording can be armed and then punched in
+//! and out at transport beats, so takes line up with the rest of the composition.
+//!
+//! Recorded audio is kept either in a buffer that grows for as long as recording continues or in a
+//! fixed-size ring buffer that keeps only the most recent audio, which is useful for always-on
+//! capture.  The recorded region can be exported as a WAV file or as planar samples for loading
+//! directly into the looper or sampler.
+//!
+//! The IO buffer isn't modified; monitoring is handled by whatever the input is connected to.
+
+use common::ffi::{self, ErrorCode};
+use dsp::MAX_FRAME_SIZE;
+
+use self::buffer::RecordBuffer;
+
+mod buffer;
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+  fn log_err(ptr: *const u8, len: usize);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+use common::native::log_err;
+
+pub const CHANNEL_COUNT: usize = 2;
+/// Ring buffers longer than this are almost certainly a mistake; it's about 20 minutes at 48kHz
+pub const MAX_RING_CAPACITY_FRAMES: usize = 48_000 * 60 * 20;
+/// Room reserved in growable buffers when recording is armed; about 30 seconds at 48kHz
+pub const GROWABLE_CHUNK_FRAMES: usize = 48_000 * 30;
+
+/// Index into the SAB of the current `RecorderState`
+pub const SAB_STATE_IX: usize = 0;
+/// Index into the SAB of the number of recorded frames.  This is only exact up to 2^24 frames; use
+/// `recorder_get_recorded_frame_count` when an exact count is needed.
+pub const SAB_RECORDED_FRAME_COUNT_IX: usize = 1;
+/// Index into the SAB of the number of frames dropped because a growable buffer ran out of
+/// reserved room.  Call `recorder_reserve_frames` to make more room.
+pub const SAB_OVERFLOWED_FRAME_COUNT_IX: usize = 2;
+const SAB_SIZE: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecorderState {
+  Idle = 0,
+  /// Waiting for the punch-in point to be reached
+  Armed = 1,
+  Recording = 2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferMode {
+  Growable = 0,
+  Ring = 1,
+}
+
+impl BufferMode {
+  pub fn from_u32(mode: u32) -> Option<Self> {
+    match mode {
+      0 => Some(BufferMode::Growable),
+      1 => Some(BufferMode::Ring),
+      _ => None,
+    }
+  }
+}
+
+fn wav_format_from_u32(format: u32) -> Option<wav::SampleFormat> {
+  match format {
+    0 => Some(wav::SampleFormat::Float32),
+    1 => Some(wav::SampleFormat::Pcm16),
+    2 => Some(wav::SampleFormat::Pcm24),
+    _ => None,
+  }
+}
+
+pub struct RecorderCtx {
+  /// Planar stereo buffer; the left channel starts at index 0 and the right channel at index
+  /// `MAX_FRAME_SIZE`, regardless of the frame size being processed.
+  pub io_buffer: [f32; MAX_FRAME_SIZE * CHANNEL_COUNT],
+  buffer: RecordBuffer,
+  state: RecorderState,
+  /// If set, armed recordings start once the transport reaches this beat.  Otherwise, they start
+  /// with the next processed frame.
+  pub punch_in_beat: Option<f64>,
+  /// If set, recording stops once the transport reaches this beat
+  pub punch_out_beat: Option<f64>,
+  /// Transport position of the first recorded sample, or `None` if the transport wasn't running
+  /// when recording started
+  recorded_start_beat: Option<f64>,
+  /// Planar copy of the recorded region populated by `export_planar`
+  exported: Vec<f32>,
+  /// WAV file populated by `encode_wav`
+  encoded: Vec<u8>,
+  pub sab: [f32; SAB_SIZE],
+}
+
+impl Default for RecorderCtx {
+  fn default() -> Self {
+    Self {
+      io_buffer: [0.; MAX_FRAME_SIZE * CHANNEL_COUNT],
+      buffer: RecordBuffer::growable(0),
+      state: RecorderState::Idle,
+      punch_in_beat: None,
+      punch_out_beat: None,
+      recorded_start_beat: None,
+      exported: Vec::new(),
+      encoded: Vec::new(),
+      sab: [0.; SAB_SIZE],
+    }
+  }
+}
+
+impl RecorderCtx {
+  pub fn state(&self) -> RecorderState { self.state }
+
+  fn set_state(&mut self, state: RecorderState) {
+    self.state = state;
+    self.sab[SAB_STATE_IX] = state as u32 as f32;
+  }
+
+  fn reset_recorded_counts(&mut self) {
+    self.recorded_start_beat = None;
+    self.sab[SAB_RECORDED_FRAME_COUNT_IX] = 0.;
+    self.sab[SAB_OVERFLOWED_FRAME_COUNT_IX] = 0.;
+  }
+
+  /// Clears any previous recording and waits for the punch-in point.  Growable buffers reserve
+  /// room for `GROWABLE_CHUNK_FRAMES` frames up front so that `process` never allocates.
+  pub fn arm(&mut self) {
+    self.buffer.clear();
+    self.buffer.reserve_frames(GROWABLE_CHUNK_FRAMES);
+    self.reset_recorded_counts();
+    self.set_state(RecorderState::Armed);
+  }
+
+  /// Stops recording or cancels a pending punch-in, keeping whatever was recorded so far
+  pub fn stop(&mut self) { self.set_state(RecorderState::Idle); }
+
+  pub fn set_buffer_mode(&mut self, mode: BufferMode, ring_capacity_frames: usize) {
+    self.buffer = match mode {
+      BufferMode::Growable => RecordBuffer::growable(0),
+      BufferMode::Ring => RecordBuffer::ring(ring_capacity_frames),
+    };
+    self.reset_recorded_counts();
+  }
+
+  /// Makes room for at least `frame_count` more frames in a growable buffer.  This may allocate,
+  /// so it's meant to be called between calls to `process` once a recording runs long.
+  pub fn reserve_frames(&mut self, frame_count: usize) { self.buffer.reserve_frames(frame_count); }
+
+  pub fn recorded_frame_count(&self) -> usize { self.buffer.frame_count() }
+
+  pub fn overflowed_frame_count(&self) -> usize { self.buffer.overflowed_frame_count() }
+
+  /// Transport position of the oldest retained sample, or `None` if it's not known.  Once a ring
+  /// buffer has started overwriting old frames, this assumes the tempo was constant.
+  pub fn recorded_start_beat(&self, bpm: f32) -> Option<f64> {
+    let beats_per_sample = bpm as f64 / 60. / dsp::sample_rate() as f64;
+    self
+      .recorded_start_beat
+      .map(|beat| beat + self.buffer.dropped_frame_count() as f64 * beats_per_sample)
+  }
+
+  /// Copies the recorded region into the export buffer with all samples for the left channel
+  /// followed by all samples for the right channel.  Returns the number of frames exported.
+  pub fn export_planar(&mut self) -> usize {
+    let frame_count = self.buffer.frame_count();
+    self.exported.clear();
+    self.exported.resize(frame_count * CHANNEL_COUNT, 0.);
+    let (first, second) = self.buffer.as_slices();
+    let frames = first
+      .chunks_exact(CHANNEL_COUNT)
+      .chain(second.chunks_exact(CHANNEL_COUNT));
+    for (frame_ix, frame) in frames.enumerate() {
+      for (channel_ix, &sample) in frame.iter().enumerate() {
+        self.exported[channel_ix * frame_count + frame_ix] = sample;
+      }
+    }
+    frame_count
+  }
+
+  /// Encodes the recorded region into a WAV file, returning its length in bytes
+  pub fn encode_wav(&mut self, format: wav::SampleFormat) -> Result<usize, wav::WavError> {
+    let (first, second) = self.buffer.as_slices();
+    let spec = wav::WavSpec {
+      channel_count: CHANNEL_COUNT as u16,
+      sample_rate: dsp::sample_rate() as u32,
+      format,
+    };
+    self.encoded = if second.is_empty() {
+      wav::encode(&spec, first)?
+    } else {
+      wav::encode(&spec, &[first, second].concat())?
+    };
+    Ok(self.encoded.len())
+  }
+
+  /// Records the contents of the IO buffer, handling punch-in and punch-out.  `cur_beat` is the
+  /// transport position at the start of the frame and is ignored unless `transport_running` is
+  /// set.  Punch points are only reached while the transport is running.
+  pub fn process(&mut self, frame_size: usize, cur_beat: f64, bpm: f32, transport_running: bool) {
+    if self.state == RecorderState::Idle {
+      return;
+    }
+
+    let beats_per_sample = bpm as f64 / 60. / dsp::sample_rate() as f64;
+    for i in 0..frame_size {
+      let beat = transport_running.then_some(cur_beat + i as f64 * beats_per_sample);
+
+      if self.state == RecorderState::Armed {
+        let reached_punch_in = match self.punch_in_beat {
+          Some(punch_in_beat) => beat.is_some_and(|beat| beat >= punch_in_beat),
+          None => true,
+        };
+        // Jumping straight past the punch-out point, from a loop for example, doesn't start
+        // recording
+        let past_punch_out = matches!(
+          (beat, self.punch_out_beat),
+          (Some(beat), Some(punch_out_beat)) if beat >= punch_out_beat
+        );
+        if !reached_punch_in || past_punch_out {
+          continue;
+        }
+
+        self.recorded_start_beat = beat;
+        self.set_state(RecorderState::Recording);
+      }
+
+      if let (Some(beat), Some(punch_out_beat)) = (beat, self.punch_out_beat) {
+        if beat >= punch_out_beat {
+          self.set_state(RecorderState::Idle);
+          break;
+        }
+      }
+
+      self
+        .buffer
+        .push_frame([self.io_buffer[i], self.io_buffer[MAX_FRAME_SIZE + i]]);
+    }
+
+    self.sab[SAB_RECORDED_FRAME_COUNT_IX] = self.buffer.frame_count() as f32;
+    self.sab[SAB_OVERFLOWED_FRAME_COUNT_IX] = self.buffer.overflowed_frame_count() as f32;
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn recorder_create_ctx() -> *mut RecorderCtx {
+  common::set_raw_panic_hook(log_err);
+
+  Box::into_raw(Box::default())
+}
+
+#[no_mangle]
+pub extern "C" fn recorder_free_ctx(ctx: *mut RecorderCtx) -> ErrorCode {
+  if ctx.is_null() {
+    return ffi::set_last_error(
+      ErrorCode::InvalidHandle,
+      "recorder_free_ctx: null context pointer",
+    );
+  }
+
+  drop(unsafe { Box::from_raw(ctx) });
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn recorder_get_io_buf_ptr(ctx: *mut RecorderCtx) -> *mut f32 {
+  match unsafe { ffi::handle(ctx, "recorder_get_io_buf_ptr") } {
+    Ok(ctx) => ctx.io_buffer.as_mut_ptr(),
+    Err(_) => std::ptr::null_mut(),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn recorder_get_sab_ptr(ctx: *mut RecorderCtx) -> *const f32 {
+  match unsafe { ffi::handle(ctx, "recorder_get_sab_ptr") } {
+    Ok(ctx) => ctx.sab.as_ptr(),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+/// Switches between growable and ring buffers, discarding anything that was recorded.
+/// `ring_capacity_frames` is ignored for growable buffers.
+#[no_mangle]
+pub extern "C" fn recorder_set_buffer_mode(
+  ctx: *mut RecorderCtx,
+  mode: u32,
+  ring_capacity_frames: usize,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "recorder_set_buffer_mode") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  let Some(mode) = BufferMode::from_u32(mode) else {
+    return ffi::set_last_error(
+      ErrorCode::Unsupported,
+      &format!("unknown buffer mode {mode}"),
+    );
+  };
+  ffi::status((|| {
+    if mode == BufferMode::Ring {
+      ffi::check_range(
+        "ring_capacity_frames",
+        ring_capacity_frames,
+        1,
+        MAX_RING_CAPACITY_FRAMES,
+      )?;
+    }
+    if ctx.state() != RecorderState::Idle {
+      return Err(ffi::set_last_error(
+        ErrorCode::Unsupported,
+        "buffer mode can't be changed while recording is armed",
+      ));
+    }
+
+    ctx.set_buffer_mode(mode, ring_capacity_frames);
+    Ok(())
+  })())
+}
+
+/// Sets the punch-in and punch-out points in beats.  Either can be disabled, in which case
+/// recording starts as soon as it's armed or continues until it's stopped respectively.
+#[no_mangle]
+pub extern "C" fn recorder_set_punch_points(
+  ctx: *mut RecorderCtx,
+  punch_in_enabled: bool,
+  punch_in_beat: f64,
+  punch_out_enabled: bool,
+  punch_out_beat: f64,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "recorder_set_punch_points") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    let punch_in_beat = if punch_in_enabled {
+      ffi::check_range("punch_in_beat", punch_in_beat, 0., f64::MAX)?;
+      Some(punch_in_beat)
+    } else {
+      None
+    };
+    let punch_out_beat = if punch_out_enabled {
+      ffi::check_range(
+        "punch_out_beat",
+        punch_out_beat,
+        punch_in_beat.unwrap_or(0.),
+        f64::MAX,
+      )?;
+      Some(punch_out_beat)
+    } else {
+      None
+    };
+
+    ctx.punch_in_beat = punch_in_beat;
+    ctx.punch_out_beat = punch_out_beat;
+    Ok(())
+  })())
+}
+
+/// Makes room for at least `frame_count` more frames in a growable buffer, which is a no-op for
+/// ring buffers.  Arming reserves `GROWABLE_CHUNK_FRAMES` frames; longer recordings should call
+/// this between calls to `recorder_process` before the `SAB_OVERFLOWED_FRAME_COUNT_IX` entry of the
+/// SAB starts counting dropped frames.
+#[no_mangle]
+pub extern "C" fn recorder_reserve_frames(ctx: *mut RecorderCtx, frame_count: usize) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "recorder_reserve_frames") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("frame_count", frame_count, 1, MAX_RING_CAPACITY_FRAMES)?;
+
+    ctx.reserve_frames(frame_count);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn recorder_arm(ctx: *mut RecorderCtx) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "recorder_arm") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ctx.arm();
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn recorder_stop(ctx: *mut RecorderCtx) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "recorder_stop") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ctx.stop();
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn recorder_process(
+  ctx: *mut RecorderCtx,
+  frame_size: usize,
+  cur_beat: f64,
+  bpm: f32,
+  transport_running: bool,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "recorder_process") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("frame_size", frame_size, 1, MAX_FRAME_SIZE)?;
+    ffi::check_range("bpm", bpm, 0., 1000.)?;
+
+    ctx.process(frame_size, cur_beat, bpm, transport_running);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn recorder_get_recorded_frame_count(ctx: *mut RecorderCtx) -> usize {
+  match unsafe { ffi::handle(ctx, "recorder_get_recorded_frame_count") } {
+    Ok(ctx) => ctx.recorded_frame_count(),
+    Err(_) => 0,
+  }
+}
+
+/// Returns the number of frames dropped because a growable buffer ran out of reserved room
+#[no_mangle]
+pub extern "C" fn recorder_get_overflowed_frame_count(ctx: *mut RecorderCtx) -> usize {
+  match unsafe { ffi::handle(ctx, "recorder_get_overflowed_frame_count") } {
+    Ok(ctx) => ctx.overflowed_frame_count(),
+    Err(_) => 0,
+  }
+}
+
+/// Returns the transport position of the start of the recorded region, or NaN if it's not known
+/// or the context is invalid
+#[no_mangle]
+pub extern "C" fn recorder_get_recorded_start_beat(ctx: *mut RecorderCtx, bpm: f32) -> f64 {
+  match unsafe { ffi::handle(ctx, "recorder_get_recorded_start_beat") } {
+    Ok(ctx) => ctx.recorded_start_beat(bpm).unwrap_or(f64::NAN),
+    Err(_) => f64::NAN,
+  }
+}
+
+/// Copies the recorded region into a planar buffer for loading into the looper or sampler.  Call
+/// `recorder_get_recorded_frame_count` for the number of frames copied and
+/// `recorder_get_exported_samples_ptr` to read them.
+#[no_mangle]
+pub extern "C" fn recorder_export_planar(ctx: *mut RecorderCtx) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "recorder_export_planar") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ctx.export_planar();
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn recorder_get_exported_samples_ptr(ctx: *mut RecorderCtx) -> *const f32 {
+  match unsafe { ffi::handle(ctx, "recorder_get_exported_samples_ptr") } {
+    Ok(ctx) => ctx.exported.as_ptr(),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+/// Encodes the recorded region as a WAV file.  `format` is 0 for 32-bit float, 1 for 16-bit PCM,
+/// or 2 for 24-bit PCM.  Call `recorder_get_encoded_len` and `recorder_get_encoded_ptr` to read
+/// the encoded file.
+#[no_mangle]
+pub extern "C" fn recorder_encode_wav(ctx: *mut RecorderCtx, format: u32) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "recorder_encode_wav") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  let Some(format) = wav_format_from_u32(format) else {
+    return ffi::set_last_error(
+      ErrorCode::Unsupported,
+      &format!("unknown WAV format {format}"),
+    );
+  };
+
+  match ctx.encode_wav(format) {
+    Ok(_) => ErrorCode::Ok,
+    Err(err) => ffi::set_last_error(ErrorCode::Unsupported, &format!("{err}")),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn recorder_get_encoded_len(ctx: *mut RecorderCtx) -> usize {
+  match unsafe { ffi::handle(ctx, "recorder_get_encoded_len") } {
+    Ok(ctx) => ctx.encoded.len(),
+    Err(_) => 0,
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn recorder_get_encoded_ptr(ctx: *mut RecorderCtx) -> *const u8 {
+  match unsafe { ffi::handle(ctx, "recorder_get_encoded_ptr") } {
+    Ok(ctx) => ctx.encoded.as_ptr(),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+/// Fills the IO buffer with sample indices scaled down to stay within [-1, 1]; negated on the right
+/// channel
+#[cfg(test)]
+fn fill_ramp(ctx: &mut RecorderCtx, frame_size: usize, start_sample_ix: usize) {
+  for i in 0..frame_size {
+    ctx.io_buffer[i] = ramp_sample(start_sample_ix + i);
+    ctx.io_buffer[MAX_FRAME_SIZE + i] = -ramp_sample(start_sample_ix + i);
+  }
+}
+
+#[cfg(test)]
+fn ramp_sample(sample_ix: usize) -> f32 { sample_ix as f32 / (1 << 20) as f32 }
+
+#[test]
+fn punch_in_out() {
+  let mut ctx = RecorderCtx {
+    punch_in_beat: Some(1.),
+    punch_out_beat: Some(2.),
+    ..Default::default()
+  };
+  ctx.arm();
+
+  // Record four beats of audio at 120 BPM with the transport running from the start
+  let bpm = 120.;
+  let samples_per_beat = dsp::sample_rate() as f64 * 60. / bpm as f64;
+  let beats_per_frame = 128. / samples_per_beat;
+  let frame_count = (4. / beats_per_frame).ceil() as usize;
+  for frame_ix in 0..frame_count {
+    fill_ramp(&mut ctx, 128, frame_ix * 128);
+    ctx.process(128, frame_ix as f64 * beats_per_frame, bpm, true);
+  }
+
+  assert_eq!(ctx.state(), RecorderState::Idle);
+  let first_sample_ix = samples_per_beat.ceil();
+  assert_eq!(
+    ctx.recorded_frame_count(),
+    (samples_per_beat * 2.).ceil() as usize - first_sample_ix as usize
+  );
+  let start_beat = ctx.recorded_start_beat(bpm).unwrap();
+  assert!((start_beat - first_sample_ix / samples_per_beat).abs() < 1e-9);
+
+  let frame_count = ctx.export_planar();
+  assert_eq!(ctx.exported[0], ramp_sample(first_sample_ix as usize));
+  assert_eq!(
+    ctx.exported[frame_count],
+    -ramp_sample(first_sample_ix as usize)
+  );
+}
+
+#[test]
+fn ring_buffer_keeps_most_recent_frames() {
+  let mut ctx = RecorderCtx::default();
+  ctx.set_buffer_mode(BufferMode::Ring, 300);
+  ctx.arm();
+  for frame_ix in 0..4 {
+    fill_ramp(&mut ctx, 128, frame_ix * 128);
+    ctx.process(128, 0., 120., false);
+  }
+
+  assert_eq!(ctx.state(), RecorderState::Recording);
+  assert_eq!(ctx.recorded_frame_count(), 300);
+  assert_eq!(ctx.recorded_start_beat(120.), None);
+  ctx.export_planar();
+  let left = &ctx.exported[..300];
+  assert!(left.iter().copied().eq((212..512).map(ramp_sample)));
+
+  ctx.encode_wav(wav::SampleFormat::Float32).unwrap();
+  let (spec, decoded) = wav::decode(&ctx.encoded).unwrap();
+  assert_eq!(spec.channel_count, 2);
+  assert_eq!(&decoded[..4], &[
+    ramp_sample(212),
+    -ramp_sample(212),
+    ramp_sample(213),
+    -ramp_sample(213)
+  ]);
+}
+
+#[test]
+fn growable_buffer_overflows_instead_of_allocating() {
+  // Skip `arm` so that only the room reserved here is available
+  let mut ctx = RecorderCtx {
+    buffer: RecordBuffer::growable(300),
+    ..Default::default()
+  };
+  ctx.set_state(RecorderState::Armed);
+  for frame_ix in 0..4 {
+    fill_ramp(&mut ctx, 128, frame_ix * 128);
+    ctx.process(128, 0., 120., false);
+  }
+
+  assert_eq!(ctx.recorded_frame_count(), 300);
+  assert_eq!(ctx.overflowed_frame_count(), 212);
+  assert_eq!(ctx.sab[SAB_OVERFLOWED_FRAME_COUNT_IX], 212.);
+  ctx.export_planar();
+  assert!(ctx.exported[..300]
+    .iter()
+    .copied()
+    .eq((0..300).map(ramp_sample)));
+
+  ctx.reserve_frames(128);
+  fill_ramp(&mut ctx, 128, 512);
+  ctx.process(128, 0., 120., false);
+  assert_eq!(ctx.recorded_frame_count(), 428);
+  assert_eq!(ctx.overflowed_frame_count(), 212);
+
+  ctx.arm();
+  assert_eq!(ctx.overflowed_frame_count(), 0);
+  assert_eq!(ctx.sab[SAB_OVERFLOWED_FRAME_COUNT_IX], 0.);
+}
+
+#[test]
+fn getters_reject_null_ctx() {
+  let ctx = std::ptr::null_mut();
+  assert!(recorder_get_io_buf_ptr(ctx).is_null());
+  assert!(recorder_get_sab_ptr(ctx).is_null());
+  assert_eq!(recorder_get_recorded_frame_count(ctx), 0);
+  assert_eq!(recorder_get_overflowed_frame_count(ctx), 0);
+  assert!(recorder_get_recorded_start_beat(ctx, 120.).is_nan());
+  assert_eq!(recorder_export_planar(ctx), ErrorCode::InvalidHandle);
+  assert!(recorder_get_exported_samples_ptr(ctx).is_null());
+  assert_eq!(recorder_get_encoded_len(ctx), 0);
+  assert!(recorder_get_encoded_ptr(ctx).is_null());
+  assert_eq!(recorder_reserve_frames(ctx, 1), ErrorCode::InvalidHandle);
+}
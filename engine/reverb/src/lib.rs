@@ -0,0 +1,519 @@
+//! Standalone stereo reverb based on the Freeverb algorithm: a bank of parallel lowpass-feedback
+//! comb filters per channel followed by a chain of series allpass filters.  An optional pre-delay
+//! is applied to the input before it reaches the tank.
+//!
+//! This lives outside of the FM synth effect chain so that any signal in the graph can be routed
+//! through it.
+
+use common::{
+  ffi::{self, ErrorCode, FfiResult},
+  state::{ABCompare, ModuleState, StateSnapshot, MAX_AB_FADE_MS},
+};
+use dsp::{
+  delay_line::{DelayInterpolation, DelayLine},
+  linear_to_db_checked,
+  smoothed_param::{SmoothedParam, SmoothingMode},
+  MAX_FRAME_SIZE,
+};
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+  fn log_err(ptr: *const u8, len: usize);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+use common::native::log_err;
+
+const CHANNEL_COUNT: usize = 2;
+
+/// Comb filter delay lengths in samples at 44.1kHz, from the original Freeverb
+const COMB_TUNINGS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+/// Allpass filter delay lengths in samples at 44.1kHz, from the original Freeverb
+const ALLPASS_TUNINGS: [usize; 4] = [556, 441, 341, 225];
+/// Added to all delay lengths for the right channel to decorrelate it from the left
+const STEREO_SPREAD: usize = 23;
+const TUNING_SAMPLE_RATE: f32 = 44_100.;
+
+const ALLPASS_FEEDBACK: f32 = 0.5;
+/// Input gain into the tank.  The combs are in parallel and sum to a much higher level than their
+/// input, so this brings the wet signal back down to roughly unity.
+const FIXED_GAIN: f32 = 0.015;
+const SCALE_WET: f32 = 3.;
+const SCALE_DAMPING: f32 = 0.4;
+const SCALE_ROOM: f32 = 0.28;
+const OFFSET_ROOM: f32 = 0.7;
+
+pub const MAX_PRE_DELAY_MS: f32 = 500.;
+const PRE_DELAY_BUFFER_SIZE: usize =
+  (dsp::MAX_SAMPLE_RATE as usize * MAX_PRE_DELAY_MS as usize / 1000 + 1).next_power_of_two();
+const PARAM_SMOOTHING_MS: f32 = 20.;
+/// Pre-delay is smoothed more heavily since sweeping it pitch-shifts the wet signal
+const PRE_DELAY_SMOOTHING_MS: f32 = 100.;
+
+/// Index into the SAB of the peak input level in dB over the last frame
+pub const SAB_INPUT_LEVEL_DB_IX: usize = 0;
+/// Index into the SAB of the peak output level in dB over the last frame
+pub const SAB_OUTPUT_LEVEL_DB_IX: usize = 1;
+/// Index into the SAB of the peak level of the reverb tail in dB over the last frame, before it's
+/// mixed with the dry signal
+pub const SAB_WET_LEVEL_DB_IX: usize = 2;
+const SAB_SIZE: usize = 3;
+
+/// Names of the params included in the reverb's state, in the order used for A/B slots
+const STATE_PARAM_NAMES: [&str; 5] = ["room_size", "damping", "width", "mix", "pre_delay_ms"];
+
+fn scale_tuning(tuning: usize) -> usize {
+  ((tuning as f32 * dsp::sample_rate() / TUNING_SAMPLE_RATE).round() as usize).max(1)
+}
+
+/// Comb filter with a one-pole lowpass in its feedback path
+#[derive(Clone)]
+struct Comb {
+  buffer: Vec<f32>,
+  ix: usize,
+  filter_state: f32,
+}
+
+impl Comb {
+  fn new(len: usize) -> Self {
+    Comb {
+      buffer: vec![0.; len],
+      ix: 0,
+      filter_state: 0.,
+    }
+  }
+
+  #[inline]
+  fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+    let output = self.buffer[self.ix];
+    self.filter_state = output * (1. - damping) + self.filter_state * damping;
+    self.buffer[self.ix] = input + self.filter_state * feedback;
+    self.ix += 1;
+    if self.ix == self.buffer.len() {
+      self.ix = 0;
+    }
+    output
+  }
+
+  fn reset(&mut self) {
+    self.buffer.fill(0.);
+    self.filter_state = 0.;
+  }
+}
+
+/// Schroeder allpass as implemented by Freeverb, which isn't quite allpass but sounds better
+#[derive(Clone)]
+struct Allpass {
+  buffer: Vec<f32>,
+  ix: usize,
+}
+
+impl Allpass {
+  fn new(len: usize) -> Self {
+    Allpass {
+      buffer: vec![0.; len],
+      ix: 0,
+    }
+  }
+
+  #[inline]
+  fn process(&mut self, input: f32) -> f32 {
+    let delayed = self.buffer[self.ix];
+    self.buffer[self.ix] = input + delayed * ALLPASS_FEEDBACK;
+    self.ix += 1;
+    if self.ix == self.buffer.len() {
+      self.ix = 0;
+    }
+    delayed - input
+  }
+
+  fn reset(&mut self) { self.buffer.fill(0.); }
+}
+
+#[derive(Clone)]
+struct Tank {
+  combs: Vec<Comb>,
+  allpasses: Vec<Allpass>,
+}
+
+impl Tank {
+  fn new(spread: usize) -> Self {
+    Tank {
+      combs: COMB_TUNINGS
+        .iter()
+        .map(|&tuning| Comb::new(scale_tuning(tuning + spread)))
+        .collect(),
+      allpasses: ALLPASS_TUNINGS
+        .iter()
+        .map(|&tuning| Allpass::new(scale_tuning(tuning + spread)))
+        .collect(),
+    }
+  }
+
+  #[inline]
+  fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+    let mut output = 0.;
+    for comb in &mut self.combs {
+      output += comb.process(input, feedback, damping);
+    }
+    for allpass in &mut self.allpasses {
+      output = allpass.process(output);
+    }
+    output
+  }
+
+  fn reset(&mut self) {
+    self.combs.iter_mut().for_each(Comb::reset);
+    self.allpasses.iter_mut().for_each(Allpass::reset);
+  }
+}
+
+pub struct ReverbCtx {
+  /// Planar stereo buffer; the left channel starts at index 0 and the right channel at index
+  /// `MAX_FRAME_SIZE`, regardless of the frame size being processed.
+  pub io_buffer: [f32; MAX_FRAME_SIZE * CHANNEL_COUNT],
+  tanks: [Tank; CHANNEL_COUNT],
+  pre_delay: Box<DelayLine<PRE_DELAY_BUFFER_SIZE>>,
+  room_size: SmoothedParam,
+  damping: SmoothedParam,
+  width: SmoothedParam,
+  mix: SmoothedParam,
+  pre_delay_samples: SmoothedParam,
+  pub sab: [f32; SAB_SIZE],
+  pub ab: ABCompare,
+}
+
+impl Default for ReverbCtx {
+  fn default() -> Self {
+    Self {
+      io_buffer: [0.; MAX_FRAME_SIZE * CHANNEL_COUNT],
+      tanks: [Tank::new(0), Tank::new(STEREO_SPREAD)],
+      pre_delay: Box::new(DelayLine::new(DelayInterpolation::Linear)),
+      room_size: SmoothedParam::new(SmoothingMode::Exponential, 0.5, PARAM_SMOOTHING_MS),
+      damping: SmoothedParam::new(SmoothingMode::Exponential, 0.5, PARAM_SMOOTHING_MS),
+      width: SmoothedParam::new(SmoothingMode::Exponential, 1., PARAM_SMOOTHING_MS),
+      mix: SmoothedParam::new(SmoothingMode::Exponential, 0., PARAM_SMOOTHING_MS),
+      pre_delay_samples: SmoothedParam::new(SmoothingMode::Exponential, 0., PRE_DELAY_SMOOTHING_MS),
+      sab: [-100.; SAB_SIZE],
+      ab: ABCompare::default(),
+    }
+  }
+}
+
+impl ReverbCtx {
+  /// All params are normalized to `[0, 1]` except for `pre_delay_ms`.
+  pub fn process(
+    &mut self,
+    frame_size: usize,
+    room_size: f32,
+    damping: f32,
+    width: f32,
+    mix: f32,
+    pre_delay_ms: f32,
+  ) {
+    self.room_size.set_target(room_size);
+    self.damping.set_target(damping);
+    self.width.set_target(width);
+    self.mix.set_target(mix);
+    self
+      .pre_delay_samples
+      .set_target(dsp::ms_to_samples(pre_delay_ms));
+
+    let mut input_peak = 0.0f32;
+    let mut output_peak = 0.0f32;
+    let mut wet_peak = 0.0f32;
+
+    let (left, right) = self.io_buffer.split_at_mut(MAX_FRAME_SIZE);
+    for (l, r) in left[..frame_size]
+      .iter_mut()
+      .zip(right[..frame_size].iter_mut())
+    {
+      // Anything non-finite would get stuck circulating in the tank forever
+      let dry_l = if l.is_finite() { *l } else { 0. };
+      let dry_r = if r.is_finite() { *r } else { 0. };
+      input_peak = input_peak.max(dry_l.abs()).max(dry_r.abs());
+
+      let feedback = self.room_size.tick() * SCALE_ROOM + OFFSET_ROOM;
+      let damping = self.damping.tick() * SCALE_DAMPING;
+      let width = self.width.tick();
+      let mix = self.mix.tick();
+      let pre_delay_samples = self.pre_delay_samples.tick();
+
+      let mut tank_input = (dry_l + dry_r) * FIXED_GAIN;
+      let delayed = self.pre_delay.read_at(pre_delay_samples);
+      self.pre_delay.write(tank_input);
+      if pre_delay_samples >= 1. {
+        tank_input = delayed;
+      }
+
+      let tank_l = self.tanks[0].process(tank_input, feedback, damping);
+      let tank_r = self.tanks[1].process(tank_input, feedback, damping);
+
+      let wet = mix * SCALE_WET;
+      let wet_1 = wet * (width / 2. + 0.5);
+      let wet_2 = wet * ((1. - width) / 2.);
+      let dry = 1. - mix;
+      *l = tank_l * wet_1 + tank_r * wet_2 + dry_l * dry;
+      *r = tank_r * wet_1 + tank_l * wet_2 + dry_r * dry;
+
+      wet_peak = wet_peak.max(tank_l.abs()).max(tank_r.abs());
+      output_peak = output_peak.max(l.abs()).max(r.abs());
+    }
+
+    self.sab[SAB_INPUT_LEVEL_DB_IX] = linear_to_db_checked(input_peak);
+    self.sab[SAB_OUTPUT_LEVEL_DB_IX] = linear_to_db_checked(output_peak);
+    self.sab[SAB_WET_LEVEL_DB_IX] = linear_to_db_checked(wet_peak * SCALE_WET);
+  }
+
+  /// Clears the reverb tail and pre-delay buffer
+  pub fn reset(&mut self) {
+    self.tanks.iter_mut().for_each(Tank::reset);
+    self.pre_delay.reset();
+    self.sab = [-100.; SAB_SIZE];
+  }
+}
+
+impl ReverbCtx {
+  /// Params from the most recent `reverb_process` call in the order of `STATE_PARAM_NAMES`
+  fn state_params(&self) -> [f32; STATE_PARAM_NAMES.len()] {
+    [
+      self.room_size.target(),
+      self.damping.target(),
+      self.width.target(),
+      self.mix.target(),
+      self.pre_delay_samples.target() / dsp::ms_to_samples(1.),
+    ]
+  }
+}
+
+fn params_to_state(params: &[f32]) -> StateSnapshot {
+  let mut state = StateSnapshot::default();
+  for (name, &val) in STATE_PARAM_NAMES.iter().zip(params) {
+    state.push_number(name, val);
+  }
+  state
+}
+
+/// State is the params from the most recent `reverb_process` call, keyed by the names of the
+/// AWP's `AudioParam`s.  Those params are passed in every frame, so restored state is only heard
+/// once the host starts passing the restored values.
+impl ModuleState for ReverbCtx {
+  fn get_state(&self) -> StateSnapshot { params_to_state(&self.state_params()) }
+
+  fn set_state(&mut self, state: &StateSnapshot) -> FfiResult {
+    let get = |name: &str, max: f32| -> FfiResult<Option<f32>> {
+      let Some(val) = state.number(name)? else {
+        return Ok(None);
+      };
+      ffi::check_range(name, val, 0., max)?;
+      Ok(Some(val))
+    };
+    let room_size = get("room_size", 1.)?;
+    let damping = get("damping", 1.)?;
+    let width = get("width", 1.)?;
+    let mix = get("mix", 1.)?;
+    let pre_delay_ms = get("pre_delay_ms", MAX_PRE_DELAY_MS)?;
+
+    for (param, val) in [
+      (&mut self.room_size, room_size),
+      (&mut self.damping, damping),
+      (&mut self.width, width),
+      (&mut self.mix, mix),
+      (
+        &mut self.pre_delay_samples,
+        pre_delay_ms.map(dsp::ms_to_samples),
+      ),
+    ] {
+      if let Some(val) = val {
+        param.set_target(val);
+      }
+    }
+    Ok(())
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn reverb_create_ctx() -> *mut ReverbCtx {
+  common::set_raw_panic_hook(log_err);
+
+  Box::into_raw(Box::default())
+}
+
+#[no_mangle]
+pub extern "C" fn reverb_get_io_buf_ptr(ctx: *mut ReverbCtx) -> *mut f32 {
+  match unsafe { ffi::handle(ctx, "reverb_get_io_buf_ptr") } {
+    Ok(ctx) => ctx.io_buffer.as_mut_ptr(),
+    Err(_) => std::ptr::null_mut(),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn reverb_get_sab_ptr(ctx: *mut ReverbCtx) -> *const f32 {
+  match unsafe { ffi::handle(ctx, "reverb_get_sab_ptr") } {
+    Ok(ctx) => ctx.sab.as_ptr(),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn reverb_process(
+  ctx: *mut ReverbCtx,
+  frame_size: usize,
+  room_size: f32,
+  damping: f32,
+  width: f32,
+  mix: f32,
+  pre_delay_ms: f32,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "reverb_process") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("frame_size", frame_size, 1, MAX_FRAME_SIZE)?;
+    ffi::check_range("room_size", room_size, 0., 1.)?;
+    ffi::check_range("damping", damping, 0., 1.)?;
+    ffi::check_range("width", width, 0., 1.)?;
+    ffi::check_range("mix", mix, 0., 1.)?;
+    ffi::check_range("pre_delay_ms", pre_delay_ms, 0., MAX_PRE_DELAY_MS)?;
+
+    // Blended params take precedence over the ones passed in while an A/B crossfade is running
+    let mut params = [room_size, damping, width, mix, pre_delay_ms];
+    ctx.ab.tick(frame_size, &mut params);
+    let [room_size, damping, width, mix, pre_delay_ms] = params;
+    ctx.process(frame_size, room_size, damping, width, mix, pre_delay_ms);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn reverb_reset(ctx: *mut ReverbCtx) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "reverb_reset") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ctx.reset();
+  ErrorCode::Ok
+}
+
+/// Snapshots the reverb's params as JSON; see `common::state`.  Returns the length of the JSON in
+/// bytes.
+#[no_mangle]
+pub extern "C" fn reverb_get_state_json(ctx: *mut ReverbCtx) -> usize {
+  match unsafe { ffi::handle(ctx, "reverb_get_state_json") } {
+    Ok(ctx) => common::state::export_state(ctx),
+    Err(_) => 0,
+  }
+}
+
+/// Restores params from the first `len` bytes of the state JSON buffer
+#[no_mangle]
+pub extern "C" fn reverb_set_state_json(ctx: *mut ReverbCtx, len: usize) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "reverb_set_state_json") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  common::state::import_state(ctx, len, "reverb_set_state_json")
+}
+
+/// Saves the current params into the active A/B slot and crossfades to the params in `slot`.  See
+/// `common::state::ABCompare`.
+#[no_mangle]
+pub extern "C" fn reverb_ab_select(ctx: *mut ReverbCtx, slot: usize, fade_ms: f32) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "reverb_ab_select") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("fade_ms", fade_ms, 0., MAX_AB_FADE_MS)?;
+    let current = ctx.state_params();
+    let fade_samples = dsp::ms_to_samples(fade_ms) as usize;
+    ctx.ab.select(slot, &current, fade_samples)
+  })())
+}
+
+/// Writes the params in A/B `slot` as state JSON, returning its length in bytes or 0 if the slot
+/// is empty
+#[no_mangle]
+pub extern "C" fn reverb_ab_get_slot_state_json(ctx: *mut ReverbCtx, slot: usize) -> usize {
+  let ctx = match unsafe { ffi::handle(ctx, "reverb_ab_get_slot_state_json") } {
+    Ok(ctx) => ctx,
+    Err(_) => return 0,
+  };
+  match ctx.ab.slot(slot) {
+    Some(params) => common::state::write_state_json(&params_to_state(params)),
+    None => 0,
+  }
+}
+
+#[test]
+fn impulse_response_decays() {
+  let mut ctx = ReverbCtx::default();
+  let frame_size = 128;
+  ctx.io_buffer[0] = 1.;
+  ctx.process(frame_size, 0.5, 0.5, 1., 1., 0.);
+
+  let mut max_tail = 0.0f32;
+  for frame_ix in 0..4000 {
+    ctx.io_buffer.fill(0.);
+    ctx.process(frame_size, 0.5, 0.5, 1., 1., 0.);
+    assert!(ctx.io_buffer.iter().all(|s| s.is_finite()));
+
+    let peak = ctx.io_buffer.iter().fold(0.0f32, |acc, s| acc.max(s.abs()));
+    if frame_ix < 100 {
+      max_tail = max_tail.max(peak);
+    } else if frame_ix > 3900 {
+      assert!(peak < 1e-5, "frame {frame_ix}: {peak}");
+    }
+  }
+  assert!(max_tail > 1e-3, "{max_tail}");
+}
+
+#[test]
+fn zero_width_is_mono() {
+  let mut ctx = ReverbCtx::default();
+  let frame_size = 64;
+  for frame_ix in 0..50 {
+    for i in 0..frame_size {
+      let t = (frame_ix * frame_size + i) as f32;
+      ctx.io_buffer[i] = (t * 0.05).sin();
+      ctx.io_buffer[MAX_FRAME_SIZE + i] = (t * 0.031).sin();
+    }
+    ctx.process(frame_size, 0.7, 0.2, 0., 1., 20.);
+  }
+
+  // With the mix fully wet, both outputs contain the same blend of the two tanks
+  for i in 0..frame_size {
+    let (l, r) = (ctx.io_buffer[i], ctx.io_buffer[MAX_FRAME_SIZE + i]);
+    assert!((l - r).abs() < 1e-4, "{i}: {l} != {r}");
+  }
+  assert!(ctx.sab[SAB_WET_LEVEL_DB_IX] > -60.);
+}
+
+#[test]
+fn state_roundtrip() {
+  use common::state::StateValue;
+
+  let mut ctx = ReverbCtx::default();
+  ctx.process(64, 0.8, 0.3, 0.6, 0.4, 120.);
+  let state = ctx.get_state();
+
+  let mut restored = ReverbCtx::default();
+  restored.set_state(&state).unwrap();
+  let restored_state = restored.get_state();
+  for (name, val) in &state.entries {
+    let (StateValue::Number(expected), Some(StateValue::Number(val))) =
+      (val, restored_state.get(name))
+    else {
+      panic!("{name} missing from restored state");
+    };
+    assert!((expected - val).abs() < 1e-3, "{name}: {expected} != {val}");
+  }
+
+  // Out-of-range values reject the whole snapshot
+  let mut invalid = StateSnapshot::default();
+  invalid.push_number("mix", 0.9);
+  invalid.push_number("pre_delay_ms", MAX_PRE_DELAY_MS * 2.);
+  assert!(restored.set_state(&invalid).is_err());
+  assert_eq!(restored.mix.target(), 0.4);
+}
@@ -0,0 +1,185 @@
+//! Final safety stage that sits between the global volume and the audio context's destination.  It
+//! removes DC offset and applies a fast, linked-channel peak limiter with a hard ceiling at 0 dBFS
+//! so that runaway feedback patches can't damage speakers or ears.
+
+use common::ffi::{self, ErrorCode};
+use dsp::{filters::dc_blocker::DCBlocker, gain_to_db, FRAME_SIZE};
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+  fn log_err(ptr: *const u8, len: usize);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+use common::native::log_err;
+
+const CHANNEL_COUNT: usize = 2;
+/// Hard ceiling for output samples, 0 dBFS
+const CEILING: f32 = 1.;
+const RELEASE_MS: f32 = 50.;
+/// Low enough to leave everything audible alone while still removing DC offset
+const DC_BLOCKER_CUTOFF_HZ: f32 = 5.;
+
+/// Index into the SAB of a flag that is set to 1 if the limiter engaged during the last frame
+pub const SAB_ENGAGED_IX: usize = 0;
+/// Index into the SAB of the max gain reduction in dB applied during the last frame
+pub const SAB_GAIN_REDUCTION_DB_IX: usize = 1;
+/// Index into the SAB of the number of frames in which the limiter has engaged since creation
+pub const SAB_ENGAGED_FRAME_COUNT_IX: usize = 2;
+/// Index into the SAB of the number of non-finite samples that have been replaced with silence
+pub const SAB_NON_FINITE_SAMPLE_COUNT_IX: usize = 3;
+const SAB_SIZE: usize = 4;
+
+pub struct SafetyLimiterCtx {
+  /// Planar stereo buffer; the left channel is followed by the right channel
+  pub io_buffer: [f32; FRAME_SIZE * CHANNEL_COUNT],
+  pub dc_blockers: [DCBlocker; CHANNEL_COUNT],
+  /// Current gain applied to both channels, in (0, 1]
+  pub gain: f32,
+  release_coefficient: f32,
+  pub sab: [f32; SAB_SIZE],
+}
+
+impl Default for SafetyLimiterCtx {
+  fn default() -> Self {
+    Self {
+      io_buffer: [0.; FRAME_SIZE * CHANNEL_COUNT],
+      dc_blockers: [
+        DCBlocker::new(DC_BLOCKER_CUTOFF_HZ),
+        DCBlocker::new(DC_BLOCKER_CUTOFF_HZ),
+      ],
+      gain: 1.,
+      release_coefficient: (-1. / dsp::ms_to_samples(RELEASE_MS)).exp(),
+      sab: [0.; SAB_SIZE],
+    }
+  }
+}
+
+impl SafetyLimiterCtx {
+  pub fn process(&mut self) {
+    let mut engaged = false;
+    let mut min_gain = 1.0f32;
+
+    let (left, right) = self.io_buffer.split_at_mut(FRAME_SIZE);
+    for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+      for (sample, dc_blocker) in [&mut *l, &mut *r].into_iter().zip(&mut self.dc_blockers) {
+        if !sample.is_finite() {
+          *sample = 0.;
+          // Filter state is poisoned as well if a NaN made it through
+          dc_blocker.reset();
+          self.sab[SAB_NON_FINITE_SAMPLE_COUNT_IX] += 1.;
+        }
+        *sample = dc_blocker.apply(*sample);
+      }
+
+      // Instant attack so that nothing gets past the ceiling, exponential release back to unity
+      let peak = l.abs().max(r.abs());
+      let target_gain = if peak > CEILING { CEILING / peak } else { 1. };
+      self.gain = if target_gain < self.gain {
+        target_gain
+      } else {
+        target_gain + self.release_coefficient * (self.gain - target_gain)
+      };
+      // The release curve only approaches unity asymptotically
+      if self.gain > 0.9999 {
+        self.gain = 1.;
+      }
+
+      if self.gain < 1. {
+        engaged = true;
+        min_gain = min_gain.min(self.gain);
+      }
+
+      *l = (*l * self.gain).clamp(-CEILING, CEILING);
+      *r = (*r * self.gain).clamp(-CEILING, CEILING);
+    }
+
+    self.sab[SAB_ENGAGED_IX] = if engaged { 1. } else { 0. };
+    self.sab[SAB_GAIN_REDUCTION_DB_IX] = -gain_to_db(min_gain);
+    if engaged {
+      self.sab[SAB_ENGAGED_FRAME_COUNT_IX] += 1.;
+    }
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn safety_limiter_create_ctx() -> *mut SafetyLimiterCtx {
+  common::set_raw_panic_hook(log_err);
+
+  Box::into_raw(Box::new(SafetyLimiterCtx::default()))
+}
+
+#[no_mangle]
+pub extern "C" fn safety_limiter_get_io_buf_ptr(ctx: *mut SafetyLimiterCtx) -> *mut f32 {
+  match unsafe { ffi::handle(ctx, "safety_limiter_get_io_buf_ptr") } {
+    Ok(ctx) => ctx.io_buffer.as_mut_ptr(),
+    Err(_) => std::ptr::null_mut(),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn safety_limiter_get_sab_ptr(ctx: *mut SafetyLimiterCtx) -> *const f32 {
+  match unsafe { ffi::handle(ctx, "safety_limiter_get_sab_ptr") } {
+    Ok(ctx) => ctx.sab.as_ptr(),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn safety_limiter_process(ctx: *mut SafetyLimiterCtx) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "safety_limiter_process") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ctx.process();
+  ErrorCode::Ok
+}
+
+#[test]
+fn safety_limiter_ceiling() {
+  let mut ctx = SafetyLimiterCtx::default();
+  for (i, sample) in ctx.io_buffer.iter_mut().enumerate() {
+    *sample = if i % 2 == 0 { 8. } else { -8. };
+  }
+  ctx.io_buffer[3] = f32::NAN;
+  ctx.process();
+
+  assert!(ctx
+    .io_buffer
+    .iter()
+    .all(|s| s.is_finite() && s.abs() <= CEILING));
+  assert_eq!(ctx.sab[SAB_ENGAGED_IX], 1.);
+  assert!(ctx.sab[SAB_GAIN_REDUCTION_DB_IX] > 0.);
+  assert_eq!(ctx.sab[SAB_NON_FINITE_SAMPLE_COUNT_IX], 1.);
+
+  ctx.io_buffer = [0.1; FRAME_SIZE * CHANNEL_COUNT];
+  for _ in 0..200 {
+    ctx.process();
+  }
+  assert_eq!(ctx.sab[SAB_ENGAGED_IX], 0.);
+}
+
+#[test]
+fn safety_limiter_dc_blocker_passes_bass() {
+  dsp::set_sample_rate(48_000.);
+  let mut ctx = SafetyLimiterCtx::default();
+  // 20 Hz with a DC offset; the offset should be removed while the tone is left alone
+  let mut phase = 0.0f32;
+  let (mut peak, mut sum) = (0.0f32, 0.);
+  for frame_ix in 0..1000 {
+    for i in 0..FRAME_SIZE {
+      let sample = 0.5 * phase.sin() + 0.25;
+      ctx.io_buffer[i] = sample;
+      ctx.io_buffer[FRAME_SIZE + i] = sample;
+      phase += std::f32::consts::TAU * 20. / 48_000.;
+    }
+    ctx.process();
+    if frame_ix >= 500 {
+      peak = ctx.io_buffer.iter().fold(peak, |peak, s| peak.max(s.abs()));
+      sum += ctx.io_buffer[..FRAME_SIZE].iter().sum::<f32>();
+    }
+  }
+  let mean = sum / (500 * FRAME_SIZE) as f32;
+  assert!(mean.abs() < 0.01, "mean={mean}");
+  assert!(peak > 0.45, "peak={peak}");
+}
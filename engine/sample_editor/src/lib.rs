@@ -3,6 +3,7 @@ use std::{
   collections::BinaryHeap,
 };
 
+use common::ffi::{self, ErrorCode};
 use float_ord::FloatOrd;
 use slotmap::{DefaultKey, SlotMap};
 
@@ -232,30 +233,48 @@ pub extern "C" fn create_sample(
 }
 
 #[no_mangle]
-pub extern "C" fn remove_sample(ctx: *mut SampleEditorCtx, key: u32) {
-  let ctx = unsafe { &mut *ctx };
+pub extern "C" fn remove_sample(ctx: *mut SampleEditorCtx, key: u32) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "remove_sample") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
   let key = unsafe { std::mem::transmute(key as u64) };
-  let _sample = ctx
-    .samples
-    .remove(key)
-    .unwrap_or_else(|| panic!("{}", format!("No sample found with key={:?}", key)));
+  if ctx.samples.remove(key).is_none() {
+    return ffi::set_last_error(
+      ErrorCode::ParamOutOfRange,
+      &format!("No sample found with key={:?}", key),
+    );
+  }
 
   // Remove playhead for this sample if one exists
   let key = unsafe { std::mem::transmute(key) };
   ctx.playheads.retain(|playhead| playhead.sample_key != key);
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub extern "C" fn start_playback(ctx: *mut SampleEditorCtx, start_beat: f64, bpm: f64) {
-  let ctx = unsafe { &mut *ctx };
+pub extern "C" fn start_playback(
+  ctx: *mut SampleEditorCtx,
+  start_beat: f64,
+  bpm: f64,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "start_playback") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
   ctx.schedule_samples(start_beat, bpm);
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub extern "C" fn stop_playback(ctx: *mut SampleEditorCtx) {
-  let ctx = unsafe { &mut *ctx };
+pub extern "C" fn stop_playback(ctx: *mut SampleEditorCtx) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "stop_playback") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
   ctx.upcoming_samples.clear();
   ctx.playheads.clear();
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -1,10 +1,19 @@
+use common::ffi::{self, ErrorCode, FfiResult};
+use dsp::{
+  crossfade::{crossfade, CrossfadeLaw},
+  time_stretch::{TimeStretchQuality, TimeStretcher},
+};
+
 const MAX_VOICE_COUNT: usize = 8;
 const FRAME_SIZE: usize = 128;
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Playhead {
   pub pos: f32,
   pub playback_speed: f32,
+  /// Set if time stretching was enabled when the playhead was started, in which case `pos` and
+  /// `playback_speed` are unused
+  pub stretcher: Option<Box<TimeStretcher>>,
 }
 
 #[derive(Default)]
@@ -13,9 +22,31 @@ pub struct CrossfadeParams {
   pub threshold: f32,
 }
 
+#[derive(Clone, Copy)]
+pub struct TimeStretchParams {
+  pub enabled: bool,
+  /// Tempo the sample was recorded at.  Stretched samples are played at the ratio of the transport
+  /// tempo to this.
+  pub source_bpm: f32,
+  pub quality: TimeStretchQuality,
+  pub preserve_transients: bool,
+}
+
+impl Default for TimeStretchParams {
+  fn default() -> Self {
+    TimeStretchParams {
+      enabled: false,
+      source_bpm: 120.,
+      quality: TimeStretchQuality::Balanced,
+      preserve_transients: true,
+    }
+  }
+}
+
 pub struct SampleDescriptor {
   pub sample_buffer: Vec<f32>,
   pub crossfade_params: CrossfadeParams,
+  pub time_stretch_params: TimeStretchParams,
   pub crossfaded_sample_buffer: Vec<f32>,
   pub is_gated: bool,
   pub playheads: Vec<Playhead>,
@@ -26,6 +57,7 @@ impl Default for SampleDescriptor {
     SampleDescriptor {
       sample_buffer: Vec::new(),
       crossfade_params: CrossfadeParams::default(),
+      time_stretch_params: TimeStretchParams::default(),
       crossfaded_sample_buffer: Vec::new(),
       is_gated: false,
       playheads: Vec::new(),
@@ -34,8 +66,24 @@ impl Default for SampleDescriptor {
 }
 
 impl SampleDescriptor {
+  fn build_playhead(&self) -> Playhead {
+    let TimeStretchParams {
+      enabled,
+      quality,
+      preserve_transients,
+      ..
+    } = self.time_stretch_params;
+
+    Playhead {
+      pos: 0.,
+      playback_speed: 1.,
+      stretcher: enabled.then(|| Box::new(TimeStretcher::new(quality, preserve_transients))),
+    }
+  }
+
+  /// `bpm` is the current transport tempo, which stretched playheads follow
   #[inline(never)]
-  pub fn get_sample(&mut self) -> f32 {
+  pub fn get_sample(&mut self, bpm: f32) -> f32 {
     let mut sample = 0.;
 
     let sample_buf = if self.crossfade_params.enabled {
@@ -43,22 +91,37 @@ impl SampleDescriptor {
     } else {
       &self.sample_buffer
     };
+    let stretch_speed = bpm / self.time_stretch_params.source_bpm;
 
     let mut i = 0;
     while i < self.playheads.len() {
-      let mut new_playhead = self.playheads[i];
-      new_playhead.pos += new_playhead.playback_speed;
-      if new_playhead.pos > (sample_buf.len() - 2) as f32 {
+      if let Some(stretcher) = &mut self.playheads[i].stretcher {
+        if stretcher.is_finished(sample_buf.len()) {
+          if self.crossfade_params.enabled {
+            stretcher.reset(0.);
+          } else {
+            self.playheads.swap_remove(i);
+            continue;
+          }
+        }
+
+        sample += stretcher.next_sample(sample_buf, stretch_speed);
+        i += 1;
+        continue;
+      }
+
+      let playhead = &mut self.playheads[i];
+      playhead.pos += playhead.playback_speed;
+      if playhead.pos > (sample_buf.len() - 2) as f32 {
         if self.crossfade_params.enabled {
-          new_playhead.pos = new_playhead.pos - (sample_buf.len() - 2) as f32;
+          playhead.pos = playhead.pos - (sample_buf.len() - 2) as f32;
         } else {
           self.playheads.swap_remove(i);
           continue;
         }
       }
 
-      self.playheads[i] = new_playhead;
-      sample += dsp::read_interpolated(sample_buf, new_playhead.pos);
+      sample += dsp::read_interpolated(sample_buf, playhead.pos);
       i += 1;
     }
 
@@ -108,8 +171,11 @@ pub extern "C" fn get_output_buffer_ptr(ctx: *const SamplePlayerCtx) -> *const f
 }
 
 #[no_mangle]
-pub extern "C" fn process_sample_player(ctx: *mut SamplePlayerCtx) {
-  let ctx = unsafe { &mut *ctx };
+pub extern "C" fn process_sample_player(ctx: *mut SamplePlayerCtx, bpm: f32) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "process_sample_player") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
   ctx.output_buffer.fill(0.);
 
   for voice_ix in 0..ctx.voices.len() {
@@ -124,45 +190,64 @@ pub extern "C" fn process_sample_player(ctx: *mut SamplePlayerCtx) {
       let is_gated = gate_inputs[sample_ix] > 0.;
       if is_gated && !voice.is_gated {
         voice.is_gated = true;
-        voice.playheads.push(Playhead {
-          pos: 0.,
-          playback_speed: 1.,
-        });
+        let playhead = voice.build_playhead();
+        voice.playheads.push(playhead);
       } else if !is_gated && voice.is_gated {
         voice.is_gated = false;
       }
 
-      let sample = voice.get_sample();
+      let sample = voice.get_sample(bpm);
       let gain = gain_inputs[sample_ix];
       ctx.output_buffer[sample_ix] += sample * gain;
     }
   }
-}
 
-#[no_mangle]
-pub extern "C" fn add_sample(ctx: *mut SamplePlayerCtx, _gain: f32) {
-  let ctx = unsafe { &mut *ctx };
+  ErrorCode::Ok
+}
 
-  if ctx.voices.len() > MAX_VOICE_COUNT {
-    panic!("Tried to add more samples than the maximum");
+fn check_voice_ix(ctx: &SamplePlayerCtx, voice_ix: usize, action: &str) -> FfiResult {
+  if voice_ix >= ctx.voices.len() {
+    return Err(ffi::set_last_error(
+      ErrorCode::ParamOutOfRange,
+      &format!(
+        "Tried to {action} sample at index={voice_ix} but only {} samples exist",
+        ctx.voices.len()
+      ),
+    ));
   }
-
-  ctx.voices.push(Default::default());
+  Ok(())
 }
 
 #[no_mangle]
-pub extern "C" fn remove_sample(ctx: *mut SamplePlayerCtx, voice_ix: usize) {
-  let ctx = unsafe { &mut *ctx };
-
-  if ctx.voices.get(voice_ix).is_none() {
-    panic!(
-      "Tried to remove sample at index={} but only {} samples exist",
-      voice_ix,
-      ctx.voices.len()
+pub extern "C" fn add_sample(ctx: *mut SamplePlayerCtx, _gain: f32) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "add_sample") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+
+  // The gain and gate input buffers only have room for `MAX_VOICE_COUNT` voices
+  if ctx.voices.len() >= MAX_VOICE_COUNT {
+    return ffi::set_last_error(
+      ErrorCode::ParamOutOfRange,
+      &format!("Tried to add more than the maximum of {MAX_VOICE_COUNT} samples"),
     );
   }
 
-  ctx.voices.remove(voice_ix);
+  ctx.voices.push(Default::default());
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn remove_sample(ctx: *mut SamplePlayerCtx, voice_ix: usize) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "remove_sample") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    check_voice_ix(ctx, voice_ix, "remove")?;
+    ctx.voices.remove(voice_ix);
+    Ok(())
+  })())
 }
 
 fn gen_crossfaded_sample_buffer(sample_buffer: &[f32], threshold: f32) -> Vec<f32> {
@@ -173,16 +258,22 @@ fn gen_crossfaded_sample_buffer(sample_buffer: &[f32], threshold: f32) -> Vec<f3
   for i in 0..sample_buffer.len() {
     let sample = if i < (half_threshold * sample_count) as usize {
       let factor = i as f32 / (half_threshold * sample_count);
-      let base_sample = sample_buffer[i] * factor;
       let other_end_ix = sample_buffer.len() - i - 1;
-      let other_end_sample = sample_buffer[other_end_ix] * (1. - factor);
-      base_sample + other_end_sample
+      crossfade(
+        sample_buffer[other_end_ix],
+        sample_buffer[i],
+        factor,
+        CrossfadeLaw::EqualPower,
+      )
     } else if i > (sample_count - half_threshold * sample_count) as usize {
       let factor = (sample_buffer.len() - i) as f32 / (half_threshold * sample_count);
-      let base_sample = sample_buffer[i] * factor;
       let other_end_ix = sample_buffer.len() - i - 1;
-      let other_end_sample = sample_buffer[other_end_ix] * (1. - factor);
-      base_sample + other_end_sample
+      crossfade(
+        sample_buffer[other_end_ix],
+        sample_buffer[i],
+        factor,
+        CrossfadeLaw::EqualPower,
+      )
     } else {
       sample_buffer[i]
     };
@@ -198,24 +289,60 @@ pub extern "C" fn set_sample_crossfade_params(
   voice_ix: usize,
   enabled: bool,
   threshold: f32,
-) {
-  let ctx = unsafe { &mut *ctx };
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "set_sample_crossfade_params") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    check_voice_ix(ctx, voice_ix, "set crossfade params for")?;
+    ffi::check_range("threshold", threshold, 0., 1.)?;
 
-  if ctx.voices.get(voice_ix).is_none() {
-    panic!(
-      "Tried to set crossfade params for sample at index={} but only {} samples exist",
-      voice_ix,
-      ctx.voices.len()
-    );
-  }
+    let voice = &mut ctx.voices[voice_ix];
+    voice.crossfade_params.enabled = enabled;
+    voice.crossfade_params.threshold = threshold;
 
-  let voice = &mut ctx.voices[voice_ix];
-  voice.crossfade_params.enabled = enabled;
-  voice.crossfade_params.threshold = threshold;
+    if enabled && voice.sample_buffer.len() > 0 {
+      voice.crossfaded_sample_buffer =
+        gen_crossfaded_sample_buffer(&voice.sample_buffer, threshold);
+    }
+    Ok(())
+  })())
+}
 
-  if enabled && voice.sample_buffer.len() > 0 {
-    voice.crossfaded_sample_buffer = gen_crossfaded_sample_buffer(&voice.sample_buffer, threshold);
-  }
+/// Stretching applies to playheads started after this is called, but changes to `source_bpm` take
+/// effect immediately.
+#[no_mangle]
+pub extern "C" fn set_sample_time_stretch_params(
+  ctx: *mut SamplePlayerCtx,
+  voice_ix: usize,
+  enabled: bool,
+  source_bpm: f32,
+  quality: u32,
+  preserve_transients: bool,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "set_sample_time_stretch_params") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    check_voice_ix(ctx, voice_ix, "set time stretch params for")?;
+    let quality = TimeStretchQuality::from_u32(quality).ok_or_else(|| {
+      ffi::set_last_error(
+        ErrorCode::ParamOutOfRange,
+        &format!("Invalid time stretch quality: {quality}"),
+      )
+    })?;
+    ffi::check_range("source_bpm", source_bpm, f32::MIN_POSITIVE, f32::MAX)?;
+
+    ctx.voices[voice_ix].time_stretch_params = TimeStretchParams {
+      enabled,
+      source_bpm,
+      quality,
+      preserve_transients,
+    };
+    Ok(())
+  })())
 }
 
 #[no_mangle]
@@ -239,12 +366,19 @@ pub extern "C" fn get_sample_buf_ptr(
 }
 
 #[no_mangle]
-pub extern "C" fn on_sample_data_set(ctx: *mut SamplePlayerCtx, sample_ix: usize) {
-  let ctx = unsafe { &mut *ctx };
-  let sample = &mut ctx.voices[sample_ix];
-
-  if sample.crossfade_params.enabled {
-    sample.crossfaded_sample_buffer =
-      gen_crossfaded_sample_buffer(&sample.sample_buffer, sample.crossfade_params.threshold);
-  }
+pub extern "C" fn on_sample_data_set(ctx: *mut SamplePlayerCtx, sample_ix: usize) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "on_sample_data_set") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    check_voice_ix(ctx, sample_ix, "set data for")?;
+    let sample = &mut ctx.voices[sample_ix];
+
+    if sample.crossfade_params.enabled {
+      sample.crossfaded_sample_buffer =
+        gen_crossfaded_sample_buffer(&sample.sample_buffer, sample.crossfade_params.threshold);
+    }
+    Ok(())
+  })())
 }
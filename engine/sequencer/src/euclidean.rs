@@ -0,0 +1,59 @@
+//! Euclidean rhythms: `pulses` onsets distributed as evenly as possible over `steps` steps.
+
+/// Returns a pattern of length `steps` with `pulses` onsets spread as evenly as possible, rotated
+/// left by `rotation` steps.  `pulses` is clamped to `steps`.
+pub fn euclidean_pattern(pulses: usize, steps: usize, rotation: usize) -> Vec<bool> {
+  if steps == 0 {
+    return Vec::new();
+  }
+
+  let pulses = pulses.min(steps);
+  (0..steps)
+    .map(|i| ((i + rotation) * pulses) % steps < pulses)
+    .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EuclideanConfig {
+  pub pulses: usize,
+  pub steps: usize,
+  pub rotation: usize,
+  pattern: Vec<bool>,
+}
+
+impl EuclideanConfig {
+  pub fn new(pulses: usize, steps: usize, rotation: usize) -> Self {
+    EuclideanConfig {
+      pulses,
+      steps,
+      rotation,
+      pattern: euclidean_pattern(pulses, steps, rotation),
+    }
+  }
+
+  /// Whether the step at `abs_step_ix` steps since the start of the transport is an onset.  The
+  /// pattern repeats with its own length, independent of the sequencer's step count.
+  pub fn is_onset(&self, abs_step_ix: usize) -> bool {
+    if self.pattern.is_empty() {
+      return false;
+    }
+    self.pattern[abs_step_ix % self.pattern.len()]
+  }
+}
+
+#[test]
+fn euclidean_patterns() {
+  let to_str = |pattern: Vec<bool>| -> String {
+    pattern
+      .into_iter()
+      .map(|onset| if onset { 'x' } else { '.' })
+      .collect()
+  };
+
+  assert_eq!(to_str(euclidean_pattern(3, 8, 0)), "x..x..x.");
+  assert_eq!(to_str(euclidean_pattern(5, 8, 0)), "x.x.xx.x");
+  assert_eq!(to_str(euclidean_pattern(4, 16, 0)), "x...x...x...x...");
+  assert_eq!(to_str(euclidean_pattern(3, 8, 1)), "..x..x.x");
+  assert_eq!(to_str(euclidean_pattern(9, 4, 0)), "xxxx");
+  assert_eq!(to_str(euclidean_pattern(0, 4, 0)), "....");
+}
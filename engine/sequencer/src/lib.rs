@@ -0,0 +1,327 @@
+use common::ffi::{self, ErrorCode};
+
+use crate::{
+  euclidean::EuclideanConfig,
+  sequencer::{
+    Sequencer, SequencerEvent, SequencerEventKind, Step, MAX_EVENTS_PER_TICK, MAX_PARAM_LOCKS,
+    MAX_STEP_COUNT, MAX_TRACK_COUNT,
+  },
+};
+
+pub mod euclidean;
+pub mod sequencer;
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+  fn log_err(ptr: *const u8, len: usize);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+use common::native::log_err;
+
+/// `MIDIEventType` values from `eventScheduler.ts`
+const MIDI_EVENT_TYPE_ATTACK: f32 = 0.;
+const MIDI_EVENT_TYPE_RELEASE: f32 = 1.;
+const MIDI_EVENT_TYPE_GENERIC_CONTROL: f32 = 4.;
+
+/// Each event in the output buffer has the following format:
+///
+/// [0] = `MIDIEventType`
+/// [1] = track index
+/// [2] = note for attack/release events, param index for parameter locks
+/// [3] = velocity for attack events, locked value for parameter locks
+/// [4] = number of beats before the beat passed to `sequencer_tick` at which the event occurs
+const EVENT_SIZE: usize = 5;
+
+pub struct SequencerCtx {
+  pub sequencer: Sequencer,
+  pub events: Vec<f32>,
+}
+
+fn push_event(events: &mut Vec<f32>, cur_beat: f64, evt: SequencerEvent) {
+  let (event_type, param_0, param_1) = match evt.kind {
+    SequencerEventKind::Attack { note, velocity } =>
+      (MIDI_EVENT_TYPE_ATTACK, note as f32, velocity),
+    SequencerEventKind::Release { note } => (MIDI_EVENT_TYPE_RELEASE, note as f32, 0.),
+    SequencerEventKind::ParamLock { param_ix, value } =>
+      (MIDI_EVENT_TYPE_GENERIC_CONTROL, param_ix as f32, value),
+  };
+  events.extend_from_slice(&[
+    event_type,
+    evt.track_ix as f32,
+    param_0,
+    param_1,
+    (cur_beat - evt.beat).max(0.) as f32,
+  ]);
+}
+
+fn check_track(ctx: &SequencerCtx, track_ix: usize) -> ffi::FfiResult {
+  ffi::check_index("track_ix", track_ix, ctx.sequencer.tracks.len())
+}
+
+fn check_position(ctx: &SequencerCtx, track_ix: usize, step_ix: usize) -> ffi::FfiResult {
+  check_track(ctx, track_ix)?;
+  ffi::check_index("step_ix", step_ix, ctx.sequencer.step_count())
+}
+
+#[no_mangle]
+pub extern "C" fn sequencer_create_ctx(track_count: usize, step_count: usize) -> *mut SequencerCtx {
+  common::set_raw_panic_hook(log_err);
+
+  let ctx = SequencerCtx {
+    sequencer: Sequencer::new(
+      track_count.min(MAX_TRACK_COUNT),
+      step_count.min(MAX_STEP_COUNT),
+    ),
+    events: Vec::with_capacity(MAX_EVENTS_PER_TICK * EVENT_SIZE),
+  };
+  Box::into_raw(Box::new(ctx))
+}
+
+#[no_mangle]
+pub extern "C" fn sequencer_resize(
+  ctx: *mut SequencerCtx,
+  track_count: usize,
+  step_count: usize,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "sequencer_resize") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("track_count", track_count, 0, MAX_TRACK_COUNT)?;
+    ffi::check_range("step_count", step_count, 0, MAX_STEP_COUNT)?;
+    ctx.sequencer.resize(track_count, step_count);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn sequencer_set_beats_per_step(
+  ctx: *mut SequencerCtx,
+  beats_per_step: f64,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "sequencer_set_beats_per_step") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("beats_per_step", beats_per_step, 1. / 64., 64.) {
+    return code;
+  }
+
+  ctx.sequencer.beats_per_step = beats_per_step;
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn sequencer_set_track_muted(
+  ctx: *mut SequencerCtx,
+  track_ix: usize,
+  muted: bool,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "sequencer_set_track_muted") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = check_track(ctx, track_ix) {
+    return code;
+  }
+
+  ctx.sequencer.tracks[track_ix].muted = muted;
+  ErrorCode::Ok
+}
+
+/// Sets the value that `param_ix` is restored to on steps that don't lock it.  Pass NaN to stop
+/// restoring it.
+#[no_mangle]
+pub extern "C" fn sequencer_set_param_default(
+  ctx: *mut SequencerCtx,
+  track_ix: usize,
+  param_ix: usize,
+  value: f32,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "sequencer_set_param_default") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    check_track(ctx, track_ix)?;
+    ffi::check_range("param_ix", param_ix, 0, MAX_PARAM_LOCKS - 1)?;
+    ctx.sequencer.tracks[track_ix].param_defaults[param_ix] =
+      if value.is_nan() { None } else { Some(value) };
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn sequencer_set_step(
+  ctx: *mut SequencerCtx,
+  track_ix: usize,
+  step_ix: usize,
+  enabled: bool,
+  note: u8,
+  velocity: f32,
+  gate: f32,
+  probability: f32,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "sequencer_set_step") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    check_position(ctx, track_ix, step_ix)?;
+    ffi::check_range("note", note, 0, 127)?;
+    ffi::check_range("velocity", velocity, 0., 1.)?;
+    ffi::check_range("gate", gate, 0.01, 1.)?;
+    ffi::check_range("probability", probability, 0., 1.)?;
+
+    let step = &mut ctx.sequencer.tracks[track_ix].steps[step_ix];
+    *step = Step {
+      enabled,
+      note,
+      velocity,
+      gate,
+      probability,
+      param_locks: step.param_locks,
+    };
+    Ok(())
+  })())
+}
+
+/// Locks `param_ix` to `value` for the given step.  Pass NaN to clear the lock.
+#[no_mangle]
+pub extern "C" fn sequencer_set_param_lock(
+  ctx: *mut SequencerCtx,
+  track_ix: usize,
+  step_ix: usize,
+  param_ix: usize,
+  value: f32,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "sequencer_set_param_lock") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    check_position(ctx, track_ix, step_ix)?;
+    ffi::check_range("param_ix", param_ix, 0, MAX_PARAM_LOCKS - 1)?;
+    ctx.sequencer.tracks[track_ix].steps[step_ix].param_locks[param_ix] =
+      if value.is_nan() { None } else { Some(value) };
+    Ok(())
+  })())
+}
+
+/// Generates the track's gates from a Euclidean pattern of `pulses` onsets over `steps` steps,
+/// rotated by `rotation`.  The pattern is recomputed every time this is called, so it can be
+/// driven live.  Pass `steps` = 0 to go back to using each step's `enabled` flag.
+#[no_mangle]
+pub extern "C" fn sequencer_set_track_euclidean(
+  ctx: *mut SequencerCtx,
+  track_ix: usize,
+  pulses: usize,
+  steps: usize,
+  rotation: usize,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "sequencer_set_track_euclidean") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    check_track(ctx, track_ix)?;
+    ffi::check_range("steps", steps, 0, MAX_STEP_COUNT)?;
+    ffi::check_range("pulses", pulses, 0, steps)?;
+    ctx.sequencer.tracks[track_ix].euclidean = match steps {
+      0 => None,
+      _ => Some(EuclideanConfig::new(pulses, steps, rotation % steps)),
+    };
+    Ok(())
+  })())
+}
+
+/// Overwrites the `enabled` flag of every step in the track with a Euclidean pattern.
+#[no_mangle]
+pub extern "C" fn sequencer_fill_track_euclidean(
+  ctx: *mut SequencerCtx,
+  track_ix: usize,
+  pulses: usize,
+  steps: usize,
+  rotation: usize,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "sequencer_fill_track_euclidean") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    check_track(ctx, track_ix)?;
+    ffi::check_range("steps", steps, 1, MAX_STEP_COUNT)?;
+    ffi::check_range("pulses", pulses, 0, steps)?;
+    ctx.sequencer.tracks[track_ix].fill_euclidean(pulses, steps, rotation % steps);
+    Ok(())
+  })())
+}
+
+/// Advances the sequencer to `cur_beat`, returning the number of events that were generated.  The
+/// events can be read out of the buffer returned by `sequencer_get_events_ptr`.
+#[no_mangle]
+pub extern "C" fn sequencer_tick(ctx: *mut SequencerCtx, cur_beat: f64) -> usize {
+  let SequencerCtx { sequencer, events } = match unsafe { ffi::handle(ctx, "sequencer_tick") } {
+    Ok(ctx) => ctx,
+    Err(_) => return 0,
+  };
+  events.clear();
+  sequencer.tick(cur_beat, |evt| push_event(events, cur_beat, evt));
+  events.len() / EVENT_SIZE
+}
+
+/// Releases all held notes and resets the sequencer's position.  Returns the number of generated
+/// release events in the same way as `sequencer_tick`.
+#[no_mangle]
+pub extern "C" fn sequencer_stop(ctx: *mut SequencerCtx) -> usize {
+  let SequencerCtx { sequencer, events } = match unsafe { ffi::handle(ctx, "sequencer_stop") } {
+    Ok(ctx) => ctx,
+    Err(_) => return 0,
+  };
+  events.clear();
+  sequencer.stop(|evt| push_event(events, evt.beat, evt));
+  events.len() / EVENT_SIZE
+}
+
+#[no_mangle]
+pub extern "C" fn sequencer_get_events_ptr(ctx: *mut SequencerCtx) -> *const f32 {
+  match unsafe { ffi::handle(ctx, "sequencer_get_events_ptr") } {
+    Ok(ctx) => ctx.events.as_ptr(),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+#[test]
+fn setters_reject_positions_when_empty() {
+  let ctx = sequencer_create_ctx(2, 4);
+  let setters_status = |track_ix: usize, step_ix: usize| {
+    [
+      sequencer_set_track_muted(ctx, track_ix, true),
+      sequencer_set_param_default(ctx, track_ix, 0, 0.5),
+      sequencer_set_track_euclidean(ctx, track_ix, 1, 4, 0),
+      sequencer_fill_track_euclidean(ctx, track_ix, 1, 4, 0),
+      sequencer_set_step(ctx, track_ix, step_ix, true, 60, 1., 0.5, 1.),
+      sequencer_set_param_lock(ctx, track_ix, step_ix, 0, 0.5),
+    ]
+  };
+  assert_eq!(setters_status(1, 3), [ErrorCode::Ok; 6]);
+  assert_eq!(setters_status(1, 4)[4..], [ErrorCode::ParamOutOfRange; 2]);
+
+  // Tracks without any steps can still be configured, but none of their steps can be set
+  assert_eq!(sequencer_resize(ctx, 2, 0), ErrorCode::Ok);
+  assert_eq!(setters_status(0, 0), [
+    ErrorCode::Ok,
+    ErrorCode::Ok,
+    ErrorCode::Ok,
+    ErrorCode::Ok,
+    ErrorCode::ParamOutOfRange,
+    ErrorCode::ParamOutOfRange,
+  ]);
+
+  assert_eq!(sequencer_resize(ctx, 0, 4), ErrorCode::Ok);
+  assert_eq!(setters_status(0, 0), [ErrorCode::ParamOutOfRange; 6]);
+
+  drop(unsafe { Box::from_raw(ctx) });
+}
@@ -0,0 +1,465 @@
+//! N-track x M-step sequencer.  Each step has its own note, velocity, gate length, and trigger
+//! probability along with optional parameter locks which override a track-level parameter for the
+//! duration of that step.
+//!
+//! The sequencer doesn't keep its own clock; its position is derived entirely from the shared
+//! transport's current beat so that it stays in sync with everything else that's playing.
+
+use rand::Rng;
+
+use crate::euclidean::EuclideanConfig;
+
+/// Max number of lockable parameters per track
+pub const MAX_PARAM_LOCKS: usize = 8;
+pub const MAX_TRACK_COUNT: usize = 64;
+pub const MAX_STEP_COUNT: usize = 256;
+/// Steps fired in a single tick are capped so that the event buffers never have to grow on the
+/// audio thread.  A tick normally covers a small fraction of a step, so this only kicks in for very
+/// short steps after a hitch.
+pub const MAX_STEPS_PER_TICK: usize = 4;
+/// Most events that a single tick can generate: every track firing `MAX_STEPS_PER_TICK` steps with
+/// all params locked along with their releases, plus the release of a note held from the last tick
+pub const MAX_EVENTS_PER_TICK: usize =
+  MAX_TRACK_COUNT * (MAX_STEPS_PER_TICK * (MAX_PARAM_LOCKS + 2) + 1);
+/// The transport advancing further than this between ticks is treated as a seek.  Ticks happen
+/// every frame, which covers a small fraction of a beat at any usable tempo.
+const SEEK_THRESHOLD_BEATS: f64 = 2.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SequencerEventKind {
+  Attack { note: u8, velocity: f32 },
+  Release { note: u8 },
+  ParamLock { param_ix: usize, value: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SequencerEvent {
+  pub beat: f64,
+  pub track_ix: usize,
+  pub kind: SequencerEventKind,
+}
+
+impl SequencerEvent {
+  fn kind_order(&self) -> u8 {
+    match self.kind {
+      SequencerEventKind::Release { .. } => 0,
+      SequencerEventKind::ParamLock { .. } => 1,
+      SequencerEventKind::Attack { .. } => 2,
+    }
+  }
+
+  /// Events at the same beat are ordered releases first so that re-triggering the same note on
+  /// consecutive steps doesn't leave consumers in a bad state.
+  fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    self
+      .beat
+      .total_cmp(&other.beat)
+      .then(self.kind_order().cmp(&other.kind_order()))
+      .then(self.track_ix.cmp(&other.track_ix))
+  }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Step {
+  pub enabled: bool,
+  pub note: u8,
+  pub velocity: f32,
+  /// Fraction of the step length that the note is held for, in (0, 1]
+  pub gate: f32,
+  /// Probability that the step fires each time it's reached, in [0, 1]
+  pub probability: f32,
+  pub param_locks: [Option<f32>; MAX_PARAM_LOCKS],
+}
+
+impl Default for Step {
+  fn default() -> Self {
+    Step {
+      enabled: false,
+      note: 60,
+      velocity: 1.,
+      gate: 0.5,
+      probability: 1.,
+      param_locks: [None; MAX_PARAM_LOCKS],
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct Track {
+  pub steps: Vec<Step>,
+  pub muted: bool,
+  /// Value that each parameter falls back to on steps that don't lock it.  Parameters that have
+  /// never been locked aren't touched.
+  pub param_defaults: [Option<f32>; MAX_PARAM_LOCKS],
+  /// If set, the track's gates are generated by this Euclidean pattern rather than each step's
+  /// `enabled` flag.  Notes, velocities, etc. are still taken from the steps.
+  pub euclidean: Option<EuclideanConfig>,
+  /// Parameters that were locked by the most recently fired step and need to be restored
+  locked_params: [bool; MAX_PARAM_LOCKS],
+}
+
+impl Track {
+  fn new(step_count: usize) -> Self {
+    Track {
+      steps: vec![Step::default(); step_count],
+      muted: false,
+      param_defaults: [None; MAX_PARAM_LOCKS],
+      euclidean: None,
+      locked_params: [false; MAX_PARAM_LOCKS],
+    }
+  }
+
+  /// Overwrites the `enabled` flag of every step with a Euclidean pattern.  The pattern is repeated
+  /// if it's shorter than the track.
+  pub fn fill_euclidean(&mut self, pulses: usize, steps: usize, rotation: usize) {
+    let config = EuclideanConfig::new(pulses, steps, rotation);
+    for (step_ix, step) in self.steps.iter_mut().enumerate() {
+      step.enabled = config.is_onset(step_ix);
+    }
+  }
+}
+
+struct PendingRelease {
+  pub beat: f64,
+  pub track_ix: usize,
+  pub note: u8,
+}
+
+pub struct Sequencer {
+  pub tracks: Vec<Track>,
+  step_count: usize,
+  /// Length of a single step in beats
+  pub beats_per_step: f64,
+  /// Beat up to which steps have been processed.  `None` if the transport isn't running.
+  last_beat: Option<f64>,
+  pending_releases: Vec<PendingRelease>,
+  /// Reused between ticks to avoid allocating on the audio thread
+  events: Vec<SequencerEvent>,
+}
+
+impl Sequencer {
+  pub fn new(track_count: usize, step_count: usize) -> Self {
+    Sequencer {
+      tracks: vec![Track::new(step_count); track_count],
+      step_count,
+      beats_per_step: 0.25,
+      last_beat: None,
+      pending_releases: Vec::with_capacity(MAX_TRACK_COUNT * (MAX_STEPS_PER_TICK + 1)),
+      events: Vec::with_capacity(MAX_EVENTS_PER_TICK),
+    }
+  }
+
+  pub fn step_count(&self) -> usize { self.step_count }
+
+  /// Changes the dimensions of the sequencer, preserving existing steps where possible.  Pending
+  /// releases of removed tracks are dropped along with them.
+  pub fn resize(&mut self, track_count: usize, step_count: usize) {
+    self
+      .pending_releases
+      .retain(|release| release.track_ix < track_count);
+    self.tracks.resize(track_count, Track::new(step_count));
+    for track in &mut self.tracks {
+      track.steps.resize(step_count, Step::default());
+    }
+    self.step_count = step_count;
+  }
+
+  /// Releases every held note and resets the sequencer's position.  Steps are kept.
+  pub fn stop(&mut self, mut emit: impl FnMut(SequencerEvent)) {
+    for release in self.pending_releases.drain(..) {
+      emit(SequencerEvent {
+        beat: release.beat,
+        track_ix: release.track_ix,
+        kind: SequencerEventKind::Release { note: release.note },
+      });
+    }
+    self.last_beat = None;
+  }
+
+  fn fire_step(
+    &mut self,
+    track_ix: usize,
+    abs_step_ix: usize,
+    step_beat: f64,
+    events: &mut Vec<SequencerEvent>,
+  ) {
+    let beats_per_step = self.beats_per_step;
+    let track = &mut self.tracks[track_ix];
+    let step = track.steps[abs_step_ix % self.step_count];
+    let gate = match &track.euclidean {
+      Some(euclidean) => euclidean.is_onset(abs_step_ix),
+      None => step.enabled,
+    };
+    if track.muted || !gate {
+      return;
+    }
+    if step.probability < 1. && common::rng().gen::<f32>() >= step.probability {
+      return;
+    }
+
+    for (param_ix, lock) in step.param_locks.iter().enumerate() {
+      let value = match (lock, track.locked_params[param_ix]) {
+        (Some(value), _) => Some(*value),
+        // Restore the default for params locked by the previous step
+        (None, true) => track.param_defaults[param_ix],
+        (None, false) => None,
+      };
+      track.locked_params[param_ix] = lock.is_some();
+
+      if let Some(value) = value {
+        events.push(SequencerEvent {
+          beat: step_beat,
+          track_ix,
+          kind: SequencerEventKind::ParamLock { param_ix, value },
+        });
+      }
+    }
+
+    events.push(SequencerEvent {
+      beat: step_beat,
+      track_ix,
+      kind: SequencerEventKind::Attack {
+        note: step.note,
+        velocity: step.velocity,
+      },
+    });
+    self.pending_releases.push(PendingRelease {
+      beat: step_beat + beats_per_step * step.gate.clamp(0.01, 1.) as f64,
+      track_ix,
+      note: step.note,
+    });
+  }
+
+  /// Generates all events that occur in `(last_beat, cur_beat]`, where `last_beat` is the beat
+  /// passed to the previous call, and calls `emit` with each of them in order.
+  pub fn tick(&mut self, cur_beat: f64, mut emit: impl FnMut(SequencerEvent)) {
+    if self.step_count == 0 || self.beats_per_step <= 0. {
+      return;
+    }
+
+    let last_beat = match self.last_beat {
+      // The transport was restarted or seeked.  Playback picks up from the new position without
+      // firing the steps that were skipped over.
+      Some(last_beat) if last_beat > cur_beat || cur_beat - last_beat > SEEK_THRESHOLD_BEATS => {
+        self.stop(&mut emit);
+        None
+      },
+      last_beat => last_beat,
+    };
+
+    let mut events = std::mem::take(&mut self.events);
+    let first_step = match last_beat {
+      Some(last_beat) => (last_beat / self.beats_per_step).floor() as i64 + 1,
+      None => (cur_beat / self.beats_per_step).ceil() as i64,
+    };
+    let last_step = (cur_beat / self.beats_per_step).floor() as i64;
+    // Only the most recent steps are fired if more than `MAX_STEPS_PER_TICK` have elapsed
+    let first_step = first_step
+      .max(last_step - MAX_STEPS_PER_TICK as i64 + 1)
+      .max(0);
+    for abs_step_ix in first_step..=last_step {
+      let step_beat = abs_step_ix as f64 * self.beats_per_step;
+      for track_ix in 0..self.tracks.len() {
+        self.fire_step(track_ix, abs_step_ix as usize, step_beat, &mut events);
+      }
+    }
+
+    self.pending_releases.retain(|release| {
+      if release.beat > cur_beat {
+        return true;
+      }
+      events.push(SequencerEvent {
+        beat: release.beat,
+        track_ix: release.track_ix,
+        kind: SequencerEventKind::Release { note: release.note },
+      });
+      false
+    });
+
+    events.sort_unstable_by(SequencerEvent::cmp);
+    for evt in events.drain(..) {
+      emit(evt);
+    }
+    self.events = events;
+    self.last_beat = Some(cur_beat);
+  }
+}
+
+#[cfg(test)]
+fn collect_events(seq: &mut Sequencer, beats: &[f64]) -> Vec<SequencerEvent> {
+  let mut events = Vec::new();
+  for &beat in beats {
+    seq.tick(beat, |evt| events.push(evt));
+  }
+  events
+}
+
+#[test]
+fn sequencer_steps_and_gates() {
+  let mut seq = Sequencer::new(1, 4);
+  seq.beats_per_step = 1.;
+  seq.tracks[0].steps[0] = Step {
+    enabled: true,
+    note: 60,
+    gate: 1.,
+    ..Default::default()
+  };
+  seq.tracks[0].steps[2] = Step {
+    enabled: true,
+    note: 64,
+    probability: 0.,
+    ..Default::default()
+  };
+  seq.tracks[0].steps[3] = Step {
+    enabled: true,
+    note: 67,
+    gate: 0.5,
+    ..Default::default()
+  };
+
+  let events = collect_events(&mut seq, &[0., 1.5, 3.25, 4.]);
+  let kinds: Vec<_> = events.iter().map(|evt| (evt.beat, evt.kind)).collect();
+  assert_eq!(kinds, vec![
+    (0., SequencerEventKind::Attack {
+      note: 60,
+      velocity: 1.
+    }),
+    (1., SequencerEventKind::Release { note: 60 }),
+    (3., SequencerEventKind::Attack {
+      note: 67,
+      velocity: 1.
+    }),
+    (3.5, SequencerEventKind::Release { note: 67 }),
+    (4., SequencerEventKind::Attack {
+      note: 60,
+      velocity: 1.
+    }),
+  ]);
+
+  let mut released = Vec::new();
+  seq.stop(|evt| released.push(evt.kind));
+  assert_eq!(released, vec![SequencerEventKind::Release { note: 60 }]);
+}
+
+#[test]
+fn sequencer_euclidean_gates() {
+  let mut seq = Sequencer::new(1, 4);
+  seq.beats_per_step = 1.;
+  seq.tracks[0].steps[1].note = 62;
+  // 3 pulses over 5 steps repeats independently of the 4-step track
+  seq.tracks[0].euclidean = Some(EuclideanConfig::new(3, 5, 0));
+
+  let beats: Vec<f64> = (0..10).map(|beat| beat as f64).collect();
+  let attacks: Vec<_> = collect_events(&mut seq, &beats)
+    .into_iter()
+    .filter_map(|evt| match evt.kind {
+      SequencerEventKind::Attack { note, .. } => Some((evt.beat, note)),
+      _ => None,
+    })
+    .collect();
+  assert_eq!(attacks, vec![
+    (0., 60),
+    (2., 60),
+    (4., 60),
+    (5., 62),
+    (7., 60),
+    (9., 62)
+  ]);
+
+  seq.tracks[0].fill_euclidean(1, 2, 1);
+  let enabled: Vec<_> = seq.tracks[0]
+    .steps
+    .iter()
+    .map(|step| step.enabled)
+    .collect();
+  assert_eq!(enabled, vec![false, true, false, true]);
+}
+
+#[test]
+fn sequencer_param_locks() {
+  let mut seq = Sequencer::new(1, 2);
+  seq.beats_per_step = 1.;
+  seq.tracks[0].param_defaults[1] = Some(0.25);
+  seq.tracks[0].steps[0].enabled = true;
+  seq.tracks[0].steps[0].param_locks[1] = Some(0.75);
+  seq.tracks[0].steps[1].enabled = true;
+
+  let locks: Vec<_> = collect_events(&mut seq, &[0., 1.])
+    .into_iter()
+    .filter_map(|evt| match evt.kind {
+      SequencerEventKind::ParamLock { param_ix, value } => Some((evt.beat, param_ix, value)),
+      _ => None,
+    })
+    .collect();
+  assert_eq!(locks, vec![(0., 1, 0.75), (1., 1, 0.25)]);
+}
+
+#[test]
+fn sequencer_forward_seek_skips_steps() {
+  let mut seq = Sequencer::new(1, 4);
+  seq.beats_per_step = 1.;
+  for step in &mut seq.tracks[0].steps {
+    *step = Step {
+      enabled: true,
+      gate: 1.,
+      ..Default::default()
+    };
+  }
+
+  let events = collect_events(&mut seq, &[0., 0.5, 10.]);
+  let kinds: Vec<_> = events.iter().map(|evt| (evt.beat, evt.kind)).collect();
+  assert_eq!(kinds, vec![
+    (0., SequencerEventKind::Attack {
+      note: 60,
+      velocity: 1.
+    }),
+    (1., SequencerEventKind::Release { note: 60 }),
+    (10., SequencerEventKind::Attack {
+      note: 60,
+      velocity: 1.
+    }),
+  ]);
+}
+
+#[test]
+fn sequencer_resize_drops_removed_track_releases() {
+  let mut seq = Sequencer::new(2, 1);
+  seq.beats_per_step = 1.;
+  for track in &mut seq.tracks {
+    track.steps[0].enabled = true;
+  }
+  collect_events(&mut seq, &[0.]);
+
+  seq.resize(1, 1);
+  let mut released = Vec::new();
+  seq.stop(|evt| released.push(evt.track_ix));
+  assert_eq!(released, vec![0]);
+}
+
+#[test]
+fn sequencer_caps_steps_per_tick() {
+  let mut seq = Sequencer::new(1, 4);
+  seq.beats_per_step = 1. / 64.;
+  for step in &mut seq.tracks[0].steps {
+    *step = Step {
+      enabled: true,
+      note: 60,
+      gate: 1.,
+      ..Default::default()
+    };
+  }
+
+  // A whole beat is 64 steps but is well within the seek threshold
+  let attack_beats: Vec<_> = collect_events(&mut seq, &[0., 1.])
+    .into_iter()
+    .filter(|evt| matches!(evt.kind, SequencerEventKind::Attack { .. }))
+    .map(|evt| evt.beat)
+    .collect();
+  assert_eq!(attack_beats, vec![
+    0.,
+    1. - 3. / 64.,
+    1. - 2. / 64.,
+    1. - 1. / 64.,
+    1.
+  ]);
+}
@@ -1,5 +1,7 @@
 use std::collections::VecDeque;
 
+use common::ffi::{self, ErrorCode};
+
 struct BufEntry {
   pub i: u32, /* This will eventually overflow and wrap, but only after 1 day straight at
                * 44100 samples/second lol */
@@ -13,8 +15,10 @@ static mut LOWPASS_COEFFICIENT: f32 = 0.5;
 static mut WINDOW_SIZE_SAMPLES: u32 = 800;
 static mut RANGE_MULTIPLIER: f32 = -1.;
 
+/// Windows larger than this could underflow the sliding min/max computation
+const MAX_WINDOW_SIZE_SAMPLES: u32 = 100_000;
 // Start it a bit higher to avoid potential underflows in sliding min/max computation
-static mut CUR_SAMPLE_IX: u32 = 100_000;
+static mut CUR_SAMPLE_IX: u32 = MAX_WINDOW_SIZE_SAMPLES;
 static mut RANGE_ACC: f32 = 0.;
 
 #[no_mangle]
@@ -28,7 +32,14 @@ pub unsafe extern "C" fn init(frame_size: usize) -> *mut f32 {
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn process() {
+pub unsafe extern "C" fn process() -> ErrorCode {
+  if INPUT_BUFFER.is_null() {
+    return ffi::set_last_error(
+      ErrorCode::NotInitialized,
+      "sidechain `process` called before `init`",
+    );
+  }
+
   for i in 0..(*INPUT_BUFFER).len() {
     let sample = (*INPUT_BUFFER)[i];
 
@@ -78,19 +89,40 @@ pub unsafe extern "C" fn process() {
 
     CUR_SAMPLE_IX += 1;
   }
+
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn set_window_size_samples(window_size_samples: u32) {
-  WINDOW_SIZE_SAMPLES = window_size_samples;
+pub unsafe extern "C" fn set_window_size_samples(window_size_samples: u32) -> ErrorCode {
+  ffi::status((|| {
+    ffi::check_range(
+      "window_size_samples",
+      window_size_samples,
+      1,
+      MAX_WINDOW_SIZE_SAMPLES,
+    )?;
+    WINDOW_SIZE_SAMPLES = window_size_samples;
+    Ok(())
+  })())
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn set_lowpass_coefficient(lowpass_coefficient: f32) {
-  LOWPASS_COEFFICIENT = lowpass_coefficient;
+pub unsafe extern "C" fn set_lowpass_coefficient(lowpass_coefficient: f32) -> ErrorCode {
+  ffi::status((|| {
+    ffi::check_range("lowpass_coefficient", lowpass_coefficient, 0., 1.)?;
+    LOWPASS_COEFFICIENT = lowpass_coefficient;
+    Ok(())
+  })())
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn set_range_multiplier(range_multiplier: f32) {
-  RANGE_MULTIPLIER = range_multiplier;
+pub unsafe extern "C" fn set_range_multiplier(range_multiplier: f32) -> ErrorCode {
+  match ffi::clamp_param("range_multiplier", range_multiplier, f32::MIN, f32::MAX) {
+    Ok(range_multiplier) => {
+      RANGE_MULTIPLIER = range_multiplier;
+      ErrorCode::Ok
+    },
+    Err(code) => code,
+  }
 }
@@ -0,0 +1,573 @@
+//! Chops a sample or recorded loop into slices at its transients and plays the slices back in
+//! response to MIDI notes.
+//!
+//! Slice `i` is mapped to note `base_note + i`, so slices can be played from a keyboard or
+//! re-sequenced by pointing step sequencer steps at those notes.  Slice boundaries start out at the
+//! detected onsets and can then be moved, added, or removed from the UI.
+//!
+//! The source is mono; recordings exported from the recorder should be mixed down or have a
+//! single channel copied in.
+
+use common::ffi::{self, ErrorCode};
+use dsp::{
+  onset::{detect_onsets, OnsetDetectionParams},
+  MAX_FRAME_SIZE,
+};
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+  fn log_err(ptr: *const u8, len: usize);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+use common::native::log_err;
+
+/// One slice per MIDI note
+pub const MAX_SLICE_COUNT: usize = 128;
+pub const MAX_VOICE_COUNT: usize = 16;
+/// Length of the fades applied at slice boundaries and when voices are released or choked
+const DECLICK_SAMPLES: usize = 64;
+
+struct SliceVoice {
+  note: u8,
+  pos: usize,
+  start: usize,
+  end: usize,
+  gain: f32,
+  /// Samples left before the voice is silent once it's been released or choked
+  release_remaining: Option<usize>,
+}
+
+impl SliceVoice {
+  fn release(&mut self) {
+    if self.release_remaining.is_none() {
+      self.release_remaining = Some(DECLICK_SAMPLES);
+    }
+  }
+
+  fn is_done(&self) -> bool { self.pos >= self.end || self.release_remaining == Some(0) }
+
+  fn next_sample(&mut self, source: &[f32]) -> f32 {
+    let fade_in = ((self.pos - self.start) as f32 / DECLICK_SAMPLES as f32).min(1.);
+    let fade_out = ((self.end - self.pos) as f32 / DECLICK_SAMPLES as f32).min(1.);
+    let release = match &mut self.release_remaining {
+      Some(remaining) => {
+        *remaining -= 1;
+        *remaining as f32 / DECLICK_SAMPLES as f32
+      },
+      None => 1.,
+    };
+
+    let sample = source.get(self.pos).copied().unwrap_or(0.);
+    self.pos += 1;
+    sample * self.gain * fade_in * fade_out * release
+  }
+}
+
+pub struct SlicerCtx {
+  sample_buffer: Vec<f32>,
+  /// Start of each slice in ascending order.  Each slice runs until the start of the next one, and
+  /// the last runs until the end of the sample.
+  slice_starts: Vec<u32>,
+  /// Step sequencer step for each slice populated by `map_slices_to_steps`
+  slice_steps: Vec<u32>,
+  pub base_note: u8,
+  /// If set, triggering a slice cuts off any slices that are already playing
+  pub choke: bool,
+  /// If set, slices stop when their note is released.  Otherwise, they play through to the end.
+  pub gated: bool,
+  voices: Vec<SliceVoice>,
+  pub output_buffer: [f32; MAX_FRAME_SIZE],
+}
+
+impl Default for SlicerCtx {
+  fn default() -> Self {
+    SlicerCtx {
+      sample_buffer: Vec::new(),
+      slice_starts: vec![0],
+      slice_steps: Vec::new(),
+      base_note: 36,
+      choke: true,
+      gated: false,
+      voices: Vec::with_capacity(MAX_VOICE_COUNT),
+      output_buffer: [0.; MAX_FRAME_SIZE],
+    }
+  }
+}
+
+impl SlicerCtx {
+  /// Resizes the sample buffer to `len` samples for the caller to write into.  Playing slices are
+  /// stopped and the slices are reset to a single slice covering the whole sample.
+  pub fn set_sample_len(&mut self, len: usize) -> &mut [f32] {
+    self.voices.clear();
+    self.slice_starts = vec![0];
+    self.sample_buffer.clear();
+    self.sample_buffer.resize(len, 0.);
+    &mut self.sample_buffer
+  }
+
+  pub fn slice_starts(&self) -> &[u32] { &self.slice_starts }
+
+  pub fn slice_range(&self, slice_ix: usize) -> Option<(usize, usize)> {
+    let start = *self.slice_starts.get(slice_ix)? as usize;
+    let end = self
+      .slice_starts
+      .get(slice_ix + 1)
+      .map_or(self.sample_buffer.len(), |&end| end as usize);
+    Some((start, end))
+  }
+
+  /// Replaces the slices with ones starting at each onset detected in the sample.  The first slice
+  /// always starts at the beginning of the sample.  Returns the number of slices.
+  pub fn detect_slices(&mut self, params: OnsetDetectionParams) -> usize {
+    let onsets = detect_onsets(&self.sample_buffer, params);
+    self.slice_starts = std::iter::once(0)
+      .chain(
+        onsets
+          .into_iter()
+          .filter(|&onset| onset >= params.min_gap_samples.max(1)),
+      )
+      .take(MAX_SLICE_COUNT)
+      .map(|start| start as u32)
+      .collect();
+    self.slice_starts.len()
+  }
+
+  /// Returns the range that the start of slice `slice_ix` can be moved within without reordering
+  /// the slices
+  pub fn slice_start_bounds(&self, slice_ix: usize) -> Option<(usize, usize)> {
+    if slice_ix >= self.slice_starts.len() {
+      return None;
+    }
+
+    let min = match slice_ix {
+      0 => 0,
+      _ => self.slice_starts[slice_ix - 1] as usize + 1,
+    };
+    let max = self
+      .slice_starts
+      .get(slice_ix + 1)
+      .map_or(self.sample_buffer.len(), |&next| next as usize)
+      .saturating_sub(1);
+    Some((min, max.max(min)))
+  }
+
+  /// Moves the start of a slice.  The caller must check that `pos` is within
+  /// `slice_start_bounds`.
+  pub fn set_slice_start(&mut self, slice_ix: usize, pos: usize) {
+    self.slice_starts[slice_ix] = pos as u32;
+  }
+
+  /// Splits the slice containing `pos`, returning the index of the new slice or `None` if a slice
+  /// already starts there or the maximum number of slices has been reached
+  pub fn add_slice(&mut self, pos: usize) -> Option<usize> {
+    if self.slice_starts.len() >= MAX_SLICE_COUNT {
+      return None;
+    }
+
+    match self.slice_starts.binary_search(&(pos as u32)) {
+      Ok(_) => None,
+      Err(slice_ix) => {
+        self.slice_starts.insert(slice_ix, pos as u32);
+        Some(slice_ix)
+      },
+    }
+  }
+
+  /// Merges a slice into the one before it.  The first slice can't be removed.
+  pub fn remove_slice(&mut self, slice_ix: usize) -> bool {
+    if slice_ix == 0 || slice_ix >= self.slice_starts.len() {
+      return false;
+    }
+
+    self.slice_starts.remove(slice_ix);
+    true
+  }
+
+  pub fn note_to_slice(&self, note: u8) -> Option<usize> {
+    let slice_ix = note.checked_sub(self.base_note)? as usize;
+    (slice_ix < self.slice_starts.len()).then_some(slice_ix)
+  }
+
+  /// Maps each slice to the step of a `step_count`-step pattern spanning the whole sample that's
+  /// nearest its start, which recreates the original loop when each step triggers its slice
+  pub fn map_slices_to_steps(&mut self, step_count: usize) -> &[u32] {
+    let sample_len = self.sample_buffer.len().max(1) as f64;
+    self.slice_steps = self
+      .slice_starts
+      .iter()
+      .map(|&start| {
+        let step = (start as f64 / sample_len * step_count as f64).round() as usize;
+        step.min(step_count.saturating_sub(1)) as u32
+      })
+      .collect();
+    &self.slice_steps
+  }
+
+  /// Starts playing the slice mapped to `note`.  Notes that aren't mapped to a slice are ignored.
+  pub fn note_on(&mut self, note: u8, velocity: f32) {
+    let Some((start, end)) = self.note_to_slice(note).and_then(|ix| self.slice_range(ix)) else {
+      return;
+    };
+
+    if self.choke {
+      self.voices.iter_mut().for_each(SliceVoice::release);
+    }
+    if self.voices.len() >= MAX_VOICE_COUNT {
+      self.voices.remove(0);
+    }
+    self.voices.push(SliceVoice {
+      note,
+      pos: start,
+      start,
+      end: end.min(self.sample_buffer.len()),
+      gain: velocity,
+      release_remaining: None,
+    });
+  }
+
+  pub fn note_off(&mut self, note: u8) {
+    if !self.gated {
+      return;
+    }
+
+    self
+      .voices
+      .iter_mut()
+      .filter(|voice| voice.note == note)
+      .for_each(SliceVoice::release);
+  }
+
+  pub fn process(&mut self, frame_size: usize) {
+    let output = &mut self.output_buffer[..frame_size];
+    output.fill(0.);
+
+    for voice in &mut self.voices {
+      for out in output.iter_mut() {
+        if voice.is_done() {
+          break;
+        }
+        *out += voice.next_sample(&self.sample_buffer);
+      }
+    }
+    self.voices.retain(|voice| !voice.is_done());
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn slicer_create_ctx() -> *mut SlicerCtx {
+  common::set_raw_panic_hook(log_err);
+
+  Box::into_raw(Box::default())
+}
+
+#[no_mangle]
+pub extern "C" fn slicer_free_ctx(ctx: *mut SlicerCtx) -> ErrorCode {
+  if ctx.is_null() {
+    return ffi::set_last_error(
+      ErrorCode::InvalidHandle,
+      "slicer_free_ctx: null context pointer",
+    );
+  }
+
+  drop(unsafe { Box::from_raw(ctx) });
+  ErrorCode::Ok
+}
+
+/// Resizes the sample buffer and returns a pointer to it for the sample to be written into.  This
+/// resets the slices, so `slicer_detect_slices` should be called once the sample has been written.
+#[no_mangle]
+pub extern "C" fn slicer_get_sample_buf_ptr(ctx: *mut SlicerCtx, len_samples: usize) -> *mut f32 {
+  match unsafe { ffi::handle(ctx, "slicer_get_sample_buf_ptr") } {
+    Ok(ctx) => ctx.set_sample_len(len_samples).as_mut_ptr(),
+    Err(_) => std::ptr::null_mut(),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn slicer_get_output_buf_ptr(ctx: *mut SlicerCtx) -> *const f32 {
+  match unsafe { ffi::handle(ctx, "slicer_get_output_buf_ptr") } {
+    Ok(ctx) => ctx.output_buffer.as_ptr(),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+/// Replaces the slices with ones detected from the sample's transients.  `sensitivity` is in
+/// [0, 1], and slices shorter than `min_slice_ms` are merged into the slice before them.
+#[no_mangle]
+pub extern "C" fn slicer_detect_slices(
+  ctx: *mut SlicerCtx,
+  sensitivity: f32,
+  min_slice_ms: f32,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "slicer_detect_slices") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    let sensitivity = ffi::clamp_param("sensitivity", sensitivity, 0., 1.)?;
+    ffi::check_range("min_slice_ms", min_slice_ms, 0., 10_000.)?;
+
+    ctx.detect_slices(OnsetDetectionParams {
+      sensitivity,
+      min_gap_samples: dsp::ms_to_samples(min_slice_ms) as usize,
+    });
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn slicer_get_slice_count(ctx: *mut SlicerCtx) -> usize {
+  match unsafe { ffi::handle(ctx, "slicer_get_slice_count") } {
+    Ok(ctx) => ctx.slice_starts().len(),
+    Err(_) => 0,
+  }
+}
+
+/// Returns a pointer to the start of each slice in samples as a `u32`.  It's invalidated by any
+/// call that changes the slices.
+#[no_mangle]
+pub extern "C" fn slicer_get_slice_starts_ptr(ctx: *mut SlicerCtx) -> *const u32 {
+  match unsafe { ffi::handle(ctx, "slicer_get_slice_starts_ptr") } {
+    Ok(ctx) => ctx.slice_starts().as_ptr(),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+/// Moves the start of a slice.  Slices can't be moved past their neighbors.
+#[no_mangle]
+pub extern "C" fn slicer_set_slice_start(
+  ctx: *mut SlicerCtx,
+  slice_ix: usize,
+  pos: usize,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "slicer_set_slice_start") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("slice_ix", slice_ix, 0, ctx.slice_starts().len() - 1)?;
+    let (min, max) = ctx.slice_start_bounds(slice_ix).unwrap();
+    ffi::check_range("pos", pos, min, max)?;
+
+    ctx.set_slice_start(slice_ix, pos);
+    Ok(())
+  })())
+}
+
+/// Splits the slice containing `pos` in two
+#[no_mangle]
+pub extern "C" fn slicer_add_slice(ctx: *mut SlicerCtx, pos: usize) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "slicer_add_slice") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("pos", pos, 0, ctx.sample_buffer.len().saturating_sub(1))?;
+
+    match ctx.add_slice(pos) {
+      Some(_) => Ok(()),
+      None => Err(ffi::set_last_error(
+        ErrorCode::Unsupported,
+        &format!(
+          "can't add a slice at {pos}; either one already starts there or the maximum of \
+           {MAX_SLICE_COUNT} slices has been reached"
+        ),
+      )),
+    }
+  })())
+}
+
+/// Merges a slice into the one before it
+#[no_mangle]
+pub extern "C" fn slicer_remove_slice(ctx: *mut SlicerCtx, slice_ix: usize) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "slicer_remove_slice") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("slice_ix", slice_ix, 1, ctx.slice_starts().len() - 1)?;
+
+    ctx.remove_slice(slice_ix);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn slicer_set_base_note(ctx: *mut SlicerCtx, base_note: u32) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "slicer_set_base_note") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("base_note", base_note, 0, 127)?;
+
+    ctx.base_note = base_note as u8;
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn slicer_set_playback_mode(
+  ctx: *mut SlicerCtx,
+  choke: bool,
+  gated: bool,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "slicer_set_playback_mode") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ctx.choke = choke;
+  ctx.gated = gated;
+  ErrorCode::Ok
+}
+
+/// Maps each slice to a step of a `step_count`-step pattern spanning the sample.  Call
+/// `slicer_get_slice_steps_ptr` to read the step for each slice as a `u32`.
+#[no_mangle]
+pub extern "C" fn slicer_map_slices_to_steps(ctx: *mut SlicerCtx, step_count: usize) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "slicer_map_slices_to_steps") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("step_count", step_count, 1, 256)?;
+
+    ctx.map_slices_to_steps(step_count);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn slicer_get_slice_steps_ptr(ctx: *mut SlicerCtx) -> *const u32 {
+  match unsafe { ffi::handle(ctx, "slicer_get_slice_steps_ptr") } {
+    Ok(ctx) => ctx.slice_steps.as_ptr(),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn slicer_note_on(ctx: *mut SlicerCtx, note: u32, velocity: f32) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "slicer_note_on") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("note", note, 0, 127)?;
+    let velocity = ffi::clamp_param("velocity", velocity, 0., 1.)?;
+
+    ctx.note_on(note as u8, velocity);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn slicer_note_off(ctx: *mut SlicerCtx, note: u32) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "slicer_note_off") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("note", note, 0, 127)?;
+
+    ctx.note_off(note as u8);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub extern "C" fn slicer_process(ctx: *mut SlicerCtx, frame_size: usize) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "slicer_process") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range("frame_size", frame_size, 1, MAX_FRAME_SIZE)?;
+
+    ctx.process(frame_size);
+    Ok(())
+  })())
+}
+
+#[cfg(test)]
+fn load_hits(ctx: &mut SlicerCtx, hit_positions: &[usize], len: usize) {
+  let buf = ctx.set_sample_len(len);
+  for &start in hit_positions {
+    for (i, sample) in buf[start..].iter_mut().take(2000).enumerate() {
+      *sample = (i as f32 * 0.05).sin() * (-(i as f32) / 400.).exp();
+    }
+  }
+}
+
+#[test]
+fn slices_are_detected_and_mapped_to_notes_and_steps() {
+  let len = 48_000;
+  let hit_positions = [0, 12_000, 24_000, 36_000];
+  let mut ctx = SlicerCtx::default();
+  load_hits(&mut ctx, &hit_positions, len);
+
+  ctx.detect_slices(OnsetDetectionParams {
+    sensitivity: 0.5,
+    min_gap_samples: 2_400,
+  });
+  assert_eq!(ctx.slice_starts().len(), hit_positions.len());
+  for (&start, &expected) in ctx.slice_starts().iter().zip(&hit_positions) {
+    assert!(
+      (start as usize).abs_diff(expected) < 16,
+      "{start} {expected}"
+    );
+  }
+  assert_eq!(ctx.map_slices_to_steps(16), &[0, 4, 8, 12]);
+
+  assert_eq!(ctx.note_to_slice(35), None);
+  assert_eq!(ctx.note_to_slice(38), Some(2));
+  assert_eq!(ctx.note_to_slice(40), None);
+
+  // Edits keep the slices ordered
+  let (min, max) = ctx.slice_start_bounds(2).unwrap();
+  assert_eq!(min, ctx.slice_starts()[1] as usize + 1);
+  assert_eq!(max, ctx.slice_starts()[3] as usize - 1);
+  assert_eq!(ctx.add_slice(30_000), Some(3));
+  assert_eq!(ctx.add_slice(30_000), None);
+  assert!(ctx.remove_slice(3));
+  assert!(!ctx.remove_slice(0));
+  assert_eq!(ctx.slice_starts().len(), hit_positions.len());
+}
+
+#[test]
+fn notes_play_their_slice_and_choke() {
+  let mut ctx = SlicerCtx::default();
+  let buf = ctx.set_sample_len(1_000);
+  buf[..500].fill(0.5);
+  buf[500..].fill(-0.25);
+  ctx.add_slice(500);
+
+  ctx.note_on(ctx.base_note + 1, 1.);
+  let mut rendered = Vec::new();
+  for _ in 0..5 {
+    ctx.process(128);
+    rendered.extend_from_slice(&ctx.output_buffer[..128]);
+  }
+  // Only the second slice plays, faded in and out at its boundaries
+  assert_eq!(rendered[0], 0.);
+  assert_eq!(rendered[DECLICK_SAMPLES], -0.25);
+  assert!(rendered[500..].iter().all(|&x| x == 0.));
+  assert!(ctx.voices.is_empty());
+
+  ctx.note_on(ctx.base_note, 1.);
+  ctx.process(128);
+  ctx.note_on(ctx.base_note + 1, 1.);
+  ctx.process(128);
+  assert_eq!(ctx.voices.len(), 1);
+  assert_eq!(ctx.voices[0].note, ctx.base_note + 1);
+}
+
+#[test]
+fn getters_reject_null_ctx() {
+  let ctx = std::ptr::null_mut();
+  assert!(slicer_get_sample_buf_ptr(ctx, 128).is_null());
+  assert!(slicer_get_output_buf_ptr(ctx).is_null());
+  assert_eq!(slicer_get_slice_count(ctx), 0);
+  assert!(slicer_get_slice_starts_ptr(ctx).is_null());
+  assert!(slicer_get_slice_steps_ptr(ctx).is_null());
+}
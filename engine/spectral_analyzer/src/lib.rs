@@ -0,0 +1,219 @@
+//! Measures the spectral features of its input, such as its brightness and how noisy it is, for
+//! music-reactive visuals and for modulating other modules from the character of a sound.  See
+//! `dsp::spectral_features` for what each feature measures.
+//!
+//! The input is passed through unchanged on the first output.  The centroid, rolloff, and flatness
+//! are also emitted as smoothed control signals from 0 to 1 on the remaining outputs and published
+//! as control outputs in the same order for other modules to subscribe to over the control bus.
+//! All features are written to the SAB after every frame.
+//!
+//! Exposed through the standard `module_api` entry points.
+
+use common::ffi::{self, FfiResult};
+use dsp::{
+  smoothed_param::{SmoothedParam, SmoothingMode},
+  spectral_features::{SpectralAnalyzer, BAND_COUNT},
+  MAX_FRAME_SIZE,
+};
+use module_api::{ChannelLayout, DspModule};
+
+const FFT_SIZE: usize = 2048;
+const HOP_SIZE: usize = 512;
+
+/// The input is read from channel 0.  Channel 0 is also the audio output, followed by one channel
+/// per modulation output in the order given by `MOD_OUTPUT_*_IX`.
+const CHANNEL_COUNT: usize = 1 + MOD_OUTPUT_COUNT;
+const MOD_OUTPUT_CENTROID_IX: usize = 0;
+const MOD_OUTPUT_ROLLOFF_IX: usize = 1;
+const MOD_OUTPUT_FLATNESS_IX: usize = 2;
+const MOD_OUTPUT_COUNT: usize = 3;
+const OUTPUT_LAYOUTS: [ChannelLayout; CHANNEL_COUNT] = [ChannelLayout::Mono; CHANNEL_COUNT];
+
+const PARAM_SMOOTHING_MS_IX: usize = 0;
+/// Portion of the energy that lies below the rolloff frequency, from 0.5 to 0.99
+const PARAM_ROLLOFF_FRACTION_IX: usize = 1;
+const PARAM_COUNT: usize = 2;
+const MAX_SMOOTHING_MS: f32 = 2_000.;
+
+/// SAB layout: the features of the most recent analysis in the order below, followed by the RMS
+/// level of each of `dsp::spectral_features::BAND_EDGES_HZ`.  Frequencies are in Hz and levels are
+/// linear.
+const SAB_CENTROID_HZ_IX: usize = 0;
+const SAB_ROLLOFF_HZ_IX: usize = 1;
+const SAB_FLATNESS_IX: usize = 2;
+const SAB_RMS_IX: usize = 3;
+const SAB_BAND_RMS_OFFSET: usize = 4;
+const SAB_LEN: usize = SAB_BAND_RMS_OFFSET + BAND_COUNT;
+
+/// Frequencies are mapped to modulation outputs logarithmically between these, so that equal steps
+/// in pitch move the output by equal amounts
+const MIN_MOD_FREQ_HZ: f32 = 20.;
+const MAX_MOD_FREQ_HZ: f32 = 20_000.;
+
+fn normalize_freq(freq_hz: f32) -> f32 {
+  if freq_hz <= MIN_MOD_FREQ_HZ {
+    return 0.;
+  }
+  ((freq_hz / MIN_MOD_FREQ_HZ).log2() / (MAX_MOD_FREQ_HZ / MIN_MOD_FREQ_HZ).log2()).min(1.)
+}
+
+pub struct SpectralAnalyzerCtx {
+  /// Planar; see `CHANNEL_COUNT` for the layout.  Processed in place.
+  pub io_buffer: [f32; MAX_FRAME_SIZE * CHANNEL_COUNT],
+  /// Written by JS; see `PARAM_SMOOTHING_MS_IX` for the layout
+  pub params: [f32; PARAM_COUNT],
+  analyzer: SpectralAnalyzer,
+  mod_outputs: [SmoothedParam; MOD_OUTPUT_COUNT],
+  /// The last value of each modulation output
+  control_outputs: [f32; MOD_OUTPUT_COUNT],
+  pub sab: [f32; SAB_LEN],
+}
+
+impl Default for SpectralAnalyzerCtx {
+  fn default() -> Self {
+    let mut params = [0.; PARAM_COUNT];
+    params[PARAM_SMOOTHING_MS_IX] = 50.;
+    params[PARAM_ROLLOFF_FRACTION_IX] = 0.85;
+
+    SpectralAnalyzerCtx {
+      io_buffer: [0.; MAX_FRAME_SIZE * CHANNEL_COUNT],
+      params,
+      analyzer: SpectralAnalyzer::new(FFT_SIZE, HOP_SIZE),
+      mod_outputs: std::array::from_fn(|_| {
+        SmoothedParam::new(
+          SmoothingMode::Exponential,
+          0.,
+          params[PARAM_SMOOTHING_MS_IX],
+        )
+      }),
+      control_outputs: [0.; MOD_OUTPUT_COUNT],
+      sab: [0.; SAB_LEN],
+    }
+  }
+}
+
+impl SpectralAnalyzerCtx {
+  /// Clamps the params into their valid ranges in place.  NaNs are rejected.
+  fn validate_params(&mut self) -> FfiResult {
+    self.params[PARAM_SMOOTHING_MS_IX] = ffi::clamp_param(
+      "smoothing_ms",
+      self.params[PARAM_SMOOTHING_MS_IX],
+      0.,
+      MAX_SMOOTHING_MS,
+    )?;
+    self.params[PARAM_ROLLOFF_FRACTION_IX] = ffi::clamp_param(
+      "rolloff_fraction",
+      self.params[PARAM_ROLLOFF_FRACTION_IX],
+      0.5,
+      0.99,
+    )?;
+    Ok(())
+  }
+
+  fn apply(&mut self, frame_size: usize) {
+    self.analyzer.rolloff_fraction = self.params[PARAM_ROLLOFF_FRACTION_IX];
+    let (input, mod_bufs) = self.io_buffer.split_at_mut(MAX_FRAME_SIZE);
+    if self.analyzer.process(&input[..frame_size]) {
+      let features = *self.analyzer.features();
+      let mut targets = [0.; MOD_OUTPUT_COUNT];
+      targets[MOD_OUTPUT_CENTROID_IX] = normalize_freq(features.centroid_hz);
+      targets[MOD_OUTPUT_ROLLOFF_IX] = normalize_freq(features.rolloff_hz);
+      targets[MOD_OUTPUT_FLATNESS_IX] = features.flatness;
+      for (mod_output, target) in self.mod_outputs.iter_mut().zip(targets) {
+        mod_output.set_target(target);
+      }
+
+      self.sab[SAB_CENTROID_HZ_IX] = features.centroid_hz;
+      self.sab[SAB_ROLLOFF_HZ_IX] = features.rolloff_hz;
+      self.sab[SAB_FLATNESS_IX] = features.flatness;
+      self.sab[SAB_RMS_IX] = features.rms;
+      self.sab[SAB_BAND_RMS_OFFSET..].copy_from_slice(&features.band_rms);
+    }
+
+    for ((mod_output, buf), control_output) in self
+      .mod_outputs
+      .iter_mut()
+      .zip(mod_bufs.chunks_exact_mut(MAX_FRAME_SIZE))
+      .zip(&mut self.control_outputs)
+    {
+      mod_output.set_ramp_ms(self.params[PARAM_SMOOTHING_MS_IX]);
+      mod_output.fill(&mut buf[..frame_size]);
+      *control_output = buf[frame_size - 1];
+    }
+  }
+}
+
+impl DspModule for SpectralAnalyzerCtx {
+  const CHANNEL_COUNT: usize = CHANNEL_COUNT;
+  const CONTROL_OUTPUT_COUNT: usize = MOD_OUTPUT_COUNT;
+  const NAME: &'static str = "spectral_analyzer";
+  const OUTPUT_LAYOUTS: &'static [ChannelLayout] = &OUTPUT_LAYOUTS;
+  const PARAM_COUNT: usize = PARAM_COUNT;
+  const SAB_LEN: usize = SAB_LEN;
+  const VERSION: u32 = 1;
+
+  fn new() -> Self { Self::default() }
+
+  fn param_name(param_ix: usize) -> String {
+    match param_ix {
+      PARAM_SMOOTHING_MS_IX => "smoothing_ms",
+      _ => "rolloff_fraction",
+    }
+    .to_owned()
+  }
+
+  fn io_buffer(&mut self) -> &mut [f32] { &mut self.io_buffer }
+
+  fn params(&mut self) -> &mut [f32] { &mut self.params }
+
+  fn sab(&mut self) -> &mut [f32] { &mut self.sab }
+
+  fn control_outputs(&self) -> &[f32] { &self.control_outputs }
+
+  fn process(&mut self, frame_size: usize) -> FfiResult {
+    self.validate_params()?;
+    self.apply(frame_size);
+    Ok(())
+  }
+
+  fn reset(&mut self) {
+    self.analyzer.reset();
+    for mod_output in &mut self.mod_outputs {
+      mod_output.set_immediate(0.);
+    }
+    self.control_outputs = [0.; MOD_OUTPUT_COUNT];
+    self.sab = [0.; SAB_LEN];
+  }
+}
+
+module_api::export_module!(SpectralAnalyzerCtx);
+
+#[test]
+fn features_track_input_and_audio_passes_through() {
+  let mut ctx = SpectralAnalyzerCtx::default();
+  ctx.params[PARAM_SMOOTHING_MS_IX] = 0.;
+  let frame_size = 128;
+  let freq = 2_000.;
+  for frame_ix in 0..64 {
+    let mut input = [0.; 128];
+    for (i, sample) in input.iter_mut().enumerate() {
+      let t = (frame_ix * frame_size + i) as f32 / dsp::sample_rate();
+      *sample = 0.5 * (std::f32::consts::TAU * freq * t).sin();
+    }
+    ctx.io_buffer[..frame_size].copy_from_slice(&input);
+    ctx.process(frame_size).unwrap();
+    assert_eq!(&ctx.io_buffer[..frame_size], &input[..]);
+  }
+
+  assert!((ctx.sab[SAB_CENTROID_HZ_IX] - freq).abs() < 50.);
+  let centroid_mod = ctx.io_buffer[MAX_FRAME_SIZE * (1 + MOD_OUTPUT_CENTROID_IX)];
+  assert!((centroid_mod - normalize_freq(freq)).abs() < 0.01);
+  let flatness_mod = ctx.io_buffer[MAX_FRAME_SIZE * (1 + MOD_OUTPUT_FLATNESS_IX)];
+  assert!(flatness_mod < 0.01);
+  let rolloff_mod = ctx.io_buffer[MAX_FRAME_SIZE * (1 + MOD_OUTPUT_ROLLOFF_IX)];
+  assert!(rolloff_mod > 0.5 && rolloff_mod < 0.7);
+  assert_eq!(ctx.control_outputs()[MOD_OUTPUT_CENTROID_IX], centroid_mod);
+
+  ctx.reset();
+  assert_eq!(ctx.sab, [0.; SAB_LEN]);
+}
@@ -0,0 +1,421 @@
+//! Multiband spectral gate.  The input is split into octave-spaced bands by a tree of
+//! Linkwitz-Riley crossovers, and each band is gated independently: while a band's level is below
+//! its threshold, it is attenuated by the configured reduction.  Useful for cleaning up noisy live
+//! input before it hits the looper or vocoder.
+//!
+//! Level detection is linked across channels so that the stereo image doesn't wander when only one
+//! side of a band crosses the threshold.
+//!
+//! Exposed through the standard `module_api` entry points.
+
+use common::ffi::{self, FfiResult};
+use dsp::{
+  db_to_gain,
+  filters::biquad::{BiquadFilter, FilterMode},
+  MAX_FRAME_SIZE,
+};
+use module_api::DspModule;
+
+const CHANNEL_COUNT: usize = 2;
+pub const BAND_COUNT: usize = 8;
+/// Crossover frequencies between adjacent bands, an octave apart
+const CROSSOVER_FREQS_HZ: [f32; BAND_COUNT - 1] = [100., 200., 400., 800., 1600., 3200., 6400.];
+/// Q of a second-order Butterworth section (1/sqrt(2)), in dB as `BiquadFilter` expects
+const BUTTERWORTH_Q_DB: f32 = -3.0103;
+
+/// Param buffer layout: the reduction comes first so that it can be automated by the host, followed
+/// by the per-band params interleaved as threshold, attack, release.
+const PARAM_REDUCTION_DB_IX: usize = 0;
+const BAND_PARAMS_OFFSET: usize = 1;
+pub const BAND_PARAM_COUNT: usize = 3;
+const PARAM_COUNT: usize = BAND_PARAMS_OFFSET + BAND_COUNT * BAND_PARAM_COUNT;
+const BAND_PARAM_THRESHOLD_DB_IX: usize = 0;
+const BAND_PARAM_ATTACK_MS_IX: usize = 1;
+const BAND_PARAM_RELEASE_MS_IX: usize = 2;
+
+const MIN_THRESHOLD_DB: f32 = -100.;
+const MAX_THRESHOLD_DB: f32 = 0.;
+const MAX_ENVELOPE_TIME_MS: f32 = 2000.;
+pub const MIN_REDUCTION_DB: f32 = -100.;
+/// A band's gate only closes once its level falls this far below the threshold, which keeps it
+/// from chattering on signals that hover around the threshold
+const HYSTERESIS_DB: f32 = 3.;
+/// Release time of the per-band peak level detectors
+const DETECTOR_RELEASE_MS: f32 = 20.;
+
+/// Computes the coefficient for a one-pole smoother with the given time constant.  Times shorter
+/// than a sample give 0, meaning no smoothing at all.
+fn compute_envelope_coefficient(time_ms: f32) -> f32 {
+  let time_samples = dsp::ms_to_samples(time_ms);
+  if time_samples.is_nan() || time_samples <= 0. {
+    return 0.;
+  }
+  (-1. / time_samples).exp()
+}
+
+/// Fourth-order Linkwitz-Riley crossover built from two cascaded Butterworth sections per side.
+/// The low and high outputs sum back to a flat magnitude response.
+#[derive(Clone, Copy)]
+struct Crossover {
+  lowpass: [BiquadFilter; 2],
+  highpass: [BiquadFilter; 2],
+}
+
+impl Crossover {
+  fn new(freq: f32) -> Self {
+    // Keep the top crossovers valid at low sample rates
+    let freq = freq.min(dsp::nyquist() * 0.9);
+    let lowpass = BiquadFilter::new(FilterMode::Lowpass, BUTTERWORTH_Q_DB, 0., freq, 0.);
+    let highpass = BiquadFilter::new(FilterMode::Highpass, BUTTERWORTH_Q_DB, 0., freq, 0.);
+    Crossover {
+      lowpass: [lowpass; 2],
+      highpass: [highpass; 2],
+    }
+  }
+
+  #[inline]
+  fn split(&mut self, input: f32) -> (f32, f32) {
+    let [lowpass_0, lowpass_1] = &mut self.lowpass;
+    let [highpass_0, highpass_1] = &mut self.highpass;
+    let low = lowpass_1.apply(lowpass_0.apply(input));
+    let high = highpass_1.apply(highpass_0.apply(input));
+    (low, high)
+  }
+
+  fn reset(&mut self) {
+    for filter in self.lowpass.iter_mut().chain(self.highpass.iter_mut()) {
+      filter.reset();
+    }
+  }
+}
+
+/// Second-order allpass with the same phase response as the summed outputs of a `Crossover` at
+/// `freq`
+fn build_crossover_allpass(freq: f32) -> BiquadFilter {
+  let freq = freq.min(dsp::nyquist() * 0.9);
+  let w0 = std::f32::consts::PI * freq / dsp::nyquist();
+  let alpha = w0.sin() / (2. * std::f32::consts::FRAC_1_SQRT_2);
+  let a0 = 1. + alpha;
+  BiquadFilter {
+    b0_over_a0: (1. - alpha) / a0,
+    b1_over_a0: (-2. * w0.cos()) / a0,
+    b2_over_a0: 1.,
+    a1_over_a0: (-2. * w0.cos()) / a0,
+    a2_over_a0: (1. - alpha) / a0,
+    ..Default::default()
+  }
+}
+
+/// Splits a signal into `BAND_COUNT` bands by repeatedly peeling the lowest band off of what's
+/// left above the previous crossover
+#[derive(Clone)]
+struct BandSplitter {
+  crossovers: [Crossover; BAND_COUNT - 1],
+  /// Indexed by `[band][crossover]`.  Each band is passed through allpasses matching the phase
+  /// shift of all of the crossovers above it, which the higher bands pick up on their way through
+  /// the tree.  Without this, the bands don't sum back to a flat response.
+  compensation: [[BiquadFilter; BAND_COUNT - 1]; BAND_COUNT - 1],
+}
+
+impl Default for BandSplitter {
+  fn default() -> Self {
+    BandSplitter {
+      crossovers: CROSSOVER_FREQS_HZ.map(Crossover::new),
+      compensation: [CROSSOVER_FREQS_HZ.map(build_crossover_allpass); BAND_COUNT - 1],
+    }
+  }
+}
+
+impl BandSplitter {
+  #[inline]
+  fn split(&mut self, input: f32) -> [f32; BAND_COUNT] {
+    let mut bands = [0.; BAND_COUNT];
+    let mut remaining = input;
+    for (band, crossover) in bands.iter_mut().zip(self.crossovers.iter_mut()) {
+      let (low, high) = crossover.split(remaining);
+      *band = low;
+      remaining = high;
+    }
+    bands[BAND_COUNT - 1] = remaining;
+
+    for (band_ix, (band, allpasses)) in bands
+      .iter_mut()
+      .zip(self.compensation.iter_mut())
+      .enumerate()
+    {
+      for allpass in &mut allpasses[band_ix + 1..] {
+        *band = allpass.apply(*band);
+      }
+    }
+    bands
+  }
+
+  fn reset(&mut self) {
+    for crossover in &mut self.crossovers {
+      crossover.reset();
+    }
+    for allpass in self.compensation.iter_mut().flatten() {
+      allpass.reset();
+    }
+  }
+}
+
+#[derive(Clone, Copy)]
+struct GateBand {
+  /// Peak level of the band, linked across channels
+  level: f32,
+  open: bool,
+  gain: f32,
+}
+
+impl Default for GateBand {
+  fn default() -> Self {
+    GateBand {
+      level: 0.,
+      open: true,
+      gain: 1.,
+    }
+  }
+}
+
+pub struct SpectralGateCtx {
+  /// Planar stereo: left channel at 0, right channel at `MAX_FRAME_SIZE`.  Processed in place.
+  pub io_buffer: [f32; MAX_FRAME_SIZE * CHANNEL_COUNT],
+  /// Written by JS; see `PARAM_REDUCTION_DB_IX` for the layout
+  pub params: [f32; PARAM_COUNT],
+  splitters: [BandSplitter; CHANNEL_COUNT],
+  bands: [GateBand; BAND_COUNT],
+  /// Current gain of each band, for metering
+  pub sab: [f32; BAND_COUNT],
+}
+
+impl Default for SpectralGateCtx {
+  fn default() -> Self {
+    let mut params = [0.; PARAM_COUNT];
+    params[PARAM_REDUCTION_DB_IX] = -60.;
+    for params in params[BAND_PARAMS_OFFSET..].chunks_exact_mut(BAND_PARAM_COUNT) {
+      params[BAND_PARAM_THRESHOLD_DB_IX] = -60.;
+      params[BAND_PARAM_ATTACK_MS_IX] = 1.;
+      params[BAND_PARAM_RELEASE_MS_IX] = 100.;
+    }
+
+    SpectralGateCtx {
+      io_buffer: [0.; MAX_FRAME_SIZE * CHANNEL_COUNT],
+      params,
+      splitters: [BandSplitter::default(), BandSplitter::default()],
+      bands: [GateBand::default(); BAND_COUNT],
+      sab: [1.; BAND_COUNT],
+    }
+  }
+}
+
+/// Per-band values derived from the band params once per frame
+#[derive(Clone, Copy, Default)]
+struct BandCoefficients {
+  open_level: f32,
+  close_level: f32,
+  attack_coefficient: f32,
+  release_coefficient: f32,
+}
+
+impl SpectralGateCtx {
+  fn band_params(&self) -> std::slice::ChunksExact<'_, f32> {
+    self.params[BAND_PARAMS_OFFSET..].chunks_exact(BAND_PARAM_COUNT)
+  }
+
+  /// Clamps the params into their valid ranges in place.  NaNs are rejected.
+  fn validate_params(&mut self) -> FfiResult {
+    self.params[PARAM_REDUCTION_DB_IX] = ffi::clamp_param(
+      "reduction_db",
+      self.params[PARAM_REDUCTION_DB_IX],
+      MIN_REDUCTION_DB,
+      0.,
+    )?;
+    for params in self.params[BAND_PARAMS_OFFSET..].chunks_exact_mut(BAND_PARAM_COUNT) {
+      params[BAND_PARAM_THRESHOLD_DB_IX] = ffi::clamp_param(
+        "threshold_db",
+        params[BAND_PARAM_THRESHOLD_DB_IX],
+        MIN_THRESHOLD_DB,
+        MAX_THRESHOLD_DB,
+      )?;
+      params[BAND_PARAM_ATTACK_MS_IX] = ffi::clamp_param(
+        "attack_ms",
+        params[BAND_PARAM_ATTACK_MS_IX],
+        0.,
+        MAX_ENVELOPE_TIME_MS,
+      )?;
+      params[BAND_PARAM_RELEASE_MS_IX] = ffi::clamp_param(
+        "release_ms",
+        params[BAND_PARAM_RELEASE_MS_IX],
+        0.,
+        MAX_ENVELOPE_TIME_MS,
+      )?;
+    }
+    Ok(())
+  }
+
+  fn compute_band_coefficients(&self) -> [BandCoefficients; BAND_COUNT] {
+    let mut coefficients = [BandCoefficients::default(); BAND_COUNT];
+    for (coefficients, params) in coefficients.iter_mut().zip(self.band_params()) {
+      let threshold_db = params[BAND_PARAM_THRESHOLD_DB_IX];
+      *coefficients = BandCoefficients {
+        open_level: db_to_gain(threshold_db),
+        close_level: db_to_gain(threshold_db - HYSTERESIS_DB),
+        attack_coefficient: compute_envelope_coefficient(params[BAND_PARAM_ATTACK_MS_IX]),
+        release_coefficient: compute_envelope_coefficient(params[BAND_PARAM_RELEASE_MS_IX]),
+      };
+    }
+    coefficients
+  }
+
+  pub fn apply(&mut self, frame_size: usize) {
+    let closed_gain = db_to_gain(self.params[PARAM_REDUCTION_DB_IX]);
+    let detector_coefficient = compute_envelope_coefficient(DETECTOR_RELEASE_MS);
+    let coefficients = self.compute_band_coefficients();
+
+    let (left, right) = self.io_buffer.split_at_mut(MAX_FRAME_SIZE);
+    let [left_splitter, right_splitter] = &mut self.splitters;
+    for (l, r) in left[..frame_size]
+      .iter_mut()
+      .zip(right[..frame_size].iter_mut())
+    {
+      let left_bands = left_splitter.split(*l);
+      let right_bands = right_splitter.split(*r);
+
+      let (mut l_out, mut r_out) = (0., 0.);
+      for (band_ix, band) in self.bands.iter_mut().enumerate() {
+        let coefficients = &coefficients[band_ix];
+        let (l_band, r_band) = (left_bands[band_ix], right_bands[band_ix]);
+
+        let abs = l_band.abs().max(r_band.abs());
+        band.level = if abs > band.level {
+          abs
+        } else {
+          band.level * detector_coefficient
+        };
+        band.open = if band.open {
+          band.level >= coefficients.close_level
+        } else {
+          band.level > coefficients.open_level
+        };
+
+        let target_gain = if band.open { 1. } else { closed_gain };
+        let coefficient = if target_gain > band.gain {
+          coefficients.attack_coefficient
+        } else {
+          coefficients.release_coefficient
+        };
+        band.gain = coefficient * band.gain + (1. - coefficient) * target_gain;
+
+        l_out += l_band * band.gain;
+        r_out += r_band * band.gain;
+      }
+
+      *l = l_out;
+      *r = r_out;
+    }
+
+    for (sab, band) in self.sab.iter_mut().zip(self.bands.iter()) {
+      *sab = band.gain;
+    }
+  }
+}
+
+impl DspModule for SpectralGateCtx {
+  const AUTOMATABLE_PARAM_COUNT: usize = 1;
+  const CHANNEL_COUNT: usize = CHANNEL_COUNT;
+  const NAME: &'static str = "spectral_gate";
+  const PARAM_COUNT: usize = PARAM_COUNT;
+  const SAB_LEN: usize = BAND_COUNT;
+  const VERSION: u32 = 1;
+
+  fn new() -> Self { Self::default() }
+
+  fn param_name(param_ix: usize) -> String {
+    if param_ix == PARAM_REDUCTION_DB_IX {
+      return "reduction_db".to_owned();
+    }
+
+    let band_ix = (param_ix - BAND_PARAMS_OFFSET) / BAND_PARAM_COUNT;
+    let name = match (param_ix - BAND_PARAMS_OFFSET) % BAND_PARAM_COUNT {
+      BAND_PARAM_THRESHOLD_DB_IX => "threshold_db",
+      BAND_PARAM_ATTACK_MS_IX => "attack_ms",
+      _ => "release_ms",
+    };
+    format!("band_{band_ix}_{name}")
+  }
+
+  fn io_buffer(&mut self) -> &mut [f32] { &mut self.io_buffer }
+
+  fn params(&mut self) -> &mut [f32] { &mut self.params }
+
+  fn sab(&mut self) -> &mut [f32] { &mut self.sab }
+
+  fn process(&mut self, frame_size: usize) -> FfiResult {
+    self.validate_params()?;
+    self.apply(frame_size);
+    Ok(())
+  }
+
+  fn reset(&mut self) {
+    for splitter in &mut self.splitters {
+      splitter.reset();
+    }
+    self.bands = [GateBand::default(); BAND_COUNT];
+    self.sab.fill(1.);
+  }
+}
+
+module_api::export_module!(SpectralGateCtx);
+
+#[cfg(test)]
+fn render_sine(ctx: &mut SpectralGateCtx, freq: f32, amplitude: f32, frame_count: usize) -> f32 {
+  let frame_size = 128;
+  let mut sample_ix = 0;
+  let mut peak = 0.0f32;
+  for frame_ix in 0..frame_count {
+    for i in 0..frame_size {
+      let t = sample_ix as f32 / dsp::sample_rate();
+      let sample = amplitude * (std::f32::consts::TAU * freq * t).sin();
+      ctx.io_buffer[i] = sample;
+      ctx.io_buffer[MAX_FRAME_SIZE + i] = sample;
+      sample_ix += 1;
+    }
+    ctx.apply(frame_size);
+    // Only measure once the filters and envelopes have settled
+    if frame_ix >= frame_count / 2 {
+      peak = ctx.io_buffer[..frame_size]
+        .iter()
+        .fold(peak, |acc, s| acc.max(s.abs()));
+    }
+  }
+  peak
+}
+
+#[test]
+fn open_bands_sum_to_unity() {
+  let mut ctx = SpectralGateCtx::default();
+  for freq in [50., 300., 1000., 5000.] {
+    ctx.reset();
+    let peak = render_sine(&mut ctx, freq, 0.5, 200);
+    assert!((peak - 0.5).abs() < 0.01, "{freq}Hz -> {peak}");
+  }
+}
+
+#[test]
+fn quiet_bands_are_gated() {
+  let mut ctx = SpectralGateCtx::default();
+  ctx.params[PARAM_REDUCTION_DB_IX] = MIN_REDUCTION_DB;
+  for params in ctx.params[BAND_PARAMS_OFFSET..].chunks_exact_mut(BAND_PARAM_COUNT) {
+    params[BAND_PARAM_THRESHOLD_DB_IX] = -20.;
+    params[BAND_PARAM_RELEASE_MS_IX] = 10.;
+  }
+
+  let quiet_peak = render_sine(&mut ctx, 1000., db_to_gain(-40.), 200);
+  assert!(quiet_peak < db_to_gain(-80.), "{quiet_peak}");
+
+  // The neighboring bands only see the crossover skirts of the tone and stay closed, so a little
+  // bit of level is lost
+  let loud_peak = render_sine(&mut ctx, 1000., db_to_gain(-6.), 200);
+  assert!(loud_peak > db_to_gain(-8.), "{loud_peak}");
+}
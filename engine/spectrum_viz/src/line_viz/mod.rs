@@ -1,4 +1,5 @@
 use canvas_utils::VizView;
+use common::ffi::ErrorCode;
 
 use self::{conf::FFT_BUFFER_SIZE, viz::LineSpectrumCtx};
 
@@ -54,7 +55,11 @@ static mut CTX: LineSpectrumCtx = LineSpectrumCtx {
 fn ctx() -> &'static mut LineSpectrumCtx { unsafe { &mut CTX } }
 
 #[no_mangle]
-pub extern "C" fn line_spectrogram_set_view(width_px: usize, height_px: usize, dpr: usize) {
+pub extern "C" fn line_spectrogram_set_view(
+  width_px: usize,
+  height_px: usize,
+  dpr: usize,
+) -> ErrorCode {
   maybe_set_panic_hook();
 
   ctx().set_view(VizView {
@@ -62,6 +67,7 @@ pub extern "C" fn line_spectrogram_set_view(width_px: usize, height_px: usize, d
     height: height_px,
     dpr,
   });
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -75,4 +81,7 @@ pub extern "C" fn line_spectrogram_get_image_data_ptr() -> *mut u8 {
 }
 
 #[no_mangle]
-pub extern "C" fn line_spectrogram_process() { ctx().process(); }
+pub extern "C" fn line_spectrogram_process() -> ErrorCode {
+  ctx().process();
+  ErrorCode::Ok
+}
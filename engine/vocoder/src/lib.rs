@@ -2,6 +2,7 @@
 
 use std::mem::MaybeUninit;
 
+use common::ffi::{self, ErrorCode};
 use dsp::{
   filters::biquad::{BiquadFilter, BiquadFilterBank2D, FilterMode},
   rms_level_detector::RMSLevelDetector,
@@ -14,9 +15,8 @@ extern "C" {
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-extern "C" fn log_err(ptr: *const u8, len: usize) {}
+use common::native::log_err;
 
-const SAMPLE_RATE: usize = 44_100;
 const BAND_ORDER: usize = 16; // 24;
 const BAND_COUNT: usize = 22; // 36;
 const FILTERS_PER_BAND: usize = BAND_ORDER;
@@ -24,7 +24,7 @@ const FILTERS_PER_BAND: usize = BAND_ORDER;
 /// We use RMS level detection, so we need to make sure that the window size is big enough to
 /// capture a full cycle of the signal.  For lower frequencies, we need a longer window.
 fn compute_level_detection_window_samples(band_center_freq_hz: f32) -> f32 {
-  (1. / band_center_freq_hz) * SAMPLE_RATE as f32
+  (1. / band_center_freq_hz) * dsp::sample_rate()
 }
 
 pub struct VocoderBand {
@@ -305,9 +305,13 @@ pub extern "C" fn vocoder_process(
   carrier_gain: f32,
   modulator_gain: f32,
   output_gain: f32,
-) {
-  let ctx = unsafe { &mut *ctx };
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "vocoder_process") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
   ctx.process(carrier_gain, modulator_gain, output_gain);
+  ErrorCode::Ok
 }
 
 #[test]
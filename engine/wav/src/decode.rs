@@ -0,0 +1,224 @@
+use crate::{SampleFormat, WavError, WavSpec, WAVE_FORMAT_EXTENSIBLE};
+
+/// `data` chunk sizes that indicate that the length wasn't known when the header was written, as
+/// is the case for some streamed recordings.  Sample data is read until the end of the input.
+const UNKNOWN_DATA_LEN: [u32; 2] = [0, u32::MAX];
+
+#[derive(Clone, Copy, Debug, Default)]
+enum State {
+  #[default]
+  RiffHeader,
+  ChunkHeader,
+  Fmt {
+    len: usize,
+  },
+  SkipChunk {
+    remaining: usize,
+  },
+  Data {
+    /// `None` if the length of the data chunk is unknown
+    remaining: Option<usize>,
+  },
+  Done,
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 { u16::from_le_bytes([buf[offset], buf[offset + 1]]) }
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+  u32::from_le_bytes([
+    buf[offset],
+    buf[offset + 1],
+    buf[offset + 2],
+    buf[offset + 3],
+  ])
+}
+
+fn parse_fmt_chunk(chunk: &[u8]) -> Result<WavSpec, WavError> {
+  if chunk.len() < 16 {
+    return Err(WavError::InvalidFmtChunk("chunk is too short"));
+  }
+
+  let mut format_tag = read_u16(chunk, 0);
+  let channel_count = read_u16(chunk, 2);
+  let sample_rate = read_u32(chunk, 4);
+  let block_align = read_u16(chunk, 12) as usize;
+  let bits_per_sample = read_u16(chunk, 14);
+  if format_tag == WAVE_FORMAT_EXTENSIBLE {
+    if chunk.len() < 40 {
+      return Err(WavError::InvalidFmtChunk("extensible chunk is too short"));
+    }
+    // The first two bytes of the sub-format GUID hold the actual format tag
+    format_tag = read_u16(chunk, 24);
+  }
+
+  let spec = WavSpec {
+    channel_count,
+    sample_rate,
+    format: SampleFormat::from_parts(format_tag, bits_per_sample)?,
+  };
+  spec.validate()?;
+  if block_align != spec.block_align() {
+    return Err(WavError::InvalidFmtChunk(
+      "block align doesn't match channel count and sample size",
+    ));
+  }
+  Ok(spec)
+}
+
+fn read_sample(format: SampleFormat, bytes: &[u8]) -> f32 {
+  match format {
+    SampleFormat::Pcm8 => (bytes[0] as f32 - 128.) / 128.,
+    SampleFormat::Pcm16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32_768.,
+    SampleFormat::Pcm24 => {
+      // Shift into the top of an `i32` and back down to sign-extend
+      let val = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+      val as f32 / 8_388_608.
+    },
+    SampleFormat::Pcm32 =>
+      (i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64 / 2_147_483_648.) as f32,
+    SampleFormat::Float32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+  }
+}
+
+/// Incrementally decodes a WAV file as chunks of it become available so that large files don't
+/// need to be held in memory in their entirety.  Only the bytes of an incomplete header or sample
+/// are buffered between calls to `push`.
+#[derive(Default)]
+pub struct StreamDecoder {
+  buf: Vec<u8>,
+  state: State,
+  spec: Option<WavSpec>,
+}
+
+impl StreamDecoder {
+  /// Returns the format of the file once its `fmt ` chunk has been decoded
+  pub fn spec(&self) -> Option<WavSpec> { self.spec }
+
+  /// Decodes as much of `chunk` as possible, appending decoded interleaved samples to `out`.
+  pub fn push(&mut self, chunk: &[u8], out: &mut Vec<f32>) -> Result<(), WavError> {
+    self.buf.extend_from_slice(chunk);
+    let buf = &self.buf;
+    let mut pos = 0;
+
+    loop {
+      let available = buf.len() - pos;
+      match self.state {
+        State::RiffHeader => {
+          if available < 12 {
+            // Fail early on obviously invalid data rather than waiting for more
+            let prefix_len = available.min(4);
+            if buf[pos..pos + prefix_len] != b"RIFF"[..prefix_len] {
+              return Err(WavError::NotWav);
+            }
+            break;
+          }
+          if &buf[pos..pos + 4] != b"RIFF" || &buf[pos + 8..pos + 12] != b"WAVE" {
+            return Err(WavError::NotWav);
+          }
+          pos += 12;
+          self.state = State::ChunkHeader;
+        },
+        State::ChunkHeader => {
+          if available < 8 {
+            break;
+          }
+          let id = &buf[pos..pos + 4];
+          let len = read_u32(buf, pos + 4);
+          pos += 8;
+
+          self.state = match id {
+            b"fmt " => State::Fmt { len: len as usize },
+            b"data" => {
+              if self.spec.is_none() {
+                return Err(WavError::MissingFmtChunk);
+              }
+              State::Data {
+                remaining: if UNKNOWN_DATA_LEN.contains(&len) {
+                  None
+                } else {
+                  Some(len as usize)
+                },
+              }
+            },
+            _ => State::SkipChunk {
+              remaining: len as usize + len as usize % 2,
+            },
+          };
+        },
+        State::Fmt { len } => {
+          let padded_len = len + len % 2;
+          if available < padded_len {
+            break;
+          }
+          self.spec = Some(parse_fmt_chunk(&buf[pos..pos + len])?);
+          pos += padded_len;
+          self.state = State::ChunkHeader;
+        },
+        State::SkipChunk { remaining } => {
+          let skipped = remaining.min(available);
+          pos += skipped;
+          if skipped < remaining {
+            self.state = State::SkipChunk {
+              remaining: remaining - skipped,
+            };
+            break;
+          }
+          self.state = State::ChunkHeader;
+        },
+        State::Data { remaining } => {
+          let spec = self.spec.unwrap();
+          let block_align = spec.block_align();
+          let readable = remaining.map(|r| r.min(available)).unwrap_or(available);
+          let read_len = readable - readable % block_align;
+
+          let bytes_per_sample = spec.format.bytes_per_sample();
+          out.extend(
+            buf[pos..pos + read_len]
+              .chunks_exact(bytes_per_sample)
+              .map(|bytes| read_sample(spec.format, bytes)),
+          );
+          pos += read_len;
+
+          match remaining {
+            // Anything after the sample data (metadata chunks, padding) is ignored
+            Some(remaining) if remaining - read_len < block_align => {
+              self.state = State::Done;
+            },
+            Some(remaining) => {
+              self.state = State::Data {
+                remaining: Some(remaining - read_len),
+              };
+              break;
+            },
+            None => break,
+          }
+        },
+        State::Done => {
+          pos = buf.len();
+          break;
+        },
+      }
+    }
+
+    self.buf.drain(..pos);
+    Ok(())
+  }
+
+  /// Checks that the sample data was reached.  Files whose sample data is shorter than their
+  /// header claims are accepted since truncated recordings are common.
+  pub fn finish(&self) -> Result<(), WavError> {
+    match self.state {
+      State::Data { .. } | State::Done => Ok(()),
+      _ => Err(WavError::Truncated),
+    }
+  }
+}
+
+/// Decodes a complete WAV file, returning its format and interleaved samples
+pub fn decode(data: &[u8]) -> Result<(WavSpec, Vec<f32>), WavError> {
+  let mut decoder = StreamDecoder::default();
+  let mut samples = Vec::new();
+  decoder.push(data, &mut samples)?;
+  decoder.finish()?;
+  Ok((decoder.spec.unwrap(), samples))
+}
@@ -0,0 +1,97 @@
+//! Dithering for reducing the bit depth of rendered audio.  Plain rounding to 16 bits leaves
+//! quantization error that's correlated with the signal, which is audible as harsh distortion on
+//! quiet passages and fade-outs.  Adding triangular (TPDF) noise of +/-1 LSB before rounding turns
+//! that error into a constant, signal-independent noise floor.
+//!
+//! The noise can optionally be shaped by feeding the quantization error back through a filter,
+//! moving it towards high frequencies where hearing is less sensitive at the cost of more total
+//! noise.
+
+use rand::prelude::*;
+use rand_pcg::Pcg32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoiseShaping {
+  /// Unshaped TPDF dither with a flat noise spectrum
+  Flat,
+  /// Simple first-order highpass shaping.  Gentle and safe for any sample rate.
+  FirstOrder,
+  /// The 5-tap E-weighted curve from Lipshitz, Vanderkooy, and Wannamaker, designed for 44.1kHz.
+  /// Pushes the most noise out of the audible range but is too aggressive for material that's
+  /// already close to full scale.
+  EWeighted,
+}
+
+impl NoiseShaping {
+  /// Returns the coefficients applied to previous quantization errors, most recent first
+  fn error_feedback(self) -> &'static [f32] {
+    match self {
+      NoiseShaping::Flat => &[],
+      NoiseShaping::FirstOrder => &[1.],
+      NoiseShaping::EWeighted => &[2.033, -2.165, 1.959, -1.590, 0.6149],
+    }
+  }
+}
+
+const MAX_FEEDBACK_LEN: usize = 5;
+
+/// Quantizes interleaved samples one channel at a time, keeping track of each channel's previous
+/// quantization errors for noise shaping
+pub struct Ditherer {
+  bits: u32,
+  noise_shaping: NoiseShaping,
+  rng: Pcg32,
+  /// Most recent error first for each channel
+  errors: Vec<[f32; MAX_FEEDBACK_LEN]>,
+}
+
+impl Ditherer {
+  /// `seed` makes the added noise, and so the output, reproducible
+  pub fn new(bits: u32, channel_count: usize, noise_shaping: NoiseShaping, seed: u64) -> Self {
+    Ditherer {
+      bits,
+      noise_shaping,
+      rng: Pcg32::seed_from_u64(seed),
+      errors: vec![[0.; MAX_FEEDBACK_LEN]; channel_count],
+    }
+  }
+
+  /// Returns `sample` rounded to the nearest step of the target bit depth after adding dither.  The
+  /// result is exact in the target format, so encoding it doesn't round it any further.
+  pub fn process(&mut self, channel_ix: usize, sample: f32) -> f32 {
+    let scale = (1i64 << (self.bits - 1)) as f32;
+    let errors = &mut self.errors[channel_ix];
+
+    let feedback: f32 = self
+      .noise_shaping
+      .error_feedback()
+      .iter()
+      .zip(errors.iter())
+      .map(|(coefficient, error)| coefficient * error)
+      .sum();
+    let shaped = sample * scale - feedback;
+    let dither = self.rng.gen::<f32>() - self.rng.gen::<f32>();
+    let quantized = (shaped + dither).round().clamp(-scale, scale - 1.);
+
+    errors.rotate_right(1);
+    // Limited so that clipping near full scale can't make the feedback loop run away
+    errors[0] = (quantized - shaped).clamp(-2., 2.);
+
+    quantized / scale
+  }
+
+  /// Dithers interleaved samples in place
+  pub fn process_interleaved(&mut self, samples: &mut [f32]) {
+    let channel_count = self.errors.len();
+    for frame in samples.chunks_mut(channel_count) {
+      for (channel_ix, sample) in frame.iter_mut().enumerate() {
+        let clean = if sample.is_nan() {
+          0.
+        } else {
+          sample.clamp(-1., 1.)
+        };
+        *sample = self.process(channel_ix, clean);
+      }
+    }
+  }
+}
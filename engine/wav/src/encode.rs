@@ -0,0 +1,88 @@
+use crate::{
+  dither::{Ditherer, NoiseShaping},
+  SampleFormat, WavError, WavSpec,
+};
+
+const HEADER_LEN: usize = 44;
+
+/// Scales `sample` to a signed integer of `bits` bits.  The scale matches the one used when
+/// decoding so that round trips are lossless apart from quantization.
+fn quantize(sample: f32, bits: u32) -> i32 {
+  let scale = (1i64 << (bits - 1)) as f64;
+  (sample as f64 * scale).round().clamp(-scale, scale - 1.) as i32
+}
+
+fn write_sample(buf: &mut Vec<u8>, format: SampleFormat, sample: f32) {
+  let sample = if sample.is_nan() {
+    0.
+  } else {
+    sample.clamp(-1., 1.)
+  };
+
+  match format {
+    SampleFormat::Pcm8 => buf.push((quantize(sample, 8) + 128) as u8),
+    SampleFormat::Pcm16 => buf.extend_from_slice(&(quantize(sample, 16) as i16).to_le_bytes()),
+    SampleFormat::Pcm24 => buf.extend_from_slice(&quantize(sample, 24).to_le_bytes()[..3]),
+    SampleFormat::Pcm32 => buf.extend_from_slice(&quantize(sample, 32).to_le_bytes()),
+    SampleFormat::Float32 => buf.extend_from_slice(&sample.to_le_bytes()),
+  }
+}
+
+/// Encodes interleaved `samples` into a complete WAV file.  Samples are clamped to [-1, 1].
+pub fn encode(spec: &WavSpec, samples: &[f32]) -> Result<Vec<u8>, WavError> {
+  spec.validate()?;
+  if samples.len() % spec.channel_count as usize != 0 {
+    return Err(WavError::PartialFrame);
+  }
+
+  let data_len = samples.len() * spec.format.bytes_per_sample();
+  // Chunks are padded to an even length
+  let pad_len = data_len % 2;
+  let mut buf = Vec::with_capacity(HEADER_LEN + data_len + pad_len);
+
+  buf.extend_from_slice(b"RIFF");
+  buf.extend_from_slice(&((HEADER_LEN - 8 + data_len + pad_len) as u32).to_le_bytes());
+  buf.extend_from_slice(b"WAVE");
+
+  buf.extend_from_slice(b"fmt ");
+  buf.extend_from_slice(&16u32.to_le_bytes());
+  buf.extend_from_slice(&spec.format.format_tag().to_le_bytes());
+  buf.extend_from_slice(&spec.channel_count.to_le_bytes());
+  buf.extend_from_slice(&spec.sample_rate.to_le_bytes());
+  let byte_rate = spec.sample_rate * spec.block_align() as u32;
+  buf.extend_from_slice(&byte_rate.to_le_bytes());
+  buf.extend_from_slice(&(spec.block_align() as u16).to_le_bytes());
+  buf.extend_from_slice(&spec.format.bits_per_sample().to_le_bytes());
+
+  buf.extend_from_slice(b"data");
+  buf.extend_from_slice(&(data_len as u32).to_le_bytes());
+  for &sample in samples {
+    write_sample(&mut buf, spec.format, sample);
+  }
+  if pad_len != 0 {
+    buf.push(0);
+  }
+
+  Ok(buf)
+}
+
+/// Like `encode`, but dithers samples before reducing them to 8, 16, or 24-bit PCM.  Samples for
+/// 32-bit formats are encoded as-is.
+pub fn encode_dithered(
+  spec: &WavSpec,
+  samples: &[f32],
+  noise_shaping: NoiseShaping,
+  seed: u64,
+) -> Result<Vec<u8>, WavError> {
+  spec.validate()?;
+  let bits = match spec.format {
+    SampleFormat::Pcm8 | SampleFormat::Pcm16 | SampleFormat::Pcm24 =>
+      spec.format.bits_per_sample() as u32,
+    SampleFormat::Pcm32 | SampleFormat::Float32 => return encode(spec, samples),
+  };
+
+  let mut dithered = samples.to_owned();
+  Ditherer::new(bits, spec.channel_count as usize, noise_shaping, seed)
+    .process_interleaved(&mut dithered);
+  encode(spec, &dithered)
+}
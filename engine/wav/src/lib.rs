@@ -0,0 +1,140 @@
+//! WAV encoding and decoding shared by the modules that move audio in and out of Wasm: recorded
+//! loops, bounced mixes, and sampler content.  Samples are always exchanged as interleaved `f32`s
+//! in [-1, 1] regardless of the format they're stored in.
+
+use std::fmt;
+
+mod decode;
+pub mod dither;
+mod encode;
+#[cfg(test)]
+mod tests;
+
+pub use self::{
+  decode::{decode, StreamDecoder},
+  dither::NoiseShaping,
+  encode::{encode, encode_dithered},
+};
+
+/// Sample rates outside of this range are almost certainly the result of a corrupt header
+pub const MIN_SAMPLE_RATE: u32 = 8_000;
+pub const MAX_SAMPLE_RATE: u32 = 384_000;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SampleFormat {
+  /// Unsigned 8-bit PCM
+  Pcm8,
+  Pcm16,
+  Pcm24,
+  Pcm32,
+  Float32,
+}
+
+impl SampleFormat {
+  pub fn bits_per_sample(self) -> u16 {
+    match self {
+      SampleFormat::Pcm8 => 8,
+      SampleFormat::Pcm16 => 16,
+      SampleFormat::Pcm24 => 24,
+      SampleFormat::Pcm32 | SampleFormat::Float32 => 32,
+    }
+  }
+
+  pub fn bytes_per_sample(self) -> usize { self.bits_per_sample() as usize / 8 }
+
+  fn format_tag(self) -> u16 {
+    match self {
+      SampleFormat::Float32 => WAVE_FORMAT_IEEE_FLOAT,
+      _ => WAVE_FORMAT_PCM,
+    }
+  }
+
+  fn from_parts(format_tag: u16, bits_per_sample: u16) -> Result<Self, WavError> {
+    match (format_tag, bits_per_sample) {
+      (WAVE_FORMAT_PCM, 8) => Ok(SampleFormat::Pcm8),
+      (WAVE_FORMAT_PCM, 16) => Ok(SampleFormat::Pcm16),
+      (WAVE_FORMAT_PCM, 24) => Ok(SampleFormat::Pcm24),
+      (WAVE_FORMAT_PCM, 32) => Ok(SampleFormat::Pcm32),
+      (WAVE_FORMAT_IEEE_FLOAT, 32) => Ok(SampleFormat::Float32),
+      _ => Err(WavError::UnsupportedFormat {
+        format_tag,
+        bits_per_sample,
+      }),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WavSpec {
+  pub channel_count: u16,
+  pub sample_rate: u32,
+  pub format: SampleFormat,
+}
+
+impl WavSpec {
+  pub fn validate(&self) -> Result<(), WavError> {
+    if self.channel_count == 0 {
+      return Err(WavError::InvalidChannelCount(self.channel_count));
+    }
+    if !(MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&self.sample_rate) {
+      return Err(WavError::InvalidSampleRate(self.sample_rate));
+    }
+    Ok(())
+  }
+
+  /// Size in bytes of one sample for every channel
+  pub fn block_align(&self) -> usize {
+    self.channel_count as usize * self.format.bytes_per_sample()
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WavError {
+  /// The data doesn't start with a RIFF/WAVE header
+  NotWav,
+  /// A `data` chunk was found before the `fmt ` chunk
+  MissingFmtChunk,
+  InvalidFmtChunk(&'static str),
+  UnsupportedFormat {
+    format_tag: u16,
+    bits_per_sample: u16,
+  },
+  InvalidSampleRate(u32),
+  InvalidChannelCount(u16),
+  /// The number of samples to encode isn't a multiple of the channel count
+  PartialFrame,
+  /// The data ended before the start of the `data` chunk
+  Truncated,
+}
+
+impl fmt::Display for WavError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      WavError::NotWav => write!(f, "not a RIFF/WAVE file"),
+      WavError::MissingFmtChunk => write!(f, "`data` chunk found before `fmt ` chunk"),
+      WavError::InvalidFmtChunk(reason) => write!(f, "invalid `fmt ` chunk: {reason}"),
+      WavError::UnsupportedFormat {
+        format_tag,
+        bits_per_sample,
+      } => write!(
+        f,
+        "unsupported sample format; format tag={format_tag}, bits per sample={bits_per_sample}"
+      ),
+      WavError::InvalidSampleRate(sample_rate) => write!(
+        f,
+        "sample rate {sample_rate} is outside of the supported range [{MIN_SAMPLE_RATE}, \
+         {MAX_SAMPLE_RATE}]"
+      ),
+      WavError::InvalidChannelCount(channel_count) =>
+        write!(f, "invalid channel count {channel_count}"),
+      WavError::PartialFrame => write!(f, "sample count is not a multiple of the channel count"),
+      WavError::Truncated => write!(f, "file ended before the start of the sample data"),
+    }
+  }
+}
+
+impl std::error::Error for WavError {}
@@ -0,0 +1,125 @@
+use super::*;
+
+fn test_signal(len: usize) -> Vec<f32> {
+  (0..len)
+    .map(|i| (i as f32 * 0.05).sin() * 0.9)
+    .chain([1., -1., 0.])
+    .collect()
+}
+
+#[test]
+fn round_trip() {
+  let samples = test_signal(1000);
+  for (format, tolerance) in [
+    (SampleFormat::Pcm8, 1. / 128.),
+    (SampleFormat::Pcm16, 1. / 32_768.),
+    (SampleFormat::Pcm24, 1. / 8_388_608.),
+    (SampleFormat::Pcm32, 1e-7),
+    (SampleFormat::Float32, 0.),
+  ] {
+    let spec = WavSpec {
+      channel_count: 1,
+      sample_rate: 48_000,
+      format,
+    };
+    let encoded = encode(&spec, &samples).unwrap();
+    let (decoded_spec, decoded) = decode(&encoded).unwrap();
+    assert_eq!(decoded_spec, spec);
+    assert_eq!(decoded.len(), samples.len());
+    for (a, b) in samples.iter().zip(decoded.iter()) {
+      assert!((a - b).abs() <= tolerance, "{format:?}: {a} != {b}");
+    }
+  }
+}
+
+#[test]
+fn chunked_decode_matches_full_decode() {
+  let spec = WavSpec {
+    channel_count: 2,
+    sample_rate: 44_100,
+    format: SampleFormat::Pcm24,
+  };
+  let encoded = encode(&spec, &test_signal(999)).unwrap();
+  let (_, expected) = decode(&encoded).unwrap();
+
+  for chunk_size in [1, 7, 4096] {
+    let mut decoder = StreamDecoder::default();
+    let mut decoded = Vec::new();
+    for chunk in encoded.chunks(chunk_size) {
+      decoder.push(chunk, &mut decoded).unwrap();
+    }
+    decoder.finish().unwrap();
+    assert_eq!(decoder.spec(), Some(spec));
+    assert_eq!(decoded, expected);
+  }
+}
+
+#[test]
+fn rejects_invalid_headers() {
+  let spec = WavSpec {
+    channel_count: 1,
+    sample_rate: 44_100,
+    format: SampleFormat::Pcm16,
+  };
+  let mut encoded = encode(&spec, &[0., 0.5]).unwrap();
+
+  // Sample rate is stored at byte 24
+  encoded[24..28].copy_from_slice(&1u32.to_le_bytes());
+  assert_eq!(decode(&encoded), Err(WavError::InvalidSampleRate(1)));
+
+  assert_eq!(decode(b"RIFF\0\0\0\0WAVE"), Err(WavError::Truncated));
+  assert_eq!(decode(b"not a wav file"), Err(WavError::NotWav));
+  assert_eq!(
+    encode(
+      &WavSpec {
+        channel_count: 2,
+        ..spec
+      },
+      &[0.]
+    ),
+    Err(WavError::PartialFrame)
+  );
+}
+
+#[test]
+fn dither_preserves_signals_below_one_lsb() {
+  let spec = WavSpec {
+    channel_count: 2,
+    sample_rate: 44_100,
+    format: SampleFormat::Pcm16,
+  };
+  let lsb = 1. / 32_768.;
+  let samples = vec![0.25 * lsb; 44_100 * 2];
+
+  // Rounding alone loses the signal entirely
+  let (_, truncated) = decode(&encode(&spec, &samples).unwrap()).unwrap();
+  assert!(truncated.iter().all(|&sample| sample == 0.));
+
+  for noise_shaping in [
+    NoiseShaping::Flat,
+    NoiseShaping::FirstOrder,
+    NoiseShaping::EWeighted,
+  ] {
+    let encoded = encode_dithered(&spec, &samples, noise_shaping, 0).unwrap();
+    assert_eq!(
+      encoded,
+      encode_dithered(&spec, &samples, noise_shaping, 0).unwrap()
+    );
+
+    let (_, decoded) = decode(&encoded).unwrap();
+    let mean = decoded.iter().map(|&sample| sample as f64).sum::<f64>() / decoded.len() as f64;
+    assert!(
+      (mean / lsb as f64 - 0.25).abs() < 0.05,
+      "{noise_shaping:?}: mean was {} LSB",
+      mean / lsb as f64
+    );
+    let max_error = decoded
+      .iter()
+      .map(|&sample| ((sample - samples[0]) / lsb).abs())
+      .fold(0., f32::max);
+    assert!(
+      max_error <= 16.,
+      "{noise_shaping:?}: error of {max_error} LSB"
+    );
+  }
+}
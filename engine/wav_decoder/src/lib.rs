@@ -1,51 +1,195 @@
 #[macro_use]
 extern crate log;
 
+use dsp::resampler::{resample_interleaved, ResamplerQuality};
 use wasm_bindgen::prelude::*;
+use wav::{NoiseShaping, SampleFormat, WavError, WavSpec};
 
 static mut ERROR_MESSAGE: String = String::new();
 
 #[wasm_bindgen]
 pub fn get_error_message() -> String { unsafe { ERROR_MESSAGE.clone() } }
 
+fn set_error(context: &str, err: WavError) {
+  error!("{}: {}", context, err);
+  unsafe {
+    ERROR_MESSAGE = format!("{}: {}", context, err);
+  }
+}
+
+/// Sample rate of the most recently decoded file, or 0 if decoding failed
+static mut LAST_DECODED_SAMPLE_RATE: u32 = 0;
+static mut LAST_DECODED_CHANNEL_COUNT: u16 = 0;
+
+#[wasm_bindgen]
+pub fn get_last_decoded_sample_rate() -> u32 { unsafe { LAST_DECODED_SAMPLE_RATE } }
+
+#[wasm_bindgen]
+pub fn get_last_decoded_channel_count() -> u16 { unsafe { LAST_DECODED_CHANNEL_COUNT } }
+
+/// Decodes a complete WAV file into interleaved samples.  Returns an empty buffer and sets the
+/// error message if the file couldn't be decoded.
 #[wasm_bindgen]
 pub fn decode_wav(data: Vec<u8>) -> Vec<f32> {
   common::maybe_init(None);
   wbg_logging::maybe_init();
 
-  let mut reader = match hound::WavReader::new(data.as_slice()) {
-    Ok(r) => r,
-    Err(e) => {
-      error!("Error parsing wav file: {}", e);
+  let (spec, samples) = match wav::decode(&data) {
+    Ok(decoded) => decoded,
+    Err(err) => {
+      set_error("Error decoding wav file", err);
       unsafe {
-        ERROR_MESSAGE = format!("Error parsing wav file: {}", e);
+        LAST_DECODED_SAMPLE_RATE = 0;
+        LAST_DECODED_CHANNEL_COUNT = 0;
       }
       return Vec::new();
     },
   };
-
-  let spec = reader.spec();
   info!("{:?}", spec);
-  let res: Result<Vec<f32>, _> = match spec.sample_format {
-    hound::SampleFormat::Int => reader
-      .samples::<i32>()
-      .map(|res| {
-        let sample = res?;
-        Ok(sample as f32 / (1 << 15) as f32)
-      })
-      .collect::<Result<Vec<f32>, _>>(),
-    hound::SampleFormat::Float => reader
-      .into_samples::<f32>()
-      .collect::<Result<Vec<f32>, _>>(),
+  unsafe {
+    LAST_DECODED_SAMPLE_RATE = spec.sample_rate;
+    LAST_DECODED_CHANNEL_COUNT = spec.channel_count;
+  }
+  samples
+}
+
+/// `format`: 0 = 16-bit PCM, 1 = 24-bit PCM, 2 = 32-bit float
+///
+/// `dither`: 0 = none, 1 = flat TPDF, 2 = TPDF with first-order noise shaping, 3 = TPDF with
+/// E-weighted noise shaping.  Only applies to PCM formats; see `wav::dither`.
+///
+/// Returns an empty buffer and sets the error message if the parameters are invalid.
+#[wasm_bindgen]
+pub fn encode_wav(
+  samples: &[f32],
+  channel_count: u16,
+  sample_rate: u32,
+  format: u8,
+  dither: u8,
+) -> Vec<u8> {
+  let format = match format {
+    0 => SampleFormat::Pcm16,
+    1 => SampleFormat::Pcm24,
+    2 => SampleFormat::Float32,
+    _ => {
+      unsafe {
+        ERROR_MESSAGE = format!("Unsupported wav sample format: {}", format);
+      }
+      return Vec::new();
+    },
   };
-  match res {
-    Ok(samples) => samples,
-    Err(err) => {
-      error!("Error decoding wav file: {:?}", err);
+  let noise_shaping = match dither {
+    0 => None,
+    1 => Some(NoiseShaping::Flat),
+    2 => Some(NoiseShaping::FirstOrder),
+    3 => Some(NoiseShaping::EWeighted),
+    _ => {
       unsafe {
-        ERROR_MESSAGE = format!("Error decoding wav file: {:?}", err);
+        ERROR_MESSAGE = format!("Unsupported dither mode: {}", dither);
       }
+      return Vec::new();
+    },
+  };
+  let spec = WavSpec {
+    channel_count,
+    sample_rate,
+    format,
+  };
+
+  let encoded = match noise_shaping {
+    Some(noise_shaping) => wav::encode_dithered(&spec, samples, noise_shaping, 0),
+    None => wav::encode(&spec, samples),
+  };
+  match encoded {
+    Ok(encoded) => encoded,
+    Err(err) => {
+      set_error("Error encoding wav file", err);
       Vec::new()
     },
   }
 }
+
+/// Converts interleaved samples to `output_sample_rate` with the windowed-sinc resampler, for
+/// exporting at a different rate than the one audio was rendered at.  Should be called before
+/// `encode_wav` so that dithering is applied after resampling.
+#[wasm_bindgen]
+pub fn resample_for_export(
+  samples: &[f32],
+  channel_count: u16,
+  input_sample_rate: u32,
+  output_sample_rate: u32,
+) -> Vec<f32> {
+  if input_sample_rate == output_sample_rate || input_sample_rate == 0 || output_sample_rate == 0 {
+    return samples.to_owned();
+  }
+
+  resample_interleaved(
+    samples,
+    channel_count as usize,
+    input_sample_rate as f64,
+    output_sample_rate as f64,
+    ResamplerQuality::Sinc,
+  )
+}
+
+/// Decodes a WAV file a chunk at a time, for files too large to decode in one go
+#[wasm_bindgen]
+pub struct WavStreamDecoder {
+  inner: wav::StreamDecoder,
+  errored: bool,
+}
+
+#[wasm_bindgen]
+impl WavStreamDecoder {
+  #[wasm_bindgen(constructor)]
+  pub fn new() -> Self {
+    common::maybe_init(None);
+    wbg_logging::maybe_init();
+
+    WavStreamDecoder {
+      inner: wav::StreamDecoder::default(),
+      errored: false,
+    }
+  }
+
+  /// Returns the interleaved samples that could be decoded from `chunk` and any data left over
+  /// from previous chunks.  If decoding fails, `is_errored` will return true and the error message
+  /// will be set.
+  pub fn push(&mut self, chunk: &[u8]) -> Vec<f32> {
+    let mut samples = Vec::new();
+    if self.errored {
+      return samples;
+    }
+
+    if let Err(err) = self.inner.push(chunk, &mut samples) {
+      set_error("Error decoding wav file", err);
+      self.errored = true;
+    }
+    samples
+  }
+
+  /// Must be called after the last chunk has been pushed.  Returns false and sets the error
+  /// message if the file ended before any sample data was found.
+  pub fn finish(&mut self) -> bool {
+    if self.errored {
+      return false;
+    }
+
+    match self.inner.finish() {
+      Ok(()) => true,
+      Err(err) => {
+        set_error("Error decoding wav file", err);
+        self.errored = true;
+        false
+      },
+    }
+  }
+
+  pub fn is_errored(&self) -> bool { self.errored }
+
+  /// 0 until the format chunk has been decoded
+  pub fn sample_rate(&self) -> u32 { self.inner.spec().map(|s| s.sample_rate).unwrap_or(0) }
+
+  /// 0 until the format chunk has been decoded
+  pub fn channel_count(&self) -> u16 { self.inner.spec().map(|s| s.channel_count).unwrap_or(0) }
+}
@@ -2,10 +2,14 @@ use std::f32::consts::PI;
 
 use waveform_renderer::WaveformRendererCtx;
 
+#[cfg(target_arch = "wasm32")]
 extern "C" {
   fn log_err(s: *const u8, len: usize);
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+use common::native::log_err;
+
 const WAVEFORM_LENGTH_SAMPLES: usize = 1024 * 4;
 const HARMONIC_COUNT: usize = 64;
 const WAVEFORM_HEIGHT_PX: u32 = 256;
@@ -0,0 +1,471 @@
+//! Offline rendering of note sequences through an FM synth instance.  Rather than recording
+//! realtime playback, a copy of the synth is driven directly from a list of note events and
+//! rendered in chunks as fast as the caller wants into a growable buffer.
+//!
+//! Only the synth itself is rendered.  Anything that its output goes through in the audio graph,
+//! such as synth designer filters or effect nodes, isn't part of the bounce.
+//!
+//! Each event is tagged with a stem, and every voice's output goes to the buffer for the stem of
+//! the note it's playing.  This lets several tracks that share a synth be rendered to separate
+//! buffers in one pass.  Since voices are keyed by MIDI number, a note that's already held by one
+//! stem when another stem attacks it keeps playing on the first stem.
+
+use common::ffi::{self, ErrorCode, FfiResult};
+
+use dsp::{
+  profiling::Profiler,
+  smoothed_param::{SmoothedParam, SmoothingMode},
+};
+
+use super::{
+  control_events::CONTROL_EVENT_BUF_LEN, init_polysynth, morph::PresetMorph, uninit,
+  FMSynthContext, EFFECT_PARAM_VIZ_BUF_LEN, FRAME_SIZE, MASTER_GAIN_SMOOTHING_MS,
+  PITCH_BEND_SMOOTHING_MS,
+};
+
+/// Each event in the events buffer has the following format:
+///
+/// [0] = beat at which the event occurs
+/// [1] = MIDI number
+/// [2] = 1 for attack, 0 for release
+/// [3] = index of the stem that the note is rendered to
+pub const BOUNCE_EVENT_SIZE: usize = 4;
+/// Upper bound on the length of a single bounce to avoid running out of memory
+const MAX_BOUNCE_LEN_SECONDS: f32 = 60. * 30.;
+const MAX_STEM_COUNT: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BounceNoteEvent {
+  pub beat: f64,
+  pub note: usize,
+  pub is_attack: bool,
+  pub stem_ix: usize,
+}
+
+#[derive(Default)]
+pub struct Bouncer {
+  pub events_buf: Vec<f64>,
+  events: Vec<BounceNoteEvent>,
+  next_event_ix: usize,
+  bpm: f32,
+  len_samples: usize,
+  rendered_samples: usize,
+  /// The stem that each voice is currently rendering to, indexed by voice
+  voice_stems: Vec<usize>,
+  /// One buffer per stem, all the same length
+  pub outputs: Vec<Vec<f32>>,
+  /// Copy of the synth that the bounce is rendered with, set while a bounce is running
+  render_ctx: Option<Box<FMSynthContext>>,
+}
+
+impl Bouncer {
+  fn start(
+    &mut self,
+    event_count: usize,
+    stem_count: usize,
+    bpm: f32,
+    end_beat: f64,
+    tail_seconds: f32,
+  ) -> FfiResult {
+    ffi::check_range(
+      "event_count",
+      event_count,
+      0,
+      self.events_buf.len() / BOUNCE_EVENT_SIZE,
+    )?;
+    ffi::check_range("stem_count", stem_count, 1, MAX_STEM_COUNT)?;
+    ffi::check_range("bpm", bpm, 1., 1000.)?;
+    ffi::check_range("tail_seconds", tail_seconds, 0., 60.)?;
+    let len_seconds = end_beat as f32 * 60. / bpm + tail_seconds;
+    ffi::check_range("len_seconds", len_seconds, 0., MAX_BOUNCE_LEN_SECONDS)?;
+
+    self.events.clear();
+    for raw in self.events_buf[..event_count * BOUNCE_EVENT_SIZE].chunks_exact(BOUNCE_EVENT_SIZE) {
+      ffi::check_range("beat", raw[0], 0., f64::MAX)?;
+      ffi::check_range("note", raw[1], 0., 127.)?;
+      ffi::check_range("stem_ix", raw[3], 0., (stem_count - 1) as f64)?;
+      self.events.push(BounceNoteEvent {
+        beat: raw[0],
+        note: raw[1] as usize,
+        is_attack: raw[2] != 0.,
+        stem_ix: raw[3] as usize,
+      });
+    }
+    // Releases go before attacks on the same beat so that a note ending exactly where the next one
+    // on the same line starts doesn't cut off the new one
+    self.events.sort_by(|a, b| {
+      a.beat
+        .total_cmp(&b.beat)
+        .then(a.is_attack.cmp(&b.is_attack))
+    });
+
+    self.next_event_ix = 0;
+    self.bpm = bpm;
+    self.len_samples = (len_seconds * dsp::sample_rate()) as usize;
+    self.rendered_samples = 0;
+    self.outputs.truncate(stem_count);
+    self.outputs.resize_with(stem_count, Vec::new);
+    for output in &mut self.outputs {
+      output.clear();
+      output.reserve_exact(self.len_samples);
+    }
+    Ok(())
+  }
+
+  fn samples_per_beat(&self) -> f64 { dsp::sample_rate() as f64 * 60. / self.bpm as f64 }
+
+  fn is_done(&self) -> bool { self.rendered_samples >= self.len_samples }
+
+  /// Returns the events that occur before `end_beat` that haven't been returned yet
+  fn take_events_before(&mut self, end_beat: f64) -> &[BounceNoteEvent] {
+    let start_ix = self.next_event_ix;
+    while self
+      .events
+      .get(self.next_event_ix)
+      .map(|evt| evt.beat < end_beat)
+      .unwrap_or(false)
+    {
+      self.next_event_ix += 1;
+    }
+    &self.events[start_ix..self.next_event_ix]
+  }
+}
+
+/// Builds a copy of `ctx` to render a bounce with so that live playback through `ctx` carries on
+/// undisturbed while the bounce is running.  Later changes to `ctx` aren't picked up by the copy.
+///
+/// Every voice of the copy is a copy of an idle voice of `ctx` so that effect tails from notes
+/// being played live don't leak into the bounce.  Param buffers keep the values they had for the
+/// last rendered frame.
+unsafe fn fork_for_offline_render(ctx: &FMSynthContext) -> Box<FMSynthContext> {
+  let voice_count = ctx.voices.len();
+  let template_voice_ix = ctx
+    .voices
+    .iter()
+    .zip(ctx.base_frequency_input_buffer.iter())
+    .position(|(voice, base_frequency)| base_frequency[0] == 0. || voice.is_effectively_silent())
+    .unwrap_or(0);
+  let mut template_voice = ctx.voices[template_voice_ix].clone();
+  // Phases are only reported for the live synth's voices
+  for adsr in &mut template_voice.adsrs {
+    adsr.store_phase_to = None;
+  }
+  template_voice.gain_envelope_generator.adsr.store_phase_to = None;
+  template_voice.filter_envelope_generator.adsr.store_phase_to = None;
+
+  let render_ctx = Box::into_raw(Box::new(FMSynthContext {
+    voices: vec![template_voice; voice_count],
+    modulation_matrix: ctx.modulation_matrix.clone(),
+    param_buffers: ctx.param_buffers,
+    operator_base_frequency_sources: ctx.operator_base_frequency_sources.clone(),
+    base_frequency_input_buffer: vec![[0.; FRAME_SIZE]; voice_count],
+    output_buffers: vec![[[0.; FRAME_SIZE]; 2]; voice_count],
+    frequency_multiplier: ctx.frequency_multiplier,
+    master_gain: SmoothedParam::new(
+      SmoothingMode::Linear,
+      ctx.master_gain.target(),
+      MASTER_GAIN_SMOOTHING_MS,
+    ),
+    pitch_bend: SmoothedParam::new(SmoothingMode::Linear, 0., PITCH_BEND_SMOOTHING_MS),
+    tuning: ctx.tuning,
+    most_recent_gated_voice_ix: 0,
+    adsr_phase_buf: [0.; 256],
+    detune: ctx.detune.clone(),
+    wavetables: ctx.wavetables.clone(),
+    sample_mapping_manager: ctx.sample_mapping_manager.clone(),
+    polysynth: uninit(),
+    bouncer: Bouncer::default(),
+    profiler: Profiler::new(1 + voice_count),
+    preset_morph: PresetMorph::default(),
+    voice_layers: ctx.voice_layers.clone(),
+    panning: ctx.panning.clone(),
+    effect_param_viz_buf: Box::new([0.; EFFECT_PARAM_VIZ_BUF_LEN]),
+    control_event_buf: Box::new([0.; CONTROL_EVENT_BUF_LEN]),
+  }));
+  // The UI tracks the live synth's voices, so it isn't told about the bounce's
+  init_polysynth(render_ctx, false);
+  Box::from_raw(render_ctx)
+}
+
+/// Returns a pointer to a buffer with room for `event_count` events which should be filled in
+/// before calling `fm_synth_bounce_start`.  See `BOUNCE_EVENT_SIZE` for the layout.
+#[no_mangle]
+pub extern "C" fn fm_synth_bounce_get_events_buf_ptr(
+  ctx: *mut FMSynthContext,
+  event_count: usize,
+) -> *mut f64 {
+  let bouncer = match unsafe { ffi::handle(ctx, "fm_synth_bounce_get_events_buf_ptr") } {
+    Ok(ctx) => &mut ctx.bouncer,
+    Err(_) => return std::ptr::null_mut(),
+  };
+  bouncer
+    .events_buf
+    .resize(event_count * BOUNCE_EVENT_SIZE, 0.);
+  bouncer.events_buf.as_mut_ptr()
+}
+
+/// Starts bouncing the first `event_count` events from the events buffer to `stem_count` separate
+/// buffers.  The rendered audio will cover `end_beat` beats at `bpm` plus `tail_seconds` to let
+/// released notes ring out.
+///
+/// The bounce is rendered by a copy of the synth as it's currently configured, so the synth can
+/// keep playing live in the meantime.
+#[no_mangle]
+pub extern "C" fn fm_synth_bounce_start(
+  ctx: *mut FMSynthContext,
+  event_count: usize,
+  stem_count: usize,
+  bpm: f32,
+  end_beat: f64,
+  tail_seconds: f32,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "fm_synth_bounce_start") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ctx
+    .bouncer
+    .start(event_count, stem_count, bpm, end_beat, tail_seconds)
+  {
+    return code;
+  }
+
+  let render_ctx = unsafe { fork_for_offline_render(ctx) };
+  ctx.bouncer.voice_stems.clear();
+  ctx.bouncer.voice_stems.resize(render_ctx.voices.len(), 0);
+  ctx.bouncer.render_ctx = Some(render_ctx);
+  ErrorCode::Ok
+}
+
+/// Renders up to `max_frames` more frames of the current bounce.  Use
+/// `fm_synth_bounce_get_progress` to check when it's complete.
+#[no_mangle]
+pub extern "C" fn fm_synth_bounce_render(ctx: *mut FMSynthContext, max_frames: usize) -> ErrorCode {
+  let bouncer = match unsafe { ffi::handle(ctx, "fm_synth_bounce_render") } {
+    Ok(ctx) => &mut ctx.bouncer,
+    Err(code) => return code,
+  };
+  let render_ctx: *mut FMSynthContext = match bouncer.render_ctx.as_deref_mut() {
+    Some(render_ctx) => render_ctx,
+    None =>
+      return ffi::set_last_error(
+        ErrorCode::NotInitialized,
+        "fm_synth_bounce_render: no bounce is running",
+      ),
+  };
+  // Beat-synced effects read the global BPM
+  unsafe { crate::set_cur_bpm(bouncer.bpm) };
+
+  for _ in 0..max_frames {
+    if bouncer.is_done() {
+      break;
+    }
+
+    let frame_end_beat =
+      (bouncer.rendered_samples + FRAME_SIZE) as f64 / bouncer.samples_per_beat();
+    // Events are quantized to the start of the frame they fall in, same as live playback
+    let event_count = bouncer.take_events_before(frame_end_beat).len();
+    for event_ix in bouncer.next_event_ix - event_count..bouncer.next_event_ix {
+      let evt = bouncer.events[event_ix];
+      unsafe {
+        if evt.is_attack {
+          if let Some((voice_ix, note_id, velocity)) =
+            (*render_ctx).polysynth.trigger_attack_cb(evt.note, 0)
+          {
+            bouncer.voice_stems[voice_ix] = evt.stem_ix;
+            ((*render_ctx).polysynth.synth_cbs.trigger_attack)(voice_ix, note_id, velocity, None);
+          }
+        } else {
+          (*render_ctx).polysynth.trigger_release(evt.note, None);
+        }
+      }
+    }
+
+    let render_ctx = unsafe { &mut *render_ctx };
+    render_ctx.generate(bouncer.bpm, 0.);
+
+    let frame_len = (bouncer.len_samples - bouncer.rendered_samples).min(FRAME_SIZE);
+    let frame_start = bouncer.rendered_samples;
+    for output in &mut bouncer.outputs {
+      output.resize(frame_start + frame_len, 0.);
+    }
+    for ((base_frequency, output), &stem_ix) in render_ctx
+      .base_frequency_input_buffer
+      .iter()
+      .zip(render_ctx.output_buffers.iter())
+      .zip(bouncer.voice_stems.iter())
+    {
+      // Voices that have never been gated aren't rendered by `generate`
+      if base_frequency[0] == 0. {
+        continue;
+      }
+      // Bounces are mono, so stereo voices are mixed down.  Un-panned voices have identical
+      // channels, so this leaves them unchanged.
+      let [left, right] = output;
+      let stem = &mut bouncer.outputs[stem_ix][frame_start..];
+      for i in 0..frame_len {
+        stem[i] += (left[i] + right[i]) * 0.5;
+      }
+    }
+    bouncer.rendered_samples += frame_len;
+  }
+
+  ErrorCode::Ok
+}
+
+/// Returns the fraction of the current bounce that has been rendered so far; the bounce is complete
+/// once this returns 1.
+#[no_mangle]
+pub extern "C" fn fm_synth_bounce_get_progress(ctx: *mut FMSynthContext) -> f32 {
+  let bouncer = match unsafe { ffi::handle(ctx, "fm_synth_bounce_get_progress") } {
+    Ok(ctx) => &ctx.bouncer,
+    Err(_) => return 0.,
+  };
+  if bouncer.len_samples == 0 {
+    return 1.;
+  }
+  bouncer.rendered_samples as f32 / bouncer.len_samples as f32
+}
+
+/// Returns null if `stem_ix` is out of range
+#[no_mangle]
+pub extern "C" fn fm_synth_bounce_get_output_ptr(
+  ctx: *mut FMSynthContext,
+  stem_ix: usize,
+) -> *const f32 {
+  match unsafe { ffi::handle(ctx, "fm_synth_bounce_get_output_ptr") } {
+    Ok(ctx) => ctx
+      .bouncer
+      .outputs
+      .get(stem_ix)
+      .map_or(std::ptr::null(), |output| output.as_ptr()),
+    Err(_) => std::ptr::null(),
+  }
+}
+
+/// Returns 0 if `stem_ix` is out of range
+#[no_mangle]
+pub extern "C" fn fm_synth_bounce_get_output_len(
+  ctx: *mut FMSynthContext,
+  stem_ix: usize,
+) -> usize {
+  match unsafe { ffi::handle(ctx, "fm_synth_bounce_get_output_len") } {
+    Ok(ctx) => ctx.bouncer.outputs.get(stem_ix).map_or(0, Vec::len),
+    Err(_) => 0,
+  }
+}
+
+/// Frees the bounce's output buffers and the copy of the synth that rendered it.
+#[no_mangle]
+pub extern "C" fn fm_synth_bounce_finish(ctx: *mut FMSynthContext) -> ErrorCode {
+  let bouncer = match unsafe { ffi::handle(ctx, "fm_synth_bounce_finish") } {
+    Ok(ctx) => &mut ctx.bouncer,
+    Err(code) => return code,
+  };
+  bouncer.render_ctx = None;
+  bouncer.outputs = Vec::new();
+  bouncer.events.clear();
+  bouncer.len_samples = 0;
+  bouncer.rendered_samples = 0;
+  ErrorCode::Ok
+}
+
+#[test]
+fn bounce_events_are_sorted_and_taken_in_order() {
+  let mut bouncer = Bouncer::default();
+  bouncer.events_buf = vec![
+    2., 60., 1., 0., //
+    1., 60., 0., 0., //
+    0., 60., 1., 0., //
+    1., 60., 1., 0., //
+  ];
+  bouncer.start(4, 1, 120., 4., 0.).unwrap();
+
+  assert_eq!(bouncer.take_events_before(0.5), &[BounceNoteEvent {
+    beat: 0.,
+    note: 60,
+    is_attack: true,
+    stem_ix: 0,
+  }]);
+  let evts = bouncer.take_events_before(1.5);
+  assert_eq!(evts.len(), 2);
+  assert!(!evts[0].is_attack && evts[1].is_attack);
+  assert!(bouncer.take_events_before(1.5).is_empty());
+  assert_eq!(bouncer.take_events_before(10.).len(), 1);
+
+  assert_eq!(bouncer.len_samples, dsp::DEFAULT_SAMPLE_RATE as usize * 2);
+
+  for sample_rate in [44_100., 48_000., 96_000.] {
+    assert!(dsp::set_sample_rate(sample_rate));
+    bouncer.start(4, 1, 120., 4., 1.).unwrap();
+    // 4 beats at 120 BPM plus a second of tail
+    assert_eq!(bouncer.len_samples, sample_rate as usize * 3);
+    assert_eq!(bouncer.samples_per_beat(), sample_rate as f64 / 2.);
+  }
+}
+
+#[test]
+fn bounce_stems_are_validated_and_allocated() {
+  let mut bouncer = Bouncer::default();
+  bouncer.events_buf = vec![
+    0., 60., 1., 0., //
+    0., 64., 1., 2., //
+  ];
+  assert!(bouncer.start(2, 2, 120., 4., 0.).is_err());
+  assert!(bouncer.start(2, 0, 120., 4., 0.).is_err());
+
+  bouncer.start(2, 3, 120., 4., 0.).unwrap();
+  assert_eq!(bouncer.outputs.len(), 3);
+  let stems: Vec<usize> = bouncer
+    .take_events_before(1.)
+    .iter()
+    .map(|evt| evt.stem_ix)
+    .collect();
+  assert_eq!(stems, vec![0, 2]);
+
+  bouncer.events_buf.truncate(BOUNCE_EVENT_SIZE);
+  bouncer.start(1, 1, 120., 4., 0.).unwrap();
+  assert_eq!(bouncer.outputs.len(), 1);
+}
+
+#[test]
+fn bouncing_leaves_live_playback_alone() {
+  let ctx = unsafe { super::init_fm_synth_ctx(2) };
+  let first_voice_base_frequency = || unsafe { (&*ctx).base_frequency_input_buffer[0][0] };
+  assert_eq!(unsafe { super::gate(ctx, 60) }, ErrorCode::Ok);
+  let live_base_frequency = first_voice_base_frequency();
+  assert_ne!(live_base_frequency, 0.);
+
+  let events_buf = fm_synth_bounce_get_events_buf_ptr(ctx, 2);
+  unsafe {
+    std::slice::from_raw_parts_mut(events_buf, 2 * BOUNCE_EVENT_SIZE).copy_from_slice(&[
+      0., 64., 1., 0., //
+      1., 64., 0., 0., //
+    ])
+  };
+  assert_eq!(
+    fm_synth_bounce_start(ctx, 2, 1, 120., 2., 0.),
+    ErrorCode::Ok
+  );
+  while fm_synth_bounce_get_progress(ctx) < 1. {
+    assert_eq!(fm_synth_bounce_render(ctx, 16), ErrorCode::Ok);
+    // Live frames are rendered in between chunks of the bounce
+    unsafe { (*ctx).generate(120., 0.) };
+  }
+
+  let len = fm_synth_bounce_get_output_len(ctx, 0);
+  assert_eq!(len, dsp::sample_rate() as usize);
+  assert!(fm_synth_bounce_get_output_ptr(ctx, 1).is_null());
+  let render_ctx = unsafe { (*ctx).bouncer.render_ctx.as_ref().unwrap() };
+  assert_ne!(render_ctx.base_frequency_input_buffer[0][0], 0.);
+  assert_ne!(
+    render_ctx.base_frequency_input_buffer[0][0],
+    live_base_frequency
+  );
+
+  assert_eq!(fm_synth_bounce_finish(ctx), ErrorCode::Ok);
+  assert!(unsafe { (*ctx).bouncer.render_ctx.is_none() });
+  // The note that was playing live when the bounce started is still playing
+  assert_eq!(first_voice_base_frequency(), live_base_frequency);
+  assert_eq!(fm_synth_bounce_render(ctx, 1), ErrorCode::NotInitialized);
+}
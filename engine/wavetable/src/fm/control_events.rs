@@ -0,0 +1,212 @@
+//! Control events applied in a batch at the start of each frame.  Rather than posting a message to
+//! the AWP and making an FFI call for every note and param change, the UI writes events into a
+//! lock-free ring buffer in shared memory (see `src/eventRing.ts`).  `FMSynthAWP` copies the events
+//! that are due during the next frame into the control event buffer along with any MIDI events from
+//! its mailbox, and they're all applied with a single call to `fm_synth_apply_control_events`.
+//!
+//! Each event includes its offset into the frame in samples.  Events are applied in order of their
+//! offsets.  Gates and ungates take effect at their offsets: voice envelopes render the part of
+//! the frame before the event and the part after it separately.  Param changes are smoothed over
+//! much longer than a frame, so they're applied at the start of the frame.
+
+use common::ffi::{self, ErrorCode, FfiResult};
+
+use super::{
+  fm_synth_set_frequency_multiplier, fm_synth_set_master_gain, fm_synth_set_midi_control_value,
+  fm_synth_set_pitch_bend, gate_at, morph::fm_synth_set_morph_position, ungate_all_at, ungate_at,
+  FMSynthContext, FRAME_SIZE,
+};
+
+/// Each event in the control event buffer has the following format:
+///
+/// [0] = event type
+/// [1] = offset of the event into the frame in samples
+/// [2] = first param
+/// [3] = second param
+///
+/// gate: MIDI number
+/// ungate: MIDI number
+/// ungate all
+/// pitch bend: semitones
+/// master gain: gain
+/// frequency multiplier: multiplier
+/// morph position: position
+/// MIDI control value: control index, value in [0, 127]
+pub const CONTROL_EVENT_SIZE: usize = 4;
+pub const CONTROL_EVENT_TYPE_GATE: u32 = 0;
+pub const CONTROL_EVENT_TYPE_UNGATE: u32 = 1;
+pub const CONTROL_EVENT_TYPE_UNGATE_ALL: u32 = 2;
+pub const CONTROL_EVENT_TYPE_PITCH_BEND: u32 = 3;
+pub const CONTROL_EVENT_TYPE_MASTER_GAIN: u32 = 4;
+pub const CONTROL_EVENT_TYPE_FREQUENCY_MULTIPLIER: u32 = 5;
+pub const CONTROL_EVENT_TYPE_MORPH_POSITION: u32 = 6;
+pub const CONTROL_EVENT_TYPE_MIDI_CONTROL_VALUE: u32 = 7;
+/// Events past this many in a single frame are left in the ring until the next frame
+pub const MAX_CONTROL_EVENTS_PER_FRAME: usize = 128;
+pub const CONTROL_EVENT_BUF_LEN: usize = MAX_CONTROL_EVENTS_PER_FRAME * CONTROL_EVENT_SIZE;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlEvent {
+  Gate { midi_number: usize },
+  Ungate { midi_number: usize },
+  UngateAll,
+  PitchBend { semitones: f32 },
+  MasterGain { gain: f32 },
+  FrequencyMultiplier { multiplier: f32 },
+  MorphPosition { position: f32 },
+  MIDIControlValue { index: usize, value: usize },
+}
+
+/// Returns the event's offset into the frame along with the decoded event
+fn decode_control_event(raw: &[f32]) -> FfiResult<(usize, ControlEvent)> {
+  let [event_type, sample_offset, param_0, param_1] = raw else {
+    unreachable!()
+  };
+  ffi::check_range("sample_offset", *sample_offset, 0., (FRAME_SIZE - 1) as f32)?;
+  let int_param = |name: &str, val: f32, max: f32| -> FfiResult<usize> {
+    ffi::check_range(name, val, 0., max)?;
+    Ok(val as usize)
+  };
+
+  let event = match int_param("event_type", *event_type, f32::MAX)? as u32 {
+    CONTROL_EVENT_TYPE_GATE => ControlEvent::Gate {
+      midi_number: int_param("midi_number", *param_0, 127.)?,
+    },
+    CONTROL_EVENT_TYPE_UNGATE => ControlEvent::Ungate {
+      midi_number: int_param("midi_number", *param_0, 127.)?,
+    },
+    CONTROL_EVENT_TYPE_UNGATE_ALL => ControlEvent::UngateAll,
+    CONTROL_EVENT_TYPE_PITCH_BEND => ControlEvent::PitchBend {
+      semitones: ffi::clamp_param("semitones", *param_0, -48., 48.)?,
+    },
+    CONTROL_EVENT_TYPE_MASTER_GAIN => ControlEvent::MasterGain {
+      gain: ffi::clamp_param("gain", *param_0, 0., f32::MAX)?,
+    },
+    CONTROL_EVENT_TYPE_FREQUENCY_MULTIPLIER => ControlEvent::FrequencyMultiplier {
+      multiplier: ffi::clamp_param("multiplier", *param_0, 0., f32::MAX)?,
+    },
+    CONTROL_EVENT_TYPE_MORPH_POSITION => ControlEvent::MorphPosition {
+      position: ffi::clamp_param("position", *param_0, 0., 1.)?,
+    },
+    CONTROL_EVENT_TYPE_MIDI_CONTROL_VALUE => ControlEvent::MIDIControlValue {
+      index: int_param("index", *param_0, 1023.)?,
+      value: int_param("value", *param_1, 127.)?,
+    },
+    _ =>
+      return Err(ffi::set_last_error(
+        ErrorCode::Unsupported,
+        &format!("unknown control event type: {event_type}"),
+      )),
+  };
+  Ok((*sample_offset as usize, event))
+}
+
+unsafe fn apply_control_event(
+  ctx: *mut FMSynthContext,
+  sample_offset: usize,
+  event: ControlEvent,
+) -> FfiResult {
+  match event {
+    ControlEvent::Gate { midi_number } => gate_at(ctx, midi_number, sample_offset),
+    ControlEvent::Ungate { midi_number } => ungate_at(ctx, midi_number, sample_offset),
+    ControlEvent::UngateAll => ungate_all_at(ctx, sample_offset),
+    ControlEvent::PitchBend { semitones } => ffi::result(fm_synth_set_pitch_bend(ctx, semitones))?,
+    ControlEvent::MasterGain { gain } => ffi::result(fm_synth_set_master_gain(ctx, gain))?,
+    ControlEvent::FrequencyMultiplier { multiplier } =>
+      ffi::result(fm_synth_set_frequency_multiplier(ctx, multiplier))?,
+    ControlEvent::MorphPosition { position } =>
+      ffi::result(fm_synth_set_morph_position(ctx, position))?,
+    ControlEvent::MIDIControlValue { index, value } =>
+      ffi::result(fm_synth_set_midi_control_value(index, value))?,
+  }
+  Ok(())
+}
+
+/// Returns a pointer to the buffer that events should be written to before calling
+/// `fm_synth_apply_control_events`, or null if `ctx` is.  See `CONTROL_EVENT_SIZE` for the layout.
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_get_control_event_buf_ptr(ctx: *mut FMSynthContext) -> *mut f32 {
+  match unsafe { ffi::handle(ctx, "fm_synth_get_control_event_buf_ptr") } {
+    Ok(ctx) => ctx.control_event_buf.as_mut_ptr(),
+    Err(_) => std::ptr::null_mut(),
+  }
+}
+
+/// Applies the first `event_count` events in the control event buffer.  Since events come from a
+/// realtime stream, invalid events are skipped rather than rejecting the whole batch; the error of
+/// the first invalid event is returned.
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_apply_control_events(
+  ctx: *mut FMSynthContext,
+  event_count: usize,
+) -> ErrorCode {
+  let ctx_ref = match unsafe { ffi::handle(ctx, "fm_synth_apply_control_events") } {
+    Ok(ctx_ref) => ctx_ref,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("event_count", event_count, 0, MAX_CONTROL_EVENTS_PER_FRAME) {
+    return code;
+  }
+
+  let mut events = [(0, ControlEvent::UngateAll); MAX_CONTROL_EVENTS_PER_FRAME];
+  let mut valid_event_count = 0;
+  let mut status = ErrorCode::Ok;
+  for raw in
+    ctx_ref.control_event_buf[..event_count * CONTROL_EVENT_SIZE].chunks_exact(CONTROL_EVENT_SIZE)
+  {
+    match decode_control_event(raw) {
+      Ok(event) => {
+        events[valid_event_count] = event;
+        valid_event_count += 1;
+      },
+      Err(code) =>
+        if status == ErrorCode::Ok {
+          status = code;
+        },
+    }
+  }
+
+  // Stable so that events at the same offset are applied in the order they were written
+  let events = &mut events[..valid_event_count];
+  events.sort_by_key(|(sample_offset, _)| *sample_offset);
+  for &(sample_offset, event) in events.iter() {
+    if let Err(code) = apply_control_event(ctx, sample_offset, event) {
+      if status == ErrorCode::Ok {
+        status = code;
+      }
+    }
+  }
+  status
+}
+
+#[test]
+fn decodes_control_events() {
+  assert_eq!(
+    decode_control_event(&[CONTROL_EVENT_TYPE_GATE as f32, 12., 60., 0.]),
+    Ok((12, ControlEvent::Gate { midi_number: 60 }))
+  );
+  assert_eq!(
+    decode_control_event(&[CONTROL_EVENT_TYPE_MIDI_CONTROL_VALUE as f32, 0., 7., 100.]),
+    Ok((0, ControlEvent::MIDIControlValue {
+      index: 7,
+      value: 100
+    }))
+  );
+  assert_eq!(
+    decode_control_event(&[
+      CONTROL_EVENT_TYPE_MASTER_GAIN as f32,
+      FRAME_SIZE as f32,
+      1.,
+      0.
+    ]),
+    Err(ErrorCode::ParamOutOfRange)
+  );
+  assert_eq!(
+    decode_control_event(&[CONTROL_EVENT_TYPE_UNGATE as f32, 0., f32::NAN, 0.]),
+    Err(ErrorCode::ParamOutOfRange)
+  );
+  assert_eq!(
+    decode_control_event(&[99., 0., 0., 0.]),
+    Err(ErrorCode::Unsupported)
+  );
+}
@@ -48,7 +48,7 @@ impl Bitcrusher {
 impl Effect for Bitcrusher {
   fn apply(&mut self, rendered_params: &[f32], _base_frequency: f32, sample: f32) -> f32 {
     let sample_rate = (unsafe { *rendered_params.get_unchecked(0) }).max(1.);
-    let undersample_ratio = sample_rate / 44_100.;
+    let undersample_ratio = sample_rate / dsp::sample_rate();
     let sample_hold_time = 1. / undersample_ratio;
     self.samples_since_last_sample += 1;
     if (self.samples_since_last_sample as f32) < sample_hold_time {
@@ -1,9 +1,10 @@
 use dsp::circular_buffer::CircularBuffer;
 
 use super::Effect;
-use crate::fm::{ParamSource, FRAME_SIZE, SAMPLE_RATE};
+use crate::fm::{ParamSource, FRAME_SIZE};
 
-const MAX_DELAY_SAMPLES: usize = SAMPLE_RATE * 4;
+/// Four seconds at the default sample rate
+const MAX_DELAY_SAMPLES: usize = dsp::DEFAULT_SAMPLE_RATE as usize * 4;
 
 #[derive(Clone)]
 pub struct CombFilter {
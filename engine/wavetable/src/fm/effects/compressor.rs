@@ -29,8 +29,8 @@ impl Effect for CompressorEffect {
     if self.cur_frame_ix == FRAME_SIZE {
       self.cur_frame_ix = 0;
       self.inner.apply(
-        1., 1., 1., 1., 1., 1., 3., 250., 3., 250., 3., 250., -34., -34., -34., -24., -24., -24.,
-        1., 1., 1., 12., 12., 12., 30., 256, 1., 1., 1.,
+        FRAME_SIZE, 1., 1., 1., 1., 1., 1., 3., 250., 3., 250., 3., 250., false, false, false,
+        -34., -34., -34., -24., -24., -24., 1., 1., 1., 12., 12., 12., 30., 256, 256, 1., 1., 1.,
       );
     }
 
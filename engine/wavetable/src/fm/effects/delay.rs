@@ -1,17 +1,46 @@
-use dsp::circular_buffer::CircularBuffer;
+use dsp::{
+  delay_line::{DelayInterpolation, DelayLine},
+  smoothed_param::{SmoothedParam, SmoothingMode},
+};
 
 use super::Effect;
-use crate::fm::{ParamSource, SAMPLE_RATE};
+use crate::fm::ParamSource;
 
-const MAX_DELAY_SAMPLES: usize = SAMPLE_RATE * 10;
+/// Ten seconds at the default sample rate
+const MAX_DELAY_SAMPLES: usize = dsp::DEFAULT_SAMPLE_RATE as usize * 10;
+/// Rounded up to a power of two so that indexing into the buffer is a mask
+const BUFFER_SIZE: usize = MAX_DELAY_SAMPLES.next_power_of_two();
+const MIX_SMOOTHING_MS: f32 = 5.;
 
 #[derive(Clone)]
 pub struct Delay {
-  pub buffer: Box<CircularBuffer<MAX_DELAY_SAMPLES>>,
+  pub delay_line: Box<DelayLine<BUFFER_SIZE>>,
   pub delay_samples: ParamSource,
   pub wet: ParamSource,
   pub dry: ParamSource,
   pub feedback: ParamSource,
+  /// Wet and dry levels can be changed abruptly from the UI, so they're smoothed to avoid clicks
+  pub wet_smoother: SmoothedParam,
+  pub dry_smoother: SmoothedParam,
+}
+
+impl Delay {
+  pub fn new(
+    delay_samples: ParamSource,
+    wet: ParamSource,
+    dry: ParamSource,
+    feedback: ParamSource,
+  ) -> Self {
+    Delay {
+      delay_line: Box::new(DelayLine::new(DelayInterpolation::Linear)),
+      delay_samples,
+      wet,
+      dry,
+      feedback,
+      wet_smoother: SmoothedParam::new(SmoothingMode::Exponential, 0., MIX_SMOOTHING_MS),
+      dry_smoother: SmoothedParam::new(SmoothingMode::Exponential, 1., MIX_SMOOTHING_MS),
+    }
+  }
 }
 
 impl Effect for Delay {
@@ -23,12 +52,19 @@ impl Effect for Delay {
   }
 
   fn apply(&mut self, rendered_params: &[f32], _base_frequency: f32, sample: f32) -> f32 {
-    let delay_samples = dsp::clamp(0., (MAX_DELAY_SAMPLES - 2) as f32, rendered_params[0]);
-    let wet = dsp::clamp(0., 1., rendered_params[1]);
-    let dry = dsp::clamp(0., 1., rendered_params[2]);
+    self
+      .delay_line
+      .set_delay_samples(rendered_params[0].min(MAX_DELAY_SAMPLES as f32));
+    self
+      .wet_smoother
+      .set_target(dsp::clamp(0., 1., rendered_params[1]));
+    self
+      .dry_smoother
+      .set_target(dsp::clamp(0., 1., rendered_params[2]));
+    let wet = self.wet_smoother.tick();
+    let dry = self.dry_smoother.tick();
     let feedback = dsp::clamp(0., 1., rendered_params[3]);
-    let delayed_sample = self.buffer.read_interpolated(-delay_samples);
-    self.buffer.set(sample + (delayed_sample * feedback));
+    let delayed_sample = self.delay_line.process_with_feedback(sample, feedback);
 
     (sample * dry) + (delayed_sample * wet)
   }
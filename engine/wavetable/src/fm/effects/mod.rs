@@ -1,5 +1,8 @@
 use ::compressor::MultibandCompressor;
-use dsp::circular_buffer::CircularBuffer;
+use dsp::{
+  circular_buffer::CircularBuffer,
+  smoothed_param::{SmoothedParam, SmoothingMode},
+};
 use soft_clipper::SoftClipper;
 use spectral_warping::SpectralWarpingParams;
 
@@ -236,37 +239,36 @@ impl EffectInstance {
         EffectInstance::ButterworthFilter(ButterworthFilter::new(mode, cutoff_freq))
       },
       6 => {
-        let delay = Delay {
-          buffer: Box::new(CircularBuffer::new()),
-          delay_samples: ParamSource::from_parts(
+        let delay = Delay::new(
+          ParamSource::from_parts(
             param_1_type,
             param_1_int_val,
             param_1_float_val,
             param_1_float_val_2,
             param_1_float_val_3,
           ),
-          wet: ParamSource::from_parts(
+          ParamSource::from_parts(
             param_2_type,
             param_2_int_val,
             param_2_float_val,
             param_2_float_val_2,
             param_2_float_val_3,
           ),
-          dry: ParamSource::from_parts(
+          ParamSource::from_parts(
             param_3_type,
             param_3_int_val,
             param_3_float_val,
             param_3_float_val_2,
             param_3_float_val_3,
           ),
-          feedback: ParamSource::from_parts(
+          ParamSource::from_parts(
             param_4_type,
             param_4_int_val,
             param_4_float_val,
             param_4_float_val_2,
             param_4_float_val_3,
           ),
-        };
+        );
 
         EffectInstance::Delay(delay)
       },
@@ -650,16 +652,74 @@ impl Effect for EffectInstance {
   }
 }
 
+/// Ramp time for changes to an effect's wet/dry mix, including fading it in and out when it's
+/// bypassed
+const MIX_SMOOTHING_MS: f32 = 20.;
+/// Time taken to fade the chain out to its dry input before re-ordering effects, and back in after
+const REORDER_FADE_MS: f32 = 10.;
+
 #[derive(Clone)]
 pub struct EffectContainer {
   pub inst: Box<EffectInstance>,
   pub is_bypassed: bool,
+  /// Wet/dry mix in [0, 1] where 1 is fully wet
+  pub mix: f32,
+  /// The mix that's actually applied.  It follows `mix`, but fades to 0 while the effect is
+  /// bypassed so that toggling bypass mid-note doesn't click.
+  applied_mix: SmoothedParam,
+}
+
+impl EffectContainer {
+  /// Creates a new container that fades in from dry
+  fn new(inst: Box<EffectInstance>, is_bypassed: bool) -> Self {
+    let mut container = EffectContainer {
+      inst,
+      is_bypassed,
+      mix: 1.,
+      applied_mix: SmoothedParam::new(SmoothingMode::Linear, 0., MIX_SMOOTHING_MS),
+    };
+    container.update_applied_mix();
+    container
+  }
+
+  fn update_applied_mix(&mut self) {
+    self
+      .applied_mix
+      .set_target(if self.is_bypassed { 0. } else { self.mix });
+  }
+
+  /// Returns `true` if this effect has finished fading out and doesn't need to be run at all
+  fn is_silent(&self) -> bool {
+    self.applied_mix.target() == 0. && !self.applied_mix.is_smoothing()
+  }
+
+  fn is_fully_wet(&self) -> bool {
+    self.applied_mix.target() == 1. && !self.applied_mix.is_smoothing()
+  }
+
+  #[inline]
+  fn mix_sample(&mut self, dry: f32, wet: f32) -> f32 {
+    let mix = self.applied_mix.tick();
+    dry + (wet - dry) * mix
+  }
 }
 
+/// Number of effect types that can be constructed by `EffectInstance::from_parts`, one per variant
+pub const EFFECT_TYPE_COUNT: usize = std::mem::variant_count::<EffectInstance>();
+pub const MAX_EFFECT_COUNT: usize = 16;
+
 #[derive(Clone)]
 pub struct EffectChain {
-  effects: [Option<EffectContainer>; 16],
-  param_render_buf: Box<[[[f32; FRAME_SIZE]; 4]; 16]>,
+  effects: [Option<EffectContainer>; MAX_EFFECT_COUNT],
+  param_render_buf: Box<[[[f32; FRAME_SIZE]; 4]; MAX_EFFECT_COUNT]>,
+  /// Crossfade between the chain's dry input (0) and its output (1).  Re-ordering effects is done
+  /// while this is at 0 so that the jump in the chain's output isn't audible.
+  reorder_fade: SmoothedParam,
+  /// `(from_ix, to_ix)` moves waiting for `reorder_fade` to reach 0 before they're applied
+  pending_moves: Vec<(usize, usize)>,
+  /// Value of each effect's params at the start of the most recently rendered frame, after
+  /// modulation.  Used to visualize the modulation in the UI.
+  rendered_param_snapshot: [[f32; 4]; MAX_EFFECT_COUNT],
 }
 
 impl Default for EffectChain {
@@ -670,6 +730,9 @@ impl Default for EffectChain {
         None,
       ],
       param_render_buf: Box::new(uninit()),
+      reorder_fade: SmoothedParam::new(SmoothingMode::Linear, 1., REORDER_FADE_MS),
+      pending_moves: Vec::new(),
+      rendered_param_snapshot: [[0.; 4]; MAX_EFFECT_COUNT],
     }
   }
 }
@@ -727,12 +790,13 @@ impl EffectChain {
       );
       if successfully_updated {
         effect.is_bypassed = is_bypassed;
+        effect.update_applied_mix();
         return;
       }
     }
 
-    self.effects[effect_ix] = Some(EffectContainer {
-      inst: Box::new(EffectInstance::from_parts(
+    self.effects[effect_ix] = Some(EffectContainer::new(
+      Box::new(EffectInstance::from_parts(
         effect_type,
         param_1_type,
         param_1_int_val,
@@ -756,7 +820,59 @@ impl EffectChain {
         param_4_float_val_3,
       )),
       is_bypassed,
-    });
+    ));
+  }
+
+  /// Fades the effect out or back in.  Bypassed effects stop being run once they're fully faded
+  /// out.
+  pub fn set_bypassed(&mut self, effect_ix: usize, is_bypassed: bool) {
+    if let Some(effect) = &mut self.effects[effect_ix] {
+      effect.is_bypassed = is_bypassed;
+      effect.update_applied_mix();
+    }
+  }
+
+  pub fn set_mix(&mut self, effect_ix: usize, mix: f32) {
+    if let Some(effect) = &mut self.effects[effect_ix] {
+      effect.mix = dsp::clamp(0., 1., mix);
+      effect.update_applied_mix();
+    }
+  }
+
+  /// Moves the effect at `from_ix` to `to_ix`, shifting the effects in between over by one.  The
+  /// chain is briefly crossfaded to its dry input while the move happens.
+  pub fn move_effect(&mut self, from_ix: usize, to_ix: usize) {
+    if from_ix == to_ix || self.effects[from_ix].is_none() || self.effects[to_ix].is_none() {
+      return;
+    }
+
+    self.pending_moves.push((from_ix, to_ix));
+    self.reorder_fade.set_target(0.);
+  }
+
+  /// Applies any pending moves once the chain has fully faded out to dry.  Called at the start of
+  /// each frame before params are rendered.
+  fn maybe_apply_pending_moves(&mut self) {
+    if self.pending_moves.is_empty() || self.reorder_fade.current() != 0. {
+      return;
+    }
+
+    for (from_ix, to_ix) in self.pending_moves.drain(..) {
+      if from_ix < to_ix {
+        self.effects[from_ix..=to_ix].rotate_left(1);
+      } else {
+        self.effects[to_ix..=from_ix].rotate_right(1);
+      }
+    }
+    self.reorder_fade.set_target(1.);
+  }
+
+  pub fn rendered_param_snapshot(&self) -> &[[f32; 4]; MAX_EFFECT_COUNT] {
+    &self.rendered_param_snapshot
+  }
+
+  fn is_reordering(&self) -> bool {
+    self.reorder_fade.target() != 1. || self.reorder_fade.is_smoothing()
   }
 
   pub fn remove_effect(&mut self, effect_ix: usize) {
@@ -791,12 +907,24 @@ fn render_effect_params<'a, E: Effect>(
   4
 }
 
+fn snapshot_rendered_params(
+  snapshot: &mut [f32; 4],
+  rendered_params: &[[f32; FRAME_SIZE]; 4],
+  param_count: usize,
+) {
+  for (snapshot_val, rendered) in snapshot.iter_mut().zip(rendered_params).take(param_count) {
+    *snapshot_val = rendered[0];
+  }
+}
+
 impl EffectChain {
   pub fn pre_render_params<'a>(&mut self, render_params: &RenderRawParams<'a>) {
+    self.maybe_apply_pending_moves();
+
     for (effect_ix, effect) in self.effects.iter_mut().enumerate() {
       let effect = match effect {
         Some(effect_container) =>
-          if effect_container.is_bypassed {
+          if effect_container.is_silent() {
             continue;
           } else {
             &mut effect_container.inst
@@ -805,12 +933,17 @@ impl EffectChain {
       };
 
       let buffers = unsafe { self.param_render_buf.get_unchecked_mut(effect_ix) };
-      render_effect_params(&mut **effect, buffers, render_params);
+      let param_count = render_effect_params(&mut **effect, buffers, render_params);
+      snapshot_rendered_params(
+        &mut self.rendered_param_snapshot[effect_ix],
+        buffers,
+        param_count,
+      );
     }
   }
 
   fn get_rendered_param(
-    param_render_buf: &[[[f32; FRAME_SIZE]; 4]; 16],
+    param_render_buf: &[[[f32; FRAME_SIZE]; 4]; MAX_EFFECT_COUNT],
     effect_ix: usize,
     param_ix: usize,
     sample_ix_within_frame: usize,
@@ -833,12 +966,12 @@ impl EffectChain {
 
     let mut params_for_sample: [f32; 4] = uninit();
     for (effect_ix, effect) in self.effects.iter_mut().enumerate() {
-      let effect = match effect {
+      let effect_container = match effect {
         Some(effect_container) =>
-          if effect_container.is_bypassed {
+          if effect_container.is_silent() {
             continue;
           } else {
-            &mut effect_container.inst
+            effect_container
           },
         None => break,
       };
@@ -852,7 +985,19 @@ impl EffectChain {
         );
       }
 
-      output = effect.apply(&params_for_sample, base_frequency, output);
+      let wet = effect_container
+        .inst
+        .apply(&params_for_sample, base_frequency, output);
+      output = if effect_container.is_fully_wet() {
+        wet
+      } else {
+        effect_container.mix_sample(output, wet)
+      };
+    }
+
+    if self.is_reordering() {
+      let fade = self.reorder_fade.tick();
+      output = sample + (output - sample) * fade;
     }
     output
   }
@@ -862,23 +1007,146 @@ impl EffectChain {
     render_params: &RenderRawParams<'a>,
     samples: &mut [f32; FRAME_SIZE],
   ) {
-    let mut rendered_params: [[f32; FRAME_SIZE]; 4] = uninit();
+    self.maybe_apply_pending_moves();
+    let is_reordering = self.is_reordering();
+    let chain_input = if is_reordering { Some(*samples) } else { None };
 
-    for effect in &mut self.effects {
-      let effect = match effect {
+    let mut rendered_params: [[f32; FRAME_SIZE]; 4] = uninit();
+    for (effect_ix, effect) in self.effects.iter_mut().enumerate() {
+      let effect_container = match effect {
         Some(effect_container) =>
-          if effect_container.is_bypassed {
+          if effect_container.is_silent() {
             continue;
           } else {
-            &mut effect_container.inst
+            effect_container
           },
-        None => return,
+        None => break,
       };
+      let is_fully_wet = effect_container.is_fully_wet();
+      let effect = &mut effect_container.inst;
       let param_count = render_effect_params(&mut **effect, &mut rendered_params, render_params);
+      snapshot_rendered_params(
+        &mut self.rendered_param_snapshot[effect_ix],
+        &rendered_params,
+        param_count,
+      );
       let rendered_params =
         unsafe { std::slice::from_raw_parts(rendered_params.as_ptr(), param_count) };
 
-      effect.apply_all(rendered_params, &render_params.base_frequencies, samples);
+      if is_fully_wet {
+        effect.apply_all(rendered_params, &render_params.base_frequencies, samples);
+      } else {
+        let dry = *samples;
+        effect.apply_all(rendered_params, &render_params.base_frequencies, samples);
+        for (sample, dry) in samples.iter_mut().zip(dry) {
+          *sample = effect_container.mix_sample(dry, *sample);
+        }
+      }
     }
+
+    if let Some(chain_input) = chain_input {
+      for (sample, dry) in samples.iter_mut().zip(chain_input) {
+        let fade = self.reorder_fade.tick();
+        *sample = dry + (*sample - dry) * fade;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+fn render_test_frame(chain: &mut EffectChain, input: f32) -> [f32; FRAME_SIZE] {
+  let render_params = RenderRawParams {
+    param_buffers: &[],
+    adsrs: &[],
+    base_frequencies: &[440.; FRAME_SIZE],
+  };
+  let mut samples = [input; FRAME_SIZE];
+  chain.apply_all(&render_params, &mut samples);
+  samples
+}
+
+#[cfg(test)]
+fn set_test_wavefolder(chain: &mut EffectChain, effect_ix: usize, gain: f32) {
+  // Built directly since `EffectInstance::from_parts` overflows the test thread's stack in debug
+  // builds
+  let wavefolder = Wavefolder::new(
+    ParamSource::new_constant(gain),
+    ParamSource::new_constant(0.),
+  );
+  chain.effects[effect_ix] = Some(EffectContainer::new(
+    Box::new(EffectInstance::Wavefolder(wavefolder)),
+    false,
+  ));
+}
+
+#[test]
+fn bypass_and_reordering_crossfade_instead_of_jumping() {
+  let mut chain = EffectChain::default();
+  set_test_wavefolder(&mut chain, 0, 3.);
+  // New effects fade in from dry
+  let first_frame = render_test_frame(&mut chain, 0.3);
+  assert!((first_frame[0] - 0.3).abs() < 0.01);
+  for _ in 0..20 {
+    render_test_frame(&mut chain, 0.3);
+  }
+  let wet = render_test_frame(&mut chain, 0.3)[FRAME_SIZE - 1];
+  assert!((wet - 0.3).abs() > 0.05);
+
+  chain.set_mix(0, 0.5);
+  for _ in 0..20 {
+    render_test_frame(&mut chain, 0.3);
+  }
+  let half_wet = render_test_frame(&mut chain, 0.3)[0];
+  assert!((half_wet - (0.3 + (wet - 0.3) * 0.5)).abs() < 0.0001);
+
+  chain.set_bypassed(0, true);
+  let frame = render_test_frame(&mut chain, 0.3);
+  assert!((frame[0] - half_wet).abs() < 0.01);
+  for _ in 0..20 {
+    render_test_frame(&mut chain, 0.3);
+  }
+  assert_eq!(render_test_frame(&mut chain, 0.3)[0], 0.3);
+  assert!(chain.effects[0].as_ref().unwrap().is_silent());
+
+  // Moves are held until the chain has faded out to dry
+  chain.set_bypassed(0, false);
+  chain.set_mix(0, 1.);
+  set_test_wavefolder(&mut chain, 1, 1.5);
+  for _ in 0..20 {
+    render_test_frame(&mut chain, 0.3);
+  }
+  let before_move = render_test_frame(&mut chain, 0.3)[FRAME_SIZE - 1];
+  chain.move_effect(1, 0);
+  let frame = render_test_frame(&mut chain, 0.3);
+  assert!((frame[0] - before_move).abs() < 0.02);
+  assert!(chain.effects[0].is_some() && chain.pending_moves.len() == 1);
+  for _ in 0..20 {
+    render_test_frame(&mut chain, 0.3);
+  }
+  assert!(chain.pending_moves.is_empty());
+  assert!(!chain.is_reordering());
+}
+
+#[test]
+fn soft_clipper_and_wavefolder_remove_dc_offset() {
+  // Their DC blockers used to pass DC straight through since they never updated their state
+  let mut wavefolder =
+    Wavefolder::new(ParamSource::new_constant(1.), ParamSource::new_constant(0.));
+  let mut soft_clipper = SoftClipper::new(
+    ParamSource::new_constant(1.),
+    ParamSource::new_constant(1.),
+    0,
+  );
+  let mut folded = Vec::new();
+  let mut clipped = Vec::new();
+  for _ in 0..10_000 {
+    folded.push(wavefolder.apply(&[1., 0.], 440., 0.3));
+    clipped.push(soft_clipper.apply(&[1., 1.], 440., 0.3));
+  }
+
+  for output in [&folded, &clipped] {
+    assert!(output[0].abs() > 0.1);
+    assert!(output[1] < output[0]);
+    assert!(output.last().unwrap().abs() < 1e-3);
   }
 }
@@ -3,8 +3,10 @@
 
 use std::f32::consts::PI;
 
+use dsp::filters::dc_blocker::{DCBlocker, DEFAULT_SUBSONIC_CUTOFF_HZ};
+
 use super::Effect;
-use crate::fm::{ParamSource, FRAME_SIZE, SAMPLE_RATE};
+use crate::fm::{ParamSource, FRAME_SIZE};
 
 // Thermal voltage (26 milliwats at room temperature)
 const VT: f32 = 0.312;
@@ -20,6 +22,9 @@ pub struct MoogFilter {
   pub drive: ParamSource,
 
   last_sample: f32,
+  /// The `tanh` stages saturate asymmetrically once resonance feeds back into a heavily driven
+  /// input, which leaves DC on the output
+  dc_blocker: DCBlocker,
 }
 
 impl MoogFilter {
@@ -33,64 +38,84 @@ impl MoogFilter {
       resonance,
       drive,
       last_sample: 0.,
+      dc_blocker: DCBlocker::new(DEFAULT_SUBSONIC_CUTOFF_HZ),
     }
   }
 }
 
 fn tanh(x: f32) -> f32 { fastapprox::fast::tanh(x) }
 
-impl Effect for MoogFilter {
-  fn apply(&mut self, rendered_params: &[f32], _base_frequency: f32, sample: f32) -> f32 {
-    let cutoff = unsafe { *rendered_params.get_unchecked(0) };
-    let resonance = unsafe { *rendered_params.get_unchecked(1) };
-    let drive = unsafe { *rendered_params.get_unchecked(2) };
+/// Quality policy: oversampling is dropped first since it doubles the cost of the filter
+fn oversample_factor() -> usize { dsp::quality::quality_level().pick(2, 1, 1) }
 
-    let mut dV0;
-    let mut dV1;
-    let mut dV2;
-    let mut dV3;
+impl MoogFilter {
+  /// Runs the filter for one input sample, oversampling by linearly interpolating from
+  /// `last_sample`.
+  #[inline(always)]
+  fn process_sample(
+    &mut self,
+    last_sample: f32,
+    sample: f32,
+    cutoff: f32,
+    resonance: f32,
+    drive: f32,
+    oversample_factor: usize,
+  ) -> f32 {
+    let cutoff = dsp::clamp(1., 22_100., cutoff);
+    let resonance = dsp::clamp(0., 20., resonance);
+
+    let x = (PI * cutoff) / (2. * dsp::sample_rate());
+    let g = 4. * PI * VT * cutoff * (1. - x) / (1. + x);
+    let integration_divisor = 2.0 * (oversample_factor as f32 * dsp::sample_rate());
 
     let mut out_sample = 0.;
-    // 2x oversampling
-    for j in 0..=1 {
-      let sample = if j == 0 {
-        dsp::mix(0.5, self.last_sample, sample)
-      } else {
-        sample
-      };
-
-      let cutoff = dsp::clamp(1., 22_100., cutoff);
-      let resonance = dsp::clamp(0., 20., resonance);
-      let drive = drive;
-
-      let x = (PI * cutoff) / (SAMPLE_RATE * 2) as f32;
-      let g = 4. * PI * VT * cutoff * (1. - x) / (1. + x);
-
-      dV0 = -g * (tanh((drive * sample + resonance * self.V[3]) / (2.0 * VT)) + self.tV[0]);
-      self.V[0] += (dV0 + self.dV[0]) / (2.0 * (SAMPLE_RATE * 2) as f32);
+    for j in 0..oversample_factor {
+      let last_sample_pct = 1. - (j + 1) as f32 / oversample_factor as f32;
+      let sample = dsp::mix(last_sample_pct, last_sample, sample);
+
+      let dV0 = -g * (tanh((drive * sample + resonance * self.V[3]) / (2.0 * VT)) + self.tV[0]);
+      self.V[0] += (dV0 + self.dV[0]) / integration_divisor;
       self.dV[0] = dV0;
       self.tV[0] = tanh(self.V[0] / (2.0 * VT));
 
-      dV1 = g * (self.tV[0] - self.tV[1]);
-      self.V[1] += (dV1 + self.dV[1]) / (2.0 * (SAMPLE_RATE * 2) as f32);
+      let dV1 = g * (self.tV[0] - self.tV[1]);
+      self.V[1] += (dV1 + self.dV[1]) / integration_divisor;
       self.dV[1] = dV1;
       self.tV[1] = tanh(self.V[1] / (2.0 * VT));
 
-      dV2 = g * (self.tV[1] - self.tV[2]);
-      self.V[2] += (dV2 + self.dV[2]) / (2.0 * (SAMPLE_RATE * 2) as f32);
+      let dV2 = g * (self.tV[1] - self.tV[2]);
+      self.V[2] += (dV2 + self.dV[2]) / integration_divisor;
       self.dV[2] = dV2;
       self.tV[2] = tanh(self.V[2] / (2.0 * VT));
 
-      dV3 = g * (self.tV[2] - self.tV[3]);
-      self.V[3] += (dV3 + self.dV[3]) / (2.0 * (SAMPLE_RATE * 2) as f32);
+      let dV3 = g * (self.tV[2] - self.tV[3]);
+      self.V[3] += (dV3 + self.dV[3]) / integration_divisor;
       self.dV[3] = dV3;
       self.tV[3] = tanh(self.V[3] / (2.0 * VT));
 
       out_sample += self.V[3];
     }
-    self.last_sample = sample;
 
-    out_sample / 2.
+    self.dc_blocker.apply(out_sample / oversample_factor as f32)
+  }
+}
+
+impl Effect for MoogFilter {
+  fn apply(&mut self, rendered_params: &[f32], _base_frequency: f32, sample: f32) -> f32 {
+    let cutoff = unsafe { *rendered_params.get_unchecked(0) };
+    let resonance = unsafe { *rendered_params.get_unchecked(1) };
+    let drive = unsafe { *rendered_params.get_unchecked(2) };
+
+    let out = self.process_sample(
+      self.last_sample,
+      sample,
+      cutoff,
+      resonance,
+      drive,
+      oversample_factor(),
+    );
+    self.last_sample = sample;
+    out
   }
 
   fn apply_all(
@@ -112,64 +137,27 @@ impl Effect for MoogFilter {
     //     self.tV = [0.0; 4];
     // }
 
-    let mut dV0;
-    let mut dV1;
-    let mut dV2;
-    let mut dV3;
-
     // Param orderings:
     // [cutoff, resonance, drive]
     let cutoffs = unsafe { rendered_params.get_unchecked(0) };
     let resonances = unsafe { rendered_params.get_unchecked(1) };
     let drives = unsafe { rendered_params.get_unchecked(2) };
 
+    let oversample_factor = oversample_factor();
     let mut last_sample = self.last_sample;
     for i in 0..samples.len() {
-      let mut out_sample = 0.;
-
       if i > 0 {
         last_sample = samples[i - 1];
       }
 
-      // 2x oversampling
-      for j in 0..=1 {
-        let sample = if j == 0 {
-          dsp::mix(0.5, last_sample, samples[i])
-        } else {
-          samples[i]
-        };
-
-        let cutoff = dsp::clamp(1., 22_100., cutoffs[i]);
-        let resonance = dsp::clamp(0., 20., resonances[i]);
-        let drive = drives[i];
-
-        let x = (PI * cutoff) / (2 * SAMPLE_RATE) as f32;
-        let g = 4. * PI * VT * cutoff * (1. - x) / (1. + x);
-
-        dV0 = -g * (tanh((drive * sample + resonance * self.V[3]) / (2.0 * VT)) + self.tV[0]);
-        self.V[0] += (dV0 + self.dV[0]) / (2.0 * (2 * SAMPLE_RATE) as f32);
-        self.dV[0] = dV0;
-        self.tV[0] = tanh(self.V[0] / (2.0 * VT));
-
-        dV1 = g * (self.tV[0] - self.tV[1]);
-        self.V[1] += (dV1 + self.dV[1]) / (2.0 * (2 * SAMPLE_RATE) as f32);
-        self.dV[1] = dV1;
-        self.tV[1] = tanh(self.V[1] / (2.0 * VT));
-
-        dV2 = g * (self.tV[1] - self.tV[2]);
-        self.V[2] += (dV2 + self.dV[2]) / (2.0 * (2 * SAMPLE_RATE) as f32);
-        self.dV[2] = dV2;
-        self.tV[2] = tanh(self.V[2] / (2.0 * VT));
-
-        dV3 = g * (self.tV[2] - self.tV[3]);
-        self.V[3] += (dV3 + self.dV[3]) / (2.0 * (2 * SAMPLE_RATE) as f32);
-        self.dV[3] = dV3;
-        self.tV[3] = tanh(self.V[3] / (2.0 * VT));
-
-        out_sample += self.V[3];
-      }
-
-      samples[i] = out_sample / 2.;
+      samples[i] = self.process_sample(
+        last_sample,
+        samples[i],
+        cutoffs[i],
+        resonances[i],
+        drives[i],
+        oversample_factor,
+      );
     }
     self.last_sample = last_sample;
   }
@@ -1,7 +1,7 @@
 use dsp::circular_buffer::CircularBuffer;
 
 use super::Effect;
-use crate::fm::{ExponentialOscillator, ParamSource, SAMPLE_RATE};
+use crate::fm::{ExponentialOscillator, ParamSource};
 
 pub const SPECTRAL_WARPING_BUFFER_SIZE: usize = 44100 * 2;
 
@@ -50,7 +50,7 @@ impl Effect for SpectralWarping {
     let frequency = unsafe { *rendered_params.get_unchecked(0) };
     let stretch_factor = unsafe { *rendered_params.get_unchecked(1) };
     // We look back half of the wavelength of the frequency.
-    let base_lookback_samples = ((SAMPLE_RATE as f32) / frequency) / 2.;
+    let base_lookback_samples = (dsp::sample_rate() / frequency) / 2.;
     if !base_lookback_samples.is_normal() {
       return sample;
     }
@@ -0,0 +1,192 @@
+//! Sub-oscillator and noise layers that are mixed into each voice alongside the operators.  They
+//! cover the common cases of reinforcing the fundamental an octave or two down and adding a burst
+//! of noise to the attack without having to give up an operator for either.
+//!
+//! Both layers are mixed in before the voice's main effect chain, so they go through the same
+//! filtering as the operators.
+
+use dsp::smoothed_param::{SmoothedParam, SmoothingMode};
+use rand::Rng;
+
+use super::FRAME_SIZE;
+
+/// Ramp time for changes to the layer levels, which are set directly from the UI
+const LEVEL_SMOOTHING_MS: f32 = 20.;
+/// The noise envelope is considered finished once it decays to this level (-60 dB)
+const NOISE_ENVELOPE_FLOOR: f32 = 0.001;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubOscillatorShape {
+  Sine = 0,
+  Square = 1,
+  Triangle = 2,
+  Sawtooth = 3,
+}
+
+impl SubOscillatorShape {
+  pub fn from_usize(val: usize) -> Option<Self> {
+    match val {
+      0 => Some(Self::Sine),
+      1 => Some(Self::Square),
+      2 => Some(Self::Triangle),
+      3 => Some(Self::Sawtooth),
+      _ => None,
+    }
+  }
+
+  fn sample(self, phase: f32) -> f32 {
+    let lookup_table = match self {
+      SubOscillatorShape::Sine => dsp::lookup_tables::get_sine_lookup_table(),
+      SubOscillatorShape::Square => return if phase < 0.5 { 1. } else { -1. },
+      SubOscillatorShape::Triangle => dsp::lookup_tables::get_triangle_lookup_table(),
+      SubOscillatorShape::Sawtooth => dsp::lookup_tables::get_sawtooth_lookup_table(),
+    };
+    dsp::read_interpolated(lookup_table, phase * (lookup_table.len() - 2) as f32)
+  }
+}
+
+/// Layer config shared by all voices
+#[derive(Clone)]
+pub struct VoiceLayers {
+  /// Number of octaves below the voice's base frequency that the sub-oscillator plays; 1 or 2
+  pub sub_octaves: u32,
+  pub sub_shape: SubOscillatorShape,
+  sub_level: SmoothedParam,
+  noise_level: SmoothedParam,
+  /// Multiplied into the noise envelope every sample.  1 holds the noise for the whole note.
+  noise_decay_coefficient: f32,
+  /// Levels for the current frame, rendered once and shared by all voices
+  sub_levels: [f32; FRAME_SIZE],
+  noise_levels: [f32; FRAME_SIZE],
+}
+
+impl Default for VoiceLayers {
+  fn default() -> Self {
+    VoiceLayers {
+      sub_octaves: 1,
+      sub_shape: SubOscillatorShape::Sine,
+      sub_level: SmoothedParam::new(SmoothingMode::Linear, 0., LEVEL_SMOOTHING_MS),
+      noise_level: SmoothedParam::new(SmoothingMode::Linear, 0., LEVEL_SMOOTHING_MS),
+      noise_decay_coefficient: 1.,
+      sub_levels: [0.; FRAME_SIZE],
+      noise_levels: [0.; FRAME_SIZE],
+    }
+  }
+}
+
+impl VoiceLayers {
+  /// `noise_decay_ms` is the time it takes for the noise to fade out after the voice is gated.  0
+  /// holds the noise for the whole note.
+  pub fn set(
+    &mut self,
+    sub_octaves: u32,
+    sub_shape: SubOscillatorShape,
+    sub_level: f32,
+    noise_level: f32,
+    noise_decay_ms: f32,
+  ) {
+    self.sub_octaves = sub_octaves.clamp(1, 2);
+    self.sub_shape = sub_shape;
+    self.sub_level.set_target(sub_level);
+    self.noise_level.set_target(noise_level);
+    self.noise_decay_coefficient = if noise_decay_ms > 0. {
+      let decay_samples = dsp::ms_to_samples(noise_decay_ms).max(1.);
+      NOISE_ENVELOPE_FLOOR.powf(1. / decay_samples)
+    } else {
+      1.
+    };
+  }
+
+  /// Renders the layer levels for the upcoming frame.  Returns `false` if both layers are silent,
+  /// in which case voices can skip them entirely.
+  pub fn render_frame(&mut self) -> bool {
+    let sub_active = self.sub_level.is_smoothing() || self.sub_level.current() != 0.;
+    if sub_active {
+      self.sub_level.fill(&mut self.sub_levels);
+    } else {
+      self.sub_levels.fill(0.);
+    }
+    let noise_active = self.noise_level.is_smoothing() || self.noise_level.current() != 0.;
+    if noise_active {
+      self.noise_level.fill(&mut self.noise_levels);
+    } else {
+      self.noise_levels.fill(0.);
+    }
+    sub_active || noise_active
+  }
+}
+
+/// Per-voice state for the layers
+#[derive(Clone, Default)]
+pub struct VoiceLayerState {
+  sub_phase: f32,
+  noise_envelope: f32,
+}
+
+impl VoiceLayerState {
+  pub fn gate(&mut self) {
+    self.sub_phase = 0.;
+    self.noise_envelope = 1.;
+  }
+
+  /// Mixes the layers into `output`.  `base_frequencies` are the voice's base frequencies for the
+  /// frame with detune applied.
+  pub fn apply(
+    &mut self,
+    layers: &VoiceLayers,
+    base_frequencies: &[f32; FRAME_SIZE],
+    output: &mut [f32; FRAME_SIZE],
+  ) {
+    let sub_frequency_multiplier = 1. / (1 << layers.sub_octaves) as f32;
+    let rng = common::rng();
+
+    for i in 0..FRAME_SIZE {
+      let sub_level = layers.sub_levels[i];
+      if sub_level != 0. {
+        let frequency = base_frequencies[i] * sub_frequency_multiplier;
+        self.sub_phase = (self.sub_phase + frequency / dsp::sample_rate()).rem_euclid(1.);
+        output[i] += layers.sub_shape.sample(self.sub_phase) * sub_level;
+      }
+
+      let noise_level = layers.noise_levels[i] * self.noise_envelope;
+      if noise_level > NOISE_ENVELOPE_FLOOR {
+        output[i] += rng.gen_range(-1.0f32, 1.) * noise_level;
+        self.noise_envelope *= layers.noise_decay_coefficient;
+      }
+    }
+  }
+}
+
+#[test]
+fn noise_layer_decays_and_sub_oscillator_plays_octaves_down() {
+  dsp::lookup_tables::maybe_init_lookup_tables();
+  let mut layers = VoiceLayers::default();
+  layers.set(2, SubOscillatorShape::Square, 1., 1., 10.);
+  layers.sub_level.set_immediate(1.);
+  layers.noise_level.set_immediate(1.);
+  assert!(layers.render_frame());
+
+  let mut state = VoiceLayerState::default();
+  state.gate();
+  // One cycle of the base frequency is 4 frames long, so the sub-oscillator plays a square wave
+  // with a period of 16 frames
+  let base_frequencies = [dsp::sample_rate() / (FRAME_SIZE * 4) as f32; FRAME_SIZE];
+  let mut output = [0.; FRAME_SIZE];
+  state.apply(&layers, &base_frequencies, &mut output);
+  assert!(state.noise_envelope < 1.);
+
+  // Once the noise has decayed, only the sub-oscillator is left
+  layers.set(2, SubOscillatorShape::Square, 1., 1., 1.);
+  for _ in 0..16 {
+    state.apply(&layers, &base_frequencies, &mut [0.; FRAME_SIZE]);
+  }
+  assert!(state.noise_envelope <= NOISE_ENVELOPE_FLOOR);
+  let mut output = [0.; FRAME_SIZE];
+  state.apply(&layers, &base_frequencies, &mut output);
+  assert!(output.iter().all(|&sample| sample.abs() == 1.));
+
+  layers.set(1, SubOscillatorShape::Sine, 0., 0., 0.);
+  layers.sub_level.set_immediate(0.);
+  layers.noise_level.set_immediate(0.);
+  assert!(!layers.render_frame());
+}
@@ -8,42 +8,77 @@ use adsr::{
   exports::AdsrLengthMode, managed_adsr::ManagedAdsr, Adsr, AdsrStep, EarlyReleaseConfig,
   EarlyReleaseStrategy, GateStatus, RampFn, RENDERED_BUFFER_SIZE,
 };
-use dsp::{midi_number_to_frequency, oscillator::PhasedOscillator};
+use common::ffi::{self, ErrorCode, FfiResult};
+use dsp::{
+  oscillator::PhasedOscillator,
+  profiling::Profiler,
+  smoothed_param::{SmoothedParam, SmoothingMode},
+  tuning::Tuning,
+};
 
+pub mod bounce;
+pub mod control_events;
 pub mod effects;
+pub mod layers;
+pub mod morph;
+pub mod panning;
+pub mod preset;
+pub mod randomize;
 mod samples;
 mod standalone_fx;
 use crate::{WaveTable, WaveTableSettings};
 
 use self::{
-  effects::EffectChain,
+  bounce::Bouncer,
+  control_events::CONTROL_EVENT_BUF_LEN,
+  effects::{EffectChain, EFFECT_TYPE_COUNT, MAX_EFFECT_COUNT},
+  layers::{SubOscillatorShape, VoiceLayerState, VoiceLayers},
+  morph::PresetMorph,
+  panning::Panning,
   samples::{
     init_sample_manager, sample_manager, SampleMappingEmitter, SampleMappingManager,
     SampleMappingOperatorConfig, TunedSampleEmitter,
   },
 };
 
+#[cfg(target_arch = "wasm32")]
 extern "C" {
   pub(crate) fn log_err(ptr: *const u8, len: usize);
 
   fn on_gate_cb(midi_number: usize, voice_ix: usize);
 
   fn on_ungate_cb(midi_number: usize, voice_ix: usize);
+
+  /// Timestamp in milliseconds, only called while profiling is enabled
+  fn now() -> f64;
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use common::native::{log_err, now as host_now};
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn on_gate_cb(_midi_number: usize, _voice_ix: usize) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn on_ungate_cb(_midi_number: usize, _voice_ix: usize) {}
+
+#[cfg(target_arch = "wasm32")]
+fn host_now() -> f64 { unsafe { now() } }
+
 pub static mut MIDI_CONTROL_VALUES: [f32; 1024] = [0.; 1024];
 const GAIN_ENVELOPE_PHASE_BUF_INDEX: usize = 255;
 const FILTER_ENVELOPE_PHASE_BUF_INDEX: usize = 254;
 
-fn samples_to_ms(samples: f32) -> f32 { samples * 1000. / SAMPLE_RATE as f32 }
+fn samples_to_ms(samples: f32) -> f32 { samples * 1000. / dsp::sample_rate() }
 
 #[no_mangle]
-pub unsafe extern "C" fn fm_synth_set_midi_control_value(index: usize, value: usize) {
-  if index >= MIDI_CONTROL_VALUES.len() || value > 127 {
-    panic!();
-  }
-
-  MIDI_CONTROL_VALUES[index] = (value as f32) / 127.;
+pub unsafe extern "C" fn fm_synth_set_midi_control_value(index: usize, value: usize) -> ErrorCode {
+  ffi::status((|| {
+    ffi::check_range("index", index, 0, MIDI_CONTROL_VALUES.len() - 1)?;
+    ffi::check_range("value", value, 0, 127)?;
+    MIDI_CONTROL_VALUES[index] = (value as f32) / 127.;
+    Ok(())
+  })())
 }
 
 #[derive(Clone, Default, PartialEq)]
@@ -254,6 +289,12 @@ pub struct UnisonOscillator<T> {
   pub middle_oscillator_ix: f32,
   pub middle_gain_pct: f32,
   pub outer_gain_pct: f32,
+  /// How far the outermost oscillators are panned from the center in [0, 1].  The oscillators are
+  /// spread evenly between them.
+  pub stereo_spread: f32,
+  /// Side component of the most recently generated sample.  Only computed if `stereo_spread` is
+  /// non-zero.
+  last_side_sample: f32,
 }
 
 impl<T> UnisonOscillator<T> {
@@ -277,6 +318,8 @@ impl<T> UnisonOscillator<T> {
       middle_oscillator_ix,
       middle_gain_pct,
       outer_gain_pct,
+      stereo_spread: 0.,
+      last_side_sample: 0.,
     }
   }
 }
@@ -299,8 +342,17 @@ impl<T: PhasedOscillator> UnisonOscillator<T> {
     }
     phases
   }
+
+  pub fn scale_phases(&mut self, factor: f32) {
+    for osc in &mut self.oscillators {
+      osc.set_phase(osc.get_phase() * factor);
+    }
+  }
 }
 
+/// Quality policy: only every `n`th unison oscillator is rendered when quality is reduced
+fn unison_stride() -> usize { dsp::quality::quality_level().pick(1, 2, 4) }
+
 impl<T: Oscillator + PhasedOscillator> UnisonOscillator<T> {
   fn gen_sample(
     &mut self,
@@ -324,7 +376,27 @@ impl<T: Oscillator + PhasedOscillator> UnisonOscillator<T> {
     let unison_detune_step_semitones =
       unison_detune_range_semitones / (self.oscillators.len() - 1) as f32;
 
-    for (i, osc) in self.oscillators.iter_mut().enumerate() {
+    // Pan position of each oscillator is proportional to its distance from the middle one
+    let spread_step = if self.middle_oscillator_ix > 0. {
+      self.stereo_spread / self.middle_oscillator_ix
+    } else {
+      0.
+    };
+    let mut side = 0.;
+
+    // Skipped oscillators are chosen so that the middle one is always rendered, and the gain of the
+    // rendered ones is scaled back up to make up for the missing ones.
+    let stride = unison_stride();
+    let first_ix = ((self.oscillators.len() - 1) / 2) % stride;
+    let mut rendered_gain = 0.;
+
+    for (i, osc) in self
+      .oscillators
+      .iter_mut()
+      .enumerate()
+      .skip(first_ix)
+      .step_by(stride)
+    {
       let frequency = compute_detune(
         frequency,
         unison_detune_semitones_start + i as f32 * unison_detune_step_semitones,
@@ -335,7 +407,7 @@ impl<T: Oscillator + PhasedOscillator> UnisonOscillator<T> {
       } else {
         self.outer_gain_pct
       };
-      out += osc.gen_sample(
+      let sample = osc.gen_sample(
         frequency,
         wavetables,
         param_buffers,
@@ -343,7 +415,19 @@ impl<T: Oscillator + PhasedOscillator> UnisonOscillator<T> {
         sample_ix_within_frame,
         base_frequency,
       ) * gain;
+      out += sample;
+      rendered_gain += gain;
+      if spread_step != 0. {
+        side += sample * (i as f32 - self.middle_oscillator_ix) * spread_step;
+      }
+    }
+
+    if stride > 1 && rendered_gain > 0. {
+      let gain_compensation = 1. / rendered_gain;
+      out *= gain_compensation;
+      side *= gain_compensation;
     }
+    self.last_side_sample = side;
     out
   }
 }
@@ -504,6 +588,9 @@ pub struct Operator {
   pub effect_chain: EffectChain,
   pub enabled: bool,
   pub randomize_start_phases: bool,
+  /// Stereo spread of the operator's unison oscillators in [0, 1].  Kept here as well as on the
+  /// oscillator source so that it survives the source being rebuilt.
+  pub unison_spread: f32,
 }
 
 impl Operator {
@@ -642,6 +729,74 @@ impl OscillatorSource {
     }
   }
 
+  /// Returns the phase used to detect when this oscillator wraps around for hard sync.  Unison
+  /// oscillators use the phase of their first voice.  Doesn't allocate, unlike `get_phase`.
+  pub fn get_sync_phase(&self) -> Option<f32> {
+    match self {
+      OscillatorSource::Wavetable(handle) => Some(handle.phase),
+      OscillatorSource::Sine(osc) => Some(osc.get_phase()),
+      OscillatorSource::ExponentialOscillator(osc) => Some(osc.get_phase()),
+      OscillatorSource::Square(osc) => Some(osc.get_phase()),
+      OscillatorSource::Triangle(osc) => Some(osc.get_phase()),
+      OscillatorSource::Sawtooth(osc) => Some(osc.get_phase()),
+      OscillatorSource::UnisonSine(osc) => osc.oscillators.first().map(|o| o.get_phase()),
+      OscillatorSource::UnisonWavetable(osc) => osc.oscillators.first().map(|o| o.get_phase()),
+      OscillatorSource::UnisonSquare(osc) => osc.oscillators.first().map(|o| o.get_phase()),
+      OscillatorSource::UnisonTriangle(osc) => osc.oscillators.first().map(|o| o.get_phase()),
+      OscillatorSource::UnisonSawtooth(osc) => osc.oscillators.first().map(|o| o.get_phase()),
+      OscillatorSource::ParamBuffer(_)
+      | OscillatorSource::SampleMapping(_)
+      | OscillatorSource::TunedSample(_) => None,
+    }
+  }
+
+  /// Multiplies the phases of all oscillators by `factor`.  A factor of 0 resets them to the start
+  /// of their waveforms, which is what hard sync does when the master oscillator wraps around.
+  /// Sources without a phase are left alone.
+  pub fn scale_phases(&mut self, factor: f32) {
+    match self {
+      OscillatorSource::Wavetable(handle) => handle.phase *= factor,
+      OscillatorSource::Sine(osc) => osc.set_phase(osc.get_phase() * factor),
+      OscillatorSource::ExponentialOscillator(osc) => osc.set_phase(osc.get_phase() * factor),
+      OscillatorSource::Square(osc) => osc.set_phase(osc.get_phase() * factor),
+      OscillatorSource::Triangle(osc) => osc.set_phase(osc.get_phase() * factor),
+      OscillatorSource::Sawtooth(osc) => osc.set_phase(osc.get_phase() * factor),
+      OscillatorSource::UnisonSine(osc) => osc.scale_phases(factor),
+      OscillatorSource::UnisonWavetable(osc) => osc.scale_phases(factor),
+      OscillatorSource::UnisonSquare(osc) => osc.scale_phases(factor),
+      OscillatorSource::UnisonTriangle(osc) => osc.scale_phases(factor),
+      OscillatorSource::UnisonSawtooth(osc) => osc.scale_phases(factor),
+      OscillatorSource::ParamBuffer(_)
+      | OscillatorSource::SampleMapping(_)
+      | OscillatorSource::TunedSample(_) => (),
+    }
+  }
+
+  /// Sets the stereo spread of unison oscillators.  Other sources are always centered.
+  pub fn set_unison_spread(&mut self, spread: f32) {
+    match self {
+      OscillatorSource::UnisonSine(osc) => osc.stereo_spread = spread,
+      OscillatorSource::UnisonWavetable(osc) => osc.stereo_spread = spread,
+      OscillatorSource::UnisonSquare(osc) => osc.stereo_spread = spread,
+      OscillatorSource::UnisonTriangle(osc) => osc.stereo_spread = spread,
+      OscillatorSource::UnisonSawtooth(osc) => osc.stereo_spread = spread,
+      _ => (),
+    }
+  }
+
+  /// Returns the side component of the most recently generated sample.  The left channel is
+  /// `sample - side` and the right channel is `sample + side`.
+  pub fn get_unison_side_sample(&self) -> f32 {
+    match self {
+      OscillatorSource::UnisonSine(osc) => osc.last_side_sample,
+      OscillatorSource::UnisonWavetable(osc) => osc.last_side_sample,
+      OscillatorSource::UnisonSquare(osc) => osc.last_side_sample,
+      OscillatorSource::UnisonTriangle(osc) => osc.last_side_sample,
+      OscillatorSource::UnisonSawtooth(osc) => osc.last_side_sample,
+      _ => 0.,
+    }
+  }
+
   /// Given a new operator source, if the new one is the same type as the old one, we
   pub fn maybe_update(&mut self, other: &OscillatorSource) -> bool {
     match self {
@@ -860,10 +1015,17 @@ pub struct FMSynthVoice {
   pub last_samples: [f32; OPERATOR_COUNT],
   pub last_sample_frequencies_per_operator: [f32; OPERATOR_COUNT],
   pub effect_chain: EffectChain,
+  /// Copy of `effect_chain` that processes the right channel.  It's only created once the voice
+  /// first renders in stereo, since most patches never pan their operators.
+  pub effect_chain_right: Option<EffectChain>,
   cached_modulation_indices: [[[f32; FRAME_SIZE]; OPERATOR_COUNT]; OPERATOR_COUNT],
   pub gain_envelope_generator: ManagedAdsr,
   pub filter_envelope_generator: ManagedAdsr,
   pub last_gated_midi_number: usize,
+  pub layer_state: VoiceLayerState,
+  /// Number of consecutive frames that the voice has been silent with its gain envelope finished.
+  /// See `is_effectively_silent`.
+  silent_frame_count: usize,
 }
 
 /// Applies modulation from all other operators to the provided frequency, returning the modulated
@@ -875,9 +1037,19 @@ fn compute_modulated_frequency(
   carrier_base_frequency: f32,
   last_sample_modulator_frequencies: &[f32; OPERATOR_COUNT],
   modulation_indices: &[[[f32; FRAME_SIZE]; OPERATOR_COUNT]; OPERATOR_COUNT],
+  connection_types: &[[OperatorConnectionType; OPERATOR_COUNT]; OPERATOR_COUNT],
 ) -> f32 {
   let mut output_freq = carrier_base_frequency;
   for modulator_operator_ix in 0..OPERATOR_COUNT {
+    let connection_type = unsafe {
+      *connection_types
+        .get_unchecked(modulator_operator_ix)
+        .get_unchecked(operator_ix)
+    };
+    if connection_type != OperatorConnectionType::FrequencyModulation {
+      continue;
+    }
+
     let modulator_output = unsafe { last_samples.get_unchecked(modulator_operator_ix) };
     let modulation_index = unsafe {
       *modulation_indices
@@ -894,6 +1066,55 @@ fn compute_modulated_frequency(
   output_freq
 }
 
+/// Called when the phase of `src_operator_ix` wraps around.  Resets the phases of all operators
+/// that it's hard synced to, scaled by the modulation index of the connection.
+fn apply_hard_sync(
+  operators: &mut [Operator; OPERATOR_COUNT],
+  src_operator_ix: usize,
+  sample_ix_within_frame: usize,
+  modulation_indices: &[[[f32; FRAME_SIZE]; OPERATOR_COUNT]; OPERATOR_COUNT],
+  connection_types: &[[OperatorConnectionType; OPERATOR_COUNT]; OPERATOR_COUNT],
+) {
+  for dst_operator_ix in 0..OPERATOR_COUNT {
+    if connection_types[src_operator_ix][dst_operator_ix] != OperatorConnectionType::HardSync {
+      continue;
+    }
+
+    let amount = modulation_indices[src_operator_ix][dst_operator_ix][sample_ix_within_frame];
+    if amount <= 0. {
+      continue;
+    }
+    operators[dst_operator_ix]
+      .oscillator_source
+      .scale_phases(1. - amount.min(1.));
+  }
+}
+
+/// Multiplies `sample`, the output of `operator_ix`, by the outputs of all operators that ring
+/// modulate it
+fn apply_ring_modulation(
+  mut sample: f32,
+  last_samples: &[f32; OPERATOR_COUNT],
+  operator_ix: usize,
+  sample_ix_within_frame: usize,
+  modulation_indices: &[[[f32; FRAME_SIZE]; OPERATOR_COUNT]; OPERATOR_COUNT],
+  connection_types: &[[OperatorConnectionType; OPERATOR_COUNT]; OPERATOR_COUNT],
+) -> f32 {
+  for src_operator_ix in 0..OPERATOR_COUNT {
+    if connection_types[src_operator_ix][operator_ix] != OperatorConnectionType::RingModulation {
+      continue;
+    }
+
+    let amount = dsp::clamp(
+      0.,
+      1.,
+      modulation_indices[src_operator_ix][operator_ix][sample_ix_within_frame],
+    );
+    sample *= 1. - amount + amount * last_samples[src_operator_ix];
+  }
+  sample
+}
+
 /// Based off of WebAudio's detune:
 /// https://www.w3.org/TR/webaudio/#computedfrequency
 ///
@@ -944,18 +1165,19 @@ impl FMSynthVoice {
       last_samples: [0.0; OPERATOR_COUNT],
       last_sample_frequencies_per_operator: [0.0; OPERATOR_COUNT],
       effect_chain: EffectChain::default(),
+      effect_chain_right: None,
       cached_modulation_indices: [[[0.0; FRAME_SIZE]; OPERATOR_COUNT]; OPERATOR_COUNT],
       gain_envelope_generator: ManagedAdsr {
         adsr: Adsr::new(
           build_default_gain_adsr_steps(),
           None,
-          44_100.,
+          dsp::sample_rate(),
           None,
           0.975,
           shared_gain_adsr_rendered_buffer,
           EarlyReleaseConfig {
             strategy: EarlyReleaseStrategy::LinearMix,
-            len_samples: 3_400,
+            len_samples: dsp::ms_to_samples(77.) as usize,
           },
           false,
         ),
@@ -966,13 +1188,13 @@ impl FMSynthVoice {
         adsr: Adsr::new(
           build_default_gain_adsr_steps(),
           None,
-          44_100.,
+          dsp::sample_rate(),
           None,
           0.975,
           shared_filter_adsr_rendered_buffer,
           EarlyReleaseConfig {
             strategy: EarlyReleaseStrategy::LinearMix,
-            len_samples: 3_400,
+            len_samples: dsp::ms_to_samples(77.) as usize,
           },
           false,
         ),
@@ -980,9 +1202,35 @@ impl FMSynthVoice {
         length_mode: AdsrLengthMode::Ms,
       },
       last_gated_midi_number: 0,
+      layer_state: VoiceLayerState::default(),
+      silent_frame_count: 0,
     }
   }
 
+  /// Returns `true` if the voice's gain envelope has finished and its output has stayed silent for
+  /// long enough that it can be skipped entirely until it's gated again.
+  pub fn is_effectively_silent(&self) -> bool {
+    matches!(
+      self.gain_envelope_generator.adsr.gate_status,
+      GateStatus::Done
+    ) && self.silent_frame_count >= SILENT_VOICE_FRAME_COUNT
+  }
+
+  fn update_silent_frame_count(&mut self, output_buffer: &[[f32; FRAME_SIZE]; 2]) {
+    let is_silent = matches!(
+      self.gain_envelope_generator.adsr.gate_status,
+      GateStatus::Done
+    ) && output_buffer
+      .as_flattened()
+      .iter()
+      .all(|sample| sample.abs() < SILENT_VOICE_THRESHOLD);
+    self.silent_frame_count = if is_silent {
+      self.silent_frame_count.saturating_add(1)
+    } else {
+      0
+    };
+  }
+
   pub fn gen_samples(
     &mut self,
     modulation_matrix: &mut ModulationMatrix,
@@ -990,9 +1238,11 @@ impl FMSynthVoice {
     param_buffers: &[[f32; FRAME_SIZE]],
     operator_base_frequency_sources: &[ParamSource; OPERATOR_COUNT],
     raw_base_frequencies: &[f32; FRAME_SIZE],
-    output_buffer: &mut [f32; FRAME_SIZE],
+    output_buffer: &mut [[f32; FRAME_SIZE]; 2],
     detune: Option<&ParamSource>,
     sample_mapping_manager: &SampleMappingManager,
+    voice_layers: Option<&VoiceLayers>,
+    panning: &Panning,
   ) {
     let mut samples_per_operator_bufs: [[f32; OPERATOR_COUNT]; 2] = [self.last_samples, uninit()];
     let mut last_samples_per_operator: &mut [f32; OPERATOR_COUNT] =
@@ -1058,6 +1308,13 @@ impl FMSynthVoice {
       base_frequencies,
     };
 
+    // Hard sync and ring modulation are rare, so we only check operators that use them
+    let mut has_hard_sync_outputs = [false; OPERATOR_COUNT];
+    let mut has_ring_modulation_inputs = [false; OPERATOR_COUNT];
+    // Same goes for panning; the voice is only rendered in stereo if some operator is panned
+    let mut is_operator_stereo = [false; OPERATOR_COUNT];
+    let mut operator_pans: [[f32; FRAME_SIZE]; OPERATOR_COUNT] = uninit();
+
     for operator_ix in 0..OPERATOR_COUNT {
       let operator = unsafe { self.operators.get_unchecked_mut(operator_ix) };
       if !operator.enabled {
@@ -1080,10 +1337,26 @@ impl FMSynthVoice {
       // Render the params for all per-operator-per-voice effects ahead of time as well
       operator.effect_chain.pre_render_params(&render_params);
 
+      let pans = unsafe { operator_pans.get_unchecked_mut(operator_ix) };
+      if !panning.is_operator_centered(operator_ix) {
+        panning.operator_pans[operator_ix].render_raw(&render_params, pans);
+        is_operator_stereo[operator_ix] = true;
+      } else if operator.unison_spread != 0. {
+        pans.fill(0.);
+        is_operator_stereo[operator_ix] = true;
+      }
+
       // Render all modulation indices for the full frame ahead of time using SIMD
       // modulation_indices[src_operator_ix][dst_operator_ix][sample_ix_within_frame]
       let src_operator_ix = operator_ix;
       for dst_operator_ix in 0..OPERATOR_COUNT {
+        match modulation_matrix.connection_types[src_operator_ix][dst_operator_ix] {
+          OperatorConnectionType::FrequencyModulation => (),
+          OperatorConnectionType::HardSync => has_hard_sync_outputs[src_operator_ix] = true,
+          OperatorConnectionType::RingModulation =>
+            has_ring_modulation_inputs[dst_operator_ix] = true,
+        }
+
         let param =
           modulation_matrix.get_operator_modulation_index(src_operator_ix, dst_operator_ix);
         let buf = unsafe {
@@ -1096,8 +1369,12 @@ impl FMSynthVoice {
       }
     }
 
+    let is_stereo = is_operator_stereo.contains(&true);
+
     for sample_ix_within_frame in 0..FRAME_SIZE {
+      // Holds the left channel when rendering in stereo
       let mut output_sample = 0.0f32;
+      let mut right_output_sample = 0.0f32;
 
       let base_frequency = *unsafe { base_frequencies.get_unchecked(sample_ix_within_frame) };
 
@@ -1119,10 +1396,17 @@ impl FMSynthVoice {
           carrier_base_frequency,
           &last_frequencies_per_operator,
           &self.cached_modulation_indices,
+          &modulation_matrix.connection_types,
         );
         *unsafe { frequencies_per_operator.get_unchecked_mut(operator_ix) } = modulated_frequency;
 
-        let sample = carrier_operator.gen_sample(
+        let phase_before = if has_hard_sync_outputs[operator_ix] {
+          carrier_operator.oscillator_source.get_sync_phase()
+        } else {
+          None
+        };
+
+        let mut sample = carrier_operator.gen_sample(
           modulated_frequency,
           wavetables,
           param_buffers,
@@ -1132,21 +1416,75 @@ impl FMSynthVoice {
           base_frequency,
           &sample_mapping_manager.config_by_operator[operator_ix],
         );
+        let side_sample = if is_operator_stereo[operator_ix] {
+          carrier_operator.oscillator_source.get_unison_side_sample()
+        } else {
+          0.
+        };
+
+        if let Some(phase_before) = phase_before {
+          // Phases are advanced by less than half a cycle per sample, so a big jump means that the
+          // phase wrapped around in either direction
+          let wrapped = carrier_operator
+            .oscillator_source
+            .get_sync_phase()
+            .is_some_and(|phase_after| (phase_after - phase_before).abs() > 0.5);
+          if wrapped {
+            apply_hard_sync(
+              &mut self.operators,
+              operator_ix,
+              sample_ix_within_frame,
+              &self.cached_modulation_indices,
+              &modulation_matrix.connection_types,
+            );
+          }
+        }
+
+        if has_ring_modulation_inputs[operator_ix] {
+          sample = apply_ring_modulation(
+            sample,
+            last_samples_per_operator,
+            operator_ix,
+            sample_ix_within_frame,
+            &self.cached_modulation_indices,
+            &modulation_matrix.connection_types,
+          );
+        }
 
         *unsafe { samples_per_operator.get_unchecked_mut(operator_ix) } = sample;
 
-        output_sample += sample
-          * unsafe {
-            *rendered_output_weights
+        let output_weight = unsafe {
+          *rendered_output_weights
+            .get_unchecked(operator_ix)
+            .get_unchecked(sample_ix_within_frame)
+        };
+        if !is_stereo {
+          output_sample += sample * output_weight;
+        } else if is_operator_stereo[operator_ix] {
+          let pan = unsafe {
+            *operator_pans
               .get_unchecked(operator_ix)
               .get_unchecked(sample_ix_within_frame)
           };
+          let (left_gain, right_gain) = panning::balance_gains(pan);
+          output_sample += (sample - side_sample) * output_weight * left_gain;
+          right_output_sample += (sample + side_sample) * output_weight * right_gain;
+        } else {
+          output_sample += sample * output_weight;
+          right_output_sample += sample * output_weight;
+        }
       }
 
       debug_assert!(output_sample == 0. || output_sample.is_normal());
+      let [left_output_buffer, right_output_buffer] = &mut *output_buffer;
       unsafe {
-        *output_buffer.get_unchecked_mut(sample_ix_within_frame) =
+        *left_output_buffer.get_unchecked_mut(sample_ix_within_frame) =
           dsp::clamp(-10., 10., output_sample);
+        if is_stereo {
+          debug_assert!(right_output_sample == 0. || right_output_sample.is_normal());
+          *right_output_buffer.get_unchecked_mut(sample_ix_within_frame) =
+            dsp::clamp(-10., 10., right_output_sample);
+        }
       }
       std::mem::swap(&mut samples_per_operator, &mut last_samples_per_operator);
       std::mem::swap(
@@ -1158,10 +1496,49 @@ impl FMSynthVoice {
     self.last_samples = *last_samples_per_operator;
     self.last_sample_frequencies_per_operator = *last_frequencies_per_operator;
 
-    self.effect_chain.apply_all(&render_params, output_buffer);
+    let [left_output_buffer, right_output_buffer] = output_buffer;
+    if is_stereo {
+      if let Some(voice_layers) = voice_layers {
+        let mut layers_output = [0.; FRAME_SIZE];
+        self
+          .layer_state
+          .apply(voice_layers, base_frequencies, &mut layers_output);
+        for i in 0..FRAME_SIZE {
+          left_output_buffer[i] += layers_output[i];
+          right_output_buffer[i] += layers_output[i];
+        }
+      }
+
+      let effect_chain_right = self
+        .effect_chain_right
+        .get_or_insert_with(|| self.effect_chain.clone());
+      self
+        .effect_chain
+        .apply_all(&render_params, left_output_buffer);
+      effect_chain_right.apply_all(&render_params, right_output_buffer);
+    } else {
+      if let Some(voice_layers) = voice_layers {
+        self
+          .layer_state
+          .apply(voice_layers, base_frequencies, left_output_buffer);
+      }
+
+      self
+        .effect_chain
+        .apply_all(&render_params, left_output_buffer);
+      *right_output_buffer = *left_output_buffer;
+    }
+
+    let mut voice_pans: [f32; FRAME_SIZE] = uninit();
+    if panning.render_voice_pan(&render_params, &mut voice_pans) {
+      panning::apply_voice_pan(&voice_pans, output_buffer);
+    }
   }
 }
 
+/// Highest value type accepted by `ParamSource::from_parts`
+pub(crate) const MAX_PARAM_SOURCE_VALUE_TYPE: usize = 6;
+
 #[derive(Clone, PartialEq)]
 pub enum ParamSource {
   /// Each sample, the value for this param is pulled out of the parameter buffer of this index.
@@ -1182,7 +1559,7 @@ pub enum ParamSource {
     shift: f32,
   },
   /// Converts the provided number of beats into samples.  If the cur BPM is 60, that equates to
-  /// 1 beat per second which comes out to 44_100 samples.
+  /// 1 beat per second which comes out to `sample_rate` samples.
   BeatsToSamples(f32),
   Random {
     last_val: Cell<f32>,
@@ -1315,7 +1692,7 @@ impl ParamSource {
         let cur_bpm = crate::get_cur_bpm();
         let cur_bps = cur_bpm / 60.;
         let seconds_per_beat = 1. / cur_bps;
-        let samples_per_beat = seconds_per_beat * SAMPLE_RATE as f32;
+        let samples_per_beat = seconds_per_beat * dsp::sample_rate();
         samples_per_beat * *beats
       },
       ParamSource::Random {
@@ -1387,7 +1764,7 @@ impl ParamSource {
           smoothing_coefficient: value_param_float_3,
         }
       },
-      _ => panic!("Invalid value type; expected [0,4]"),
+      _ => panic!("Invalid value type; expected [0,{MAX_PARAM_SOURCE_VALUE_TYPE}]"),
     }
   }
 
@@ -1483,7 +1860,7 @@ impl ParamSource {
         let cur_bpm = crate::get_cur_bpm();
         let cur_bps = cur_bpm / 60.;
         let seconds_per_beat = 1. / cur_bps;
-        let samples_per_beat = seconds_per_beat * SAMPLE_RATE as f32;
+        let samples_per_beat = seconds_per_beat * dsp::sample_rate();
         let samples = samples_per_beat * *beats;
 
         let splat = f32x4_splat(samples);
@@ -1596,7 +1973,7 @@ impl ParamSource {
         let cur_bpm = crate::get_cur_bpm();
         let cur_bps = cur_bpm / 60.;
         let seconds_per_beat = 1. / cur_bps;
-        let samples_per_beat = seconds_per_beat * SAMPLE_RATE as f32;
+        let samples_per_beat = seconds_per_beat * dsp::sample_rate();
         let samples = samples_per_beat * *beats;
 
         for i in 0..FRAME_SIZE {
@@ -1645,15 +2022,54 @@ impl ParamSource {
 
 pub const OPERATOR_COUNT: usize = 8;
 pub const FRAME_SIZE: usize = 128;
-pub const SAMPLE_RATE: usize = 44_100;
 pub const MAX_PARAM_BUFFERS: usize = 16;
+/// Ramp time for changes to the master gain, which is set directly from the UI
+const MASTER_GAIN_SMOOTHING_MS: f32 = 20.;
+/// Pitch bend is usually sent as a stream of discrete values, so it's smoothed to avoid stepping
+const PITCH_BEND_SMOOTHING_MS: f32 = 10.;
+/// Voices with a finished gain envelope are considered silent once their output stays below this
+/// level (about -100dB)
+const SILENT_VOICE_THRESHOLD: f32 = 0.00001;
+/// Number of consecutive silent frames before a voice stops being rendered
+const SILENT_VOICE_FRAME_COUNT: usize = 8;
+/// Operator effect chains followed by the main effect chain, with four params for each effect.  See
+/// `FMSynthContext::effect_param_viz_buf`.
+pub const EFFECT_PARAM_VIZ_BUF_LEN: usize = (OPERATOR_COUNT + 1) * MAX_EFFECT_COUNT * 4;
+
+/// How one operator affects another.  The modulation index of the pair controls the amount for all
+/// connection types.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OperatorConnectionType {
+  /// The source operator's output modulates the destination operator's frequency
+  #[default]
+  FrequencyModulation = 0,
+  /// The destination operator's phase is reset each time the source operator's phase wraps around.
+  /// Indices below 1 scale the phase towards zero rather than resetting it, for a softer sync.
+  HardSync = 1,
+  /// The destination operator's output is multiplied by the source operator's output, crossfading
+  /// from no effect at an index of 0 to full ring modulation at 1
+  RingModulation = 2,
+}
+
+impl OperatorConnectionType {
+  pub fn from_usize(val: usize) -> Option<Self> {
+    match val {
+      0 => Some(Self::FrequencyModulation),
+      1 => Some(Self::HardSync),
+      2 => Some(Self::RingModulation),
+      _ => None,
+    }
+  }
+}
 
 /// Holds the weights that controls how much each operator modulates each of the other operators,
 /// itself via feedback, and outputs
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct ModulationMatrix {
   pub weights_per_operator: [[ParamSource; OPERATOR_COUNT]; OPERATOR_COUNT],
   pub output_weights: [ParamSource; OPERATOR_COUNT],
+  /// `connection_types[src_operator_ix][dst_operator_ix]`
+  pub connection_types: [[OperatorConnectionType; OPERATOR_COUNT]; OPERATOR_COUNT],
 }
 
 impl ModulationMatrix {
@@ -1689,8 +2105,16 @@ pub struct FMSynthContext {
   pub param_buffers: [[f32; FRAME_SIZE]; MAX_PARAM_BUFFERS],
   pub operator_base_frequency_sources: [ParamSource; OPERATOR_COUNT],
   pub base_frequency_input_buffer: Vec<[f32; FRAME_SIZE]>,
-  pub output_buffers: Vec<[f32; FRAME_SIZE]>,
+  /// Left and right channels for each voice
+  pub output_buffers: Vec<[[f32; FRAME_SIZE]; 2]>,
   pub frequency_multiplier: f32,
+  /// Gain applied to the output of all voices
+  pub master_gain: SmoothedParam,
+  /// Pitch bend in semitones applied to the base frequencies of all voices, including ones that
+  /// are already playing
+  pub pitch_bend: SmoothedParam,
+  /// Maps incoming note numbers to frequencies.  Defaults to 12-TET.
+  pub tuning: Tuning,
   pub most_recent_gated_voice_ix: usize,
   pub adsr_phase_buf: [f32; 256],
   pub detune: Option<ParamSource>,
@@ -1698,10 +2122,44 @@ pub struct FMSynthContext {
   pub sample_mapping_manager: SampleMappingManager,
   pub polysynth:
     PolySynth<Box<dyn Fn(usize, usize, u8, Option<f32>)>, Box<dyn Fn(usize, usize, Option<f32>)>>,
+  pub bouncer: Bouncer,
+  /// Slot 0 is the whole of `generate`, followed by one slot per voice
+  pub profiler: Profiler,
+  pub preset_morph: PresetMorph,
+  /// Sub-oscillator and noise layers mixed into every voice
+  pub voice_layers: VoiceLayers,
+  pub panning: Panning,
+  /// The modulated value of every effect param for the most recently gated voice as of the start
+  /// of the last frame, so the UI can show them moving.  Laid out as
+  /// `[chain_ix][effect_ix][param_ix]` where chains `0..OPERATOR_COUNT` are the operators' effect
+  /// chains and chain `OPERATOR_COUNT` is the main effect chain.  Params are in the order returned
+  /// by each effect's `get_params`.
+  pub effect_param_viz_buf: Box<[f32; EFFECT_PARAM_VIZ_BUF_LEN]>,
+  /// Events written by the AWP before calling `fm_synth_apply_control_events`; see
+  /// `control_events::CONTROL_EVENT_SIZE` for the layout
+  pub control_event_buf: Box<[f32; CONTROL_EVENT_BUF_LEN]>,
 }
 
 impl FMSynthContext {
   pub fn generate(&mut self, cur_bpm: f32, cur_frame_start_beat: f32) {
+    let generate_start = self.profiler.start(host_now);
+    let mut master_gain = [0.; FRAME_SIZE];
+    self.master_gain.fill(&mut master_gain);
+
+    let pitch_bend_active = self.pitch_bend.is_smoothing() || self.pitch_bend.current() != 0.;
+    let mut pitch_bend_multipliers = [1.; FRAME_SIZE];
+    if pitch_bend_active {
+      self.pitch_bend.fill(&mut pitch_bend_multipliers);
+      for multiplier in &mut pitch_bend_multipliers {
+        *multiplier = fastapprox::fast::pow2(*multiplier / 12.);
+      }
+    }
+    let mut bent_base_frequencies = [0.; FRAME_SIZE];
+    let voice_layers = self
+      .voice_layers
+      .render_frame()
+      .then_some(&self.voice_layers);
+
     for (voice_ix, voice) in self.voices.iter_mut().enumerate() {
       let base_frequency_buffer =
         unsafe { self.base_frequency_input_buffer.get_unchecked(voice_ix) };
@@ -1711,9 +2169,29 @@ impl FMSynthContext {
             unsafe { *store_phase_to = 0. };
           }
         }
+        if self.profiler.enabled {
+          self.profiler.record(1 + voice_ix, 0.);
+        }
         continue;
       }
       let output_buffer = unsafe { self.output_buffers.get_unchecked_mut(voice_ix) };
+      // Finished voices would just render zeros through all of their operators and effects
+      if voice.is_effectively_silent() {
+        *output_buffer = [[0.; FRAME_SIZE]; 2];
+        if self.profiler.enabled {
+          self.profiler.record(1 + voice_ix, 0.);
+        }
+        continue;
+      }
+      let voice_start = self.profiler.start(host_now);
+      let base_frequency_buffer = if pitch_bend_active {
+        for i in 0..FRAME_SIZE {
+          bent_base_frequencies[i] = base_frequency_buffer[i] * pitch_bend_multipliers[i];
+        }
+        &bent_base_frequencies
+      } else {
+        base_frequency_buffer
+      };
 
       voice.gen_samples(
         &mut self.modulation_matrix,
@@ -1724,15 +2202,17 @@ impl FMSynthContext {
         output_buffer,
         self.detune.as_ref(),
         &self.sample_mapping_manager,
+        voice_layers,
+        &self.panning,
       );
 
       voice
         .gain_envelope_generator
         .render_frame(1., 0., cur_bpm, cur_frame_start_beat);
       // TODO: Skip rendering filter ADSR if not enabled
-      // Currently hard-coded output range from [20, 44_100 / 2]
+      // Currently hard-coded output range from [20, nyquist]
       let filter_adsr_shift = 20.;
-      let filter_adsr_scale = 44_100. / 2. - filter_adsr_shift;
+      let filter_adsr_scale = dsp::nyquist() - filter_adsr_shift;
       voice.filter_envelope_generator.render_frame(
         filter_adsr_scale,
         filter_adsr_shift,
@@ -1749,9 +2229,28 @@ impl FMSynthContext {
         if voice.gain_envelope_generator.adsr.log_scale {
           gain = (gain - 0.001).max(0.);
         }
-        output_buffer[i] *= gain;
+        let gain = gain * master_gain[i];
+        output_buffer[0][i] *= gain;
+        output_buffer[1][i] *= gain;
+      }
+      voice.update_silent_frame_count(output_buffer);
+      self.profiler.end(1 + voice_ix, voice_start, host_now);
+
+      if voice_ix == self.most_recent_gated_voice_ix {
+        let effect_chains = voice
+          .operators
+          .iter()
+          .map(|operator| &operator.effect_chain)
+          .chain(std::iter::once(&voice.effect_chain));
+        let viz_chunks = self
+          .effect_param_viz_buf
+          .chunks_exact_mut(MAX_EFFECT_COUNT * 4);
+        for (effect_chain, viz_chunk) in effect_chains.zip(viz_chunks) {
+          viz_chunk.copy_from_slice(effect_chain.rendered_param_snapshot().as_flattened());
+        }
       }
     }
+    self.profiler.end(0, generate_start, host_now);
   }
 
   pub fn update_operator_enabled_statuses(&mut self) {
@@ -1787,6 +2286,43 @@ impl FMSynthContext {
   }
 }
 
+/// Creates the polysynth that allocates the voices of `ctx`.  JS is notified when voices are gated
+/// and ungated if `notify_voice_changes` is set.
+unsafe fn init_polysynth(ctx: *mut FMSynthContext, notify_voice_changes: bool) {
+  std::ptr::write(
+    &mut (*ctx).polysynth,
+    PolySynth::new(SynthCallbacks {
+      trigger_attack: Box::new(
+        move |voice_ix: usize, note_id: usize, _velocity: u8, offset: Option<f32>| {
+          let sample_offset = offset.map_or(0, |offset| offset as usize);
+          let frequency = (*ctx).tuning.note_to_frequency(note_id) * (*ctx).frequency_multiplier;
+          let base_frequencies = &mut (&mut *ctx).base_frequency_input_buffer[voice_ix];
+          // A voice that's still playing its previous note keeps its frequency until the gate.
+          // Idle voices are skipped if their first base frequency is 0, so theirs is set for the
+          // whole frame.
+          if base_frequencies[0] == 0. {
+            base_frequencies.fill(frequency);
+          } else {
+            base_frequencies[sample_offset.min(FRAME_SIZE - 1)..].fill(frequency);
+          }
+          gate_voice_inner(ctx, voice_ix, note_id, sample_offset);
+          if notify_voice_changes {
+            on_gate_cb(note_id, voice_ix);
+          }
+        },
+      ),
+      trigger_release: Box::new(
+        move |voice_ix: usize, note_id: usize, offset: Option<f32>| {
+          ungate_voice_inner(ctx, voice_ix, offset.map_or(0, |offset| offset as usize));
+          if notify_voice_changes {
+            on_ungate_cb(note_id, voice_ix);
+          }
+        },
+      ),
+    }),
+  );
+}
+
 #[no_mangle]
 #[cold]
 pub unsafe extern "C" fn init_fm_synth_ctx(voice_count: usize) -> *mut FMSynthContext {
@@ -1802,33 +2338,25 @@ pub unsafe extern "C" fn init_fm_synth_ctx(voice_count: usize) -> *mut FMSynthCo
     base_frequency_input_buffer: Vec::with_capacity(voice_count),
     output_buffers: Vec::with_capacity(voice_count),
     frequency_multiplier: 1.,
+    master_gain: SmoothedParam::new(SmoothingMode::Linear, 1., MASTER_GAIN_SMOOTHING_MS),
+    pitch_bend: SmoothedParam::new(SmoothingMode::Linear, 0., PITCH_BEND_SMOOTHING_MS),
+    tuning: Tuning::default(),
     most_recent_gated_voice_ix: 0,
     adsr_phase_buf: [0.; 256],
     detune: None,
     wavetables: Vec::new(),
     sample_mapping_manager: SampleMappingManager::default(),
     polysynth: uninit(),
+    bouncer: Bouncer::default(),
+    profiler: Profiler::new(1 + voice_count),
+    preset_morph: PresetMorph::default(),
+    voice_layers: VoiceLayers::default(),
+    panning: Panning::default(),
+    effect_param_viz_buf: Box::new([0.; EFFECT_PARAM_VIZ_BUF_LEN]),
+    control_event_buf: Box::new([0.; CONTROL_EVENT_BUF_LEN]),
   }));
 
-  std::ptr::write(
-    &mut (*ctx).polysynth,
-    PolySynth::new(SynthCallbacks {
-      trigger_attack: Box::new(
-        move |voice_ix: usize, note_id: usize, _velocity: u8, _offset: Option<f32>| {
-          let frequency = midi_number_to_frequency(note_id) * (*ctx).frequency_multiplier;
-          (&mut *ctx).base_frequency_input_buffer[voice_ix].fill(frequency);
-          gate_voice_inner(ctx, voice_ix, note_id);
-          on_gate_cb(note_id, voice_ix);
-        },
-      ),
-      trigger_release: Box::new(
-        move |voice_ix: usize, note_id: usize, _offset: Option<f32>| {
-          ungate_voice_inner(ctx, voice_ix);
-          on_ungate_cb(note_id, voice_ix);
-        },
-      ),
-    }),
-  );
+  init_polysynth(ctx, true);
 
   for i in 0..OPERATOR_COUNT {
     (*ctx)
@@ -1878,11 +2406,37 @@ pub unsafe extern "C" fn fm_synth_generate(
   ctx: *mut FMSynthContext,
   cur_bpm: f32,
   cur_frame_start_beat: f32,
-) -> *const [f32; FRAME_SIZE] {
+) -> *const [[f32; FRAME_SIZE]; 2] {
   (*ctx).generate(cur_bpm, cur_frame_start_beat);
   (*ctx).output_buffers.as_ptr()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_set_profiling_enabled(
+  ctx: *mut FMSynthContext,
+  enabled: bool,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_profiling_enabled") }?;
+    ctx.profiler.enabled = enabled;
+    Ok(())
+  })())
+}
+
+/// `(1 + voice_count) * dsp::profiling::STATS_PER_SLOT` long; see `FMSynthContext::profiler`
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_get_profiling_stats_ptr(ctx: *mut FMSynthContext) -> *const f32 {
+  (*ctx).profiler.stats().as_ptr()
+}
+
+/// `EFFECT_PARAM_VIZ_BUF_LEN` long; see `FMSynthContext::effect_param_viz_buf`
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_get_effect_param_viz_buf_ptr(
+  ctx: *mut FMSynthContext,
+) -> *const f32 {
+  (*ctx).effect_param_viz_buf.as_ptr()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn fm_synth_set_modulation_index(
   ctx: *mut FMSynthContext,
@@ -1893,17 +2447,47 @@ pub unsafe extern "C" fn fm_synth_set_modulation_index(
   val_param_float: f32,
   val_param_float_2: f32,
   val_param_float_3: f32,
-) {
-  let param = ParamSource::from_parts(
-    value_type,
-    val_param_int,
-    val_param_float,
-    val_param_float_2,
-    val_param_float_3,
-  );
-  (*ctx).modulation_matrix.weights_per_operator[src_operator_ix][dst_operator_ix].replace(param);
-
-  (*ctx).update_operator_enabled_statuses();
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_modulation_index") }?;
+    ffi::check_range("src_operator_ix", src_operator_ix, 0, OPERATOR_COUNT - 1)?;
+    ffi::check_range("dst_operator_ix", dst_operator_ix, 0, OPERATOR_COUNT - 1)?;
+    let param = param_source_from_parts(
+      "value_type",
+      value_type,
+      val_param_int,
+      val_param_float,
+      val_param_float_2,
+      val_param_float_3,
+    )?;
+    ctx.modulation_matrix.weights_per_operator[src_operator_ix][dst_operator_ix].replace(param);
+
+    ctx.update_operator_enabled_statuses();
+    Ok(())
+  })())
+}
+
+/// `connection_type` is the discriminant of an `OperatorConnectionType`
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_set_operator_connection_type(
+  ctx: *mut FMSynthContext,
+  src_operator_ix: usize,
+  dst_operator_ix: usize,
+  connection_type: usize,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_operator_connection_type") }?;
+    ffi::check_range("src_operator_ix", src_operator_ix, 0, OPERATOR_COUNT - 1)?;
+    ffi::check_range("dst_operator_ix", dst_operator_ix, 0, OPERATOR_COUNT - 1)?;
+    let connection_type = OperatorConnectionType::from_usize(connection_type).ok_or_else(|| {
+      ffi::set_last_error(
+        ErrorCode::ParamOutOfRange,
+        &format!("invalid operator connection type: {connection_type}"),
+      )
+    })?;
+    ctx.modulation_matrix.connection_types[src_operator_ix][dst_operator_ix] = connection_type;
+    Ok(())
+  })())
 }
 
 #[no_mangle]
@@ -1915,17 +2499,43 @@ pub unsafe extern "C" fn fm_synth_set_output_weight_value(
   val_param_float: f32,
   val_param_float_2: f32,
   val_param_float_3: f32,
-) {
-  let param = ParamSource::from_parts(
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_output_weight_value") }?;
+    ffi::check_range("operator_ix", operator_ix, 0, OPERATOR_COUNT - 1)?;
+    let param = param_source_from_parts(
+      "value_type",
+      value_type,
+      val_param_int,
+      val_param_float,
+      val_param_float_2,
+      val_param_float_3,
+    )?;
+    ctx.modulation_matrix.output_weights[operator_ix].replace(param);
+
+    ctx.update_operator_enabled_statuses();
+    Ok(())
+  })())
+}
+
+/// Builds a `ParamSource` from the raw parts passed in from JS, failing with
+/// `ErrorCode::ParamOutOfRange` rather than panicking if `value_type` is unknown.
+fn param_source_from_parts(
+  param_name: &str,
+  value_type: usize,
+  value_param_int: usize,
+  value_param_float: f32,
+  value_param_float_2: f32,
+  value_param_float_3: f32,
+) -> FfiResult<ParamSource> {
+  ffi::check_range(param_name, value_type, 0, MAX_PARAM_SOURCE_VALUE_TYPE)?;
+  Ok(ParamSource::from_parts(
     value_type,
-    val_param_int,
-    val_param_float,
-    val_param_float_2,
-    val_param_float_3,
-  );
-  (*ctx).modulation_matrix.output_weights[operator_ix].replace(param);
-
-  (*ctx).update_operator_enabled_statuses();
+    value_param_int,
+    value_param_float,
+    value_param_float_2,
+    value_param_float_3,
+  ))
 }
 
 fn initialize_phases<T: PhasedOscillator>(old_phases: &[f32], mut oscs: Vec<T>) -> Vec<T> {
@@ -1935,6 +2545,9 @@ fn initialize_phases<T: PhasedOscillator>(old_phases: &[f32], mut oscs: Vec<T>)
   oscs
 }
 
+/// Operator types accepted by `build_oscillator_source`.  Unison variants are offset by 50.
+pub(crate) const OPERATOR_TYPES: [usize; 14] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 50, 52, 54, 55, 56];
+
 fn build_oscillator_source(
   operator_type: usize,
   unison: usize,
@@ -1964,32 +2577,35 @@ fn build_oscillator_source(
   param_4_val_float_2: f32,
   param_4_val_float_3: f32,
   old_phases: &[f32],
-) -> OscillatorSource {
-  match operator_type {
+) -> FfiResult<OscillatorSource> {
+  Ok(match operator_type {
     0 => OscillatorSource::Wavetable(WaveTableHandle {
       wavetable_index: param_0_val_int,
       phase: old_phases.get(0).copied().unwrap_or_default(),
-      dim_0_intra_mix: ParamSource::from_parts(
+      dim_0_intra_mix: param_source_from_parts(
+        "param_1_value_type",
         param_1_value_type,
         param_1_val_int,
         param_1_val_float,
         param_1_val_float_2,
         param_1_val_float_3,
-      ),
-      dim_1_intra_mix: ParamSource::from_parts(
+      )?,
+      dim_1_intra_mix: param_source_from_parts(
+        "param_2_value_type",
         param_2_value_type,
         param_2_val_int,
         param_2_val_float,
         param_2_val_float_2,
         param_2_val_float_3,
-      ),
-      inter_dim_mix: ParamSource::from_parts(
+      )?,
+      inter_dim_mix: param_source_from_parts(
+        "param_3_value_type",
         param_3_value_type,
         param_3_val_int,
         param_3_val_float,
         param_3_val_float_2,
         param_3_val_float_3,
-      ),
+      )?,
     }),
     1 => OscillatorSource::ParamBuffer(param_0_val_int),
     2 => OscillatorSource::Sine(SineOscillator {
@@ -1997,13 +2613,14 @@ fn build_oscillator_source(
     }),
     3 => OscillatorSource::ExponentialOscillator(ExponentialOscillator {
       phase: old_phases.get(0).copied().unwrap_or_default(),
-      stretch_factor: ParamSource::from_parts(
+      stretch_factor: param_source_from_parts(
+        "param_0_value_type",
         param_0_value_type,
         param_0_val_int,
         param_0_val_float,
         param_0_val_float_2,
         param_0_val_float_3,
-      ),
+      )?,
     }),
     4 => OscillatorSource::Square(SquareOscillator {
       phase: old_phases.get(0).copied().unwrap_or_default(),
@@ -2017,84 +2634,96 @@ fn build_oscillator_source(
     7 => OscillatorSource::SampleMapping(SampleMappingEmitter::new()),
     8 => OscillatorSource::TunedSample(TunedSampleEmitter {}),
     52 => OscillatorSource::UnisonSine(UnisonOscillator::new(
-      ParamSource::from_parts(
+      param_source_from_parts(
+        "param_4_value_type",
         param_4_value_type,
         param_4_val_int,
         param_4_val_float,
         param_4_val_float_2,
         param_4_val_float_3,
-      ),
+      )?,
       initialize_phases(old_phases, vec![SineOscillator { phase: 0. }; unison]),
     )),
     50 => OscillatorSource::UnisonWavetable(UnisonOscillator::new(
-      ParamSource::from_parts(
+      param_source_from_parts(
+        "param_4_value_type",
         param_4_value_type,
         param_4_val_int,
         param_4_val_float,
         param_4_val_float_2,
         param_4_val_float_3,
-      ),
+      )?,
       initialize_phases(old_phases, vec![
         WaveTableHandle {
           wavetable_index: param_0_val_int,
           phase: old_phases.get(0).copied().unwrap_or_default(),
-          dim_0_intra_mix: ParamSource::from_parts(
+          dim_0_intra_mix: param_source_from_parts(
+            "param_1_value_type",
             param_1_value_type,
             param_1_val_int,
             param_1_val_float,
             param_1_val_float_2,
             param_1_val_float_3,
-          ),
-          dim_1_intra_mix: ParamSource::from_parts(
+          )?,
+          dim_1_intra_mix: param_source_from_parts(
+            "param_2_value_type",
             param_2_value_type,
             param_2_val_int,
             param_2_val_float,
             param_2_val_float_2,
             param_2_val_float_3,
-          ),
-          inter_dim_mix: ParamSource::from_parts(
+          )?,
+          inter_dim_mix: param_source_from_parts(
+            "param_3_value_type",
             param_3_value_type,
             param_3_val_int,
             param_3_val_float,
             param_3_val_float_2,
             param_3_val_float_3,
-          ),
+          )?,
         };
         unison
       ]),
     )),
     54 => OscillatorSource::UnisonSquare(UnisonOscillator::new(
-      ParamSource::from_parts(
+      param_source_from_parts(
+        "param_4_value_type",
         param_4_value_type,
         param_4_val_int,
         param_4_val_float,
         param_4_val_float_2,
         param_4_val_float_3,
-      ),
+      )?,
       initialize_phases(old_phases, vec![SquareOscillator { phase: 0. }; unison]),
     )),
     55 => OscillatorSource::UnisonTriangle(UnisonOscillator::new(
-      ParamSource::from_parts(
+      param_source_from_parts(
+        "param_4_value_type",
         param_4_value_type,
         param_4_val_int,
         param_4_val_float,
         param_4_val_float_2,
         param_4_val_float_3,
-      ),
+      )?,
       initialize_phases(old_phases, vec![TriangleOscillator { phase: 0. }; unison]),
     )),
     56 => OscillatorSource::UnisonSawtooth(UnisonOscillator::new(
-      ParamSource::from_parts(
+      param_source_from_parts(
+        "param_4_value_type",
         param_4_value_type,
         param_4_val_int,
         param_4_val_float,
         param_4_val_float_2,
         param_4_val_float_3,
-      ),
+      )?,
       initialize_phases(old_phases, vec![SawtoothOscillator { phase: 0. }; unison]),
     )),
-    _ => panic!("Invalid operator type: {}", operator_type),
-  }
+    _ =>
+      return Err(ffi::set_last_error(
+        ErrorCode::Unsupported,
+        &format!("invalid operator type: {operator_type}"),
+      )),
+  })
 }
 
 #[no_mangle]
@@ -2129,11 +2758,19 @@ pub unsafe extern "C" fn fm_synth_set_operator_config(
   param_4_val_float: f32,
   param_4_val_float_2: f32,
   param_4_val_float_3: f32,
-) {
-  for voice in &mut (*ctx).voices {
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "fm_synth_set_operator_config") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("operator_ix", operator_ix, 0, OPERATOR_COUNT - 1) {
+    return code;
+  }
+
+  for voice in &mut ctx.voices {
     let operator = &mut voice.operators[operator_ix];
     let old_phases = operator.oscillator_source.get_phase();
-    let new_oscillator_source = build_oscillator_source(
+    let new_oscillator_source = match build_oscillator_source(
       operator_type,
       unison,
       param_0_value_type,
@@ -2162,15 +2799,23 @@ pub unsafe extern "C" fn fm_synth_set_operator_config(
       param_4_val_float_2,
       param_4_val_float_3,
       &old_phases,
-    );
+    ) {
+      Ok(source) => source,
+      // Every voice gets the same config, so this can only fail on the first one
+      Err(code) => return code,
+    };
     let did_update = operator
       .oscillator_source
       .maybe_update(&new_oscillator_source);
     if !did_update {
       operator.oscillator_source = new_oscillator_source;
     }
+    operator
+      .oscillator_source
+      .set_unison_spread(operator.unison_spread);
     operator.randomize_start_phases = unison_phase_randomization_enabled;
   }
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -2182,15 +2827,20 @@ pub unsafe extern "C" fn fm_synth_set_operator_base_frequency_source(
   value_param_float: f32,
   val_param_float_2: f32,
   val_param_float_3: f32,
-) {
-  let param = ParamSource::from_parts(
-    value_type,
-    value_param_int,
-    value_param_float,
-    val_param_float_2,
-    val_param_float_3,
-  );
-  (*ctx).operator_base_frequency_sources[operator_ix] = param;
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_operator_base_frequency_source") }?;
+    ffi::check_range("operator_ix", operator_ix, 0, OPERATOR_COUNT - 1)?;
+    ctx.operator_base_frequency_sources[operator_ix] = param_source_from_parts(
+      "value_type",
+      value_type,
+      value_param_int,
+      value_param_float,
+      val_param_float_2,
+      val_param_float_3,
+    )?;
+    Ok(())
+  })())
 }
 
 #[no_mangle]
@@ -2201,22 +2851,27 @@ pub unsafe extern "C" fn fm_synth_set_detune(
   param_float_val: f32,
   param_float_val_2: f32,
   param_float_val_3: f32,
-) {
-  if param_type < 0 {
-    (*ctx).detune = None;
-    return;
-  }
-  let param = ParamSource::from_parts(
-    param_type as usize,
-    param_int_val,
-    param_float_val,
-    param_float_val_2,
-    param_float_val_3,
-  );
-  match &mut (*ctx).detune {
-    Some(old_detune) => old_detune.replace(param),
-    None => (*ctx).detune = Some(param),
-  }
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_detune") }?;
+    if param_type < 0 {
+      ctx.detune = None;
+      return Ok(());
+    }
+    let param = param_source_from_parts(
+      "param_type",
+      param_type as usize,
+      param_int_val,
+      param_float_val,
+      param_float_val_2,
+      param_float_val_3,
+    )?;
+    match &mut ctx.detune {
+      Some(old_detune) => old_detune.replace(param),
+      None => ctx.detune = Some(param),
+    }
+    Ok(())
+  })())
 }
 
 #[no_mangle]
@@ -2246,14 +2901,22 @@ pub unsafe extern "C" fn fm_synth_set_effect(
   param_4_float_val_2: f32,
   param_4_float_val_3: f32,
   is_bypassed: bool,
-) {
-  for voice in &mut (*ctx).voices {
-    let effect_chain = if operator_ix == -1 {
-      &mut voice.effect_chain
-    } else {
-      &mut voice.operators[operator_ix as usize].effect_chain
-    };
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "fm_synth_set_effect") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = check_effect_ix(operator_ix, effect_ix) {
+    return code;
+  }
+  if effect_type < -1 || effect_type >= EFFECT_TYPE_COUNT as isize {
+    return ffi::set_last_error(
+      ErrorCode::Unsupported,
+      &format!("fm_synth_set_effect: unknown effect type {effect_type}"),
+    );
+  }
 
+  for effect_chain in effect_chains_mut(ctx, operator_ix) {
     if effect_type == -1 {
       effect_chain.remove_effect(effect_ix);
     } else {
@@ -2284,19 +2947,121 @@ pub unsafe extern "C" fn fm_synth_set_effect(
       );
     }
   }
+  ErrorCode::Ok
+}
+
+/// Checks that `operator_ix` refers to an operator or to the main effect chain (-1) and that
+/// `effect_ix` is a valid slot in it.
+fn check_effect_ix(operator_ix: isize, effect_ix: usize) -> FfiResult {
+  ffi::check_range("operator_ix", operator_ix, -1, OPERATOR_COUNT as isize - 1)?;
+  ffi::check_range("effect_ix", effect_ix, 0, MAX_EFFECT_COUNT - 1)
+}
+
+/// Returns the effect chains of every voice that `operator_ix` refers to, where -1 is the main
+/// effect chain.
+fn effect_chains_mut(
+  ctx: &mut FMSynthContext,
+  operator_ix: isize,
+) -> impl Iterator<Item = &mut EffectChain> {
+  ctx.voices.iter_mut().flat_map(move |voice| {
+    let effect_chains = if operator_ix == -1 {
+      [
+        Some(&mut voice.effect_chain),
+        voice.effect_chain_right.as_mut(),
+      ]
+    } else {
+      [
+        Some(&mut voice.operators[operator_ix as usize].effect_chain),
+        None,
+      ]
+    };
+    effect_chains.into_iter().flatten()
+  })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn get_adsr_phases_buf_ptr(ctx: *mut FMSynthContext) -> *const f32 {
-  (*ctx).adsr_phase_buf.as_ptr()
+pub unsafe extern "C" fn fm_synth_move_effect(
+  ctx: *mut FMSynthContext,
+  operator_ix: isize,
+  from_effect_ix: usize,
+  to_effect_ix: usize,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_move_effect") }?;
+    check_effect_ix(operator_ix, from_effect_ix)?;
+    ffi::check_range("to_effect_ix", to_effect_ix, 0, MAX_EFFECT_COUNT - 1)?;
+    for effect_chain in effect_chains_mut(ctx, operator_ix) {
+      effect_chain.move_effect(from_effect_ix, to_effect_ix);
+    }
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_set_effect_bypassed(
+  ctx: *mut FMSynthContext,
+  operator_ix: isize,
+  effect_ix: usize,
+  is_bypassed: bool,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_effect_bypassed") }?;
+    check_effect_ix(operator_ix, effect_ix)?;
+    for effect_chain in effect_chains_mut(ctx, operator_ix) {
+      effect_chain.set_bypassed(effect_ix, is_bypassed);
+    }
+    Ok(())
+  })())
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn gate(ctx: *mut FMSynthContext, midi_number: usize) {
-  (*ctx).polysynth.trigger_attack(midi_number, 0, None);
+pub unsafe extern "C" fn fm_synth_set_effect_mix(
+  ctx: *mut FMSynthContext,
+  operator_ix: isize,
+  effect_ix: usize,
+  mix: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_effect_mix") }?;
+    check_effect_ix(operator_ix, effect_ix)?;
+    for effect_chain in effect_chains_mut(ctx, operator_ix) {
+      effect_chain.set_mix(effect_ix, mix);
+    }
+    Ok(())
+  })())
 }
 
-unsafe fn gate_voice_inner(ctx: *mut FMSynthContext, voice_ix: usize, midi_number: usize) {
+#[no_mangle]
+pub unsafe extern "C" fn get_adsr_phases_buf_ptr(ctx: *mut FMSynthContext) -> *const f32 {
+  (*ctx).adsr_phase_buf.as_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn gate(ctx: *mut FMSynthContext, midi_number: usize) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "gate") }?;
+    ffi::check_range("midi_number", midi_number, 0, 127)?;
+    ctx.polysynth.trigger_attack(midi_number, 0, None);
+    Ok(())
+  })())
+}
+
+/// Gates `midi_number` `sample_offset` samples into the next frame
+pub(crate) unsafe fn gate_at(ctx: *mut FMSynthContext, midi_number: usize, sample_offset: usize) {
+  (*ctx)
+    .polysynth
+    .trigger_attack(midi_number, 0, Some(sample_offset as f32));
+}
+
+/// Envelopes are gated `sample_offset` samples into the next frame.  Everything else about the
+/// voice is reset at the start of the frame, which is inaudible since its gain envelope is still
+/// at the end of its previous release or the gain of its previous note until then.
+unsafe fn gate_voice_inner(
+  ctx: *mut FMSynthContext,
+  voice_ix: usize,
+  midi_number: usize,
+  sample_offset: usize,
+) {
   // Stop recording phases for the last recently gated voice so the new one can record them
   let old_phases_voice = &mut (*ctx).voices[(*ctx).most_recent_gated_voice_ix];
   for adsr in &mut old_phases_voice.adsrs {
@@ -2312,14 +3077,22 @@ unsafe fn gate_voice_inner(ctx: *mut FMSynthContext, voice_ix: usize, midi_numbe
   let voice = &mut (*ctx).voices[voice_ix];
   for (i, adsr) in voice.adsrs.iter_mut().enumerate() {
     adsr.store_phase_to = Some(((*ctx).adsr_phase_buf.as_mut_ptr() as *mut f32).add(i));
-    adsr.gate(0.);
+    adsr.gate_at(0., sample_offset);
   }
 
   voice.last_gated_midi_number = midi_number;
-  voice.gain_envelope_generator.adsr.gate(0.);
+  voice.silent_frame_count = 0;
+  voice.layer_state.gate();
+  voice
+    .gain_envelope_generator
+    .adsr
+    .gate_at(0., sample_offset);
   voice.gain_envelope_generator.adsr.store_phase_to =
     Some(((*ctx).adsr_phase_buf.as_mut_ptr() as *mut f32).add(GAIN_ENVELOPE_PHASE_BUF_INDEX));
-  voice.filter_envelope_generator.adsr.gate(0.);
+  voice
+    .filter_envelope_generator
+    .adsr
+    .gate_at(0., sample_offset);
   voice.filter_envelope_generator.adsr.store_phase_to =
     Some(((*ctx).adsr_phase_buf.as_mut_ptr() as *mut f32).add(FILTER_ENVELOPE_PHASE_BUF_INDEX));
 
@@ -2338,53 +3111,273 @@ unsafe fn gate_voice_inner(ctx: *mut FMSynthContext, voice_ix: usize, midi_numbe
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn fm_synth_clear_output_buffer(ctx: *mut FMSynthContext, voice_ix: usize) {
-  let ctx = &mut (*ctx);
-  ctx.base_frequency_input_buffer[voice_ix].fill(0.);
-  let buf = &mut ctx.output_buffers[voice_ix];
-  buf.fill(0.);
+pub unsafe extern "C" fn fm_synth_clear_output_buffer(
+  ctx: *mut FMSynthContext,
+  voice_ix: usize,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_clear_output_buffer") }?;
+    ffi::check_range(
+      "voice_ix",
+      voice_ix,
+      0,
+      ctx.output_buffers.len().saturating_sub(1),
+    )?;
+    ctx.base_frequency_input_buffer[voice_ix].fill(0.);
+    let buf = &mut ctx.output_buffers[voice_ix];
+    buf[0].fill(0.);
+    buf[1].fill(0.);
+    Ok(())
+  })())
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn fm_synth_set_frequency_multiplier(
   ctx: *mut FMSynthContext,
   frequency_multiplier: f32,
-) {
-  (*ctx).frequency_multiplier = frequency_multiplier;
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_frequency_multiplier") }?;
+    ctx.frequency_multiplier =
+      ffi::clamp_param("frequency_multiplier", frequency_multiplier, 0., f32::MAX)?;
+    Ok(())
+  })())
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn ungate(ctx: *mut FMSynthContext, midi_number: usize) {
-  (*ctx).polysynth.trigger_release(midi_number, None);
+pub unsafe extern "C" fn fm_synth_set_master_gain(
+  ctx: *mut FMSynthContext,
+  master_gain: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_master_gain") }?;
+    ctx
+      .master_gain
+      .set_target(ffi::clamp_param("master_gain", master_gain, 0., f32::MAX)?);
+    Ok(())
+  })())
+}
+
+/// `sub_shape` is the discriminant of a `SubOscillatorShape`.  Levels of 0 disable the layers.
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_set_voice_layers(
+  ctx: *mut FMSynthContext,
+  sub_octaves: u32,
+  sub_shape: usize,
+  sub_level: f32,
+  noise_level: f32,
+  noise_decay_ms: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_voice_layers") }?;
+    let sub_shape = SubOscillatorShape::from_usize(sub_shape).ok_or_else(|| {
+      ffi::set_last_error(
+        ErrorCode::ParamOutOfRange,
+        &format!("invalid sub-oscillator shape: {sub_shape}"),
+      )
+    })?;
+    ctx.voice_layers.set(
+      sub_octaves,
+      sub_shape,
+      sub_level,
+      noise_level,
+      noise_decay_ms,
+    );
+    Ok(())
+  })())
 }
 
+/// Sets the pan of an operator along with the stereo spread of its unison oscillators, if it has
+/// any.  See `panning::balance_gains` for the pan law.
 #[no_mangle]
-pub unsafe extern "C" fn ungate_all(ctx: *mut FMSynthContext) { (*ctx).polysynth.release_all(); }
+pub unsafe extern "C" fn fm_synth_set_operator_pan(
+  ctx: *mut FMSynthContext,
+  operator_ix: usize,
+  unison_spread: f32,
+  pan_value_type: usize,
+  pan_val_int: usize,
+  pan_val_float: f32,
+  pan_val_float_2: f32,
+  pan_val_float_3: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_operator_pan") }?;
+    ffi::check_range("operator_ix", operator_ix, 0, OPERATOR_COUNT - 1)?;
+    let pan = param_source_from_parts(
+      "pan_value_type",
+      pan_value_type,
+      pan_val_int,
+      pan_val_float,
+      pan_val_float_2,
+      pan_val_float_3,
+    )?;
+    let unison_spread = ffi::clamp_param("unison_spread", unison_spread, 0., 1.)?;
+    for voice in &mut ctx.voices {
+      let operator = &mut voice.operators[operator_ix];
+      operator.unison_spread = unison_spread;
+      operator.oscillator_source.set_unison_spread(unison_spread);
+    }
+
+    ctx.panning.operator_pans[operator_ix].replace(pan);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_set_voice_pan(
+  ctx: *mut FMSynthContext,
+  pan_value_type: usize,
+  pan_val_int: usize,
+  pan_val_float: f32,
+  pan_val_float_2: f32,
+  pan_val_float_3: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_voice_pan") }?;
+    ctx.panning.voice_pan.replace(param_source_from_parts(
+      "pan_value_type",
+      pan_value_type,
+      pan_val_int,
+      pan_val_float,
+      pan_val_float_2,
+      pan_val_float_3,
+    )?);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_set_pitch_bend(
+  ctx: *mut FMSynthContext,
+  semitones: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_pitch_bend") }?;
+    ctx
+      .pitch_bend
+      .set_target(ffi::clamp_param("semitones", semitones, -48., 48.)?);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_set_tuning(
+  ctx: *mut FMSynthContext,
+  divisions_per_octave: usize,
+  reference_note: usize,
+  reference_frequency: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_tuning") }?;
+    ffi::check_range("divisions_per_octave", divisions_per_octave, 1, 128)?;
+    ffi::check_range("reference_note", reference_note, 0, 127)?;
+    ffi::check_range("reference_frequency", reference_frequency, 1., 20_000.)?;
+    ctx.tuning = Tuning::new(divisions_per_octave, reference_note, reference_frequency);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ungate(ctx: *mut FMSynthContext, midi_number: usize) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "ungate") }?;
+    ffi::check_range("midi_number", midi_number, 0, 127)?;
+    ctx.polysynth.trigger_release(midi_number, None);
+    Ok(())
+  })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ungate_all(ctx: *mut FMSynthContext) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "ungate_all") }?;
+    ctx.polysynth.release_all();
+    Ok(())
+  })())
+}
+
+/// Ungates `midi_number` `sample_offset` samples into the next frame
+pub(crate) unsafe fn ungate_at(ctx: *mut FMSynthContext, midi_number: usize, sample_offset: usize) {
+  (*ctx)
+    .polysynth
+    .trigger_release(midi_number, Some(sample_offset as f32));
+}
+
+/// Ungates all playing notes `sample_offset` samples into the next frame
+pub(crate) unsafe fn ungate_all_at(ctx: *mut FMSynthContext, sample_offset: usize) {
+  (*ctx).polysynth.release_all_at(Some(sample_offset as f32));
+}
 
-unsafe fn ungate_voice_inner(ctx: *mut FMSynthContext, voice_ix: usize) {
+unsafe fn ungate_voice_inner(ctx: *mut FMSynthContext, voice_ix: usize, sample_offset: usize) {
   let voice = &mut (*ctx).voices[voice_ix];
 
   for adsr in &mut voice.adsrs {
-    adsr.ungate();
+    adsr.ungate_at(sample_offset);
   }
 
-  voice.gain_envelope_generator.adsr.ungate();
-  voice.filter_envelope_generator.adsr.ungate();
+  voice.gain_envelope_generator.adsr.ungate_at(sample_offset);
+  voice
+    .filter_envelope_generator
+    .adsr
+    .ungate_at(sample_offset);
 }
 
-static mut ADSR_STEP_BUFFER: [AdsrStep; 512] = [AdsrStep {
+/// Most steps an ADSR can have when set through `set_adsr`
+pub const MAX_ADSR_STEP_COUNT: usize = 512;
+
+static mut ADSR_STEP_BUFFER: [AdsrStep; MAX_ADSR_STEP_COUNT] = [AdsrStep {
   x: 0.,
   y: 0.,
   ramper: RampFn::Linear,
-}; 512];
+}; MAX_ADSR_STEP_COUNT];
+
+/// Ramper types accepted by `RampFn::from_u32`
+pub(crate) const MAX_RAMPER_TYPE: u32 = 2;
 
 #[no_mangle]
-pub unsafe extern "C" fn set_adsr_step_buffer(i: usize, x: f32, y: f32, ramper: u32, param: f32) {
-  ADSR_STEP_BUFFER[i] = AdsrStep {
-    x,
-    y,
-    ramper: RampFn::from_u32(ramper, param),
+pub unsafe extern "C" fn set_adsr_step_buffer(
+  i: usize,
+  x: f32,
+  y: f32,
+  ramper: u32,
+  param: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    ffi::check_range("i", i, 0, MAX_ADSR_STEP_COUNT - 1)?;
+    ffi::check_range("ramper", ramper, 0, MAX_RAMPER_TYPE)?;
+    ADSR_STEP_BUFFER[i] = AdsrStep {
+      x,
+      y,
+      ramper: RampFn::from_u32(ramper, param),
+    };
+    Ok(())
+  })())
+}
+
+/// Checks that `adsr_ix` is either the gain envelope (-1), the filter envelope (-2), or one of the
+/// `adsr_count` existing ADSRs.  If `allow_new` is set, `adsr_count` is accepted as well to add a
+/// new ADSR.  The gain and filter envelopes only support lengths in samples or beats.
+fn check_adsr_ix(
+  adsr_ix: isize,
+  adsr_count: usize,
+  allow_new: bool,
+  len_samples_type: usize,
+) -> FfiResult {
+  let max_adsr_ix = if allow_new {
+    adsr_count as isize
+  } else {
+    adsr_count as isize - 1
+  };
+  ffi::check_range("adsr_ix", adsr_ix, -2, max_adsr_ix)?;
+  if adsr_ix < 0 && len_samples_type != 1 && len_samples_type != 5 {
+    return Err(ffi::set_last_error(
+      ErrorCode::Unsupported,
+      &format!(
+        "envelope length value type {len_samples_type} is unsupported for adsr_ix={adsr_ix}"
+      ),
+    ));
   }
+  Ok(())
 }
 
 #[no_mangle]
@@ -2400,105 +3393,106 @@ pub unsafe extern "C" fn set_adsr(
   release_start_phase: f32,
   loop_point: f32,
   log_scale: bool,
-) {
-  let shared_buffer = Box::new([0.0f32; RENDERED_BUFFER_SIZE]);
-  let shared_buffer: Rc<[f32; RENDERED_BUFFER_SIZE]> = shared_buffer.into();
-
-  for voice in &mut (*ctx).voices {
-    let mut new_adsr = Adsr::new(
-      ADSR_STEP_BUFFER[..step_count].to_owned(),
-      if loop_point < 0. {
-        None
-      } else {
-        Some(loop_point)
-      },
-      0.,   // This will be overridden when ADSRs are rendered
-      None, // Maybe we want to set this later?
-      release_start_phase,
-      shared_buffer.clone(),
-      EarlyReleaseConfig::default(),
-      log_scale,
-    );
-    let params = AdsrParams {
-      len_samples: ParamSource::from_parts(
-        len_samples_type,
-        len_samples_int_val,
-        len_samples_float_val,
-        len_samples_float_val_2,
-        len_samples_float_val_3,
-      ),
-    };
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "set_adsr") }?;
+    ffi::check_range("step_count", step_count, 0, MAX_ADSR_STEP_COUNT)?;
+    let adsr_count = ctx.voices.first().map_or(0, |voice| voice.adsrs.len());
+    check_adsr_ix(adsr_ix, adsr_count, true, len_samples_type)?;
+    let len_samples = param_source_from_parts(
+      "len_samples_type",
+      len_samples_type,
+      len_samples_int_val,
+      len_samples_float_val,
+      len_samples_float_val_2,
+      len_samples_float_val_3,
+    )?;
+
+    let shared_buffer = Box::new([0.0f32; RENDERED_BUFFER_SIZE]);
+    let shared_buffer: Rc<[f32; RENDERED_BUFFER_SIZE]> = shared_buffer.into();
+
+    for voice in &mut ctx.voices {
+      let mut new_adsr = Adsr::new(
+        ADSR_STEP_BUFFER[..step_count].to_owned(),
+        if loop_point < 0. {
+          None
+        } else {
+          Some(loop_point)
+        },
+        0.,   // This will be overridden when ADSRs are rendered
+        None, // Maybe we want to set this later?
+        release_start_phase,
+        shared_buffer.clone(),
+        EarlyReleaseConfig::default(),
+        log_scale,
+      );
+      let params = AdsrParams {
+        len_samples: len_samples.clone(),
+      };
 
-    let old_adsr = if adsr_ix == -1 {
-      let adsr = &mut voice.gain_envelope_generator;
-      match params.len_samples {
-        ParamSource::Constant { cur_val, .. } =>
-          adsr.set_length(AdsrLengthMode::Ms, samples_to_ms(cur_val)),
-        ParamSource::BeatsToSamples(beats) => adsr.set_length(AdsrLengthMode::Beats, beats),
-        _ => unimplemented!(),
-      }
-      Some(&mut adsr.adsr)
-    } else if adsr_ix == -2 {
-      let adsr = &mut voice.filter_envelope_generator;
-      match params.len_samples {
-        ParamSource::Constant { cur_val, .. } =>
-          adsr.set_length(AdsrLengthMode::Ms, samples_to_ms(cur_val)),
-        ParamSource::BeatsToSamples(beats) => adsr.set_length(AdsrLengthMode::Beats, beats),
-        _ => unimplemented!(),
-      }
-      Some(&mut adsr.adsr)
-    } else {
-      voice.adsrs.get_mut(adsr_ix as usize)
-    };
+      let old_adsr = if adsr_ix == -1 {
+        let adsr = &mut voice.gain_envelope_generator;
+        match params.len_samples {
+          ParamSource::Constant { cur_val, .. } =>
+            adsr.set_length(AdsrLengthMode::Ms, samples_to_ms(cur_val)),
+          ParamSource::BeatsToSamples(beats) => adsr.set_length(AdsrLengthMode::Beats, beats),
+          _ => unreachable!(),
+        }
+        Some(&mut adsr.adsr)
+      } else if adsr_ix == -2 {
+        let adsr = &mut voice.filter_envelope_generator;
+        match params.len_samples {
+          ParamSource::Constant { cur_val, .. } =>
+            adsr.set_length(AdsrLengthMode::Ms, samples_to_ms(cur_val)),
+          ParamSource::BeatsToSamples(beats) => adsr.set_length(AdsrLengthMode::Beats, beats),
+          _ => unreachable!(),
+        }
+        Some(&mut adsr.adsr)
+      } else {
+        voice.adsrs.get_mut(adsr_ix as usize)
+      };
 
-    if let Some(old_adsr) = old_adsr {
-      let old_phase = old_adsr.phase;
-      let gate_status = old_adsr.gate_status;
-      let store_phase_to = old_adsr.store_phase_to;
+      if let Some(old_adsr) = old_adsr {
+        let old_phase = old_adsr.phase;
+        let gate_status = old_adsr.gate_status;
+        let store_phase_to = old_adsr.store_phase_to;
 
-      new_adsr.phase = match gate_status {
-        GateStatus::GatedFrozen => release_start_phase,
-        GateStatus::Done => 1.,
-        _ => old_phase,
-      };
-      // Switch out of frozen states into active ones to trigger one frame of samples to be
-      // generated for the new ADSR
-      new_adsr.gate_status = match gate_status {
-        GateStatus::GatedFrozen => GateStatus::Gated,
-        GateStatus::Done => GateStatus::Releasing,
-        other => other,
-      };
-      new_adsr.store_phase_to = store_phase_to;
-      *old_adsr = new_adsr;
-      if adsr_ix >= 0 {
-        voice.adsr_params[adsr_ix as usize] = params;
-      } else if adsr_ix == -1 {
-        old_adsr.set_len(len_samples_float_val, None);
-      } else if adsr_ix == -2 {
-        // TODO: Need to be able to handle beat length mode
-        old_adsr.set_len(len_samples_float_val, None);
+        new_adsr.phase = match gate_status {
+          GateStatus::GatedFrozen => release_start_phase,
+          GateStatus::Done => 1.,
+          _ => old_phase,
+        };
+        // Switch out of frozen states into active ones to trigger one frame of samples to be
+        // generated for the new ADSR
+        new_adsr.gate_status = match gate_status {
+          GateStatus::GatedFrozen => GateStatus::Gated,
+          GateStatus::Done => GateStatus::Releasing,
+          other => other,
+        };
+        new_adsr.store_phase_to = store_phase_to;
+        *old_adsr = new_adsr;
+        if adsr_ix >= 0 {
+          voice.adsr_params[adsr_ix as usize] = params;
+        } else {
+          // TODO: Need to be able to handle beat length mode for the filter envelope
+          old_adsr.set_len(len_samples_float_val, None);
+        }
       } else {
-        unimplemented!()
+        // `check_adsr_ix` only lets through one past the last ADSR
+        voice.adsrs.push(new_adsr);
+        voice.adsr_params.push(params);
       }
-    } else if voice.adsrs.len() != adsr_ix as usize {
-      panic!(
-        "Tried to set ADSR index {} but only {} adsrs exist",
-        adsr_ix,
-        voice.adsrs.len()
-      );
-    } else {
-      voice.adsrs.push(new_adsr);
-      voice.adsr_params.push(params);
     }
-  }
-  // Render the ADSR's shared buffer
-  if adsr_ix == -1 {
-    (*ctx).voices[0].gain_envelope_generator.render();
-  } else if adsr_ix == -2 {
-    (*ctx).voices[0].filter_envelope_generator.render();
-  } else {
-    (*ctx).voices[0].adsrs[adsr_ix as usize].render();
-  }
+    // Render the ADSR's shared buffer
+    if let Some(voice) = ctx.voices.first_mut() {
+      match adsr_ix {
+        -1 => voice.gain_envelope_generator.render(),
+        -2 => voice.filter_envelope_generator.render(),
+        _ => voice.adsrs[adsr_ix as usize].render(),
+      }
+    }
+    Ok(())
+  })())
 }
 
 #[no_mangle]
@@ -2510,45 +3504,43 @@ pub unsafe extern "C" fn set_adsr_length(
   len_samples_float_val: f32,
   len_samples_float_val_2: f32,
   len_samples_float_val_3: f32,
-) {
-  let param = ParamSource::from_parts(
-    len_samples_type,
-    len_samples_int_val,
-    len_samples_float_val,
-    len_samples_float_val_2,
-    len_samples_float_val_3,
-  );
-
-  if adsr_ix == -1 {
-    // gain envelope
-    for voice in &mut (*ctx).voices {
-      let adsr = &mut voice.gain_envelope_generator;
-      match param {
-        ParamSource::Constant { cur_val, .. } =>
-          adsr.set_length(AdsrLengthMode::Ms, samples_to_ms(cur_val)),
-        ParamSource::BeatsToSamples(beats) => adsr.set_length(AdsrLengthMode::Beats, beats),
-        _ => unimplemented!(),
-      }
-    }
-    return;
-  } else if adsr_ix == -2 {
-    // filter envelope
-    for voice in &mut (*ctx).voices {
-      let adsr = &mut voice.filter_envelope_generator;
-      match param {
-        ParamSource::Constant { cur_val, .. } =>
-          adsr.set_length(AdsrLengthMode::Ms, samples_to_ms(cur_val)),
-        ParamSource::BeatsToSamples(beats) => adsr.set_length(AdsrLengthMode::Beats, beats),
-        _ => unimplemented!(),
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "set_adsr_length") }?;
+    let adsr_count = ctx.voices.first().map_or(0, |voice| voice.adsrs.len());
+    check_adsr_ix(adsr_ix, adsr_count, false, len_samples_type)?;
+    let param = param_source_from_parts(
+      "len_samples_type",
+      len_samples_type,
+      len_samples_int_val,
+      len_samples_float_val,
+      len_samples_float_val_2,
+      len_samples_float_val_3,
+    )?;
+
+    if adsr_ix < 0 {
+      for voice in &mut ctx.voices {
+        let adsr = if adsr_ix == -1 {
+          &mut voice.gain_envelope_generator
+        } else {
+          &mut voice.filter_envelope_generator
+        };
+        match param {
+          ParamSource::Constant { cur_val, .. } =>
+            adsr.set_length(AdsrLengthMode::Ms, samples_to_ms(cur_val)),
+          ParamSource::BeatsToSamples(beats) => adsr.set_length(AdsrLengthMode::Beats, beats),
+          _ => unreachable!(),
+        }
       }
+      return Ok(());
     }
-    return;
-  }
-  let adsr_ix = adsr_ix as usize;
+    let adsr_ix = adsr_ix as usize;
 
-  for voice in &mut (*ctx).voices {
-    voice.adsr_params[adsr_ix].len_samples = param.clone();
-  }
+    for voice in &mut ctx.voices {
+      voice.adsr_params[adsr_ix].len_samples = param.clone();
+    }
+    Ok(())
+  })())
 }
 
 #[no_mangle]
@@ -2603,10 +3595,15 @@ pub extern "C" fn fm_synth_set_mapped_sample_midi_number_count(
   ctx: *mut FMSynthContext,
   operator_ix: usize,
   mapped_midi_number_count: usize,
-) {
-  let ctx = unsafe { &mut *ctx };
-  ctx.sample_mapping_manager.config_by_operator[operator_ix]
-    .set_mapped_sample_midi_number_count(mapped_midi_number_count);
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_mapped_sample_midi_number_count") }?;
+    ffi::check_range("operator_ix", operator_ix, 0, OPERATOR_COUNT - 1)?;
+    ffi::check_range("mapped_midi_number_count", mapped_midi_number_count, 0, 128)?;
+    ctx.sample_mapping_manager.config_by_operator[operator_ix]
+      .set_mapped_sample_midi_number_count(mapped_midi_number_count);
+    Ok(())
+  })())
 }
 
 #[no_mangle]
@@ -2616,10 +3613,27 @@ pub extern "C" fn fm_synth_set_mapped_sample_data_for_midi_number_slot(
   midi_number_slot_ix: usize,
   midi_number: usize,
   mapped_sample_count: usize,
-) {
-  let ctx = unsafe { &mut *ctx };
-  ctx.sample_mapping_manager.config_by_operator[operator_ix]
-    .set_mapped_sample_data_for_midi_number(midi_number_slot_ix, midi_number, mapped_sample_count);
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_mapped_sample_data_for_midi_number_slot") }?;
+    ffi::check_range("operator_ix", operator_ix, 0, OPERATOR_COUNT - 1)?;
+    let config = &mut ctx.sample_mapping_manager.config_by_operator[operator_ix];
+    if midi_number_slot_ix >= config.mapped_samples_by_midi_number.len() {
+      return Err(ffi::set_last_error(
+        ErrorCode::ParamOutOfRange,
+        &format!(
+          "no midi number slot has been allocated at midi_number_slot_ix={midi_number_slot_ix}"
+        ),
+      ));
+    }
+    ffi::check_range("midi_number", midi_number, 0, 127)?;
+    config.set_mapped_sample_data_for_midi_number(
+      midi_number_slot_ix,
+      midi_number,
+      mapped_sample_count,
+    );
+    Ok(())
+  })())
 }
 
 #[no_mangle]
@@ -2634,10 +3648,38 @@ pub extern "C" fn fm_synth_set_mapped_sample_config(
   start_ix: usize,
   end_ix: usize,
   playback_rate: f32,
-) {
-  let ctx = unsafe { &mut *ctx };
-  ctx.sample_mapping_manager.config_by_operator[operator_ix]
-    .set_mapped_sample_config_for_midi_number(
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_mapped_sample_config") }?;
+    ffi::check_range("operator_ix", operator_ix, 0, OPERATOR_COUNT - 1)?;
+    let config = &mut ctx.sample_mapping_manager.config_by_operator[operator_ix];
+    let mapped_samples = config
+      .mapped_samples_by_midi_number
+      .get(midi_number_ix)
+      .ok_or_else(|| {
+        ffi::set_last_error(
+          ErrorCode::ParamOutOfRange,
+          &format!("no midi number slot has been allocated at midi_number_ix={midi_number_ix}"),
+        )
+      })?;
+    if mapped_sample_ix >= mapped_samples.data.len() {
+      return Err(ffi::set_last_error(
+        ErrorCode::ParamOutOfRange,
+        &format!(
+          "mapped_sample_ix={mapped_sample_ix} is outside of the {} mapped samples for \
+           midi_number_ix={midi_number_ix}",
+          mapped_samples.data.len()
+        ),
+      ));
+    }
+    let sample_count = sample_manager().samples.len();
+    ffi::check_range(
+      "sample_data_ix",
+      sample_data_ix,
+      -1,
+      sample_count as isize - 1,
+    )?;
+    config.set_mapped_sample_config_for_midi_number(
       midi_number_ix,
       mapped_sample_ix,
       sample_data_ix,
@@ -2646,7 +3688,9 @@ pub extern "C" fn fm_synth_set_mapped_sample_config(
       start_ix,
       end_ix,
       playback_rate,
-    )
+    );
+    Ok(())
+  })())
 }
 
 #[no_mangle]
@@ -2662,3 +3706,96 @@ pub unsafe extern "C" fn fm_synth_get_filter_adsr_output_buf_ptr(
     .get_cur_frame_output()
     .as_ptr()
 }
+
+#[test]
+fn hard_sync_and_ring_modulation_only_affect_their_connections() {
+  let mut operators: [Operator; OPERATOR_COUNT] = Default::default();
+  for operator in &mut operators {
+    operator.oscillator_source.set_phase(&[0.5]);
+  }
+  let mut modulation_indices = Box::new([[[0.; FRAME_SIZE]; OPERATOR_COUNT]; OPERATOR_COUNT]);
+  modulation_indices[0][1] = [1.; FRAME_SIZE];
+  modulation_indices[0][2] = [0.5; FRAME_SIZE];
+  modulation_indices[0][3] = [1.; FRAME_SIZE];
+  let mut connection_types = [[OperatorConnectionType::default(); OPERATOR_COUNT]; OPERATOR_COUNT];
+  connection_types[0][1] = OperatorConnectionType::HardSync;
+  connection_types[0][2] = OperatorConnectionType::HardSync;
+  connection_types[0][3] = OperatorConnectionType::RingModulation;
+
+  apply_hard_sync(&mut operators, 0, 0, &modulation_indices, &connection_types);
+  let phases: Vec<_> = operators
+    .iter()
+    .map(|op| op.oscillator_source.get_sync_phase().unwrap())
+    .collect();
+  assert_eq!(&phases[..4], &[0.5, 0., 0.25, 0.5]);
+
+  let mut last_samples = [0.; OPERATOR_COUNT];
+  last_samples[0] = -0.5;
+  let ring_modulated = |operator_ix| {
+    apply_ring_modulation(
+      0.8,
+      &last_samples,
+      operator_ix,
+      0,
+      &modulation_indices,
+      &connection_types,
+    )
+  };
+  assert_eq!(ring_modulated(3), -0.4);
+  assert_eq!(ring_modulated(1), 0.8);
+}
+
+#[test]
+fn finished_voices_are_skipped_once_silent() {
+  let mut voice = FMSynthVoice::new(
+    Rc::new([0.; RENDERED_BUFFER_SIZE]),
+    Rc::new([0.; RENDERED_BUFFER_SIZE]),
+  );
+  let mut output = [[0.; FRAME_SIZE]; 2];
+  for _ in 0..SILENT_VOICE_FRAME_COUNT - 1 {
+    voice.update_silent_frame_count(&output);
+  }
+  assert!(!voice.is_effectively_silent());
+
+  // Any audible output restarts the count
+  output[1][10] = 0.01;
+  voice.update_silent_frame_count(&output);
+  output[1][10] = 0.;
+  for _ in 0..SILENT_VOICE_FRAME_COUNT {
+    voice.update_silent_frame_count(&output);
+  }
+  assert!(voice.is_effectively_silent());
+
+  voice.gain_envelope_generator.adsr.gate(0.);
+  assert!(!voice.is_effectively_silent());
+}
+
+#[test]
+fn invalid_operator_connection_types_are_rejected() {
+  let ctx = unsafe { init_fm_synth_ctx(1) };
+  assert_eq!(
+    unsafe { fm_synth_set_operator_connection_type(ctx, 0, 1, 3) },
+    ErrorCode::ParamOutOfRange
+  );
+  assert_eq!(
+    unsafe { fm_synth_set_operator_connection_type(ctx, 0, 1, 2) },
+    ErrorCode::Ok
+  );
+  assert_eq!(
+    unsafe { (*ctx).modulation_matrix.connection_types[0][1] },
+    OperatorConnectionType::RingModulation
+  );
+}
+
+#[test]
+fn invalid_sub_oscillator_shapes_are_rejected() {
+  let ctx = unsafe { init_fm_synth_ctx(1) };
+  assert_eq!(
+    unsafe { fm_synth_set_voice_layers(ctx, 1, 4, 0.5, 0., 0.) },
+    ErrorCode::ParamOutOfRange
+  );
+  assert_eq!(
+    unsafe { fm_synth_set_voice_layers(ctx, 1, 3, 0.5, 0., 0.) },
+    ErrorCode::Ok
+  );
+}
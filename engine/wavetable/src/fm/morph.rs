@@ -0,0 +1,374 @@
+//! Morphing between two voice presets during performance.  Both presets' continuous params are
+//! loaded in up front, and then moving the morph position interpolates between them and applies
+//! the result to the live synth.  Constant params are smoothed by the synth as usual, so the
+//! position can be swept freely without clicks.
+//!
+//! Only the parts of a preset that are made up of `ParamSource`s are morphed: the modulation
+//! matrix, output weights, operator base frequencies, and detune.  Discrete config like operator
+//! types and effects is left to the caller.
+
+use common::ffi::{self, ErrorCode};
+
+use super::{AdsrState, FMSynthContext, ParamSource, OPERATOR_COUNT};
+
+#[derive(Clone, Default)]
+pub struct MorphPreset {
+  pub modulation_indices: [[ParamSource; OPERATOR_COUNT]; OPERATOR_COUNT],
+  pub output_weights: [ParamSource; OPERATOR_COUNT],
+  pub operator_base_frequency_sources: [ParamSource; OPERATOR_COUNT],
+  pub detune: Option<ParamSource>,
+}
+
+#[derive(Default)]
+pub struct PresetMorph {
+  pub presets: [MorphPreset; 2],
+  /// 0 is entirely the first preset and 1 is entirely the second
+  pub position: f32,
+}
+
+#[derive(Clone, Copy)]
+pub enum MorphParam {
+  ModulationIndex {
+    src_operator_ix: usize,
+    dst_operator_ix: usize,
+  },
+  OutputWeight {
+    operator_ix: usize,
+  },
+  OperatorBaseFrequency {
+    operator_ix: usize,
+  },
+  Detune,
+}
+
+impl MorphParam {
+  /// Returns `None` if `param_type` is invalid
+  pub fn from_parts(param_type: usize, operator_ix: usize, dst_operator_ix: usize) -> Option<Self> {
+    match param_type {
+      0 => Some(MorphParam::ModulationIndex {
+        src_operator_ix: operator_ix,
+        dst_operator_ix,
+      }),
+      1 => Some(MorphParam::OutputWeight { operator_ix }),
+      2 => Some(MorphParam::OperatorBaseFrequency { operator_ix }),
+      3 => Some(MorphParam::Detune),
+      _ => None,
+    }
+  }
+}
+
+impl MorphPreset {
+  pub fn set(&mut self, param: MorphParam, source: Option<ParamSource>) {
+    let source_or_default = || source.clone().unwrap_or_default();
+    match param {
+      MorphParam::ModulationIndex {
+        src_operator_ix,
+        dst_operator_ix,
+      } => self.modulation_indices[src_operator_ix][dst_operator_ix] = source_or_default(),
+      MorphParam::OutputWeight { operator_ix } =>
+        self.output_weights[operator_ix] = source_or_default(),
+      MorphParam::OperatorBaseFrequency { operator_ix } =>
+        self.operator_base_frequency_sources[operator_ix] = source_or_default(),
+      MorphParam::Detune => self.detune = source,
+    }
+  }
+}
+
+/// Constant params can be expressed as ADSR or MIDI control sources with a scale of zero, so those
+/// are blended by scaling the modulation in or out.  Sources that can't be blended, like two
+/// different param types or ones driven by different ADSRs, switch over at the halfway point.
+pub fn interpolate_param_source(a: &ParamSource, b: &ParamSource, t: f32) -> ParamSource {
+  if t <= 0. {
+    return a.clone();
+  } else if t >= 1. {
+    return b.clone();
+  }
+
+  let lerp = |a: f32, b: f32| a + (b - a) * t;
+  match (a, b) {
+    (ParamSource::Constant { cur_val: a, .. }, ParamSource::Constant { cur_val: b, .. }) =>
+      ParamSource::new_constant(lerp(*a, *b)),
+    (ParamSource::BaseFrequencyMultiplier(a), ParamSource::BaseFrequencyMultiplier(b)) =>
+      ParamSource::BaseFrequencyMultiplier(lerp(*a, *b)),
+    (ParamSource::BeatsToSamples(a), ParamSource::BeatsToSamples(b)) =>
+      ParamSource::BeatsToSamples(lerp(*a, *b)),
+    (ParamSource::PerVoiceADSR(a), ParamSource::PerVoiceADSR(b)) if a.adsr_ix == b.adsr_ix =>
+      ParamSource::PerVoiceADSR(AdsrState {
+        adsr_ix: a.adsr_ix,
+        scale: lerp(a.scale, b.scale),
+        shift: lerp(a.shift, b.shift),
+      }),
+    (ParamSource::PerVoiceADSR(a), ParamSource::Constant { cur_val, .. }) =>
+      ParamSource::PerVoiceADSR(AdsrState {
+        adsr_ix: a.adsr_ix,
+        scale: lerp(a.scale, 0.),
+        shift: lerp(a.shift, *cur_val),
+      }),
+    (ParamSource::Constant { cur_val, .. }, ParamSource::PerVoiceADSR(b)) =>
+      ParamSource::PerVoiceADSR(AdsrState {
+        adsr_ix: b.adsr_ix,
+        scale: lerp(0., b.scale),
+        shift: lerp(*cur_val, b.shift),
+      }),
+    (
+      ParamSource::MIDIControlValue {
+        control_index: a_control_index,
+        scale: a_scale,
+        shift: a_shift,
+      },
+      ParamSource::MIDIControlValue {
+        control_index: b_control_index,
+        scale: b_scale,
+        shift: b_shift,
+      },
+    ) if a_control_index == b_control_index => ParamSource::MIDIControlValue {
+      control_index: *a_control_index,
+      scale: lerp(*a_scale, *b_scale),
+      shift: lerp(*a_shift, *b_shift),
+    },
+    (
+      ParamSource::MIDIControlValue {
+        control_index,
+        scale,
+        shift,
+      },
+      ParamSource::Constant { cur_val, .. },
+    ) => ParamSource::MIDIControlValue {
+      control_index: *control_index,
+      scale: lerp(*scale, 0.),
+      shift: lerp(*shift, *cur_val),
+    },
+    (
+      ParamSource::Constant { cur_val, .. },
+      ParamSource::MIDIControlValue {
+        control_index,
+        scale,
+        shift,
+      },
+    ) => ParamSource::MIDIControlValue {
+      control_index: *control_index,
+      scale: lerp(0., *scale),
+      shift: lerp(*cur_val, *shift),
+    },
+    (
+      ParamSource::Random {
+        min: a_min,
+        max: a_max,
+        update_interval_samples: a_update_interval_samples,
+        smoothing_coefficient: a_smoothing_coefficient,
+        ..
+      },
+      ParamSource::Random {
+        min: b_min,
+        max: b_max,
+        update_interval_samples: b_update_interval_samples,
+        smoothing_coefficient: b_smoothing_coefficient,
+        ..
+      },
+    ) => {
+      // `ParamSource::replace` keeps the live random state and only takes these params
+      let mut out = a.clone();
+      if let ParamSource::Random {
+        min,
+        max,
+        update_interval_samples,
+        smoothing_coefficient,
+        ..
+      } = &mut out
+      {
+        *min = lerp(*a_min, *b_min);
+        *max = lerp(*a_max, *b_max);
+        *update_interval_samples = lerp(
+          *a_update_interval_samples as f32,
+          *b_update_interval_samples as f32,
+        )
+        .round() as usize;
+        *smoothing_coefficient = lerp(*a_smoothing_coefficient, *b_smoothing_coefficient);
+      }
+      out
+    },
+    _ =>
+      if t < 0.5 {
+        a.clone()
+      } else {
+        b.clone()
+      },
+  }
+}
+
+/// No detune is the same as a constant detune of zero, which lets detune be faded in and out
+fn interpolate_detune(
+  a: &Option<ParamSource>,
+  b: &Option<ParamSource>,
+  t: f32,
+) -> Option<ParamSource> {
+  match (a, b) {
+    (None, None) => None,
+    _ if t <= 0. => a.clone(),
+    _ if t >= 1. => b.clone(),
+    (a, b) => Some(interpolate_param_source(
+      a.as_ref().unwrap_or(&ParamSource::default()),
+      b.as_ref().unwrap_or(&ParamSource::default()),
+      t,
+    )),
+  }
+}
+
+impl FMSynthContext {
+  pub fn apply_preset_morph(&mut self) {
+    let [a, b] = &self.preset_morph.presets;
+    let t = self.preset_morph.position;
+
+    for src_operator_ix in 0..OPERATOR_COUNT {
+      for dst_operator_ix in 0..OPERATOR_COUNT {
+        self.modulation_matrix.weights_per_operator[src_operator_ix][dst_operator_ix].replace(
+          interpolate_param_source(
+            &a.modulation_indices[src_operator_ix][dst_operator_ix],
+            &b.modulation_indices[src_operator_ix][dst_operator_ix],
+            t,
+          ),
+        );
+      }
+      self.modulation_matrix.output_weights[src_operator_ix].replace(interpolate_param_source(
+        &a.output_weights[src_operator_ix],
+        &b.output_weights[src_operator_ix],
+        t,
+      ));
+      self.operator_base_frequency_sources[src_operator_ix] = interpolate_param_source(
+        &a.operator_base_frequency_sources[src_operator_ix],
+        &b.operator_base_frequency_sources[src_operator_ix],
+        t,
+      );
+    }
+
+    match (
+      &mut self.detune,
+      interpolate_detune(&a.detune, &b.detune, t),
+    ) {
+      (Some(old_detune), Some(new_detune)) => old_detune.replace(new_detune),
+      (detune, new_detune) => *detune = new_detune,
+    }
+
+    self.update_operator_enabled_statuses();
+  }
+}
+
+/// Sets one param of one of the two presets being morphed between.  `param_type` is 0 for a
+/// modulation index, 1 for an output weight, 2 for an operator base frequency, and 3 for detune.
+/// A `value_type` of -1 clears detune; the rest are the same as `ParamSource::from_parts`.
+///
+/// Changes don't take effect until the next call to `fm_synth_set_morph_position`.
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_set_morph_preset_param(
+  ctx: *mut FMSynthContext,
+  preset_ix: usize,
+  param_type: usize,
+  operator_ix: usize,
+  dst_operator_ix: usize,
+  value_type: isize,
+  val_param_int: usize,
+  val_param_float: f32,
+  val_param_float_2: f32,
+  val_param_float_3: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_morph_preset_param") }?;
+    ffi::check_range("preset_ix", preset_ix, 0, 1)?;
+    ffi::check_range("operator_ix", operator_ix, 0, OPERATOR_COUNT - 1)?;
+    ffi::check_range("dst_operator_ix", dst_operator_ix, 0, OPERATOR_COUNT - 1)?;
+    let param =
+      MorphParam::from_parts(param_type, operator_ix, dst_operator_ix).ok_or_else(|| {
+        ffi::set_last_error(
+          ErrorCode::ParamOutOfRange,
+          &format!("invalid morph param type: {param_type}"),
+        )
+      })?;
+    let min_value_type = if matches!(param, MorphParam::Detune) {
+      -1
+    } else {
+      0
+    };
+    ffi::check_range("value_type", value_type, min_value_type, 6)?;
+
+    let source = (value_type >= 0).then(|| {
+      ParamSource::from_parts(
+        value_type as usize,
+        val_param_int,
+        val_param_float,
+        val_param_float_2,
+        val_param_float_3,
+      )
+    });
+    ctx.preset_morph.presets[preset_ix].set(param, source);
+    Ok(())
+  })())
+}
+
+/// Moves the morph to `position`, clamped to [0, 1], and applies the interpolated params to the
+/// synth
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_set_morph_position(
+  ctx: *mut FMSynthContext,
+  position: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx = unsafe { ffi::handle(ctx, "fm_synth_set_morph_position") }?;
+    ctx.preset_morph.position = ffi::clamp_param("position", position, 0., 1.)?;
+    ctx.apply_preset_morph();
+    Ok(())
+  })())
+}
+
+#[test]
+fn param_source_interpolation() {
+  let get_constant = |source: &ParamSource| match source {
+    ParamSource::Constant { cur_val, .. } => *cur_val,
+    _ => panic!("expected constant"),
+  };
+  let a = ParamSource::new_constant(1.);
+  let b = ParamSource::new_constant(3.);
+  assert_eq!(get_constant(&interpolate_param_source(&a, &b, 0.)), 1.);
+  assert_eq!(get_constant(&interpolate_param_source(&a, &b, 0.5)), 2.);
+  assert_eq!(get_constant(&interpolate_param_source(&a, &b, 1.)), 3.);
+
+  // Modulation is faded in from a constant
+  let adsr = ParamSource::PerVoiceADSR(AdsrState {
+    adsr_ix: 1,
+    scale: 4.,
+    shift: 2.,
+  });
+  assert!(
+    interpolate_param_source(&a, &adsr, 0.25)
+      == ParamSource::PerVoiceADSR(AdsrState {
+        adsr_ix: 1,
+        scale: 1.,
+        shift: 1.25,
+      })
+  );
+
+  // Mismatched sources switch over halfway through
+  let param_buffer = ParamSource::ParamBuffer(2);
+  assert!(interpolate_param_source(&param_buffer, &adsr, 0.49) == param_buffer);
+  assert!(interpolate_param_source(&param_buffer, &adsr, 0.5) == adsr);
+
+  // Missing detune acts like zero detune
+  let detune = Some(ParamSource::new_constant(10.));
+  let interpolated = interpolate_detune(&None, &detune, 0.5);
+  assert_eq!(get_constant(interpolated.as_ref().unwrap()), 5.);
+  assert!(interpolate_detune(&None, &None, 0.5).is_none());
+}
+
+#[test]
+fn invalid_morph_param_types_are_rejected() {
+  assert!(MorphParam::from_parts(4, 0, 0).is_none());
+
+  let ctx = unsafe { super::init_fm_synth_ctx(1) };
+  assert_eq!(
+    unsafe { fm_synth_set_morph_preset_param(ctx, 0, 4, 0, 0, 0, 0, 1., 0., 0.) },
+    ErrorCode::ParamOutOfRange
+  );
+  assert_eq!(
+    unsafe { fm_synth_set_morph_preset_param(ctx, 0, 3, 0, 0, -1, 0, 0., 0., 0.) },
+    ErrorCode::Ok
+  );
+}
@@ -0,0 +1,91 @@
+//! Stereo panning for operators and voices.  Pans are in [-1, 1] where -1 is hard left, and use a
+//! balance law that leaves the centered channel at unity gain.  That keeps un-panned patches
+//! sounding exactly the same as they did when voices were mono.
+//!
+//! Operators are panned individually as they're mixed into the voice, along with the spread of
+//! their unison oscillators.  Unison spread is produced as a side signal alongside the operator's
+//! output, so it doesn't pass through the operator's own effects.  The voice pan is applied
+//! afterwards to the output of the voice's main effect chain.
+
+use super::{ParamSource, RenderRawParams, FRAME_SIZE, OPERATOR_COUNT};
+
+/// Returns `(left_gain, right_gain)` for the provided pan
+#[inline(always)]
+pub fn balance_gains(pan: f32) -> (f32, f32) {
+  let pan = dsp::clamp(-1., 1., pan);
+  ((1. - pan).min(1.), (1. + pan).min(1.))
+}
+
+/// Returns `true` if `param` is a constant pan of 0 that's done smoothing, in which case it doesn't
+/// need to be rendered.
+fn is_centered(param: &ParamSource) -> bool {
+  matches!(
+    param,
+    ParamSource::Constant { last_val, cur_val } if *cur_val == 0. && last_val.get().abs() < 0.000001
+  )
+}
+
+/// Pan config shared by all voices
+#[derive(Clone)]
+pub struct Panning {
+  pub operator_pans: [ParamSource; OPERATOR_COUNT],
+  pub voice_pan: ParamSource,
+}
+
+impl Default for Panning {
+  fn default() -> Self {
+    Panning {
+      operator_pans: std::array::from_fn(|_| ParamSource::new_constant(0.)),
+      voice_pan: ParamSource::new_constant(0.),
+    }
+  }
+}
+
+impl Panning {
+  pub fn is_operator_centered(&self, operator_ix: usize) -> bool {
+    is_centered(&self.operator_pans[operator_ix])
+  }
+
+  /// Renders the voice pan for the current frame into `output`.  Returns `false` without rendering
+  /// anything if the voice is centered.
+  pub fn render_voice_pan<'a>(
+    &self,
+    render_params: &RenderRawParams<'a>,
+    output: &mut [f32; FRAME_SIZE],
+  ) -> bool {
+    if is_centered(&self.voice_pan) {
+      return false;
+    }
+    self.voice_pan.render_raw(render_params, output);
+    true
+  }
+}
+
+/// Applies the voice pan to a stereo voice output
+pub fn apply_voice_pan(pans: &[f32; FRAME_SIZE], output: &mut [[f32; FRAME_SIZE]; 2]) {
+  let [left, right] = output;
+  for i in 0..FRAME_SIZE {
+    let (left_gain, right_gain) = balance_gains(pans[i]);
+    left[i] *= left_gain;
+    right[i] *= right_gain;
+  }
+}
+
+#[test]
+fn centered_pans_leave_the_signal_unchanged() {
+  assert_eq!(balance_gains(0.), (1., 1.));
+  assert_eq!(balance_gains(-1.), (1., 0.));
+  assert_eq!(balance_gains(0.5), (0.5, 1.));
+  assert_eq!(balance_gains(4.), (0., 1.));
+
+  let mut panning = Panning::default();
+  assert!(panning.is_operator_centered(0));
+  panning.operator_pans[0].replace(ParamSource::new_constant(0.5));
+  assert!(!panning.is_operator_centered(0));
+  // Smoothing back to the center still needs to be rendered
+  panning.operator_pans[0] = ParamSource::Constant {
+    last_val: std::cell::Cell::new(0.5),
+    cur_val: 0.,
+  };
+  assert!(!panning.is_operator_centered(0));
+}
@@ -0,0 +1,671 @@
+//! Loads a whole voice preset into the synth in one call.  Going through the individual setters
+//! takes a message to the audio thread and an FFI call for every modulation index, output weight,
+//! operator, effect, and envelope in the preset.  Instead, the caller encodes the preset into the
+//! preset buffer as a flat list of records, and it's compiled into the synth's operators, effect
+//! chains, and ADSRs all at once.
+//!
+//! The whole buffer is decoded and validated before anything is applied, so a malformed preset
+//! leaves the synth as it was.
+
+use common::ffi::{self, ErrorCode, FfiResult};
+
+use super::{
+  effects::{EFFECT_TYPE_COUNT, MAX_EFFECT_COUNT},
+  fm_synth_set_detune, fm_synth_set_effect, fm_synth_set_effect_mix, fm_synth_set_operator_config,
+  fm_synth_set_operator_pan, fm_synth_set_voice_pan,
+  layers::SubOscillatorShape,
+  set_adsr, set_adsr_step_buffer, FMSynthContext, OperatorConnectionType, ParamSource,
+  MAX_ADSR_STEP_COUNT, MAX_PARAM_SOURCE_VALUE_TYPE, MAX_RAMPER_TYPE, OPERATOR_COUNT,
+  OPERATOR_TYPES,
+};
+
+/// Each record in the preset buffer starts with one of these tags, followed by its fields:
+///
+/// modulation index: src operator ix, dst operator ix, source
+/// output weight: operator ix, source
+/// operator config: operator ix, operator type, unison, unison phase randomization enabled, then
+///   five param sources
+/// operator base frequency: operator ix, source
+/// effect: operator ix (-1 for the main effect chain), effect ix, effect type (-1 to remove the
+///   effect), four param sources, is bypassed
+/// ADSR: ADSR ix (-1 for the gain envelope, -2 for the filter envelope), step count, length source,
+///   release point, loop point (-1 for none), log scale, then x, y, ramper, and ramper param for
+///   each step
+/// detune: source, where a value type of -1 clears detune
+/// operator connection type: src operator ix, dst operator ix, `OperatorConnectionType`
+/// voice layers: sub-oscillator octaves (1 or 2), `SubOscillatorShape`, sub-oscillator level, noise
+///   level, noise decay ms
+/// operator pan: operator ix, unison spread, source
+/// voice pan: source
+/// effect mix: operator ix (-1 for the main effect chain), effect ix, mix
+///
+/// Param sources take up `ENCODED_PARAM_SOURCE_SIZE` values: the value type, int param, and three
+/// float params as passed to `ParamSource::from_parts`.  Booleans are 0 or 1.
+pub const RECORD_TYPE_MODULATION_INDEX: isize = 0;
+pub const RECORD_TYPE_OUTPUT_WEIGHT: isize = 1;
+pub const RECORD_TYPE_OPERATOR_CONFIG: isize = 2;
+pub const RECORD_TYPE_OPERATOR_BASE_FREQUENCY: isize = 3;
+pub const RECORD_TYPE_EFFECT: isize = 4;
+pub const RECORD_TYPE_ADSR: isize = 5;
+pub const RECORD_TYPE_DETUNE: isize = 6;
+pub const RECORD_TYPE_OPERATOR_CONNECTION_TYPE: isize = 7;
+pub const RECORD_TYPE_VOICE_LAYERS: isize = 8;
+pub const RECORD_TYPE_OPERATOR_PAN: isize = 9;
+pub const RECORD_TYPE_VOICE_PAN: isize = 10;
+pub const RECORD_TYPE_EFFECT_MIX: isize = 11;
+pub const ENCODED_PARAM_SOURCE_SIZE: usize = 5;
+
+/// Largest integer that every f32 below it can represent exactly
+const MAX_INT_FIELD: isize = 1 << 24;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EncodedParamSource {
+  pub value_type: usize,
+  pub int_val: usize,
+  pub float_val: f32,
+  pub float_val_2: f32,
+  pub float_val_3: f32,
+}
+
+impl EncodedParamSource {
+  fn to_param_source(self) -> ParamSource {
+    ParamSource::from_parts(
+      self.value_type,
+      self.int_val,
+      self.float_val,
+      self.float_val_2,
+      self.float_val_3,
+    )
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EncodedAdsrStep {
+  pub x: f32,
+  pub y: f32,
+  pub ramper: u32,
+  pub ramper_param: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PresetRecord {
+  ModulationIndex {
+    src_operator_ix: usize,
+    dst_operator_ix: usize,
+    source: EncodedParamSource,
+  },
+  OutputWeight {
+    operator_ix: usize,
+    source: EncodedParamSource,
+  },
+  OperatorConfig {
+    operator_ix: usize,
+    operator_type: usize,
+    unison: usize,
+    unison_phase_randomization_enabled: bool,
+    params: [EncodedParamSource; 5],
+  },
+  OperatorBaseFrequency {
+    operator_ix: usize,
+    source: EncodedParamSource,
+  },
+  Effect {
+    operator_ix: isize,
+    effect_ix: usize,
+    effect_type: isize,
+    params: [EncodedParamSource; 4],
+    is_bypassed: bool,
+  },
+  Adsr {
+    adsr_ix: isize,
+    len_samples: EncodedParamSource,
+    release_point: f32,
+    loop_point: f32,
+    log_scale: bool,
+    steps: Vec<EncodedAdsrStep>,
+  },
+  Detune(Option<EncodedParamSource>),
+  OperatorConnectionType {
+    src_operator_ix: usize,
+    dst_operator_ix: usize,
+    connection_type: OperatorConnectionType,
+  },
+  VoiceLayers {
+    sub_octaves: u32,
+    sub_shape: SubOscillatorShape,
+    sub_level: f32,
+    noise_level: f32,
+    noise_decay_ms: f32,
+  },
+  OperatorPan {
+    operator_ix: usize,
+    unison_spread: f32,
+    source: EncodedParamSource,
+  },
+  VoicePan(EncodedParamSource),
+  EffectMix {
+    operator_ix: isize,
+    effect_ix: usize,
+    mix: f32,
+  },
+}
+
+struct RecordReader<'a> {
+  buf: &'a [f32],
+  pos: usize,
+}
+
+impl<'a> RecordReader<'a> {
+  fn next(&mut self, field_name: &str) -> FfiResult<f32> {
+    let val = self.buf.get(self.pos).copied().ok_or_else(|| {
+      ffi::set_last_error(
+        ErrorCode::ParamOutOfRange,
+        &format!("preset buffer ended while reading {field_name}"),
+      )
+    })?;
+    self.pos += 1;
+    Ok(val)
+  }
+
+  fn next_int(&mut self, field_name: &str, min: isize, max: isize) -> FfiResult<isize> {
+    let val = self.next(field_name)?;
+    ffi::check_range(field_name, val, min as f32, max as f32)?;
+    Ok(val as isize)
+  }
+
+  fn next_usize(&mut self, field_name: &str, max: usize) -> FfiResult<usize> {
+    self
+      .next_int(field_name, 0, max as isize)
+      .map(|val| val as usize)
+  }
+
+  fn next_bool(&mut self, field_name: &str) -> FfiResult<bool> {
+    self.next_int(field_name, 0, 1).map(|val| val == 1)
+  }
+
+  /// Returns `None` if `optional` is set and the value type is -1
+  fn next_param_source_inner(&mut self, optional: bool) -> FfiResult<Option<EncodedParamSource>> {
+    let min_value_type = if optional { -1 } else { 0 };
+    let value_type = self.next_int(
+      "param source value type",
+      min_value_type,
+      MAX_PARAM_SOURCE_VALUE_TYPE as isize,
+    )?;
+    // Negative int params are passed through the same way that the individual setters receive them
+    let int_val = self.next_int("param source int param", -MAX_INT_FIELD, MAX_INT_FIELD)?;
+    let source = EncodedParamSource {
+      value_type: value_type.max(0) as usize,
+      int_val: int_val as usize,
+      float_val: self.next("param source float param")?,
+      float_val_2: self.next("param source float param 2")?,
+      float_val_3: self.next("param source float param 3")?,
+    };
+    Ok((value_type >= 0).then_some(source))
+  }
+
+  fn next_param_source(&mut self) -> FfiResult<EncodedParamSource> {
+    self
+      .next_param_source_inner(false)
+      .map(|source| source.unwrap())
+  }
+
+  fn next_param_sources<const N: usize>(&mut self) -> FfiResult<[EncodedParamSource; N]> {
+    let mut sources = [EncodedParamSource {
+      value_type: 0,
+      int_val: 0,
+      float_val: 0.,
+      float_val_2: 0.,
+      float_val_3: 0.,
+    }; N];
+    for source in &mut sources {
+      *source = self.next_param_source()?;
+    }
+    Ok(sources)
+  }
+
+  fn next_adsr_step(&mut self) -> FfiResult<EncodedAdsrStep> {
+    Ok(EncodedAdsrStep {
+      x: self.next("ADSR step x")?,
+      y: self.next("ADSR step y")?,
+      ramper: self.next_int("ADSR step ramper", 0, MAX_RAMPER_TYPE as isize)? as u32,
+      ramper_param: self.next("ADSR step ramper param")?,
+    })
+  }
+}
+
+/// Decodes and validates every record in `buf`.  `adsr_count` is the number of ADSRs the synth
+/// currently has; ADSR records can replace any of them or append a new one to the end.
+pub fn decode_voice_preset(buf: &[f32], mut adsr_count: usize) -> FfiResult<Vec<PresetRecord>> {
+  let mut reader = RecordReader { buf, pos: 0 };
+  let mut records = Vec::new();
+  while reader.pos < buf.len() {
+    let record = match reader.next_int("record type", 0, RECORD_TYPE_EFFECT_MIX)? {
+      RECORD_TYPE_MODULATION_INDEX => PresetRecord::ModulationIndex {
+        src_operator_ix: reader.next_usize("src_operator_ix", OPERATOR_COUNT - 1)?,
+        dst_operator_ix: reader.next_usize("dst_operator_ix", OPERATOR_COUNT - 1)?,
+        source: reader.next_param_source()?,
+      },
+      RECORD_TYPE_OUTPUT_WEIGHT => PresetRecord::OutputWeight {
+        operator_ix: reader.next_usize("operator_ix", OPERATOR_COUNT - 1)?,
+        source: reader.next_param_source()?,
+      },
+      RECORD_TYPE_OPERATOR_CONFIG => {
+        let operator_ix = reader.next_usize("operator_ix", OPERATOR_COUNT - 1)?;
+        let operator_type = reader.next_usize("operator_type", MAX_INT_FIELD as usize)?;
+        if !OPERATOR_TYPES.contains(&operator_type) {
+          return Err(ffi::set_last_error(
+            ErrorCode::Unsupported,
+            &format!("invalid operator type: {operator_type}"),
+          ));
+        }
+        PresetRecord::OperatorConfig {
+          operator_ix,
+          operator_type,
+          unison: reader.next_usize("unison", MAX_INT_FIELD as usize)?,
+          unison_phase_randomization_enabled: reader
+            .next_bool("unison_phase_randomization_enabled")?,
+          params: reader.next_param_sources()?,
+        }
+      },
+      RECORD_TYPE_OPERATOR_BASE_FREQUENCY => PresetRecord::OperatorBaseFrequency {
+        operator_ix: reader.next_usize("operator_ix", OPERATOR_COUNT - 1)?,
+        source: reader.next_param_source()?,
+      },
+      RECORD_TYPE_EFFECT => PresetRecord::Effect {
+        operator_ix: reader.next_int("operator_ix", -1, OPERATOR_COUNT as isize - 1)?,
+        effect_ix: reader.next_usize("effect_ix", MAX_EFFECT_COUNT - 1)?,
+        effect_type: reader.next_int("effect_type", -1, EFFECT_TYPE_COUNT as isize - 1)?,
+        params: reader.next_param_sources()?,
+        is_bypassed: reader.next_bool("is_bypassed")?,
+      },
+      RECORD_TYPE_ADSR => {
+        let adsr_ix = reader.next_int("adsr_ix", -2, adsr_count as isize)?;
+        if adsr_ix == adsr_count as isize {
+          adsr_count += 1;
+        }
+        let step_count = reader.next_usize("step_count", MAX_ADSR_STEP_COUNT)?;
+        let len_samples = reader.next_param_source()?;
+        // The gain and filter envelopes only support lengths in samples or beats
+        if adsr_ix < 0 && len_samples.value_type != 1 && len_samples.value_type != 5 {
+          return Err(ffi::set_last_error(
+            ErrorCode::Unsupported,
+            &format!(
+              "envelope length value type {} is unsupported for adsr_ix={adsr_ix}",
+              len_samples.value_type
+            ),
+          ));
+        }
+        let release_point = reader.next("release_point")?;
+        let loop_point = reader.next("loop_point")?;
+        let log_scale = reader.next_bool("log_scale")?;
+        let steps = (0..step_count)
+          .map(|_| reader.next_adsr_step())
+          .collect::<FfiResult<Vec<_>>>()?;
+        PresetRecord::Adsr {
+          adsr_ix,
+          len_samples,
+          release_point,
+          loop_point,
+          log_scale,
+          steps,
+        }
+      },
+      RECORD_TYPE_DETUNE => PresetRecord::Detune(reader.next_param_source_inner(true)?),
+      RECORD_TYPE_OPERATOR_CONNECTION_TYPE => PresetRecord::OperatorConnectionType {
+        src_operator_ix: reader.next_usize("src_operator_ix", OPERATOR_COUNT - 1)?,
+        dst_operator_ix: reader.next_usize("dst_operator_ix", OPERATOR_COUNT - 1)?,
+        connection_type: OperatorConnectionType::from_usize(
+          reader.next_usize("connection_type", MAX_INT_FIELD as usize)?,
+        )
+        .ok_or_else(|| {
+          ffi::set_last_error(ErrorCode::Unsupported, "invalid operator connection type")
+        })?,
+      },
+      RECORD_TYPE_VOICE_LAYERS => PresetRecord::VoiceLayers {
+        sub_octaves: reader.next_int("sub_octaves", 1, 2)? as u32,
+        sub_shape: SubOscillatorShape::from_usize(
+          reader.next_usize("sub_shape", MAX_INT_FIELD as usize)?,
+        )
+        .ok_or_else(|| {
+          ffi::set_last_error(ErrorCode::Unsupported, "invalid sub-oscillator shape")
+        })?,
+        sub_level: reader.next("sub_level")?,
+        noise_level: reader.next("noise_level")?,
+        noise_decay_ms: reader.next("noise_decay_ms")?,
+      },
+      RECORD_TYPE_OPERATOR_PAN => PresetRecord::OperatorPan {
+        operator_ix: reader.next_usize("operator_ix", OPERATOR_COUNT - 1)?,
+        unison_spread: reader.next("unison_spread")?,
+        source: reader.next_param_source()?,
+      },
+      RECORD_TYPE_VOICE_PAN => PresetRecord::VoicePan(reader.next_param_source()?),
+      _ => PresetRecord::EffectMix {
+        operator_ix: reader.next_int("operator_ix", -1, OPERATOR_COUNT as isize - 1)?,
+        effect_ix: reader.next_usize("effect_ix", MAX_EFFECT_COUNT - 1)?,
+        mix: reader.next("mix")?,
+      },
+    };
+    records.push(record);
+  }
+  Ok(records)
+}
+
+/// Applies decoded records to the synth in order.  Records have already been validated by
+/// `decode_voice_preset`, so this only fails if the synth rejects one anyway.
+unsafe fn apply_voice_preset(ctx: *mut FMSynthContext, records: &[PresetRecord]) -> FfiResult {
+  for record in records {
+    match record {
+      PresetRecord::ModulationIndex {
+        src_operator_ix,
+        dst_operator_ix,
+        source,
+      } => (*ctx).modulation_matrix.weights_per_operator[*src_operator_ix][*dst_operator_ix]
+        .replace(source.to_param_source()),
+      PresetRecord::OutputWeight {
+        operator_ix,
+        source,
+      } => (*ctx).modulation_matrix.output_weights[*operator_ix].replace(source.to_param_source()),
+      PresetRecord::OperatorConfig {
+        operator_ix,
+        operator_type,
+        unison,
+        unison_phase_randomization_enabled,
+        params: [p0, p1, p2, p3, p4],
+      } => ffi::result(fm_synth_set_operator_config(
+        ctx,
+        *operator_ix,
+        *operator_type,
+        *unison,
+        *unison_phase_randomization_enabled,
+        p0.value_type,
+        p0.int_val,
+        p0.float_val,
+        p0.float_val_2,
+        p0.float_val_3,
+        p1.value_type,
+        p1.int_val,
+        p1.float_val,
+        p1.float_val_2,
+        p1.float_val_3,
+        p2.value_type,
+        p2.int_val,
+        p2.float_val,
+        p2.float_val_2,
+        p2.float_val_3,
+        p3.value_type,
+        p3.int_val,
+        p3.float_val,
+        p3.float_val_2,
+        p3.float_val_3,
+        p4.value_type,
+        p4.int_val,
+        p4.float_val,
+        p4.float_val_2,
+        p4.float_val_3,
+      ))?,
+      PresetRecord::OperatorBaseFrequency {
+        operator_ix,
+        source,
+      } => (*ctx).operator_base_frequency_sources[*operator_ix] = source.to_param_source(),
+      PresetRecord::Effect {
+        operator_ix,
+        effect_ix,
+        effect_type,
+        params: [p1, p2, p3, p4],
+        is_bypassed,
+      } => ffi::result(fm_synth_set_effect(
+        ctx,
+        *operator_ix,
+        *effect_ix,
+        *effect_type,
+        p1.value_type,
+        p1.int_val,
+        p1.float_val,
+        p1.float_val_2,
+        p1.float_val_3,
+        p2.value_type,
+        p2.int_val,
+        p2.float_val,
+        p2.float_val_2,
+        p2.float_val_3,
+        p3.value_type,
+        p3.int_val,
+        p3.float_val,
+        p3.float_val_2,
+        p3.float_val_3,
+        p4.value_type,
+        p4.int_val,
+        p4.float_val,
+        p4.float_val_2,
+        p4.float_val_3,
+        *is_bypassed,
+      ))?,
+      PresetRecord::Adsr {
+        adsr_ix,
+        len_samples,
+        release_point,
+        loop_point,
+        log_scale,
+        steps,
+      } => {
+        for (step_ix, step) in steps.iter().enumerate() {
+          ffi::result(set_adsr_step_buffer(
+            step_ix,
+            step.x,
+            step.y,
+            step.ramper,
+            step.ramper_param,
+          ))?;
+        }
+        ffi::result(set_adsr(
+          ctx,
+          *adsr_ix,
+          steps.len(),
+          len_samples.value_type,
+          len_samples.int_val,
+          len_samples.float_val,
+          len_samples.float_val_2,
+          len_samples.float_val_3,
+          *release_point,
+          *loop_point,
+          *log_scale,
+        ))?;
+      },
+      PresetRecord::Detune(source) => match source {
+        Some(source) => ffi::result(fm_synth_set_detune(
+          ctx,
+          source.value_type as isize,
+          source.int_val,
+          source.float_val,
+          source.float_val_2,
+          source.float_val_3,
+        ))?,
+        None => ffi::result(fm_synth_set_detune(ctx, -1, 0, 0., 0., 0.))?,
+      },
+      PresetRecord::OperatorConnectionType {
+        src_operator_ix,
+        dst_operator_ix,
+        connection_type,
+      } =>
+        (*ctx).modulation_matrix.connection_types[*src_operator_ix][*dst_operator_ix] =
+          *connection_type,
+      PresetRecord::VoiceLayers {
+        sub_octaves,
+        sub_shape,
+        sub_level,
+        noise_level,
+        noise_decay_ms,
+      } => (*ctx).voice_layers.set(
+        *sub_octaves,
+        *sub_shape,
+        *sub_level,
+        *noise_level,
+        *noise_decay_ms,
+      ),
+      PresetRecord::OperatorPan {
+        operator_ix,
+        unison_spread,
+        source,
+      } => ffi::result(fm_synth_set_operator_pan(
+        ctx,
+        *operator_ix,
+        *unison_spread,
+        source.value_type,
+        source.int_val,
+        source.float_val,
+        source.float_val_2,
+        source.float_val_3,
+      ))?,
+      PresetRecord::VoicePan(source) => ffi::result(fm_synth_set_voice_pan(
+        ctx,
+        source.value_type,
+        source.int_val,
+        source.float_val,
+        source.float_val_2,
+        source.float_val_3,
+      ))?,
+      PresetRecord::EffectMix {
+        operator_ix,
+        effect_ix,
+        mix,
+      } => ffi::result(fm_synth_set_effect_mix(ctx, *operator_ix, *effect_ix, *mix))?,
+    }
+  }
+
+  // Deferred until the end rather than recomputed for every modulation index and output weight
+  (*ctx).update_operator_enabled_statuses();
+  Ok(())
+}
+
+static mut VOICE_PRESET_BUF: Vec<f32> = Vec::new();
+
+/// Resizes the preset buffer to hold `len` values
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_get_voice_preset_buf_ptr(len: usize) -> *mut f32 {
+  VOICE_PRESET_BUF.resize(len, 0.);
+  VOICE_PRESET_BUF.as_mut_ptr()
+}
+
+/// Decodes the first `len` values of the preset buffer and applies them to the synth.  Nothing is
+/// applied if any of the records are invalid.
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_load_voice_preset(
+  ctx: *mut FMSynthContext,
+  len: usize,
+) -> ErrorCode {
+  ffi::status((|| {
+    let ctx_ref = unsafe { ffi::handle(ctx, "fm_synth_load_voice_preset") }?;
+    ffi::check_range("len", len, 0, VOICE_PRESET_BUF.len())?;
+    let adsr_count = ctx_ref.voices.first().map_or(0, |voice| voice.adsrs.len());
+    let records = decode_voice_preset(&VOICE_PRESET_BUF[..len], adsr_count)?;
+    apply_voice_preset(ctx, &records)
+  })())
+}
+
+#[test]
+fn decodes_voice_preset_records() {
+  let constant = |val: f32| [1., 0., val, 0., 0.];
+  let mut buf = vec![RECORD_TYPE_MODULATION_INDEX as f32, 1., 2.];
+  buf.extend(constant(0.5));
+  buf.extend([RECORD_TYPE_ADSR as f32, 0., 2.]);
+  buf.extend(constant(44_100.));
+  buf.extend([0.9, -1., 0.]);
+  buf.extend([0., 0., 1., 0., 1., 1., 2., 1.5]);
+  buf.extend([RECORD_TYPE_DETUNE as f32, -1., 0., 0., 0., 0.]);
+  buf.extend([RECORD_TYPE_OPERATOR_CONNECTION_TYPE as f32, 3., 0., 1.]);
+
+  let records = decode_voice_preset(&buf, 0).unwrap();
+  let constant_source = |val: f32| EncodedParamSource {
+    value_type: 1,
+    int_val: 0,
+    float_val: val,
+    float_val_2: 0.,
+    float_val_3: 0.,
+  };
+  assert_eq!(records, vec![
+    PresetRecord::ModulationIndex {
+      src_operator_ix: 1,
+      dst_operator_ix: 2,
+      source: constant_source(0.5),
+    },
+    PresetRecord::Adsr {
+      adsr_ix: 0,
+      len_samples: constant_source(44_100.),
+      release_point: 0.9,
+      loop_point: -1.,
+      log_scale: false,
+      steps: vec![
+        EncodedAdsrStep {
+          x: 0.,
+          y: 0.,
+          ramper: 1,
+          ramper_param: 0.,
+        },
+        EncodedAdsrStep {
+          x: 1.,
+          y: 1.,
+          ramper: 2,
+          ramper_param: 1.5,
+        },
+      ],
+    },
+    PresetRecord::Detune(None),
+    PresetRecord::OperatorConnectionType {
+      src_operator_ix: 3,
+      dst_operator_ix: 0,
+      connection_type: OperatorConnectionType::HardSync,
+    },
+  ]);
+
+  // Truncated records, out-of-range indices, and ADSRs past the end are all rejected
+  assert!(decode_voice_preset(&buf[..buf.len() - 1], 0).is_err());
+  let mut bad_operator_ix = buf.clone();
+  bad_operator_ix[1] = OPERATOR_COUNT as f32;
+  assert!(decode_voice_preset(&bad_operator_ix, 0).is_err());
+  let mut bad_adsr_ix = buf.clone();
+  bad_adsr_ix[9] = 1.;
+  assert!(decode_voice_preset(&bad_adsr_ix, 0).is_err());
+  assert!(decode_voice_preset(&bad_adsr_ix, 1).is_ok());
+  let mut bad_connection_type = buf.clone();
+  *bad_connection_type.last_mut().unwrap() = 3.;
+  assert!(decode_voice_preset(&bad_connection_type, 0).is_err());
+
+  let voice_layers = [RECORD_TYPE_VOICE_LAYERS as f32, 2., 1., 0.5, 0.25, 30.];
+  assert_eq!(decode_voice_preset(&voice_layers, 0).unwrap(), vec![
+    PresetRecord::VoiceLayers {
+      sub_octaves: 2,
+      sub_shape: SubOscillatorShape::Square,
+      sub_level: 0.5,
+      noise_level: 0.25,
+      noise_decay_ms: 30.,
+    }
+  ]);
+  assert!(decode_voice_preset(
+    &[RECORD_TYPE_VOICE_LAYERS as f32, 3., 1., 0.5, 0.25, 30.],
+    0
+  )
+  .is_err());
+
+  let mut pans = vec![RECORD_TYPE_OPERATOR_PAN as f32, 2., 0.75];
+  pans.extend(constant(-0.5));
+  pans.push(RECORD_TYPE_VOICE_PAN as f32);
+  pans.extend(constant(0.25));
+  assert_eq!(decode_voice_preset(&pans, 0).unwrap(), vec![
+    PresetRecord::OperatorPan {
+      operator_ix: 2,
+      unison_spread: 0.75,
+      source: constant_source(-0.5),
+    },
+    PresetRecord::VoicePan(constant_source(0.25)),
+  ]);
+
+  let effect_mix = [RECORD_TYPE_EFFECT_MIX as f32, -1., 3., 0.4];
+  assert_eq!(decode_voice_preset(&effect_mix, 0).unwrap(), vec![
+    PresetRecord::EffectMix {
+      operator_ix: -1,
+      effect_ix: 3,
+      mix: 0.4,
+    }
+  ]);
+  assert!(decode_voice_preset(&[RECORD_TYPE_EFFECT_MIX as f32, -2., 3., 0.4], 0).is_err());
+}
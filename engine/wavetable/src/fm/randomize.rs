@@ -0,0 +1,193 @@
+//! Generates random voice params for sound design exploration.  The engine doesn't know what the
+//! params mean; the caller describes each one with a range and some flags, and gets back a new
+//! value for each which it maps back into a preset.
+//!
+//! Results only depend on the seed and constraints so that a patch that sounds good can be
+//! regenerated later.
+
+use std::cmp::Ordering;
+
+use common::ffi::{self, ErrorCode};
+use rand::prelude::*;
+use rand_pcg::Pcg32;
+
+/// Each constraint in the constraints buffer has the following format:
+///
+/// [0] = min
+/// [1] = max
+/// [2] = current value, kept as-is if locked and used as the starting point if `amount` < 1
+/// [3] = flags; see `CONSTRAINT_FLAG_*`
+pub const ENCODED_CONSTRAINT_SIZE: usize = 4;
+pub const CONSTRAINT_FLAG_LOCKED: u32 = 1;
+/// Values are picked uniformly in the log domain, for frequencies and similar.  Ignored unless
+/// `min` is positive.
+pub const CONSTRAINT_FLAG_LOG_SCALE: u32 = 2;
+/// Values are whole numbers, for picking between the options of an enum
+pub const CONSTRAINT_FLAG_INTEGER: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParamConstraint {
+  pub min: f32,
+  pub max: f32,
+  pub current: f32,
+  pub locked: bool,
+  pub log_scale: bool,
+  pub integer: bool,
+}
+
+impl ParamConstraint {
+  pub fn decode(encoded: &[f32]) -> Self {
+    let flags = encoded[3] as u32;
+    ParamConstraint {
+      min: encoded[0],
+      max: encoded[1],
+      current: encoded[2],
+      locked: flags & CONSTRAINT_FLAG_LOCKED != 0,
+      log_scale: flags & CONSTRAINT_FLAG_LOG_SCALE != 0,
+      integer: flags & CONSTRAINT_FLAG_INTEGER != 0,
+    }
+  }
+
+  fn randomize(&self, rng: &mut Pcg32, amount: f32) -> f32 {
+    let is_empty_range = if self.integer {
+      self.min.ceil() > self.max.floor()
+    } else {
+      self.max.partial_cmp(&self.min) != Some(Ordering::Greater)
+    };
+    if self.locked || is_empty_range {
+      return self.current;
+    }
+
+    let current = if self.current.is_finite() {
+      self.current.clamp(self.min, self.max)
+    } else {
+      self.min
+    };
+    let val = if self.integer {
+      // Every integer in the range is equally likely, including `max`
+      let target = rng
+        .gen_range(self.min.ceil(), self.max.floor() + 1.)
+        .floor();
+      (current + (target - current) * amount).round()
+    } else if self.log_scale && self.min > 0. {
+      let (min, max, current) = (self.min.ln(), self.max.ln(), current.ln());
+      let target = rng.gen_range(min, max);
+      (current + (target - current) * amount).exp()
+    } else {
+      let target = rng.gen_range(self.min, self.max);
+      current + (target - current) * amount
+    };
+    val.clamp(self.min, self.max)
+  }
+}
+
+/// Returns a new value for each of `constraints`.  `amount` controls how far params move from
+/// their current values towards the random ones, from 0 (no change) to 1 (fully random).
+pub fn randomize_voice_params(seed: u64, constraints: &[ParamConstraint], amount: f32) -> Vec<f32> {
+  let mut rng = Pcg32::seed_from_u64(seed);
+  let amount = amount.clamp(0., 1.);
+  constraints
+    .iter()
+    .map(|constraint| constraint.randomize(&mut rng, amount))
+    .collect()
+}
+
+static mut ENCODED_CONSTRAINTS_BUF: Vec<f32> = Vec::new();
+static mut RANDOMIZED_PARAMS_BUF: Vec<f32> = Vec::new();
+
+/// Resizes the constraints buffer to hold `param_count` constraints
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_get_randomize_constraints_buf_ptr(
+  param_count: usize,
+) -> *mut f32 {
+  ENCODED_CONSTRAINTS_BUF.resize(param_count * ENCODED_CONSTRAINT_SIZE, 0.);
+  ENCODED_CONSTRAINTS_BUF.as_mut_ptr()
+}
+
+/// Randomizes the params described by the first `param_count` constraints in the constraints
+/// buffer.  The results are read from `fm_synth_get_randomized_params_ptr`.
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_randomize_voice_params(
+  seed: u32,
+  param_count: usize,
+  amount: f32,
+) -> ErrorCode {
+  ffi::status((|| {
+    ffi::check_range(
+      "param_count",
+      param_count,
+      0,
+      ENCODED_CONSTRAINTS_BUF.len() / ENCODED_CONSTRAINT_SIZE,
+    )?;
+    let amount = ffi::clamp_param("amount", amount, 0., 1.)?;
+
+    let constraints: Vec<ParamConstraint> = ENCODED_CONSTRAINTS_BUF
+      .chunks_exact(ENCODED_CONSTRAINT_SIZE)
+      .take(param_count)
+      .map(ParamConstraint::decode)
+      .collect();
+    RANDOMIZED_PARAMS_BUF = randomize_voice_params(seed as u64, &constraints, amount);
+    Ok(())
+  })())
+}
+
+/// Holds one value per param from the last successful call to `fm_synth_randomize_voice_params`
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_get_randomized_params_ptr() -> *const f32 {
+  RANDOMIZED_PARAMS_BUF.as_ptr()
+}
+
+#[test]
+fn randomized_params_are_reproducible_and_constrained() {
+  let constraints = [
+    ParamConstraint {
+      min: -1.,
+      max: 1.,
+      current: 0.,
+      locked: false,
+      log_scale: false,
+      integer: false,
+    },
+    ParamConstraint {
+      min: 20.,
+      max: 20_000.,
+      current: 1_000.,
+      locked: false,
+      log_scale: true,
+      integer: false,
+    },
+    ParamConstraint {
+      min: 0.,
+      max: 3.,
+      current: 1.,
+      locked: false,
+      log_scale: false,
+      integer: true,
+    },
+    ParamConstraint {
+      min: 0.,
+      max: 1.,
+      current: 0.25,
+      locked: true,
+      log_scale: false,
+      integer: false,
+    },
+  ];
+
+  let mut seen_ints = [false; 4];
+  for seed in 0..200 {
+    let vals = randomize_voice_params(seed, &constraints, 1.);
+    assert_eq!(vals, randomize_voice_params(seed, &constraints, 1.));
+    assert!((-1. ..=1.).contains(&vals[0]));
+    assert!((20. ..=20_000.).contains(&vals[1]));
+    assert_eq!(vals[2], vals[2].round());
+    seen_ints[vals[2] as usize] = true;
+    assert_eq!(vals[3], 0.25);
+  }
+  assert!(seen_ints.iter().all(|&seen| seen));
+
+  // Nothing moves with an amount of 0
+  let vals = randomize_voice_params(1337, &constraints, 0.);
+  assert_eq!([vals[0], vals[2], vals[3]], [0., 1., 0.25]);
+  assert!((vals[1] - 1_000.).abs() < 0.01);
+}
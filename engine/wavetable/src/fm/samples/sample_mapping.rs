@@ -83,6 +83,7 @@ impl SampleMappingEmitter {
   }
 }
 
+#[derive(Clone)]
 pub struct MappedSampleData {
   pub sample: Option<&'static [f32]>,
   pub do_loop: bool,
@@ -140,13 +141,13 @@ impl Default for MappedSampleData {
   }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct MappedSample {
   pub midi_number: usize,
   pub data: Vec<MappedSampleData>,
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct SampleMappingOperatorConfig {
   pub mapped_samples_by_midi_number: Vec<MappedSample>,
 }
@@ -195,7 +196,7 @@ impl SampleMappingOperatorConfig {
   }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct SampleMappingManager {
   pub config_by_operator: [SampleMappingOperatorConfig; OPERATOR_COUNT],
 }
@@ -2,8 +2,13 @@
 //! FM synth.
 
 use adsr::Adsr;
+use common::ffi::{self, ErrorCode};
+use dsp::MAX_FRAME_SIZE;
 
-use super::{effects::EffectChain, AdsrParams, RenderRawParams, FRAME_SIZE};
+use super::{
+  effects::{EffectChain, EFFECT_TYPE_COUNT, MAX_EFFECT_COUNT},
+  AdsrParams, RenderRawParams, FRAME_SIZE,
+};
 
 const FM_SYNTH_PARAM_BUFFER_COUNT: usize = 4;
 
@@ -11,9 +16,11 @@ pub struct FMSynthFxCtx {
   pub adsrs: Vec<Adsr>,
   pub adsr_params: Vec<AdsrParams>,
   pub param_buffers: [[f32; FRAME_SIZE]; FM_SYNTH_PARAM_BUFFER_COUNT],
+  /// Param values written by JS for the whole block, one run of `frame_size` samples per param
+  pub param_input_buf: [f32; FM_SYNTH_PARAM_BUFFER_COUNT * MAX_FRAME_SIZE],
   pub base_frequencies: [f32; FRAME_SIZE],
   pub effect_chain: EffectChain,
-  pub io_buf: [f32; FRAME_SIZE],
+  pub io_buf: [f32; MAX_FRAME_SIZE],
 }
 
 impl FMSynthFxCtx {
@@ -30,19 +37,44 @@ impl FMSynthFxCtx {
     }
   }
 
-  pub fn process(&mut self) {
-    self.update_adsrs();
+  /// Processes `frame_size` samples of `io_buf` in place.  The effects are built around
+  /// `FRAME_SIZE`-sample frames, so the block is split into chunks of at most that size and any
+  /// trailing partial chunk is processed sample-by-sample.
+  pub fn process(&mut self, frame_size: usize) {
+    let mut chunk_start = 0;
+    while chunk_start < frame_size {
+      let chunk_len = (frame_size - chunk_start).min(FRAME_SIZE);
+      for (param_ix, param_buf) in self.param_buffers.iter_mut().enumerate() {
+        let src_start = param_ix * frame_size + chunk_start;
+        param_buf[..chunk_len]
+          .copy_from_slice(&self.param_input_buf[src_start..src_start + chunk_len]);
+      }
+      for adsr in &mut self.adsrs {
+        adsr.set_frame_size(chunk_len);
+      }
+      self.update_adsrs();
 
-    let render_params = RenderRawParams {
-      param_buffers: &self.param_buffers,
-      adsrs: &self.adsrs,
-      base_frequencies: &self.base_frequencies,
-    };
-    self.effect_chain.pre_render_params(&render_params);
+      let render_params = RenderRawParams {
+        param_buffers: &self.param_buffers,
+        adsrs: &self.adsrs,
+        base_frequencies: &self.base_frequencies,
+      };
+      self.effect_chain.pre_render_params(&render_params);
 
-    self
-      .effect_chain
-      .apply_all(&render_params, &mut self.io_buf);
+      let chunk = &mut self.io_buf[chunk_start..chunk_start + chunk_len];
+      if chunk_len == FRAME_SIZE {
+        let chunk: &mut [f32; FRAME_SIZE] = chunk.try_into().unwrap();
+        self.effect_chain.apply_all(&render_params, chunk);
+      } else {
+        for (sample_ix, sample) in chunk.iter_mut().enumerate() {
+          *sample = self
+            .effect_chain
+            .apply(sample_ix, self.base_frequencies[sample_ix], *sample);
+        }
+      }
+
+      chunk_start += chunk_len;
+    }
   }
 }
 
@@ -52,9 +84,10 @@ pub extern "C" fn fm_synth_fx_create_ctx() -> *mut FMSynthFxCtx {
     adsrs: Vec::new(),
     adsr_params: Vec::new(),
     param_buffers: [[0.0; FRAME_SIZE]; FM_SYNTH_PARAM_BUFFER_COUNT],
+    param_input_buf: [0.0; FM_SYNTH_PARAM_BUFFER_COUNT * MAX_FRAME_SIZE],
     base_frequencies: [0.0; FRAME_SIZE],
     effect_chain: EffectChain::default(),
-    io_buf: [0.0; FRAME_SIZE],
+    io_buf: [0.0; MAX_FRAME_SIZE],
   });
   Box::into_raw(ctx)
 }
@@ -85,12 +118,24 @@ pub extern "C" fn fm_synth_fx_set_effect(
   param_4_float_val_2: f32,
   param_4_float_val_3: f32,
   is_bypassed: bool,
-) {
-  let ctx = unsafe { &mut *ctx };
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "fm_synth_fx_set_effect") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("effect_ix", effect_ix, 0, MAX_EFFECT_COUNT - 1) {
+    return code;
+  }
 
   if effect_type == -1 {
     ctx.effect_chain.remove_effect(effect_ix);
-    return;
+    return ErrorCode::Ok;
+  }
+  if effect_type < 0 || effect_type as usize >= EFFECT_TYPE_COUNT {
+    return ffi::set_last_error(
+      ErrorCode::Unsupported,
+      &format!("fm_synth_fx_set_effect: unknown effect type {effect_type}"),
+    );
   }
 
   ctx.effect_chain.set_effect(
@@ -118,12 +163,77 @@ pub extern "C" fn fm_synth_fx_set_effect(
     param_4_float_val_3,
     is_bypassed,
   );
+  ErrorCode::Ok
 }
 
 #[no_mangle]
-pub extern "C" fn fm_synth_fx_process(ctx: *mut FMSynthFxCtx) {
-  let ctx = unsafe { &mut *ctx };
-  ctx.process();
+pub extern "C" fn fm_synth_fx_move_effect(
+  ctx: *mut FMSynthFxCtx,
+  from_effect_ix: usize,
+  to_effect_ix: usize,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "fm_synth_fx_move_effect") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("from_effect_ix", from_effect_ix, 0, MAX_EFFECT_COUNT - 1) {
+    return code;
+  }
+  if let Err(code) = ffi::check_range("to_effect_ix", to_effect_ix, 0, MAX_EFFECT_COUNT - 1) {
+    return code;
+  }
+
+  ctx.effect_chain.move_effect(from_effect_ix, to_effect_ix);
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn fm_synth_fx_set_effect_bypassed(
+  ctx: *mut FMSynthFxCtx,
+  effect_ix: usize,
+  is_bypassed: bool,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "fm_synth_fx_set_effect_bypassed") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("effect_ix", effect_ix, 0, MAX_EFFECT_COUNT - 1) {
+    return code;
+  }
+
+  ctx.effect_chain.set_bypassed(effect_ix, is_bypassed);
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn fm_synth_fx_set_effect_mix(
+  ctx: *mut FMSynthFxCtx,
+  effect_ix: usize,
+  mix: f32,
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "fm_synth_fx_set_effect_mix") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("effect_ix", effect_ix, 0, MAX_EFFECT_COUNT - 1) {
+    return code;
+  }
+
+  ctx.effect_chain.set_mix(effect_ix, mix);
+  ErrorCode::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn fm_synth_fx_process(ctx: *mut FMSynthFxCtx, frame_size: usize) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(ctx, "fm_synth_fx_process") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if let Err(code) = ffi::check_range("frame_size", frame_size, 1, MAX_FRAME_SIZE) {
+    return code;
+  }
+  ctx.process(frame_size);
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -135,5 +245,5 @@ pub extern "C" fn fm_synth_fx_get_io_buf_ptr(ctx: *mut FMSynthFxCtx) -> *mut f32
 #[no_mangle]
 pub extern "C" fn fm_synth_fx_get_params_buf_ptr(ctx: *mut FMSynthFxCtx) -> *mut f32 {
   let ctx = unsafe { &mut *ctx };
-  ctx.param_buffers[0].as_mut_ptr()
+  ctx.param_input_buf.as_mut_ptr()
 }
@@ -1,14 +1,28 @@
-#![feature(stdsimd, const_maybe_uninit_assume_init, get_mut_unchecked)]
+#![feature(
+  stdsimd,
+  const_maybe_uninit_assume_init,
+  get_mut_unchecked,
+  variant_count
+)]
+
+use common::ffi::{self, ErrorCode};
 
 pub mod fm;
 
 pub static mut CUR_BPM: f32 = 0.;
 
 #[no_mangle]
-pub unsafe extern "C" fn set_cur_bpm(bpm: f32) { CUR_BPM = bpm; }
+pub unsafe extern "C" fn set_cur_bpm(bpm: f32) -> ErrorCode {
+  ffi::status((|| {
+    ffi::check_range("bpm", bpm, 0., f32::MAX)?;
+    CUR_BPM = bpm;
+    Ok(())
+  })())
+}
 
 pub fn get_cur_bpm() -> f32 { unsafe { CUR_BPM } }
 
+#[derive(Clone)]
 pub struct WaveTableSettings {
   /// Number of `f32` samples in a single waveform
   pub waveform_length: usize,
@@ -32,6 +46,7 @@ impl WaveTableSettings {
   }
 }
 
+#[derive(Clone)]
 pub struct WaveTable {
   pub settings: WaveTableSettings,
   pub samples: Vec<f32>,
@@ -220,8 +235,21 @@ pub fn get_data_table_ptr(handle_ptr: *mut WaveTable) -> *mut f32 {
 }
 
 #[no_mangle]
-pub extern "C" fn set_base_frequency(handle_ptr: *mut WaveTable, base_frequency: f32) {
-  unsafe { (*handle_ptr).settings.base_frequency = base_frequency }
+pub extern "C" fn set_base_frequency(handle_ptr: *mut WaveTable, base_frequency: f32) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(handle_ptr, "set_base_frequency") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  ffi::status((|| {
+    ffi::check_range(
+      "base_frequency",
+      base_frequency,
+      f32::MIN_POSITIVE,
+      f32::MAX,
+    )?;
+    ctx.settings.base_frequency = base_frequency;
+    Ok(())
+  })())
 }
 
 #[no_mangle]
@@ -230,9 +258,24 @@ pub extern "C" fn resize_wavetable(
   waveforms_per_dimension: usize,
   dimension_count: usize,
   waveform_length: usize,
-) {
-  let ctx = unsafe { &mut *handle_ptr };
+) -> ErrorCode {
+  let ctx = match unsafe { ffi::handle(handle_ptr, "resize_wavetable") } {
+    Ok(ctx) => ctx,
+    Err(code) => return code,
+  };
+  if waveforms_per_dimension == 0 || dimension_count == 0 || waveform_length == 0 {
+    return ffi::set_last_error(
+      ErrorCode::ParamOutOfRange,
+      &format!(
+        "Wavetable dimensions must be non-zero; got \
+         waveforms_per_dimension={waveforms_per_dimension}, dimension_count={dimension_count}, \
+         waveform_length={waveform_length}"
+      ),
+    );
+  }
+
   ctx.resize(waveforms_per_dimension, dimension_count, waveform_length);
+  ErrorCode::Ok
 }
 
 #[no_mangle]
@@ -1,5 +1,8 @@
-const FRAME_SIZE = 128;
 const BYTES_PER_F32 = 32 / 8;
+/**
+ * Must match `LogLevel` in the `logging` crate
+ */
+const LOG_LEVEL_CONSOLE_FNS = ['error', 'warn', 'info', 'debug'];
 
 class MultiADSR2AWP extends AudioWorkletProcessor {
   constructor(options) {
@@ -76,6 +79,10 @@ class MultiADSR2AWP extends AudioWorkletProcessor {
           this.wasmInstance.exports.adsr_set_log_scale(this.ctxPtr, evt.data.logScale);
           break;
         }
+        case 'setLogLevel': {
+          this.wasmInstance?.exports.set_log_level(evt.data.level);
+          break;
+        }
         case 'shutdown': {
           this.isShutdown = true;
           break;
@@ -181,8 +188,14 @@ class MultiADSR2AWP extends AudioWorkletProcessor {
             .join('');
           console.error(str);
         },
+        log_raw: (ptr, len, level) => {
+          const memory = new Uint8Array(this.wasmInstance.exports.memory.buffer);
+          const str = String.fromCharCode(...memory.subarray(ptr, ptr + len));
+          console[LOG_LEVEL_CONSOLE_FNS[level] ?? 'log'](str);
+        },
       },
     });
+    this.wasmInstance.exports.set_sample_rate(sampleRate);
 
     this.setEncodedSteps(encodedSteps);
 
@@ -228,12 +241,14 @@ class MultiADSR2AWP extends AudioWorkletProcessor {
       return false;
     }
 
+    const frameSize = output.length;
     const curPhase = this.wasmInstance.exports.process_adsr(
       this.ctxPtr,
       this.outputRange[0],
       this.outputRange[1],
       globalThis.globalTempoBPM,
-      globalThis.curBeat
+      globalThis.curBeat,
+      frameSize
     );
     // Record the current phase of the most recently gated ADSR which will be displayed
     // in the UI as an indicator on the ADSR UI
@@ -248,7 +263,7 @@ class MultiADSR2AWP extends AudioWorkletProcessor {
       const ptr = this.outputBufPtrs[i];
       const outputsSlice = this.getWasmMemoryBuffer().subarray(
         ptr / BYTES_PER_F32,
-        ptr / BYTES_PER_F32 + FRAME_SIZE
+        ptr / BYTES_PER_F32 + frameSize
       );
       output.set(outputsSlice);
     }
@@ -0,0 +1,283 @@
+import { checkWasmStatus } from './wasmStatus.js';
+
+const SAMPLE_RATE = sampleRate;
+const FRAME_SIZE = 128;
+/**
+ * See `POINT_SIZE` in `engine/automation/src/lib.rs` for the layout of each point
+ */
+const POINT_SIZE = 4;
+/**
+ * See `CHANGE_SIZE` in `engine/automation/src/lib.rs`
+ */
+const CHANGE_SIZE = 3;
+const CURVE_TYPES = { step: 0, linear: 1, exponential: 2 };
+const CURVE_TYPE_NAMES = ['step', 'linear', 'exponential'];
+
+class AutomationAWP extends AudioWorkletProcessor {
+  constructor() {
+    super();
+
+    this.wasmInstance = null;
+    this.ctxPtr = 0;
+    this.laneCount = 0;
+    /**
+     * Used to end the recording pass when the global beat counter stops
+     */
+    this.transportRunning = false;
+    this.recordedDuringPass = false;
+    /**
+     * Messages received before the Wasm module finished initializing
+     */
+    this.pendingMessages = [];
+
+    this.port.onmessage = evt => {
+      if (evt.data.type === 'setWasmBytes') {
+        this.initWasm(evt.data.wasmBytes);
+        return;
+      }
+
+      if (!this.wasmInstance) {
+        this.pendingMessages.push(evt.data);
+        return;
+      }
+      this.handleMessage(evt.data);
+    };
+  }
+
+  handleWasmPanic = (ptr, len) => {
+    const mem = new Uint8Array(this.wasmInstance.exports.memory.buffer);
+    const slice = mem.subarray(ptr, ptr + len);
+    const str = String.fromCharCode(...slice);
+    console.error(`AutomationAWP Wasm panic: ${str}`);
+  };
+
+  async initWasm(wasmBytes) {
+    const importObject = { env: { log_err: (ptr, len) => this.handleWasmPanic(ptr, len) } };
+    const compiledModule = await WebAssembly.compile(wasmBytes);
+    this.wasmInstance = await WebAssembly.instantiate(compiledModule, importObject);
+    this.ctxPtr = this.wasmInstance.exports.automation_create_ctx(0);
+
+    this.pendingMessages.forEach(msg => this.handleMessage(msg));
+    this.pendingMessages = [];
+  }
+
+  writePointsBuf(points) {
+    const exports = this.wasmInstance.exports;
+    const pointsBufPtr = exports.automation_get_points_buf_ptr(this.ctxPtr, points.length);
+    const pointsBuf = new Float64Array(
+      exports.memory.buffer,
+      pointsBufPtr,
+      points.length * POINT_SIZE
+    );
+    points.forEach(({ beat, value, curve }, pointIx) => {
+      const offset = pointIx * POINT_SIZE;
+      pointsBuf[offset] = beat;
+      pointsBuf[offset + 1] = value;
+      pointsBuf[offset + 2] = CURVE_TYPES[curve.type] ?? CURVE_TYPES.linear;
+      pointsBuf[offset + 3] = curve.type === 'exponential' ? curve.exponent : 0;
+    });
+  }
+
+  setLanePoints(laneIx, points) {
+    this.writePointsBuf(points);
+    checkWasmStatus(
+      this,
+      this.wasmInstance.exports.automation_commit_lane_points(this.ctxPtr, laneIx, points.length)
+    );
+  }
+
+  setRecordedLanes(lanes) {
+    const exports = this.wasmInstance.exports;
+    exports.automation_clear_recording(this.ctxPtr);
+    lanes.forEach(({ moduleId, paramId, points }) => {
+      this.writePointsBuf(points);
+      checkWasmStatus(
+        this,
+        exports.automation_commit_recorded_lane_points(
+          this.ctxPtr,
+          moduleId,
+          paramId,
+          points.length
+        )
+      );
+    });
+  }
+
+  readRecordedLanes() {
+    const exports = this.wasmInstance.exports;
+    const laneCount = exports.automation_get_recorded_lane_count(this.ctxPtr);
+    const lanes = [];
+    for (let laneIx = 0; laneIx < laneCount; laneIx++) {
+      checkWasmStatus(this, exports.automation_export_recorded_lane_points(this.ctxPtr, laneIx));
+      const pointCount = exports.automation_get_points_buf_point_count(this.ctxPtr);
+      const pointsBuf = new Float64Array(
+        exports.memory.buffer,
+        exports.automation_get_points_buf_ptr(this.ctxPtr, pointCount),
+        pointCount * POINT_SIZE
+      );
+      const points = [];
+      for (let pointIx = 0; pointIx < pointCount; pointIx++) {
+        const offset = pointIx * POINT_SIZE;
+        const type = CURVE_TYPE_NAMES[pointsBuf[offset + 2]] ?? 'linear';
+        points.push({
+          beat: pointsBuf[offset],
+          value: pointsBuf[offset + 1],
+          curve: type === 'exponential' ? { type, exponent: pointsBuf[offset + 3] } : { type },
+        });
+      }
+      lanes.push({
+        moduleId: exports.automation_get_recorded_lane_module_id(this.ctxPtr, laneIx),
+        paramId: exports.automation_get_recorded_lane_param_id(this.ctxPtr, laneIx),
+        points,
+      });
+    }
+    return lanes;
+  }
+
+  /**
+   * Ends the current recording pass and sends the recorded lanes to the main thread so that they're
+   * saved with the node's state
+   */
+  endRecordingPass() {
+    this.wasmInstance.exports.automation_end_recording_pass(this.ctxPtr);
+    if (this.recordedDuringPass) {
+      this.recordedDuringPass = false;
+      this.port.postMessage({ type: 'recordedLanes', lanes: this.readRecordedLanes() });
+    }
+  }
+
+  setState(state) {
+    checkWasmStatus(
+      this,
+      this.wasmInstance.exports.automation_set_lane_count(this.ctxPtr, state.lanes.length)
+    );
+    this.laneCount = state.lanes.length;
+    state.lanes.forEach((lane, laneIx) => this.setLanePoints(laneIx, lane.points));
+  }
+
+  handleMessage(data) {
+    switch (data.type) {
+      case 'setState': {
+        this.setState(data.state);
+        break;
+      }
+      case 'setLanePoints': {
+        this.setLanePoints(data.laneIx, data.points);
+        break;
+      }
+      case 'setRecordedLanes': {
+        this.setRecordedLanes(data.lanes);
+        break;
+      }
+      case 'setRecordingArmed': {
+        if (!data.armed) {
+          this.endRecordingPass();
+        }
+        this.wasmInstance.exports.automation_set_recording_armed(this.ctxPtr, data.armed);
+        break;
+      }
+      case 'recordParamChange': {
+        // Changes are only recorded while the transport is running
+        if (!globalThis.globalBeatCounterStarted) {
+          break;
+        }
+
+        checkWasmStatus(
+          this,
+          this.wasmInstance.exports.automation_record_param_change(
+            this.ctxPtr,
+            data.moduleId,
+            data.paramId,
+            data.value,
+            globalThis.curBeat ?? 0
+          )
+        );
+        this.recordedDuringPass = true;
+        break;
+      }
+      case 'clearRecording': {
+        this.wasmInstance.exports.automation_clear_recording(this.ctxPtr);
+        this.recordedDuringPass = false;
+        break;
+      }
+      default: {
+        console.warn('Unhandled message type in automation AWP: ', data.type);
+      }
+    }
+  }
+
+  /**
+   * Plays back recorded param changes while the transport is running, sending any changed values to
+   * the main thread to be applied
+   */
+  playRecorded() {
+    const transportRunning = !!globalThis.globalBeatCounterStarted;
+    if (this.transportRunning && !transportRunning) {
+      this.endRecordingPass();
+    }
+    this.transportRunning = transportRunning;
+    if (!transportRunning) {
+      return;
+    }
+
+    const exports = this.wasmInstance.exports;
+    checkWasmStatus(this, exports.automation_play_recorded(this.ctxPtr, globalThis.curBeat ?? 0));
+    const changeCount = exports.automation_get_recorded_change_count(this.ctxPtr);
+    if (changeCount === 0) {
+      return;
+    }
+
+    const changesBuf = new Float64Array(
+      exports.memory.buffer,
+      exports.automation_get_recorded_changes_ptr(this.ctxPtr),
+      changeCount * CHANGE_SIZE
+    );
+    const changes = [];
+    for (let changeIx = 0; changeIx < changeCount; changeIx++) {
+      const offset = changeIx * CHANGE_SIZE;
+      changes.push({
+        moduleId: changesBuf[offset],
+        paramId: changesBuf[offset + 1],
+        value: changesBuf[offset + 2],
+      });
+    }
+    this.port.postMessage({ type: 'recordedParamChanges', changes });
+  }
+
+  process(_inputs, outputs) {
+    if (!this.wasmInstance) {
+      return true;
+    }
+
+    this.playRecorded();
+    if (this.laneCount === 0) {
+      return true;
+    }
+
+    // The playhead only moves while the global beat counter is running, so this holds the value at
+    // the current position while stopped
+    const startBeat = globalThis.curBeat ?? 0;
+    const endBeat = globalThis.globalBeatCounterStarted
+      ? startBeat + (FRAME_SIZE * (globalThis.globalTempoBPM || 120)) / 60 / SAMPLE_RATE
+      : startBeat;
+    checkWasmStatus(
+      this,
+      this.wasmInstance.exports.automation_process(this.ctxPtr, startBeat, endBeat)
+    );
+
+    const outputPtr = this.wasmInstance.exports.automation_get_output_ptr(this.ctxPtr);
+    const output = new Float32Array(
+      this.wasmInstance.exports.memory.buffer,
+      outputPtr,
+      this.laneCount * FRAME_SIZE
+    );
+    const channels = outputs[0];
+    for (let laneIx = 0; laneIx < Math.min(this.laneCount, channels.length); laneIx++) {
+      channels[laneIx].set(output.subarray(laneIx * FRAME_SIZE, (laneIx + 1) * FRAME_SIZE));
+    }
+
+    return true;
+  }
+}
+
+registerProcessor('automation-awp', AutomationAWP);
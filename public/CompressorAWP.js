@@ -1,7 +1,59 @@
-const SAMPLE_RATE = 44_100;
-const FRAME_SIZE = 128;
+import { checkWasmStatus, getLastWasmErrorMessage } from './wasmStatus.js';
+
+const SAMPLE_RATE = sampleRate;
 const BYTES_PER_F32 = 32 / 8;
 const SAB_SIZE = 16 * BYTES_PER_F32;
+/**
+ * Must match the `PACKED_PARAMS_*` constants in the `compressor` crate
+ */
+const PACKED_PARAMS_VERSION = 1;
+const PACKED_PARAMS_VERSION_IX = 0;
+const PACKED_PARAMS_DIRTY_IX = 1;
+const PACKED_PARAMS_HEADER_LEN = 2;
+const PACKED_PARAM_COUNT = 30;
+/**
+ * `AudioParam`s written to the packed param block before the auto release flags
+ */
+const PACKED_LEADING_PARAM_NAMES = [
+  'mix',
+  'pre_gain',
+  'post_gain',
+  'low_band_gain',
+  'mid_band_gain',
+  'high_band_gain',
+  'low_band_attack_ms',
+  'low_band_release_ms',
+  'mid_band_attack_ms',
+  'mid_band_release_ms',
+  'high_band_attack_ms',
+  'high_band_release_ms',
+];
+/**
+ * `AudioParam`s written to the packed param block after the auto release flags and before the
+ * lookahead and RMS window
+ */
+const PACKED_TRAILING_PARAM_NAMES = [
+  'low_band_bottom_threshold_db',
+  'mid_band_bottom_threshold_db',
+  'high_band_bottom_threshold_db',
+  'low_band_top_threshold_db',
+  'mid_band_top_threshold_db',
+  'high_band_top_threshold_db',
+  'low_band_bottom_ratio',
+  'mid_band_bottom_ratio',
+  'high_band_bottom_ratio',
+  'low_band_top_ratio',
+  'mid_band_top_ratio',
+  'high_band_top_ratio',
+  'knee',
+];
+/**
+ * Render load is reported to `dsp::quality` each time this many samples have been rendered, which
+ * is a bit under a second at 48kHz
+ */
+const QUALITY_LOAD_REPORT_INTERVAL_SAMPLES = 32768;
+// `performance` isn't exposed to worklets in all browsers
+const nowMs = typeof performance !== 'undefined' ? () => performance.now() : () => Date.now();
 
 class CompressorAWP extends AudioWorkletProcessor {
   static get parameterDescriptors() {
@@ -46,7 +98,7 @@ class CompressorAWP extends AudioWorkletProcessor {
         defaultValue: 0,
         automationRate: 'k-rate',
         minValue: 0,
-        maxValue: 1000,
+        maxValue: 20,
       },
       {
         name: 'low_band_attack_ms',
@@ -188,6 +240,13 @@ class CompressorAWP extends AudioWorkletProcessor {
         minValue: 0,
         maxValue: 100,
       },
+      {
+        name: 'rms_window_ms',
+        defaultValue: 30,
+        automationRate: 'k-rate',
+        minValue: 0.1,
+        maxValue: 100,
+      },
     ];
   }
 
@@ -205,7 +264,15 @@ class CompressorAWP extends AudioWorkletProcessor {
     this.ctxPtr = 0;
     this.inputBufPtr = 0;
     this.outputBufPtr = 0;
+    this.packedParamsPtr = 0;
+    this.packedParamValues = new Float32Array(PACKED_PARAM_COUNT);
     this.bypass = false;
+    this.autoRelease = { low: false, mid: false, high: false };
+    this.inputDCBlocker = { enabled: false, cutoffHz: 10 };
+    this.widebandMode = false;
+    this.latencySamples = 0;
+    this.qualityRenderTimeMs = 0;
+    this.qualityRenderedSampleCount = 0;
 
     this.port.onmessage = async evt => {
       switch (evt.data.type) {
@@ -217,10 +284,55 @@ class CompressorAWP extends AudioWorkletProcessor {
           this.isShutdown = true;
           break;
         }
+        case 'reportUnderrun': {
+          this.wasmInstance?.exports.quality_report_underrun();
+          break;
+        }
         case 'setBypassed': {
+          // Start from a clean slate when coming out of bypass so that stale lookahead and
+          // envelope state from before the bypass isn't played back
+          if (this.bypass && !evt.data.bypass && this.ctxPtr) {
+            checkWasmStatus(this, this.wasmInstance.exports.reset_compressor(this.ctxPtr));
+          }
           this.bypass = evt.data.bypass;
           break;
         }
+        case 'setAutoRelease': {
+          this.autoRelease = evt.data.autoRelease;
+          break;
+        }
+        case 'setInputDCBlocker': {
+          this.inputDCBlocker = evt.data.inputDCBlocker;
+          this.applyInputDCBlocker();
+          break;
+        }
+        case 'setWidebandMode': {
+          this.widebandMode = evt.data.widebandMode;
+          this.applyWidebandMode();
+          break;
+        }
+        case 'setLogLevel': {
+          this.wasmInstance?.exports.set_log_level(evt.data.level);
+          break;
+        }
+        case 'reset': {
+          if (this.ctxPtr) {
+            checkWasmStatus(this, this.wasmInstance.exports.reset_compressor(this.ctxPtr));
+          }
+          break;
+        }
+        case 'getState': {
+          this.postState();
+          break;
+        }
+        case 'setState': {
+          this.setState(evt.data.state);
+          break;
+        }
+        case 'abSelect': {
+          this.abSelect(evt.data.slot, evt.data.fadeMs);
+          break;
+        }
         default:
           console.error('Unknown message type in CompressorAWP', evt.data.type);
       }
@@ -232,12 +344,63 @@ class CompressorAWP extends AudioWorkletProcessor {
     this.wasmInstance = await WebAssembly.instantiate(compiledModule, {
       env: { log_raw: (ptr, len, level) => this.logFromWasm(ptr, len, level) },
     });
+    this.wasmInstance.exports.set_sample_rate(sampleRate);
 
     this.ctxPtr = this.wasmInstance.exports.init_compressor();
     this.inputBufPtr = this.wasmInstance.exports.get_compressor_input_buf_ptr(this.ctxPtr);
     this.outputBufPtr = this.wasmInstance.exports.get_compressor_output_buf_ptr(this.ctxPtr);
     this.sabPtr = this.wasmInstance.exports.get_sab_ptr(this.ctxPtr);
+    this.packedParamsPtr = this.wasmInstance.exports.compressor_get_packed_params_ptr(this.ctxPtr);
     this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
+    // May have been set before the wasm finished loading
+    this.applyInputDCBlocker();
+    this.applyWidebandMode();
+  }
+
+  applyInputDCBlocker() {
+    if (!this.ctxPtr) {
+      return;
+    }
+
+    checkWasmStatus(
+      this,
+      this.wasmInstance.exports.set_compressor_input_dc_blocker(
+        this.ctxPtr,
+        this.inputDCBlocker.enabled,
+        this.inputDCBlocker.cutoffHz
+      )
+    );
+  }
+
+  /**
+   * Skips band splitting and compresses the whole signal using the mid band's params
+   */
+  applyWidebandMode() {
+    if (!this.ctxPtr) {
+      return;
+    }
+
+    checkWasmStatus(
+      this,
+      this.wasmInstance.exports.set_compressor_wideband_mode(this.ctxPtr, this.widebandMode)
+    );
+  }
+
+  /**
+   * Accumulates the time spent rendering and periodically reports it to `dsp::quality` as a
+   * fraction of the real-time budget, which is how quality is restored after underruns.
+   */
+  trackRenderTime(renderTimeMs, frameSize) {
+    this.qualityRenderTimeMs += renderTimeMs;
+    this.qualityRenderedSampleCount += frameSize;
+    if (this.qualityRenderedSampleCount < QUALITY_LOAD_REPORT_INTERVAL_SAMPLES) {
+      return;
+    }
+
+    const budgetMs = (this.qualityRenderedSampleCount / sampleRate) * 1000;
+    this.wasmInstance.exports.quality_report_load(this.qualityRenderTimeMs / budgetMs);
+    this.qualityRenderTimeMs = 0;
+    this.qualityRenderedSampleCount = 0;
   }
 
   getWasmMemoryBuffer() {
@@ -257,10 +420,158 @@ class CompressorAWP extends AudioWorkletProcessor {
         0: 'error',
         1: 'warn',
         2: 'info',
+        3: 'debug',
       }[level] || 'log';
     console[levelStr](str);
   }
 
+  /**
+   * Replies with the compressor's params from its most recent frame.  See `common::state` in the
+   * engine.
+   *
+   * @param {string | undefined} error
+   */
+  postState(error) {
+    if (!this.ctxPtr) {
+      this.port.postMessage({ type: 'state', state: null, error: 'Wasm not loaded' });
+      return;
+    }
+
+    const len = this.wasmInstance.exports.compressor_get_state_json(this.ctxPtr);
+    this.port.postMessage({ type: 'state', state: this.readStateJson(len), error });
+  }
+
+  /**
+   * @param {number} len length of the JSON written to the state JSON buffer
+   */
+  readStateJson(len) {
+    const exports = this.wasmInstance.exports;
+    const ptr = exports.get_state_json_ptr();
+    return JSON.parse(String.fromCharCode(...new Uint8Array(exports.memory.buffer, ptr, len)));
+  }
+
+  /**
+   * Restores params from a state object produced by `postState`.  Only ASCII param names are
+   * supported.
+   *
+   * @param {Record<string, number | boolean>} state
+   */
+  setState(state) {
+    if (!this.ctxPtr) {
+      this.postState();
+      return;
+    }
+
+    const exports = this.wasmInstance.exports;
+    const json = JSON.stringify(state);
+    const ptr = exports.alloc_state_json_buf(json.length);
+    const buf = new Uint8Array(exports.memory.buffer, ptr, json.length);
+    for (let i = 0; i < json.length; i++) {
+      buf[i] = json.charCodeAt(i);
+    }
+    const status = exports.compressor_set_state_json(this.ctxPtr, json.length);
+    if (status === 0) {
+      this.applyAutoRelease(state);
+    }
+    this.postState(status === 0 ? undefined : getLastWasmErrorMessage(this.wasmInstance));
+  }
+
+  /**
+   * Saves the current params into the active A/B slot, crossfades to the params in `slot`, and
+   * replies with them.  See `common::state::ABCompare` in the engine.
+   *
+   * @param {number} slot
+   * @param {number} fadeMs
+   */
+  abSelect(slot, fadeMs) {
+    if (!this.ctxPtr) {
+      this.postState();
+      return;
+    }
+
+    const exports = this.wasmInstance.exports;
+    const status = exports.compressor_ab_select(this.ctxPtr, slot, fadeMs);
+    if (status !== 0) {
+      this.port.postMessage({
+        type: 'state',
+        state: null,
+        error: getLastWasmErrorMessage(this.wasmInstance),
+      });
+      return;
+    }
+    const state = this.readStateJson(exports.compressor_ab_get_slot_state_json(this.ctxPtr, slot));
+    this.applyAutoRelease(state);
+    this.port.postMessage({ type: 'state', state });
+  }
+
+  /**
+   * Auto release is passed to `process_compressor` from here rather than through a param, so it
+   * has to be picked up from restored state
+   *
+   * @param {Record<string, number | boolean>} state
+   */
+  applyAutoRelease(state) {
+    this.autoRelease = {
+      low: state.low_band_auto_release ?? this.autoRelease.low,
+      mid: state.mid_band_auto_release ?? this.autoRelease.mid,
+      high: state.high_band_auto_release ?? this.autoRelease.high,
+    };
+  }
+
+  /**
+   * Notifies the main thread when the lookahead period changes so that parallel paths in the patch
+   * network can be delayed to match
+   *
+   * @param {number} latencySamples
+   */
+  setLatencySamples(latencySamples) {
+    if (latencySamples === this.latencySamples) {
+      return;
+    }
+    this.latencySamples = latencySamples;
+    this.port.postMessage({ type: 'latency', latencySamples });
+  }
+
+  /**
+   * Writes the current params into the compressor's packed param block.  The block is only marked
+   * dirty if something changed since the last frame, which lets the Wasm side skip decoding it.
+   */
+  writePackedParams(wasmMemory, params, lookaheadSamples) {
+    const values = this.packedParamValues;
+    let ix = 0;
+    for (const name of PACKED_LEADING_PARAM_NAMES) {
+      values[ix++] = params[name][0];
+    }
+    values[ix++] = this.autoRelease.low ? 1 : 0;
+    values[ix++] = this.autoRelease.mid ? 1 : 0;
+    values[ix++] = this.autoRelease.high ? 1 : 0;
+    for (const name of PACKED_TRAILING_PARAM_NAMES) {
+      values[ix++] = params[name][0];
+    }
+    values[ix++] = lookaheadSamples;
+    values[ix++] = params.rms_window_ms[0];
+
+    const blockIx = this.packedParamsPtr / BYTES_PER_F32;
+    const block = wasmMemory.subarray(
+      blockIx + PACKED_PARAMS_HEADER_LEN,
+      blockIx + PACKED_PARAMS_HEADER_LEN + PACKED_PARAM_COUNT
+    );
+    let changed = false;
+    for (let i = 0; i < PACKED_PARAM_COUNT; i++) {
+      if (block[i] !== values[i]) {
+        changed = true;
+        break;
+      }
+    }
+    if (!changed) {
+      return;
+    }
+
+    block.set(values);
+    wasmMemory[blockIx + PACKED_PARAMS_VERSION_IX] = PACKED_PARAMS_VERSION;
+    wasmMemory[blockIx + PACKED_PARAMS_DIRTY_IX] = 1;
+  }
+
   /**
    *
    * @param {Float32Array[][]} inputs
@@ -279,76 +590,31 @@ class CompressorAWP extends AudioWorkletProcessor {
 
     if (this.bypass) {
       output.set(input);
+      this.setLatencySamples(0);
       return true;
     }
 
+    const frameSize = input.length;
     const wasmMemory = this.getWasmMemoryBuffer();
     const inputBuffer = wasmMemory.subarray(
       this.inputBufPtr / BYTES_PER_F32,
-      this.inputBufPtr / BYTES_PER_F32 + FRAME_SIZE
+      this.inputBufPtr / BYTES_PER_F32 + frameSize
     );
     inputBuffer.set(input);
 
-    const mix = params.mix[0];
-    const preGain = params.pre_gain[0];
-    const postGain = params.post_gain[0];
-    const lowBandGain = params.low_band_gain[0];
-    const midBandGain = params.mid_band_gain[0];
-    const highBandGain = params.high_band_gain[0];
-    const lowBandAttackMs = params.low_band_attack_ms[0];
-    const lowBandReleaseMs = params.low_band_release_ms[0];
-    const midBandAttackMs = params.mid_band_attack_ms[0];
-    const midBandReleaseMs = params.mid_band_release_ms[0];
-    const highBandAttackMs = params.high_band_attack_ms[0];
-    const highBandReleaseMs = params.high_band_release_ms[0];
-    const lowBandThresholdBottomDb = params.low_band_bottom_threshold_db[0];
-    const midBandThresholdBottomDb = params.mid_band_bottom_threshold_db[0];
-    const highBandThresholdBottomDb = params.high_band_bottom_threshold_db[0];
-    const lowBandThresholdTopDb = params.low_band_top_threshold_db[0];
-    const midBandThresholdTopDb = params.mid_band_top_threshold_db[0];
-    const highBandThresholdTopDb = params.high_band_top_threshold_db[0];
-    const lowBandBottomRatio = params.low_band_bottom_ratio[0];
-    const midBandBottomRatio = params.mid_band_bottom_ratio[0];
-    const highBandBottomRatio = params.high_band_bottom_ratio[0];
-    const lowBandTopRatio = params.low_band_top_ratio[0];
-    const midBandTopRatio = params.mid_band_top_ratio[0];
-    const highBandTopRatio = params.high_band_top_ratio[0];
-    const knee = params.knee[0];
     const lookaheadSamples = Math.floor(params.lookahead_ms[0] * 0.001 * SAMPLE_RATE);
+    // Both the compressed and dry signals are delayed by the lookahead period
+    this.setLatencySamples(lookaheadSamples);
+    this.writePackedParams(wasmMemory, params, lookaheadSamples);
 
-    this.wasmInstance.exports.process_compressor(
-      this.ctxPtr,
-      mix,
-      preGain,
-      postGain,
-      lowBandGain,
-      midBandGain,
-      highBandGain,
-      lowBandAttackMs,
-      lowBandReleaseMs,
-      midBandAttackMs,
-      midBandReleaseMs,
-      highBandAttackMs,
-      highBandReleaseMs,
-      lowBandThresholdBottomDb,
-      midBandThresholdBottomDb,
-      highBandThresholdBottomDb,
-      lowBandThresholdTopDb,
-      midBandThresholdTopDb,
-      highBandThresholdTopDb,
-      lowBandBottomRatio,
-      midBandBottomRatio,
-      highBandBottomRatio,
-      lowBandTopRatio,
-      midBandTopRatio,
-      highBandTopRatio,
-      knee,
-      lookaheadSamples
-    );
+    const renderStartMs = nowMs();
+    const status = this.wasmInstance.exports.process_compressor_packed(this.ctxPtr, frameSize);
+    this.trackRenderTime(nowMs() - renderStartMs, frameSize);
+    checkWasmStatus(this, status);
 
     const outputBuffer = wasmMemory.subarray(
       this.outputBufPtr / BYTES_PER_F32,
-      this.outputBufPtr / BYTES_PER_F32 + FRAME_SIZE
+      this.outputBufPtr / BYTES_PER_F32 + frameSize
     );
     output.set(outputBuffer);
 
@@ -1,3 +1,5 @@
+import { checkWasmStatus } from './wasmStatus.js';
+
 const FRAME_SIZE = 128;
 const BYTES_PER_F32 = 32 / 8;
 
@@ -55,6 +57,7 @@ class DelayAWP extends AudioWorkletProcessor {
   async initWasmInstance(wasmBytes) {
     const compiledModule = await WebAssembly.compile(wasmBytes);
     this.wasmInstance = await WebAssembly.instantiate(compiledModule, { env: {} });
+    this.wasmInstance.exports.set_sample_rate(sampleRate);
 
     this.ctxPtr = this.wasmInstance.exports.init_delay_ctx();
     this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
@@ -129,7 +132,7 @@ class DelayAWP extends AudioWorkletProcessor {
     this.copyParam(highpassCutoff, this.paramPointers.highpassCutoff);
 
     // Process delay, overwriting the main IO buffer in Wasm and populating the delay output buffer
-    this.wasmInstance.exports.process_delay(this.ctxPtr);
+    checkWasmStatus(this, this.wasmInstance.exports.process_delay(this.ctxPtr));
 
     // Copy outputs output Wasm to output array
     outputs[0]?.[0]?.set(
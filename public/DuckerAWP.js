@@ -0,0 +1,250 @@
+import { checkWasmStatus } from './wasmStatus.js';
+
+const BYTES_PER_F32 = 32 / 8;
+const SAB_SIZE = 2 * BYTES_PER_F32;
+/**
+ * Must match `dsp::MAX_FRAME_SIZE`; the right channel starts at this offset in the IO buffer.
+ */
+const MAX_FRAME_SIZE = 1024;
+
+/**
+ * Applies an attack/hold/release attenuation envelope to the main input (input 0) each time it's
+ * triggered, either by the trigger input (input 1) or by the global transport.
+ */
+class DuckerAWP extends AudioWorkletProcessor {
+  static get parameterDescriptors() {
+    return [
+      {
+        name: 'depth',
+        defaultValue: 0.8,
+        automationRate: 'k-rate',
+        minValue: 0,
+        maxValue: 1,
+      },
+      {
+        name: 'attack_ms',
+        defaultValue: 5,
+        automationRate: 'k-rate',
+        minValue: 0,
+        maxValue: 2000,
+      },
+      {
+        name: 'hold_ms',
+        defaultValue: 20,
+        automationRate: 'k-rate',
+        minValue: 0,
+        maxValue: 2000,
+      },
+      {
+        name: 'release_ms',
+        defaultValue: 200,
+        automationRate: 'k-rate',
+        minValue: 0,
+        maxValue: 2000,
+      },
+      {
+        name: 'shape',
+        defaultValue: 0,
+        automationRate: 'k-rate',
+        minValue: -1,
+        maxValue: 1,
+      },
+      {
+        name: 'threshold_db',
+        defaultValue: -24,
+        automationRate: 'k-rate',
+        minValue: -100,
+        maxValue: 12,
+      },
+    ];
+  }
+
+  constructor() {
+    super({ numberOfInputs: 2, numberOfOutputs: 1, outputChannelCount: [2] });
+
+    this.isShutdown = false;
+    this.sab = typeof SharedArrayBuffer !== 'undefined' ? new SharedArrayBuffer(SAB_SIZE) : null;
+    if (this.sab) {
+      this.port.postMessage({ type: 'sab', sab: this.sab });
+    }
+    this.sabView = this.sab ? new Float32Array(this.sab) : null;
+    this.sabPtr = 0;
+    this.wasmInstance = null;
+    this.ctxPtr = 0;
+    this.ioBufPtr = 0;
+    this.triggerBufPtr = 0;
+    this.wasmMemoryBuffer = null;
+    this.pendingTriggerMode = null;
+    this.pendingBeatInterval = null;
+
+    this.port.onmessage = async evt => {
+      switch (evt.data.type) {
+        case 'setWasmBytes': {
+          await this.initWasm(evt.data.wasmBytes);
+          break;
+        }
+        case 'shutdown': {
+          this.isShutdown = true;
+          break;
+        }
+        case 'setTriggerMode': {
+          this.setTriggerMode(evt.data.triggerMode);
+          break;
+        }
+        case 'setBeatInterval': {
+          this.setBeatInterval(evt.data.beatInterval);
+          break;
+        }
+        default:
+          console.error('Unknown message type in DuckerAWP', evt.data.type);
+      }
+    };
+  }
+
+  handleWasmPanic = (ptr, len) => {
+    const mem = new Uint8Array(this.wasmInstance.exports.memory.buffer);
+    const slice = mem.subarray(ptr, ptr + len);
+    const str = String.fromCharCode(...slice);
+    console.error(`DuckerAWP Wasm panic: ${str}`);
+  };
+
+  async initWasm(wasmBytes) {
+    const importObject = { env: { log_err: (ptr, len) => this.handleWasmPanic(ptr, len) } };
+    const compiledModule = await WebAssembly.compile(wasmBytes);
+    this.wasmInstance = await WebAssembly.instantiate(compiledModule, importObject);
+    this.wasmInstance.exports.set_sample_rate(sampleRate);
+    this.ctxPtr = this.wasmInstance.exports.ducker_create_ctx();
+    this.ioBufPtr = this.wasmInstance.exports.ducker_get_io_buf_ptr(this.ctxPtr);
+    this.triggerBufPtr = this.wasmInstance.exports.ducker_get_trigger_buf_ptr(this.ctxPtr);
+    this.sabPtr = this.wasmInstance.exports.ducker_get_sab_ptr(this.ctxPtr);
+    this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
+
+    if (this.pendingTriggerMode !== null) {
+      this.setTriggerMode(this.pendingTriggerMode);
+    }
+    if (this.pendingBeatInterval !== null) {
+      this.setBeatInterval(this.pendingBeatInterval);
+    }
+  }
+
+  /**
+   * @param {number} triggerMode 0 for the trigger input, 1 for the global transport
+   */
+  setTriggerMode(triggerMode) {
+    if (!this.ctxPtr) {
+      this.pendingTriggerMode = triggerMode;
+      return;
+    }
+    checkWasmStatus(
+      this,
+      this.wasmInstance.exports.ducker_set_trigger_mode(this.ctxPtr, triggerMode)
+    );
+  }
+
+  /**
+   * @param {number} beatInterval Number of beats between triggers when synced to the transport
+   */
+  setBeatInterval(beatInterval) {
+    if (!this.ctxPtr) {
+      this.pendingBeatInterval = beatInterval;
+      return;
+    }
+    checkWasmStatus(
+      this,
+      this.wasmInstance.exports.ducker_set_beat_interval(this.ctxPtr, beatInterval)
+    );
+  }
+
+  getWasmMemoryBuffer() {
+    if (this.wasmMemoryBuffer.buffer !== this.wasmInstance.exports.memory.buffer) {
+      this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
+    }
+    return this.wasmMemoryBuffer;
+  }
+
+  /**
+   * @param {Float32Array[][]} inputs
+   * @param {Float32Array[][]} outputs
+   * @param {{[key: string]: Float32Array}} params
+   * @returns {boolean}
+   */
+  process(inputs, outputs, params) {
+    if (this.isShutdown) {
+      return false;
+    }
+
+    const output = outputs[0];
+    if (!output?.[0]) {
+      return true;
+    }
+
+    const left = inputs[0]?.[0];
+    // Mono input is upmixed to both channels
+    const right = inputs[0]?.[1] ?? left;
+    if (!this.ctxPtr) {
+      if (left) {
+        output[0].set(left);
+        output[1]?.set(right);
+      }
+      return true;
+    }
+
+    const frameSize = output[0].length;
+    const wasmMemory = this.getWasmMemoryBuffer();
+    const ioBufIx = this.ioBufPtr / BYTES_PER_F32;
+    const leftBuf = wasmMemory.subarray(ioBufIx, ioBufIx + frameSize);
+    const rightBuf = wasmMemory.subarray(
+      ioBufIx + MAX_FRAME_SIZE,
+      ioBufIx + MAX_FRAME_SIZE + frameSize
+    );
+    if (left) {
+      leftBuf.set(left);
+      rightBuf.set(right);
+    } else {
+      leftBuf.fill(0);
+      rightBuf.fill(0);
+    }
+
+    const triggerBuf = wasmMemory.subarray(
+      this.triggerBufPtr / BYTES_PER_F32,
+      this.triggerBufPtr / BYTES_PER_F32 + frameSize
+    );
+    const trigger = inputs[1]?.[0];
+    if (trigger) {
+      triggerBuf.set(trigger);
+    } else {
+      triggerBuf.fill(0);
+    }
+
+    const status = this.wasmInstance.exports.ducker_process(
+      this.ctxPtr,
+      frameSize,
+      globalThis.curBeat ?? 0,
+      globalThis.globalTempoBPM || 120,
+      !!globalThis.globalBeatCounterStarted,
+      params.depth[0],
+      params.attack_ms[0],
+      params.hold_ms[0],
+      params.release_ms[0],
+      params.shape[0],
+      params.threshold_db[0]
+    );
+    checkWasmStatus(this, status);
+
+    output[0].set(leftBuf);
+    output[1]?.set(rightBuf);
+
+    if (this.sab) {
+      this.sabView.set(
+        wasmMemory.subarray(
+          this.sabPtr / BYTES_PER_F32,
+          this.sabPtr / BYTES_PER_F32 + SAB_SIZE / BYTES_PER_F32
+        )
+      );
+    }
+
+    return true;
+  }
+}
+
+registerProcessor('ducker-awp', DuckerAWP);
@@ -9,6 +9,8 @@
 // [2] = (generic param 2)
 // [3] = sample index within current frame
 
+import { checkWasmStatus } from './wasmStatus.js';
+
 class MIDIEventMailboxRegistry {
   constructor() {
     this.mailboxes = new Map();
@@ -93,8 +95,16 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
     this.port.postMessage({ type: 'beatManagerSAB', beatManagerSAB: this.beatManagerSAB });
 
     this.pendingEvents = [];
+    this.pendingArpeggiatorMessages = [];
+    this.arpeggiatorIxByID = new Map();
     this.lastRecordedTime = 0;
     this.isStarted = false;
+    /**
+     * Outgoing MIDI messages generated during the current render, handed to the main thread at the
+     * end of it to be sent to MIDI outputs
+     */
+    this.pendingMIDIOutput = [];
+    this.phaseSyncEnabled = false;
 
     this.port.onmessage = event => {
       switch (event.data.type) {
@@ -112,6 +122,8 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
           this.lastRecordedTime = currentTime;
           globalThis.globalBeatCounterStarted = true;
           this.isStarted = true;
+          this.wasmInstance.exports.midi_clock_output_start(currentTime);
+          this.flushMIDIOutput();
           break;
         }
         case 'stop': {
@@ -124,6 +136,8 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
           globalThis.globalBeatCounterStarted = false;
           this.wasmInstance.exports.stop();
           this.isStarted = false;
+          this.wasmInstance.exports.midi_clock_output_stop(currentTime);
+          this.flushMIDIOutput();
           break;
         }
         case 'schedule': {
@@ -174,6 +188,48 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
           this.isShutdown = true;
           break;
         }
+        case 'createArpeggiator':
+        case 'deleteArpeggiator':
+        case 'setArpeggiatorConfig':
+        case 'arpeggiatorNoteOn':
+        case 'arpeggiatorNoteOff':
+        case 'clearArpeggiator': {
+          if (!this.wasmInstance) {
+            this.pendingArpeggiatorMessages.push(event.data);
+            break;
+          }
+
+          this.handleArpeggiatorMessage(event.data);
+          break;
+        }
+        case 'tapTempo':
+        case 'externalClockTick':
+        case 'resetExternalClock':
+        case 'setExternalClockTicksPerBeat':
+        case 'setPhaseSync':
+        case 'phaseSyncReport': {
+          if (!this.wasmInstance) {
+            console.error('Tried to update tempo sync before Wasm initialized');
+            break;
+          }
+
+          this.handleTempoSyncMessage(event.data);
+          break;
+        }
+        case 'setMIDIClockOutputEnabled': {
+          if (!this.wasmInstance) {
+            console.error('Tried to set MIDI clock output before Wasm initialized');
+            break;
+          }
+
+          this.wasmInstance.exports.midi_clock_output_set_enabled(
+            event.data.enabled,
+            event.data.sendMMC,
+            currentTime
+          );
+          this.flushMIDIOutput();
+          break;
+        }
         case 'postMIDIEvent': {
           globalThis.midiEventMailboxRegistry.submitEvent(
             event.data.mailboxID,
@@ -212,6 +268,10 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
             0
           );
         },
+        send_midi_output: (ptr, len, time) => {
+          const bytes = new Uint8Array(this.wasmInstance.exports.memory.buffer, ptr, len);
+          this.pendingMIDIOutput.push({ data: Array.from(bytes), time });
+        },
       },
     });
 
@@ -222,6 +282,99 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
         : this.scheduleEvent(event.time, event.cbId)
     );
     this.pendingEvents = null;
+    this.pendingArpeggiatorMessages.forEach(msg => this.handleArpeggiatorMessage(msg));
+    this.pendingArpeggiatorMessages = null;
+  }
+
+  handleArpeggiatorMessage(msg) {
+    const exports = this.wasmInstance.exports;
+    if (msg.type === 'createArpeggiator') {
+      const mailboxIx = globalThis.midiEventMailboxRegistry.getMailboxIndex(msg.mailboxID);
+      if (mailboxIx === undefined) {
+        console.error(`Tried to create arpeggiator with unknown mailbox: ${msg.mailboxID}`);
+        return;
+      }
+      this.arpeggiatorIxByID.set(msg.arpID, exports.arpeggiator_create(mailboxIx));
+      return;
+    }
+
+    const arpIx = this.arpeggiatorIxByID.get(msg.arpID);
+    if (arpIx === undefined) {
+      console.error(`Tried to use unknown arpeggiator: ${msg.arpID}`);
+      return;
+    }
+
+    switch (msg.type) {
+      case 'deleteArpeggiator': {
+        checkWasmStatus(this, exports.arpeggiator_delete(arpIx));
+        this.arpeggiatorIxByID.delete(msg.arpID);
+        break;
+      }
+      case 'setArpeggiatorConfig': {
+        const { pattern, octaveRange, rateBeats, gateLength, swing } = msg.config;
+        checkWasmStatus(
+          this,
+          exports.arpeggiator_set_config(arpIx, pattern, octaveRange, rateBeats, gateLength, swing)
+        );
+        break;
+      }
+      case 'arpeggiatorNoteOn': {
+        checkWasmStatus(this, exports.arpeggiator_note_on(arpIx, msg.note, msg.velocity));
+        break;
+      }
+      case 'arpeggiatorNoteOff': {
+        checkWasmStatus(this, exports.arpeggiator_note_off(arpIx, msg.note));
+        break;
+      }
+      case 'clearArpeggiator': {
+        checkWasmStatus(this, exports.arpeggiator_clear(arpIx));
+        break;
+      }
+      default: {
+        console.error(`Unhandled arpeggiator message type: ${msg.type}`);
+      }
+    }
+  }
+
+  handleTempoSyncMessage(msg) {
+    const exports = this.wasmInstance.exports;
+    switch (msg.type) {
+      case 'tapTempo': {
+        const bpm = exports.tap_tempo(msg.time);
+        if (bpm > 0) {
+          this.port.postMessage({ type: 'tempoEstimate', bpm, source: 'tap' });
+        }
+        break;
+      }
+      case 'externalClockTick': {
+        const bpm = exports.external_clock_tick(msg.time);
+        if (bpm > 0) {
+          this.port.postMessage({ type: 'tempoEstimate', bpm, source: 'externalClock' });
+        }
+        break;
+      }
+      case 'resetExternalClock': {
+        exports.external_clock_reset();
+        break;
+      }
+      case 'setExternalClockTicksPerBeat': {
+        exports.external_clock_set_ticks_per_beat(msg.ticksPerBeat);
+        break;
+      }
+      case 'setPhaseSync': {
+        this.phaseSyncEnabled = msg.enabled;
+        exports.phase_sync_reset();
+        exports.phase_sync_set_quantum(msg.quantum);
+        break;
+      }
+      case 'phaseSyncReport': {
+        exports.phase_sync_add_report(msg.time, msg.beat, msg.bpm);
+        break;
+      }
+      default: {
+        console.error(`Unhandled tempo sync message type: ${msg.type}`);
+      }
+    }
   }
 
   /**
@@ -280,6 +433,16 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
   }
 
   updateGlobalBeats(globalTempoBPM) {
+    if (this.phaseSyncEnabled && this.isStarted && this.wasmInstance) {
+      // Steers the beat counter into phase with the shared session
+      const steeredBPM = this.wasmInstance.exports.phase_sync_steer(
+        currentTime,
+        globalThis.curBeat
+      );
+      if (steeredBPM > 0) {
+        globalTempoBPM = steeredBPM;
+      }
+    }
     globalThis.globalTempoBPM = globalTempoBPM;
 
     if (this.isStarted) {
@@ -295,11 +458,26 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
     }
   }
 
+  flushMIDIOutput() {
+    if (this.pendingMIDIOutput.length === 0) {
+      return;
+    }
+
+    this.port.postMessage({ type: 'midiOutput', messages: this.pendingMIDIOutput });
+    this.pendingMIDIOutput = [];
+  }
+
   process(_inputs, _outputs, params) {
     this.updateGlobalBeats(params.global_tempo_bpm[0]);
 
     if (this.wasmInstance) {
       this.wasmInstance.exports.run(currentTime, globalThis.curBeat);
+      this.wasmInstance.exports.midi_clock_output_run(
+        currentTime,
+        globalThis.curBeat,
+        globalThis.globalTempoBPM
+      );
+      this.flushMIDIOutput();
     }
 
     return true;
@@ -1,13 +1,168 @@
 const FRAME_SIZE = 128;
 const BYTES_PER_F32 = 32 / 8;
 const OUTPUT_BYTES_PER_OPERATOR = FRAME_SIZE * BYTES_PER_F32;
+const OUTPUT_BYTES_PER_VOICE = OUTPUT_BYTES_PER_OPERATOR * 2;
 const VOICE_COUNT = 10;
 const PARAM_COUNT = 8;
 const ADSR_PHASE_BUF_LENGTH = 256;
+/**
+ * Must match `dsp::profiling::STATS_PER_SLOT`.  The synth has one slot for the whole frame followed
+ * by one per voice; they're appended to the shared buffer after the ADSR phases.
+ */
+const PROFILING_STATS_PER_SLOT = 2;
+const PROFILING_STATS_LEN = (1 + VOICE_COUNT) * PROFILING_STATS_PER_SLOT;
+/**
+ * Must match `EFFECT_PARAM_VIZ_BUF_LEN` in `engine/wavetable/src/fm/mod.rs`.  Appended to the
+ * shared buffer after the profiling stats.
+ */
+const EFFECT_PARAM_VIZ_LEN = (8 + 1) * 16 * 4;
+/**
+ * See `BOUNCE_EVENT_SIZE` in `engine/wavetable/src/fm/bounce.rs` for the layout of each event
+ */
+const BOUNCE_EVENT_SIZE = 4;
+/**
+ * Number of frames rendered each time `process` is called while bouncing
+ */
+const BOUNCE_FRAMES_PER_PROCESS = 32;
+/**
+ * See `ENCODED_CONSTRAINT_SIZE` in `engine/wavetable/src/fm/randomize.rs` for the layout
+ */
+const RANDOMIZE_CONSTRAINT_SIZE = 4;
+/**
+ * Render load is reported to `dsp::quality` each time this many samples have been rendered, which
+ * is a bit under a second at 48kHz
+ */
+const QUALITY_LOAD_REPORT_INTERVAL_SAMPLES = 32768;
+/**
+ * See `CONTROL_EVENT_SIZE` in `engine/wavetable/src/fm/control_events.rs` for the layout of each
+ * event and the event types
+ */
+const CONTROL_EVENT_SIZE = 4;
+const CONTROL_EVENT_TYPE_GATE = 0;
+const CONTROL_EVENT_TYPE_UNGATE = 1;
+const CONTROL_EVENT_TYPE_UNGATE_ALL = 2;
+const CONTROL_EVENT_TYPE_PITCH_BEND = 3;
+const MAX_CONTROL_EVENTS_PER_FRAME = 128;
+/**
+ * Must match the layout of the ring buffer in `src/eventRing.ts`
+ */
+const CONTROL_EVENT_RING_HEADER_BYTES = 8;
+const CONTROL_EVENT_RING_WRITE_IX = 0;
+const CONTROL_EVENT_RING_READ_IX = 1;
+/**
+ * Must match `NotificationType` and the layout of `NotificationRing` in `src/eventRing.ts`
+ */
+const NOTIFICATION_TYPE_VOICE_STARTED = 0;
+const NOTIFICATION_TYPE_VOICE_ENDED = 1;
+const NOTIFICATION_TYPE_ERROR = 2;
+const NOTIFICATION_RING_HEADER_BYTES = 16;
+const NOTIFICATION_RING_DROPPED_COUNT_IX = 2;
+// `performance` isn't exposed to worklets in all browsers
+const nowMs = typeof performance !== 'undefined' ? () => performance.now() : () => Date.now();
+
+/**
+ * Must match `RECORD_TYPE_*` in `engine/wavetable/src/fm/preset.rs`, which documents the layout of
+ * each record.  Records are built from the same messages that set each part of the voice
+ * individually.
+ */
+const VOICE_PRESET_RECORD_TYPE_BY_MESSAGE_TYPE = {
+  setModulationIndex: 0,
+  setOutputWeightValue: 1,
+  setOperatorConfig: 2,
+  setOperatorBaseFrequencySource: 3,
+  setEffect: 4,
+  setAdsr: 5,
+  setDetune: 6,
+  setOperatorConnectionType: 7,
+  setVoiceLayers: 8,
+  setOperatorPan: 9,
+  setVoicePan: 10,
+  setEffectMix: 11,
+};
+
+const encodeParamSourceFields = paramSource => [
+  paramSource?.valueType ?? 0,
+  paramSource?.valParamInt ?? 0,
+  paramSource?.valParamFloat ?? 0,
+  paramSource?.valParamFloat2 ?? 0,
+  paramSource?.valParamFloat3 ?? 0,
+];
+
+const encodeVoicePresetRecord = msg => {
+  const recordType = VOICE_PRESET_RECORD_TYPE_BY_MESSAGE_TYPE[msg.type];
+  switch (msg.type) {
+    case 'setModulationIndex':
+      return [recordType, msg.srcOperatorIx, msg.dstOperatorIx, ...encodeParamSourceFields(msg)];
+    case 'setOutputWeightValue':
+    case 'setOperatorBaseFrequencySource':
+      return [recordType, msg.operatorIx, ...encodeParamSourceFields(msg)];
+    case 'setOperatorConfig':
+      return [
+        recordType,
+        msg.operatorIx,
+        msg.operatorType,
+        msg.unison,
+        msg.unisonPhaseRandomizationEnabled ? 1 : 0,
+        ...[msg.param1, msg.param2, msg.param3, msg.param4, msg.param5].flatMap(
+          encodeParamSourceFields
+        ),
+      ];
+    case 'setEffect':
+      return [
+        recordType,
+        msg.operatorIx ?? -1,
+        msg.effectIx,
+        msg.effectType,
+        ...[msg.param1, msg.param2, msg.param3, msg.param4].flatMap(encodeParamSourceFields),
+        msg.isBypassed ? 1 : 0,
+        // Removed effects have no mix to set
+        ...(msg.effectType === -1
+          ? []
+          : encodeVoicePresetRecord({ ...msg, type: 'setEffectMix', mix: msg.mix ?? 1 })),
+      ];
+    case 'setAdsr':
+      return [
+        recordType,
+        msg.adsrIx,
+        msg.steps.length,
+        ...encodeParamSourceFields(msg.lenSamples),
+        msg.releasePoint,
+        msg.loopPoint ?? -1,
+        msg.logScale ? 1 : 0,
+        ...msg.steps.flatMap(({ x, y, ramper, param }) => [x, y, ramper, param]),
+      ];
+    case 'setDetune':
+      return [recordType, ...encodeParamSourceFields(msg)];
+    case 'setOperatorConnectionType':
+      return [recordType, msg.srcOperatorIx, msg.dstOperatorIx, msg.connectionType];
+    case 'setVoiceLayers':
+      return [
+        recordType,
+        msg.subOctaves,
+        msg.subShape,
+        msg.subLevel,
+        msg.noiseLevel,
+        msg.noiseDecayMs,
+      ];
+    case 'setOperatorPan':
+      return [recordType, msg.operatorIx, msg.unisonSpread, ...encodeParamSourceFields(msg)];
+    case 'setVoicePan':
+      return [recordType, ...encodeParamSourceFields(msg)];
+    case 'setEffectMix':
+      return [recordType, msg.operatorIx ?? -1, msg.effectIx, msg.mix];
+    default:
+      throw new Error(`Unhandled voice preset record type: ${msg.type}`);
+  }
+};
 
 const hashSampleDescriptor = descriptor =>
   `${descriptor.name}${descriptor.isLocal}${descriptor.id}`;
 
+/**
+ * Must match `LogLevel` in the `logging` crate
+ */
+const LOG_LEVEL_CONSOLE_FNS = ['error', 'warn', 'info', 'debug'];
+
 class FMSynthAWP extends AudioWorkletProcessor {
   static get parameterDescriptors() {
     return [
@@ -35,6 +190,24 @@ class FMSynthAWP extends AudioWorkletProcessor {
     this.ctxPtr = 0;
     this.wasmMemoryBuffer = null;
     this.sampleDataIxByHashedSampleDescriptor = new Map();
+    /**
+     * Set while an offline bounce is running.  Bounces are rendered by a copy of the synth, so live
+     * playback carries on alongside them.
+     */
+    this.bounce = null;
+    this.profilingEnabled = false;
+    this.qualityRenderTimeMs = 0;
+    this.qualityRenderedSampleCount = 0;
+    /**
+     * Ring buffer of control events written by the main thread; see `src/eventRing.ts`
+     */
+    this.controlEventRing = null;
+    this.controlEventCount = 0;
+    /**
+     * Ring buffer of notifications read by the main thread; see `src/eventRing.ts`.  Notifications
+     * are posted as messages instead if it isn't set.
+     */
+    this.notificationRing = null;
 
     this.port.onmessage = evt => {
       switch (evt.data.type) {
@@ -65,6 +238,72 @@ class FMSynthAWP extends AudioWorkletProcessor {
           );
           break;
         }
+        case 'setOperatorConnectionType': {
+          if (!this.wasmInstance) {
+            console.error('Tried setting operator connection type before Wasm instance loaded');
+            return;
+          }
+          const status = this.wasmInstance.exports.fm_synth_set_operator_connection_type(
+            this.ctxPtr,
+            evt.data.srcOperatorIx,
+            evt.data.dstOperatorIx,
+            evt.data.connectionType
+          );
+          if (status !== 0) {
+            console.error(`Invalid operator connection type (code ${status})`, evt.data);
+          }
+          break;
+        }
+        case 'setVoiceLayers': {
+          if (!this.wasmInstance) {
+            console.error('Tried setting voice layers before Wasm instance loaded');
+            return;
+          }
+          const status = this.wasmInstance.exports.fm_synth_set_voice_layers(
+            this.ctxPtr,
+            evt.data.subOctaves,
+            evt.data.subShape,
+            evt.data.subLevel,
+            evt.data.noiseLevel,
+            evt.data.noiseDecayMs
+          );
+          if (status !== 0) {
+            console.error(`Invalid voice layers (code ${status})`, evt.data);
+          }
+          break;
+        }
+        case 'setOperatorPan': {
+          if (!this.wasmInstance) {
+            console.error('Tried setting operator pan before Wasm instance loaded');
+            return;
+          }
+          this.wasmInstance.exports.fm_synth_set_operator_pan(
+            this.ctxPtr,
+            evt.data.operatorIx,
+            evt.data.unisonSpread,
+            evt.data.valueType,
+            evt.data.valParamInt,
+            evt.data.valParamFloat,
+            evt.data.valParamFloat2,
+            evt.data.valParamFloat3
+          );
+          break;
+        }
+        case 'setVoicePan': {
+          if (!this.wasmInstance) {
+            console.error('Tried setting voice pan before Wasm instance loaded');
+            return;
+          }
+          this.wasmInstance.exports.fm_synth_set_voice_pan(
+            this.ctxPtr,
+            evt.data.valueType,
+            evt.data.valParamInt,
+            evt.data.valParamFloat,
+            evt.data.valParamFloat2,
+            evt.data.valParamFloat3
+          );
+          break;
+        }
         case 'setOutputWeightValue': {
           if (!this.wasmInstance) {
             console.error('Tried setting output weight value before Wasm instance loaded');
@@ -181,6 +420,53 @@ class FMSynthAWP extends AudioWorkletProcessor {
             param4?.valParamFloat3 ?? 0,
             isBypassed ?? false
           );
+          if (effectType !== -1) {
+            this.wasmInstance.exports.fm_synth_set_effect_mix(
+              this.ctxPtr,
+              evt.data.operatorIx ?? -1,
+              evt.data.effectIx,
+              evt.data.mix ?? 1
+            );
+          }
+          break;
+        }
+        case 'setEffectBypassed': {
+          if (!this.wasmInstance) {
+            console.error('Tried setting effect bypass before Wasm instance loaded');
+            return;
+          }
+          this.wasmInstance.exports.fm_synth_set_effect_bypassed(
+            this.ctxPtr,
+            evt.data.operatorIx ?? -1,
+            evt.data.effectIx,
+            evt.data.isBypassed
+          );
+          break;
+        }
+        case 'setEffectMix': {
+          if (!this.wasmInstance) {
+            console.error('Tried setting effect mix before Wasm instance loaded');
+            return;
+          }
+          this.wasmInstance.exports.fm_synth_set_effect_mix(
+            this.ctxPtr,
+            evt.data.operatorIx ?? -1,
+            evt.data.effectIx,
+            evt.data.mix
+          );
+          break;
+        }
+        case 'moveEffect': {
+          if (!this.wasmInstance) {
+            console.error('Tried moving effect before Wasm instance loaded');
+            return;
+          }
+          this.wasmInstance.exports.fm_synth_move_effect(
+            this.ctxPtr,
+            evt.data.operatorIx ?? -1,
+            evt.data.fromEffectIx,
+            evt.data.toEffectIx
+          );
           break;
         }
         case 'setAdsr': {
@@ -354,10 +640,140 @@ class FMSynthAWP extends AudioWorkletProcessor {
           );
           break;
         }
+        case 'setMasterGain': {
+          if (!this.wasmInstance) {
+            console.warn('Tried to set master gain before Wasm instance loaded');
+            return;
+          }
+
+          this.wasmInstance.exports.fm_synth_set_master_gain(this.ctxPtr, evt.data.masterGain);
+          break;
+        }
+        case 'setTuning': {
+          if (!this.wasmInstance) {
+            console.warn('Tried to set tuning before Wasm instance loaded');
+            return;
+          }
+
+          const { divisionsPerOctave, referenceNote, referenceFrequency } = evt.data.tuning;
+          const status = this.wasmInstance.exports.fm_synth_set_tuning(
+            this.ctxPtr,
+            divisionsPerOctave,
+            referenceNote,
+            referenceFrequency
+          );
+          if (status !== 0) {
+            console.error(`Invalid tuning (code ${status})`, evt.data.tuning);
+          }
+          break;
+        }
+        case 'bounce': {
+          if (!this.wasmInstance) {
+            this.port.postMessage({
+              type: 'bounceError',
+              message: 'Tried to bounce before Wasm instance loaded',
+            });
+            return;
+          }
+
+          this.startBounce(evt.data);
+          break;
+        }
+        case 'loadVoicePreset': {
+          if (!this.wasmInstance) {
+            console.error('Tried loading voice preset before Wasm instance loaded');
+            return;
+          }
+
+          this.loadVoicePreset(evt.data.records);
+          break;
+        }
+        case 'setMorphPresets': {
+          if (!this.wasmInstance) {
+            console.error('Tried setting morph presets before Wasm instance loaded');
+            return;
+          }
+
+          for (const param of evt.data.params) {
+            const status = this.wasmInstance.exports.fm_synth_set_morph_preset_param(
+              this.ctxPtr,
+              param.presetIx,
+              param.paramType,
+              param.operatorIx,
+              param.dstOperatorIx,
+              param.valueType,
+              param.valParamInt,
+              param.valParamFloat,
+              param.valParamFloat2,
+              param.valParamFloat3
+            );
+            if (status !== 0) {
+              console.error(`Invalid morph preset param (code ${status})`, param);
+            }
+          }
+          break;
+        }
+        case 'setMorphPosition': {
+          if (!this.wasmInstance) {
+            console.error('Tried setting morph position before Wasm instance loaded');
+            return;
+          }
+          this.wasmInstance.exports.fm_synth_set_morph_position(this.ctxPtr, evt.data.position);
+          break;
+        }
+        case 'randomizeVoiceParams': {
+          if (!this.wasmInstance) {
+            console.error('Tried randomizing voice params before Wasm instance loaded');
+            return;
+          }
+          this.randomizeVoiceParams(evt.data);
+          break;
+        }
+        case 'setProfilingEnabled': {
+          this.profilingEnabled = evt.data.enabled;
+          if (this.wasmInstance) {
+            this.wasmInstance.exports.fm_synth_set_profiling_enabled(
+              this.ctxPtr,
+              this.profilingEnabled
+            );
+          }
+          break;
+        }
+        case 'setLogLevel': {
+          this.wasmInstance?.exports.set_log_level(evt.data.level);
+          break;
+        }
         case 'shutdown': {
           this.shutdown = true;
           break;
         }
+        case 'setControlEventRing': {
+          const { sab } = evt.data;
+          const capacity =
+            (sab.byteLength - CONTROL_EVENT_RING_HEADER_BYTES) /
+            (CONTROL_EVENT_SIZE * Float64Array.BYTES_PER_ELEMENT);
+          this.controlEventRing = {
+            header: new Int32Array(sab, 0, 2),
+            events: new Float64Array(sab, CONTROL_EVENT_RING_HEADER_BYTES),
+            capacity,
+          };
+          break;
+        }
+        case 'setNotificationRing': {
+          const { sab } = evt.data;
+          this.notificationRing = {
+            header: new Int32Array(sab, 0, 4),
+            events: new Float64Array(sab, NOTIFICATION_RING_HEADER_BYTES),
+            capacity:
+              (sab.byteLength - NOTIFICATION_RING_HEADER_BYTES) /
+              (CONTROL_EVENT_SIZE * Float64Array.BYTES_PER_ELEMENT),
+          };
+          break;
+        }
+        case 'reportUnderrun': {
+          this.wasmInstance?.exports.quality_report_underrun();
+          break;
+        }
         default: {
           console.warn('Unhandled message type in FM Synth AWP: ', evt.data.type);
         }
@@ -365,6 +781,15 @@ class FMSynthAWP extends AudioWorkletProcessor {
     };
   }
 
+  /**
+   * Handles messages from the `logging` crate; `level` indexes into `LOG_LEVEL_CONSOLE_FNS`.
+   */
+  logFromWasm = (ptr, len, level) => {
+    const mem = new Uint8Array(this.wasmInstance.exports.memory.buffer);
+    const str = String.fromCharCode(...mem.subarray(ptr, ptr + len));
+    console[LOG_LEVEL_CONSOLE_FNS[level] ?? 'log'](str);
+  };
+
   handleWasmPanic = (ptr, len) => {
     const mem = new Uint8Array(this.getWasmMemoryBuffer().buffer);
     const slice = mem.subarray(ptr, ptr + len);
@@ -425,70 +850,62 @@ class FMSynthAWP extends AudioWorkletProcessor {
     const importObject = {
       env: {
         log_err: this.handleWasmPanic,
-        log_raw: (ptr, len, _level) => this.handleWasmPanic(ptr, len),
+        log_raw: this.logFromWasm,
         debug1: (v1, v2, v3) => console.log({ v1, v2, v3 }),
         on_gate_cb: (midiNumber, voiceIx) => {
           this.tacentVoiceFlags[voiceIx] = 0;
-          this.port.postMessage({ type: 'onGate', midiNumber, voiceIx });
+          if (!this.pushNotification(NOTIFICATION_TYPE_VOICE_STARTED, midiNumber, voiceIx)) {
+            this.port.postMessage({ type: 'onGate', midiNumber, voiceIx });
+          }
+        },
+        on_ungate_cb: (midiNumber, voiceIx) => {
+          if (!this.pushNotification(NOTIFICATION_TYPE_VOICE_ENDED, midiNumber, voiceIx)) {
+            this.port.postMessage({ type: 'onUngate', midiNumber, voiceIx });
+          }
         },
-        on_ungate_cb: (midiNumber, voiceIx) =>
-          this.port.postMessage({ type: 'onUngate', midiNumber, voiceIx }),
+        // `performance` isn't exposed to worklets in all browsers.  `Date.now` is much coarser,
+        // but the rolling average still converges on something useful.
+        now: typeof performance !== 'undefined' ? () => performance.now() : () => Date.now(),
       },
     };
     const compiledModule = await WebAssembly.compile(wasmBytes);
     this.wasmInstance = await WebAssembly.instantiate(compiledModule, importObject);
+    this.wasmInstance.exports.set_sample_rate(sampleRate);
     this.wasmInstance.exports.memory.grow(1024 * 4);
     this.ctxPtr = this.wasmInstance.exports.init_fm_synth_ctx(VOICE_COUNT);
     this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
     this.tacentVoiceFlags = new Uint8Array(VOICE_COUNT).fill(1);
+    this.wasmInstance.exports.fm_synth_set_profiling_enabled(this.ctxPtr, this.profilingEnabled);
+    this.profilingStatsPtr = this.wasmInstance.exports.fm_synth_get_profiling_stats_ptr(
+      this.ctxPtr
+    );
+    this.effectParamVizPtr = this.wasmInstance.exports.fm_synth_get_effect_param_viz_buf_ptr(
+      this.ctxPtr
+    );
+    this.controlEventBufPtr = this.wasmInstance.exports.fm_synth_get_control_event_buf_ptr(
+      this.ctxPtr
+    );
 
-    outputWeights.forEach((paramSource, operatorIx) =>
-      this.wasmInstance.exports.fm_synth_set_output_weight_value(
-        this.ctxPtr,
+    this.loadVoicePreset([
+      ...outputWeights.map((paramSource, operatorIx) => ({
+        type: 'setOutputWeightValue',
         operatorIx,
-        paramSource.valueType,
-        paramSource.valParamInt,
-        paramSource.valParamFloat,
-        paramSource.valParamFloat2,
-        paramSource.valParamFloat3
-      )
-    );
-    adsrs.forEach(({ steps, lenSamples, releasePoint, loopPoint, logScale, adsrIx }) => {
-      steps.forEach(({ x, y, ramper, param }, stepIx) => {
-        this.wasmInstance.exports.set_adsr_step_buffer(stepIx, x, y, ramper, param);
-      });
-      this.wasmInstance.exports.set_adsr(
-        this.ctxPtr,
-        adsrIx,
-        steps.length,
-        lenSamples.valueType,
-        lenSamples.valParamInt,
-        lenSamples.valParamFloat,
-        lenSamples.valParamFloat2,
-        lenSamples.valParamFloat3,
-        releasePoint,
-        loopPoint ?? -1.0,
-        logScale
-      );
-    });
-    modulationMatrix.forEach((indices, srcOperatorIx) =>
-      indices.forEach((paramSource, dstOperatorIx) =>
-        this.wasmInstance.exports.fm_synth_set_modulation_index(
-          this.ctxPtr,
+        ...paramSource,
+      })),
+      ...adsrs.map(adsr => ({ type: 'setAdsr', ...adsr })),
+      ...modulationMatrix.flatMap((indices, srcOperatorIx) =>
+        indices.map((paramSource, dstOperatorIx) => ({
+          type: 'setModulationIndex',
           srcOperatorIx,
           dstOperatorIx,
-          paramSource.valueType,
-          paramSource.valParamInt,
-          paramSource.valParamFloat,
-          paramSource.valParamFloat2,
-          paramSource.valParamFloat3
-        )
-      )
-    );
+          ...paramSource,
+        }))
+      ),
+    ]);
 
     if (typeof SharedArrayBuffer !== 'undefined') {
       this.audioThreadDataBufferInner = new SharedArrayBuffer(
-        ADSR_PHASE_BUF_LENGTH * BYTES_PER_F32
+        (ADSR_PHASE_BUF_LENGTH + PROFILING_STATS_LEN + EFFECT_PARAM_VIZ_LEN) * BYTES_PER_F32
       );
       this.audioThreadDataBuffer = new Float32Array(this.audioThreadDataBufferInner);
       this.adsrPhasesBufPtr = this.wasmInstance.exports.get_adsr_phases_buf_ptr(this.ctxPtr);
@@ -502,6 +919,23 @@ class FMSynthAWP extends AudioWorkletProcessor {
     });
   }
 
+  /**
+   * Accumulates the time spent rendering and periodically reports it to `dsp::quality` as a
+   * fraction of the real-time budget, which is how quality is restored after underruns.
+   */
+  trackRenderTime(renderTimeMs, frameSize) {
+    this.qualityRenderTimeMs += renderTimeMs;
+    this.qualityRenderedSampleCount += frameSize;
+    if (this.qualityRenderedSampleCount < QUALITY_LOAD_REPORT_INTERVAL_SAMPLES) {
+      return;
+    }
+
+    const budgetMs = (this.qualityRenderedSampleCount / sampleRate) * 1000;
+    this.wasmInstance.exports.quality_report_load(this.qualityRenderTimeMs / budgetMs);
+    this.qualityRenderTimeMs = 0;
+    this.qualityRenderedSampleCount = 0;
+  }
+
   getWasmMemoryBuffer() {
     if (this.wasmMemoryBuffer.buffer !== this.wasmInstance.exports.memory.buffer) {
       this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
@@ -509,46 +943,257 @@ class FMSynthAWP extends AudioWorkletProcessor {
     return this.wasmMemoryBuffer;
   }
 
+  /**
+   * Writes an event to the control event buffer in Wasm memory.  Returns `false` if the buffer is
+   * already full for this frame.
+   */
+  pushControlEvent(eventType, sampleOffset, param0 = 0, param1 = 0) {
+    if (this.controlEventCount >= MAX_CONTROL_EVENTS_PER_FRAME) {
+      return false;
+    }
+
+    const wasmMemory = this.getWasmMemoryBuffer();
+    const ix =
+      this.controlEventBufPtr / BYTES_PER_F32 + this.controlEventCount * CONTROL_EVENT_SIZE;
+    wasmMemory[ix] = eventType;
+    wasmMemory[ix + 1] = sampleOffset;
+    wasmMemory[ix + 2] = param0;
+    wasmMemory[ix + 3] = param1;
+    this.controlEventCount += 1;
+    return true;
+  }
+
   checkMailbox() {
     if (!this.mailboxID) {
       return;
     }
 
     let msg;
-    while ((msg = globalThis.midiEventMailboxRegistry.getEvent(this.mailboxID))) {
+    while (
+      this.controlEventCount < MAX_CONTROL_EVENTS_PER_FRAME &&
+      (msg = globalThis.midiEventMailboxRegistry.getEvent(this.mailboxID))
+    ) {
       const { eventType, param1 } = msg;
       switch (eventType) {
         case 0: // Attack
-          if (!this.wasmInstance) {
-            console.warn('Tried gating before Wasm instance loaded');
-            break;
-          }
-
-          this.wasmInstance.exports.gate(this.ctxPtr, param1);
+          this.pushControlEvent(CONTROL_EVENT_TYPE_GATE, 0, param1);
           break;
         case 1: // Release
-          if (!this.wasmInstance) {
-            console.warn('Tried un-gating before Wasm instance loaded');
-            break;
-          }
-
-          this.wasmInstance.exports.ungate(this.ctxPtr, param1);
+          this.pushControlEvent(CONTROL_EVENT_TYPE_UNGATE, 0, param1);
           break;
         case 2: // Pitch bend
-          console.error('Pitch bend not implemented');
+          this.pushControlEvent(CONTROL_EVENT_TYPE_PITCH_BEND, 0, param1);
           break;
         case 3: // Clear All
-          if (!this.wasmInstance) {
-            console.warn('Tried clearing all before Wasm instance loaded');
-            break;
-          }
-
-          this.wasmInstance.exports.ungate_all(this.ctxPtr);
+          this.pushControlEvent(CONTROL_EVENT_TYPE_UNGATE_ALL, 0);
           break;
         default:
-          console.error('Unhandled MIDI event type', evt);
+          console.error('Unhandled MIDI event type', msg);
+      }
+    }
+  }
+
+  /**
+   * Moves events from the control event ring that are due before the end of this frame into the
+   * control event buffer.  Events that don't fit are left in the ring until the next frame.
+   */
+  drainControlEventRing() {
+    const ring = this.controlEventRing;
+    if (!ring) {
+      return;
+    }
+
+    const writeIx = Atomics.load(ring.header, CONTROL_EVENT_RING_WRITE_IX);
+    let readIx = ring.header[CONTROL_EVENT_RING_READ_IX];
+    const frameEndTime = currentTime + FRAME_SIZE / sampleRate;
+    while (readIx !== writeIx && this.controlEventCount < MAX_CONTROL_EVENTS_PER_FRAME) {
+      const offset = readIx * CONTROL_EVENT_SIZE;
+      const time = ring.events[offset + 1];
+      if (time >= frameEndTime) {
+        break;
+      }
+
+      // Events scheduled in the past, including those with a time of 0, are applied immediately
+      const sampleOffset = Math.min(
+        Math.max(Math.floor((time - currentTime) * sampleRate), 0),
+        FRAME_SIZE - 1
+      );
+      this.pushControlEvent(
+        ring.events[offset],
+        sampleOffset,
+        ring.events[offset + 2],
+        ring.events[offset + 3]
+      );
+      readIx = (readIx + 1) % ring.capacity;
+    }
+    // Frees up the slots that were read for the writer
+    Atomics.store(ring.header, CONTROL_EVENT_RING_READ_IX, readIx);
+  }
+
+  /**
+   * Collects control events from the mailbox and the control event ring and applies them all with
+   * a single call into Wasm
+   */
+  applyControlEvents() {
+    this.controlEventCount = 0;
+    this.checkMailbox();
+    this.drainControlEventRing();
+    if (this.controlEventCount === 0) {
+      return;
+    }
+
+    const status = this.wasmInstance.exports.fm_synth_apply_control_events(
+      this.ctxPtr,
+      this.controlEventCount
+    );
+    if (status !== 0 && !this.pushNotification(NOTIFICATION_TYPE_ERROR, status)) {
+      console.error(`Invalid control event (code ${status})`);
+    }
+  }
+
+  /**
+   * Writes a notification to the notification ring for the main thread.  If the ring is full, the
+   * notification is dropped and counted so that the main thread knows it missed some.
+   *
+   * Returns `false` if there's no notification ring, in which case the caller should post a
+   * message instead.
+   */
+  pushNotification(notificationType, param0 = 0, param1 = 0) {
+    const ring = this.notificationRing;
+    if (!ring) {
+      return false;
+    }
+
+    const writeIx = ring.header[CONTROL_EVENT_RING_WRITE_IX];
+    const nextWriteIx = (writeIx + 1) % ring.capacity;
+    if (nextWriteIx === Atomics.load(ring.header, CONTROL_EVENT_RING_READ_IX)) {
+      Atomics.add(ring.header, NOTIFICATION_RING_DROPPED_COUNT_IX, 1);
+      return true;
+    }
+
+    const offset = writeIx * CONTROL_EVENT_SIZE;
+    ring.events[offset] = notificationType;
+    ring.events[offset + 1] = currentTime;
+    ring.events[offset + 2] = param0;
+    ring.events[offset + 3] = param1;
+    Atomics.store(ring.header, CONTROL_EVENT_RING_WRITE_IX, nextWriteIx);
+    return true;
+  }
+
+  /**
+   * Starts rendering `events` offline to `stemCount` separate buffers.  Rendering happens a chunk
+   * at a time in `process` so that the audio thread isn't blocked for the whole bounce.
+   */
+  startBounce({ events, stemCount = 1, bpm, endBeat, tailSeconds }) {
+    const exports = this.wasmInstance.exports;
+    const eventsBufPtr = exports.fm_synth_bounce_get_events_buf_ptr(this.ctxPtr, events.length);
+    const eventsBuf = new Float64Array(
+      exports.memory.buffer,
+      eventsBufPtr,
+      events.length * BOUNCE_EVENT_SIZE
+    );
+    events.forEach(({ beat, midiNumber, isAttack, stemIx = 0 }, eventIx) => {
+      eventsBuf[eventIx * BOUNCE_EVENT_SIZE] = beat;
+      eventsBuf[eventIx * BOUNCE_EVENT_SIZE + 1] = midiNumber;
+      eventsBuf[eventIx * BOUNCE_EVENT_SIZE + 2] = isAttack ? 1 : 0;
+      eventsBuf[eventIx * BOUNCE_EVENT_SIZE + 3] = stemIx;
+    });
+
+    this.bounce = { lastReportedProgress: 0, stemCount };
+    const status = exports.fm_synth_bounce_start(
+      this.ctxPtr,
+      events.length,
+      stemCount,
+      bpm,
+      endBeat,
+      tailSeconds
+    );
+    if (status !== 0) {
+      this.finishBounce();
+      this.port.postMessage({
+        type: 'bounceError',
+        message: `Invalid bounce parameters (code ${status})`,
+      });
+    }
+  }
+
+  /**
+   * Applies a list of voice setter messages to the synth in a single call.  See
+   * `engine/wavetable/src/fm/preset.rs`.
+   */
+  loadVoicePreset(records) {
+    const exports = this.wasmInstance.exports;
+    const encoded = records.flatMap(encodeVoicePresetRecord);
+    const bufPtr = exports.fm_synth_get_voice_preset_buf_ptr(encoded.length);
+    new Float32Array(exports.memory.buffer, bufPtr, encoded.length).set(encoded);
+
+    const status = exports.fm_synth_load_voice_preset(this.ctxPtr, encoded.length);
+    if (status !== 0) {
+      console.error(`Invalid voice preset (code ${status})`, records);
+    }
+  }
+
+  randomizeVoiceParams({ requestId, seed, constraints, amount }) {
+    const exports = this.wasmInstance.exports;
+    const paramCount = constraints.length / RANDOMIZE_CONSTRAINT_SIZE;
+    const constraintsBufPtr = exports.fm_synth_get_randomize_constraints_buf_ptr(paramCount);
+    new Float32Array(exports.memory.buffer, constraintsBufPtr, constraints.length).set(
+      constraints
+    );
+
+    const status = exports.fm_synth_randomize_voice_params(seed, paramCount, amount);
+    if (status !== 0) {
+      this.port.postMessage({
+        type: 'randomizeVoiceParamsError',
+        requestId,
+        message: `Invalid randomization constraints (code ${status})`,
+      });
+      return;
+    }
+
+    const valuesPtr = exports.fm_synth_get_randomized_params_ptr();
+    const values = new Float32Array(exports.memory.buffer, valuesPtr, paramCount).slice();
+    this.port.postMessage({ type: 'randomizedVoiceParams', requestId, values });
+  }
+
+  /**
+   * Renders the next chunk of the current bounce, sending the rendered samples for each stem to the
+   * main thread once it's complete.
+   */
+  renderBounceChunk() {
+    const exports = this.wasmInstance.exports;
+    const status = exports.fm_synth_bounce_render(this.ctxPtr, BOUNCE_FRAMES_PER_PROCESS);
+    if (status !== 0) {
+      this.finishBounce();
+      this.port.postMessage({
+        type: 'bounceError',
+        message: `Error rendering bounce (code ${status})`,
+      });
+      return;
+    }
+
+    const progress = exports.fm_synth_bounce_get_progress(this.ctxPtr);
+    if (progress < 1) {
+      if (progress - this.bounce.lastReportedProgress >= 0.01) {
+        this.bounce.lastReportedProgress = progress;
+        this.port.postMessage({ type: 'bounceProgress', progress });
       }
+      return;
+    }
+
+    const stems = [];
+    for (let stemIx = 0; stemIx < this.bounce.stemCount; stemIx++) {
+      const outputPtr = exports.fm_synth_bounce_get_output_ptr(this.ctxPtr, stemIx);
+      const outputLen = exports.fm_synth_bounce_get_output_len(this.ctxPtr, stemIx);
+      stems.push(new Float32Array(exports.memory.buffer, outputPtr, outputLen).slice());
     }
+    this.finishBounce();
+    this.port.postMessage({ type: 'bounceComplete', stems }, stems.map(samples => samples.buffer));
+  }
+
+  finishBounce() {
+    this.wasmInstance.exports.fm_synth_bounce_finish(this.ctxPtr);
+    this.bounce = null;
   }
 
   process(_inputs, outputs, params) {
@@ -560,11 +1205,16 @@ class FMSynthAWP extends AudioWorkletProcessor {
       return false;
     }
 
+    // Bounces render from their own copy of the synth, so the live frame is rendered as usual after
+    if (this.bounce) {
+      this.renderBounceChunk();
+    }
+
     if (globalThis.globalTempoBPM) {
       this.wasmInstance.exports.set_cur_bpm(globalThis.globalTempoBPM);
     }
 
-    this.checkMailbox();
+    this.applyControlEvents();
 
     let wasmMemory = this.getWasmMemoryBuffer();
     const paramBuffersPtr = this.wasmInstance.exports.get_param_buffers_ptr(this.ctxPtr);
@@ -584,6 +1234,7 @@ class FMSynthAWP extends AudioWorkletProcessor {
       }
     }
 
+    const renderStartMs = nowMs();
     const outputsPtr = this.wasmInstance.exports.fm_synth_generate(
       this.ctxPtr,
       globalThis.globalTempoBPM,
@@ -595,6 +1246,7 @@ class FMSynthAWP extends AudioWorkletProcessor {
       // globalThis.globalBeatCounterStarted ? globalThis.curBeat : 0
       0
     );
+    this.trackRenderTime(nowMs() - renderStartMs, FRAME_SIZE);
     wasmMemory = this.getWasmMemoryBuffer();
     for (let voiceIx = 0; voiceIx < VOICE_COUNT; voiceIx++) {
       const voiceIsTacent = this.tacentVoiceFlags[voiceIx];
@@ -603,11 +1255,15 @@ class FMSynthAWP extends AudioWorkletProcessor {
         continue;
       }
 
-      const outputSlice = wasmMemory.subarray(
-        (outputsPtr + voiceIx * OUTPUT_BYTES_PER_OPERATOR) / 4,
-        (outputsPtr + voiceIx * OUTPUT_BYTES_PER_OPERATOR + OUTPUT_BYTES_PER_OPERATOR) / 4
+      // Each voice has a left channel followed by a right channel
+      const leftPtr = outputsPtr + voiceIx * OUTPUT_BYTES_PER_VOICE;
+      const rightPtr = leftPtr + OUTPUT_BYTES_PER_OPERATOR;
+      outputs[voiceIx]?.[0]?.set(
+        wasmMemory.subarray(leftPtr / 4, (leftPtr + OUTPUT_BYTES_PER_OPERATOR) / 4)
+      );
+      outputs[voiceIx]?.[1]?.set(
+        wasmMemory.subarray(rightPtr / 4, (rightPtr + OUTPUT_BYTES_PER_OPERATOR) / 4)
       );
-      outputs[voiceIx]?.[0]?.set(outputSlice);
 
       const filterADSROutput = outputs[VOICE_COUNT + voiceIx]?.[0];
       if (filterADSROutput) {
@@ -630,6 +1286,20 @@ class FMSynthAWP extends AudioWorkletProcessor {
         this.adsrPhasesBufPtr / BYTES_PER_F32 + ADSR_PHASE_BUF_LENGTH
       );
       this.audioThreadDataBuffer.set(adsrPhaseBuf);
+
+      if (this.profilingEnabled) {
+        const profilingStatsIx = this.profilingStatsPtr / BYTES_PER_F32;
+        this.audioThreadDataBuffer.set(
+          wasmMemory.subarray(profilingStatsIx, profilingStatsIx + PROFILING_STATS_LEN),
+          ADSR_PHASE_BUF_LENGTH
+        );
+      }
+
+      const effectParamVizIx = this.effectParamVizPtr / BYTES_PER_F32;
+      this.audioThreadDataBuffer.set(
+        wasmMemory.subarray(effectParamVizIx, effectParamVizIx + EFFECT_PARAM_VIZ_LEN),
+        ADSR_PHASE_BUF_LENGTH + PROFILING_STATS_LEN
+      );
     }
 
     return true;
@@ -1,5 +1,18 @@
-const FRAME_SIZE = 128;
+import { checkWasmStatus } from './wasmStatus.js';
+
 const PARAM_COUNT = 4;
+/**
+ * Render load is reported to `dsp::quality` each time this many samples have been rendered, which
+ * is a bit under a second at 48kHz
+ */
+const QUALITY_LOAD_REPORT_INTERVAL_SAMPLES = 32768;
+// `performance` isn't exposed to worklets in all browsers
+const nowMs = typeof performance !== 'undefined' ? () => performance.now() : () => Date.now();
+
+/**
+ * Must match `LogLevel` in the `logging` crate
+ */
+const LOG_LEVEL_CONSOLE_FNS = ['error', 'warn', 'info', 'debug'];
 
 class FMSynthFxAWP extends AudioWorkletProcessor {
   static get parameterDescriptors() {
@@ -12,6 +25,15 @@ class FMSynthFxAWP extends AudioWorkletProcessor {
     ];
   }
 
+  /**
+   * Handles messages from the `logging` crate; `level` indexes into `LOG_LEVEL_CONSOLE_FNS`.
+   */
+  logFromWasm = (ptr, len, level) => {
+    const mem = new Uint8Array(this.wasmInstance.exports.memory.buffer);
+    const str = String.fromCharCode(...mem.subarray(ptr, ptr + len));
+    console[LOG_LEVEL_CONSOLE_FNS[level] ?? 'log'](str);
+  };
+
   handleWasmPanic = (ptr, len) => {
     const mem = new Uint8Array(this.getWasmMemoryBuffer().buffer);
     const slice = mem.subarray(ptr, ptr + len);
@@ -24,7 +46,7 @@ class FMSynthFxAWP extends AudioWorkletProcessor {
     this.wasmInstance = await WebAssembly.instantiate(compiledModule, {
       env: {
         log_err: this.handleWasmPanic,
-        log_raw: (ptr, len, _level) => this.handleWasmPanic(ptr, len),
+        log_raw: this.logFromWasm,
         debug1: (v1, v2, v3) => console.log({ v1, v2, v3 }),
         on_gate_cb: () => {
           throw new Error('Unused by FM synth fx');
@@ -32,8 +54,12 @@ class FMSynthFxAWP extends AudioWorkletProcessor {
         on_ungate_cb: () => {
           throw new Error('Unused by FM synth fx');
         },
+        now: () => {
+          throw new Error('Unused by FM synth fx');
+        },
       },
     });
+    this.wasmInstance.exports.set_sample_rate(sampleRate);
 
     this.ctxPtr = this.wasmInstance.exports.fm_synth_fx_create_ctx();
     this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
@@ -52,9 +78,13 @@ class FMSynthFxAWP extends AudioWorkletProcessor {
         this.isShutdown = true;
         break;
       }
+      case 'reportUnderrun': {
+        this.wasmInstance?.exports.quality_report_underrun();
+        break;
+      }
       case 'setEffect': {
-        const { encodedEffect, effectIx, isBypassed } = data;
-        this.wasmInstance.exports.fm_synth_fx_set_effect(
+        const { encodedEffect, effectIx, isBypassed, mix } = data;
+        const status = this.wasmInstance.exports.fm_synth_fx_set_effect(
           this.ctxPtr,
           effectIx,
           encodedEffect[0],
@@ -80,6 +110,40 @@ class FMSynthFxAWP extends AudioWorkletProcessor {
           encodedEffect[4]?.valParamFloat3 ?? 0,
           isBypassed
         );
+        checkWasmStatus(this, status);
+        if (encodedEffect[0] !== -1) {
+          checkWasmStatus(
+            this,
+            this.wasmInstance.exports.fm_synth_fx_set_effect_mix(this.ctxPtr, effectIx, mix ?? 1)
+          );
+        }
+        break;
+      }
+      case 'setEffectBypassed': {
+        const status = this.wasmInstance.exports.fm_synth_fx_set_effect_bypassed(
+          this.ctxPtr,
+          data.effectIx,
+          data.isBypassed
+        );
+        checkWasmStatus(this, status);
+        break;
+      }
+      case 'setEffectMix': {
+        const status = this.wasmInstance.exports.fm_synth_fx_set_effect_mix(
+          this.ctxPtr,
+          data.effectIx,
+          data.mix
+        );
+        checkWasmStatus(this, status);
+        break;
+      }
+      case 'moveEffect': {
+        const status = this.wasmInstance.exports.fm_synth_fx_move_effect(
+          this.ctxPtr,
+          data.fromEffectIx,
+          data.toEffectIx
+        );
+        checkWasmStatus(this, status);
         break;
       }
       default: {
@@ -88,11 +152,30 @@ class FMSynthFxAWP extends AudioWorkletProcessor {
     }
   }
 
+  /**
+   * Accumulates the time spent rendering and periodically reports it to `dsp::quality` as a
+   * fraction of the real-time budget, which is how quality is restored after underruns.
+   */
+  trackRenderTime(renderTimeMs, frameSize) {
+    this.qualityRenderTimeMs += renderTimeMs;
+    this.qualityRenderedSampleCount += frameSize;
+    if (this.qualityRenderedSampleCount < QUALITY_LOAD_REPORT_INTERVAL_SAMPLES) {
+      return;
+    }
+
+    const budgetMs = (this.qualityRenderedSampleCount / sampleRate) * 1000;
+    this.wasmInstance.exports.quality_report_load(this.qualityRenderTimeMs / budgetMs);
+    this.qualityRenderTimeMs = 0;
+    this.qualityRenderedSampleCount = 0;
+  }
+
   constructor() {
     super();
 
     this.isShutdown = false;
     this.ctxPtr = 0;
+    this.qualityRenderTimeMs = 0;
+    this.qualityRenderedSampleCount = 0;
     this.port.onmessage = evt => this.handleMessage(evt.data);
   }
 
@@ -113,26 +196,30 @@ class FMSynthFxAWP extends AudioWorkletProcessor {
       return true;
     }
 
+    const frameSize = input.length;
     const ioBufPtr = this.wasmInstance.exports.fm_synth_fx_get_io_buf_ptr(this.ctxPtr);
-    const ioBuf = new Float32Array(this.wasmInstance.exports.memory.buffer, ioBufPtr, FRAME_SIZE);
+    const ioBuf = new Float32Array(this.wasmInstance.exports.memory.buffer, ioBufPtr, frameSize);
     ioBuf.set(input);
 
     const paramsBufPtr = this.wasmInstance.exports.fm_synth_fx_get_params_buf_ptr(this.ctxPtr);
     const paramsBuf = new Float32Array(
       this.wasmInstance.exports.memory.buffer,
       paramsBufPtr,
-      PARAM_COUNT * FRAME_SIZE
+      PARAM_COUNT * frameSize
     );
     for (let paramIx = 0; paramIx < PARAM_COUNT; paramIx += 1) {
       const param = params[paramIx.toString()];
       if (param.length === 1) {
-        paramsBuf.fill(param[0], paramIx * FRAME_SIZE, (paramIx + 1) * FRAME_SIZE);
+        paramsBuf.fill(param[0], paramIx * frameSize, (paramIx + 1) * frameSize);
       } else {
-        paramsBuf.set(param, paramIx * FRAME_SIZE);
+        paramsBuf.set(param, paramIx * frameSize);
       }
     }
 
-    this.wasmInstance.exports.fm_synth_fx_process(this.ctxPtr);
+    const renderStartMs = nowMs();
+    const status = this.wasmInstance.exports.fm_synth_fx_process(this.ctxPtr, frameSize);
+    this.trackRenderTime(nowMs() - renderStartMs, frameSize);
+    checkWasmStatus(this, status);
 
     output.set(ioBuf);
 
@@ -1,3 +1,5 @@
+import { checkWasmStatus } from './wasmStatus.js';
+
 const FRAME_SIZE = 128;
 const BYTES_PER_F32 = 32 / 8;
 
@@ -69,7 +71,10 @@ class LevelDetectorWorkletProcessor extends AudioWorkletProcessor {
     ioBuf.set(input);
 
     const windowSizeSamples = params.window_size_samples[0];
-    this.wasmInstance.exports.level_detector_process(this.ctxPtr, windowSizeSamples);
+    checkWasmStatus(
+      this,
+      this.wasmInstance.exports.level_detector_process(this.ctxPtr, windowSizeSamples)
+    );
 
     output.set(ioBuf);
 
@@ -0,0 +1,786 @@
+import { checkWasmStatus, getLastWasmErrorMessage } from './wasmStatus.js';
+
+const BYTES_PER_F32 = 32 / 8;
+const BYTES_PER_U32 = 32 / 8;
+/**
+ * Must match `MAX_AUTOMATABLE_PARAMS` in the `module_api` crate
+ */
+const MAX_AUTOMATABLE_PARAMS = 16;
+/**
+ * Must match the `CAPABILITY_*_IX` constants in the `module_api` crate
+ */
+const CAPABILITY_API_VERSION_IX = 0;
+const CAPABILITY_MODULE_VERSION_IX = 1;
+const CAPABILITY_CHANNEL_COUNT_IX = 2;
+const CAPABILITY_MAX_FRAME_SIZE_IX = 3;
+const CAPABILITY_PARAM_COUNT_IX = 4;
+const CAPABILITY_AUTOMATABLE_PARAM_COUNT_IX = 5;
+const CAPABILITY_SAB_LEN_IX = 6;
+const CAPABILITY_OUTPUT_CHANNEL_COUNT_IX = 7;
+const CAPABILITY_OUTPUT_COUNT_IX = 8;
+const CAPABILITY_CONTROL_OUTPUT_COUNT_IX = 9;
+/**
+ * Must match `API_VERSION` in the `module_api` crate
+ */
+const SUPPORTED_API_VERSION = 1;
+/**
+ * Must match `ErrorCode::Unsupported` in the `common` crate; `module_create` returns a 0 handle
+ * when the registry is full.
+ */
+const ERROR_CODE_UNSUPPORTED = 4;
+/**
+ * Must match `dsp::output_guard::STATS_LEN`.  The output guard's stats are appended to the end of
+ * the module's own SAB entries.
+ */
+const OUTPUT_GUARD_STATS_LEN = 2;
+/**
+ * Must match `PROFILING_STATS_LEN` in the `module_api` crate.  Appended after the output guard's
+ * stats.
+ */
+const PROFILING_STATS_LEN = 2;
+/**
+ * Must match `CONTROL_BLOCK_SIZE` and `MAX_CONTROL_ROUTES` in `module_api::control_bus`
+ */
+const CONTROL_BLOCK_SIZE = 16;
+const MAX_CONTROL_ROUTES = 8;
+
+/**
+ * Control bus shared by all module hosts in the audio context, mapping channel names to their
+ * values for the current frame, one per control block.  See `module_api::control_bus`.
+ *
+ * Channels are written by publishing modules right after they process and read by subscribing
+ * modules right before they process, so a subscriber that's processed before its publisher in the
+ * audio graph sees values that are one frame late.  If several modules publish to the same channel,
+ * the last one processed wins.
+ *
+ * @type {Map<string, Float32Array>}
+ */
+const controlBus = (globalThis.moduleHostControlBus ??= new Map());
+
+/**
+ * @param {string} channel
+ * @param {number} maxControlBlocks
+ */
+const getControlBusChannel = (channel, maxControlBlocks) => {
+  let values = controlBus.get(channel);
+  if (!values || values.length < maxControlBlocks) {
+    values = new Float32Array(maxControlBlocks);
+    controlBus.set(channel, values);
+  }
+  return values;
+};
+
+/**
+ * Generic host for any Wasm module built with `module_api::export_module!`.  The module's layout
+ * is read from its capabilities after instantiation; the first `automatableParamCount` params are
+ * driven by the `param_n` `AudioParam`s and the rest are set via `setParams` messages.
+ */
+class ModuleHostAWP extends AudioWorkletProcessor {
+  static get parameterDescriptors() {
+    return Array.from({ length: MAX_AUTOMATABLE_PARAMS }, (_, paramIx) => ({
+      name: `param_${paramIx}`,
+      defaultValue: 0,
+      automationRate: 'k-rate',
+    }));
+  }
+
+  constructor() {
+    super();
+
+    this.isShutdown = false;
+    this.wasmInstance = null;
+    this.handle = 0;
+    this.capabilities = null;
+    this.moduleName = 'unknown';
+    this.ioBufPtr = 0;
+    this.paramBufPtr = 0;
+    this.sabPtr = 0;
+    this.outputGuardStatsPtr = 0;
+    this.outputGuardEnabled = false;
+    this.profilingStatsPtr = 0;
+    this.profilingEnabled = false;
+    this.sab = null;
+    this.sabView = null;
+    this.wasmMemoryBuffer = null;
+    this.bypass = false;
+    this.pendingParams = [];
+    this.latencySamples = 0;
+    this.controlOutputsPtr = 0;
+    this.controlInputsPtr = 0;
+    /**
+     * Bus channel that each control output is published to, by output index
+     * @type {(string | null)[]}
+     */
+    this.controlOutputChannels = [];
+    /**
+     * @type {({ channel: string, paramIx: number, depth: number } | null)[]}
+     */
+    this.controlInputRoutes = new Array(MAX_CONTROL_ROUTES).fill(null);
+    /**
+     * Macro and scene messages received before the module loaded, handled in order once it has
+     * @type {Record<string, any>[]}
+     */
+    this.pendingModuleMessages = [];
+
+    this.port.onmessage = async evt => {
+      switch (evt.data.type) {
+        case 'setWasmBytes': {
+          await this.initWasm(evt.data.wasmBytes);
+          break;
+        }
+        case 'shutdown': {
+          this.isShutdown = true;
+          if (this.handle) {
+            this.wasmInstance.exports.module_destroy(this.handle);
+            this.handle = 0;
+          }
+          break;
+        }
+        case 'setBypassed': {
+          this.bypass = evt.data.bypass;
+          if (this.bypass) {
+            this.reset();
+            // The input is passed straight through while bypassed
+            this.setLatencySamples(0);
+          }
+          break;
+        }
+        case 'setParams': {
+          this.setParams(evt.data.offset, evt.data.values);
+          break;
+        }
+        case 'scheduleParamChange': {
+          this.scheduleParamChange(evt.data.paramIx, evt.data.value, evt.data.time);
+          break;
+        }
+        case 'clearParamEvents': {
+          if (this.handle) {
+            checkWasmStatus(this, this.wasmInstance.exports.module_clear_param_events(this.handle));
+          }
+          break;
+        }
+        case 'reset': {
+          this.reset();
+          break;
+        }
+        case 'setOutputGuardEnabled': {
+          this.setOutputGuardEnabled(evt.data.enabled);
+          break;
+        }
+        case 'setProfilingEnabled': {
+          this.setProfilingEnabled(evt.data.enabled);
+          break;
+        }
+        case 'getState': {
+          this.postState();
+          break;
+        }
+        case 'setState': {
+          this.setState(evt.data.state);
+          break;
+        }
+        case 'abSelect': {
+          this.abSelect(evt.data.slot, evt.data.fadeMs);
+          break;
+        }
+        case 'setControlOutputBusChannel': {
+          this.setControlOutputBusChannel(evt.data.outputIx, evt.data.channel);
+          break;
+        }
+        case 'setControlInputRoute': {
+          this.setControlInputRoute(evt.data.routeIx, evt.data.route);
+          break;
+        }
+        case 'setMacroValue':
+        case 'setMacroTarget': {
+          this.handleMacroMessage(evt.data);
+          break;
+        }
+        case 'setScene':
+        case 'captureScene':
+        case 'recallScene': {
+          this.handleSceneMessage(evt.data);
+          break;
+        }
+        default:
+          console.error('Unknown message type in ModuleHostAWP', evt.data.type);
+      }
+    };
+  }
+
+  get wasmStatusLabel() {
+    return `ModuleHostAWP (${this.moduleName})`;
+  }
+
+  handleWasmPanic = (ptr, len) => {
+    const mem = new Uint8Array(this.wasmInstance.exports.memory.buffer);
+    const slice = mem.subarray(ptr, ptr + len);
+    const str = String.fromCharCode(...slice);
+    console.error(`ModuleHostAWP (${this.moduleName}) Wasm panic: ${str}`);
+  };
+
+  async initWasm(wasmBytes) {
+    const importObject = {
+      env: {
+        log_err: (ptr, len) => this.handleWasmPanic(ptr, len),
+        // `performance` isn't exposed to worklets in all browsers.  `Date.now` is much coarser,
+        // but the rolling average still converges on something useful.
+        now: typeof performance !== 'undefined' ? () => performance.now() : () => Date.now(),
+      },
+    };
+    const compiledModule = await WebAssembly.compile(wasmBytes);
+    this.wasmInstance = await WebAssembly.instantiate(compiledModule, importObject);
+    const exports = this.wasmInstance.exports;
+    exports.set_sample_rate(sampleRate);
+
+    const capabilitiesPtr = exports.module_get_capabilities_ptr();
+    this.capabilities = Array.from(
+      new Uint32Array(exports.memory.buffer).subarray(
+        capabilitiesPtr / BYTES_PER_U32,
+        capabilitiesPtr / BYTES_PER_U32 + exports.module_get_capabilities_len()
+      )
+    );
+    const outputLayoutsPtr = exports.module_get_output_layouts_ptr();
+    const outputLayouts = Array.from(
+      new Uint32Array(exports.memory.buffer).subarray(
+        outputLayoutsPtr / BYTES_PER_U32,
+        outputLayoutsPtr / BYTES_PER_U32 + this.capabilities[CAPABILITY_OUTPUT_COUNT_IX]
+      )
+    );
+    const namePtr = exports.module_get_name_ptr();
+    this.moduleName = String.fromCharCode(
+      ...new Uint8Array(exports.memory.buffer).subarray(
+        namePtr,
+        namePtr + exports.module_get_name_len()
+      )
+    );
+
+    const apiVersion = this.capabilities[CAPABILITY_API_VERSION_IX];
+    if (apiVersion !== SUPPORTED_API_VERSION) {
+      console.error(
+        `ModuleHostAWP: module ${this.moduleName} has unsupported API version ${apiVersion}`
+      );
+      return;
+    }
+
+    const handle = exports.module_create();
+    if (!handle) {
+      checkWasmStatus(this, ERROR_CODE_UNSUPPORTED);
+      return;
+    }
+    this.ioBufPtr = exports.module_get_io_buf_ptr(handle);
+    this.paramBufPtr = exports.module_get_param_buf_ptr(handle);
+    this.sabPtr = exports.module_get_sab_ptr(handle);
+    this.outputGuardStatsPtr = exports.module_get_output_guard_stats_ptr(handle);
+    this.profilingStatsPtr = exports.module_get_profiling_stats_ptr(handle);
+    this.controlOutputsPtr = exports.module_get_control_outputs_ptr(handle);
+    this.controlInputsPtr = exports.module_get_control_inputs_ptr(handle);
+    this.wasmMemoryBuffer = new Float32Array(exports.memory.buffer);
+    this.handle = handle;
+
+    const sabLen = this.capabilities[CAPABILITY_SAB_LEN_IX];
+    if (typeof SharedArrayBuffer !== 'undefined') {
+      this.sab = new SharedArrayBuffer(
+        (sabLen + OUTPUT_GUARD_STATS_LEN + PROFILING_STATS_LEN) * BYTES_PER_F32
+      );
+      this.sabView = new Float32Array(this.sab);
+    }
+    this.setOutputGuardEnabled(this.outputGuardEnabled);
+    this.setProfilingEnabled(this.profilingEnabled);
+    this.syncControlOutputsEnabled();
+    this.controlInputRoutes.forEach((route, routeIx) => this.setControlInputRoute(routeIx, route));
+
+    this.port.postMessage({
+      type: 'capabilities',
+      name: this.moduleName,
+      moduleVersion: this.capabilities[CAPABILITY_MODULE_VERSION_IX],
+      channelCount: this.capabilities[CAPABILITY_CHANNEL_COUNT_IX],
+      outputChannelCount: this.capabilities[CAPABILITY_OUTPUT_CHANNEL_COUNT_IX],
+      outputLayouts,
+      paramCount: this.capabilities[CAPABILITY_PARAM_COUNT_IX],
+      automatableParamCount: this.capabilities[CAPABILITY_AUTOMATABLE_PARAM_COUNT_IX],
+      sabLen,
+      outputGuardStatsOffset: sabLen,
+      profilingStatsOffset: sabLen + OUTPUT_GUARD_STATS_LEN,
+      controlOutputCount: this.getControlOutputCount(),
+    });
+    if (this.sab) {
+      this.port.postMessage({ type: 'sab', sab: this.sab });
+    }
+
+    const pendingParams = this.pendingParams;
+    this.pendingParams = [];
+    pendingParams.forEach(({ offset, values }) => this.setParams(offset, values));
+    const pendingModuleMessages = this.pendingModuleMessages;
+    this.pendingModuleMessages = [];
+    pendingModuleMessages.forEach(msg =>
+      msg.type.startsWith('setMacro') ? this.handleMacroMessage(msg) : this.handleSceneMessage(msg)
+    );
+  }
+
+  /**
+   * Writes `values` into the module's param buffer starting at `offset`.  Values past the end of
+   * the buffer are dropped.
+   *
+   * @param {number} offset
+   * @param {number[]} values
+   */
+  setParams(offset, values) {
+    if (!this.handle) {
+      this.pendingParams.push({ offset, values });
+      return;
+    }
+
+    const paramCount = this.capabilities[CAPABILITY_PARAM_COUNT_IX];
+    if (offset < 0 || offset >= paramCount) {
+      console.error(
+        `ModuleHostAWP (${this.moduleName}): param offset ${offset} is past ${paramCount} params`
+      );
+      return;
+    }
+
+    const paramBufIx = this.paramBufPtr / BYTES_PER_F32;
+    this.getWasmMemoryBuffer().set(values.slice(0, paramCount - offset), paramBufIx + offset);
+  }
+
+  /**
+   * Sets param `paramIx` to `value` on the sample at `time`, in the same timebase as
+   * `AudioContext.currentTime`.  Times in the past are applied at the start of the next frame.
+   * Changes scheduled before the module has loaded are dropped.
+   *
+   * @param {number} paramIx
+   * @param {number} value
+   * @param {number} time
+   */
+  scheduleParamChange(paramIx, value, time) {
+    if (!this.handle) {
+      return;
+    }
+
+    const frameOffset = Math.max(Math.round((time - currentTime) * sampleRate), 0);
+    checkWasmStatus(
+      this,
+      this.wasmInstance.exports.module_schedule_param_change(
+        this.handle,
+        paramIx,
+        value,
+        frameOffset
+      )
+    );
+  }
+
+  /**
+   * The guard hard clips, scrubs non-finite samples, and removes DC from the module's outputs.  See
+   * `dsp::output_guard`.
+   *
+   * @param {boolean} enabled
+   */
+  setOutputGuardEnabled(enabled) {
+    this.outputGuardEnabled = enabled;
+    if (this.handle) {
+      checkWasmStatus(
+        this,
+        this.wasmInstance.exports.module_set_output_guard_enabled(this.handle, enabled)
+      );
+    }
+  }
+
+  /**
+   * Tracks the time spent processing each frame.  See `dsp::profiling`.
+   *
+   * @param {boolean} enabled
+   */
+  setProfilingEnabled(enabled) {
+    this.profilingEnabled = enabled;
+    if (this.handle) {
+      checkWasmStatus(
+        this,
+        this.wasmInstance.exports.module_set_profiling_enabled(this.handle, enabled)
+      );
+    }
+  }
+
+  getControlOutputCount() {
+    // Modules built before control outputs were added have a shorter capabilities buffer
+    return this.capabilities?.[CAPABILITY_CONTROL_OUTPUT_COUNT_IX] ?? 0;
+  }
+
+  getMaxControlBlocks() {
+    return this.capabilities[CAPABILITY_MAX_FRAME_SIZE_IX] / CONTROL_BLOCK_SIZE;
+  }
+
+  /**
+   * Publishes control output `outputIx` to the bus channel `channel`, or stops publishing it if
+   * `channel` is null.  The module only samples its control outputs while at least one is
+   * published.
+   *
+   * @param {number} outputIx
+   * @param {string | null} channel
+   */
+  setControlOutputBusChannel(outputIx, channel) {
+    this.controlOutputChannels[outputIx] = channel;
+    this.syncControlOutputsEnabled();
+  }
+
+  syncControlOutputsEnabled() {
+    if (!this.handle) {
+      return;
+    }
+
+    const controlOutputCount = this.getControlOutputCount();
+    const enabled = this.controlOutputChannels
+      .slice(0, controlOutputCount)
+      .some(channel => channel !== null && channel !== undefined);
+    checkWasmStatus(
+      this,
+      this.wasmInstance.exports.module_set_control_outputs_enabled(this.handle, enabled)
+    );
+  }
+
+  /**
+   * Modulates param `route.paramIx` by the bus channel `route.channel` scaled by `route.depth`, or
+   * removes the route in slot `routeIx` if `route` is null.  Routes set before the module has
+   * loaded are applied once it has.
+   *
+   * @param {number} routeIx
+   * @param {{ channel: string, paramIx: number, depth: number } | null} route
+   */
+  setControlInputRoute(routeIx, route) {
+    if (routeIx < 0 || routeIx >= MAX_CONTROL_ROUTES) {
+      console.error(`ModuleHostAWP (${this.moduleName}): invalid control route index ${routeIx}`);
+      return;
+    }
+    this.controlInputRoutes[routeIx] = route;
+    if (!this.handle) {
+      return;
+    }
+
+    const exports = this.wasmInstance.exports;
+    if (route) {
+      checkWasmStatus(
+        this,
+        exports.module_set_control_route(this.handle, routeIx, route.paramIx, route.depth)
+      );
+    } else {
+      checkWasmStatus(this, exports.module_clear_control_route(this.handle, routeIx));
+    }
+  }
+
+  /**
+   * Copies the bus channels that the module subscribes to into its control inputs.  Channels that
+   * nothing has published to yet read as zero.
+   *
+   * @param {Float32Array} wasmMemory
+   * @param {number} frameSize
+   */
+  readControlInputs(wasmMemory, frameSize) {
+    const maxControlBlocks = this.getMaxControlBlocks();
+    const blockCount = Math.ceil(frameSize / CONTROL_BLOCK_SIZE);
+    const inputsIx = this.controlInputsPtr / BYTES_PER_F32;
+    this.controlInputRoutes.forEach((route, routeIx) => {
+      if (!route) {
+        return;
+      }
+
+      const start = inputsIx + routeIx * maxControlBlocks;
+      const values = controlBus.get(route.channel);
+      if (values) {
+        wasmMemory.set(values.subarray(0, blockCount), start);
+      } else {
+        wasmMemory.fill(0, start, start + blockCount);
+      }
+    });
+  }
+
+  /**
+   * Copies the module's published control outputs into their bus channels
+   *
+   * @param {Float32Array} wasmMemory
+   * @param {number} frameSize
+   */
+  writeControlOutputs(wasmMemory, frameSize) {
+    const maxControlBlocks = this.getMaxControlBlocks();
+    const blockCount = Math.ceil(frameSize / CONTROL_BLOCK_SIZE);
+    const outputsIx = this.controlOutputsPtr / BYTES_PER_F32;
+    const controlOutputCount = this.getControlOutputCount();
+    for (let outputIx = 0; outputIx < controlOutputCount; outputIx++) {
+      const channel = this.controlOutputChannels[outputIx];
+      if (channel === null || channel === undefined) {
+        continue;
+      }
+
+      const start = outputsIx + outputIx * maxControlBlocks;
+      getControlBusChannel(channel, maxControlBlocks).set(
+        wasmMemory.subarray(start, start + blockCount)
+      );
+    }
+  }
+
+  /**
+   * Sets a macro's value or one of its targets, or clears the target if `msg.target` is null.  See
+   * `module_api::macros`.
+   *
+   * @param {Record<string, any>} msg a `setMacroValue` or `setMacroTarget` message
+   */
+  handleMacroMessage(msg) {
+    if (!this.handle) {
+      this.pendingModuleMessages.push(msg);
+      return;
+    }
+
+    const exports = this.wasmInstance.exports;
+    if (msg.type === 'setMacroValue') {
+      checkWasmStatus(this, exports.module_set_macro_value(this.handle, msg.macroIx, msg.value));
+    } else if (msg.target) {
+      const { paramIx, min, max, curve } = msg.target;
+      checkWasmStatus(
+        this,
+        exports.module_set_macro_target(
+          this.handle,
+          msg.macroIx,
+          msg.targetIx,
+          paramIx,
+          min,
+          max,
+          curve
+        )
+      );
+    } else {
+      checkWasmStatus(
+        this,
+        exports.module_clear_macro_target(this.handle, msg.macroIx, msg.targetIx)
+      );
+    }
+  }
+
+  /**
+   * Replaces a scene's params, captures the current values of some params into a scene, or
+   * recalls a scene.  Captured values are sent back in a `sceneCaptured` message.  See
+   * `module_api::scenes`.
+   *
+   * @param {Record<string, any>} msg a `setScene`, `captureScene`, or `recallScene` message
+   */
+  handleSceneMessage(msg) {
+    if (!this.handle) {
+      this.pendingModuleMessages.push(msg);
+      return;
+    }
+
+    const exports = this.wasmInstance.exports;
+    switch (msg.type) {
+      case 'setScene': {
+        checkWasmStatus(this, exports.module_clear_scene(this.handle, msg.sceneIx));
+        for (const [paramIx, value] of msg.values ?? []) {
+          checkWasmStatus(
+            this,
+            exports.module_set_scene_param(this.handle, msg.sceneIx, paramIx, value)
+          );
+        }
+        break;
+      }
+      case 'captureScene': {
+        const paramCount = this.capabilities[CAPABILITY_PARAM_COUNT_IX];
+        const paramBufIx = this.paramBufPtr / BYTES_PER_F32;
+        const wasmMemory = this.getWasmMemoryBuffer();
+        const values = msg.paramIxs
+          .filter(paramIx => paramIx >= 0 && paramIx < paramCount)
+          .map(paramIx => [paramIx, wasmMemory[paramBufIx + paramIx]]);
+        this.handleSceneMessage({ type: 'setScene', sceneIx: msg.sceneIx, values });
+        this.port.postMessage({ type: 'sceneCaptured', sceneIx: msg.sceneIx, values });
+        break;
+      }
+      case 'recallScene': {
+        checkWasmStatus(this, exports.module_recall_scene(this.handle, msg.sceneIx, msg.glideMs));
+        break;
+      }
+    }
+  }
+
+  /**
+   * Notifies the main thread when the module's latency changes so that parallel paths in the patch
+   * network can be delayed to match.  See `DspModule::latency_samples`.
+   *
+   * @param {number} latencySamples
+   */
+  setLatencySamples(latencySamples) {
+    if (latencySamples === this.latencySamples) {
+      return;
+    }
+    this.latencySamples = latencySamples;
+    this.port.postMessage({ type: 'latency', latencySamples });
+  }
+
+  reset() {
+    if (this.handle) {
+      checkWasmStatus(this, this.wasmInstance.exports.module_reset(this.handle));
+    }
+  }
+
+  getWasmMemoryBuffer() {
+    if (this.wasmMemoryBuffer.buffer !== this.wasmInstance.exports.memory.buffer) {
+      this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
+    }
+    return this.wasmMemoryBuffer;
+  }
+
+  /**
+   * Replies with the module's current params.  See `common::state` in the engine.
+   *
+   * @param {string | undefined} error
+   */
+  postState(error) {
+    if (!this.handle) {
+      this.port.postMessage({ type: 'state', state: null, error: 'Wasm not loaded' });
+      return;
+    }
+
+    const len = this.wasmInstance.exports.module_get_state_json(this.handle);
+    this.port.postMessage({ type: 'state', state: this.readStateJson(len), error });
+  }
+
+  /**
+   * @param {number} len length of the JSON written to the state JSON buffer
+   */
+  readStateJson(len) {
+    const exports = this.wasmInstance.exports;
+    const ptr = exports.get_state_json_ptr();
+    return JSON.parse(String.fromCharCode(...new Uint8Array(exports.memory.buffer, ptr, len)));
+  }
+
+  /**
+   * Restores params from a state object produced by `postState`.  Only ASCII param names are
+   * supported.
+   *
+   * @param {Record<string, number | boolean>} state
+   */
+  setState(state) {
+    if (!this.handle) {
+      this.postState();
+      return;
+    }
+
+    const exports = this.wasmInstance.exports;
+    const json = JSON.stringify(state);
+    const ptr = exports.alloc_state_json_buf(json.length);
+    const buf = new Uint8Array(exports.memory.buffer, ptr, json.length);
+    for (let i = 0; i < json.length; i++) {
+      buf[i] = json.charCodeAt(i);
+    }
+    const status = exports.module_set_state_json(this.handle, json.length);
+    this.postState(status === 0 ? undefined : getLastWasmErrorMessage(this.wasmInstance));
+  }
+
+  /**
+   * Saves the current params into the active A/B slot, crossfades to the params in `slot`, and
+   * replies with them.  See `common::state::ABCompare` in the engine.
+   *
+   * @param {number} slot
+   * @param {number} fadeMs
+   */
+  abSelect(slot, fadeMs) {
+    if (!this.handle) {
+      this.postState();
+      return;
+    }
+
+    const exports = this.wasmInstance.exports;
+    const status = exports.module_ab_select(this.handle, slot, fadeMs);
+    if (status !== 0) {
+      this.port.postMessage({
+        type: 'state',
+        state: null,
+        error: getLastWasmErrorMessage(this.wasmInstance),
+      });
+      return;
+    }
+    const state = this.readStateJson(exports.module_ab_get_slot_state_json(this.handle, slot));
+    this.port.postMessage({ type: 'state', state });
+  }
+
+  /**
+   * @param {Float32Array[][]} inputs
+   * @param {Float32Array[][]} outputs
+   * @param {{[key: string]: Float32Array}} params
+   * @returns {boolean}
+   */
+  process(inputs, outputs, params) {
+    if (this.isShutdown) {
+      return false;
+    }
+
+    const input = inputs[0];
+    const output = outputs[0];
+    if (!output?.[0]) {
+      return true;
+    }
+
+    if (!this.handle || this.bypass) {
+      output.forEach((outputChannel, channelIx) => {
+        const inputChannel = input?.[channelIx] ?? input?.[0];
+        if (inputChannel) {
+          outputChannel.set(inputChannel);
+        }
+      });
+      return true;
+    }
+
+    const frameSize = output[0].length;
+    const wasmMemory = this.getWasmMemoryBuffer();
+
+    const paramBufIx = this.paramBufPtr / BYTES_PER_F32;
+    const automatableParamCount = this.capabilities[CAPABILITY_AUTOMATABLE_PARAM_COUNT_IX];
+    for (let paramIx = 0; paramIx < automatableParamCount; paramIx++) {
+      wasmMemory[paramBufIx + paramIx] = params[`param_${paramIx}`][0];
+    }
+
+    // Missing input channels are filled from the first one (or silence).  Outputs are read back
+    // from the first `outputChannelCount` channels, which are spread across the node's outputs in
+    // order.  Module channels past the outputs' total channel count are dropped.
+    const channelCount = this.capabilities[CAPABILITY_CHANNEL_COUNT_IX];
+    const maxFrameSize = this.capabilities[CAPABILITY_MAX_FRAME_SIZE_IX];
+    const ioBufIx = this.ioBufPtr / BYTES_PER_F32;
+    const channelBufs = [];
+    for (let channelIx = 0; channelIx < channelCount; channelIx++) {
+      const start = ioBufIx + channelIx * maxFrameSize;
+      const channelBuf = wasmMemory.subarray(start, start + frameSize);
+      const inputChannel = input?.[channelIx] ?? input?.[0];
+      if (inputChannel) {
+        channelBuf.set(inputChannel);
+      } else {
+        channelBuf.fill(0);
+      }
+      channelBufs.push(channelBuf);
+    }
+
+    this.readControlInputs(wasmMemory, frameSize);
+    checkWasmStatus(this, this.wasmInstance.exports.module_process(this.handle, frameSize));
+    this.writeControlOutputs(wasmMemory, frameSize);
+    this.setLatencySamples(this.wasmInstance.exports.module_get_latency_samples(this.handle));
+
+    const outputChannelCount = this.capabilities[CAPABILITY_OUTPUT_CHANNEL_COUNT_IX];
+    let channelIx = 0;
+    for (const nodeOutput of outputs) {
+      for (const outputChannel of nodeOutput) {
+        outputChannel.set(channelBufs[channelIx < outputChannelCount ? channelIx : 0]);
+        channelIx += 1;
+      }
+    }
+
+    if (this.sabView) {
+      const sabLen = this.capabilities[CAPABILITY_SAB_LEN_IX];
+      const sabIx = this.sabPtr / BYTES_PER_F32;
+      this.sabView.set(wasmMemory.subarray(sabIx, sabIx + sabLen));
+      const statsIx = this.outputGuardStatsPtr / BYTES_PER_F32;
+      this.sabView.set(wasmMemory.subarray(statsIx, statsIx + OUTPUT_GUARD_STATS_LEN), sabLen);
+      const profilingStatsIx = this.profilingStatsPtr / BYTES_PER_F32;
+      this.sabView.set(
+        wasmMemory.subarray(profilingStatsIx, profilingStatsIx + PROFILING_STATS_LEN),
+        sabLen + OUTPUT_GUARD_STATS_LEN
+      );
+    }
+
+    return true;
+  }
+}
+
+registerProcessor('module-host-awp', ModuleHostAWP);
@@ -0,0 +1,277 @@
+import { checkWasmStatus, getLastWasmErrorMessage } from './wasmStatus.js';
+
+const BYTES_PER_F32 = 32 / 8;
+const SAB_SIZE = 3 * BYTES_PER_F32;
+/**
+ * Must match `dsp::MAX_FRAME_SIZE`; the right channel starts at this offset in the IO buffer.
+ */
+const MAX_FRAME_SIZE = 1024;
+
+/**
+ * Freeverb-style stereo reverb.  Levels are written to the SAB after each frame for metering.
+ */
+class ReverbAWP extends AudioWorkletProcessor {
+  static get parameterDescriptors() {
+    return [
+      {
+        name: 'room_size',
+        defaultValue: 0.5,
+        automationRate: 'k-rate',
+        minValue: 0,
+        maxValue: 1,
+      },
+      {
+        name: 'damping',
+        defaultValue: 0.5,
+        automationRate: 'k-rate',
+        minValue: 0,
+        maxValue: 1,
+      },
+      {
+        name: 'width',
+        defaultValue: 1,
+        automationRate: 'k-rate',
+        minValue: 0,
+        maxValue: 1,
+      },
+      {
+        name: 'mix',
+        defaultValue: 0.3,
+        automationRate: 'k-rate',
+        minValue: 0,
+        maxValue: 1,
+      },
+      {
+        name: 'pre_delay_ms',
+        defaultValue: 0,
+        automationRate: 'k-rate',
+        minValue: 0,
+        maxValue: 500,
+      },
+    ];
+  }
+
+  constructor() {
+    super({ numberOfInputs: 1, numberOfOutputs: 1, outputChannelCount: [2] });
+
+    this.isShutdown = false;
+    this.sab = typeof SharedArrayBuffer !== 'undefined' ? new SharedArrayBuffer(SAB_SIZE) : null;
+    if (this.sab) {
+      this.port.postMessage({ type: 'sab', sab: this.sab });
+    }
+    this.sabView = this.sab ? new Float32Array(this.sab) : null;
+    this.sabPtr = 0;
+    this.wasmInstance = null;
+    this.ctxPtr = 0;
+    this.ioBufPtr = 0;
+    this.wasmMemoryBuffer = null;
+    this.bypass = false;
+
+    this.port.onmessage = async evt => {
+      switch (evt.data.type) {
+        case 'setWasmBytes': {
+          await this.initWasm(evt.data.wasmBytes);
+          break;
+        }
+        case 'shutdown': {
+          this.isShutdown = true;
+          break;
+        }
+        case 'setBypassed': {
+          this.bypass = evt.data.bypass;
+          // Don't let a stale tail play out when the reverb is re-enabled
+          if (this.bypass && this.ctxPtr) {
+            checkWasmStatus(this, this.wasmInstance.exports.reverb_reset(this.ctxPtr));
+          }
+          break;
+        }
+        case 'getState': {
+          this.postState();
+          break;
+        }
+        case 'setState': {
+          this.setState(evt.data.state);
+          break;
+        }
+        case 'abSelect': {
+          this.abSelect(evt.data.slot, evt.data.fadeMs);
+          break;
+        }
+        default:
+          console.error('Unknown message type in ReverbAWP', evt.data.type);
+      }
+    };
+  }
+
+  handleWasmPanic = (ptr, len) => {
+    const mem = new Uint8Array(this.wasmInstance.exports.memory.buffer);
+    const slice = mem.subarray(ptr, ptr + len);
+    const str = String.fromCharCode(...slice);
+    console.error(`ReverbAWP Wasm panic: ${str}`);
+  };
+
+  async initWasm(wasmBytes) {
+    const importObject = { env: { log_err: (ptr, len) => this.handleWasmPanic(ptr, len) } };
+    const compiledModule = await WebAssembly.compile(wasmBytes);
+    this.wasmInstance = await WebAssembly.instantiate(compiledModule, importObject);
+    this.wasmInstance.exports.set_sample_rate(sampleRate);
+    this.ctxPtr = this.wasmInstance.exports.reverb_create_ctx();
+    this.ioBufPtr = this.wasmInstance.exports.reverb_get_io_buf_ptr(this.ctxPtr);
+    this.sabPtr = this.wasmInstance.exports.reverb_get_sab_ptr(this.ctxPtr);
+    this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
+  }
+
+  getWasmMemoryBuffer() {
+    if (this.wasmMemoryBuffer.buffer !== this.wasmInstance.exports.memory.buffer) {
+      this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
+    }
+    return this.wasmMemoryBuffer;
+  }
+
+  /**
+   * Replies with the reverb's current params.  See `common::state` in the engine.
+   *
+   * @param {string | undefined} error
+   */
+  postState(error) {
+    if (!this.ctxPtr) {
+      this.port.postMessage({ type: 'state', state: null, error: 'Wasm not loaded' });
+      return;
+    }
+
+    const len = this.wasmInstance.exports.reverb_get_state_json(this.ctxPtr);
+    this.port.postMessage({ type: 'state', state: this.readStateJson(len), error });
+  }
+
+  /**
+   * @param {number} len length of the JSON written to the state JSON buffer
+   */
+  readStateJson(len) {
+    const exports = this.wasmInstance.exports;
+    const ptr = exports.get_state_json_ptr();
+    return JSON.parse(String.fromCharCode(...new Uint8Array(exports.memory.buffer, ptr, len)));
+  }
+
+  /**
+   * Restores params from a state object produced by `postState`.  Only ASCII param names are
+   * supported.
+   *
+   * @param {Record<string, number | boolean>} state
+   */
+  setState(state) {
+    if (!this.ctxPtr) {
+      this.postState();
+      return;
+    }
+
+    const exports = this.wasmInstance.exports;
+    const json = JSON.stringify(state);
+    const ptr = exports.alloc_state_json_buf(json.length);
+    const buf = new Uint8Array(exports.memory.buffer, ptr, json.length);
+    for (let i = 0; i < json.length; i++) {
+      buf[i] = json.charCodeAt(i);
+    }
+    const status = exports.reverb_set_state_json(this.ctxPtr, json.length);
+    this.postState(status === 0 ? undefined : getLastWasmErrorMessage(this.wasmInstance));
+  }
+
+  /**
+   * Saves the current params into the active A/B slot, crossfades to the params in `slot`, and
+   * replies with them.  See `common::state::ABCompare` in the engine.
+   *
+   * @param {number} slot
+   * @param {number} fadeMs
+   */
+  abSelect(slot, fadeMs) {
+    if (!this.ctxPtr) {
+      this.postState();
+      return;
+    }
+
+    const exports = this.wasmInstance.exports;
+    const status = exports.reverb_ab_select(this.ctxPtr, slot, fadeMs);
+    if (status !== 0) {
+      this.port.postMessage({
+        type: 'state',
+        state: null,
+        error: getLastWasmErrorMessage(this.wasmInstance),
+      });
+      return;
+    }
+    const state = this.readStateJson(exports.reverb_ab_get_slot_state_json(this.ctxPtr, slot));
+    this.port.postMessage({ type: 'state', state });
+  }
+
+  /**
+   * @param {Float32Array[][]} inputs
+   * @param {Float32Array[][]} outputs
+   * @param {{[key: string]: Float32Array}} params
+   * @returns {boolean}
+   */
+  process(inputs, outputs, params) {
+    if (this.isShutdown) {
+      return false;
+    }
+
+    const input = inputs[0];
+    const output = outputs[0];
+    if (!output?.[0]) {
+      return true;
+    }
+
+    const left = input?.[0];
+    // Mono input is upmixed to both channels
+    const right = input?.[1] ?? left;
+    if (!this.ctxPtr || this.bypass) {
+      if (left) {
+        output[0].set(left);
+        output[1]?.set(right);
+      }
+      return true;
+    }
+
+    const frameSize = output[0].length;
+    const wasmMemory = this.getWasmMemoryBuffer();
+    const ioBufIx = this.ioBufPtr / BYTES_PER_F32;
+    const leftBuf = wasmMemory.subarray(ioBufIx, ioBufIx + frameSize);
+    const rightBuf = wasmMemory.subarray(
+      ioBufIx + MAX_FRAME_SIZE,
+      ioBufIx + MAX_FRAME_SIZE + frameSize
+    );
+    // The tail keeps ringing out after the input is disconnected
+    if (left) {
+      leftBuf.set(left);
+      rightBuf.set(right);
+    } else {
+      leftBuf.fill(0);
+      rightBuf.fill(0);
+    }
+
+    const status = this.wasmInstance.exports.reverb_process(
+      this.ctxPtr,
+      frameSize,
+      params.room_size[0],
+      params.damping[0],
+      params.width[0],
+      params.mix[0],
+      params.pre_delay_ms[0]
+    );
+    checkWasmStatus(this, status);
+
+    output[0].set(leftBuf);
+    output[1]?.set(rightBuf);
+
+    if (this.sab) {
+      this.sabView.set(
+        wasmMemory.subarray(
+          this.sabPtr / BYTES_PER_F32,
+          this.sabPtr / BYTES_PER_F32 + SAB_SIZE / BYTES_PER_F32
+        )
+      );
+    }
+
+    return true;
+  }
+}
+
+registerProcessor('reverb-awp', ReverbAWP);
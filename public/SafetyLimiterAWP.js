@@ -0,0 +1,118 @@
+import { checkWasmStatus } from './wasmStatus.js';
+
+const FRAME_SIZE = 128;
+const BYTES_PER_F32 = 32 / 8;
+const SAB_SIZE = 4 * BYTES_PER_F32;
+
+/**
+ * Final output stage that sits between the global volume and the destination.  Removes DC offset
+ * and hard-limits the output to 0 dBFS.
+ */
+class SafetyLimiterAWP extends AudioWorkletProcessor {
+  constructor() {
+    super();
+
+    this.sab = typeof SharedArrayBuffer !== 'undefined' ? new SharedArrayBuffer(SAB_SIZE) : null;
+    if (this.sab) {
+      this.port.postMessage({ type: 'sab', sab: this.sab });
+    }
+    this.sabView = this.sab ? new Float32Array(this.sab) : null;
+    this.sabPtr = 0;
+    this.wasmInstance = null;
+    this.ctxPtr = 0;
+    this.ioBufPtr = 0;
+    this.wasmMemoryBuffer = null;
+
+    this.port.onmessage = evt => {
+      switch (evt.data.type) {
+        case 'setWasmBytes': {
+          this.initWasm(evt.data.wasmBytes);
+          break;
+        }
+        default: {
+          console.warn('Unhandled message type in safety limiter AWP: ', evt.data.type);
+        }
+      }
+    };
+  }
+
+  handleWasmPanic = (ptr, len) => {
+    const mem = new Uint8Array(this.wasmInstance.exports.memory.buffer);
+    const slice = mem.subarray(ptr, ptr + len);
+    const str = String.fromCharCode(...slice);
+    console.error(`SafetyLimiterAWP Wasm panic: ${str}`);
+  };
+
+  async initWasm(wasmBytes) {
+    const importObject = { env: { log_err: (ptr, len) => this.handleWasmPanic(ptr, len) } };
+    const compiledModule = await WebAssembly.compile(wasmBytes);
+    this.wasmInstance = await WebAssembly.instantiate(compiledModule, importObject);
+    this.wasmInstance.exports.set_sample_rate(sampleRate);
+    this.ctxPtr = this.wasmInstance.exports.safety_limiter_create_ctx();
+    this.ioBufPtr = this.wasmInstance.exports.safety_limiter_get_io_buf_ptr(this.ctxPtr);
+    this.sabPtr = this.wasmInstance.exports.safety_limiter_get_sab_ptr(this.ctxPtr);
+    this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
+  }
+
+  /**
+   * @param {Float32Array[][]} inputs
+   * @param {Float32Array[][]} outputs
+   * @returns {boolean}
+   */
+  process(inputs, outputs) {
+    const input = inputs[0];
+    const output = outputs[0];
+    if (!output?.[0]) {
+      return true;
+    }
+
+    // Pass audio through unprocessed until the Wasm module is ready rather than going silent
+    if (!this.wasmInstance) {
+      for (let channelIx = 0; channelIx < output.length; channelIx++) {
+        const inputChannel = input?.[channelIx] ?? input?.[0];
+        if (inputChannel) {
+          output[channelIx].set(inputChannel);
+        }
+      }
+      return true;
+    }
+
+    if (this.wasmMemoryBuffer.buffer !== this.wasmInstance.exports.memory.buffer) {
+      this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
+    }
+
+    const ioBuf = this.wasmMemoryBuffer.subarray(
+      this.ioBufPtr / BYTES_PER_F32,
+      this.ioBufPtr / BYTES_PER_F32 + FRAME_SIZE * 2
+    );
+    const left = input?.[0];
+    // Mono input is upmixed to both channels
+    const right = input?.[1] ?? left;
+    if (left) {
+      ioBuf.set(left, 0);
+      ioBuf.set(right, FRAME_SIZE);
+    } else {
+      ioBuf.fill(0);
+    }
+
+    checkWasmStatus(this, this.wasmInstance.exports.safety_limiter_process(this.ctxPtr));
+
+    output[0].set(ioBuf.subarray(0, FRAME_SIZE));
+    if (output[1]) {
+      output[1].set(ioBuf.subarray(FRAME_SIZE, FRAME_SIZE * 2));
+    }
+
+    if (this.sab) {
+      this.sabView.set(
+        this.wasmMemoryBuffer.subarray(
+          this.sabPtr / BYTES_PER_F32,
+          this.sabPtr / BYTES_PER_F32 + SAB_SIZE / BYTES_PER_F32
+        )
+      );
+    }
+
+    return true;
+  }
+}
+
+registerProcessor('safety-limiter-awp', SafetyLimiterAWP);
@@ -110,6 +110,17 @@ class SamplePlayerAWP extends AudioWorkletProcessor {
     this.wasmInstance.exports.set_sample_crossfade_params(this.ctxPtr, voiceIx, enabled, threshold);
   }
 
+  setSampleTimeStretchParams(voiceIx, { enabled, sourceBPM, quality, preserveTransients }) {
+    this.wasmInstance.exports.set_sample_time_stretch_params(
+      this.ctxPtr,
+      voiceIx,
+      enabled,
+      sourceBPM,
+      quality,
+      preserveTransients
+    );
+  }
+
   handleMessage(data) {
     // Store all events other than the initialization event until after Wasm is loaded and they can be handled.
     //
@@ -144,6 +155,10 @@ class SamplePlayerAWP extends AudioWorkletProcessor {
         this.setSampleCrossfadeParams(data.voiceIx, data.enabled, data.threshold);
         break;
       }
+      case 'setSampleTimeStretchParams': {
+        this.setSampleTimeStretchParams(data.voiceIx, data.params);
+        break;
+      }
       default: {
         console.error('Unhandled message type in sample player AWP: ', data.type);
       }
@@ -193,7 +208,10 @@ class SamplePlayerAWP extends AudioWorkletProcessor {
       }
     }
 
-    this.wasmInstance.exports.process_sample_player(this.ctxPtr);
+    this.wasmInstance.exports.process_sample_player(
+      this.ctxPtr,
+      globalThis.globalTempoBPM || 120
+    );
     const outputSubarray = wasmMemory.subarray(
       this.outputBufPtr / BYTES_PER_F32,
       this.outputBufPtr / BYTES_PER_F32 + FRAME_SIZE
@@ -0,0 +1,216 @@
+import { checkWasmStatus } from './wasmStatus.js';
+
+const FRAME_SIZE = 128;
+const BYTES_PER_F32 = 32 / 8;
+/**
+ * See `EVENT_SIZE` in `engine/sequencer/src/lib.rs` for the layout of each event
+ */
+const EVENT_SIZE = 5;
+
+class StepSequencerAWP extends AudioWorkletProcessor {
+  constructor() {
+    super();
+
+    this.wasmInstance = null;
+    this.ctxPtr = 0;
+    this.isRunning = false;
+    /**
+     * Mailbox ID that events generated by each track are delivered to
+     * @type {(string | null)[]}
+     */
+    this.trackMailboxIDs = [];
+    /**
+     * Messages received before the Wasm module finished initializing
+     */
+    this.pendingMessages = [];
+
+    this.port.onmessage = evt => {
+      if (evt.data.type === 'setWasmBytes') {
+        this.initWasm(evt.data.wasmBytes);
+        return;
+      }
+
+      if (!this.wasmInstance) {
+        this.pendingMessages.push(evt.data);
+        return;
+      }
+      this.handleMessage(evt.data);
+    };
+  }
+
+  handleWasmPanic = (ptr, len) => {
+    const mem = new Uint8Array(this.wasmInstance.exports.memory.buffer);
+    const slice = mem.subarray(ptr, ptr + len);
+    const str = String.fromCharCode(...slice);
+    console.error(`StepSequencerAWP Wasm panic: ${str}`);
+  };
+
+  async initWasm(wasmBytes) {
+    const importObject = { env: { log_err: (ptr, len) => this.handleWasmPanic(ptr, len) } };
+    const compiledModule = await WebAssembly.compile(wasmBytes);
+    this.wasmInstance = await WebAssembly.instantiate(compiledModule, importObject);
+    this.ctxPtr = this.wasmInstance.exports.sequencer_create_ctx(0, 0);
+
+    this.pendingMessages.forEach(msg => this.handleMessage(msg));
+    this.pendingMessages = [];
+  }
+
+  setStep(trackIx, stepIx, step) {
+    const exports = this.wasmInstance.exports;
+    checkWasmStatus(
+      this,
+      exports.sequencer_set_step(
+        this.ctxPtr,
+        trackIx,
+        stepIx,
+        step.enabled,
+        step.note,
+        step.velocity,
+        step.gate,
+        step.probability
+      )
+    );
+    step.paramLocks.forEach((value, paramIx) =>
+      checkWasmStatus(
+        this,
+        exports.sequencer_set_param_lock(this.ctxPtr, trackIx, stepIx, paramIx, value ?? NaN)
+      )
+    );
+  }
+
+  setState(state) {
+    const exports = this.wasmInstance.exports;
+    checkWasmStatus(
+      this,
+      exports.sequencer_resize(this.ctxPtr, state.tracks.length, state.stepCount)
+    );
+    checkWasmStatus(this, exports.sequencer_set_beats_per_step(this.ctxPtr, state.beatsPerStep));
+
+    this.trackMailboxIDs = state.tracks.map(track => track.mailboxID);
+    state.tracks.forEach((track, trackIx) => {
+      checkWasmStatus(this, exports.sequencer_set_track_muted(this.ctxPtr, trackIx, track.muted));
+      track.paramDefaults.forEach((value, paramIx) =>
+        checkWasmStatus(
+          this,
+          exports.sequencer_set_param_default(this.ctxPtr, trackIx, paramIx, value ?? NaN)
+        )
+      );
+      track.steps.forEach((step, stepIx) => this.setStep(trackIx, stepIx, step));
+      this.setTrackEuclidean(trackIx, track.euclidean);
+    });
+  }
+
+  setTrackEuclidean(trackIx, euclidean) {
+    checkWasmStatus(
+      this,
+      this.wasmInstance.exports.sequencer_set_track_euclidean(
+        this.ctxPtr,
+        trackIx,
+        euclidean?.pulses ?? 0,
+        euclidean?.steps ?? 0,
+        euclidean?.rotation ?? 0
+      )
+    );
+  }
+
+  handleMessage(data) {
+    const exports = this.wasmInstance.exports;
+
+    switch (data.type) {
+      case 'setState': {
+        this.setState(data.state);
+        break;
+      }
+      case 'setStep': {
+        this.setStep(data.trackIx, data.stepIx, data.step);
+        break;
+      }
+      case 'setTrackMuted': {
+        checkWasmStatus(
+          this,
+          exports.sequencer_set_track_muted(this.ctxPtr, data.trackIx, data.muted)
+        );
+        break;
+      }
+      case 'setTrackEuclidean': {
+        this.setTrackEuclidean(data.trackIx, data.euclidean);
+        break;
+      }
+      case 'fillTrackEuclidean': {
+        const { pulses, steps, rotation } = data.euclidean;
+        checkWasmStatus(
+          this,
+          exports.sequencer_fill_track_euclidean(this.ctxPtr, data.trackIx, pulses, steps, rotation)
+        );
+        break;
+      }
+      case 'setTrackMailboxID': {
+        this.trackMailboxIDs[data.trackIx] = data.mailboxID;
+        break;
+      }
+      case 'setBeatsPerStep': {
+        checkWasmStatus(this, exports.sequencer_set_beats_per_step(this.ctxPtr, data.beatsPerStep));
+        break;
+      }
+      default: {
+        console.warn('Unhandled message type in step sequencer AWP: ', data.type);
+      }
+    }
+  }
+
+  /**
+   * Delivers the `eventCount` events most recently generated by the Wasm module to the mailboxes
+   * of the tracks that generated them.
+   */
+  submitEvents(eventCount) {
+    if (eventCount === 0) {
+      return;
+    }
+
+    const eventsPtr = this.wasmInstance.exports.sequencer_get_events_ptr(this.ctxPtr);
+    const events = new Float32Array(
+      this.wasmInstance.exports.memory.buffer,
+      eventsPtr,
+      eventCount * EVENT_SIZE
+    );
+    const samplesPerBeat = (sampleRate * 60) / (globalThis.globalTempoBPM || 120);
+
+    for (let i = 0; i < eventCount; i++) {
+      const offset = i * EVENT_SIZE;
+      const mailboxID = this.trackMailboxIDs[events[offset + 1]];
+      if (!mailboxID) {
+        continue;
+      }
+
+      const beatsBeforeNow = events[offset + 4];
+      const sampleIx = Math.max(0, FRAME_SIZE - 1 - Math.round(beatsBeforeNow * samplesPerBeat));
+      globalThis.midiEventMailboxRegistry.submitEvent(
+        mailboxID,
+        events[offset],
+        events[offset + 2],
+        events[offset + 3],
+        sampleIx
+      );
+    }
+  }
+
+  process() {
+    if (!this.wasmInstance) {
+      return true;
+    }
+
+    if (!globalThis.globalBeatCounterStarted) {
+      if (this.isRunning) {
+        this.isRunning = false;
+        this.submitEvents(this.wasmInstance.exports.sequencer_stop(this.ctxPtr));
+      }
+      return true;
+    }
+
+    this.isRunning = true;
+    this.submitEvents(this.wasmInstance.exports.sequencer_tick(this.ctxPtr, globalThis.curBeat));
+    return true;
+  }
+}
+
+registerProcessor('step-sequencer-awp', StepSequencerAWP);
@@ -39,6 +39,7 @@ class VocoderAWP extends AudioWorkletProcessor {
     const importObject = { env: { log_err: (ptr, len) => this.handleWasmPanic(ptr, len) } };
     const compiledModule = await WebAssembly.compile(wasmBytes);
     this.wasmInstance = await WebAssembly.instantiate(compiledModule, importObject);
+    this.wasmInstance.exports.set_sample_rate(sampleRate);
     // this.wasmInstance.exports.memory.grow(1024 * 4);
 
     const filterParamsBufPtr = this.wasmInstance.exports.get_filter_params_buf_ptr();
@@ -2,6 +2,11 @@ const FRAME_SIZE = 128;
 const MAX_DIMENSION_COUNT = 2;
 const BYTES_PER_F32 = 32 / 8;
 
+/**
+ * Must match `LogLevel` in the `logging` crate
+ */
+const LOG_LEVEL_CONSOLE_FNS = ['error', 'warn', 'info', 'debug'];
+
 class WaveTableNodeProcessor extends AudioWorkletProcessor {
   static get parameterDescriptors() {
     return [
@@ -36,6 +41,15 @@ class WaveTableNodeProcessor extends AudioWorkletProcessor {
     ];
   }
 
+  /**
+   * Handles messages from the `logging` crate; `level` indexes into `LOG_LEVEL_CONSOLE_FNS`.
+   */
+  logFromWasm = (ptr, len, level) => {
+    const mem = new Uint8Array(this.wasmInstance.exports.memory.buffer);
+    const str = String.fromCharCode(...mem.subarray(ptr, ptr + len));
+    console[LOG_LEVEL_CONSOLE_FNS[level] ?? 'log'](str);
+  };
+
   handleWasmPanic = (ptr, len) => {
     const mem = new Uint8Array(this.wasmInstance.exports.memory.buffer);
     const slice = mem.subarray(ptr, ptr + len);
@@ -52,8 +66,11 @@ class WaveTableNodeProcessor extends AudioWorkletProcessor {
         on_ungate_cb: () => {
           throw new Error('this should only be called by the FM synth');
         },
+        now: () => {
+          throw new Error('this should only be called by the FM synth');
+        },
         log_err: (ptr, len) => this.handleWasmPanic(ptr, len),
-        log_raw: (ptr, len, _level) => this.handleWasmPanic(ptr, len),
+        log_raw: this.logFromWasm,
       },
     };
 
@@ -0,0 +1,45 @@
+/**
+ * Helpers shared by AWPs hosting Wasm modules whose exports return the status codes defined in
+ * `common::ffi` in the engine.
+ */
+
+/**
+ * Reads the message describing the most recent error out of the Wasm module's memory
+ *
+ * @param {WebAssembly.Instance} wasmInstance
+ * @returns {string}
+ */
+export const getLastWasmErrorMessage = wasmInstance => {
+  const ptr = wasmInstance.exports.get_last_error_message();
+  const len = wasmInstance.exports.get_last_error_message_len();
+  return String.fromCharCode.apply(
+    null,
+    new Uint8Array(wasmInstance.exports.memory.buffer).subarray(ptr, ptr + len)
+  );
+};
+
+/**
+ * Logs the error message set by the Wasm module of `processor` if `status` is non-zero.  Only logs
+ * when the status changes to avoid flooding the console from the audio thread; the last status is
+ * stored on `processor` as `lastWasmStatus`.
+ *
+ * Messages are prefixed with `processor.wasmStatusLabel` if it's set, or else the processor's class
+ * name.
+ *
+ * @param {AudioWorkletProcessor & { wasmInstance: WebAssembly.Instance }} processor
+ * @param {number} status
+ */
+export const checkWasmStatus = (processor, status) => {
+  if (status === processor.lastWasmStatus) {
+    return;
+  }
+  processor.lastWasmStatus = status;
+  if (status === 0) {
+    return;
+  }
+
+  const label = processor.wasmStatusLabel ?? processor.constructor.name;
+  console.error(
+    `${label} error (code ${status}): ${getLastWasmErrorMessage(processor.wasmInstance)}`
+  );
+};
@@ -52,5 +52,22 @@ export const getIsVcHidden = (vcId: string): boolean => {
     return true;
   }
   const activeVcId = vc.uuid;
-  return activeVcId !== vcId;
+  return activeVcId !== vcId && state.viewContextManager.splitViewContextId !== vcId;
+};
+
+/**
+ * Returns the width in pixels available to the VC with the provided ID, which is half of the window
+ * if it's being displayed in a split view.
+ */
+export const getVcPaneWidth = (vcId: string): number => {
+  const splitVcId = mainReduxGetState?.().viewContextManager.splitViewContextId;
+  if (!splitVcId) {
+    return window.innerWidth;
+  }
+  const state = mainReduxGetState!();
+  const activeVcId =
+    state.viewContextManager.activeViewContexts[state.viewContextManager.activeViewContextIx]?.uuid;
+  return vcId === splitVcId || vcId === activeVcId
+    ? Math.floor(window.innerWidth / 2)
+    : window.innerWidth;
 };
@@ -164,6 +164,10 @@ interface ViewContextTabProps {
   uuid: string;
   title?: string;
   active: boolean;
+  /**
+   * If true, this VC is displayed side by side with the active one
+   */
+  split: boolean;
   i: number;
 }
 
@@ -207,7 +211,29 @@ const VCTabCloseIcon: React.FC<VCTabCloseIconProps> = ({ onClick }) => (
   </div>
 );
 
-const ViewContextTab: React.FC<ViewContextTabProps> = ({ engine, name, uuid, title, active }) => {
+interface VCTabSplitIconProps {
+  split: boolean;
+  onClick: (e: React.MouseEvent<HTMLDivElement>) => void;
+}
+
+const VCTabSplitIcon: React.FC<VCTabSplitIconProps> = ({ split, onClick }) => (
+  <div
+    onClick={onClick}
+    className='vc-split-tab-icon'
+    title={split ? 'Close split view' : 'Open side by side with the active view'}
+  >
+    {split ? '▣' : '◫'}
+  </div>
+);
+
+const ViewContextTab: React.FC<ViewContextTabProps> = ({
+  engine,
+  name,
+  uuid,
+  title,
+  active,
+  split,
+}) => {
   const [isRenaming, setIsRenaming] = useState(false);
   const [renamingTitle, setRenamingTitle] = useState(title || '');
 
@@ -220,7 +246,7 @@ const ViewContextTab: React.FC<ViewContextTabProps> = ({ engine, name, uuid, tit
       key={uuid}
       style={{
         ...styles.viewContextTab,
-        backgroundColor: active ? '#419282' : undefined,
+        backgroundColor: active ? '#419282' : split ? '#2f6a5e' : undefined,
       }}
       onClick={() => {
         if (!active) {
@@ -258,6 +284,22 @@ const ViewContextTab: React.FC<ViewContextTabProps> = ({ engine, name, uuid, tit
           <span className='vc-switcher-tab-title' data-vc-id={uuid} data-vc-name={name}>
             {displayName}
           </span>
+          {active ? null : (
+            <VCTabSplitIcon
+              split={split}
+              onClick={e => {
+                getSentry()?.addBreadcrumb({
+                  message: `${split ? 'Closing' : 'Opening'} split view for VC ${uuid} name=${name}`,
+                });
+                if (split) {
+                  engine.clear_split_view_context();
+                } else {
+                  engine.set_split_view_context(uuid);
+                }
+                e.stopPropagation();
+              }}
+            />
+          )}
         </>
       )}
     </ViewContextIcon>
@@ -274,10 +316,11 @@ interface ViewContextSwitcherProps {
  * backend every time there is a change.
  */
 export const ViewContextSwitcher: React.FC<ViewContextSwitcherProps> = ({ engine }) => {
-  const { activeViewContexts, activeViewContextIx } = useSelector(
+  const { activeViewContexts, activeViewContextIx, splitViewContextId } = useSelector(
     (state: ReduxStore) => ({
       activeViewContexts: state.viewContextManager.activeViewContexts,
       activeViewContextIx: state.viewContextManager.activeViewContextIx,
+      splitViewContextId: state.viewContextManager.splitViewContextId,
     }),
     shallowEqual
   );
@@ -302,6 +345,7 @@ export const ViewContextSwitcher: React.FC<ViewContextSwitcherProps> = ({ engine
           i={i}
           key={i}
           active={activeViewContextIx === i}
+          split={splitViewContextId === props.uuid}
         />
       ))}
     </div>
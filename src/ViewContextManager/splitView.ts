@@ -0,0 +1,38 @@
+import { getState } from 'src/redux';
+
+const SPLIT_VIEW_STYLE_ID = 'vc-split-view-style';
+
+/**
+ * Lays out the active VC and the split VC, if there is one, side by side.
+ *
+ * All VC containers are absolutely positioned children of `main#content` with IDs ending in their
+ * VC ID, but they're created lazily by each VC.  Using a stylesheet rather than setting styles on
+ * the containers directly means that containers created after this is called are laid out as well.
+ */
+export const updateSplitViewLayout = () => {
+  const { activeViewContexts, activeViewContextIx, splitViewContextId } =
+    getState().viewContextManager;
+  const activeVcId = activeViewContexts[activeViewContextIx]?.uuid;
+
+  let styleElem = document.getElementById(SPLIT_VIEW_STYLE_ID);
+  if (!styleElem) {
+    styleElem = document.createElement('style');
+    styleElem.id = SPLIT_VIEW_STYLE_ID;
+    document.head.appendChild(styleElem);
+  }
+
+  styleElem.textContent =
+    activeVcId && splitViewContextId
+      ? `
+main#content > [id$="${activeVcId}"] { width: 50% !important; left: 0 !important; }
+main#content > [id$="${splitViewContextId}"] {
+  width: 50% !important;
+  left: 50% !important;
+  border-left: 1px solid #666;
+  box-sizing: border-box;
+}`
+      : '';
+
+  // VCs size themselves based off of the window size, so this lets them re-compute their layout
+  window.dispatchEvent(new Event('resize'));
+};
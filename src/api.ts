@@ -4,11 +4,12 @@ import type { CompositionDefinition } from 'src/compositionSharing/CompositionSh
 import { BACKEND_BASE_URL } from 'src/conf';
 import type { BuildWavetableInstanceState } from 'src/fmSynth/Wavetable/BuildWavetableInstance';
 import type { SerializedMIDIEditorState } from 'src/midiEditor/MIDIEditorUIInstance';
-import { getLoginToken } from 'src/persistance';
+import { getLoginToken, setLoginToken } from 'src/persistance';
 import type { Effect } from 'src/redux/modules/effects';
 import type { SerializedLooperInstState } from 'src/redux/modules/looper';
 import type { serializeSynthModule } from 'src/redux/modules/synthDesigner';
 import type { SampleDescriptor } from 'src/sampleLibrary';
+import type { SerializedStepSequencerState } from 'src/sequencer/stepSequencer';
 
 const buildURL = (path: string) => `${BACKEND_BASE_URL}${path}`;
 
@@ -16,6 +17,10 @@ export const saveSynthVoicePreset = async (preset: {
   title: string;
   description: string;
   body: ReturnType<typeof serializeSynthModule>;
+  /**
+   * Defaults to `true`.  Ignored if not logged in; anonymous presets are always public.
+   */
+  isPublic?: boolean;
 }) => {
   const maybeLoginToken = await getLoginToken();
   return fetch(buildURL('/synth_voice_presets'), {
@@ -37,6 +42,10 @@ export const saveSynthPreset = async (preset: {
   body: {
     voices: ReturnType<typeof serializeSynthModule>[];
   };
+  /**
+   * Defaults to `true`.  Ignored if not logged in; anonymous presets are always public.
+   */
+  isPublic?: boolean;
 }) => {
   const maybeLoginToken = await getLoginToken();
   fetch(buildURL('/synth_presets'), {
@@ -52,6 +61,43 @@ export const saveSynthPreset = async (preset: {
   });
 };
 
+const postAuthenticatedAction = async (path: string) => {
+  const maybeLoginToken = await getLoginToken();
+  return fetch(buildURL(path), {
+    method: 'POST',
+    headers: {
+      Authorization: maybeLoginToken,
+    },
+  }).then(async res => {
+    if (!res.ok) {
+      throw await res.text();
+    }
+    return res;
+  });
+};
+
+/**
+ * Makes a synth preset owned by the logged-in user visible to everyone, or only to them
+ */
+export const setSynthPresetIsPublic = async (presetId: number, isPublic: boolean) => {
+  await postAuthenticatedAction(`/synth_presets/${presetId}/${isPublic ? 'publish' : 'unpublish'}`);
+};
+
+export const setSynthVoicePresetIsPublic = async (presetId: number, isPublic: boolean) => {
+  await postAuthenticatedAction(
+    `/synth_voice_presets/${presetId}/${isPublic ? 'publish' : 'unpublish'}`
+  );
+};
+
+/**
+ * Copies a synth preset into a new private preset owned by the logged-in user, returning its ID
+ */
+export const forkSynthPreset = (presetId: number): Promise<number> =>
+  postAuthenticatedAction(`/synth_presets/${presetId}/fork`).then(res => res.json());
+
+export const forkSynthVoicePreset = (presetId: number): Promise<number> =>
+  postAuthenticatedAction(`/synth_voice_presets/${presetId}/fork`).then(res => res.json());
+
 export const fetchAllSharedCompositions = (): Promise<Omit<CompositionDefinition, 'content'>[]> =>
   fetch(`${BACKEND_BASE_URL}/compositions`).then(async res => {
     if (!res.ok) {
@@ -81,15 +127,109 @@ export const getLoadedComposition = async (compositionID: string | number) => {
   return composition;
 };
 
+/**
+ * Returns the ID of the saved composition.  If `parentId` is provided and the composition it refers
+ * to is owned by the logged-in user, a new version of it is saved instead of a new composition.
+ */
 export const saveComposition = async (
   title: string,
   description: string,
   serializedComposition: { [key: string]: string },
-  tags: string[]
+  tags: string[],
+  parentId?: number | null
 ): Promise<number> =>
   fetch(`${BACKEND_BASE_URL}/compositions`, {
     method: 'POST',
-    body: JSON.stringify({ title, description, content: serializedComposition, tags }),
+    body: JSON.stringify({ title, description, content: serializedComposition, tags, parentId }),
+    headers: {
+      'Content-Type': 'application/json',
+      Authorization: await getLoginToken(),
+    },
+  }).then(async res => {
+    if (!res.ok) {
+      throw await res.text();
+    }
+    return res.json();
+  });
+
+export interface CompositionVersion {
+  version: number;
+  title: string;
+  description: string;
+  /**
+   * Unix timestamp in seconds
+   */
+  createdAt: number;
+}
+
+export const getCompositionVersions = (compositionId: number): Promise<CompositionVersion[]> =>
+  fetch(`${BACKEND_BASE_URL}/compositions/${compositionId}/versions`).then(async res => {
+    if (!res.ok) {
+      throw await res.text();
+    }
+    return res.json();
+  });
+
+/**
+ * Returns the number of the new version that was created from the restored one
+ */
+export const restoreCompositionVersion = (
+  compositionId: number,
+  version: number
+): Promise<number> =>
+  postAuthenticatedAction(`/compositions/${compositionId}/versions/${version}/restore`).then(res =>
+    res.json()
+  );
+
+export interface BundledPreset {
+  id: number;
+  title: string;
+  description: string;
+  body: string;
+}
+
+export interface BundledEffect {
+  id: number;
+  title: string;
+  description: string;
+  code: string;
+}
+
+/**
+ * A composition along with all of the presets, effects, and samples it references.  Bundles can be
+ * saved as files and imported into other instances.
+ */
+export interface CompositionBundle {
+  formatVersion: number;
+  composition: {
+    title: string;
+    description: string;
+    tags: string[];
+    content: { [key: string]: string };
+  };
+  synthPresets: BundledPreset[];
+  voicePresets: BundledPreset[];
+  effects: BundledEffect[];
+  samples: { id?: string | null; name: string; url: string }[];
+}
+
+export const exportCompositionBundle = async (compositionId: number): Promise<CompositionBundle> =>
+  fetch(`${BACKEND_BASE_URL}/compositions/${compositionId}/export`, {
+    headers: { Authorization: await getLoginToken() },
+  }).then(async res => {
+    if (!res.ok) {
+      throw await res.text();
+    }
+    return res.json();
+  });
+
+/**
+ * Returns the ID of the newly created composition
+ */
+export const importCompositionBundle = async (bundle: CompositionBundle): Promise<number> =>
+  fetch(`${BACKEND_BASE_URL}/compositions/import`, {
+    method: 'POST',
+    body: JSON.stringify(bundle),
     headers: {
       'Content-Type': 'application/json',
       Authorization: await getLoginToken(),
@@ -134,6 +274,69 @@ export const listRemoteSamples = async (): Promise<RemoteSample[]> =>
     }
   );
 
+export interface UserSample {
+  id: number;
+  name: string;
+  byteSize: number;
+  /**
+   * Hex-encoded SHA-256 hash of the uploaded file
+   */
+  checksum: string;
+  contentType: string;
+  storageUrl: string;
+  /**
+   * Unix timestamp in seconds
+   */
+  createdAt: number;
+}
+
+export const listUserSamples = async (): Promise<UserSample[]> =>
+  fetch(buildURL('/user_samples'), { headers: { Authorization: await getLoginToken() } }).then(
+    async res => {
+      if (!res.ok) {
+        throw await res.text();
+      }
+      return res.json();
+    }
+  );
+
+/**
+ * Uploads an audio file to the logged-in user's sample library.  If the user has already uploaded
+ * the same file, the existing sample is returned instead.
+ */
+export const uploadUserSample = async (file: File, name = file.name): Promise<UserSample> => {
+  const res = await fetch(buildURL(`/user_samples?name=${encodeURIComponent(name)}`), {
+    method: 'POST',
+    body: file,
+    headers: {
+      Authorization: await getLoginToken(),
+      'Content-Type': file.type,
+    },
+  });
+  if (!res.ok) {
+    throw await res.text();
+  }
+  return res.json();
+};
+
+export const renameUserSample = async (sampleId: number, name: string) => {
+  const res = await fetch(buildURL(`/user_samples/${sampleId}/rename`), {
+    method: 'POST',
+    body: JSON.stringify({ name }),
+    headers: {
+      Authorization: await getLoginToken(),
+      'Content-Type': 'application/json',
+    },
+  });
+  if (!res.ok) {
+    throw await res.text();
+  }
+};
+
+export const deleteUserSample = async (sampleId: number) => {
+  await postAuthenticatedAction(`/user_samples/${sampleId}/delete`);
+};
+
 export const fetchEffects = (): Promise<Effect[]> =>
   fetch(`${BACKEND_BASE_URL}/effects`).then(async res => {
     if (!res.ok) {
@@ -142,7 +345,10 @@ export const fetchEffects = (): Promise<Effect[]> =>
     return res.json();
   });
 
-export const saveEffect = async (effect: Without<Effect, 'id'>) => {
+/**
+ * Creates a new effect, returning its ID
+ */
+export const saveEffect = async (effect: Without<Effect, 'id' | 'revision'>): Promise<number> => {
   const maybeLoginToken = await getLoginToken();
   return fetch(`${BACKEND_BASE_URL}/effects`, {
     method: 'POST',
@@ -154,9 +360,98 @@ export const saveEffect = async (effect: Without<Effect, 'id'>) => {
     if (!res.ok) {
       throw await res.text();
     }
+    return res.json();
   });
 };
 
+export interface EffectRevisionDescriptor {
+  revision: number;
+  codeHash: string;
+  /**
+   * Unix timestamp in seconds
+   */
+  createdAt: number;
+}
+
+/**
+ * A module compiled from an effect's code which can be loaded from the Faust compiler's cache
+ */
+export interface CompiledEffectArtifact {
+  optimized: boolean;
+  moduleId: string;
+}
+
+export interface EffectRevision {
+  effectId: number;
+  revision: number;
+  code: string;
+  codeHash: string;
+  createdAt: number;
+  compiledArtifacts: CompiledEffectArtifact[];
+}
+
+/**
+ * Saves new code for an effect owned by the logged-in user, returning the new revision number
+ */
+export const saveEffectRevision = async (
+  effectId: number,
+  revision: { code: string; title?: string; description?: string }
+): Promise<number> => {
+  const res = await fetch(buildURL(`/effects/${effectId}/revisions`), {
+    method: 'POST',
+    body: JSON.stringify(revision),
+    headers: {
+      Authorization: await getLoginToken(),
+      'Content-Type': 'application/json',
+    },
+  });
+  if (!res.ok) {
+    throw await res.text();
+  }
+  return res.json();
+};
+
+export const getEffectRevisions = async (effectId: number): Promise<EffectRevisionDescriptor[]> => {
+  const res = await fetch(buildURL(`/effects/${effectId}/revisions`));
+  if (!res.ok) {
+    throw await res.text();
+  }
+  return res.json();
+};
+
+export const getEffectRevision = async (
+  effectId: number,
+  revision: number
+): Promise<EffectRevision> => {
+  const res = await fetch(buildURL(`/effects/${effectId}/revisions/${revision}`));
+  if (!res.ok) {
+    throw await res.text();
+  }
+  return res.json();
+};
+
+/**
+ * Records that the code of an effect revision was compiled into the module with the provided ID by
+ * the Faust compiler
+ */
+export const registerCompiledEffectArtifact = async (
+  effectId: number,
+  revision: number,
+  artifact: CompiledEffectArtifact
+) => {
+  const res = await fetch(
+    buildURL(`/effects/${effectId}/revisions/${revision}/compiled_artifacts`),
+    {
+      method: 'POST',
+      body: JSON.stringify(artifact),
+      headers: { 'Content-Type': 'application/json' },
+    }
+  );
+  if (!res.ok) {
+    throw await res.text();
+  }
+};
+
 export interface SavedMIDIComposition {
   id: number;
   name: string;
@@ -170,22 +465,63 @@ export interface SavedMIDIComposition {
 export const getSavedMIDICompositions = async (): Promise<SavedMIDIComposition[]> =>
   fetch(`${BACKEND_BASE_URL}/midi_compositions`).then(res => res.json());
 
+/**
+ * Returns the ID of the saved composition.  If `parentId` is provided and the composition it refers
+ * to is owned by the logged-in user, a new version of it is saved instead of a new composition.
+ */
 export const saveMIDIComposition = async (
   name: string,
   description: string,
   composition: SerializedMIDIEditorState,
-  tags: string[]
-) => {
+  tags: string[],
+  parentId?: number | null
+): Promise<number> => {
   const maybeLoginToken = await getLoginToken();
   return fetch(`${BACKEND_BASE_URL}/midi_compositions`, {
-    body: JSON.stringify({ name, description, composition, tags }),
+    body: JSON.stringify({ name, description, composition, tags, parentId }),
     method: 'POST',
     headers: {
       Authorization: maybeLoginToken,
     },
+  }).then(async res => {
+    if (!res.ok) {
+      throw await res.text();
+    }
+    return res.json();
   });
 };
 
+export interface MIDICompositionVersion {
+  version: number;
+  name: string;
+  description: string;
+  /**
+   * Unix timestamp in seconds
+   */
+  createdAt: number;
+}
+
+export const getMIDICompositionVersions = (
+  midiCompositionId: number
+): Promise<MIDICompositionVersion[]> =>
+  fetch(`${BACKEND_BASE_URL}/midi_compositions/${midiCompositionId}/versions`).then(async res => {
+    if (!res.ok) {
+      throw await res.text();
+    }
+    return res.json();
+  });
+
+/**
+ * Returns the number of the new version that was created from the restored one
+ */
+export const restoreMIDICompositionVersion = (
+  midiCompositionId: number,
+  version: number
+): Promise<number> =>
+  postAuthenticatedAction(
+    `/midi_compositions/${midiCompositionId}/versions/${version}/restore`
+  ).then(res => res.json());
+
 export const getExistingMIDICompositionTags = async (): Promise<
   { name: string; count: number }[]
 > =>
@@ -250,6 +586,62 @@ export const getLooperPreset = async (id: number): Promise<SerializedLooperInstS
     return res.json();
   });
 
+export interface SequencerPreset {
+  id: number;
+  name: string;
+  description: string;
+  tags: string[];
+  userId: number | null | undefined;
+  userName: string | null | undefined;
+}
+
+export const fetchSequencerPresets = async (): Promise<SequencerPreset[]> =>
+  fetch(`${BACKEND_BASE_URL}/sequencer_presets`).then(async res => {
+    if (!res.ok) {
+      throw await res.text();
+    }
+    return res.json();
+  });
+
+export const saveSequencerPreset = async (preset: {
+  name: string;
+  description: string;
+  tags: string[];
+  serializedSequencerState: SerializedStepSequencerState;
+}) => {
+  const maybeLoginToken = await getLoginToken();
+  return fetch(`${BACKEND_BASE_URL}/sequencer_preset`, {
+    body: JSON.stringify(preset),
+    method: 'POST',
+    headers: {
+      Authorization: maybeLoginToken,
+    },
+  }).then(async res => {
+    if (!res.ok) {
+      throw await res.text();
+    }
+    return res.json();
+  });
+};
+
+export const getExistingSequencerPresetTags = async (): Promise<
+  { name: string; count: number }[]
+> =>
+  fetch(`${BACKEND_BASE_URL}/sequencer_preset_tags`).then(async res => {
+    if (!res.ok) {
+      throw await res.text();
+    }
+    return res.json();
+  });
+
+export const getSequencerPreset = async (id: number): Promise<SerializedStepSequencerState> =>
+  fetch(`${BACKEND_BASE_URL}/sequencer_preset/${id}`).then(async res => {
+    if (!res.ok) {
+      throw await res.text();
+    }
+    return res.json();
+  });
+
 export interface WavetablePreset {
   waveforms: {
     instState: BuildWavetableInstanceState;
@@ -359,3 +751,95 @@ export const getLoggedInUsername = async (): Promise<string | null> => {
   }
   return res.text();
 };
+
+/**
+ * Exchanges the current login token for a new one with a fresh expiry and stores it in place of the
+ * old one.  Login tokens expire 30 days after they're issued.
+ */
+export const refreshLoginToken = async () => {
+  const res = await postAuthenticatedAction('/refresh_login_token');
+  await setLoginToken(await res.text());
+};
+
+/**
+ * Revokes the current login token on the server and forgets it locally
+ */
+export const logout = async () => {
+  await postAuthenticatedAction('/logout');
+  await setLoginToken('');
+};
+
+export type SearchableEntity =
+  | 'compositions'
+  | 'midi_compositions'
+  | 'synth_presets'
+  | 'looper_presets';
+
+export interface SearchParams {
+  /**
+   * Whitespace-separated terms which must all appear in the title or description
+   */
+  query?: string;
+  /**
+   * Only entities with all of these tags are returned
+   */
+  tags?: string[];
+  sort?: 'newest' | 'most_used';
+  /**
+   * Zero-indexed
+   */
+  page?: number;
+  pageSize?: number;
+}
+
+export interface SearchResultDescriptor {
+  id: number;
+  title: string;
+  description: string;
+  tags: string[];
+  userId: number | null | undefined;
+  userName: string | null | undefined;
+  useCount: number;
+}
+
+export interface SearchResults {
+  results: SearchResultDescriptor[];
+  /**
+   * Total number of matches across all pages
+   */
+  total: number;
+  page: number;
+  pageSize: number;
+}
+
+export const searchEntities = async (
+  entity: SearchableEntity,
+  { query, tags = [], sort, page, pageSize }: SearchParams
+): Promise<SearchResults> => {
+  const params = new URLSearchParams();
+  if (query) {
+    params.set('q', query);
+  }
+  tags.forEach(tag => params.append('tags', tag));
+  if (sort) {
+    params.set('sort', sort);
+  }
+  if (page !== undefined) {
+    params.set('page', `${page}`);
+  }
+  if (pageSize !== undefined) {
+    params.set('page_size', `${pageSize}`);
+  }
+
+  const maybeLoginToken = await getLoginToken();
+  return fetch(`${BACKEND_BASE_URL}/search/${entity}?${params.toString()}`, {
+    headers: {
+      Authorization: maybeLoginToken,
+    },
+  }).then(async res => {
+    if (!res.ok) {
+      throw await res.text();
+    }
+    return res.json();
+  });
+};
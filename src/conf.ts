@@ -1,5 +1,13 @@
 export const BACKEND_BASE_URL = process.env.BACKEND_BASE_URL || 'http://localhost:7467';
 
+/**
+ * WebSocket server used to edit MIDI compositions together with other users.  It runs alongside the
+ * backend on its own port.
+ */
+export const COLLABORATION_BASE_URL =
+  process.env.COLLABORATION_BASE_URL ||
+  BACKEND_BASE_URL.replace(/^http/, 'ws').replace(/:7467$/, ':7468');
+
 export const FAUST_COMPILER_ENDPOINT =
   process.env.FAUST_COMPILER_ENDPOINT ||
   (process.env.NODE_ENV === 'development'
@@ -0,0 +1,209 @@
+/**
+ * Single-producer single-consumer ring buffers of events in `SharedArrayBuffer`s, one for each
+ * direction between the UI thread and an AWP.
+ *
+ * For control events, the UI thread pushes events and the AWP drains the ones that are due at the
+ * start of each frame, which avoids a `postMessage` and a Wasm FFI call for every event.
+ *
+ * The buffer starts with two `Int32`s holding the write and read indices, followed by `capacity`
+ * events of `CONTROL_EVENT_SIZE` `Float64`s: [event type, time, param 0, param 1].  Times are in
+ * `AudioContext.currentTime` seconds; 0 means as soon as possible.  Events must be pushed in time
+ * order since the reader stops at the first event that isn't due yet.
+ *
+ * Only the writer updates the write index and only the reader updates the read index, so the
+ * indices are all the synchronization that's needed.  One slot is always left empty to tell a full
+ * ring apart from an empty one.
+ */
+
+/**
+ * Must match `CONTROL_EVENT_TYPE_*` in `engine/wavetable/src/fm/control_events.rs`
+ */
+export enum ControlEventType {
+  Gate = 0,
+  Ungate = 1,
+  UngateAll = 2,
+  PitchBend = 3,
+  MasterGain = 4,
+  FrequencyMultiplier = 5,
+  MorphPosition = 6,
+  MIDIControlValue = 7,
+}
+
+/**
+ * Must match `CONTROL_EVENT_RING_*` in the AWPs that read from the ring
+ */
+const CONTROL_EVENT_SIZE = 4;
+const HEADER_BYTES = 2 * Int32Array.BYTES_PER_ELEMENT;
+const WRITE_IX = 0;
+const READ_IX = 1;
+const DEFAULT_CAPACITY = 512;
+
+export class ControlEventRing {
+  public readonly sab: SharedArrayBuffer;
+  private header: Int32Array;
+  private events: Float64Array;
+  private capacity: number;
+
+  constructor(capacity = DEFAULT_CAPACITY) {
+    this.capacity = capacity;
+    this.sab = new SharedArrayBuffer(
+      HEADER_BYTES + capacity * CONTROL_EVENT_SIZE * Float64Array.BYTES_PER_ELEMENT
+    );
+    this.header = new Int32Array(this.sab, 0, 2);
+    this.events = new Float64Array(this.sab, HEADER_BYTES, capacity * CONTROL_EVENT_SIZE);
+  }
+
+  /**
+   * Returns `false` if the ring is full, in which case the event is dropped
+   */
+  public push(eventType: ControlEventType, time: number, param0 = 0, param1 = 0): boolean {
+    const writeIx = this.header[WRITE_IX];
+    const nextWriteIx = (writeIx + 1) % this.capacity;
+    if (nextWriteIx === Atomics.load(this.header, READ_IX)) {
+      return false;
+    }
+
+    const offset = writeIx * CONTROL_EVENT_SIZE;
+    this.events[offset] = eventType;
+    this.events[offset + 1] = time;
+    this.events[offset + 2] = param0;
+    this.events[offset + 3] = param1;
+    // Publishes the event to the reader
+    Atomics.store(this.header, WRITE_IX, nextWriteIx);
+    return true;
+  }
+}
+
+/**
+ * Returns a new ring, or `null` if `SharedArrayBuffer` isn't available because the page isn't
+ * cross-origin isolated.  Callers should fall back to posting messages in that case.
+ */
+export const maybeCreateControlEventRing = (capacity?: number): ControlEventRing | null =>
+  typeof SharedArrayBuffer === 'undefined' ? null : new ControlEventRing(capacity);
+
+/**
+ * Must match `NOTIFICATION_TYPE_*` in the AWPs that write to notification rings
+ */
+export enum NotificationType {
+  /**
+   * param 0: MIDI number, param 1: voice index
+   */
+  VoiceStarted = 0,
+  /**
+   * param 0: MIDI number, param 1: voice index
+   */
+  VoiceEnded = 1,
+  /**
+   * param 0: error code returned from Wasm
+   */
+  Error = 2,
+}
+
+export type Notification =
+  | { type: NotificationType; time: number; param0: number; param1: number }
+  | {
+      type: 'overflow';
+      /**
+       * Number of notifications that were dropped because the ring was full.  Anything the
+       * handler tracks from notifications may be out of date.
+       */
+      droppedCount: number;
+    };
+
+/**
+ * Must match `NOTIFICATION_RING_*` in the AWPs that write to notification rings
+ */
+const NOTIFICATION_RING_HEADER_BYTES = 4 * Int32Array.BYTES_PER_ELEMENT;
+const DROPPED_COUNT_IX = 2;
+
+/**
+ * Ring buffer of notifications written by an AWP and read on the UI thread, the mirror image of
+ * `ControlEventRing`.  Events have the same layout except that their time is the `currentTime`
+ * at which they were written.
+ *
+ * The header holds a third `Int32` after the indices which the writer increments for each
+ * notification it drops because the ring is full.  Dropped notifications are reported to the
+ * handler as a single `overflow` notification the next time the ring is drained.
+ */
+export class NotificationRing {
+  public readonly sab: SharedArrayBuffer;
+  private header: Int32Array;
+  private events: Float64Array;
+  private capacity: number;
+
+  constructor(capacity = DEFAULT_CAPACITY) {
+    this.capacity = capacity;
+    this.sab = new SharedArrayBuffer(
+      NOTIFICATION_RING_HEADER_BYTES +
+        capacity * CONTROL_EVENT_SIZE * Float64Array.BYTES_PER_ELEMENT
+    );
+    this.header = new Int32Array(this.sab, 0, 4);
+    this.events = new Float64Array(
+      this.sab,
+      NOTIFICATION_RING_HEADER_BYTES,
+      capacity * CONTROL_EVENT_SIZE
+    );
+  }
+
+  public drain(handler: (notification: Notification) => void) {
+    const writeIx = Atomics.load(this.header, WRITE_IX);
+    let readIx = this.header[READ_IX];
+    while (readIx !== writeIx) {
+      const offset = readIx * CONTROL_EVENT_SIZE;
+      handler({
+        type: this.events[offset] as NotificationType,
+        time: this.events[offset + 1],
+        param0: this.events[offset + 2],
+        param1: this.events[offset + 3],
+      });
+      readIx = (readIx + 1) % this.capacity;
+    }
+    Atomics.store(this.header, READ_IX, readIx);
+
+    const droppedCount = Atomics.exchange(this.header, DROPPED_COUNT_IX, 0);
+    if (droppedCount > 0) {
+      handler({ type: 'overflow', droppedCount });
+    }
+  }
+}
+
+/**
+ * Returns a new ring, or `null` if `SharedArrayBuffer` isn't available.  AWPs fall back to posting
+ * messages in that case.
+ */
+export const maybeCreateNotificationRing = (capacity?: number): NotificationRing | null =>
+  typeof SharedArrayBuffer === 'undefined' ? null : new NotificationRing(capacity);
+
+// All registered rings are drained from a single animation frame loop rather than each owner
+// polling its own
+const NotificationHandlers = new Map<NotificationRing, (notification: Notification) => void>();
+let AnimationFrameHandle: number | null = null;
+
+const drainNotificationRings = () => {
+  for (const [ring, handler] of NotificationHandlers) {
+    ring.drain(handler);
+  }
+  AnimationFrameHandle = requestAnimationFrame(drainNotificationRings);
+};
+
+/**
+ * Calls `handler` with each notification written to `ring` once per animation frame.  Returns a
+ * function that stops draining the ring.
+ */
+export const registerNotificationRing = (
+  ring: NotificationRing,
+  handler: (notification: Notification) => void
+) => {
+  NotificationHandlers.set(ring, handler);
+  if (AnimationFrameHandle === null) {
+    AnimationFrameHandle = requestAnimationFrame(drainNotificationRings);
+  }
+
+  return () => {
+    NotificationHandlers.delete(ring);
+    if (NotificationHandlers.size === 0 && AnimationFrameHandle !== null) {
+      cancelAnimationFrame(AnimationFrameHandle);
+      AnimationFrameHandle = null;
+    }
+  };
+};
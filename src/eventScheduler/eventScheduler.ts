@@ -1,7 +1,8 @@
-import { UnimplementedError, UnreachableException } from 'ameo-utils';
+import { UnimplementedError, UnreachableException, type IterableValueOf } from 'ameo-utils';
 import { useEffect, useState } from 'react';
 
-import { globalTempoCSN } from 'src/globalMenu/GlobalMenu';
+import { getGlobalBpm, globalTempoCSN, rampGlobalBpm } from 'src/globalMenu/GlobalMenu';
+import type { MIDIAccess } from 'src/patchNetwork/midiNode';
 import { getSentry } from 'src/sentry';
 import { retryAsync } from 'src/util';
 
@@ -35,7 +36,8 @@ type PendingEvent =
       eventType: MIDIEventType;
       param0: number;
       param1: number;
-    };
+    }
+  | { type: 'arpeggiator'; message: { type: string; arpID: number; [key: string]: any } };
 
 let PendingEvents: PendingEvent[] = [];
 
@@ -154,6 +156,16 @@ const callCb = (cbId: number) => {
 
 let beatManagerSAB: Float32Array | null = null;
 
+/**
+ * Tempo changes from tap tempo and external clock sync are ramped over this long so that things
+ * synced to the global tempo don't jump
+ */
+const TEMPO_SYNC_RAMP_SECONDS = 0.25;
+/**
+ * Nudges are small, so they're ramped more quickly than other tempo changes
+ */
+const NUDGE_RAMP_SECONDS = 0.05;
+
 /**
  * Returns the current beat of the global beat counter.  This value is updated directly from the web audio rendering thread
  * and shared with the main thread via `SharedArrayBuffer` meaning that it's quite accurate.
@@ -197,6 +209,10 @@ Promise.all([
         callCb(evt.data);
       } else if (evt.data.type === 'beatManagerSAB') {
         beatManagerSAB = evt.data.beatManagerSAB;
+      } else if (evt.data.type === 'tempoEstimate') {
+        rampGlobalBpm(evt.data.bpm, TEMPO_SYNC_RAMP_SECONDS);
+      } else if (evt.data.type === 'midiOutput') {
+        sendMIDIClockOutput(evt.data.messages);
       } else {
         console.warn('Unhandled event manager message: ', evt.data);
       }
@@ -228,6 +244,8 @@ Promise.all([
       } else if (evt.type === 'interactiveMIDIEvent') {
         const { eventType, param0, param1 } = evt;
         SchedulerHandle!.port.postMessage({ type: 'postMIDIEvent', eventType, param0, param1 });
+      } else if (evt.type === 'arpeggiator') {
+        SchedulerHandle!.port.postMessage(evt.message);
       } else {
         throw new UnreachableException();
       }
@@ -372,3 +390,235 @@ export const cancelAndRescheduleManyEvents = (
     newEvents,
   });
 };
+
+export enum ArpPattern {
+  Up = 0,
+  Down = 1,
+  UpDown = 2,
+  Random = 3,
+  AsPlayed = 4,
+}
+
+export interface ArpeggiatorConfig {
+  pattern: ArpPattern;
+  /**
+   * Number of octaves that held notes are repeated over, starting at the octave they were played in
+   */
+  octaveRange: number;
+  /**
+   * Length of each step in beats
+   */
+  rateBeats: number;
+  /**
+   * Fraction of each step that notes are held for, in (0, 1]
+   */
+  gateLength: number;
+  /**
+   * Fraction of half a step by which every other step is delayed, in [0, 1)
+   */
+  swing: number;
+}
+
+let arpIDCounter = 0;
+
+const postArpeggiatorMessage = (message: { type: string; arpID: number; [key: string]: any }) => {
+  if (!SchedulerHandle) {
+    PendingEvents.push({ type: 'arpeggiator', message });
+    return;
+  }
+
+  SchedulerHandle.port.postMessage(message);
+};
+
+/**
+ * Creates an arpeggiator on the audio thread that generates notes from the notes held on it.  The
+ * generated events are synced to the global beat counter and delivered to the MIDI event mailbox
+ * with ID `mailboxID` via the same path as scheduled MIDI events.
+ *
+ * Returns an ID that is used to refer to the arpeggiator in the other arpeggiator functions.
+ */
+export const createArpeggiator = (mailboxID: string, config: ArpeggiatorConfig): number => {
+  const arpID = arpIDCounter++;
+  postArpeggiatorMessage({ type: 'createArpeggiator', arpID, mailboxID });
+  postArpeggiatorMessage({ type: 'setArpeggiatorConfig', arpID, config });
+  return arpID;
+};
+
+export const deleteArpeggiator = (arpID: number) =>
+  postArpeggiatorMessage({ type: 'deleteArpeggiator', arpID });
+
+export const setArpeggiatorConfig = (arpID: number, config: ArpeggiatorConfig) =>
+  postArpeggiatorMessage({ type: 'setArpeggiatorConfig', arpID, config });
+
+export const arpeggiatorNoteOn = (arpID: number, note: number, velocity: number) =>
+  postArpeggiatorMessage({ type: 'arpeggiatorNoteOn', arpID, note, velocity });
+
+export const arpeggiatorNoteOff = (arpID: number, note: number) =>
+  postArpeggiatorMessage({ type: 'arpeggiatorNoteOff', arpID, note });
+
+export const clearArpeggiator = (arpID: number) =>
+  postArpeggiatorMessage({ type: 'clearArpeggiator', arpID });
+
+const postTempoSyncMessage = (message: { type: string; [key: string]: any }) => {
+  if (!SchedulerHandle) {
+    console.warn('Tempo sync used before scheduler initialized');
+    return;
+  }
+
+  SchedulerHandle.port.postMessage(message);
+};
+
+/**
+ * Records a tapped beat.  Once there have been two taps in a row, the global tempo is ramped to the
+ * average tempo of the recent taps, ignoring any that are far off from the rest.  Taps more than
+ * two seconds apart start over.
+ */
+export const tapTempo = () =>
+  postTempoSyncMessage({ type: 'tapTempo', time: performance.now() / 1000 });
+
+/**
+ * Bumps the global tempo by `deltaBPM`, which can be negative
+ */
+export const nudgeTempo = (deltaBPM: number) =>
+  rampGlobalBpm(getGlobalBpm() + deltaBPM, NUDGE_RAMP_SECONDS);
+
+/**
+ * Slaves the global tempo to an external clock like MIDI clock.  This should be called for each
+ * tick of the clock with the time that it was received, such as the `timeStamp` of a
+ * `MIDIMessageEvent`.
+ * The global tempo is ramped to the clock's tempo once per beat.
+ *
+ * @param timeStampMs Time of the tick in milliseconds on the same clock as `performance.now()`
+ */
+export const postExternalClockTick = (timeStampMs: number) =>
+  postTempoSyncMessage({ type: 'externalClockTick', time: timeStampMs / 1000 });
+
+/**
+ * Forgets the timing of previous external clock ticks.  Should be called when the external clock
+ * is stopped or started so that the gap isn't counted as a tick.
+ */
+export const resetExternalClock = () => postTempoSyncMessage({ type: 'resetExternalClock' });
+
+/**
+ * Sets the number of ticks per beat sent by the external clock.  Defaults to 24, which is what MIDI
+ * clock uses.
+ */
+export const setExternalClockTicksPerBeat = (ticksPerBeat: number) =>
+  postTempoSyncMessage({ type: 'setExternalClockTicksPerBeat', ticksPerBeat });
+
+export type BuiltinMIDIOutput = IterableValueOf<MIDIAccess['outputs']>;
+
+const MIDI_STOP = 0xfc;
+
+let MIDIClockOutput: BuiltinMIDIOutput | null = null;
+let MIDIClockSendsMMC = false;
+
+/**
+ * Sends the MIDI clock and transport messages generated on the audio thread to the selected output.
+ * They're timestamped in audio context time, so they're converted to the `performance.now()` clock
+ * that Web MIDI uses, lined up with the audio that's currently being heard.
+ */
+const sendMIDIClockOutput = (messages: { data: number[]; time: number }[]) => {
+  if (!MIDIClockOutput) {
+    return;
+  }
+
+  const { contextTime, performanceTime } = ctx.getOutputTimestamp();
+  for (const { data, time } of messages) {
+    const timestamp = performanceTime! + (time - contextTime!) * 1000;
+    try {
+      MIDIClockOutput.send(data, timestamp);
+    } catch (err) {
+      console.error('Error sending MIDI clock output', err);
+    }
+  }
+};
+
+export const getMIDIClockOutputConfig = () => ({
+  output: MIDIClockOutput,
+  sendMMC: MIDIClockSendsMMC,
+});
+
+/**
+ * Starts sending MIDI clock derived from the global beat counter to `output` so that external gear
+ * can follow it, along with start, stop, continue, and song position messages as the global beat
+ * counter is started and stopped.  If `sendMMC` is set, MIDI machine control play and stop commands
+ * are sent as well.  Passing `null` stops sending.
+ */
+export const setMIDIClockOutput = (output: BuiltinMIDIOutput | null, sendMMC = false) => {
+  if (!SchedulerHandle) {
+    console.error('Tried to set MIDI clock output before scheduler initialized');
+    return;
+  }
+
+  const prevOutput = MIDIClockOutput;
+  MIDIClockOutput = output;
+  MIDIClockSendsMMC = sendMMC;
+  if (prevOutput !== output) {
+    if (prevOutput && isStarted) {
+      // The stop message generated on the audio thread will go to the new output instead
+      prevOutput.send([MIDI_STOP]);
+    }
+    // Disabling and re-enabling makes the new output pick up from the current position
+    SchedulerHandle.port.postMessage({
+      type: 'setMIDIClockOutputEnabled',
+      enabled: false,
+      sendMMC,
+    });
+  }
+  if (output) {
+    SchedulerHandle.port.postMessage({ type: 'setMIDIClockOutputEnabled', enabled: true, sendMMC });
+  }
+};
+
+/**
+ * Converts a `performance.now()` timestamp in milliseconds into the audio context time of the audio
+ * that's heard at that moment
+ */
+const performanceTimeToContextTime = (timeStampMs: number) => {
+  const { contextTime, performanceTime } = ctx.getOutputTimestamp();
+  return contextTime! + (timeStampMs - performanceTime!) / 1000;
+};
+
+let lastPhaseSyncBPM: number | null = null;
+
+/**
+ * Starts or stops keeping the global beat counter in phase with a shared session timeline, like an
+ * Ableton Link session bridged in by a native helper or one shared between peers over WebRTC.  The
+ * host feeds the session's timeline in with `postPhaseSyncReport`.
+ *
+ * @param quantum Number of beats that phase is aligned over, as with Link.  The global beat counter
+ * can be offset from the session's beats by any multiple of it.
+ */
+export const setPhaseSyncEnabled = (enabled: boolean, quantum = 4) => {
+  lastPhaseSyncBPM = null;
+  postTempoSyncMessage({ type: 'setPhaseSync', enabled, quantum });
+};
+
+export interface PhaseSyncReport {
+  /**
+   * When the session was at `beat`, in milliseconds on the same clock as `performance.now()`
+   */
+  timeStampMs: number;
+  beat: number;
+  bpm: number;
+}
+
+/**
+ * Reports where the shared session's timeline is.  Reports can be jittery; they're smoothed on the
+ * audio thread and the global beat counter is sped up or slowed down slightly until it's in phase
+ * rather than jumping.  Changes to the session's tempo are applied to the global tempo.
+ */
+export const postPhaseSyncReport = ({ timeStampMs, beat, bpm }: PhaseSyncReport) => {
+  postTempoSyncMessage({
+    type: 'phaseSyncReport',
+    time: performanceTimeToContextTime(timeStampMs),
+    beat,
+    bpm,
+  });
+
+  if (lastPhaseSyncBPM !== bpm) {
+    lastPhaseSyncBPM = bpm;
+    rampGlobalBpm(bpm, TEMPO_SYNC_RAMP_SECONDS);
+  }
+};
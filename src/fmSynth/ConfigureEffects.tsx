@@ -1,10 +1,17 @@
 import { filterNils } from 'ameo-utils';
 import { Option } from 'funfix-core';
-import React, { useCallback, useMemo } from 'react';
+import * as R from 'ramda';
+import React, { useCallback, useEffect, useMemo, useState } from 'react';
 import ControlPanel from 'react-control-panel';
 
 import ConfigureParamSource from 'src/fmSynth/ConfigureParamSource';
-import { ButterworthFilterMode, SoftClipperAlgorithm, type Effect } from 'src/fmSynth/Effect';
+import {
+  ButterworthFilterMode,
+  EFFECT_PARAM_NAMES,
+  getEffectParamVizOffset,
+  SoftClipperAlgorithm,
+  type Effect,
+} from 'src/fmSynth/Effect';
 import type { ParamSource } from 'src/fmSynth/ParamSource';
 import type { AdsrParams } from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
 import FlatButton from 'src/misc/FlatButton';
@@ -569,8 +576,9 @@ const ConfigureCompressor: EffectConfigurator<'compressor'> = ({
 interface EffectManagementProps {
   effectIx: number;
   isBypassed: boolean;
+  mix: number;
   operatorEffects: (Effect | null)[];
-  setOperatorEffects: (newOperatorEffects: (Effect | null)[]) => void;
+  moveEffect: (fromEffectIx: number, toEffectIx: number) => void;
   onChange: (newEffect: Partial<Effect> | null) => void;
   collapsed: boolean;
   setCollapsed: (collapsed: boolean) => void;
@@ -579,8 +587,9 @@ interface EffectManagementProps {
 const EffectManagement: React.FC<EffectManagementProps> = ({
   effectIx,
   isBypassed,
+  mix,
   operatorEffects,
-  setOperatorEffects,
+  moveEffect,
   onChange,
   collapsed,
   setCollapsed,
@@ -602,11 +611,7 @@ const EffectManagement: React.FC<EffectManagementProps> = ({
               getSentry()?.captureMessage('Shift FM synth effect position', {
                 extra: { direction: 'up', effectIx },
               });
-              const newEffects = [...operatorEffects];
-              const swapEffect = operatorEffects[effectIx - 1];
-              newEffects[effectIx - 1] = newEffects[effectIx];
-              newEffects[effectIx] = swapEffect;
-              setOperatorEffects(newEffects);
+              moveEffect(effectIx, effectIx - 1);
             }}
           >
             ↑
@@ -618,11 +623,7 @@ const EffectManagement: React.FC<EffectManagementProps> = ({
               getSentry()?.captureMessage('Shift FM synth effect position', {
                 extra: { direction: 'down', effectIx },
               });
-              const newEffects = [...operatorEffects];
-              const swapEffect = operatorEffects[effectIx + 1];
-              newEffects[effectIx + 1] = newEffects[effectIx];
-              newEffects[effectIx] = swapEffect;
-              setOperatorEffects(newEffects);
+              moveEffect(effectIx, effectIx + 1);
             }}
           >
             ↓
@@ -640,6 +641,16 @@ const EffectManagement: React.FC<EffectManagementProps> = ({
         />
         <label style={{ fontSize: 10, color: '#888' }}>bypass</label>
         <div className='effect-title'>{operatorEffects[effectIx]?.type}</div>
+        <input
+          className='mix-slider'
+          type='range'
+          min={0}
+          max={1}
+          step={0.01}
+          value={mix}
+          title={`mix: ${Math.round(mix * 100)}% wet`}
+          onChange={evt => onChange({ mix: +evt.target.value })}
+        />
         <FlatButton className='toggle-collapsed' onClick={() => setCollapsed(!collapsed)}>
           {collapsed ? '>' : 'v'}
         </FlatButton>
@@ -688,15 +699,58 @@ const ConfigureEffectSpecific: React.FC<ConfigureEffectSpecificProps> = ({
   );
 };
 
+const formatParamValue = (value: number) =>
+  Math.abs(value) >= 100 ? value.toFixed(0) : value.toFixed(3);
+
+interface EffectParamVizProps {
+  paramVizBuffer: Float32Array;
+  offset: number;
+  paramNames: string[];
+}
+
+/**
+ * Shows the current value of each of the effect's params after modulation, as rendered by the most
+ * recently gated voice
+ */
+const EffectParamViz: React.FC<EffectParamVizProps> = ({ paramVizBuffer, offset, paramNames }) => {
+  const [values, setValues] = useState<number[]>([]);
+
+  useEffect(() => {
+    let frameHandle = requestAnimationFrame(function update() {
+      const newValues = Array.from(paramVizBuffer.subarray(offset, offset + paramNames.length));
+      setValues(values => (R.equals(values, newValues) ? values : newValues));
+      frameHandle = requestAnimationFrame(update);
+    });
+    return () => cancelAnimationFrame(frameHandle);
+  }, [paramVizBuffer, offset, paramNames.length]);
+
+  if (paramNames.length === 0) {
+    return null;
+  }
+
+  return (
+    <div className='effect-param-viz'>
+      {paramNames.map((name, paramIx) => (
+        <div key={name} className='effect-param-viz-value'>
+          <span className='effect-param-viz-name'>{name}</span>
+          {formatParamValue(values[paramIx] ?? 0)}
+        </div>
+      ))}
+    </div>
+  );
+};
+
 interface ConfigureEffectProps {
   effectIx: number;
   state: Effect;
   onChange: (newEffect: Partial<Effect> | null) => void;
   operatorEffects: (Effect | null)[];
-  setOperatorEffects: (newOperatorEffects: (Effect | null)[]) => void;
+  moveEffect: (fromEffectIx: number, toEffectIx: number) => void;
   adsrs: AdsrParams[];
   onAdsrChange: AdsrChangeHandler;
   vcId?: string;
+  paramVizBuffer?: Float32Array | null;
+  paramVizOffset: number;
 }
 
 const ConfigureEffect: React.FC<ConfigureEffectProps> = ({
@@ -704,17 +758,19 @@ const ConfigureEffect: React.FC<ConfigureEffectProps> = ({
   operatorEffects,
   state,
   onChange,
-  setOperatorEffects,
+  moveEffect,
   adsrs,
   onAdsrChange,
   vcId,
+  paramVizBuffer,
+  paramVizOffset,
 }) => {
   return (
     <div className='configure-effect'>
       <EffectManagement
         effectIx={effectIx}
         operatorEffects={operatorEffects}
-        setOperatorEffects={setOperatorEffects}
+        moveEffect={moveEffect}
         onChange={onChange}
         collapsed={state.isCollapsed ?? false}
         setCollapsed={useCallback(
@@ -722,16 +778,26 @@ const ConfigureEffect: React.FC<ConfigureEffectProps> = ({
           [onChange, state.isCollapsed]
         )}
         isBypassed={state.isBypassed ?? false}
+        mix={state.mix ?? 1}
       />
 
       {state.isCollapsed ? null : (
-        <ConfigureEffectSpecific
-          state={state}
-          onChange={onChange}
-          adsrs={adsrs}
-          onAdsrChange={onAdsrChange}
-          vcId={vcId}
-        />
+        <>
+          <ConfigureEffectSpecific
+            state={state}
+            onChange={onChange}
+            adsrs={adsrs}
+            onAdsrChange={onAdsrChange}
+            vcId={vcId}
+          />
+          {paramVizBuffer && !state.isBypassed ? (
+            <EffectParamViz
+              paramVizBuffer={paramVizBuffer}
+              offset={paramVizOffset}
+              paramNames={EFFECT_PARAM_NAMES[state.type]}
+            />
+          ) : null}
+        </>
       )}
     </div>
   );
@@ -740,11 +806,19 @@ const ConfigureEffect: React.FC<ConfigureEffectProps> = ({
 export interface ConfigureEffectsProps {
   state: (Effect | null)[];
   onChange: (ix: number, newState: Partial<Effect> | null) => void;
-  setOperatorEffects: (newOperatorEffects: (Effect | null)[]) => void;
+  /**
+   * Moves an effect to a new position in the chain, shifting the effects in between over by one
+   */
+  moveEffect: (fromEffectIx: number, toEffectIx: number) => void;
   adsrs: AdsrParams[];
   onAdsrChange: AdsrChangeHandler;
   operatorIx: number | null;
   vcId: string | undefined;
+  /**
+   * Buffer from `FMSynth.getEffectParamVizBuffer` that the modulated values of each effect's params
+   * are read from.  The values aren't shown if it isn't provided.
+   */
+  paramVizBuffer?: Float32Array | null;
 }
 
 interface ConfigureEffectsState {
@@ -767,7 +841,7 @@ class ConfigureEffects extends React.Component<ConfigureEffectsProps, ConfigureE
   private effectChangeHandlers: ((newEffect: Partial<Effect> | null) => void)[];
 
   public render() {
-    const { state, onChange, setOperatorEffects, operatorIx, adsrs, onAdsrChange, vcId } =
+    const { state, onChange, moveEffect, operatorIx, adsrs, onAdsrChange, vcId, paramVizBuffer } =
       this.props;
 
     return (
@@ -793,10 +867,12 @@ class ConfigureEffects extends React.Component<ConfigureEffectsProps, ConfigureE
               state={effect}
               onChange={this.effectChangeHandlers[i]}
               operatorEffects={state}
-              setOperatorEffects={setOperatorEffects}
+              moveEffect={moveEffect}
               adsrs={adsrs}
               onAdsrChange={onAdsrChange}
               vcId={vcId}
+              paramVizBuffer={paramVizBuffer}
+              paramVizOffset={getEffectParamVizOffset(operatorIx, i)}
             />
           ))}
         </div>
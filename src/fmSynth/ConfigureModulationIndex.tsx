@@ -1,9 +1,10 @@
-import React, { useCallback } from 'react';
+import React, { useCallback, useMemo } from 'react';
+import ControlPanel from 'react-control-panel';
 
 import type { AdsrChangeHandler } from 'src/fmSynth/ConfigureEffects';
 import ConfigureParamSource from 'src/fmSynth/ConfigureParamSource';
 import type { ParamSource } from 'src/fmSynth/ParamSource';
-import type { AdsrParams } from 'src/graphEditor/nodes/CustomAudio/FMSynth';
+import { OperatorConnectionType, type AdsrParams } from 'src/graphEditor/nodes/CustomAudio/FMSynth';
 import HelpIcon from 'src/misc/HelpIcon';
 
 const EXCLUDED_TYPES: ParamSource['type'][] = ['base frequency multiplier'];
@@ -13,11 +14,29 @@ const TITLE = (
   </>
 );
 
+const CONNECTION_TYPE_SETTINGS = [
+  {
+    type: 'select',
+    label: 'connection type',
+    options: {
+      FM: OperatorConnectionType.FrequencyModulation,
+      'hard sync': OperatorConnectionType.HardSync,
+      'ring mod': OperatorConnectionType.RingModulation,
+    },
+  },
+];
+
 interface ConfigureModulationIndexProps {
   srcOperatorIx: number;
   dstOperatorIx: number;
   modulationIndices: ParamSource[][];
   onChange: (srcOperatorIx: number, dstOperatorIx: number, newModulationIndex: ParamSource) => void;
+  connectionType: OperatorConnectionType;
+  onConnectionTypeChange: (
+    srcOperatorIx: number,
+    dstOperatorIx: number,
+    connectionType: OperatorConnectionType
+  ) => void;
   adsrs: AdsrParams[];
   onAdsrChange: AdsrChangeHandler;
   synthID: string;
@@ -29,12 +48,33 @@ const ConfigureModulationIndex: React.FC<ConfigureModulationIndexProps> = ({
   dstOperatorIx,
   modulationIndices,
   onChange,
+  connectionType,
+  onConnectionTypeChange,
   adsrs,
   onAdsrChange,
   synthID,
   vcId,
 }) => (
   <div className='configure-modulation-index' data-synth-id={synthID}>
+    <ControlPanel
+      width={500}
+      settings={CONNECTION_TYPE_SETTINGS}
+      state={useMemo(() => ({ 'connection type': connectionType }), [connectionType])}
+      onChange={useCallback(
+        (_key: string, val: string | number) =>
+          onConnectionTypeChange(srcOperatorIx, dstOperatorIx, +val),
+        [dstOperatorIx, onConnectionTypeChange, srcOperatorIx]
+      )}
+    />
+    {connectionType === OperatorConnectionType.FrequencyModulation ? null : (
+      <p className='connection-type-description'>
+        {connectionType === OperatorConnectionType.HardSync
+          ? 'Resets the phase of the destination operator each time this operator completes a ' +
+            'cycle.  Indices between 0 and 1 only partially reset it for a softer sync.'
+          : 'Multiplies the output of the destination operator by the output of this operator.  ' +
+            'Indices between 0 and 1 blend between the dry and ring modulated signal.'}
+      </p>
+    )}
     <ConfigureParamSource
       title={TITLE}
       state={modulationIndices[srcOperatorIx][dstOperatorIx]}
@@ -356,7 +356,7 @@ interface ConfigureOperatorProps {
   onChange: (newConfig: OperatorConfig) => void;
   effects: (Effect | null)[];
   onEffectsChange: (effectIx: number, newEffect: Effect | null) => void;
-  setEffects: (newEffects: (Effect | null)[]) => void;
+  moveEffect: (fromEffectIx: number, toEffectIx: number) => void;
   operatorIx: number;
   adsrs: AdsrParams[];
   onAdsrChange: AdsrChangeHandler;
@@ -366,6 +366,7 @@ interface ConfigureOperatorProps {
   sampleMappingStore: Writable<SampleMappingState>;
   registerGateUngateCallbacks: GateUngateCallbackRegistrar;
   useLegacyWavetableControls: boolean;
+  effectParamVizBuffer: Float32Array | null;
 }
 
 const OperatorTypeSettings = [
@@ -460,7 +461,7 @@ const ConfigureOperator: React.FC<ConfigureOperatorProps> = ({
   onChange,
   effects,
   onEffectsChange,
-  setEffects,
+  moveEffect,
   operatorIx,
   adsrs,
   onAdsrChange,
@@ -470,6 +471,7 @@ const ConfigureOperator: React.FC<ConfigureOperatorProps> = ({
   sampleMappingStore,
   registerGateUngateCallbacks,
   useLegacyWavetableControls,
+  effectParamVizBuffer,
 }) => {
   const operatorTypeState = useMemo(() => ({ 'operator type': config.type }), [config.type]);
 
@@ -590,10 +592,11 @@ const ConfigureOperator: React.FC<ConfigureOperatorProps> = ({
         operatorIx={operatorIx}
         state={effects}
         onChange={onEffectsChange}
-        setOperatorEffects={setEffects}
+        moveEffect={moveEffect}
         adsrs={adsrs}
         onAdsrChange={onAdsrChange}
         vcId={vcId}
+        paramVizBuffer={effectParamVizBuffer}
       />
     </div>
   );
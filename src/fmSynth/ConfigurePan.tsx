@@ -0,0 +1,98 @@
+import React, { useState } from 'react';
+import ControlPanel from 'react-control-panel';
+
+import type { AdsrChangeHandler } from 'src/fmSynth/ConfigureEffects';
+import ConfigureParamSource from 'src/fmSynth/ConfigureParamSource';
+import type { ParamSource } from 'src/fmSynth/ParamSource';
+import type { AdsrParams, OperatorPan } from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
+
+const EXCLUDED_TYPES: ParamSource['type'][] = ['base frequency multiplier'];
+
+const UNISON_SPREAD_SETTINGS = [{ type: 'range', label: 'unison spread', min: 0, max: 1 }];
+
+interface ConfigureOperatorPanProps {
+  operatorIx: number;
+  initialState: OperatorPan;
+  onChange: (newState: OperatorPan) => void;
+  adsrs: AdsrParams[];
+  onAdsrChange: AdsrChangeHandler;
+  vcId: string | undefined;
+}
+
+export const ConfigureOperatorPan: React.FC<ConfigureOperatorPanProps> = ({
+  operatorIx,
+  initialState,
+  onChange,
+  adsrs,
+  onAdsrChange,
+  vcId,
+}) => {
+  const [state, setState] = useState(initialState);
+  const handleChange = (newState: OperatorPan) => {
+    setState(newState);
+    onChange(newState);
+  };
+
+  return (
+    <div className='configure-pan'>
+      <ConfigureParamSource
+        title={`Operator ${operatorIx} pan`}
+        state={state.pan}
+        onChange={pan => handleChange({ ...state, pan })}
+        adsrs={adsrs}
+        onAdsrChange={onAdsrChange}
+        min={-1}
+        max={1}
+        excludedTypes={EXCLUDED_TYPES}
+        vcId={vcId}
+      />
+      <ControlPanel
+        width={500}
+        settings={UNISON_SPREAD_SETTINGS}
+        state={{ 'unison spread': state.unisonSpread }}
+        onChange={(_label: string, unisonSpread: number) =>
+          handleChange({ ...state, unisonSpread })
+        }
+      />
+    </div>
+  );
+};
+
+interface ConfigureVoicePanProps {
+  initialState: ParamSource;
+  onChange: (newState: ParamSource) => void;
+  adsrs: AdsrParams[];
+  onAdsrChange: AdsrChangeHandler;
+  vcId: string | undefined;
+}
+
+/**
+ * Pans the whole voice after the main effect chain.  Modulating it with a per-voice ADSR moves each
+ * note independently.
+ */
+export const ConfigureVoicePan: React.FC<ConfigureVoicePanProps> = ({
+  initialState,
+  onChange,
+  adsrs,
+  onAdsrChange,
+  vcId,
+}) => {
+  const [state, setState] = useState(initialState);
+
+  return (
+    <ConfigureParamSource
+      title='voice pan'
+      state={state}
+      onChange={newState => {
+        setState(newState);
+        onChange(newState);
+      }}
+      adsrs={adsrs}
+      onAdsrChange={onAdsrChange}
+      min={-1}
+      max={1}
+      excludedTypes={EXCLUDED_TYPES}
+      vcId={vcId}
+    />
+  );
+};
@@ -0,0 +1,77 @@
+import React, { useCallback, useMemo, useState } from 'react';
+import ControlPanel from 'react-control-panel';
+
+import {
+  SubOscillatorShape,
+  type VoiceLayersConfig,
+} from 'src/graphEditor/nodes/CustomAudio/FMSynth';
+
+const SETTINGS = [
+  { type: 'select', label: 'sub octave', options: { '-1': 1, '-2': 2 } },
+  {
+    type: 'select',
+    label: 'sub shape',
+    options: {
+      sine: SubOscillatorShape.Sine,
+      square: SubOscillatorShape.Square,
+      triangle: SubOscillatorShape.Triangle,
+      sawtooth: SubOscillatorShape.Sawtooth,
+    },
+  },
+  { type: 'range', label: 'sub level', min: 0, max: 1 },
+  { type: 'range', label: 'noise level', min: 0, max: 1 },
+  { type: 'range', label: 'noise decay ms', min: 0, max: 2000, step: 1 },
+];
+
+const KEY_BY_LABEL: { [label: string]: keyof VoiceLayersConfig } = {
+  'sub octave': 'subOctaves',
+  'sub shape': 'subShape',
+  'sub level': 'subLevel',
+  'noise level': 'noiseLevel',
+  'noise decay ms': 'noiseDecayMs',
+};
+
+interface ConfigureVoiceLayersProps {
+  initialState: VoiceLayersConfig;
+  onChange: (newState: VoiceLayersConfig) => void;
+}
+
+/**
+ * Sub-oscillator and noise layers that are added to every voice without using up an operator
+ */
+const ConfigureVoiceLayers: React.FC<ConfigureVoiceLayersProps> = ({ initialState, onChange }) => {
+  const [state, setState] = useState(initialState);
+
+  return (
+    <div className='configure-voice-layers'>
+      <ControlPanel
+        title='sub oscillator + noise'
+        width={500}
+        settings={SETTINGS}
+        state={useMemo(
+          () => ({
+            'sub octave': state.subOctaves,
+            'sub shape': state.subShape,
+            'sub level': state.subLevel,
+            'noise level': state.noiseLevel,
+            'noise decay ms': state.noiseDecayMs,
+          }),
+          [state]
+        )}
+        onChange={useCallback(
+          (label: string, val: number | string) => {
+            const newState = { ...state, [KEY_BY_LABEL[label]]: +val };
+            setState(newState);
+            onChange(newState);
+          },
+          [onChange, state]
+        )}
+      />
+      <p className='voice-layers-description'>
+        A noise decay of 0 holds the noise for the whole note.
+      </p>
+    </div>
+  );
+};
+
+export default ConfigureVoiceLayers;
@@ -70,8 +70,43 @@ export type EffectInner =
 export type Effect = EffectInner & {
   isBypassed?: boolean;
   isCollapsed?: boolean;
+  /**
+   * Wet/dry mix in [0, 1].  Defaults to 1 (fully wet).
+   */
+  mix?: number;
 };
 
+/**
+ * Names of the params that each effect renders, in the order they're written to the effect param
+ * visualization buffer.  Must match `get_params` for each effect in
+ * `engine/wavetable/src/fm/effects`.
+ */
+export const EFFECT_PARAM_NAMES: { [K in Effect['type']]: string[] } = {
+  'spectral warping': ['frequency', 'warp factor'],
+  wavecruncher: [
+    'top fold position',
+    'top fold width',
+    'bottom fold position',
+    'bottom fold width',
+  ],
+  bitcrusher: ['sample rate', 'bit depth'],
+  wavefolder: ['gain', 'offset'],
+  'soft clipper': ['pre gain', 'post gain'],
+  'butterworth filter': ['cutoff frequency'],
+  delay: ['delay samples', 'wet', 'dry', 'feedback'],
+  'moog filter': ['cutoff frequency', 'resonance', 'drive'],
+  'comb filter': ['delay samples', 'feedback delay samples', 'feedback gain', 'feedforward gain'],
+  compressor: [],
+};
+
+/**
+ * Returns the offset of an effect's param values within the buffer returned by
+ * `FMSynth.getEffectParamVizBuffer`; see `FMSynthContext::effect_param_viz_buf`.  `operatorIx` is
+ * `null` for the main effect chain, which comes after the effect chains of all 8 operators.
+ */
+export const getEffectParamVizOffset = (operatorIx: number | null, effectIx: number) =>
+  ((operatorIx ?? 8) * 16 + effectIx) * 4;
+
 type EncodedEffect = [
   number,
   EncodedParamSource | null,
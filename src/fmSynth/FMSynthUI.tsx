@@ -8,24 +8,34 @@ import './FMSynth.scss';
 import ConfigureEffects, { type AdsrChangeHandler } from 'src/fmSynth/ConfigureEffects';
 import ConfigureModulationIndex from 'src/fmSynth/ConfigureModulationIndex';
 import ConfigureOutputWeight from 'src/fmSynth/ConfigureOutputWeight';
+import { ConfigureOperatorPan, ConfigureVoicePan } from 'src/fmSynth/ConfigurePan';
 import ConfigureParamSource from 'src/fmSynth/ConfigureParamSource';
+import ConfigureVoiceLayers from 'src/fmSynth/ConfigureVoiceLayers';
 import type { Effect } from 'src/fmSynth/Effect';
 import type { GateUngateCallbackRegistrar } from 'src/fmSynth/midiSampleUI/types';
 import ModulationMatrix from 'src/fmSynth/ModulationMatrix';
 import { buildDefaultParamSource, type ParamSource } from 'src/fmSynth/ParamSource';
 import TrainingMIDIControlIndexContext from 'src/fmSynth/TrainingMIDIControlIndexContext';
 import type FMSynth from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
-import type { AdsrParams } from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
+import {
+  FM_SYNTH_PROFILING_SLOT_LABELS,
+  FM_SYNTH_PROFILING_STATS_OFFSET,
+  type AdsrParams,
+  type OperatorConnectionType,
+} from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
 import type MIDIControlValuesCache from 'src/graphEditor/nodes/CustomAudio/FMSynth/MIDIControlValuesCache';
 import type { SampleMappingState } from 'src/graphEditor/nodes/CustomAudio/FMSynth/sampleMapping';
+import ProfilingStats from 'src/graphEditor/nodes/CustomAudio/ModuleHost/ProfilingStats.svelte';
 import HelpIcon from 'src/misc/HelpIcon';
 import { WaveformIcon } from 'src/misc/Icons';
 import type { MIDINode } from 'src/patchNetwork/midiNode';
+import { mkSvelteComponentShim, type SveltePropTypesOf } from 'src/svelteUtils';
 import { classNameIncludes } from 'src/util';
 import { buildWavyJonesInstance, WavyJones } from 'src/visualizations/WavyJones';
 
 interface FMSynthState {
   modulationMatrix: ParamSource[][];
+  operatorConnectionTypes: OperatorConnectionType[][];
   outputWeights: ParamSource[];
   operatorConfigs: OperatorConfig[];
   operatorEffects: (Effect | null)[][];
@@ -94,28 +104,31 @@ const setOutput = (
 interface ConfigureMainEffectChainProps {
   mainEffectChain: (Effect | null)[];
   onChange: (ix: number, newEffect: Effect | null) => void;
-  setEffects: (newEffects: (Effect | null)[]) => void;
+  moveEffect: (fromEffectIx: number, toEffectIx: number) => void;
   adsrs: AdsrParams[];
   onAdsrChange: AdsrChangeHandler;
   vcId: string | undefined;
+  effectParamVizBuffer: Float32Array | null;
 }
 
 const ConfigureMainEffectChain: React.FC<ConfigureMainEffectChainProps> = ({
   mainEffectChain,
   onChange,
-  setEffects,
+  moveEffect,
   adsrs,
   onAdsrChange,
   vcId,
+  effectParamVizBuffer,
 }) => (
   <ConfigureEffects
     operatorIx={null}
     state={mainEffectChain}
     onChange={onChange}
-    setOperatorEffects={setEffects}
+    moveEffect={moveEffect}
     adsrs={adsrs}
     onAdsrChange={onAdsrChange}
     vcId={vcId}
+    paramVizBuffer={effectParamVizBuffer}
   />
 );
 
@@ -134,13 +147,24 @@ export type UISelection =
   | { type: 'operator'; index: number }
   | { type: 'modulationIndex'; srcOperatorIx: number; dstOperatorIx: number }
   | { type: 'outputWeight'; operatorIx: number }
+  | { type: 'voiceLayers' }
   | { type: 'oscilloscope' };
 
+const ProfilingStatsShim = mkSvelteComponentShim<SveltePropTypesOf<typeof ProfilingStats>>(
+  ProfilingStats
+);
+
 interface FMSynthUIProps {
   fmSynth: FMSynth;
   updateBackendModulation: BackendModulationUpdater;
   updateBackendOutput: BackendOutputUpdater;
   modulationMatrix: ParamSource[][];
+  operatorConnectionTypes: OperatorConnectionType[][];
+  onOperatorConnectionTypeChange: (
+    srcOperatorIx: number,
+    dstOperatorIx: number,
+    connectionType: OperatorConnectionType
+  ) => void;
   outputWeights: ParamSource[];
   operatorConfigs: OperatorConfig[];
   onOperatorConfigChange: (operatorIx: number, newConfig: OperatorConfig) => void;
@@ -171,6 +195,8 @@ const FMSynthUI: React.FC<FMSynthUIProps> = ({
   updateBackendModulation,
   updateBackendOutput,
   modulationMatrix,
+  operatorConnectionTypes,
+  onOperatorConnectionTypeChange,
   outputWeights,
   operatorConfigs,
   onOperatorConfigChange,
@@ -197,6 +223,7 @@ const FMSynthUI: React.FC<FMSynthUIProps> = ({
 }) => {
   const [state, setState] = useState<FMSynthState>({
     modulationMatrix,
+    operatorConnectionTypes,
     outputWeights,
     operatorConfigs,
     operatorEffects,
@@ -214,6 +241,7 @@ const FMSynthUI: React.FC<FMSynthUIProps> = ({
     [onSelectedUIChange]
   );
   const wavyJonesInstance = useRef<WavyJones | null>(null);
+  const [profilingEnabled, setProfilingEnabled] = useState(fmSynth.profilingEnabled);
 
   // If the fm synth instance changes, we need to re-initialize local state
   const lastFMSynthInst = useRef<FMSynth>(fmSynth);
@@ -225,6 +253,7 @@ const FMSynthUI: React.FC<FMSynthUIProps> = ({
 
     setState({
       modulationMatrix,
+      operatorConnectionTypes,
       outputWeights,
       operatorConfigs,
       operatorEffects,
@@ -240,6 +269,7 @@ const FMSynthUI: React.FC<FMSynthUIProps> = ({
     mainEffectChain,
     modulationMatrix,
     operatorConfigs,
+    operatorConnectionTypes,
     operatorEffects,
     outputWeights,
     wavetableState,
@@ -268,6 +298,18 @@ const FMSynthUI: React.FC<FMSynthUIProps> = ({
       )
     );
 
+  const onConnectionTypeChange = (
+    srcOperatorIx: number,
+    dstOperatorIx: number,
+    connectionType: OperatorConnectionType
+  ) => {
+    const newConnectionTypes = [...state.operatorConnectionTypes];
+    newConnectionTypes[srcOperatorIx] = [...newConnectionTypes[srcOperatorIx]];
+    newConnectionTypes[srcOperatorIx][dstOperatorIx] = connectionType;
+    setState({ ...state, operatorConnectionTypes: newConnectionTypes });
+    onOperatorConnectionTypeChange(srcOperatorIx, dstOperatorIx, connectionType);
+  };
+
   const onOutputWeightChange = (operatorIx: number, newOutputWeight: ParamSource) =>
     setState(setOutput(state, operatorIx, updateBackendOutput, newOutputWeight));
 
@@ -467,6 +509,7 @@ const FMSynthUI: React.FC<FMSynthUIProps> = ({
             [updateBackendModulation]
           )}
           modulationIndices={state.modulationMatrix}
+          connectionTypes={state.operatorConnectionTypes}
           operatorConfigs={state.operatorConfigs}
           outputWeights={state.outputWeights}
           selectedUI={selectedUI}
@@ -486,6 +529,14 @@ const FMSynthUI: React.FC<FMSynthUIProps> = ({
           >
             MAIN EFFECT CHAIN
           </div>
+          <div
+            role='button'
+            className='main-effect-chain-selector voice-layers-selector'
+            data-active={selectedUI?.type === 'voiceLayers' ? 'true' : 'false'}
+            onClick={() => setSelectedUI({ type: 'voiceLayers' })}
+          >
+            SUB + NOISE
+          </div>
           <div
             role='button'
             className='oscilloscope-button'
@@ -539,19 +590,51 @@ const FMSynthUI: React.FC<FMSynthUIProps> = ({
             vcId={vcId}
           />
         ) : null}
+        <ConfigureVoicePan
+          initialState={fmSynth.getVoicePan()}
+          onChange={newVoicePan => fmSynth.handleVoicePanChange(newVoicePan)}
+          adsrs={state.adsrs}
+          onAdsrChange={handleAdsrChange}
+          vcId={vcId}
+        />
+        <ControlPanel
+          state={{ 'profile voices': profilingEnabled }}
+          settings={[{ type: 'checkbox', label: 'profile voices' }]}
+          onChange={(_key: string, val: boolean) => {
+            fmSynth.setProfilingEnabled(val);
+            setProfilingEnabled(val);
+          }}
+        />
+        {profilingEnabled && fmSynth.getAudioThreadDataBuffer() ? (
+          <ProfilingStatsShim
+            sab={fmSynth.getAudioThreadDataBuffer()!}
+            offset={FM_SYNTH_PROFILING_STATS_OFFSET}
+            slotLabels={FM_SYNTH_PROFILING_SLOT_LABELS}
+          />
+        ) : null}
       </div>
       <div className='fm-synth-configuration'>
         {selectedUI?.type === 'mainEffectChain' ? (
           <ConfigureMainEffectChain
             mainEffectChain={state.mainEffectChain}
             onChange={handleMainEffectChainChange}
-            setEffects={(newEffects: (Effect | null)[]) => {
-              newEffects.forEach((effect, effectIx) => setEffect(null, effectIx, effect));
-              setState({ ...state, mainEffectChain: newEffects });
+            moveEffect={(fromEffectIx, toEffectIx) => {
+              fmSynth.moveEffect(null, fromEffectIx, toEffectIx);
+              setState({
+                ...state,
+                mainEffectChain: R.move(fromEffectIx, toEffectIx, state.mainEffectChain),
+              });
             }}
             adsrs={state.adsrs}
             onAdsrChange={handleAdsrChange}
             vcId={vcId}
+            effectParamVizBuffer={fmSynth.getEffectParamVizBuffer()}
+          />
+        ) : null}
+        {selectedUI?.type === 'voiceLayers' ? (
+          <ConfigureVoiceLayers
+            initialState={fmSynth.getVoiceLayers()}
+            onChange={newVoiceLayers => fmSynth.handleVoiceLayersChange(newVoiceLayers)}
           />
         ) : null}
         {selectedUI?.type === 'operator'
@@ -559,37 +642,49 @@ const FMSynthUI: React.FC<FMSynthUIProps> = ({
               const selectedOperatorIx = selectedUI.index;
 
               return (
-                <ConfigureOperator
-                  operatorIx={selectedUI.index}
-                  config={state.operatorConfigs[selectedOperatorIx]}
-                  onChange={newConf => onOperatorChange(selectedOperatorIx, newConf)}
-                  effects={state.operatorEffects[selectedOperatorIx]}
-                  onEffectsChange={handleEffectChange}
-                  setEffects={newEffects => {
-                    newEffects.forEach((effect, effectIx) =>
-                      setEffect(selectedOperatorIx, effectIx, effect)
-                    );
-                    setState({
-                      ...state,
-                      operatorEffects: R.set(
-                        R.lensIndex(selectedOperatorIx),
-                        newEffects,
-                        state.operatorEffects
-                      ),
-                    });
-                  }}
-                  adsrs={state.adsrs}
-                  onAdsrChange={handleAdsrChange}
-                  wavetableState={state.wavetableState}
-                  setWavetableState={newWavetableState => {
-                    setState(state => ({ ...state, wavetableState: newWavetableState }));
-                    setWavetableState(newWavetableState);
-                  }}
-                  vcId={vcId}
-                  sampleMappingStore={sampleMappingStore}
-                  registerGateUngateCallbacks={registerGateUngateCallbacks}
-                  useLegacyWavetableControls={useLegacyWavetableControls}
-                />
+                <>
+                  <ConfigureOperator
+                    operatorIx={selectedUI.index}
+                    config={state.operatorConfigs[selectedOperatorIx]}
+                    onChange={newConf => onOperatorChange(selectedOperatorIx, newConf)}
+                    effects={state.operatorEffects[selectedOperatorIx]}
+                    onEffectsChange={handleEffectChange}
+                    moveEffect={(fromEffectIx, toEffectIx) => {
+                      fmSynth.moveEffect(selectedOperatorIx, fromEffectIx, toEffectIx);
+                      setState({
+                        ...state,
+                        operatorEffects: R.adjust(
+                          selectedOperatorIx,
+                          effects => R.move(fromEffectIx, toEffectIx, effects),
+                          state.operatorEffects
+                        ),
+                      });
+                    }}
+                    adsrs={state.adsrs}
+                    onAdsrChange={handleAdsrChange}
+                    wavetableState={state.wavetableState}
+                    setWavetableState={newWavetableState => {
+                      setState(state => ({ ...state, wavetableState: newWavetableState }));
+                      setWavetableState(newWavetableState);
+                    }}
+                    vcId={vcId}
+                    sampleMappingStore={sampleMappingStore}
+                    registerGateUngateCallbacks={registerGateUngateCallbacks}
+                    useLegacyWavetableControls={useLegacyWavetableControls}
+                    effectParamVizBuffer={fmSynth.getEffectParamVizBuffer()}
+                  />
+                  <ConfigureOperatorPan
+                    key={selectedOperatorIx}
+                    operatorIx={selectedOperatorIx}
+                    initialState={fmSynth.getOperatorPans()[selectedOperatorIx]}
+                    onChange={newOperatorPan =>
+                      fmSynth.handleOperatorPanChange(selectedOperatorIx, newOperatorPan)
+                    }
+                    adsrs={state.adsrs}
+                    onAdsrChange={handleAdsrChange}
+                    vcId={vcId}
+                  />
+                </>
               );
             })()
           : null}
@@ -599,6 +694,10 @@ const FMSynthUI: React.FC<FMSynthUIProps> = ({
             dstOperatorIx={selectedUI.dstOperatorIx}
             modulationIndices={state.modulationMatrix}
             onChange={onModulationIndexChange}
+            connectionType={
+              state.operatorConnectionTypes[selectedUI.srcOperatorIx][selectedUI.dstOperatorIx]
+            }
+            onConnectionTypeChange={onConnectionTypeChange}
             adsrs={state.adsrs}
             onAdsrChange={handleAdsrChange}
             synthID={synthID}
@@ -656,6 +755,12 @@ const ConnectedFMSynthUIInner: React.FC<ConnectedFMSynthUIProps> = ({
       [synth]
     )}
     modulationMatrix={synth.getModulationMatrix()}
+    operatorConnectionTypes={synth.getOperatorConnectionTypes()}
+    onOperatorConnectionTypeChange={useCallback(
+      (srcOperatorIx: number, dstOperatorIx: number, connectionType: OperatorConnectionType) =>
+        synth.handleOperatorConnectionTypeChange(srcOperatorIx, dstOperatorIx, connectionType),
+      [synth]
+    )}
     outputWeights={synth.getOutputWeights()}
     operatorConfigs={synth.getOperatorConfigs()}
     onOperatorConfigChange={useCallback(
@@ -4,6 +4,7 @@ import type { OperatorConfig } from 'src/fmSynth/ConfigureOperator';
 import type { UISelection } from 'src/fmSynth/FMSynthUI';
 import type { ParamSource } from 'src/fmSynth/ParamSource';
 import TrainingMIDIControlIndexContext from 'src/fmSynth/TrainingMIDIControlIndexContext';
+import { OperatorConnectionType } from 'src/graphEditor/nodes/CustomAudio/FMSynth';
 import type MIDIControlValuesCache from 'src/graphEditor/nodes/CustomAudio/FMSynth/MIDIControlValuesCache';
 
 const formatOperatorConfig = (config: OperatorConfig) => {
@@ -174,7 +175,23 @@ const OutputWeightSquare: React.FC<OutputWeightSquareProps> = ({
   );
 };
 
-const getModulationIndexName = (srcOperatorIx: number, dstOperatorIx: number) => {
+const CONNECTION_TYPE_LABELS: Record<OperatorConnectionType, string | null> = {
+  [OperatorConnectionType.FrequencyModulation]: null,
+  [OperatorConnectionType.HardSync]: 'SYNC',
+  [OperatorConnectionType.RingModulation]: 'RING',
+};
+
+const getModulationIndexName = (
+  srcOperatorIx: number,
+  dstOperatorIx: number,
+  connectionType: OperatorConnectionType
+) => {
+  if (connectionType === OperatorConnectionType.HardSync) {
+    return `Operator ${srcOperatorIx + 1} -> ${dstOperatorIx + 1} Hard Sync`;
+  } else if (connectionType === OperatorConnectionType.RingModulation) {
+    return `Operator ${srcOperatorIx + 1} -> ${dstOperatorIx + 1} Ring Modulation`;
+  }
+
   if (srcOperatorIx === dstOperatorIx) {
     return `Operator ${srcOperatorIx + 1} Feedback`;
   }
@@ -188,6 +205,7 @@ interface ModulationMatrixProps {
   resetModulationIndex: (srcOperatorIx: number, dstOperatorIx: number) => void;
   onModulationIndexSelected: (srcOperatorIx: number, dstOperatorIx: number) => void;
   modulationIndices: ParamSource[][];
+  connectionTypes: OperatorConnectionType[][];
   operatorConfigs: OperatorConfig[];
   outputWeights: ParamSource[];
   onOutputWeightSelected: (operatorIx: number) => void;
@@ -200,6 +218,7 @@ export const ModulationMatrix: React.FC<ModulationMatrixProps> = ({
   resetModulationIndex,
   onModulationIndexSelected,
   modulationIndices,
+  connectionTypes,
   operatorConfigs,
   outputWeights,
   onOutputWeightSelected,
@@ -234,43 +253,50 @@ export const ModulationMatrix: React.FC<ModulationMatrixProps> = ({
             >
               {formatOperatorConfig(operatorConfigs[srcOperatorIx])}
             </div>
-            {row.map((val, dstOperatorIx) => (
-              <div
-                data-src-operator-ix={srcOperatorIx}
-                data-dst-operator-ix={dstOperatorIx}
-                data-active={
-                  selectedUI?.type === 'modulationIndex' &&
-                  selectedUI.srcOperatorIx === srcOperatorIx &&
-                  selectedUI.dstOperatorIx === dstOperatorIx
-                    ? 'true'
-                    : 'false'
-                }
-                className={`operator-square${
-                  srcOperatorIx === dstOperatorIx ? ' operator-square-feedback' : ''
-                }`}
-                data-synth-id={synthID}
-                key={dstOperatorIx}
-                onClick={() => onModulationIndexSelected(srcOperatorIx, dstOperatorIx)}
-                onMouseEnter={() => {
-                  setHoveredColIx(dstOperatorIx);
-                  setHoveredModulationEntity(getModulationIndexName(srcOperatorIx, dstOperatorIx));
-                }}
-                onMouseLeave={() => {
-                  if (
-                    hoveredModulationEntity === getModulationIndexName(srcOperatorIx, dstOperatorIx)
-                  ) {
-                    setHoveredModulationEntity(null);
-                  }
-                }}
-                onDoubleClick={() => {
-                  if (val.type === 'constant') {
-                    resetModulationIndex(srcOperatorIx, dstOperatorIx);
+            {row.map((val, dstOperatorIx) => {
+              const connectionType = connectionTypes[srcOperatorIx][dstOperatorIx];
+              const name = getModulationIndexName(srcOperatorIx, dstOperatorIx, connectionType);
+              return (
+                <div
+                  data-src-operator-ix={srcOperatorIx}
+                  data-dst-operator-ix={dstOperatorIx}
+                  data-active={
+                    selectedUI?.type === 'modulationIndex' &&
+                    selectedUI.srcOperatorIx === srcOperatorIx &&
+                    selectedUI.dstOperatorIx === dstOperatorIx
+                      ? 'true'
+                      : 'false'
                   }
-                }}
-              >
-                <FormattedParamSource param={val} />
-              </div>
-            ))}
+                  className={`operator-square${
+                    srcOperatorIx === dstOperatorIx ? ' operator-square-feedback' : ''
+                  }`}
+                  data-synth-id={synthID}
+                  key={dstOperatorIx}
+                  onClick={() => onModulationIndexSelected(srcOperatorIx, dstOperatorIx)}
+                  onMouseEnter={() => {
+                    setHoveredColIx(dstOperatorIx);
+                    setHoveredModulationEntity(name);
+                  }}
+                  onMouseLeave={() => {
+                    if (hoveredModulationEntity === name) {
+                      setHoveredModulationEntity(null);
+                    }
+                  }}
+                  onDoubleClick={() => {
+                    if (val.type === 'constant') {
+                      resetModulationIndex(srcOperatorIx, dstOperatorIx);
+                    }
+                  }}
+                >
+                  {CONNECTION_TYPE_LABELS[connectionType] ? (
+                    <span className='connection-type-label'>
+                      {CONNECTION_TYPE_LABELS[connectionType]}
+                    </span>
+                  ) : null}
+                  <FormattedParamSource param={val} />
+                </div>
+              );
+            })}
             <OutputWeightSquare
               onClick={() => onOutputWeightSelected(srcOperatorIx)}
               operatorIx={srcOperatorIx}
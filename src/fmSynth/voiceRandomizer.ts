@@ -0,0 +1,303 @@
+import * as R from 'ramda';
+
+import type { OperatorConfig } from 'src/fmSynth/ConfigureOperator';
+import type { ParamSource } from 'src/fmSynth/ParamSource';
+import type FMSynth from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
+import type { SynthVoicePreset } from 'src/redux/modules/presets';
+import { msToSamples } from 'src/util';
+
+export interface VoiceParamRange {
+  min: number;
+  max: number;
+  /**
+   * Locked params keep their current value
+   */
+  locked?: boolean;
+  /**
+   * Pick values uniformly in the log domain, for frequencies and similar
+   */
+  logScale?: boolean;
+  /**
+   * Only pick whole numbers, for choosing between the options of an enum
+   */
+  integer?: boolean;
+}
+
+export interface VoiceParamConstraint extends VoiceParamRange {
+  current: number;
+}
+
+/**
+ * Must match `CONSTRAINT_FLAG_*` in `engine/wavetable/src/fm/randomize.rs`
+ */
+const CONSTRAINT_FLAG_LOCKED = 1;
+const CONSTRAINT_FLAG_LOG_SCALE = 2;
+const CONSTRAINT_FLAG_INTEGER = 4;
+
+export const encodeVoiceParamConstraints = (constraints: VoiceParamConstraint[]): Float32Array =>
+  new Float32Array(
+    constraints.flatMap(({ min, max, current, locked, logScale, integer }) => [
+      min,
+      max,
+      current,
+      (locked ? CONSTRAINT_FLAG_LOCKED : 0) |
+        (logScale ? CONSTRAINT_FLAG_LOG_SCALE : 0) |
+        (integer ? CONSTRAINT_FLAG_INTEGER : 0),
+    ])
+  );
+
+interface RandomizableVoiceParam {
+  /**
+   * Used to look up user-provided ranges, like "operator 0 frequency multiplier"
+   */
+  key: string;
+  defaultRange: VoiceParamRange;
+  get: (preset: SynthVoicePreset) => number;
+  set: (preset: SynthVoicePreset, val: number) => void;
+}
+
+/**
+ * Oscillator types that share a config shape, so an operator can be switched between them
+ */
+const RANDOMIZABLE_OSCILLATOR_TYPES = [
+  'sine oscillator',
+  'square oscillator',
+  'triangle oscillator',
+  'sawtooth oscillator',
+] as const;
+
+const buildConstantParamSourceParam = (
+  key: string,
+  source: ParamSource | null | undefined,
+  defaultRange: VoiceParamRange,
+  getSource: (preset: SynthVoicePreset) => ParamSource
+): RandomizableVoiceParam[] => {
+  if (source?.type !== 'constant') {
+    return [];
+  }
+
+  return [
+    {
+      key,
+      defaultRange,
+      get: preset => (getSource(preset) as { value: number }).value,
+      set: (preset, val) => {
+        (getSource(preset) as { value: number }).value = val;
+      },
+    },
+  ];
+};
+
+const buildOperatorParams = (preset: SynthVoicePreset): RandomizableVoiceParam[] => {
+  const { operatorConfigs, modulationMatrix, outputWeights } = preset.fmSynthConfig;
+
+  return operatorConfigs.flatMap((config, operatorIx) => {
+    const params: RandomizableVoiceParam[] = [];
+    const getConfig = (preset: SynthVoicePreset) =>
+      preset.fmSynthConfig.operatorConfigs[operatorIx] as Extract<
+        OperatorConfig,
+        { frequency: ParamSource }
+      >;
+
+    const oscillatorTypeIx = RANDOMIZABLE_OSCILLATOR_TYPES.indexOf(config.type as any);
+    if (oscillatorTypeIx !== -1) {
+      params.push({
+        key: `operator ${operatorIx} type`,
+        defaultRange: { min: 0, max: RANDOMIZABLE_OSCILLATOR_TYPES.length - 1, integer: true },
+        get: () => oscillatorTypeIx,
+        set: (preset, val) => {
+          getConfig(preset).type = RANDOMIZABLE_OSCILLATOR_TYPES[val] as any;
+        },
+      });
+    }
+
+    if ('frequency' in config && config.frequency.type === 'base frequency multiplier') {
+      params.push({
+        key: `operator ${operatorIx} frequency multiplier`,
+        defaultRange: { min: 0.25, max: 8, logScale: true },
+        get: preset => (getConfig(preset).frequency as { multiplier: number }).multiplier,
+        set: (preset, val) => {
+          (getConfig(preset).frequency as { multiplier: number }).multiplier = val;
+        },
+      });
+    }
+
+    params.push(
+      ...buildConstantParamSourceParam(
+        `operator ${operatorIx} output weight`,
+        outputWeights[operatorIx],
+        { min: 0, max: 1 },
+        preset => preset.fmSynthConfig.outputWeights[operatorIx]
+      ),
+      ...modulationMatrix[operatorIx].flatMap((source, dstOperatorIx) =>
+        buildConstantParamSourceParam(
+          `modulation ${operatorIx} -> ${dstOperatorIx}`,
+          source,
+          { min: 0, max: 5 },
+          preset => preset.fmSynthConfig.modulationMatrix[operatorIx][dstOperatorIx]
+        )
+      )
+    );
+    return params;
+  });
+};
+
+const buildFilterParams = (preset: SynthVoicePreset): RandomizableVoiceParam[] => [
+  {
+    key: 'filter frequency',
+    defaultRange: { min: 40, max: 16_000, logScale: true },
+    get: preset => preset.filter.frequency,
+    set: (preset, val) => {
+      preset.filter.frequency = val;
+    },
+  },
+  ...(R.isNil(preset.filter.Q)
+    ? []
+    : [
+        {
+          key: 'filter Q',
+          defaultRange: { min: 0.1, max: 20, logScale: true },
+          get: (preset: SynthVoicePreset) => preset.filter.Q!,
+          set: (preset: SynthVoicePreset, val: number) => {
+            preset.filter.Q = val;
+          },
+        },
+      ]),
+];
+
+const buildEnvelopeParams = (preset: SynthVoicePreset): RandomizableVoiceParam[] => {
+  const envelopes = [
+    { name: 'gain envelope', getEnvelope: (p: SynthVoicePreset) => p.fmSynthConfig.gainEnvelope },
+    ...preset.fmSynthConfig.adsrs.map((_adsr, adsrIx) => ({
+      name: `adsr ${adsrIx}`,
+      getEnvelope: (p: SynthVoicePreset) => p.fmSynthConfig.adsrs[adsrIx],
+    })),
+  ];
+
+  return envelopes.flatMap(({ name, getEnvelope }) => {
+    const envelope = getEnvelope(preset);
+    // The first and last steps are pinned to the start and end of the envelope, so only their
+    // levels are randomized
+    const stepParams = envelope.steps.flatMap((_step, stepIx) => [
+      {
+        key: `${name} step ${stepIx} level`,
+        defaultRange: { min: 0, max: 1 },
+        get: (preset: SynthVoicePreset) => getEnvelope(preset).steps[stepIx].y,
+        set: (preset: SynthVoicePreset, val: number) => {
+          getEnvelope(preset).steps[stepIx].y = val;
+        },
+      },
+      ...(stepIx === 0 || stepIx === envelope.steps.length - 1
+        ? []
+        : [
+            {
+              key: `${name} step ${stepIx} position`,
+              defaultRange: { min: 0, max: 1 },
+              get: (preset: SynthVoicePreset) => getEnvelope(preset).steps[stepIx].x,
+              set: (preset: SynthVoicePreset, val: number) => {
+                getEnvelope(preset).steps[stepIx].x = val;
+              },
+            },
+          ]),
+    ]);
+
+    const lengthParams = buildConstantParamSourceParam(
+      `${name} length samples`,
+      envelope.lenSamples,
+      { min: msToSamples(20), max: msToSamples(4000), logScale: true },
+      preset => getEnvelope(preset).lenSamples
+    );
+
+    return [...stepParams, ...lengthParams];
+  });
+};
+
+const buildEffectParams = (preset: SynthVoicePreset): RandomizableVoiceParam[] =>
+  preset.fmSynthConfig.mainEffectChain.flatMap((effect, effectIx) => {
+    if (!effect) {
+      return [];
+    }
+
+    const getEffect = (preset: SynthVoicePreset) =>
+      preset.fmSynthConfig.mainEffectChain[effectIx] as Record<string, any>;
+    return Object.entries(effect).flatMap(([field, val]): RandomizableVoiceParam[] => {
+      const key = `effect ${effectIx} ${field}`;
+      if (field === 'mode' || field === 'algorithm') {
+        // `ButterworthFilterMode` and `SoftClipperAlgorithm`
+        return [
+          {
+            key,
+            defaultRange: { min: 0, max: field === 'mode' ? 2 : 3, integer: true },
+            get: preset => getEffect(preset)[field],
+            set: (preset, val) => {
+              getEffect(preset)[field] = val;
+            },
+          },
+        ];
+      }
+
+      // Effect params have wildly different scales, so by default they're only moved within a
+      // range around their current value
+      const current = (val as ParamSource | null)?.type === 'constant' ? val.value : 0;
+      return buildConstantParamSourceParam(
+        key,
+        val,
+        { min: Math.min(current * 2, 0), max: Math.max(current * 2, 1) },
+        preset => getEffect(preset)[field]
+      );
+    });
+  });
+
+/**
+ * Lists the params of `preset` that can be randomized, keyed by a human readable name
+ */
+export const getRandomizableVoiceParams = (preset: SynthVoicePreset): RandomizableVoiceParam[] => [
+  ...buildOperatorParams(preset),
+  ...buildFilterParams(preset),
+  ...buildEnvelopeParams(preset),
+  ...buildEffectParams(preset),
+];
+
+export interface RandomizeVoicePresetParams {
+  seed: number;
+  /**
+   * How far to move params from their current values towards random ones, from 0 (no change) to
+   * 1 (fully random)
+   */
+  amount: number;
+  /**
+   * Overrides the default range of params by key; see `getRandomizableVoiceParams`
+   */
+  ranges?: Record<string, Partial<VoiceParamRange>>;
+}
+
+/**
+ * Builds a new voice preset with random values for the oscillator, filter, envelope, and effect
+ * params of `preset`, within the default or provided ranges.  The result can be loaded into a
+ * synth or saved like any other preset.
+ */
+export const randomizeVoicePreset = async (
+  fmSynth: FMSynth,
+  preset: SynthVoicePreset,
+  { seed, amount, ranges = {} }: RandomizeVoicePresetParams
+): Promise<SynthVoicePreset> => {
+  const randomized = R.clone(preset);
+  const params = getRandomizableVoiceParams(randomized);
+  const constraints = params.map(
+    ({ key, defaultRange, get }): VoiceParamConstraint => ({
+      ...defaultRange,
+      ...ranges[key],
+      current: get(randomized),
+    })
+  );
+
+  const values = await fmSynth.randomizeVoiceParams(seed, constraints, amount);
+  params.forEach(({ set }, paramIx) => set(randomized, values[paramIx]));
+
+  // Steps have to stay in order for the envelope to be valid
+  [randomized.fmSynthConfig.gainEnvelope, ...randomized.fmSynthConfig.adsrs].forEach(envelope =>
+    envelope.steps.sort((a, b) => a.x - b.x)
+  );
+
+  return randomized;
+};
@@ -7,6 +7,14 @@ import { useQuery } from 'react-query';
 import { getLoggedInUsername } from 'src/api';
 import { parseUploadedFileAsText } from 'src/controls/FileUploader';
 import { renderModalWithControls } from 'src/controls/Modal';
+import {
+  getMIDIClockOutputConfig,
+  nudgeTempo,
+  setMIDIClockOutput,
+  tapTempo,
+  type BuiltinMIDIOutput,
+} from 'src/eventScheduler/eventScheduler';
+import { KeymapEditor } from 'src/keymap/KeymapEditor';
 import { LoginModal } from 'src/login/LoginModal';
 import {
   getLoginToken,
@@ -40,16 +48,66 @@ export const globalTempoCSN = new ConstantSourceNode(ctx);
 globalTempoCSN.offset.value = +(localStorage.getItem('globalTempo') ?? 120);
 globalTempoCSN.start();
 
+export const MIN_GLOBAL_BPM = 0.5;
+export const MAX_GLOBAL_BPM = 1200;
+
+let GlobalBpmChangeCBs: ((newGlobalTempo: number) => void)[] = [];
+
+/**
+ * Registers a callback to be called with the new tempo whenever the global tempo is set, including
+ * when it's set by tap tempo or external clock sync.  When the tempo is ramped, it's called once
+ * with the target tempo.
+ */
+export const registerGlobalBpmChangeCB = (cb: (newGlobalTempo: number) => void) =>
+  GlobalBpmChangeCBs.push(cb);
+
+export const unregisterGlobalBpmChangeCB = (cb: (newGlobalTempo: number) => void) => {
+  GlobalBpmChangeCBs = GlobalBpmChangeCBs.filter(ocb => ocb !== cb);
+};
+
 export const getGlobalBpm = () => globalTempoCSN.offset.value;
 
+const commitGlobalBpm = (newGlobalTempo: number) => {
+  localStorage.globalTempo = newGlobalTempo.toFixed(1);
+  GlobalBpmChangeCBs.forEach(cb => cb(newGlobalTempo));
+};
+
 export const setGlobalBpm = (newGlobalTempo: number) => {
+  // Cancel any in-progress ramp so that it doesn't override the new value
+  globalTempoCSN.offset.cancelScheduledValues(0);
   globalTempoCSN.offset.value = newGlobalTempo;
-  localStorage.globalTempo = newGlobalTempo.toFixed(1);
+  commitGlobalBpm(newGlobalTempo);
 };
 
+/**
+ * Glides the global tempo linearly from its current value to `newGlobalTempo` over `rampSeconds`.
+ * The beat counter and everything synced to the global tempo, like delays and LFOs, follow the ramp
+ * rather than jumping to the new tempo.
+ */
+export const rampGlobalBpm = (newGlobalTempo: number, rampSeconds: number) => {
+  newGlobalTempo = R.clamp(MIN_GLOBAL_BPM, MAX_GLOBAL_BPM, newGlobalTempo);
+  const param = globalTempoCSN.offset;
+  const now = ctx.currentTime;
+  param.cancelScheduledValues(now);
+  param.setValueAtTime(param.value, now);
+  param.linearRampToValueAtTime(newGlobalTempo, now + rampSeconds);
+  commitGlobalBpm(newGlobalTempo);
+};
+
+/**
+ * Amount that the global tempo is bumped by the nudge buttons
+ */
+const NUDGE_STEP_BPM = 0.5;
+
 const GlobalTempoControl: React.FC = () => {
   const [tempo, setTempo] = useState<string>(globalTempoCSN.offset.value.toFixed(1));
 
+  useEffect(() => {
+    const cb = (newGlobalTempo: number) => setTempo(newGlobalTempo.toFixed(1));
+    registerGlobalBpmChangeCB(cb);
+    return () => unregisterGlobalBpmChangeCB(cb);
+  }, []);
+
   return (
     <div className='global-tempo-control'>
       <p>Global Tempo</p>
@@ -61,7 +119,7 @@ const GlobalTempoControl: React.FC = () => {
           let parsed = Number.parseFloat(value);
 
           if (!Number.isNaN(parsed)) {
-            parsed = R.clamp(0.5, 1200, parsed);
+            parsed = R.clamp(MIN_GLOBAL_BPM, MAX_GLOBAL_BPM, parsed);
             setGlobalBpm(parsed);
             setTempo(parsed.toFixed(1));
           } else {
@@ -69,6 +127,74 @@ const GlobalTempoControl: React.FC = () => {
           }
         }}
       />
+      <div className='global-tempo-buttons'>
+        <button onClick={() => nudgeTempo(-NUDGE_STEP_BPM)} title='Nudge tempo down'>
+          -
+        </button>
+        <button onClick={tapTempo} title='Tap repeatedly to set the tempo'>
+          Tap
+        </button>
+        <button onClick={() => nudgeTempo(NUDGE_STEP_BPM)} title='Nudge tempo up'>
+          +
+        </button>
+      </div>
+    </div>
+  );
+};
+
+const MIDIClockOutputControl: React.FC = () => {
+  const [outputs, setOutputs] = useState<BuiltinMIDIOutput[] | null>(null);
+  const [config, setConfig] = useState(getMIDIClockOutputConfig());
+  const [error, setError] = useState<string | null>(null);
+
+  const loadOutputs = async () => {
+    try {
+      const access = await navigator.requestMIDIAccess();
+      setOutputs([...access.outputs.values()]);
+    } catch (err) {
+      console.error('Error requesting MIDI access', err);
+      setError(`${err}`);
+    }
+  };
+
+  const updateConfig = (output: BuiltinMIDIOutput | null, sendMMC: boolean) => {
+    setMIDIClockOutput(output, sendMMC);
+    setConfig(getMIDIClockOutputConfig());
+  };
+
+  return (
+    <div className='midi-clock-output-control'>
+      <p>MIDI Clock Out</p>
+      {error ? <span className='error'>{error}</span> : null}
+      {!outputs && !error ? <button onClick={loadOutputs}>Select Output...</button> : null}
+      {outputs ? (
+        <>
+          <select
+            value={config.output?.id ?? ''}
+            onChange={evt =>
+              updateConfig(
+                outputs.find(output => output.id === evt.target.value) ?? null,
+                config.sendMMC
+              )
+            }
+          >
+            <option value=''>None</option>
+            {outputs.map(output => (
+              <option key={output.id} value={output.id}>
+                {output.name}
+              </option>
+            ))}
+          </select>
+          <label>
+            <input
+              type='checkbox'
+              checked={config.sendMMC}
+              onChange={evt => updateConfig(config.output, evt.target.checked)}
+            />
+            Send MMC
+          </label>
+        </>
+      ) : null}
     </div>
   );
 };
@@ -129,6 +255,7 @@ const GlobalMenu: React.FC<GlobalMenuProps> = ({ closeMenu, engine, isOpen }) =>
     <div className='global-menu' role='menu' style={isOpen ? undefined : { right: -300 }}>
       <RetractGlobalMenuButton onClose={closeMenu} />
       <GlobalTempoControl />
+      <MIDIClockOutputControl />
       <GlobalMenuItem
         onClick={() => {
           serializeAndDownloadComposition();
@@ -172,6 +299,14 @@ const GlobalMenu: React.FC<GlobalMenuProps> = ({ closeMenu, engine, isOpen }) =>
           Load from File
         </>
       </GlobalMenuItem>
+      <GlobalMenuItem
+        onClick={() => {
+          closeMenu();
+          renderModalWithControls(KeymapEditor).catch(() => undefined);
+        }}
+      >
+        Keyboard Shortcuts
+      </GlobalMenuItem>
 
       <LoginStatus />
     </div>
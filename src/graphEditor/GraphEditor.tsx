@@ -21,6 +21,7 @@ import { getEngine, tryParseJson } from 'src/util';
 import { ViewContextDescriptors } from 'src/ViewContextManager/AddModulePicker';
 import {
   getIsVcHidden,
+  getVcPaneWidth,
   registerVcHideCb,
   unregisterVcHideCb,
 } from 'src/ViewContextManager/VcHideStatusRegistry';
@@ -320,21 +321,21 @@ const GraphEditor: React.FC<{ stateKey: string }> = ({ stateKey }) => {
     R.pick(['patchNetwork', 'activeViewContexts', 'isLoaded'], state.viewContextManager)
   );
 
+  const vcId = stateKey.split('_')[1];
+
   const [canvasHeight, setCanvasHeight] = useState(window.innerHeight - 130);
   useEffect(() => {
     const onResize = () => {
       const newHeight = window.innerHeight - 130;
       setCanvasHeight(newHeight);
-      const newWidth = curSelectedNodeRef.current
-        ? window.innerWidth - 500 - 44
-        : window.innerWidth - 44;
+      const paneWidth = getVcPaneWidth(vcId);
+      const newWidth = curSelectedNodeRef.current ? paneWidth - 500 - 44 : paneWidth - 44;
       lGraphCanvasRef.current?.resize(newWidth, newHeight);
     };
     window.addEventListener('resize', onResize);
     return () => window.removeEventListener('resize', onResize);
-  }, []);
+  }, [vcId]);
 
-  const vcId = stateKey.split('_')[1];
   const smallViewDOMId = `graph-editor_${vcId}_small-view-dom-id`;
 
   useEffect(() => {
@@ -532,7 +533,7 @@ const GraphEditor: React.FC<{ stateKey: string }> = ({ stateKey }) => {
           ref={canvasRef}
           id={stateKey + '_canvas'}
           className='graph-editor'
-          width={curSelectedNode ? window.innerWidth - 500 - 44 : window.innerWidth - 44}
+          width={curSelectedNode ? getVcPaneWidth(vcId) - 500 - 44 : getVcPaneWidth(vcId) - 44}
           height={canvasHeight}
           style={{ maxHeight: canvasHeight }}
         />
@@ -0,0 +1,163 @@
+import { Map as ImmMap } from 'immutable';
+import * as R from 'ramda';
+import { get, writable, type Writable } from 'svelte/store';
+
+import {
+  ArpPattern,
+  arpeggiatorNoteOff,
+  arpeggiatorNoteOn,
+  clearArpeggiator,
+  createArpeggiator,
+  deleteArpeggiator,
+  setArpeggiatorConfig,
+  type ArpeggiatorConfig,
+} from 'src/eventScheduler';
+import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio/CustomAudio';
+import type { OverridableAudioParam } from 'src/graphEditor/nodes/util';
+import type { AudioConnectables, ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
+import { MIDINode, type MIDIInputCbs } from 'src/patchNetwork/midiNode';
+import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils';
+import ArpeggiatorSmallView from './ArpeggiatorSmallView.svelte';
+
+export const buildDefaultArpeggiatorConfig = (): ArpeggiatorConfig => ({
+  pattern: ArpPattern.Up,
+  octaveRange: 1,
+  rateBeats: 0.25,
+  gateLength: 0.5,
+  swing: 0,
+});
+
+/**
+ * Arpeggiates the notes held on its MIDI input, from a MIDI input device or MIDI editor for
+ * example.  The arpeggio is generated on the audio thread by the event scheduler, which delivers it
+ * straight to the mailboxes of destinations with audio thread scheduling enabled such as the FM
+ * synth.  Destinations without a mailbox don't receive any notes.
+ *
+ * An arpeggiator is created in the event scheduler for each destination mailbox, and they're all
+ * re-created from the notes held here whenever the connected destinations change.
+ */
+export class ArpeggiatorNode implements ForeignNode {
+  private vcId: string;
+  private store: Writable<ArpeggiatorConfig>;
+  private inputMIDINode: MIDINode;
+  private outputMIDINode: MIDINode;
+  /**
+   * Velocity of each held note, keyed by note
+   */
+  private heldNotes: Map<number, number> = new Map();
+  /**
+   * ID of the arpeggiator feeding each destination, keyed by mailbox ID
+   */
+  private arpIDByMailboxID: Map<string, number> = new Map();
+
+  static typeName = 'Arpeggiator';
+  public nodeType = 'customAudio/arpeggiator';
+
+  public paramOverrides: {
+    [name: string]: { param: OverridableAudioParam; override: ConstantSourceNode };
+  } = {};
+
+  constructor(_ctx: AudioContext, vcId: string, params?: { [key: string]: any } | null) {
+    this.vcId = vcId;
+    this.store = writable({ ...buildDefaultArpeggiatorConfig(), ...(params ?? {}) });
+
+    this.inputMIDINode = new MIDINode(this.getMIDIInputCbs);
+    this.outputMIDINode = new MIDINode();
+    this.outputMIDINode.registerOnConnectionsChangedCb(this.syncArpeggiators);
+    this.store.subscribe(config =>
+      this.arpIDByMailboxID.forEach(arpID => setArpeggiatorConfig(arpID, config))
+    );
+
+    this.renderSmallView = mkSvelteContainerRenderHelper({
+      Comp: ArpeggiatorSmallView,
+      getProps: () => ({ store: this.store }),
+    });
+    this.cleanupSmallView = mkSvelteContainerCleanupHelper({ preserveRoot: true });
+  }
+
+  private getMIDIInputCbs = (): MIDIInputCbs => ({
+    onAttack: (note, velocity) => {
+      this.heldNotes.set(note, velocity);
+      this.arpIDByMailboxID.forEach(arpID => arpeggiatorNoteOn(arpID, note, velocity));
+    },
+    onRelease: (note, _velocity) => {
+      this.heldNotes.delete(note);
+      this.arpIDByMailboxID.forEach(arpID => arpeggiatorNoteOff(arpID, note));
+    },
+    onPitchBend: bendAmount => this.outputMIDINode.onPitchBend(bendAmount),
+    onClearAll: () => {
+      this.heldNotes.clear();
+      this.arpIDByMailboxID.forEach(clearArpeggiator);
+      this.outputMIDINode.clearAll();
+    },
+    onGenericControl: (controlIndex, controlValue) =>
+      this.outputMIDINode.outputCbs.forEach(cbs =>
+        cbs.onGenericControl?.(controlIndex, controlValue)
+      ),
+    onTuningChange: tuning =>
+      this.outputMIDINode.outputCbs.forEach(cbs => cbs.onTuningChange?.(tuning)),
+  });
+
+  /**
+   * Creates arpeggiators for newly connected destinations and deletes the ones of destinations that
+   * have been disconnected
+   */
+  private syncArpeggiators = () => {
+    const outputCbs = this.outputMIDINode.outputCbs;
+    if (outputCbs.some(cbs => !cbs.enableRxAudioThreadScheduling)) {
+      console.warn(
+        'Arpeggiator is connected to a MIDI destination without audio thread scheduling; it will ' +
+          "not receive the arpeggiator's notes"
+      );
+    }
+    const mailboxIDs = new Set(
+      outputCbs.flatMap(cbs => cbs.enableRxAudioThreadScheduling?.mailboxIDs ?? [])
+    );
+
+    for (const [mailboxID, arpID] of this.arpIDByMailboxID) {
+      if (!mailboxIDs.has(mailboxID)) {
+        deleteArpeggiator(arpID);
+        this.arpIDByMailboxID.delete(mailboxID);
+      }
+    }
+
+    const config = get(this.store);
+    for (const mailboxID of mailboxIDs) {
+      if (this.arpIDByMailboxID.has(mailboxID)) {
+        continue;
+      }
+
+      const arpID = createArpeggiator(mailboxID, config);
+      this.heldNotes.forEach((velocity, note) => arpeggiatorNoteOn(arpID, note, velocity));
+      this.arpIDByMailboxID.set(mailboxID, arpID);
+    }
+  };
+
+  public shutdown() {
+    this.arpIDByMailboxID.forEach(deleteArpeggiator);
+    this.arpIDByMailboxID.clear();
+  }
+
+  public serialize(): ArpeggiatorConfig {
+    return R.clone(get(this.store));
+  }
+
+  public buildConnectables(): AudioConnectables & { node: ArpeggiatorNode } {
+    return {
+      inputs: ImmMap<string, ConnectableInput>().set('midi', {
+        type: 'midi',
+        node: this.inputMIDINode,
+      }),
+      outputs: ImmMap<string, ConnectableOutput>().set('midi', {
+        type: 'midi',
+        node: this.outputMIDINode,
+      }),
+      vcId: this.vcId,
+      node: this,
+    };
+  }
+
+  // These are set dynamically at initialization time in the constructor
+  public renderSmallView: ForeignNode['renderSmallView'];
+  public cleanupSmallView: ForeignNode['cleanupSmallView'];
+}
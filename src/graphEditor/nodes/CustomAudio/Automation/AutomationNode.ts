@@ -0,0 +1,227 @@
+import { Map as ImmMap } from 'immutable';
+import * as R from 'ramda';
+import { get, writable, type Writable } from 'svelte/store';
+
+import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio';
+import {
+  AUTOMATION_MAX_LANE_COUNT,
+  buildDefaultAutomationNodeState,
+  type AutomationNodeState,
+  type RecordedAutomationLane,
+} from 'src/graphEditor/nodes/CustomAudio/Automation/types';
+import type { OverridableAudioParam } from 'src/graphEditor/nodes/util';
+import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
+import { updateConnectables } from 'src/patchNetwork/interface';
+import { getSentry } from 'src/sentry';
+import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils';
+import { AsyncOnce } from 'src/util';
+import AutomationNodeUI from './AutomationNodeUI.svelte';
+
+const AutomationWasmBytes = new AsyncOnce(
+  () =>
+    fetch(
+      process.env.ASSET_PATH +
+        'automation.wasm?cacheBust=' +
+        (window.location.href.includes('localhost') ? '' : crypto.randomUUID())
+    ).then(res => res.arrayBuffer()),
+  true
+);
+
+const ctx = new AudioContext();
+const AutomationAWPRegistered = new AsyncOnce(
+  () =>
+    ctx.audioWorklet.addModule(
+      process.env.ASSET_PATH +
+        'AutomationAWP.js?cacheBust=' +
+        (window.location.href.includes('localhost') ? '' : crypto.randomUUID())
+    ),
+  true
+);
+
+/**
+ * Called with param values played back from a recording.  See `AutomationNode.recordParamChange`.
+ */
+export type RecordedParamListener = (paramId: number, value: number) => void;
+
+/**
+ * Plays back automation lanes in time with the global beat counter.  Each lane is exposed as a
+ * separate output which can be connected to any parameter.
+ *
+ * Param changes made in the UI can also be recorded while the global beat counter is running and
+ * are played back to listeners registered for the module they came from.
+ */
+export default class AutomationNode implements ForeignNode {
+  private ctx: AudioContext;
+  private vcId: string | undefined;
+  private awpHandle: AudioWorkletNode | null = null;
+  private splitter: ChannelSplitterNode;
+  /**
+   * One per possible lane.  Outputs are named by index rather than lane name so that connections
+   * survive lanes being renamed.
+   */
+  private laneOutputs: GainNode[];
+  private store: Writable<AutomationNodeState> = writable(buildDefaultAutomationNodeState());
+  private lastLaneCount: number;
+  private recordingArmed: Writable<boolean> = writable(false);
+  private recordedParamListeners: Map<number, Set<RecordedParamListener>> = new Map();
+
+  static typeName = 'Automation';
+  public nodeType = 'customAudio/automation';
+
+  public paramOverrides: {
+    [name: string]: { param: OverridableAudioParam; override: ConstantSourceNode };
+  } = {};
+
+  constructor(ctx: AudioContext, vcId?: string, params?: { [key: string]: any } | null) {
+    this.ctx = ctx;
+    this.vcId = vcId;
+    this.splitter = ctx.createChannelSplitter(AUTOMATION_MAX_LANE_COUNT);
+    this.laneOutputs = new Array(AUTOMATION_MAX_LANE_COUNT).fill(null).map((_, laneIx) => {
+      const output = ctx.createGain();
+      this.splitter.connect(output, laneIx);
+      return output;
+    });
+
+    if (params) {
+      this.deserialize(params as AutomationNodeState);
+    }
+    this.lastLaneCount = get(this.store).lanes.length;
+
+    this.init().catch(err => {
+      console.error('Error initializing AutomationNode', err);
+      getSentry()?.captureException(err);
+    });
+
+    this.store.subscribe(this.onChange);
+
+    this.renderSmallView = mkSvelteContainerRenderHelper({
+      Comp: AutomationNodeUI,
+      getProps: () => ({
+        store: this.store,
+        recordingArmed: this.recordingArmed,
+        onClearRecording: this.clearRecording,
+      }),
+    });
+
+    this.cleanupSmallView = mkSvelteContainerCleanupHelper({ preserveRoot: true });
+  }
+
+  private async init() {
+    const [wasmBytes] = await Promise.all([
+      AutomationWasmBytes.get(),
+      AutomationAWPRegistered.get(),
+    ] as const);
+    this.awpHandle = new AudioWorkletNode(this.ctx, 'automation-awp', {
+      numberOfInputs: 0,
+      numberOfOutputs: 1,
+      outputChannelCount: [AUTOMATION_MAX_LANE_COUNT],
+    });
+    this.awpHandle.connect(this.splitter);
+    this.awpHandle.port.onmessage = evt => this.handleAWPMessage(evt.data);
+
+    this.awpHandle.port.postMessage({ type: 'setWasmBytes', wasmBytes });
+    this.onChange(get(this.store));
+    this.awpHandle.port.postMessage({
+      type: 'setRecordedLanes',
+      lanes: get(this.store).recordedLanes ?? [],
+    });
+    this.recordingArmed.subscribe(armed =>
+      this.awpHandle?.port.postMessage({ type: 'setRecordingArmed', armed })
+    );
+  }
+
+  private handleAWPMessage(data: any) {
+    switch (data.type) {
+      case 'recordedLanes': {
+        this.store.update(state => ({
+          ...state,
+          recordedLanes: data.lanes as RecordedAutomationLane[],
+        }));
+        break;
+      }
+      case 'recordedParamChanges': {
+        for (const { moduleId, paramId, value } of data.changes) {
+          this.recordedParamListeners.get(moduleId)?.forEach(listener => listener(paramId, value));
+        }
+        break;
+      }
+      default: {
+        console.warn('Unhandled message type from automation AWP: ', data.type);
+      }
+    }
+  }
+
+  /**
+   * Records a change to a param made in the UI if recording is armed and the global beat counter
+   * is running.  Module and param IDs are chosen by the caller and must be non-negative integers.
+   */
+  public recordParamChange(moduleId: number, paramId: number, value: number) {
+    this.awpHandle?.port.postMessage({ type: 'recordParamChange', moduleId, paramId, value });
+  }
+
+  /**
+   * Registers a listener for values played back from recordings of `moduleId`'s params.  Returns a
+   * function that unregisters it.
+   */
+  public registerRecordedParamListener(moduleId: number, listener: RecordedParamListener) {
+    let listeners = this.recordedParamListeners.get(moduleId);
+    if (!listeners) {
+      listeners = new Set();
+      this.recordedParamListeners.set(moduleId, listeners);
+    }
+    listeners.add(listener);
+    return () => {
+      listeners!.delete(listener);
+    };
+  }
+
+  public setRecordingArmed(armed: boolean) {
+    this.recordingArmed.set(armed);
+  }
+
+  private clearRecording = () => {
+    this.awpHandle?.port.postMessage({ type: 'clearRecording' });
+    this.store.update(state => ({ ...state, recordedLanes: [] }));
+  };
+
+  private onChange = (newState: AutomationNodeState) => {
+    this.awpHandle?.port.postMessage({ type: 'setState', state: newState });
+
+    if (newState.lanes.length !== this.lastLaneCount) {
+      this.lastLaneCount = newState.lanes.length;
+      if (this.vcId) {
+        updateConnectables(this.vcId, this.buildConnectables());
+      }
+    }
+  };
+
+  private deserialize(params: AutomationNodeState) {
+    this.store.set({
+      ...params,
+      lanes: params.lanes.slice(0, AUTOMATION_MAX_LANE_COUNT),
+    });
+  }
+
+  public serialize(): AutomationNodeState {
+    return R.clone(get(this.store));
+  }
+
+  public buildConnectables() {
+    const outputs = get(this.store).lanes.reduce(
+      (acc, _lane, laneIx) =>
+        acc.set(`lane ${laneIx + 1}`, { type: 'number', node: this.laneOutputs[laneIx] }),
+      ImmMap<string, ConnectableOutput>()
+    );
+
+    return {
+      inputs: ImmMap<string, ConnectableInput>(),
+      outputs,
+      vcId: this.vcId!,
+      node: this,
+    };
+  }
+
+  // These are set dynamically at initialization time in the constructor
+  public renderSmallView: ForeignNode['renderSmallView'];
+  public cleanupSmallView: ForeignNode['cleanupSmallView'];
+}
@@ -0,0 +1,110 @@
+/**
+ * Must match `MAX_LANE_COUNT` in `engine/automation/src/lib.rs`
+ */
+export const AUTOMATION_MAX_LANE_COUNT = 16;
+
+/**
+ * Shape of the segment between an automation point and the point after it
+ */
+export type AutomationCurve =
+  | { type: 'step' }
+  | { type: 'linear' }
+  | { type: 'exponential'; exponent: number };
+
+export interface AutomationPoint {
+  beat: number;
+  value: number;
+  curve: AutomationCurve;
+}
+
+export interface AutomationLaneState {
+  name: string;
+  /**
+   * Sorted by beat
+   */
+  points: AutomationPoint[];
+}
+
+/**
+ * Param changes recorded from the UI for a single param.  See `engine/automation/src/recorder.rs`.
+ */
+export interface RecordedAutomationLane {
+  moduleId: number;
+  paramId: number;
+  /**
+   * Sorted by beat
+   */
+  points: AutomationPoint[];
+}
+
+export interface AutomationNodeState {
+  lanes: AutomationLaneState[];
+  recordedLanes?: RecordedAutomationLane[];
+}
+
+export const buildDefaultAutomationLane = (laneIx: number): AutomationLaneState => ({
+  name: `Lane ${laneIx + 1}`,
+  points: [],
+});
+
+export const buildDefaultAutomationNodeState = (): AutomationNodeState => ({
+  lanes: [buildDefaultAutomationLane(0)],
+});
+
+const formatCurve = (curve: AutomationCurve): string => {
+  switch (curve.type) {
+    case 'step':
+      return 'step';
+    case 'linear':
+      return 'linear';
+    case 'exponential':
+      return `exp:${curve.exponent}`;
+  }
+};
+
+const parseCurve = (raw: string | undefined): AutomationCurve | null => {
+  if (!raw || raw === 'linear') {
+    return { type: 'linear' };
+  } else if (raw === 'step') {
+    return { type: 'step' };
+  } else if (raw.startsWith('exp:')) {
+    const exponent = +raw.slice(4);
+    return Number.isFinite(exponent) && exponent > 0 ? { type: 'exponential', exponent } : null;
+  }
+  return null;
+};
+
+/**
+ * Formats points as one `beat value curve` line per point for editing as text
+ */
+export const formatAutomationPoints = (points: AutomationPoint[]): string =>
+  points.map(({ beat, value, curve }) => `${beat} ${value} ${formatCurve(curve)}`).join('\n');
+
+/**
+ * Parses points in the format produced by `formatAutomationPoints`.  The curve can be omitted, in
+ * which case it defaults to linear.  The returned points are sorted by beat.
+ */
+export const parseAutomationPoints = (
+  text: string
+): { type: 'success'; points: AutomationPoint[] } | { type: 'error'; message: string } => {
+  const points: AutomationPoint[] = [];
+  const lines = text.split('\n').map(line => line.trim());
+  for (let lineIx = 0; lineIx < lines.length; lineIx++) {
+    if (!lines[lineIx]) {
+      continue;
+    }
+
+    const [rawBeat, rawValue, rawCurve, ...rest] = lines[lineIx].split(/\s+/);
+    const beat = +rawBeat;
+    const value = +rawValue;
+    const curve = parseCurve(rawCurve);
+    if (!Number.isFinite(beat) || beat < 0 || !Number.isFinite(value) || !curve || rest.length) {
+      return { type: 'error', message: `Invalid point on line ${lineIx + 1}` };
+    }
+    points.push({ beat, value, curve });
+  }
+
+  // Stable, so points at the same beat keep their order
+  points.sort((a, b) => a.beat - b.beat);
+  return { type: 'success', points };
+};
@@ -8,16 +8,68 @@ import BandSplitterSmallView, {
 } from 'src/graphEditor/nodes/CustomAudio/BandSplitter/BandSplitterSmallView';
 import { OverridableAudioParam } from 'src/graphEditor/nodes/util';
 import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
+import { setNodeLatency } from 'src/patchNetwork/latencyCompensation';
 import { mkContainerCleanupHelper, mkContainerRenderHelper } from 'src/reactUtils';
 
 export interface SerializedBandSplitterNode {
   gains: [number, number, number];
+  /**
+   * Splits the bands with linear-phase FIR crossovers instead of the biquad chains.  They don't
+   * smear transients or shift the phase of each band differently, but they add
+   * `LINEAR_PHASE_FIR_DELAY` samples of latency.
+   */
+  linearPhase?: boolean;
 }
 
 const buildDefaultSerializedBandSplitterNode = (): SerializedBandSplitterNode => ({
   gains: [1, 1, 1],
+  linearPhase: false,
 });
 
+/**
+ * Taps in each linear-phase crossover filter.  Odd so that the filters delay by a whole number of
+ * samples.
+ */
+const LINEAR_PHASE_FIR_LEN = 2047;
+const LINEAR_PHASE_FIR_DELAY = (LINEAR_PHASE_FIR_LEN - 1) / 2;
+
+/**
+ * Blackman-windowed sinc lowpass with unity gain at DC
+ */
+const buildLinearPhaseLowpass = (cutoffHz: number, sampleRate: number): Float32Array => {
+  const cutoff = (cutoffHz / sampleRate) * 2;
+  const taps = new Float32Array(LINEAR_PHASE_FIR_LEN);
+  for (let i = 0; i < LINEAR_PHASE_FIR_LEN; i++) {
+    const d = i - LINEAR_PHASE_FIR_DELAY;
+    const x = Math.PI * cutoff * d;
+    const sinc = x === 0 ? 1 : Math.sin(x) / x;
+    const windowPos = i / (LINEAR_PHASE_FIR_LEN - 1);
+    const blackman =
+      0.42 - 0.5 * Math.cos(2 * Math.PI * windowPos) + 0.08 * Math.cos(4 * Math.PI * windowPos);
+    taps[i] = cutoff * sinc * blackman;
+  }
+  const dcGain = taps.reduce((acc, tap) => acc + tap, 0);
+  return taps.map(tap => tap / dcGain);
+};
+
+/**
+ * Builds the impulse responses of the low, mid, and high bands.  The low band is a lowpass at the
+ * low crossover, the high band is the input minus a lowpass at the high crossover, and the mid band
+ * is what's left over, so the bands always sum back to the delayed input.
+ */
+const buildLinearPhaseBandKernels = (
+  lowCutoffHz: number,
+  highCutoffHz: number,
+  sampleRate: number
+): [Float32Array, Float32Array, Float32Array] => {
+  const low = buildLinearPhaseLowpass(lowCutoffHz, sampleRate);
+  const belowHigh = buildLinearPhaseLowpass(highCutoffHz, sampleRate);
+  const mid = belowHigh.map((tap, i) => tap - low[i]);
+  const high = belowHigh.map(tap => -tap);
+  high[LINEAR_PHASE_FIR_DELAY] += 1;
+  return [low, mid, high];
+};
+
 export default class BandSplitterNode implements ForeignNode {
   private ctx: AudioContext;
   private vcId: string | undefined;
@@ -25,6 +77,8 @@ export default class BandSplitterNode implements ForeignNode {
   private gainParams: [OverridableAudioParam, OverridableAudioParam, OverridableAudioParam];
   private outputGainNodes: [GainNode, GainNode, GainNode];
   private filterChains: [BiquadFilterNode[], BiquadFilterNode[], BiquadFilterNode[]];
+  private linearPhaseConvolvers: [ConvolverNode, ConvolverNode, ConvolverNode];
+  private linearPhase = false;
 
   static typeName = 'Band Splitter';
   public nodeType = 'customAudio/bandSplitter';
@@ -51,12 +105,8 @@ export default class BandSplitterNode implements ForeignNode {
       param.manualControl.offset.value = 1;
     });
 
-    const params = providedParams ?? buildDefaultSerializedBandSplitterNode();
-    if (params) {
-      this.deserialize(params);
-    }
-
-    this.filterChains = buildBandSplitterPreset().filterGroups.map((group, bandIx) => {
+    const preset = buildBandSplitterPreset();
+    this.filterChains = preset.filterGroups.map((group, bandIx) => {
       const constructedFilters = group.map(params => {
         const filter = new BiquadFilterNode(ctx);
         setFilter(filter, undefined, params, params.frequency);
@@ -69,6 +119,24 @@ export default class BandSplitterNode implements ForeignNode {
       return constructedFilters;
     }) as [BiquadFilterNode[], BiquadFilterNode[], BiquadFilterNode[]];
 
+    const [lowCutoffHz, , highCutoffHz] = preset.lockedFrequencyByGroup;
+    this.linearPhaseConvolvers = buildLinearPhaseBandKernels(
+      lowCutoffHz!,
+      highCutoffHz!,
+      ctx.sampleRate
+    ).map((kernel, bandIx) => {
+      const buffer = new AudioBuffer({ length: kernel.length, sampleRate: ctx.sampleRate });
+      buffer.copyToChannel(kernel, 0);
+      const convolver = new ConvolverNode(ctx, { buffer, disableNormalization: true });
+      convolver.connect(this.outputGainNodes[bandIx]);
+      return convolver;
+    }) as [ConvolverNode, ConvolverNode, ConvolverNode];
+
+    const params = providedParams ?? buildDefaultSerializedBandSplitterNode();
+    if (params) {
+      this.deserialize(params);
+    }
+
     this.renderSmallView = mkContainerRenderHelper({
       Comp: BandSplitterSmallView,
       getProps: (): BandSplitterSmallViewProps => ({
@@ -86,6 +154,7 @@ export default class BandSplitterNode implements ForeignNode {
         number,
         number
       ],
+      linearPhase: this.linearPhase,
     };
   };
 
@@ -93,8 +162,29 @@ export default class BandSplitterNode implements ForeignNode {
     params.gains?.forEach((gain, i) => {
       this.gainParams[i].manualControl.offset.value = gain;
     });
+    this.setLinearPhase(params.linearPhase ?? false);
   };
 
+  /**
+   * Routes the input through either the biquad chains or the linear-phase convolvers
+   */
+  private setLinearPhase(linearPhase: boolean) {
+    if (linearPhase === this.linearPhase) {
+      return;
+    }
+
+    this.linearPhase = linearPhase;
+    const firstFilters = this.filterChains.map(chain => chain[0]);
+    const [from, to] = linearPhase
+      ? [firstFilters, this.linearPhaseConvolvers]
+      : [this.linearPhaseConvolvers, firstFilters];
+    from.forEach(node => this.inputGainNode.disconnect(node));
+    to.forEach(node => this.inputGainNode.connect(node));
+    if (this.vcId) {
+      setNodeLatency(this.vcId, linearPhase ? LINEAR_PHASE_FIR_DELAY : 0);
+    }
+  }
+
   public buildConnectables() {
     return {
       inputs: ['low', 'mid', 'high'].reduce(
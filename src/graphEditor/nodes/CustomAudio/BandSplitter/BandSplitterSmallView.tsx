@@ -9,13 +9,16 @@ export interface BandSplitterSmallViewProps {
   onChange: (newState: SerializedBandSplitterNode) => void;
 }
 
-const BAND_SPLITTER_SETTINGS = ['low', 'mid', 'high'].map(bandName => ({
-  type: 'range',
-  label: `${bandName} band gain`,
-  min: 0,
-  max: 2,
-  step: 0.01,
-}));
+const BAND_SPLITTER_SETTINGS = [
+  ...['low', 'mid', 'high'].map(bandName => ({
+    type: 'range',
+    label: `${bandName} band gain`,
+    min: 0,
+    max: 2,
+    step: 0.01,
+  })),
+  { type: 'checkbox', label: 'linear phase' },
+];
 
 const BandSplitterSmallView: React.FC<BandSplitterSmallViewProps> = ({
   initialState,
@@ -28,12 +31,22 @@ const BandSplitterSmallView: React.FC<BandSplitterSmallViewProps> = ({
       'low band gain': Math.round(state.gains[0] * 1000) / 1000,
       'mid band gain': Math.round(state.gains[1] * 1000) / 1000,
       'high band gain': Math.round(state.gains[2] * 1000) / 1000,
+      'linear phase': state.linearPhase ?? false,
     }),
     [state]
   );
 
   const handleChange = useCallback(
     (key: string, val: any) => {
+      if (key === 'linear phase') {
+        setState(state => {
+          const newState: SerializedBandSplitterNode = { ...state, linearPhase: val };
+          onChange(newState);
+          return newState;
+        });
+        return;
+      }
+
       const gainIx = (
         {
           'low band gain': 0,
@@ -72,6 +85,10 @@ const BandSplitterSmallView: React.FC<BandSplitterSmallViewProps> = ({
         0-400hz, 400-3000hz, and 3000-∞hz. This is useful for effects such as multi-band compression
         or multi-band distortion.
       </p>
+      <p style={{ paddingLeft: 8, paddingRight: 8 }}>
+        Linear phase mode keeps transients intact and shifts all bands by the same amount, but it
+        delays the output by about 20ms. Parallel paths in the patch network are delayed to match.
+      </p>
     </div>
   );
 };
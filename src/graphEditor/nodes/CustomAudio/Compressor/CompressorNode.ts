@@ -3,10 +3,16 @@ import * as R from 'ramda';
 import { get, writable, type Writable } from 'svelte/store';
 
 import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio/CustomAudio';
+import {
+  NativeStateRequests,
+  type NativeModuleState,
+} from 'src/graphEditor/nodes/CustomAudio/nativeState';
 import DummyNode from 'src/graphEditor/nodes/DummyNode';
 import { OverridableAudioParam } from 'src/graphEditor/nodes/util';
 import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
 import { updateConnectables } from 'src/patchNetwork/interface';
+import { setNodeLatency } from 'src/patchNetwork/latencyCompensation';
+import { registerQualityGovernedPort } from 'src/qualityGovernor';
 import { getSentry } from 'src/sentry';
 import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils';
 import { AsyncOnce, SAMPLE_RATE, samplesToMs } from 'src/util';
@@ -18,6 +24,11 @@ export interface CompressorBandState {
   top_ratio: number;
   attack_ms: number;
   release_ms: number;
+  /**
+   * If set, the release time adapts to the input: fast after transients and slow after sustained
+   * level changes.  `release_ms` sets the fast release time.
+   */
+  auto_release: boolean;
   bottom_threshold: number;
   top_threshold: number;
   mix: number;
@@ -35,8 +46,27 @@ export interface CompressorNodeUIState {
   knee: number;
   sab: Float32Array | null;
   bypass: boolean;
+  /**
+   * Blend between the unprocessed input (0) and the compressed signal (1) for parallel
+   * compression.  The input is delayed to line up with the compressed signal.
+   */
   mix: number;
   lowLatencyMode: boolean;
+  /**
+   * Length of the window that the RMS level detection averages over, independent of lookahead
+   */
+  rmsWindowMs: number;
+  /**
+   * Highpasses the input below `inputDCBlockerCutoffHz` to keep DC from upstream distortion out of
+   * level detection
+   */
+  inputDCBlocker: boolean;
+  inputDCBlockerCutoffHz: number;
+  /**
+   * Skips the crossover filters and compresses the whole signal as a single band using the mid
+   * band's settings
+   */
+  widebandMode: boolean;
 }
 
 const DEFAULT_LOOKAHEAD_SAMPLES = SAMPLE_RATE / 10 / 3;
@@ -47,6 +77,7 @@ const buildDefaultCompressorBandState = (band: 'low' | 'mid' | 'high'): Compress
   top_ratio: { low: 444, mid: 66.7, high: 66.7 }[band],
   attack_ms: 3,
   release_ms: 250,
+  auto_release: false,
   bottom_threshold: { low: -40.8, mid: -41.8, high: -40.8 }[band],
   top_threshold: { low: -35.5, mid: -30.2, high: -33.8 }[band],
   mix: 1,
@@ -66,6 +97,10 @@ export const buildDefaultCompressorNodeUIState = (): CompressorNodeUIState => ({
   bypass: false,
   mix: 1,
   lowLatencyMode: false,
+  rmsWindowMs: 30,
+  inputDCBlocker: false,
+  inputDCBlockerCutoffHz: 10,
+  widebandMode: false,
 });
 
 const CompressorWasmBytes = new AsyncOnce(
@@ -90,6 +125,7 @@ export class CompressorNode implements ForeignNode {
   private vcId: string;
   private awpHandle: AudioWorkletNode | null = null;
   private store: Writable<CompressorNodeUIState> = writable(buildDefaultCompressorNodeUIState());
+  private stateRequests = new NativeStateRequests();
 
   // params
   private mix: OverridableAudioParam | DummyNode = new DummyNode();
@@ -118,6 +154,7 @@ export class CompressorNode implements ForeignNode {
   private highBandTopRatio: OverridableAudioParam | DummyNode = new DummyNode();
   private knee: OverridableAudioParam | DummyNode = new DummyNode();
   private lookaheadMs: OverridableAudioParam | DummyNode = new DummyNode();
+  private rmsWindowMs: OverridableAudioParam | DummyNode = new DummyNode();
 
   static typeName = 'Multi Compressor';
   public nodeType = 'customAudio/compressor';
@@ -142,7 +179,7 @@ export class CompressorNode implements ForeignNode {
 
     this.renderSmallView = mkSvelteContainerRenderHelper({
       Comp: CompressorSmallView,
-      getProps: () => ({ store: this.store }),
+      getProps: () => ({ store: this.store, onSelectABSlot: this.selectABSlot }),
       predicate: () => {
         unsubscribe = this.store.subscribe(state => this.onChange(state));
       },
@@ -173,6 +210,14 @@ export class CompressorNode implements ForeignNode {
         this.store.update(s => ({ ...s, sab: new Float32Array(sab) }));
         break;
       }
+      case 'state': {
+        this.stateRequests.handleReply(data as any);
+        break;
+      }
+      case 'latency': {
+        setNodeLatency(this.vcId, data.latencySamples);
+        break;
+      }
       default:
         console.warn('Unknown message from AWP', data);
     }
@@ -189,6 +234,7 @@ export class CompressorNode implements ForeignNode {
       channelCount: 1,
     });
     this.awpHandle.port.onmessage = (e: MessageEvent) => this.handleMessageFromAWP(e);
+    registerQualityGovernedPort(this.ctx, this.awpHandle.port);
 
     const params = this.awpHandle.parameters as Map<string, AudioParam>;
     this.mix = new OverridableAudioParam(ctx, params.get('mix')!, undefined, true);
@@ -322,6 +368,12 @@ export class CompressorNode implements ForeignNode {
     );
     this.knee = new OverridableAudioParam(ctx, params.get('knee')!, undefined, true);
     this.lookaheadMs = new OverridableAudioParam(ctx, params.get('lookahead_ms')!, undefined, true);
+    this.rmsWindowMs = new OverridableAudioParam(
+      ctx,
+      params.get('rms_window_ms')!,
+      undefined,
+      true
+    );
 
     const state = get(this.store);
     this.awpHandle.port.postMessage({ type: 'setBypassed', bypass: state.bypass });
@@ -336,6 +388,25 @@ export class CompressorNode implements ForeignNode {
     }
 
     this.awpHandle?.port.postMessage({ type: 'setBypassed', bypass: newState.bypass });
+    this.awpHandle?.port.postMessage({
+      type: 'setAutoRelease',
+      autoRelease: {
+        low: newState.low.auto_release,
+        mid: newState.mid.auto_release,
+        high: newState.high.auto_release,
+      },
+    });
+    this.awpHandle?.port.postMessage({
+      type: 'setInputDCBlocker',
+      inputDCBlocker: {
+        enabled: newState.inputDCBlocker,
+        cutoffHz: newState.inputDCBlockerCutoffHz,
+      },
+    });
+    this.awpHandle?.port.postMessage({
+      type: 'setWidebandMode',
+      widebandMode: newState.widebandMode,
+    });
     (this.mix as OverridableAudioParam).manualControl.offset.value = newState.mix;
     (this.preGain as OverridableAudioParam).manualControl.offset.value = newState.preGain;
     (this.postGain as OverridableAudioParam).manualControl.offset.value = newState.postGain;
@@ -382,6 +453,7 @@ export class CompressorNode implements ForeignNode {
     (this.lookaheadMs as OverridableAudioParam).manualControl.offset.value = newState.lowLatencyMode
       ? samplesToMs(DEFAULT_LOOKAHEAD_SAMPLES / 2.5)
       : samplesToMs(DEFAULT_LOOKAHEAD_SAMPLES);
+    (this.rmsWindowMs as OverridableAudioParam).manualControl.offset.value = newState.rmsWindowMs;
   };
 
   private deserialize(params: CompressorNodeUIState) {
@@ -396,18 +468,21 @@ export class CompressorNode implements ForeignNode {
         bottom_threshold: params.high.bottom_threshold ?? -34,
         top_threshold: params.high.top_threshold ?? -24,
         mix: params.high.mix ?? 1,
+        auto_release: params.high.auto_release ?? false,
       },
       mid: {
         ...params.mid,
         bottom_threshold: params.mid.bottom_threshold ?? -34,
         top_threshold: params.mid.top_threshold ?? -24,
         mix: params.mid.mix ?? 1,
+        auto_release: params.mid.auto_release ?? false,
       },
       low: {
         ...params.low,
         bottom_threshold: params.low.bottom_threshold ?? -34,
         top_threshold: params.low.top_threshold ?? -24,
         mix: params.low.mix ?? 1,
+        auto_release: params.low.auto_release ?? false,
       },
       bottomRatio: params.bottomRatio ?? 0.2,
       topRatio: params.topRatio ?? 12,
@@ -415,9 +490,81 @@ export class CompressorNode implements ForeignNode {
       sab: null,
       mix: params.mix ?? 1,
       lowLatencyMode: params.lowLatencyMode ?? false,
+      rmsWindowMs: params.rmsWindowMs ?? 30,
+      inputDCBlocker: params.inputDCBlocker ?? false,
+      inputDCBlockerCutoffHz: params.inputDCBlockerCutoffHz ?? 10,
+      widebandMode: params.widebandMode ?? false,
     });
   }
 
+  public getNativeState = async (): Promise<NativeModuleState> => {
+    if (!this.awpHandle) {
+      throw new Error('Compressor has not been initialized');
+    }
+    return this.stateRequests.get(this.awpHandle.port);
+  };
+
+  public setNativeState = async (state: NativeModuleState) => {
+    if (!this.awpHandle) {
+      throw new Error('Compressor has not been initialized');
+    }
+    this.applyNativeState(await this.stateRequests.set(this.awpHandle.port, state));
+  };
+
+  public selectABSlot = async (slot: number, fadeMs: number) => {
+    if (!this.awpHandle) {
+      throw new Error('Compressor has not been initialized');
+    }
+    this.applyNativeState(await this.stateRequests.selectAB(this.awpHandle.port, slot, fadeMs));
+  };
+
+  /**
+   * The AWP feeds params into the engine every frame, so state applied on the engine side has to
+   * be applied to the `AudioParam`s as well
+   */
+  private applyNativeState = (applied: NativeModuleState) => {
+    const num = (name: string, fallback: number) => {
+      const val = applied[name];
+      return typeof val === 'number' ? val : fallback;
+    };
+    const buildBand = (band: 'low' | 'mid' | 'high', prev: CompressorBandState) => {
+      const autoRelease = applied[`${band}_band_auto_release`];
+      return {
+        ...prev,
+        gain: num(`${band}_band_gain`, prev.gain),
+        attack_ms: num(`${band}_band_attack_ms`, prev.attack_ms),
+        release_ms: num(`${band}_band_release_ms`, prev.release_ms),
+        auto_release: typeof autoRelease === 'boolean' ? autoRelease : prev.auto_release,
+        bottom_threshold: num(`${band}_band_bottom_threshold_db`, prev.bottom_threshold),
+        top_threshold: num(`${band}_band_top_threshold_db`, prev.top_threshold),
+        bottom_ratio: num(`${band}_band_bottom_ratio`, prev.bottom_ratio),
+        top_ratio: num(`${band}_band_top_ratio`, prev.top_ratio),
+      };
+    };
+
+    const prev = get(this.store);
+    // Lookahead is only exposed as a choice between the normal and low latency periods
+    const lookaheadMs = applied.lookahead_ms;
+    const lowLatencyThresholdMs = samplesToMs(DEFAULT_LOOKAHEAD_SAMPLES * 0.7);
+    const newState: CompressorNodeUIState = {
+      ...prev,
+      mix: num('mix', prev.mix),
+      preGain: num('pre_gain', prev.preGain),
+      postGain: num('post_gain', prev.postGain),
+      low: buildBand('low', prev.low),
+      mid: buildBand('mid', prev.mid),
+      high: buildBand('high', prev.high),
+      knee: num('knee', prev.knee),
+      lowLatencyMode:
+        typeof lookaheadMs === 'number'
+          ? lookaheadMs < lowLatencyThresholdMs
+          : prev.lowLatencyMode,
+      rmsWindowMs: num('rms_window_ms', prev.rmsWindowMs),
+    };
+    this.store.set(newState);
+    this.onChange(newState);
+  };
+
   public serialize(): CompressorNodeUIState {
     return R.clone({ ...get(this.store), sab: null });
   }
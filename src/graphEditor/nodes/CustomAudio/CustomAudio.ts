@@ -10,7 +10,9 @@ import * as R from 'ramda';
 import type React from 'react';
 
 import { AddNode } from 'src/graphEditor/nodes/CustomAudio/AddNode/AddNode';
+import { ArpeggiatorNode } from 'src/graphEditor/nodes/CustomAudio/Arpeggiator/ArpeggiatorNode';
 import { MicNode } from 'src/graphEditor/nodes/CustomAudio/audioUtils';
+import AutomationNode from 'src/graphEditor/nodes/CustomAudio/Automation/AutomationNode';
 import BandSplitterNode from 'src/graphEditor/nodes/CustomAudio/BandSplitter/BandSplitterNode';
 import { CompressorNode } from 'src/graphEditor/nodes/CustomAudio/Compressor/CompressorNode';
 import { CSNSmallView } from 'src/graphEditor/nodes/CustomAudio/CSNSmallView';
@@ -18,25 +20,33 @@ import CustomBiquadFilterNodeSmallView from 'src/graphEditor/nodes/CustomAudio/C
 import CustomGainNodeSmallView from 'src/graphEditor/nodes/CustomAudio/CustomGainNodeSmallView';
 import CustomDelayNode from 'src/graphEditor/nodes/CustomAudio/Delay/Delay';
 import DistortionNode from 'src/graphEditor/nodes/CustomAudio/Distortion/Distortion';
+import { DuckerNode } from 'src/graphEditor/nodes/CustomAudio/Ducker/DuckerNode';
 import { EnvelopeGenerator } from 'src/graphEditor/nodes/CustomAudio/EnvelopeGenerator';
 import { Equalizer } from 'src/graphEditor/nodes/CustomAudio/Equalizer';
+import { ExciterNode } from 'src/graphEditor/nodes/CustomAudio/Exciter/ExciterNode';
 import FMSynth from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
 import { LevelDetectorNode } from 'src/graphEditor/nodes/CustomAudio/LevelDetectorNode/LevelDetectorNode';
 import { LFONode } from 'src/graphEditor/nodes/CustomAudio/LFONode';
 import MIDIQuantizerNode from 'src/graphEditor/nodes/CustomAudio/MIDIQuantizer/MIDIQuantizerNode';
 import { MIDIToFrequencyNode } from 'src/graphEditor/nodes/CustomAudio/MIDIToFrequency/MIDIToFrequency';
+import { MixBusNode } from 'src/graphEditor/nodes/CustomAudio/MixBus/MixBusNode';
 import { MixerNode } from 'src/graphEditor/nodes/CustomAudio/mixer/mixer';
 import { MBDLDNode } from 'src/graphEditor/nodes/CustomAudio/MultibandDiodeLadderDistortion/MultibandDiodeLadderDistortionNode';
 import { MultiplyNode } from 'src/graphEditor/nodes/CustomAudio/MultiplyNode/MultiplyNode';
 import { NativeCompressorSmallViewShim } from 'src/graphEditor/nodes/CustomAudio/NativeCompressor/NativeCompressorSmallViewShim';
 import { NoiseGenNode } from 'src/graphEditor/nodes/CustomAudio/NoiseGen';
+import { OnsetDetectorNode } from 'src/graphEditor/nodes/CustomAudio/OnsetDetector/OnsetDetectorNode';
 import QuantizerNode from 'src/graphEditor/nodes/CustomAudio/Quantizer/QuantizerNode';
+import { ReverbNode } from 'src/graphEditor/nodes/CustomAudio/Reverb/ReverbNode';
 import SamplePlayerNode from 'src/graphEditor/nodes/CustomAudio/SamplePlayer/SamplePlayer';
 import { ScaleAndShiftNode } from 'src/graphEditor/nodes/CustomAudio/ScaleAndShift';
 import { Sidechain } from 'src/graphEditor/nodes/CustomAudio/Sidechain';
+import { SpectralAnalyzerNode } from 'src/graphEditor/nodes/CustomAudio/SpectralAnalyzer/SpectralAnalyzerNode';
+import { SpectralGateNode } from 'src/graphEditor/nodes/CustomAudio/SpectralGate/SpectralGateNode';
 import StatisticsNode from 'src/graphEditor/nodes/CustomAudio/StatisticsNode/StatisticsNode';
 import { TypeConverterNode } from 'src/graphEditor/nodes/CustomAudio/TypeConverter/TypeConverterNode';
 import { VocoderNode } from 'src/graphEditor/nodes/CustomAudio/Vocoder/VocoderNode';
+import type { NativeModuleState } from 'src/graphEditor/nodes/CustomAudio/nativeState';
 import WaveTable from 'src/graphEditor/nodes/CustomAudio/WaveTable/WaveTable';
 import { OverridableAudioParam } from 'src/graphEditor/nodes/util';
 import type { AudioConnectables, ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
@@ -72,6 +82,20 @@ export interface ForeignNode<T = any> {
   renderSmallView?: (domId: string) => void;
   cleanupSmallView?: (domId: string) => void;
   listUsedSamples?: () => SampleDescriptor[];
+  /**
+   * Snapshots the params of the native engine module backing this node, if there is one.  See
+   * `nativeState.ts`.
+   */
+  getNativeState?: () => Promise<NativeModuleState>;
+  /**
+   * Restores a snapshot taken with `getNativeState` and updates the node's own state to match.
+   */
+  setNativeState?: (state: NativeModuleState) => Promise<void>;
+  /**
+   * Switches the native engine module to A/B `slot`, crossfading its params over `fadeMs`, and
+   * updates the node's own state to match.  See `NativeStateRequests.selectAB`.
+   */
+  selectABSlot?: (slot: number, fadeMs: number) => Promise<void>;
 }
 
 interface EnhanceAudioNodeParams<T> {
@@ -502,6 +526,38 @@ export const audioNodeGetters: {
   'customAudio/multibandDiodeLadderDistortion': {
     nodeGetter: MBDLDNode,
   },
+  'customAudio/automation': {
+    nodeGetter: AutomationNode,
+  },
+  'customAudio/reverb': {
+    nodeGetter: ReverbNode,
+  },
+  'customAudio/ducker': {
+    nodeGetter: DuckerNode,
+  },
+  'customAudio/spectralGate': {
+    nodeGetter: SpectralGateNode,
+  },
+  'customAudio/exciter': {
+    nodeGetter: ExciterNode,
+  },
+  'customAudio/mixBus': {
+    nodeGetter: MixBusNode,
+  },
+  'customAudio/spectralAnalyzer': {
+    nodeGetter: SpectralAnalyzerNode,
+  },
+  'customAudio/onsetDetector': {
+    nodeGetter: OnsetDetectorNode,
+  },
+  'customAudio/arpeggiator': {
+    nodeGetter: ArpeggiatorNode,
+    protoParams: {
+      onRemovedCustom: function () {
+        this.connectables.node.shutdown();
+      },
+    },
+  },
 };
 
 const registerCustomAudioNode = (
@@ -0,0 +1,238 @@
+import { Map as ImmMap } from 'immutable';
+import * as R from 'ramda';
+import { get, writable, type Writable } from 'svelte/store';
+
+import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio/CustomAudio';
+import DummyNode from 'src/graphEditor/nodes/DummyNode';
+import { OverridableAudioParam } from 'src/graphEditor/nodes/util';
+import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
+import { updateConnectables } from 'src/patchNetwork/interface';
+import { getSentry } from 'src/sentry';
+import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils';
+import { AsyncOnce } from 'src/util';
+import DuckerSmallView from './DuckerSmallView.svelte';
+
+/**
+ * Layout of the SAB shared by the ducker AWP.  Must match the `SAB_*_IX` constants in the `ducker`
+ * crate.
+ */
+export const DUCKER_SAB_GAIN_IX = 0;
+export const DUCKER_SAB_TRIGGER_COUNT_IX = 1;
+
+/**
+ * Values must match the `TriggerMode` enum in the `ducker` crate.
+ */
+export enum DuckerTriggerMode {
+  /**
+   * Triggered when the level of the trigger input rises above the threshold
+   */
+  Signal = 0,
+  /**
+   * Triggered at the start of each beat interval while the global transport is running
+   */
+  Beat = 1,
+}
+
+export interface DuckerParams {
+  depth: number;
+  attack_ms: number;
+  hold_ms: number;
+  release_ms: number;
+  shape: number;
+  threshold_db: number;
+}
+
+export interface DuckerNodeUIState extends DuckerParams {
+  triggerMode: DuckerTriggerMode;
+  /**
+   * Number of beats between triggers in `DuckerTriggerMode.Beat`
+   */
+  beatInterval: number;
+  sab: Float32Array | null;
+}
+
+const PARAM_NAMES: (keyof DuckerParams)[] = [
+  'depth',
+  'attack_ms',
+  'hold_ms',
+  'release_ms',
+  'shape',
+  'threshold_db',
+];
+
+export const buildDefaultDuckerNodeUIState = (): DuckerNodeUIState => ({
+  depth: 0.8,
+  attack_ms: 5,
+  hold_ms: 20,
+  release_ms: 200,
+  shape: 0,
+  threshold_db: -24,
+  triggerMode: DuckerTriggerMode.Signal,
+  beatInterval: 1,
+  sab: null,
+});
+
+const DuckerWasmBytes = new AsyncOnce(
+  () => fetch(process.env.ASSET_PATH + 'ducker.wasm').then(res => res.arrayBuffer()),
+  true
+);
+const ctx = new AudioContext();
+const DuckerAWPRegistered = new AsyncOnce(
+  () =>
+    ctx.audioWorklet.addModule(
+      process.env.ASSET_PATH +
+        'DuckerAWP.js?cacheBust=' +
+        (window.location.href.includes('localhost') ? '' : btoa(Math.random().toString()))
+    ),
+  true
+);
+
+export class DuckerNode implements ForeignNode {
+  private ctx: AudioContext;
+  private vcId: string;
+  private mainInput: GainNode;
+  private triggerInput: GainNode;
+  private dummyOutput = new DummyNode('DuckerNodeOutput');
+  private awpHandle: AudioWorkletNode | null = null;
+  private store: Writable<DuckerNodeUIState> = writable(buildDefaultDuckerNodeUIState());
+  private params: Record<keyof DuckerParams, OverridableAudioParam | DummyNode> = {
+    depth: new DummyNode(),
+    attack_ms: new DummyNode(),
+    hold_ms: new DummyNode(),
+    release_ms: new DummyNode(),
+    shape: new DummyNode(),
+    threshold_db: new DummyNode(),
+  };
+
+  static typeName = 'Ducker';
+  public nodeType = 'customAudio/ducker';
+
+  public paramOverrides: {
+    [name: string]: { param: OverridableAudioParam; override: ConstantSourceNode };
+  } = {};
+
+  constructor(ctx: AudioContext, vcId?: string, params?: { [key: string]: any } | null) {
+    this.ctx = ctx;
+    if (!vcId) {
+      throw new Error('vcId is required');
+    }
+    this.vcId = vcId;
+    this.mainInput = new GainNode(ctx);
+    this.triggerInput = new GainNode(ctx);
+
+    if (params) {
+      this.deserialize(params as Partial<DuckerNodeUIState>);
+    }
+
+    let unsubscribe: (() => void) | undefined;
+
+    this.renderSmallView = mkSvelteContainerRenderHelper({
+      Comp: DuckerSmallView,
+      getProps: () => ({ store: this.store }),
+      predicate: () => {
+        unsubscribe = this.store.subscribe(state => this.onChange(state));
+      },
+    });
+
+    this.cleanupSmallView = mkSvelteContainerCleanupHelper({
+      preserveRoot: true,
+      predicate: () => {
+        try {
+          unsubscribe?.();
+        } catch (e) {
+          console.warn('Error unsubscribing from ducker store', e);
+        }
+      },
+    });
+
+    this.init().catch(err => {
+      console.error('Error initializing ducker node', err);
+      getSentry()?.captureException(err);
+    });
+  }
+
+  private handleMessageFromAWP = (e: MessageEvent) => {
+    const data = e.data as Record<string, any>;
+    switch (data.type) {
+      case 'sab': {
+        const sab = data.sab as SharedArrayBuffer;
+        this.store.update(s => ({ ...s, sab: new Float32Array(sab) }));
+        break;
+      }
+      default:
+        console.warn('Unknown message from AWP', data);
+    }
+  };
+
+  private async init() {
+    const [wasmBytes] = await Promise.all([
+      DuckerWasmBytes.get(),
+      DuckerAWPRegistered.get(),
+    ] as const);
+    this.awpHandle = new AudioWorkletNode(this.ctx, 'ducker-awp', {
+      numberOfInputs: 2,
+      numberOfOutputs: 1,
+      channelCount: 2,
+      outputChannelCount: [2],
+    });
+    this.awpHandle.port.onmessage = (e: MessageEvent) => this.handleMessageFromAWP(e);
+
+    const awpParams = this.awpHandle.parameters as Map<string, AudioParam>;
+    for (const name of PARAM_NAMES) {
+      this.params[name] = new OverridableAudioParam(ctx, awpParams.get(name)!, undefined, true);
+    }
+
+    this.mainInput.connect(this.awpHandle, 0, 0);
+    this.triggerInput.connect(this.awpHandle, 0, 1);
+
+    this.awpHandle.port.postMessage({ type: 'setWasmBytes', wasmBytes });
+    this.onChange(get(this.store));
+    updateConnectables(this.vcId, this.buildConnectables());
+  }
+
+  private onChange = (newState: DuckerNodeUIState) => {
+    if (!this.awpHandle) {
+      return;
+    }
+
+    this.awpHandle.port.postMessage({ type: 'setTriggerMode', triggerMode: newState.triggerMode });
+    this.awpHandle.port.postMessage({
+      type: 'setBeatInterval',
+      beatInterval: newState.beatInterval,
+    });
+    for (const name of PARAM_NAMES) {
+      (this.params[name] as OverridableAudioParam).manualControl.offset.value = newState[name];
+    }
+  };
+
+  private deserialize(params: Partial<DuckerNodeUIState>) {
+    this.store.set({ ...buildDefaultDuckerNodeUIState(), ...params, sab: null });
+  }
+
+  public serialize(): DuckerNodeUIState {
+    return R.clone({ ...get(this.store), sab: null });
+  }
+
+  public buildConnectables() {
+    let inputs = ImmMap<string, ConnectableInput>()
+      .set('input', { node: this.mainInput, type: 'customAudio' })
+      .set('trigger', { node: this.triggerInput, type: 'customAudio' });
+    for (const name of PARAM_NAMES) {
+      inputs = inputs.set(name, { node: this.params[name], type: 'number' });
+    }
+
+    return {
+      vcId: this.vcId,
+      node: this,
+      inputs,
+      outputs: ImmMap<string, ConnectableOutput>().set('output', {
+        node: this.awpHandle ? this.awpHandle : this.dummyOutput,
+        type: 'customAudio',
+      }),
+    };
+  }
+
+  // These are set dynamically at initialization time in the constructor
+  public renderSmallView: ForeignNode['renderSmallView'];
+  public cleanupSmallView: ForeignNode['cleanupSmallView'];
+}
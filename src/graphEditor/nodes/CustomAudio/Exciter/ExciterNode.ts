@@ -0,0 +1,239 @@
+import { Map as ImmMap } from 'immutable';
+import * as R from 'ramda';
+import { get, writable, type Writable } from 'svelte/store';
+
+import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio/CustomAudio';
+import {
+  createModuleHostNode,
+  getModuleHostParamName,
+  mkModuleWasmBytes,
+  setModuleHostOutputGuardEnabled,
+  setModuleHostProfilingEnabled,
+  setModuleHostParams,
+} from 'src/graphEditor/nodes/CustomAudio/ModuleHost/moduleHost';
+import DummyNode from 'src/graphEditor/nodes/DummyNode';
+import { OverridableAudioParam } from 'src/graphEditor/nodes/util';
+import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
+import { updateConnectables } from 'src/patchNetwork/interface';
+import { getSentry } from 'src/sentry';
+import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils';
+import ExciterSmallView from './ExciterSmallView.svelte';
+
+/**
+ * Must match `BAND_COUNT` in the `exciter` crate.  The SAB holds the peak level of the harmonics
+ * generated by each band.
+ */
+export const EXCITER_BAND_COUNT = 3;
+export const EXCITER_BAND_NAMES = ['low', 'mid', 'high'];
+export const EXCITER_SAB_LEN = EXCITER_BAND_COUNT;
+
+/**
+ * Must match the param layout of the `exciter` crate.  Band params follow `output_gain_db`,
+ * interleaved per band as drive_db, amount, asymmetry, followed by the linear-phase flag.
+ */
+const PARAM_MIX_IX = 0;
+const PARAM_OUTPUT_GAIN_DB_IX = 1;
+const BAND_PARAMS_OFFSET = 2;
+const PARAM_LINEAR_PHASE_IX = BAND_PARAMS_OFFSET + EXCITER_BAND_COUNT * 3;
+
+export interface ExciterBandState {
+  drive_db: number;
+  /**
+   * How much of the harmonics generated by this band are mixed in, from 0 to 1
+   */
+  amount: number;
+  /**
+   * Skews the saturation curve to add even harmonics, from 0 to 1
+   */
+  asymmetry: number;
+}
+
+export interface ExciterNodeUIState {
+  /**
+   * Level of the generated harmonics mixed into the dry signal, from 0 to 1
+   */
+  mix: number;
+  output_gain_db: number;
+  bands: ExciterBandState[];
+  /**
+   * Splits the bands with linear-phase crossovers, which keeps transients intact at the cost of
+   * latency.  The latency is compensated for automatically on parallel paths in the patch network.
+   */
+  linear_phase: boolean;
+  bypass: boolean;
+  output_guard: boolean;
+  profiling: boolean;
+  sab: Float32Array | null;
+}
+
+const buildDefaultExciterBandState = (): ExciterBandState => ({
+  drive_db: 12,
+  amount: 1,
+  asymmetry: 0,
+});
+
+export const buildDefaultExciterNodeUIState = (): ExciterNodeUIState => ({
+  mix: 0.5,
+  output_gain_db: 0,
+  bands: R.times(buildDefaultExciterBandState, EXCITER_BAND_COUNT),
+  linear_phase: false,
+  bypass: false,
+  output_guard: false,
+  profiling: false,
+  sab: null,
+});
+
+const ExciterWasmBytes = mkModuleWasmBytes('exciter.wasm');
+
+export class ExciterNode implements ForeignNode {
+  private dummyInput = new DummyNode('ExciterNodeInput');
+  private dummyOutput = new DummyNode('ExciterNodeOutput');
+  private ctx: AudioContext;
+  private vcId: string;
+  private awpHandle: AudioWorkletNode | null = null;
+  private store: Writable<ExciterNodeUIState> = writable(buildDefaultExciterNodeUIState());
+  private mix: OverridableAudioParam | DummyNode = new DummyNode();
+  private outputGainDb: OverridableAudioParam | DummyNode = new DummyNode();
+
+  static typeName = 'Exciter';
+  public nodeType = 'customAudio/exciter';
+
+  public paramOverrides: {
+    [name: string]: { param: OverridableAudioParam; override: ConstantSourceNode };
+  } = {};
+
+  constructor(ctx: AudioContext, vcId?: string, params?: { [key: string]: any } | null) {
+    this.ctx = ctx;
+    if (!vcId) {
+      throw new Error('vcId is required');
+    }
+    this.vcId = vcId;
+
+    if (params) {
+      this.deserialize(params as Partial<ExciterNodeUIState>);
+    }
+
+    let unsubscribe: (() => void) | undefined;
+
+    this.renderSmallView = mkSvelteContainerRenderHelper({
+      Comp: ExciterSmallView,
+      getProps: () => ({ store: this.store }),
+      predicate: () => {
+        unsubscribe = this.store.subscribe(state => this.onChange(state));
+      },
+    });
+
+    this.cleanupSmallView = mkSvelteContainerCleanupHelper({
+      preserveRoot: true,
+      predicate: () => {
+        try {
+          unsubscribe?.();
+        } catch (e) {
+          console.warn('Error unsubscribing from exciter store', e);
+        }
+      },
+    });
+
+    this.init().catch(err => {
+      console.error('Error initializing exciter node', err);
+      getSentry()?.captureException(err);
+    });
+  }
+
+  private handleMessageFromAWP = (data: Record<string, any>) => {
+    switch (data.type) {
+      case 'capabilities':
+        // The param layout is fixed for this module, so there's nothing to pick up
+        break;
+      case 'sab': {
+        const sab = data.sab as SharedArrayBuffer;
+        this.store.update(s => ({ ...s, sab: new Float32Array(sab) }));
+        break;
+      }
+      default:
+        console.warn('Unknown message from AWP', data);
+    }
+  };
+
+  private async init() {
+    this.awpHandle = await createModuleHostNode(
+      this.ctx,
+      this.vcId,
+      ExciterWasmBytes,
+      2,
+      this.handleMessageFromAWP
+    );
+
+    const awpParams = this.awpHandle.parameters as Map<string, AudioParam>;
+    this.mix = new OverridableAudioParam(
+      this.ctx,
+      awpParams.get(getModuleHostParamName(PARAM_MIX_IX))!,
+      undefined,
+      true
+    );
+    this.outputGainDb = new OverridableAudioParam(
+      this.ctx,
+      awpParams.get(getModuleHostParamName(PARAM_OUTPUT_GAIN_DB_IX))!,
+      undefined,
+      true
+    );
+
+    this.onChange(get(this.store));
+    updateConnectables(this.vcId, this.buildConnectables());
+  }
+
+  private onChange = (newState: ExciterNodeUIState) => {
+    if (!this.awpHandle) {
+      return;
+    }
+
+    this.awpHandle.port.postMessage({ type: 'setBypassed', bypass: newState.bypass });
+    setModuleHostOutputGuardEnabled(this.awpHandle, newState.output_guard);
+    setModuleHostProfilingEnabled(this.awpHandle, newState.profiling);
+    setModuleHostParams(
+      this.awpHandle,
+      BAND_PARAMS_OFFSET,
+      newState.bands.flatMap(band => [band.drive_db, band.amount, band.asymmetry])
+    );
+    setModuleHostParams(this.awpHandle, PARAM_LINEAR_PHASE_IX, [newState.linear_phase ? 1 : 0]);
+    (this.mix as OverridableAudioParam).manualControl.offset.value = newState.mix;
+    (this.outputGainDb as OverridableAudioParam).manualControl.offset.value =
+      newState.output_gain_db;
+  };
+
+  private deserialize(params: Partial<ExciterNodeUIState>) {
+    const defaults = buildDefaultExciterNodeUIState();
+    this.store.set({
+      ...defaults,
+      ...params,
+      bands: defaults.bands.map((band, bandIx) => ({ ...band, ...params.bands?.[bandIx] })),
+      sab: null,
+    });
+  }
+
+  public serialize(): ExciterNodeUIState {
+    return R.clone({ ...get(this.store), sab: null });
+  }
+
+  public buildConnectables() {
+    return {
+      vcId: this.vcId,
+      node: this,
+      inputs: ImmMap<string, ConnectableInput>()
+        .set('input', {
+          node: this.awpHandle ? this.awpHandle : this.dummyInput,
+          type: 'customAudio',
+        })
+        .set('mix', { node: this.mix, type: 'number' })
+        .set('output_gain_db', { node: this.outputGainDb, type: 'number' }),
+      outputs: ImmMap<string, ConnectableOutput>().set('output', {
+        node: this.awpHandle ? this.awpHandle : this.dummyOutput,
+        type: 'customAudio',
+      }),
+    };
+  }
+
+  // These are set dynamically at initialization time in the constructor
+  public renderSmallView: ForeignNode['renderSmallView'];
+  public cleanupSmallView: ForeignNode['cleanupSmallView'];
+}
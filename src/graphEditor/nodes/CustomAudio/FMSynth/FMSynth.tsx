@@ -5,6 +5,15 @@ import * as React from 'react';
 import { get, writable, type Writable } from 'svelte/store';
 
 import type { AudioThreadData } from 'src/controls/adsr2/adsr2';
+import {
+  ControlEventType,
+  maybeCreateControlEventRing,
+  maybeCreateNotificationRing,
+  NotificationType,
+  registerNotificationRing,
+  type ControlEventRing,
+  type Notification,
+} from 'src/eventRing';
 import {
   buildDefaultOperatorConfig,
   deserializeWavetableState,
@@ -22,6 +31,10 @@ import {
   encodeParamSource,
   type ParamSource,
 } from 'src/fmSynth/ParamSource';
+import {
+  encodeVoiceParamConstraints,
+  type VoiceParamConstraint,
+} from 'src/fmSynth/voiceRandomizer';
 import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio';
 import MIDIControlValuesCache from 'src/graphEditor/nodes/CustomAudio/FMSynth/MIDIControlValuesCache';
 import {
@@ -35,14 +48,33 @@ import DummyNode from 'src/graphEditor/nodes/DummyNode';
 import type { OverridableAudioParam } from 'src/graphEditor/nodes/util';
 import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
 import { MIDINode } from 'src/patchNetwork/midiNode';
+import { registerQualityGovernedPort } from 'src/qualityGovernor';
 import { mkContainerCleanupHelper, mkContainerRenderHelper } from 'src/reactUtils';
 import { getSample, hashSampleDescriptor, type SampleDescriptor } from 'src/sampleLibrary';
 import { getSentry } from 'src/sentry';
+import { isTwelveTET, TWELVE_TET, type Tuning } from 'src/tuning';
 import { AsyncOnce, normalizeEnvelope } from 'src/util';
 
 const OPERATOR_COUNT = 8;
 const VOICE_COUNT = 10;
 
+/**
+ * Profiling stats follow the ADSR phases in the audio thread data buffer; must match
+ * `ADSR_PHASE_BUF_LENGTH` in `FMSynthAWP.js`.  See `FMSynthContext::profiler` for the layout.
+ */
+export const FM_SYNTH_PROFILING_STATS_OFFSET = 256;
+export const FM_SYNTH_PROFILING_SLOT_LABELS = [
+  'total',
+  ...R.times(voiceIx => `voice ${voiceIx}`, VOICE_COUNT),
+];
+
+/**
+ * The modulated values of each effect's params follow the profiling stats, which take up two values
+ * per slot.  See `FMSynthContext::effect_param_viz_buf` for the layout.
+ */
+const FM_SYNTH_EFFECT_PARAM_VIZ_OFFSET =
+  FM_SYNTH_PROFILING_STATS_OFFSET + FM_SYNTH_PROFILING_SLOT_LABELS.length * 2;
+
 const ctx = new AudioContext();
 
 const RegisterFMSynthAWP = new AsyncOnce(
@@ -64,6 +96,83 @@ const buildDefaultModulationIndices = (): ParamSource[][] => {
   return indices;
 };
 
+/**
+ * Must match `OperatorConnectionType` in the `wavetable` crate.  The modulation index of each pair
+ * of operators controls the amount for all connection types.
+ */
+export enum OperatorConnectionType {
+  FrequencyModulation = 0,
+  /**
+   * The destination operator's phase is reset whenever the source operator's phase wraps around
+   */
+  HardSync = 1,
+  /**
+   * The destination operator's output is multiplied by the source operator's output
+   */
+  RingModulation = 2,
+}
+
+const buildDefaultOperatorConnectionTypes = (): OperatorConnectionType[][] =>
+  new Array(OPERATOR_COUNT)
+    .fill(null)
+    .map(() => new Array(OPERATOR_COUNT).fill(OperatorConnectionType.FrequencyModulation));
+
+/**
+ * Must match `SubOscillatorShape` in the `wavetable` crate
+ */
+export enum SubOscillatorShape {
+  Sine = 0,
+  Square = 1,
+  Triangle = 2,
+  Sawtooth = 3,
+}
+
+/**
+ * Sub-oscillator and noise layers mixed into every voice before the main effect chain.  See
+ * `engine/wavetable/src/fm/layers.rs`.
+ */
+export interface VoiceLayersConfig {
+  /**
+   * 1 or 2 octaves below the voice's base frequency
+   */
+  subOctaves: number;
+  subShape: SubOscillatorShape;
+  subLevel: number;
+  noiseLevel: number;
+  /**
+   * Time for the noise to fade out after each note starts.  0 holds it for the whole note.
+   */
+  noiseDecayMs: number;
+}
+
+export const buildDefaultVoiceLayersConfig = (): VoiceLayersConfig => ({
+  subOctaves: 1,
+  subShape: SubOscillatorShape.Sine,
+  subLevel: 0,
+  noiseLevel: 0,
+  noiseDecayMs: 40,
+});
+
+/**
+ * Stereo placement of an operator within each voice.  See `engine/wavetable/src/fm/panning.rs`.
+ */
+export interface OperatorPan {
+  /**
+   * -1 is hard left, 1 is hard right
+   */
+  pan: ParamSource;
+  /**
+   * How far the operator's unison oscillators are spread across the stereo field, from 0 to 1.
+   * Has no effect on operators without unison.
+   */
+  unisonSpread: number;
+}
+
+export const buildDefaultOperatorPan = (): OperatorPan => ({
+  pan: { type: 'constant', value: 0 },
+  unisonSpread: 0,
+});
+
 /**
  * Corresponds to `RampFn` in the Wasm engine
  */
@@ -131,12 +240,70 @@ interface ADSRParamsWithLenSamples extends AdsrParams {
   lenSamples: { type: 'constant'; value: number } | { type: 'beats to samples'; value: number };
 }
 
+export interface BounceNoteEvent {
+  beat: number;
+  midiNumber: number;
+  isAttack: boolean;
+  /**
+   * Index of the stem the note is rendered to when bouncing stems.  Defaults to 0.
+   */
+  stemIx?: number;
+}
+
+export interface BounceParams {
+  events: BounceNoteEvent[];
+  /**
+   * Number of separate buffers to render when bouncing stems.  Defaults to 1.
+   */
+  stemCount?: number;
+  bpm: number;
+  /**
+   * Beat at which the bounce ends, not including the tail
+   */
+  endBeat: number;
+  /**
+   * Extra time rendered after `endBeat` to let released notes ring out
+   */
+  tailSeconds: number;
+  onProgress?: (progress: number) => void;
+}
+
+/**
+ * The parts of a serialized FM synth config, such as the `fmSynthConfig` of a voice preset, that
+ * are used when morphing between presets
+ */
+export interface FMSynthMorphPreset {
+  modulationMatrix: ParamSource[][];
+  outputWeights: ParamSource[];
+  operatorConfigs: OperatorConfig[];
+  detune: ParamSource | null;
+}
+
+/**
+ * Must match `MorphParam::from_parts` in the `wavetable` crate
+ */
+enum MorphParamType {
+  ModulationIndex = 0,
+  OutputWeight = 1,
+  OperatorBaseFrequency = 2,
+  Detune = 3,
+}
+
 export default class FMSynth implements ForeignNode {
   private ctx: AudioContext;
   private vcId: string | undefined;
   private audioThreadMIDIEventMailboxID?: string;
   private awpHandle: AudioWorkletNode | null = null;
+  private unregisterQualityGovernor: (() => void) | null = null;
+  /**
+   * Used to send param changes to the AWP without a message per change.  `null` if shared memory
+   * isn't available, in which case changes are posted as messages instead.
+   */
+  private controlEventRing: ControlEventRing | null = maybeCreateControlEventRing();
+  private unregisterNotificationRing: (() => void) | null = null;
   private modulationMatrix: ParamSource[][] = buildDefaultModulationIndices();
+  private operatorConnectionTypes: OperatorConnectionType[][] =
+    buildDefaultOperatorConnectionTypes();
   private outputWeights: ParamSource[] = new Array(OPERATOR_COUNT)
     .fill(null as any)
     .map(() => ({ type: 'constant' as const, value: 0 }));
@@ -153,6 +320,16 @@ export default class FMSynth implements ForeignNode {
   private onInitializedCBs: ((inst: FMSynth) => void)[] = [];
   private audioThreadDataBuffer: Float32Array | null = null;
   private detune: ParamSource | null = null;
+  private voiceLayers: VoiceLayersConfig = buildDefaultVoiceLayersConfig();
+  private operatorPans: OperatorPan[] = new Array(OPERATOR_COUNT)
+    .fill(null)
+    .map(buildDefaultOperatorPan);
+  private voicePan: ParamSource = { type: 'constant', value: 0 };
+  /**
+   * Set by the source of the synth's MIDI input rather than being part of the voice, so it isn't
+   * serialized
+   */
+  private tuning: Tuning = TWELVE_TET;
   public midiControlValuesCache: MIDIControlValuesCache;
   private wavetableState: WavetableState = { wavetableBanks: [] };
   private wavetableBackendIxByName: string[] = [];
@@ -200,6 +377,25 @@ export default class FMSynth implements ForeignNode {
   private ungateCallbacks: Set<(midiNumber: number, voiceIx: number) => void> = new Set();
   private fetchedSampleDescriptorHashes: Set<string> = new Set();
   public useLegacyWavetableControls = true;
+  /**
+   * Not persisted; it's only meant for tracking down performance problems
+   */
+  public profilingEnabled = false;
+  private pendingRandomizations: Map<
+    number,
+    { resolve: (values: Float32Array) => void; reject: (err: Error) => void }
+  > = new Map();
+  private nextRandomizationRequestId = 0;
+  private morphPresets: [FMSynthMorphPreset, FMSynthMorphPreset] | null = null;
+  /**
+   * Index of the morph preset whose operator configs are currently applied
+   */
+  private morphNearestPresetIx: 0 | 1 | null = null;
+  private pendingBounce: {
+    resolve: (stems: Float32Array[]) => void;
+    reject: (err: Error) => void;
+    onProgress?: (progress: number) => void;
+  } | null = null;
   public readonly debugID = crypto.randomUUID();
 
   static typeName = 'FM Synthesizer';
@@ -212,6 +408,9 @@ export default class FMSynth implements ForeignNode {
   public getModulationMatrix() {
     return this.modulationMatrix;
   }
+  public getOperatorConnectionTypes() {
+    return this.operatorConnectionTypes;
+  }
   public getOutputWeights() {
     return this.outputWeights;
   }
@@ -230,6 +429,26 @@ export default class FMSynth implements ForeignNode {
   public getDetune() {
     return this.detune;
   }
+  public getVoiceLayers() {
+    return this.voiceLayers;
+  }
+  public getOperatorPans() {
+    return this.operatorPans;
+  }
+  public getVoicePan() {
+    return this.voicePan;
+  }
+  public getAudioThreadDataBuffer() {
+    return this.audioThreadDataBuffer;
+  }
+
+  /**
+   * View into the audio thread data buffer holding the modulated values of each effect's params.
+   * Index into it with `getEffectParamVizOffset`.
+   */
+  public getEffectParamVizBuffer() {
+    return this.audioThreadDataBuffer?.subarray(FM_SYNTH_EFFECT_PARAM_VIZ_OFFSET) ?? null;
+  }
   public getWavetableState() {
     return this.wavetableState;
   }
@@ -252,12 +471,8 @@ export default class FMSynth implements ForeignNode {
     const midiNode =
       params?.midiNode ??
       new MIDINode(() => ({
-        onAttack: () => {
-          // ignore
-        },
-        onRelease: () => {
-          // ignore
-        },
+        onAttack: note => this.gate(note),
+        onRelease: note => this.ungate(note),
         onPitchBend: () => {
           // ignore
         },
@@ -333,13 +548,32 @@ export default class FMSynth implements ForeignNode {
       RegisterFMSynthAWP.get(),
     ] as const);
     this.awpHandle = new AudioWorkletNode(this.ctx, 'fm-synth-audio-worklet-processor', {
-      // First `VOICE_COUNT` outputs are for the actual voice outputs
+      // First `VOICE_COUNT` outputs are for the actual voice outputs, which are stereo
       // Second `VOICE_COUNT` outputs are for the outputs of the filter envelope generator
       numberOfInputs: 0,
       numberOfOutputs: VOICE_COUNT * 2,
+      outputChannelCount: [
+        ...new Array(VOICE_COUNT).fill(2),
+        ...new Array(VOICE_COUNT).fill(1),
+      ],
       channelCount: 1,
       processorOptions: { mailboxID: this.audioThreadMIDIEventMailboxID },
     });
+    this.unregisterQualityGovernor = registerQualityGovernedPort(this.ctx, this.awpHandle.port);
+    if (this.controlEventRing) {
+      this.awpHandle.port.postMessage({
+        type: 'setControlEventRing',
+        sab: this.controlEventRing.sab,
+      });
+    }
+    const notificationRing = maybeCreateNotificationRing();
+    if (notificationRing) {
+      this.unregisterNotificationRing = registerNotificationRing(
+        notificationRing,
+        this.handleNotification
+      );
+      this.awpHandle.port.postMessage({ type: 'setNotificationRing', sab: notificationRing.sab });
+    }
 
     this.awpHandle.port.postMessage({
       type: 'setWasmBytes',
@@ -369,19 +603,41 @@ export default class FMSynth implements ForeignNode {
             this.filterEnvelope.audioThreadData.buffer = this.audioThreadDataBuffer!;
           }
 
-          // Initialize backend with all effects and modulation indices that were deserialized
-          this.operatorConfigs.forEach((config, opIx) =>
-            this.handleOperatorConfigChange(opIx, config)
-          );
-          this.operatorEffects.forEach((effectsForOp, opIx) => {
-            effectsForOp.forEach((effect, effectIx) => {
-              this.setEffect(opIx, effectIx, effect);
-            });
+          // Initialize backend with all operators, effects, and detune that were deserialized in a
+          // single call rather than one message per setting
+          this.awpHandle!.port.postMessage({
+            type: 'loadVoicePreset',
+            records: [
+              ...this.operatorConfigs.flatMap((config, opIx) =>
+                this.buildOperatorConfigMessages(opIx, config)
+              ),
+              ...this.operatorEffects.flatMap((effectsForOp, opIx) =>
+                effectsForOp.map((effect, effectIx) =>
+                  this.buildEffectMessage(opIx, effectIx, effect)
+                )
+              ),
+              ...this.mainEffectChain.map((effect, effectIx) =>
+                this.buildEffectMessage(null, effectIx, effect)
+              ),
+              { type: 'setDetune', ...encodeParamSource(this.detune) },
+              { type: 'setVoiceLayers', ...this.voiceLayers },
+              ...this.operatorPans.map((operatorPan, operatorIx) =>
+                this.buildOperatorPanMessage(operatorIx, operatorPan)
+              ),
+              { type: 'setVoicePan', ...encodeParamSource(this.voicePan) },
+              ...this.operatorConnectionTypes.flatMap((row, srcOperatorIx) =>
+                row.map((connectionType, dstOperatorIx) => ({
+                  type: 'setOperatorConnectionType',
+                  srcOperatorIx,
+                  dstOperatorIx,
+                  connectionType,
+                }))
+              ),
+            ],
           });
-          this.mainEffectChain.forEach((effect, effectIx) =>
-            this.setEffect(null, effectIx, effect)
-          );
-          this.handleDetuneChange(this.detune);
+          if (!isTwelveTET(this.tuning)) {
+            this.awpHandle!.port.postMessage({ type: 'setTuning', tuning: this.tuning });
+          }
           this.sampleMappingStore.subscribe(this.handleSampleMappingStateChange);
 
           for (const cb of this.onInitializedCBs) {
@@ -390,15 +646,35 @@ export default class FMSynth implements ForeignNode {
           break;
         }
         case 'onGate': {
-          for (const gateCb of this.gateCallbacks) {
-            gateCb(evt.data.midiNumber, evt.data.voiceIx);
-          }
+          this.handleGate(evt.data.midiNumber, evt.data.voiceIx);
           break;
         }
         case 'onUngate': {
-          for (const ungateCb of this.ungateCallbacks) {
-            ungateCb(evt.data.midiNumber, evt.data.voiceIx);
-          }
+          this.handleUngate(evt.data.midiNumber, evt.data.voiceIx);
+          break;
+        }
+        case 'bounceProgress': {
+          this.pendingBounce?.onProgress?.(evt.data.progress);
+          break;
+        }
+        case 'bounceComplete': {
+          this.pendingBounce?.resolve(evt.data.stems);
+          this.pendingBounce = null;
+          break;
+        }
+        case 'bounceError': {
+          this.pendingBounce?.reject(new Error(evt.data.message));
+          this.pendingBounce = null;
+          break;
+        }
+        case 'randomizedVoiceParams': {
+          this.pendingRandomizations.get(evt.data.requestId)?.resolve(evt.data.values);
+          this.pendingRandomizations.delete(evt.data.requestId);
+          break;
+        }
+        case 'randomizeVoiceParamsError': {
+          this.pendingRandomizations.get(evt.data.requestId)?.reject(new Error(evt.data.message));
+          this.pendingRandomizations.delete(evt.data.requestId);
           break;
         }
         default: {
@@ -458,6 +734,39 @@ export default class FMSynth implements ForeignNode {
     });
   }
 
+  private handleGate(midiNumber: number, voiceIx: number) {
+    for (const gateCb of this.gateCallbacks) {
+      gateCb(midiNumber, voiceIx);
+    }
+  }
+
+  private handleUngate(midiNumber: number, voiceIx: number) {
+    for (const ungateCb of this.ungateCallbacks) {
+      ungateCb(midiNumber, voiceIx);
+    }
+  }
+
+  private handleNotification = (notification: Notification) => {
+    if (notification.type === 'overflow') {
+      console.warn(`Dropped ${notification.droppedCount} notifications from FM synth AWP`);
+      return;
+    }
+
+    switch (notification.type) {
+      case NotificationType.VoiceStarted:
+        this.handleGate(notification.param0, notification.param1);
+        break;
+      case NotificationType.VoiceEnded:
+        this.handleUngate(notification.param0, notification.param1);
+        break;
+      case NotificationType.Error:
+        console.error(`Error in FM synth AWP (code ${notification.param0})`);
+        break;
+      default:
+        console.error('Unhandled notification type from FM synth AWP: ', notification.type);
+    }
+  };
+
   public registerGateUngateCallbacks: GateUngateCallbackRegistrar = (onGate, onUngate) => {
     this.gateCallbacks.add(onGate);
     this.ungateCallbacks.add(onUngate);
@@ -469,18 +778,6 @@ export default class FMSynth implements ForeignNode {
     return { unregister };
   };
 
-  private setOperatorBaseFrequencySource(operatorIx: number, source: ParamSource) {
-    if (!this.awpHandle) {
-      throw new UnreachableException();
-    }
-
-    this.awpHandle.port.postMessage({
-      type: 'setOperatorBaseFrequencySource',
-      operatorIx,
-      ...encodeParamSource(source),
-    });
-  }
-
   public getSampleMappingStore(): Writable<SampleMappingState> {
     return this.sampleMappingStore;
   }
@@ -492,6 +789,16 @@ export default class FMSynth implements ForeignNode {
       return;
     }
 
+    for (const msg of this.buildOperatorConfigMessages(operatorIx, config)) {
+      this.awpHandle.port.postMessage(msg);
+    }
+  }
+
+  /**
+   * Builds the messages that set the operator config along with its base frequency source for
+   * operators that support one.  Loads the operator's wavetable into the backend if necessary.
+   */
+  private buildOperatorConfigMessages(operatorIx: number, config: OperatorConfig) {
     if (config.type === 'wavetable' && config.wavetableName !== null) {
       const bank = this.wavetableState.wavetableBanks.find(R.propEq('name', config.wavetableName));
       if (!bank) {
@@ -523,7 +830,8 @@ export default class FMSynth implements ForeignNode {
       }[config.type] + (unisonEnabled ? 50 : 0);
 
     // Set the operator config along with any hyperparam config
-    this.awpHandle.port.postMessage({
+    const messages: Record<string, any>[] = [];
+    messages.push({
       type: 'setOperatorConfig',
       operatorIx,
       operatorType,
@@ -565,10 +873,15 @@ export default class FMSynth implements ForeignNode {
       case 'triangle oscillator':
       case 'sawtooth oscillator':
       case 'wavetable': {
-        this.setOperatorBaseFrequencySource(operatorIx, config.frequency);
+        messages.push({
+          type: 'setOperatorBaseFrequencySource',
+          operatorIx,
+          ...encodeParamSource(config.frequency),
+        });
         break;
       }
     }
+    return messages;
   }
 
   public handleOutputWeightChange(operatorIx: number, rawVal: ParamSource | number) {
@@ -615,6 +928,25 @@ export default class FMSynth implements ForeignNode {
     });
   }
 
+  public handleOperatorConnectionTypeChange(
+    srcOperatorIx: number,
+    dstOperatorIx: number,
+    connectionType: OperatorConnectionType
+  ) {
+    if (!this.awpHandle) {
+      console.error('Tried to update operator connection type before AWP initialization');
+      return;
+    }
+
+    this.operatorConnectionTypes[srcOperatorIx][dstOperatorIx] = connectionType;
+    this.awpHandle.port.postMessage({
+      type: 'setOperatorConnectionType',
+      srcOperatorIx,
+      dstOperatorIx,
+      connectionType,
+    });
+  }
+
   public onInitialized(): Promise<FMSynth> {
     if (this.awpHandle) {
       return Promise.resolve(this);
@@ -700,15 +1032,59 @@ export default class FMSynth implements ForeignNode {
       console.error('Tried to set effect before AWP initialization');
       return;
     }
-    if (operatorIx === null) {
-      this.mainEffectChain[effectIx] = R.clone(newEffect);
-    } else {
-      this.operatorEffects[operatorIx][effectIx] = R.clone(newEffect);
+    const effects = operatorIx === null ? this.mainEffectChain : this.operatorEffects[operatorIx];
+    const oldEffect = effects[effectIx];
+    effects[effectIx] = R.clone(newEffect);
+
+    // Bypass and mix changes are applied to the running effect so they can be crossfaded rather
+    // than rebuilding it
+    const omitRuntimeFields = R.omit(['isBypassed', 'mix', 'isCollapsed']);
+    if (
+      oldEffect &&
+      newEffect &&
+      R.equals(omitRuntimeFields(oldEffect), omitRuntimeFields(newEffect))
+    ) {
+      if ((oldEffect.isBypassed ?? false) !== (newEffect.isBypassed ?? false)) {
+        this.awpHandle.port.postMessage({
+          type: 'setEffectBypassed',
+          operatorIx,
+          effectIx,
+          isBypassed: newEffect.isBypassed ?? false,
+        });
+      }
+      if ((oldEffect.mix ?? 1) !== (newEffect.mix ?? 1)) {
+        this.awpHandle.port.postMessage({
+          type: 'setEffectMix',
+          operatorIx,
+          effectIx,
+          mix: newEffect.mix ?? 1,
+        });
+      }
+      return;
     }
 
-    const [effectType, param1, param2, param3, param4] = encodeEffect(newEffect);
+    this.awpHandle.port.postMessage(this.buildEffectMessage(operatorIx, effectIx, newEffect));
+  }
 
-    this.awpHandle.port.postMessage({
+  /**
+   * Moves an effect to a new position in its chain, shifting the effects in between over by one.
+   * The effect keeps its state, and the chain crossfades through its dry signal during the move.
+   */
+  public moveEffect(operatorIx: number | null, fromEffectIx: number, toEffectIx: number) {
+    if (!this.awpHandle) {
+      console.error('Tried to move effect before AWP initialization');
+      return;
+    }
+    const effects = operatorIx === null ? this.mainEffectChain : this.operatorEffects[operatorIx];
+    const [movedEffect] = effects.splice(fromEffectIx, 1);
+    effects.splice(toEffectIx, 0, movedEffect);
+
+    this.awpHandle.port.postMessage({ type: 'moveEffect', operatorIx, fromEffectIx, toEffectIx });
+  }
+
+  private buildEffectMessage(operatorIx: number | null, effectIx: number, effect: Effect | null) {
+    const [effectType, param1, param2, param3, param4] = encodeEffect(effect);
+    return {
       type: 'setEffect',
       operatorIx,
       effectIx,
@@ -717,8 +1093,9 @@ export default class FMSynth implements ForeignNode {
       param2,
       param3,
       param4,
-      isBypassed: newEffect?.isBypassed ?? false,
-    });
+      isBypassed: effect?.isBypassed ?? false,
+      mix: effect?.mix ?? 1,
+    };
   }
 
   public deserialize(params: { [key: string]: any }) {
@@ -732,6 +1109,9 @@ export default class FMSynth implements ForeignNode {
         })
       );
     }
+    if (params.operatorConnectionTypes) {
+      this.operatorConnectionTypes = params.operatorConnectionTypes;
+    }
     if (params.outputWeights) {
       if (typeof params.outputWeights[0] === 'number') {
         this.outputWeights = params.outputWeights.map((value: number) => ({
@@ -799,6 +1179,15 @@ export default class FMSynth implements ForeignNode {
     if (params.detune) {
       this.detune = params.detune;
     }
+    if (params.voiceLayers) {
+      this.voiceLayers = { ...buildDefaultVoiceLayersConfig(), ...params.voiceLayers };
+    }
+    if (params.operatorPans) {
+      this.operatorPans = params.operatorPans;
+    }
+    if (params.voicePan) {
+      this.voicePan = params.voicePan;
+    }
     if (params.wavetableState) {
       this.wavetableState = deserializeWavetableState(params.wavetableState);
     }
@@ -841,11 +1230,14 @@ export default class FMSynth implements ForeignNode {
     }
 
     this.awpHandle.port.postMessage({ type: 'shutdown' });
+    this.unregisterQualityGovernor?.();
+    this.unregisterNotificationRing?.();
   }
 
   public serialize() {
     return {
       modulationMatrix: this.modulationMatrix,
+      operatorConnectionTypes: this.operatorConnectionTypes,
       outputWeights: this.outputWeights,
       operatorConfigs: this.operatorConfigs,
       operatorEffects: this.operatorEffects,
@@ -853,6 +1245,9 @@ export default class FMSynth implements ForeignNode {
       mainEffectChain: this.mainEffectChain,
       adsrs: this.adsrs.map(serializeADSR),
       detune: this.detune,
+      voiceLayers: this.voiceLayers,
+      operatorPans: this.operatorPans,
+      voicePan: this.voicePan,
       lastSeenMIDIControlValues: this.midiControlValuesCache.serialize(),
       wavetableState: serializeWavetableState(this.wavetableState),
       gainEnvelope: this.gainEnvelope,
@@ -862,6 +1257,41 @@ export default class FMSynth implements ForeignNode {
     };
   }
 
+  /**
+   * Starts playing `midiNumber` at `time`, in `AudioContext.currentTime` seconds.  Notes start on
+   * the exact sample if the control event ring is available; otherwise they start as soon as the
+   * message is received.
+   *
+   * Notes must be gated and ungated in time order, and events scheduled in the future hold back
+   * the param changes sent after them, so notes should only be scheduled a little ahead of time.
+   */
+  public gate(midiNumber: number, time = 0) {
+    if (!this.awpHandle) {
+      console.warn('Tried to gate FM synth before AWP initialized');
+      return;
+    }
+
+    if (this.pushControlEvent(ControlEventType.Gate, midiNumber, 0, time)) {
+      return;
+    }
+    this.awpHandle.port.postMessage({ type: 'gate', midiNumber });
+  }
+
+  /**
+   * Releases `midiNumber` at `time`; see `gate`
+   */
+  public ungate(midiNumber: number, time = 0) {
+    if (!this.awpHandle) {
+      console.warn('Tried to ungate FM synth before AWP initialized');
+      return;
+    }
+
+    if (this.pushControlEvent(ControlEventType.Ungate, midiNumber, 0, time)) {
+      return;
+    }
+    this.awpHandle.port.postMessage({ type: 'ungate', midiNumber });
+  }
+
   public clearOutputBuffer(voiceIx: number) {
     if (!this.awpHandle) {
       console.warn('Tried to clear FM synth output buffer before AWP initialized');
@@ -877,9 +1307,240 @@ export default class FMSynth implements ForeignNode {
       return;
     }
 
+    if (this.pushControlEvent(ControlEventType.FrequencyMultiplier, frequencyMultiplier)) {
+      return;
+    }
     this.awpHandle.port.postMessage({ type: 'setFrequencyMultiplier', frequencyMultiplier });
   }
 
+  /**
+   * Sets the gain applied to the output of all voices.  Changes are smoothed inside the synth to
+   * avoid clicks.
+   */
+  public setMasterGain(masterGain: number) {
+    if (!this.awpHandle) {
+      console.warn('Tried to set FM synth master gain before AWP initialized');
+      return;
+    }
+
+    if (this.pushControlEvent(ControlEventType.MasterGain, masterGain)) {
+      return;
+    }
+    this.awpHandle.port.postMessage({ type: 'setMasterGain', masterGain });
+  }
+
+  public handleVoiceLayersChange(newVoiceLayers: VoiceLayersConfig) {
+    this.voiceLayers = newVoiceLayers;
+    if (!this.awpHandle) {
+      console.warn('Tried to set FM synth voice layers before AWP initialized');
+      return;
+    }
+
+    this.awpHandle.port.postMessage({ type: 'setVoiceLayers', ...newVoiceLayers });
+  }
+
+  private buildOperatorPanMessage(operatorIx: number, operatorPan: OperatorPan) {
+    return {
+      type: 'setOperatorPan',
+      operatorIx,
+      unisonSpread: operatorPan.unisonSpread,
+      ...encodeParamSource(operatorPan.pan),
+    };
+  }
+
+  public handleOperatorPanChange(operatorIx: number, newOperatorPan: OperatorPan) {
+    this.operatorPans[operatorIx] = R.clone(newOperatorPan);
+    if (!this.awpHandle) {
+      console.warn('Tried to set FM synth operator pan before AWP initialized');
+      return;
+    }
+
+    this.awpHandle.port.postMessage(this.buildOperatorPanMessage(operatorIx, newOperatorPan));
+  }
+
+  public handleVoicePanChange(newVoicePan: ParamSource) {
+    this.voicePan = R.clone(newVoicePan);
+    if (!this.awpHandle) {
+      console.warn('Tried to set FM synth voice pan before AWP initialized');
+      return;
+    }
+
+    this.awpHandle.port.postMessage({ type: 'setVoicePan', ...encodeParamSource(newVoicePan) });
+  }
+
+  /**
+   * Tracks how long each voice takes to render, written into the audio thread data buffer at
+   * `FM_SYNTH_PROFILING_STATS_OFFSET`
+   */
+  public setProfilingEnabled(enabled: boolean) {
+    this.profilingEnabled = enabled;
+    if (!this.awpHandle) {
+      console.warn('Tried to set FM synth profiling before AWP initialized');
+      return;
+    }
+
+    this.awpHandle.port.postMessage({ type: 'setProfilingEnabled', enabled });
+  }
+
+  /**
+   * Loads two presets to morph between with `setMorphPosition`.  The synth isn't changed until the
+   * morph position is next set.
+   */
+  public setMorphPresets(presets: [FMSynthMorphPreset, FMSynthMorphPreset]) {
+    if (!this.awpHandle) {
+      console.warn('Tried to set FM synth morph presets before AWP initialized');
+      return;
+    }
+    this.morphPresets = R.clone(presets);
+    this.morphNearestPresetIx = null;
+
+    const params = presets.flatMap((preset, presetIx) => {
+      const buildParam = (
+        paramType: MorphParamType,
+        operatorIx: number,
+        dstOperatorIx: number,
+        source: ParamSource | null
+      ) => ({ presetIx, paramType, operatorIx, dstOperatorIx, ...encodeParamSource(source) });
+
+      return [
+        ...preset.modulationMatrix.flatMap((row, srcOperatorIx) =>
+          row.map((source, dstOperatorIx) =>
+            buildParam(MorphParamType.ModulationIndex, srcOperatorIx, dstOperatorIx, source)
+          )
+        ),
+        ...preset.outputWeights.map((source, operatorIx) =>
+          buildParam(MorphParamType.OutputWeight, operatorIx, 0, source)
+        ),
+        ...preset.operatorConfigs.flatMap((config, operatorIx) =>
+          'frequency' in config
+            ? [buildParam(MorphParamType.OperatorBaseFrequency, operatorIx, 0, config.frequency)]
+            : []
+        ),
+        buildParam(MorphParamType.Detune, 0, 0, preset.detune),
+      ];
+    });
+    this.awpHandle.port.postMessage({ type: 'setMorphPresets', params });
+  }
+
+  /**
+   * Interpolates between the presets set with `setMorphPresets` and applies the result to the
+   * synth.  0 is entirely the first preset and 1 is entirely the second.
+   *
+   * Operator types can't be interpolated, so operators use the configs from whichever preset is
+   * nearer to `position`; their frequencies and everything else made up of `ParamSource`s are
+   * interpolated inside the synth.
+   */
+  public setMorphPosition(position: number) {
+    if (!this.awpHandle) {
+      console.warn('Tried to set FM synth morph position before AWP initialized');
+      return;
+    } else if (!this.morphPresets) {
+      console.warn('Tried to set FM synth morph position before setting morph presets');
+      return;
+    }
+
+    const nearestPresetIx = position < 0.5 ? 0 : 1;
+    if (nearestPresetIx !== this.morphNearestPresetIx) {
+      this.morphNearestPresetIx = nearestPresetIx;
+      this.morphPresets[nearestPresetIx].operatorConfigs.forEach((config, operatorIx) =>
+        this.handleOperatorConfigChange(operatorIx, config)
+      );
+    }
+
+    if (this.pushControlEvent(ControlEventType.MorphPosition, position)) {
+      return;
+    }
+    this.awpHandle.port.postMessage({ type: 'setMorphPosition', position });
+  }
+
+  public getTuning(): Tuning {
+    return this.tuning;
+  }
+
+  /**
+   * Sets the tuning used to map incoming note numbers to frequencies.  If the AWP hasn't been
+   * initialized yet, it's applied once it is.
+   */
+  public setTuning(tuning: Tuning) {
+    this.tuning = tuning;
+    this.awpHandle?.port.postMessage({ type: 'setTuning', tuning });
+  }
+
+  /**
+   * Renders `events` offline, faster than realtime, through a copy of this synth as it's currently
+   * configured.  Live playback through the synth carries on while the bounce is running, and
+   * changes made to the synth in the meantime don't affect the bounce.
+   *
+   * The returned samples are the summed output of all voices at the audio context's sample rate,
+   * before any processing done outside of the synth such as synth designer filters.
+   */
+  public bounce(params: BounceParams): Promise<Float32Array> {
+    return this.bounceStems({ ...params, stemCount: 1 }).then(stems => stems[0]);
+  }
+
+  /**
+   * Like `bounce`, but renders the output of each stem's notes to its own buffer in a single pass.
+   * Each event's `stemIx` selects the stem it's rendered to.  All returned buffers are the same
+   * length so they line up when mixed or exported side by side.
+   */
+  public bounceStems({
+    events,
+    stemCount = 1,
+    bpm,
+    endBeat,
+    tailSeconds,
+    onProgress,
+  }: BounceParams): Promise<Float32Array[]> {
+    if (!this.awpHandle) {
+      return Promise.reject(new Error('Tried to bounce FM synth before AWP initialized'));
+    } else if (this.pendingBounce) {
+      return Promise.reject(new Error('FM synth is already bouncing'));
+    }
+
+    return new Promise<Float32Array[]>((resolve, reject) => {
+      this.pendingBounce = { resolve, reject, onProgress };
+      this.awpHandle!.port.postMessage({
+        type: 'bounce',
+        events,
+        stemCount,
+        bpm,
+        endBeat,
+        tailSeconds,
+      });
+    });
+  }
+
+  /**
+   * Picks a new value for each of `constraints` using the synth's seeded RNG.  This doesn't change
+   * the synth itself; see `randomizeVoicePreset` for building a preset out of the results.
+   *
+   * @param amount how far to move params from their current values towards random ones, from 0 (no
+   * change) to 1 (fully random)
+   */
+  public randomizeVoiceParams(
+    seed: number,
+    constraints: VoiceParamConstraint[],
+    amount: number
+  ): Promise<Float32Array> {
+    if (!this.awpHandle) {
+      return Promise.reject(
+        new Error('Tried to randomize FM synth voice params before AWP initialized')
+      );
+    }
+
+    const requestId = this.nextRandomizationRequestId++;
+    return new Promise<Float32Array>((resolve, reject) => {
+      this.pendingRandomizations.set(requestId, { resolve, reject });
+      this.awpHandle!.port.postMessage({
+        type: 'randomizeVoiceParams',
+        requestId,
+        seed,
+        constraints: encodeVoiceParamConstraints(constraints),
+        amount,
+      });
+    });
+  }
+
   public handleDetuneChange(newDetune: ParamSource | null) {
     this.detune = R.clone(newDetune);
     if (!this.awpHandle) {
@@ -942,9 +1603,26 @@ export default class FMSynth implements ForeignNode {
       console.warn('Tried to set MIDI control value before AWP initialized');
       return;
     }
+    if (this.pushControlEvent(ControlEventType.MIDIControlValue, controlIndex, controlValue)) {
+      return;
+    }
     this.awpHandle.port.postMessage({ type: 'midiControlValue', controlIndex, controlValue });
   }
 
+  /**
+   * Writes an event to the control event ring to be applied at `time`, or at the start of the next
+   * frame if it's 0.  Returns `false` if the ring isn't available or is full, in which case the
+   * caller should post a message instead.
+   */
+  private pushControlEvent(
+    eventType: ControlEventType,
+    param0: number,
+    param1 = 0,
+    time = 0
+  ): boolean {
+    return this.controlEventRing?.push(eventType, time, param0, param1) ?? false;
+  }
+
   public getAWPNode() {
     return this.awpHandle;
   }
@@ -9,6 +9,7 @@ import DummyNode from 'src/graphEditor/nodes/DummyNode';
 import { OverridableAudioParam } from 'src/graphEditor/nodes/util';
 import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
 import { updateConnectables } from 'src/patchNetwork/interface';
+import { registerQualityGovernedPort } from 'src/qualityGovernor';
 import { getSentry } from 'src/sentry';
 import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils';
 import { AsyncOnce } from 'src/util';
@@ -71,6 +72,8 @@ export class FMSynthFxNode implements ForeignNode {
       getProps: () => ({
         store: this.store,
         onChange: (ix: number, newState: Partial<Effect> | null) => this.handleChange(ix, newState),
+        moveEffect: (fromEffectIx: number, toEffectIx: number) =>
+          this.moveEffect(fromEffectIx, toEffectIx),
       }),
     });
 
@@ -87,6 +90,7 @@ export class FMSynthFxNode implements ForeignNode {
       numberOfOutputs: 1,
       channelCount: 1,
     });
+    registerQualityGovernedPort(this.ctx, this.awpHandle.port);
     updateConnectables(this.vcId, this.buildConnectables());
 
     this.awpHandle.port.onmessage = evt => {
@@ -119,17 +123,53 @@ export class FMSynthFxNode implements ForeignNode {
       effectIx,
       encodedEffect,
       isBypassed: newEffect?.isBypassed ?? false,
+      mix: newEffect?.mix ?? 1,
     });
   }
 
+  /**
+   * Applies bypass and mix changes to the running effect so that they're crossfaded.  Returns
+   * `false` if anything else about the effect changed and it needs to be re-committed.
+   */
+  private maybeCommitRuntimeFields(effectIx: number, oldEffect: Effect, newEffect: Effect) {
+    const omitRuntimeFields = R.omit(['isBypassed', 'mix', 'isCollapsed']);
+    if (!R.equals(omitRuntimeFields(oldEffect), omitRuntimeFields(newEffect))) {
+      return false;
+    }
+
+    if ((oldEffect.isBypassed ?? false) !== (newEffect.isBypassed ?? false)) {
+      this.awpHandle?.port.postMessage({
+        type: 'setEffectBypassed',
+        effectIx,
+        isBypassed: newEffect.isBypassed ?? false,
+      });
+    }
+    if ((oldEffect.mix ?? 1) !== (newEffect.mix ?? 1)) {
+      this.awpHandle?.port.postMessage({ type: 'setEffectMix', effectIx, mix: newEffect.mix ?? 1 });
+    }
+    return true;
+  }
+
+  private moveEffect = (fromEffectIx: number, toEffectIx: number) => {
+    this.awpHandle?.port.postMessage({ type: 'moveEffect', fromEffectIx, toEffectIx });
+    this.store.update(state => ({
+      ...state,
+      effects: R.move(fromEffectIx, toEffectIx, state.effects),
+    }));
+  };
+
   private handleChange = (effectIx: number, effectUpdate: Partial<Effect> | null) =>
     this.store.update(state => {
-      const oldEffect = state.effects[effectIx] ?? {};
+      const oldEffect = state.effects[effectIx];
       const newEffect: Effect | null = effectUpdate
-        ? { ...oldEffect, ...(effectUpdate as any) }
+        ? { ...(oldEffect ?? {}), ...(effectUpdate as any) }
         : effectUpdate;
 
-      this.commitEffect(effectIx, newEffect);
+      const committedRuntimeFields =
+        oldEffect && newEffect && this.maybeCommitRuntimeFields(effectIx, oldEffect, newEffect);
+      if (!committedRuntimeFields) {
+        this.commitEffect(effectIx, newEffect);
+      }
       const newState = { ...state };
       newState.effects = [...state.effects];
       newState.effects[effectIx] = newEffect;
@@ -1,21 +1,23 @@
 import { Map } from 'immutable';
 import * as R from 'ramda';
-import { writable, type Unsubscriber } from 'svelte/store';
+import { writable, type Unsubscriber, type Writable } from 'svelte/store';
 
 import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio/CustomAudio';
 import { OverridableAudioParam } from 'src/graphEditor/nodes/util';
 import type { AudioConnectables, ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
 import { MIDINode, type MIDIInputCbs } from 'src/patchNetwork/midiNode';
 import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils';
-import { midiToFrequency } from 'src/util';
+import { noteToFrequency, TWELVE_TET, type Tuning } from 'src/tuning';
 import MidiToFrequencySmallView, { RegateMode } from './MIDIToFrequencySmallView.svelte';
 
 export interface MIDIToFrequencyState {
   regateMode: RegateMode;
+  tuning: Tuning;
 }
 
 const buildDefaultMIDIToFrequencyState = (): MIDIToFrequencyState => ({
   regateMode: RegateMode.AnyAttack,
+  tuning: TWELVE_TET,
 });
 
 export class MIDIToFrequencyNode {
@@ -32,9 +34,10 @@ export class MIDIToFrequencyNode {
    */
   private activeNotes: number[] = [];
   private state: MIDIToFrequencyState;
+  private store: Writable<MIDIToFrequencyState>;
 
   private noteToFrequency(note: number): number {
-    return midiToFrequency(note); // TODO: Make configurable somehow
+    return noteToFrequency(note, this.state.tuning);
   }
 
   private gate() {
@@ -81,6 +84,10 @@ export class MIDIToFrequencyNode {
       this.frequencyCSN.offset.cancelScheduledValues(0);
       this.activeNotes = [];
     },
+    onTuningChange: tuning => {
+      this.state = { ...this.state, tuning };
+      this.store.set(this.state);
+    },
   });
 
   public nodeType = 'customAudio/MIDIToFrequency';
@@ -110,13 +117,13 @@ export class MIDIToFrequencyNode {
     this.midiNode = new MIDINode(this.getMIDIInputCbs);
 
     let unsubscribe: Unsubscriber | undefined;
-    const store = writable(this.state);
+    this.store = writable(this.state);
 
     this.renderSmallView = mkSvelteContainerRenderHelper({
       Comp: MidiToFrequencySmallView,
-      getProps: () => ({ state: store }),
+      getProps: () => ({ state: this.store }),
       predicate: () => {
-        unsubscribe = store.subscribe(newState => this.handleChange(newState));
+        unsubscribe = this.store.subscribe(newState => this.handleChange(newState));
       },
     });
 
@@ -158,7 +165,11 @@ export class MIDIToFrequencyNode {
       return buildDefaultMIDIToFrequencyState();
     }
 
-    return { ...params, regateMode: params.regateMode ?? RegateMode.AnyAttack };
+    return {
+      ...params,
+      regateMode: params.regateMode ?? RegateMode.AnyAttack,
+      tuning: params.tuning ?? TWELVE_TET,
+    };
   }
 
   // These are set dynamically at initialization time in the constructor
@@ -0,0 +1,300 @@
+import { Map as ImmMap } from 'immutable';
+import * as R from 'ramda';
+import { get, writable, type Writable } from 'svelte/store';
+
+import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio/CustomAudio';
+import {
+  createModuleHostNode,
+  getModuleChannelLayoutChannelCount,
+  getModuleHostParamName,
+  mkModuleWasmBytes,
+  ModuleChannelLayout,
+  setModuleHostOutputGuardEnabled,
+  setModuleHostProfilingEnabled,
+  setModuleHostParams,
+} from 'src/graphEditor/nodes/CustomAudio/ModuleHost/moduleHost';
+import DummyNode from 'src/graphEditor/nodes/DummyNode';
+import { OverridableAudioParam } from 'src/graphEditor/nodes/util';
+import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
+import { updateConnectables } from 'src/patchNetwork/interface';
+import { getSentry } from 'src/sentry';
+import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils';
+import MixBusSmallView from './MixBusSmallView.svelte';
+
+/**
+ * Must match `INPUT_COUNT` and `BUS_COUNT` in the `mix_bus` crate
+ */
+export const MIX_BUS_INPUT_COUNT = 8;
+export const MIX_BUS_BUS_COUNT = 4;
+/**
+ * Must match `OUTPUT_LAYOUTS` in the `mix_bus` crate.  The master is output first, followed by each
+ * bus.
+ */
+const MIX_BUS_OUTPUT_LAYOUTS = R.times(() => ModuleChannelLayout.Stereo, 1 + MIX_BUS_BUS_COUNT);
+
+/**
+ * Must match the param layout of the `mix_bus` crate.  The master gain and input gains are driven
+ * by `AudioParam`s; everything after them is sent as one block.
+ */
+const PARAM_MASTER_GAIN_IX = 0;
+const INPUT_GAINS_OFFSET = 1;
+const INPUT_PARAMS_OFFSET = INPUT_GAINS_OFFSET + MIX_BUS_INPUT_COUNT;
+
+/**
+ * Must match the SAB layout of the `mix_bus` crate.  Each entry is the peak level of one channel
+ * over the last frame, stereo pairs for each input, then each bus, then the master.
+ */
+export const MIX_BUS_SAB_INPUT_LEVELS_OFFSET = 0;
+export const MIX_BUS_SAB_BUS_LEVELS_OFFSET =
+  MIX_BUS_SAB_INPUT_LEVELS_OFFSET + MIX_BUS_INPUT_COUNT * 2;
+export const MIX_BUS_SAB_MASTER_LEVELS_OFFSET =
+  MIX_BUS_SAB_BUS_LEVELS_OFFSET + MIX_BUS_BUS_COUNT * 2;
+export const MIX_BUS_SAB_LEN = MIX_BUS_SAB_MASTER_LEVELS_OFFSET + 2;
+
+export interface MixBusInputState {
+  gain: number;
+  /**
+   * -1 is hard left, 1 is hard right
+   */
+  pan: number;
+  mute: boolean;
+  solo: boolean;
+  /**
+   * Send level from this input to each bus
+   */
+  sends: number[];
+}
+
+export interface MixBusNodeUIState {
+  master_gain: number;
+  inputs: MixBusInputState[];
+  bus_gains: number[];
+  output_guard: boolean;
+  profiling: boolean;
+  sab: Float32Array | null;
+}
+
+const buildDefaultMixBusInputState = (): MixBusInputState => ({
+  gain: 1,
+  pan: 0,
+  mute: false,
+  solo: false,
+  // Everything goes to the first bus by default
+  sends: R.times(busIx => (busIx === 0 ? 1 : 0), MIX_BUS_BUS_COUNT),
+});
+
+export const buildDefaultMixBusNodeUIState = (): MixBusNodeUIState => ({
+  master_gain: 1,
+  inputs: R.times(buildDefaultMixBusInputState, MIX_BUS_INPUT_COUNT),
+  bus_gains: R.times(() => 1, MIX_BUS_BUS_COUNT),
+  output_guard: false,
+  profiling: false,
+  sab: null,
+});
+
+const MixBusWasmBytes = mkModuleWasmBytes('mix_bus.wasm');
+
+export class MixBusNode implements ForeignNode {
+  private ctx: AudioContext;
+  private vcId: string;
+  private awpHandle: AudioWorkletNode | null = null;
+  private store: Writable<MixBusNodeUIState> = writable(buildDefaultMixBusNodeUIState());
+  /**
+   * Upmix mono sources to stereo before each input is split into the module's channels
+   */
+  private inputNodes: GainNode[];
+  private inputMerger: ChannelMergerNode;
+  /**
+   * Master first, followed by each bus.  Each is fed by the matching output of the module.
+   */
+  private outputNodes: GainNode[];
+  private masterGain: OverridableAudioParam | DummyNode = new DummyNode();
+  private inputGains: (OverridableAudioParam | DummyNode)[] = R.times(
+    () => new DummyNode(),
+    MIX_BUS_INPUT_COUNT
+  );
+
+  static typeName = 'Mix Bus';
+  public nodeType = 'customAudio/mixBus';
+
+  public paramOverrides: {
+    [name: string]: { param: OverridableAudioParam; override: ConstantSourceNode };
+  } = {};
+
+  constructor(ctx: AudioContext, vcId?: string, params?: { [key: string]: any } | null) {
+    this.ctx = ctx;
+    if (!vcId) {
+      throw new Error('vcId is required');
+    }
+    this.vcId = vcId;
+
+    this.inputMerger = new ChannelMergerNode(ctx, { numberOfInputs: MIX_BUS_INPUT_COUNT * 2 });
+    this.inputNodes = R.times(inputIx => {
+      const inputNode = new GainNode(ctx, {
+        channelCount: 2,
+        channelCountMode: 'explicit',
+        channelInterpretation: 'speakers',
+      });
+      const splitter = new ChannelSplitterNode(ctx, { numberOfOutputs: 2 });
+      inputNode.connect(splitter);
+      splitter.connect(this.inputMerger, 0, inputIx * 2);
+      splitter.connect(this.inputMerger, 1, inputIx * 2 + 1);
+      return inputNode;
+    }, MIX_BUS_INPUT_COUNT);
+
+    this.outputNodes = MIX_BUS_OUTPUT_LAYOUTS.map(
+      layout =>
+        new GainNode(ctx, {
+          channelCount: getModuleChannelLayoutChannelCount(layout),
+          channelCountMode: 'explicit',
+        })
+    );
+
+    if (params) {
+      this.deserialize(params as Partial<MixBusNodeUIState>);
+    }
+
+    let unsubscribe: (() => void) | undefined;
+
+    this.renderSmallView = mkSvelteContainerRenderHelper({
+      Comp: MixBusSmallView,
+      getProps: () => ({ store: this.store }),
+      predicate: () => {
+        unsubscribe = this.store.subscribe(state => this.onChange(state));
+      },
+    });
+
+    this.cleanupSmallView = mkSvelteContainerCleanupHelper({
+      preserveRoot: true,
+      predicate: () => {
+        try {
+          unsubscribe?.();
+        } catch (e) {
+          console.warn('Error unsubscribing from mix bus store', e);
+        }
+      },
+    });
+
+    this.init().catch(err => {
+      console.error('Error initializing mix bus node', err);
+      getSentry()?.captureException(err);
+    });
+  }
+
+  private handleMessageFromAWP = (data: Record<string, any>) => {
+    switch (data.type) {
+      case 'capabilities':
+        // The param layout is fixed for this module, so there's nothing to pick up
+        break;
+      case 'sab': {
+        const sab = data.sab as SharedArrayBuffer;
+        this.store.update(s => ({ ...s, sab: new Float32Array(sab) }));
+        break;
+      }
+      default:
+        console.warn('Unknown message from AWP', data);
+    }
+  };
+
+  private async init() {
+    this.awpHandle = await createModuleHostNode(
+      this.ctx,
+      this.vcId,
+      MixBusWasmBytes,
+      MIX_BUS_INPUT_COUNT * 2,
+      this.handleMessageFromAWP,
+      MIX_BUS_OUTPUT_LAYOUTS
+    );
+    this.inputMerger.connect(this.awpHandle);
+    this.outputNodes.forEach((outputNode, outputIx) =>
+      this.awpHandle!.connect(outputNode, outputIx)
+    );
+
+    const awpParams = this.awpHandle.parameters as Map<string, AudioParam>;
+    const buildParam = (paramIx: number) =>
+      new OverridableAudioParam(
+        this.ctx,
+        awpParams.get(getModuleHostParamName(paramIx))!,
+        undefined,
+        true
+      );
+    this.masterGain = buildParam(PARAM_MASTER_GAIN_IX);
+    this.inputGains = R.times(
+      inputIx => buildParam(INPUT_GAINS_OFFSET + inputIx),
+      MIX_BUS_INPUT_COUNT
+    );
+
+    this.onChange(get(this.store));
+    updateConnectables(this.vcId, this.buildConnectables());
+  }
+
+  private onChange = (newState: MixBusNodeUIState) => {
+    if (!this.awpHandle) {
+      return;
+    }
+
+    setModuleHostOutputGuardEnabled(this.awpHandle, newState.output_guard);
+    setModuleHostProfilingEnabled(this.awpHandle, newState.profiling);
+    (this.masterGain as OverridableAudioParam).manualControl.offset.value = newState.master_gain;
+    newState.inputs.forEach((input, inputIx) => {
+      (this.inputGains[inputIx] as OverridableAudioParam).manualControl.offset.value = input.gain;
+    });
+    setModuleHostParams(this.awpHandle, INPUT_PARAMS_OFFSET, [
+      ...newState.inputs.flatMap(input => [
+        input.pan,
+        input.mute ? 1 : 0,
+        input.solo ? 1 : 0,
+        ...input.sends,
+      ]),
+      ...newState.bus_gains,
+    ]);
+  };
+
+  private deserialize(params: Partial<MixBusNodeUIState>) {
+    const defaults = buildDefaultMixBusNodeUIState();
+    this.store.set({
+      ...defaults,
+      ...params,
+      inputs: defaults.inputs.map((input, inputIx) => {
+        const saved = params.inputs?.[inputIx];
+        return {
+          ...input,
+          ...saved,
+          sends: input.sends.map((send, busIx) => saved?.sends?.[busIx] ?? send),
+        };
+      }),
+      bus_gains: defaults.bus_gains.map((gain, busIx) => params.bus_gains?.[busIx] ?? gain),
+      sab: null,
+    });
+  }
+
+  public serialize(): MixBusNodeUIState {
+    return R.clone({ ...get(this.store), sab: null });
+  }
+
+  public buildConnectables() {
+    let inputs = ImmMap<string, ConnectableInput>().set('Master Gain', {
+      node: this.masterGain,
+      type: 'number',
+    });
+    this.inputNodes.forEach((inputNode, inputIx) => {
+      inputs = inputs
+        .set(`Input ${inputIx}`, { node: inputNode, type: 'customAudio' })
+        .set(`Input ${inputIx} Gain`, { node: this.inputGains[inputIx], type: 'number' });
+    });
+
+    let outputs = ImmMap<string, ConnectableOutput>().set('output', {
+      node: this.outputNodes[0],
+      type: 'customAudio',
+    });
+    this.outputNodes.slice(1).forEach((outputNode, busIx) => {
+      outputs = outputs.set(`Bus ${busIx}`, { node: outputNode, type: 'customAudio' });
+    });
+
+    return { vcId: this.vcId, node: this, inputs, outputs };
+  }
+
+  // These are set dynamically at initialization time in the constructor
+  public renderSmallView: ForeignNode['renderSmallView'];
+  public cleanupSmallView: ForeignNode['cleanupSmallView'];
+}
@@ -0,0 +1,416 @@
+import {
+  NativeStateRequests,
+  type NativeModuleState,
+} from 'src/graphEditor/nodes/CustomAudio/nativeState';
+import { setNodeLatency } from 'src/patchNetwork/latencyCompensation';
+import { AsyncOnce } from 'src/util';
+
+/**
+ * Shared by every DSP module built with `module_api::export_module!`.  See `ModuleHostAWP.js`.
+ */
+export const ModuleHostAWPRegistered = new AsyncOnce(
+  () =>
+    new AudioContext().audioWorklet.addModule(
+      process.env.ASSET_PATH +
+        'ModuleHostAWP.js?cacheBust=' +
+        (window.location.href.includes('localhost') ? '' : crypto.randomUUID())
+    ),
+  true
+);
+
+/**
+ * Must match `MAX_AUTOMATABLE_PARAMS` in the `module_api` crate
+ */
+export const MODULE_HOST_MAX_AUTOMATABLE_PARAMS = 16;
+/**
+ * Must match `MAX_CONTROL_ROUTES` in `module_api::control_bus`
+ */
+export const MODULE_HOST_MAX_CONTROL_ROUTES = 8;
+/**
+ * Must match `MAX_MACROS` and `MAX_MACRO_TARGETS` in `module_api::macros`
+ */
+export const MODULE_HOST_MAX_MACROS = 8;
+export const MODULE_HOST_MAX_MACRO_TARGETS = 8;
+/**
+ * Must match `MAX_SCENES` and `MAX_SCENE_GLIDE_MS` in `module_api::scenes`
+ */
+export const MODULE_HOST_MAX_SCENES = 128;
+export const MODULE_HOST_MAX_SCENE_GLIDE_MS = 60_000;
+
+/**
+ * Name of the `AudioParam` on the host node that drives the module's param at `paramIx`.  Only
+ * params below the module's `automatableParamCount` have one.
+ */
+export const getModuleHostParamName = (paramIx: number) => {
+  if (paramIx < 0 || paramIx >= MODULE_HOST_MAX_AUTOMATABLE_PARAMS) {
+    throw new Error(`Module host param index out of range: ${paramIx}`);
+  }
+  return `param_${paramIx}`;
+};
+
+/**
+ * Must match `ChannelLayout` in the `module_api` crate.  Channels are ordered like Web Audio's
+ * speaker layouts.
+ */
+export enum ModuleChannelLayout {
+  Mono = 0,
+  Stereo = 1,
+  /**
+   * L, R, SL, SR
+   */
+  Quad = 2,
+  /**
+   * L, R, C, LFE, SL, SR
+   */
+  Surround5_1 = 3,
+  /**
+   * L, R, C, LFE, SL, SR, BL, BR.  Web Audio has no speaker layout for 8 channels, so outputs with
+   * this layout should be connected to nodes with a `discrete` channel interpretation.
+   */
+  Surround7_1 = 4,
+}
+
+export const getModuleChannelLayoutChannelCount = (layout: ModuleChannelLayout): number => {
+  switch (layout) {
+    case ModuleChannelLayout.Mono:
+      return 1;
+    case ModuleChannelLayout.Stereo:
+      return 2;
+    case ModuleChannelLayout.Quad:
+      return 4;
+    case ModuleChannelLayout.Surround5_1:
+      return 6;
+    case ModuleChannelLayout.Surround7_1:
+      return 8;
+  }
+};
+
+/**
+ * Reported by the host once the module has been instantiated
+ */
+export interface ModuleHostCapabilities {
+  name: string;
+  moduleVersion: number;
+  channelCount: number;
+  outputChannelCount: number;
+  /**
+   * Layout of each of the module's outputs, or empty if all of its output channels go to a single
+   * output
+   */
+  outputLayouts: ModuleChannelLayout[];
+  paramCount: number;
+  automatableParamCount: number;
+  sabLen: number;
+  /**
+   * Index in the SAB of the output guard stats, which follow the module's own entries
+   */
+  outputGuardStatsOffset: number;
+  /**
+   * Index in the SAB of the profiling stats, which follow the output guard stats
+   */
+  profilingStatsOffset: number;
+  /**
+   * Number of control-rate signals the module can publish to the control bus
+   */
+  controlOutputCount: number;
+}
+
+/**
+ * Must match the `STATS_*_IX` constants in `dsp::output_guard`.  Relative to the output guard
+ * stats offset in the SAB, which is the module's own SAB length.
+ */
+export const OUTPUT_GUARD_STATS_CLIPPED_SAMPLE_COUNT_IX = 0;
+export const OUTPUT_GUARD_STATS_NON_FINITE_SAMPLE_COUNT_IX = 1;
+/**
+ * Must match `STATS_LEN` in `dsp::output_guard`
+ */
+export const OUTPUT_GUARD_STATS_LEN = 2;
+
+/**
+ * Must match the constants in `dsp::profiling`.  Relative to the start of each profiling slot.
+ */
+export const PROFILING_STATS_AVG_MS_IX = 0;
+export const PROFILING_STATS_PEAK_MS_IX = 1;
+export const PROFILING_STATS_PER_SLOT = 2;
+
+export const mkModuleWasmBytes = (wasmFileName: string) =>
+  new AsyncOnce(
+    () =>
+      fetch(
+        process.env.ASSET_PATH +
+          wasmFileName +
+          '?cacheBust=' +
+          (window.location.host.includes('localhost') ? '' : crypto.randomUUID())
+      ).then(res => res.arrayBuffer()),
+    true
+  );
+
+const StateRequestsByNode = new WeakMap<AudioWorkletNode, NativeStateRequests>();
+const NodesByVcId = new Map<string, AudioWorkletNode>();
+
+/**
+ * Returns the most recently created host node for the VC `vcId`, if any
+ */
+export const getModuleHostNode = (vcId: string): AudioWorkletNode | undefined =>
+  NodesByVcId.get(vcId);
+
+/**
+ * `[paramIx, value]` pairs of the params in a scene
+ */
+export type ModuleHostSceneValues = [number, number][];
+
+/**
+ * Resolvers for `captureModuleHostScene` calls, in the order that the host will reply to them
+ */
+const SceneCapturesByNode = new WeakMap<
+  AudioWorkletNode,
+  ((values: ModuleHostSceneValues) => void)[]
+>();
+
+/**
+ * Creates a host node and starts loading the module into it.  Messages from the host
+ * (`capabilities`, `sab`) are delivered to `onMessage`.  Replies to state requests are handled
+ * internally; see `getModuleHostState`.  Changes to the module's latency are reported for the VC
+ * `vcId` so that the patch network can compensate for them.
+ *
+ * The node has one output per entry in `outputLayouts`, which must match the module's
+ * `OUTPUT_LAYOUTS`.  If it's not provided, the node has a single output with `channelCount`
+ * channels.
+ */
+export const createModuleHostNode = async (
+  ctx: AudioContext,
+  vcId: string,
+  wasmBytes: AsyncOnce<ArrayBuffer>,
+  channelCount: number,
+  onMessage: (data: Record<string, any>) => void,
+  outputLayouts?: ModuleChannelLayout[]
+): Promise<AudioWorkletNode> => {
+  const [bytes] = await Promise.all([wasmBytes.get(), ModuleHostAWPRegistered.get()] as const);
+  const outputChannelCount = outputLayouts
+    ? outputLayouts.map(getModuleChannelLayoutChannelCount)
+    : [channelCount];
+  const node = new AudioWorkletNode(ctx, 'module-host-awp', {
+    numberOfInputs: 1,
+    numberOfOutputs: outputChannelCount.length,
+    channelCount,
+    channelCountMode: 'explicit',
+    outputChannelCount,
+  });
+  const stateRequests = new NativeStateRequests();
+  StateRequestsByNode.set(node, stateRequests);
+  NodesByVcId.set(vcId, node);
+  const sceneCaptures: ((values: ModuleHostSceneValues) => void)[] = [];
+  SceneCapturesByNode.set(node, sceneCaptures);
+  node.port.onmessage = (e: MessageEvent) => {
+    if (e.data.type === 'state') {
+      stateRequests.handleReply(e.data);
+    } else if (e.data.type === 'sceneCaptured') {
+      sceneCaptures.shift()?.(e.data.values);
+    } else if (e.data.type === 'latency') {
+      setNodeLatency(vcId, e.data.latencySamples);
+    } else {
+      onMessage(e.data);
+    }
+  };
+  node.port.postMessage({ type: 'setWasmBytes', wasmBytes: bytes });
+  return node;
+};
+
+/**
+ * Writes `values` into the module's param buffer starting at `offset`.  Messages sent before the
+ * module has loaded are applied once it does.
+ */
+export const setModuleHostParams = (node: AudioWorkletNode, offset: number, values: number[]) =>
+  node.port.postMessage({ type: 'setParams', offset, values });
+
+/**
+ * Sets param `paramIx` on the exact sample at `time`, given in the `AudioContext`'s timebase,
+ * rather than at the start of the next 128-sample frame.  See `module_api::param_events` in the
+ * engine.
+ */
+export const scheduleModuleHostParamChange = (
+  node: AudioWorkletNode,
+  paramIx: number,
+  value: number,
+  time: number
+) => node.port.postMessage({ type: 'scheduleParamChange', paramIx, value, time });
+
+/**
+ * Drops any param changes scheduled with `scheduleModuleHostParamChange` that haven't landed yet
+ */
+export const clearModuleHostParamEvents = (node: AudioWorkletNode) =>
+  node.port.postMessage({ type: 'clearParamEvents' });
+
+/**
+ * The output guard hard clips, scrubs non-finite samples, and removes DC from the module's outputs.
+ * Off by default.
+ */
+export const setModuleHostOutputGuardEnabled = (node: AudioWorkletNode, enabled: boolean) =>
+  node.port.postMessage({ type: 'setOutputGuardEnabled', enabled });
+
+/**
+ * Tracks how long the module takes to process each frame.  See `ProfilingStats.svelte`.  Off by
+ * default.
+ */
+export const setModuleHostProfilingEnabled = (node: AudioWorkletNode, enabled: boolean) =>
+  node.port.postMessage({ type: 'setProfilingEnabled', enabled });
+
+/**
+ * Snapshots the values of all of the module's params, keyed by `DspModule::param_name`, along with
+ * its macros.
+ */
+export const getModuleHostState = (node: AudioWorkletNode): Promise<NativeModuleState> =>
+  StateRequestsByNode.get(node)!.get(node.port);
+
+/**
+ * Restores params from a snapshot taken with `getModuleHostState`, resolving to the module's
+ * state afterwards.  Automatable params are driven by the host's `AudioParam`s every frame, so
+ * callers must update those from the resolved state for restored values to stick.
+ */
+export const setModuleHostState = (
+  node: AudioWorkletNode,
+  state: NativeModuleState
+): Promise<NativeModuleState> => StateRequestsByNode.get(node)!.set(node.port, state);
+
+/**
+ * Switches the module to A/B `slot`, resolving to the slot's state.  As with `setModuleHostState`,
+ * callers must update the host's `AudioParam`s from the resolved state.
+ */
+export const selectModuleHostABSlot = (
+  node: AudioWorkletNode,
+  slot: number,
+  fadeMs: number
+): Promise<NativeModuleState> => StateRequestsByNode.get(node)!.selectAB(node.port, slot, fadeMs);
+
+/**
+ * Publishes the module's control output `outputIx` to the control bus channel `busChannel`, or
+ * stops publishing it if `busChannel` is null.  Any module in the same `AudioContext` can subscribe
+ * to the channel with `routeModuleHostControlInput`.  See `module_api::control_bus` in the engine.
+ */
+export const publishModuleHostControlOutput = (
+  node: AudioWorkletNode,
+  outputIx: number,
+  busChannel: string | null
+) => node.port.postMessage({ type: 'setControlOutputBusChannel', outputIx, channel: busChannel });
+
+export interface ModuleHostControlRoute {
+  busChannel: string;
+  paramIx: number;
+  /**
+   * The bus signal is multiplied by this before being added to the param
+   */
+  depth: number;
+}
+
+/**
+ * Modulates one of the module's params from a control bus channel, or removes the route in slot
+ * `routeIx` if `route` is null.  Up to `MODULE_HOST_MAX_CONTROL_ROUTES` routes can be active at
+ * once, and several can target the same param.  Values from modules processed later in the audio
+ * graph arrive one 128-sample frame late.
+ */
+export const routeModuleHostControlInput = (
+  node: AudioWorkletNode,
+  routeIx: number,
+  route: ModuleHostControlRoute | null
+) => {
+  if (routeIx < 0 || routeIx >= MODULE_HOST_MAX_CONTROL_ROUTES) {
+    throw new Error(`Module host control route index out of range: ${routeIx}`);
+  }
+  node.port.postMessage({
+    type: 'setControlInputRoute',
+    routeIx,
+    route: route
+      ? { channel: route.busChannel, paramIx: route.paramIx, depth: route.depth }
+      : null,
+  });
+};
+
+/**
+ * Sets macro `macroIx` to `value`, from 0 to 1, updating all of its targets at the start of the
+ * next frame.  See `module_api::macros` in the engine.
+ */
+export const setModuleHostMacroValue = (node: AudioWorkletNode, macroIx: number, value: number) =>
+  node.port.postMessage({ type: 'setMacroValue', macroIx, value });
+
+export interface ModuleHostMacroTarget {
+  paramIx: number;
+  /**
+   * Param value when the macro is at 0.  Can be above `max` to invert the mapping.
+   */
+  min: number;
+  /**
+   * Param value when the macro is at 1
+   */
+  max: number;
+  /**
+   * From -1 to 1.  0 maps the macro linearly; positive values spend more of its travel near `min`
+   * and negative values more of it near `max`.
+   */
+  curve: number;
+}
+
+/**
+ * Maps macro `macroIx` onto a param, or removes the target in slot `targetIx` if `target` is null.
+ * Targeted params are overwritten by the macro every frame, so automating them directly has no
+ * effect.  Each param can be targeted at most once per macro.  Macros are saved in the module's
+ * state; see `getModuleHostState`.
+ */
+export const setModuleHostMacroTarget = (
+  node: AudioWorkletNode,
+  macroIx: number,
+  targetIx: number,
+  target: ModuleHostMacroTarget | null
+) => {
+  if (macroIx < 0 || macroIx >= MODULE_HOST_MAX_MACROS) {
+    throw new Error(`Module host macro index out of range: ${macroIx}`);
+  }
+  if (targetIx < 0 || targetIx >= MODULE_HOST_MAX_MACRO_TARGETS) {
+    throw new Error(`Module host macro target index out of range: ${targetIx}`);
+  }
+  node.port.postMessage({ type: 'setMacroTarget', macroIx, targetIx, target });
+};
+
+const checkSceneIx = (sceneIx: number) => {
+  if (sceneIx < 0 || sceneIx >= MODULE_HOST_MAX_SCENES) {
+    throw new Error(`Module host scene index out of range: ${sceneIx}`);
+  }
+};
+
+/**
+ * Replaces the params in scene `sceneIx` with `values`, or empties it if `values` is null.  See
+ * `module_api::scenes` in the engine.
+ */
+export const setModuleHostScene = (
+  node: AudioWorkletNode,
+  sceneIx: number,
+  values: ModuleHostSceneValues | null
+) => {
+  checkSceneIx(sceneIx);
+  node.port.postMessage({ type: 'setScene', sceneIx, values });
+};
+
+/**
+ * Replaces the params in scene `sceneIx` with the current values of the params in `paramIxs`,
+ * resolving to the captured values.  Resolves once the module has loaded if it hasn't yet.
+ */
+export const captureModuleHostScene = (
+  node: AudioWorkletNode,
+  sceneIx: number,
+  paramIxs: number[]
+): Promise<ModuleHostSceneValues> => {
+  checkSceneIx(sceneIx);
+  return new Promise(resolve => {
+    SceneCapturesByNode.get(node)!.push(resolve);
+    node.port.postMessage({ type: 'captureScene', sceneIx, paramIxs });
+  });
+};
+
+/**
+ * Glides the params in scene `sceneIx` to their values in it over `glideMs`.  As with
+ * `setModuleHostState`, params driven by the host's `AudioParam`s snap back to them once the glide
+ * completes unless callers update them.
+ */
+export const recallModuleHostScene = (node: AudioWorkletNode, sceneIx: number, glideMs: number) => {
+  checkSceneIx(sceneIx);
+  node.port.postMessage({ type: 'recallScene', sceneIx, glideMs });
+};
@@ -0,0 +1,278 @@
+import { Map as ImmMap } from 'immutable';
+import * as R from 'ramda';
+import { get, writable, type Writable } from 'svelte/store';
+
+import { getGlobalBpm, setGlobalBpm } from 'src/globalMenu';
+import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio/CustomAudio';
+import {
+  createModuleHostNode,
+  mkModuleWasmBytes,
+  setModuleHostOutputGuardEnabled,
+  setModuleHostProfilingEnabled,
+  setModuleHostParams,
+} from 'src/graphEditor/nodes/CustomAudio/ModuleHost/moduleHost';
+import DummyNode from 'src/graphEditor/nodes/DummyNode';
+import type { OverridableAudioParam } from 'src/graphEditor/nodes/util';
+import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
+import { updateConnectables } from 'src/patchNetwork/interface';
+import { getSentry } from 'src/sentry';
+import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils';
+import OnsetDetectorSmallView from './OnsetDetectorSmallView.svelte';
+
+/**
+ * Must match the param layout of the `onset_detector` crate
+ */
+const PARAM_SENSITIVITY_IX = 0;
+
+/**
+ * Must match the SAB layout of the `onset_detector` crate.  Times are in seconds of audio processed
+ * by the module ("stream time").
+ */
+const SAB_STREAM_TIME_IX = 0;
+const SAB_ONSET_COUNT_IX = 1;
+const SAB_BPM_IX = 2;
+const SAB_PHASE_IX = 3;
+const SAB_CONFIDENCE_IX = 4;
+const SAB_ONSET_TIMES_OFFSET = 5;
+const ONSET_HISTORY_LEN = 16;
+export const ONSET_DETECTOR_SAB_LEN = SAB_ONSET_TIMES_OFFSET + ONSET_HISTORY_LEN;
+
+/**
+ * How often the global tempo is updated from the estimate while syncing
+ */
+const TRANSPORT_SYNC_INTERVAL_MS = 500;
+/**
+ * Estimates less confident than this don't change the global tempo
+ */
+const MIN_SYNC_CONFIDENCE = 0.3;
+
+export interface OnsetDetectorReading {
+  /**
+   * Stream time at the end of the most recently processed frame
+   */
+  streamTime: number;
+  /**
+   * Total number of onsets detected so far
+   */
+  onsetCount: number;
+  /**
+   * `null` until enough onsets have been detected to estimate the tempo
+   */
+  bpm: number | null;
+  /**
+   * Position within the current beat at `streamTime`, from 0 (on the beat) up to 1
+   */
+  phase: number;
+  confidence: number;
+}
+
+export const readOnsetDetector = (sab: Float32Array): OnsetDetectorReading => ({
+  streamTime: sab[SAB_STREAM_TIME_IX],
+  onsetCount: sab[SAB_ONSET_COUNT_IX],
+  bpm: sab[SAB_BPM_IX] || null,
+  phase: sab[SAB_PHASE_IX],
+  confidence: sab[SAB_CONFIDENCE_IX],
+});
+
+/**
+ * Returns the stream times of the onsets detected after the first `sinceCount`, oldest first.  Only
+ * the most recent onsets are kept, so older ones may have been dropped if this isn't polled often.
+ *
+ * Stream times can be converted to `AudioContext` time by subtracting them from the reading's
+ * `streamTime` and subtracting that from `ctx.currentTime`.
+ */
+export const readOnsetTimes = (sab: Float32Array, sinceCount: number): number[] => {
+  const onsetCount = sab[SAB_ONSET_COUNT_IX];
+  const start = Math.max(sinceCount, onsetCount - ONSET_HISTORY_LEN);
+  return R.range(start, onsetCount).map(
+    onsetIx => sab[SAB_ONSET_TIMES_OFFSET + (onsetIx % ONSET_HISTORY_LEN)]
+  );
+};
+
+export interface OnsetDetectorNodeUIState {
+  /**
+   * From 0 to 1.  Higher values detect quieter and less sharp attacks.
+   */
+  sensitivity: number;
+  /**
+   * Tempos outside of this range are doubled or halved into it
+   */
+  min_bpm: number;
+  max_bpm: number;
+  /**
+   * Sets the global tempo from the estimated tempo of the input
+   */
+  sync_transport: boolean;
+  output_guard: boolean;
+  profiling: boolean;
+  sab: Float32Array | null;
+}
+
+export const buildDefaultOnsetDetectorNodeUIState = (): OnsetDetectorNodeUIState => ({
+  sensitivity: 0.5,
+  min_bpm: 80,
+  max_bpm: 160,
+  sync_transport: false,
+  output_guard: false,
+  profiling: false,
+  sab: null,
+});
+
+const OnsetDetectorWasmBytes = mkModuleWasmBytes('onset_detector.wasm');
+
+export class OnsetDetectorNode implements ForeignNode {
+  private dummyInput = new DummyNode('OnsetDetectorNodeInput');
+  private dummyOutput = new DummyNode('OnsetDetectorNodeOutput');
+  private ctx: AudioContext;
+  private vcId: string;
+  private awpHandle: AudioWorkletNode | null = null;
+  private store: Writable<OnsetDetectorNodeUIState> = writable(
+    buildDefaultOnsetDetectorNodeUIState()
+  );
+  private syncIntervalHandle: number | null = null;
+
+  static typeName = 'Onset Detector';
+  public nodeType = 'customAudio/onsetDetector';
+
+  public paramOverrides: {
+    [name: string]: { param: OverridableAudioParam; override: ConstantSourceNode };
+  } = {};
+
+  constructor(ctx: AudioContext, vcId?: string, params?: { [key: string]: any } | null) {
+    this.ctx = ctx;
+    if (!vcId) {
+      throw new Error('vcId is required');
+    }
+    this.vcId = vcId;
+
+    if (params) {
+      this.deserialize(params as Partial<OnsetDetectorNodeUIState>);
+    }
+
+    let unsubscribe: (() => void) | undefined;
+
+    this.renderSmallView = mkSvelteContainerRenderHelper({
+      Comp: OnsetDetectorSmallView,
+      getProps: () => ({ store: this.store }),
+      predicate: () => {
+        unsubscribe = this.store.subscribe(state => this.onChange(state));
+      },
+    });
+
+    this.cleanupSmallView = mkSvelteContainerCleanupHelper({
+      preserveRoot: true,
+      predicate: () => {
+        try {
+          unsubscribe?.();
+        } catch (e) {
+          console.warn('Error unsubscribing from onset detector store', e);
+        }
+      },
+    });
+
+    this.init().catch(err => {
+      console.error('Error initializing onset detector node', err);
+      getSentry()?.captureException(err);
+    });
+  }
+
+  private handleMessageFromAWP = (data: Record<string, any>) => {
+    switch (data.type) {
+      case 'capabilities':
+        // The param layout is fixed for this module, so there's nothing to pick up
+        break;
+      case 'sab': {
+        const sab = data.sab as SharedArrayBuffer;
+        this.store.update(s => ({ ...s, sab: new Float32Array(sab) }));
+        break;
+      }
+      default:
+        console.warn('Unknown message from AWP', data);
+    }
+  };
+
+  private async init() {
+    this.awpHandle = await createModuleHostNode(
+      this.ctx,
+      this.vcId,
+      OnsetDetectorWasmBytes,
+      1,
+      this.handleMessageFromAWP
+    );
+
+    this.onChange(get(this.store));
+    updateConnectables(this.vcId, this.buildConnectables());
+  }
+
+  private syncTransport = () => {
+    const sab = get(this.store).sab;
+    if (!sab) {
+      return;
+    }
+
+    const { bpm, confidence } = readOnsetDetector(sab);
+    if (bpm === null || confidence < MIN_SYNC_CONFIDENCE) {
+      return;
+    }
+    const roundedBpm = Math.round(bpm * 10) / 10;
+    if (roundedBpm !== getGlobalBpm()) {
+      setGlobalBpm(roundedBpm);
+    }
+  };
+
+  private onChange = (newState: OnsetDetectorNodeUIState) => {
+    if (newState.sync_transport && this.syncIntervalHandle === null) {
+      this.syncIntervalHandle = window.setInterval(this.syncTransport, TRANSPORT_SYNC_INTERVAL_MS);
+    } else if (!newState.sync_transport && this.syncIntervalHandle !== null) {
+      window.clearInterval(this.syncIntervalHandle);
+      this.syncIntervalHandle = null;
+    }
+
+    if (!this.awpHandle) {
+      return;
+    }
+
+    setModuleHostOutputGuardEnabled(this.awpHandle, newState.output_guard);
+    setModuleHostProfilingEnabled(this.awpHandle, newState.profiling);
+    setModuleHostParams(this.awpHandle, PARAM_SENSITIVITY_IX, [
+      newState.sensitivity,
+      newState.min_bpm,
+      newState.max_bpm,
+    ]);
+  };
+
+  private deserialize(params: Partial<OnsetDetectorNodeUIState>) {
+    this.store.set({ ...buildDefaultOnsetDetectorNodeUIState(), ...params, sab: null });
+  }
+
+  public serialize(): OnsetDetectorNodeUIState {
+    return R.clone({ ...get(this.store), sab: null });
+  }
+
+  /**
+   * The SAB that the module writes onsets and tempo estimates to, or `null` if it hasn't loaded
+   * yet or `SharedArrayBuffer` isn't available.  See `readOnsetDetector` and `readOnsetTimes`.
+   */
+  public getSAB(): Float32Array | null {
+    return get(this.store).sab;
+  }
+
+  public buildConnectables() {
+    return {
+      vcId: this.vcId,
+      node: this,
+      inputs: ImmMap<string, ConnectableInput>().set('input', {
+        node: this.awpHandle ? this.awpHandle : this.dummyInput,
+        type: 'customAudio',
+      }),
+      outputs: ImmMap<string, ConnectableOutput>().set('output', {
+        node: this.awpHandle ? this.awpHandle : this.dummyOutput,
+        type: 'customAudio',
+      }),
+    };
+  }
+
+  // These are set dynamically at initialization time in the constructor
+  public renderSmallView: ForeignNode['renderSmallView'];
+  public cleanupSmallView: ForeignNode['cleanupSmallView'];
+}
@@ -0,0 +1,254 @@
+import { Map as ImmMap } from 'immutable';
+import * as R from 'ramda';
+import { get, writable, type Writable } from 'svelte/store';
+
+import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio/CustomAudio';
+import {
+  NativeStateRequests,
+  type NativeModuleState,
+} from 'src/graphEditor/nodes/CustomAudio/nativeState';
+import DummyNode from 'src/graphEditor/nodes/DummyNode';
+import { OverridableAudioParam } from 'src/graphEditor/nodes/util';
+import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
+import { updateConnectables } from 'src/patchNetwork/interface';
+import { getSentry } from 'src/sentry';
+import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils';
+import { AsyncOnce } from 'src/util';
+import ReverbSmallView from './ReverbSmallView.svelte';
+
+/**
+ * Layout of the SAB shared by the reverb AWP.  Must match the `SAB_*_IX` constants in the `reverb`
+ * crate.
+ */
+export const REVERB_SAB_INPUT_LEVEL_DB_IX = 0;
+export const REVERB_SAB_OUTPUT_LEVEL_DB_IX = 1;
+export const REVERB_SAB_WET_LEVEL_DB_IX = 2;
+
+export interface ReverbParams {
+  room_size: number;
+  damping: number;
+  width: number;
+  mix: number;
+  pre_delay_ms: number;
+}
+
+export interface ReverbNodeUIState extends ReverbParams {
+  bypass: boolean;
+  sab: Float32Array | null;
+}
+
+const PARAM_NAMES: (keyof ReverbParams)[] = [
+  'room_size',
+  'damping',
+  'width',
+  'mix',
+  'pre_delay_ms',
+];
+
+export const buildDefaultReverbNodeUIState = (): ReverbNodeUIState => ({
+  room_size: 0.5,
+  damping: 0.5,
+  width: 1,
+  mix: 0.3,
+  pre_delay_ms: 0,
+  bypass: false,
+  sab: null,
+});
+
+const ReverbWasmBytes = new AsyncOnce(
+  () => fetch(process.env.ASSET_PATH + 'reverb.wasm').then(res => res.arrayBuffer()),
+  true
+);
+const ctx = new AudioContext();
+const ReverbAWPRegistered = new AsyncOnce(
+  () =>
+    ctx.audioWorklet.addModule(
+      process.env.ASSET_PATH +
+        'ReverbAWP.js?cacheBust=' +
+        (window.location.href.includes('localhost') ? '' : btoa(Math.random().toString()))
+    ),
+  true
+);
+
+export class ReverbNode implements ForeignNode {
+  private dummyInput = new DummyNode('ReverbNodeInput');
+  private dummyOutput = new DummyNode('ReverbNodeOutput');
+  private ctx: AudioContext;
+  private vcId: string;
+  private awpHandle: AudioWorkletNode | null = null;
+  private store: Writable<ReverbNodeUIState> = writable(buildDefaultReverbNodeUIState());
+  private stateRequests = new NativeStateRequests();
+  private params: Record<keyof ReverbParams, OverridableAudioParam | DummyNode> = {
+    room_size: new DummyNode(),
+    damping: new DummyNode(),
+    width: new DummyNode(),
+    mix: new DummyNode(),
+    pre_delay_ms: new DummyNode(),
+  };
+
+  static typeName = 'Reverb';
+  public nodeType = 'customAudio/reverb';
+
+  public paramOverrides: {
+    [name: string]: { param: OverridableAudioParam; override: ConstantSourceNode };
+  } = {};
+
+  constructor(ctx: AudioContext, vcId?: string, params?: { [key: string]: any } | null) {
+    this.ctx = ctx;
+    if (!vcId) {
+      throw new Error('vcId is required');
+    }
+    this.vcId = vcId;
+
+    if (params) {
+      this.deserialize(params as Partial<ReverbNodeUIState>);
+    }
+
+    let unsubscribe: (() => void) | undefined;
+
+    this.renderSmallView = mkSvelteContainerRenderHelper({
+      Comp: ReverbSmallView,
+      getProps: () => ({ store: this.store, onSelectABSlot: this.selectABSlot }),
+      predicate: () => {
+        unsubscribe = this.store.subscribe(state => this.onChange(state));
+      },
+    });
+
+    this.cleanupSmallView = mkSvelteContainerCleanupHelper({
+      preserveRoot: true,
+      predicate: () => {
+        try {
+          unsubscribe?.();
+        } catch (e) {
+          console.warn('Error unsubscribing from reverb store', e);
+        }
+      },
+    });
+
+    this.init().catch(err => {
+      console.error('Error initializing reverb node', err);
+      getSentry()?.captureException(err);
+    });
+  }
+
+  private handleMessageFromAWP = (e: MessageEvent) => {
+    const data = e.data as Record<string, any>;
+    switch (data.type) {
+      case 'sab': {
+        const sab = data.sab as SharedArrayBuffer;
+        this.store.update(s => ({ ...s, sab: new Float32Array(sab) }));
+        break;
+      }
+      case 'state': {
+        this.stateRequests.handleReply(data as any);
+        break;
+      }
+      default:
+        console.warn('Unknown message from AWP', data);
+    }
+  };
+
+  private async init() {
+    const [wasmBytes] = await Promise.all([
+      ReverbWasmBytes.get(),
+      ReverbAWPRegistered.get(),
+    ] as const);
+    this.awpHandle = new AudioWorkletNode(this.ctx, 'reverb-awp', {
+      numberOfInputs: 1,
+      numberOfOutputs: 1,
+      channelCount: 2,
+      outputChannelCount: [2],
+    });
+    this.awpHandle.port.onmessage = (e: MessageEvent) => this.handleMessageFromAWP(e);
+
+    const awpParams = this.awpHandle.parameters as Map<string, AudioParam>;
+    for (const name of PARAM_NAMES) {
+      this.params[name] = new OverridableAudioParam(ctx, awpParams.get(name)!, undefined, true);
+    }
+
+    const state = get(this.store);
+    this.awpHandle.port.postMessage({ type: 'setWasmBytes', wasmBytes });
+    this.onChange(state);
+    updateConnectables(this.vcId, this.buildConnectables());
+  }
+
+  private onChange = (newState: ReverbNodeUIState) => {
+    if (!this.awpHandle) {
+      return;
+    }
+
+    this.awpHandle.port.postMessage({ type: 'setBypassed', bypass: newState.bypass });
+    for (const name of PARAM_NAMES) {
+      (this.params[name] as OverridableAudioParam).manualControl.offset.value = newState[name];
+    }
+  };
+
+  private deserialize(params: Partial<ReverbNodeUIState>) {
+    this.store.set({ ...buildDefaultReverbNodeUIState(), ...params, sab: null });
+  }
+
+  public serialize(): ReverbNodeUIState {
+    return R.clone({ ...get(this.store), sab: null });
+  }
+
+  public getNativeState = async (): Promise<NativeModuleState> => {
+    if (!this.awpHandle) {
+      throw new Error('Reverb has not been initialized');
+    }
+    return this.stateRequests.get(this.awpHandle.port);
+  };
+
+  public setNativeState = async (state: NativeModuleState) => {
+    if (!this.awpHandle) {
+      throw new Error('Reverb has not been initialized');
+    }
+    this.applyNativeState(await this.stateRequests.set(this.awpHandle.port, state));
+  };
+
+  public selectABSlot = async (slot: number, fadeMs: number) => {
+    if (!this.awpHandle) {
+      throw new Error('Reverb has not been initialized');
+    }
+    this.applyNativeState(await this.stateRequests.selectAB(this.awpHandle.port, slot, fadeMs));
+  };
+
+  /**
+   * The AWP feeds params into the engine every frame, so state applied on the engine side has to
+   * be applied to the `AudioParam`s as well
+   */
+  private applyNativeState = (applied: NativeModuleState) => {
+    const newState = { ...get(this.store) };
+    for (const name of PARAM_NAMES) {
+      const val = applied[name];
+      if (typeof val === 'number') {
+        newState[name] = val;
+      }
+    }
+    this.store.set(newState);
+    this.onChange(newState);
+  };
+
+  public buildConnectables() {
+    let inputs = ImmMap<string, ConnectableInput>().set('input', {
+      node: this.awpHandle ? this.awpHandle : this.dummyInput,
+      type: 'customAudio',
+    });
+    for (const name of PARAM_NAMES) {
+      inputs = inputs.set(name, { node: this.params[name], type: 'number' });
+    }
+
+    return {
+      vcId: this.vcId,
+      node: this,
+      inputs,
+      outputs: ImmMap<string, ConnectableOutput>().set('output', {
+        node: this.awpHandle ? this.awpHandle : this.dummyOutput,
+        type: 'customAudio',
+      }),
+    };
+  }
+
+  // These are set dynamically at initialization time in the constructor
+  public renderSmallView: ForeignNode['renderSmallView'];
+  public cleanupSmallView: ForeignNode['cleanupSmallView'];
+}
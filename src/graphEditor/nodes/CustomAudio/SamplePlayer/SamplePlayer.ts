@@ -37,11 +37,37 @@ export interface SampleCrossfadeParams {
   threshold: number;
 }
 
+export enum TimeStretchQuality {
+  Fast = 0,
+  Balanced = 1,
+  High = 2,
+}
+
+/**
+ * If enabled, the sample follows the global tempo without being repitched by playing it back at
+ * the ratio of the global BPM to `sourceBPM`.  Higher qualities sound smoother but use more CPU and
+ * respond to tempo changes more slowly.
+ */
+export interface SampleTimeStretchParams {
+  enabled: boolean;
+  sourceBPM: number;
+  quality: TimeStretchQuality;
+  preserveTransients: boolean;
+}
+
+export const buildDefaultTimeStretchParams = (): SampleTimeStretchParams => ({
+  enabled: false,
+  sourceBPM: 120,
+  quality: TimeStretchQuality.Balanced,
+  preserveTransients: true,
+});
+
 export interface SamplePlayerSampleDescriptor {
   id: string;
   descriptor: SampleDescriptor;
   sample: AudioBuffer | null;
   crossfadeParams: SampleCrossfadeParams;
+  timeStretchParams: SampleTimeStretchParams;
   gain: number;
 }
 
@@ -81,6 +107,7 @@ export default class SamplePlayerNode implements ForeignNode {
         removeSample: this.removeSample.bind(this),
         setSampleGain: this.setSampleGain.bind(this),
         setSampleCrossfadeParams: this.setSampleCrossfadeParams.bind(this),
+        setSampleTimeStretchParams: this.setSampleTimeStretchParams.bind(this),
         setSampleDescriptor: this.setSampleDescriptor.bind(this),
         initialState: [...this.sampleDescriptors],
       }),
@@ -126,6 +153,11 @@ export default class SamplePlayerNode implements ForeignNode {
         enabled: desc.crossfadeParams.enabled,
         threshold: desc.crossfadeParams.threshold,
       });
+      this.awpHandle!.port.postMessage({
+        type: 'setSampleTimeStretchParams',
+        voiceIx: i,
+        params: desc.timeStretchParams,
+      });
     });
 
     if (!R.isNil(this.vcId)) {
@@ -153,7 +185,8 @@ export default class SamplePlayerNode implements ForeignNode {
     descriptor: SampleDescriptor,
     id: string,
     gain?: number,
-    crossfadeParams?: SampleCrossfadeParams
+    crossfadeParams?: SampleCrossfadeParams,
+    timeStretchParams?: SampleTimeStretchParams
   ) {
     this.sampleDescriptors.push({
       id,
@@ -164,6 +197,7 @@ export default class SamplePlayerNode implements ForeignNode {
         enabled: false,
         threshold: 0,
       },
+      timeStretchParams: timeStretchParams ?? buildDefaultTimeStretchParams(),
     });
 
     if (this.awpHandle) {
@@ -196,6 +230,16 @@ export default class SamplePlayerNode implements ForeignNode {
     });
   }
 
+  private setSampleTimeStretchParams(voiceIx: number, timeStretchParams: SampleTimeStretchParams) {
+    this.sampleDescriptors[voiceIx].timeStretchParams = timeStretchParams;
+
+    this.awpHandle?.port.postMessage({
+      type: 'setSampleTimeStretchParams',
+      voiceIx,
+      params: timeStretchParams,
+    });
+  }
+
   private removeSample(index: number) {
     this.sampleDescriptors = R.remove(index, 1, this.sampleDescriptors);
 
@@ -231,7 +275,8 @@ export default class SamplePlayerNode implements ForeignNode {
             descriptor.descriptor,
             btoa(Math.random().toString()),
             descriptor.gain,
-            descriptor.crossfadeParams
+            descriptor.crossfadeParams,
+            descriptor.timeStretchParams
           );
         }
       );
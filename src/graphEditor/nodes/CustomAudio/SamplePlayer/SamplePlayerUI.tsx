@@ -3,14 +3,22 @@ import * as R from 'ramda';
 import React, { useCallback, useMemo, useReducer, useRef } from 'react';
 import ControlPanel from 'react-control-panel';
 
-import type {
-  SampleCrossfadeParams,
-  SamplePlayerSampleDescriptor,
+import {
+  buildDefaultTimeStretchParams,
+  TimeStretchQuality,
+  type SampleCrossfadeParams,
+  type SamplePlayerSampleDescriptor,
+  type SampleTimeStretchParams,
 } from 'src/graphEditor/nodes/CustomAudio/SamplePlayer/SamplePlayer';
 import type { SampleDescriptor } from 'src/sampleLibrary/sampleLibrary';
 import { selectSample } from 'src/sampleLibrary/SampleLibraryUI/SelectSample';
 
 const MAX_SAMPLE_COUNT = 8;
+const STRETCH_QUALITY_OPTIONS: { [label: string]: TimeStretchQuality } = {
+  fast: TimeStretchQuality.Fast,
+  balanced: TimeStretchQuality.Balanced,
+  high: TimeStretchQuality.High,
+};
 
 interface SamplePlayerUIProps {
   initialState: SamplePlayerSampleDescriptor[];
@@ -18,6 +26,7 @@ interface SamplePlayerUIProps {
   removeSample: (index: number) => void;
   setSampleGain: (index: number, gain: number) => void;
   setSampleCrossfadeParams: (index: number, crossfadeParams: SampleCrossfadeParams) => void;
+  setSampleTimeStretchParams: (index: number, timeStretchParams: SampleTimeStretchParams) => void;
   setSampleDescriptor: (index: number, descriptor: SampleDescriptor) => void;
 }
 
@@ -26,6 +35,7 @@ type SamplePlayerUIState = SamplePlayerSampleDescriptor[];
 type SamplePlayerUIAction =
   | { type: 'SET_GAIN'; index: number; gain: number }
   | { type: 'SET_CROSSFADE_PARAMS'; index: number; crossfadeParams: SampleCrossfadeParams }
+  | { type: 'SET_TIME_STRETCH_PARAMS'; index: number; timeStretchParams: SampleTimeStretchParams }
   | { type: 'SET_SAMPLE'; index: number; descriptor: SampleDescriptor }
   | { type: 'ADD_SAMPLE'; descriptor: SampleDescriptor }
   | { type: 'REMOVE_SAMPLE'; index: number };
@@ -36,6 +46,7 @@ const buildDefaultSlot = (descriptor: SampleDescriptor): SamplePlayerSampleDescr
   sample: null,
   descriptor,
   crossfadeParams: { enabled: false, threshold: 0 },
+  timeStretchParams: buildDefaultTimeStretchParams(),
 });
 
 const mkSamplePlayerUIReducer =
@@ -44,6 +55,7 @@ const mkSamplePlayerUIReducer =
     removeSample,
     setSampleGain,
     setSampleCrossfadeParams,
+    setSampleTimeStretchParams,
     setSampleDescriptor,
   }: Pick<
     SamplePlayerUIProps,
@@ -51,6 +63,7 @@ const mkSamplePlayerUIReducer =
     | 'removeSample'
     | 'setSampleGain'
     | 'setSampleCrossfadeParams'
+    | 'setSampleTimeStretchParams'
     | 'setSampleDescriptor'
   >) =>
   (state: SamplePlayerUIState, action: SamplePlayerUIAction): SamplePlayerUIState => {
@@ -69,6 +82,15 @@ const mkSamplePlayerUIReducer =
           state
         );
       }
+      case 'SET_TIME_STRETCH_PARAMS': {
+        const slot = state[action.index];
+        setSampleTimeStretchParams(action.index, action.timeStretchParams);
+        return R.set(
+          R.lensIndex(action.index),
+          { ...slot, timeStretchParams: action.timeStretchParams },
+          state
+        );
+      }
       case 'SET_SAMPLE': {
         const slot = state[action.index] ?? buildDefaultSlot(action.descriptor);
         setSampleDescriptor(action.index, action.descriptor);
@@ -85,6 +107,7 @@ const mkSamplePlayerUIReducer =
             gain: 1,
             sample: null,
             crossfadeParams: { enabled: false, threshold: 0 },
+            timeStretchParams: buildDefaultTimeStretchParams(),
           },
         ];
       }
@@ -111,6 +134,10 @@ const ConfigureSample: React.FC<ConfigureSampleProps> = ({ index, descriptor, di
     gain: descriptor.gain,
     'enable crossfade': descriptor.crossfadeParams.enabled,
     'crossfade threshold': descriptor.crossfadeParams.threshold,
+    'time stretch': descriptor.timeStretchParams.enabled,
+    'source bpm': descriptor.timeStretchParams.sourceBPM,
+    'stretch quality': TimeStretchQuality[descriptor.timeStretchParams.quality].toLowerCase(),
+    'preserve transients': descriptor.timeStretchParams.preserveTransients,
   });
   const settings = useMemo(
     () =>
@@ -128,13 +155,35 @@ const ConfigureSample: React.FC<ConfigureSampleProps> = ({ index, descriptor, di
         descriptor.crossfadeParams.enabled
           ? { type: 'range', label: 'crossfade threshold', min: 0, max: 1 }
           : null,
+        { type: 'checkbox', label: 'time stretch' },
+        ...(descriptor.timeStretchParams.enabled
+          ? [
+              { type: 'range', label: 'source bpm', min: 40, max: 240, step: 0.5 },
+              {
+                type: 'select',
+                label: 'stretch quality',
+                options: Object.keys(STRETCH_QUALITY_OPTIONS),
+              },
+              { type: 'checkbox', label: 'preserve transients' },
+            ]
+          : []),
         {
           type: 'button',
           label: 'delete',
           action: () => dispatch({ type: 'REMOVE_SAMPLE', index }),
         },
       ]),
-    [dispatch, index, descriptor.crossfadeParams.enabled]
+    [dispatch, index, descriptor.crossfadeParams.enabled, descriptor.timeStretchParams.enabled]
+  );
+
+  const setTimeStretchParams = useCallback(
+    (newParams: Partial<SampleTimeStretchParams>) =>
+      dispatch({
+        type: 'SET_TIME_STRETCH_PARAMS',
+        index,
+        timeStretchParams: { ...descriptor.timeStretchParams, ...newParams },
+      }),
+    [descriptor.timeStretchParams, dispatch, index]
   );
 
   const handleChange = useCallback(
@@ -160,12 +209,28 @@ const ConfigureSample: React.FC<ConfigureSampleProps> = ({ index, descriptor, di
           });
           break;
         }
+        case 'time stretch': {
+          setTimeStretchParams({ enabled: value });
+          break;
+        }
+        case 'source bpm': {
+          setTimeStretchParams({ sourceBPM: value });
+          break;
+        }
+        case 'stretch quality': {
+          setTimeStretchParams({ quality: STRETCH_QUALITY_OPTIONS[value] });
+          break;
+        }
+        case 'preserve transients': {
+          setTimeStretchParams({ preserveTransients: value });
+          break;
+        }
         default: {
           console.error('Unhandled key in `ConfigureSample`: ' + key);
         }
       }
     },
-    [descriptor.crossfadeParams.threshold, dispatch, index]
+    [descriptor.crossfadeParams.threshold, dispatch, index, setTimeStretchParams]
   );
 
   return (
@@ -209,6 +274,7 @@ const SamplePlayerUI: React.FC<SamplePlayerUIProps> = ({
   removeSample,
   setSampleGain,
   setSampleCrossfadeParams,
+  setSampleTimeStretchParams,
   setSampleDescriptor,
 }) => {
   const reducer = useMemo(
@@ -218,9 +284,17 @@ const SamplePlayerUI: React.FC<SamplePlayerUIProps> = ({
         removeSample,
         setSampleGain,
         setSampleCrossfadeParams,
+        setSampleTimeStretchParams,
         setSampleDescriptor,
       }),
-    [addSample, removeSample, setSampleDescriptor, setSampleGain, setSampleCrossfadeParams]
+    [
+      addSample,
+      removeSample,
+      setSampleDescriptor,
+      setSampleGain,
+      setSampleCrossfadeParams,
+      setSampleTimeStretchParams,
+    ]
   );
   const [state, dispatch] = useReducer(reducer, initialState);
 
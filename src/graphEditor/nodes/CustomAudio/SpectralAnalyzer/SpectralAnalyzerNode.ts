@@ -0,0 +1,251 @@
+import { Map as ImmMap } from 'immutable';
+import * as R from 'ramda';
+import { get, writable, type Writable } from 'svelte/store';
+
+import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio/CustomAudio';
+import {
+  createModuleHostNode,
+  mkModuleWasmBytes,
+  ModuleChannelLayout,
+  setModuleHostOutputGuardEnabled,
+  setModuleHostProfilingEnabled,
+  setModuleHostParams,
+} from 'src/graphEditor/nodes/CustomAudio/ModuleHost/moduleHost';
+import DummyNode from 'src/graphEditor/nodes/DummyNode';
+import type { OverridableAudioParam } from 'src/graphEditor/nodes/util';
+import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
+import { updateConnectables } from 'src/patchNetwork/interface';
+import { getSentry } from 'src/sentry';
+import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils';
+import SpectralAnalyzerSmallView from './SpectralAnalyzerSmallView.svelte';
+
+/**
+ * Must match `BAND_EDGES_HZ` in `engine/dsp/src/spectral_features.rs`
+ */
+export const SPECTRAL_ANALYZER_BAND_EDGES_HZ = [
+  20, 60, 150, 400, 1_000, 2_500, 6_000, 12_000, 20_000,
+];
+export const SPECTRAL_ANALYZER_BAND_COUNT = SPECTRAL_ANALYZER_BAND_EDGES_HZ.length - 1;
+
+/**
+ * Must match the outputs of the `spectral_analyzer` crate.  The input is passed through on the
+ * first output, followed by the centroid, rolloff, and flatness as control signals from 0 to 1.
+ */
+const MOD_OUTPUT_NAMES = ['centroid', 'rolloff', 'flatness'];
+const OUTPUT_LAYOUTS = R.times(() => ModuleChannelLayout.Mono, 1 + MOD_OUTPUT_NAMES.length);
+
+/**
+ * Must match the param layout of the `spectral_analyzer` crate
+ */
+const PARAM_SMOOTHING_MS_IX = 0;
+
+/**
+ * Must match the SAB layout of the `spectral_analyzer` crate
+ */
+const SAB_CENTROID_HZ_IX = 0;
+const SAB_ROLLOFF_HZ_IX = 1;
+const SAB_FLATNESS_IX = 2;
+const SAB_RMS_IX = 3;
+const SAB_BAND_RMS_OFFSET = 4;
+export const SPECTRAL_ANALYZER_SAB_LEN = SAB_BAND_RMS_OFFSET + SPECTRAL_ANALYZER_BAND_COUNT;
+
+export interface SpectralFeatures {
+  centroidHz: number;
+  rolloffHz: number;
+  /**
+   * Near 0 for tonal sounds and near 1 for noise
+   */
+  flatness: number;
+  rms: number;
+  /**
+   * Linear RMS level within each of `SPECTRAL_ANALYZER_BAND_EDGES_HZ`
+   */
+  bandRMS: number[];
+}
+
+/**
+ * Reads the most recently measured features out of the analyzer's SAB.  Cheap enough to call every
+ * animation frame for driving visuals.
+ */
+export const readSpectralFeatures = (sab: Float32Array): SpectralFeatures => ({
+  centroidHz: sab[SAB_CENTROID_HZ_IX],
+  rolloffHz: sab[SAB_ROLLOFF_HZ_IX],
+  flatness: sab[SAB_FLATNESS_IX],
+  rms: sab[SAB_RMS_IX],
+  bandRMS: Array.from(
+    sab.subarray(SAB_BAND_RMS_OFFSET, SAB_BAND_RMS_OFFSET + SPECTRAL_ANALYZER_BAND_COUNT)
+  ),
+});
+
+export interface SpectralAnalyzerNodeUIState {
+  /**
+   * How quickly the modulation outputs follow changes in the features
+   */
+  smoothing_ms: number;
+  /**
+   * Portion of the energy that lies below the rolloff frequency
+   */
+  rolloff_fraction: number;
+  output_guard: boolean;
+  profiling: boolean;
+  sab: Float32Array | null;
+}
+
+export const buildDefaultSpectralAnalyzerNodeUIState = (): SpectralAnalyzerNodeUIState => ({
+  smoothing_ms: 50,
+  rolloff_fraction: 0.85,
+  output_guard: false,
+  profiling: false,
+  sab: null,
+});
+
+const SpectralAnalyzerWasmBytes = mkModuleWasmBytes('spectral_analyzer.wasm');
+
+export class SpectralAnalyzerNode implements ForeignNode {
+  private dummyInput = new DummyNode('SpectralAnalyzerNodeInput');
+  private ctx: AudioContext;
+  private vcId: string;
+  private awpHandle: AudioWorkletNode | null = null;
+  private store: Writable<SpectralAnalyzerNodeUIState> = writable(
+    buildDefaultSpectralAnalyzerNodeUIState()
+  );
+  /**
+   * The audio passthrough first, followed by each modulation output.  Each is fed by the matching
+   * output of the module.
+   */
+  private outputNodes: GainNode[];
+
+  static typeName = 'Spectral Analyzer';
+  public nodeType = 'customAudio/spectralAnalyzer';
+
+  public paramOverrides: {
+    [name: string]: { param: OverridableAudioParam; override: ConstantSourceNode };
+  } = {};
+
+  constructor(ctx: AudioContext, vcId?: string, params?: { [key: string]: any } | null) {
+    this.ctx = ctx;
+    if (!vcId) {
+      throw new Error('vcId is required');
+    }
+    this.vcId = vcId;
+
+    this.outputNodes = OUTPUT_LAYOUTS.map(
+      () => new GainNode(ctx, { channelCount: 1, channelCountMode: 'explicit' })
+    );
+
+    if (params) {
+      this.deserialize(params as Partial<SpectralAnalyzerNodeUIState>);
+    }
+
+    let unsubscribe: (() => void) | undefined;
+
+    this.renderSmallView = mkSvelteContainerRenderHelper({
+      Comp: SpectralAnalyzerSmallView,
+      getProps: () => ({ store: this.store }),
+      predicate: () => {
+        unsubscribe = this.store.subscribe(state => this.onChange(state));
+      },
+    });
+
+    this.cleanupSmallView = mkSvelteContainerCleanupHelper({
+      preserveRoot: true,
+      predicate: () => {
+        try {
+          unsubscribe?.();
+        } catch (e) {
+          console.warn('Error unsubscribing from spectral analyzer store', e);
+        }
+      },
+    });
+
+    this.init().catch(err => {
+      console.error('Error initializing spectral analyzer node', err);
+      getSentry()?.captureException(err);
+    });
+  }
+
+  private handleMessageFromAWP = (data: Record<string, any>) => {
+    switch (data.type) {
+      case 'capabilities':
+        // The param layout is fixed for this module, so there's nothing to pick up
+        break;
+      case 'sab': {
+        const sab = data.sab as SharedArrayBuffer;
+        this.store.update(s => ({ ...s, sab: new Float32Array(sab) }));
+        break;
+      }
+      default:
+        console.warn('Unknown message from AWP', data);
+    }
+  };
+
+  private async init() {
+    this.awpHandle = await createModuleHostNode(
+      this.ctx,
+      this.vcId,
+      SpectralAnalyzerWasmBytes,
+      1,
+      this.handleMessageFromAWP,
+      OUTPUT_LAYOUTS
+    );
+    this.outputNodes.forEach((outputNode, outputIx) =>
+      this.awpHandle!.connect(outputNode, outputIx)
+    );
+
+    this.onChange(get(this.store));
+    updateConnectables(this.vcId, this.buildConnectables());
+  }
+
+  private onChange = (newState: SpectralAnalyzerNodeUIState) => {
+    if (!this.awpHandle) {
+      return;
+    }
+
+    setModuleHostOutputGuardEnabled(this.awpHandle, newState.output_guard);
+    setModuleHostProfilingEnabled(this.awpHandle, newState.profiling);
+    setModuleHostParams(this.awpHandle, PARAM_SMOOTHING_MS_IX, [
+      newState.smoothing_ms,
+      newState.rolloff_fraction,
+    ]);
+  };
+
+  private deserialize(params: Partial<SpectralAnalyzerNodeUIState>) {
+    this.store.set({ ...buildDefaultSpectralAnalyzerNodeUIState(), ...params, sab: null });
+  }
+
+  public serialize(): SpectralAnalyzerNodeUIState {
+    return R.clone({ ...get(this.store), sab: null });
+  }
+
+  /**
+   * The SAB that the module writes its features to, or `null` if it hasn't loaded yet or
+   * `SharedArrayBuffer` isn't available.  See `readSpectralFeatures`.
+   */
+  public getSAB(): Float32Array | null {
+    return get(this.store).sab;
+  }
+
+  public buildConnectables() {
+    let outputs = ImmMap<string, ConnectableOutput>().set('output', {
+      node: this.outputNodes[0],
+      type: 'customAudio',
+    });
+    MOD_OUTPUT_NAMES.forEach((name, modOutputIx) => {
+      outputs = outputs.set(name, { node: this.outputNodes[1 + modOutputIx], type: 'number' });
+    });
+
+    return {
+      vcId: this.vcId,
+      node: this,
+      inputs: ImmMap<string, ConnectableInput>().set('input', {
+        node: this.awpHandle ? this.awpHandle : this.dummyInput,
+        type: 'customAudio',
+      }),
+      outputs,
+    };
+  }
+
+  // These are set dynamically at initialization time in the constructor
+  public renderSmallView: ForeignNode['renderSmallView'];
+  public cleanupSmallView: ForeignNode['cleanupSmallView'];
+}
@@ -0,0 +1,214 @@
+import { Map as ImmMap } from 'immutable';
+import * as R from 'ramda';
+import { get, writable, type Writable } from 'svelte/store';
+
+import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio/CustomAudio';
+import {
+  createModuleHostNode,
+  getModuleHostParamName,
+  mkModuleWasmBytes,
+  setModuleHostOutputGuardEnabled,
+  setModuleHostProfilingEnabled,
+  setModuleHostParams,
+} from 'src/graphEditor/nodes/CustomAudio/ModuleHost/moduleHost';
+import DummyNode from 'src/graphEditor/nodes/DummyNode';
+import { OverridableAudioParam } from 'src/graphEditor/nodes/util';
+import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
+import { updateConnectables } from 'src/patchNetwork/interface';
+import { getSentry } from 'src/sentry';
+import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils';
+import SpectralGateSmallView from './SpectralGateSmallView.svelte';
+
+/**
+ * Must match `BAND_COUNT` and `CROSSOVER_FREQS_HZ` in the `spectral_gate` crate.  The SAB holds the
+ * current gain of each band.
+ */
+export const SPECTRAL_GATE_BAND_COUNT = 8;
+export const SPECTRAL_GATE_CROSSOVER_FREQS_HZ = [100, 200, 400, 800, 1600, 3200, 6400];
+export const SPECTRAL_GATE_SAB_LEN = SPECTRAL_GATE_BAND_COUNT;
+
+/**
+ * Must match the param layout of the `spectral_gate` crate.  Band params follow `reduction_db`,
+ * interleaved per band as threshold_db, attack_ms, release_ms.
+ */
+const PARAM_REDUCTION_DB_IX = 0;
+const BAND_PARAMS_OFFSET = 1;
+
+export interface SpectralGateBandState {
+  threshold_db: number;
+  attack_ms: number;
+  release_ms: number;
+}
+
+export interface SpectralGateNodeUIState {
+  /**
+   * Attenuation applied to bands while their gates are closed
+   */
+  reduction_db: number;
+  bands: SpectralGateBandState[];
+  bypass: boolean;
+  output_guard: boolean;
+  profiling: boolean;
+  sab: Float32Array | null;
+}
+
+const buildDefaultSpectralGateBandState = (): SpectralGateBandState => ({
+  threshold_db: -60,
+  attack_ms: 1,
+  release_ms: 100,
+});
+
+export const buildDefaultSpectralGateNodeUIState = (): SpectralGateNodeUIState => ({
+  reduction_db: -60,
+  bands: R.times(buildDefaultSpectralGateBandState, SPECTRAL_GATE_BAND_COUNT),
+  bypass: false,
+  output_guard: false,
+  profiling: false,
+  sab: null,
+});
+
+const SpectralGateWasmBytes = mkModuleWasmBytes('spectral_gate.wasm');
+
+export class SpectralGateNode implements ForeignNode {
+  private dummyInput = new DummyNode('SpectralGateNodeInput');
+  private dummyOutput = new DummyNode('SpectralGateNodeOutput');
+  private ctx: AudioContext;
+  private vcId: string;
+  private awpHandle: AudioWorkletNode | null = null;
+  private store: Writable<SpectralGateNodeUIState> = writable(
+    buildDefaultSpectralGateNodeUIState()
+  );
+  private reductionDb: OverridableAudioParam | DummyNode = new DummyNode();
+
+  static typeName = 'Spectral Gate';
+  public nodeType = 'customAudio/spectralGate';
+
+  public paramOverrides: {
+    [name: string]: { param: OverridableAudioParam; override: ConstantSourceNode };
+  } = {};
+
+  constructor(ctx: AudioContext, vcId?: string, params?: { [key: string]: any } | null) {
+    this.ctx = ctx;
+    if (!vcId) {
+      throw new Error('vcId is required');
+    }
+    this.vcId = vcId;
+
+    if (params) {
+      this.deserialize(params as Partial<SpectralGateNodeUIState>);
+    }
+
+    let unsubscribe: (() => void) | undefined;
+
+    this.renderSmallView = mkSvelteContainerRenderHelper({
+      Comp: SpectralGateSmallView,
+      getProps: () => ({ store: this.store }),
+      predicate: () => {
+        unsubscribe = this.store.subscribe(state => this.onChange(state));
+      },
+    });
+
+    this.cleanupSmallView = mkSvelteContainerCleanupHelper({
+      preserveRoot: true,
+      predicate: () => {
+        try {
+          unsubscribe?.();
+        } catch (e) {
+          console.warn('Error unsubscribing from spectral gate store', e);
+        }
+      },
+    });
+
+    this.init().catch(err => {
+      console.error('Error initializing spectral gate node', err);
+      getSentry()?.captureException(err);
+    });
+  }
+
+  private handleMessageFromAWP = (data: Record<string, any>) => {
+    switch (data.type) {
+      case 'capabilities':
+        // The param layout is fixed for this module, so there's nothing to pick up
+        break;
+      case 'sab': {
+        const sab = data.sab as SharedArrayBuffer;
+        this.store.update(s => ({ ...s, sab: new Float32Array(sab) }));
+        break;
+      }
+      default:
+        console.warn('Unknown message from AWP', data);
+    }
+  };
+
+  private async init() {
+    this.awpHandle = await createModuleHostNode(
+      this.ctx,
+      this.vcId,
+      SpectralGateWasmBytes,
+      2,
+      this.handleMessageFromAWP
+    );
+
+    const awpParams = this.awpHandle.parameters as Map<string, AudioParam>;
+    this.reductionDb = new OverridableAudioParam(
+      this.ctx,
+      awpParams.get(getModuleHostParamName(PARAM_REDUCTION_DB_IX))!,
+      undefined,
+      true
+    );
+
+    this.onChange(get(this.store));
+    updateConnectables(this.vcId, this.buildConnectables());
+  }
+
+  private onChange = (newState: SpectralGateNodeUIState) => {
+    if (!this.awpHandle) {
+      return;
+    }
+
+    this.awpHandle.port.postMessage({ type: 'setBypassed', bypass: newState.bypass });
+    setModuleHostOutputGuardEnabled(this.awpHandle, newState.output_guard);
+    setModuleHostProfilingEnabled(this.awpHandle, newState.profiling);
+    setModuleHostParams(
+      this.awpHandle,
+      BAND_PARAMS_OFFSET,
+      newState.bands.flatMap(band => [band.threshold_db, band.attack_ms, band.release_ms])
+    );
+    (this.reductionDb as OverridableAudioParam).manualControl.offset.value = newState.reduction_db;
+  };
+
+  private deserialize(params: Partial<SpectralGateNodeUIState>) {
+    const defaults = buildDefaultSpectralGateNodeUIState();
+    this.store.set({
+      ...defaults,
+      ...params,
+      bands: defaults.bands.map((band, bandIx) => ({ ...band, ...params.bands?.[bandIx] })),
+      sab: null,
+    });
+  }
+
+  public serialize(): SpectralGateNodeUIState {
+    return R.clone({ ...get(this.store), sab: null });
+  }
+
+  public buildConnectables() {
+    return {
+      vcId: this.vcId,
+      node: this,
+      inputs: ImmMap<string, ConnectableInput>()
+        .set('input', {
+          node: this.awpHandle ? this.awpHandle : this.dummyInput,
+          type: 'customAudio',
+        })
+        .set('reduction_db', { node: this.reductionDb, type: 'number' }),
+      outputs: ImmMap<string, ConnectableOutput>().set('output', {
+        node: this.awpHandle ? this.awpHandle : this.dummyOutput,
+        type: 'customAudio',
+      }),
+    };
+  }
+
+  // These are set dynamically at initialization time in the constructor
+  public renderSmallView: ForeignNode['renderSmallView'];
+  public cleanupSmallView: ForeignNode['cleanupSmallView'];
+}
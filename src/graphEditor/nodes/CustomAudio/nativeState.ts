@@ -0,0 +1,55 @@
+/**
+ * Param state of a native (Wasm) engine module, keyed by param name.  See `common::state` in the
+ * engine.
+ */
+export type NativeModuleState = Record<string, number | boolean>;
+
+/**
+ * Tracks `getState` and `setState` requests sent to an AWP hosting a native module.  The AWP
+ * replies to each request in order with a `state` message containing the module's state after the
+ * request was handled, which must be passed to `handleReply`.
+ */
+export class NativeStateRequests {
+  private pending: {
+    resolve: (state: NativeModuleState) => void;
+    reject: (err: Error) => void;
+  }[] = [];
+
+  private request(port: MessagePort, msg: Record<string, any>): Promise<NativeModuleState> {
+    return new Promise((resolve, reject) => {
+      this.pending.push({ resolve, reject });
+      port.postMessage(msg);
+    });
+  }
+
+  public get = (port: MessagePort) => this.request(port, { type: 'getState' });
+
+  /**
+   * Params missing from `state` keep their current values.  If any param is invalid, nothing is
+   * applied and the returned promise rejects.
+   */
+  public set = (port: MessagePort, state: NativeModuleState) =>
+    this.request(port, { type: 'setState', state });
+
+  /**
+   * Saves the current params into the active A/B slot (A = 0, B = 1) and crossfades to the params
+   * in `slot` over `fadeMs`, resolving to them.  A slot that hasn't been used yet starts as a copy
+   * of the current params.  See `common::state::ABCompare` in the engine.
+   */
+  public selectAB = (port: MessagePort, slot: number, fadeMs: number) =>
+    this.request(port, { type: 'abSelect', slot, fadeMs });
+
+  public handleReply(data: { state: NativeModuleState | null; error?: string }) {
+    const req = this.pending.shift();
+    if (!req) {
+      console.warn('Received native module state without a pending request');
+      return;
+    }
+
+    if (data.error || !data.state) {
+      req.reject(new Error(data.error ?? 'Native module not loaded'));
+    } else {
+      req.resolve(data.state);
+    }
+  }
+}
@@ -3,6 +3,7 @@ import { createRoot } from 'react-dom/client';
 import { QueryClientProvider as ReactQueryProvider } from 'react-query';
 import { Provider } from 'react-redux';
 
+import { initLessonHooks } from 'src/lessons/lessonHooks';
 import { createBrowserNotSupportedMessage } from 'src/misc/BrowserNotSupported';
 import {
   fetchAndLoadSharedComposition,
@@ -10,6 +11,7 @@ import {
   onBeforeUnload,
 } from 'src/persistance';
 import { getReactQueryClient } from 'src/reactUtils';
+import { initSafetyLimiter } from 'src/safetyLimiter';
 import { initializeDefaultVCMState } from 'src/redux/modules/vcmUtils';
 import { getSentry, initSentry } from 'src/sentry';
 import { setEngine } from 'src/util';
@@ -50,6 +52,9 @@ if (typeof AudioWorkletNode === 'undefined') {
 } else {
   initSentry();
 
+  initSafetyLimiter(ctx);
+  initLessonHooks();
+
   wasm.then(async engine => {
     setEngine(engine);
 
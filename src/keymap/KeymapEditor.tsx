@@ -0,0 +1,120 @@
+import React, { useEffect, useReducer, useState } from 'react';
+
+import type { ModalCompProps } from 'src/controls/Modal';
+import {
+  chordFromEvent,
+  formatChord,
+  getKeyBindings,
+  KeymapActions,
+  resetKeymap,
+  setKeyBindings,
+  type KeymapAction,
+} from 'src/keymap';
+import './KeymapEditor.scss';
+
+type KeymapEditorProps = ModalCompProps<undefined>;
+
+interface KeymapEditorRowProps {
+  action: KeymapAction;
+  isRecording: boolean;
+  onStartRecording: () => void;
+  onChange: () => void;
+}
+
+const KeymapEditorRow: React.FC<KeymapEditorRowProps> = ({
+  action,
+  isRecording,
+  onStartRecording,
+  onChange,
+}) => {
+  const bindings = getKeyBindings(action);
+
+  return (
+    <div className='keymap-editor-row'>
+      <div className='keymap-editor-action'>{KeymapActions[action].description}</div>
+      <div className='keymap-editor-bindings'>
+        {bindings.map(chord => (
+          <span className='keymap-editor-chord' key={chord}>
+            {chord}
+            <button
+              title='Remove binding'
+              onClick={() => {
+                setKeyBindings(action, bindings.filter(other => other !== chord));
+                onChange();
+              }}
+            >
+              ×
+            </button>
+          </span>
+        ))}
+        <button onClick={onStartRecording} disabled={isRecording}>
+          {isRecording ? 'Press keys...' : 'Add'}
+        </button>
+      </div>
+    </div>
+  );
+};
+
+/**
+ * Lists all actions along with the chords they're bound to and allows adding and removing bindings
+ */
+export const KeymapEditor: React.FC<KeymapEditorProps> = ({ onSubmit }) => {
+  const [, forceUpdate] = useReducer((x: number) => x + 1, 0);
+  const [recordingAction, setRecordingAction] = useState<KeymapAction | null>(null);
+
+  useEffect(() => {
+    if (!recordingAction) {
+      return;
+    }
+
+    // Registered in the capture phase so that views don't also handle the keypress
+    const handleKeyDown = (evt: KeyboardEvent) => {
+      evt.preventDefault();
+      evt.stopPropagation();
+      if (evt.code === 'Escape') {
+        setRecordingAction(null);
+        return;
+      }
+
+      const chord = chordFromEvent(evt);
+      if (!chord) {
+        return;
+      }
+
+      const formatted = formatChord(chord);
+      const bindings = getKeyBindings(recordingAction);
+      if (!bindings.includes(formatted)) {
+        setKeyBindings(recordingAction, [...bindings, formatted]);
+      }
+      setRecordingAction(null);
+    };
+    document.addEventListener('keydown', handleKeyDown, { capture: true });
+    return () => document.removeEventListener('keydown', handleKeyDown, { capture: true });
+  }, [recordingAction]);
+
+  return (
+    <div className='keymap-editor'>
+      <h2>Keyboard Shortcuts</h2>
+      {(Object.keys(KeymapActions) as KeymapAction[]).map(action => (
+        <KeymapEditorRow
+          key={action}
+          action={action}
+          isRecording={recordingAction === action}
+          onStartRecording={() => setRecordingAction(action)}
+          onChange={forceUpdate}
+        />
+      ))}
+      <div className='keymap-editor-buttons'>
+        <button
+          onClick={() => {
+            resetKeymap();
+            forceUpdate();
+          }}
+        >
+          Reset to Defaults
+        </button>
+        <button onClick={() => onSubmit(undefined)}>Close</button>
+      </div>
+    </div>
+  );
+};
@@ -0,0 +1,237 @@
+/**
+ * Maps keyboard shortcuts to actions.  Views look up which action a `KeyboardEvent` triggers rather
+ * than matching on keys directly so that users can customize the bindings.
+ *
+ * Bindings are stored as lists of chords like `Ctrl+Shift+KeyD` where the last component is a
+ * `KeyboardEvent.code`, making them independent of keyboard layout.  Only bindings that differ from
+ * the defaults are persisted.
+ */
+
+const KEYMAP_LOCALSTORAGE_KEY = 'keymap';
+
+export const KeymapActions = {
+  'midiEditor.deleteSelection': {
+    description: 'MIDI Editor: Delete selected notes',
+    defaultBindings: ['Delete'],
+  },
+  'midiEditor.copySelection': {
+    description: 'MIDI Editor: Copy selected notes',
+    defaultBindings: ['Ctrl+KeyC'],
+  },
+  'midiEditor.cutSelection': {
+    description: 'MIDI Editor: Cut selected notes',
+    defaultBindings: ['Ctrl+KeyX'],
+  },
+  'midiEditor.paste': {
+    description: 'MIDI Editor: Paste notes at the cursor',
+    defaultBindings: ['Ctrl+KeyV'],
+  },
+  'midiEditor.scrollLeft': {
+    description: 'MIDI Editor: Scroll left one beat',
+    defaultBindings: ['ArrowLeft'],
+  },
+  'midiEditor.scrollRight': {
+    description: 'MIDI Editor: Scroll right one beat',
+    defaultBindings: ['ArrowRight'],
+  },
+  'midiEditor.transposeUpScaleDegree': {
+    description: 'MIDI Editor: Transpose selected notes up one degree of the scale',
+    defaultBindings: ['Alt+ArrowUp'],
+  },
+  'midiEditor.transposeDownScaleDegree': {
+    description: 'MIDI Editor: Transpose selected notes down one degree of the scale',
+    defaultBindings: ['Alt+ArrowDown'],
+  },
+  'midiEditor.invertChords': {
+    description: 'MIDI Editor: Invert selected chords by moving their lowest note up an octave',
+    defaultBindings: ['Alt+KeyI'],
+  },
+  'midiEditor.toggleStaccato': {
+    description: 'MIDI Editor: Toggle staccato on selected notes',
+    defaultBindings: ['Alt+KeyS'],
+  },
+  'midiEditor.toggleAccent': {
+    description: 'MIDI Editor: Toggle accent on selected notes',
+    defaultBindings: ['Alt+KeyA'],
+  },
+  'midiEditor.toggleTie': {
+    description: 'MIDI Editor: Toggle tie to the next note on selected notes',
+    defaultBindings: ['Alt+KeyT'],
+  },
+  'midiEditor.selectRow': {
+    description: 'MIDI Editor: Select all notes on the last clicked row',
+    defaultBindings: ['Alt+KeyR'],
+  },
+  'midiEditor.selectEveryOther': {
+    description: 'MIDI Editor: Narrow the selection to every other selected note',
+    defaultBindings: ['Alt+KeyE'],
+  },
+  'midiEditor.toggleInputRecording': {
+    description: 'MIDI Editor: Start recording note edits, or stop and download the recording',
+    defaultBindings: ['Ctrl+Alt+KeyI'],
+  },
+} as const;
+
+export type KeymapAction = keyof typeof KeymapActions;
+
+export interface KeyChord {
+  code: string;
+  ctrl: boolean;
+  shift: boolean;
+  alt: boolean;
+  meta: boolean;
+}
+
+const MODIFIERS: [string, 'ctrl' | 'shift' | 'alt' | 'meta'][] = [
+  ['Ctrl', 'ctrl'],
+  ['Shift', 'shift'],
+  ['Alt', 'alt'],
+  ['Meta', 'meta'],
+];
+
+const MODIFIER_CODES = new Set([
+  'ControlLeft',
+  'ControlRight',
+  'ShiftLeft',
+  'ShiftRight',
+  'AltLeft',
+  'AltRight',
+  'MetaLeft',
+  'MetaRight',
+]);
+
+/**
+ * Parses a chord like `Ctrl+Shift+KeyD`, returning `null` if it's invalid.
+ */
+export const parseChord = (chord: string): KeyChord | null => {
+  const parts = chord.split('+').map(part => part.trim());
+  const code = parts.pop();
+  if (!code || MODIFIER_CODES.has(code)) {
+    return null;
+  }
+
+  const parsed: KeyChord = { code, ctrl: false, shift: false, alt: false, meta: false };
+  for (const modifier of parts) {
+    const key = MODIFIERS.find(([name]) => name === modifier)?.[1];
+    if (!key) {
+      return null;
+    }
+    parsed[key] = true;
+  }
+  return parsed;
+};
+
+export const formatChord = (chord: KeyChord): string =>
+  [...MODIFIERS.filter(([, key]) => chord[key]).map(([name]) => name), chord.code].join('+');
+
+/**
+ * Returns the chord pressed in the provided event, or `null` if only modifier keys are pressed.
+ */
+export const chordFromEvent = (evt: KeyboardEvent): KeyChord | null => {
+  if (MODIFIER_CODES.has(evt.code)) {
+    return null;
+  }
+
+  return {
+    code: evt.code,
+    ctrl: evt.ctrlKey,
+    shift: evt.shiftKey,
+    alt: evt.altKey,
+    meta: evt.metaKey,
+  };
+};
+
+const loadCustomBindings = (): Partial<Record<KeymapAction, string[]>> => {
+  const serialized = localStorage.getItem(KEYMAP_LOCALSTORAGE_KEY);
+  if (!serialized) {
+    return {};
+  }
+
+  try {
+    const parsed = JSON.parse(serialized);
+    const bindings: Partial<Record<KeymapAction, string[]>> = {};
+    for (const [action, chords] of Object.entries(parsed)) {
+      if (!(action in KeymapActions) || !Array.isArray(chords)) {
+        console.warn(`Ignoring invalid keymap entry for action "${action}"`);
+        continue;
+      }
+      bindings[action as KeymapAction] = chords.filter(
+        chord => typeof chord === 'string' && parseChord(chord)
+      );
+    }
+    return bindings;
+  } catch (err) {
+    console.error('Failed to parse keymap from localStorage; using defaults', err);
+    return {};
+  }
+};
+
+let customBindings = loadCustomBindings();
+/**
+ * Lookup table from formatted chord to the actions that it triggers
+ */
+let actionsByChord: Map<string, KeymapAction[]> = new Map();
+
+const buildActionsByChord = () => {
+  actionsByChord = new Map();
+  for (const action of Object.keys(KeymapActions) as KeymapAction[]) {
+    for (const chord of getKeyBindings(action)) {
+      const parsed = parseChord(chord);
+      if (!parsed) {
+        continue;
+      }
+      const formatted = formatChord(parsed);
+      actionsByChord.set(formatted, [...(actionsByChord.get(formatted) ?? []), action]);
+    }
+  }
+};
+
+export const getKeyBindings = (action: KeymapAction): readonly string[] =>
+  customBindings[action] ?? KeymapActions[action].defaultBindings;
+
+/**
+ * Replaces the bindings for `action`, persisting them to `localStorage`.
+ */
+export const setKeyBindings = (action: KeymapAction, chords: string[]) => {
+  const normalized = chords.flatMap(chord => {
+    const parsed = parseChord(chord);
+    return parsed ? [formatChord(parsed)] : [];
+  });
+
+  const defaults: readonly string[] = KeymapActions[action].defaultBindings;
+  if (
+    normalized.length === defaults.length &&
+    normalized.every((chord, i) => chord === defaults[i])
+  ) {
+    delete customBindings[action];
+  } else {
+    customBindings[action] = normalized;
+  }
+  localStorage.setItem(KEYMAP_LOCALSTORAGE_KEY, JSON.stringify(customBindings));
+  buildActionsByChord();
+};
+
+export const resetKeymap = () => {
+  customBindings = {};
+  localStorage.removeItem(KEYMAP_LOCALSTORAGE_KEY);
+  buildActionsByChord();
+};
+
+/**
+ * Returns the first of `candidates` bound to the chord pressed in `evt`, if any.  Views pass the
+ * actions that they handle so that the same chord can be bound to actions in different views.
+ */
+export function matchKeymapAction<A extends KeymapAction>(
+  evt: KeyboardEvent,
+  candidates: readonly A[]
+): A | null {
+  const chord = chordFromEvent(evt);
+  if (!chord) {
+    return null;
+  }
+
+  const actions = actionsByChord.get(formatChord(chord));
+  return (candidates.find(action => actions?.includes(action)) as A | undefined) ?? null;
+}
+
+buildActionsByChord();
@@ -0,0 +1,186 @@
+import {
+  getIsGlobalBeatCounterStarted,
+  registerGlobalStartCB,
+  registerGlobalStopCB,
+  startAll,
+  stopAll,
+} from 'src/eventScheduler/eventScheduler';
+import { getMIDIEditorInstance } from 'src/midiEditor';
+import type { HighlightRegion } from 'src/midiEditor/HighlightRegions';
+
+/**
+ * Events that embedded lesson scripts can subscribe to in order to follow along with what the user
+ * is doing.
+ */
+export type LessonEvent =
+  | {
+      type: 'noteDrawn';
+      vcId: string;
+      instanceID: string;
+      lineIx: number;
+      startPoint: number;
+      length: number;
+    }
+  | { type: 'playbackStarted' }
+  | { type: 'playbackStopped' }
+  | {
+      type: 'presetLoaded';
+      /**
+       * What the preset was loaded into, for example `'synthDesigner'` or `'composition'`
+       */
+      target: string;
+      vcId?: string;
+      presetID?: number | string;
+    }
+  /**
+   * Named checkpoint reached by the lesson script itself
+   */
+  | { type: 'checkpoint'; name: string };
+
+export type LessonEventType = LessonEvent['type'];
+type LessonEventOfType<T extends LessonEventType> = Extract<LessonEvent, { type: T }>;
+
+const Subscribers: Map<LessonEventType, Set<(evt: any) => void>> = new Map();
+
+/**
+ * Registers `cb` to be called every time an event of type `type` is emitted.
+ *
+ * @returns a function that unsubscribes the callback
+ */
+export const subscribeLessonEvent = <T extends LessonEventType>(
+  type: T,
+  cb: (evt: LessonEventOfType<T>) => void
+): (() => void) => {
+  let subscribers = Subscribers.get(type);
+  if (!subscribers) {
+    subscribers = new Set();
+    Subscribers.set(type, subscribers);
+  }
+  subscribers.add(cb);
+
+  return () => {
+    Subscribers.get(type)?.delete(cb);
+  };
+};
+
+export const emitLessonEvent = (evt: LessonEvent) => {
+  const subscribers = Subscribers.get(evt.type);
+  if (!subscribers) {
+    return;
+  }
+
+  // Subscribers can unsubscribe themselves while being called
+  for (const cb of [...subscribers]) {
+    try {
+      cb(evt);
+    } catch (err) {
+      console.error(`Error in lesson event subscriber for "${evt.type}": `, err);
+    }
+  }
+};
+
+/**
+ * Resolves with the first event of type `type` that matches `predicate`.  Lesson scripts can use
+ * this to wait for the user to complete a step before moving on to the next one.
+ */
+export const waitForLessonEvent = <T extends LessonEventType>(
+  type: T,
+  predicate: (evt: LessonEventOfType<T>) => boolean = () => true
+): Promise<LessonEventOfType<T>> =>
+  new Promise(resolve => {
+    const unsubscribe = subscribeLessonEvent(type, evt => {
+      if (predicate(evt)) {
+        unsubscribe();
+        resolve(evt);
+      }
+    });
+  });
+
+export const reachLessonCheckpoint = (name: string) =>
+  emitLessonEvent({ type: 'checkpoint', name });
+
+let transportHooksInstalled = false;
+
+/**
+ * Forwards global beat counter start/stop to lesson subscribers.  Called once at startup.
+ */
+export const initLessonHooks = () => {
+  if (transportHooksInstalled) {
+    return;
+  }
+  transportHooksInstalled = true;
+
+  registerGlobalStartCB(() => emitLessonEvent({ type: 'playbackStarted' }));
+  registerGlobalStopCB(() => emitLessonEvent({ type: 'playbackStopped' }));
+
+  // Lesson scripts are loaded separately from the main bundle and find the hooks here
+  (window as any).lessonHooks = {
+    subscribe: subscribeLessonEvent,
+    waitFor: waitForLessonEvent,
+    reachCheckpoint: reachLessonCheckpoint,
+    highlightMIDIEditorRegions,
+    simulateNoteDrawn,
+    simulatePlayback,
+  };
+};
+
+/**
+ * Highlights regions of a MIDI editor instance's grid to draw the user's attention to them.  Pass
+ * an empty array to clear existing highlights.
+ */
+export const highlightMIDIEditorRegions = (
+  vcId: string,
+  instanceID: string,
+  regions: HighlightRegion[]
+) => {
+  const uiInst = getMIDIEditorInstance(vcId)?.uiManager.getUIInstanceByID(instanceID);
+  if (!uiInst) {
+    console.warn(`Lesson tried to highlight regions of missing MIDI editor ${vcId}/${instanceID}`);
+    return;
+  }
+
+  uiInst.setHighlightRegions(regions);
+};
+
+/**
+ * Draws a note into a MIDI editor instance as if the user had drawn it, emitting a `noteDrawn`
+ * event.
+ *
+ * @returns ID of the created note, or `null` if the note couldn't be added
+ */
+export const simulateNoteDrawn = (
+  vcId: string,
+  instanceID: string,
+  lineIx: number,
+  startPoint: number,
+  length: number
+): number | null => {
+  const uiInst = getMIDIEditorInstance(vcId)?.uiManager.getUIInstanceByID(instanceID);
+  if (!uiInst?.wasm) {
+    console.warn(`Lesson tried to draw note into missing MIDI editor ${vcId}/${instanceID}`);
+    return null;
+  }
+  const { instance, noteLinesCtxPtr } = uiInst.wasm;
+  if (!instance.check_can_add_note(noteLinesCtxPtr, lineIx, startPoint, length)) {
+    return null;
+  }
+
+  const id = uiInst.addNote(lineIx, startPoint, length);
+  emitLessonEvent({ type: 'noteDrawn', vcId, instanceID, lineIx, startPoint, length });
+  return id;
+};
+
+/**
+ * Starts or stops global playback as if the user had pressed the play/stop button.
+ */
+export const simulatePlayback = (play: boolean) => {
+  if (play === getIsGlobalBeatCounterStarted()) {
+    return;
+  }
+
+  if (play) {
+    startAll();
+  } else {
+    stopAll();
+  }
+};
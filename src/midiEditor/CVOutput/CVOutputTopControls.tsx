@@ -1,8 +1,9 @@
+import * as R from 'ramda';
 import React from 'react';
 
 import { MIDIEditorControlButton } from 'src/midiEditor/MIDIEditorControlButton';
 import './CVOutputTopControls.css';
-import { MIDIEditorInstance } from 'src/midiEditor';
+import { DEFAULT_DRUM_LANES, MIDIEditorInstance } from 'src/midiEditor';
 
 interface CVOutputTopControlsProps {
   inst: MIDIEditorInstance;
@@ -15,6 +16,11 @@ export const CVOutputTopControls: React.FC<CVOutputTopControlsProps> = ({ inst }
       onClick={() => inst.uiManager.addMIDIEditorInstance()}
       title='Add MIDI Editor Instance'
     />
+    <MIDIEditorControlButton
+      label='AD'
+      onClick={() => inst.uiManager.addMIDIEditorInstance(true, R.clone(DEFAULT_DRUM_LANES))}
+      title='Add Drum Grid Instance'
+    />
     <MIDIEditorControlButton label='AO' onClick={() => inst.addCVOutput()} title='Add CV Output' />
   </div>
 );
@@ -0,0 +1,71 @@
+import * as PIXI from 'src/controls/pixi';
+import type MIDIEditorUIInstance from 'src/midiEditor/MIDIEditorUIInstance';
+import * as conf from './conf';
+
+export interface HighlightRegion {
+  startBeat: number;
+  endBeat: number;
+  /**
+   * Inclusive
+   */
+  startLineIx: number;
+  /**
+   * Inclusive
+   */
+  endLineIx: number;
+}
+
+/**
+ * Rectangles drawn on top of the note grid, used by lessons to point out parts of the grid to the
+ * user.  They don't intercept any pointer events.
+ */
+export default class HighlightRegions {
+  private app: MIDIEditorUIInstance;
+  private graphics: PIXI.Graphics;
+  private regions: HighlightRegion[] = [];
+
+  constructor(app: MIDIEditorUIInstance) {
+    this.app = app;
+    this.graphics = new PIXI.Graphics();
+    this.graphics.interactiveChildren = false;
+  }
+
+  public setRegions(regions: HighlightRegion[]) {
+    this.regions = regions;
+    this.handleViewChange();
+  }
+
+  public handleViewChange() {
+    this.graphics.clear();
+    if (this.regions.length === 0) {
+      if (this.graphics.parent) {
+        this.app.linesContainer.removeChild(this.graphics);
+      }
+      return;
+    }
+
+    // Lines are re-added to the container as they're un-culled, so keep this on top of them
+    this.app.linesContainer.addChild(this.graphics);
+
+    const { scrollHorizontalBeats } = this.app.parentInstance.baseView;
+    const { scrollVerticalPx } = this.app.view;
+    for (const { startBeat, endBeat, startLineIx, endLineIx } of this.regions) {
+      const x = this.app.beatsToPx(Math.min(startBeat, endBeat) - scrollHorizontalBeats);
+      const width = this.app.beatsToPx(Math.abs(endBeat - startBeat));
+      const y = Math.min(startLineIx, endLineIx) * conf.LINE_HEIGHT - scrollVerticalPx;
+      const height = (Math.abs(endLineIx - startLineIx) + 1) * conf.LINE_HEIGHT;
+
+      this.graphics.lineStyle(2, conf.HIGHLIGHT_REGION_COLOR);
+      this.graphics.beginFill(conf.HIGHLIGHT_REGION_COLOR, 0.2);
+      this.graphics.drawRect(x, y, width, height);
+      this.graphics.endFill();
+    }
+  }
+
+  public destroy() {
+    if (this.graphics.parent) {
+      this.app.linesContainer.removeChild(this.graphics);
+    }
+    this.graphics.destroy();
+  }
+}
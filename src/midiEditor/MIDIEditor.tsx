@@ -12,22 +12,51 @@ import { renderGenericPresetSaverWithModal } from 'src/controls/GenericPresetPic
 import { getMidiImportSettings, type MidiFileInfo } from 'src/controls/MidiImportDialog';
 import { renderModalWithControls, type ModalCompProps } from 'src/controls/Modal';
 import { useIsGlobalBeatCounterStarted } from 'src/eventScheduler';
-import type { MIDIEditorInstance, SerializedMIDIEditorState } from 'src/midiEditor';
+import type {
+  HumanizeConfig,
+  MIDIEditorInstance,
+  MIDIEditorTrackSettings,
+  NoteVariationConfig,
+  SelectionFilter,
+  SerializedMIDIEditorState,
+} from 'src/midiEditor';
 import { CVOutputTopControls } from 'src/midiEditor/CVOutput/CVOutputTopControls';
 import { mkLoadMIDICompositionModal } from 'src/midiEditor/LoadMIDICompositionModal';
 import { MIDIEditorControlButton } from 'src/midiEditor/MIDIEditorControlButton';
 import BasicModal from 'src/misc/BasicModal';
+import { getLoginToken } from 'src/persistance';
 import { mkImageLoadPlaceholder, useWindowSize } from 'src/reactUtils';
 import { mkSvelteComponentShim } from 'src/svelteUtils';
+import {
+  buildEDOTuning,
+  EDO_DIVISIONS,
+  KEY_ROOT_NAMES,
+  SCALE_INTERVALS,
+  type KeyAndScale,
+  type ScaleName,
+} from 'src/tuning';
 import { AsyncOnce } from 'src/util';
+import { getVcPaneWidth } from 'src/ViewContextManager/VcHideStatusRegistry';
 import CVOutputControls from './CVOutput/CVOutputControls.svelte';
 import './CVOutput/CVOutputControls.css';
 import CollapsedMIDIEditor from 'src/midiEditor/CollapsedMIDIEditor.svelte';
 import { PIANO_KEYBOARD_WIDTH } from 'src/midiEditor/conf';
 import MIDIEditorUIInstance from 'src/midiEditor/MIDIEditorUIInstance';
-import type { ManagedInstance, MIDIEditorUIManager } from 'src/midiEditor/MIDIEditorUIManager';
+import type {
+  ManagedInstance,
+  ManagedMIDIEditorUIInstance,
+  MIDIEditorUIManager,
+} from 'src/midiEditor/MIDIEditorUIManager';
 import MIDIEditorPlaybackHandler from 'src/midiEditor/PlaybackHandler';
+import {
+  formatBarBeatTick,
+  formatTimeSignatureChanges,
+  parseBarBeatTick,
+  parseTimeSignatureChanges,
+  type TimeSignatureChange,
+} from 'src/midiEditor/timeSignature';
 import EditableInstanceName from './EditableInstanceName.svelte';
+import PitchBendControls from './PitchBendLane/PitchBendLaneControls.svelte';
 
 const ctx = new AudioContext();
 
@@ -158,6 +187,317 @@ const UploadMIDIFileModal: React.FC<UploadMIDIFileModalProps> = ({ onSubmit, onC
   );
 };
 
+type HumanizeModalProps = ModalCompProps<{
+  config: HumanizeConfig;
+  /**
+   * If `true`, notes are humanized during playback rather than being modified
+   */
+  duringPlayback: boolean;
+}>;
+
+const HumanizeModal: React.FC<HumanizeModalProps> = ({ onSubmit, onCancel }) => {
+  const [state, setState] = useState<Record<string, any>>({
+    'timing amount (beats)': 0.02,
+    'velocity amount': 0.1,
+    seed: 0,
+    'apply during playback': false,
+  });
+
+  return (
+    <BasicModal className='midi-modal'>
+      <h2>Humanize</h2>
+      <p>
+        Randomly offsets the timing and velocity of the selected notes, or of all notes as they're
+        played back, by up to the configured amounts. The same seed always gives the same result.
+      </p>
+      <ControlPanel
+        style={{ width: '100%' }}
+        state={state}
+        settings={[
+          { type: 'range', label: 'timing amount (beats)', min: 0, max: 0.25, step: 0.005 },
+          { type: 'range', label: 'velocity amount', min: 0, max: 1, step: 0.01 },
+          { type: 'range', label: 'seed', min: 0, max: 1000, step: 1 },
+          { type: 'checkbox', label: 'apply during playback' },
+          {
+            type: 'button',
+            label: 'apply',
+            action: () =>
+              onSubmit({
+                config: {
+                  timingAmountBeats: state['timing amount (beats)'],
+                  velocityAmount: state['velocity amount'],
+                  seed: state.seed,
+                },
+                duringPlayback: state['apply during playback'],
+              }),
+          },
+          { type: 'button', label: 'cancel', action: onCancel },
+        ]}
+        onChange={(_key: string, _val: any, newState: Record<string, any>) => setState(newState)}
+      />
+    </BasicModal>
+  );
+};
+
+const handleHumanize = async (inst: { current: MIDIEditorUIInstance | undefined }) => {
+  try {
+    const { config, duringPlayback } = await renderModalWithControls(HumanizeModal);
+    if (!inst.current) {
+      return;
+    }
+
+    if (duringPlayback) {
+      const isEnabled = config.timingAmountBeats > 0 || config.velocityAmount > 0;
+      inst.current.managedInst.setPlaybackHumanize(isEnabled ? config : null);
+    } else {
+      inst.current.humanizeSelectedNotes(config);
+    }
+  } catch (_err) {
+    return;
+  }
+};
+
+const ROUND_ROBIN_GROUP_OPTIONS = ['none', ...R.range(1, 9).map(String)];
+
+type NoteVariationModalProps = ModalCompProps<{
+  config: NoteVariationConfig;
+  /**
+   * Seed for the instance's playback variation, shared by all of its notes
+   */
+  seed: number;
+}>;
+
+const mkNoteVariationModal = (initialSeed: number): React.FC<NoteVariationModalProps> => {
+  const NoteVariationModal: React.FC<NoteVariationModalProps> = ({ onSubmit, onCancel }) => {
+    const [state, setState] = useState<Record<string, any>>({
+      probability: 1,
+      'round robin group': 'none',
+      seed: initialSeed,
+    });
+
+    return (
+      <BasicModal className='midi-modal'>
+        <h2>Note Variation</h2>
+        <p>
+          Selected notes are only played back with the configured probability. Notes in the same
+          round robin group that start on the same beat are alternate takes, and only one of them is
+          played each time through. The same seed always gives the same result.
+        </p>
+        <ControlPanel
+          style={{ width: '100%' }}
+          state={state}
+          settings={[
+            { type: 'range', label: 'probability', min: 0, max: 1, step: 0.01 },
+            { type: 'select', label: 'round robin group', options: ROUND_ROBIN_GROUP_OPTIONS },
+            { type: 'range', label: 'seed', min: 0, max: 1000, step: 1 },
+            {
+              type: 'button',
+              label: 'apply',
+              action: () => {
+                const group = state['round robin group'];
+                onSubmit({
+                  config: {
+                    probability: state.probability,
+                    roundRobinGroup: group === 'none' ? null : +group,
+                  },
+                  seed: state.seed,
+                });
+              },
+            },
+            { type: 'button', label: 'cancel', action: onCancel },
+          ]}
+          onChange={(_key: string, _val: any, newState: Record<string, any>) => setState(newState)}
+        />
+      </BasicModal>
+    );
+  };
+  return NoteVariationModal;
+};
+
+const handleNoteVariation = async (inst: { current: MIDIEditorUIInstance | undefined }) => {
+  if (!inst.current) {
+    return;
+  }
+
+  try {
+    const { config, seed } = await renderModalWithControls(
+      mkNoteVariationModal(inst.current.managedInst.playbackVariationSeed)
+    );
+    if (!inst.current) {
+      return;
+    }
+
+    inst.current.setSelectedNotesVariation(config);
+    inst.current.managedInst.playbackVariationSeed = seed;
+  } catch (_err) {
+    return;
+  }
+};
+
+const SELECTION_FILTER_TYPES = ['shorter than', 'longer than', 'every nth'];
+
+const SelectionFilterModal: React.FC<ModalCompProps<SelectionFilter>> = ({
+  onSubmit,
+  onCancel,
+}) => {
+  const [state, setState] = useState<Record<string, any>>({
+    filter: 'shorter than',
+    'length beats': 0.5,
+    n: 2,
+  });
+
+  const buildFilter = (): SelectionFilter => {
+    switch (state.filter) {
+      case 'longer than':
+        return { type: 'longerThan', lengthBeats: state['length beats'] };
+      case 'every nth':
+        return { type: 'everyNth', n: state.n };
+      default:
+        return { type: 'shorterThan', lengthBeats: state['length beats'] };
+    }
+  };
+
+  return (
+    <BasicModal className='midi-modal'>
+      <h2>Filter Selection</h2>
+      <p>
+        Narrows the selection down to the selected notes that match. Length filters pick from all
+        notes if nothing is selected. Every nth keeps every nth selected note from left to right.
+      </p>
+      <ControlPanel
+        style={{ width: '100%' }}
+        state={state}
+        settings={[
+          { type: 'select', label: 'filter', options: SELECTION_FILTER_TYPES },
+          { type: 'range', label: 'length beats', min: 0, max: 8, step: 0.125 },
+          { type: 'range', label: 'n', min: 2, max: 16, step: 1 },
+          { type: 'button', label: 'apply', action: () => onSubmit(buildFilter()) },
+          { type: 'button', label: 'cancel', action: onCancel },
+        ]}
+        onChange={(_key: string, _val: any, newState: Record<string, any>) => setState(newState)}
+      />
+    </BasicModal>
+  );
+};
+
+const handleSelectionFilter = async (inst: { current: MIDIEditorUIInstance | undefined }) => {
+  try {
+    const filter = await renderModalWithControls(SelectionFilterModal);
+    inst.current?.applySelectionFilter(filter);
+  } catch (_err) {
+    return;
+  }
+};
+
+const mkTimeSignatureChangesModal = (
+  initialChanges: readonly TimeSignatureChange[]
+): React.FC<ModalCompProps<TimeSignatureChange[]>> => {
+  const TimeSignatureChangesModal: React.FC<ModalCompProps<TimeSignatureChange[]>> = ({
+    onSubmit,
+    onCancel,
+  }) => {
+    const [state, setState] = useState<Record<string, any>>({
+      changes: formatTimeSignatureChanges(initialChanges),
+    });
+    const [error, setError] = useState<string | null>(null);
+
+    const submit = () => {
+      const changes = parseTimeSignatureChanges(state.changes);
+      if (!changes) {
+        setError('Changes must look like "9 3/4, 17 7/8"');
+        return;
+      }
+      onSubmit(changes);
+    };
+
+    return (
+      <BasicModal className='midi-modal'>
+        <h2>Time Signature Changes</h2>
+        <p>
+          Comma-separated list of the bars at which the time signature changes and what it changes
+          to, such as <code>9 3/4, 17 7/8</code>. Before the first change, measures are as many
+          quarter notes long as the notes per measure setting.
+        </p>
+        <ControlPanel
+          style={{ width: '100%' }}
+          state={state}
+          settings={[
+            { type: 'text', label: 'changes' },
+            { type: 'button', label: 'apply', action: submit },
+            { type: 'button', label: 'cancel', action: onCancel },
+          ]}
+          onChange={(_key: string, _val: any, newState: Record<string, any>) => setState(newState)}
+        />
+        {error ? <p style={{ color: 'red' }}>{error}</p> : null}
+      </BasicModal>
+    );
+  };
+  return TimeSignatureChangesModal;
+};
+
+const handleTimeSignatureChanges = async (parentInst: MIDIEditorInstance) => {
+  try {
+    const changes = await renderModalWithControls(
+      mkTimeSignatureChangesModal(parentInst.baseView.timeSignatureChanges)
+    );
+    parentInst.setTimeSignatureChanges(changes);
+  } catch (_err) {
+    return;
+  }
+};
+
+interface CursorPositionDisplayProps {
+  parentInst: MIDIEditorInstance;
+}
+
+/**
+ * Shows the position of the cursor as bar.beat.tick.  A position can be typed in to move the
+ * cursor there.
+ */
+const CursorPositionDisplay: React.FC<CursorPositionDisplayProps> = ({ parentInst }) => {
+  const [editingValue, setEditingValue] = useState<string | null>(null);
+  const [position, setPosition] = useState('1.1.000');
+  useEffect(() => {
+    let frameHandle = requestAnimationFrame(function update() {
+      const pos = parentInst.baseView.timeSignatureMap.beatsToBarBeatTick(
+        parentInst.getCursorPosBeats()
+      );
+      setPosition(formatBarBeatTick(pos));
+      frameHandle = requestAnimationFrame(update);
+    });
+    return () => cancelAnimationFrame(frameHandle);
+  }, [parentInst]);
+
+  const commit = () => {
+    const pos = editingValue === null ? null : parseBarBeatTick(editingValue);
+    setEditingValue(null);
+    if (pos) {
+      parentInst.playbackHandler.setCursorPosBeats(
+        parentInst.baseView.timeSignatureMap.barBeatTickToBeats(pos)
+      );
+    }
+  };
+
+  return (
+    <div className='labeled-container'>
+      <label>Position</label>
+      <input
+        type='text'
+        value={editingValue ?? position}
+        onFocus={() => setEditingValue(position)}
+        onChange={evt => setEditingValue(evt.target.value)}
+        onBlur={commit}
+        onKeyDown={evt => {
+          if (evt.key === 'Enter') {
+            (evt.target as HTMLInputElement).blur();
+          }
+        }}
+        style={{ width: 90, fontSize: 20 }}
+      />
+    </div>
+  );
+};
+
 const handleMIDIFileUpload = async (
   inst: React.MutableRefObject<MIDIEditorUIInstance | undefined>
 ) => {
@@ -232,6 +572,10 @@ const MIDIEditorControlsInner: React.FC<MIDIEditorControlsProps> = ({
   const [state, setStateInner] = useState(initialState);
   const [isRecording, setIsRecording] = useState(false);
   const [metronomeEnabled, setMetronomeEnabled] = useState(initialState.metronomeEnabled);
+  const [followPlayback, setFollowPlayback] = useState(parentInst.followPlayback);
+  // Saves after loading a composition are stored as new versions of it rather than overwriting it
+  const loadedCompositionID = useRef<number | null>(null);
+  const [collabInstance, setCollabInstance] = useState<ManagedMIDIEditorUIInstance | null>(null);
   const onChange = (newState: MIDIEditorControlsState) => {
     onChangeInner(newState);
     setStateInner(newState);
@@ -321,6 +665,20 @@ const MIDIEditorControlsInner: React.FC<MIDIEditorControlsProps> = ({
         style={{ fontSize: 15, textAlign: 'center' }}
         active={state.loopEnabled}
       />
+      <MIDIEditorControlButton
+        onClick={() => {
+          parentInst.followPlayback = !followPlayback;
+          setFollowPlayback(!followPlayback);
+        }}
+        label='FLW'
+        title={
+          followPlayback
+            ? 'Stop scrolling to follow the cursor during playback'
+            : 'Scroll to follow the cursor during playback'
+        }
+        style={{ fontSize: 15, textAlign: 'center' }}
+        active={followPlayback}
+      />
       <MIDIEditorControlButton
         onClick={() => {
           if (playbackHandler.isPlaying) {
@@ -403,6 +761,24 @@ const MIDIEditorControlsInner: React.FC<MIDIEditorControlsProps> = ({
         label='▥'
         style={{ fontSize: 40, textAlign: 'center', lineHeight: '36px' }}
       />
+      <MIDIEditorControlButton
+        onClick={() => handleHumanize(activeInstance)}
+        title='Humanize selected notes'
+        label='≈'
+        style={{ fontSize: 32, textAlign: 'center', lineHeight: '36px' }}
+      />
+      <MIDIEditorControlButton
+        onClick={() => handleNoteVariation(activeInstance)}
+        title='Set playback probability and round robin group of selected notes'
+        label='⚄'
+        style={{ fontSize: 32, textAlign: 'center', lineHeight: '36px' }}
+      />
+      <MIDIEditorControlButton
+        onClick={() => handleSelectionFilter(activeInstance)}
+        title='Filter selected notes by length or keep every nth one'
+        label='⧩'
+        style={{ fontSize: 32, textAlign: 'center', lineHeight: '36px' }}
+      />
       <div className='labeled-container'>
         <label>BPM</label>
         <input
@@ -432,6 +808,13 @@ const MIDIEditorControlsInner: React.FC<MIDIEditorControlsProps> = ({
           style={{ width: 63, fontSize: 20 }}
         />
       </div>
+      <MIDIEditorControlButton
+        onClick={() => handleTimeSignatureChanges(parentInst)}
+        title='Edit time signature changes'
+        label='𝄴'
+        style={{ fontSize: 26, textAlign: 'center', lineHeight: '36px' }}
+      />
+      <CursorPositionDisplay parentInst={parentInst} />
       <MIDIEditorControlButton
         onClick={async () => {
           if (!activeInstance.current) {
@@ -444,7 +827,13 @@ const MIDIEditorControlsInner: React.FC<MIDIEditorControlsProps> = ({
               getExistingTags: getExistingMIDICompositionTags,
             });
             const composition = activeInstance.current!.serialize(true);
-            await saveMIDIComposition(name, description ?? '', composition, tags ?? []);
+            loadedCompositionID.current = await saveMIDIComposition(
+              name,
+              description ?? '',
+              composition,
+              tags ?? [],
+              loadedCompositionID.current
+            );
           } catch (err) {
             return;
           }
@@ -462,10 +851,14 @@ const MIDIEditorControlsInner: React.FC<MIDIEditorControlsProps> = ({
 
           try {
             const {
-              preset: { composition },
+              preset: { id, composition },
             } = await mkLoadMIDICompositionModal();
 
+            // Otherwise, the loaded notes would replace the ones in the room
+            activeInstance.current.managedInst.stopCollaborating();
+            setCollabInstance(null);
             activeInstance.current.reInitialize(composition);
+            loadedCompositionID.current = id;
           } catch (_err) {
             return;
           }
@@ -475,6 +868,43 @@ const MIDIEditorControlsInner: React.FC<MIDIEditorControlsProps> = ({
         style={{ fontSize: 18, textAlign: 'center' }}
         active={state.loopEnabled}
       />
+      <MIDIEditorControlButton
+        onClick={async () => {
+          const inst = activeInstance.current?.managedInst;
+          if (!inst) {
+            return;
+          } else if (inst.collab) {
+            inst.stopCollaborating();
+            setCollabInstance(null);
+            return;
+          }
+
+          const midiCompositionID = loadedCompositionID.current;
+          if (midiCompositionID === null) {
+            alert('Save or load a MIDI composition before collaborating on it');
+            return;
+          }
+          const loginToken = await getLoginToken();
+          if (!loginToken) {
+            alert('Log in to collaborate on MIDI compositions');
+            return;
+          }
+
+          inst.startCollaborating(midiCompositionID, loginToken, reason => {
+            setCollabInstance(null);
+            alert(`Disconnected from collaboration room${reason ? `: ${reason}` : ''}`);
+          });
+          setCollabInstance(inst);
+        }}
+        title={
+          collabInstance
+            ? `Stop collaborating on "${collabInstance.name}"`
+            : 'Edit the saved MIDI composition together with other users'
+        }
+        label='👥'
+        style={{ fontSize: 18, textAlign: 'center' }}
+        active={!!collabInstance}
+      />
       <MIDIEditorControlButton
         onClick={() => handleMIDIFileUpload(activeInstance)}
         title='Upload MIDI File'
@@ -524,6 +954,126 @@ type EditableInstanceNameProps = EditableInstanceName extends SvelteComponentTyp
 const EditableInstanceNameShim =
   mkSvelteComponentShim<EditableInstanceNameProps>(EditableInstanceName);
 
+type PitchBendControlsProps = PitchBendControls extends SvelteComponentTyped<infer Props, any>
+  ? Props
+  : never;
+
+const PitchBendControlsShim = mkSvelteComponentShim<PitchBendControlsProps>(PitchBendControls);
+
+const toCSSColor = (color: number) => `#${color.toString(16).padStart(6, '0')}`;
+
+interface MIDIEditorTrackControlsProps {
+  inst: ManagedMIDIEditorUIInstance;
+}
+
+/**
+ * Mute, solo, note color, legato, pitch bend lane, key, and tuning controls for a MIDI editor
+ * instance
+ */
+const MIDIEditorTrackControls: React.FC<MIDIEditorTrackControlsProps> = ({ inst }) => {
+  const [track, setTrackInner] = useState(inst.track);
+  const setTrack = (newSettings: Partial<MIDIEditorTrackSettings>) => {
+    inst.setTrackSettings(newSettings);
+    setTrackInner(inst.track);
+  };
+  const [legato, setLegato] = useState(inst.legato);
+  const [keyAndScale, setKeyAndScaleInner] = useState(inst.keyAndScale);
+  const setKeyAndScale = (newKeyAndScale: Partial<KeyAndScale>) => {
+    inst.keyAndScale = { ...inst.keyAndScale, ...newKeyAndScale };
+    setKeyAndScaleInner(inst.keyAndScale);
+  };
+  const [divisionsPerOctave, setDivisionsPerOctave] = useState(inst.tuning.divisionsPerOctave);
+  const divisionOptions = EDO_DIVISIONS.includes(divisionsPerOctave)
+    ? EDO_DIVISIONS
+    : [...EDO_DIVISIONS, divisionsPerOctave].sort((a, b) => a - b);
+
+  return (
+    <div className='midi-editor-track-controls'>
+      <button
+        title={track.muted ? 'Unmute' : 'Mute'}
+        className={track.muted ? 'active' : undefined}
+        onClick={() => setTrack({ muted: !track.muted })}
+      >
+        M
+      </button>
+      <button
+        title={track.soloed ? 'Unsolo' : 'Solo'}
+        className={track.soloed ? 'active' : undefined}
+        onClick={() => setTrack({ soloed: !track.soloed })}
+      >
+        S
+      </button>
+      <input
+        type='color'
+        title='Note color'
+        value={toCSSColor(track.color)}
+        onChange={evt => setTrack({ color: Number.parseInt(evt.target.value.slice(1), 16) })}
+      />
+      <button
+        title={legato ? 'Re-trigger adjacent notes' : 'Tie adjacent notes (legato)'}
+        className={legato ? 'active' : undefined}
+        onClick={() => {
+          inst.legato = !legato;
+          setLegato(inst.legato);
+        }}
+      >
+        L
+      </button>
+      <button
+        title={inst.pitchBend ? 'Remove pitch bend lane' : 'Add pitch bend lane'}
+        className={inst.pitchBend ? 'active' : undefined}
+        onClick={() => {
+          if (!inst.pitchBend) {
+            inst.addPitchBendLane();
+          } else if (confirm(`Remove the pitch bend lane from "${inst.name}"?`)) {
+            inst.removePitchBendLane();
+          }
+        }}
+      >
+        PB
+      </button>
+      <select
+        title='Key used to transpose by scale degrees'
+        value={keyAndScale.root}
+        onChange={evt => setKeyAndScale({ root: +evt.target.value })}
+      >
+        {KEY_ROOT_NAMES.map((name, root) => (
+          <option key={root} value={root}>
+            {name}
+          </option>
+        ))}
+      </select>
+      <select
+        title='Scale used to transpose by scale degrees'
+        value={keyAndScale.scale}
+        onChange={evt => setKeyAndScale({ scale: evt.target.value as ScaleName })}
+      >
+        {Object.keys(SCALE_INTERVALS).map(scale => (
+          <option key={scale} value={scale}>
+            {scale}
+          </option>
+        ))}
+      </select>
+      {inst.drumLanes ? null : (
+        <select
+          title='Tuning; note numbers sent to connected synths are degrees of this tuning'
+          value={divisionsPerOctave}
+          onChange={evt => {
+            inst.setTuning(buildEDOTuning(+evt.target.value));
+            setDivisionsPerOctave(inst.tuning.divisionsPerOctave);
+          }}
+        >
+          {divisionOptions.map(divisions => (
+            <option key={divisions} value={divisions}>
+              {divisions === 12 ? '12-TET' : `${divisions}-EDO`}
+            </option>
+          ))}
+        </select>
+      )}
+    </div>
+  );
+};
+
 class ActiveInstanceProxy {
   private uiManager: MIDIEditorUIManager;
 
@@ -555,22 +1105,28 @@ const MIDIEditor: React.FC<MIDIEditorProps> = ({
     metronomeEnabled: initialState.metronomeEnabled,
   });
 
+  // The window size is only used to re-render on resize; the editor may only be given half of the
+  // window if it's displayed in a split view
   const windowSize = useWindowSize();
-  const lastWindowSize = useRef(windowSize);
+  const paneWidth = getVcPaneWidth(vcId);
+  const lastWindowSize = useRef({ width: paneWidth, height: windowSize.height });
   useEffect(() => {
     if (
-      lastWindowSize.current.width !== windowSize.width ||
+      lastWindowSize.current.width !== paneWidth ||
       lastWindowSize.current.height !== windowSize.height
     ) {
-      lastWindowSize.current = windowSize;
-      parentInstance.uiManager.handleWindowResize(windowSize.width, windowSize.height);
+      lastWindowSize.current = { width: paneWidth, height: windowSize.height };
+      parentInstance.uiManager.handleWindowResize(paneWidth, windowSize.height);
     }
-  }, [parentInstance.uiManager, windowSize]);
+  }, [parentInstance.uiManager, paneWidth, windowSize.height]);
 
   const handleChange = useCallback(
-    ({ bpm, loopEnabled }: MIDIEditorControlsState) => {
+    ({ bpm, loopEnabled, beatsPerMeasure }: MIDIEditorControlsState) => {
       parentInstance.localBPM = bpm;
       parentInstance.setLoopEnabled(loopEnabled);
+      if (beatsPerMeasure !== parentInstance.baseView.beatsPerMeasure) {
+        parentInstance.setBeatsPerMeasure(beatsPerMeasure);
+      }
     },
     [parentInstance]
   );
@@ -616,6 +1172,7 @@ const MIDIEditor: React.FC<MIDIEditorProps> = ({
                     name={inst.name}
                     setName={newName => parentInstance.uiManager.renameInstance(inst.name, newName)}
                   />
+                  <MIDIEditorTrackControls inst={inst} />
                   <button
                     className='delete-cv-output-button'
                     onClick={() => parentInstance.uiManager.deleteMIDIEditorInstance(inst.id)}
@@ -640,7 +1197,7 @@ const MIDIEditor: React.FC<MIDIEditorProps> = ({
                       )!;
                       const instanceHeight = parentInstance.uiManager.computeUIInstanceHeight();
                       const newInst = new MIDIEditorUIInstance(
-                        windowSize.width,
+                        paneWidth,
                         instanceHeight,
                         canvas,
                         parentInstance,
@@ -659,6 +1216,18 @@ const MIDIEditor: React.FC<MIDIEditorProps> = ({
                       evt.stopPropagation();
                     }}
                   />
+                  {inst.pitchBend ? (
+                    <PitchBendControlsShim
+                      instanceName={inst.name}
+                      state={inst.pitchBend}
+                      removeLane={() => {
+                        if (confirm(`Remove the pitch bend lane from "${inst.name}"?`)) {
+                          inst.removePitchBendLane();
+                        }
+                      }}
+                      registerInstance={uiInstance => inst.registerPitchBendUIInstance(uiInstance)}
+                    />
+                  ) : null}
                 </div>
               );
             }
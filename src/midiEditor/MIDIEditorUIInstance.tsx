@@ -1,15 +1,25 @@
 import { UnreachableException } from 'ameo-utils';
+import download from 'downloadjs';
 import * as R from 'ramda';
 
 import * as PIXI from 'src/controls/pixi';
 import { destroyPIXIApp } from 'src/controls/pixiUtils';
-import type {
-  MIDIEditorInstance,
-  MIDIEditorInstanceView,
-  SerializedMIDIEditorInstance,
-  SerializedMIDILine,
+import { matchKeymapAction, type KeymapAction } from 'src/keymap';
+import {
+  NoteArticulation,
+  type HumanizeConfig,
+  type MIDIEditorInstance,
+  type MIDIEditorInstanceView,
+  type NoteVariationConfig,
+  type SelectionFilter,
+  type SerializedMIDIEditorInstance,
+  type SerializedMIDILine,
+  type SerializedMIDINote,
 } from 'src/midiEditor';
+import type { CollabRenderChange } from 'src/midiEditor/collaboration';
 import { Cursor, CursorGutter, LoopCursor } from 'src/midiEditor/Cursor';
+import HighlightRegions, { type HighlightRegion } from 'src/midiEditor/HighlightRegions';
+import MeasureLines from 'src/midiEditor/MeasureLines';
 import { ManagedMIDIEditorUIInstance } from 'src/midiEditor/MIDIEditorUIManager';
 import MIDINoteBox, {
   NoteDragHandle,
@@ -18,7 +28,9 @@ import MIDINoteBox, {
 import type { NoteBox } from 'src/midiEditor/NoteBox/NoteBox';
 import NoteLine from 'src/midiEditor/NoteLine';
 import PianoKeys from 'src/midiEditor/PianoKeyboard';
+import MarqueeZoomBox from 'src/midiEditor/MarqueeZoomBox';
 import SelectionBox from 'src/midiEditor/SelectionBox';
+import { transposeByScaleDegrees } from 'src/tuning';
 import {
   getIsVcHidden,
   registerVcHideCb,
@@ -37,6 +49,24 @@ export interface Note {
 
 PIXI.utils.skipHello();
 
+const MIDI_EDITOR_KEYMAP_ACTIONS: KeymapAction[] = [
+  'midiEditor.deleteSelection',
+  'midiEditor.copySelection',
+  'midiEditor.cutSelection',
+  'midiEditor.paste',
+  'midiEditor.scrollLeft',
+  'midiEditor.scrollRight',
+  'midiEditor.transposeUpScaleDegree',
+  'midiEditor.transposeDownScaleDegree',
+  'midiEditor.invertChords',
+  'midiEditor.toggleStaccato',
+  'midiEditor.toggleAccent',
+  'midiEditor.toggleTie',
+  'midiEditor.selectRow',
+  'midiEditor.selectEveryOther',
+  'midiEditor.toggleInputRecording',
+];
+
 interface MIDIEditorPanningView {
   scrollHorizontalBeats: number;
   scrollVerticalPx: number;
@@ -55,6 +85,10 @@ export default class MIDIEditorUIInstance {
   public lines: NoteLine[] = [];
   public allNotesByID: Map<number, NoteBox> = new Map();
   public selectedNoteIDs: Set<number> = new Set();
+  /**
+   * Row that was last clicked in the grid, used by `midiEditor.selectRow`
+   */
+  private lastClickedLineIx: number | null = null;
   public multiSelectEnabled = false;
   private eventHandlerCBs!: {
     keyUp: (evt: KeyboardEvent) => void;
@@ -73,15 +107,43 @@ export default class MIDIEditorUIInstance {
   private dragData: {
     globalStartPoint: PIXI.Point;
     originalPosBeatsByNoteId: Map<number, number>;
+    originalLineIxByNoteId: Map<number, number>;
+    /**
+     * Line index under the pointer when dragging started
+     */
+    originLineIx: number;
+    /**
+     * Line index under the pointer the last time the selected notes were moved vertically
+     */
     startLineIx: number;
+    /**
+     * Where each selected note would end up if nothing were in its way.  Committed on mouse up.
+     */
+    desiredPositionsByNoteId: Map<number, { lineIx: number; startPoint: number }>;
+    /**
+     * Direction of the drag in beats and lines, used to order moves when committing it
+     */
+    direction: { beats: number; lines: number };
+    ghostsByNoteId: Map<number, { graphics: PIXI.Graphics; lineIx: number }>;
   } | null = null;
   private selectionBox: SelectionBox | null = null;
+  private marqueeZoomBox: MarqueeZoomBox | null = null;
+  private highlightRegions: HighlightRegions;
+  public measureLines: MeasureLines;
   public selectionBoxButtonDown = false;
   public cursor: Cursor;
   private pianoKeys: PianoKeys | undefined;
   private cursorGutter: CursorGutter;
   public loopCursor: LoopCursor | null;
-  private clipboard: { startPoint: number; length: number; lineIx: number }[] = [];
+  private clipboard: {
+    startPoint: number;
+    length: number;
+    lineIx: number;
+    velocity: number;
+    probability: number;
+    roundRobinGroup: number;
+    articulation: number;
+  }[] = [];
   public noteMetadataByNoteID: Map<number, any> = new Map();
   private vcId: string;
   private isHidden: boolean;
@@ -134,7 +196,16 @@ export default class MIDIEditorUIInstance {
     this.linesContainer
       .on('pointerdown', (evt: PIXI.InteractionEvent) => {
         if (evt.data.button === 0) {
-          if (this.selectionBoxButtonDown && !this.selectionBox) {
+          this.lastClickedLineIx = this.computeLineIndex(
+            evt.data.getLocalPosition(this.linesContainer).y
+          );
+          if ((evt.data.originalEvent as MouseEvent).altKey) {
+            this.marqueeZoomBox?.destroy();
+            this.marqueeZoomBox = new MarqueeZoomBox(
+              this,
+              evt.data.getLocalPosition(this.linesContainer)
+            );
+          } else if (this.selectionBoxButtonDown && !this.selectionBox) {
             this.selectionBox = new SelectionBox(
               this,
               evt.data.getLocalPosition(this.linesContainer)
@@ -151,6 +222,7 @@ export default class MIDIEditorUIInstance {
         if (this.selectionBox) {
           this.selectionBox.update(evt.data.getLocalPosition(this.linesContainer));
         }
+        this.marqueeZoomBox?.update(evt.data.getLocalPosition(this.linesContainer));
       });
 
     this.linesContainer.x = conf.PIANO_KEYBOARD_WIDTH;
@@ -158,15 +230,22 @@ export default class MIDIEditorUIInstance {
     // Clip stuff hidden at the top outside of it
     this.linesContainer.mask = this.buildLinesContainerMask();
     this.app.stage.addChild(this.linesContainer);
+    this.highlightRegions = new HighlightRegions(this);
 
     // Cursor gutter
     this.cursorGutter = new CursorGutter(this);
+    this.measureLines = new MeasureLines(this);
 
     this.cursor = new Cursor(this);
     this.cursor.setPosBeats(parentInstance.getCursorPosBeats());
     this.app.ticker.add(() => {
-      this.cursor.setPosBeats(this.parentInstance.getCursorPosBeats());
+      const cursorPosBeats = this.parentInstance.getCursorPosBeats();
+      this.cursor.setPosBeats(cursorPosBeats);
+      this.maybeFollowPlayback(cursorPosBeats);
       this.parentInstance.playbackHandler?.recordingCtx?.tick();
+      if (this.managedInst.isRecordingInput && this.wasm) {
+        this.wasm.instance.advance_input_recording_frame(this.wasm.noteLinesCtxPtr);
+      }
     });
 
     this.init().then(() => {
@@ -217,18 +296,35 @@ export default class MIDIEditorUIInstance {
     const linesWithIDs: Note[][] = new Array(newState.lines.length).fill(null).map(() => []);
     for (const { midiNumber, notes } of newState.lines) {
       const lineIx = newState.lines.length - midiNumber;
-      for (const { length, startPoint } of notes) {
-        const id = this.wasm.instance.create_note(
-          this.wasm.noteLinesCtxPtr,
-          lineIx,
-          startPoint,
-          length,
-          0
-        );
+      for (const {
+        length,
+        startPoint,
+        velocity,
+        probability,
+        roundRobinGroup,
+        articulation,
+      } of notes) {
+        const { instance, noteLinesCtxPtr } = this.wasm;
+        const id = instance.create_note(noteLinesCtxPtr, lineIx, startPoint, length, 0);
+        if (!R.isNil(velocity)) {
+          instance.set_note_velocity(noteLinesCtxPtr, id, velocity);
+        }
+        if (!R.isNil(probability)) {
+          instance.set_note_probability(noteLinesCtxPtr, id, probability);
+        }
+        if (!R.isNil(roundRobinGroup)) {
+          instance.set_note_round_robin_group(noteLinesCtxPtr, id, roundRobinGroup);
+        }
+        if (!R.isNil(articulation)) {
+          instance.set_note_articulation(noteLinesCtxPtr, id, articulation);
+        }
         linesWithIDs[lineIx].push({ id, startPoint, length });
       }
     }
     this.lines = this.buildNoteLines(linesWithIDs);
+    for (const noteBox of this.allNotesByID.values()) {
+      this.recordCollabEdit(noteBox);
+    }
 
     // Destroy + re-create piano notes
     this.pianoKeys?.destroy();
@@ -255,16 +351,30 @@ export default class MIDIEditorUIInstance {
     return beats * this.parentInstance.baseView.pxPerBeat;
   }
 
+  public setHighlightRegions(regions: HighlightRegion[]) {
+    this.highlightRegions.setRegions(regions);
+  }
+
+  /**
+   * Re-draws everything that depends on the labels of rows, which change along with the tuning or
+   * drum lanes
+   */
+  public rebuildPianoKeys() {
+    this.pianoKeys?.destroy();
+    this.pianoKeys = new PianoKeys(this);
+    this.lines.forEach(line => line.renderOctaveBorder());
+  }
+
   public setSize(width: number, height: number) {
     this.width = width;
     this.height = height;
     this.app.renderer.resize(width, height);
 
     this.linesContainer.mask = this.buildLinesContainerMask();
-    this.pianoKeys?.destroy();
-    this.pianoKeys = new PianoKeys(this);
+    this.rebuildPianoKeys();
     this.cursorGutter.destroy();
     this.cursorGutter = new CursorGutter(this);
+    this.measureLines.bringToFront();
     this.app.stage.removeChild(this.cursor.graphics);
     this.cursor.destroy();
     this.cursor = new Cursor(this);
@@ -304,6 +414,7 @@ export default class MIDIEditorUIInstance {
     this.lines[lineIx].notesByID.set(id, noteBox);
     this.allNotesByID.set(id, noteBox);
     this.selectNote(id);
+    this.recordCollabEdit(noteBox);
     return id;
   }
 
@@ -327,6 +438,50 @@ export default class MIDIEditorUIInstance {
     note.line.notesByID.delete(id);
     note.destroy();
     this.allNotesByID.delete(id);
+    this.managedInst.collab?.recordDelete(id);
+  }
+
+  /**
+   * Sends the note's current placement to everyone else editing this instance's notes, if anyone.
+   * Must be called after every edit that moves or resizes a note.
+   */
+  public recordCollabEdit({ note, line }: NoteBox) {
+    this.managedInst.collab?.recordEdit(note.id, line.index, note.startPoint, note.length);
+  }
+
+  /**
+   * Updates the displayed notes to match edits that were made to the note lines by a collaboration
+   * session, either directly or by merging edits made by others.  The notes in the Wasm have
+   * already been updated.
+   */
+  public applyCollabRenderChanges(changes: CollabRenderChange[]) {
+    for (const { id, lineIx, startPoint, length } of changes) {
+      const noteBox = this.allNotesByID.get(id);
+      if (lineIx === -1) {
+        if (noteBox) {
+          this.selectedNoteIDs.delete(id);
+          noteBox.line.notesByID.delete(id);
+          noteBox.destroy();
+          this.allNotesByID.delete(id);
+        }
+        continue;
+      }
+
+      if (!noteBox) {
+        const newNoteBox = new MIDINoteBox(this.lines[lineIx], { id, startPoint, length });
+        this.lines[lineIx].notesByID.set(id, newNoteBox);
+        this.allNotesByID.set(id, newNoteBox);
+        newNoteBox.render();
+        continue;
+      }
+
+      if (noteBox.line.index !== lineIx) {
+        this.setNoteBoxLine(noteBox, lineIx);
+      }
+      noteBox.note.startPoint = startPoint;
+      noteBox.note.length = length;
+      noteBox.render();
+    }
   }
 
   public selectNote(id: number) {
@@ -364,6 +519,78 @@ export default class MIDIEditorUIInstance {
     }
   }
 
+  /**
+   * Replaces the current selection with the notes in `ids`
+   */
+  private setSelection(ids: Iterable<number>) {
+    this.deselectAllNotes();
+    for (const id of ids) {
+      this.allNotesByID.get(id)?.setIsSelected(true);
+      this.selectedNoteIDs.add(id);
+    }
+  }
+
+  /**
+   * Selects all notes on the line at `lineIx`, adding them to the current selection if
+   * multi-select is enabled
+   */
+  public selectAllNotesInRow(lineIx: number) {
+    if (!this.wasm || lineIx < 0 || lineIx >= this.lines.length) {
+      return;
+    }
+
+    const ids = this.wasm.instance.iter_notes(
+      this.wasm.noteLinesCtxPtr,
+      lineIx,
+      lineIx,
+      0,
+      Infinity
+    );
+    this.setSelection(this.multiSelectEnabled ? [...this.selectedNoteIDs, ...ids] : ids);
+  }
+
+  /**
+   * Starts recording note edits, or stops recording and downloads the recording so that it can be
+   * attached to a bug report and replayed natively
+   */
+  private toggleInputRecording() {
+    const recording = this.managedInst.toggleInputRecording();
+    if (recording) {
+      download(recording, `${this.managedInst.name}.wsir`, 'application/octet-stream');
+    }
+  }
+
+  /**
+   * Narrows the current selection down to the notes matching `filter`.  If nothing is selected,
+   * length filters select from all notes instead.
+   */
+  public applySelectionFilter(filter: SelectionFilter) {
+    const wasm = this.wasm;
+    if (!wasm) {
+      return;
+    }
+
+    if (filter.type === 'everyNth') {
+      const n = Math.max(Math.round(filter.n), 1);
+      const kept = [...this.selectedNoteIDs]
+        .map(id => this.allNotesByID.get(id)!)
+        .sort((a, b) => a.note.startPoint - b.note.startPoint || a.line.index - b.line.index)
+        .filter((_note, ix) => ix % n === 0)
+        .map(note => note.note.id);
+      this.setSelection(kept);
+      return;
+    }
+
+    const [minLength, maxLength] =
+      filter.type === 'shorterThan' ? [0, filter.lengthBeats] : [filter.lengthBeats, Infinity];
+    const matching = wasm.instance.iter_notes_by_length(wasm.noteLinesCtxPtr, minLength, maxLength);
+    this.setSelection(
+      this.selectedNoteIDs.size === 0
+        ? matching
+        : [...matching].filter(id => this.selectedNoteIDs.has(id))
+    );
+  }
+
   public resizeNoteHorizontalStart(
     lineIx: number,
     startPoint: number,
@@ -399,6 +626,7 @@ export default class MIDIEditorUIInstance {
     note.note.length += note.note.startPoint - realNewStartPoint;
     note.note.startPoint = realNewStartPoint;
     note.render();
+    this.recordCollabEdit(note);
     return realNewStartPoint;
   }
 
@@ -438,6 +666,7 @@ export default class MIDIEditorUIInstance {
     );
     note.note.length = realNewEndPoint - startPoint;
     note.render();
+    this.recordCollabEdit(note);
     return realNewEndPoint;
   }
 
@@ -542,12 +771,17 @@ export default class MIDIEditorUIInstance {
 
   public copySelection() {
     this.clipboard = [];
+    const { instance, noteLinesCtxPtr } = this.wasm!;
     for (const noteID of this.selectedNoteIDs.values()) {
       const note = this.allNotesByID.get(noteID)!;
       this.clipboard.push({
         lineIx: note.line.index,
         startPoint: note.note.startPoint,
         length: note.note.length,
+        velocity: instance.get_note_velocity(noteLinesCtxPtr, noteID),
+        probability: instance.get_note_probability(noteLinesCtxPtr, noteID),
+        roundRobinGroup: instance.get_note_round_robin_group(noteLinesCtxPtr, noteID),
+        articulation: instance.get_note_articulation(noteLinesCtxPtr, noteID),
       });
     }
   }
@@ -588,6 +822,10 @@ export default class MIDIEditorUIInstance {
       }
 
       const id = this.addNote(note.lineIx, normalizedStartPoint, note.length);
+      wasm.instance.set_note_velocity(wasm.noteLinesCtxPtr, id, note.velocity);
+      wasm.instance.set_note_probability(wasm.noteLinesCtxPtr, id, note.probability);
+      wasm.instance.set_note_round_robin_group(wasm.noteLinesCtxPtr, id, note.roundRobinGroup);
+      wasm.instance.set_note_articulation(wasm.noteLinesCtxPtr, id, note.articulation);
       createdNoteIDs.push(id);
     });
 
@@ -597,6 +835,69 @@ export default class MIDIEditorUIInstance {
     this.parentInstance.playbackHandler.setCursorPosBeats(normalizedEndBeat);
   }
 
+  /**
+   * Applies bounded random offsets to the start points and velocities of all selected notes.  Notes
+   * keep their lengths and stop early if they would run into another note.
+   */
+  public humanizeSelectedNotes({ timingAmountBeats, velocityAmount, seed }: HumanizeConfig) {
+    const wasm = this.wasm;
+    if (!wasm) {
+      return;
+    }
+
+    // Process notes in a stable order so that the same seed always produces the same result
+    const notes = [...this.selectedNoteIDs]
+      .map(id => this.allNotesByID.get(id)!)
+      .sort((a, b) => a.line.index - b.line.index || a.note.startPoint - b.note.startPoint);
+    const humanizerPtr = wasm.instance.create_humanizer(seed, timingAmountBeats, velocityAmount);
+    for (const noteBox of notes) {
+      noteBox.note.startPoint = wasm.instance.humanize_note(
+        wasm.noteLinesCtxPtr,
+        humanizerPtr,
+        noteBox.line.index,
+        noteBox.note.startPoint,
+        noteBox.note.id
+      );
+      noteBox.render();
+      this.recordCollabEdit(noteBox);
+    }
+    wasm.instance.free_humanizer(humanizerPtr);
+  }
+
+  /**
+   * Sets the playback probability and round-robin group of all selected notes
+   */
+  public setSelectedNotesVariation({ probability, roundRobinGroup }: NoteVariationConfig) {
+    const wasm = this.wasm;
+    if (!wasm) {
+      return;
+    }
+
+    for (const noteID of this.selectedNoteIDs) {
+      wasm.instance.set_note_probability(wasm.noteLinesCtxPtr, noteID, probability);
+      wasm.instance.set_note_round_robin_group(wasm.noteLinesCtxPtr, noteID, roundRobinGroup ?? -1);
+    }
+  }
+
+  /**
+   * Sets `flag` on all selected notes, or clears it from all of them if they all have it already
+   */
+  public toggleSelectedNotesArticulation(flag: NoteArticulation) {
+    const wasm = this.wasm;
+    if (!wasm || this.selectedNoteIDs.size === 0) {
+      return;
+    }
+
+    const articulations = [...this.selectedNoteIDs].map(
+      noteID => [noteID, wasm.instance.get_note_articulation(wasm.noteLinesCtxPtr, noteID)] as const
+    );
+    const allHaveFlag = articulations.every(([, articulation]) => articulation & flag);
+    for (const [noteID, articulation] of articulations) {
+      const newArticulation = allHaveFlag ? articulation & ~flag : articulation | flag;
+      wasm.instance.set_note_articulation(wasm.noteLinesCtxPtr, noteID, newArticulation);
+    }
+  }
+
   /**
    * Quantizes all notes' start and end points to the nearest `beatSnapInterval`, handling conflicts and
    * performing some other special-case operations.  See https://synth.ameo.dev/docs/2021-04-18
@@ -649,30 +950,20 @@ export default class MIDIEditorUIInstance {
 
       const shortNotes = notes.filter(({ note }) => note.length <= this.beatSnapInterval / 2);
       shortNotes.forEach(({ note }) => {
-        wasm.instance.delete_note(wasm.noteLinesCtxPtr, lineIx, note.startPoint, note.id);
         const snappedStart = this.parentInstance.snapBeat(note.startPoint);
-        const canMove = wasm.instance.check_can_add_note(
+        // The note is left where it was in case of conflict
+        const moved = wasm.instance.move_note(
           wasm.noteLinesCtxPtr,
           lineIx,
+          note.startPoint,
+          note.id,
+          lineIx,
           snappedStart,
           note.length
         );
-
-        if (!canMove) {
-          // Re-insert the note where it was before in case of conflict
-          wasm.instance.create_note(
-            wasm.noteLinesCtxPtr,
-            lineIx,
-            note.startPoint,
-            note.length,
-            note.id
-          );
-          return;
+        if (moved) {
+          note.startPoint = snappedStart;
         }
-
-        // We're good to move the note, so re-insert at the snapped start point
-        note.startPoint = snappedStart;
-        wasm.instance.create_note(wasm.noteLinesCtxPtr, lineIx, snappedStart, note.length, note.id);
       });
     }
 
@@ -689,69 +980,44 @@ export default class MIDIEditorUIInstance {
       const snappedStart = this.parentInstance.snapBeat(note.startPoint);
       const snappedEnd = this.parentInstance.snapBeat(note.startPoint + note.length);
 
+      // Notes are left as they were in case of conflict
       if (snappedStart < note.startPoint) {
-        wasm.instance.delete_note(wasm.noteLinesCtxPtr, line.index, note.startPoint, note.id);
-        const canMove = wasm.instance.check_can_add_note(
+        const newLength = note.length + (note.startPoint - snappedStart);
+        const moved = wasm.instance.move_note(
           wasm.noteLinesCtxPtr,
           line.index,
+          note.startPoint,
+          note.id,
+          line.index,
           snappedStart,
-          note.length
+          newLength
         );
-
-        if (!canMove) {
-          // Re-insert the note where it was before in case of conflict
-          wasm.instance.create_note(
-            wasm.noteLinesCtxPtr,
-            line.index,
-            note.startPoint,
-            note.length,
-            note.id
-          );
-        } else {
-          note.length += snappedStart - note.startPoint;
+        if (moved) {
+          note.length = newLength;
           note.startPoint = snappedStart;
-          wasm.instance.create_note(
-            wasm.noteLinesCtxPtr,
-            line.index,
-            note.startPoint,
-            note.length,
-            note.id
-          );
         }
       }
 
       if (snappedEnd > note.startPoint + note.length) {
-        wasm.instance.delete_note(wasm.noteLinesCtxPtr, line.index, note.startPoint, note.id);
         const newLength = note.length + (snappedEnd - (note.startPoint + note.length));
-        const canMove = wasm.instance.check_can_add_note(
+        const moved = wasm.instance.move_note(
           wasm.noteLinesCtxPtr,
           line.index,
           note.startPoint,
+          note.id,
+          line.index,
+          note.startPoint,
           newLength
         );
-
-        if (!canMove) {
-          // Re-insert the note where it was before in case of conflict
-          wasm.instance.create_note(
-            wasm.noteLinesCtxPtr,
-            line.index,
-            note.startPoint,
-            note.length,
-            note.id
-          );
-        } else {
+        if (moved) {
           note.length = newLength;
-          wasm.instance.create_note(
-            wasm.noteLinesCtxPtr,
-            line.index,
-            note.startPoint,
-            note.length,
-            note.id
-          );
         }
       }
     }
 
+    for (const noteID of this.selectedNoteIDs) {
+      this.recordCollabEdit(this.allNotesByID.get(noteID)!);
+    }
     // Since we manually updated note lengths, re-render everything
     this.handleViewChange();
   }
@@ -763,10 +1029,16 @@ export default class MIDIEditorUIInstance {
 
   public startDraggingSelectedNotes(data: PIXI.InteractionData) {
     const localY = data.getLocalPosition(this.linesContainer).y;
+    const startLineIx = this.computeLineIndex(localY);
     this.dragData = {
       globalStartPoint: data.global.clone(),
       originalPosBeatsByNoteId: new Map(),
-      startLineIx: this.computeLineIndex(localY),
+      originalLineIxByNoteId: new Map(),
+      originLineIx: startLineIx,
+      startLineIx,
+      desiredPositionsByNoteId: new Map(),
+      direction: { beats: 0, lines: 0 },
+      ghostsByNoteId: new Map(),
     };
 
     for (const noteId of this.selectedNoteIDs.values()) {
@@ -779,6 +1051,7 @@ export default class MIDIEditorUIInstance {
 
       const originalPosBeats = note.note.startPoint;
       this.dragData.originalPosBeatsByNoteId.set(noteId, originalPosBeats);
+      this.dragData.originalLineIxByNoteId.set(noteId, note.line.index);
     }
   }
 
@@ -825,6 +1098,27 @@ export default class MIDIEditorUIInstance {
 
     const xDiffPx = data.global.x - this.dragData.globalStartPoint.x;
     const xDiffBeats = this.pxToBeats(xDiffPx);
+    // Holding shift while dragging toggles snapping
+    const snap = !(data.originalEvent as MouseEvent | undefined)?.shiftKey;
+    const snapBeat = (beat: number) => (snap ? this.parentInstance.snapBeat(beat) : beat);
+
+    this.handleDragInner(data, xDiffBeats, snapBeat);
+    this.updateDragGhosts(data, xDiffBeats, snapBeat);
+  }
+
+  /**
+   * Moves the selected notes as close as possible to where they're being dragged, in both
+   * dimensions at once.  Notes stop at any notes in their way; they're moved the rest of the way
+   * on mouse up if possible.
+   */
+  private handleDragInner(
+    data: PIXI.InteractionData,
+    xDiffBeats: number,
+    snapBeat: (beat: number) => number
+  ) {
+    if (!this.dragData) {
+      return;
+    }
 
     // We first move all of the notes horizontally before attempting any vertical movement
     for (const noteId of this.selectedNoteIDs.values()) {
@@ -839,10 +1133,7 @@ export default class MIDIEditorUIInstance {
           `Note id ${noteId} is selected but not in original pos mapping`
         );
       }
-      const newDesiredStartPosBeats = Math.max(
-        this.parentInstance.snapBeat(originalPosBeats + xDiffBeats),
-        0
-      );
+      const newDesiredStartPosBeats = Math.max(snapBeat(originalPosBeats + xDiffBeats), 0);
       note.handleDrag(newDesiredStartPosBeats);
     }
 
@@ -903,26 +1194,222 @@ export default class MIDIEditorUIInstance {
   }
 
   private moveNoteToLine(note: NoteBox, newLineIx: number) {
-    note.line.container.removeChild(note.graphics);
-    note.line.notesByID.delete(note.note.id);
-    this.wasm!.instance.delete_note(
+    this.wasm!.instance.move_note(
       this.wasm!.noteLinesCtxPtr,
       note.line.index,
       note.note.startPoint,
-      note.note.id
-    );
-    this.wasm!.instance.create_note(
-      this.wasm!.noteLinesCtxPtr,
+      note.note.id,
       newLineIx,
       note.note.startPoint,
-      note.note.length,
-      note.note.id
+      note.note.length
     );
+    this.setNoteBoxLine(note, newLineIx);
+    this.recordCollabEdit(note);
+  }
+
+  private setNoteBoxLine(note: NoteBox, newLineIx: number) {
+    note.line.container.removeChild(note.graphics);
+    note.line.notesByID.delete(note.note.id);
     note.line = this.lines[newLineIx];
     note.line.container.addChild(note.graphics);
     note.line.notesByID.set(note.note.id, note);
   }
 
+  /**
+   * Moves a note to another line if it's in range and there's room for it there.  Returns `false`
+   * and leaves the note where it is otherwise.
+   */
+  private tryMoveNoteToLine(note: NoteBox, newLineIx: number): boolean {
+    if (!this.wasm || newLineIx < 0 || newLineIx >= this.lines.length) {
+      return false;
+    }
+
+    const moved = this.wasm.instance.move_note_vertically(
+      this.wasm.noteLinesCtxPtr,
+      note.line.index,
+      newLineIx,
+      note.note.startPoint,
+      note.note.id
+    );
+    if (moved) {
+      this.setNoteBoxLine(note, newLineIx);
+      this.recordCollabEdit(note);
+    }
+    return moved;
+  }
+
+  /**
+   * Moves each selected note by `steps` notes of the instance's key and scale.  Notes furthest in
+   * the direction of the transposition are moved first so that they get out of the way of the
+   * ones behind them; notes that are still blocked by other notes stay where they are.
+   */
+  public transposeSelectedNotesByScaleDegrees(steps: number) {
+    // Drum lanes aren't pitches, so there's no scale to move along
+    if (this.managedInst.drumLanes) {
+      return;
+    }
+
+    const { keyAndScale, tuning } = this.managedInst;
+    const notes = [...this.selectedNoteIDs]
+      .map(id => this.allNotesByID.get(id)!)
+      .sort((a, b) => (a.line.index - b.line.index) * Math.sign(steps));
+    for (const note of notes) {
+      const midiNumber = this.managedInst.lineIxToMIDINumber(note.line.index);
+      const transposed = transposeByScaleDegrees(midiNumber, steps, keyAndScale, tuning);
+      const newLineIx = this.managedInst.midiNumberToLineIx(transposed);
+      if (newLineIx !== null) {
+        this.tryMoveNoteToLine(note, newLineIx);
+      }
+    }
+  }
+
+  /**
+   * Treats selected notes that start on the same beat as chords and inverts each of them by moving
+   * its lowest note up an octave.
+   */
+  public invertSelectedChords() {
+    if (this.managedInst.drumLanes) {
+      return;
+    }
+
+    const lowestNoteByStartPoint: Map<number, NoteBox> = new Map();
+    for (const id of this.selectedNoteIDs) {
+      const note = this.allNotesByID.get(id)!;
+      const lowest = lowestNoteByStartPoint.get(note.note.startPoint);
+      // Lower notes are further down, at higher line indices
+      if (!lowest || note.line.index > lowest.line.index) {
+        lowestNoteByStartPoint.set(note.note.startPoint, note);
+      }
+    }
+
+    const { divisionsPerOctave } = this.managedInst.tuning;
+    for (const note of lowestNoteByStartPoint.values()) {
+      this.tryMoveNoteToLine(note, note.line.index - divisionsPerOctave);
+    }
+  }
+
+  /**
+   * Draws translucent previews of where the selected notes are being dragged for those that are
+   * held back by other notes in the way.
+   */
+  private updateDragGhosts(
+    data: PIXI.InteractionData,
+    xDiffBeats: number,
+    snapBeat: (beat: number) => number
+  ) {
+    if (!this.dragData) {
+      return;
+    }
+
+    const totalLineDiff =
+      this.computeLineIndex(data.getLocalPosition(this.linesContainer).y) -
+      this.dragData.originLineIx;
+    this.dragData.direction = { beats: Math.sign(xDiffBeats), lines: Math.sign(totalLineDiff) };
+    for (const noteId of this.selectedNoteIDs.values()) {
+      const note = this.allNotesByID.get(noteId)!;
+      const originalPosBeats = this.dragData.originalPosBeatsByNoteId.get(noteId)!;
+      const originalLineIx = this.dragData.originalLineIxByNoteId.get(noteId)!;
+      const desired = {
+        lineIx: R.clamp(0, this.lines.length - 1, originalLineIx + totalLineDiff),
+        startPoint: Math.max(snapBeat(originalPosBeats + xDiffBeats), 0),
+      };
+      this.dragData.desiredPositionsByNoteId.set(noteId, desired);
+
+      const existingGhost = this.dragData.ghostsByNoteId.get(noteId);
+      const isBlocked =
+        desired.lineIx !== note.line.index || desired.startPoint !== note.note.startPoint;
+      if (!isBlocked) {
+        if (existingGhost) {
+          this.lines[existingGhost.lineIx].container.removeChild(existingGhost.graphics);
+          existingGhost.graphics.destroy();
+          this.dragData.ghostsByNoteId.delete(noteId);
+        }
+        continue;
+      }
+
+      let ghost = existingGhost;
+      if (!ghost) {
+        const graphics = new PIXI.Graphics();
+        graphics.alpha = conf.NOTE_GHOST_ALPHA;
+        ghost = { graphics, lineIx: desired.lineIx };
+        this.lines[desired.lineIx].container.addChild(graphics);
+        this.dragData.ghostsByNoteId.set(noteId, ghost);
+      } else if (ghost.lineIx !== desired.lineIx) {
+        this.lines[ghost.lineIx].container.removeChild(ghost.graphics);
+        this.lines[desired.lineIx].container.addChild(ghost.graphics);
+        ghost.lineIx = desired.lineIx;
+      }
+
+      const widthPx = this.beatsToPx(note.note.length) - 1;
+      ghost.graphics.clear();
+      ghost.graphics.lineStyle(1, 0x333333);
+      ghost.graphics.beginFill(conf.NOTE_SELECTED_COLOR);
+      ghost.graphics.drawRect(1, 0, widthPx, conf.LINE_HEIGHT - 1);
+      ghost.graphics.endFill();
+      ghost.graphics.x =
+        this.beatsToPx(desired.startPoint - this.parentInstance.baseView.scrollHorizontalBeats) - 1;
+    }
+  }
+
+  /**
+   * Called on mouse up after dragging notes.  Moves each note that was held back by other notes to
+   * where it was dragged if that spot is free now, leaving it where it is otherwise.
+   */
+  private commitDrag() {
+    if (!this.dragData || !this.wasm) {
+      return;
+    }
+    const { noteLinesCtxPtr, instance } = this.wasm;
+
+    for (const { graphics, lineIx } of this.dragData.ghostsByNoteId.values()) {
+      this.lines[lineIx].container.removeChild(graphics);
+      graphics.destroy();
+    }
+
+    // Move notes that are furthest along in the direction of the drag first so that they get out
+    // of the way of the ones behind them
+    const { direction } = this.dragData;
+    const pending = [...this.dragData.desiredPositionsByNoteId.entries()]
+      .map(([noteId, desired]) => ({ note: this.allNotesByID.get(noteId), desired }))
+      .filter(
+        (entry): entry is { note: NoteBox; desired: { lineIx: number; startPoint: number } } =>
+          !!entry.note &&
+          (entry.desired.lineIx !== entry.note.line.index ||
+            entry.desired.startPoint !== entry.note.note.startPoint)
+      )
+      .sort(
+        (a, b) =>
+          direction.beats * (b.desired.startPoint - a.desired.startPoint) ||
+          direction.lines * (b.desired.lineIx - a.desired.lineIx)
+      );
+
+    for (const { note, desired } of pending) {
+      const moved = instance.move_note(
+        noteLinesCtxPtr,
+        note.line.index,
+        note.note.startPoint,
+        note.note.id,
+        desired.lineIx,
+        desired.startPoint,
+        note.note.length
+      );
+      if (!moved) {
+        continue;
+      }
+
+      if (desired.lineIx !== note.line.index) {
+        note.line.container.removeChild(note.graphics);
+        note.line.notesByID.delete(note.note.id);
+        note.line = this.lines[desired.lineIx];
+        note.line.container.addChild(note.graphics);
+        note.line.notesByID.set(note.note.id, note);
+      }
+      note.note.startPoint = desired.startPoint;
+      note.render();
+      this.recordCollabEdit(note);
+    }
+  }
+
   public setLoopPoint(loopPoint?: number | null | undefined) {
     const newLoopPoint = this.parentInstance.snapBeat(
       loopPoint ??
@@ -950,10 +1437,42 @@ export default class MIDIEditorUIInstance {
   public serializeLines(): SerializedMIDILine[] {
     return this.lines.map((line, lineIx) => ({
       midiNumber: this.lines.length - lineIx,
-      notes: [...line.notesByID.values()].map(note => ({
-        startPoint: note.note.startPoint,
-        length: note.note.length,
-      })),
+      notes: [...line.notesByID.values()].map(note => {
+        const serialized: SerializedMIDINote = {
+          startPoint: note.note.startPoint,
+          length: note.note.length,
+        };
+        // Most notes have full velocity, so it's only stored for the ones that don't
+        const velocity = this.wasm?.instance.get_note_velocity(
+          this.wasm.noteLinesCtxPtr,
+          note.note.id
+        );
+        if (!R.isNil(velocity) && velocity < 1) {
+          serialized.velocity = velocity;
+        }
+        const probability = this.wasm?.instance.get_note_probability(
+          this.wasm.noteLinesCtxPtr,
+          note.note.id
+        );
+        if (!R.isNil(probability) && probability < 1) {
+          serialized.probability = probability;
+        }
+        const roundRobinGroup = this.wasm?.instance.get_note_round_robin_group(
+          this.wasm.noteLinesCtxPtr,
+          note.note.id
+        );
+        if (!R.isNil(roundRobinGroup) && roundRobinGroup >= 0) {
+          serialized.roundRobinGroup = roundRobinGroup;
+        }
+        const articulation = this.wasm?.instance.get_note_articulation(
+          this.wasm.noteLinesCtxPtr,
+          note.note.id
+        );
+        if (articulation) {
+          serialized.articulation = articulation;
+        }
+        return serialized;
+      }),
     }));
   }
 
@@ -999,10 +1518,39 @@ export default class MIDIEditorUIInstance {
   public handleViewChange() {
     this.view.scrollVerticalPx = R.clamp(0, this.maxVerticalScrollPx, this.view.scrollVerticalPx);
 
+    this.measureLines.handleViewChange();
     this.lines.forEach(line => line.handleViewChange());
     this.cursor.handleViewChange();
     this.loopCursor?.handleViewChange();
     this.pianoKeys?.handleViewChange();
+    this.highlightRegions.handleViewChange();
+  }
+
+  /**
+   * Pages the view forward if following playback is enabled and the cursor has moved past the
+   * visible area, or back if it's jumped behind it due to looping.
+   */
+  private maybeFollowPlayback(cursorPosBeats: number) {
+    if (
+      this.isHidden ||
+      !this.parentInstance.followPlayback ||
+      !this.parentInstance.playbackHandler?.isPlaying
+    ) {
+      return;
+    }
+
+    const startBeat = this.parentInstance.baseView.scrollHorizontalBeats;
+    const widthBeats = this.pxToBeats(this.width - conf.PIANO_KEYBOARD_WIDTH);
+    if (
+      cursorPosBeats >= startBeat &&
+      cursorPosBeats < startBeat + widthBeats * conf.FOLLOW_PLAYBACK_PAGE_THRESHOLD
+    ) {
+      return;
+    }
+
+    this.parentInstance.setScrollHorizontalBeats(
+      Math.max(cursorPosBeats - widthBeats * conf.FOLLOW_PLAYBACK_LEAD_FRACTION, 0)
+    );
   }
 
   private handleZoom(evt: WheelEvent) {
@@ -1044,52 +1592,87 @@ export default class MIDIEditorUIInstance {
           case 'ControlLeft':
           case 'ControlRight': {
             this.multiSelectEnabled = true;
-            break;
+            return;
           }
           case 'ShiftLeft':
           case 'ShiftRight': {
             this.selectionBoxButtonDown = true;
-            break;
+            return;
           }
-          case 'Delete': {
+        }
+
+        switch (matchKeymapAction(evt, MIDI_EDITOR_KEYMAP_ACTIONS)) {
+          case 'midiEditor.deleteSelection': {
             for (const id of this.selectedNoteIDs) {
               this.deleteNote(id);
             }
             this.selectedNoteIDs.clear();
             break;
           }
-          case 'KeyC': {
-            if (this.multiSelectEnabled) {
-              this.copySelection();
-            }
+          case 'midiEditor.copySelection': {
+            this.copySelection();
             break;
           }
-          case 'KeyX': {
-            if (this.multiSelectEnabled) {
-              this.cutSelection();
-            }
+          case 'midiEditor.cutSelection': {
+            this.cutSelection();
             break;
           }
-          case 'KeyV': {
-            if (this.multiSelectEnabled) {
-              this.pasteSelection();
-            }
+          case 'midiEditor.paste': {
+            this.pasteSelection();
             break;
           }
-          case 'ArrowLeft': {
+          case 'midiEditor.scrollLeft': {
             this.parentInstance.setScrollHorizontalBeats(
               Math.max(this.parentInstance.baseView.scrollHorizontalBeats - 1, 0)
             );
             this.handleViewChange();
             break;
           }
-          case 'ArrowRight': {
+          case 'midiEditor.scrollRight': {
             this.parentInstance.setScrollHorizontalBeats(
               this.parentInstance.baseView.scrollHorizontalBeats + 1
             );
             this.handleViewChange();
             break;
           }
+          case 'midiEditor.transposeUpScaleDegree': {
+            this.transposeSelectedNotesByScaleDegrees(1);
+            break;
+          }
+          case 'midiEditor.transposeDownScaleDegree': {
+            this.transposeSelectedNotesByScaleDegrees(-1);
+            break;
+          }
+          case 'midiEditor.invertChords': {
+            this.invertSelectedChords();
+            break;
+          }
+          case 'midiEditor.toggleStaccato': {
+            this.toggleSelectedNotesArticulation(NoteArticulation.Staccato);
+            break;
+          }
+          case 'midiEditor.toggleAccent': {
+            this.toggleSelectedNotesArticulation(NoteArticulation.Accent);
+            break;
+          }
+          case 'midiEditor.toggleTie': {
+            this.toggleSelectedNotesArticulation(NoteArticulation.Tie);
+            break;
+          }
+          case 'midiEditor.selectRow': {
+            if (this.lastClickedLineIx !== null) {
+              this.selectAllNotesInRow(this.lastClickedLineIx);
+            }
+            break;
+          }
+          case 'midiEditor.selectEveryOther': {
+            this.applySelectionFilter({ type: 'everyNth', n: 2 });
+            break;
+          }
+          case 'midiEditor.toggleInputRecording': {
+            this.toggleInputRecording();
+            break;
+          }
         }
       },
       keyUp: (evt: KeyboardEvent) => {
@@ -1113,12 +1696,19 @@ export default class MIDIEditorUIInstance {
           this.mouseUpCBs = [];
 
           this.resizeData = null;
+          this.commitDrag();
           this.dragData = null;
 
           if (this.selectionBox) {
             this.selectionBox.destroy();
             this.selectionBox = null;
           }
+
+          if (this.marqueeZoomBox) {
+            this.marqueeZoomBox.apply();
+            this.marqueeZoomBox.destroy();
+            this.marqueeZoomBox = null;
+          }
         } else if (evt.button === 1) {
           this.stopPanning();
         }
@@ -1201,6 +1791,8 @@ export default class MIDIEditorUIInstance {
 
     this.destroyed = true;
     this.cleanupEventHandlers();
+    this.highlightRegions.destroy();
+    this.measureLines.destroy();
     try {
       destroyPIXIApp(this.app);
     } catch (err) {
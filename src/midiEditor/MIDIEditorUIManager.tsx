@@ -1,24 +1,88 @@
 import * as R from 'ramda';
 import { get, writable, type Writable } from 'svelte/store';
 
+import type { ADSR2Instance } from 'src/controls/adsr2/adsr2';
 import {
+  DrumLane,
   get_midi_editor_audio_connectables,
+  HumanizeConfig,
   MIDIEditorInstance,
   MIDIEditorInstanceView,
+  MIDIEditorTrackSettings,
   SerializedMIDIEditorBaseInstance,
   SerializedMIDIEditorInstance,
   SerializedMIDIEditorState,
   SerializedMIDILine,
 } from 'src/midiEditor';
+import {
+  buildDefaultClipLauncherState,
+  ClipLauncher,
+  type SerializedClipLauncher,
+} from 'src/midiEditor/clips';
+import { MIDIEditorCollabSession } from 'src/midiEditor/collaboration';
 import { buildDefaultCVOutputState, CVOutput } from 'src/midiEditor/CVOutput/CVOutput';
 import type MIDIEditorUIInstance from 'src/midiEditor/MIDIEditorUIInstance';
 import { Note } from 'src/midiEditor/MIDIEditorUIInstance';
 import { renderMIDIMinimap } from 'src/midiEditor/Minimap/MinimapRenderer';
+import {
+  buildDefaultPitchBendLaneState,
+  samplePitchBendEvents,
+  type PitchBendEvent,
+  type PitchBendLaneState,
+} from 'src/midiEditor/PitchBendLane/PitchBendLane';
 import { updateConnectables } from 'src/patchNetwork/interface';
 import { MIDINode, mkBuildPasthroughInputCBs, type MIDIInputCbs } from 'src/patchNetwork/midiNode';
+import {
+  DEFAULT_KEY_AND_SCALE,
+  getNoteRowInfo,
+  TWELVE_TET,
+  type KeyAndScale,
+  type NoteRowInfo,
+  type Tuning,
+} from 'src/tuning';
 import { AsyncOnce } from 'src/util';
-
-const NoteContainerWasm = new AsyncOnce(() => import('src/note_container'), true);
+import * as conf from './conf';
+
+export const NoteContainerWasm = new AsyncOnce(() => import('src/note_container'), true);
+
+/**
+ * Adds all notes in `lines` to the note lines at `noteLinesCtxPtr`, which must have the same number
+ * of lines.  Returns the notes with their new IDs by line index.
+ */
+export const loadNoteLines = (
+  wasm: typeof import('src/note_container'),
+  noteLinesCtxPtr: number,
+  lines: SerializedMIDILine[]
+): Note[][] => {
+  const linesWithIDs: Note[][] = new Array(lines.length).fill(null).map(() => []);
+  for (const { midiNumber, notes } of lines) {
+    const lineIx = lines.length - midiNumber;
+    for (const {
+      length,
+      startPoint,
+      velocity,
+      probability,
+      roundRobinGroup,
+      articulation,
+    } of notes) {
+      const id = wasm.create_note(noteLinesCtxPtr, lineIx, startPoint, length, 0);
+      if (!R.isNil(velocity)) {
+        wasm.set_note_velocity(noteLinesCtxPtr, id, velocity);
+      }
+      if (!R.isNil(probability)) {
+        wasm.set_note_probability(noteLinesCtxPtr, id, probability);
+      }
+      if (!R.isNil(roundRobinGroup)) {
+        wasm.set_note_round_robin_group(noteLinesCtxPtr, id, roundRobinGroup);
+      }
+      if (!R.isNil(articulation)) {
+        wasm.set_note_articulation(noteLinesCtxPtr, id, articulation);
+      }
+      linesWithIDs[lineIx].push({ id, startPoint, length });
+    }
+  }
+  return linesWithIDs;
+};
 
 export class ManagedMIDIEditorUIInstance {
   public manager: MIDIEditorUIManager;
@@ -30,7 +94,55 @@ export class ManagedMIDIEditorUIInstance {
   public midiOutput: MIDINode;
   public midiInputCBs: MIDIInputCbs;
   public lines: SerializedMIDILine[];
+  /**
+   * Determines how many rows make up an octave and how they're labeled.  Each row is one scale
+   * degree of the tuning.
+   */
+  public tuning: Tuning;
+  public keyAndScale: KeyAndScale;
+  /**
+   * If set, random timing and velocity offsets are applied to notes as they're played back without
+   * modifying the notes themselves.
+   */
+  public playbackHumanize: HumanizeConfig | null;
+  public track: MIDIEditorTrackSettings;
+  /**
+   * If set, rows are drum lanes rather than pitches.  Contains one entry per line.
+   */
+  public drumLanes: DrumLane[] | null;
+  /**
+   * If set, notes played by this instance are bent along the lane's curve during playback
+   */
+  public pitchBend: Writable<PitchBendLaneState> | null;
+  private pitchBendUIInst: ADSR2Instance | null = null;
+  /**
+   * If set, adjacent notes on the same line are played as one sustained note rather than being
+   * re-triggered
+   */
+  public legato: boolean;
+  public playbackVariationSeed: number;
+  /**
+   * Re-created with the configured seed every time playback starts so that each playthrough from
+   * the start is identical.  0 if playback humanization is disabled.
+   */
+  private playbackHumanizerPtr = 0;
+  /**
+   * Holds the RNG and round-robin positions used to vary which notes are played.  Re-created
+   * every time playback starts like the humanizer.
+   */
+  private playbackVariationPtr = 0;
+  public clipLauncher: ClipLauncher;
   private onWasmInitCBs: ((linesWithIDs: readonly Note[][]) => void)[] = [];
+  /**
+   * Set while all note edits are being recorded by the Wasm.  Recording stops when the Wasm is
+   * re-initialized since that creates new note lines.
+   */
+  public isRecordingInput = false;
+  /**
+   * Set while this instance's notes are shared with other users through a collaboration room.  The
+   * session is tied to the current note lines, so it's closed when the Wasm is re-initialized.
+   */
+  public collab: MIDIEditorCollabSession | null = null;
   public wasm:
     | {
         instance: typeof import('src/note_container');
@@ -45,13 +157,30 @@ export class ManagedMIDIEditorUIInstance {
     name: string,
     view: MIDIEditorInstanceView,
     id: string,
-    lines: SerializedMIDILine[]
+    lines: SerializedMIDILine[],
+    tuning: Tuning = TWELVE_TET,
+    playbackHumanize: HumanizeConfig | null = null,
+    track: MIDIEditorTrackSettings = { muted: false, soloed: false, color: conf.NOTE_COLOR },
+    drumLanes: DrumLane[] | null = null,
+    pitchBend: PitchBendLaneState | null = null,
+    legato = false,
+    playbackVariationSeed = 0,
+    keyAndScale: KeyAndScale = DEFAULT_KEY_AND_SCALE,
+    clips: SerializedClipLauncher = buildDefaultClipLauncherState()
   ) {
     this.manager = manager;
     this.id = id;
     this.name = name;
     this.view = view;
     this.lines = lines;
+    this.tuning = tuning;
+    this.playbackHumanize = playbackHumanize;
+    this.track = track;
+    this.drumLanes = drumLanes;
+    this.pitchBend = pitchBend ? writable(pitchBend) : null;
+    this.legato = legato;
+    this.playbackVariationSeed = playbackVariationSeed;
+    this.keyAndScale = keyAndScale;
     this.midiInputCBs = this.buildInstanceMIDIInputCbs();
 
     this.midiInput = new MIDINode(() => this.midiInputCBs);
@@ -59,6 +188,9 @@ export class ManagedMIDIEditorUIInstance {
     this.midiOutput.getInputCbs = mkBuildPasthroughInputCBs(this.midiOutput);
     // By default, we pass MIDI events through from the input to the output
     this.midiInput.connect(this.midiOutput);
+    // Newly connected destinations need to know how to map our note numbers to frequencies
+    this.midiOutput.registerOnConnectionsChangedCb(this.broadcastTuning);
+    this.clipLauncher = new ClipLauncher(this, clips);
 
     this.initWasm(lines);
   }
@@ -67,19 +199,13 @@ export class ManagedMIDIEditorUIInstance {
     const wasm = await NoteContainerWasm.get();
 
     const noteLinesCtxPtr = wasm.create_note_lines(lines.length);
+    const linesWithIDs = loadNoteLines(wasm, noteLinesCtxPtr, lines);
 
-    const linesWithIDs: Note[][] = new Array(lines.length).fill(null).map(() => []);
-    for (const { midiNumber, notes } of lines) {
-      const lineIx = lines.length - midiNumber;
-      for (const { length, startPoint } of notes) {
-        const id = wasm.create_note(noteLinesCtxPtr, lineIx, startPoint, length, 0);
-        linesWithIDs[lineIx].push({ id, startPoint, length });
-      }
-    }
-
+    this.stopCollaborating();
     if (this.wasm) {
       this.wasm.instance.free_note_lines(this.wasm.noteLinesCtxPtr);
     }
+    this.isRecordingInput = false;
 
     this.wasm = {
       instance: wasm,
@@ -91,10 +217,56 @@ export class ManagedMIDIEditorUIInstance {
     this.onWasmInitCBs = [];
   }
 
+  /**
+   * Starts recording all note edits, or stops recording and returns the encoded recording.  See
+   * `engine/note_container/src/input_recording.rs`.
+   */
+  public toggleInputRecording(): Uint8Array | null {
+    if (!this.wasm) {
+      return null;
+    }
+
+    const { instance, noteLinesCtxPtr } = this.wasm;
+    if (this.isRecordingInput) {
+      this.isRecordingInput = false;
+      return instance.stop_input_recording(noteLinesCtxPtr);
+    }
+
+    instance.start_input_recording(noteLinesCtxPtr);
+    this.isRecordingInput = true;
+    return null;
+  }
+
   public get lineCount(): number {
     return this.lines.length;
   }
 
+  /**
+   * Returns the MIDI number played by notes on the given line
+   */
+  public lineIxToMIDINumber(lineIx: number): number {
+    return this.drumLanes ? this.drumLanes[lineIx].midiNumber : this.lineCount - lineIx;
+  }
+
+  /**
+   * Returns the line that notes with the given MIDI number are played on, or `null` if it doesn't
+   * correspond to any drum lane
+   */
+  public midiNumberToLineIx(midiNumber: number): number | null {
+    if (!this.drumLanes) {
+      return this.lineCount - midiNumber;
+    }
+    const lineIx = this.drumLanes.findIndex(lane => lane.midiNumber === midiNumber);
+    return lineIx === -1 ? null : lineIx;
+  }
+
+  public getRowInfo(lineIx: number): NoteRowInfo {
+    if (this.drumLanes) {
+      return { label: this.drumLanes[lineIx].name, isNatural: true, isOctaveRoot: false };
+    }
+    return getNoteRowInfo(this.lineCount - lineIx, this.tuning);
+  }
+
   public onWasmLoaded = (cb: (linesWithIDs: readonly Note[][]) => void) => {
     if (this.wasm) {
       cb(this.wasm.linesWithIDs);
@@ -107,7 +279,10 @@ export class ManagedMIDIEditorUIInstance {
     onAttack: (note, velocity) => {
       // if (!this.playbackHandler.isPlaying || this.playbackHandler.recordingCtx) {
       this.midiInput.onAttack(note, velocity);
-      this.uiInst?.onGated(this.lineCount - note);
+      const lineIx = this.midiNumberToLineIx(note);
+      if (lineIx !== null) {
+        this.uiInst?.onGated(lineIx);
+      }
       // }
 
       if (this.manager.parentInst.playbackHandler.recordingCtx) {
@@ -117,7 +292,10 @@ export class ManagedMIDIEditorUIInstance {
     onRelease: (note, velocity) => {
       // if (!this.playbackHandler.isPlaying || this.playbackHandler.recordingCtx) {
       this.midiInput.onRelease(note, velocity);
-      this.uiInst?.onUngated(this.lineCount - note);
+      const lineIx = this.midiNumberToLineIx(note);
+      if (lineIx !== null) {
+        this.uiInst?.onUngated(lineIx);
+      }
       // }
 
       if (this.manager.parentInst.playbackHandler.recordingCtx) {
@@ -146,7 +324,7 @@ export class ManagedMIDIEditorUIInstance {
   public iterNotesWithCB = (
     startBeatInclusive: number | null | undefined,
     endBeatExclusive: number | null | undefined,
-    cb: (isAttack: boolean, lineIx: number, rawBeat: number, noteID: number) => void
+    cb: (isAttack: boolean, lineIx: number, rawBeat: number, velocity: number) => void
   ) => {
     if (!this.wasm) {
       throw new Error('Wasm instance not initialized; cannot get Wasm instance');
@@ -157,20 +335,142 @@ export class ManagedMIDIEditorUIInstance {
       noteLinesCtxPtr,
       startBeatInclusive ?? 0,
       endBeatExclusive ?? -1,
-      cb
+      cb,
+      this.playbackHumanizerPtr,
+      this.legato,
+      this.playbackVariationPtr
     );
   };
 
+  public setPlaybackHumanize(config: HumanizeConfig | null) {
+    this.playbackHumanize = config;
+  }
+
+  /**
+   * Called when playback starts
+   */
+  public resetPlaybackHumanizer() {
+    if (!this.wasm) {
+      return;
+    }
+
+    if (this.playbackHumanizerPtr) {
+      this.wasm.instance.free_humanizer(this.playbackHumanizerPtr);
+      this.playbackHumanizerPtr = 0;
+    }
+    if (this.playbackHumanize) {
+      const { seed, timingAmountBeats, velocityAmount } = this.playbackHumanize;
+      this.playbackHumanizerPtr = this.wasm.instance.create_humanizer(
+        seed,
+        timingAmountBeats,
+        velocityAmount
+      );
+    }
+  }
+
+  /**
+   * Called when playback starts
+   */
+  public resetPlaybackVariation() {
+    if (!this.wasm) {
+      return;
+    }
+
+    if (this.playbackVariationPtr) {
+      this.wasm.instance.free_playback_variation(this.playbackVariationPtr);
+    }
+    this.playbackVariationPtr = this.wasm.instance.create_playback_variation(
+      this.playbackVariationSeed
+    );
+  }
+
   public gate(lineIx: number) {
-    this.midiInputCBs.onAttack(this.lineCount - lineIx, 255);
+    this.midiInputCBs.onAttack(this.lineIxToMIDINumber(lineIx), 255);
   }
 
   public ungate(lineIx: number) {
-    this.midiInputCBs.onRelease(this.lineCount - lineIx, 255);
+    this.midiInputCBs.onRelease(this.lineIxToMIDINumber(lineIx), 255);
   }
 
   public stopPlayback() {
     this.midiOutput.clearAll();
+    // Don't leave notes played after playback stops bent by wherever the lane left off
+    if (this.pitchBend) {
+      this.midiOutput.onPitchBend(0);
+    }
+  }
+
+  public addPitchBendLane() {
+    if (this.pitchBend) {
+      return;
+    }
+
+    this.pitchBend = writable(buildDefaultPitchBendLaneState());
+    this.manager.instances.set(get(this.manager.instances));
+  }
+
+  public removePitchBendLane() {
+    if (!this.pitchBend) {
+      return;
+    }
+
+    this.pitchBend = null;
+    this.pitchBendUIInst = null;
+    this.midiOutput.onPitchBend(0);
+    this.manager.instances.set(get(this.manager.instances));
+  }
+
+  public registerPitchBendUIInstance(uiInst: ADSR2Instance) {
+    this.pitchBendUIInst = uiInst;
+    this.handlePitchBendViewChange();
+  }
+
+  public handlePitchBendViewChange() {
+    if (!this.pitchBendUIInst) {
+      return;
+    }
+
+    const { pxPerBeat, scrollHorizontalBeats } = this.manager.parentInst.baseView;
+    this.pitchBendUIInst.setRenderedRegion({
+      start: scrollHorizontalBeats,
+      end: scrollHorizontalBeats + this.pitchBendUIInst.width / pxPerBeat,
+    });
+  }
+
+  /**
+   * Returns pitch bend events from the lane with beats relative to `startBeat`, or nothing if this
+   * instance doesn't have a pitch bend lane.
+   */
+  public getPitchBendEvents(startBeat: number, endBeat: number | null): PitchBendEvent[] {
+    if (!this.pitchBend) {
+      return [];
+    }
+    return samplePitchBendEvents(get(this.pitchBend).breakpoints, startBeat, endBeat);
+  }
+
+  public setTuning(tuning: Tuning) {
+    this.midiOutput.clearAll();
+    this.tuning = tuning;
+    this.uiInst?.rebuildPianoKeys();
+    this.broadcastTuning();
+  }
+
+  private broadcastTuning = () =>
+    this.midiOutput.outputCbs.forEach(cbs => cbs.onTuningChange?.(this.tuning));
+
+  /**
+   * Renames or re-routes the lanes of a drum grid.  The number of lanes can't be changed since each
+   * one corresponds to an existing line.
+   */
+  public setDrumLanes(drumLanes: DrumLane[]) {
+    if (!this.drumLanes || drumLanes.length !== this.drumLanes.length) {
+      console.error('Drum lanes can only be updated in place for drum grid instances');
+      return;
+    }
+
+    this.midiOutput.clearAll();
+    this.drumLanes = drumLanes;
+    this.uiInst?.rebuildPianoKeys();
   }
 
   public serialize(isExpanded: boolean): SerializedMIDIEditorInstance {
@@ -179,11 +479,73 @@ export class ManagedMIDIEditorUIInstance {
       lines: this.uiInst?.serializeLines() ?? this.lines,
       name: this.name,
       view: this.view,
+      tuning: this.tuning,
+      playbackHumanize: this.playbackHumanize,
+      track: this.track,
+      drumLanes: this.drumLanes,
+      pitchBend: this.pitchBend ? get(this.pitchBend) : null,
+      legato: this.legato,
+      playbackVariationSeed: this.playbackVariationSeed,
+      keyAndScale: this.keyAndScale,
+      clips: this.clipLauncher.serialize(),
     };
   }
 
+  /**
+   * Returns `false` if this instance is muted or if another instance is soloed and this one isn't
+   */
+  public get isAudible(): boolean {
+    const anySoloed = get(this.manager.instances).some(
+      inst => inst.type === 'midiEditor' && inst.instance.track.soloed
+    );
+    return anySoloed ? this.track.soloed : !this.track.muted;
+  }
+
+  public setTrackSettings(newSettings: Partial<MIDIEditorTrackSettings>) {
+    const oldColor = this.track.color;
+    this.track = { ...this.track, ...newSettings };
+    if (this.track.color !== oldColor) {
+      this.uiInst?.handleViewChange();
+    }
+    // Release any notes held by instances that were just silenced
+    for (const inst of get(this.manager.instances)) {
+      if (inst.type === 'midiEditor' && !inst.instance.isAudible) {
+        inst.instance.midiOutput.clearAll();
+      }
+    }
+  }
+
+  /**
+   * Joins the collaboration room of the saved MIDI composition with ID `midiCompositionID`.  See
+   * `MIDIEditorCollabSession` for details.
+   */
+  public startCollaborating(
+    midiCompositionID: number,
+    loginToken: string,
+    onClose: (reason: string | null) => void
+  ) {
+    this.stopCollaborating();
+    this.collab = new MIDIEditorCollabSession(this, midiCompositionID, loginToken, reason => {
+      this.collab = null;
+      onClose(reason);
+    });
+  }
+
+  public stopCollaborating() {
+    this.collab?.close();
+    this.collab = null;
+  }
+
   public destroy() {
+    this.stopCollaborating();
     this.uiInst?.destroy();
+    this.clipLauncher.destroy();
+    if (this.playbackHumanizerPtr) {
+      this.wasm?.instance.free_humanizer(this.playbackHumanizerPtr);
+    }
+    if (this.playbackVariationPtr) {
+      this.wasm?.instance.free_playback_variation(this.playbackVariationPtr);
+    }
     this.wasm?.instance.free_note_lines(this.wasm.noteLinesCtxPtr);
   }
 }
@@ -260,6 +622,27 @@ export class MIDIEditorUIManager {
     this.instances.set(instances);
   }
 
+  /**
+   * Replaces all notes of the MIDI editor instance with the given ID, such as when recovering them
+   * from an auto-save snapshot
+   */
+  public async restoreInstanceLines(id: string, lines: SerializedMIDILine[]) {
+    const inst = this.getMIDIEditorInstanceByID(id);
+    if (!inst) {
+      return;
+    }
+
+    if (inst.uiInst) {
+      await inst.uiInst.reInitialize({ ...inst.serialize(true), lines });
+      return;
+    }
+
+    inst.lines = lines;
+    await inst.initWasm(lines);
+    const svg = await renderMIDIMinimap(lines, this.parentInst.baseView.timeSignatureMap);
+    this.setMinimapForID(id, svg);
+  }
+
   private setMinimapForID(id: string, svg: SVGSVGElement) {
     const insts = get(this.instances);
     const inst = insts.find(inst => inst.id === id);
@@ -285,10 +668,12 @@ export class MIDIEditorUIManager {
     }
 
     if (inst.type === 'midiEditor' && inst.instance.uiInst) {
+      // Remote edits can't be displayed while the instance is collapsed
+      inst.instance.stopCollaborating();
       inst.instance.lines = inst.instance.uiInst.serializeLines();
       const renderMinimapPromise = renderMIDIMinimap(
         inst.instance.lines,
-        this.parentInst.baseView.beatsPerMeasure
+        this.parentInst.baseView.timeSignatureMap
       );
 
       if (this.activeUIInstance === inst.instance.uiInst) {
@@ -356,7 +741,11 @@ export class MIDIEditorUIManager {
     }
   }
 
-  public addMIDIEditorInstance(defaultActive = true) {
+  /**
+   * Adds a new instance to the end of the list.  If `drumLanes` are provided, it's created as a
+   * drum grid with one line per lane.
+   */
+  public addMIDIEditorInstance(defaultActive = true, drumLanes: DrumLane[] | null = null) {
     const instances = get(this.instances);
     let instName = `midi_${instances.length}`;
     while (
@@ -369,7 +758,7 @@ export class MIDIEditorUIManager {
       instName += '_1';
     }
 
-    const maxMIDINumber = 120;
+    const maxMIDINumber = drumLanes?.length ?? 120;
     const lines: SerializedMIDILine[] = new Array(maxMIDINumber)
       .fill(null)
       .map((_, lineIx) => ({ notes: [], midiNumber: maxMIDINumber - lineIx }));
@@ -379,7 +768,11 @@ export class MIDIEditorUIManager {
       instName,
       { scrollVerticalPx: 0 },
       id,
-      lines
+      lines,
+      undefined,
+      undefined,
+      undefined,
+      drumLanes
     );
     instances.push({ type: 'midiEditor', id, isExpanded: defaultActive, instance });
     this.resizeInstances(instances);
@@ -538,12 +931,21 @@ export class MIDIEditorUIManager {
           inst.state.name,
           inst.state.view,
           crypto.randomUUID(),
-          inst.state.lines
+          inst.state.lines,
+          inst.state.tuning,
+          inst.state.playbackHumanize,
+          inst.state.track,
+          inst.state.drumLanes,
+          inst.state.pitchBend,
+          inst.state.legato,
+          inst.state.playbackVariationSeed,
+          inst.state.keyAndScale,
+          inst.state.clips
         );
 
         if (!inst.state.isExpanded) {
-          renderMIDIMinimap(inst.state.lines, this.parentInst.baseView.beatsPerMeasure).then(svg =>
-            this.setMinimapForID(instance.id, svg)
+          renderMIDIMinimap(inst.state.lines, this.parentInst.baseView.timeSignatureMap).then(
+            svg => this.setMinimapForID(instance.id, svg)
           );
         }
 
@@ -580,12 +982,30 @@ export class MIDIEditorUIManager {
     for (const inst of insts) {
       if (inst.type === 'midiEditor' && inst.isExpanded) {
         inst.instance.uiInst?.handleViewChange();
+        inst.instance.handlePitchBendViewChange();
       } else if (inst.type === 'cvOutput') {
         inst.instance.handleViewChange(this.parentInst.baseView);
       }
     }
   }
 
+  /**
+   * Re-renders measure lines and bar numbers of all instances, including the minimaps of collapsed
+   * ones, after the time signature has changed
+   */
+  public handleTimeSignatureChange() {
+    this.updateAllViews();
+    for (const inst of get(this.instances)) {
+      if (inst.type !== 'midiEditor' || inst.isExpanded) {
+        continue;
+      }
+
+      renderMIDIMinimap(inst.instance.lines, this.parentInst.baseView.timeSignatureMap).then(svg =>
+        this.setMinimapForID(inst.id, svg)
+      );
+    }
+  }
+
   public stopAllPlayback() {
     const insts = get(this.instances);
     for (const inst of insts) {
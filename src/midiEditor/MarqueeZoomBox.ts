@@ -0,0 +1,65 @@
+import * as PIXI from 'src/controls/pixi';
+import MIDIEditorUIInstance from 'src/midiEditor/MIDIEditorUIInstance';
+import * as conf from './conf';
+
+/**
+ * Box drawn by alt-dragging over the grid.  On release, the view is zoomed and scrolled so that the
+ * region inside of the box fills the editor horizontally and starts at the top of it vertically.
+ */
+export default class MarqueeZoomBox {
+  private app: MIDIEditorUIInstance;
+  private graphics: PIXI.Graphics;
+  private startPoint: PIXI.Point;
+  private endPoint: PIXI.Point;
+
+  constructor(app: MIDIEditorUIInstance, startPoint: PIXI.Point) {
+    this.app = app;
+    this.startPoint = startPoint;
+    this.endPoint = startPoint;
+    this.graphics = new PIXI.Graphics();
+    this.app.linesContainer.addChild(this.graphics);
+    this.update(startPoint);
+  }
+
+  public update(newEndPoint: PIXI.Point) {
+    this.endPoint = newEndPoint;
+    this.graphics.clear();
+    const minX = Math.min(this.startPoint.x, this.endPoint.x);
+    const maxX = Math.max(this.startPoint.x, this.endPoint.x);
+    const minY = Math.min(this.startPoint.y, this.endPoint.y);
+    const maxY = Math.max(this.startPoint.y, this.endPoint.y);
+    this.graphics.lineStyle(1, conf.MARQUEE_ZOOM_BOX_COLOR);
+    this.graphics.beginFill(conf.MARQUEE_ZOOM_BOX_COLOR, 0.15);
+    this.graphics.drawRect(minX, minY, maxX - minX, maxY - minY);
+    this.graphics.endFill();
+  }
+
+  /**
+   * Zooms to the region inside of the box.  Boxes narrower than `MARQUEE_ZOOM_MIN_WIDTH_PX` are
+   * assumed to be accidental and ignored.
+   */
+  public apply() {
+    const minX = Math.max(Math.min(this.startPoint.x, this.endPoint.x), 0);
+    const maxX = Math.max(this.startPoint.x, this.endPoint.x);
+    if (maxX - minX < conf.MARQUEE_ZOOM_MIN_WIDTH_PX) {
+      return;
+    }
+
+    const { parentInstance } = this.app;
+    const startBeat = this.app.pxToBeats(minX) + parentInstance.baseView.scrollHorizontalBeats;
+    const endBeat = this.app.pxToBeats(maxX) + parentInstance.baseView.scrollHorizontalBeats;
+    const topLineIx = this.app.computeLineIndex(Math.min(this.startPoint.y, this.endPoint.y));
+
+    this.app.view.scrollVerticalPx = Math.max(topLineIx, 0) * conf.LINE_HEIGHT;
+    parentInstance.baseView.scrollHorizontalBeats = startBeat;
+    // Updates all views, clamping vertical scroll and culling notes that are now out of view
+    parentInstance.setPxPerBeat(
+      (this.app.width - conf.PIANO_KEYBOARD_WIDTH) / (endBeat - startBeat)
+    );
+  }
+
+  public destroy() {
+    this.app.linesContainer.removeChild(this.graphics);
+    this.graphics.destroy();
+  }
+}
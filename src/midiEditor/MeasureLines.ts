@@ -0,0 +1,91 @@
+import * as PIXI from 'src/controls/pixi';
+import type MIDIEditorUIInstance from 'src/midiEditor/MIDIEditorUIInstance';
+import * as conf from './conf';
+
+/**
+ * Lines at the start of each measure drawn through every note line, along with the number of each
+ * measure in the cursor gutter.  Measures are laid out with the time signature map of the MIDI
+ * editor, so they can change length partway through.
+ *
+ * The lines are drawn once per view change into a geometry that's shared by all note lines.
+ */
+export default class MeasureLines {
+  private app: MIDIEditorUIInstance;
+  private lineGraphics: PIXI.Graphics;
+  private barNumbers: PIXI.Container;
+  private barNumberTexts: PIXI.Text[] = [];
+
+  constructor(app: MIDIEditorUIInstance) {
+    this.app = app;
+    this.lineGraphics = new PIXI.Graphics();
+    this.barNumbers = new PIXI.Container();
+    this.barNumbers.x = conf.PIANO_KEYBOARD_WIDTH;
+    this.barNumbers.interactiveChildren = false;
+    this.app.app.stage.addChild(this.barNumbers);
+    this.handleViewChange();
+  }
+
+  /**
+   * Builds graphics for a single note line that are kept up to date along with all the others
+   */
+  public buildLineGraphics(): PIXI.Graphics {
+    return new PIXI.Graphics(this.lineGraphics.geometry);
+  }
+
+  /**
+   * Moves the bar numbers back on top of the cursor gutter after it's been re-created
+   */
+  public bringToFront() {
+    this.app.app.stage.addChild(this.barNumbers);
+  }
+
+  private getBarNumberText(ix: number): PIXI.Text {
+    if (!this.barNumberTexts[ix]) {
+      const text = new PIXI.Text('', {
+        fontSize: conf.BAR_NUMBER_FONT_SIZE,
+        fill: conf.BAR_NUMBER_COLOR,
+      });
+      text.y = conf.CURSOR_GUTTER_HEIGHT - conf.BAR_NUMBER_FONT_SIZE - 4;
+      this.barNumbers.addChild(text);
+      this.barNumberTexts[ix] = text;
+    }
+    return this.barNumberTexts[ix];
+  }
+
+  public handleViewChange() {
+    const { scrollHorizontalBeats, timeSignatureMap } = this.app.parentInstance.baseView;
+    const endBeat =
+      scrollHorizontalBeats + this.app.pxToBeats(this.app.width - conf.PIANO_KEYBOARD_WIDTH);
+
+    this.lineGraphics.clear();
+    this.lineGraphics.lineStyle(1, conf.MEASURE_LINE_COLOR);
+    let textCount = 0;
+    let lastTextX = -Infinity;
+    for (const { beat, bar } of timeSignatureMap.getBarLines(scrollHorizontalBeats, endBeat)) {
+      const x = Math.round(this.app.beatsToPx(beat - scrollHorizontalBeats));
+      this.lineGraphics.moveTo(x, 0);
+      this.lineGraphics.lineTo(x, conf.LINE_HEIGHT);
+
+      if (x - lastTextX < conf.BAR_NUMBER_MIN_SPACING_PX) {
+        continue;
+      }
+      lastTextX = x;
+      // Bar numbers are displayed one-indexed
+      const text = this.getBarNumberText(textCount);
+      text.text = `${bar + 1}`;
+      text.x = x + 3;
+      text.visible = true;
+      textCount += 1;
+    }
+
+    for (let i = textCount; i < this.barNumberTexts.length; i++) {
+      this.barNumberTexts[i].visible = false;
+    }
+  }
+
+  public destroy() {
+    this.app.app.stage.removeChild(this.barNumbers);
+    this.barNumbers.destroy({ children: true });
+    this.lineGraphics.destroy();
+  }
+}
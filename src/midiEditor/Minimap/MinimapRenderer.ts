@@ -2,6 +2,7 @@ import * as Comlink from 'comlink';
 
 import { SerializedMIDILine } from 'src/midiEditor';
 import { MIDIMinimapRendererWorker } from 'src/midiEditor/Minimap/MinimapRenderer.worker';
+import type { TimeSignatureMap } from 'src/midiEditor/timeSignature';
 import { logError } from 'src/sentry';
 import { AsyncOnce } from 'src/util';
 
@@ -67,11 +68,19 @@ const encodeMIDINotes = (lines: SerializedMIDILine[]): ArrayBuffer => {
  */
 export const renderMIDIMinimap = async (
   lines: SerializedMIDILine[],
-  beatsPerMeasure: number
+  timeSignatureMap: TimeSignatureMap
 ): Promise<SVGSVGElement> => {
   const worker = await getWorker();
   const encodedNotes = encodeMIDINotes(lines);
-  const svgText = await worker.renderMinimap(encodedNotes, beatsPerMeasure);
+  const endBeat = lines.reduce(
+    (acc, line) =>
+      line.notes.reduce((acc, note) => Math.max(acc, note.startPoint + note.length), acc),
+    0
+  );
+  const measureLineBeats = new Float32Array(
+    timeSignatureMap.getBarLines(0, endBeat + 1).map(({ beat }) => beat)
+  );
+  const svgText = await worker.renderMinimap(encodedNotes, measureLineBeats);
   const parser = new DOMParser();
   const svgDoc = parser.parseFromString(svgText, 'image/svg+xml');
   const svg = svgDoc.documentElement as unknown as SVGSVGElement;
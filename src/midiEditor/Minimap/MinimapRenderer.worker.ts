@@ -26,9 +26,12 @@ export class MIDIMinimapRendererWorker {
     this.setWasmInstance(wasmInstance);
   };
 
+  /**
+   * @param measureLineBeats beats at which to draw measure lines
+   */
   public renderMinimap = async (
     encodedNotes: ArrayBuffer,
-    beatsPerMeasure: number
+    measureLineBeats: Float32Array
   ): Promise<string> => {
     const wasm = await this.wasmInstance;
 
@@ -42,7 +45,15 @@ export class MIDIMinimapRendererWorker {
     );
     encodedNotesBuf.set(new Uint8Array(encodedNotes));
 
-    const minimapSVGStringPtr = (wasm.exports.midi_minimap_render_minimap as any)(beatsPerMeasure);
+    const measureLinesBufPtr: number = (wasm.exports.get_measure_lines_buf_ptr as any)(
+      measureLineBeats.length
+    );
+    memory = wasm.exports.memory as WebAssembly.Memory;
+    new Float32Array(memory.buffer, measureLinesBufPtr, measureLineBeats.length).set(
+      measureLineBeats
+    );
+
+    const minimapSVGStringPtr = (wasm.exports.midi_minimap_render_minimap as any)();
     const svgTextLength = (wasm.exports.midi_minimap_get_svg_text_length as any)();
     memory = wasm.exports.memory as WebAssembly.Memory;
     const svgText = this.textDecoder.decode(
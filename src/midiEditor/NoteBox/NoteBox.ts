@@ -61,7 +61,9 @@ export class NoteBox {
 
     this.graphics.clear();
     this.graphics.lineStyle(1, 0x333333);
-    this.graphics.beginFill(this.isSelected ? conf.NOTE_SELECTED_COLOR : conf.NOTE_COLOR);
+    this.graphics.beginFill(
+      this.isSelected ? conf.NOTE_SELECTED_COLOR : this.line.app.managedInst.track.color
+    );
     this.graphics.drawRect(1, 0, widthPx, conf.LINE_HEIGHT - 1);
     this.graphics.endFill();
     this.graphics.x = startPointPx;
@@ -80,6 +82,7 @@ export class NoteBox {
       newDesiredStartPos
     );
     this.render();
+    this.line.app.recordCollabEdit(this);
   }
 
   public setIsSelected(isSelected: boolean) {
@@ -2,6 +2,7 @@ import { UnreachableException } from 'ameo-utils';
 import * as R from 'ramda';
 
 import * as PIXI from 'src/controls/pixi';
+import { emitLessonEvent } from 'src/lessons/lessonHooks';
 import MIDIEditorUIInstance, { type Note } from 'src/midiEditor/MIDIEditorUIInstance';
 import MIDINoteBox from 'src/midiEditor/NoteBox/MIDINoteBox';
 import { NoteBox } from 'src/midiEditor/NoteBox/NoteBox';
@@ -18,7 +19,8 @@ export interface NoteCreationState {
 }
 
 /**
- * A cache for storing line marker sprites keyed by `pxPerBeat`
+ * A cache for storing line marker sprites keyed by `pxPerBeat`.  Markers are drawn every beat, so
+ * they're the same for every time signature; measure lines are drawn separately by `MeasureLines`.
  */
 const MarkersCache: Map<number, PIXI.Graphics> = new Map();
 
@@ -29,6 +31,12 @@ export default class NoteLine {
   public background: PIXI.DisplayObject;
   public index: number;
   private graphics: PIXI.Graphics | undefined;
+  private measureLines: PIXI.Graphics;
+  /**
+   * Drawn under the first row of each octave so that octaves can be told apart in tunings other
+   * than 12-TET where the piano key colors don't repeat in a familiar pattern
+   */
+  private octaveBorder: PIXI.Graphics | null = null;
   private noteCreationState: NoteCreationState | null = null;
   private isCulled = true;
   /**
@@ -50,6 +58,9 @@ export default class NoteLine {
     this.background.hitArea = new PIXI.Rectangle(0, 0, this.app.width, conf.LINE_HEIGHT);
     this.background.interactive = true;
     this.container.addChild(this.background);
+    this.measureLines = this.app.measureLines.buildLineGraphics();
+    this.container.addChild(this.measureLines);
+    this.renderOctaveBorder();
     this.container.width = this.app.width;
     this.container.y = index * conf.LINE_HEIGHT - this.app.managedInst.view.scrollVerticalPx;
 
@@ -67,7 +78,12 @@ export default class NoteLine {
   private installNoteCreationHandlers() {
     this.background
       .on('pointerdown', (evt: any) => {
-        if (evt.data.button !== 0 || this.app.selectionBoxButtonDown) {
+        // Alt-dragging draws a marquee zoom box rather than a note
+        if (
+          evt.data.button !== 0 ||
+          this.app.selectionBoxButtonDown ||
+          (evt.data.originalEvent as MouseEvent).altKey
+        ) {
           return;
         }
 
@@ -88,6 +104,12 @@ export default class NoteLine {
         }
 
         this.app.deselectAllNotes();
+        if (this.app.managedInst.drumLanes) {
+          this.placeDrumHit(posBeats);
+          this.app.addMouseUpCB(() => this.app.ungate(this.index));
+          return;
+        }
+
         this.noteCreationState = {
           originalPosBeats: posBeats,
           startPositionBeats: posBeats,
@@ -96,6 +118,17 @@ export default class NoteLine {
         };
 
         this.app.addMouseUpCB(() => {
+          if (this.noteCreationState && !R.isNil(this.noteCreationState.id)) {
+            const { startPositionBeats, endPositionBeats } = this.noteCreationState;
+            emitLessonEvent({
+              type: 'noteDrawn',
+              vcId: this.app.parentInstance.vcId,
+              instanceID: this.app.managedInst.id,
+              lineIx: this.index,
+              startPoint: startPositionBeats,
+              length: endPositionBeats - startPositionBeats,
+            });
+          }
           this.noteCreationState = null;
           this.app.ungate(this.index);
         });
@@ -160,6 +193,33 @@ export default class NoteLine {
       });
   }
 
+  /**
+   * Drum hits are placed with a single click rather than drawn out, spanning one step of the snap
+   * interval.
+   */
+  private placeDrumHit(posBeats: number) {
+    const length = this.app.parentInstance.beatSnapInterval || conf.DRUM_HIT_FALLBACK_LENGTH_BEATS;
+    const canAdd = this.app.wasm!.instance.check_can_add_note(
+      this.app.wasm!.noteLinesCtxPtr,
+      this.index,
+      posBeats,
+      length
+    );
+    if (!canAdd) {
+      return;
+    }
+
+    this.app.addNote(this.index, posBeats, length);
+    emitLessonEvent({
+      type: 'noteDrawn',
+      vcId: this.app.parentInstance.vcId,
+      instanceID: this.app.managedInst.id,
+      lineIx: this.index,
+      startPoint: posBeats,
+      length,
+    });
+  }
+
   public handleViewChange() {
     const newY = Math.max(
       -conf.LINE_HEIGHT,
@@ -197,19 +257,11 @@ export default class NoteLine {
     let beat = 0;
     const visibleBeats = this.app.width / this.app.parentInstance.baseView.pxPerBeat;
     const endBeat = Math.floor(visibleBeats) + 20;
+    g.lineStyle(0.8, conf.NOTE_MARK_COLOR);
     while (beat <= endBeat) {
-      const isMeasureLine = beat % this.app.parentInstance.baseView.beatsPerMeasure === 0;
-      let x = this.app.beatsToPx(beat);
-      if (isMeasureLine) {
-        x = Math.round(x);
-        g.lineStyle(1, conf.MEASURE_LINE_COLOR);
-        g.moveTo(x, 0);
-        g.lineTo(x, conf.LINE_HEIGHT - 0);
-      } else {
-        g.lineStyle(0.8, conf.NOTE_MARK_COLOR);
-        g.moveTo(x, conf.LINE_HEIGHT * 0.87);
-        g.lineTo(x, conf.LINE_HEIGHT);
-      }
+      const x = this.app.beatsToPx(beat);
+      g.moveTo(x, conf.LINE_HEIGHT * 0.87);
+      g.lineTo(x, conf.LINE_HEIGHT);
       beat += 1;
     }
 
@@ -232,13 +284,31 @@ export default class NoteLine {
       this.lastPxPerBeat = this.app.parentInstance.baseView.pxPerBeat;
     }
 
-    const xOffsetBeats = -(
-      this.app.parentInstance.baseView.scrollHorizontalBeats %
-      this.app.parentInstance.baseView.beatsPerMeasure
-    );
+    const xOffsetBeats = -(this.app.parentInstance.baseView.scrollHorizontalBeats % 1);
     this.graphics.x = this.app.beatsToPx(xOffsetBeats);
   }
 
+  /**
+   * Must be called when the tuning or the width of the editor changes
+   */
+  public renderOctaveBorder() {
+    if (this.octaveBorder) {
+      this.container.removeChild(this.octaveBorder);
+      this.octaveBorder.destroy();
+      this.octaveBorder = null;
+    }
+    if (!this.app.managedInst.getRowInfo(this.index).isOctaveRoot) {
+      return;
+    }
+
+    this.octaveBorder = new PIXI.Graphics()
+      .lineStyle(1, conf.OCTAVE_BORDER_COLOR)
+      .moveTo(0, conf.LINE_HEIGHT)
+      .lineTo(this.app.width, conf.LINE_HEIGHT);
+    // above the beat markers so that it covers their bottom border, below notes
+    this.container.addChildAt(this.octaveBorder, this.graphics ? 2 : 1);
+  }
+
   public destroy() {
     for (const note of this.notesByID.values()) {
       note.destroy();
@@ -1,6 +1,5 @@
 import * as PIXI from 'src/controls/pixi';
 import type MIDIEditorUIInstance from 'src/midiEditor/MIDIEditorUIInstance';
-import { midiNumberToNoteName } from 'src/midiUtils';
 import * as conf from './conf';
 
 const ActiveNoteMarker = new PIXI.Graphics()
@@ -17,8 +16,8 @@ export default class PianoKeys {
 
   private drawKey(lineIx: number, g: PIXI.Graphics) {
     const baseY = lineIx * conf.LINE_HEIGHT;
-    const noteName = midiNumberToNoteName(this.app.lines.length - lineIx);
-    const isBlackKey = noteName.includes('♭') || noteName.includes('♯');
+    const { isNatural } = this.app.managedInst.getRowInfo(lineIx);
+    const isBlackKey = !isNatural;
 
     g.beginFill(isBlackKey ? conf.BLACK_NOTE_COLOR : conf.WHITE_NOTE_COLOR);
     g.drawRect(0, baseY, conf.PIANO_KEYBOARD_WIDTH, conf.LINE_HEIGHT);
@@ -35,10 +34,10 @@ export default class PianoKeys {
 
   private drawLabel(lineIx: number, g: PIXI.Graphics) {
     const baseY = lineIx * conf.LINE_HEIGHT;
-    const noteName = midiNumberToNoteName(this.app.lines.length - lineIx);
-    const isBlackKey = noteName.includes('♭') || noteName.includes('♯');
+    const { label, isNatural } = this.app.managedInst.getRowInfo(lineIx);
+    const isBlackKey = !isNatural;
 
-    const text = new PIXI.Text(noteName, {
+    const text = new PIXI.Text(label, {
       fontFamily: 'PT Sans',
       fontSize: 13,
       fill: isBlackKey ? conf.WHITE_NOTE_COLOR : conf.BLACK_NOTE_COLOR,
@@ -0,0 +1,138 @@
+import type { AdsrStep, RampFn } from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
+
+/**
+ * Pitch bend curves are sampled at this interval during playback.  Consecutive samples with the
+ * same value are skipped, so flat sections of the curve don't produce any events.
+ */
+const PITCH_BEND_SAMPLE_INTERVAL_BEATS = 1 / 32;
+
+export interface PitchBendBreakpoint {
+  beat: number;
+  semitones: number;
+  /**
+   * Shape of the curve from the previous breakpoint to this one
+   */
+  ramper: RampFn;
+}
+
+export interface PitchBendLaneState {
+  /**
+   * Sorted by beat.  The bend holds the value of the first breakpoint before it and the value of
+   * the last breakpoint after it.
+   */
+  breakpoints: PitchBendBreakpoint[];
+  /**
+   * The lane can be drawn between `-rangeSemitones` and `rangeSemitones`
+   */
+  rangeSemitones: number;
+  isExpanded: boolean;
+}
+
+export interface PitchBendEvent {
+  beat: number;
+  semitones: number;
+}
+
+export const buildDefaultPitchBendLaneState = (): PitchBendLaneState => ({
+  breakpoints: [
+    { beat: 0, semitones: 0, ramper: { type: 'linear' } },
+    { beat: 4, semitones: 0, ramper: { type: 'linear' } },
+  ],
+  rangeSemitones: 12,
+  isExpanded: true,
+});
+
+/**
+ * Mirrors `compute_pos` in the `adsr` crate
+ */
+export const computePitchBendAtBeat = (
+  breakpoints: PitchBendBreakpoint[],
+  beat: number
+): number => {
+  if (breakpoints.length === 0) {
+    return 0;
+  }
+
+  const nextIx = breakpoints.findIndex(bp => bp.beat > beat);
+  if (nextIx === 0) {
+    return breakpoints[0].semitones;
+  } else if (nextIx === -1) {
+    return breakpoints[breakpoints.length - 1].semitones;
+  }
+
+  const prev = breakpoints[nextIx - 1];
+  const next = breakpoints[nextIx];
+  const pct = (beat - prev.beat) / (next.beat - prev.beat);
+  const diff = next.semitones - prev.semitones;
+  switch (next.ramper.type) {
+    case 'instant':
+      return prev.semitones;
+    case 'linear':
+      return prev.semitones + pct * diff;
+    case 'exponential':
+      return prev.semitones + Math.pow(pct, next.ramper.exponent) * diff;
+  }
+};
+
+/**
+ * Samples the pitch bend curve into events from `startBeat` up to `endBeat` or the last breakpoint
+ * if it's not set.  Returned beats are relative to `startBeat`, and the first event is always at 0
+ * so that the bend is set correctly when playback starts partway through the curve.
+ */
+export const samplePitchBendEvents = (
+  breakpoints: PitchBendBreakpoint[],
+  startBeat: number,
+  endBeat: number | null
+): PitchBendEvent[] => {
+  if (breakpoints.length === 0) {
+    return [];
+  }
+
+  const lastBeat = endBeat ?? breakpoints[breakpoints.length - 1].beat;
+  const beats: number[] = [startBeat];
+  for (
+    let beat = startBeat + PITCH_BEND_SAMPLE_INTERVAL_BEATS;
+    beat < lastBeat;
+    beat += PITCH_BEND_SAMPLE_INTERVAL_BEATS
+  ) {
+    beats.push(beat);
+  }
+  // Breakpoints are always sampled exactly so that instant ramps land on the right beat
+  for (const bp of breakpoints) {
+    if (bp.beat > startBeat && bp.beat <= lastBeat) {
+      beats.push(bp.beat);
+    }
+  }
+  beats.sort((a, b) => a - b);
+
+  const events: PitchBendEvent[] = [];
+  for (const beat of beats) {
+    const semitones = computePitchBendAtBeat(breakpoints, beat);
+    if (events.length > 0 && Math.abs(events[events.length - 1].semitones - semitones) < 0.001) {
+      continue;
+    }
+    events.push({ beat: beat - startBeat, semitones });
+  }
+  return events;
+};
+
+/**
+ * Converts breakpoints into steps for the envelope editor used to draw the lane.  Step positions
+ * are in beats and values are normalized to [0, 1] across the lane's range.
+ */
+export const pitchBendBreakpointsToSteps = (state: PitchBendLaneState): AdsrStep[] =>
+  state.breakpoints.map(bp => ({
+    x: bp.beat,
+    y: (bp.semitones + state.rangeSemitones) / (2 * state.rangeSemitones),
+    ramper: bp.ramper,
+  }));
+
+export const stepsToPitchBendBreakpoints = (
+  steps: AdsrStep[],
+  rangeSemitones: number
+): PitchBendBreakpoint[] =>
+  steps.map(step => ({
+    beat: step.x,
+    semitones: step.y * 2 * rangeSemitones - rangeSemitones,
+    ramper: step.ramper,
+  }));
@@ -16,12 +16,22 @@ import {
 import { getGlobalBpm } from 'src/globalMenu';
 import type { MIDIEditorInstance, SerializedMIDIEditorState } from 'src/midiEditor';
 import { ManagedMIDIEditorUIInstance } from 'src/midiEditor/MIDIEditorUIManager';
+import type { PitchBendEvent } from 'src/midiEditor/PitchBendLane/PitchBendLane';
 
 interface SchedulableNoteEvent {
   isAttack: boolean;
   lineIx: number;
+  /**
+   * In [0, 1]
+   */
+  velocity: number;
 }
 
+/**
+ * Note velocities are stored in [0, 1] but MIDI events are sent with velocities in [0, 255]
+ */
+const MAX_MIDI_VELOCITY = 255;
+
 type ScheduleParams =
   | { type: 'globalBeatCounter'; curBeat: number }
   | {
@@ -71,7 +81,7 @@ class RecordingContext {
 
       const startBeat = noteBox.note.startPoint;
       const newLength = curBeat - startBeat;
-      const lineIx = uiInstance.lines.length - midiNumber;
+      const lineIx = noteBox.line.index;
 
       // handle wrapping around when looping
       if (newLength < 0) {
@@ -99,7 +109,10 @@ class RecordingContext {
       return;
     }
 
-    const lineIx = uiInstance.lines.length - midiNumber;
+    const lineIx = this.activeInstance.midiNumberToLineIx(midiNumber);
+    if (lineIx === null) {
+      return;
+    }
     const canAdd = wasm.instance.check_can_add_note(wasm.noteLinesCtxPtr, lineIx, curBeat, 0.001);
     if (!canAdd) {
       return;
@@ -129,7 +142,7 @@ class RecordingContext {
     }
     const startBeat = noteBox.note.startPoint;
     const newLength = curBeat - startBeat;
-    const lineIx = uiInstance.lines.length - midiNumber;
+    const lineIx = noteBox.line.index;
     uiInstance.resizeNoteHorizontalEnd(lineIx, startBeat, noteID, startBeat + newLength);
     this.downNoteIdsByMIDINumber.delete(midiNumber);
   }
@@ -293,14 +306,14 @@ export default class MIDIEditorPlaybackHandler {
     endBeatExclusive: number | null
   ): Map<number, SchedulableNoteEvent[]> {
     const noteEventsByBeat: Map<number, SchedulableNoteEvent[]> = new Map();
-    const cb = (isAttack: boolean, lineIx: number, rawBeat: number) => {
+    const cb = (isAttack: boolean, lineIx: number, rawBeat: number, velocity: number) => {
       const beat = rawBeat - (startBeatInclusive ?? 0);
       let entry = noteEventsByBeat.get(beat);
       if (!entry) {
         entry = [];
         noteEventsByBeat.set(beat, entry);
       }
-      entry.push({ isAttack, lineIx });
+      entry.push({ isAttack, lineIx, velocity });
     };
     inst.iterNotesWithCB(startBeatInclusive, endBeatExclusive, cb);
 
@@ -312,20 +325,24 @@ export default class MIDIEditorPlaybackHandler {
     noteEventsByBeat: Map<number, SchedulableNoteEvent[]>,
     scheduleParams: ScheduleParams
   ) {
-    const lineCount = managedInst.lineCount;
     for (const [beat, entries] of noteEventsByBeat.entries()) {
       let handle: number;
       const cb = () => {
-        entries.forEach(({ isAttack, lineIx }) => {
+        entries.forEach(({ isAttack, lineIx, velocity }) => {
           if (isAttack) {
-            if (scheduleParams.type === 'localTempo') {
-              managedInst.midiInput.onAttack(lineCount - lineIx, 255, true);
+            // Checked when the note plays so that muting and soloing take effect immediately
+            if (scheduleParams.type === 'localTempo' && managedInst.isAudible) {
+              managedInst.midiInput.onAttack(
+                managedInst.lineIxToMIDINumber(lineIx),
+                velocity * MAX_MIDI_VELOCITY,
+                true
+              );
             }
             managedInst.uiInst?.onGated(lineIx);
             this.addHeldLineIndex(managedInst.id, lineIx);
           } else {
             if (scheduleParams.type === 'localTempo') {
-              managedInst.midiInput.onRelease(lineCount - lineIx, 255, true);
+              managedInst.midiInput.onRelease(managedInst.lineIxToMIDINumber(lineIx), 255, true);
             }
             managedInst.uiInst?.onUngated(lineIx);
             this.removeHeldLineIndex(managedInst.id, lineIx);
@@ -336,12 +353,14 @@ export default class MIDIEditorPlaybackHandler {
       };
 
       if (scheduleParams.type === 'globalBeatCounter') {
-        for (const { isAttack, lineIx } of entries) {
-          const midiNumber = lineCount - lineIx;
+        // Events are scheduled ahead of time when following the global beat counter, so changes to
+        // muting and soloing only apply to events scheduled after they're made
+        for (const { isAttack, lineIx, velocity } of managedInst.isAudible ? entries : []) {
+          const midiNumber = managedInst.lineIxToMIDINumber(lineIx);
           managedInst.midiOutput.scheduleEvent(scheduleParams.curBeat + beat, {
             type: isAttack ? MIDIEventType.Attack : MIDIEventType.Release,
             note: midiNumber,
-            velocity: 255,
+            velocity: velocity * MAX_MIDI_VELOCITY,
           });
         }
 
@@ -357,6 +376,37 @@ export default class MIDIEditorPlaybackHandler {
     }
   }
 
+  /**
+   * Pitch bend events are sent through the same path as notes so that they stay in sync with them
+   */
+  private schedulePitchBends(
+    managedInst: ManagedMIDIEditorUIInstance,
+    events: PitchBendEvent[],
+    scheduleParams: ScheduleParams
+  ) {
+    for (const { beat, semitones } of events) {
+      if (scheduleParams.type === 'globalBeatCounter') {
+        if (managedInst.isAudible) {
+          managedInst.midiOutput.scheduleEvent(scheduleParams.curBeat + beat, {
+            type: MIDIEventType.PitchBend,
+            semitones,
+          });
+        }
+        continue;
+      }
+
+      const secondsPerBeat = 60 / scheduleParams.bpm;
+      const time = scheduleParams.startTime + beat * secondsPerBeat;
+      const handle = scheduleEventTimeAbsolute(time, () => {
+        if (managedInst.isAudible) {
+          managedInst.midiInput.onPitchBend(semitones, true);
+        }
+        this.scheduledEventHandles.delete(handle);
+      });
+      this.scheduledEventHandles.add(handle);
+    }
+  }
+
   private cancelAllScheduledNotes() {
     for (const eventHandle of this.scheduledEventHandles.values()) {
       cancelCb(eventHandle);
@@ -370,7 +420,7 @@ export default class MIDIEditorPlaybackHandler {
       }
 
       for (const lineIx of map.values()) {
-        inst.midiInput.onRelease(inst.lineCount - lineIx, 255);
+        inst.midiInput.onRelease(inst.lineIxToMIDINumber(lineIx), 255);
         inst.uiInst?.onUngated(lineIx);
       }
     }
@@ -388,6 +438,8 @@ export default class MIDIEditorPlaybackHandler {
       }
       const notesInRange = this.getNotesInRange(inst.instance, this.lastSetCursorPosBeats, null);
       this.scheduleNotes(inst.instance, notesInRange, scheduleParams);
+      const pitchBends = inst.instance.getPitchBendEvents(this.lastSetCursorPosBeats, null);
+      this.schedulePitchBends(inst.instance, pitchBends, scheduleParams);
     }
   }
 
@@ -449,6 +501,13 @@ export default class MIDIEditorPlaybackHandler {
         } else {
           this.scheduleNotes(instance, notesInRange, newScheduleParams);
         }
+
+        const pitchBendStartBeat =
+          this.lastPlaybackSchedulParams.type === 'localTempo' && loopIx === 0
+            ? this.lastSetCursorPosBeats
+            : 0;
+        const pitchBends = instance.getPitchBendEvents(pitchBendStartBeat, loopPoint);
+        this.schedulePitchBends(instance, pitchBends, newScheduleParams);
       }
 
       // Schedule an event before the loop ends to recursively schedule another.
@@ -546,6 +605,9 @@ export default class MIDIEditorPlaybackHandler {
     for (const inst of get(this.inst.uiManager.instances)) {
       if (inst.type === 'cvOutput') {
         inst.instance.startPlayback();
+      } else if (inst.type === 'midiEditor') {
+        inst.instance.resetPlaybackHumanizer();
+        inst.instance.resetPlaybackVariation();
       }
     }
 
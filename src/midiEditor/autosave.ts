@@ -0,0 +1,187 @@
+import Dexie from 'dexie';
+import { get } from 'svelte/store';
+
+import type { MIDIEditorInstance, SerializedMIDILine, SerializedMIDINote } from 'src/midiEditor';
+import { NoteContainerWasm } from 'src/midiEditor/MIDIEditorUIManager';
+
+const AUTOSAVE_INTERVAL_MS = 30 * 1000;
+/**
+ * Number of elements per note returned by `decode_note_lines_snapshot`
+ */
+const DECODED_NOTE_SIZE = 7;
+
+interface AutosaveSnapshot {
+  savedAt: number;
+  /**
+   * Binary snapshots of the notes of each MIDI editor instance, created with `snapshot_note_lines`
+   */
+  instances: { name: string; notes: Uint8Array }[];
+}
+
+const autosaveDbClient = new Dexie('midiEditorAutosave');
+autosaveDbClient.version(1).stores({
+  snapshots: '',
+});
+const snapshotsTable = autosaveDbClient.table<AutosaveSnapshot, string>('snapshots');
+
+const snapshotsEqual = (a: AutosaveSnapshot['instances'], b: AutosaveSnapshot['instances']) =>
+  a.length === b.length &&
+  a.every(
+    (inst, i) =>
+      inst.name === b[i].name &&
+      inst.notes.length === b[i].notes.length &&
+      inst.notes.every((byte, byteIx) => byte === b[i].notes[byteIx])
+  );
+
+export const decodeNoteSnapshot = (
+  wasm: typeof import('src/note_container'),
+  snapshot: Uint8Array
+): SerializedMIDILine[] | null => {
+  const decoded = wasm.decode_note_lines_snapshot(snapshot);
+  if (decoded.length === 0) {
+    return null;
+  }
+
+  const lineCount = decoded[0];
+  const lines: SerializedMIDILine[] = new Array(lineCount)
+    .fill(null)
+    .map((_, lineIx) => ({ midiNumber: lineCount - lineIx, notes: [] }));
+  for (let i = 1; i < decoded.length; i += DECODED_NOTE_SIZE) {
+    const [lineIx, startPoint, length, velocity, probability, roundRobinGroup, articulation] =
+      decoded.subarray(i, i + DECODED_NOTE_SIZE);
+    const note: SerializedMIDINote = { startPoint, length };
+    if (velocity < 1) {
+      note.velocity = velocity;
+    }
+    if (probability < 1) {
+      note.probability = probability;
+    }
+    if (roundRobinGroup >= 0) {
+      note.roundRobinGroup = roundRobinGroup;
+    }
+    if (articulation !== 0) {
+      note.articulation = articulation;
+    }
+    lines[lineIx].notes.push(note);
+  }
+  return lines;
+};
+
+/**
+ * Periodically snapshots the notes of all instances in a MIDI editor to IndexedDB so that changes
+ * made since the editor was last saved can be recovered if the tab crashes before it's saved to
+ * localStorage on close.
+ *
+ * Snapshots are deleted when the editor is saved normally, so a snapshot that's newer than the
+ * saved state when the editor is created is left over from a crash.  The user is asked whether to
+ * restore it before auto-saving starts so that it isn't overwritten in the meantime.
+ */
+export class MIDIEditorAutosaver {
+  private vcId: string;
+  private inst: MIDIEditorInstance;
+  private intervalHandle: number | null = null;
+  private lastSnapshot: AutosaveSnapshot['instances'] = [];
+  private stopped = false;
+
+  /**
+   * @param lastSavedAt time at which the editor's initial state was saved, if known
+   */
+  constructor(vcId: string, inst: MIDIEditorInstance, lastSavedAt: number | undefined) {
+    this.vcId = vcId;
+    this.inst = inst;
+
+    this.maybeRecover(lastSavedAt)
+      .catch(err => console.error('Error recovering MIDI editor auto-save snapshot: ', err))
+      .finally(() => {
+        if (!this.stopped) {
+          // Nothing has changed since the editor was loaded or restored, so there's no need to
+          // save until something does
+          this.lastSnapshot = this.buildSnapshot() ?? [];
+          this.intervalHandle = window.setInterval(this.save, AUTOSAVE_INTERVAL_MS);
+        }
+      });
+  }
+
+  private async maybeRecover(lastSavedAt: number | undefined) {
+    const snapshot = await snapshotsTable.get(this.vcId);
+    if (!snapshot || (lastSavedAt !== undefined && snapshot.savedAt <= lastSavedAt)) {
+      return;
+    }
+
+    const shouldRestore = confirm(
+      `The MIDI editor has unsaved changes from ${new Date(
+        snapshot.savedAt
+      ).toLocaleString()}, possibly left over from a crash.  Restore them?`
+    );
+    if (!shouldRestore || this.stopped) {
+      await snapshotsTable.delete(this.vcId);
+      return;
+    }
+
+    const wasm = await NoteContainerWasm.get();
+    const managedInsts = get(this.inst.uiManager.instances);
+    for (const { name, notes } of snapshot.instances) {
+      const managed = managedInsts.find(
+        inst => inst.type === 'midiEditor' && inst.instance.name === name
+      );
+      const lines = decodeNoteSnapshot(wasm, notes);
+      if (!managed || !lines) {
+        console.warn(`Unable to restore auto-saved notes for MIDI editor instance "${name}"`);
+        continue;
+      }
+
+      await this.inst.uiManager.restoreInstanceLines(managed.id, lines);
+    }
+  }
+
+  /**
+   * Returns `null` if the Wasm for any instance hasn't been loaded yet
+   */
+  private buildSnapshot(): AutosaveSnapshot['instances'] | null {
+    const instances: AutosaveSnapshot['instances'] = [];
+    for (const inst of get(this.inst.uiManager.instances)) {
+      if (inst.type !== 'midiEditor') {
+        continue;
+      }
+      const wasm = inst.instance.wasm;
+      if (!wasm) {
+        return null;
+      }
+
+      instances.push({
+        name: inst.instance.name,
+        notes: wasm.instance.snapshot_note_lines(wasm.noteLinesCtxPtr),
+      });
+    }
+    return instances;
+  }
+
+  private save = async () => {
+    const instances = this.buildSnapshot();
+    if (!instances || this.stopped || snapshotsEqual(instances, this.lastSnapshot)) {
+      return;
+    }
+    this.lastSnapshot = instances;
+
+    try {
+      await snapshotsTable.put({ savedAt: Date.now(), instances }, this.vcId);
+    } catch (err) {
+      console.error('Error auto-saving MIDI editor: ', err);
+    }
+  };
+
+  /**
+   * Stops auto-saving and deletes the snapshot.  Should be called once the editor's state has been
+   * saved normally.
+   */
+  public stop() {
+    this.stopped = true;
+    if (this.intervalHandle !== null) {
+      clearInterval(this.intervalHandle);
+      this.intervalHandle = null;
+    }
+    snapshotsTable
+      .delete(this.vcId)
+      .catch(err => console.error('Error deleting MIDI editor auto-save snapshot: ', err));
+  }
+}
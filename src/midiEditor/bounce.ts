@@ -0,0 +1,255 @@
+import { get } from 'svelte/store';
+
+import FMSynth, { type BounceNoteEvent } from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
+import { getGlobalBpm } from 'src/globalMenu';
+import { getMIDIEditorInstance } from 'src/midiEditor';
+import { getState } from 'src/redux';
+import { SynthDesignerStateByStateKey } from 'src/redux/modules/synthDesigner';
+import { AsyncOnce } from 'src/util';
+
+const WavEncoder = new AsyncOnce(() => import('src/wav_decoder'));
+
+/**
+ * Must match the `format` argument of `encode_wav` in `engine/wav_decoder/src/lib.rs`
+ */
+export enum BounceWAVFormat {
+  Pcm16 = 0,
+  Pcm24 = 1,
+  Float32 = 2,
+}
+
+/**
+ * Must match the `dither` argument of `encode_wav` in `engine/wav_decoder/src/lib.rs`
+ */
+export enum BounceDither {
+  None = 0,
+  /**
+   * TPDF dither with a flat noise spectrum
+   */
+  Flat = 1,
+  /**
+   * TPDF dither with gentle first-order noise shaping
+   */
+  FirstOrder = 2,
+  /**
+   * TPDF dither with aggressive E-weighted noise shaping, best suited to 44.1kHz exports that
+   * aren't close to full scale
+   */
+  EWeighted = 3,
+}
+
+/**
+ * Collects the attack and release events of all notes in a MIDI editor instance, in the same form
+ * that they're played back in.
+ */
+export const collectMIDIEditorBounceEvents = (
+  vcId: string,
+  instanceID: string
+): BounceNoteEvent[] => {
+  const uiManager = getMIDIEditorInstance(vcId)?.uiManager;
+  const managedInst = uiManager?.getMIDIEditorInstanceByID(instanceID);
+  if (!managedInst) {
+    throw new Error(`No MIDI editor instance found with vcId=${vcId}, instanceID=${instanceID}`);
+  }
+
+  // Humanization and variation are re-seeded at the start of playback, so do the same here to
+  // match it
+  managedInst.resetPlaybackHumanizer();
+  managedInst.resetPlaybackVariation();
+  const events: BounceNoteEvent[] = [];
+  managedInst.iterNotesWithCB(null, null, (isAttack, lineIx, beat) =>
+    events.push({ beat, midiNumber: managedInst.lineIxToMIDINumber(lineIx), isAttack })
+  );
+  return events;
+};
+
+/**
+ * Finds the FM synths that the output of a MIDI editor instance is connected to, either directly
+ * or through a synth designer.
+ */
+const getConnectedFMSynths = (vcId: string, instanceName: string): FMSynth[] => {
+  const { patchNetwork, activeViewContexts } = getState().viewContextManager;
+
+  return patchNetwork.connections
+    .filter(([from]) => from.vcId === vcId && from.name === `${instanceName}_out`)
+    .flatMap(([_from, to]): FMSynth[] => {
+      const node = patchNetwork.connectables.get(to.vcId)?.node;
+      if (node instanceof FMSynth) {
+        return [node];
+      }
+
+      const vc = activeViewContexts.find(vc => vc.uuid === to.vcId);
+      if (vc?.name === 'synth_designer') {
+        const reduxInfra = SynthDesignerStateByStateKey.get(`synthDesigner_${to.vcId}`);
+        return reduxInfra?.getState().synthDesigner.synths.map(synth => synth.fmSynth) ?? [];
+      }
+      return [];
+    });
+};
+
+/**
+ * Collects the events of a MIDI editor instance for bouncing, cut off at the loop point if one is
+ * set.  Also returns the beat of the last event so bounces without a loop point end after it.
+ */
+const collectLoopedBounceEvents = (
+  vcId: string,
+  instanceID: string,
+  loopPoint: number | null
+): { events: BounceNoteEvent[]; lastBeat: number } => {
+  let events = collectMIDIEditorBounceEvents(vcId, instanceID);
+  if (loopPoint !== null) {
+    // Notes held past the loop point are released at it
+    events = events
+      .filter(evt => !evt.isAttack || evt.beat < loopPoint)
+      .map(evt => ({ ...evt, beat: Math.min(evt.beat, loopPoint) }));
+  }
+  const lastBeat = events.reduce((acc, evt) => Math.max(acc, evt.beat), 0);
+  return { events, lastBeat };
+};
+
+/**
+ * Renders a MIDI editor instance offline through the FM synth it's connected to, returning the
+ * rendered samples.  The bounce covers all notes in the instance or up to the loop point if one is
+ * set.
+ *
+ * This only captures the FM synth itself; see `FMSynth.bounce`.
+ */
+export const bounceMIDIEditorInstanceFMSynth = async (
+  vcId: string,
+  instanceID: string,
+  {
+    tailSeconds = 2,
+    onProgress,
+  }: { tailSeconds?: number; onProgress?: (progress: number) => void } = {}
+): Promise<Float32Array> => {
+  const inst = getMIDIEditorInstance(vcId);
+  const managedInst = inst?.uiManager.getMIDIEditorInstanceByID(instanceID);
+  if (!inst || !managedInst) {
+    throw new Error(`No MIDI editor instance found with vcId=${vcId}, instanceID=${instanceID}`);
+  }
+
+  const fmSynths = getConnectedFMSynths(vcId, managedInst.name);
+  if (fmSynths.length === 0) {
+    throw new Error('MIDI editor instance must be connected to an FM synth in order to bounce it');
+  } else if (fmSynths.length > 1) {
+    console.warn('MIDI editor instance is connected to multiple FM synths; bouncing the first one');
+  }
+
+  const loopPoint = inst.playbackHandler.getLoopPoint();
+  const { events, lastBeat } = collectLoopedBounceEvents(vcId, instanceID, loopPoint);
+  const endBeat = loopPoint ?? lastBeat;
+
+  return fmSynths[0].bounce({ events, bpm: getGlobalBpm(), endBeat, tailSeconds, onProgress });
+};
+
+/**
+ * Bounces every instance in a MIDI editor that's connected to an FM synth to its own stem,
+ * returning the rendered samples keyed by instance name.  Like `bounceMIDIEditorInstanceFMSynth`,
+ * only the FM synths themselves are captured; instances playing through anything else are skipped.
+ *
+ * Instances that play through the same synth are rendered together in a single pass with each
+ * one's voices going to a separate buffer, and different synths are rendered in parallel.  All
+ * stems cover the same span so that they line up when imported side by side.
+ */
+export const bounceMIDIEditorFMSynthStems = async (
+  vcId: string,
+  {
+    tailSeconds = 2,
+    onProgress,
+  }: { tailSeconds?: number; onProgress?: (progress: number) => void } = {}
+): Promise<Map<string, Float32Array>> => {
+  const inst = getMIDIEditorInstance(vcId);
+  if (!inst) {
+    throw new Error(`No MIDI editor found with vcId=${vcId}`);
+  }
+
+  const loopPoint = inst.playbackHandler.getLoopPoint();
+  const stemsBySynth = new Map<FMSynth, { names: string[]; events: BounceNoteEvent[] }>();
+  let endBeat = loopPoint ?? 0;
+  for (const managedInst of get(inst.uiManager.instances)) {
+    if (managedInst.type !== 'midiEditor') {
+      continue;
+    }
+    const { id, name } = managedInst.instance;
+    const fmSynth = getConnectedFMSynths(vcId, name)[0];
+    if (!fmSynth) {
+      continue;
+    }
+
+    const { events, lastBeat } = collectLoopedBounceEvents(vcId, id, loopPoint);
+    endBeat = Math.max(endBeat, lastBeat);
+    const stems = stemsBySynth.get(fmSynth) ?? { names: [], events: [] };
+    const stemIx = stems.names.length;
+    stems.names.push(name);
+    stems.events.push(...events.map(evt => ({ ...evt, stemIx })));
+    stemsBySynth.set(fmSynth, stems);
+  }
+
+  const progressBySynth = new Map<FMSynth, number>();
+  const reportProgress = (fmSynth: FMSynth, progress: number) => {
+    progressBySynth.set(fmSynth, progress);
+    const totalProgress = [...progressBySynth.values()].reduce((acc, p) => acc + p, 0);
+    onProgress?.(totalProgress / stemsBySynth.size);
+  };
+
+  const bpm = getGlobalBpm();
+  const bounced = new Map<string, Float32Array>();
+  await Promise.all(
+    [...stemsBySynth].map(async ([fmSynth, { names, events }]) => {
+      const stems = await fmSynth.bounceStems({
+        events,
+        stemCount: names.length,
+        bpm,
+        endBeat,
+        tailSeconds,
+        onProgress: progress => reportProgress(fmSynth, progress),
+      });
+      names.forEach((name, stemIx) => bounced.set(name, stems[stemIx]));
+    })
+  );
+  return bounced;
+};
+
+/**
+ * Encodes bounced samples as a mono WAV file.  If `outputSampleRate` differs from the rate the
+ * samples were rendered at, they're converted with the windowed-sinc resampler first so that the
+ * export can match what its target platform requires.  When exporting to PCM, the samples are then
+ * dithered before their bit depth is reduced so that quiet passages and fade-outs aren't truncated
+ * harshly.
+ */
+export const encodeBounceAsWAV = async (
+  samples: Float32Array,
+  sampleRate: number,
+  {
+    format = BounceWAVFormat.Pcm16,
+    dither = BounceDither.Flat,
+    outputSampleRate = sampleRate,
+  }: { format?: BounceWAVFormat; dither?: BounceDither; outputSampleRate?: number } = {}
+): Promise<Uint8Array> => {
+  const wavEncoder = await WavEncoder.get();
+  const resampled =
+    outputSampleRate === sampleRate
+      ? samples
+      : wavEncoder.resample_for_export(samples, 1, sampleRate, outputSampleRate);
+  const encoded = wavEncoder.encode_wav(resampled, 1, outputSampleRate, format, dither);
+  if (encoded.length === 0) {
+    throw new Error(`Error encoding bounce as WAV: ${wavEncoder.get_error_message()}`);
+  }
+  return encoded;
+};
+
+/**
+ * Encodes each of a set of bounced stems as a mono WAV file with the same options as
+ * `encodeBounceAsWAV`, keyed by the same names as the input.
+ */
+export const encodeStemsAsWAV = async (
+  stems: Map<string, Float32Array>,
+  sampleRate: number,
+  opts: Parameters<typeof encodeBounceAsWAV>[2] = {}
+): Promise<Map<string, Uint8Array>> => {
+  const encoded = new Map<string, Uint8Array>();
+  for (const [name, samples] of stems) {
+    encoded.set(name, await encodeBounceAsWAV(samples, sampleRate, opts));
+  }
+  return encoded;
+};
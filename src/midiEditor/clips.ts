@@ -0,0 +1,403 @@
+import { get, writable, type Writable } from 'svelte/store';
+
+import {
+  cancelCb,
+  getCurBeat,
+  getIsGlobalBeatCounterStarted,
+  MIDIEventType,
+  registerGlobalStartCB,
+  registerGlobalStopCB,
+  scheduleEventBeats,
+  unregisterStartCB,
+  unregisterStopCB,
+} from 'src/eventScheduler';
+import type { SerializedMIDILine } from 'src/midiEditor';
+import { decodeNoteSnapshot } from 'src/midiEditor/autosave';
+import {
+  loadNoteLines,
+  NoteContainerWasm,
+  type ManagedMIDIEditorUIInstance,
+} from 'src/midiEditor/MIDIEditorUIManager';
+
+/**
+ * Clip events are pulled from the Wasm and scheduled this many beats at a time.  Launches and stops
+ * take effect no sooner than the end of the window that's already been scheduled.
+ */
+const SCHEDULE_WINDOW_BEATS = 0.5;
+const MAX_MIDI_VELOCITY = 255;
+
+/**
+ * Must match the `FOLLOW_ACTION_*` constants in `engine/note_container/src/clips.rs`
+ */
+export enum FollowAction {
+  /**
+   * Loops until stopped
+   */
+  None = 0,
+  PlayNext = 1,
+  /**
+   * Plays a random clip other than the one that just finished
+   */
+  PlayRandom = 2,
+  Stop = 3,
+}
+
+export interface SerializedClip {
+  name: string;
+  lengthBeats: number;
+  lines: SerializedMIDILine[];
+  /**
+   * Evaluated once the clip has played through `followActionRepeats` times.  Defaults to
+   * `FollowAction.None` if not set.
+   */
+  followAction?: FollowAction;
+  /**
+   * Defaults to 1 if not set
+   */
+  followActionRepeats?: number;
+}
+
+export interface SerializedClipLauncher {
+  /**
+   * Clips are launched and stopped on multiples of this many beats.  0 launches them immediately.
+   */
+  quantizationBeats: number;
+  clips: SerializedClip[];
+}
+
+export const buildDefaultClipLauncherState = (): SerializedClipLauncher => ({
+  quantizationBeats: 4,
+  clips: [],
+});
+
+/**
+ * Must match the `CLIP_STATE_*` constants in `engine/note_container/src/clips.rs`
+ */
+export enum ClipStateType {
+  Stopped = 0,
+  Queued = 1,
+  Playing = 2,
+  Stopping = 3,
+}
+
+export interface ClipStatus {
+  name: string;
+  lengthBeats: number;
+  followAction: FollowAction;
+  followActionRepeats: number;
+  state: ClipStateType;
+  /**
+   * The beat at which the clip launches or stops if it's queued or stopping, or the beat at which
+   * it started playing if it's playing
+   */
+  beat: number;
+}
+
+/**
+ * Holds the clips for a MIDI editor instance: named note patterns that loop while they're playing
+ * and are launched and stopped in time with the global transport, like the clips in a track in
+ * Ableton Live's session view.  At most one clip plays at a time.
+ *
+ * Clip notes live in the Wasm in the same structures as the instance's own notes and are played
+ * through the instance's MIDI output.  See `engine/note_container/src/clips.rs`.
+ */
+export class ClipLauncher {
+  private managedInst: ManagedMIDIEditorUIInstance;
+  /**
+   * The state that was loaded, used until the Wasm is ready
+   */
+  private initialState: SerializedClipLauncher;
+  private wasm: { instance: typeof import('src/note_container'); launcherPtr: number } | null =
+    null;
+  private quantizationBeats: number;
+  /**
+   * Global beat up to which clip events have been scheduled, or `null` if the transport is stopped
+   */
+  private scheduledUntilBeat: number | null = null;
+  private scheduleHandle: number | null = null;
+  private cbs: { start: () => void; stop: () => void };
+  private destroyed = false;
+  /**
+   * The name, length, and current state of each clip, for rendering a launcher UI
+   */
+  public clipStatuses: Writable<ClipStatus[]>;
+
+  constructor(managedInst: ManagedMIDIEditorUIInstance, state: SerializedClipLauncher) {
+    this.managedInst = managedInst;
+    this.initialState = state;
+    this.quantizationBeats = state.quantizationBeats;
+    this.clipStatuses = writable(
+      state.clips.map(({ name, lengthBeats, followAction, followActionRepeats }) => ({
+        name,
+        lengthBeats,
+        followAction: followAction ?? FollowAction.None,
+        followActionRepeats: followActionRepeats ?? 1,
+        state: ClipStateType.Stopped,
+        beat: 0,
+      }))
+    );
+
+    this.cbs = { start: () => this.onGlobalStart(), stop: () => this.onGlobalStop() };
+    registerGlobalStartCB(this.cbs.start);
+    registerGlobalStopCB(this.cbs.stop);
+
+    this.initWasm();
+  }
+
+  private async initWasm() {
+    const instance = await NoteContainerWasm.get();
+    if (this.destroyed) {
+      return;
+    }
+
+    const launcherPtr = instance.create_clip_launcher(
+      this.quantizationBeats,
+      this.managedInst.playbackVariationSeed
+    );
+    for (const clip of this.initialState.clips) {
+      const { name, lengthBeats, lines } = clip;
+      const clipIx = instance.clip_launcher_add_clip(launcherPtr, name, lengthBeats, lines.length);
+      loadNoteLines(instance, instance.clip_launcher_get_clip_notes(launcherPtr, clipIx), lines);
+      instance.clip_launcher_set_follow_action(
+        launcherPtr,
+        clipIx,
+        clip.followAction ?? FollowAction.None,
+        clip.followActionRepeats ?? 1
+      );
+    }
+    this.wasm = { instance, launcherPtr };
+
+    if (getIsGlobalBeatCounterStarted()) {
+      this.onGlobalStart();
+    }
+  }
+
+  /**
+   * Returns the beat from which launches and stops should be quantized.  While the transport is
+   * stopped, clips are queued to start with it.
+   */
+  private getLaunchBeat(): number {
+    if (this.scheduledUntilBeat === null) {
+      return 0;
+    }
+    return Math.max(getCurBeat(), this.scheduledUntilBeat);
+  }
+
+  private updateStatuses() {
+    if (!this.wasm) {
+      return;
+    }
+
+    const { instance, launcherPtr } = this.wasm;
+    const states = instance.clip_launcher_get_states(launcherPtr);
+    this.clipStatuses.update(statuses =>
+      statuses.map((status, clipIx) => ({
+        ...status,
+        state: states[clipIx * 2] as ClipStateType,
+        beat: states[clipIx * 2 + 1],
+      }))
+    );
+  }
+
+  private scheduleWindow = () => {
+    if (!this.wasm || this.scheduledUntilBeat === null) {
+      return;
+    }
+
+    const { instance, launcherPtr } = this.wasm;
+    const startBeat = this.scheduledUntilBeat;
+    const endBeat = startBeat + SCHEDULE_WINDOW_BEATS;
+    // Checked once per window like regular playback, so muting applies to later windows
+    const isAudible = this.managedInst.isAudible;
+    instance.clip_launcher_iter_events_with_cb(
+      launcherPtr,
+      startBeat,
+      endBeat,
+      (_clipIx: number, isAttack: boolean, lineIx: number, beat: number, velocity: number) => {
+        if (!isAudible) {
+          return;
+        }
+        this.managedInst.midiOutput.scheduleEvent(beat, {
+          type: isAttack ? MIDIEventType.Attack : MIDIEventType.Release,
+          note: this.managedInst.lineIxToMIDINumber(lineIx),
+          velocity: velocity * MAX_MIDI_VELOCITY,
+        });
+      }
+    );
+    this.scheduledUntilBeat = endBeat;
+    this.updateStatuses();
+
+    // Keeps one window scheduled ahead of the one that's playing
+    this.scheduleHandle = scheduleEventBeats(startBeat, this.scheduleWindow);
+  };
+
+  private onGlobalStart() {
+    if (!this.wasm) {
+      return;
+    }
+
+    this.scheduledUntilBeat = 0;
+    this.scheduleWindow();
+  }
+
+  private onGlobalStop() {
+    if (this.scheduleHandle !== null) {
+      cancelCb(this.scheduleHandle);
+      this.scheduleHandle = null;
+    }
+    this.scheduledUntilBeat = null;
+    if (!this.wasm) {
+      return;
+    }
+
+    this.wasm.instance.clip_launcher_reset(this.wasm.launcherPtr);
+    this.managedInst.midiOutput.clearAll();
+    this.updateStatuses();
+  }
+
+  /**
+   * Returns the index of the new clip, or `null` if the Wasm isn't loaded yet
+   */
+  public addClip(name: string, lengthBeats: number): number | null {
+    if (!this.wasm) {
+      return null;
+    }
+
+    const { instance, launcherPtr } = this.wasm;
+    const lineCount = this.managedInst.lines.length;
+    const clipIx = instance.clip_launcher_add_clip(launcherPtr, name, lengthBeats, lineCount);
+    this.clipStatuses.update(statuses => [
+      ...statuses,
+      {
+        name,
+        lengthBeats,
+        followAction: FollowAction.None,
+        followActionRepeats: 1,
+        state: ClipStateType.Stopped,
+        beat: 0,
+      },
+    ]);
+    return clipIx;
+  }
+
+  public removeClip(clipIx: number) {
+    if (!this.wasm) {
+      return;
+    }
+
+    const { instance, launcherPtr } = this.wasm;
+    instance.clip_launcher_remove_clip(launcherPtr, clipIx);
+    // Any notes the clip was holding are released rather than left hanging
+    this.managedInst.midiOutput.clearAll();
+    this.clipStatuses.update(statuses => statuses.filter((_, i) => i !== clipIx));
+  }
+
+  /**
+   * Returns a pointer to the clip's note lines, which can be edited with the same Wasm functions as
+   * the instance's own notes.  Only valid until the clip is removed.
+   */
+  public getClipNotesPtr(clipIx: number): number | null {
+    if (!this.wasm) {
+      return null;
+    }
+    return this.wasm.instance.clip_launcher_get_clip_notes(this.wasm.launcherPtr, clipIx);
+  }
+
+  public setClipLength(clipIx: number, lengthBeats: number) {
+    if (!this.wasm) {
+      return;
+    }
+
+    this.wasm.instance.clip_launcher_set_clip_length(this.wasm.launcherPtr, clipIx, lengthBeats);
+    this.clipStatuses.update(statuses =>
+      statuses.map((status, i) => (i === clipIx ? { ...status, lengthBeats } : status))
+    );
+  }
+
+  /**
+   * Sets what the clip does once it's played through `repeats` times, such as launching the next
+   * clip.  Follow actions are evaluated in the Wasm as events are scheduled.
+   */
+  public setFollowAction(clipIx: number, followAction: FollowAction, repeats: number) {
+    if (!this.wasm) {
+      return;
+    }
+
+    const { instance, launcherPtr } = this.wasm;
+    instance.clip_launcher_set_follow_action(launcherPtr, clipIx, followAction, repeats);
+    this.clipStatuses.update(statuses =>
+      statuses.map((status, i) =>
+        i === clipIx ? { ...status, followAction, followActionRepeats: repeats } : status
+      )
+    );
+  }
+
+  public getQuantizationBeats(): number {
+    return this.quantizationBeats;
+  }
+
+  public setQuantizationBeats(quantizationBeats: number) {
+    this.quantizationBeats = quantizationBeats;
+    if (this.wasm) {
+      this.wasm.instance.clip_launcher_set_quantization(this.wasm.launcherPtr, quantizationBeats);
+    }
+  }
+
+  public launch(clipIx: number) {
+    if (!this.wasm) {
+      return;
+    }
+
+    this.wasm.instance.clip_launcher_launch(this.wasm.launcherPtr, clipIx, this.getLaunchBeat());
+    this.updateStatuses();
+  }
+
+  public stop(clipIx: number) {
+    if (!this.wasm) {
+      return;
+    }
+
+    this.wasm.instance.clip_launcher_stop(this.wasm.launcherPtr, clipIx, this.getLaunchBeat());
+    this.updateStatuses();
+  }
+
+  public stopAll() {
+    if (!this.wasm) {
+      return;
+    }
+
+    this.wasm.instance.clip_launcher_stop_all(this.wasm.launcherPtr, this.getLaunchBeat());
+    this.updateStatuses();
+  }
+
+  public serialize(): SerializedClipLauncher {
+    if (!this.wasm) {
+      return this.initialState;
+    }
+
+    const { instance, launcherPtr } = this.wasm;
+    const clips = get(this.clipStatuses).map((status, clipIx): SerializedClip => {
+      const { name, lengthBeats, followAction, followActionRepeats } = status;
+      const notesPtr = instance.clip_launcher_get_clip_notes(launcherPtr, clipIx);
+      const lines = decodeNoteSnapshot(instance, instance.snapshot_note_lines(notesPtr));
+      if (!lines) {
+        throw new Error(`Failed to serialize notes for clip "${name}"`);
+      }
+      return { name, lengthBeats, lines, followAction, followActionRepeats };
+    });
+    return { quantizationBeats: this.quantizationBeats, clips };
+  }
+
+  public destroy() {
+    this.destroyed = true;
+    unregisterStartCB(this.cbs.start);
+    unregisterStopCB(this.cbs.stop);
+    if (this.scheduleHandle !== null) {
+      cancelCb(this.scheduleHandle);
+    }
+    if (this.wasm) {
+      this.wasm.instance.free_clip_launcher(this.wasm.launcherPtr);
+      this.wasm = null;
+    }
+  }
+}
@@ -0,0 +1,248 @@
+import { COLLABORATION_BASE_URL } from 'src/conf';
+import type { ManagedMIDIEditorUIInstance } from 'src/midiEditor/MIDIEditorUIManager';
+
+/**
+ * Local edits are batched for this long before being sent so that dragging a note doesn't send an
+ * operation for every mouse move.  Only the latest operation for each note is sent.
+ */
+const FLUSH_INTERVAL_MS = 50;
+
+/**
+ * Note operations as they're sent over the collaboration WebSocket.  Mirrors `NoteOperation` in
+ * `backend/src/models/collaboration.rs`.
+ */
+interface NoteOperation {
+  noteId: { clientId: string; clock: number };
+  stamp: { clientId: string; clock: number };
+  op:
+    | { type: 'insert' | 'move'; lineIx: number; startBeat: number; length: number }
+    | { type: 'delete' };
+}
+
+type ServerMessage =
+  | { type: 'joined'; clientId: string }
+  | { type: 'sync'; ops: NoteOperation[] }
+  | { type: 'op'; op: NoteOperation }
+  | { type: 'error'; message: string };
+
+/**
+ * Where a note is rendered after being changed by a collaboration operation.  `lineIx` is -1 if
+ * the note was removed or hidden.
+ */
+export interface CollabRenderChange {
+  id: number;
+  lineIx: number;
+  startPoint: number;
+  length: number;
+}
+
+/**
+ * Connects the notes of a MIDI editor instance to the collaboration room of a saved MIDI
+ * composition so that they can be edited by several people at once.  Edits are merged by
+ * `engine/note_container/src/collaboration.rs`.
+ *
+ * The room's notes replace the instance's notes when joining, unless the room is empty in which
+ * case the instance's notes are sent to it.
+ */
+export class MIDIEditorCollabSession {
+  private inst: ManagedMIDIEditorUIInstance;
+  private wasm: NonNullable<ManagedMIDIEditorUIInstance['wasm']>;
+  /**
+   * Created once the server has assigned the client ID that all of our operations must be made with
+   */
+  private sessionPtr: number | null = null;
+  private ws: WebSocket;
+  private flushTimeout: ReturnType<typeof setTimeout> | null = null;
+  /**
+   * Edits aren't recorded until the room's notes have been received since they'd be replaced
+   */
+  private hasSynced = false;
+  /**
+   * Set while the instance's own notes are being replaced by the room's notes
+   */
+  private isReplacingNotes = false;
+  private closed = false;
+  private onClose: (reason: string | null) => void;
+
+  /**
+   * @param onClose called when the connection closes for any reason other than `close` being
+   *        called, with the error sent by the server if there was one
+   */
+  constructor(
+    inst: ManagedMIDIEditorUIInstance,
+    midiCompositionID: number,
+    loginToken: string,
+    onClose: (reason: string | null) => void
+  ) {
+    if (!inst.wasm) {
+      throw new Error('Tried to start collaborating before Wasm initialized');
+    }
+    this.inst = inst;
+    this.wasm = inst.wasm;
+    this.onClose = onClose;
+
+    const token = encodeURIComponent(loginToken);
+    this.ws = new WebSocket(`${COLLABORATION_BASE_URL}/rooms/${midiCompositionID}?token=${token}`);
+    let lastError: string | null = null;
+    this.ws.onmessage = evt => {
+      const msg: ServerMessage = JSON.parse(evt.data);
+      if (msg.type === 'error') {
+        console.error('Collaboration error:', msg.message);
+        lastError = msg.message;
+        return;
+      }
+      if (msg.type === 'joined') {
+        this.sessionPtr = this.wasm.instance.create_collab_session(msg.clientId);
+        return;
+      }
+      this.handleRemoteOps(msg.type === 'sync' ? msg.ops : [msg.op], msg.type === 'sync');
+    };
+    this.ws.onclose = () => {
+      if (!this.closed) {
+        this.close();
+        this.onClose(lastError);
+      }
+    };
+  }
+
+  private get uiInst() {
+    return this.inst.uiInst;
+  }
+
+  private handleRemoteOps(ops: NoteOperation[], isSync: boolean) {
+    const sessionPtr = this.sessionPtr;
+    if (sessionPtr === null) {
+      return;
+    }
+
+    if (isSync && !this.hasSynced) {
+      this.hasSynced = true;
+      if (ops.length === 0) {
+        // We're the first ones here, so everyone else will start with our notes
+        for (const { note, line } of [...(this.uiInst?.allNotesByID.values() ?? [])]) {
+          this.recordEdit(note.id, line.index, note.startPoint, note.length);
+        }
+        return;
+      }
+
+      this.isReplacingNotes = true;
+      for (const id of [...(this.uiInst?.allNotesByID.keys() ?? [])]) {
+        this.uiInst!.deleteNote(id);
+      }
+      this.isReplacingNotes = false;
+    }
+
+    const { instance, noteLinesCtxPtr } = this.wasm;
+    for (const { noteId, stamp, op } of ops) {
+      const placement = op.type === 'delete' ? { lineIx: 0, startBeat: 0, length: 0 } : op;
+      instance.collab_apply_remote_op(
+        sessionPtr,
+        noteLinesCtxPtr,
+        noteId.clientId,
+        noteId.clock,
+        stamp.clientId,
+        stamp.clock,
+        op.type === 'delete',
+        placement.lineIx,
+        placement.startBeat,
+        placement.length
+      );
+    }
+    this.applyRenderChanges(sessionPtr);
+  }
+
+  private applyRenderChanges(sessionPtr: number) {
+    const changes: CollabRenderChange[] = this.wasm.instance
+      .collab_take_render_changes(sessionPtr)
+      .map(([id, lineIx, startPoint, length]: [number, number, number, number]) => ({
+        id,
+        lineIx,
+        startPoint,
+        length,
+      }));
+    this.uiInst?.applyCollabRenderChanges(changes);
+  }
+
+  private flush = () => {
+    this.flushTimeout = null;
+    if (this.closed || this.sessionPtr === null || this.ws.readyState !== WebSocket.OPEN) {
+      return;
+    }
+
+    const pending: [string, number, string, number, string, number, number, number][] =
+      this.wasm.instance.collab_take_pending_ops(this.sessionPtr);
+    for (const [noteClientId, noteClock, clientId, clock, type, ...placement] of pending) {
+      const [lineIx, startBeat, length] = placement;
+      const op: NoteOperation = {
+        noteId: { clientId: noteClientId, clock: noteClock },
+        stamp: { clientId, clock },
+        op:
+          type === 'delete'
+            ? { type }
+            : { type: type as 'insert' | 'move', lineIx, startBeat, length },
+      };
+      this.ws.send(JSON.stringify({ type: 'op', op }));
+    }
+  };
+
+  private record(
+    id: number,
+    isDelete: boolean,
+    lineIx: number,
+    startPoint: number,
+    length: number
+  ) {
+    const sessionPtr = this.sessionPtr;
+    if (this.closed || sessionPtr === null || !this.hasSynced || this.isReplacingNotes) {
+      return;
+    }
+
+    this.wasm.instance.collab_record_local_op(
+      sessionPtr,
+      this.wasm.noteLinesCtxPtr,
+      id,
+      isDelete,
+      lineIx,
+      startPoint,
+      length
+    );
+    // Moving or deleting a note can un-hide or extend notes that it was overlapping
+    this.applyRenderChanges(sessionPtr);
+    if (this.flushTimeout === null) {
+      this.flushTimeout = setTimeout(this.flush, FLUSH_INTERVAL_MS);
+    }
+  }
+
+  /**
+   * Must be called after a note has been created, moved, or resized in the note lines
+   */
+  public recordEdit(id: number, lineIx: number, startPoint: number, length: number) {
+    this.record(id, false, lineIx, startPoint, length);
+  }
+
+  /**
+   * Must be called after a note has been deleted from the note lines
+   */
+  public recordDelete(id: number) {
+    this.record(id, true, 0, 0, 0);
+  }
+
+  /**
+   * Leaves the room.  Edits that haven't been sent yet are sent first.
+   */
+  public close() {
+    if (this.closed) {
+      return;
+    }
+
+    if (this.flushTimeout !== null) {
+      clearTimeout(this.flushTimeout);
+      this.flush();
+    }
+    this.closed = true;
+    this.ws.close();
+    if (this.sessionPtr !== null) {
+      this.wasm.instance.free_collab_session(this.sessionPtr);
+    }
+  }
+}
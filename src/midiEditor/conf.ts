@@ -2,12 +2,30 @@ export const BACKGROUND_COLOR = 0x181818;
 export const LINE_HEIGHT = 16;
 export const NOTE_COLOR = 0x70ff03;
 export const NOTE_SELECTED_COLOR = 0xe309c6;
+/**
+ * Opacity of the preview drawn where notes are being dragged to if they're blocked by other notes
+ */
+export const NOTE_GHOST_ALPHA = 0.35;
 export const MEASURE_LINE_COLOR = 0x606060;
+export const BAR_NUMBER_COLOR = 0xaaaaaa;
+export const BAR_NUMBER_FONT_SIZE = 10;
+/**
+ * Bar numbers closer than this to the previous one are skipped when zoomed out
+ */
+export const BAR_NUMBER_MIN_SPACING_PX = 24;
 export const LINE_BORDER_COLOR = 0x444444;
+export const OCTAVE_BORDER_COLOR = 0x7a7a7a;
 export const NOTE_MARK_COLOR = 0x737373;
 export const SELECTION_BOX_BORDER_COLOR = 0xa0a0a0;
 export const SELECTION_BOX_FILL_COLOR = 0xcacaca;
+export const MARQUEE_ZOOM_BOX_COLOR = 0x2fa7ff;
+export const MARQUEE_ZOOM_MIN_WIDTH_PX = 8;
+export const HIGHLIGHT_REGION_COLOR = 0x2fa7ff;
 export const MIN_DRAWING_NOTE_WIDTH_PX = 6;
+/**
+ * Length of drum hits placed on drum grids when snapping is disabled
+ */
+export const DRUM_HIT_FALLBACK_LENGTH_BEATS = 0.25;
 /**
  * After scrolling `SCROLL_ZOOM_DOUBLE_INTERVAL_PX` pixels, the zoom factor (px per beat) will be either doubled if scrolling
  * up (negative scroll delta) or halved if scrolling down (positive scroll delta)
@@ -22,6 +40,12 @@ export const CURSOR_GUTTER_HEIGHT = 18.5;
 export const CURSOR_GUTTER_COLOR = 0x070707;
 export const LOOP_CURSOR_COLOR = 0xff00ff;
 export const PIANO_KEYBOARD_WIDTH = 79.5;
+/**
+ * When following playback, the view is scrolled once the cursor passes this fraction of the visible
+ * width.  It's then re-positioned `FOLLOW_PLAYBACK_LEAD_FRACTION` of the width from the left edge.
+ */
+export const FOLLOW_PLAYBACK_PAGE_THRESHOLD = 0.9;
+export const FOLLOW_PLAYBACK_LEAD_FRACTION = 0.1;
 export const BLACK_NOTE_COLOR = 0x383838;
 export const WHITE_NOTE_COLOR = 0xe5e5e5;
 export const SAMPLE_EDITOR_LABEL_HEIGHT = 24;
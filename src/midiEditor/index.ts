@@ -3,10 +3,14 @@ import { Option } from 'funfix-core';
 import { Map as ImmMap } from 'immutable';
 import { derived, get, Readable, Writable, writable } from 'svelte/store';
 
+import { MIDIEditorAutosaver } from 'src/midiEditor/autosave';
+import type { SerializedClipLauncher } from 'src/midiEditor/clips';
 import { type SerializedCVOutputState } from 'src/midiEditor/CVOutput/CVOutput';
 import MIDIEditor from 'src/midiEditor/MIDIEditor';
 import { MIDIEditorUIManager } from 'src/midiEditor/MIDIEditorUIManager';
+import type { PitchBendLaneState } from 'src/midiEditor/PitchBendLane/PitchBendLane';
 import MIDIEditorPlaybackHandler from 'src/midiEditor/PlaybackHandler';
+import { TimeSignatureMap, type TimeSignatureChange } from 'src/midiEditor/timeSignature';
 import type { AudioConnectables, ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
 import {
   mkContainerCleanupHelper,
@@ -14,6 +18,7 @@ import {
   mkContainerRenderHelper,
   mkContainerUnhider,
 } from 'src/reactUtils';
+import type { KeyAndScale, Tuning } from 'src/tuning';
 
 interface OldMIDIEditorView {
   /**
@@ -43,6 +48,10 @@ export interface MIDIEditorBaseView {
   pxPerBeat: number;
   scrollHorizontalBeats: number;
   beatsPerMeasure: number;
+  /**
+   * Changes away from the `beatsPerMeasure`/4 time signature that's used from the start
+   */
+  timeSignatureChanges?: TimeSignatureChange[];
 }
 
 export interface MIDIEditorInstanceView {
@@ -52,6 +61,82 @@ export interface MIDIEditorInstanceView {
 export interface SerializedMIDINote {
   startPoint: number;
   length: number;
+  /**
+   * In [0, 1].  Defaults to 1 if not set.
+   */
+  velocity?: number;
+  /**
+   * Chance of the note being played each time it's reached during playback, in [0, 1].  Defaults to
+   * 1 if not set.
+   */
+  probability?: number;
+  /**
+   * Notes in the same round-robin group that start on the same beat are alternate takes of each
+   * other.  Only one of them is played each time that beat is reached, cycling through them from
+   * top to bottom.
+   */
+  roundRobinGroup?: number;
+  /**
+   * Bitmask of `NoteArticulation` flags.  Defaults to 0 if not set.
+   */
+  articulation?: number;
+}
+
+/**
+ * Flags that change how notes are played back.  Must match `ARTICULATION_*` in
+ * `engine/note_container/src/articulation.rs`.
+ */
+export enum NoteArticulation {
+  /**
+   * The note is released halfway through
+   */
+  Staccato = 1 << 0,
+  /**
+   * The note is played with a higher velocity
+   */
+  Accent = 1 << 1,
+  /**
+   * The note is tied to the note starting where it ends, as if legato was enabled for just that
+   * pair of notes
+   */
+  Tie = 1 << 2,
+}
+
+/**
+ * Per-note settings used to vary which notes are played back each time they're reached
+ */
+export interface NoteVariationConfig {
+  probability: number;
+  /**
+   * `null` removes notes from their group
+   */
+  roundRobinGroup: number | null;
+}
+
+export type SelectionFilter =
+  | { type: 'shorterThan'; lengthBeats: number }
+  | { type: 'longerThan'; lengthBeats: number }
+  | {
+      type: 'everyNth';
+      /**
+       * Every `n`th selected note is kept, starting with the first
+       */
+      n: number;
+    };
+
+export interface HumanizeConfig {
+  /**
+   * Max distance in beats that notes are moved in either direction
+   */
+  timingAmountBeats: number;
+  /**
+   * Max change in velocity in either direction as a fraction of full velocity
+   */
+  velocityAmount: number;
+  /**
+   * Humanizing the same notes with the same seed always produces the same result
+   */
+  seed: number;
 }
 
 export interface SerializedMIDILine {
@@ -59,11 +144,95 @@ export interface SerializedMIDILine {
   notes: SerializedMIDINote[];
 }
 
+/**
+ * Settings for one MIDI editor instance when several are used as tracks within one composition
+ */
+export interface MIDIEditorTrackSettings {
+  muted: boolean;
+  /**
+   * If any instance is soloed, only soloed instances are played back
+   */
+  soloed: boolean;
+  /**
+   * Color used to draw notes, as a hex number like `0x70ff03`
+   */
+  color: number;
+}
+
+/**
+ * One row of a drum grid.  Notes on the row trigger `midiNumber`, so lanes can be routed to
+ * different samples or synth voices by mapping that note on whatever the instance's output is
+ * connected to, such as the sample mapping of an FM synth.
+ */
+export interface DrumLane {
+  name: string;
+  midiNumber: number;
+}
+
+/**
+ * General MIDI percussion notes for the common kit pieces, followed by sample slots
+ */
+export const DEFAULT_DRUM_LANES: DrumLane[] = [
+  { name: 'Kick', midiNumber: 36 },
+  { name: 'Snare', midiNumber: 38 },
+  { name: 'Clap', midiNumber: 39 },
+  { name: 'Closed Hat', midiNumber: 42 },
+  { name: 'Open Hat', midiNumber: 46 },
+  { name: 'Low Tom', midiNumber: 45 },
+  { name: 'High Tom', midiNumber: 50 },
+  { name: 'Crash', midiNumber: 49 },
+  { name: 'Ride', midiNumber: 51 },
+  { name: 'Sample 1', midiNumber: 60 },
+  { name: 'Sample 2', midiNumber: 61 },
+  { name: 'Sample 3', midiNumber: 62 },
+  { name: 'Sample 4', midiNumber: 63 },
+];
+
 export interface SerializedMIDIEditorInstance {
   name: string;
   lines: SerializedMIDILine[];
   isExpanded: boolean;
   view: MIDIEditorInstanceView;
+  /**
+   * Defaults to 12-TET if not set
+   */
+  tuning?: Tuning;
+  /**
+   * If set, notes are humanized as they're played back rather than being modified
+   */
+  playbackHumanize?: HumanizeConfig | null;
+  /**
+   * Defaults to unmuted, unsoloed, and the default note color if not set
+   */
+  track?: MIDIEditorTrackSettings;
+  /**
+   * If set, the instance is a drum grid with one row per lane from top to bottom instead of one
+   * row per pitch.  `lines` still store row positions as `midiNumber`; the lanes determine which
+   * notes are actually played.
+   */
+  drumLanes?: DrumLane[] | null;
+  /**
+   * If set, the pitch of all notes played by the instance is bent along this curve during playback
+   */
+  pitchBend?: PitchBendLaneState | null;
+  /**
+   * If set, notes that start right where the previous note on the same line ends are tied to it
+   * during playback instead of being re-triggered.  Defaults to false if not set.
+   */
+  legato?: boolean;
+  /**
+   * Seeds the RNG used to pick which notes are played based on their probability.  Playback is the
+   * same every time it's started with the same seed.  Defaults to 0 if not set.
+   */
+  playbackVariationSeed?: number;
+  /**
+   * Used to transpose notes by scale degrees.  Defaults to C major if not set.
+   */
+  keyAndScale?: KeyAndScale;
+  /**
+   * Clips that can be launched in time with the global transport.  Defaults to no clips if not set.
+   */
+  clips?: SerializedClipLauncher;
 }
 
 export type SerializedMIDIEditorBaseInstance =
@@ -78,8 +247,18 @@ export interface SerializedMIDIEditorState {
   localBPM: number;
   loopPoint: number | null;
   metronomeEnabled: boolean;
+  /**
+   * If true, the view scrolls to keep the cursor visible during playback.  Defaults to false if
+   * not set.
+   */
+  followPlayback?: boolean;
   beatSnapInterval: number;
   cursorPosBeats: number;
+  /**
+   * Time at which the state was saved to localStorage, used to tell whether an auto-save snapshot
+   * is newer than it
+   */
+  savedAt?: number;
 }
 
 const buildDefaultMIDIEditorInstanceState = (): SerializedMIDIEditorInstance => {
@@ -155,9 +334,14 @@ class ProxyMIDIEditorBaseView {
   public readonly pxPerBeatStore: Readable<number>;
   public readonly scrollHorizontalBeatsStore: Readable<number>;
   public readonly inner: MIDIEditorBaseView;
+  /**
+   * Built from `beatsPerMeasure` and `timeSignatureChanges`, and re-built whenever they change
+   */
+  public timeSignatureMap: TimeSignatureMap;
 
   constructor(inner: MIDIEditorBaseView) {
     this.inner = inner;
+    this.timeSignatureMap = new TimeSignatureMap(inner.beatsPerMeasure, inner.timeSignatureChanges);
     this.store = writable(inner);
     this.pxPerBeatStore = derived(this.store, v => v.pxPerBeat);
     this.scrollHorizontalBeatsStore = derived(this.store, v => v.scrollHorizontalBeats);
@@ -187,8 +371,19 @@ class ProxyMIDIEditorBaseView {
 
   public set beatsPerMeasure(val: number) {
     this.inner.beatsPerMeasure = val;
+    this.timeSignatureMap = new TimeSignatureMap(val, this.inner.timeSignatureChanges);
     this.store.update(v => ({ ...v, beatsPerMeasure: val }));
   }
+
+  public get timeSignatureChanges(): readonly TimeSignatureChange[] {
+    return this.inner.timeSignatureChanges ?? [];
+  }
+
+  public set timeSignatureChanges(val: readonly TimeSignatureChange[]) {
+    this.inner.timeSignatureChanges = [...val];
+    this.timeSignatureMap = new TimeSignatureMap(this.inner.beatsPerMeasure, val);
+    this.store.update(v => ({ ...v, timeSignatureChanges: [...val] }));
+  }
 }
 
 export class MIDIEditorInstance {
@@ -196,18 +391,22 @@ export class MIDIEditorInstance {
   public baseView: ProxyMIDIEditorBaseView;
   public localBPM: number;
   public beatSnapInterval: number;
+  public followPlayback: boolean;
   public playbackHandler: MIDIEditorPlaybackHandler;
   public uiManager: MIDIEditorUIManager;
+  public autosaver: MIDIEditorAutosaver;
 
   constructor(ctx: AudioContext, vcId: string, initialState: SerializedMIDIEditorState) {
     this.vcId = vcId;
     this.baseView = new ProxyMIDIEditorBaseView(initialState.view);
     this.localBPM = initialState.localBPM;
     this.beatSnapInterval = initialState.beatSnapInterval;
+    this.followPlayback = initialState.followPlayback ?? false;
 
     this.playbackHandler = new MIDIEditorPlaybackHandler(this, initialState);
 
     this.uiManager = new MIDIEditorUIManager(ctx, this, initialState, vcId);
+    this.autosaver = new MIDIEditorAutosaver(vcId, this, initialState.savedAt);
   }
 
   public serialize(): SerializedMIDIEditorState {
@@ -219,6 +418,7 @@ export class MIDIEditorInstance {
       localBPM: this.localBPM,
       loopPoint: this.playbackHandler.getLoopPoint(),
       metronomeEnabled: this.playbackHandler.metronomeEnabled,
+      followPlayback: this.followPlayback,
       scrollHorizontalBeats: this.baseView.scrollHorizontalBeats,
       version: 2,
       view: this.baseView.inner,
@@ -251,6 +451,19 @@ export class MIDIEditorInstance {
     this.uiManager.updateAllViews();
   }
 
+  public setBeatsPerMeasure(beatsPerMeasure: number) {
+    if (!Number.isFinite(beatsPerMeasure) || beatsPerMeasure < 1) {
+      return;
+    }
+    this.baseView.beatsPerMeasure = beatsPerMeasure;
+    this.uiManager.handleTimeSignatureChange();
+  }
+
+  public setTimeSignatureChanges(changes: readonly TimeSignatureChange[]) {
+    this.baseView.timeSignatureChanges = changes;
+    this.uiManager.handleTimeSignatureChange();
+  }
+
   public setLoopEnabled(enabled: boolean) {
     const loopCurrentlyEnabled = this.playbackHandler.getLoopPoint() !== null;
     if (loopCurrentlyEnabled === enabled) {
@@ -305,6 +518,9 @@ export class MIDIEditorInstance {
 
 const Instances: Map<string, MIDIEditorInstance> = new Map();
 
+export const getMIDIEditorInstance = (vcId: string): MIDIEditorInstance | undefined =>
+  Instances.get(vcId);
+
 const getContainerID = (vcId: string) => `midiEditor_${vcId}`;
 
 export const hide_midi_editor = mkContainerHider(getContainerID);
@@ -360,8 +576,9 @@ export const cleanup_midi_editor = (vcId: string) => {
     );
   }
 
-  const serializedState = JSON.stringify(inst.serialize());
+  const serializedState = JSON.stringify({ ...inst.serialize(), savedAt: Date.now() });
   localStorage.setItem(stateKey, serializedState);
+  inst.autosaver.stop();
 
   Instances.delete(vcId);
   inst.destroy();
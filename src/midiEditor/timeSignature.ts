@@ -0,0 +1,205 @@
+/**
+ * Time signature map used to lay out measures in the MIDI editor.  Beats are always quarter notes,
+ * matching the rest of the MIDI editor; bar/beat/tick positions are expressed in terms of the time
+ * signature in effect at that point.
+ */
+
+export const TICKS_PER_BEAT = 960;
+
+export interface TimeSignatureChange {
+  /**
+   * Zero-indexed bar at which this time signature takes effect
+   */
+  bar: number;
+  numerator: number;
+  denominator: number;
+}
+
+/**
+ * A position in a composition with a one-indexed bar and beat.  Beats are in units of the time
+ * signature's denominator.
+ */
+export interface BarBeatTick {
+  bar: number;
+  beat: number;
+  tick: number;
+}
+
+interface ResolvedTimeSignatureChange extends TimeSignatureChange {
+  /**
+   * Beat at which `bar` starts; derived from the changes before this one
+   */
+  startBeat: number;
+  /**
+   * Length of one beat of this time signature in quarter-note beats
+   */
+  beatUnitBeats: number;
+  beatsPerBar: number;
+}
+
+const resolveChange = (
+  bar: number,
+  startBeat: number,
+  numerator: number,
+  denominator: number
+): ResolvedTimeSignatureChange => {
+  numerator = Math.max(Math.round(numerator), 1);
+  denominator = Math.max(Math.round(denominator), 1);
+  const beatUnitBeats = 4 / denominator;
+  return {
+    bar,
+    startBeat,
+    numerator,
+    denominator,
+    beatUnitBeats,
+    beatsPerBar: numerator * beatUnitBeats,
+  };
+};
+
+export class TimeSignatureMap {
+  private changes: ResolvedTimeSignatureChange[];
+
+  /**
+   * @param beatsPerMeasure numerator of the time signature used until the first change, in quarter
+   *        notes
+   * @param changes don't need to be sorted; later changes at the same bar take precedence
+   */
+  constructor(beatsPerMeasure: number, changes: readonly TimeSignatureChange[] = []) {
+    this.changes = [resolveChange(0, 0, beatsPerMeasure, 4)];
+    const sortedChanges = [...changes].sort((a, b) => a.bar - b.bar);
+    for (const { bar, numerator, denominator } of sortedChanges) {
+      const last = this.changes[this.changes.length - 1];
+      const roundedBar = Math.max(Math.round(bar), 0);
+      if (roundedBar === last.bar) {
+        this.changes[this.changes.length - 1] = resolveChange(
+          last.bar,
+          last.startBeat,
+          numerator,
+          denominator
+        );
+        continue;
+      }
+
+      const startBeat = last.startBeat + (roundedBar - last.bar) * last.beatsPerBar;
+      this.changes.push(resolveChange(roundedBar, startBeat, numerator, denominator));
+    }
+  }
+
+  private changeForBeat(beat: number): ResolvedTimeSignatureChange {
+    let ix = this.changes.length - 1;
+    while (ix > 0 && this.changes[ix].startBeat > beat) {
+      ix -= 1;
+    }
+    return this.changes[ix];
+  }
+
+  private changeForBar(bar: number): ResolvedTimeSignatureChange {
+    let ix = this.changes.length - 1;
+    while (ix > 0 && this.changes[ix].bar > bar) {
+      ix -= 1;
+    }
+    return this.changes[ix];
+  }
+
+  /**
+   * @param bar zero-indexed
+   */
+  public barStartBeat(bar: number): number {
+    const change = this.changeForBar(bar);
+    return change.startBeat + (bar - change.bar) * change.beatsPerBar;
+  }
+
+  /**
+   * Returns the zero-indexed bar containing `beat`
+   */
+  public barAtBeat(beat: number): number {
+    const change = this.changeForBeat(Math.max(beat, 0));
+    return change.bar + Math.floor((Math.max(beat, 0) - change.startBeat) / change.beatsPerBar);
+  }
+
+  /**
+   * Returns the start beat and zero-indexed bar of every bar that starts within
+   * `[startBeat, endBeat)`
+   */
+  public getBarLines(startBeat: number, endBeat: number): { beat: number; bar: number }[] {
+    const barLines: { beat: number; bar: number }[] = [];
+    for (let bar = this.barAtBeat(startBeat); ; bar += 1) {
+      const beat = this.barStartBeat(bar);
+      if (beat >= endBeat) {
+        break;
+      }
+      if (beat >= startBeat) {
+        barLines.push({ beat, bar });
+      }
+    }
+    return barLines;
+  }
+
+  public beatsToBarBeatTick(beats: number): BarBeatTick {
+    beats = Math.max(beats, 0);
+    const change = this.changeForBeat(beats);
+    const bar = this.barAtBeat(beats);
+    const beatUnitsIntoBar = (beats - this.barStartBeat(bar)) / change.beatUnitBeats;
+    const beat = Math.min(Math.floor(beatUnitsIntoBar), change.numerator - 1);
+    const tick = Math.min(
+      Math.floor((beatUnitsIntoBar - beat) * TICKS_PER_BEAT),
+      TICKS_PER_BEAT - 1
+    );
+    return { bar: bar + 1, beat: beat + 1, tick };
+  }
+
+  public barBeatTickToBeats({ bar, beat, tick }: BarBeatTick): number {
+    const change = this.changeForBar(bar - 1);
+    return (
+      this.barStartBeat(bar - 1) + (beat - 1 + tick / TICKS_PER_BEAT) * change.beatUnitBeats
+    );
+  }
+}
+
+export const formatBarBeatTick = ({ bar, beat, tick }: BarBeatTick): string =>
+  `${bar}.${beat}.${tick.toString().padStart(3, '0')}`;
+
+/**
+ * Parses a position formatted like `formatBarBeatTick`.  The beat and tick can be left off.
+ */
+export const parseBarBeatTick = (formatted: string): BarBeatTick | null => {
+  const parts = formatted.trim().split('.');
+  if (parts.length > 3 || parts.some(part => !/^\d+$/.test(part))) {
+    return null;
+  }
+
+  const [bar, beat = 1, tick = 0] = parts.map(part => +part);
+  if (bar < 1 || beat < 1 || tick >= TICKS_PER_BEAT) {
+    return null;
+  }
+  return { bar, beat, tick };
+};
+
+/**
+ * Formats time signature changes as a comma-separated list like `9 3/4, 17 7/8` with one-indexed
+ * bars
+ */
+export const formatTimeSignatureChanges = (changes: readonly TimeSignatureChange[]): string =>
+  changes
+    .map(({ bar, numerator, denominator }) => `${bar + 1} ${numerator}/${denominator}`)
+    .join(', ');
+
+export const parseTimeSignatureChanges = (formatted: string): TimeSignatureChange[] | null => {
+  const changes: TimeSignatureChange[] = [];
+  for (const part of formatted.split(',')) {
+    if (!part.trim()) {
+      continue;
+    }
+
+    const match = part.trim().match(/^(\d+)\s+(\d+)\s*\/\s*(\d+)$/);
+    if (!match) {
+      return null;
+    }
+    const [bar, numerator, denominator] = match.slice(1).map(Number);
+    if (bar < 1 || numerator < 1 || denominator < 1) {
+      return null;
+    }
+    changes.push({ bar: bar - 1, numerator, denominator });
+  }
+  return changes.sort((a, b) => a.bar - b.bar);
+};
@@ -3,9 +3,15 @@ import { Option } from 'funfix-core';
 import * as R from 'ramda';
 
 import { MIDINode, type MIDIAccess } from 'src/patchNetwork/midiNode';
+import { recallSceneForProgram } from 'src/scenes';
 
 export type BulitinMIDIInput = IterableValueOf<MIDIAccess['inputs']>;
 
+/**
+ * Pitch bend wheels bend notes by this many semitones in either direction at their extremes
+ */
+const PITCH_BEND_RANGE_SEMITONES = 2;
+
 /**
  * Processes MIDI events from some hardware MIDI device
  */
@@ -75,7 +81,7 @@ export class MIDIInput {
         this.midiNode?.onRelease(note, velocity),
       (_lsb: number, msb: number) => {
         this.pitchBendNode.offset.value = msb;
-        this.midiNode?.outputCbs.forEach(({ onPitchBend }) => onPitchBend(msb));
+        this.midiNode?.onPitchBend(((msb - 64) / 64) * PITCH_BEND_RANGE_SEMITONES);
       },
       (modWheelValue: number) => {
         this.modWheelNode.offset.value = modWheelValue;
@@ -83,7 +89,11 @@ export class MIDIInput {
       (controlIndex: number, controlValue: number) =>
         this.midiNode?.outputCbs.forEach(({ onGenericControl }) =>
           onGenericControl?.(controlIndex, controlValue)
-        )
+        ),
+      (program: number) => {
+        recallSceneForProgram(program);
+        this.midiNode?.outputCbs.forEach(({ onProgramChange }) => onProgramChange?.(program));
+      }
     );
     this.wasmMidiCtxPtr = ctxPtr;
 
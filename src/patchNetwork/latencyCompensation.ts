@@ -0,0 +1,213 @@
+import { PlaceholderInput } from 'src/controlPanel/PlaceholderInput';
+import type { PatchNetwork } from 'src/patchNetwork/patchNetwork';
+import { getState } from 'src/redux';
+
+/**
+ * Automatic delay compensation for the patch network.  Nodes that delay their output, like
+ * compressors with lookahead, report their latency with `setNodeLatency`.  Whenever latencies or
+ * connections change, audio connections are delayed so that every signal arriving at a node lines
+ * up with the one that took the slowest path there.  Without this, mixing a dry signal with a
+ * processed copy of itself comb filters.
+ *
+ * Connections that are part of a feedback loop are never delayed since there's no single amount
+ * that would line them up.
+ */
+
+/**
+ * Compensation delays are clamped to this
+ */
+const MAX_COMPENSATION_DELAY_SECONDS = 2;
+
+/**
+ * Reported latency of each node in samples, keyed by VC ID.  Nodes without latency aren't stored.
+ */
+const NodeLatencies = new Map<string, number>();
+
+/**
+ * Delays inserted between connected nodes, keyed by source node and then destination node.  While
+ * a delay is in place, the source is connected to the delay rather than to the destination.
+ */
+const CompensationDelays = new Map<AudioNode, Map<AudioNode, DelayNode>>();
+
+interface AudioEdge {
+  src: AudioNode;
+  dst: AudioNode;
+  srcVcId: string;
+  dstVcId: string;
+}
+
+let updateScheduled = false;
+
+/**
+ * Called by nodes whenever their latency changes, including when they're bypassed
+ */
+export const setNodeLatency = (vcId: string, latencySamples: number) => {
+  if ((NodeLatencies.get(vcId) ?? 0) === latencySamples) {
+    return;
+  }
+
+  if (latencySamples > 0) {
+    NodeLatencies.set(vcId, latencySamples);
+  } else {
+    NodeLatencies.delete(vcId);
+  }
+  scheduleLatencyCompensationUpdate();
+};
+
+/**
+ * Re-computes compensation delays for the current patch network after the current Redux action has
+ * been handled.  Multiple calls before then are batched into a single update.
+ */
+export const scheduleLatencyCompensationUpdate = () => {
+  if (updateScheduled) {
+    return;
+  }
+
+  updateScheduled = true;
+  setTimeout(() => {
+    updateScheduled = false;
+    updateLatencyCompensation(getState().viewContextManager.patchNetwork);
+  });
+};
+
+const getAudioEdges = (patchNetwork: PatchNetwork): AudioEdge[] =>
+  patchNetwork.connections.flatMap(([from, to]) => {
+    const src = patchNetwork.connectables.get(from.vcId)?.outputs.get(from.name);
+    const dst = patchNetwork.connectables.get(to.vcId)?.inputs.get(to.name);
+    if (
+      src?.type !== 'customAudio' ||
+      dst?.type !== 'customAudio' ||
+      !(src.node instanceof AudioNode) ||
+      !(dst.node instanceof AudioNode) ||
+      src.node instanceof PlaceholderInput ||
+      dst.node instanceof PlaceholderInput
+    ) {
+      return [];
+    }
+
+    return [{ src: src.node, dst: dst.node, srcVcId: from.vcId, dstVcId: to.vcId }];
+  });
+
+/**
+ * Returns the number of samples that each edge needs to be delayed by.  Nodes are visited in
+ * topological order so that the latency of every path leading into a node is known by the time it's
+ * reached.  Nodes in cycles are never reached, and edges touching them aren't delayed.
+ */
+const computeCompensationSamples = (edges: AudioEdge[]): number[] => {
+  const incomingEdges = new Map<string, AudioEdge[]>();
+  const outgoingEdges = new Map<string, AudioEdge[]>();
+  for (const edge of edges) {
+    incomingEdges.set(edge.dstVcId, [...(incomingEdges.get(edge.dstVcId) ?? []), edge]);
+    outgoingEdges.set(edge.srcVcId, [...(outgoingEdges.get(edge.srcVcId) ?? []), edge]);
+    if (!incomingEdges.has(edge.srcVcId)) {
+      incomingEdges.set(edge.srcVcId, []);
+    }
+  }
+
+  const remainingInputCounts = new Map<string, number>();
+  const ready: string[] = [];
+  for (const [vcId, incoming] of incomingEdges) {
+    remainingInputCounts.set(vcId, incoming.length);
+    if (incoming.length === 0) {
+      ready.push(vcId);
+    }
+  }
+
+  // Latency accumulated by the time signals arrive at each node's inputs and leave its outputs
+  const arrivalLatencies = new Map<string, number>();
+  const outputLatencies = new Map<string, number>();
+  while (ready.length > 0) {
+    const vcId = ready.pop()!;
+    const arrivalLatency = Math.max(
+      0,
+      ...incomingEdges.get(vcId)!.map(edge => outputLatencies.get(edge.srcVcId)!)
+    );
+    arrivalLatencies.set(vcId, arrivalLatency);
+    outputLatencies.set(vcId, arrivalLatency + (NodeLatencies.get(vcId) ?? 0));
+
+    for (const edge of outgoingEdges.get(vcId) ?? []) {
+      const remainingInputCount = remainingInputCounts.get(edge.dstVcId)! - 1;
+      remainingInputCounts.set(edge.dstVcId, remainingInputCount);
+      if (remainingInputCount === 0) {
+        ready.push(edge.dstVcId);
+      }
+    }
+  }
+
+  return edges.map(edge => {
+    const arrivalLatency = arrivalLatencies.get(edge.dstVcId);
+    const outputLatency = outputLatencies.get(edge.srcVcId);
+    if (arrivalLatency === undefined || outputLatency === undefined) {
+      return 0;
+    }
+    return arrivalLatency - outputLatency;
+  });
+};
+
+const takeCompensationDelay = (src: AudioNode, dst: AudioNode): DelayNode | undefined => {
+  const delaysForSrc = CompensationDelays.get(src);
+  const delay = delaysForSrc?.get(dst);
+  if (!delaysForSrc || !delay) {
+    return undefined;
+  }
+
+  delaysForSrc.delete(dst);
+  if (delaysForSrc.size === 0) {
+    CompensationDelays.delete(src);
+  }
+  src.disconnect(delay);
+  delay.disconnect();
+  return delay;
+};
+
+/**
+ * Removes the compensation delay between `src` and `dst` if there is one, leaving them
+ * disconnected.  Returns `true` if a delay was removed.
+ */
+export const disconnectCompensationDelay = (src: AudioNode, dst: AudioNode): boolean =>
+  !!takeCompensationDelay(src, dst);
+
+const setCompensationDelay = (src: AudioNode, dst: AudioNode, delaySamples: number) => {
+  if (delaySamples <= 0) {
+    if (takeCompensationDelay(src, dst)) {
+      src.connect(dst);
+    }
+    return;
+  }
+
+  let delay = CompensationDelays.get(src)?.get(dst);
+  if (!delay) {
+    delay = new DelayNode(src.context, { maxDelayTime: MAX_COMPENSATION_DELAY_SECONDS });
+    src.disconnect(dst);
+    src.connect(delay);
+    delay.connect(dst);
+    CompensationDelays.set(src, (CompensationDelays.get(src) ?? new Map()).set(dst, delay));
+  }
+  delay.delayTime.value = Math.min(
+    delaySamples / src.context.sampleRate,
+    MAX_COMPENSATION_DELAY_SECONDS
+  );
+};
+
+/**
+ * Inserts, updates, and removes compensation delays to match the current connections and node
+ * latencies
+ */
+export const updateLatencyCompensation = (patchNetwork: PatchNetwork) => {
+  const edges = getAudioEdges(patchNetwork);
+  const compensationSamples = computeCompensationSamples(edges);
+
+  // Drop delays for connections that were removed without going through `disconnectNodes`, for
+  // example when one of the nodes was replaced
+  for (const [src, delaysForSrc] of CompensationDelays) {
+    for (const dst of delaysForSrc.keys()) {
+      if (!edges.some(edge => edge.src === src && edge.dst === dst)) {
+        takeCompensationDelay(src, dst);
+      }
+    }
+  }
+
+  edges.forEach((edge, edgeIx) =>
+    setCompensationDelay(edge.src, edge.dst, compensationSamples[edgeIx])
+  );
+};
@@ -11,6 +11,7 @@ import {
   scheduleMIDIEventBeats,
   type EventToReschedule,
 } from 'src/eventScheduler';
+import type { Tuning } from 'src/tuning';
 
 /**
  * The set of functions that must be provided to a MIDI node that accepts input from other MIDI nodes.
@@ -19,9 +20,19 @@ export interface MIDIInputCbs {
   enableRxAudioThreadScheduling?: { mailboxIDs: string[] };
   onAttack: (note: number, velocity: number) => void;
   onRelease: (note: number, velocity: number) => void;
+  /**
+   * `bendAmount` is in semitones and applies to all playing and future notes
+   */
   onPitchBend: (bendAmount: number) => void;
   onClearAll: () => void;
   onGenericControl?: (controlIndex: number, controlValue: number) => void;
+  onProgramChange?: (program: number) => void;
+  /**
+   * Called by sources whose note numbers are degrees of a tuning other than 12-TET, such as MIDI
+   * editors with a microtonal tuning, so that destinations can map them to the right frequencies.
+   * Sources call it again whenever their outputs are re-connected.
+   */
+  onTuningChange?: (tuning: Tuning) => void;
 }
 
 // hilarious
@@ -30,15 +41,24 @@ export type MIDIAccess = PromiseResolveType<ReturnType<(typeof navigator)['reque
 export const mkBuildPasthroughInputCBs = (node: MIDINode) => (): MIDIInputCbs => ({
   onAttack: (note, velocity) => node.onAttack(note, velocity),
   onRelease: (note, velocity) => node.onRelease(note, velocity),
-  onPitchBend: bendAmount => node.outputCbs.forEach(cb => cb.onPitchBend(bendAmount)),
+  onPitchBend: bendAmount => node.onPitchBend(bendAmount),
   onClearAll: () => node.outputCbs.forEach(cbs => cbs.onClearAll()),
   onGenericControl: (controlIndex, controlValue) =>
     node.outputCbs.forEach(cbs => cbs.onGenericControl?.(controlIndex, controlValue)),
+  onProgramChange: program => node.outputCbs.forEach(cbs => cbs.onProgramChange?.(program)),
+  onTuningChange: tuning => node.outputCbs.forEach(cbs => cbs.onTuningChange?.(tuning)),
 });
 
 type MIDIEvent =
   | { type: MIDIEventType.Attack; note: number; velocity: number }
-  | { type: MIDIEventType.Release; note: number; velocity: number };
+  | { type: MIDIEventType.Release; note: number; velocity: number }
+  | { type: MIDIEventType.PitchBend; semitones: number };
+
+/**
+ * Returns the two params that are sent along with the event to audio thread mailboxes
+ */
+const encodeMIDIEventParams = (evt: MIDIEvent): [number, number] =>
+  evt.type === MIDIEventType.PitchBend ? [evt.semitones, 0] : [evt.note, evt.velocity];
 
 /**
  * A `MIDINode` is a special kind of connectable that deals with polyphonic MIDI events.  They are connectable
@@ -175,6 +195,28 @@ export class MIDINode {
     });
   }
 
+  /**
+   * @param semitones Amount to bend the pitch of all playing and future notes by
+   * @param interactiveOnly If set, this event will only be sent to connected outputs that do not have
+   * audio thread scheduling enabled.
+   */
+  public onPitchBend(semitones: number, interactiveOnly = false) {
+    this.outputCbs.forEach(cbs => {
+      if (cbs.enableRxAudioThreadScheduling) {
+        if (interactiveOnly) {
+          return;
+        }
+
+        for (const mailboxID of cbs.enableRxAudioThreadScheduling.mailboxIDs) {
+          postMIDIEventToAudioThread(mailboxID, MIDIEventType.PitchBend, semitones, 0);
+        }
+        return;
+      }
+
+      cbs.onPitchBend(semitones);
+    });
+  }
+
   public clearAll() {
     this.outputCbs.forEach(cbs => {
       if (cbs.enableRxAudioThreadScheduling) {
@@ -211,11 +253,17 @@ export class MIDINode {
 
     if (needsUIThreadScheduling) {
       const cb = () => {
-        const fn = {
-          [MIDIEventType.Attack]: this.onAttack,
-          [MIDIEventType.Release]: this.onRelease,
-        }[evt.type].bind(this);
-        fn(evt.note, evt.velocity, true);
+        switch (evt.type) {
+          case MIDIEventType.Attack:
+            this.onAttack(evt.note, evt.velocity, true);
+            break;
+          case MIDIEventType.Release:
+            this.onRelease(evt.note, evt.velocity, true);
+            break;
+          case MIDIEventType.PitchBend:
+            this.onPitchBend(evt.semitones, true);
+            break;
+        }
       };
 
       cbIDs.push(scheduleEventBeats(beat, cb));
@@ -227,8 +275,9 @@ export class MIDINode {
           continue;
         }
 
+        const [param0, param1] = encodeMIDIEventParams(evt);
         for (const mailboxID of cbs.enableRxAudioThreadScheduling.mailboxIDs) {
-          cbIDs.push(scheduleMIDIEventBeats(beat, mailboxID, evt.type, evt.note, evt.velocity));
+          cbIDs.push(scheduleMIDIEventBeats(beat, mailboxID, evt.type, param0, param1));
         }
       }
     }
@@ -272,6 +321,7 @@ export class MIDINode {
       }
 
       const cbIDs: number[] = [];
+      const [param0, param1] = encodeMIDIEventParams(evt);
 
       if (needsUIThreadScheduling) {
         const cbId = getUniqueCBID();
@@ -281,8 +331,8 @@ export class MIDINode {
           cbId,
           mailboxID: null,
           midiEventType: evt.type,
-          param0: evt.note,
-          param1: evt.velocity,
+          param0,
+          param1,
         });
       }
 
@@ -300,8 +350,8 @@ export class MIDINode {
               cbId,
               mailboxID,
               midiEventType: evt.type,
-              param0: evt.note,
-              param1: evt.velocity,
+              param0,
+              param1,
             });
           }
         }
@@ -6,6 +6,7 @@ import { getLoadedComposition } from 'src/api';
 import type { CompositionDefinition } from 'src/compositionSharing/CompositionSharing';
 import { stopAll } from 'src/eventScheduler/eventScheduler';
 import { setGlobalBpm } from 'src/globalMenu';
+import { emitLessonEvent } from 'src/lessons/lessonHooks';
 import { actionCreators, dispatch, getState } from 'src/redux';
 import { commitForeignConnectables } from 'src/redux/modules/vcmUtils';
 
@@ -57,6 +58,7 @@ export const reinitializeWithComposition = (
 
   // Trigger applicaion to refresh using the newly set `localStorage` content
   engine.init();
+  emitLessonEvent({ type: 'presetLoaded', target: 'composition' });
 
   return Either.right(void 0);
 };
@@ -109,7 +111,7 @@ export const loadSharedComposition = async (
   await currentLoadedCompositionIdTable.add(composition.id, ['']);
 
   const deserialized = JSON.parse(composition.content);
-  const keysToRetain = ['globalVolume'];
+  const keysToRetain = ['globalVolume', 'keymap'];
   const retainedValues = keysToRetain.map(key => [key, localStorage.getItem(key)]);
   if (!retainLocalStorage) {
     localStorage.clear();
@@ -130,6 +132,8 @@ export const loadSharedComposition = async (
     console.log('Setting global tempo to', deserialized.globalTempo);
     setGlobalBpm(+deserialized.globalTempo);
   }
+
+  emitLessonEvent({ type: 'presetLoaded', target: 'composition', presetID: composition.id });
 };
 
 export const maybeRestoreLocalComposition = async () => {
@@ -0,0 +1,88 @@
+/**
+ * Detects audio underruns and forwards them to the AWPs of registered nodes so that the Wasm modules
+ * they host can lower their quality.  See `dsp::quality` in the engine for the other half of this.
+ * AWPs report their own render load to their Wasm instances directly, which is how quality gets
+ * restored.
+ *
+ * Browsers that expose `AudioContext.playbackStats` report underrun events directly.  Everywhere
+ * else, underruns are detected by checking if `currentTime` fell behind the wall clock, which is
+ * what happens when the audio thread doesn't produce output in time.
+ */
+
+const POLL_INTERVAL_MS = 500;
+/**
+ * How far the audio clock needs to fall behind the wall clock in a single poll to be counted as an
+ * underrun.  Leaves some room for jitter in how `currentTime` is updated.
+ */
+const UNDERRUN_DRIFT_THRESHOLD_SECONDS = 0.02;
+
+interface ClockSnapshot {
+  wallTime: number;
+  audioTime: number;
+  underrunEvents: number | undefined;
+}
+
+// Ports are held weakly since not all nodes have a lifecycle hook to unregister from
+const GovernedPorts = new Set<WeakRef<MessagePort>>();
+let GovernedCtx: AudioContext | null = null;
+let PollIntervalHandle: ReturnType<typeof setInterval> | null = null;
+let LastSnapshot: ClockSnapshot | null = null;
+
+const takeSnapshot = (ctx: AudioContext): ClockSnapshot => ({
+  wallTime: performance.now() / 1000,
+  audioTime: ctx.currentTime,
+  underrunEvents: (ctx as any).playbackStats?.underrunEvents,
+});
+
+const detectUnderrun = (prev: ClockSnapshot, cur: ClockSnapshot): boolean => {
+  if (cur.underrunEvents !== undefined && prev.underrunEvents !== undefined) {
+    return cur.underrunEvents > prev.underrunEvents;
+  }
+
+  const drift = cur.wallTime - prev.wallTime - (cur.audioTime - prev.audioTime);
+  return drift > UNDERRUN_DRIFT_THRESHOLD_SECONDS;
+};
+
+const poll = () => {
+  if (!GovernedCtx || GovernedCtx.state !== 'running') {
+    LastSnapshot = null;
+    return;
+  }
+
+  const snapshot = takeSnapshot(GovernedCtx);
+  if (LastSnapshot && detectUnderrun(LastSnapshot, snapshot)) {
+    for (const portRef of GovernedPorts) {
+      const port = portRef.deref();
+      if (port) {
+        port.postMessage({ type: 'reportUnderrun' });
+      } else {
+        GovernedPorts.delete(portRef);
+      }
+    }
+  }
+  LastSnapshot = snapshot;
+};
+
+/**
+ * Starts forwarding underruns to the AWP behind `port`.  The AWP must handle `reportUnderrun`
+ * messages by calling `quality_report_underrun` on its Wasm instance.
+ *
+ * Returns a function that stops forwarding underruns to the port.
+ */
+export const registerQualityGovernedPort = (ctx: AudioContext, port: MessagePort) => {
+  const portRef = new WeakRef(port);
+  GovernedPorts.add(portRef);
+  GovernedCtx = ctx;
+  if (PollIntervalHandle === null) {
+    PollIntervalHandle = setInterval(poll, POLL_INTERVAL_MS);
+  }
+
+  return () => {
+    GovernedPorts.delete(portRef);
+    if (GovernedPorts.size === 0 && PollIntervalHandle !== null) {
+      clearInterval(PollIntervalHandle);
+      PollIntervalHandle = null;
+      LastSnapshot = null;
+    }
+  };
+};
@@ -5,4 +5,8 @@ export interface Effect {
   code: string;
   userId: number | null;
   userName: string | null;
+  /**
+   * The revision that `code` belongs to
+   */
+  revision: number;
 }
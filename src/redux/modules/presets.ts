@@ -3,6 +3,7 @@ import { createSelector } from 'reselect';
 
 import { BACKEND_BASE_URL } from 'src/conf';
 import type { ADSRValues } from 'src/controls/adsr';
+import { getLoginToken } from 'src/persistance';
 import { actionCreators, dispatch, type ReduxStore } from 'src/redux';
 import type { serializeSynthModule } from 'src/redux/modules/synthDesigner';
 
@@ -12,6 +13,15 @@ export interface SynthPresetEntry {
   description: string;
   body: { voices: SynthVoicePreset[] };
   userId: number | null | undefined;
+  userName: string | null | undefined;
+  /**
+   * Private presets are only listed for their owner
+   */
+  isPublic: boolean;
+  /**
+   * ID of the preset this one was forked from, if any
+   */
+  forkedFrom: number | null | undefined;
 }
 
 export type SynthVoicePreset = ReturnType<typeof serializeSynthModule> & {
@@ -25,6 +35,15 @@ export interface SynthVoicePresetEntry {
   description: string;
   body: SynthVoicePreset;
   userId: number | null | undefined;
+  userName: string | null | undefined;
+  /**
+   * Private presets are only listed for their owner
+   */
+  isPublic: boolean;
+  /**
+   * ID of the preset this one was forked from, if any
+   */
+  forkedFrom: number | null | undefined;
 }
 
 export type PresetsState = {
@@ -74,16 +93,22 @@ const fetchWithRetries = async <T>(
 
 export const fetchSynthPresets = async () => {
   dispatch(actionCreators.presets.SET_SYNTH_PRESETS('FETCHING'));
+  const maybeLoginToken = await getLoginToken();
   const presets: SynthPresetEntry[] = await fetchWithRetries(3, () =>
-    fetch(`${BACKEND_BASE_URL}/synth_presets`).then(res => res.json())
+    fetch(`${BACKEND_BASE_URL}/synth_presets`, {
+      headers: { Authorization: maybeLoginToken },
+    }).then(res => res.json())
   );
   dispatch(actionCreators.presets.SET_SYNTH_PRESETS(presets));
 };
 
 export const fetchSynthVoicePresets = async () => {
   dispatch(actionCreators.presets.SET_SYNTH_VOICE_PRESETS('FETCHING'));
+  const maybeLoginToken = await getLoginToken();
   const presets: SynthVoicePresetEntry[] = await fetchWithRetries(3, () =>
-    fetch(`${BACKEND_BASE_URL}/synth_voice_presets`).then(res => res.json())
+    fetch(`${BACKEND_BASE_URL}/synth_voice_presets`, {
+      headers: { Authorization: maybeLoginToken },
+    }).then(res => res.json())
   );
   dispatch(actionCreators.presets.SET_SYNTH_VOICE_PRESETS(presets));
 };
@@ -322,8 +322,9 @@ const buildDefaultSynthModule = (
         if (!getState) {
           throw new Error(`Failed to get state for stateKey=${stateKey}`);
         }
-        const pitchMultiplier = getState().synthDesigner.synths[synthIx].pitchMultiplier;
+        const { pitchMultiplier, masterGain } = getState().synthDesigner.synths[synthIx];
         fmSynth.setFrequencyMultiplier(pitchMultiplier);
+        fmSynth.setMasterGain(masterGain + 1);
 
         connectFMSynth(stateKey, synthIx);
 
@@ -413,6 +414,7 @@ export const deserializeSynthModule = (
       : fmSynthConfig.filterEnvelope,
     onInitialized: () => {
       fmSynth.setFrequencyMultiplier(pitchMultiplier);
+      fmSynth.setMasterGain(masterGain + 1);
 
       connectFMSynth(stateKey, synthIx);
 
@@ -440,7 +442,6 @@ export const deserializeSynthModule = (
   }
 
   const voices = base.voices.map(voice => {
-    voice.outerGainNode.gain.value = masterGain + 1;
     // voice.filterNode.getOutput().connect(voice.outerGainNode);
     Object.entries(filterParams)
       .filter(([k, _v]) => k !== 'type')
@@ -542,6 +543,10 @@ const maybeUpdateMIDINode = (state: SynthDesignerState) => {
           'Should never be called; should be handled by audio thread scheduling'
         );
       },
+      onTuningChange: tuning =>
+        getSynthDesignerReduxInfra(stateKey)
+          .getState()
+          .synthDesigner.synths.forEach(synth => synth.fmSynth.setTuning(tuning)),
     };
   });
 };
@@ -568,6 +573,11 @@ const actionGroups = {
         }),
         false
       );
+      // New voices play in the same tuning as the existing ones, which were set by the MIDI source
+      const tuning = state.synths[0]?.fmSynth.getTuning();
+      if (tuning) {
+        newModule.fmSynth.setTuning(tuning);
+      }
 
       const newState = {
         ...state,
@@ -686,9 +696,8 @@ const actionGroups = {
     subReducer: (state: SynthDesignerState, { synthIx, gain }) => {
       const targetSynth = getSynth(synthIx, state.synths);
       const newTargetSynth: SynthModule = { ...targetSynth, masterGain: gain };
-      newTargetSynth.voices.forEach(voice => {
-        voice.outerGainNode.gain.value = gain + 1;
-      });
+      // Applied inside the synth rather than via the voices' gain nodes so that changes are smoothed
+      newTargetSynth.fmSynth.setMasterGain(gain + 1);
       return setSynth(synthIx, newTargetSynth, state);
     },
   }),
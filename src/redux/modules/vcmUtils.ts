@@ -12,6 +12,10 @@ import type {
   ConnectableOutput,
   PatchNetwork,
 } from 'src/patchNetwork';
+import {
+  disconnectCompensationDelay,
+  scheduleLatencyCompensationUpdate,
+} from 'src/patchNetwork/latencyCompensation';
 import type { MIDINode } from 'src/patchNetwork/midiNode';
 import { reinitializeWithComposition } from 'src/persistance';
 import { getState } from 'src/redux';
@@ -70,6 +74,15 @@ export const disconnectNodes = (
     dst.setIsOverridden(true);
   }
 
+  // Connections that are being delayed for latency compensation are routed through the delay
+  if (
+    src instanceof AudioNode &&
+    dst instanceof AudioNode &&
+    disconnectCompensationDelay(src, dst)
+  ) {
+    return;
+  }
+
   try {
     (src as any).disconnect(dst, src instanceof PlaceholderInput ? dstDescriptor : undefined);
   } catch (err) {
@@ -102,6 +115,10 @@ export const maybeUpdateVCM = (
   oldPatchNetwork: PatchNetwork,
   newPatchNetwork: PatchNetwork
 ) => {
+  // Nodes can be swapped out without changing connections or foreign connectables, so this runs
+  // after every change
+  scheduleLatencyCompensationUpdate();
+
   const connectionsUnchanged =
     oldPatchNetwork.connections.length === newPatchNetwork.connections.length &&
     oldPatchNetwork.connections.every(conn =>
@@ -19,6 +19,10 @@ import { getEngine } from 'src/util';
 export interface VCMState {
   activeViewContexts: { name: string; uuid: string; title?: string }[];
   activeViewContextIx: number;
+  /**
+   * ID of the VC displayed side by side with the active one, if any
+   */
+  splitViewContextId: string | null;
   patchNetwork: PatchNetwork;
   /**
    * If true, this indicates that the patch network has been populated from any persisted state
@@ -33,7 +37,10 @@ const actionGroups = {
   }),
   SET_VCM_STATE: buildActionGroup({
     actionCreator: (
-      newState: Pick<VCMState, 'activeViewContextIx' | 'activeViewContexts'> & {
+      newState: Pick<
+        VCMState,
+        'activeViewContextIx' | 'splitViewContextId' | 'activeViewContexts'
+      > & {
         foreignConnectables: { type: string; id: string; params?: { [key: string]: any } | null }[];
       },
       getPatchNetworkReturnVal: PatchNetwork
@@ -381,11 +388,19 @@ const actionGroups = {
       activeViewContextIx: newActiveVcIx,
     }),
   }),
+  SET_SPLIT_VC_ID: buildActionGroup({
+    actionCreator: (newSplitVcId: string | null) => ({ type: 'SET_SPLIT_VC_ID', newSplitVcId }),
+    subReducer: (state: VCMState, { newSplitVcId }) => ({
+      ...state,
+      splitViewContextId: newSplitVcId,
+    }),
+  }),
 };
 
 const initialState: VCMState = {
   activeViewContexts: [],
   activeViewContextIx: 0,
+  splitViewContextId: null,
   patchNetwork: {
     connectables: Map(),
     connections: [],
@@ -0,0 +1,98 @@
+import { getSentry } from 'src/sentry';
+import { AsyncOnce } from 'src/util';
+
+const SafetyLimiterWasmBytes = new AsyncOnce(
+  () =>
+    fetch(
+      process.env.ASSET_PATH +
+        'safety_limiter.wasm?cacheBust=' +
+        (window.location.host.includes('localhost') ? '' : crypto.randomUUID())
+    ).then(res => res.arrayBuffer()),
+  true
+);
+const SafetyLimiterAWPRegistered = new AsyncOnce(
+  () =>
+    new AudioContext().audioWorklet.addModule(
+      process.env.ASSET_PATH +
+        'SafetyLimiterAWP.js?cacheBust=' +
+        (window.location.href.includes('localhost') ? '' : crypto.randomUUID())
+    ),
+  true
+);
+
+/**
+ * Layout of the SAB shared by the safety limiter AWP.  Must match the `SAB_*_IX` constants in the
+ * `safety_limiter` crate.
+ */
+const SAB_ENGAGED_IX = 0;
+const SAB_GAIN_REDUCTION_DB_IX = 1;
+const SAB_ENGAGED_FRAME_COUNT_IX = 2;
+const SAB_NON_FINITE_SAMPLE_COUNT_IX = 3;
+
+export interface SafetyLimiterState {
+  /**
+   * `true` if the limiter reduced gain at any point during the most recently processed frame
+   */
+  engaged: boolean;
+  /**
+   * Max gain reduction in dB applied during the most recently processed frame
+   */
+  gainReductionDb: number;
+  engagedFrameCount: number;
+  nonFiniteSampleCount: number;
+}
+
+let SafetyLimiterSAB: Float32Array | null = null;
+
+/**
+ * Returns the current state of the global output safety limiter, or `null` if it hasn't been
+ * initialized yet or `SharedArrayBuffer` isn't available.
+ */
+export const getSafetyLimiterState = (): SafetyLimiterState | null => {
+  if (!SafetyLimiterSAB) {
+    return null;
+  }
+
+  return {
+    engaged: SafetyLimiterSAB[SAB_ENGAGED_IX] !== 0,
+    gainReductionDb: SafetyLimiterSAB[SAB_GAIN_REDUCTION_DB_IX],
+    engagedFrameCount: SafetyLimiterSAB[SAB_ENGAGED_FRAME_COUNT_IX],
+    nonFiniteSampleCount: SafetyLimiterSAB[SAB_NON_FINITE_SAMPLE_COUNT_IX],
+  };
+};
+
+/**
+ * Inserts the safety limiter between the global volume node and the audio context's destination.
+ * Everything that reaches the speakers passes through it, so it removes DC offset and hard-limits
+ * the output to 0 dBFS.
+ */
+export const initSafetyLimiter = async (ctx: AudioContext) => {
+  try {
+    const [wasmBytes] = await Promise.all([
+      SafetyLimiterWasmBytes.get(),
+      SafetyLimiterAWPRegistered.get(),
+    ] as const);
+
+    const awpHandle = new AudioWorkletNode(ctx, 'safety-limiter-awp', {
+      numberOfInputs: 1,
+      numberOfOutputs: 1,
+      channelCount: 2,
+      channelCountMode: 'explicit',
+      outputChannelCount: [2],
+    });
+    awpHandle.port.onmessage = evt => {
+      if (evt.data.type === 'sab') {
+        SafetyLimiterSAB = new Float32Array(evt.data.sab as SharedArrayBuffer);
+      }
+    };
+    awpHandle.port.postMessage({ type: 'setWasmBytes', wasmBytes });
+
+    const globalVolume = (ctx as any).globalVolume as GainNode;
+    awpHandle.connect(ctx.destination);
+    globalVolume.connect(awpHandle);
+    globalVolume.disconnect(ctx.destination);
+  } catch (err) {
+    console.error('Error initializing global output safety limiter', err);
+    getSentry()?.captureException(err);
+  }
+};
@@ -0,0 +1,180 @@
+/**
+ * Scenes are named snapshots of selected params across any number of native modules.  Recalling a
+ * scene glides every module in it to its captured values at once, for switching sounds during a
+ * live set.  Scenes can also be assigned a MIDI program number so that program changes from any
+ * MIDI input recall them.
+ *
+ * Scene values are kept here, keyed by the VC ID of each module, and persisted to `localStorage` so
+ * that they're saved with the composition.  They're also uploaded into the modules themselves (see
+ * `module_api::scenes` in the engine) so that each module can glide to a scene with a single call
+ * on the audio thread.  A scene's index in the list is its slot in every module.
+ */
+
+import * as R from 'ramda';
+
+import {
+  captureModuleHostScene,
+  getModuleHostNode,
+  MODULE_HOST_MAX_SCENE_GLIDE_MS,
+  MODULE_HOST_MAX_SCENES,
+  recallModuleHostScene,
+  setModuleHostScene,
+  type ModuleHostSceneValues,
+} from 'src/graphEditor/nodes/CustomAudio/ModuleHost/moduleHost';
+
+const SCENES_LOCALSTORAGE_KEY = 'scenes';
+export const DEFAULT_SCENE_GLIDE_MS = 500;
+
+export interface Scene {
+  name: string;
+  /**
+   * MIDI program number from 0 to 127 that recalls the scene, if any
+   */
+  program: number | null;
+  glideMs: number;
+  /**
+   * Captured `[paramIx, value]` pairs of each module in the scene, keyed by VC ID
+   */
+  values: Record<string, ModuleHostSceneValues>;
+}
+
+/**
+ * Params of a single module to include in a scene
+ */
+export interface SceneSelection {
+  vcId: string;
+  paramIxs: number[];
+}
+
+const loadScenes = (): Scene[] => {
+  const serialized = localStorage.getItem(SCENES_LOCALSTORAGE_KEY);
+  if (!serialized) {
+    return [];
+  }
+
+  try {
+    const parsed = JSON.parse(serialized);
+    if (!Array.isArray(parsed)) {
+      throw new Error('Expected an array of scenes');
+    }
+    return parsed.slice(0, MODULE_HOST_MAX_SCENES);
+  } catch (err) {
+    console.error('Failed to parse scenes from localStorage; starting with none', err);
+    return [];
+  }
+};
+
+let scenes: Scene[] = loadScenes();
+/**
+ * Incremented whenever scenes change so that modules can be brought up to date lazily, including
+ * ones created after the change
+ */
+let revision = 0;
+/**
+ * The revision last uploaded to each module and how many scene slots it had
+ */
+const UploadedRevisions = new WeakMap<AudioWorkletNode, { revision: number; sceneCount: number }>();
+
+const saveScenes = (newScenes: Scene[]) => {
+  scenes = newScenes;
+  revision += 1;
+  localStorage.setItem(SCENES_LOCALSTORAGE_KEY, JSON.stringify(scenes));
+};
+
+export const getScenes = (): readonly Scene[] => scenes;
+
+/**
+ * Uploads the values of every scene for the module `vcId` unless it already has the latest ones.
+ * Slots of scenes that have been deleted since are emptied.
+ */
+const syncModuleScenes = (vcId: string, node: AudioWorkletNode) => {
+  const uploaded = UploadedRevisions.get(node);
+  if (uploaded?.revision === revision) {
+    return;
+  }
+
+  const slotCount = Math.max(scenes.length, uploaded?.sceneCount ?? 0);
+  for (let sceneIx = 0; sceneIx < slotCount; sceneIx++) {
+    setModuleHostScene(node, sceneIx, scenes[sceneIx]?.values[vcId] ?? null);
+  }
+  UploadedRevisions.set(node, { revision, sceneCount: scenes.length });
+};
+
+/**
+ * Captures the current values of the selected params into a new scene, resolving to its index.
+ * Modules that haven't been created are left out.
+ */
+export const captureScene = async (
+  name: string,
+  selection: SceneSelection[],
+  glideMs = DEFAULT_SCENE_GLIDE_MS
+): Promise<number> => {
+  if (scenes.length >= MODULE_HOST_MAX_SCENES) {
+    throw new Error(`Can't have more than ${MODULE_HOST_MAX_SCENES} scenes`);
+  }
+
+  const sceneIx = scenes.length;
+  const values: Record<string, ModuleHostSceneValues> = {};
+  await Promise.all(
+    selection.map(async ({ vcId, paramIxs }) => {
+      const node = getModuleHostNode(vcId);
+      if (!node) {
+        console.warn(`Leaving module ${vcId} out of scene "${name}" since it hasn't been created`);
+        return;
+      }
+      values[vcId] = await captureModuleHostScene(node, sceneIx, paramIxs);
+    })
+  );
+
+  saveScenes([...scenes, { name, program: null, glideMs, values }]);
+  return sceneIx;
+};
+
+export const updateScene = (
+  sceneIx: number,
+  update: Partial<Pick<Scene, 'name' | 'program' | 'glideMs'>>
+) => {
+  if (!scenes[sceneIx]) {
+    console.error(`Tried to update nonexistent scene ${sceneIx}`);
+    return;
+  }
+  saveScenes(R.update(sceneIx, { ...scenes[sceneIx], ...update }, scenes));
+};
+
+/**
+ * Scenes after the deleted one move down a slot
+ */
+export const deleteScene = (sceneIx: number) => saveScenes(R.remove(sceneIx, 1, scenes));
+
+/**
+ * Glides every module in the scene to its values in it, over the scene's glide time unless
+ * `glideMs` is given
+ */
+export const recallScene = (sceneIx: number, glideMs?: number) => {
+  const scene = scenes[sceneIx];
+  if (!scene) {
+    console.error(`Tried to recall nonexistent scene ${sceneIx}`);
+    return;
+  }
+
+  const clampedGlideMs = R.clamp(0, MODULE_HOST_MAX_SCENE_GLIDE_MS, glideMs ?? scene.glideMs);
+  for (const vcId of Object.keys(scene.values)) {
+    const node = getModuleHostNode(vcId);
+    if (!node) {
+      continue;
+    }
+    syncModuleScenes(vcId, node);
+    recallModuleHostScene(node, sceneIx, clampedGlideMs);
+  }
+};
+
+/**
+ * Recalls the first scene assigned to `program`, if any.  Called for program changes from all MIDI
+ * inputs.
+ */
+export const recallSceneForProgram = (program: number) => {
+  const sceneIx = scenes.findIndex(scene => scene.program === program);
+  if (sceneIx !== -1) {
+    recallScene(sceneIx);
+  }
+};
@@ -0,0 +1,211 @@
+import { getSentry } from 'src/sentry';
+import { AsyncOnce } from 'src/util';
+
+const StepSequencerWasmBytes = new AsyncOnce(
+  () =>
+    fetch(
+      process.env.ASSET_PATH +
+        'sequencer.wasm?cacheBust=' +
+        (window.location.host.includes('localhost') ? '' : crypto.randomUUID())
+    ).then(res => res.arrayBuffer()),
+  true
+);
+const StepSequencerAWPRegistered = new AsyncOnce(
+  () =>
+    new AudioContext().audioWorklet.addModule(
+      process.env.ASSET_PATH +
+        'StepSequencerAWP.js?cacheBust=' +
+        (window.location.href.includes('localhost') ? '' : crypto.randomUUID())
+    ),
+  true
+);
+
+/**
+ * Must match `MAX_PARAM_LOCKS` in `engine/sequencer/src/sequencer.rs`
+ */
+export const STEP_SEQUENCER_MAX_PARAM_LOCKS = 8;
+
+export interface SerializedStepSequencerStep {
+  enabled: boolean;
+  note: number;
+  velocity: number;
+  /**
+   * Fraction of the step length that the note is held for, in (0, 1]
+   */
+  gate: number;
+  /**
+   * Probability that the step fires each time it's reached, in [0, 1]
+   */
+  probability: number;
+  /**
+   * Value for each lockable param, or `null` if the param isn't locked on this step
+   */
+  paramLocks: (number | null)[];
+}
+
+export interface EuclideanConfig {
+  pulses: number;
+  steps: number;
+  rotation: number;
+}
+
+/**
+ * Returns a pattern of length `steps` with `pulses` onsets spread as evenly as possible, rotated
+ * left by `rotation` steps.  Matches `euclidean_pattern` in `engine/sequencer/src/euclidean.rs`.
+ */
+export const buildEuclideanPattern = ({ pulses, steps, rotation }: EuclideanConfig): boolean[] => {
+  pulses = Math.min(pulses, steps);
+  return new Array(steps)
+    .fill(null)
+    .map((_, i) => ((i + rotation) * pulses) % steps < pulses);
+};
+
+export interface SerializedStepSequencerTrack {
+  name: string;
+  /**
+   * ID of the MIDI event mailbox that events generated by this track are delivered to
+   */
+  mailboxID: string | null;
+  muted: boolean;
+  /**
+   * Value that each param is restored to on steps that don't lock it
+   */
+  paramDefaults: (number | null)[];
+  steps: SerializedStepSequencerStep[];
+  /**
+   * If set, gates for this track are generated live from this Euclidean pattern rather than from
+   * each step's `enabled` flag.  The pattern repeats with its own length.
+   */
+  euclidean?: EuclideanConfig | null;
+}
+
+export interface SerializedStepSequencerState {
+  stepCount: number;
+  beatsPerStep: number;
+  tracks: SerializedStepSequencerTrack[];
+}
+
+export const buildDefaultStepSequencerStep = (): SerializedStepSequencerStep => ({
+  enabled: false,
+  note: 60,
+  velocity: 1,
+  gate: 0.5,
+  probability: 1,
+  paramLocks: new Array(STEP_SEQUENCER_MAX_PARAM_LOCKS).fill(null),
+});
+
+export const buildDefaultStepSequencerState = (
+  trackCount = 4,
+  stepCount = 16
+): SerializedStepSequencerState => ({
+  stepCount,
+  beatsPerStep: 0.25,
+  tracks: new Array(trackCount).fill(null).map((_, trackIx) => ({
+    name: `Track ${trackIx + 1}`,
+    mailboxID: null,
+    muted: false,
+    paramDefaults: new Array(STEP_SEQUENCER_MAX_PARAM_LOCKS).fill(null),
+    steps: new Array(stepCount).fill(null).map(buildDefaultStepSequencerStep),
+  })),
+});
+
+/**
+ * Handle to a step sequencer running on the audio thread.  Playback follows the global beat
+ * counter, and generated notes and parameter locks are delivered to each track's MIDI event
+ * mailbox.  Parameter locks are sent as generic control events with the param index as the control
+ * index.
+ */
+export class StepSequencer {
+  private awpHandle: AudioWorkletNode | null = null;
+  private pendingMessages: any[] = [];
+  private state: SerializedStepSequencerState;
+
+  constructor(ctx: AudioContext, state?: SerializedStepSequencerState | null) {
+    this.state = state ?? buildDefaultStepSequencerState();
+    this.postMessage({ type: 'setState', state: this.state });
+
+    this.init(ctx).catch(err => {
+      console.error('Error initializing step sequencer', err);
+      getSentry()?.captureException(err);
+    });
+  }
+
+  private async init(ctx: AudioContext) {
+    const [wasmBytes] = await Promise.all([
+      StepSequencerWasmBytes.get(),
+      StepSequencerAWPRegistered.get(),
+    ] as const);
+    this.awpHandle = new AudioWorkletNode(ctx, 'step-sequencer-awp', {
+      numberOfInputs: 0,
+      numberOfOutputs: 1,
+    });
+    // The AWP has no audio output, but it needs to be connected to something in order to run
+    this.awpHandle.connect(ctx.destination);
+    this.awpHandle.port.postMessage({ type: 'setWasmBytes', wasmBytes });
+    this.pendingMessages.forEach(msg => this.awpHandle!.port.postMessage(msg));
+    this.pendingMessages = [];
+  }
+
+  private postMessage(msg: any) {
+    if (this.awpHandle) {
+      this.awpHandle.port.postMessage(msg);
+    } else {
+      this.pendingMessages.push(msg);
+    }
+  }
+
+  public setStep(trackIx: number, stepIx: number, step: SerializedStepSequencerStep) {
+    this.state.tracks[trackIx].steps[stepIx] = step;
+    this.postMessage({ type: 'setStep', trackIx, stepIx, step });
+  }
+
+  public setTrackMuted(trackIx: number, muted: boolean) {
+    this.state.tracks[trackIx].muted = muted;
+    this.postMessage({ type: 'setTrackMuted', trackIx, muted });
+  }
+
+  public setTrackEuclidean(trackIx: number, euclidean: EuclideanConfig | null) {
+    this.state.tracks[trackIx].euclidean = euclidean;
+    this.postMessage({ type: 'setTrackEuclidean', trackIx, euclidean });
+  }
+
+  /**
+   * Overwrites the `enabled` flag of every step in the track with a Euclidean pattern
+   */
+  public fillTrackEuclidean(trackIx: number, euclidean: EuclideanConfig) {
+    if (euclidean.steps <= 0) {
+      return;
+    }
+
+    const pattern = buildEuclideanPattern(euclidean);
+    this.state.tracks[trackIx].steps.forEach((step, stepIx) => {
+      step.enabled = pattern[stepIx % pattern.length];
+    });
+    this.postMessage({ type: 'fillTrackEuclidean', trackIx, euclidean });
+  }
+
+  public setTrackMailboxID(trackIx: number, mailboxID: string | null) {
+    this.state.tracks[trackIx].mailboxID = mailboxID;
+    this.postMessage({ type: 'setTrackMailboxID', trackIx, mailboxID });
+  }
+
+  public setBeatsPerStep(beatsPerStep: number) {
+    this.state.beatsPerStep = beatsPerStep;
+    this.postMessage({ type: 'setBeatsPerStep', beatsPerStep });
+  }
+
+  public setState(state: SerializedStepSequencerState) {
+    this.state = state;
+    this.postMessage({ type: 'setState', state });
+  }
+
+  public serialize(): SerializedStepSequencerState {
+    return this.state;
+  }
+
+  public destroy() {
+    this.awpHandle?.disconnect();
+    this.awpHandle?.port.close();
+    this.awpHandle = null;
+  }
+}
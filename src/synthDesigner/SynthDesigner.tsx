@@ -7,6 +7,7 @@ import { Provider, shallowEqual, useSelector } from 'react-redux';
 import './SynthDesigner.scss';
 import { saveSynthPreset } from 'src/api';
 import { renderGenericPresetSaverWithModal } from 'src/controls/GenericPresetPicker/GenericPresetSaver';
+import { emitLessonEvent } from 'src/lessons/lessonHooks';
 import { updateConnectables } from 'src/patchNetwork/interface';
 import { store, type ReduxStore } from 'src/redux';
 import { voicePresetIdsSelector } from 'src/redux/modules/presets';
@@ -70,6 +71,14 @@ const AddModuleControls: React.FC<AddModuleControlsProps> = ({
           );
           const newConnectables = get_synth_designer_audio_connectables(stateKey);
           updateConnectables(vcId, newConnectables);
+          if (selectedVoicePreset) {
+            emitLessonEvent({
+              type: 'presetLoaded',
+              target: 'synthDesignerVoice',
+              vcId,
+              presetID: selectedVoicePresetId,
+            });
+          }
         },
       },
     ];
@@ -159,6 +168,12 @@ const FullPresetControlsInner: React.FC<FullPresetControlsProps> = ({
           const newConnectables = get_synth_designer_audio_connectables(stateKey);
           const vcId = stateKey.split('_')[1]!;
           updateConnectables(vcId, newConnectables);
+          emitLessonEvent({
+            type: 'presetLoaded',
+            target: 'synthDesigner',
+            vcId,
+            presetID: state.preset,
+          });
         },
       },
       {
@@ -0,0 +1,150 @@
+import { midiNumberToNoteName } from 'src/midiUtils';
+
+/**
+ * Equal division of the octave tuning.  Note numbers are interpreted as scale degrees of the tuning
+ * rather than as 12-TET semitones.  Matches `Tuning` in `engine/dsp/src/tuning.rs`.
+ */
+export interface Tuning {
+  divisionsPerOctave: number;
+  /**
+   * Note number that plays at exactly `referenceFrequency`
+   */
+  referenceNote: number;
+  referenceFrequency: number;
+}
+
+export const TWELVE_TET: Tuning = {
+  divisionsPerOctave: 12,
+  referenceNote: 69,
+  referenceFrequency: 440,
+};
+
+export const isTwelveTET = (tuning: Tuning | null | undefined): boolean =>
+  !tuning ||
+  (tuning.divisionsPerOctave === TWELVE_TET.divisionsPerOctave &&
+    tuning.referenceNote === TWELVE_TET.referenceNote &&
+    tuning.referenceFrequency === TWELVE_TET.referenceFrequency);
+
+/**
+ * Builds an N-EDO tuning with the same reference note and frequency as 12-TET
+ */
+export const buildEDOTuning = (divisionsPerOctave: number): Tuning => ({
+  ...TWELVE_TET,
+  divisionsPerOctave,
+});
+
+/**
+ * Divisions of the octave offered when picking a tuning
+ */
+export const EDO_DIVISIONS = [12, 17, 19, 22, 24, 31, 41, 53];
+
+export const noteToFrequency = (note: number, tuning: Tuning = TWELVE_TET): number =>
+  tuning.referenceFrequency *
+  Math.pow(2, (note - tuning.referenceNote) / tuning.divisionsPerOctave);
+
+/**
+ * Semitone offsets of the natural notes (C D E F G A B) from C
+ */
+const NATURAL_SEMITONES = [0, 2, 4, 5, 7, 9, 11];
+/**
+ * Semitone offset of A from C
+ */
+const A_SEMITONE = 9;
+
+export interface NoteRowInfo {
+  label: string;
+  /**
+   * `true` for scale degrees that are the closest match to one of the natural notes (the white
+   * keys of a piano).  In 12-TET, these are exactly the white keys.
+   */
+  isNatural: boolean;
+  /**
+   * `true` for the first scale degree of each octave
+   */
+  isOctaveRoot: boolean;
+}
+
+/**
+ * Returns display info for the piano roll row of `note`.  The reference note is treated as A4, and
+ * octaves start on the scale degree closest to the C below it.
+ */
+export const getNoteRowInfo = (note: number, tuning: Tuning = TWELVE_TET): NoteRowInfo => {
+  const divisions = tuning.divisionsPerOctave;
+  const rootNote = tuning.referenceNote - Math.round((A_SEMITONE * divisions) / 12);
+  const degree = (((note - rootNote) % divisions) + divisions) % divisions;
+  const octave = 4 + Math.floor((note - rootNote) / divisions);
+  const isNatural = NATURAL_SEMITONES.some(
+    semitone => Math.round((semitone * divisions) / 12) === degree
+  );
+
+  if (divisions === 12) {
+    return { label: midiNumberToNoteName(note), isNatural, isOctaveRoot: degree === 0 };
+  }
+
+  return { label: `${degree}\\${divisions} ${octave}`, isNatural, isOctaveRoot: degree === 0 };
+};
+
+/**
+ * Semitone offsets from the root of each scale that can be used as the key of a MIDI editor
+ * instance.  In other tunings, each offset maps to the closest degree of the tuning.
+ */
+export const SCALE_INTERVALS = {
+  major: [0, 2, 4, 5, 7, 9, 11],
+  'natural minor': [0, 2, 3, 5, 7, 8, 10],
+  'harmonic minor': [0, 2, 3, 5, 7, 8, 11],
+  dorian: [0, 2, 3, 5, 7, 9, 10],
+  mixolydian: [0, 2, 4, 5, 7, 9, 10],
+  'major pentatonic': [0, 2, 4, 7, 9],
+  'minor pentatonic': [0, 3, 5, 7, 10],
+  chromatic: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+};
+
+export type ScaleName = keyof typeof SCALE_INTERVALS;
+
+export const KEY_ROOT_NAMES = ['C', 'D♭', 'D', 'E♭', 'E', 'F', 'F♯', 'G', 'A♭', 'A', 'B♭', 'B'];
+
+export interface KeyAndScale {
+  /**
+   * Semitones above C, from 0 to 11
+   */
+  root: number;
+  scale: ScaleName;
+}
+
+export const DEFAULT_KEY_AND_SCALE: KeyAndScale = { root: 0, scale: 'major' };
+
+export const isNoteInScale = (
+  note: number,
+  { root, scale }: KeyAndScale,
+  tuning: Tuning = TWELVE_TET
+): boolean => {
+  const divisions = tuning.divisionsPerOctave;
+  const cNote = tuning.referenceNote - Math.round((A_SEMITONE * divisions) / 12);
+  const keyRootNote = cNote + Math.round((root * divisions) / 12);
+  const degree = (((note - keyRootNote) % divisions) + divisions) % divisions;
+  return SCALE_INTERVALS[scale].some(
+    semitone => Math.round((semitone * divisions) / 12) === degree
+  );
+};
+
+/**
+ * Moves `note` by `steps` notes of the scale, up if positive and down if negative.  Notes that
+ * aren't in the scale move to the nearest scale note in that direction for their first step.
+ */
+export const transposeByScaleDegrees = (
+  note: number,
+  steps: number,
+  keyAndScale: KeyAndScale,
+  tuning: Tuning = TWELVE_TET
+): number => {
+  const direction = Math.sign(steps);
+  let remaining = Math.abs(steps);
+  let transposed = note;
+  while (remaining > 0) {
+    transposed += direction;
+    if (isNoteInScale(transposed, keyAndScale, tuning)) {
+      remaining -= 1;
+    }
+  }
+  return transposed;
+};
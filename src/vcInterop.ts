@@ -4,6 +4,7 @@ import { initializeDefaultVCMState } from 'src/redux/modules/vcmUtils';
 import type { VCMState } from 'src/redux/modules/viewContextManager';
 import type { SampleDescriptor } from 'src/sampleLibrary';
 import { getEngine, tryParseJson } from 'src/util';
+import { updateSplitViewLayout } from 'src/ViewContextManager/splitView';
 import { onVcHideStatusChange } from 'src/ViewContextManager/VcHideStatusRegistry';
 import { actionCreators, dispatch, getState } from './redux';
 
@@ -11,6 +12,7 @@ const ctx = new AudioContext();
 
 export const init_view_contexts = (
   activeViewContextIx: number,
+  splitViewContextIx: number | undefined,
   activeVcsJson: string,
   connectionsJson: string,
   foreignConnectablesJson: string
@@ -35,10 +37,17 @@ export const init_view_contexts = (
 
   dispatch(actionCreators.viewContextManager.SET_IS_LOADED(false));
 
-  const newVCMState: Pick<VCMState, 'activeViewContextIx' | 'activeViewContexts'> & {
+  const newVCMState: Pick<
+    VCMState,
+    'activeViewContextIx' | 'splitViewContextId' | 'activeViewContexts'
+  > & {
     foreignConnectables: { type: string; id: string; params?: { [key: string]: any } | null }[];
   } = {
     activeViewContextIx,
+    splitViewContextId:
+      splitViewContextIx === undefined
+        ? null
+        : activeViewContexts[splitViewContextIx]?.minimal_def.uuid ?? null,
     activeViewContexts: activeViewContexts.map(({ minimal_def, ...rest }) => ({
       ...minimal_def,
       ...rest,
@@ -55,6 +64,7 @@ export const init_view_contexts = (
     ctx
   );
   dispatch(actionCreators.viewContextManager.SET_VCM_STATE(newVCMState, patchNetwork));
+  updateSplitViewLayout();
 };
 
 export const add_view_context = (id: string, name: string) => {
@@ -73,14 +83,40 @@ export const delete_view_context = (id: string) => {
 export const set_active_vc_ix = (newActiveVxIx: number) => {
   const oldActiveVcIx = getState().viewContextManager.activeViewContextIx;
   const oldActiveVcId = getState().viewContextManager.activeViewContexts[oldActiveVcIx]?.uuid;
-  if (oldActiveVcId) {
+  const newActiveVcId = getState().viewContextManager.activeViewContexts[newActiveVxIx].uuid;
+  // If the new active VC was displayed in a split view, the old one stays visible and the engine
+  // will follow up by making it the split VC
+  const splitVcId = getState().viewContextManager.splitViewContextId;
+  if (oldActiveVcId && oldActiveVcId !== newActiveVcId && splitVcId !== newActiveVcId) {
     onVcHideStatusChange(oldActiveVcId, true);
   }
 
-  const newActiveVcId = getState().viewContextManager.activeViewContexts[newActiveVxIx].uuid;
   onVcHideStatusChange(newActiveVcId, false);
 
   dispatch(actionCreators.viewContextManager.SET_ACTIVE_VC_IX(newActiveVxIx));
+  updateSplitViewLayout();
+};
+
+export const set_split_vc_ix = (newSplitVcIx: number | undefined) => {
+  const { activeViewContexts, activeViewContextIx, splitViewContextId } =
+    getState().viewContextManager;
+  const activeVcId = activeViewContexts[activeViewContextIx]?.uuid;
+  const newSplitVcId =
+    newSplitVcIx === undefined ? null : activeViewContexts[newSplitVcIx]?.uuid ?? null;
+
+  if (
+    splitViewContextId &&
+    splitViewContextId !== activeVcId &&
+    splitViewContextId !== newSplitVcId
+  ) {
+    onVcHideStatusChange(splitViewContextId, true);
+  }
+  if (newSplitVcId) {
+    onVcHideStatusChange(newSplitVcId, false);
+  }
+
+  dispatch(actionCreators.viewContextManager.SET_SPLIT_VC_ID(newSplitVcId));
+  updateSplitViewLayout();
 };
 
 export const list_foreign_node_used_samples = (id: string): SampleDescriptor[] => {
@@ -99,6 +99,7 @@ const config = {
       chunks: ['fmDemo'],
     }),
     new webpack.EnvironmentPlugin(['BACKEND_BASE_URL', 'FAUST_COMPILER_ENDPOINT']),
+    new webpack.EnvironmentPlugin({ COLLABORATION_BASE_URL: '' }),
   ],
   devServer: {
     port: 9000,